@@ -0,0 +1,474 @@
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::{FromRequest, FromRequestParts, Query, RawPathParams, Request, State},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    RequestExt,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL, Engine};
+use hmac::{Hmac, Mac};
+use kicad_db::{
+    find_api_key, find_membership, get_commit_visibility, get_organization, get_registered_repo, get_repo_visibility, touch_api_key,
+    ApiScope, OrgFilter, PgPool, Visibility,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use crate::cors;
+use crate::error::{AppError, ResultExt};
+use crate::request_id;
+use crate::services::git;
+use crate::state::AppState;
+use crate::storage;
+
+/// Prefix of generated API keys, so they are recognisable in configs and logs
+pub const API_KEY_PREFIX: &str = "kw_";
+
+/// Header carrying an API key, as an alternative to `Authorization: Bearer`
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// Stored keys are marked used at most this often, to avoid a write per request
+const TOUCH_INTERVAL_SECS: i64 = 60;
+
+/// Marks a request the config lets use read-scoped routes without
+/// credentials (`auth.allow_anonymous_read`), attached by [`authenticate`]
+#[derive(Clone, Copy)]
+struct AnonymousReads;
+
+/// An authenticated caller, attached to the request by [`authenticate`]
+#[derive(Debug, Clone)]
+pub struct Caller {
+    /// Stable identifier for per-client limits: key id, token hash or JWT subject
+    pub id: String,
+    /// Key name, or the JWT subject
+    pub name: String,
+    pub scope: ApiScope,
+    /// Org the caller acts for; `None` for instance-wide keys, which see every org
+    pub org: Option<i32>,
+}
+
+/// Compare two byte strings in constant time
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Whether `token` is one of `tokens`, comparing each in constant time and
+/// all of them, so the time taken says nothing about which one nearly matched
+fn is_one_of(tokens: &[String], token: &str) -> bool {
+    tokens
+        .iter()
+        .fold(false, |found, t| found | constant_time_eq(t.as_bytes(), token.as_bytes()))
+}
+
+/// Hex SHA-256 of a key, as stored in `api_keys.key_hash`
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// A new random API key
+pub fn generate_key() -> String {
+    format!(
+        "{}{}{}",
+        API_KEY_PREFIX,
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+#[derive(Deserialize)]
+struct AccessTokenQuery {
+    access_token: Option<String>,
+}
+
+/// The credential sent with a request: `X-API-Key`, else `Authorization: Bearer`,
+/// else on event streams and WebSockets the `access_token` query parameter
+fn credential(request: &Request) -> Option<String> {
+    let headers = request.headers();
+    let from_headers = headers
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| {
+            headers
+                .get(AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+        })
+        .map(str::to_string);
+    let from_query = || {
+        Query::<AccessTokenQuery>::try_from_uri(request.uri())
+            .ok()
+            .and_then(|Query(q)| q.access_token)
+    };
+    from_headers
+        .or_else(|| cors::is_stream_request(headers).then(from_query).flatten())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtClaims {
+    sub: String,
+    scope: String,
+    exp: i64,
+    nbf: Option<i64>,
+    /// Slug of the org the subject acts for
+    org: Option<String>,
+}
+
+/// Verify an HS256 JWT and read its claims
+fn verify_jwt(token: &str, secret: &str) -> Result<JwtClaims, &'static str> {
+    let mut parts = token.split('.');
+    let (Some(header), Some(payload), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err("malformed token");
+    };
+
+    let decode = |part: &str| BASE64URL.decode(part).map_err(|_| "malformed token");
+    let header: JwtHeader = serde_json::from_slice(&decode(header)?).map_err(|_| "malformed token")?;
+    if header.alg != "HS256" {
+        return Err("unsupported signing algorithm");
+    }
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(&token.as_bytes()[..token.len() - signature.len() - 1]);
+    mac.verify_slice(&decode(signature)?)
+        .map_err(|_| "invalid signature")?;
+
+    let claims: JwtClaims = serde_json::from_slice(&decode(payload)?).map_err(|_| "malformed claims")?;
+    let now = chrono::Utc::now().timestamp();
+    if claims.exp <= now {
+        return Err("token expired");
+    }
+    if claims.nbf.is_some_and(|nbf| nbf > now) {
+        return Err("token not yet valid");
+    }
+    Ok(claims)
+}
+
+/// The caller a verified JWT stands for
+///
+/// With an `org` claim the subject must be a member of that org, and gets the
+/// narrower of the token's scope and their role in it.
+async fn jwt_caller(pool: &PgPool, claims: JwtClaims) -> Result<Caller, AppError> {
+    let invalid = |reason: &str| AppError::unauthorized(format!("Invalid bearer token: {}", reason));
+    let scope: ApiScope = claims.scope.parse().map_err(|_| invalid("unknown scope"))?;
+    let (scope, org) = match &claims.org {
+        Some(slug) => {
+            let (org, role) = find_membership(pool, slug, &claims.sub)
+                .await
+                .or_internal("Failed to look up org membership")?
+                .ok_or_else(|| invalid("subject is not a member of the org"))?;
+            (scope.min(role), Some(org.id))
+        }
+        None => (scope, None),
+    };
+    Ok(Caller {
+        id: format!("jwt:{}", claims.sub),
+        name: claims.sub,
+        scope,
+        org,
+    })
+}
+
+/// Resolve a credential to a caller, or fail with 401
+///
+/// Stored keys and org JWTs are looked up in Postgres, so a SQLite-backed
+/// server answers 503 for them; only the environment's tokens and JWTs
+/// without an org claim work there.
+async fn resolve(state: &AppState, token: &str) -> Result<Caller, AppError> {
+    let pool = &state.pool;
+    let config = state.config();
+    let sqlite = config.uses_sqlite();
+    if is_one_of(&config.auth.admin_api_keys, token) {
+        return Ok(Caller {
+            id: format!("env:{}", &hash_key(token)[..16]),
+            name: "ADMIN_API_KEYS".to_string(),
+            scope: ApiScope::Admin,
+            org: None,
+        });
+    }
+    if is_one_of(&config.auth.summary_read_tokens, token) {
+        return Ok(Caller {
+            id: format!("env:{}", &hash_key(token)[..16]),
+            name: "SUMMARY_READ_TOKENS".to_string(),
+            scope: ApiScope::Read,
+            org: None,
+        });
+    }
+
+    if !token.starts_with(API_KEY_PREFIX) && token.matches('.').count() == 2 {
+        let secret = Some(config.auth.jwt_secret.as_str())
+            .filter(|secret| !secret.is_empty())
+            .ok_or_else(|| AppError::unauthorized("Bearer JWTs are not accepted by this server"))?;
+        let claims = verify_jwt(token, secret)
+            .map_err(|reason| AppError::unauthorized(format!("Invalid bearer token: {}", reason)))?;
+        if sqlite && claims.org.is_some() {
+            return Err(storage::postgres_required("a JWT with an org claim"));
+        }
+        return jwt_caller(pool, claims).await;
+    }
+
+    if sqlite {
+        return Err(storage::postgres_required("a stored API key (use ADMIN_API_KEYS)"));
+    }
+    let key = find_api_key(pool, &hash_key(token))
+        .await
+        .or_internal("Failed to look up API key")?
+        .ok_or_else(|| AppError::unauthorized("Invalid or revoked API key"))?;
+
+    let stale = key
+        .last_used_at
+        .is_none_or(|t| (chrono::Utc::now() - t).num_seconds() >= TOUCH_INTERVAL_SECS);
+    if stale {
+        let pool = pool.clone();
+        request_id::spawn(async move {
+            if let Err(e) = touch_api_key(&pool, key.id).await {
+                warn!("Failed to record use of API key {}: {}", key.id, e);
+            }
+        });
+    }
+    Ok(Caller {
+        id: format!("key:{}", key.id),
+        name: key.name,
+        scope: key.scope,
+        org: key.org_id,
+    })
+}
+
+/// Middleware resolving the request's API key or JWT into a [`Caller`]
+///
+/// Requests without credentials continue anonymously; routes decide what
+/// anonymous callers may do with [`require_scope`]. A credential that doesn't
+/// check out is rejected outright rather than silently downgraded.
+pub async fn authenticate(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    if let Some(token) = credential(&request) {
+        match resolve(&state, &token).await {
+            Ok(caller) => {
+                debug!("Request authenticated as {} ({})", caller.name, caller.scope);
+                request.extensions_mut().insert(caller);
+            }
+            Err(e) => return e.into_response(),
+        }
+    } else if state.config().auth.allow_anonymous_read {
+        request.extensions_mut().insert(AnonymousReads);
+    }
+    next.run(request).await
+}
+
+/// Route middleware rejecting callers whose scope doesn't cover the route's
+///
+/// Use as `route_layer(middleware::from_fn_with_state(ApiScope::Hook, require_scope))`.
+pub async fn require_scope(
+    State(scope): State<ApiScope>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    Viewer::from_parts(request.extensions()).require_scope(scope)?;
+    Ok(next.run(request).await)
+}
+
+#[derive(Deserialize)]
+struct RepoField {
+    repo: Option<String>,
+}
+
+/// Route middleware answering 404 for repos registered to an org other than
+/// the caller's, as if they didn't exist
+///
+/// The repo is the `repo` path parameter, else the `repo` query parameter,
+/// else the `repo` field of the JSON body. Instance-wide callers pass
+/// unchecked. Add it before [`require_scope`] so it runs after it:
+/// `route_layer(middleware::from_fn_with_state(pool, require_repo_access))`.
+pub async fn require_repo_access(
+    State(pool): State<Arc<PgPool>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let orgs = Viewer::from_parts(request.extensions()).orgs();
+    if orgs == OrgFilter::Any {
+        return Ok(next.run(request).await);
+    }
+
+    let from_path = request.extract_parts::<RawPathParams>().await.ok().and_then(|params| {
+        params
+            .iter()
+            .find(|(name, _)| *name == "repo")
+            .map(|(_, value)| value.to_string())
+    });
+    let mut repo = from_path;
+    if repo.is_none() {
+        repo = request
+            .extract_parts::<Query<RepoField>>()
+            .await
+            .ok()
+            .and_then(|Query(q)| q.repo);
+    }
+    if repo.is_none() {
+        // Read within the route's body limit (see crate::limits)
+        let (parts, body) = request.into_parts();
+        let bytes = Bytes::from_request(Request::from_parts(parts.clone(), body), &())
+            .await
+            .map_err(|rejection| match rejection.status() {
+                StatusCode::PAYLOAD_TOO_LARGE => AppError::payload_too_large(rejection.body_text()),
+                _ => AppError::bad_request(format!("Failed to read the request body: {}", rejection.body_text())),
+            })?;
+        repo = serde_json::from_slice::<RepoField>(&bytes).ok().and_then(|f| f.repo);
+        request = Request::from_parts(parts, Body::from(bytes));
+    }
+
+    if let Some(repo) = repo.as_deref().map(|r| r.trim().trim_matches('/')).filter(|r| !r.is_empty()) {
+        let owner = get_registered_repo(&pool, &git::repo_url(repo))
+            .await
+            .or_internal("Failed to look up repository")?
+            .and_then(|r| r.org_id);
+        if !orgs.allows(owner) {
+            return Err(AppError::not_found(format!("Repository {} not found", repo)).for_repo(repo));
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+/// The caller of a request, as far as handlers are concerned
+///
+/// Extracting never fails: a request without credentials yields an anonymous
+/// viewer, who only sees public summaries.
+#[derive(Debug, Clone, Copy)]
+pub struct Viewer {
+    scope: Option<ApiScope>,
+    org: Option<i32>,
+    anonymous_reads: bool,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Viewer {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Viewer::from_parts(&parts.extensions))
+    }
+}
+
+impl Viewer {
+    fn from_parts(extensions: &axum::http::Extensions) -> Self {
+        let caller = extensions.get::<Caller>();
+        Viewer {
+            scope: caller.map(|c| c.scope),
+            org: caller.and_then(|c| c.org),
+            anonymous_reads: extensions.get::<AnonymousReads>().is_some(),
+        }
+    }
+
+    pub fn is_authenticated(&self) -> bool {
+        self.scope.is_some()
+    }
+
+    /// The org the caller acts for, if they are bound to one
+    pub fn org(&self) -> Option<i32> {
+        self.org
+    }
+
+    /// Whose data the caller may see: instance-wide callers every org's, org
+    /// callers their own org's, anonymous callers only repos no org owns
+    pub fn orgs(&self) -> OrgFilter {
+        match (self.scope, self.org) {
+            (Some(_), None) => OrgFilter::Any,
+            (_, org) => OrgFilter::Only(org),
+        }
+    }
+
+    /// The org something the caller creates belongs to: their own, or for
+    /// instance-wide callers the org named by `requested` (none if unset)
+    pub async fn owning_org(&self, pool: &PgPool, requested: Option<&str>) -> Result<Option<i32>, AppError> {
+        let requested = requested.map(str::trim).filter(|slug| !slug.is_empty());
+        match (self.org, requested) {
+            (Some(org), None) => Ok(Some(org)),
+            (Some(_), Some(_)) => Err(AppError::new(
+                StatusCode::FORBIDDEN,
+                "forbidden",
+                "Only instance-wide keys may act for another org",
+            )),
+            (None, Some(slug)) => get_organization(pool, slug)
+                .await
+                .or_internal("Failed to look up org")?
+                .map(|org| Some(org.id))
+                .ok_or_else(|| AppError::not_found(format!("No org '{}'", slug))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Whether summaries with this visibility may be shown to the caller,
+    /// once [`require_repo_access`] or [`Self::require_summary_access`] has
+    /// let them at the repo
+    pub fn can_see(&self, visibility: Visibility) -> bool {
+        self.is_authenticated() || visibility.is_public()
+    }
+
+    /// Fail with 401 without credentials, or 403 if the caller's scope is too narrow
+    pub fn require_scope(&self, required: ApiScope) -> Result<(), AppError> {
+        match self.scope {
+            Some(scope) if scope.allows(required) => Ok(()),
+            Some(scope) => Err(AppError::new(
+                StatusCode::FORBIDDEN,
+                "forbidden",
+                format!("This requires the '{}' scope; the caller's key has scope '{}'", required, scope),
+            )),
+            None if required == ApiScope::Read && self.anonymous_reads => Ok(()),
+            None => Err(AppError::unauthorized(format!(
+                "An API key with scope '{}' is required (X-API-Key or Authorization: Bearer)",
+                required
+            ))),
+        }
+    }
+
+    /// Fail unless the caller may read the repo's (or the commit's) summaries:
+    /// with 404 for a repo registered to an org other than the caller's, as
+    /// [`require_repo_access`] does, else with 401 for an anonymous caller
+    /// and a private summary
+    pub async fn require_summary_access(
+        &self,
+        pool: &PgPool,
+        repo: &str,
+        commit: Option<&str>,
+    ) -> Result<(), AppError> {
+        let repo_url = git::repo_url(repo);
+        let orgs = self.orgs();
+        if orgs != OrgFilter::Any {
+            let owner = get_registered_repo(pool, &repo_url)
+                .await
+                .or_internal("Failed to look up repository")?
+                .and_then(|r| r.org_id);
+            if !orgs.allows(owner) {
+                return Err(AppError::not_found(format!("Repository {} not found", repo)).for_repo(repo));
+            }
+        }
+        if self.is_authenticated() {
+            return Ok(());
+        }
+        let visibility = match commit {
+            Some(commit) => get_commit_visibility(pool, &repo_url, commit).await,
+            None => get_repo_visibility(pool, &repo_url).await,
+        }
+        .or_internal("Failed to check summary visibility")?;
+
+        if visibility.is_public() {
+            Ok(())
+        } else {
+            Err(AppError::unauthorized(format!(
+                "Summaries for {} are private; a valid API key is required",
+                repo
+            ))
+            .for_repo(repo))
+        }
+    }
+}
@@ -0,0 +1,446 @@
+//! `kicad-tool check`: ERC over the staged schematics (or a commit's), failing
+//! on violations the base revision doesn't have
+
+use anyhow::{Context, Result};
+use git2::{Commit, Repository};
+use kicad_db::schematic::{Project, SchematicFormat};
+use kicad_db::{introduced_findings, run_erc, ErcFinding, ErcRule, ErcSeverity, SchematicError};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use kicad_backend::services::{distill, git};
+
+/// Read from the repository's root unless --config names another file
+pub const CONFIG_FILE: &str = ".kicad-check.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CheckConfig {
+    /// Whether new warnings fail the check too, not only new errors
+    fail_on_warnings: bool,
+    suppress: Vec<Suppression>,
+}
+
+/// Findings of `rule` to let through, narrowed by any of the other fields
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Suppression {
+    rule: ErcRule,
+    reference: Option<String>,
+    pin: Option<String>,
+    net: Option<String>,
+    /// Shown next to what it suppresses
+    reason: Option<String>,
+}
+
+impl Suppression {
+    fn matches(&self, finding: &ErcFinding) -> bool {
+        let field = |want: &Option<String>, have: &Option<String>| want.is_none() || want == have;
+        self.rule == finding.rule
+            && field(&self.reference, &finding.reference)
+            && field(&self.pin, &finding.pin)
+            && field(&self.net, &finding.net)
+    }
+}
+
+/// How the report is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+    /// JUnit XML, which most CI systems show as test results
+    Junit,
+    /// GitHub Actions workflow commands, shown inline on pull requests
+    Github,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "junit" => Ok(Format::Junit),
+            "github" => Ok(Format::Github),
+            other => Err(format!("unknown format '{}' (expected text, json, junit or github)", other)),
+        }
+    }
+}
+
+/// A new ERC finding, or a schematic that doesn't parse
+#[derive(Debug, Serialize)]
+struct Problem {
+    severity: ErcSeverity,
+    /// An ERC rule, or "parse_error"
+    rule: String,
+    message: String,
+    /// Path in the repository, and line, of the symbol or label it's about
+    file: Option<String>,
+    line: Option<usize>,
+    reference: Option<String>,
+    pin: Option<String>,
+    net: Option<String>,
+    /// Whether it fails the check
+    blocking: bool,
+    suppressed: bool,
+    /// Of the suppression
+    reason: Option<String>,
+}
+
+impl Problem {
+    fn parse_error(error: &anyhow::Error) -> Self {
+        let (file, line, message) = match error.downcast_ref::<SchematicError>() {
+            Some(SchematicError::Syntax { file, line, message, .. }) => (Some(file), Some(*line), message.clone()),
+            Some(
+                SchematicError::NotASchematic { file, .. }
+                | SchematicError::NotASymbolLibrary { file, .. }
+                | SchematicError::RecursiveSheet { file, .. }
+                | SchematicError::MissingRoot(file),
+            ) => (Some(file), None, error.to_string()),
+            _ => (None, None, format!("{:#}", error)),
+        };
+        Problem {
+            severity: ErcSeverity::Error,
+            rule: "parse_error".to_string(),
+            message,
+            file: file.cloned(),
+            line,
+            reference: None,
+            pin: None,
+            net: None,
+            blocking: true,
+            suppressed: false,
+            reason: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    /// "the staged schematics" or "commit <hash> (<summary>)"
+    checked: String,
+    base: String,
+    /// False when no schematic changed, so nothing was checked
+    changed: bool,
+    passed: bool,
+    problems: Vec<Problem>,
+}
+
+/// `path`, or the repo's CONFIG_FILE if it has one
+fn load_config(path: Option<&Path>, root: &Path) -> Result<CheckConfig> {
+    let default = root.join(CONFIG_FILE);
+    let path = match path {
+        Some(path) => path,
+        None if default.is_file() => &default,
+        None => return Ok(CheckConfig::default()),
+    };
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("Invalid check config {}", path.display()))
+}
+
+/// Every project file staged in the index, by path
+fn index_sources(repo: &Repository) -> Result<BTreeMap<String, String>> {
+    let mut sources = BTreeMap::new();
+    for entry in repo.index()?.iter() {
+        let path = String::from_utf8_lossy(&entry.path).to_string();
+        if git::is_project_file(path.rsplit('/').next().unwrap_or_default()) {
+            let blob = repo.find_blob(entry.id)?;
+            if let Ok(text) = std::str::from_utf8(blob.content()) {
+                sources.insert(path, text.to_string());
+            }
+        }
+    }
+    Ok(sources)
+}
+
+fn commit_label(commit: &Commit) -> String {
+    format!("{} ({})", &commit.id().to_string()[..7], commit.summary().unwrap_or_default())
+}
+
+/// Finds where in the sources a finding's symbol or net label is
+struct Locator<'a> {
+    sources: &'a BTreeMap<String, String>,
+    /// The sheet file each reference is first placed in
+    files: HashMap<String, &'a str>,
+    root: Option<&'a str>,
+}
+
+impl<'a> Locator<'a> {
+    fn new(projects: &'a [Project], sources: &'a BTreeMap<String, String>) -> Self {
+        let mut files = HashMap::new();
+        for project in projects {
+            for instance in &project.instances {
+                for symbol in &project.files[&instance.file].symbols {
+                    files.entry(project.reference_of(instance, symbol)).or_insert(instance.file.as_str());
+                }
+            }
+        }
+        Locator {
+            sources,
+            files,
+            root: projects.first().map(|p| p.root_file.as_str()),
+        }
+    }
+
+    /// The first line of `file` holding any of `needles`, counting from 1
+    fn line_of(&self, file: &str, needles: &[String]) -> Option<usize> {
+        let text = self.sources.get(file)?;
+        text.lines().position(|line| needles.iter().any(|n| line.contains(n.as_str()))).map(|i| i + 1)
+    }
+
+    fn locate(&self, finding: &ErcFinding) -> (Option<String>, Option<usize>) {
+        if let Some(reference) = &finding.reference {
+            if let Some(file) = self.files.get(reference) {
+                let needles = [format!("\"Reference\" \"{}\"", reference), format!("(reference \"{}\")", reference)];
+                return (Some(file.to_string()), self.line_of(file, &needles));
+            }
+        }
+        if let Some(net) = &finding.net {
+            // Hierarchical nets are prefixed with their sheet's path
+            let name = net.rsplit('/').next().unwrap_or(net);
+            let needles = ["label", "global_label", "hierarchical_label"].map(|kind| format!("({} \"{}\"", kind, name));
+            for file in self.sources.keys().filter(|f| f.ends_with(".kicad_sch")) {
+                if let Some(line) = self.line_of(file, &needles) {
+                    return (Some(file.clone()), Some(line));
+                }
+            }
+        }
+        (self.root.map(str::to_string), None)
+    }
+}
+
+/// Check the staged schematics against HEAD, or `commit` against its first
+/// parent; `base` overrides what they're compared with. Prints the report
+/// and returns whether the check passed.
+pub fn run(
+    repo_dir: &Path,
+    commit: Option<&str>,
+    base: Option<&str>,
+    config: Option<&Path>,
+    format: Format,
+) -> Result<bool> {
+    let repo = Repository::discover(repo_dir).with_context(|| format!("No git repository at {}", repo_dir.display()))?;
+    let root = repo.workdir().unwrap_or_else(|| repo.path());
+    let config = load_config(config, root)?;
+
+    let resolve = |rev: &str| -> Result<Commit> {
+        Ok(repo.revparse_single(rev).with_context(|| format!("No such revision {}", rev))?.peel_to_commit()?)
+    };
+    let (checked, current, default_base) = match commit {
+        Some(rev) => {
+            let commit = resolve(rev)?;
+            let sources = super::tree_sources(&repo, &commit)?;
+            (format!("commit {}", commit_label(&commit)), sources, commit.parents().next())
+        }
+        // Before the first commit there is no HEAD to compare with
+        None => {
+            let head = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+            ("the staged schematics".to_string(), index_sources(&repo)?, head)
+        }
+    };
+    let base = match base {
+        Some(rev) => Some(resolve(rev)?),
+        None => default_base,
+    };
+    let previous = match &base {
+        Some(base) => super::tree_sources(&repo, base)?,
+        None => BTreeMap::new(),
+    };
+    let mut report = Report {
+        checked,
+        base: base.as_ref().map_or_else(|| "an empty repository".to_string(), commit_label),
+        changed: current != previous && current.values().any(|text| SchematicFormat::detect(text).is_some()),
+        passed: true,
+        problems: Vec::new(),
+    };
+    if report.changed {
+        report.problems = problems(&config, &current, &previous);
+        report.passed = !report.problems.iter().any(|p| p.blocking);
+    }
+    print!("{}", render(&report, format)?);
+    Ok(report.passed)
+}
+
+/// The current sources' new ERC findings, or why they don't parse
+fn problems(config: &CheckConfig, current: &BTreeMap<String, String>, previous: &BTreeMap<String, String>) -> Vec<Problem> {
+    let projects = match super::load_projects(current) {
+        Ok(projects) => projects,
+        Err(e) => return vec![Problem::parse_error(&e)],
+    };
+    for project in &projects {
+        super::warn_incomplete(project);
+    }
+    let findings = run_erc(&distill::merge_distilled(&projects));
+    let previous_findings = match super::load_projects(previous) {
+        Ok(projects) => run_erc(&distill::merge_distilled(&projects)),
+        Err(e) => {
+            eprintln!("warning: the base revision doesn't parse ({:#}); every violation counts as new", e);
+            Vec::new()
+        }
+    };
+
+    let locator = Locator::new(&projects, current);
+    introduced_findings(&findings, &previous_findings)
+        .into_iter()
+        .map(|finding| {
+            let suppression = config.suppress.iter().find(|s| s.matches(finding));
+            let (file, line) = locator.locate(finding);
+            Problem {
+                severity: finding.severity(),
+                rule: finding.rule.as_str().to_string(),
+                message: finding.message.clone(),
+                file,
+                line,
+                reference: finding.reference.clone(),
+                pin: finding.pin.clone(),
+                net: finding.net.clone(),
+                blocking: suppression.is_none()
+                    && (config.fail_on_warnings || finding.severity() == ErcSeverity::Error),
+                suppressed: suppression.is_some(),
+                reason: suppression.and_then(|s| s.reason.clone()),
+            }
+        })
+        .collect()
+}
+
+fn render(report: &Report, format: Format) -> Result<String> {
+    Ok(match format {
+        Format::Text => render_text(report),
+        Format::Json => serde_json::to_string_pretty(report)? + "\n",
+        Format::Junit => render_junit(report),
+        Format::Github => render_github(report),
+    })
+}
+
+fn text_line(problem: &Problem) -> String {
+    let location = match (&problem.file, problem.line) {
+        (Some(file), Some(line)) => format!(" ({}:{})", file, line),
+        (Some(file), None) => format!(" ({})", file),
+        _ => String::new(),
+    };
+    format!("  {:<7} [{}] {}{}", problem.severity.as_str(), problem.rule, problem.message, location)
+}
+
+fn render_text(report: &Report) -> String {
+    if !report.changed {
+        return "No schematic changes to check.\n".to_string();
+    }
+    let (suppressed, new): (Vec<&Problem>, Vec<&Problem>) = report.problems.iter().partition(|p| p.suppressed);
+    let mut out = String::new();
+    if new.is_empty() {
+        out += &format!("No new ERC violations in {}, against {}.\n", report.checked, report.base);
+    } else {
+        out += &format!("{} new problem(s) in {}, against {}:\n", new.len(), report.checked, report.base);
+        for problem in &new {
+            out += &text_line(problem);
+            out.push('\n');
+        }
+    }
+    if !suppressed.is_empty() {
+        out += &format!("{} suppressed:\n", suppressed.len());
+        for problem in &suppressed {
+            out += &text_line(problem);
+            if let Some(reason) = &problem.reason {
+                out += &format!(" ({})", reason);
+            }
+            out.push('\n');
+        }
+    }
+    let blocking = new.iter().filter(|p| p.blocking).count();
+    if blocking > 0 {
+        out += &format!(
+            "\n{} blocking problem(s): fix them, or suppress ERC findings in {} with a [[suppress]] entry.\n",
+            blocking, CONFIG_FILE
+        );
+    }
+    out
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// One test case per problem, failing if it blocks; a single passing case
+/// when there are none, so the suite isn't empty
+fn render_junit(report: &Report) -> String {
+    let failures = report.problems.iter().filter(|p| p.blocking).count();
+    let tests = report.problems.len().max(1);
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+    out += &format!(
+        "  <testsuite name=\"kicad-tool check\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\">\n",
+        tests,
+        failures,
+        report.problems.iter().filter(|p| p.suppressed).count()
+    );
+    out += &format!(
+        "    <properties><property name=\"checked\" value=\"{}\"/><property name=\"base\" value=\"{}\"/></properties>\n",
+        xml_escape(&report.checked),
+        xml_escape(&report.base)
+    );
+    if report.problems.is_empty() {
+        out += "    <testcase classname=\"erc\" name=\"No new ERC violations\"/>\n";
+    }
+    for problem in &report.problems {
+        let mut attributes = format!("classname=\"erc.{}\" name=\"{}\"", problem.rule, xml_escape(&problem.message));
+        if let Some(file) = &problem.file {
+            attributes += &format!(" file=\"{}\"", xml_escape(file));
+        }
+        if let Some(line) = problem.line {
+            attributes += &format!(" line=\"{}\"", line);
+        }
+        out += &format!("    <testcase {}>\n", attributes);
+        if problem.suppressed {
+            out += &format!("      <skipped message=\"{}\"/>\n", xml_escape(problem.reason.as_deref().unwrap_or("suppressed")));
+        } else if problem.blocking {
+            out += &format!(
+                "      <failure type=\"{}\" message=\"{}\"/>\n",
+                problem.severity.as_str(),
+                xml_escape(&problem.message)
+            );
+        } else {
+            out += &format!("      <system-out>{}: {}</system-out>\n", problem.severity.as_str(), xml_escape(&problem.message));
+        }
+        out += "    </testcase>\n";
+    }
+    out += "  </testsuite>\n</testsuites>\n";
+    out
+}
+
+/// Escape a workflow command's message
+fn github_data(text: &str) -> String {
+    text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escape a workflow command's property value
+fn github_property(text: &str) -> String {
+    github_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// `::error`/`::warning` annotations for the unsuppressed problems, then the
+/// text report for the log
+fn render_github(report: &Report) -> String {
+    let mut out = String::new();
+    for problem in report.problems.iter().filter(|p| !p.suppressed) {
+        let mut properties = Vec::new();
+        if let Some(file) = &problem.file {
+            properties.push(format!("file={}", github_property(file)));
+        }
+        if let Some(line) = problem.line {
+            properties.push(format!("line={}", line));
+        }
+        let title = match problem.rule.as_str() {
+            "parse_error" => "Schematic doesn't parse".to_string(),
+            rule => format!("ERC {}", rule),
+        };
+        properties.push(format!("title={}", github_property(&title)));
+        let command = if problem.blocking { "error" } else { "warning" };
+        out += &format!("::{} {}::{}\n", command, properties.join(","), github_data(&problem.message));
+    }
+    out + &render_text(report)
+}
+
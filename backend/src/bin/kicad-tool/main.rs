@@ -0,0 +1,350 @@
+//! kicad-tool: the backend's schematic processing on local files, without
+//! the server or a database, e.g. for pre-commit hooks
+//!
+//! Every command but `summarize` works offline; `summarize` reads the
+//! backend's configuration (config.toml, .env, XAI_API_KEY) for the model.
+
+use anyhow::{bail, Context, Result};
+use git2::{ObjectType, Repository, TreeWalkMode, TreeWalkResult};
+use kicad_db::detail_levels::DetailLevel;
+use kicad_db::schematic::{self, diff_projects, render_sheet_svg, Project, TitleBlock};
+use kicad_db::PromptLibrary;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing_subscriber::EnvFilter;
+
+use kicad_backend::config::Config;
+use kicad_backend::services::{distill, git, llm::ChatProvider, sampling::sampled, summary};
+
+mod check;
+
+const USAGE: &str = "\
+Usage: kicad-tool <command> [options]
+
+Commands:
+  parse <schematic>                    Print the project's distilled netlist as JSON
+  diff <old schematic> <new schematic> Print the component and net changes [--json]
+  bom <schematic>                      Print the bill of materials as JSON [--csv]
+  render <schematic>                   Write an SVG of each sheet [--out <dir>]
+  summarize [<commit>]                 Summarize a commit of a local git repo with Grok
+                                       [--repo <dir>] [--detail brief|standard|deep]
+  check [<commit>]                     Run ERC on the staged schematics (or a commit's) and
+                                       fail on violations new since HEAD (or its parent)
+                                       [--repo <dir>] [--base <rev>] [--config <file>]
+                                       [--format text|json|junit|github]
+
+<schematic> is a project's root .kicad_sch; the files in its directory
+(sub-sheets, .kicad_pro, symbol libraries) are read along with it.
+
+check reads suppressions from .kicad-check.toml at the repository's root:
+  fail_on_warnings = false
+  [[suppress]]
+  rule = \"unconnected_pin\"   # reference, pin and net narrow it down
+  reference = \"J3\"
+  reason = \"Spare header pins\"";
+
+/// Options taking a value; any other --name is a switch
+const VALUED_OPTIONS: &[&str] = &["out", "repo", "detail", "base", "config", "format"];
+
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    switches: Vec<String>,
+}
+
+impl Args {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut parsed = Args {
+            positional: Vec::new(),
+            options: HashMap::new(),
+            switches: Vec::new(),
+        };
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if VALUED_OPTIONS.contains(&name) => {
+                    let value = args.next().with_context(|| format!("--{} needs a value", name))?;
+                    parsed.options.insert(name.to_string(), value);
+                }
+                Some(name) => parsed.switches.push(name.to_string()),
+                None => parsed.positional.push(arg),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn switch(&self, name: &str) -> bool {
+        self.switches.iter().any(|s| s == name)
+    }
+
+    /// The positional arguments, exactly `count` of them
+    fn exactly(&self, count: usize) -> Result<&[String]> {
+        if self.positional.len() != count {
+            bail!("Expected {} argument(s), got {}\n\n{}", count, self.positional.len(), USAGE);
+        }
+        Ok(&self.positional)
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Logs go to stderr, so output can be piped; RUST_LOG=info shows more
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr).init();
+    // Offline commands don't load the backend's config, only this from it
+    distill::configure(None, std::env::var_os("KICAD_SYMBOL_DIR").map(PathBuf::from));
+
+    let mut args = std::env::args().skip(1);
+    let Some(command) = args.next() else {
+        println!("{}", USAGE);
+        return Ok(());
+    };
+    let args = Args::parse(args)?;
+    match command.as_str() {
+        "parse" => {
+            let project = load_schematic(&args.exactly(1)?[0])?;
+            println!("{}", serde_json::to_string_pretty(&project.to_distilled())?);
+        }
+        "diff" => {
+            let paths = args.exactly(2)?;
+            let diff = diff_projects(&load_schematic(&paths[0])?, &load_schematic(&paths[1])?);
+            if args.switch("json") {
+                println!("{}", serde_json::to_string_pretty(&diff)?);
+            } else if diff.is_empty() {
+                println!("No component or net changes.");
+            } else {
+                println!("{}", diff.describe());
+            }
+        }
+        "bom" => {
+            let bom = load_schematic(&args.exactly(1)?[0])?.bom();
+            if args.switch("csv") {
+                print!("{}", bom_csv(&bom));
+            } else {
+                println!("{}", serde_json::to_string_pretty(&bom)?);
+            }
+        }
+        "render" => {
+            let project = load_schematic(&args.exactly(1)?[0])?;
+            let out = PathBuf::from(args.options.get("out").map_or(".", String::as_str));
+            for path in render(&project, &out)? {
+                println!("{}", path.display());
+            }
+        }
+        "summarize" => {
+            let commit = match args.positional.as_slice() {
+                [] => "HEAD",
+                [commit] => commit.as_str(),
+                _ => bail!("Expected at most one commit\n\n{}", USAGE),
+            };
+            let repo = Path::new(args.options.get("repo").map_or(".", String::as_str));
+            let level = match args.options.get("detail") {
+                Some(level) => level.parse::<DetailLevel>().map_err(anyhow::Error::msg)?,
+                None => DetailLevel::default(),
+            };
+            println!("{}", summarize(repo, commit, level).await?);
+        }
+        "check" => {
+            let commit = match args.positional.as_slice() {
+                [] => None,
+                [commit] => Some(commit.as_str()),
+                _ => bail!("Expected at most one commit\n\n{}", USAGE),
+            };
+            let repo = Path::new(args.options.get("repo").map_or(".", String::as_str));
+            let base = args.options.get("base").map(String::as_str);
+            let config = args.options.get("config").map(Path::new);
+            let format = match args.options.get("format") {
+                Some(format) => format.parse::<check::Format>().map_err(anyhow::Error::msg)?,
+                None => check::Format::Text,
+            };
+            if !check::run(repo, commit, base, config, format)? {
+                std::process::exit(1);
+            }
+        }
+        "help" | "--help" | "-h" => println!("{}", USAGE),
+        other => bail!("Unknown command {:?}\n\n{}", other, USAGE),
+    }
+    Ok(())
+}
+
+/// Every project file under `dir` (hidden directories aside), by path
+/// relative to it
+fn dir_sources(dir: &Path) -> Result<BTreeMap<String, String>> {
+    let mut sources = BTreeMap::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current).with_context(|| format!("Failed to read {}", current.display()))? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            if name.starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                pending.push(path);
+            } else if git::is_project_file(name) {
+                let relative = path.strip_prefix(dir)?.components();
+                let key = relative.map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+                let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+                sources.insert(key, text);
+            }
+        }
+    }
+    Ok(sources)
+}
+
+/// The project rooted at the schematic `path`, its sub-sheets and libraries
+/// read from the same directory
+fn load_schematic(path: &str) -> Result<Project> {
+    let path = Path::new(path);
+    if !path.is_file() {
+        bail!("No such schematic: {}", path.display());
+    }
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let projects = load_projects(&dir_sources(dir)?).with_context(|| format!("Failed to parse {}", path.display()))?;
+    let project = projects
+        .into_iter()
+        .find(|p| p.root_file == name)
+        .with_context(|| format!("{} isn't the root sheet of a project; pass the one next to the .kicad_pro", path.display()))?;
+    warn_incomplete(&project);
+    Ok(project)
+}
+
+/// The projects among `sources`, with the repo's and KiCad's symbol libraries
+fn load_projects(sources: &BTreeMap<String, String>) -> Result<Vec<Project>> {
+    Ok(schematic::load_projects_with_libraries(sources, &distill::symbol_libraries(sources))?)
+}
+
+/// Warn on stderr about sheets and symbols `project` couldn't find
+fn warn_incomplete(project: &Project) {
+    let root = &project.root_file;
+    if !project.missing_files.is_empty() {
+        eprintln!("warning: {} references missing sheet(s): {}", root, project.missing_files.join(", "));
+    }
+    if !project.unresolved_symbols.is_empty() {
+        eprintln!("warning: {} places symbol(s) with no definition: {}", root, project.unresolved_symbols.join(", "));
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    match text.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_string(),
+    }
+}
+
+fn bom_csv(bom: &[schematic::BomLine]) -> String {
+    let mut csv = String::from("Quantity,References,Value,Footprint,MPN\n");
+    for line in bom {
+        let fields = [
+            line.quantity.to_string(),
+            line.references.join(" "),
+            line.value.clone(),
+            line.footprint.clone().unwrap_or_default(),
+            line.mpn.clone().unwrap_or_default(),
+        ];
+        csv.push_str(&fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Write `<project>.svg`, or `<project>-<page>.svg` for each page of a
+/// hierarchy, into `out`; returns the files written
+fn render(project: &Project, out: &Path) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(out).with_context(|| format!("Failed to create {}", out.display()))?;
+    let date = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    let pages = project.instances.len();
+    let mut written = Vec::new();
+    for instance in 0..pages {
+        let title = TitleBlock {
+            repo: &project.name,
+            commit: "working tree",
+            date: &date,
+            page: instance + 1,
+            pages,
+        };
+        let path = match pages {
+            1 => out.join(format!("{}.svg", project.name)),
+            _ => out.join(format!("{}-{}.svg", project.name, instance + 1)),
+        };
+        std::fs::write(&path, render_sheet_svg(project, instance, &title))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Every project file in `commit`'s tree, by path
+fn tree_sources(repo: &Repository, commit: &git2::Commit) -> Result<BTreeMap<String, String>> {
+    let mut sources = BTreeMap::new();
+    commit.tree()?.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        let name = entry.name().unwrap_or_default();
+        if entry.kind() == Some(ObjectType::Blob) && git::is_project_file(name) {
+            let blob = entry.to_object(repo).and_then(|object| object.peel_to_blob());
+            if let Some(text) = blob.ok().and_then(|blob| String::from_utf8(blob.content().to_vec()).ok()) {
+                sources.insert(format!("{}{}", dir, name), text);
+            }
+        }
+        TreeWalkResult::Ok
+    })?;
+    Ok(sources)
+}
+
+/// "owner/repo" from the origin remote's URL, else the directory's name
+fn repo_name(repo: &Repository) -> String {
+    let origin = repo.find_remote("origin").ok().and_then(|r| r.url().map(str::to_string));
+    let from_origin = origin.and_then(|url| {
+        let path = url.trim_end_matches('/').trim_end_matches(".git").replace(':', "/");
+        let segments: Vec<&str> = path.rsplit('/').take(2).collect();
+        (segments.len() == 2).then(|| format!("{}/{}", segments[1], segments[0]))
+    });
+    from_origin.unwrap_or_else(|| {
+        let dir = repo.workdir().unwrap_or_else(|| repo.path());
+        dir.file_name().map_or_else(|| "repo".to_string(), |n| n.to_string_lossy().to_string())
+    })
+}
+
+/// Grok's account of what `commit` changed in the schematics, from its
+/// component and net diff against its first parent
+async fn summarize(repo_dir: &Path, commit: &str, level: DetailLevel) -> Result<String> {
+    let repo = Repository::discover(repo_dir).with_context(|| format!("No git repository at {}", repo_dir.display()))?;
+    let head = repo.revparse_single(commit)?.peel_to_commit()?;
+    let parent = head.parents().next();
+    let current = tree_sources(&repo, &head)?;
+    let previous = match &parent {
+        Some(parent) => tree_sources(&repo, parent)?,
+        None => BTreeMap::new(),
+    };
+    let changes = distill::diff_project_sets(&load_projects(&previous)?, &load_projects(&current)?);
+    if changes.is_empty() {
+        return Ok("No component or net changes.".to_string());
+    }
+
+    kicad_db::init(&[]).context("Invalid environment")?;
+    let config = Config::load().context("Invalid configuration")?;
+    let mut prompts = PromptLibrary::builtin();
+    if let Some(dir) = &config.prompts_dir {
+        prompts.load_dir(dir).context("Failed to load prompt templates")?;
+    }
+    let chat: Arc<dyn ChatProvider> = Arc::new(config.xai_client().context("Failed to initialize XAI client")?);
+    let chat = sampled(chat, &config.sampling.summary);
+    let model = &config.models.summary;
+    let head_hash = head.id().to_string();
+    let base_hash = parent.map_or_else(|| "(none)".to_string(), |p| p.id().to_string());
+    let (text, _) = summary::generate_comparison(
+        chat.as_ref(),
+        &prompts,
+        model,
+        config.models.context_window(model),
+        &repo_name(&repo),
+        &base_hash,
+        &head_hash,
+        &[(head_hash.clone(), head.summary().map(str::to_string))],
+        &distill::describe_changes(&changes),
+        level,
+    )
+    .await?;
+    Ok(text)
+}
@@ -0,0 +1,63 @@
+use axum::{
+    extract::Request,
+    http::{header, Extensions, HeaderMap, HeaderValue, StatusCode, Version},
+    middleware::Next,
+    response::Response,
+};
+use tower_http::compression::{
+    predicate::{NotForContentType, Predicate, SizeAbove},
+    CompressionLayer,
+};
+
+/// Bodies smaller than this aren't worth the compressor's time
+const MIN_SIZE_BYTES: u16 = 1024;
+
+/// Content types that shrink well: JSON (and NDJSON exports), SVG and text
+const COMPRESSIBLE: &[&str] = &[
+    "application/json",
+    "application/x-ndjson",
+    "image/svg+xml",
+    "text/",
+];
+
+fn is_compressible(_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| COMPRESSIBLE.iter().any(|prefix| v.starts_with(prefix)))
+}
+
+/// gzip or brotli, whichever the client prefers, for compressible bodies
+/// over 1 KiB
+///
+/// Server-sent events are never compressed: the encoder holds output back
+/// until it has a block's worth, which would stall the stream.
+pub fn layer() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new().br(true).gzip(true).compress_when(
+        SizeAbove::new(MIN_SIZE_BYTES)
+            .and(NotForContentType::SSE)
+            .and(is_compressible),
+    )
+}
+
+/// Middleware marking the ETag of a compressed response weak
+///
+/// A strong ETag promises the exact bytes, which the compressed copy isn't.
+/// `If-None-Match` is compared weakly, so the tag still revalidates.
+/// Goes outside [`layer`], to see its `Content-Encoding`.
+pub async fn weaken_etag(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    if !response.headers().contains_key(header::CONTENT_ENCODING) {
+        return response;
+    }
+    let weak = response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"))
+        .and_then(|etag| HeaderValue::from_str(&format!("W/{}", etag)).ok());
+    if let Some(weak) = weak {
+        response.headers_mut().insert(header::ETAG, weak);
+    }
+    response
+}
@@ -0,0 +1,75 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+/// Whether the request's `If-None-Match` lists `etag`
+///
+/// Weak comparison, as RFC 9110 asks for `If-None-Match`: `W/"x"` matches `"x"`.
+pub fn matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+        })
+}
+
+/// A strong ETag for `body`: the start of its SHA-256, quoted
+pub fn content_etag(body: &[u8]) -> String {
+    format!("\"{}\"", &hex::encode(Sha256::digest(body))[..32])
+}
+
+/// Whether `response` is a JSON body this middleware may read whole; streams
+/// such as SSE and NDJSON are passed through
+fn is_json(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"))
+}
+
+/// Route middleware answering conditional GETs of JSON responses
+///
+/// Successful responses without an ETag of their own get one hashed from
+/// the body, and a request whose `If-None-Match` lists it gets `304 Not
+/// Modified` without the body. The handler still runs; what's saved is the
+/// transfer, which for a commit's netlist or diff is most of the cost.
+/// Handlers that set their own ETag (images, files) check it themselves.
+/// Use as `route_layer(middleware::from_fn(conditional::etag))`.
+pub async fn etag(request: Request, next: Next) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+    let conditions = request.headers().clone();
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK || response.headers().contains_key(header::ETAG) || !is_json(&response) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Failed to read a response body to tag it: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let etag = content_etag(&bytes);
+    parts.headers.insert(header::ETAG, HeaderValue::from_str(&etag).expect("ETag is ASCII"));
+    if matches(&conditions, &etag) {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_TYPE);
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::empty());
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
@@ -0,0 +1,915 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::{DateTime, Duration, Months, NaiveDate, Utc};
+use kicad_db::{
+    experiment_results, feedback_totals, get_registered_repo, get_repo_schedule, get_webhook_delivery, list_repo_schedules, recent_prompt_audits,
+    recent_webhook_deliveries, set_repo_schedule, spend_report, system_totals, PgPool, RepoSchedule, SpendTotal,
+};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::auth::Viewer;
+use crate::config::Config;
+use crate::controllers::{grok::backfill_settings, hook, repos::processing_policy};
+use crate::error::{AppError, ResultExt};
+use crate::services::{backfill, costs, git, github, lifecycle, onboarding, reload, sampling::sampled, status, taxonomy};
+use crate::state::AppState;
+use crate::types::{
+    AdminOverviewResponse, BackfillProgress, ClassifyPartsResponse, LifecycleCheckResponse, ReloadConfigResponse, BackfillStatus, CacheHitRate, CostLine, CostReportQuery, CostReportResponse, ExperimentVariantItem, ExperimentsQuery, ExperimentsResponse, FeedbackReportQuery, FeedbackReportResponse, HookUpdateResponse,
+    JobCommit, JobDetailResponse, JobListQuery, JobListResponse, OnboardGithubOrgRequest, OnboardGithubOrgResponse, OnboardStatus, OnboardWebhook, OnboardedRepoItem, PromptAuditItem, PromptAuditListResponse,
+    PromptAuditQuery, RepoScheduleItem, ScheduleListResponse, ErrorRate, JobQueueDepth, ClassQueueDepth, WorkClass,
+    UpdateScheduleRequest, WebhookDeliveryItem, WebhookDeliveryListResponse,
+};
+
+/// Shortest per-repo re-sync interval; each re-sync fetches the whole repo
+const MIN_INTERVAL_SECS: u32 = 5 * 60;
+
+/// Deliveries listed by `/api/admin/webhooks`
+const WEBHOOK_LIST_LIMIT: i64 = 100;
+
+/// Default and most entries listed by `/api/admin/prompts`
+const PROMPT_LIST_LIMIT: i64 = 50;
+const MAX_PROMPT_LIST_LIMIT: i64 = 200;
+
+/// Default period of `/api/admin/feedback` and `/api/admin/experiments`, in days
+const FEEDBACK_REPORT_DAYS: u32 = 30;
+
+fn schedule_item(s: RepoSchedule) -> RepoScheduleItem {
+    RepoScheduleItem {
+        repo: git::repo_slug(&s.repo_url).unwrap_or_else(|| s.repo_url.clone()),
+        repo_url: s.repo_url,
+        enabled: s.enabled,
+        interval_secs: s.interval_secs,
+        effective_interval_secs: s.effective_interval_secs,
+        last_checked_at: s.last_checked_at,
+        last_error: s.last_error,
+        next_check_at: s.next_check_at,
+    }
+}
+
+fn default_interval(config: &Config) -> i64 {
+    i64::try_from(config.resync_interval_secs).unwrap_or(i64::MAX)
+}
+
+/// List re-sync schedules
+///
+/// Every repo with stored commits is re-checked for commits a webhook missed,
+/// at the default interval unless it has its own. Org keys only list their
+/// org's repos. Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/admin/schedules",
+    responses(
+        (status = 200, description = "Schedules by repo URL", body = ScheduleListResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn list_schedules(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    viewer: Viewer,
+) -> Result<Json<ScheduleListResponse>, AppError> {
+    let schedules = list_repo_schedules(&state, default_interval(&config), viewer.orgs())
+        .await
+        .or_internal("Failed to list schedules")?;
+    Ok(Json(ScheduleListResponse {
+        default_interval_secs: config.resync_interval_secs,
+        schedules: schedules.into_iter().map(schedule_item).collect(),
+    }))
+}
+
+/// Set a repo's re-sync schedule
+///
+/// Repos don't need stored commits to be scheduled; the first re-sync is
+/// then due right away. Requires an admin key.
+#[utoipa::path(
+    put,
+    path = "/api/admin/schedules",
+    request_body = UpdateScheduleRequest,
+    responses(
+        (status = 200, description = "The repo's schedule", body = RepoScheduleItem),
+        (status = 400, description = "Empty repo or interval below five minutes", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn update_schedule(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    Json(req): Json<UpdateScheduleRequest>,
+) -> Result<Json<RepoScheduleItem>, AppError> {
+    let repo = req.repo.trim().trim_matches('/');
+    if repo.is_empty() {
+        return Err(AppError::bad_request("repo must not be empty"));
+    }
+    if let Some(secs) = req.interval_secs.filter(|&secs| secs < MIN_INTERVAL_SECS) {
+        return Err(AppError::bad_request(format!(
+            "interval_secs must be at least {} (got {})",
+            MIN_INTERVAL_SECS, secs
+        )));
+    }
+    let interval = req
+        .interval_secs
+        .map(|secs| i32::try_from(secs).map_err(|_| AppError::bad_request("interval_secs is too large")))
+        .transpose()?;
+    let enabled = req.enabled.unwrap_or(true);
+
+    let repo_url = git::repo_url(repo);
+    set_repo_schedule(&state, &repo_url, enabled, interval)
+        .await
+        .or_internal("Failed to store schedule")?;
+    info!("Re-sync schedule for {}: enabled={}, interval={:?}", repo, enabled, interval);
+
+    let schedule = get_repo_schedule(&state, &repo_url, default_interval(&config))
+        .await
+        .or_internal("Failed to load schedule")?
+        .ok_or_else(|| AppError::internal("Stored schedule is missing"))?;
+    Ok(Json(schedule_item(schedule)))
+}
+
+/// Sum `totals` into one line per key, most expensive first
+fn cost_lines(
+    totals: &[SpendTotal],
+    key: impl Fn(&SpendTotal) -> (Option<String>, Option<String>),
+    budget: impl Fn(Option<&str>) -> Option<f64>,
+) -> Vec<CostLine> {
+    let mut lines: Vec<CostLine> = Vec::new();
+    for total in totals {
+        let (key, name) = key(total);
+        let index = match lines.iter().position(|line| line.key == key) {
+            Some(index) => index,
+            None => {
+                lines.push(CostLine {
+                    key,
+                    name,
+                    calls: 0,
+                    prompt_tokens: 0,
+                    completion_tokens: 0,
+                    cost_usd: 0.0,
+                    budget_usd: None,
+                    over_budget: false,
+                });
+                lines.len() - 1
+            }
+        };
+        let line = &mut lines[index];
+        line.calls += total.calls;
+        line.prompt_tokens += total.prompt_tokens;
+        line.completion_tokens += total.completion_tokens;
+        line.cost_usd += total.cost_usd;
+    }
+    for line in &mut lines {
+        line.budget_usd = budget(line.key.as_deref());
+        line.over_budget = line.budget_usd.is_some_and(|budget| line.cost_usd >= budget);
+    }
+    lines.sort_by(|a, b| b.cost_usd.total_cmp(&a.cost_usd));
+    lines
+}
+
+/// Report AI spend for a month
+///
+/// Every AI call is priced from its token usage with the configured pricing
+/// and charged to the repo and API key it was made for; cached answers cost
+/// nothing. Calls that failed over are reported under the provider that
+/// served them. Budgets apply per calendar month (UTC). Org keys only see their
+/// org's spend. Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/admin/costs",
+    params(CostReportQuery),
+    responses(
+        (status = 200, description = "Spend by repo, API key, provider and model", body = CostReportResponse),
+        (status = 400, description = "Month is not YYYY-MM", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn cost_report(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    viewer: Viewer,
+    Query(query): Query<CostReportQuery>,
+) -> Result<Json<CostReportResponse>, AppError> {
+    let since: DateTime<Utc> = match query.month.as_deref() {
+        Some(month) => NaiveDate::parse_from_str(&format!("{}-01", month.trim()), "%Y-%m-%d")
+            .map_err(|_| AppError::bad_request(format!("month must be YYYY-MM (got {:?})", month)))?
+            .and_hms_opt(0, 0, 0)
+            .expect("midnight is a valid time")
+            .and_utc(),
+        None => costs::month_start(Utc::now()),
+    };
+    let until = since
+        .checked_add_months(Months::new(1))
+        .ok_or_else(|| AppError::bad_request("month is out of range"))?;
+
+    let totals = spend_report(&state, since, until, viewer.orgs())
+        .await
+        .or_internal("Failed to load AI spend")?;
+    let budgets = &config.costs;
+    let by_repo = cost_lines(
+        &totals,
+        |t| {
+            let slug = t.repo_url.as_deref().map(|url| git::repo_slug(url).unwrap_or_else(|| url.to_string()));
+            (slug, None)
+        },
+        |slug| budgets.repo_budget(slug?),
+    );
+    let by_api_key = cost_lines(
+        &totals,
+        |t| (t.api_key.clone(), t.api_key_name.clone()),
+        |key| key.and(budgets.key_budget()),
+    );
+    let by_provider = cost_lines(&totals, |t| (Some(t.provider.clone()), None), |_| None);
+    let by_model = cost_lines(&totals, |t| (Some(t.model.clone()), None), |_| None);
+
+    Ok(Json(CostReportResponse {
+        month: since.format("%Y-%m").to_string(),
+        total_usd: totals.iter().fold(0.0, |sum, t| sum + t.cost_usd),
+        fallback_model: config.costs.fallback_model.clone(),
+        by_repo,
+        by_api_key,
+        by_provider,
+        by_model,
+    }))
+}
+
+/// Report feedback on AI summaries
+///
+/// Thumbs up and down given through `POST /api/grok/feedback` over the last
+/// `days` days, totalled per kind of summary, prompt template and model, to
+/// compare how useful each combination's summaries are. Org keys only see
+/// ratings of their org's summaries. Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/admin/feedback",
+    params(FeedbackReportQuery),
+    responses(
+        (status = 200, description = "Ratings by kind, prompt template and model", body = FeedbackReportResponse),
+        (status = 400, description = "days is 0", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn feedback_report(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Query(query): Query<FeedbackReportQuery>,
+) -> Result<Json<FeedbackReportResponse>, AppError> {
+    let days = query.days.unwrap_or(FEEDBACK_REPORT_DAYS);
+    if days == 0 {
+        return Err(AppError::bad_request("days must be at least 1"));
+    }
+    let since = Utc::now() - Duration::days(days.into());
+    let totals = feedback_totals(&state, since, viewer.orgs())
+        .await
+        .or_internal("Failed to load summary feedback")?;
+    Ok(Json(FeedbackReportResponse {
+        since,
+        totals: totals.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Compare the variants of A/B experiments
+///
+/// Per experiment and variant, the commit summaries handed out over the last
+/// `days` days, the feedback given on them and the AI calls made for them,
+/// to weigh how useful each variant's summaries are against what they cost.
+/// Variants of the running experiment carry their configured weight. Org
+/// keys only see their org's repos. Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/admin/experiments",
+    params(ExperimentsQuery),
+    responses(
+        (status = 200, description = "Summaries, ratings and spend by experiment and variant", body = ExperimentsResponse),
+        (status = 400, description = "days is 0", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn list_experiments(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    viewer: Viewer,
+    Query(query): Query<ExperimentsQuery>,
+) -> Result<Json<ExperimentsResponse>, AppError> {
+    let days = query.days.unwrap_or(FEEDBACK_REPORT_DAYS);
+    if days == 0 {
+        return Err(AppError::bad_request("days must be at least 1"));
+    }
+    let since = Utc::now() - Duration::days(days.into());
+    let results = experiment_results(&state, since, viewer.orgs())
+        .await
+        .or_internal("Failed to load experiment results")?;
+    let active = config.experiment.as_ref();
+    let variants = results
+        .into_iter()
+        .map(|result| {
+            let weight = active
+                .filter(|e| e.name == result.experiment)
+                .and_then(|e| e.variants.iter().find(|v| v.name == result.variant))
+                .map(|v| v.weight);
+            ExperimentVariantItem { weight, ..result.into() }
+        })
+        .collect();
+    Ok(Json(ExperimentsResponse {
+        active: active.map(|e| e.name.clone()),
+        since,
+        variants,
+    }))
+}
+
+/// Get a system overview
+///
+/// What a dashboard shows, in one call: registered repos, commits processed
+/// and failed in the last 24 hours, the work queued (in total and by work
+/// class), AI spend since midnight UTC, this server's error rate over the
+/// last hour and its cache hit rates since it started. Org keys only count
+/// their org's repos, commits, spend and backfills; running jobs, AI calls
+/// waiting, errors and caches are this server's.
+/// Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/admin/overview",
+    responses(
+        (status = 200, description = "The overview", body = AdminOverviewResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn overview(
+    State(state): State<AppState>,
+    viewer: Viewer,
+) -> Result<Json<AdminOverviewResponse>, AppError> {
+    let now = Utc::now();
+    let midnight = now.date_naive().and_hms_opt(0, 0, 0).expect("midnight is a valid time").and_utc();
+    let totals = system_totals(&state.pool, viewer.orgs(), now - Duration::hours(24), midnight)
+        .await
+        .or_internal("Failed to load the overview")?;
+
+    let backfills: Vec<_> = backfill::jobs()
+        .into_iter()
+        .filter(|job| viewer.orgs().allows(job.org) && job.progress.status == BackfillStatus::Running)
+        .collect();
+    let queue = JobQueueDepth {
+        commits_queued: totals.commits_queued,
+        active_jobs: status::active_job_count(),
+        running_backfills: backfills.len(),
+        backfill_commits_remaining: backfills.iter().map(|job| job.progress.remaining).sum(),
+        classes: WorkClass::ALL
+            .into_iter()
+            .map(|class| {
+                let running: Vec<_> = backfills.iter().filter(|job| job.progress.class == class).collect();
+                ClassQueueDepth {
+                    class,
+                    running_backfills: running.len(),
+                    backfill_commits_remaining: running.iter().map(|job| job.progress.remaining).sum(),
+                    ai_calls_waiting: state.limiter.waiting(class),
+                }
+            })
+            .collect(),
+    };
+
+    let requests = status::recent_requests();
+    let error_rate = ErrorRate {
+        requests: requests.requests,
+        server_errors: requests.server_errors,
+        rate: if requests.requests == 0 {
+            0.0
+        } else {
+            requests.server_errors as f64 / requests.requests as f64
+        },
+    };
+    let cache_hit_rates = status::cache_lookups()
+        .into_iter()
+        .map(|(cache, lookups)| {
+            let total = lookups.hits + lookups.misses;
+            CacheHitRate {
+                cache: cache.to_string(),
+                hits: lookups.hits,
+                misses: lookups.misses,
+                hit_rate: (total > 0).then(|| lookups.hits as f64 / total as f64),
+            }
+        })
+        .collect();
+
+    Ok(Json(AdminOverviewResponse {
+        registered_repos: totals.registered_repos,
+        commits_processed_24h: totals.commits_processed,
+        commits_failed_24h: totals.commits_failed,
+        queue,
+        ai_spend_today_usd: totals.ai_spend_usd,
+        error_rate,
+        cache_hit_rates,
+        generated_at: now,
+    }))
+}
+
+/// Classify parts in the component taxonomy now
+///
+/// Asks the classification model, or the routing fallback while that model
+/// is slow or failing, about the stored parts no one has classified yet, as
+/// the background task does after each processed commit, and returns how
+/// many it classified; a large backlog takes several calls.
+/// Parts are shared by every org, so this needs an instance-wide admin key.
+#[utoipa::path(
+    post,
+    path = "/api/admin/taxonomy/classify",
+    responses(
+        (status = 200, description = "Parts classified", body = ClassifyPartsResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an instance-wide admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError),
+        (status = 503, description = "XAI is unavailable", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn classify_parts(
+    State(app): State<AppState>,
+    viewer: Viewer,
+) -> Result<Json<ClassifyPartsResponse>, AppError> {
+    if viewer.org().is_some() {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "Only instance-wide admin keys may classify parts",
+        ));
+    }
+    let model = app.health.route("classification", &app.config().models.classification);
+    let chat = sampled(app.chat.clone(), &app.config().sampling.classification);
+    let classified = taxonomy::classify_pending(&app.pool, chat.as_ref(), &app.prompts(), &model)
+        .await
+        .or_internal("Failed to classify parts")?;
+    info!("Classified {} parts with {}", classified, model);
+    Ok(Json(ClassifyPartsResponse { classified, model }))
+}
+
+/// Reload the config and prompt templates
+///
+/// Reads the config file and environment again, as SIGHUP or a change to the
+/// file does, and swaps them in with the prompt templates for requests and
+/// jobs that start afterwards. A config that doesn't validate is rejected
+/// and the running one stays in effect. The config covers every org, so
+/// this needs an instance-wide admin key.
+#[utoipa::path(
+    post,
+    path = "/api/admin/reload",
+    responses(
+        (status = 200, description = "Reloaded", body = ReloadConfigResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an instance-wide admin key", body = ApiError),
+        (status = 422, description = "Invalid config or prompt templates; nothing changed", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn reload_config(
+    State(app): State<AppState>,
+    viewer: Viewer,
+) -> Result<Json<ReloadConfigResponse>, AppError> {
+    if viewer.org().is_some() {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "Only instance-wide admin keys may reload the config",
+        ));
+    }
+    let reloaded = reload::reload(&app, "POST /api/admin/reload").await.map_err(|e| {
+        AppError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_config", format!("{:#}", e))
+    })?;
+    Ok(Json(ReloadConfigResponse {
+        prompts: reloaded.prompts,
+        restart_required: reloaded.restart_required.into_iter().map(String::from).collect(),
+    }))
+}
+
+/// Check registered repos' parts for lifecycle risks now
+///
+/// Runs the scheduled check: looks up every MPN of each registered repo's
+/// latest commit with the part supplier, opens alerts for NRND, EOL and
+/// obsolete parts, resolves alerts for parts that are gone or recovered, and
+/// announces new ones on the notification webhook. Alerts cover every org, so
+/// this needs an instance-wide admin key.
+#[utoipa::path(
+    post,
+    path = "/api/admin/lifecycle/check",
+    responses(
+        (status = 200, description = "What the check found", body = LifecycleCheckResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an instance-wide admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn check_lifecycles(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+) -> Result<Json<LifecycleCheckResponse>, AppError> {
+    if viewer.org().is_some() {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "Only instance-wide admin keys may run lifecycle checks",
+        ));
+    }
+    let check = lifecycle::check_lifecycles(&state)
+        .await
+        .or_internal("Failed to check part lifecycles")?;
+    Ok(Json(LifecycleCheckResponse {
+        repos: check.repos,
+        parts: check.parts,
+        open_alerts: check.open_alerts,
+        resolved: check.resolved,
+        notified: check.notified,
+    }))
+}
+
+/// Onboard a GitHub org's KiCad repositories
+///
+/// Lists every repository of the org the server's `GITHUB_TOKEN` can see and
+/// registers those with a KiCad project, schematic or layout on their default
+/// branch (forks and archived repos only if asked). With `public_url`, each
+/// gets a push webhook with its own secret, which needs a token with webhook
+/// admin access. An initial sync of the new registrations is then queued:
+/// their history is processed in the background, then summarized if
+/// `processing.ai` is set. Repos already registered are left as they are,
+/// so calling again picks up new repos. The report lists what became of
+/// each one. Requires an admin key.
+#[utoipa::path(
+    post,
+    path = "/api/admin/onboard/github-org",
+    request_body = OnboardGithubOrgRequest,
+    responses(
+        (status = 200, description = "What became of each repository", body = OnboardGithubOrgResponse),
+        (status = 400, description = "Missing org, invalid public URL or branch glob", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key, or an org key naming an org", body = ApiError),
+        (status = 404, description = "No such GitHub org, or no org with the given slug", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError),
+        (status = 503, description = "GITHUB_TOKEN is not configured", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn onboard_github_org(
+    State(app): State<AppState>,
+    viewer: Viewer,
+    Json(req): Json<OnboardGithubOrgRequest>,
+) -> Result<Json<OnboardGithubOrgResponse>, AppError> {
+    let github_org = req.github_org.trim().to_string();
+    if github_org.is_empty() || github_org.contains('/') {
+        return Err(AppError::bad_request("github_org must be a GitHub org name, e.g. acme-hardware"));
+    }
+    let public_url = req.public_url.map(|url| url.trim().trim_end_matches('/').to_string()).filter(|url| !url.is_empty());
+    if let Some(url) = public_url.as_deref().filter(|url| !url.starts_with("https://") && !url.starts_with("http://")) {
+        return Err(AppError::bad_request(format!("public_url must be an http(s) URL (got {})", url)));
+    }
+    let processing = processing_policy(req.processing)?;
+    let org_id = viewer.owning_org(&app.pool, req.org.as_deref()).await?;
+    if !github::is_configured() {
+        return Err(AppError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "not_configured",
+            "Onboarding needs GITHUB_TOKEN to be configured on the server",
+        ));
+    }
+
+    let onboarding = onboarding::Onboarding {
+        github_org: github_org.clone(),
+        public_url,
+        include_forks: req.include_forks,
+        include_archived: req.include_archived,
+        processing,
+        org_id,
+    };
+    let repos = onboarding::onboard(&app, &onboarding)
+        .await
+        .or_internal("Failed to list the org's repositories")?
+        .ok_or_else(|| AppError::not_found(format!("No GitHub org '{}'", github_org)))?;
+    let count = |pred: fn(&OnboardedRepoItem) -> bool| repos.iter().filter(|r| pred(r)).count();
+    Ok(Json(OnboardGithubOrgResponse {
+        github_org,
+        scanned: repos.len(),
+        registered: count(|r| r.status == OnboardStatus::Registered),
+        webhooks_installed: count(|r| matches!(r.webhook, OnboardWebhook::Installed | OnboardWebhook::Updated)),
+        backfills_queued: count(|r| r.backfill_queued),
+        repos,
+    }))
+}
+
+/// List recent webhook deliveries
+///
+/// Deliveries are kept for a week with their payloads (secrets redacted), so
+/// they can be replayed. Org keys only list their org's repos' deliveries.
+/// Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/admin/webhooks",
+    responses(
+        (status = 200, description = "The latest deliveries, newest first", body = WebhookDeliveryListResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn list_webhook_deliveries(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+) -> Result<Json<WebhookDeliveryListResponse>, AppError> {
+    let deliveries = recent_webhook_deliveries(&state, WEBHOOK_LIST_LIMIT, viewer.orgs())
+        .await
+        .or_internal("Failed to list webhook deliveries")?;
+    Ok(Json(WebhookDeliveryListResponse {
+        deliveries: deliveries
+            .into_iter()
+            .map(|d| WebhookDeliveryItem {
+                repo: git::repo_slug(&d.repo_url).unwrap_or(d.repo_url),
+                provider: d.provider,
+                delivery_id: d.delivery_id,
+                event: d.event,
+                received_at: d.received_at,
+            })
+            .collect(),
+    }))
+}
+
+/// Replay a webhook delivery
+///
+/// Processes the repo again as the stored delivery did, without checking
+/// its signature or skipping it as a duplicate, e.g. after fixing a bug that
+/// broke it. Commits that already have an overview are still skipped; delete
+/// them first to have them regenerated. Requires an admin key.
+#[utoipa::path(
+    post,
+    path = "/api/admin/webhooks/{delivery_id}/replay",
+    params(
+        ("delivery_id" = String, Path, description = "Delivery ID, as listed by /api/admin/webhooks")
+    ),
+    responses(
+        (status = 200, description = "Repository processed again", body = HookUpdateResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "No delivery with this ID is kept, or the repository is no longer registered", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn replay_webhook(
+    State(app): State<AppState>,
+    viewer: Viewer,
+    Path(delivery_id): Path<String>,
+) -> Result<Json<HookUpdateResponse>, AppError> {
+    let not_kept = || AppError::not_found(format!("No webhook delivery {} is kept", delivery_id));
+    let delivery = get_webhook_delivery(&app.pool, &delivery_id)
+        .await
+        .or_internal("Failed to load webhook delivery")?
+        .ok_or_else(not_kept)?;
+    let owner = get_registered_repo(&app.pool, &delivery.repo_url)
+        .await
+        .or_internal("Failed to look up repository")?
+        .and_then(|r| r.org_id);
+    if !viewer.orgs().allows(owner) {
+        return Err(not_kept());
+    }
+    hook::replay_delivery(&app, delivery).await
+}
+
+/// A backfill job the caller may see, with its commits
+fn visible_job(viewer: &Viewer, id: u64) -> Result<(backfill::JobInfo, Vec<JobCommit>), AppError> {
+    backfill::job(id)
+        .filter(|(info, _)| viewer.orgs().allows(info.org))
+        .ok_or_else(|| AppError::not_found(format!("No job {}", id)))
+}
+
+/// List background jobs
+///
+/// Summary backfills of this server, running and recently finished, newest
+/// first. Finished jobs are kept until 50 newer ones have finished, and
+/// none are kept across restarts. Org keys only list their org's repos'
+/// jobs. Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/admin/jobs",
+    params(JobListQuery),
+    responses(
+        (status = 200, description = "The jobs, newest first", body = JobListResponse),
+        (status = 400, description = "Unknown status", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn list_jobs(viewer: Viewer, Query(query): Query<JobListQuery>) -> Result<Json<JobListResponse>, AppError> {
+    let status = query.status.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    if let Some(status) = status {
+        let known = [
+            BackfillStatus::Running,
+            BackfillStatus::Completed,
+            BackfillStatus::Stopped,
+            BackfillStatus::Cancelled,
+        ];
+        if !known.iter().any(|s| s.as_str() == status) {
+            return Err(AppError::bad_request(format!(
+                "status must be running, completed, stopped or cancelled (got {:?})",
+                status
+            )));
+        }
+    }
+    let repo = query.repo.as_deref().map(|r| r.trim().trim_matches('/')).filter(|r| !r.is_empty());
+
+    let jobs = backfill::jobs()
+        .into_iter()
+        .filter(|job| viewer.orgs().allows(job.org))
+        .map(|job| job.progress)
+        .filter(|p| repo.is_none_or(|repo| p.repo == repo))
+        .filter(|p| status.is_none_or(|status| p.status.as_str() == status))
+        .collect();
+    Ok(Json(JobListResponse { jobs }))
+}
+
+/// Get a background job
+///
+/// The job's progress, and where each commit it set out to summarize
+/// stands. Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/admin/jobs/{id}",
+    params(
+        ("id" = u64, Path, description = "Job ID, as listed by /api/admin/jobs")
+    ),
+    responses(
+        (status = 200, description = "The job and its commits", body = JobDetailResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "No such job is kept", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn get_job(viewer: Viewer, Path(id): Path<u64>) -> Result<Json<JobDetailResponse>, AppError> {
+    let (info, commits) = visible_job(&viewer, id)?;
+    Ok(Json(JobDetailResponse {
+        job: info.progress,
+        commits,
+    }))
+}
+
+/// Cancel a background job
+///
+/// The job stops once the commit it is summarizing is done, or at once
+/// while it waits for a deferred summary, and ends with status `cancelled`.
+/// Summaries already stored are kept. Requires an admin key.
+#[utoipa::path(
+    post,
+    path = "/api/admin/jobs/{id}/cancel",
+    params(
+        ("id" = u64, Path, description = "Job ID, as listed by /api/admin/jobs")
+    ),
+    responses(
+        (status = 200, description = "Cancellation requested; the job as it stands", body = BackfillProgress),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "No such job is kept", body = ApiError),
+        (status = 409, description = "The job has already finished", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn cancel_job(viewer: Viewer, Path(id): Path<u64>) -> Result<Json<BackfillProgress>, AppError> {
+    let (info, _) = visible_job(&viewer, id)?;
+    if info.progress.status != BackfillStatus::Running {
+        return Err(AppError::new(
+            StatusCode::CONFLICT,
+            "conflict",
+            format!("Job {} has already finished ({})", id, info.progress.status.as_str()),
+        ));
+    }
+    let info = backfill::cancel(id).ok_or_else(|| AppError::not_found(format!("No job {}", id)))?;
+    Ok(Json(info.progress))
+}
+
+/// Retry a background job
+///
+/// Starts a new backfill of the job's repo, which summarizes the commits
+/// that failed or were never reached, with the repo's current settings.
+/// Only jobs that stopped, were cancelled or had commits fail can be
+/// retried. Requires an admin key.
+#[utoipa::path(
+    post,
+    path = "/api/admin/jobs/{id}/retry",
+    params(
+        ("id" = u64, Path, description = "Job ID, as listed by /api/admin/jobs")
+    ),
+    responses(
+        (status = 200, description = "The new job", body = BackfillProgress),
+        (status = 400, description = "Repo default persona is unknown", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "No such job is kept, or the repository is no longer registered", body = ApiError),
+        (status = 409, description = "The job is running or succeeded, or another job of the repo is running", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn retry_job(
+    State(state): State<AppState>,
+    viewer: Viewer,
+    Path(id): Path<u64>,
+) -> Result<Json<BackfillProgress>, AppError> {
+    let (info, _) = visible_job(&viewer, id)?;
+    let job = info.progress;
+    let conflict = |msg: String| AppError::new(StatusCode::CONFLICT, "conflict", msg);
+    match job.status {
+        BackfillStatus::Running => return Err(conflict(format!("Job {} is still running", id))),
+        BackfillStatus::Completed if job.failed == 0 => {
+            return Err(conflict(format!("Job {} completed without failures", id)))
+        }
+        _ => {}
+    }
+    if let Some(running) = backfill::running_job(&job.repo) {
+        return Err(conflict(format!("Job {} of {} is already running", running, job.repo)));
+    }
+
+    let settings = backfill_settings(&state, &job.repo).await?;
+    info!("Retrying summary backfill {} of {}", id, job.repo);
+    let progress = backfill::start(state, job.repo, settings, job.class);
+    let retried = progress.borrow().clone();
+    Ok(Json(retried))
+}
+
+/// List audited AI calls
+///
+/// The prompts sent to the model and its answers, newest first, as recorded
+/// when the prompt audit is enabled (`[audit]` in the config). Secrets are
+/// redacted and long text cut short before anything is stored. Org keys only
+/// list calls made for their org. Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/admin/prompts",
+    params(PromptAuditQuery),
+    responses(
+        (status = 200, description = "The latest calls, newest first", body = PromptAuditListResponse),
+        (status = 400, description = "Limit out of range", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "admin"
+)]
+pub async fn list_prompt_audits(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Query(query): Query<PromptAuditQuery>,
+) -> Result<Json<PromptAuditListResponse>, AppError> {
+    let limit = query.limit.unwrap_or(PROMPT_LIST_LIMIT);
+    if !(1..=MAX_PROMPT_LIST_LIMIT).contains(&limit) {
+        return Err(AppError::bad_request(format!(
+            "limit must be between 1 and {} (got {})",
+            MAX_PROMPT_LIST_LIMIT, limit
+        )));
+    }
+    let repo_url = query
+        .repo
+        .as_deref()
+        .map(|r| r.trim().trim_matches('/'))
+        .filter(|r| !r.is_empty())
+        .map(git::repo_url);
+
+    let audits = recent_prompt_audits(&state, repo_url.as_deref(), limit, viewer.orgs())
+        .await
+        .or_internal("Failed to list audited prompts")?;
+    Ok(Json(PromptAuditListResponse {
+        prompts: audits
+            .into_iter()
+            .map(|a| PromptAuditItem {
+                id: a.id,
+                request_id: a.request_id,
+                endpoint: a.endpoint,
+                provider: a.provider,
+                model: a.model,
+                repo: a.repo_url.map(|url| git::repo_slug(&url).unwrap_or(url)),
+                api_key: a.api_key,
+                messages: a.messages,
+                response: a.response,
+                error: a.error,
+                latency_ms: a.latency_ms,
+                prompt_tokens: a.prompt_tokens,
+                completion_tokens: a.completion_tokens,
+                created_at: a.created_at,
+            })
+            .collect(),
+    }))
+}
@@ -0,0 +1,226 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Json, Response},
+};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::auth::Viewer;
+use crate::error::{AppError, ResultExt};
+use crate::services::datasheets::{self, DatasheetError};
+use crate::services::{enrichment, file_stream, git};
+use crate::state::AppState;
+use crate::types::{
+    ComponentUsageItem, ComponentUsageResponse, PartMetadataResponse, SharedComponentItem, SharedComponentsQuery,
+    SharedComponentsResponse,
+};
+use kicad_db::{component_usage, shared_components, PgPool};
+
+const DEFAULT_MIN_REPOS: i64 = 2;
+const DEFAULT_SHARED_LIMIT: i64 = 100;
+const MAX_SHARED_LIMIT: i64 = 500;
+/// Datasheets rarely change, and a cached one is served as it was downloaded
+const DATASHEET_CACHE_CONTROL: &str = "private, max-age=86400";
+
+/// "owner/repo" for a stored repo URL
+fn repo_name(repo_url: String) -> String {
+    git::repo_slug(&repo_url).unwrap_or(repo_url)
+}
+
+/// Get supplier metadata (datasheet, lifecycle, pricing) for a manufacturer part number
+///
+/// Served from the `part_metadata` cache when fresh, otherwise fetched from the
+/// configured supplier (`PART_SUPPLIER`) and cached.
+#[utoipa::path(
+    get,
+    path = "/api/components/{mpn}",
+    params(
+        ("mpn" = String, Path, description = "Manufacturer part number, e.g. LM358DR")
+    ),
+    responses(
+        (status = 200, description = "Part metadata", body = PartMetadataResponse),
+        (status = 404, description = "Part not known to the supplier", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "components"
+)]
+pub async fn get_part_metadata(
+    State(state): State<Arc<PgPool>>,
+    Path(mpn): Path<String>,
+) -> Result<Json<PartMetadataResponse>, AppError> {
+    info!("Looking up part metadata for {}", mpn);
+
+    let supplier = enrichment::configured_supplier();
+    let metadata = enrichment::lookup(&state, supplier.as_deref(), &mpn)
+        .await
+        .or_internal(format!("Failed to look up part metadata for {}", mpn))?;
+
+    match metadata {
+        Some(metadata) => Ok(Json(metadata.into())),
+        None => Err(AppError::not_found(format!("No supplier data found for {}", mpn))),
+    }
+}
+
+/// Stream the datasheet PDF of a manufacturer part number
+///
+/// The datasheet URL comes from the part's supplier metadata, as for
+/// `/api/components/{mpn}`. The PDF is downloaded once and cached on disk,
+/// then served from this origin (with `Range` support) so the viewer can
+/// embed it despite the manufacturer's CORS and framing rules. Downloads
+/// over `datasheet_max_mb`, or that aren't PDFs, are refused with a 502.
+#[utoipa::path(
+    get,
+    path = "/api/components/{mpn}/datasheet",
+    params(
+        ("mpn" = String, Path, description = "Manufacturer part number, e.g. LM358DR"),
+        ("Range" = Option<String>, Header, description = "Optional single byte range, e.g. bytes=0-1023")
+    ),
+    responses(
+        (status = 200, description = "Datasheet PDF", content_type = "application/pdf"),
+        (status = 206, description = "Requested byte range", content_type = "application/pdf"),
+        (status = 404, description = "Part unknown or without a datasheet URL", body = ApiError),
+        (status = 416, description = "Requested range not satisfiable"),
+        (status = 502, description = "Datasheet unavailable, too large or not a PDF", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "components"
+)]
+pub async fn get_datasheet(
+    State(state): State<AppState>,
+    Path(mpn): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let max_bytes = state.config().datasheet_max_mb * 1024 * 1024;
+    let supplier = enrichment::configured_supplier();
+    let metadata = enrichment::lookup(&state.pool, supplier.as_deref(), &mpn)
+        .await
+        .or_internal(format!("Failed to look up part metadata for {}", mpn))?;
+    let url = metadata
+        .and_then(|m| m.datasheet_url)
+        .filter(|url| !url.trim().is_empty())
+        .ok_or_else(|| AppError::not_found(format!("No datasheet known for {}", mpn)))?;
+
+    info!("Serving the datasheet of {} from {}", mpn, url);
+    let path = datasheets::fetch(&url, max_bytes)
+        .await
+        .map_err(|e| datasheet_error(&mpn, e))?;
+
+    let mut response = file_stream::stream_file(&path, "application/pdf", &headers)
+        .await
+        .or_internal(format!("Failed to read the datasheet of {}", mpn))?;
+    let filename: String = mpn
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "-_.+".contains(c) { c } else { '_' })
+        .collect();
+    let response_headers = response.headers_mut();
+    if let Ok(disposition) = HeaderValue::from_str(&format!("inline; filename=\"{}.pdf\"", filename)) {
+        response_headers.insert(header::CONTENT_DISPOSITION, disposition);
+    }
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(DATASHEET_CACHE_CONTROL));
+    response_headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    Ok(response)
+}
+
+/// A 404 for a datasheet URL that can't be fetched, else a 502 naming why
+/// the download was refused
+fn datasheet_error(mpn: &str, e: DatasheetError) -> AppError {
+    let code = match &e {
+        DatasheetError::InvalidUrl(_) => return AppError::not_found(format!("No datasheet known for {}: {}", mpn, e)),
+        DatasheetError::TooLarge { .. } => "datasheet_too_large",
+        DatasheetError::NotPdf { .. } => "datasheet_not_pdf",
+        DatasheetError::Unavailable(_) => "datasheet_unavailable",
+    };
+    AppError::new(StatusCode::BAD_GATEWAY, code, format!("Datasheet of {} not served: {}", mpn, e))
+}
+
+/// List the repos that use a manufacturer part number
+///
+/// Answers "which of our boards use the STM32F407?", e.g. when a part goes
+/// obsolete. Each repo counts with its newest commit whose components are
+/// stored, so a part since replaced is left out; MPNs are compared trimmed and
+/// ignoring case. Private commits are only counted for authenticated callers,
+/// and org keys only see their org's repos.
+#[utoipa::path(
+    get,
+    path = "/api/components/{mpn}/usage",
+    params(
+        ("mpn" = String, Path, description = "Manufacturer part number, e.g. STM32F407VGT6")
+    ),
+    responses(
+        (status = 200, description = "Repos using the part (empty if none)", body = ComponentUsageResponse),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "components"
+)]
+pub async fn get_component_usage(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Path(mpn): Path<String>,
+) -> Result<Json<ComponentUsageResponse>, AppError> {
+    info!("Listing repos using {}", mpn);
+    let usage = component_usage(&state, &mpn, viewer.is_authenticated(), viewer.orgs())
+        .await
+        .or_internal(format!("Failed to load the usage of {}", mpn))?;
+
+    let repos: Vec<ComponentUsageItem> = usage
+        .into_iter()
+        .map(|u| ComponentUsageItem {
+            repo: repo_name(u.repo_url),
+            commit: u.commit_hash,
+            commit_date: u.commit_date,
+            quantity: u.quantity,
+            references: u.designators,
+            value: u.value,
+            footprint: u.footprint,
+        })
+        .collect();
+    Ok(Json(ComponentUsageResponse {
+        mpn: mpn.trim().to_uppercase(),
+        total_quantity: repos.iter().map(|r| r.quantity).sum(),
+        repos,
+    }))
+}
+
+/// List the parts used across several repos
+///
+/// Parts (by MPN) on at least `min_repos` boards, the most widely used first,
+/// counted and filtered as for `/api/components/{mpn}/usage`.
+#[utoipa::path(
+    get,
+    path = "/api/components/usage",
+    params(SharedComponentsQuery),
+    responses(
+        (status = 200, description = "Parts shared by repos", body = SharedComponentsResponse),
+        (status = 400, description = "min_repos below 1 or limit out of range", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "components"
+)]
+pub async fn list_shared_components(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Query(query): Query<SharedComponentsQuery>,
+) -> Result<Json<SharedComponentsResponse>, AppError> {
+    let min_repos = query.min_repos.unwrap_or(DEFAULT_MIN_REPOS);
+    let limit = query.limit.unwrap_or(DEFAULT_SHARED_LIMIT);
+    if min_repos < 1 || !(1..=MAX_SHARED_LIMIT).contains(&limit) {
+        return Err(AppError::bad_request(format!(
+            "min_repos must be at least 1 and limit between 1 and {}",
+            MAX_SHARED_LIMIT
+        )));
+    }
+    let shared = shared_components(&state, min_repos, limit, viewer.is_authenticated(), viewer.orgs())
+        .await
+        .or_internal("Failed to load shared components")?;
+
+    let parts = shared
+        .into_iter()
+        .map(|part| SharedComponentItem {
+            mpn: part.mpn,
+            repos: part.repo_urls.into_iter().map(repo_name).collect(),
+            quantity: part.quantity,
+        })
+        .collect();
+    Ok(Json(SharedComponentsResponse { min_repos, parts }))
+}
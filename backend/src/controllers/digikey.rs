@@ -2,11 +2,9 @@ use axum::{extract::State, http::StatusCode, response::Json};
 use std::sync::Arc;
 use tracing::{error, info};
 
-use crate::services::digikey::DigiKeyClient;
-use crate::types::{ApiError, DigiKeySearchRequest, DigiKeySearchResponse};
-use kicad_db::PgPool;
-
-pub type AppState = Arc<PgPool>;
+use crate::config::Config;
+use crate::error::AppError;
+use crate::types::{DigiKeySearchRequest, DigiKeySearchResponse, DigiKeyStatusResponse};
 
 /// Search DigiKey for part information
 #[utoipa::path(
@@ -22,21 +20,16 @@ pub type AppState = Arc<PgPool>;
     tag = "digikey"
 )]
 pub async fn search_parts(
-    State(_state): State<AppState>,
+    State(config): State<Arc<Config>>,
     Json(req): Json<DigiKeySearchRequest>,
-) -> Result<Json<DigiKeySearchResponse>, (StatusCode, Json<ApiError>)> {
-    // Check if DigiKey is configured
-    if !DigiKeyClient::is_configured() {
-        return Err((
+) -> Result<Json<DigiKeySearchResponse>, AppError> {
+    let Some(client) = config.parts.digikey() else {
+        return Err(AppError::new(
             StatusCode::SERVICE_UNAVAILABLE,
-            Json(ApiError::new(
-                "not_configured",
-                "DigiKey API is not configured. Please set DIGIKEY_CLIENT_ID and DIGIKEY_CLIENT_SECRET environment variables.",
-            )),
+            "not_configured",
+            "DigiKey API is not configured. Please set DIGIKEY_CLIENT_ID and DIGIKEY_CLIENT_SECRET environment variables.",
         ));
-    }
-
-    let client = DigiKeyClient::new();
+    };
     
     // Use keyword search for all queries - it handles both MPNs and keywords well
     // The keyword search returns ExactManufacturerProducts for exact MPN matches
@@ -78,19 +71,21 @@ pub async fn search_parts(
     get,
     path = "/api/digikey/status",
     responses(
-        (status = 200, description = "DigiKey configuration status")
+        (status = 200, description = "DigiKey configuration status", body = DigiKeyStatusResponse)
     ),
     tag = "digikey"
 )]
 pub async fn get_status(
-    State(_state): State<AppState>,
-) -> Json<serde_json::Value> {
-    Json(serde_json::json!({
-        "configured": DigiKeyClient::is_configured(),
-        "message": if DigiKeyClient::is_configured() {
-            "DigiKey API is configured and ready"
-        } else {
-            "DigiKey API is not configured. Set DIGIKEY_CLIENT_ID and DIGIKEY_CLIENT_SECRET environment variables."
-        }
-    }))
+    State(config): State<Arc<Config>>,
+) -> Json<DigiKeyStatusResponse> {
+    let configured = config.parts.digikey().is_some();
+    let message = if configured {
+        "DigiKey API is configured and ready"
+    } else {
+        "DigiKey API is not configured. Set DIGIKEY_CLIENT_ID and DIGIKEY_CLIENT_SECRET environment variables."
+    };
+    Json(DigiKeyStatusResponse {
+        configured,
+        message: message.to_string(),
+    })
 }
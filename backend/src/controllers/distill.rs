@@ -1,12 +1,12 @@
-use axum::{extract::State, http::StatusCode, response::Json};
+use axum::{extract::State, response::Json};
 use std::sync::Arc;
 use tracing::{error, info};
 
-use crate::services::distill;
-use crate::types::{ApiError, DistillRequest, DistillResponse};
-use kicad_db::{retrieve_distilled_json, store_distilled_json, PgPool};
-
-pub type AppState = Arc<PgPool>;
+use crate::controllers::resolve_commit;
+use crate::error::{AppError, AppResultExt, ResultExt};
+use crate::services::{distill, git, schematic_cache::SchematicCache, timing::Stage};
+use crate::types::{DistillRequest, DistillResponse};
+use kicad_db::SchematicStore;
 
 /// Distill schematic files from a repository at a specific commit
 #[utoipa::path(
@@ -20,15 +20,17 @@ pub type AppState = Arc<PgPool>;
     tag = "distill"
 )]
 pub async fn distill_schematics(
-    State(state): State<AppState>,
-    Json(req): Json<DistillRequest>,
-) -> Result<Json<DistillResponse>, (StatusCode, Json<ApiError>)> {
+    State(store): State<Arc<dyn SchematicStore>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    Json(mut req): Json<DistillRequest>,
+) -> Result<Json<DistillResponse>, AppError> {
+    req.commit = resolve_commit(&req.repo, &req.commit).await?;
     info!("Distill request for {}/{}", req.repo, req.commit);
 
-    let repo_url = format!("https://github.com/{}.git", req.repo);
+    let repo_url = git::repo_url(&req.repo);
 
     // Check cache first
-    match retrieve_distilled_json(&state, &repo_url, &req.commit).await {
+    match store.retrieve_distilled_json(&repo_url, &req.commit).await {
         Ok(Some(cached_json)) => {
             info!("Cache hit for {}/{}", req.repo, req.commit);
             return Ok(Json(DistillResponse {
@@ -51,18 +53,15 @@ pub async fn distill_schematics(
     }
 
     // Run distillation
-    let distilled = distill::distill_repo_schematics(&req.repo, &req.commit)
+    let distilled = distill::distill_repo_schematics(&schematics, &req.repo, &req.commit)
         .await
-        .map_err(|e| {
-            error!("Distillation failed for {}/{}: {}", req.repo, req.commit, e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::internal(format!("Distillation failed: {}", e))),
-            )
-        })?;
+        .or_internal("Distillation failed")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)
+        .in_stage(Stage::Parse)?;
 
     // Store in cache
-    if let Err(e) = store_distilled_json(&state, &repo_url, &req.commit, &distilled).await {
+    if let Err(e) = store.store_distilled_json(&repo_url, &req.commit, &distilled).await {
         // Log but don't fail - we still have the result
         error!("Failed to cache distilled result: {}", e);
     } else {
@@ -1,84 +1,63 @@
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
     response::{
         sse::{Event, Sse},
         Json,
     },
 };
+use chrono::Utc;
 use futures_util::{stream::Stream, StreamExt};
-use std::{convert::Infallible, path::PathBuf, sync::Arc, time::Duration};
+use sha2::{Digest, Sha256};
+use tokio_util::sync::CancellationToken;
+use std::{convert::Infallible, sync::Arc, time::Duration};
 use tracing::{error, info, warn};
 
-use crate::services::{distill, git};
+use crate::auth::Viewer;
+use crate::config::{Config, ModelConfig};
+use crate::controllers::resolve_commit;
+use crate::services::llm::{CancelOnDisconnect, ChatProvider};
+use crate::error::{AppError, AppResultExt, ResultExt};
+use crate::services::{
+    anomalies, ask, backfill, chat_history, citations, costs, distill, enrichment, erc,
+    experiments::{self, Assignment},
+    git, github, guidelines, registry, release_notes, retrieval, review_checklist,
+    sampling::sampled,
+    schematic_cache::SchematicCache,
+    selection::{self, Resolved},
+    status,
+    summarization::{self, Summarizer},
+    resumable::{self, EventStream, Frame, ResumableStreams},
+    suggest_fix,
+    summary::{self, CommitSummary},
+    timing::{self, Stage, StageTimer},
+};
+use crate::shutdown;
+use crate::state::AppState;
+use crate::validation::{Valid, MAX_TOP_K};
 use crate::types::{
-    ApiError, GrokCommitSummaryRequest, GrokCommitSummaryResponse,
+    BackfillStatus, ChatAttachmentsEvent, ChatCitationsEvent, ChatRole, ChatSessionResponse, ChatSessionTurn, ChatSource, ChatSourcesEvent, CreateChatSessionRequest, GrokAskRequest, GrokAskResponse, GrokChatRequest, GrokChatStreamQuery, GrokCommitSummaryRequest, GrokCommitSummaryResponse,
+    GrokCompareSummaryRequest, GrokCompareSummaryResponse, GrokReleaseNotesRequest, GrokReleaseNotesResponse,
+    GrokReviewChecklistRequest, ReviewChecklistItem, ReviewChecklistResponse, GrokSuggestFixRequest, SuggestFixResponse,
     GrokObsoleteReplacementRequest, GrokObsoleteReplacementResponse,
     GrokRepoSummaryRequest, GrokRepoSummaryResponse, GrokSelectionStreamRequest,
-    GrokSelectionSummaryRequest, GrokSelectionSummaryResponse,
+    GrokSelectionSummaryRequest, GrokSelectionSummaryResponse, ModelListResponse, PersonaInfo, WorkClass,
+    PersonaListResponse, Selection, StreamUsageEvent, SummaryFeedbackRequest, SummaryFeedbackResponse, SummaryRating,
 };
 use kicad_db::{
-    messages::{ChatCompletionRequest, Message, ReasoningEffort},
-    utilities::load_environment_file::load_environment_file,
-    xai_client::{InputMessage, ResponsesRequest, Tool, XaiClient},
-    PgPool,
+    detail_levels::DetailLevel,
+    messages::{ChatCompletionRequest, ImageUrl, Message, ReasoningEffort},
+    personas::Persona,
+    prompts::{ASK, CHAT_SYSTEM, COMMIT_SUMMARY, COMPARE_SUMMARY, SELECTION_SUMMARY},
+    tokens,
+    xai_client::{InputMessage, ResponsesRequest, StreamEvent, Tool},
+    get_commit_answer, get_comparison, get_generated_summary, get_registered_repo, get_review_checklist, question_digest, record_generated_summary, record_summary_feedback, store_commit_answer, store_comparison,
+    store_review_checklist, ChatAttachment, ChatCitation, ChatSession, ChecklistItem, CommitAnswer, CommitComparison, NewGeneratedSummary, PgPool, PromptLibrary, RegisteredRepo, ReviewChecklist, StoredChatTurn, UpdateSchematic, XaiError,
 };
+use uuid::Uuid;
 
-/// Load the system prompt from the grokprompts directory
-fn load_system_prompt() -> String {
-    // Try multiple paths to find the system prompt
-    let possible_paths = [
-        // Relative to CARGO_MANIFEST_DIR (during cargo run)
-        std::env::var("CARGO_MANIFEST_DIR")
-            .ok()
-            .map(|dir| PathBuf::from(dir).parent().map(|p| p.join("grokprompts/systemprompt.txt")))
-            .flatten(),
-        // Relative to current working directory
-        Some(PathBuf::from("grokprompts/systemprompt.txt")),
-        // Parent directory (if running from backend/)
-        Some(PathBuf::from("../grokprompts/systemprompt.txt")),
-    ];
-
-    for path in possible_paths.into_iter().flatten() {
-        if path.exists() {
-            match std::fs::read_to_string(&path) {
-                Ok(content) => {
-                    info!("Loaded system prompt from {:?}", path);
-                    return content;
-                }
-                Err(e) => {
-                    warn!("Failed to read system prompt from {:?}: {}", path, e);
-                }
-            }
-        }
-    }
-
-    // Fallback to embedded default prompt
-    warn!("Could not load system prompt file, using default");
-    DEFAULT_SYSTEM_PROMPT.to_string()
-}
-
-/// Default system prompt (fallback if file not found)
-const DEFAULT_SYSTEM_PROMPT: &str = r#"You are a specialized circuit-explanation assistant for KiCad schematics.
-
-## Data Model
-
-The user provides KiCad schematic data in **JSON** with:
-
-- `components`: reference, type/kind, value/part_name, pins (with connected nets)
-- `nets`: name and list of connected pins
-
-## Primary Objective
-
-Explain **what each IC (and nearby key parts) does** in simple, plain language.
-
-## Answer Style
-
-Keep answers **short and easy to read**:
-- For simple questions, give **one short sentence per part**
-- Only go into detailed explanations if asked
-
-Use everyday language and avoid heavy jargon. When you must use a technical term, add a short explanation."#;
+/// Sources retrieved per question when the request doesn't say
+const DEFAULT_CHAT_TOP_K: usize = 5;
 
 /// Build semantic context for selected components from distilled data
 fn build_component_context(
@@ -304,154 +283,313 @@ fn build_component_context(
     (selected_context, schematic_overview)
 }
 
-pub type AppState = Arc<PgPool>;
+/// Resolve the persona for a request: an explicit `persona` wins, otherwise the
+/// repo's configured default (if any). Unknown names are rejected with 400.
+async fn resolve_persona(
+    pool: &PgPool,
+    repo: Option<&str>,
+    requested: Option<&str>,
+) -> Result<Option<&'static Persona>, AppError> {
+    let name = match requested {
+        Some(name) => Some(name.to_string()),
+        None => match repo {
+            Some(repo) => kicad_db::get_repo_default_persona(pool, &git::repo_url(repo))
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to load default persona for {}: {}", repo, e);
+                    None
+                }),
+            None => None,
+        },
+    };
+
+    match name {
+        Some(name) => match Persona::by_name(&name) {
+            Some(persona) => {
+                info!("Using persona '{}'", persona.name);
+                Ok(Some(persona))
+            }
+            None => Err(AppError::bad_request(format!(
+                "Unknown persona '{}'. Available personas: {}",
+                name,
+                Persona::names().join(", ")
+            ))),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Commit summary template and default model: the repo's registered
+/// preferences, else the built-in template and configured model
+fn summary_preferences<'a>(registered: Option<&'a RegisteredRepo>, config: &'a Config) -> (&'a str, &'a str) {
+    let template = registered.and_then(|r| r.summary_prompt.as_deref()).unwrap_or(COMMIT_SUMMARY);
+    let model = registered
+        .and_then(|r| r.summary_model.as_deref())
+        .unwrap_or(&config.models.summary);
+    (template, model)
+}
+
+/// The experiment variant a commit summary is generated with, charging the
+/// request's AI calls to it
+///
+/// Only summaries whose model and template nobody chose take part: a model
+/// the caller asked for, or a template or model the repo is registered
+/// with, always wins.
+fn summary_experiment(
+    registered: Option<&RegisteredRepo>,
+    config: &Config,
+    req: &GrokCommitSummaryRequest,
+) -> Option<Assignment> {
+    if req.model.is_some() || registered.is_some_and(|r| r.summary_prompt.is_some() || r.summary_model.is_some()) {
+        return None;
+    }
+    let assignment = experiments::assign(config, &req.repo, &req.commit)?;
+    info!(
+        "Summarizing {}/{} with variant {} of experiment {}",
+        req.repo, req.commit, assignment.variant, assignment.experiment
+    );
+    costs::charge_experiment(&assignment.experiment, &assignment.variant);
+    Some(assignment)
+}
+
+/// The model for a request: the caller's choice if it's on the allowlist, else `default`
+fn choose_model(models: &ModelConfig, requested: Option<&str>, default: &str) -> Result<String, AppError> {
+    match requested {
+        None => Ok(default.to_string()),
+        Some(model) if models.is_allowed(model) => Ok(model.to_string()),
+        Some(model) => Err(AppError::bad_request(format!(
+            "Model '{}' is not allowed. Allowed models: {}",
+            model,
+            models.allowlist().join(", ")
+        ))),
+    }
+}
+
+/// List the default model per use case and the models requests may choose
+#[utoipa::path(
+    get,
+    path = "/api/grok/models",
+    responses(
+        (status = 200, description = "Configured and allowed models", body = ModelListResponse),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError)
+    ),
+    tag = "grok"
+)]
+pub async fn list_models(State(config): State<Arc<Config>>) -> Json<ModelListResponse> {
+    let models = &config.models;
+    Json(ModelListResponse {
+        summary: models.summary.clone(),
+        chat: models.chat.clone(),
+        selection: models.selection.clone(),
+        replacement: models.replacement.clone(),
+        allowed: models.allowlist().into_iter().map(String::from).collect(),
+    })
+}
+
+/// List the available persona presets
+#[utoipa::path(
+    get,
+    path = "/api/grok/personas",
+    responses(
+        (status = 200, description = "Available persona presets", body = PersonaListResponse),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError)
+    ),
+    tag = "grok"
+)]
+pub async fn list_personas() -> Json<PersonaListResponse> {
+    let personas = kicad_db::personas::PERSONAS
+        .iter()
+        .map(|p| PersonaInfo {
+            name: p.name.to_string(),
+            description: p.description.to_string(),
+            temperature: p.temperature,
+            max_tokens: p.max_tokens,
+        })
+        .collect();
+
+    Json(PersonaListResponse { personas })
+}
+
+/// What a commit summary generation answers: requests with the same key get
+/// the same summary, so they can share one generation
+fn summary_generation_key(
+    req: &GrokCommitSummaryRequest,
+    template: &str,
+    model: &str,
+    detail_level: DetailLevel,
+    persona: Option<&Persona>,
+) -> String {
+    format!(
+        "commit_summary {} {} {} {} {} {}",
+        req.repo,
+        req.commit,
+        template,
+        model,
+        detail_level,
+        persona.map_or("-", |p| p.name)
+    )
+}
+
+/// Record a commit summary being handed out, so feedback on it can be traced
+/// to its model, template and experiment variant; its ID, or None if
+/// recording failed
+async fn record_commit_summary(
+    pool: &PgPool,
+    repo: &str,
+    commit: &str,
+    model: &str,
+    prompt_version: &str,
+    detail_level: DetailLevel,
+    experiment: Option<&Assignment>,
+) -> Option<i64> {
+    let generated = NewGeneratedSummary {
+        repo_url: git::repo_url(repo),
+        commit_hash: commit.to_string(),
+        kind: "commit_summary".to_string(),
+        model: model.to_string(),
+        prompt_version: prompt_version.to_string(),
+        detail_level: detail_level.as_str().to_string(),
+        experiment: experiment.map(|a| a.experiment.clone()),
+        variant: experiment.map(|a| a.variant.clone()),
+    };
+    match record_generated_summary(pool, &generated).await {
+        Ok(id) => Some(id),
+        Err(e) => {
+            warn!("Failed to record summary of {}/{} for feedback: {}", repo, commit, e);
+            None
+        }
+    }
+}
 
 /// Get an AI-generated summary for a specific commit
+///
+/// The repo's and its org's design guidelines are added to the system
+/// prompt, so the summary applies the team's house rules. If the same
+/// summary is being streamed at the time, this waits for it and returns it
+/// instead of asking the AI again.
 #[utoipa::path(
     post,
     path = "/api/grok/summary/commit",
     request_body = GrokCommitSummaryRequest,
     responses(
         (status = 200, description = "AI-generated commit summary", body = GrokCommitSummaryResponse),
+        (status = 400, description = "Unknown persona or model not on the allowlist", body = ApiError),
+        (status = 401, description = "Summaries for this repository are private", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required)", body = ApiError),
+        (status = 422, description = "Invalid fields, each listed in violations", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
         (status = 500, description = "Internal server error", body = ApiError)
     ),
     tag = "grok"
 )]
 pub async fn summarize_commit(
-    State(_state): State<AppState>,
-    Json(req): Json<GrokCommitSummaryRequest>,
-) -> Result<Json<GrokCommitSummaryResponse>, (StatusCode, Json<ApiError>)> {
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(chat): State<Arc<dyn ChatProvider>>,
+    State(prompts): State<Arc<PromptLibrary>>,
+    State(streams): State<Arc<ResumableStreams>>,
+    viewer: Viewer,
+    Valid(mut req): Valid<GrokCommitSummaryRequest>,
+) -> Result<Json<GrokCommitSummaryResponse>, AppError> {
+    let chat = sampled(chat, &config.sampling.summary);
+    req.commit = resolve_commit(&req.repo, &req.commit).await?;
     info!(
         "Grok summarize_commit called for {}/{}",
         req.repo, req.commit
     );
 
-    // Load environment file to get XAI_API_KEY
-    load_environment_file(None).map_err(|e| {
-        error!("Failed to load environment file: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError::internal(format!(
-                "Failed to load environment: {}",
-                e
-            ))),
-        )
-    })?;
-
-    // Create XAI client
-    let xai_client = XaiClient::new().map_err(|e| {
-        error!("Failed to create XAI client: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError::internal(format!(
-                "Failed to initialize XAI client: {}",
-                e
-            ))),
-        )
-    })?;
-
-    // Construct GitHub commit URL
-    let github_url = format!("https://github.com/{}/commit/{}", req.repo, req.commit);
-
-    // Create user message with GitHub URL
-    let user_message = format!(
-        "Search online for the changes in the commit {} and summarize the changes",
-        github_url
-    );
-
-    // Create input message for responses API
-    let input = vec![InputMessage::user(user_message)];
-
-    // Create tools - use both web_search and x_search for comprehensive results
-    let tools = vec![Tool::web_search(), Tool::x_search()];
-
-    // Create responses request with hardcoded model
-    let responses_request = ResponsesRequest::new("grok-4-1-fast".to_string(), input, tools);
+    viewer.require_summary_access(&state, &req.repo, Some(&req.commit)).await?;
+    let registered = registry::lookup(&state, &config, &req.repo).await?;
 
-    // Make API call using responses endpoint
-    let api_response = xai_client
-        .responses(&responses_request)
-        .await
-        .map_err(|e| {
-            error!("XAI API call failed: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::internal(format!(
-                    "Failed to get AI summary: {}",
-                    e
-                ))),
-            )
-        })?;
-
-    // TODO: Implement this or not.
-    // Get changed files for context
-    // let changed_files = git::get_changed_schematic_files(&req.repo, &req.commit)
-    //     .await
-    //     .map_err(|e| {
-    //         (
-    //             StatusCode::INTERNAL_SERVER_ERROR,
-    //             Json(ApiError::internal(format!(
-    //                 "Failed to fetch changed files: {}",
-    //                 e
-    //             ))),
-    //         )
-    //     })?;
-
-    // Extract response content from tool results
-    // The responses API returns tool call results, so we need to extract meaningful information
-    let summary = if let Some(output) = &api_response.output {
-        // Try to extract text from tool results
-        let mut result_parts = Vec::new();
-
-        for item in output {
-            if let Some(name) = &item.name {
-                result_parts.push(format!("Tool: {}", name));
-            }
-            if let Some(status) = &item.status {
-                result_parts.push(format!("Status: {}", status));
-            }
-            if let Some(result) = &item.result {
-                result_parts.push(format!(
-                    "Result: {}",
-                    serde_json::to_string(result).unwrap_or_else(|_| "N/A".to_string())
-                ));
-            }
-            if let Some(content) = &item.content {
-                result_parts.push(format!(
-                    "Content: {}",
-                    serde_json::to_string(content).unwrap_or_else(|_| "N/A".to_string())
-                ));
-            }
+    let persona = resolve_persona(&state, Some(&req.repo), req.persona.as_deref()).await?;
+    let guidelines = guidelines::for_repo(&state, &config, &req.repo).await;
+    let experiment = summary_experiment(registered.as_ref(), &config, &req);
+    let (template, default_model) = match &experiment {
+        Some(assignment) => (assignment.template.as_str(), assignment.model.as_str()),
+        None => summary_preferences(registered.as_ref(), &config),
+    };
+    let model = choose_model(&config.models, req.model.as_deref(), default_model)?;
+    let detail_level = req.detail_level.unwrap_or_default();
+
+    // The same summary being streamed to someone is waited for, not written twice
+    let key = summary_generation_key(&req, template, &model, detail_level, persona);
+    if let Some(complete) = streams.wait_for(&key, "summary_complete").await {
+        match serde_json::from_str::<GrokCommitSummaryResponse>(&complete) {
+            Ok(response) => return Ok(Json(response)),
+            Err(e) => warn!("Failed to read the streamed summary of {}/{}: {}", req.repo, req.commit, e),
         }
+    }
 
-        if result_parts.is_empty() {
-            format!(
-                "Found {} tool result(s) for commit {}/{}",
-                output.len(),
-                req.repo,
-                req.commit
-            )
-        } else {
-            result_parts.join("\n")
-        }
-    } else {
-        format!("No results returned for commit {}/{}", req.repo, req.commit)
-    };
+    let erc_comparison = erc_comparison(&state, &req.repo, &req.commit).await;
+    let new_violations = erc_comparison
+        .as_ref()
+        .map(|c| c.introduced_findings())
+        .unwrap_or_default();
+    let warnings = anomalies::stored(&state, &req.repo, &req.commit).await;
 
-    // For details, include more information about the response
-    let details = format!(
-        "Response ID: {:?}\nModel: {:?}\nTool Results: {}\n\n{}",
-        api_response.id,
-        api_response.model,
-        api_response.output.as_ref().map(|o| o.len()).unwrap_or(0),
-        summary
-    );
+    let _job = status::track_job("commit_summary", &req.repo, Some(&req.commit));
+    let mut timer = StageTimer::start("commit_summary");
+    let CommitSummary {
+        summary,
+        details,
+        prompt_version,
+    } = timer
+        .time(
+            Stage::Llm,
+            summary::generate_commit_summary(
+                chat.as_ref(),
+                &prompts,
+                template,
+                &model,
+                config.models.context_window(&model),
+                &req.repo,
+                &req.commit,
+                req.detail_level,
+                persona,
+                guidelines.as_ref(),
+                &new_violations,
+                &warnings,
+            ),
+        )
+        .await
+        .or_internal("Failed to get AI summary")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)
+        .in_stage(Stage::Llm)?;
 
     info!(
-        "Successfully generated summary for {}/{}",
-        req.repo, req.commit
+        "Successfully generated {} summary for {}/{}",
+        detail_level, req.repo, req.commit
     );
 
+    // Record the summary and the level and prompt version it was generated with
+    let repo_url = git::repo_url(&req.repo);
+    if let Err(e) = timer
+        .time(
+            Stage::Store,
+            UpdateSchematic::new(&repo_url, &req.commit)
+                .update_summary(&summary, detail_level.as_str())
+                .update_prompt_version(&prompt_version)
+                .execute(&state),
+        )
+        .await
+    {
+        warn!("Failed to store change summary for {}/{}: {}", req.repo, req.commit, e);
+    }
+    let timeline = timer.finish();
+    timing::store_timeline(&state, &repo_url, &req.commit, "commit_summary", &timeline).await;
+
+    let summary_id = record_commit_summary(
+        &state,
+        &req.repo,
+        &req.commit,
+        &model,
+        &prompt_version,
+        detail_level,
+        experiment.as_ref(),
+    )
+    .await;
+
     // Mock response - TODO: integrate with actual Grok API
     // let summary = format!(
     //     "[MOCK] This commit modified {} schematic file(s) in the {} repository.",
@@ -479,37 +617,510 @@ pub async fn summarize_commit(
     Ok(Json(GrokCommitSummaryResponse {
         repo: req.repo,
         commit: req.commit,
+        model,
+        detail_level,
+        prompt_version,
         summary,
         details,
+        summary_id,
+    }))
+}
+
+/// Rate an AI summary
+///
+/// Thumbs up or down, with an optional comment, on a summary by the
+/// `summary_id` its response carried. Ratings are kept with the model and
+/// prompt template that wrote the summary, and `GET /api/admin/feedback`
+/// totals them per combination. Anyone who may read the summary may rate
+/// it, as often as they like.
+#[utoipa::path(
+    post,
+    path = "/api/grok/feedback",
+    request_body = SummaryFeedbackRequest,
+    responses(
+        (status = 200, description = "Rating stored", body = SummaryFeedbackResponse),
+        (status = 400, description = "Rating is not up or down", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 404, description = "No summary with that ID, or it isn't the caller's", body = ApiError),
+        (status = 422, description = "Comment too long", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "grok"
+)]
+pub async fn summary_feedback(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Valid(req): Valid<SummaryFeedbackRequest>,
+) -> Result<Json<SummaryFeedbackResponse>, AppError> {
+    let not_found = || AppError::not_found(format!("No summary {}", req.summary_id));
+    let summary = get_generated_summary(&state, req.summary_id)
+        .await
+        .or_internal("Failed to look up summary")?
+        .ok_or_else(not_found)?;
+    let org_id = get_registered_repo(&state, &summary.repo_url)
+        .await
+        .or_internal("Failed to look up repository")?
+        .and_then(|r| r.org_id);
+    if !viewer.orgs().allows(org_id) {
+        return Err(not_found());
+    }
+
+    let comment = req.comment.as_deref().map(str::trim).filter(|c| !c.is_empty());
+    let feedback = record_summary_feedback(&state, summary.id, req.rating == SummaryRating::Up, comment)
+        .await
+        .or_internal("Failed to store feedback")?;
+    info!(
+        "Summary {} ({} with {}) rated {:?}",
+        summary.id, summary.prompt_version, summary.model, req.rating
+    );
+    Ok(Json(SummaryFeedbackResponse {
+        id: feedback.id,
+        summary_id: summary.id,
+        rating: req.rating,
+        model: summary.model,
+        prompt_version: summary.prompt_version,
     }))
 }
 
+/// The SSE event forwarding a stream event
+///
+/// Content goes out as plain `data` events; the finish reason and token usage
+/// as `finish` and `usage` events, which clients reading only data ignore.
+/// A reasoning model's thinking goes out as `reasoning` events when
+/// `reasoning` is set, and is dropped otherwise. `Done` sends nothing, as
+/// handlers end every stream with their own `[DONE]`, and neither do the
+/// further answers of a request for several choices, which handlers don't make.
+fn sse_event(event: StreamEvent, reasoning: bool) -> Option<Event> {
+    stream_frame(event, reasoning).map(Frame::into_event)
+}
+
+/// [`sse_event`], as kept for clients resuming a stream
+fn stream_frame(event: StreamEvent, reasoning: bool) -> Option<Frame> {
+    match event {
+        StreamEvent::ContentDelta { text } => Some(Frame::data(text)),
+        StreamEvent::ReasoningDelta { text } => reasoning.then(|| Frame::named("reasoning", text)),
+        StreamEvent::FinishReason { reason } => Some(Frame::named("finish", reason)),
+        StreamEvent::Usage { usage } => match Frame::json("usage", &StreamUsageEvent::from(usage)) {
+            Ok(frame) => Some(frame),
+            Err(e) => {
+                error!("Failed to encode usage event: {}", e);
+                None
+            }
+        },
+        StreamEvent::ChoiceDelta { .. } | StreamEvent::Done => None,
+    }
+}
+
+/// Stream an AI-generated summary for a single commit using Server-Sent Events
+///
+/// Text arrives as plain `data` events while the model writes, followed by a
+/// `finish` event with the finish reason and a `usage` event
+/// (StreamUsageEvent) with the tokens spent. When the model finishes, the
+/// summary is stored like `/api/grok/summary/commit` and a
+/// `summary_complete` event carries the full result, followed by `[DONE]`.
+/// A failed stream sends `[ERROR: ...]` instead and stores nothing.
+///
+/// Every event has an ID. A client whose connection drops can send the same
+/// request again with the last ID it got as `Last-Event-ID` and receives the
+/// rest of the same summary, for `stream_resume_secs` after it disconnected
+/// or the summary finished; after that, or with an unknown ID, a new summary
+/// is generated. A summary nobody reconnects to in time is cancelled.
+///
+/// A request for a summary already being streamed to someone else (same
+/// commit, template, model, detail level and persona) follows that stream
+/// from its first event instead of making another AI call; the summary is
+/// stored once.
+#[utoipa::path(
+    post,
+    path = "/api/grok/summary/commit/stream",
+    request_body = GrokCommitSummaryRequest,
+    params(
+        ("Last-Event-ID" = Option<String>, Header, description = "ID of the last event received, to resume a dropped stream")
+    ),
+    responses(
+        (status = 200, description = "Streaming summary via SSE, ending with a summary_complete event carrying a GrokCommitSummaryResponse", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Unknown persona or model not on the allowlist", body = ApiError),
+        (status = 401, description = "Summaries for this repository are private", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required)", body = ApiError),
+        (status = 422, description = "Invalid fields, each listed in violations", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "grok"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn summarize_commit_stream(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(chat): State<Arc<dyn ChatProvider>>,
+    State(prompts): State<Arc<PromptLibrary>>,
+    State(streams): State<Arc<ResumableStreams>>,
+    viewer: Viewer,
+    headers: HeaderMap,
+    Valid(mut req): Valid<GrokCommitSummaryRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let chat = sampled(chat, &config.sampling.summary);
+    req.commit = resolve_commit(&req.repo, &req.commit).await?;
+    info!(
+        "Grok summarize_commit_stream called for {}/{}",
+        req.repo, req.commit
+    );
+
+    viewer.require_summary_access(&state, &req.repo, Some(&req.commit)).await?;
+    let registered = registry::lookup(&state, &config, &req.repo).await?;
+
+    let persona = resolve_persona(&state, Some(&req.repo), req.persona.as_deref()).await?;
+    let guidelines = guidelines::for_repo(&state, &config, &req.repo).await;
+    let experiment = summary_experiment(registered.as_ref(), &config, &req);
+    let (template, default_model) = match &experiment {
+        Some(assignment) => (assignment.template.as_str(), assignment.model.as_str()),
+        None => summary_preferences(registered.as_ref(), &config),
+    };
+    let model = choose_model(&config.models, req.model.as_deref(), default_model)?;
+    let detail_level = req.detail_level.unwrap_or_default();
+
+    // A client picking up a dropped stream, or asking for a summary that is
+    // already being written, follows that generation
+    let key = summary_generation_key(&req, template, &model, detail_level, persona);
+    let resumed = resumable::last_event_id(&headers).and_then(|id| streams.resume(&key, id));
+    if let Some(events) = resumed.or_else(|| streams.join(&key)) {
+        return Ok(summary_sse(events));
+    }
+
+    let erc_comparison = erc_comparison(&state, &req.repo, &req.commit).await;
+    let new_violations = erc_comparison
+        .as_ref()
+        .map(|c| c.introduced_findings())
+        .unwrap_or_default();
+    let warnings = anomalies::stored(&state, &req.repo, &req.commit).await;
+    let max_tokens = summary::max_output_tokens(req.detail_level, persona);
+    let budget = config.models.prompt_budget(&model).reserve_output(max_tokens);
+    let prompt = summary::commit_prompt(&prompts, template, &req.repo, &req.commit, detail_level, &new_violations, &warnings, budget)
+        .or_internal("Failed to build the commit summary prompt")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)
+        .in_stage(Stage::PromptBuild)?;
+    let prompt_version = prompt.label();
+
+    let mut chat_request =
+        ChatCompletionRequest::with_stream(vec![Message::user(prompt.text)], model.clone(), true);
+    if let Some(persona) = persona {
+        persona.apply(&mut chat_request);
+    }
+    if let Some(guidelines) = &guidelines {
+        guidelines.apply(&mut chat_request);
+    }
+    // Set after the persona, whose token budget an explicit detail level overrides
+    chat_request.max_tokens = Some(max_tokens);
+
+    let job = status::track_job("commit_summary", &req.repo, Some(&req.commit));
+    let mut timer = StageTimer::start("commit_summary");
+    let llm_started = std::time::Instant::now();
+
+    // Get the stream; it's cancelled once no client has listened for a while
+    let cancel = CancellationToken::new();
+    let stream = chat
+        .chat_completion_stream(&chat_request, cancel.clone())
+        .await
+        .or_internal("Failed to start AI stream")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)
+        .in_stage(Stage::Llm)?;
+    let publisher = streams.publish(key, CancelOnDisconnect::new(cancel, "/api/grok/summary/commit/stream"));
+    let events = publisher.subscribe();
+
+    // Forward chunks as they arrive, then store and announce the full summary.
+    // Runs apart from the response, so a client that reconnects finds it.
+    tokio::spawn(async move {
+        let _job = job;
+        tokio::pin!(stream);
+
+        let mut summary = String::new();
+        loop {
+            let result = tokio::select! {
+                result = stream.next() => match result {
+                    Some(result) => result,
+                    None => break,
+                },
+                // Clients are told by shutdown::sse; the AI call ends with the publisher
+                _ = shutdown::started() => return,
+            };
+            match result {
+                Ok(event) => {
+                    if let Some(text) = event.text() {
+                        summary.push_str(text);
+                    }
+                    if let Some(frame) = stream_frame(event, false) {
+                        publisher.push(frame);
+                    }
+                }
+                Err(XaiError::Cancelled) => return,
+                Err(e) => {
+                    let e = anyhow::Error::from(e);
+                    error!("Commit summary stream failed for {}/{}: {:#}", req.repo, req.commit, e);
+                    publisher.push(Frame::data(format!("[ERROR: {:#}]", e)));
+                    publisher.push(Frame::data("[DONE]"));
+                    return;
+                }
+            }
+        }
+        timer.record(Stage::Llm, llm_started.elapsed());
+        info!(
+            "Successfully streamed {} summary for {}/{}",
+            detail_level, req.repo, req.commit
+        );
+
+        // Record the summary and the level and prompt version it was generated with
+        let repo_url = git::repo_url(&req.repo);
+        if let Err(e) = timer
+            .time(
+                Stage::Store,
+                UpdateSchematic::new(&repo_url, &req.commit)
+                    .update_summary(&summary, detail_level.as_str())
+                    .update_prompt_version(&prompt_version)
+                    .execute(&state),
+            )
+            .await
+        {
+            warn!("Failed to store change summary for {}/{}: {}", req.repo, req.commit, e);
+        }
+        let timeline = timer.finish();
+        timing::store_timeline(&state, &repo_url, &req.commit, "commit_summary", &timeline).await;
+
+        let summary_id = record_commit_summary(
+            &state,
+            &req.repo,
+            &req.commit,
+            &model,
+            &prompt_version,
+            detail_level,
+            experiment.as_ref(),
+        )
+        .await;
+        let complete = GrokCommitSummaryResponse {
+            details: format!("Model: {}\nPrompt: {}\n\n{}", model, prompt_version, summary),
+            repo: req.repo,
+            commit: req.commit,
+            model,
+            detail_level,
+            prompt_version,
+            summary,
+            summary_id,
+        };
+        match Frame::json("summary_complete", &complete) {
+            Ok(frame) => publisher.push(frame),
+            Err(e) => error!("Failed to encode summary_complete event: {}", e),
+        }
+
+        // Send a done event
+        publisher.push(Frame::data("[DONE]"));
+    });
+
+    Ok(summary_sse(events))
+}
+
+/// The SSE response of a commit summary stream
+fn summary_sse(events: EventStream) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    Sse::new(shutdown::sse(events)).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .text("keep-alive"),
+    )
+}
+
+/// Summarize every schematic commit of a repo that has no summary yet
+///
+/// Runs as a background job, so it carries on if the client disconnects;
+/// posting again while it runs follows the same job. Progress streams as
+/// `progress` events (BackfillProgress) whenever a commit starts or
+/// finishes, the last one with status `completed`, `stopped` or `cancelled`,
+/// followed by `[DONE]`. Admins can follow and cancel the job by its
+/// `job_id` under /api/admin/jobs. Summaries use the repo's registered
+/// template and model, its default persona and the default detail level.
+/// The job stops early if the
+/// AI is unavailable, rate limited or over budget. Unless
+/// `xai.deferred_backfill` is off, summaries are submitted as deferred
+/// completions and polled for, and a backfill cut short by a restart starts
+/// again with the server, collecting the summaries already submitted.
+#[utoipa::path(
+    post,
+    path = "/api/grok/summary/backfill/{repo}",
+    params(
+        ("repo" = String, Path, description = "GitHub repository in owner/repo format")
+    ),
+    responses(
+        (status = 200, description = "Backfill progress via SSE, as progress events carrying a BackfillProgress", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Repo default persona is unknown", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required)", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "grok"
+)]
+pub async fn backfill_summaries(
+    State(state): State<AppState>,
+    Path(repo): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let repo = repo.trim_matches('/').to_string();
+    info!("Grok backfill_summaries called for {}", repo);
+
+    let settings = backfill_settings(&state, &repo).await?;
+    let mut progress = backfill::start(state.clone(), repo, settings, WorkClass::Backfill);
+
+    let sse_stream = async_stream::stream! {
+        loop {
+            let current = progress.borrow_and_update().clone();
+            match Event::default().event("progress").json_data(&current) {
+                Ok(event) => yield Ok(event),
+                Err(e) => error!("Failed to encode progress event: {}", e),
+            }
+            // The job publishes its final status before it goes away
+            if current.status != BackfillStatus::Running || progress.changed().await.is_err() {
+                break;
+            }
+        }
+        yield Ok(Event::default().data("[DONE]"));
+    };
+
+    Ok(Sse::new(shutdown::sse(sse_stream)).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .text("keep-alive"),
+    ))
+}
+
+/// How a backfill of `repo` generates summaries: its registered template and
+/// model, its default persona and its design guidelines; also charges the AI
+/// calls to the repo
+pub(crate) async fn backfill_settings(state: &AppState, repo: &str) -> Result<backfill::SummarySettings, AppError> {
+    let config = state.config();
+    let registered = registry::lookup(&state.pool, &config, repo).await?;
+    let persona = resolve_persona(&state.pool, Some(repo), None).await?;
+    let (template, model) = summary_preferences(registered.as_ref(), &config);
+    Ok(backfill::SummarySettings {
+        template: template.to_string(),
+        model: model.to_string(),
+        persona,
+        guidelines: guidelines::for_repo(&state.pool, &config, repo).await,
+        org: registered.as_ref().and_then(|r| r.org_id),
+    })
+}
+
+/// ERC comparison against the parent, if the commit has been distilled;
+/// lookup failures only lose the ERC section of the prompt
+pub(crate) async fn erc_comparison(pool: &PgPool, repo: &str, commit: &str) -> Option<erc::ErcComparison> {
+    match erc::compare_with_parent(pool, repo, commit).await {
+        Ok(comparison) => comparison,
+        Err(e) => {
+            warn!("Failed to load ERC findings for {}/{}: {:#}", repo, commit, e);
+            None
+        }
+    }
+}
+
+/// The stored schematic image for a commit, if there is one in a format the
+/// model accepts; lookup failures only lose the image
+async fn schematic_snapshot(pool: &PgPool, repo: &str, commit: &str) -> Option<ImageUrl> {
+    let repo_url = git::repo_url(repo);
+    match kicad_db::retrieve_schematic_image(pool, &repo_url, commit).await {
+        Ok(Some(bytes)) => {
+            let image = ImageUrl::from_bytes(&bytes);
+            if image.is_none() {
+                warn!("Stored schematic image for {}/{} is not a PNG or JPEG under 20 MB", repo, commit);
+            }
+            image
+        }
+        Ok(None) => None,
+        Err(e) => {
+            warn!("Failed to load the schematic image for {}/{}: {}", repo, commit, e);
+            None
+        }
+    }
+}
+
+/// The distilled schematic of `repo` at `commit`: from the cache, or
+/// distilled now
+async fn load_distilled(
+    pool: &PgPool,
+    schematics: &Arc<SchematicCache>,
+    repo: &str,
+    commit: &str,
+) -> Result<serde_json::Value, AppError> {
+    let repo_url = git::repo_url(repo);
+    match kicad_db::retrieve_distilled_json(pool, &repo_url, commit).await {
+        Ok(Some(cached)) => Ok(cached),
+        _ => distill::distill_repo_schematics(schematics, repo, commit)
+            .await
+            .or_internal("Failed to distill schematic")
+            .for_repo(repo)
+            .at_commit(commit)
+            .in_stage(Stage::Parse),
+    }
+}
+
+/// What a selection request selected, resolved against `distilled`; 404 for
+/// nets, sheets or areas the schematic doesn't have
+fn resolve_selection(
+    distilled: &serde_json::Value,
+    requested: Option<&Selection>,
+    repo: &str,
+    commit: &str,
+) -> Result<Resolved, AppError> {
+    match requested {
+        Some(requested) => selection::resolve(distilled, requested)
+            .map_err(|e| AppError::not_found(format!("{} at commit {}", e, commit)))
+            .for_repo(repo),
+        None => Ok(Resolved::default()),
+    }
+}
+
 /// Get an AI-generated summary for selected components
+///
+/// The selection is either `component_ids` or a `selection` of components,
+/// nets, a hierarchical sheet or an area of a sheet; nets, sheets and areas
+/// stand for the components on them.
 #[utoipa::path(
     post,
     path = "/api/grok/summary/selection",
     request_body = GrokSelectionSummaryRequest,
     responses(
         (status = 200, description = "AI-generated component selection summary", body = GrokSelectionSummaryResponse),
+        (status = 401, description = "Summaries for this repository are private", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required), or the selected nets, sheet or area aren't in the schematic", body = ApiError),
+        (status = 422, description = "Invalid fields, each listed in violations", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
         (status = 500, description = "Internal server error", body = ApiError)
     ),
     tag = "grok"
 )]
 pub async fn summarize_selection(
-    State(_state): State<AppState>,
-    Json(req): Json<GrokSelectionSummaryRequest>,
-) -> Result<Json<GrokSelectionSummaryResponse>, (StatusCode, Json<ApiError>)> {
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    viewer: Viewer,
+    Valid(mut req): Valid<GrokSelectionSummaryRequest>,
+) -> Result<Json<GrokSelectionSummaryResponse>, AppError> {
+    req.commit = resolve_commit(&req.repo, &req.commit).await?;
+    let requested = selection::requested(&req.component_ids, req.selection.as_ref());
     info!(
-        "Grok summarize_selection called for {}/{} with {} components",
+        "Grok summarize_selection called for {}/{} with {}",
         req.repo,
         req.commit,
-        req.component_ids.len()
+        selection::describe(requested.as_ref())
     );
 
+    viewer.require_summary_access(&state, &req.repo, Some(&req.commit)).await?;
+    registry::lookup(&state, &config, &req.repo).await?;
+
+    let detail_level = req.detail_level.unwrap_or_default();
+    let distilled = load_distilled(&state, &schematics, &req.repo, &req.commit).await?;
+    let resolved = resolve_selection(&distilled, requested.as_ref(), &req.repo, &req.commit)?;
+
     // Mock response - TODO: integrate with actual Grok API
     let summary = format!(
-        "[MOCK] Analysis of {} selected component(s) in commit {}.",
-        req.component_ids.len(),
+        "[MOCK] {} analysis of {} selected component(s) in commit {}.",
+        detail_level,
+        resolved.references.len(),
         &req.commit[..8.min(req.commit.len())]
     );
 
@@ -522,92 +1133,760 @@ pub async fn summarize_selection(
         - Pin connectivity and net associations\n\
         - Related components in the design\n\
         - Suggestions for alternatives or improvements",
-        req.component_ids.join(", ")
+        resolved.references.join(", ")
     );
 
     Ok(Json(GrokSelectionSummaryResponse {
         repo: req.repo,
         commit: req.commit,
-        component_ids: req.component_ids,
+        component_ids: resolved.references,
+        detail_level,
         summary,
         details,
     }))
 }
 
 /// Get an AI-generated summary for an entire repository (latest commit on main)
+///
+/// Each sheet is summarized, in chunks that fit the model's context window,
+/// and the sheet summaries are merged into the overview; `details` lists
+/// them. Every step is cached, so asking again after a commit only
+/// re-summarizes the sheets that changed.
 #[utoipa::path(
     post,
     path = "/api/grok/summary/repo",
     request_body = GrokRepoSummaryRequest,
     responses(
         (status = 200, description = "AI-generated repository summary", body = GrokRepoSummaryResponse),
+        (status = 400, description = "Unknown persona", body = ApiError),
+        (status = 401, description = "Summaries for this repository are private", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required)", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
         (status = 500, description = "Internal server error", body = ApiError)
     ),
     tag = "grok"
 )]
 pub async fn summarize_repo(
-    State(_state): State<AppState>,
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(chat): State<Arc<dyn ChatProvider>>,
+    State(prompts): State<Arc<PromptLibrary>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    viewer: Viewer,
     Json(req): Json<GrokRepoSummaryRequest>,
-) -> Result<Json<GrokRepoSummaryResponse>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<GrokRepoSummaryResponse>, AppError> {
+    let chat = sampled(chat, &config.sampling.summary);
     info!("Grok summarize_repo called for {}", req.repo);
 
+    viewer.require_summary_access(&state, &req.repo, None).await?;
+    let registered = registry::lookup(&state, &config, &req.repo).await?;
+
+    let persona = resolve_persona(&state, Some(&req.repo), req.persona.as_deref()).await?;
+    let guidelines = guidelines::for_repo(&state, &config, &req.repo).await;
+    let (_, model) = summary_preferences(registered.as_ref(), &config);
+    let detail_level = req.detail_level.unwrap_or_default();
+
     // Get the latest commit
-    let latest_commit = git::get_latest_commit(&req.repo).await.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError::internal(format!(
-                "Failed to fetch latest commit: {}",
-                e
-            ))),
-        )
-    })?;
+    let latest_commit = git::get_latest_commit(&req.repo)
+        .await
+        .or_internal("Failed to fetch latest commit")
+        .for_repo(&req.repo)?;
 
-    // Get schematic files at latest commit
     let files = git::get_schematic_files(&req.repo, &latest_commit)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::internal(format!(
-                    "Failed to fetch schematic files: {}",
-                    e
-                ))),
-            )
-        })?;
-
-    // Mock response - TODO: integrate with actual Grok API
-    let summary = format!(
-        "[MOCK] Repository {} contains {} schematic file(s) at the latest commit.",
-        req.repo,
-        files.len()
-    );
+        .or_internal("Failed to fetch schematic files")
+        .for_repo(&req.repo)
+        .at_commit(&latest_commit)?;
+    let files: Vec<String> = files.into_iter().map(|f| f.path).collect();
+    let projects = distill::load_projects(&schematics, &req.repo, &latest_commit)
+        .await
+        .or_internal("Failed to parse the schematics")
+        .for_repo(&req.repo)
+        .at_commit(&latest_commit)
+        .in_stage(Stage::Parse)?;
 
-    let details = format!(
-        "[MOCK] Repository overview for {}:\n\n\
-        Latest commit: {}\n\
-        Schematic files:\n{}\n\n\
-        This is a placeholder response. In production, this would contain \
-        AI-generated insights about the entire project, including:\n\
-        - Overall design architecture\n\
-        - Key components and subsystems\n\
-        - Design complexity metrics\n\
-        - Potential areas for improvement",
-        req.repo,
-        latest_commit,
-        files
-            .iter()
-            .map(|f| format!("  - {}", f.path))
-            .collect::<Vec<_>>()
-            .join("\n")
+    let categories = summarization::category_lines(&state, &projects)
+        .await
+        .or_internal("Failed to load part categories")
+        .for_repo(&req.repo)
+        .at_commit(&latest_commit)?;
+
+    // Sheets are summarized in chunks, then merged into the overview
+    let _job = status::track_job("repo_overview", &req.repo, Some(&latest_commit));
+    let summarizer = Summarizer::new(
+        &state,
+        chat.as_ref(),
+        &prompts,
+        model,
+        config.models.context_window(model),
+        &req.repo,
     );
+    let (summary, sheets) = summarizer
+        .overview(
+            &latest_commit,
+            &files,
+            &summarization::sheets(&projects),
+            &categories,
+            req.detail_level,
+            persona,
+            guidelines.as_ref(),
+        )
+        .await
+        .or_internal("Failed to summarize the repository")
+        .for_repo(&req.repo)
+        .at_commit(&latest_commit)
+        .in_stage(Stage::Llm)?;
+
+    let mut details = format!("Overview of {} at commit {}", req.repo, latest_commit);
+    for sheet in &sheets {
+        details.push_str(&format!("\n\n## {}\n{}", sheet.title, sheet.text));
+    }
 
     Ok(Json(GrokRepoSummaryResponse {
         repo: req.repo,
+        detail_level,
         summary,
         details,
     }))
 }
 
+/// Get an AI-written narrative of how a repository changed between two commits
+///
+/// The component and net changes from `base` to `head`, with the messages of
+/// the commits in between, are summarized as one story, e.g. for release
+/// notes covering many commits. Comparisons are stored per commit pair and
+/// detail level, and returned again until the prompt or model changes or
+/// `refresh` is set.
+#[utoipa::path(
+    post,
+    path = "/api/grok/summary/compare",
+    request_body = GrokCompareSummaryRequest,
+    responses(
+        (status = 200, description = "AI-written comparison of the two commits", body = GrokCompareSummaryResponse),
+        (status = 400, description = "Base and head are the same commit", body = ApiError),
+        (status = 401, description = "Summaries for this repository are private", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required), or a commit doesn't exist", body = ApiError),
+        (status = 422, description = "Invalid fields, each listed in violations", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "grok"
+)]
+pub async fn summarize_compare(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(chat): State<Arc<dyn ChatProvider>>,
+    State(prompts): State<Arc<PromptLibrary>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    viewer: Viewer,
+    Valid(mut req): Valid<GrokCompareSummaryRequest>,
+) -> Result<Json<GrokCompareSummaryResponse>, AppError> {
+    let chat = sampled(chat, &config.sampling.summary);
+    req.base = resolve_commit(&req.repo, &req.base).await?;
+    req.head = resolve_commit(&req.repo, &req.head).await?;
+    info!("Grok summarize_compare called for {} {}..{}", req.repo, req.base, req.head);
+    if req.base == req.head {
+        return Err(AppError::bad_request("base and head are the same commit"));
+    }
+
+    viewer.require_summary_access(&state, &req.repo, Some(&req.base)).await?;
+    viewer.require_summary_access(&state, &req.repo, Some(&req.head)).await?;
+    let registered = registry::lookup(&state, &config, &req.repo).await?;
+    let (_, model) = summary_preferences(registered.as_ref(), &config);
+    let detail_level = req.detail_level.unwrap_or_default();
+    let repo_url = git::repo_url(&req.repo);
+
+    let prompt_version = prompts
+        .get(COMPARE_SUMMARY)
+        .map(|template| template.label())
+        .unwrap_or_default();
+    let response = |comparison: CommitComparison, cached: bool| GrokCompareSummaryResponse {
+        repo: req.repo.clone(),
+        base: comparison.base_commit,
+        head: comparison.head_commit,
+        commit_count: comparison.commit_count,
+        model: comparison.model,
+        detail_level,
+        prompt_version: comparison.prompt_version,
+        summary: comparison.summary,
+        changes: comparison.changes,
+        cached,
+        generated_at: comparison.created_at,
+    };
+    if !req.refresh {
+        match get_comparison(&state, &repo_url, &req.base, &req.head, detail_level.as_str()).await {
+            Ok(Some(stored)) if stored.model == model && stored.prompt_version == prompt_version => {
+                return Ok(Json(response(stored, true)));
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to look up a stored comparison for {}: {}", req.repo, e),
+        }
+    }
+
+    let commits = git::get_commits_between(&req.repo, &req.base, &req.head)
+        .await
+        .or_internal("Failed to list the commits in between")
+        .for_repo(&req.repo)?;
+    let projects = distill::changes_between(&schematics, &req.repo, Some(&req.base), &req.head)
+        .await
+        .or_internal("Failed to compare the schematics")
+        .for_repo(&req.repo)
+        .at_commit(&req.head)
+        .in_stage(Stage::Parse)?;
+    let changes = distill::describe_changes(&projects);
+
+    let _job = status::track_job("commit_comparison", &req.repo, Some(&req.head));
+    let (summary, prompt_version) = summary::generate_comparison(
+        chat.as_ref(),
+        &prompts,
+        model,
+        config.models.context_window(model),
+        &req.repo,
+        &req.base,
+        &req.head,
+        &commits,
+        &changes,
+        detail_level,
+    )
+    .await
+    .or_internal("Failed to compare the commits")
+    .for_repo(&req.repo)
+    .at_commit(&req.head)
+    .in_stage(Stage::Llm)?;
+
+    let comparison = CommitComparison {
+        repo_url,
+        base_commit: req.base.clone(),
+        head_commit: req.head.clone(),
+        detail_level: detail_level.as_str().to_string(),
+        commit_count: commits.len() as i32,
+        summary,
+        changes,
+        model: model.to_string(),
+        prompt_version,
+        created_at: Utc::now(),
+    };
+    if let Err(e) = store_comparison(&state, &comparison).await {
+        warn!("Failed to store the comparison of {} {}..{}: {}", req.repo, req.base, req.head, e);
+    }
+    Ok(Json(response(comparison, false)))
+}
+
+/// A stored checklist as returned to the client
+pub(crate) fn checklist_response(
+    repo: String,
+    checklist: ReviewChecklist,
+    items: Vec<ChecklistItem>,
+    cached: bool,
+) -> ReviewChecklistResponse {
+    ReviewChecklistResponse {
+        repo,
+        commit: checklist.commit_hash,
+        model: checklist.model,
+        prompt_version: checklist.prompt_version,
+        items: items.into_iter().map(ReviewChecklistItem::from).collect(),
+        cached,
+        generated_at: checklist.created_at,
+    }
+}
+
+/// Generate a design-review checklist for a commit
+///
+/// Grok lists the specific things to verify, grounded in the commit's
+/// component and net changes and the ERC findings it introduced; each item
+/// names the changed components it concerns. The checklist is stored so
+/// reviewers can tick items off (see PUT
+/// /api/repos/{repo}/commits/{commit}/checklist/{item}) and is returned as
+/// stored until `refresh` replaces it, even after the prompt or model changes.
+#[utoipa::path(
+    post,
+    path = "/api/grok/review/checklist",
+    request_body = GrokReviewChecklistRequest,
+    responses(
+        (status = 200, description = "The commit's review checklist", body = ReviewChecklistResponse),
+        (status = 401, description = "Summaries for this commit are private", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required), or the commit doesn't exist", body = ApiError),
+        (status = 422, description = "Invalid fields, each listed in violations", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "grok"
+)]
+pub async fn review_checklist(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(chat): State<Arc<dyn ChatProvider>>,
+    State(prompts): State<Arc<PromptLibrary>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    viewer: Viewer,
+    Valid(mut req): Valid<GrokReviewChecklistRequest>,
+) -> Result<Json<ReviewChecklistResponse>, AppError> {
+    let chat = sampled(chat, &config.sampling.summary);
+    req.commit = resolve_commit(&req.repo, &req.commit).await?;
+    info!("Grok review_checklist called for {}/{}", req.repo, req.commit);
+
+    viewer.require_summary_access(&state, &req.repo, Some(&req.commit)).await?;
+    let registered = registry::lookup(&state, &config, &req.repo).await?;
+    let (_, model) = summary_preferences(registered.as_ref(), &config);
+    let repo_url = git::repo_url(&req.repo);
+
+    if !req.refresh {
+        match get_review_checklist(&state, &repo_url, &req.commit).await {
+            Ok(Some((checklist, items))) => return Ok(Json(checklist_response(req.repo, checklist, items, true))),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to look up the stored checklist of {}/{}: {}", req.repo, req.commit, e),
+        }
+    }
+
+    let info = git::get_commit_info(&req.repo, &req.commit)
+        .await
+        .or_internal("Failed to read the commit")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)?;
+    let projects = distill::project_changes(&schematics, &req.repo, &req.commit)
+        .await
+        .or_internal("Failed to diff the schematics")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)
+        .in_stage(Stage::Parse)?;
+    let erc_comparison = erc_comparison(&state, &req.repo, &req.commit).await;
+    let findings = erc_comparison
+        .as_ref()
+        .map(|c| c.introduced_findings())
+        .unwrap_or_default();
+
+    let _job = status::track_job("review_checklist", &req.repo, Some(&req.commit));
+    let references = review_checklist::changed_references(&projects, &findings);
+    let (items, prompt_version) = review_checklist::generate(
+        chat.as_ref(),
+        &prompts,
+        model,
+        config.models.context_window(model),
+        &req.repo,
+        &req.commit,
+        info.message.as_deref(),
+        &distill::describe_changes(&projects),
+        &review_checklist::erc_lines(&findings),
+        &references,
+    )
+    .await
+    .or_internal("Failed to write the review checklist")
+    .for_repo(&req.repo)
+    .at_commit(&req.commit)
+    .in_stage(Stage::Llm)?;
+
+    let checklist = ReviewChecklist {
+        repo_url,
+        commit_hash: req.commit.clone(),
+        model: model.to_string(),
+        prompt_version,
+        created_at: Utc::now(),
+    };
+    let stored = store_review_checklist(&state, &checklist, &items)
+        .await
+        .or_internal("Failed to store the review checklist")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)
+        .in_stage(Stage::Store)?;
+    Ok(Json(checklist_response(req.repo, checklist, stored, false)))
+}
+
+/// Branch a suggested fix is proposed from: one per commit and change, so
+/// asking again for the same change updates its pull request
+fn fix_branch(commit: &str, change: &str) -> String {
+    let digest = format!("{:x}", Sha256::digest(change.trim().as_bytes()));
+    format!("grokicad/fix-{}-{}", &commit[..commit.len().min(7)], &digest[..8])
+}
+
+/// Open a pull request with an AI-suggested schematic change
+///
+/// Grok turns the described change into edits of the commit's schematic
+/// (component values and properties, swapped pins, no-connect flags), which
+/// are applied to the .kicad_sch files, committed on top of the commit on a
+/// bot branch and proposed to the default branch. Only repos registered with
+/// `processing.suggest_fixes` on take suggestions, and the server needs a
+/// GITHUB_TOKEN that may push branches and open pull requests.
+#[utoipa::path(
+    post,
+    path = "/api/grok/suggest-fix/{repo}/{commit}",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag")
+    ),
+    request_body = GrokSuggestFixRequest,
+    responses(
+        (status = 200, description = "The pull request proposing the change", body = SuggestFixResponse),
+        (status = 403, description = "The repo hasn't opted in to suggested fixes", body = ApiError),
+        (status = 404, description = "Repository is not registered, or the commit doesn't exist", body = ApiError),
+        (status = 422, description = "Invalid fields, or the change couldn't be made as schematic edits", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError),
+        (status = 503, description = "GITHUB_TOKEN is not configured", body = ApiError)
+    ),
+    tag = "grok"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn suggest_fix(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(chat): State<Arc<dyn ChatProvider>>,
+    State(prompts): State<Arc<PromptLibrary>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    Path((repo, commit)): Path<(String, String)>,
+    Valid(req): Valid<GrokSuggestFixRequest>,
+) -> Result<Json<SuggestFixResponse>, AppError> {
+    let chat = sampled(chat, &config.sampling.summary);
+    let commit = resolve_commit(&repo, &commit).await?;
+    info!("Grok suggest_fix called for {}/{}", repo, commit);
+
+    // Only a registration can opt in, so one is needed either way
+    let registered = registry::lookup(&state, &config, &repo)
+        .await?
+        .ok_or_else(|| AppError::not_found(format!("Repository {} is not registered", repo)).for_repo(&repo))?;
+    if !registered.processing.suggest_fixes {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "suggest_fixes_disabled",
+            format!("Repository {} hasn't opted in to suggested fixes", repo),
+        ));
+    }
+    if !github::is_configured() {
+        return Err(AppError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "not_configured",
+            "Suggested fixes need GITHUB_TOKEN to be configured on the server",
+        ));
+    }
+    let (_, model) = summary_preferences(Some(&registered), &config);
+
+    let files = git::get_schematic_files(&repo, &commit)
+        .await
+        .or_internal("Failed to read the schematic files")
+        .for_repo(&repo)
+        .at_commit(&commit)?;
+    let projects = distill::load_projects(&schematics, &repo, &commit)
+        .await
+        .or_internal("Failed to parse the schematics")
+        .for_repo(&repo)
+        .at_commit(&commit)
+        .in_stage(Stage::Parse)?;
+
+    let _job = status::track_job("suggest_fix", &repo, Some(&commit));
+    let (edits, prompt_version) = suggest_fix::generate(
+        chat.as_ref(),
+        &prompts,
+        model,
+        config.models.context_window(model),
+        &repo,
+        &commit,
+        &req.change,
+        &suggest_fix::component_lines(&projects),
+    )
+    .await
+    .or_internal("Failed to suggest edits")
+    .for_repo(&repo)
+    .at_commit(&commit)
+    .in_stage(Stage::Llm)?;
+    let unprocessable = |message: String| AppError::new(StatusCode::UNPROCESSABLE_ENTITY, "unsuitable_change", message);
+    if edits.is_empty() {
+        return Err(unprocessable("The change can't be made as schematic edits".to_string()));
+    }
+    let edited = suggest_fix::apply(&files, &edits)
+        .map_err(|e| unprocessable(format!("The suggested edits don't apply: {}", e)))?;
+    if edited.is_empty() {
+        return Err(unprocessable("The suggested edits change nothing".to_string()));
+    }
+
+    let edits: Vec<String> = edits.iter().map(ToString::to_string).collect();
+    let branch = fix_branch(&commit, &req.change);
+    let body = format!(
+        "{}\n\nSuggested edits to {}:\n\n{}\n\nSuggested by {} ({}). Check the schematic in KiCad before merging.",
+        req.change.trim(),
+        &commit[..commit.len().min(7)],
+        edits.iter().map(|e| format!("- `{}`", e)).collect::<Vec<_>>().join("\n"),
+        model,
+        prompt_version
+    );
+    let title = format!("Suggested fix: {}", req.change.trim().lines().next().unwrap_or_default());
+    let files: Vec<(String, String)> = edited.into_iter().map(|f| (f.path, f.content)).collect();
+    let pull_request_url = github::propose_files(
+        &repo,
+        &github::FilesProposal {
+            base_commit: &commit,
+            files: &files,
+            branch: &branch,
+            commit_message: &title,
+            title: &title,
+            body: &body,
+        },
+    )
+    .await
+    .or_internal("Failed to open the pull request")
+    .for_repo(&repo)
+    .at_commit(&commit)?;
+    info!("Opened {} suggesting {} edit(s) to {}/{}", pull_request_url, edits.len(), repo, commit);
+
+    Ok(Json(SuggestFixResponse {
+        repo,
+        commit,
+        pull_request_url,
+        branch,
+        edits,
+        files: files.into_iter().map(|(path, _)| path).collect(),
+        model: model.to_string(),
+        prompt_version,
+    }))
+}
+
+/// Answer a question about one commit's design
+///
+/// Unlike chat this is a single stateless request, suited to tooltips. The
+/// answer is grounded in the commit's bill of materials, the stored
+/// summaries of it and the commits before it, and the commit summaries,
+/// components and nets retrieved for the question (see `top_k`), which it
+/// cites by tag as chat does. `components` and `nets` list what it mentions
+/// that exists at the commit. Answers are stored per question (ignoring case
+/// and spacing) and reused until the prompt or model changes or `refresh`
+/// is set.
+#[utoipa::path(
+    post,
+    path = "/api/grok/ask",
+    request_body = GrokAskRequest,
+    responses(
+        (status = 200, description = "The answer and what it draws on", body = GrokAskResponse),
+        (status = 401, description = "Summaries for this commit are private", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required), or the commit doesn't exist", body = ApiError),
+        (status = 422, description = "Invalid fields, each listed in violations", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "grok"
+)]
+pub async fn ask(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(chat): State<Arc<dyn ChatProvider>>,
+    State(prompts): State<Arc<PromptLibrary>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    viewer: Viewer,
+    Valid(mut req): Valid<GrokAskRequest>,
+) -> Result<Json<GrokAskResponse>, AppError> {
+    let chat = sampled(chat, &config.sampling.chat);
+    req.commit = resolve_commit(&req.repo, &req.commit).await?;
+    info!("Grok ask called for {}/{}", req.repo, req.commit);
+
+    viewer.require_summary_access(&state, &req.repo, Some(&req.commit)).await?;
+    registry::lookup(&state, &config, &req.repo).await?;
+    let model = config.models.chat.as_str();
+    let repo_url = git::repo_url(&req.repo);
+    let include_private = viewer.is_authenticated();
+    let digest = question_digest(&req.question);
+
+    let prompt_version = prompts.get(ASK).map(|template| template.label()).unwrap_or_default();
+    let response = |answer: CommitAnswer, cached: bool| GrokAskResponse {
+        repo: req.repo.clone(),
+        commit: answer.commit_hash,
+        question: answer.question,
+        answer: answer.answer,
+        components: answer.components,
+        nets: answer.nets,
+        sources: serde_json::from_value(answer.sources).unwrap_or_default(),
+        model: answer.model,
+        prompt_version: answer.prompt_version,
+        cached,
+        generated_at: answer.created_at,
+    };
+    if !req.refresh {
+        match get_commit_answer(&state, &repo_url, &req.commit, &digest, include_private).await {
+            Ok(Some(stored)) if stored.model == model && stored.prompt_version == prompt_version => {
+                return Ok(Json(response(stored, true)));
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to look up a stored answer for {}/{}: {}", req.repo, req.commit, e),
+        }
+    }
+
+    let projects = distill::load_projects(&schematics, &req.repo, &req.commit)
+        .await
+        .or_internal("Failed to load the schematics")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)
+        .in_stage(Stage::Parse)?;
+    let summaries = ask::recent_summaries(&state, &req.repo, &req.commit, |visibility| {
+        viewer.can_see(visibility.parse().unwrap_or_default())
+    })
+    .await
+    .unwrap_or_else(|e| {
+        warn!("Failed to load recent summaries of {}: {:#}", req.repo, e);
+        String::new()
+    });
+    let sources = match req.top_k.unwrap_or(DEFAULT_CHAT_TOP_K) {
+        0 => Vec::new(),
+        top_k => {
+            let scope = retrieval::RetrievalScope {
+                repo: &req.repo,
+                commit: Some(&req.commit),
+                include_private,
+                embedding_model: &config.models.embedding,
+            };
+            retrieval::retrieve(&state, chat.as_ref(), &scope, &req.question, top_k).await
+        }
+    };
+
+    let _job = status::track_job("ask", &req.repo, Some(&req.commit));
+    let (text, prompt_version) = ask::generate(
+        chat.as_ref(),
+        &prompts,
+        model,
+        config.models.context_window(model),
+        &req.repo,
+        &req.commit,
+        req.question.trim(),
+        &ask::bom_lines(&projects),
+        &summaries,
+        &sources,
+    )
+    .await
+    .or_internal("Failed to answer the question")
+    .for_repo(&req.repo)
+    .at_commit(&req.commit)
+    .in_stage(Stage::Llm)?;
+    let (components, nets) = ask::mentioned(&text, &projects);
+
+    let answer = CommitAnswer {
+        repo_url,
+        commit_hash: req.commit.clone(),
+        question_digest: digest,
+        include_private,
+        question: req.question.trim().to_string(),
+        answer: text,
+        components,
+        nets,
+        sources: serde_json::to_value(&sources).or_internal("Failed to encode the sources")?,
+        model: model.to_string(),
+        prompt_version,
+        created_at: Utc::now(),
+    };
+    if let Err(e) = store_commit_answer(&state, &answer).await {
+        warn!("Failed to store the answer for {}/{}: {}", req.repo, req.commit, e);
+    }
+    Ok(Json(response(answer, false)))
+}
+
+/// Get AI-written hardware release notes for the commits between two tags
+///
+/// Each commit that changed a schematic or layout contributes its stored
+/// blurb; commits without one get a brief summary generated (and stored) first.
+/// The commits are filed under power, MCU, connectors, bug fixes or other by
+/// the words in their message and blurb, and Grok writes the Markdown notes
+/// from them. `changes` lists the commits with their sections.
+#[utoipa::path(
+    post,
+    path = "/api/grok/release-notes",
+    request_body = GrokReleaseNotesRequest,
+    responses(
+        (status = 200, description = "Release notes and the changes they cover", body = GrokReleaseNotesResponse),
+        (status = 400, description = "The tags point at the same commit, or span too many commits", body = ApiError),
+        (status = 401, description = "Summaries for this repository are private", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required), or a tag doesn't exist", body = ApiError),
+        (status = 422, description = "Invalid fields, each listed in violations", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "grok"
+)]
+pub async fn release_notes(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(chat): State<Arc<dyn ChatProvider>>,
+    State(prompts): State<Arc<PromptLibrary>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    viewer: Viewer,
+    Valid(req): Valid<GrokReleaseNotesRequest>,
+) -> Result<Json<GrokReleaseNotesResponse>, AppError> {
+    let chat = sampled(chat, &config.sampling.summary);
+    let from_commit = resolve_commit(&req.repo, &req.from_tag).await?;
+    let to_commit = resolve_commit(&req.repo, &req.to_tag).await?;
+    info!("Grok release_notes called for {} {}..{}", req.repo, req.from_tag, req.to_tag);
+    if from_commit == to_commit {
+        return Err(AppError::bad_request(format!(
+            "{} and {} point at the same commit",
+            req.from_tag, req.to_tag
+        )));
+    }
+
+    viewer.require_summary_access(&state, &req.repo, Some(&from_commit)).await?;
+    viewer.require_summary_access(&state, &req.repo, Some(&to_commit)).await?;
+    let registered = registry::lookup(&state, &config, &req.repo).await?;
+    let (_, model) = summary_preferences(registered.as_ref(), &config);
+    let context_window = config.models.context_window(model);
+
+    let commits = git::get_commits_between(&req.repo, &from_commit, &to_commit)
+        .await
+        .or_internal("Failed to list the commits in between")
+        .for_repo(&req.repo)?;
+    if commits.len() > release_notes::MAX_RELEASE_COMMITS {
+        return Err(AppError::bad_request(format!(
+            "{}..{} spans {} commits; release notes cover at most {}",
+            req.from_tag,
+            req.to_tag,
+            commits.len(),
+            release_notes::MAX_RELEASE_COMMITS
+        )));
+    }
+
+    let _job = status::track_job("release_notes", &req.repo, Some(&to_commit));
+    let changes = release_notes::changes(
+        &state,
+        &schematics,
+        chat.as_ref(),
+        &prompts,
+        model,
+        context_window,
+        &req.repo,
+        &commits,
+        |visibility| viewer.can_see(visibility.parse().unwrap_or_default()),
+    )
+    .await
+    .or_internal("Failed to collect the changes")
+    .for_repo(&req.repo)
+    .in_stage(Stage::Llm)?;
+
+    let (markdown, prompt_version, model) = if changes.is_empty() {
+        let markdown = format!("No hardware changes between {} and {}.", req.from_tag, req.to_tag);
+        (markdown, String::new(), String::new())
+    } else {
+        let (markdown, prompt_version) = release_notes::generate(
+            chat.as_ref(),
+            &prompts,
+            model,
+            context_window,
+            &req.repo,
+            &req.from_tag,
+            &req.to_tag,
+            &changes,
+        )
+        .await
+        .or_internal("Failed to write the release notes")
+        .for_repo(&req.repo)
+        .at_commit(&to_commit)
+        .in_stage(Stage::Llm)?;
+        (markdown, prompt_version, model.to_string())
+    };
+
+    Ok(Json(GrokReleaseNotesResponse {
+        repo: req.repo,
+        from_tag: req.from_tag,
+        to_tag: req.to_tag,
+        from_commit,
+        to_commit,
+        model,
+        prompt_version,
+        markdown,
+        changes,
+    }))
+}
+
 /// Find replacement parts for an obsolete component using Grok AI
 #[utoipa::path(
     post,
@@ -615,42 +1894,24 @@ pub async fn summarize_repo(
     request_body = GrokObsoleteReplacementRequest,
     responses(
         (status = 200, description = "AI-generated replacement recommendations", body = GrokObsoleteReplacementResponse),
+        (status = 400, description = "Model not on the allowlist", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
         (status = 500, description = "Internal server error", body = ApiError)
     ),
     tag = "grok"
 )]
 pub async fn find_replacement(
-    State(_state): State<AppState>,
+    State(config): State<Arc<Config>>,
+    State(chat): State<Arc<dyn ChatProvider>>,
     Json(req): Json<GrokObsoleteReplacementRequest>,
-) -> Result<Json<GrokObsoleteReplacementResponse>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<GrokObsoleteReplacementResponse>, AppError> {
+    let chat = sampled(chat, &config.sampling.replacement);
     info!(
         "Grok find_replacement called for obsolete part: {}",
         req.manufacturer_part_number
     );
 
-    // Load environment file to get XAI_API_KEY
-    load_environment_file(None).map_err(|e| {
-        error!("Failed to load environment file: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError::internal(format!(
-                "Failed to load environment: {}",
-                e
-            ))),
-        )
-    })?;
-
-    // Create XAI client
-    let xai_client = XaiClient::new().map_err(|e| {
-        error!("Failed to create XAI client: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError::internal(format!(
-                "Failed to initialize XAI client: {}",
-                e
-            ))),
-        )
-    })?;
+    let model = choose_model(&config.models, req.model.as_deref(), &config.models.replacement)?;
 
     // Build the context about the obsolete part
     let mut part_info = format!("Obsolete Part: {}\n", req.manufacturer_part_number);
@@ -713,24 +1974,15 @@ Format the response clearly with headers and bullet points."#,
     // Use web_search tool for comprehensive online research
     let tools = vec![Tool::web_search()];
 
-    // Create responses request with Grok model (must use grok-4 family for tools)
+    // Create responses request (the model must support tools)
     let responses_request =
-        ResponsesRequest::new("grok-4-1-fast-non-reasoning".to_string(), input, tools);
+        ResponsesRequest::new(model, input, tools);
 
     // Make API call using responses endpoint
-    let api_response = xai_client
+    let api_response = chat
         .responses(&responses_request)
         .await
-        .map_err(|e| {
-            error!("XAI API call failed: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::internal(format!(
-                    "Failed to get AI replacement suggestions: {}",
-                    e
-                ))),
-            )
-        })?;
+        .or_internal("Failed to get AI replacement suggestions")?;
 
     // Extract the analysis from the response
     let analysis = if let Some(output) = &api_response.output {
@@ -804,255 +2056,673 @@ Format the response clearly with headers and bullet points."#,
 }
 
 /// Stream an AI chat response using Server-Sent Events
+///
+/// Deprecated: answers a fixed question; use `POST /api/grok/chat/stream`.
+/// Responses carry a `Deprecation` header.
 #[utoipa::path(
     get,
     path = "/api/grok/chat/stream",
+    params(GrokChatStreamQuery),
     responses(
-        (status = 200, description = "Streaming AI chat response via SSE"),
+        (status = 200, description = "Streaming AI chat response via SSE", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Unknown persona or model not on the allowlist", body = ApiError),
+        (status = 401, description = "Summaries for this repository are private", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required)", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
         (status = 500, description = "Internal server error", body = ApiError)
     ),
     tag = "grok"
 )]
 pub async fn chat_stream(
-    State(_state): State<AppState>,
-) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<ApiError>)> {
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(chat): State<Arc<dyn ChatProvider>>,
+    State(prompts): State<Arc<PromptLibrary>>,
+    viewer: Viewer,
+    Query(query): Query<GrokChatStreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let chat = sampled(chat, &config.sampling.chat);
     info!("Grok chat_stream called");
 
-    // Load environment file to get XAI_API_KEY
-    load_environment_file(None).map_err(|e| {
-        error!("Failed to load environment file: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError::internal(format!(
-                "Failed to load environment: {}",
-                e
-            ))),
-        )
-    })?;
-
-    // Create XAI client
-    let xai_client = XaiClient::new().map_err(|e| {
-        error!("Failed to create XAI client: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError::internal(format!(
-                "Failed to initialize XAI client: {}",
-                e
-            ))),
-        )
-    })?;
+    if let Some(repo) = query.repo.as_deref() {
+        viewer.require_summary_access(&state, repo, None).await?;
+        registry::lookup(&state, &config, repo).await?;
+    }
+
+    let persona = resolve_persona(&state, query.repo.as_deref(), query.persona.as_deref()).await?;
+    let guidelines = match query.repo.as_deref() {
+        Some(repo) => guidelines::for_repo(&state, &config, repo).await,
+        None => None,
+    };
+    let model = choose_model(&config.models, query.model.as_deref(), &config.models.chat)?;
 
-    // TODO: Accept messages from request body. Currently using static prompts for testing.
-    // This endpoint should be converted to POST with a request body containing the user's
-    // selection context and question. For now, we use a hardcoded prompt to verify streaming works.
+    // Kept for old clients; the POST endpoint takes the conversation in the body
+    let system_prompt = prompts
+        .render(CHAT_SYSTEM, ())
+        .or_internal("Failed to render the chat system prompt")?;
     let messages = vec![
-        Message::system(
-            "You are Grok, an expert AI assistant specialized in electronics and PCB design. \
-            You help users understand KiCad schematics, components, and circuit design. \
-            Be concise but informative. Use technical terms when appropriate.".to_string()
-        ),
+        Message::system(system_prompt.text),
         Message::user(
             "Give me a brief overview of what to look for when reviewing a KiCad schematic for an embedded system.".to_string()
         ),
     ];
 
     // Create chat completion request with streaming
-    let chat_request =
-        ChatCompletionRequest::with_stream(messages, "grok-3-fast".to_string(), true);
+    let mut chat_request =
+        ChatCompletionRequest::with_stream(messages, model, true);
+    if let Some(persona) = persona {
+        persona.apply(&mut chat_request);
+    }
+    if let Some(guidelines) = &guidelines {
+        guidelines.apply(&mut chat_request);
+    }
 
-    // Get the stream
-    let stream = xai_client
-        .chat_completion_stream(&chat_request)
+    // Get the stream; it's cancelled if the client goes away
+    let cancel = CancellationToken::new();
+    let stream = chat
+        .chat_completion_stream(&chat_request, cancel.clone())
         .await
-        .map_err(|e| {
-            error!("Failed to create XAI stream: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::internal(format!(
-                    "Failed to start AI stream: {}",
-                    e
-                ))),
-            )
-        })?;
+        .or_internal("Failed to start AI stream")?;
 
     // Convert the stream to SSE events
     let sse_stream = async_stream::stream! {
+        let mut disconnect = CancelOnDisconnect::new(cancel, "/api/grok/chat/stream");
         tokio::pin!(stream);
 
         while let Some(result) = stream.next().await {
             match result {
-                Ok(content) => {
-                    yield Ok(Event::default().data(content));
+                Ok(event) => {
+                    if let Some(event) = sse_event(event, false) {
+                        yield Ok(event);
+                    }
+                }
+                Err(e) => {
+                    let e = anyhow::Error::from(e);
+                    error!("Stream error: {:#}", e);
+                    yield Ok(Event::default().data(format!("[ERROR: {:#}]", e)));
+                    break;
+                }
+            }
+        }
+        disconnect.finish();
+
+        // Send a done event
+        yield Ok(Event::default().data("[DONE]"));
+    };
+
+    Ok(Sse::new(shutdown::sse(sse_stream)).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .text("keep-alive"),
+    ))
+}
+
+/// Chat about a project, with relevant context retrieved for each question
+///
+/// With a `repo`, the latest user message is used to retrieve commit
+/// summaries, components and nets (see `top_k`), which go into the system
+/// prompt tagged S1, S2, ... for the model to cite. The first SSE event,
+/// `sources`, lists them; the answer follows as data chunks, then `finish`
+/// and `usage` events. Once it's complete, a `citations` event lists the
+/// components and nets (at `commit`, else the latest commit) and the
+/// commits it mentions, and an `attachments` event the cited components'
+/// symbols drawn as SVG; both are stored with the turn in a session. `[DONE]`
+/// ends the stream. With a `reasoning_effort`, the model's thinking comes
+/// first as `reasoning` events. The repo's and its org's design guidelines
+/// are added to the system prompt.
+#[utoipa::path(
+    post,
+    path = "/api/grok/chat/stream",
+    request_body = GrokChatRequest,
+    responses(
+        (status = 200, description = "`sources` event (ChatSourcesEvent), the streamed answer, then `citations` (ChatCitationsEvent) and `attachments` (ChatAttachmentsEvent) events", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Invalid conversation, unknown persona or model not on the allowlist", body = ApiError),
+        (status = 401, description = "Summaries for this repository are private", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required)", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "grok"
+)]
+pub async fn chat(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    State(chat): State<Arc<dyn ChatProvider>>,
+    State(prompts): State<Arc<PromptLibrary>>,
+    viewer: Viewer,
+    Json(mut req): Json<GrokChatRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let summarizer = sampled(chat.clone(), &config.sampling.summary);
+    let chat = sampled(chat, &config.sampling.chat);
+    info!("Grok chat called for {:?} with {} messages", req.repo, req.messages.len());
+
+    let question = match req.messages.last() {
+        Some(turn) if turn.role == ChatRole::User && !turn.content.trim().is_empty() => turn.content.clone(),
+        _ => return Err(AppError::bad_request("messages must end with a non-empty user message")),
+    };
+    let top_k = req.top_k.unwrap_or(DEFAULT_CHAT_TOP_K);
+    if top_k > MAX_TOP_K {
+        return Err(AppError::bad_request(format!("top_k must be at most {}", MAX_TOP_K)));
+    }
+    if let (Some(repo), Some(commit)) = (req.repo.as_deref(), req.commit.as_deref()) {
+        req.commit = Some(resolve_commit(repo, commit).await?);
+    }
+    if let Some(repo) = req.repo.as_deref() {
+        viewer.require_summary_access(&state, repo, req.commit.as_deref()).await?;
+        registry::lookup(&state, &config, repo).await?;
+    }
+
+    let session = match req.session_id {
+        Some(id) => {
+            let session = find_chat_session(&state, id).await?;
+            if session.repo_url != req.repo.as_deref().map(git::repo_url) {
+                let repo = session.repo_url.as_deref().and_then(git::repo_slug);
+                return Err(AppError::bad_request(format!(
+                    "Chat session {} is about {}; send the same repo",
+                    id,
+                    repo.as_deref().unwrap_or("no repository")
+                )));
+            }
+            Some(session)
+        }
+        None => None,
+    };
+
+    let persona = resolve_persona(&state, req.repo.as_deref(), req.persona.as_deref()).await?;
+    let guidelines = match req.repo.as_deref() {
+        Some(repo) => guidelines::for_repo(&state, &config, repo).await,
+        None => None,
+    };
+    let model = choose_model(&config.models, req.model.as_deref(), &config.models.chat)?;
+
+    let sources = match req.repo.as_deref() {
+        Some(repo) if top_k > 0 => {
+            let scope = retrieval::RetrievalScope {
+                repo,
+                commit: req.commit.as_deref(),
+                include_private: viewer.is_authenticated(),
+                embedding_model: &config.models.embedding,
+            };
+            retrieval::retrieve(&state, chat.as_ref(), &scope, &question, top_k).await
+        }
+        _ => Vec::new(),
+    };
+
+    let system_prompt = prompts
+        .render(
+            CHAT_SYSTEM,
+            serde_json::json!({ "repo": req.repo, "commit": req.commit, "sources": sources }),
+        )
+        .or_internal("Failed to render the chat system prompt")?;
+    let system = Message::system(system_prompt.text);
+    let turns: Vec<Message> = req
+        .messages
+        .iter()
+        .map(|turn| match turn.role {
+            ChatRole::User => Message::user(turn.content.clone()),
+            ChatRole::Assistant => Message::assistant(turn.content.clone()),
+        })
+        .collect();
+
+    // A session's stored history goes between the system prompt and the new
+    // turns, its older turns folded into a summary once they crowd the window
+    let mut messages = vec![system.clone()];
+    if let Some(session) = session {
+        let session_id = session.id;
+        let mut history = chat_history::History::load(&state, session).await.or_internal("Failed to load the chat session")?;
+        let request: Vec<Message> = std::iter::once(system).chain(turns.iter().cloned()).collect();
+        let window = config.models.context_window(&model);
+        if let Err(e) = history
+            .compress_if_needed(&state, summarizer.as_ref(), &prompts, &config.chat_history, &model, window, &request)
+            .await
+        {
+            warn!("Sending chat session {} unsummarized: {:#}", session_id, e);
+        }
+        messages.extend(history.messages());
+        let new_turns: Vec<_> = req
+            .messages
+            .into_iter()
+            .map(|turn| chat_history::new_turn(&model, turn.role.as_str(), turn.content))
+            .collect();
+        kicad_db::append_chat_turns(&state, session_id, &new_turns)
+            .await
+            .or_internal("Failed to store the chat turns")?;
+    }
+    messages.extend(turns);
+    let session_id = req.session_id;
+    let answer_model = model.clone();
+    let (repo, commit) = (req.repo, req.commit);
+
+    let mut chat_request = ChatCompletionRequest::with_stream(messages, model, true);
+    chat_request.reasoning_effort = req.reasoning_effort;
+    let reasoning = req.reasoning_effort.is_some();
+    if let Some(persona) = persona {
+        persona.apply(&mut chat_request);
+    }
+    if let Some(guidelines) = &guidelines {
+        guidelines.apply(&mut chat_request);
+    }
+
+    // Get the stream; it's cancelled if the client goes away
+    let cancel = CancellationToken::new();
+    let stream = chat
+        .chat_completion_stream(&chat_request, cancel.clone())
+        .await
+        .or_internal("Failed to start AI stream")?;
+
+    let sources_event = ChatSourcesEvent { sources };
+    let sse_stream = async_stream::stream! {
+        let mut disconnect = CancelOnDisconnect::new(cancel, "/api/grok/chat/stream");
+        match Event::default().event("sources").json_data(&sources_event) {
+            Ok(event) => yield Ok(event),
+            Err(e) => error!("Failed to encode sources event: {}", e),
+        }
+
+        let mut answer = String::new();
+        let mut failed = false;
+        tokio::pin!(stream);
+        while let Some(result) = stream.next().await {
+            match result {
+                Ok(event) => {
+                    if let Some(text) = event.text() {
+                        answer.push_str(text);
+                    }
+                    if let Some(event) = sse_event(event, reasoning) {
+                        yield Ok(event);
+                    }
                 }
                 Err(e) => {
-                    error!("Stream error: {}", e);
-                    yield Ok(Event::default().data(format!("[ERROR: {}]", e)));
+                    let e = anyhow::Error::from(e);
+                    error!("Stream error: {:#}", e);
+                    yield Ok(Event::default().data(format!("[ERROR: {:#}]", e)));
+                    failed = true;
                     break;
                 }
             }
         }
+        disconnect.finish();
+
+        if !failed && !answer.trim().is_empty() {
+            let (citations, attachments) =
+                answer_citations(&schematics, repo.as_deref(), commit.as_deref(), &answer, &sources_event.sources).await;
+            let citations_event = ChatCitationsEvent { citations: citations.iter().cloned().map(Into::into).collect() };
+            match Event::default().event("citations").json_data(&citations_event) {
+                Ok(event) => yield Ok(event),
+                Err(e) => error!("Failed to encode citations event: {}", e),
+            }
+            let attachments_event = ChatAttachmentsEvent { attachments: attachments.iter().cloned().map(Into::into).collect() };
+            match Event::default().event("attachments").json_data(&attachments_event) {
+                Ok(event) => yield Ok(event),
+                Err(e) => error!("Failed to encode attachments event: {}", e),
+            }
+
+            // The answer joins the session's history once it's complete
+            if let Some(session_id) = session_id {
+                let turn = kicad_db::NewChatTurn {
+                    citations,
+                    attachments,
+                    ..chat_history::new_turn(&answer_model, ChatRole::Assistant.as_str(), answer)
+                };
+                if let Err(e) = kicad_db::append_chat_turns(&state, session_id, &[turn]).await {
+                    error!("Failed to store the answer in chat session {}: {}", session_id, e);
+                }
+            }
+        }
 
         // Send a done event
         yield Ok(Event::default().data("[DONE]"));
     };
 
-    Ok(Sse::new(sse_stream).keep_alive(
+    Ok(Sse::new(shutdown::sse(sse_stream)).keep_alive(
         axum::response::sse::KeepAlive::new()
             .interval(Duration::from_secs(15))
             .text("keep-alive"),
     ))
 }
 
+/// What a chat answer cites, and the symbols of the cited components
+///
+/// Components and nets are looked up at `commit`, else the repo's latest
+/// commit; a repo that can't be read leaves only the commit citations.
+async fn answer_citations(
+    schematics: &Arc<SchematicCache>,
+    repo: Option<&str>,
+    commit: Option<&str>,
+    answer: &str,
+    sources: &[ChatSource],
+) -> (Vec<ChatCitation>, Vec<ChatAttachment>) {
+    let Some(repo) = repo else {
+        return (citations::cite(answer, None, &[], sources), Vec::new());
+    };
+    let commit = match commit {
+        Some(commit) => Some(commit.to_string()),
+        None => git::get_latest_commit(repo)
+            .await
+            .map_err(|e| warn!("Citing no components for {}: {:#}", repo, e))
+            .ok(),
+    };
+    let projects = match commit.as_deref() {
+        Some(commit) => distill::load_projects(schematics, repo, commit)
+            .await
+            .map_err(|e| warn!("Citing no components for {}@{}: {:#}", repo, commit, e))
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+    let citations = citations::cite(answer, commit.as_deref(), &projects, sources);
+    let attachments = citations::attach(&citations, &projects);
+    (citations, attachments)
+}
+
+/// Start a chat session, which keeps the conversation on the server
+///
+/// Chat requests with its `session_id` send only their new turns; the stored
+/// history goes before them. Once it nears the model's context window, its
+/// older turns are folded into a running summary (see `[chat_history]` in the
+/// config), and every turn stays available here.
+#[utoipa::path(
+    post,
+    path = "/api/grok/chat/sessions",
+    request_body = CreateChatSessionRequest,
+    responses(
+        (status = 200, description = "The new, empty session", body = ChatSessionResponse),
+        (status = 401, description = "Summaries for this repository are private", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required)", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "grok"
+)]
+pub async fn create_chat_session(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    viewer: Viewer,
+    Json(req): Json<CreateChatSessionRequest>,
+) -> Result<Json<ChatSessionResponse>, AppError> {
+    let repo_url = match req.repo.as_deref() {
+        Some(repo) => {
+            viewer.require_summary_access(&state, repo, None).await?;
+            registry::lookup(&state, &config, repo).await?;
+            Some(git::repo_url(repo))
+        }
+        None => None,
+    };
+    let session = kicad_db::create_chat_session(&state, repo_url.as_deref())
+        .await
+        .or_internal("Failed to create the chat session")?;
+    Ok(Json(chat_session_response(session, Vec::new())))
+}
+
+/// A chat session's turns, summarized ones included, and its running summary
+#[utoipa::path(
+    get,
+    path = "/api/grok/chat/sessions/{id}",
+    params(("id" = String, Path, description = "Session ID")),
+    responses(
+        (status = 200, description = "The session", body = ChatSessionResponse),
+        (status = 401, description = "Summaries for the session's repository are private", body = ApiError),
+        (status = 404, description = "No such session", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "grok"
+)]
+pub async fn get_chat_session(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ChatSessionResponse>, AppError> {
+    let session = find_chat_session(&state, id).await?;
+    if let Some(repo_url) = session.repo_url.as_deref() {
+        // As require_repo_access would for a request naming the repo
+        let owner = kicad_db::get_registered_repo(&state, repo_url)
+            .await
+            .or_internal("Failed to look up repository")?
+            .and_then(|r| r.org_id);
+        if !viewer.orgs().allows(owner) {
+            return Err(AppError::not_found(format!("Chat session {} not found", id)));
+        }
+        if let Some(repo) = git::repo_slug(repo_url) {
+            viewer.require_summary_access(&state, &repo, None).await?;
+        }
+    }
+    let turns = kicad_db::chat_turns(&state, id, 0).await.or_internal("Failed to load the chat session")?;
+    Ok(Json(chat_session_response(session, turns)))
+}
+
+async fn find_chat_session(pool: &PgPool, id: Uuid) -> Result<ChatSession, AppError> {
+    kicad_db::get_chat_session(pool, id)
+        .await
+        .or_internal("Failed to load the chat session")?
+        .ok_or_else(|| AppError::not_found(format!("Chat session {} not found", id)))
+}
+
+fn chat_session_response(session: ChatSession, turns: Vec<StoredChatTurn>) -> ChatSessionResponse {
+    let unsummarized = turns.iter().filter(|turn| turn.position > session.summarized_through);
+    let tokens = session.summary_tokens as usize + unsummarized.map(|turn| turn.tokens as usize).sum::<usize>();
+    ChatSessionResponse {
+        id: session.id,
+        repo: session.repo_url.as_deref().and_then(git::repo_slug),
+        summary: session.summary,
+        summarized_through: session.summarized_through,
+        tokens,
+        turns: turns
+            .into_iter()
+            .map(|turn| ChatSessionTurn {
+                position: turn.position,
+                role: if turn.role == "assistant" { ChatRole::Assistant } else { ChatRole::User },
+                content: turn.content,
+                tokens: turn.tokens,
+                citations: turn.citations.into_iter().map(Into::into).collect(),
+                attachments: turn.attachments.into_iter().map(Into::into).collect(),
+                created_at: turn.created_at,
+            })
+            .collect(),
+        created_at: session.created_at,
+        updated_at: session.updated_at,
+    }
+}
+
 /// Stream an AI analysis of selected components using Server-Sent Events
+///
+/// The selection is `component_ids` or a `selection` of components, nets, a
+/// hierarchical sheet or an area of a sheet; nets, sheets and areas are
+/// described as a whole (what a net connects, which nets leave a sheet or
+/// area) as well as by their components.
+/// The analysis arrives as data chunks, then `finish` and `usage` events and `[DONE]`.
+/// In thinking mode the model's thinking comes first, as `reasoning` events.
 #[utoipa::path(
     post,
     path = "/api/grok/selection/stream",
     request_body = GrokSelectionStreamRequest,
     responses(
-        (status = 200, description = "Streaming AI analysis response via SSE"),
+        (status = 200, description = "Streaming AI analysis response via SSE", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Unknown persona, model not on the allowlist, unreadable snapshot, or a question too long for the model's context window", body = ApiError),
+        (status = 401, description = "Summaries for this repository are private", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required), or the selected nets, sheet or area aren't in the schematic", body = ApiError),
+        (status = 422, description = "Invalid fields, each listed in violations", body = ApiError),
+        (status = 429, description = "Per-client rate limit exceeded", body = ApiError),
         (status = 500, description = "Internal server error", body = ApiError)
     ),
     tag = "grok"
 )]
 pub async fn selection_stream(
-    State(state): State<AppState>,
-    Json(req): Json<GrokSelectionStreamRequest>,
-) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<ApiError>)> {
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(chat): State<Arc<dyn ChatProvider>>,
+    State(prompts): State<Arc<PromptLibrary>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    viewer: Viewer,
+    Valid(mut req): Valid<GrokSelectionStreamRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let chat = sampled(chat, &config.sampling.selection);
+    req.commit = resolve_commit(&req.repo, &req.commit).await?;
+    let requested = selection::requested(&req.component_ids, req.selection.as_ref());
     info!(
-        "Grok selection_stream called for {}/{} with {} components",
+        "Grok selection_stream called for {}/{} with {}",
         req.repo,
         req.commit,
-        req.component_ids.len()
+        selection::describe(requested.as_ref())
     );
 
-    // Load environment file to get XAI_API_KEY
-    load_environment_file(None).map_err(|e| {
-        error!("Failed to load environment file: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError::internal(format!(
-                "Failed to load environment: {}",
-                e
-            ))),
-        )
-    })?;
-
-    // Create XAI client
-    let xai_client = XaiClient::new().map_err(|e| {
-        error!("Failed to create XAI client: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError::internal(format!(
-                "Failed to initialize XAI client: {}",
-                e
-            ))),
-        )
-    })?;
+    viewer.require_summary_access(&state, &req.repo, Some(&req.commit)).await?;
+    registry::lookup(&state, &config, &req.repo).await?;
+
+    let persona = resolve_persona(&state, Some(&req.repo), req.persona.as_deref()).await?;
+    let model = choose_model(&config.models, req.model.as_deref(), &config.models.selection)?;
 
     // Get distilled schematic data - either from request or fetch it
-    let distilled = if let Some(d) = req.distilled {
-        d
+    let distilled = match req.distilled {
+        Some(d) => d,
+        None => load_distilled(&state, &schematics, &req.repo, &req.commit).await?,
+    };
+
+    // Build rich semantic context from distilled data; nets, sheets and
+    // areas are described as a whole, then by their components
+    let resolved = resolve_selection(&distilled, requested.as_ref(), &req.repo, &req.commit)?;
+    let (mut selected_context, schematic_summary) = build_component_context(&distilled, &resolved.references);
+    if !resolved.context.is_empty() {
+        selected_context = format!("{}\n\n{}", resolved.context, selected_context);
+    }
+
+    // Add supplier data (datasheet, lifecycle, pricing) for selected parts with a known MPN
+    let mut mpns: Vec<String> = kicad_db::components_from_distilled(&distilled)
+        .into_iter()
+        .filter(|c| resolved.references.contains(&c.reference))
+        .filter_map(|c| c.mpn)
+        .collect();
+    mpns.sort();
+    mpns.dedup();
+    let part_metadata = enrichment::lookup_many(&state, &mpns).await;
+    let supplier_data = if part_metadata.is_empty() {
+        String::new()
     } else {
-        // Fetch distilled data from cache or generate it
-        let repo_url = format!("https://github.com/{}.git", req.repo);
-        match kicad_db::retrieve_distilled_json(&state, &repo_url, &req.commit).await {
-            Ok(Some(cached)) => cached,
-            _ => {
-                // Generate if not cached
-                distill::distill_repo_schematics(&req.repo, &req.commit)
-                    .await
-                    .map_err(|e| {
-                        error!("Failed to distill schematic: {}", e);
-                        (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(ApiError::internal(format!(
-                                "Failed to distill schematic: {}",
-                                e
-                            ))),
-                        )
-                    })?
-            }
-        }
+        let lines: Vec<String> = part_metadata.iter().map(|m| format!("- {}", m.prompt_line())).collect();
+        format!("\n\n## Supplier Data\n{}", lines.join("\n"))
     };
 
-    // Build rich semantic context from distilled data
-    let (selected_context, schematic_summary) = build_component_context(&distilled, &req.component_ids);
+    // A snapshot of the region lets a vision model see the circuit, not just its netlist
+    let snapshot = match req.snapshot.as_deref() {
+        Some(data) => Some(ImageUrl::from_base64(data).ok_or_else(|| {
+            AppError::bad_request("snapshot must be a base64 PNG or JPEG of at most 20 MB")
+        })?),
+        None if req.attach_schematic_image => schematic_snapshot(&state, &req.repo, &req.commit).await,
+        None => None,
+    };
 
-    // Load the system prompt from file
-    let base_system_prompt = load_system_prompt();
+    // The schematic summary goes in the system prompt; all three context
+    // sections are cut down if they don't fit the model's window
+    let bare_system_prompt = prompts
+        .render(SELECTION_SUMMARY, serde_json::json!({ "schematic_context": "" }))
+        .or_internal("Failed to render the selection system prompt")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)?
+        .text;
+    let mut budget = config.models.prompt_budget(&model).fixed(&bare_system_prompt).fixed(&req.query);
+    if let Some(persona) = persona {
+        budget = budget.reserve_output(persona.max_tokens).fixed(&persona.system_block());
+    }
+    if snapshot.is_some() {
+        budget = budget.fixed_tokens(tokens::IMAGE_TOKENS);
+    }
+    let fitted = budget
+        .section("selection", selected_context)
+        .section("supplier_data", supplier_data)
+        .section("schematic", schematic_summary)
+        .fit();
+    if !fitted.fits {
+        return Err(AppError::bad_request(format!(
+            "The question is too long for {}'s context window",
+            model
+        )));
+    }
+    if !fitted.truncated.is_empty() {
+        warn!(
+            "Shortened {} in the selection prompt for {}@{} to fit {}'s context window",
+            fitted.truncated.join(", "),
+            req.repo,
+            req.commit,
+            model
+        );
+    }
 
-    // Build system and user messages with the loaded system prompt
-    let system_prompt = format!(
-        "{}\n\n---\n\n## Schematic Context\n{}",
-        base_system_prompt,
-        schematic_summary
-    );
+    let system_prompt = prompts
+        .render(
+            SELECTION_SUMMARY,
+            serde_json::json!({ "schematic_context": fitted.text("schematic") }),
+        )
+        .or_internal("Failed to render the selection system prompt")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)?
+        .text;
 
     let user_prompt = format!(
-        "{}\n\n---\n\n## User's Question\n{}",
-        selected_context,
+        "{}{}\n\n---\n\n## User's Question\n{}",
+        fitted.text("selection"),
+        fitted.text("supplier_data"),
         req.query
     );
 
     info!(
-        "Using system prompt ({} chars), context ({} chars), thinking_mode: {}",
+        "Using system prompt ({} chars), context ({} chars), about {} prompt tokens, thinking_mode: {}",
         system_prompt.len(),
         user_prompt.len(),
+        fitted.prompt_tokens,
         req.thinking_mode
     );
 
-    let messages = vec![Message::system(system_prompt), Message::user(user_prompt)];
+    let user_message = match snapshot {
+        Some(image) => {
+            info!("Attaching a schematic snapshot to the selection prompt");
+            Message::user_with_image(user_prompt, image)
+        }
+        None => Message::user(user_prompt),
+    };
+    let messages = vec![Message::system(system_prompt), user_message];
 
     // Create chat completion request with streaming
-    // Use grok-4-1-fast model, with optional reasoning/thinking mode
-    let chat_request = if req.thinking_mode {
-        ChatCompletionRequest::with_reasoning(
-            messages,
-            "grok-4-1-fast".to_string(),
-            true,
-            ReasoningEffort::Low,
-        )
-    } else {
-        ChatCompletionRequest::with_stream(messages, "grok-4-1-fast".to_string(), true)
+    // Use the selection model, with optional reasoning/thinking mode
+    let effort = req.reasoning_effort.or(req.thinking_mode.then_some(ReasoningEffort::Low));
+    let mut chat_request = match effort {
+        Some(effort) => ChatCompletionRequest::with_reasoning(messages, model, true, effort),
+        None => ChatCompletionRequest::with_stream(messages, model, true),
     };
+    if let Some(persona) = persona {
+        persona.apply(&mut chat_request);
+    }
 
-    // Get the stream
-    let stream = xai_client
-        .chat_completion_stream(&chat_request)
+    // Get the stream; it's cancelled if the client goes away
+    let cancel = CancellationToken::new();
+    let stream = chat
+        .chat_completion_stream(&chat_request, cancel.clone())
         .await
-        .map_err(|e| {
-            error!("Failed to create XAI stream: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::internal(format!(
-                    "Failed to start AI stream: {}",
-                    e
-                ))),
-            )
-        })?;
+        .or_internal("Failed to start AI stream")?;
 
     // Convert the stream to SSE events
     let sse_stream = async_stream::stream! {
+        let mut disconnect = CancelOnDisconnect::new(cancel, "/api/grok/selection/stream");
         tokio::pin!(stream);
 
         while let Some(result) = stream.next().await {
             match result {
-                Ok(content) => {
-                    yield Ok(Event::default().data(content));
+                Ok(event) => {
+                    if let Some(event) = sse_event(event, effort.is_some()) {
+                        yield Ok(event);
+                    }
                 }
                 Err(e) => {
-                    error!("Stream error: {}", e);
-                    yield Ok(Event::default().data(format!("[ERROR: {}]", e)));
+                    let e = anyhow::Error::from(e);
+                    error!("Stream error: {:#}", e);
+                    yield Ok(Event::default().data(format!("[ERROR: {:#}]", e)));
                     break;
                 }
             }
         }
+        disconnect.finish();
 
         // Send a done event
         yield Ok(Event::default().data("[DONE]"));
     };
 
-    Ok(Sse::new(sse_stream).keep_alive(
+    Ok(Sse::new(shutdown::sse(sse_stream)).keep_alive(
         axum::response::sse::KeepAlive::new()
             .interval(Duration::from_secs(15))
             .text("keep-alive"),
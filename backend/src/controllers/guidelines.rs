@@ -0,0 +1,340 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use kicad_db::{
+    create_design_guideline, delete_design_guideline, get_registered_repo, list_design_guidelines,
+    update_design_guideline, GuidelinePrompt, GuidelineScope, NewDesignGuideline, PgPool,
+};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::auth::Viewer;
+use crate::config::Config;
+use crate::controllers::{orgs::managed_org, repos::registered_repo_url};
+use crate::error::{AppError, AppResultExt, ResultExt};
+use crate::types::{
+    DeleteGuidelineResponse, DesignGuidelineItem, DesignGuidelineRequest, OrgGuidelinesResponse,
+    RepoGuidelinesResponse,
+};
+use crate::validation::Valid;
+
+impl From<DesignGuidelineRequest> for NewDesignGuideline {
+    fn from(req: DesignGuidelineRequest) -> Self {
+        Self {
+            title: req.title.trim().to_string(),
+            body: req.body.trim().to_string(),
+            priority: req.priority.unwrap_or(0),
+        }
+    }
+}
+
+async fn org_guidelines_response(pool: &PgPool, slug: String, org_id: i32) -> Result<Json<OrgGuidelinesResponse>, AppError> {
+    let guidelines = list_design_guidelines(pool, &GuidelineScope::Org(org_id))
+        .await
+        .or_internal("Failed to list design guidelines")?;
+    Ok(Json(OrgGuidelinesResponse {
+        org: slug,
+        guidelines: guidelines.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// List an org's design guidelines
+///
+/// They apply to every repo registered to the org. Requires an admin key of
+/// the org, or an instance-wide one.
+#[utoipa::path(
+    get,
+    path = "/api/orgs/{org}/guidelines",
+    params(
+        ("org" = String, Path, description = "Org slug")
+    ),
+    responses(
+        (status = 200, description = "The org's guidelines", body = OrgGuidelinesResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "No such org, or it isn't the caller's", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "orgs"
+)]
+pub async fn org_guidelines(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Path(slug): Path<String>,
+) -> Result<Json<OrgGuidelinesResponse>, AppError> {
+    let org = managed_org(&state, &viewer, &slug).await?;
+    org_guidelines_response(&state, org.slug, org.id).await
+}
+
+/// Add a design guideline to an org
+///
+/// A house rule ("we always use 0402 passives") added to the system prompt
+/// of commit summaries, repo overviews and chat about the org's repos; a
+/// repo's own guidelines take precedence. Requires an admin key of the org,
+/// or an instance-wide one.
+#[utoipa::path(
+    post,
+    path = "/api/orgs/{org}/guidelines",
+    params(
+        ("org" = String, Path, description = "Org slug")
+    ),
+    request_body = DesignGuidelineRequest,
+    responses(
+        (status = 200, description = "The guideline", body = DesignGuidelineItem),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "No such org, or it isn't the caller's", body = ApiError),
+        (status = 422, description = "Empty or overly long title or text", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "orgs"
+)]
+pub async fn create_org_guideline(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Path(slug): Path<String>,
+    Valid(req): Valid<DesignGuidelineRequest>,
+) -> Result<Json<DesignGuidelineItem>, AppError> {
+    let org = managed_org(&state, &viewer, &slug).await?;
+    let guideline = create_design_guideline(&state, &GuidelineScope::Org(org.id), &req.into())
+        .await
+        .or_internal("Failed to store design guideline")?;
+    info!("Added design guideline {} to org {}", guideline.id, org.slug);
+    Ok(Json(guideline.into()))
+}
+
+/// Replace one of an org's design guidelines
+///
+/// Requires an admin key of the org, or an instance-wide one.
+#[utoipa::path(
+    put,
+    path = "/api/orgs/{org}/guidelines/{id}",
+    params(
+        ("org" = String, Path, description = "Org slug"),
+        ("id" = i64, Path, description = "Guideline ID")
+    ),
+    request_body = DesignGuidelineRequest,
+    responses(
+        (status = 200, description = "The guideline", body = DesignGuidelineItem),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "No such org or guideline", body = ApiError),
+        (status = 422, description = "Empty or overly long title or text", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "orgs"
+)]
+pub async fn update_org_guideline(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Path((slug, id)): Path<(String, i64)>,
+    Valid(req): Valid<DesignGuidelineRequest>,
+) -> Result<Json<DesignGuidelineItem>, AppError> {
+    let org = managed_org(&state, &viewer, &slug).await?;
+    let guideline = update_design_guideline(&state, &GuidelineScope::Org(org.id), id, &req.into())
+        .await
+        .or_internal("Failed to store design guideline")?
+        .ok_or_else(|| AppError::not_found(format!("Org '{}' has no guideline {}", org.slug, id)))?;
+    info!("Updated design guideline {} of org {}", id, org.slug);
+    Ok(Json(guideline.into()))
+}
+
+/// Delete one of an org's design guidelines
+///
+/// Requires an admin key of the org, or an instance-wide one.
+#[utoipa::path(
+    delete,
+    path = "/api/orgs/{org}/guidelines/{id}",
+    params(
+        ("org" = String, Path, description = "Org slug"),
+        ("id" = i64, Path, description = "Guideline ID")
+    ),
+    responses(
+        (status = 200, description = "Guideline deleted", body = DeleteGuidelineResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "No such org or guideline", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "orgs"
+)]
+pub async fn delete_org_guideline(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Path((slug, id)): Path<(String, i64)>,
+) -> Result<Json<DeleteGuidelineResponse>, AppError> {
+    let org = managed_org(&state, &viewer, &slug).await?;
+    let deleted = delete_design_guideline(&state, &GuidelineScope::Org(org.id), id)
+        .await
+        .or_internal("Failed to delete design guideline")?;
+    if !deleted {
+        return Err(AppError::not_found(format!("Org '{}' has no guideline {}", org.slug, id)));
+    }
+    info!("Deleted design guideline {} of org {}", id, org.slug);
+    Ok(Json(DeleteGuidelineResponse { id, deleted }))
+}
+
+/// List a registered repository's design guidelines
+///
+/// Its own and those of the org it is registered to, and the block they add
+/// to the repo's summary and chat system prompts: its own first, then its
+/// org's, the higher priority first within each, cut off at
+/// `guidelines_max_chars`. Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/guidelines",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    responses(
+        (status = 200, description = "The repo's guidelines", body = RepoGuidelinesResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "Repository is not registered", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn repo_guidelines(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    Path(repo): Path<String>,
+) -> Result<Json<RepoGuidelinesResponse>, AppError> {
+    let repo_url = registered_repo_url(&state, &repo).await?;
+    let own = list_design_guidelines(&state, &GuidelineScope::Repo(repo_url.clone()))
+        .await
+        .or_internal("Failed to list design guidelines")
+        .for_repo(&repo)?;
+    let org_id = get_registered_repo(&state, &repo_url)
+        .await
+        .or_internal("Failed to look up repository")
+        .for_repo(&repo)?
+        .and_then(|r| r.org_id);
+    let org = match org_id {
+        Some(org_id) => list_design_guidelines(&state, &GuidelineScope::Org(org_id))
+            .await
+            .or_internal("Failed to list design guidelines")
+            .for_repo(&repo)?,
+        None => Vec::new(),
+    };
+
+    let all: Vec<_> = own.iter().chain(&org).cloned().collect();
+    let prompt = match config.guidelines_max_chars {
+        0 => None,
+        max_chars => GuidelinePrompt::build(&all, max_chars),
+    };
+    Ok(Json(RepoGuidelinesResponse {
+        repo,
+        omitted: prompt.as_ref().map_or(all.len(), |p| p.omitted),
+        prompt: prompt.map(|p| p.text),
+        guidelines: own.into_iter().map(Into::into).collect(),
+        org_guidelines: org.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Add a design guideline to a registered repository
+///
+/// Repo guidelines come before those of its org in the system prompt, and
+/// win where they conflict. Requires an admin key.
+#[utoipa::path(
+    post,
+    path = "/api/repos/{repo}/guidelines",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    request_body = DesignGuidelineRequest,
+    responses(
+        (status = 200, description = "The guideline", body = DesignGuidelineItem),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "Repository is not registered", body = ApiError),
+        (status = 422, description = "Empty or overly long title or text", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn create_repo_guideline(
+    State(state): State<Arc<PgPool>>,
+    Path(repo): Path<String>,
+    Valid(req): Valid<DesignGuidelineRequest>,
+) -> Result<Json<DesignGuidelineItem>, AppError> {
+    let repo_url = registered_repo_url(&state, &repo).await?;
+    let guideline = create_design_guideline(&state, &GuidelineScope::Repo(repo_url), &req.into())
+        .await
+        .or_internal("Failed to store design guideline")
+        .for_repo(&repo)?;
+    info!("Added design guideline {} to {}", guideline.id, repo);
+    Ok(Json(guideline.into()))
+}
+
+/// Replace one of a registered repository's design guidelines
+///
+/// Requires an admin key.
+#[utoipa::path(
+    put,
+    path = "/api/repos/{repo}/guidelines/{id}",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("id" = i64, Path, description = "Guideline ID")
+    ),
+    request_body = DesignGuidelineRequest,
+    responses(
+        (status = 200, description = "The guideline", body = DesignGuidelineItem),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "Repository is not registered, or has no such guideline", body = ApiError),
+        (status = 422, description = "Empty or overly long title or text", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn update_repo_guideline(
+    State(state): State<Arc<PgPool>>,
+    Path((repo, id)): Path<(String, i64)>,
+    Valid(req): Valid<DesignGuidelineRequest>,
+) -> Result<Json<DesignGuidelineItem>, AppError> {
+    let repo_url = registered_repo_url(&state, &repo).await?;
+    let guideline = update_design_guideline(&state, &GuidelineScope::Repo(repo_url), id, &req.into())
+        .await
+        .or_internal("Failed to store design guideline")
+        .for_repo(&repo)?
+        .ok_or_else(|| AppError::not_found(format!("{} has no guideline {}", repo, id)))?;
+    info!("Updated design guideline {} of {}", id, repo);
+    Ok(Json(guideline.into()))
+}
+
+/// Delete one of a registered repository's design guidelines
+///
+/// Requires an admin key.
+#[utoipa::path(
+    delete,
+    path = "/api/repos/{repo}/guidelines/{id}",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("id" = i64, Path, description = "Guideline ID")
+    ),
+    responses(
+        (status = 200, description = "Guideline deleted", body = DeleteGuidelineResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "Repository is not registered, or has no such guideline", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn delete_repo_guideline(
+    State(state): State<Arc<PgPool>>,
+    Path((repo, id)): Path<(String, i64)>,
+) -> Result<Json<DeleteGuidelineResponse>, AppError> {
+    let repo_url = registered_repo_url(&state, &repo).await?;
+    let deleted = delete_design_guideline(&state, &GuidelineScope::Repo(repo_url), id)
+        .await
+        .or_internal("Failed to delete design guideline")
+        .for_repo(&repo)?;
+    if !deleted {
+        return Err(AppError::not_found(format!("{} has no guideline {}", repo, id)));
+    }
+    info!("Deleted design guideline {} of {}", id, repo);
+    Ok(Json(DeleteGuidelineResponse { id, deleted }))
+}
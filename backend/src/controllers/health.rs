@@ -0,0 +1,61 @@
+use axum::{extract::State, http::StatusCode, response::Json};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::config::Config;
+use crate::services::{health, status};
+use crate::types::{LivenessResponse, ReadinessResponse};
+use kicad_db::SchematicStore;
+
+/// Liveness probe
+///
+/// Answers as long as the process can serve requests; checks no dependencies,
+/// so a database outage doesn't get the container restarted.
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    responses(
+        (status = 200, description = "Process is alive", body = LivenessResponse)
+    ),
+    tag = "health"
+)]
+pub async fn healthz() -> Json<LivenessResponse> {
+    Json(LivenessResponse {
+        status: "ok".to_string(),
+        uptime_secs: status::uptime_secs(),
+    })
+}
+
+/// Readiness probe
+///
+/// Checks the database, free disk space for the git cache and that the
+/// XAI API accepts our key (a model listing, cached for a minute). Returns 503
+/// with the same body when any check fails.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    responses(
+        (status = 200, description = "All dependencies are available", body = ReadinessResponse),
+        (status = 503, description = "At least one dependency check failed", body = ReadinessResponse)
+    ),
+    tag = "health"
+)]
+pub async fn readyz(
+    State(store): State<Arc<dyn SchematicStore>>,
+    State(config): State<Arc<Config>>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let checks = health::check_all(store.as_ref(), &config).await;
+    let ready = checks.iter().all(|check| check.ok);
+    if !ready {
+        for check in checks.iter().filter(|check| !check.ok) {
+            warn!("Readiness check {} failed: {}", check.name, check.detail);
+        }
+    }
+
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(ReadinessResponse { ready, checks }))
+}
@@ -1,18 +1,43 @@
 use axum::{
+    body::Bytes,
     extract::{Path, State},
-    http::StatusCode,
+    http::HeaderMap,
     response::Json,
 };
+use anyhow::Context;
+use hmac::{Hmac, Mac};
 use serde::Deserialize;
-use std::collections::HashMap;
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Instant;
+use tokio_stream::StreamExt;
 use tracing::{error, info, warn};
+use uuid::Uuid;
 
-use crate::services::git;
-use crate::types::{ApiError, HookUpdateResponse};
-use kicad_db::{retrieve_schematic, store_schematic, PgPool};
-
-pub type AppState = Arc<PgPool>;
+use crate::auth::{constant_time_eq, Viewer};
+use crate::config::Config;
+use crate::controllers::grok::backfill_settings;
+use crate::error::{self, AppError, AppResultExt, ResultExt};
+use crate::redact;
+use crate::shutdown;
+use crate::state::AppState;
+use crate::services::{
+    anomalies, backfill, board, changelog, distill,
+    git::{self, LogFilter},
+    github, metrics, registry, response_cache::ResponseCache,
+    schematic_cache::SchematicCache, status,
+    timing::{Stage, StageTimer},
+};
+use crate::types::{AnalysisTimeline, CommitInfo, CommitTimeline, HookUpdateResponse, WorkClass};
+use crate::validation;
+use kicad_db::{
+    commits_with_overviews, forget_webhook_delivery, notify_commit_event, notify_commit_event_in, processed_up_to,
+    record_processing_error, record_processing_error_in, record_processing_started, record_webhook_delivery,
+    set_processed_up_to, store_analysis_timing_in, store_commit_metadata_in, ApiScope, CommitEvent, CommitEventKind, CommitMetadata, PgPool, RegisteredRepo,
+    SchematicStats, SchematicText, StoredDelivery, UpdateSchematic, WebhookDelivery,
+};
 
 /// GitHub webhook push event payload (simplified)
 #[derive(Debug, Deserialize)]
@@ -34,8 +59,321 @@ pub struct GitHubCommit {
     pub message: Option<String>,
 }
 
+/// GitLab webhook push event payload (simplified)
+#[derive(Debug, Deserialize)]
+pub struct GitLabPushEvent {
+    pub object_kind: Option<String>,
+    #[serde(rename = "ref")]
+    pub git_ref: Option<String>,
+    pub project: Option<GitLabProject>,
+    pub commits: Option<Vec<GitHubCommit>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitLabProject {
+    pub path_with_namespace: Option<String>,
+    pub git_http_url: Option<String>,
+}
+
+/// Bitbucket Cloud webhook push event payload (simplified)
+#[derive(Debug, Deserialize)]
+pub struct BitbucketPushEvent {
+    pub repository: Option<BitbucketRepository>,
+    pub push: Option<BitbucketPush>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BitbucketRepository {
+    pub full_name: Option<String>,
+    pub links: Option<BitbucketLinks>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BitbucketLinks {
+    pub html: Option<BitbucketLink>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BitbucketLink {
+    pub href: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BitbucketPush {
+    #[serde(default)]
+    pub changes: Vec<BitbucketChange>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BitbucketChange {
+    pub new: Option<BitbucketRef>,
+    #[serde(default)]
+    pub commits: Vec<BitbucketCommit>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BitbucketRef {
+    pub name: Option<String>,
+    /// "branch" or "tag"
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BitbucketCommit {
+    pub hash: Option<String>,
+    pub message: Option<String>,
+}
+
+/// Turn a clone/web URL into a host-qualified repo slug
+/// e.g. "https://gitlab.com/group/project.git" -> "gitlab.com/group/project"
+fn slug_from_url(url: &str) -> Option<String> {
+    let without_scheme = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let without_user = match without_scheme.split_once('@') {
+        Some((user, rest)) if !user.contains('/') => rest,
+        _ => without_scheme,
+    };
+    let slug = without_user.trim_end_matches('/').trim_end_matches(".git");
+    if slug.contains('/') {
+        Some(slug.to_string())
+    } else {
+        None
+    }
+}
+
+/// The repo a GitLab or Bitbucket webhook URL names: its `path` when that
+/// starts with a hostname, e.g. "gitlab.example.com/group/project", else
+/// `path` on `default_host`
+fn webhook_slug(path: &str, default_host: &str) -> String {
+    let path = path.trim_matches('/');
+    match path.split_once('/') {
+        Some((host, _)) if host.contains('.') => path.to_string(),
+        _ => format!("{}/{}", default_host, path),
+    }
+}
+
+/// Refuse a payload about another project than `repo`
+///
+/// The repo, and so the host it's cloned from, comes from the webhook URL
+/// and its registration; the payload only has to agree. `url` is the
+/// project URL the payload gives, else `path` its path without a host. One
+/// naming neither is taken to be about `repo`.
+fn check_payload_repo(
+    repo: &str,
+    registered: Option<&RegisteredRepo>,
+    url: Option<&str>,
+    path: Option<&str>,
+) -> Result<(), AppError> {
+    let expected: Vec<String> = std::iter::once(repo.to_string())
+        .chain(registered.and_then(|r| slug_from_url(&r.clone_url)))
+        .collect();
+    let (named, matches) = if let Some(named) = url.and_then(slug_from_url) {
+        let matches = expected.iter().any(|slug| slug.eq_ignore_ascii_case(&named));
+        (named, matches)
+    } else if let Some(path) = path {
+        let path = path.trim_matches('/');
+        let without_host = |slug: &str| slug.split_once('/').map_or(slug, |(_, rest)| rest).to_string();
+        let matches = expected.iter().any(|slug| without_host(slug).eq_ignore_ascii_case(path));
+        (path.to_string(), matches)
+    } else {
+        return Ok(());
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(AppError::bad_request(format!("Webhook payload is for {}, not {}", named, repo)))
+    }
+}
+
+/// Verify GitLab's X-Gitlab-Token header against the configured secret
+fn verify_gitlab_token(
+    headers: &HeaderMap,
+    secret: Option<&str>,
+    viewer: &Viewer,
+) -> Result<(), AppError> {
+    let Some(secret) = secret else {
+        return viewer.require_scope(ApiScope::Hook);
+    };
+
+    let token = headers
+        .get("x-gitlab-token")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    if constant_time_eq(token.as_bytes(), secret.as_bytes()) {
+        Ok(())
+    } else {
+        Err(AppError::unauthorized("Invalid X-Gitlab-Token"))
+    }
+}
+
+/// Verify a `sha256=<hex>` HMAC-SHA256 signature of the raw body in `header`
+///
+/// Without a configured secret the delivery needs a hook-scoped API key instead.
+fn verify_hmac_signature(
+    headers: &HeaderMap,
+    header: &str,
+    secret: Option<&str>,
+    body: &[u8],
+    viewer: &Viewer,
+) -> Result<(), AppError> {
+    let Some(secret) = secret else {
+        return viewer.require_scope(ApiScope::Hook);
+    };
+
+    let signature = headers
+        .get(header)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("sha256="))
+        .and_then(|v| hex::decode(v).ok())
+        .ok_or_else(|| AppError::unauthorized(format!("Missing or malformed {}", header)))?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.verify_slice(&signature)
+        .map_err(|_| AppError::unauthorized(format!("Invalid {}", header)))
+}
+
+/// The secret a delivery is checked against: the repo's own, else the provider's
+fn webhook_secret<'a>(
+    registered: Option<&'a RegisteredRepo>,
+    provider_secret: &'a Option<String>,
+) -> Option<&'a str> {
+    registered
+        .and_then(|r| r.webhook_secret.as_deref())
+        .or(provider_secret.as_deref())
+}
+
+fn parse_payload<T: for<'de> Deserialize<'de>>(
+    body: &[u8],
+) -> Result<T, AppError> {
+    serde_json::from_slice(body)
+        .map_err(|e| AppError::bad_request("Invalid webhook payload").with_source(e))
+}
+
+/// A delivery as received, remembered for deduplication and replay
+struct Delivery<'a> {
+    /// The provider's delivery ID (X-GitHub-Delivery, X-Gitlab-Event-UUID or
+    /// Bitbucket's X-Request-UUID), if it sends one; otherwise one is generated
+    id: Option<&'a str>,
+    event: Option<&'a str>,
+    body: &'a [u8],
+}
+
+/// Log the pushed commits, invalidate the cached clone and process the repo
+///
+/// A push only to `refs` the repo's processing policy leaves out is
+/// acknowledged without processing. A delivery is recorded with its redacted
+/// payload so it can be replayed. One whose ID was seen before is a
+/// redelivery and is acknowledged without processing; one that fails is
+/// forgotten, so its redelivery is processed. Replays pass no delivery.
+/// With AI summaries on, new commits are summarized in the background.
+async fn handle_push(
+    app: &AppState,
+    provider: &'static str,
+    repo: String,
+    registered: Option<&RegisteredRepo>,
+    delivery: Option<Delivery<'_>>,
+    refs: &[String],
+    commits: &[(Option<String>, Option<String>)],
+) -> Result<Json<HookUpdateResponse>, AppError> {
+    let policy = registered.map(|r| r.processing.clone()).unwrap_or_default();
+    if !refs.is_empty() && !refs.iter().any(|r| registry::processes_ref(&policy, r)) {
+        info!("Ignoring push to {} of {}: its processing policy leaves it out", refs.join(", "), repo);
+        metrics::record_webhook(provider, "ignored");
+        return Ok(Json(HookUpdateResponse {
+            repo,
+            processed: 0,
+            errors: Vec::new(),
+            timings: Vec::new(),
+            duplicate: false,
+            since_commit: None,
+        }));
+    }
+
+    let state = &app.pool;
+    let delivery_id = delivery
+        .as_ref()
+        .map(|d| d.id.map_or_else(|| Uuid::new_v4().to_string(), str::to_string));
+    if let (Some(delivery), Some(delivery_id)) = (delivery, delivery_id.as_deref()) {
+        let mut payload = serde_json::from_slice::<Value>(delivery.body).ok();
+        if let Some(payload) = payload.as_mut() {
+            redact::redact_json(payload);
+        }
+        let record = WebhookDelivery {
+            provider: provider.to_string(),
+            delivery_id: delivery_id.to_string(),
+            repo_url: git::repo_url(&repo),
+            event: delivery.event.map(str::to_string),
+            payload,
+        };
+        match record_webhook_delivery(state, &record).await {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("Delivery {} for {} was already received; skipping it", delivery_id, repo);
+                metrics::record_webhook(provider, "duplicate");
+                return Ok(Json(HookUpdateResponse {
+                    repo,
+                    processed: 0,
+                    errors: Vec::new(),
+                    timings: Vec::new(),
+                    duplicate: true,
+                    since_commit: None,
+                }));
+            }
+            // Processing twice beats dropping a push
+            Err(e) => warn!("Failed to record delivery {} for {}: {}", delivery_id, repo, e),
+        }
+    }
+
+    info!("Webhook contains {} commits", commits.len());
+    for (id, message) in commits {
+        info!(
+            "  Commit: {} - {:?}",
+            id.as_deref().unwrap_or("unknown"),
+            message
+        );
+    }
+
+    // Invalidate cache to force fresh clone
+    if let Err(e) = git::invalidate_cache(&repo).await {
+        warn!("Failed to invalidate cache for {}: {}", repo, e);
+    }
+    app.responses.invalidate_repo(&repo).await;
+
+    // Now process with fresh data
+    let result = process_repo_internal(state.clone(), app.schematics.clone(), repo.clone(), Scan::Incremental).await;
+    metrics::record_webhook(provider, if result.is_ok() { "processed" } else { "failed" });
+    if let (Err(_), Some(delivery_id)) = (&result, delivery_id.as_deref()) {
+        if let Err(e) = forget_webhook_delivery(state, provider, delivery_id).await {
+            warn!("Failed to forget failed delivery {}: {}", delivery_id, e);
+        }
+    }
+    if let Ok(Json(response)) = &result {
+        if policy.ai && response.processed > 0 {
+            summarize_new_commits(app, &repo, WorkClass::Webhook).await;
+        }
+    }
+    result
+}
+
+/// Start a summary backfill of `repo`'s new commits as `class`, as its
+/// processing policy asks; a failure to start is only logged
+pub(crate) async fn summarize_new_commits(app: &AppState, repo: &str, class: WorkClass) {
+    match backfill_settings(app, repo).await {
+        Ok(settings) => {
+            info!("Summarizing the new commits of {}", repo);
+            backfill::start(app.clone(), repo.to_string(), settings, class);
+        }
+        Err(e) => warn!("Failed to start summarizing the new commits of {}: {}", repo, e),
+    }
+}
+
 /// GitHub webhook endpoint - receives push events from GitHub
-/// This forces a fresh clone to ensure we have the latest commits
+/// This forces a fresh clone to ensure we have the latest commits.
+/// Verifies X-Hub-Signature-256 against the repo's registered secret or GITHUB_WEBHOOK_SECRET,
+/// else requires a hook-scoped API key
 #[utoipa::path(
     post,
     path = "/api/hook/github/{repo}",
@@ -44,36 +382,276 @@ pub struct GitHubCommit {
     ),
     responses(
         (status = 200, description = "Webhook processed successfully", body = HookUpdateResponse),
+        (status = 400, description = "Invalid payload", body = ApiError),
+        (status = 401, description = "Invalid signature", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required)", body = ApiError),
+        (status = 422, description = "Not a valid repository slug", body = ApiError),
         (status = 500, description = "Internal server error", body = ApiError)
     ),
     tag = "hook"
 )]
 pub async fn github_webhook(
-    State(state): State<AppState>,
+    State(app): State<AppState>,
+    viewer: Viewer,
     Path(repo): Path<String>,
-    Json(payload): Json<GitHubPushEvent>,
-) -> Result<Json<HookUpdateResponse>, (StatusCode, Json<ApiError>)> {
-    let repo = repo.trim_start_matches('/').to_string();
-
-    info!("Received GitHub webhook for repo: {}", repo);
-    if let Some(commits) = &payload.commits {
-        info!("Webhook contains {} commits", commits.len());
-        for commit in commits {
-            info!(
-                "  Commit: {} - {:?}",
-                commit.id.as_deref().unwrap_or("unknown"),
-                commit.message
-            );
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<HookUpdateResponse>, AppError> {
+    let payload: GitHubPushEvent = parse_payload(&body)?;
+
+    let mut repo = repo.trim_start_matches('/').to_string();
+    if repo.is_empty() {
+        if let Some(full_name) = payload.repository.and_then(|r| r.full_name) {
+            repo = full_name;
         }
     }
+    let repo = validation::repo_path(&repo)?;
 
-    // Invalidate cache to force fresh clone
-    if let Err(e) = git::invalidate_cache(&repo).await {
-        warn!("Failed to invalidate cache for {}: {}", repo, e);
+    let registered = registry::lookup(&app.pool, &app.config(), &repo).await?;
+    verify_hmac_signature(
+        &headers,
+        "x-hub-signature-256",
+        webhook_secret(registered.as_ref(), &app.config().webhooks.github),
+        &body,
+        &viewer,
+    )?;
+
+    info!(
+        "Received GitHub webhook for repo: {} (ref: {:?})",
+        repo, payload.git_ref
+    );
+
+    let delivery = Delivery {
+        id: headers.get("x-github-delivery").and_then(|v| v.to_str().ok()),
+        event: headers.get("x-github-event").and_then(|v| v.to_str().ok()),
+        body: &body,
+    };
+    let refs: Vec<String> = payload.git_ref.into_iter().collect();
+    handle_push(&app, "github", repo, registered.as_ref(), Some(delivery), &refs, &github_commits(payload.commits)).await
+}
+
+/// GitLab webhook endpoint - receives push events from GitLab
+/// Verifies X-Gitlab-Token against the repo's registered secret or GITLAB_WEBHOOK_SECRET,
+/// else requires a hook-scoped API key
+#[utoipa::path(
+    post,
+    path = "/api/hook/gitlab/{repo}",
+    params(
+        ("repo" = String, Path, description = "GitLab project path, on gitlab.com unless it starts with a host, e.g. gitlab.example.com/group/project")
+    ),
+    responses(
+        (status = 200, description = "Webhook processed successfully", body = HookUpdateResponse),
+        (status = 400, description = "Invalid payload, or one for another project", body = ApiError),
+        (status = 401, description = "Invalid secret token", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required)", body = ApiError),
+        (status = 422, description = "Not a valid repository slug", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "hook"
+)]
+pub async fn gitlab_webhook(
+    State(app): State<AppState>,
+    viewer: Viewer,
+    Path(repo): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<HookUpdateResponse>, AppError> {
+    let payload: GitLabPushEvent = parse_payload(&body)?;
+
+    let repo = validation::repo_path(&webhook_slug(&repo, "gitlab.com"))?;
+
+    let registered = registry::lookup(&app.pool, &app.config(), &repo).await?;
+    verify_gitlab_token(
+        &headers,
+        webhook_secret(registered.as_ref(), &app.config().webhooks.gitlab),
+        &viewer,
+    )?;
+    let project = payload.project.as_ref();
+    check_payload_repo(
+        &repo,
+        registered.as_ref(),
+        project.and_then(|p| p.git_http_url.as_deref()),
+        project.and_then(|p| p.path_with_namespace.as_deref()),
+    )?;
+
+    info!(
+        "Received GitLab webhook for repo: {} (kind: {:?}, ref: {:?})",
+        repo, payload.object_kind, payload.git_ref
+    );
+
+    if !matches!(payload.object_kind.as_deref(), Some("push") | Some("tag_push")) {
+        info!("Ignoring GitLab {:?} event", payload.object_kind);
+        metrics::record_webhook("gitlab", "ignored");
+        return Ok(Json(HookUpdateResponse {
+            repo,
+            processed: 0,
+            errors: Vec::new(),
+            timings: Vec::new(),
+            duplicate: false,
+            since_commit: None,
+        }));
     }
 
-    // Now process with fresh data
-    process_repo_internal(state, repo).await
+    let delivery = Delivery {
+        id: headers.get("x-gitlab-event-uuid").and_then(|v| v.to_str().ok()),
+        event: headers.get("x-gitlab-event").and_then(|v| v.to_str().ok()),
+        body: &body,
+    };
+    let refs: Vec<String> = payload.git_ref.into_iter().collect();
+    handle_push(&app, "gitlab", repo, registered.as_ref(), Some(delivery), &refs, &github_commits(payload.commits)).await
+}
+
+/// Bitbucket webhook endpoint - receives repo:push events from Bitbucket Cloud
+/// Verifies X-Hub-Signature against the repo's registered secret or BITBUCKET_WEBHOOK_SECRET,
+/// else requires a hook-scoped API key
+#[utoipa::path(
+    post,
+    path = "/api/hook/bitbucket/{repo}",
+    params(
+        ("repo" = String, Path, description = "Bitbucket repository in workspace/repo format, on bitbucket.org unless it starts with a host")
+    ),
+    responses(
+        (status = 200, description = "Webhook processed successfully", body = HookUpdateResponse),
+        (status = 400, description = "Invalid payload, or one for another repository", body = ApiError),
+        (status = 401, description = "Invalid signature", body = ApiError),
+        (status = 404, description = "Repository is not registered (when registration is required)", body = ApiError),
+        (status = 422, description = "Not a valid repository slug", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "hook"
+)]
+pub async fn bitbucket_webhook(
+    State(app): State<AppState>,
+    viewer: Viewer,
+    Path(repo): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<HookUpdateResponse>, AppError> {
+    let payload: BitbucketPushEvent = parse_payload(&body)?;
+
+    let repo = validation::repo_path(&webhook_slug(&repo, "bitbucket.org"))?;
+
+    let registered = registry::lookup(&app.pool, &app.config(), &repo).await?;
+    verify_hmac_signature(
+        &headers,
+        "x-hub-signature",
+        webhook_secret(registered.as_ref(), &app.config().webhooks.bitbucket),
+        &body,
+        &viewer,
+    )?;
+    let repository = payload.repository.as_ref();
+    check_payload_repo(
+        &repo,
+        registered.as_ref(),
+        repository
+            .and_then(|r| r.links.as_ref())
+            .and_then(|l| l.html.as_ref())
+            .and_then(|h| h.href.as_deref()),
+        repository.and_then(|r| r.full_name.as_deref()),
+    )?;
+
+    let event = headers
+        .get("x-event-key")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("repo:push");
+
+    info!("Received Bitbucket webhook for repo: {} (event: {})", repo, event);
+
+    if event != "repo:push" {
+        info!("Ignoring Bitbucket {} event", event);
+        metrics::record_webhook("bitbucket", "ignored");
+        return Ok(Json(HookUpdateResponse {
+            repo,
+            processed: 0,
+            errors: Vec::new(),
+            timings: Vec::new(),
+            duplicate: false,
+            since_commit: None,
+        }));
+    }
+
+    let delivery = Delivery {
+        id: headers.get("x-request-uuid").and_then(|v| v.to_str().ok()),
+        event: Some(event),
+        body: &body,
+    };
+    let refs = bitbucket_refs(payload.push.as_ref());
+    handle_push(&app, "bitbucket", repo, registered.as_ref(), Some(delivery), &refs, &bitbucket_commits(payload.push)).await
+}
+
+/// `(id, message)` of the commits in a GitHub or GitLab push
+fn github_commits(commits: Option<Vec<GitHubCommit>>) -> Vec<(Option<String>, Option<String>)> {
+    commits
+        .unwrap_or_default()
+        .into_iter()
+        .map(|c| (c.id, c.message))
+        .collect()
+}
+
+/// The refs a Bitbucket push updated, as "refs/heads/main" or "refs/tags/v1"
+fn bitbucket_refs(push: Option<&BitbucketPush>) -> Vec<String> {
+    push.into_iter()
+        .flat_map(|p| &p.changes)
+        .filter_map(|change| {
+            let new = change.new.as_ref()?;
+            let kind = if new.kind.as_deref() == Some("tag") { "tags" } else { "heads" };
+            Some(format!("refs/{}/{}", kind, new.name.as_deref()?))
+        })
+        .collect()
+}
+
+/// `(hash, message)` of the commits in a Bitbucket push
+fn bitbucket_commits(push: Option<BitbucketPush>) -> Vec<(Option<String>, Option<String>)> {
+    push.map(|p| p.changes)
+        .unwrap_or_default()
+        .into_iter()
+        .inspect(|change| {
+            if let Some(name) = change.new.as_ref().and_then(|n| n.name.as_deref()) {
+                info!("  Branch/tag: {}", name);
+            }
+        })
+        .flat_map(|change| change.commits)
+        .map(|c| (c.hash, c.message))
+        .collect()
+}
+
+/// Process a stored delivery again, without signature checks or
+/// deduplication, e.g. after fixing a bug that broke it. The repo's current
+/// processing policy applies
+pub(crate) async fn replay_delivery(
+    app: &AppState,
+    delivery: StoredDelivery,
+) -> Result<Json<HookUpdateResponse>, AppError> {
+    let repo = git::repo_slug(&delivery.repo_url).unwrap_or_else(|| delivery.repo_url.clone());
+    let registered = registry::lookup(&app.pool, &app.config(), &repo).await?;
+    let payload = delivery.payload.ok_or_else(|| {
+        AppError::not_found(format!("Delivery {} has no stored payload", delivery.delivery_id))
+    })?;
+    info!(
+        "Replaying {} delivery {} for {} (event: {:?})",
+        delivery.provider, delivery.delivery_id, repo, delivery.event
+    );
+
+    let (provider, refs, commits) = match delivery.provider.as_str() {
+        "github" => {
+            let payload = stored_payload::<GitHubPushEvent>(payload)?;
+            ("github", payload.git_ref.into_iter().collect(), github_commits(payload.commits))
+        }
+        "gitlab" => {
+            let payload = stored_payload::<GitLabPushEvent>(payload)?;
+            ("gitlab", payload.git_ref.into_iter().collect(), github_commits(payload.commits))
+        }
+        "bitbucket" => {
+            let payload = stored_payload::<BitbucketPushEvent>(payload)?;
+            ("bitbucket", bitbucket_refs(payload.push.as_ref()), bitbucket_commits(payload.push))
+        }
+        other => return Err(AppError::internal(format!("Unknown webhook provider '{}'", other))),
+    };
+    handle_push(app, provider, repo, registered.as_ref(), None, &refs, &commits).await
+}
+
+fn stored_payload<T: for<'de> Deserialize<'de>>(payload: Value) -> Result<T, AppError> {
+    serde_json::from_value(payload).or_internal("Stored webhook payload no longer parses")
 }
 
 /// Refresh a repository - forces a fresh clone and reprocesses
@@ -85,15 +663,21 @@ pub async fn github_webhook(
     ),
     responses(
         (status = 200, description = "Repository refreshed successfully", body = HookUpdateResponse),
+        (status = 404, description = "Repository is not registered (when registration is required)", body = ApiError),
+        (status = 422, description = "Not a valid repository slug", body = ApiError),
         (status = 500, description = "Internal server error", body = ApiError)
     ),
     tag = "hook"
 )]
 pub async fn refresh_repo(
-    State(state): State<AppState>,
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    State(responses): State<Arc<ResponseCache>>,
     Path(repo): Path<String>,
-) -> Result<Json<HookUpdateResponse>, (StatusCode, Json<ApiError>)> {
-    let repo = repo.trim_start_matches('/').to_string();
+) -> Result<Json<HookUpdateResponse>, AppError> {
+    let repo = validation::repo_path(&repo)?;
+    registry::lookup(&state, &config, &repo).await?;
 
     info!("Refresh requested for repo: {}", repo);
 
@@ -101,9 +685,10 @@ pub async fn refresh_repo(
     if let Err(e) = git::invalidate_cache(&repo).await {
         warn!("Failed to invalidate cache for {}: {}", repo, e);
     }
+    responses.invalidate_repo(&repo).await;
 
-    // Now process with fresh data
-    process_repo_internal(state, repo).await
+    // Now process with fresh data, all of it
+    process_repo_internal(state, schematics, repo, Scan::Full).await
 }
 
 /// Process a repository and generate overviews for commits missing them
@@ -116,152 +701,320 @@ pub async fn refresh_repo(
     ),
     responses(
         (status = 200, description = "Repository processed successfully", body = HookUpdateResponse),
+        (status = 404, description = "Repository is not registered (when registration is required)", body = ApiError),
+        (status = 422, description = "Not a valid repository slug", body = ApiError),
         (status = 500, description = "Internal server error", body = ApiError)
     ),
     tag = "hook"
 )]
 pub async fn update_repo(
-    State(state): State<AppState>,
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(schematics): State<Arc<SchematicCache>>,
     Path(repo): Path<String>,
-) -> Result<Json<HookUpdateResponse>, (StatusCode, Json<ApiError>)> {
-    let repo = repo.trim_start_matches('/').to_string();
+) -> Result<Json<HookUpdateResponse>, AppError> {
+    let repo = validation::repo_path(&repo)?;
+    registry::lookup(&state, &config, &repo).await?;
     info!("Processing update hook for repo: {}", repo);
-    process_repo_internal(state, repo).await
+    process_repo_internal(state, schematics, repo, Scan::Incremental).await
+}
+
+/// How much of the history an update looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Scan {
+    /// The commits after the branch's high-water mark
+    Incremental,
+    /// All of it, e.g. to retry old failures; the refresh endpoint's
+    Full,
 }
 
 /// Internal function to process a repository
-async fn process_repo_internal(
-    state: AppState,
+///
+/// Once every commit it looked at is processed, the branch's high-water mark
+/// moves to its tip, so the next incremental update starts from there. A
+/// mark that's gone from the history (after a force push) scans all of it.
+pub(crate) async fn process_repo_internal(
+    state: Arc<PgPool>,
+    schematics: Arc<SchematicCache>,
     repo: String,
-) -> Result<Json<HookUpdateResponse>, (StatusCode, Json<ApiError>)> {
-    let repo_url = format!("https://github.com/{}.git", repo);
-
-    // Get all commits with schematic changes
-    let commits = git::get_schematic_commits(&repo).await.map_err(|e| {
-        error!("Failed to get commits for {}: {}", repo, e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError::internal(format!(
-                "Failed to fetch commits: {}",
-                e
-            ))),
-        )
-    })?;
+    scan: Scan,
+) -> Result<Json<HookUpdateResponse>, AppError> {
+    let repo_url = git::repo_url(&repo);
+    let job = status::track_job("update", &repo, None);
 
-    info!(
-        "Found {} commits with schematic changes for repo: {}",
-        commits.len(),
-        repo
-    );
-    for (idx, commit) in commits.iter().enumerate() {
-        info!(
-            "  Commit {}: {} - {:?}",
-            idx + 1,
-            &commit.commit_hash[..8.min(commit.commit_hash.len())],
-            commit.message
-        );
+    let (branch, tip) = git::processed_branch(&repo)
+        .await
+        .or_internal("Failed to fetch commits")
+        .for_repo(&repo)
+        .in_stage(Stage::Clone)?;
+    let mark = match scan {
+        Scan::Full => None,
+        Scan::Incremental => match processed_up_to(&state, &repo_url, &branch).await {
+            Ok(mark) => mark,
+            Err(e) => {
+                warn!("Failed to load the high-water mark of {}; scanning all of it: {}", repo, e);
+                None
+            }
+        },
+    };
+    if mark.as_deref() == Some(tip.as_str()) {
+        info!("{} is processed up to the tip of {}; nothing new", repo, branch);
+        return Ok(Json(HookUpdateResponse {
+            repo,
+            processed: 0,
+            errors: Vec::new(),
+            timings: Vec::new(),
+            duplicate: false,
+            since_commit: mark,
+        }));
     }
+    let mark = match mark {
+        Some(mark) if git::contains_commit(&repo, &mark).await.unwrap_or(false) => Some(mark),
+        Some(mark) => {
+            info!("The high-water mark {} of {} is gone from its history; scanning all of it", mark, repo);
+            None
+        }
+        None => None,
+    };
 
+    // Commits with schematic changes, newest first, processed a page at a
+    // time while the rest of the history is still being walked
+    let filter = LogFilter {
+        since_commit: mark.clone(),
+        ..LogFilter::default()
+    };
+    let mut pages = git::schematic_commit_pages(&repo, filter);
+    let mut found = 0;
     let mut processed = 0;
+    let mut skipped = 0;
     let mut errors = Vec::new();
+    let mut timings = Vec::new();
 
-    for commit_info in commits {
-        // Check if we already have an overview for this commit
-        let existing = retrieve_schematic(&state, &repo_url, &commit_info.commit_hash)
-            .await
-            .ok()
-            .flatten();
-
-        let needs_processing = existing
-            .as_ref()
-            .map(|s| s.blurb.is_none() || s.description.is_none())
-            .unwrap_or(true);
-
+    'pages: while let Some(page) = pages.next().await {
+        let commits = match page {
+            Ok(commits) => commits,
+            // Nothing to report progress on yet, so it fails the update
+            Err(e) if found == 0 => {
+                return Err(e)
+                    .or_internal("Failed to fetch commits")
+                    .for_repo(&repo)
+                    .in_stage(Stage::Clone);
+            }
+            Err(e) => {
+                warn!("Failed to read the rest of the history of {}: {:#}", repo, e);
+                errors.push(format!("Stopped early: failed to read the history: {:#}", e));
+                break;
+            }
+        };
+        found += commits.len();
         info!(
-            "Commit {} needs_processing={}, existing={:?}",
-            &commit_info.commit_hash[..8.min(commit_info.commit_hash.len())],
-            needs_processing,
-            existing.as_ref().map(|s| format!(
-                "blurb={}, desc={}",
-                s.blurb.is_some(),
-                s.description.is_some()
-            ))
+            "Found {} more commits with schematic changes for repo: {}",
+            commits.len(),
+            repo
         );
+        for commit in &commits {
+            info!(
+                "  Commit {}: {:?}",
+                &commit.commit_hash[..8.min(commit.commit_hash.len())],
+                commit.message
+            );
+        }
+
+        // What is already done, in one query per page rather than one per commit
+        let hashes: Vec<String> = commits.iter().map(|c| c.commit_hash.clone()).collect();
+        let finished = match commits_with_overviews(&state, &repo_url, &hashes).await {
+            Ok(finished) => finished,
+            Err(e) => {
+                warn!("Failed to load processing state for {}; processing every commit: {}", repo, e);
+                HashSet::new()
+            }
+        };
+
+        for commit_info in commits {
+            // Each commit is stored whole, so the rest can wait for the next update
+            if shutdown::requested() {
+                warn!("Shutting down; leaving the remaining commits of {} for the next update", repo);
+                errors.push("Stopped early: the server is shutting down".to_string());
+                break 'pages;
+            }
+
+            // Commits with both overview parts are done; redeliveries skip them
+            let needs_processing = !finished.contains(&commit_info.commit_hash);
+            if !needs_processing {
+                skipped += 1;
+            }
+            info!(
+                "Commit {} needs_processing={}",
+                &commit_info.commit_hash[..8.min(commit_info.commit_hash.len())],
+                needs_processing
+            );
 
-        if needs_processing {
-            match generate_and_store_overview(
-                &state,
-                &repo,
-                &repo_url,
-                &commit_info.commit_hash,
-                commit_info.commit_date,
-                commit_info.message.as_deref(),
-            )
-            .await
-            {
-                Ok(_) => {
-                    processed += 1;
-                    info!(
-                        "Generated overview for {}/{}",
-                        repo, commit_info.commit_hash
-                    );
+            if needs_processing {
+                job.set_commit(&commit_info.commit_hash);
+                if let Err(e) = record_processing_started(&state, &repo_url, &commit_info.commit_hash).await {
+                    warn!("Failed to record processing state of {}: {}", commit_info.commit_hash, e);
                 }
-                Err(e) => {
-                    let err_msg = format!("Commit {}: {}", commit_info.commit_hash, e);
-                    // Check for rate limiting
-                    if e.to_string().contains("429")
-                        || e.to_string().to_lowercase().contains("rate")
-                    {
-                        error!(
-                            "RATE LIMITED while processing commit {}: {}",
-                            commit_info.commit_hash, e
+                match generate_and_store_overview(&state, &schematics, &repo, &repo_url, &commit_info).await {
+                    Ok(timeline) => {
+                        processed += 1;
+                        info!(
+                            "Generated overview for {}/{}",
+                            repo, commit_info.commit_hash
+                        );
+                        timings.push(CommitTimeline {
+                            commit: commit_info.commit_hash.clone(),
+                            timeline,
+                        });
+                    }
+                    Err(e) => {
+                        let err_msg = format!("Commit {}: {:#}", commit_info.commit_hash, e);
+                        note_processing_error(&state, &repo_url, &commit_info.commit_hash, &format!("{:#}", e)).await;
+                        // Check for rate limiting
+                        if error::is_rate_limited(&e) {
+                            error!(
+                                "RATE LIMITED while processing commit {}: {:#}",
+                                commit_info.commit_hash, e
+                            );
+                            warn!("XAI API rate limit hit! Stopping further processing.");
+                            status::record_error(
+                                Some(&repo),
+                                Some(&commit_info.commit_hash),
+                                Some(Stage::Llm.as_str()),
+                                &format!("RATE LIMITED: {:#}", e),
+                            );
+                            errors.push(format!("RATE LIMITED: {}", err_msg));
+                            // Break out of the loop to avoid hitting more rate limits
+                            break 'pages;
+                        }
+                        error!("Failed to generate overview: {}", err_msg);
+                        status::record_error(
+                            Some(&repo),
+                            Some(&commit_info.commit_hash),
+                            None,
+                            &format!("{:#}", e),
                         );
-                        warn!("XAI API rate limit hit! Stopping further processing.");
-                        errors.push(format!("RATE LIMITED: {}", err_msg));
-                        // Break out of the loop to avoid hitting more rate limits
-                        break;
+                        errors.push(err_msg);
                     }
-                    error!("Failed to generate overview: {}", err_msg);
-                    errors.push(err_msg);
                 }
             }
         }
     }
 
     info!(
-        "Hook processing complete for {}: processed={}, errors={}",
+        "Hook processing complete for {}: found={}, processed={}, already done={}, errors={}",
         repo,
+        found,
         processed,
+        skipped,
         errors.len()
     );
     if !errors.is_empty() {
         warn!("Errors during processing: {:?}", errors);
     }
+    if processed > 0 {
+        changelog::spawn_publish_if_enabled(state.clone(), repo.clone());
+    }
+    // Failed commits keep the mark where it was, so the next update retries them
+    if errors.is_empty() {
+        if let Err(e) = set_processed_up_to(&state, &repo_url, &branch, &tip).await {
+            warn!("Failed to record the high-water mark of {}: {}", repo, e);
+        }
+    }
 
     Ok(Json(HookUpdateResponse {
         repo,
         processed,
         errors,
+        timings,
+        duplicate: false,
+        since_commit: mark,
     }))
 }
 
-/// Generate a placeholder overview and store it in the database
+/// Store a commit's processing error for the timeline and announce it;
+/// failures are only logged
+///
+/// Written on its own, so the error outlives the rolled back commit.
+async fn note_processing_error(pool: &PgPool, repo_url: &str, commit_hash: &str, error: &str) {
+    if let Err(e) = record_processing_error(pool, repo_url, commit_hash, Some(error)).await {
+        warn!("Failed to record processing state of {}: {}", commit_hash, e);
+        return;
+    }
+    let event = CommitEvent {
+        repo_url: repo_url.to_string(),
+        commit_hash: commit_hash.to_string(),
+        kind: CommitEventKind::Failed,
+    };
+    if let Err(e) = notify_commit_event(pool, &event).await {
+        warn!("Failed to announce processing error of {}: {}", commit_hash, e);
+    }
+}
+
+/// A commit's author, tags and pull request
+///
+/// The pull request is looked up only when a GitHub token is configured; a
+/// failed lookup leaves it unknown.
+async fn lookup_commit_metadata(repo: &str, commit: &CommitInfo) -> CommitMetadata {
+    let pr_number = if github::is_configured() {
+        match github::pull_request_for_commit(repo, &commit.commit_hash).await {
+            Ok(number) => number.and_then(|n| i32::try_from(n).ok()),
+            Err(e) => {
+                warn!("Failed to look up pull request for {}: {:#}", commit.commit_hash, e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    CommitMetadata {
+        commit_hash: commit.commit_hash.clone(),
+        author_name: commit.author.clone(),
+        author_email: commit.author_email.clone(),
+        tags: commit.tags.clone(),
+        pr_number,
+    }
+}
+
+/// Generate a placeholder overview and store it in the database, returning its stage timings
+///
+/// Everything stored for the commit (overview, board stats, metadata, the
+/// cleared processing error and the timings) is written in one transaction
+/// once the slow work is done, so a crash or failed write leaves the commit
+/// as it was and the next update processes it again. Event streams hear of
+/// the commit when that transaction commits.
 async fn generate_and_store_overview(
     pool: &PgPool,
+    schematics: &Arc<SchematicCache>,
     repo_slug: &str,
     repo_url: &str,
-    commit_hash: &str,
-    commit_date: Option<chrono::DateTime<chrono::Utc>>,
-    git_message: Option<&str>,
-) -> anyhow::Result<()> {
+    commit: &CommitInfo,
+) -> anyhow::Result<AnalysisTimeline> {
+    let commit_hash = commit.commit_hash.as_str();
+    let git_message = commit.message.as_deref();
+    let mut timer = StageTimer::start("overview");
+
     // Get changed files for context
-    let changed_files = git::get_changed_schematic_files(repo_slug, commit_hash).await?;
+    let changed_files = timer
+        .time(
+            Stage::Clone,
+            git::get_changed_schematic_files(repo_slug, commit_hash),
+        )
+        .await
+        .context("Failed to list changed schematic files")?;
 
     // Generate placeholder overview (TODO: integrate with Grok)
+    let prompt_start = Instant::now();
     let num_files = changed_files.len();
+    let layout_files = changed_files.iter().filter(|f| f.ends_with(".kicad_pcb")).count();
+    let kind = match (num_files - layout_files, layout_files) {
+        (0, _) => "Layout",
+        (_, 0) => "Schematic",
+        _ => "Schematic and layout",
+    };
     let blurb = if num_files > 0 {
         format!(
-            "Schematic changes in {} file(s): {}",
+            "{} changes in {} file(s): {}",
+            kind,
             num_files,
             git_message
                 .unwrap_or("Update")
@@ -281,22 +1034,87 @@ async fn generate_and_store_overview(
     for path in &changed_files {
         description.push_str(&format!("  - {}\n", path));
     }
+    timer.record(Stage::PromptBuild, prompt_start.elapsed());
+
+    let mut update = UpdateSchematic::new(repo_url, commit_hash)
+        .update_commit_info(commit.commit_date, git_message)
+        .update_blurb(blurb);
+
+    // Component and net changes across the whole sheet hierarchy; the file
+    // list above can't show a net that moved between sheets. Changes that
+    // look unintended are flagged, and the commit's counts are stored
+    // alongside for the repo's growth series, its text for keyword search
+    match timer
+        .time(Stage::Parse, distill::projects_around(schematics, repo_slug, commit_hash))
+        .await
+    {
+        Ok((previous, current)) => {
+            let changes = distill::diff_project_sets(&previous, &current);
+            for (root_file, diff) in &changes {
+                description.push_str(&format!("\nSchematic changes in {}:\n", root_file));
+                description.push_str(&diff.describe());
+            }
+            let warnings = anomalies::detect(&previous, &current, &changes);
+            description.push_str(&anomalies::describe(&warnings));
+            update = update
+                .update_warnings(warnings)
+                .update_stats(SchematicStats::of_projects(&current))
+                .update_texts(SchematicText::of_projects(&current));
+        }
+        Err(e) => warn!(
+            "Could not diff schematic hierarchy for {}/{}: {:#}",
+            repo_slug, commit_hash, e
+        ),
+    }
+
+    // Placement, stackup and routing changes, so layout-only commits get a
+    // description too; the board stats are stored next to the schematic data
+    match timer
+        .time(Stage::Parse, board::board_changes(repo_slug, commit_hash))
+        .await
+    {
+        Ok((boards, changes)) => {
+            for (file, diff) in changes {
+                description.push_str(&format!("\nLayout changes in {}:\n", file));
+                description.push_str(&diff.describe());
+            }
+            if !boards.is_empty() {
+                update = update.update_board(board::to_json(&boards));
+            }
+        }
+        Err(e) => warn!(
+            "Could not diff board layout for {}/{}: {:#}",
+            repo_slug, commit_hash, e
+        ),
+    }
+
+    let metadata = lookup_commit_metadata(repo_slug, commit).await;
+
+    let mut tx = timer
+        .time(Stage::Store, async {
+            let mut tx = pool.begin().await?;
+            update.update_description(description).execute_in(&mut tx).await?;
+            store_commit_metadata_in(&mut tx, repo_url, &metadata).await?;
+            record_processing_error_in(&mut tx, repo_url, commit_hash, None).await?;
+            Ok::<_, sqlx::Error>(tx)
+        })
+        .await
+        .context("Failed to store overview")?;
+
+    let timeline = timer.finish();
+    let value = serde_json::to_value(&timeline).context("Failed to serialize the overview timeline")?;
+    store_analysis_timing_in(&mut tx, repo_url, commit_hash, "overview", &value)
+        .await
+        .context("Failed to store the overview timeline")?;
+    let event = CommitEvent {
+        repo_url: repo_url.to_string(),
+        commit_hash: commit_hash.to_string(),
+        kind: CommitEventKind::Processed,
+    };
+    notify_commit_event_in(&mut tx, &event)
+        .await
+        .context("Failed to announce the processed commit")?;
+    tx.commit().await.context("Failed to store overview")?;
 
-    let empty_parts = HashMap::new();
-    store_schematic(
-        pool,
-        repo_url,
-        commit_hash,
-        commit_date,
-        git_message,
-        None, // image
-        None, // summary
-        None, // overview
-        Some(&blurb),
-        Some(&description),
-        empty_parts,
-    )
-    .await?;
-
-    Ok(())
+    Ok(timeline)
 }
@@ -0,0 +1,127 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use kicad_db::{create_api_key, list_api_keys, revoke_api_key, ApiScope, PgPool};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::auth::{generate_key, hash_key, Viewer};
+use crate::error::{AppError, ResultExt};
+use crate::types::{
+    ApiKeyListResponse, CreateApiKeyRequest, CreateApiKeyResponse, RevokeApiKeyResponse,
+};
+
+/// Characters of a new key kept in the clear to identify it ("kw_" plus 8)
+const DISPLAY_PREFIX_LEN: usize = 11;
+
+/// List API keys
+///
+/// Secrets are never returned, only the display prefix. Org keys only list
+/// their org's keys. Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/keys",
+    responses(
+        (status = 200, description = "The caller's org's keys, or all for instance-wide keys, newest first", body = ApiKeyListResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "keys"
+)]
+pub async fn list_keys(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+) -> Result<Json<ApiKeyListResponse>, AppError> {
+    let keys = list_api_keys(&state, viewer.orgs())
+        .await
+        .or_internal("Failed to list API keys")?;
+    Ok(Json(ApiKeyListResponse {
+        keys: keys.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Create an API key
+///
+/// The response is the only time the key itself is shown; only its hash is
+/// stored. The key belongs to the caller's org; instance-wide keys may name
+/// another org, or none for a new instance-wide key. Requires an admin key.
+#[utoipa::path(
+    post,
+    path = "/api/keys",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 200, description = "Key created", body = CreateApiKeyResponse),
+        (status = 400, description = "Empty name or unknown scope", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key, or an org key naming an org", body = ApiError),
+        (status = 404, description = "No org with the given slug", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "keys"
+)]
+pub async fn create_key(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Json(req): Json<CreateApiKeyRequest>,
+) -> Result<Json<CreateApiKeyResponse>, AppError> {
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err(AppError::bad_request("Key name must not be empty"));
+    }
+    let scope: ApiScope = req.scope.parse().map_err(AppError::bad_request)?;
+    let org = viewer.owning_org(&state, req.org.as_deref()).await?;
+
+    let secret = generate_key();
+    let key = create_api_key(
+        &state,
+        name,
+        &secret[..DISPLAY_PREFIX_LEN],
+        &hash_key(&secret),
+        scope,
+        org,
+    )
+    .await
+    .or_internal("Failed to store API key")?;
+    info!("Created {} API key {} ({})", scope, key.id, key.name);
+
+    Ok(Json(CreateApiKeyResponse {
+        key: key.into(),
+        secret,
+    }))
+}
+
+/// Revoke an API key
+///
+/// Revoked keys are kept for the audit trail but rejected from then on. Org
+/// keys only revoke their org's keys. Requires an admin key.
+#[utoipa::path(
+    delete,
+    path = "/api/keys/{id}",
+    params(
+        ("id" = i32, Path, description = "Key id")
+    ),
+    responses(
+        (status = 200, description = "Key revoked", body = RevokeApiKeyResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "No active key with this id", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "keys"
+)]
+pub async fn revoke_key(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Path(id): Path<i32>,
+) -> Result<Json<RevokeApiKeyResponse>, AppError> {
+    let revoked = revoke_api_key(&state, id, viewer.orgs())
+        .await
+        .or_internal("Failed to revoke API key")?;
+    if !revoked {
+        return Err(AppError::not_found(format!("No active API key with id {}", id)));
+    }
+    info!("Revoked API key {}", id);
+    Ok(Json(RevokeApiKeyResponse { id, revoked }))
+}
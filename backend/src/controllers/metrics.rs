@@ -0,0 +1,20 @@
+use axum::{
+    extract::State,
+    http::header,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+use crate::services::metrics;
+use kicad_db::PgPool;
+
+/// Prometheus scrape endpoint
+///
+/// Request latency per route, XAI call durations and tokens, git operation
+/// timings, webhook counts and database pool usage.
+pub async fn prometheus_metrics(State(state): State<Arc<PgPool>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(&state),
+    )
+}
@@ -1,5 +1,30 @@
+pub mod admin;
+pub mod components;
 pub mod digikey;
 pub mod distill;
 pub mod grok;
+pub mod guidelines;
+pub mod health;
 pub mod hook;
+pub mod keys;
+pub mod metrics;
+pub mod orgs;
+pub mod public;
 pub mod repo;
+pub mod repos;
+pub mod search;
+pub mod shared;
+pub mod status;
+
+use crate::error::{AppError, AppResultExt, ResultExt};
+use crate::services::git;
+
+/// The full hash of the commit a request names by SHA (full or short),
+/// branch or tag; 404 if nothing matches, 422 if several commits do
+pub(crate) async fn resolve_commit(repo: &str, commit: &str) -> Result<String, AppError> {
+    git::resolve_ref(repo, commit)
+        .await
+        .or_internal("Failed to resolve commit")
+        .for_repo(repo)
+        .at_commit(commit)
+}
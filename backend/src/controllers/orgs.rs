@@ -0,0 +1,231 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use kicad_db::{
+    create_organization, get_organization, list_members, list_organizations, remove_membership,
+    set_membership, ApiScope, Organization, PgPool,
+};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::auth::Viewer;
+use crate::error::{AppError, ResultExt};
+use crate::types::{
+    CreateOrganizationRequest, MemberItem, MemberListResponse, OrganizationItem, OrganizationListResponse,
+    RemoveMemberResponse, SetMemberRequest,
+};
+
+fn instance_only(viewer: &Viewer) -> Result<(), AppError> {
+    match viewer.org() {
+        Some(_) => Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "Only instance-wide admin keys may manage orgs",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The org with this slug, if the caller may manage it: instance-wide
+/// callers any org, org callers their own. Other orgs answer 404.
+pub(crate) async fn managed_org(pool: &PgPool, viewer: &Viewer, slug: &str) -> Result<Organization, AppError> {
+    get_organization(pool, slug)
+        .await
+        .or_internal("Failed to look up org")?
+        .filter(|org| viewer.orgs().allows(Some(org.id)))
+        .ok_or_else(|| AppError::not_found(format!("No org '{}'", slug)))
+}
+
+/// List orgs
+///
+/// Org keys only see their own org. Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/orgs",
+    responses(
+        (status = 200, description = "Orgs, by slug", body = OrganizationListResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "orgs"
+)]
+pub async fn list_orgs(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+) -> Result<Json<OrganizationListResponse>, AppError> {
+    let orgs = list_organizations(&state)
+        .await
+        .or_internal("Failed to list orgs")?;
+    Ok(Json(OrganizationListResponse {
+        orgs: orgs
+            .into_iter()
+            .filter(|org| viewer.orgs().allows(Some(org.id)))
+            .map(Into::into)
+            .collect(),
+    }))
+}
+
+/// Create an org
+///
+/// Repos, keys and AI spend are then assigned to it by creating them with
+/// an org key, or with `org` set by an instance-wide key. Requires an
+/// instance-wide admin key.
+#[utoipa::path(
+    post,
+    path = "/api/orgs",
+    request_body = CreateOrganizationRequest,
+    responses(
+        (status = 200, description = "Org created", body = OrganizationItem),
+        (status = 400, description = "Invalid slug or empty name", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an instance-wide admin key", body = ApiError),
+        (status = 409, description = "The slug is taken", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "orgs"
+)]
+pub async fn create_org(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Json(req): Json<CreateOrganizationRequest>,
+) -> Result<Json<OrganizationItem>, AppError> {
+    instance_only(&viewer)?;
+    let slug = req.slug.trim();
+    let name = req.name.trim();
+    if slug.is_empty() || !slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+        return Err(AppError::bad_request(
+            "slug must be lowercase letters, digits and dashes, e.g. acme-robotics",
+        ));
+    }
+    if name.is_empty() {
+        return Err(AppError::bad_request("Org name must not be empty"));
+    }
+    let taken = get_organization(&state, slug)
+        .await
+        .or_internal("Failed to look up org")?
+        .is_some();
+    if taken {
+        return Err(AppError::new(StatusCode::CONFLICT, "conflict", format!("Org '{}' already exists", slug)));
+    }
+
+    let org = create_organization(&state, slug, name)
+        .await
+        .or_internal("Failed to create org")?;
+    info!("Created org {} ({})", org.slug, org.id);
+    Ok(Json(org.into()))
+}
+
+/// List an org's members
+///
+/// Requires an admin key of the org, or an instance-wide one.
+#[utoipa::path(
+    get,
+    path = "/api/orgs/{org}/members",
+    params(
+        ("org" = String, Path, description = "Org slug")
+    ),
+    responses(
+        (status = 200, description = "Members, by email", body = MemberListResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "No such org, or it isn't the caller's", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "orgs"
+)]
+pub async fn get_members(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Path(slug): Path<String>,
+) -> Result<Json<MemberListResponse>, AppError> {
+    let org = managed_org(&state, &viewer, &slug).await?;
+    let members = list_members(&state, org.id)
+        .await
+        .or_internal("Failed to list members")?;
+    Ok(Json(MemberListResponse {
+        org: org.slug,
+        members: members.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Add a member to an org, or change their role
+///
+/// Members sign in with JWTs whose `sub` is their email and whose `org`
+/// claim is the org's slug; they get the narrower of the token's scope and
+/// their role. Requires an admin key of the org, or an instance-wide one.
+#[utoipa::path(
+    put,
+    path = "/api/orgs/{org}/members",
+    params(
+        ("org" = String, Path, description = "Org slug")
+    ),
+    request_body = SetMemberRequest,
+    responses(
+        (status = 200, description = "The membership", body = MemberItem),
+        (status = 400, description = "Empty email or unknown role", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "No such org, or it isn't the caller's", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "orgs"
+)]
+pub async fn set_member(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Path(slug): Path<String>,
+    Json(req): Json<SetMemberRequest>,
+) -> Result<Json<MemberItem>, AppError> {
+    let org = managed_org(&state, &viewer, &slug).await?;
+    let email = req.email.trim();
+    if email.is_empty() {
+        return Err(AppError::bad_request("email must not be empty"));
+    }
+    let role: ApiScope = req.role.parse().map_err(AppError::bad_request)?;
+    let name = req.name.as_deref().map(str::trim).filter(|n| !n.is_empty());
+
+    let member = set_membership(&state, org.id, email, name, role)
+        .await
+        .or_internal("Failed to store membership")?;
+    info!("{} is a {} member of org {}", member.email, member.role, org.slug);
+    Ok(Json(member.into()))
+}
+
+/// Remove a member from an org
+///
+/// Their JWTs for the org are rejected from then on. Requires an admin key
+/// of the org, or an instance-wide one.
+#[utoipa::path(
+    delete,
+    path = "/api/orgs/{org}/members/{email}",
+    params(
+        ("org" = String, Path, description = "Org slug"),
+        ("email" = String, Path, description = "Member's email")
+    ),
+    responses(
+        (status = 200, description = "Member removed", body = RemoveMemberResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "No such org or member", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "orgs"
+)]
+pub async fn remove_member(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Path((slug, email)): Path<(String, String)>,
+) -> Result<Json<RemoveMemberResponse>, AppError> {
+    let org = managed_org(&state, &viewer, &slug).await?;
+    let removed = remove_membership(&state, org.id, &email)
+        .await
+        .or_internal("Failed to remove membership")?;
+    if !removed {
+        return Err(AppError::not_found(format!("{} is not a member of org '{}'", email, org.slug)));
+    }
+    info!("Removed {} from org {}", email, org.slug);
+    Ok(Json(RemoveMemberResponse { email, removed }))
+}
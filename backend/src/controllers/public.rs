@@ -0,0 +1,141 @@
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Json, Response},
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::error::{AppError, AppResultExt, ResultExt};
+use crate::rate_limit;
+use crate::services::{embed, git};
+use crate::types::PublicSummaryResponse;
+use kicad_db::PgPool;
+
+const BADGE_LABEL: &str = "latest design change";
+
+/// A 429 response if the client is over its per-minute budget
+fn rate_limited(headers: &HeaderMap, peer: SocketAddr) -> Option<Response> {
+    let client = rate_limit::client_ip(headers, peer);
+    let quota = rate_limit::PUBLIC_EMBEDS.check(&client.to_string());
+    if quota.allowed {
+        return None;
+    }
+    warn!("Rate limiting public embed requests from {}", client);
+    Some(rate_limit::too_many_requests(quota))
+}
+
+fn cache_control() -> (header::HeaderName, HeaderValue) {
+    let value = format!("public, max-age={}", embed::CACHE_TTL.as_secs());
+    (
+        header::CACHE_CONTROL,
+        HeaderValue::from_str(&value).expect("cache-control value is ASCII"),
+    )
+}
+
+fn not_public(repo: &str) -> AppError {
+    AppError::not_found(format!("{} has no public summaries", repo))
+}
+
+/// SVG badge showing a public repository's latest design change
+///
+/// Meant for README embeds: no authentication, cached for five minutes and
+/// rate-limited per client. Repos whose summaries are private return 404.
+#[utoipa::path(
+    get,
+    path = "/api/public/{repo}/badge.svg",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    responses(
+        (status = 200, description = "SVG badge", content_type = "image/svg+xml", body = String),
+        (status = 404, description = "Repository summaries are not public", body = ApiError),
+        (status = 429, description = "Too many requests", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "public"
+)]
+pub async fn badge(
+    State(state): State<Arc<PgPool>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(repo): Path<String>,
+) -> Result<Response, AppError> {
+    if let Some(limited) = rate_limited(&headers, peer) {
+        return Ok(limited);
+    }
+
+    let repo_embed = embed::repo_embed(&state, &git::repo_url(&repo))
+        .await
+        .or_internal("Failed to load public summary")
+        .for_repo(&repo)?;
+    if !repo_embed.public {
+        return Err(not_public(&repo));
+    }
+
+    let svg = match repo_embed.latest.and_then(|latest| latest.blurb) {
+        Some(blurb) => embed::render_badge(BADGE_LABEL, &blurb, "#4c1"),
+        None => embed::render_badge(BADGE_LABEL, "none yet", "#9f9f9f"),
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static("image/svg+xml; charset=utf-8")),
+            cache_control(),
+        ],
+        svg,
+    )
+        .into_response())
+}
+
+/// Latest public design-change summary for a repository
+///
+/// Same caching, rate limiting and visibility rules as the badge. Commits
+/// marked private are skipped in favour of the newest public one.
+#[utoipa::path(
+    get,
+    path = "/api/public/{repo}/latest-summary",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    responses(
+        (status = 200, description = "Latest public summary", body = PublicSummaryResponse),
+        (status = 404, description = "Repository summaries are not public, or none exist yet", body = ApiError),
+        (status = 429, description = "Too many requests", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "public"
+)]
+pub async fn latest_summary(
+    State(state): State<Arc<PgPool>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(repo): Path<String>,
+) -> Result<Response, AppError> {
+    if let Some(limited) = rate_limited(&headers, peer) {
+        return Ok(limited);
+    }
+
+    info!("Serving latest public summary for {}", repo);
+
+    let repo_embed = embed::repo_embed(&state, &git::repo_url(&repo))
+        .await
+        .or_internal("Failed to load public summary")
+        .for_repo(&repo)?;
+    if !repo_embed.public {
+        return Err(not_public(&repo));
+    }
+    let latest = repo_embed
+        .latest
+        .ok_or_else(|| AppError::not_found(format!("{} has no summarized commits yet", repo)))?;
+
+    let body = PublicSummaryResponse {
+        repo,
+        commit_hash: latest.commit_hash,
+        commit_date: latest.commit_date,
+        message: latest.git_message,
+        blurb: latest.blurb.unwrap_or_default(),
+    };
+    Ok(([cache_control()], Json(body)).into_response())
+}
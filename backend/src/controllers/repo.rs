@@ -1,20 +1,28 @@
-use axum::{extract::State, http::StatusCode, response::Json};
+use axum::{extract::State, response::Json};
 use std::sync::Arc;
 use tracing::{error, info};
 
-use crate::services::{distill, git};
+use crate::auth::Viewer;
+use crate::controllers::resolve_commit;
+use crate::error::{AppError, AppResultExt, ResultExt};
+use crate::services::{
+    changelog, distill, embed, git, github, schematic_cache::SchematicCache, status,
+    timing::{self, Stage, StageTimer},
+};
 use crate::types::{
-    ApiError, CommitFilesRequest, CommitFilesResponse, CommitInfoRequest, CommitInfoResponse,
+    CommitFilesRequest, CommitFilesResponse, CommitInfoRequest, CommitInfoResponse,
+    RepoChangelogRequest, RepoChangelogResponse, RepoChangelogSettingsRequest,
+    RepoChangelogSettingsResponse,
     RepoClearCacheRequest, RepoClearCacheResponse, RepoCommitsRequest, RepoCommitsResponse,
-    RepoInitRequest, RepoInitResponse,
+    RepoInitRequest, RepoInitResponse, RepoPersonaRequest, RepoPersonaResponse,
+    RepoVisibilityRequest, RepoVisibilityResponse,
 };
 use kicad_db::{
-    clear_distilled_json, retrieve_distilled_json, retrieve_schematic, store_distilled_json,
-    PgPool,
+    clear_distilled_json, get_changelog_settings, get_commit_visibility, personas::Persona, retrieve_distilled_json,
+    retrieve_schematic, set_changelog_settings, set_commit_visibility, set_repo_default_persona, set_repo_visibility,
+    store_distilled_json, ApiScope, PgPool, Visibility,
 };
 
-pub type AppState = Arc<PgPool>;
-
 /// Get all commits (with flag indicating schematic changes)
 #[utoipa::path(
     post,
@@ -27,19 +35,13 @@ pub type AppState = Arc<PgPool>;
     tag = "repo"
 )]
 pub async fn get_commits(
-    State(_state): State<AppState>,
+    State(_state): State<Arc<PgPool>>,
     Json(req): Json<RepoCommitsRequest>,
-) -> Result<Json<RepoCommitsResponse>, (StatusCode, Json<ApiError>)> {
-    let commits = git::get_all_commits(&req.repo).await.map_err(|e| {
-        error!("Failed to get commits for {}: {}", req.repo, e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError::internal(format!(
-                "Failed to fetch commits: {}",
-                e
-            ))),
-        )
-    })?;
+) -> Result<Json<RepoCommitsResponse>, AppError> {
+    let commits = git::get_all_commits(&req.repo)
+        .await
+        .or_internal("Failed to fetch commits")
+        .for_repo(&req.repo)?;
 
     Ok(Json(RepoCommitsResponse {
         repo: req.repo,
@@ -47,7 +49,7 @@ pub async fn get_commits(
     }))
 }
 
-/// Get all .kicad_sch files at a specific commit
+/// Get all schematic files (.kicad_sch, legacy .sch) at a specific commit
 #[utoipa::path(
     post,
     path = "/api/repo/commit/files",
@@ -59,18 +61,15 @@ pub async fn get_commits(
     tag = "repo"
 )]
 pub async fn get_commit_files(
-    State(_state): State<AppState>,
-    Json(req): Json<CommitFilesRequest>,
-) -> Result<Json<CommitFilesResponse>, (StatusCode, Json<ApiError>)> {
+    State(_state): State<Arc<PgPool>>,
+    Json(mut req): Json<CommitFilesRequest>,
+) -> Result<Json<CommitFilesResponse>, AppError> {
+    req.commit = resolve_commit(&req.repo, &req.commit).await?;
     let files = git::get_schematic_files(&req.repo, &req.commit)
         .await
-        .map_err(|e| {
-            error!("Failed to get files for {}/{}: {}", req.repo, req.commit, e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::internal(format!("Failed to fetch files: {}", e))),
-            )
-        })?;
+        .or_internal("Failed to fetch files")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)?;
 
     Ok(Json(CommitFilesResponse {
         repo: req.repo,
@@ -80,6 +79,9 @@ pub async fn get_commit_files(
 }
 
 /// Get summary information about a specific commit
+///
+/// The blurb and description are omitted for private summaries unless the
+/// request carries a valid API key.
 #[utoipa::path(
     post,
     path = "/api/repo/commit/info",
@@ -91,53 +93,41 @@ pub async fn get_commit_files(
     tag = "repo"
 )]
 pub async fn get_commit_info(
-    State(state): State<AppState>,
-    Json(req): Json<CommitInfoRequest>,
-) -> Result<Json<CommitInfoResponse>, (StatusCode, Json<ApiError>)> {
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Json(mut req): Json<CommitInfoRequest>,
+) -> Result<Json<CommitInfoResponse>, AppError> {
+    req.commit = resolve_commit(&req.repo, &req.commit).await?;
     // Get git commit info
     let commit_info = git::get_commit_info(&req.repo, &req.commit)
         .await
-        .map_err(|e| {
-            error!(
-                "Failed to get commit info for {}/{}: {}",
-                req.repo, req.commit, e
-            );
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::internal(format!(
-                    "Failed to fetch commit info: {}",
-                    e
-                ))),
-            )
-        })?;
+        .or_internal("Failed to fetch commit info")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)?;
 
     // Get changed files
     let changed_files = git::get_changed_schematic_files(&req.repo, &req.commit)
         .await
-        .map_err(|e| {
-            error!(
-                "Failed to get changed files for {}/{}: {}",
-                req.repo, req.commit, e
-            );
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::internal(format!(
-                    "Failed to fetch changed files: {}",
-                    e
-                ))),
-            )
-        })?;
+        .or_internal("Failed to fetch changed files")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)?;
 
     // Try to get stored blurb/description from database
-    let repo_url = format!("https://github.com/{}.git", req.repo);
+    let repo_url = git::repo_url(&req.repo);
     let stored = retrieve_schematic(&state, &repo_url, &req.commit)
         .await
         .ok()
         .flatten();
 
+    let visibility = get_commit_visibility(&state, &repo_url, &req.commit)
+        .await
+        .or_internal("Failed to check summary visibility")
+        .for_repo(&req.repo)
+        .at_commit(&req.commit)?;
+
     let (blurb, description) = match stored {
-        Some(s) => (s.blurb, s.description),
-        None => (None, None),
+        Some(s) if viewer.can_see(visibility) => (s.blurb, s.description),
+        _ => (None, None),
     };
 
     Ok(Json(CommitInfoResponse {
@@ -147,6 +137,7 @@ pub async fn get_commit_info(
         message: commit_info.message,
         blurb,
         description,
+        visibility: visibility.to_string(),
         changed_files,
     }))
 }
@@ -167,27 +158,27 @@ pub async fn get_commit_info(
     tag = "repo"
 )]
 pub async fn init_repo(
-    State(state): State<AppState>,
+    State(state): State<Arc<PgPool>>,
+    State(schematics): State<Arc<SchematicCache>>,
     Json(req): Json<RepoInitRequest>,
-) -> Result<Json<RepoInitResponse>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<RepoInitResponse>, AppError> {
     info!("Initializing repo: {}", req.repo);
 
+    let job = status::track_job("init", &req.repo, req.commit.as_deref());
+    let mut timer = StageTimer::start("init");
+
     // Get the commit hash - use provided or fetch latest
     let commit = match req.commit {
-        Some(c) => c,
-        None => git::get_latest_commit(&req.repo).await.map_err(|e| {
-            error!("Failed to get latest commit for {}: {}", req.repo, e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiError::internal(format!(
-                    "Failed to fetch latest commit: {}",
-                    e
-                ))),
-            )
-        })?,
+        Some(c) => resolve_commit(&req.repo, &c).await?,
+        None => git::get_latest_commit(&req.repo)
+            .await
+            .or_internal("Failed to fetch latest commit")
+            .for_repo(&req.repo)
+            .in_stage(Stage::Clone)?,
     };
+    job.set_commit(&commit);
 
-    let repo_url = format!("https://github.com/{}.git", req.repo);
+    let repo_url = git::repo_url(&req.repo);
 
     // Check if we already have distilled data cached
     let cached_distilled = retrieve_distilled_json(&state, &repo_url, &commit)
@@ -199,18 +190,13 @@ pub async fn init_repo(
         info!("Using cached distilled data for {}/{}", req.repo, commit);
 
         // Get schematic file list for response
-        let files = git::get_schematic_files(&req.repo, &commit)
+        let files = timer
+            .time(Stage::Clone, git::get_schematic_files(&req.repo, &commit))
             .await
-            .map_err(|e| {
-                error!("Failed to get schematic files: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ApiError::internal(format!(
-                        "Failed to fetch schematic files: {}",
-                        e
-                    ))),
-                )
-            })?;
+            .or_internal("Failed to fetch schematic files")
+            .for_repo(&req.repo)
+            .at_commit(&commit)
+            .in_stage(Stage::Clone)?;
 
         let file_paths: Vec<String> = files.iter().map(|f| f.path.clone()).collect();
         (cached_json, true, file_paths)
@@ -221,44 +207,40 @@ pub async fn init_repo(
         );
 
         // Get schematic files first
-        let files = git::get_schematic_files(&req.repo, &commit)
+        let files = timer
+            .time(Stage::Clone, git::get_schematic_files(&req.repo, &commit))
             .await
-            .map_err(|e| {
-                error!("Failed to get schematic files: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ApiError::internal(format!(
-                        "Failed to fetch schematic files: {}",
-                        e
-                    ))),
-                )
-            })?;
+            .or_internal("Failed to fetch schematic files")
+            .for_repo(&req.repo)
+            .at_commit(&commit)
+            .in_stage(Stage::Clone)?;
 
         let file_paths: Vec<String> = files.iter().map(|f| f.path.clone()).collect();
 
         if files.is_empty() {
-            return Err((
-                StatusCode::NOT_FOUND,
-                Json(ApiError::not_found(format!(
-                    "No .kicad_sch files found in {}/{}",
-                    req.repo, commit
-                ))),
-            ));
+            return Err(AppError::not_found(format!(
+                "No schematic files found in {}/{}",
+                req.repo, commit
+            )));
         }
 
         // Run distillation
-        let distilled_json = distill::distill_repo_schematics(&req.repo, &commit)
+        let distilled_json = timer
+            .time(Stage::Parse, distill::distill_repo_schematics(&schematics, &req.repo, &commit))
             .await
-            .map_err(|e| {
-                error!("Distillation failed for {}/{}: {}", req.repo, commit, e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ApiError::internal(format!("Distillation failed: {}", e))),
-                )
-            })?;
+            .or_internal("Distillation failed")
+            .for_repo(&req.repo)
+            .at_commit(&commit)
+            .in_stage(Stage::Parse)?;
 
         // Cache the result
-        if let Err(e) = store_distilled_json(&state, &repo_url, &commit, &distilled_json).await {
+        if let Err(e) = timer
+            .time(
+                Stage::Store,
+                store_distilled_json(&state, &repo_url, &commit, &distilled_json),
+            )
+            .await
+        {
             error!("Failed to cache distilled result: {}", e);
             // Continue anyway - we have the data
         } else {
@@ -289,6 +271,9 @@ pub async fn init_repo(
         .map(|obj| obj.len())
         .unwrap_or(0);
 
+    let timeline = timer.finish();
+    timing::store_timeline(&state, &repo_url, &commit, "init", &timeline).await;
+
     info!(
         "Initialized {}/{}: {} components, {} nets, {} files",
         req.repo,
@@ -306,6 +291,7 @@ pub async fn init_repo(
         net_count,
         schematic_files,
         distilled,
+        timeline,
     }))
 }
 
@@ -324,26 +310,24 @@ pub async fn init_repo(
     tag = "repo"
 )]
 pub async fn clear_cache(
-    State(state): State<AppState>,
-    Json(req): Json<RepoClearCacheRequest>,
-) -> Result<Json<RepoClearCacheResponse>, (StatusCode, Json<ApiError>)> {
+    State(state): State<Arc<PgPool>>,
+    Json(mut req): Json<RepoClearCacheRequest>,
+) -> Result<Json<RepoClearCacheResponse>, AppError> {
+    if let Some(commit) = &req.commit {
+        req.commit = Some(resolve_commit(&req.repo, commit).await?);
+    }
     info!(
         "Clearing cache for repo: {}, commit: {:?}",
         req.repo, req.commit
     );
 
-    let repo_url = format!("https://github.com/{}.git", req.repo);
+    let repo_url = git::repo_url(&req.repo);
 
     let rows_affected =
         clear_distilled_json(&state, &repo_url, req.commit.as_deref())
             .await
-            .map_err(|e| {
-                error!("Failed to clear cache: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ApiError::internal(format!("Failed to clear cache: {}", e))),
-                )
-            })?;
+            .or_internal("Failed to clear cache")
+            .for_repo(&req.repo)?;
 
     let message = if let Some(ref commit) = req.commit {
         format!(
@@ -365,3 +349,235 @@ pub async fn clear_cache(
         message,
     }))
 }
+
+/// Set (or clear) the default persona used for a repository's chat and summary requests
+#[utoipa::path(
+    post,
+    path = "/api/repo/persona",
+    request_body = RepoPersonaRequest,
+    responses(
+        (status = 200, description = "Default persona updated", body = RepoPersonaResponse),
+        (status = 400, description = "Unknown persona", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn set_default_persona(
+    State(state): State<Arc<PgPool>>,
+    Json(req): Json<RepoPersonaRequest>,
+) -> Result<Json<RepoPersonaResponse>, AppError> {
+    info!(
+        "Setting default persona for repo: {} to {:?}",
+        req.repo, req.persona
+    );
+
+    if let Some(ref name) = req.persona {
+        if Persona::by_name(name).is_none() {
+            return Err(AppError::bad_request(format!(
+                "Unknown persona '{}'. Available personas: {}",
+                name,
+                Persona::names().join(", ")
+            )));
+        }
+    }
+
+    let repo_url = git::repo_url(&req.repo);
+
+    set_repo_default_persona(&state, &repo_url, req.persona.as_deref())
+        .await
+        .or_internal("Failed to set default persona")
+        .for_repo(&req.repo)?;
+
+    Ok(Json(RepoPersonaResponse {
+        repo: req.repo,
+        persona: req.persona,
+    }))
+}
+
+/// Make a repository's summaries (or one commit's) public or private
+///
+/// Private summaries are only served to requests with a valid API key, on
+/// every read path: REST, SSE and search. Changing visibility requires an
+/// admin key.
+#[utoipa::path(
+    post,
+    path = "/api/repo/visibility",
+    request_body = RepoVisibilityRequest,
+    responses(
+        (status = 200, description = "Visibility updated", body = RepoVisibilityResponse),
+        (status = 400, description = "Unknown visibility", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "Commit not stored", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn set_visibility(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Json(req): Json<RepoVisibilityRequest>,
+) -> Result<Json<RepoVisibilityResponse>, AppError> {
+    viewer.require_scope(ApiScope::Admin)?;
+
+    let visibility = req
+        .visibility
+        .as_deref()
+        .map(str::parse::<Visibility>)
+        .transpose()
+        .map_err(AppError::bad_request)?;
+
+    info!(
+        "Setting visibility for repo: {} (commit {:?}) to {:?}",
+        req.repo, req.commit, visibility
+    );
+
+    let repo_url = git::repo_url(&req.repo);
+
+    let effective = match &req.commit {
+        Some(commit) => {
+            let stored = set_commit_visibility(&state, &repo_url, commit, visibility)
+                .await
+                .or_internal("Failed to set visibility")
+                .for_repo(&req.repo)
+                .at_commit(commit)?;
+            if !stored {
+                return Err(AppError::not_found(format!(
+                    "Commit {} of {} has not been stored",
+                    commit, req.repo
+                )));
+            }
+            get_commit_visibility(&state, &repo_url, commit)
+                .await
+                .or_internal("Failed to check summary visibility")
+                .for_repo(&req.repo)
+                .at_commit(commit)?
+        }
+        None => {
+            let visibility = visibility.unwrap_or_default();
+            set_repo_visibility(&state, &repo_url, visibility)
+                .await
+                .or_internal("Failed to set visibility")
+                .for_repo(&req.repo)?;
+            visibility
+        }
+    };
+    embed::invalidate(&repo_url);
+
+    Ok(Json(RepoVisibilityResponse {
+        repo: req.repo,
+        commit: req.commit,
+        visibility: effective.to_string(),
+    }))
+}
+
+/// Configure the HARDWARE_CHANGELOG.md pull requests for a repository
+///
+/// When enabled, the changelog is regenerated and proposed to the repository
+/// after each webhook run that processed new commits. Requires an admin API key.
+#[utoipa::path(
+    post,
+    path = "/api/repo/changelog/settings",
+    request_body = RepoChangelogSettingsRequest,
+    responses(
+        (status = 200, description = "Changelog settings updated", body = RepoChangelogSettingsResponse),
+        (status = 400, description = "Invalid path or branch", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn set_changelog(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Json(req): Json<RepoChangelogSettingsRequest>,
+) -> Result<Json<RepoChangelogSettingsResponse>, AppError> {
+    viewer.require_scope(ApiScope::Admin)?;
+
+    let path = req.path.as_deref().map(str::trim).filter(|p| !p.is_empty());
+    let branch = req.branch.as_deref().map(str::trim).filter(|b| !b.is_empty());
+    if path.is_some_and(|p| p.starts_with('/') || p.split('/').any(|part| part == "..")) {
+        return Err(AppError::bad_request("path must be relative to the repository root"));
+    }
+    if branch.is_some_and(|b| b.contains(char::is_whitespace) || b.contains("..")) {
+        return Err(AppError::bad_request("branch is not a valid branch name"));
+    }
+
+    info!(
+        "Setting changelog for repo: {} (enabled {}, path {:?}, branch {:?})",
+        req.repo, req.enabled, path, branch
+    );
+
+    let repo_url = git::repo_url(&req.repo);
+    set_changelog_settings(&state, &repo_url, req.enabled, path, branch)
+        .await
+        .or_internal("Failed to set changelog settings")
+        .for_repo(&req.repo)?;
+    let settings = get_changelog_settings(&state, &repo_url)
+        .await
+        .or_internal("Failed to read changelog settings")
+        .for_repo(&req.repo)?;
+
+    Ok(Json(RepoChangelogSettingsResponse {
+        repo: req.repo,
+        enabled: settings.enabled,
+        path: settings.path,
+        branch: settings.branch,
+    }))
+}
+
+/// Generate the hardware changelog, optionally proposing it to the repository
+///
+/// Commit overviews are grouped by the release tag that first contains them,
+/// newest first; only public summaries are included. Publishing opens (or
+/// updates) a pull request from the configured bot branch and requires a
+/// hook-scoped API key and a configured GITHUB_TOKEN.
+#[utoipa::path(
+    post,
+    path = "/api/repo/changelog",
+    request_body = RepoChangelogRequest,
+    responses(
+        (status = 200, description = "Generated changelog", body = RepoChangelogResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Publishing with a read-only API key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn generate_changelog(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Json(req): Json<RepoChangelogRequest>,
+) -> Result<Json<RepoChangelogResponse>, AppError> {
+    let publish = req.publish.unwrap_or(false);
+    if publish {
+        viewer.require_scope(ApiScope::Hook)?;
+        if !github::is_configured() {
+            return Err(AppError::bad_request(
+                "Publishing needs GITHUB_TOKEN to be configured on the server",
+            ));
+        }
+    }
+
+    info!("Generating changelog for repo: {} (publish {})", req.repo, publish);
+
+    let markdown = changelog::generate(&state, &req.repo)
+        .await
+        .or_internal("Failed to generate changelog")
+        .for_repo(&req.repo)?;
+    let pull_request_url = if publish {
+        changelog::publish(&state, &req.repo)
+            .await
+            .or_internal("Failed to publish changelog")
+            .for_repo(&req.repo)?
+    } else {
+        None
+    };
+
+    Ok(Json(RepoChangelogResponse {
+        repo: req.repo,
+        markdown,
+        pull_request_url,
+    }))
+}
@@ -0,0 +1,2690 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
+};
+use chrono::Utc;
+use futures_util::{Stream, StreamExt};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::auth::Viewer;
+use crate::conditional;
+use crate::config::Config;
+use crate::controllers::{grok::checklist_response, resolve_commit};
+use crate::error::{AppError, AppResultExt, ResultExt};
+use crate::services::{
+    anomalies, board as board_service, digest as digest_service, distill, erc as erc_service, events as events_service, file_stream,
+    git::{self, LogFilter},
+    image as image_service, response_cache::ResponseCache, schematic_cache::SchematicCache,
+    schematic_pdf as pdf_service, selection, status, thumbnails,
+};
+use crate::share::{self, ShareGrant};
+use crate::shutdown;
+use crate::state::AppState;
+use crate::types::{
+    BoardResponse, BoardSummary, BomLineItem, CommitStatsItem, DiffWarningItem, CommitStatsResponse, StatsHistoryQuery, StatsHistoryResponse, BoundingBox, ComponentPlacement, ComponentsQuery, ComponentsResponse, BomResponse, CommitChangesResponse, ComponentChangeItem, ComponentHistoryItem, ComponentHistoryResponse, DeleteCommitResponse, ErcFindingItem, ImportRepoResponse, RepoEvent, RepoEventKind, SchematicImageQuery, ThumbnailQuery, UploadImageQuery, UploadImageResponse, TimelineCommit, TimelineQuery, TimelineResponse, ProcessingErrorItem, ProcessingErrorsResponse, PartAlertItem, RepoAlertsQuery, RepoAlertsResponse, ErcResponse, FootprintItem, NetItem,
+    NetChangeItem, NetConnectorItem, NetConnectorKind, NetGeometryResponse, NetNodeItem, NetPinItem, NetSheetGeometry, NetlistResponse, PinChangeItem,
+    PartListQuery, PartListResponse, CategorizedPart, PartCategoryCount, ProjectNetGeometry, ProcessingPolicy, ProjectBom, ProjectChanges, RepoArchiveHeader, ProjectNetlist, ProjectSummary, RegisterRepoRequest,
+    RegisteredRepoItem, RegisteredRepoListResponse, StoredCommit, StoredCommitsQuery,
+    StoredCommitsResponse, SymbolQuery, UnregisterRepoResponse, ReviewChecklistItem, ReviewChecklistResponse,
+    UpdateChecklistItemRequest, CreateShareLinkRequest, ShareLinkResponse, ShareScope, DigestSubscriberItem, DigestSubscriberRequest, DigestSubscribersResponse,
+};
+use crate::validation::Valid;
+use kicad_db::schematic::{hierarchy::natural_key, render_symbol_svg, ConnectorKind, Project};
+use kicad_db::{
+    commit_metadata, commit_processing, processing_failures, ProcessingStatus, count_schematics, get_commit_visibility, export_commits, find_component_history, get_thumbnail, import_commit, get_registered_repo, list_part_alerts, list_registered_repos, retrieve_schematic,
+    register_repo, set_processing_policy, retrieve_board_json, CommitEventKind, retrieve_schematic_image, schematic_image_digest, soft_delete_commit, soft_delete_repo, unregister_repo, ErcSeverity, PgPool, PromptLibrary, SchematicStore,
+    ArchivedCommit, RepoRegistration, ARCHIVE_VERSION, find_image, store_image_in, UpdateSchematic,
+    blob_store, image_key, thumbnail_key, thumbnail_source_digest, get_review_checklist, set_checklist_item_done,
+    load_blob, schematic_pdf_key, store_blob, components_from_distilled, list_components, ComponentRecord,
+    add_digest_subscriber, list_digest_subscribers, remove_digest_subscriber, render_digest,
+    component_categories::TAXONOMY, part_categories, PartCategory, PartKey,
+    retrieve_schematic_stats, schematic_stats_series, SchematicStats, Visibility,
+};
+
+/// Images and files are revalidated with their ETag after this long
+const CACHE_CONTROL: &str = "private, max-age=300";
+/// Largest file returned by the files endpoint; `raw` streams bigger ones
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+/// Longest a timeline page is served from the response cache
+const TIMELINE_TTL: Duration = Duration::from_secs(60);
+/// Commits read from the database at a time while exporting
+const EXPORT_BATCH_SIZE: i64 = 20;
+/// Failed commits listed by the errors endpoint
+const ERROR_LIST_LIMIT: i64 = 200;
+/// Largest archive accepted for import
+pub const MAX_IMPORT_BYTES: usize = 512 * 1024 * 1024;
+/// Largest schematic image accepted for upload
+pub const MAX_IMAGE_BYTES: usize = 32 * 1024 * 1024;
+
+/// List commits stored in the database for a repository (paginated)
+///
+/// Unlike `/api/repo/commits` this reads from the database rather than git,
+/// so it only returns commits that have been processed at least once.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        StoredCommitsQuery
+    ),
+    responses(
+        (status = 200, description = "Page of stored commits", body = StoredCommitsResponse),
+        (status = 400, description = "Invalid pagination parameters", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn list_stored_commits(
+    State(store): State<Arc<dyn SchematicStore>>,
+    viewer: Viewer,
+    Path(repo): Path<String>,
+    Query(query): Query<StoredCommitsQuery>,
+) -> Result<Json<StoredCommitsResponse>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) || offset < 0 {
+        return Err(AppError::bad_request(format!(
+            "limit must be between 1 and {} and offset must be non-negative",
+            MAX_PAGE_SIZE
+        )));
+    }
+
+    info!(
+        "Listing stored commits for {} (limit {}, offset {})",
+        repo, limit, offset
+    );
+
+    let repo_url = git::repo_url(&repo);
+    let total = store
+        .count_schematics(&repo_url)
+        .await
+        .or_internal("Failed to list stored commits")
+        .for_repo(&repo)?;
+    let rows = store
+        .list_schematics(&repo_url, limit, offset, query.order.unwrap_or_default())
+        .await
+        .or_internal("Failed to list stored commits")
+        .for_repo(&repo)?;
+
+    let commits = rows
+        .into_iter()
+        .map(|row| {
+            let visible = viewer.can_see(row.visibility.parse().unwrap_or_default());
+            StoredCommit {
+                commit_hash: row.commit_hash,
+                commit_date: row.commit_date,
+                message: row.git_message,
+                blurb: row.blurb.filter(|_| visible),
+                visibility: row.visibility,
+                status: row.status,
+                timings: row.timings,
+            }
+        })
+        .collect();
+
+    Ok(Json(StoredCommitsResponse {
+        repo,
+        total,
+        limit,
+        offset,
+        commits,
+    }))
+}
+
+/// Show how a component (by reference designator) evolved across stored commits
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/components/{reference}/history",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("reference" = String, Path, description = "Reference designator, e.g. U1")
+    ),
+    responses(
+        (status = 200, description = "Component state at each stored commit", body = ComponentHistoryResponse),
+        (status = 404, description = "Component not found in any stored commit", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn component_history(
+    State(state): State<Arc<PgPool>>,
+    Path((repo, reference)): Path<(String, String)>,
+) -> Result<Json<ComponentHistoryResponse>, AppError> {
+    info!("Fetching history of {} in {}", reference, repo);
+
+    let repo_url = git::repo_url(&repo);
+    let entries = find_component_history(&state, &repo_url, &reference)
+        .await
+        .or_internal("Failed to fetch component history")
+        .for_repo(&repo)?;
+
+    if entries.is_empty() {
+        return Err(AppError::not_found(format!(
+            "Component {} not found in any stored commit of {}",
+            reference, repo
+        )));
+    }
+
+    let mut history: Vec<ComponentHistoryItem> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let changed = match history.last() {
+            Some(prev) => [
+                ("value", prev.value != entry.value),
+                ("footprint", prev.footprint != entry.footprint),
+                ("mpn", prev.mpn != entry.mpn),
+                ("sheet", prev.sheet != entry.sheet),
+            ]
+            .into_iter()
+            .filter(|(_, differs)| *differs)
+            .map(|(field, _)| field.to_string())
+            .collect(),
+            None => Vec::new(),
+        };
+
+        history.push(ComponentHistoryItem {
+            commit_hash: entry.commit_hash,
+            commit_date: entry.commit_date,
+            message: entry.git_message,
+            value: entry.value,
+            footprint: entry.footprint,
+            mpn: entry.mpn,
+            sheet: entry.sheet,
+            changed,
+        });
+    }
+
+    Ok(Json(ComponentHistoryResponse {
+        repo,
+        reference,
+        history,
+    }))
+}
+
+/// The stored design-review checklist of a commit
+///
+/// Checklists are generated by POST /api/grok/review/checklist; this returns
+/// one with the items ticked off so far.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/checklist",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag")
+    ),
+    responses(
+        (status = 200, description = "The commit's review checklist", body = ReviewChecklistResponse),
+        (status = 401, description = "Summaries for this commit are private", body = ApiError),
+        (status = 404, description = "No checklist has been generated for the commit", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn checklist(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Path((repo, commit)): Path<(String, String)>,
+) -> Result<Json<ReviewChecklistResponse>, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    viewer.require_summary_access(&state, &repo, Some(&commit)).await?;
+
+    let (checklist, items) = get_review_checklist(&state, &git::repo_url(&repo), &commit)
+        .await
+        .or_internal("Failed to load the review checklist")
+        .for_repo(&repo)
+        .at_commit(&commit)?
+        .ok_or_else(|| AppError::not_found(format!("No review checklist for commit {} of {}", commit, repo)))?;
+    Ok(Json(checklist_response(repo, checklist, items, true)))
+}
+
+/// Tick off an item of a commit's review checklist, or untick it
+#[utoipa::path(
+    put,
+    path = "/api/repos/{repo}/commits/{commit}/checklist/{item}",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag"),
+        ("item" = i64, Path, description = "Checklist item id")
+    ),
+    request_body = UpdateChecklistItemRequest,
+    responses(
+        (status = 200, description = "The item as updated", body = ReviewChecklistItem),
+        (status = 404, description = "The commit's checklist has no such item", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn update_checklist_item(
+    State(state): State<Arc<PgPool>>,
+    Path((repo, commit, item)): Path<(String, String, i64)>,
+    Json(req): Json<UpdateChecklistItemRequest>,
+) -> Result<Json<ReviewChecklistItem>, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    info!("Marking checklist item {} of {}/{} done: {}", item, repo, commit, req.done);
+
+    let updated = set_checklist_item_done(&state, &git::repo_url(&repo), &commit, item, req.done)
+        .await
+        .or_internal("Failed to update the checklist item")
+        .for_repo(&repo)
+        .at_commit(&commit)?
+        .ok_or_else(|| AppError::not_found(format!("No checklist item {} for commit {} of {}", item, commit, repo)))?;
+    Ok(Json(updated.into()))
+}
+
+/// Electrical rule check findings for a commit
+///
+/// Findings are computed when the commit is distilled. Each one is flagged as
+/// introduced if it is not present at the parent commit.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/erc",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag")
+    ),
+    responses(
+        (status = 200, description = "ERC findings at this commit", body = ErcResponse),
+        (status = 404, description = "Commit has not been distilled", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn erc(
+    State(state): State<Arc<PgPool>>,
+    Path((repo, commit)): Path<(String, String)>,
+) -> Result<Json<ErcResponse>, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    info!("Fetching ERC findings for {}/{}", repo, commit);
+
+    let comparison = erc_service::compare_with_parent(&state, &repo, &commit)
+        .await
+        .or_internal("Failed to load ERC findings")
+        .for_repo(&repo)
+        .at_commit(&commit)?
+        .ok_or_else(|| {
+            AppError::not_found(format!("Commit {} of {} has not been distilled", commit, repo))
+        })?;
+
+    let findings: Vec<ErcFindingItem> = comparison
+        .findings
+        .iter()
+        .enumerate()
+        .map(|(i, f)| ErcFindingItem {
+            rule: f.rule.as_str().to_string(),
+            severity: f.severity().as_str().to_string(),
+            reference: f.reference.clone(),
+            pin: f.pin.clone(),
+            net: f.net.clone(),
+            message: f.message.clone(),
+            introduced: comparison.introduced.as_ref().map(|flags| flags[i]),
+        })
+        .collect();
+
+    let error_count = comparison
+        .findings
+        .iter()
+        .filter(|f| f.severity() == ErcSeverity::Error)
+        .count();
+
+    Ok(Json(ErcResponse {
+        repo,
+        commit,
+        parent_commit: comparison.parent_commit,
+        error_count,
+        warning_count: findings.len() - error_count,
+        findings,
+    }))
+}
+
+/// Parse the repo's schematic projects at a commit, 404 if there are none
+pub(crate) async fn projects_at(schematics: &Arc<SchematicCache>, repo: &str, commit: &str) -> Result<Vec<Project>, AppError> {
+    let projects = distill::load_projects(schematics, repo, commit)
+        .await
+        .or_internal("Failed to parse schematics")
+        .for_repo(repo)
+        .at_commit(commit)?;
+    if projects.is_empty() {
+        return Err(AppError::not_found(format!(
+            "No KiCad schematic found in {} at commit {}",
+            repo, commit
+        )));
+    }
+    Ok(projects)
+}
+
+fn project_summary(project: &Project) -> ProjectSummary {
+    ProjectSummary {
+        name: project.name.clone(),
+        root_file: project.root_file.clone(),
+        sheets: project.instances.iter().map(|i| i.sheet_path.clone()).collect(),
+        missing_sheets: project.missing_files.clone(),
+        unresolved_symbols: project.unresolved_symbols.clone(),
+        formats: project
+            .files
+            .iter()
+            .map(|(file, sheet)| (file.clone(), sheet.format.name().to_string()))
+            .collect(),
+    }
+}
+
+/// Bill of materials for a commit, across every sheet of each project
+///
+/// Parts are grouped by value, footprint and MPN. Sheets used more than once
+/// contribute one set of parts per placement, with their own references.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/bom",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag")
+    ),
+    responses(
+        (status = 200, description = "BOM per project", body = BomResponse),
+        (status = 404, description = "No schematic at this commit", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn bom(
+    State(schematics): State<Arc<SchematicCache>>,
+    State(responses): State<Arc<ResponseCache>>,
+    Path((repo, commit)): Path<(String, String)>,
+) -> Result<Response, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    responses
+        .read_through("bom", &repo, &commit, "", None, true, async {
+            info!("Building BOM for {}/{}", repo, commit);
+            let projects = projects_at(&schematics, &repo, &commit).await?;
+            let projects = projects
+                .iter()
+                .map(|project| ProjectBom {
+                    project: project_summary(project),
+                    lines: project
+                        .bom()
+                        .into_iter()
+                        .map(|line| BomLineItem {
+                            value: line.value,
+                            footprint: line.footprint,
+                            mpn: line.mpn,
+                            lib_id: line.lib_id,
+                            quantity: line.quantity,
+                            references: line.references,
+                            sheets: line.sheets,
+                        })
+                        .collect(),
+                })
+                .collect();
+            Ok(BomResponse {
+                repo: repo.clone(),
+                commit: commit.clone(),
+                projects,
+            })
+        })
+        .await
+}
+
+/// Board layout for a commit: stackup, footprint placements and routing stats
+///
+/// Served from the summary stored when the commit was processed, or parsed
+/// from the .kicad_pcb files if the commit hasn't been processed yet.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/board",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag")
+    ),
+    responses(
+        (status = 200, description = "Layout summary per board file", body = BoardResponse),
+        (status = 404, description = "No board file at this commit", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn board(
+    State(state): State<Arc<PgPool>>,
+    Path((repo, commit)): Path<(String, String)>,
+) -> Result<Json<BoardResponse>, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    info!("Fetching board layout for {}/{}", repo, commit);
+
+    let stored = retrieve_board_json(&state, &git::repo_url(&repo), &commit)
+        .await
+        .or_internal("Failed to load board summary")
+        .for_repo(&repo)
+        .at_commit(&commit)?;
+    let boards = match stored {
+        Some(value) => board_service::from_json(value),
+        None => board_service::load_boards(&repo, &commit).await,
+    }
+    .or_internal("Failed to read board layout")
+    .for_repo(&repo)
+    .at_commit(&commit)?;
+
+    if boards.is_empty() {
+        return Err(AppError::not_found(format!(
+            "No KiCad board found in {} at commit {}",
+            repo, commit
+        )));
+    }
+
+    let boards = boards
+        .into_iter()
+        .map(|board| BoardSummary {
+            file: board.file,
+            layer_count: board.layer_count,
+            copper_layers: board.copper_layers,
+            track_segments: board.tracks.segments,
+            track_arcs: board.tracks.arcs,
+            track_length_mm: board.tracks.total_length_mm,
+            track_length_by_layer: board.tracks.length_by_layer,
+            vias: board.vias.count,
+            vias_by_type: board.vias.by_type,
+            zones: board.zones,
+            footprints: board
+                .footprints
+                .into_iter()
+                .map(|f| FootprintItem {
+                    reference: f.reference,
+                    value: f.value,
+                    footprint: f.footprint,
+                    layer: f.layer,
+                    x: f.x,
+                    y: f.y,
+                    rotation: f.rotation,
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(Json(BoardResponse {
+        repo,
+        commit,
+        boards,
+    }))
+}
+
+/// Size of a commit's schematics: components, nets, sheets, unique parts and
+/// a complexity score
+///
+/// Served from the counts stored when the commit was processed, or counted
+/// from the parsed schematics if the commit hasn't been processed yet.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/stats",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag")
+    ),
+    responses(
+        (status = 200, description = "Counts over every project", body = CommitStatsResponse),
+        (status = 404, description = "No schematic at this commit", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn commit_stats(
+    State(state): State<Arc<PgPool>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    Path((repo, commit)): Path<(String, String)>,
+) -> Result<Json<CommitStatsResponse>, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    info!("Fetching schematic stats for {}/{}", repo, commit);
+
+    let stored = retrieve_schematic_stats(&state, &git::repo_url(&repo), &commit)
+        .await
+        .or_internal("Failed to load schematic stats")
+        .for_repo(&repo)
+        .at_commit(&commit)?;
+    let stats = match stored {
+        Some(stats) => stats,
+        None => SchematicStats::of_projects(&projects_at(&schematics, &repo, &commit).await?),
+    };
+    Ok(Json(CommitStatsResponse {
+        repo,
+        commit,
+        stats: stats.into(),
+    }))
+}
+
+/// How a repository's schematics grew: the stats of its latest processed
+/// commits, oldest first, for charting
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/stats",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        StatsHistoryQuery
+    ),
+    responses(
+        (status = 200, description = "Stats per commit", body = StatsHistoryResponse),
+        (status = 400, description = "Invalid limit", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn stats_history(
+    State(state): State<Arc<PgPool>>,
+    Path(repo): Path<String>,
+    Query(query): Query<StatsHistoryQuery>,
+) -> Result<Json<StatsHistoryResponse>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::bad_request(format!("limit must be between 1 and {}", MAX_PAGE_SIZE)));
+    }
+    let commits = schematic_stats_series(&state, &git::repo_url(&repo), limit)
+        .await
+        .or_internal("Failed to load schematic stats")
+        .for_repo(&repo)?
+        .into_iter()
+        .map(|c| CommitStatsItem {
+            commit_hash: c.commit_hash,
+            commit_date: c.commit_date,
+            stats: c.stats.into(),
+        })
+        .collect();
+    Ok(Json(StatsHistoryResponse { repo, commits }))
+}
+
+/// Netlist for a commit, with nets followed across sheet boundaries
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/netlist",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag")
+    ),
+    responses(
+        (status = 200, description = "Netlist per project", body = NetlistResponse),
+        (status = 404, description = "No schematic at this commit", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn netlist(
+    State(schematics): State<Arc<SchematicCache>>,
+    State(responses): State<Arc<ResponseCache>>,
+    Path((repo, commit)): Path<(String, String)>,
+) -> Result<Response, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    responses
+        .read_through("netlist", &repo, &commit, "", None, true, async {
+            info!("Building netlist for {}/{}", repo, commit);
+            let projects = projects_at(&schematics, &repo, &commit).await?;
+            let projects = projects
+                .iter()
+                .map(|project| ProjectNetlist {
+                    project: project_summary(project),
+                    nets: project
+                        .netlist()
+                        .into_iter()
+                        .map(|net| NetItem {
+                            name: net.name,
+                            nodes: net
+                                .nodes
+                                .into_iter()
+                                .map(|node| NetNodeItem {
+                                    reference: node.reference,
+                                    pin: node.pin,
+                                    pin_name: node.pin_name,
+                                    pin_type: node.pin_type,
+                                })
+                                .collect(),
+                        })
+                        .collect(),
+                })
+                .collect();
+            Ok(NetlistResponse {
+                repo: repo.clone(),
+                commit: commit.clone(),
+                projects,
+            })
+        })
+        .await
+}
+
+/// Where each component of a commit is placed, for jumping to it in the viewer
+///
+/// Gives each component's sheet, anchor, rotation and bounding box. Served
+/// from the components stored when the commit was processed, or parsed from
+/// the schematics if it hasn't been (or was stored before placements were
+/// recorded). `sheet` and an `x1`/`y1`/`x2`/`y2` area narrow the list down.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/components",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag"),
+        ComponentsQuery
+    ),
+    responses(
+        (status = 200, description = "Component placements", body = ComponentsResponse),
+        (status = 400, description = "Only part of an area was given", body = ApiError),
+        (status = 404, description = "No schematic at this commit", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn components(
+    State(state): State<Arc<PgPool>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    Path((repo, commit)): Path<(String, String)>,
+    Query(query): Query<ComponentsQuery>,
+) -> Result<Json<ComponentsResponse>, AppError> {
+    let area = match (query.x1, query.y1, query.x2, query.y2) {
+        (Some(x1), Some(y1), Some(x2), Some(y2)) => Some((x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2))),
+        (None, None, None, None) => None,
+        _ => return Err(AppError::bad_request("An area needs all of x1, y1, x2 and y2")),
+    };
+    let commit = resolve_commit(&repo, &commit).await?;
+    info!("Listing component placements for {}/{}", repo, commit);
+
+    let stored = list_components(&state, &git::repo_url(&repo), &commit)
+        .await
+        .or_internal("Failed to load stored components")
+        .for_repo(&repo)
+        .at_commit(&commit)?;
+    let mut records: Vec<ComponentRecord> = match stored.iter().any(|c| c.x.is_some()) {
+        true => stored,
+        false => projects_at(&schematics, &repo, &commit)
+            .await?
+            .iter()
+            .flat_map(|project| components_from_distilled(&project.to_distilled()))
+            .collect(),
+    };
+    records.sort_by_key(|c| natural_key(&c.reference));
+
+    let sheet = query.sheet.as_deref().map(selection::sheet_path);
+    let components = records
+        .into_iter()
+        .map(|c| {
+            let bbox = match (c.bbox_min_x, c.bbox_min_y, c.bbox_max_x, c.bbox_max_y) {
+                (Some(x1), Some(y1), Some(x2), Some(y2)) => Some(BoundingBox { x1, y1, x2, y2 }),
+                _ => None,
+            };
+            ComponentPlacement {
+                reference: c.reference,
+                value: c.value,
+                footprint: c.footprint,
+                mpn: c.mpn,
+                sheet: c.sheet,
+                x: c.x,
+                y: c.y,
+                rotation: c.rotation,
+                bbox,
+            }
+        })
+        .filter(|c| sheet.is_none() || c.sheet.as_deref().or(Some("/")) == sheet.as_deref())
+        .filter(|c| match area {
+            None => true,
+            Some(area) => selection::overlaps(area, c.bbox.map(|b| (b.x1, b.y1, b.x2, b.y2)), c.x.zip(c.y)),
+        })
+        .collect();
+
+    Ok(Json(ComponentsResponse {
+        repo,
+        commit,
+        components,
+    }))
+}
+
+/// List a commit's distinct parts with their place in the component taxonomy
+///
+/// Components sharing a value, footprint and MPN are one part. Parts are
+/// classified in the background by a cheap model, so ones seen for the first
+/// time have no category for a few minutes. `category` narrows the list to a
+/// category ("regulator") or subcategory ("regulator/ldo", or just "ldo").
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/components",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        PartListQuery
+    ),
+    responses(
+        (status = 200, description = "The commit's parts and their categories", body = PartListResponse),
+        (status = 400, description = "Unknown category", body = ApiError),
+        (status = 404, description = "No schematic at this commit", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn parts(
+    State(state): State<Arc<PgPool>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    Path(repo): Path<String>,
+    Query(query): Query<PartListQuery>,
+) -> Result<Json<PartListResponse>, AppError> {
+    let filter = match query.category.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+        // A subcategory outside the taxonomy doesn't quietly widen to its category
+        Some(name) => Some(
+            PartCategory::parse(name)
+                .filter(|parsed| !name.contains('/') || parsed.subcategory.is_some())
+                .ok_or_else(|| {
+                    let known: Vec<&str> = TAXONOMY.iter().map(|(category, _)| *category).collect();
+                    AppError::bad_request(format!(
+                        "Unknown category \"{}\"; expected one of {} or one of their subcategories",
+                        name,
+                        known.join(", ")
+                    ))
+                })?,
+        ),
+        None => None,
+    };
+    let commit = match query.commit {
+        Some(commit) => resolve_commit(&repo, &commit).await?,
+        None => git::get_latest_commit(&repo)
+            .await
+            .or_internal("Failed to fetch latest commit")
+            .for_repo(&repo)?,
+    };
+    info!("Listing categorized parts for {}/{}", repo, commit);
+
+    let stored = list_components(&state, &git::repo_url(&repo), &commit)
+        .await
+        .or_internal("Failed to load stored components")
+        .for_repo(&repo)
+        .at_commit(&commit)?;
+    let records: Vec<ComponentRecord> = match stored.is_empty() {
+        false => stored,
+        true => projects_at(&schematics, &repo, &commit)
+            .await?
+            .iter()
+            .flat_map(|project| components_from_distilled(&project.to_distilled()))
+            .collect(),
+    };
+    let mut grouped: HashMap<PartKey, (ComponentRecord, Vec<String>)> = HashMap::new();
+    for record in records {
+        let entry = grouped.entry(PartKey::of(&record)).or_insert_with(|| (record.clone(), Vec::new()));
+        entry.1.push(record.reference);
+    }
+    let keys: Vec<PartKey> = grouped.keys().cloned().collect();
+    let categories = part_categories(&state, &keys)
+        .await
+        .or_internal("Failed to load part categories")
+        .for_repo(&repo)?;
+
+    let mut counts: Vec<PartCategoryCount> = Vec::new();
+    let mut unclassified = 0;
+    let mut parts: Vec<CategorizedPart> = grouped
+        .into_iter()
+        .map(|(key, (record, mut references))| {
+            references.sort_by_key(|reference| natural_key(reference));
+            let category = categories.get(&key);
+            match category {
+                Some(category) => match counts.iter_mut().find(|c| c.category == category.category) {
+                    Some(count) => {
+                        count.parts += 1;
+                        count.quantity += references.len();
+                    }
+                    None => counts.push(PartCategoryCount {
+                        category: category.category.clone(),
+                        parts: 1,
+                        quantity: references.len(),
+                    }),
+                },
+                None => unclassified += 1,
+            }
+            CategorizedPart {
+                value: record.value,
+                footprint: record.footprint,
+                mpn: record.mpn,
+                category: category.map(|c| c.category.clone()),
+                subcategory: category.and_then(|c| c.subcategory.clone()),
+                quantity: references.len(),
+                references,
+            }
+        })
+        .filter(|part| match &filter {
+            None => true,
+            Some(filter) => {
+                part.category.as_deref() == Some(filter.category.as_str())
+                    && (filter.subcategory.is_none() || part.subcategory == filter.subcategory)
+            }
+        })
+        .collect();
+    // Taxonomy order, unclassified parts last
+    let rank = |category: Option<&str>| {
+        category
+            .and_then(|name| TAXONOMY.iter().position(|(known, _)| *known == name))
+            .unwrap_or(TAXONOMY.len())
+    };
+    parts.sort_by(|a, b| {
+        (rank(a.category.as_deref()), &a.subcategory, &a.value, &a.footprint, &a.mpn)
+            .cmp(&(rank(b.category.as_deref()), &b.subcategory, &b.value, &b.footprint, &b.mpn))
+    });
+    counts.sort_by_key(|count| rank(Some(&count.category)));
+
+    Ok(Json(PartListResponse {
+        repo,
+        commit,
+        parts,
+        categories: counts,
+        unclassified,
+    }))
+}
+
+/// Where a net is drawn at a commit, for highlighting it in the viewer
+///
+/// Returns the net's wire segments, junctions, component pins and connectors
+/// (labels, sheet pins, power symbols) on every sheet it reaches, with the
+/// sheets each connector carries it on to. Local nets may be named without
+/// their leading "/".
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/nets/{net}/geometry",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag"),
+        ("net" = String, Path, description = "URL-encoded net name as in the netlist, e.g. %2FPower%2FVIN")
+    ),
+    responses(
+        (status = 200, description = "The net's geometry per project", body = NetGeometryResponse),
+        (status = 404, description = "No schematic at this commit, or no such net", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn net_geometry(
+    State(schematics): State<Arc<SchematicCache>>,
+    Path((repo, commit, net)): Path<(String, String, String)>,
+) -> Result<Json<NetGeometryResponse>, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    info!("Finding net {} in {}/{}", net, repo, commit);
+
+    let projects = projects_at(&schematics, &repo, &commit).await?;
+    let point = |(x, y): (f64, f64)| [x, y];
+    let projects: Vec<ProjectNetGeometry> = projects
+        .iter()
+        .filter_map(|project| {
+            let geometry = project
+                .net_geometry(&net)
+                .or_else(|| project.net_geometry(&format!("/{}", net)))?;
+            Some(ProjectNetGeometry {
+                project: project_summary(project),
+                sheets: geometry
+                    .sheets
+                    .into_iter()
+                    .map(|sheet| NetSheetGeometry {
+                        sheet_path: sheet.sheet_path,
+                        file: sheet.file,
+                        wires: sheet.wires.into_iter().map(|(a, b)| [point(a), point(b)]).collect(),
+                        junctions: sheet.junctions.into_iter().map(point).collect(),
+                        pins: sheet
+                            .pins
+                            .into_iter()
+                            .map(|pin| NetPinItem {
+                                reference: pin.reference,
+                                pin: pin.pin,
+                                at: point(pin.at),
+                            })
+                            .collect(),
+                        connectors: sheet
+                            .connectors
+                            .into_iter()
+                            .map(|connector| NetConnectorItem {
+                                kind: match connector.kind {
+                                    ConnectorKind::LocalLabel => NetConnectorKind::LocalLabel,
+                                    ConnectorKind::GlobalLabel => NetConnectorKind::GlobalLabel,
+                                    ConnectorKind::HierarchicalLabel => NetConnectorKind::HierarchicalLabel,
+                                    ConnectorKind::SheetPin => NetConnectorKind::SheetPin,
+                                    ConnectorKind::PowerSymbol => NetConnectorKind::PowerSymbol,
+                                },
+                                name: connector.name,
+                                at: point(connector.at),
+                                continues_to: connector.continues_to,
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            })
+        })
+        .collect();
+    if projects.is_empty() {
+        return Err(AppError::not_found(format!("No net {} in {} at commit {}", net, repo, commit)));
+    }
+
+    Ok(Json(NetGeometryResponse {
+        repo,
+        commit,
+        net,
+        projects,
+    }))
+}
+
+/// Everything a commit changed, in one payload
+///
+/// Combines the files git reports as touched, the component- and net-level
+/// diff of each project against the parent commit, the ERC violations the
+/// commit introduced and its stored AI blurb. The parts are fetched
+/// concurrently; the ERC findings and blurb are null until the commit has
+/// been processed.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/changes",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag")
+    ),
+    responses(
+        (status = 200, description = "Changes made by the commit", body = CommitChangesResponse),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn changes(
+    State(state): State<Arc<PgPool>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    viewer: Viewer,
+    Path((repo, commit)): Path<(String, String)>,
+) -> Result<Json<CommitChangesResponse>, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    info!("Assembling changes for {}/{}", repo, commit);
+    let changes = commit_changes(&state, &schematics, repo, commit, |visibility| viewer.can_see(visibility)).await?;
+    Ok(Json(changes))
+}
+
+/// The changes payload for a resolved commit; the blurb and description are
+/// only filled in if `can_see` allows the summary's visibility
+pub(crate) async fn commit_changes(
+    state: &PgPool,
+    schematics: &Arc<SchematicCache>,
+    repo: String,
+    commit: String,
+    can_see: impl FnOnce(Visibility) -> bool,
+) -> Result<CommitChangesResponse, AppError> {
+    let repo_url = git::repo_url(&repo);
+    let (info, parent_commit, changed_files, (previous, current), erc, stored, visibility) = tokio::try_join!(
+        async {
+            git::get_commit_info(&repo, &commit)
+                .await
+                .or_internal("Failed to fetch commit info")
+        },
+        async {
+            git::get_parent_commit(&repo, &commit)
+                .await
+                .or_internal("Failed to resolve parent commit")
+        },
+        async {
+            git::get_changed_schematic_files(&repo, &commit)
+                .await
+                .or_internal("Failed to fetch changed files")
+        },
+        async {
+            distill::projects_around(schematics, &repo, &commit)
+                .await
+                .or_internal("Failed to diff schematics")
+        },
+        async {
+            erc_service::compare_with_parent(state, &repo, &commit)
+                .await
+                .or_internal("Failed to load ERC findings")
+        },
+        async {
+            retrieve_schematic(state, &repo_url, &commit)
+                .await
+                .or_internal("Failed to load stored summary")
+        },
+        async {
+            get_commit_visibility(state, &repo_url, &commit)
+                .await
+                .or_internal("Failed to check summary visibility")
+        },
+    )
+    .for_repo(&repo)
+    .at_commit(&commit)?;
+
+    let project_changes = distill::diff_project_sets(&previous, &current);
+    let warnings = anomalies::detect(&previous, &current, &project_changes)
+        .into_iter()
+        .map(DiffWarningItem::from)
+        .collect();
+    let projects = project_changes
+        .into_iter()
+        .map(|(root_file, diff)| ProjectChanges {
+            root_file,
+            added_sheets: diff.added_sheets,
+            removed_sheets: diff.removed_sheets,
+            added_components: diff.added_components,
+            removed_components: diff.removed_components,
+            changed_components: diff
+                .changed_components
+                .into_iter()
+                .map(|c| ComponentChangeItem {
+                    reference: c.reference,
+                    field: c.field,
+                    old: c.old,
+                    new: c.new,
+                })
+                .collect(),
+            changed_pins: diff
+                .changed_pins
+                .into_iter()
+                .map(|p| PinChangeItem {
+                    reference: p.reference,
+                    pin: p.pin,
+                    old_net: p.old_net,
+                    new_net: p.new_net,
+                })
+                .collect(),
+            added_nets: diff.added_nets,
+            removed_nets: diff.removed_nets,
+            changed_nets: diff
+                .changed_nets
+                .into_iter()
+                .map(|n| NetChangeItem {
+                    name: n.name,
+                    added: n.added,
+                    removed: n.removed,
+                })
+                .collect(),
+        })
+        .collect();
+
+    // Only attributable once the parent has been analyzed too
+    let new_erc_findings = erc.filter(|c| c.introduced.is_some()).map(|comparison| {
+        comparison
+            .introduced_findings()
+            .into_iter()
+            .map(|f| ErcFindingItem {
+                rule: f.rule.as_str().to_string(),
+                severity: f.severity().as_str().to_string(),
+                reference: f.reference.clone(),
+                pin: f.pin.clone(),
+                net: f.net.clone(),
+                message: f.message.clone(),
+                introduced: Some(true),
+            })
+            .collect()
+    });
+
+    let (blurb, description) = match stored {
+        Some(s) if can_see(visibility) => (s.blurb, s.description),
+        _ => (None, None),
+    };
+
+    Ok(CommitChangesResponse {
+        repo,
+        commit,
+        parent_commit,
+        commit_date: info.commit_date,
+        author: info.author,
+        message: info.message,
+        changed_files,
+        projects,
+        new_erc_findings,
+        warnings,
+        blurb,
+        description,
+    })
+}
+
+/// Mint a share link to read-only views of a commit
+///
+/// The link opens the requested views (the summary, the diff, the SVG
+/// sheets; all of them by default) to anyone holding it, without an API
+/// key, until it expires. Sharing the summary needs access to it. Links
+/// aren't stored, so one can't be revoked short of changing the server's
+/// share secret.
+#[utoipa::path(
+    post,
+    path = "/api/repos/{repo}/commits/{commit}/share",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag")
+    ),
+    request_body = CreateShareLinkRequest,
+    responses(
+        (status = 200, description = "The share link", body = ShareLinkResponse),
+        (status = 400, description = "No views, or an expiry past the server's maximum", body = ApiError),
+        (status = 401, description = "The summary is private", body = ApiError),
+        (status = 503, description = "Share links are turned off", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn share_commit(
+    State(app): State<AppState>,
+    viewer: Viewer,
+    Path((repo, commit)): Path<(String, String)>,
+    Json(req): Json<CreateShareLinkRequest>,
+) -> Result<Json<ShareLinkResponse>, AppError> {
+    let settings = app.config().share.clone();
+    if settings.secret.is_empty() {
+        return Err(AppError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "not_configured",
+            "Share links are turned off: set SHARE_LINK_SECRET",
+        ));
+    }
+    let mut scopes = Vec::new();
+    for scope in req.scopes.unwrap_or_else(|| vec![ShareScope::Summary, ShareScope::Diff, ShareScope::Svg]) {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    if scopes.is_empty() {
+        return Err(AppError::bad_request("A share link needs at least one of summary, diff or svg"));
+    }
+    let ttl = req.expires_in_secs.unwrap_or(settings.default_ttl_secs);
+    if ttl == 0 || ttl > settings.max_ttl_secs {
+        return Err(AppError::bad_request(format!(
+            "Share links last 1 to {} seconds",
+            settings.max_ttl_secs
+        )));
+    }
+
+    let commit = resolve_commit(&repo, &commit).await?;
+    if scopes.contains(&ShareScope::Summary) {
+        viewer.require_summary_access(&app.pool, &repo, Some(&commit)).await?;
+    }
+    info!("Sharing {}/{} ({:?}) for {}s", repo, commit, scopes, ttl);
+
+    let grant = ShareGrant {
+        repo,
+        commit,
+        scopes,
+        expires_at: Utc::now() + chrono::Duration::seconds(ttl as i64),
+    };
+    let token = share::mint(&settings.secret, &grant);
+    Ok(Json(ShareLinkResponse {
+        path: format!("/api/shared/{}", token),
+        repo: grant.repo,
+        commit: grant.commit,
+        scopes: grant.scopes,
+        expires_at: grant.expires_at,
+        token,
+    }))
+}
+
+fn etag_headers(etag: &str) -> [(header::HeaderName, HeaderValue); 2] {
+    [
+        (header::ETAG, HeaderValue::from_str(etag).expect("ETag is ASCII")),
+        (header::CACHE_CONTROL, HeaderValue::from_static(CACHE_CONTROL)),
+    ]
+}
+
+/// A redirect to a presigned URL for `key`, so the browser downloads it
+/// from the blob store directly; None if the store can't presign or
+/// presigning is turned off
+fn presigned_redirect(config: &Config, key: &str) -> Option<Response> {
+    if config.blobs.presign_secs == 0 {
+        return None;
+    }
+    let url = blob_store().presigned_url(key, Duration::from_secs(config.blobs.presign_secs))?;
+    let location = HeaderValue::from_str(&url).ok()?;
+    Some(
+        (
+            StatusCode::TEMPORARY_REDIRECT,
+            [(header::LOCATION, location), (header::CACHE_CONTROL, HeaderValue::from_static("no-store"))],
+        )
+            .into_response(),
+    )
+}
+
+/// The stored schematic image of a commit, optionally as a thumbnail
+///
+/// Served with an ETag derived from the image (and width), so revalidating
+/// with `If-None-Match` costs a 304 and no image transfer. With an S3 blob
+/// store, unscaled images redirect to a short-lived presigned URL instead.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/image",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag from an earlier response"),
+        SchematicImageQuery
+    ),
+    responses(
+        (status = 200, description = "The image", content_type = "image/png"),
+        (status = 304, description = "The cached copy is current"),
+        (status = 307, description = "Download it from the presigned URL in Location"),
+        (status = 400, description = "Width out of range", body = ApiError),
+        (status = 404, description = "No image stored for this commit", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn schematic_image(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    Path((repo, commit)): Path<(String, String)>,
+    Query(query): Query<SchematicImageQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    if let Some(width) = query.width {
+        if !(image_service::MIN_THUMBNAIL_WIDTH..=image_service::MAX_THUMBNAIL_WIDTH).contains(&width) {
+            return Err(AppError::bad_request(format!(
+                "width must be between {} and {}",
+                image_service::MIN_THUMBNAIL_WIDTH,
+                image_service::MAX_THUMBNAIL_WIDTH
+            )));
+        }
+    }
+
+    let repo_url = git::repo_url(&repo);
+    let not_found = || AppError::not_found(format!("No schematic image stored for {} at {}", repo, commit));
+    let digest = schematic_image_digest(&state, &repo_url, &commit)
+        .await
+        .or_internal("Failed to look up schematic image")
+        .for_repo(&repo)
+        .at_commit(&commit)?
+        .ok_or_else(not_found)?;
+    let etag = match query.width {
+        Some(width) => format!("\"{}-w{}\"", digest, width),
+        None => format!("\"{}\"", digest),
+    };
+    let cache_headers = etag_headers(&etag);
+    if conditional::matches(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+    // Scaled copies are made here
+    if query.width.is_none() {
+        if let Some(redirect) = presigned_redirect(&config, &image_key(&digest)) {
+            return Ok(redirect);
+        }
+    }
+
+    let bytes = retrieve_schematic_image(&state, &repo_url, &commit)
+        .await
+        .or_internal("Failed to load schematic image")
+        .for_repo(&repo)
+        .at_commit(&commit)?
+        .ok_or_else(not_found)?;
+    let content_type = image_service::content_type(&bytes).unwrap_or("application/octet-stream");
+    let bytes = match query.width {
+        Some(width) => {
+            let original = bytes.clone();
+            tokio::task::spawn_blocking(move || image_service::thumbnail(&original, width))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|result| result)
+                .or_internal("Failed to scale schematic image")
+                .for_repo(&repo)
+                .at_commit(&commit)?
+                .unwrap_or(bytes)
+        }
+        None => bytes,
+    };
+
+    Ok((
+        [(header::CONTENT_TYPE, HeaderValue::from_static(content_type))],
+        cache_headers,
+        bytes,
+    )
+        .into_response())
+}
+
+/// Store a rendered schematic image for a commit
+///
+/// The body is the PNG or SVG itself. Images are stored once per content
+/// (keyed by sha256), so a render identical to an earlier commit's stores
+/// nothing new; with the digest from an earlier response, `?digest=` and an
+/// empty body attach that image without uploading it again. Replaces the
+/// commit's image, if it had one. Requires a hook key.
+#[utoipa::path(
+    put,
+    path = "/api/repos/{repo}/commits/{commit}/image",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag"),
+        UploadImageQuery
+    ),
+    request_body(content = Vec<u8>, description = "The rendered PNG or SVG; empty with `digest`", content_type = "image/png"),
+    responses(
+        (status = 200, description = "Image stored, or attached by digest", body = UploadImageResponse),
+        (status = 400, description = "Not a PNG or SVG, or a malformed digest", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key lacks the hook scope", body = ApiError),
+        (status = 404, description = "Commit not found, or no image stored with the digest", body = ApiError),
+        (status = 413, description = "Image too large"),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn upload_image(
+    State(state): State<Arc<PgPool>>,
+    Path((repo, commit)): Path<(String, String)>,
+    Query(query): Query<UploadImageQuery>,
+    body: Bytes,
+) -> Result<Json<UploadImageResponse>, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    let update = UpdateSchematic::new(git::repo_url(&repo), &commit);
+
+    let (image, reused) = match query.digest {
+        Some(digest) => {
+            if !body.is_empty() {
+                return Err(AppError::bad_request("Send either an image or a digest, not both"));
+            }
+            if digest.len() != 64 || !digest.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+                return Err(AppError::bad_request("digest must be a lowercase hex sha256"));
+            }
+            let not_stored = || AppError::not_found(format!("No image stored with digest {}; upload it instead", digest));
+            let image = find_image(&state, &digest)
+                .await
+                .or_internal("Failed to look up image")?
+                .ok_or_else(not_stored)?;
+            match update.update_image_digest(&digest).execute(&state).await {
+                Ok(_) => {}
+                // Purged since the lookup
+                Err(sqlx::Error::RowNotFound) => return Err(not_stored()),
+                Err(e) => return Err(AppError::internal("Failed to store schematic image").with_source(e)),
+            }
+            (image, true)
+        }
+        None => {
+            if !matches!(image_service::content_type(&body), Some("image/png" | "image/svg+xml")) {
+                return Err(AppError::bad_request("The body must be a PNG or SVG image"));
+            }
+            async {
+                let mut tx = state.begin().await?;
+                let (image, reused) = store_image_in(&mut tx, &body).await?;
+                update.update_image_digest(&image.digest).execute_in(&mut tx).await?;
+                tx.commit().await?;
+                Ok::<_, sqlx::Error>((image, reused))
+            }
+            .await
+            .or_internal("Failed to store schematic image")
+            .for_repo(&repo)
+            .at_commit(&commit)?
+        }
+    };
+    info!(
+        "Stored schematic image {} for {} at {} ({})",
+        image.digest,
+        repo,
+        commit,
+        if reused { "reused" } else { "new" }
+    );
+    thumbnails::wake();
+
+    Ok(Json(UploadImageResponse {
+        repo,
+        commit,
+        digest: image.digest,
+        content_type: image.content_type,
+        size: image.size,
+        reused,
+    }))
+}
+
+/// A pre-rendered PNG thumbnail of a commit's schematic image
+///
+/// Thumbnails are rendered in the background shortly after an image is
+/// stored (SVGs are rasterized), so they are cheap to serve while scrolling
+/// the timeline. Until then this returns 404; fall back to `/image?width=`.
+/// With an S3 blob store this redirects to a presigned URL.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/thumbnail",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag from an earlier response"),
+        ThumbnailQuery
+    ),
+    responses(
+        (status = 200, description = "The thumbnail", content_type = "image/png"),
+        (status = 304, description = "The cached copy is current"),
+        (status = 307, description = "Download it from the presigned URL in Location"),
+        (status = 400, description = "Unsupported width", body = ApiError),
+        (status = 404, description = "No thumbnail rendered for this commit (yet)", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn thumbnail(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    Path((repo, commit)): Path<(String, String)>,
+    Query(query): Query<ThumbnailQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    let width = query.width.unwrap_or(thumbnails::THUMBNAIL_WIDTHS[0]);
+    if !thumbnails::THUMBNAIL_WIDTHS.contains(&width) {
+        return Err(AppError::bad_request(format!(
+            "width must be one of {:?}",
+            thumbnails::THUMBNAIL_WIDTHS
+        )));
+    }
+
+    let repo_url = git::repo_url(&repo);
+    let not_found = || AppError::not_found(format!("No thumbnail rendered for {} at {}", repo, commit));
+    let source_digest = thumbnail_source_digest(&state, &repo_url, &commit, width)
+        .await
+        .or_internal("Failed to look up thumbnail")
+        .for_repo(&repo)
+        .at_commit(&commit)?
+        .ok_or_else(not_found)?;
+
+    let etag = format!("\"{}-t{}\"", source_digest, width);
+    let cache_headers = etag_headers(&etag);
+    if conditional::matches(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+    if let Some(redirect) = presigned_redirect(&config, &thumbnail_key(&source_digest, width)) {
+        return Ok(redirect);
+    }
+
+    let thumbnail = get_thumbnail(&state, &repo_url, &commit, width)
+        .await
+        .or_internal("Failed to load thumbnail")
+        .for_repo(&repo)
+        .at_commit(&commit)?
+        .ok_or_else(not_found)?;
+    Ok((
+        [(header::CONTENT_TYPE, HeaderValue::from_static("image/png"))],
+        cache_headers,
+        thumbnail.image,
+    )
+        .into_response())
+}
+
+/// One schematic symbol drawn as SVG, with its pin names and numbers
+///
+/// The definition comes from the sheet's embedded library, the repo's
+/// .kicad_sym libraries or the standard KiCad libraries; a symbol none of them
+/// define is drawn as a placeholder box.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/symbols/{reference}",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag"),
+        ("reference" = String, Path, description = "Component reference, e.g. U3"),
+        SymbolQuery
+    ),
+    responses(
+        (status = 200, description = "Symbol drawing", content_type = "image/svg+xml", body = String),
+        (status = 404, description = "No such component or unit at this commit", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn symbol_svg(
+    State(schematics): State<Arc<SchematicCache>>,
+    Path((repo, commit, reference)): Path<(String, String, String)>,
+    Query(query): Query<SymbolQuery>,
+) -> Result<Response, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    info!("Rendering symbol {} for {}/{}", reference, repo, commit);
+
+    let projects = projects_at(&schematics, &repo, &commit).await?;
+    let units: Vec<_> = projects
+        .iter()
+        .flat_map(|project| project.symbol_units(&reference))
+        .collect();
+    let (symbol, lib) = *units.first().ok_or_else(|| {
+        AppError::not_found(format!("No component {} in {} at commit {}", reference, repo, commit))
+    })?;
+
+    let unit = query.unit.unwrap_or(symbol.unit);
+    let defined = match lib {
+        Some(lib) => lib.units().contains(&unit),
+        None => units.iter().any(|(s, _)| s.unit == unit),
+    };
+    if !defined {
+        return Err(AppError::not_found(format!("{} has no unit {}", reference, unit)));
+    }
+
+    let caption = format!("{} {}", reference, symbol.lib_id);
+    let svg = render_symbol_svg(lib, unit, symbol.body_style, &caption);
+    Ok((
+        [(header::CONTENT_TYPE, HeaderValue::from_static("image/svg+xml; charset=utf-8"))],
+        svg,
+    )
+        .into_response())
+}
+
+/// The schematic at a commit as a PDF, one page per sheet
+///
+/// Each sheet is drawn on the smallest landscape paper it fits, with a title
+/// block giving the repo, commit, commit date and page. The PDF is drawn on
+/// the first request and stored; later ones are served from the store.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/schematic.pdf",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag from an earlier response")
+    ),
+    responses(
+        (status = 200, description = "The schematic", content_type = "application/pdf"),
+        (status = 304, description = "The cached copy is current"),
+        (status = 404, description = "No KiCad schematic at this commit", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn schematic_pdf(
+    State(state): State<Arc<PgPool>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    Path((repo, commit)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    let etag = format!("\"{}-pdf{}\"", commit, pdf_service::RENDERER_VERSION);
+    let cache_headers = etag_headers(&etag);
+    if conditional::matches(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+
+    let key = schematic_pdf_key(&git::repo_url(&repo), &commit, pdf_service::RENDERER_VERSION);
+    let stored = load_blob(&state, &key)
+        .await
+        .or_internal("Failed to load stored PDF")
+        .for_repo(&repo)
+        .at_commit(&commit)?;
+    let pdf = match stored {
+        Some(pdf) => pdf,
+        None => {
+            info!("Drawing the schematic PDF of {}/{}", repo, commit);
+            let projects = projects_at(&schematics, &repo, &commit).await?;
+            let date = git::get_commit_info(&repo, &commit)
+                .await
+                .or_internal("Failed to read commit")
+                .for_repo(&repo)
+                .at_commit(&commit)?
+                .commit_date
+                .map(|date| date.format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+            let (title_repo, title_commit) = (repo.clone(), commit.clone());
+            let pdf = tokio::task::spawn_blocking(move || {
+                pdf_service::render(&projects, &title_repo, &title_commit, &date)
+            })
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result)
+            .or_internal("Failed to draw schematic PDF")
+            .for_repo(&repo)
+            .at_commit(&commit)?;
+            if let Err(e) = store_blob(&state, &key, "application/pdf", &pdf).await {
+                warn!("Failed to store the schematic PDF of {}/{}: {}", repo, commit, e);
+            }
+            pdf
+        }
+    };
+
+    let name = repo.rsplit('/').next().unwrap_or(&repo);
+    let filename = format!("{}-{}.pdf", name, &commit[..7.min(commit.len())]);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (header::CONTENT_DISPOSITION, format!("inline; filename=\"{}\"", filename)),
+        ],
+        cache_headers,
+        pdf,
+    )
+        .into_response())
+}
+
+/// A file from the repository at a given commit
+///
+/// Returns the whole file with a MIME type from its name and content, and its
+/// blob id as ETag. Files over 10 MiB are refused with 413; use `raw` to
+/// stream those.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/files/{path}",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag"),
+        ("path" = String, Path, description = "File path within the repository"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a copy already held")
+    ),
+    responses(
+        (status = 200, description = "File content", content_type = "application/octet-stream"),
+        (status = 304, description = "The held copy is current"),
+        (status = 404, description = "File not found at this commit", body = ApiError),
+        (status = 413, description = "File too large; use the raw endpoint", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn file_at_commit(
+    Path((repo, commit, path)): Path<(String, String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    info!("Reading {} at {} from {}", path, commit, repo);
+
+    let file = git::get_file_at_commit(&repo, &commit, &path, MAX_FILE_BYTES)
+        .await
+        .or_internal(format!("Failed to read {}", path))
+        .for_repo(&repo)
+        .at_commit(&commit)?
+        .ok_or_else(|| AppError::not_found(format!("{} not found at commit {}", path, commit)))?;
+
+    let etag = format!("\"{}\"", file.oid);
+    if conditional::matches(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, etag_headers(&etag)).into_response());
+    }
+    let Some(content) = file.content else {
+        return Err(AppError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "file_too_large",
+            format!(
+                "{} is {} bytes, over the {} byte limit; fetch it from the raw endpoint",
+                path, file.size, MAX_FILE_BYTES
+            ),
+        ));
+    };
+
+    let content_type = file_stream::content_type(&path, &content[..content.len().min(1024)]);
+    Ok((
+        etag_headers(&etag),
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static(content_type)),
+            (header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+        ],
+        content,
+    )
+        .into_response())
+}
+
+/// Stream a file from the repository at a given commit
+///
+/// The body is streamed from disk and `Range` requests are honoured (206 with
+/// `Content-Range`), so clients can page through very large PCB files without
+/// the server buffering them in memory.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/commits/{commit}/raw/{path}",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag"),
+        ("path" = String, Path, description = "File path within the repository"),
+        ("Range" = Option<String>, Header, description = "Optional single byte range, e.g. bytes=0-1023")
+    ),
+    responses(
+        (status = 200, description = "Full file content", content_type = "application/octet-stream"),
+        (status = 206, description = "Requested byte range", content_type = "application/octet-stream"),
+        (status = 404, description = "File not found at this commit", body = ApiError),
+        (status = 416, description = "Requested range not satisfiable"),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn raw_file(
+    Path((repo, commit, path)): Path<(String, String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    info!("Streaming {} at {} from {}", path, commit, repo);
+
+    let blob = git::materialize_blob(&repo, &commit, &path)
+        .await
+        .or_internal(format!("Failed to read {}", path))
+        .for_repo(&repo)
+        .at_commit(&commit)?
+        .ok_or_else(|| AppError::not_found(format!("{} not found at commit {}", path, commit)))?;
+
+    file_stream::stream_file(&blob, file_stream::content_type(&path, &[]), &headers)
+        .await
+        .or_internal(format!("Failed to read {}", path))
+        .for_repo(&repo)
+        .at_commit(&commit)
+}
+
+/// Git history of a repository merged with its processing state
+///
+/// Lists commits that changed schematic or layout files, newest first, with
+/// whether each has AI summaries, has been distilled and rendered, where it
+/// stands in processing, and why its last processing attempt failed. Commits
+/// without a date are left out when `since` or `until` is given, and
+/// `since_commit` leaves out a commit and everything before it.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/timeline",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        TimelineQuery
+    ),
+    responses(
+        (status = 200, description = "One page of the timeline", body = TimelineResponse),
+        (status = 400, description = "Invalid limit, date range or cursor", body = ApiError),
+        (status = 404, description = "since_commit not found", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn timeline(
+    State(state): State<Arc<PgPool>>,
+    State(responses): State<Arc<ResponseCache>>,
+    Path(repo): Path<String>,
+    Query(query): Query<TimelineQuery>,
+) -> Result<Response, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::bad_request(format!("limit must be between 1 and {}", MAX_PAGE_SIZE)));
+    }
+    if let (Some(since), Some(until)) = (query.since, query.until) {
+        if since > until {
+            return Err(AppError::bad_request("since must not be after until"));
+        }
+    }
+    let since_commit = match &query.since_commit {
+        Some(commit) => Some(resolve_commit(&repo, commit).await?),
+        None => None,
+    };
+
+    // Statuses change while commits are processed, and summaries and renders
+    // arrive without an event, so the page is only kept briefly and not at all
+    // mid-update
+    let variant = format!(
+        "{}|{}|{}|{}|{}",
+        limit,
+        query.cursor.as_deref().unwrap_or_default(),
+        query.since.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        query.until.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        since_commit.as_deref().unwrap_or_default()
+    );
+    let idle = status::active_commits(&repo).is_empty();
+    responses
+        .read_through("timeline", &repo, "", &variant, Some(TIMELINE_TTL), idle, async {
+            // The walk stops once the page is full, so recent pages of a
+            // long history don't wait for all of it
+            let filter = LogFilter {
+                since_commit: since_commit.clone(),
+                since: query.since,
+                until: query.until,
+            };
+            let mut history = git::schematic_commit_pages(&repo, filter);
+            let mut page = Vec::new();
+            let mut after_cursor = query.cursor.is_none();
+            while page.len() <= limit as usize {
+                let Some(commits) = history.next().await else { break };
+                for commit in commits.or_internal("Failed to read git history").for_repo(&repo)? {
+                    if after_cursor {
+                        page.push(commit);
+                    } else {
+                        after_cursor = query.cursor.as_ref() == Some(&commit.commit_hash);
+                    }
+                }
+            }
+            if let (false, Some(cursor)) = (after_cursor, &query.cursor) {
+                return Err(AppError::bad_request(format!("Unknown cursor {}", cursor)));
+            }
+            let next_cursor = if page.len() > limit as usize {
+                page.truncate(limit as usize);
+                page.last().map(|c| c.commit_hash.clone())
+            } else {
+                None
+            };
+
+            let repo_url = git::repo_url(&repo);
+            let hashes: Vec<String> = page.iter().map(|c| c.commit_hash.clone()).collect();
+            let mut stored: HashMap<String, _> = commit_processing(&state, &repo_url, &hashes)
+                .await
+                .or_internal("Failed to load processing state")
+                .for_repo(&repo)?
+                .into_iter()
+                .map(|p| (p.commit_hash.clone(), p))
+                .collect();
+            let pull_requests: HashMap<String, i32> = commit_metadata(&state, &repo_url, &hashes)
+                .await
+                .or_internal("Failed to load commit metadata")
+                .for_repo(&repo)?
+                .into_iter()
+                .filter_map(|m| Some((m.commit_hash, m.pr_number?)))
+                .collect();
+            let active = status::active_commits(&repo);
+
+            let commits = page
+                .into_iter()
+                .map(|c| {
+                    // Commits never stored have nothing processed yet
+                    let state = stored.remove(&c.commit_hash).unwrap_or_default();
+                    let pr_number = pull_requests.get(&c.commit_hash).copied();
+                    let processing_status = match state.status() {
+                        // Left so by a server that stopped mid-commit; the next update retries it
+                        ProcessingStatus::Processing if !active.contains(&c.commit_hash) => ProcessingStatus::Pending,
+                        stored => stored,
+                    };
+                    let status = if active.contains(&c.commit_hash) {
+                        "processing"
+                    } else if processing_status == ProcessingStatus::Failed {
+                        "failed"
+                    } else if state.has_blurb || state.has_change_summary {
+                        "summarized"
+                    } else if state.distilled {
+                        "distilled"
+                    } else {
+                        "pending"
+                    };
+                    TimelineCommit {
+                        commit_hash: c.commit_hash,
+                        commit_date: c.commit_date,
+                        author: c.author,
+                        author_email: c.author_email,
+                        tags: c.tags,
+                        pr_number,
+                        message: c.message,
+                        status: status.to_string(),
+                        processing_status: processing_status.as_str().to_string(),
+                        has_blurb: state.has_blurb,
+                        has_description: state.has_description,
+                        has_change_summary: state.has_change_summary,
+                        distilled: state.distilled,
+                        rendered: state.rendered,
+                        error: state.processing_error,
+                        error_at: state.processing_error_at,
+                    }
+                })
+                .collect();
+
+            Ok(TimelineResponse {
+                repo: repo.clone(),
+                commits,
+                next_cursor,
+            })
+        })
+        .await
+}
+
+/// List a repository's failed commits
+///
+/// Commits whose last processing attempt failed, most recent failure first,
+/// with the error. A commit leaves the list once an update processes it
+/// successfully.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/errors",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    responses(
+        (status = 200, description = "The failed commits, up to 200", body = ProcessingErrorsResponse),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn processing_errors(
+    State(state): State<Arc<PgPool>>,
+    Path(repo): Path<String>,
+) -> Result<Json<ProcessingErrorsResponse>, AppError> {
+    let failures = processing_failures(&state, &git::repo_url(&repo), ERROR_LIST_LIMIT)
+        .await
+        .or_internal("Failed to load processing errors")
+        .for_repo(&repo)?;
+    let errors = failures
+        .into_iter()
+        .map(|f| ProcessingErrorItem {
+            commit_hash: f.commit_hash,
+            error: f.error,
+            failed_at: f.failed_at,
+        })
+        .collect();
+    Ok(Json(ProcessingErrorsResponse { repo, errors }))
+}
+
+/// List a registered repository's part lifecycle alerts
+///
+/// A scheduled check looks up each MPN of the repo's latest commit with the
+/// part supplier and opens an alert for parts marked not recommended for new
+/// designs (NRND), end of life (EOL) or obsolete, announcing it on the
+/// notification webhook. An alert resolves once the part leaves the repo or
+/// its status recovers, and reopens if it comes back.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/alerts",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        RepoAlertsQuery
+    ),
+    responses(
+        (status = 200, description = "The repo's alerts", body = RepoAlertsResponse),
+        (status = 404, description = "Repository is not registered", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn alerts(
+    State(state): State<Arc<PgPool>>,
+    Path(repo): Path<String>,
+    Query(query): Query<RepoAlertsQuery>,
+) -> Result<Json<RepoAlertsResponse>, AppError> {
+    let repo_url = registered_repo_url(&state, &repo).await?;
+    let alerts = list_part_alerts(&state, &repo_url, query.include_resolved.unwrap_or(false))
+        .await
+        .or_internal("Failed to load part alerts")
+        .for_repo(&repo)?
+        .into_iter()
+        .map(|a| PartAlertItem {
+            mpn: a.mpn,
+            risk: a.risk,
+            lifecycle_status: a.lifecycle_status,
+            commit_hash: a.commit_hash,
+            designators: a.designators,
+            first_seen_at: a.first_seen_at,
+            last_seen_at: a.last_seen_at,
+            resolved_at: a.resolved_at,
+        })
+        .collect();
+    Ok(Json(RepoAlertsResponse { repo, alerts }))
+}
+
+/// Live processing events of a repository
+///
+/// Server-sent events: a `commit` event (RepoEvent) whenever one of the
+/// repo's commits finishes or fails processing, on any server instance, so a
+/// timeline can update without polling. A `resync` event means events were
+/// dropped because the client fell behind; reload the timeline. Events are
+/// not replayed after a reconnect, so reload it then too.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/events",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    responses(
+        (status = 200, description = "Processing events via SSE, as commit events carrying a RepoEvent", content_type = "text/event-stream", body = String)
+    ),
+    tag = "repo"
+)]
+pub async fn events(Path(repo): Path<String>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    info!("Streaming processing events of {}", repo);
+    let repo_url = git::repo_url(&repo);
+    let mut events = events_service::subscribe();
+
+    let sse_stream = async_stream::stream! {
+        loop {
+            match events.recv().await {
+                Ok(event) if event.repo_url == repo_url => {
+                    let kind = match event.kind {
+                        CommitEventKind::Processed => RepoEventKind::Processed,
+                        CommitEventKind::Failed => RepoEventKind::Failed,
+                    };
+                    let event = RepoEvent { repo: repo.clone(), commit_hash: event.commit_hash, kind };
+                    match Event::default().event("commit").json_data(&event) {
+                        Ok(event) => yield Ok(event),
+                        Err(e) => warn!("Failed to encode commit event: {}", e),
+                    }
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Event stream of {} fell {} event(s) behind", repo, missed);
+                    yield Ok(Event::default().event("resync").data("Events were dropped; reload the timeline"));
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Sse::new(shutdown::sse(sse_stream)).keep_alive(
+        KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .text("keep-alive"),
+    )
+}
+
+/// Trimmed value, None if empty
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// A request's file globs trimmed, None if there are none; `field` names the
+/// field in errors
+fn globs(field: &str, globs: Option<Vec<String>>) -> Result<Option<Vec<String>>, AppError> {
+    let globs = globs
+        .map(|globs| globs.into_iter().filter_map(|g| non_empty(Some(g))).collect::<Vec<_>>())
+        .filter(|globs| !globs.is_empty());
+    if let Some((glob, e)) = globs
+        .iter()
+        .flatten()
+        .find_map(|glob| glob::Pattern::new(glob).err().map(|e| (glob, e)))
+    {
+        return Err(AppError::bad_request(format!("Invalid {} glob '{}': {}", field, glob, e)));
+    }
+    Ok(globs)
+}
+
+/// A processing policy as stored, its branch globs trimmed and checked; an
+/// empty list of globs processes no branch
+pub(crate) fn processing_policy(policy: ProcessingPolicy) -> Result<kicad_db::ProcessingPolicy, AppError> {
+    let mut policy = kicad_db::ProcessingPolicy::from(policy);
+    policy.branches = policy
+        .branches
+        .map(|globs| globs.into_iter().filter_map(|g| non_empty(Some(g))).collect());
+    if let Some((glob, e)) = policy
+        .branches
+        .iter()
+        .flatten()
+        .find_map(|glob| glob::Pattern::new(glob).err().map(|e| (glob, e)))
+    {
+        return Err(AppError::bad_request(format!("Invalid branch glob '{}': {}", glob, e)));
+    }
+    Ok(policy)
+}
+
+/// Register a repository, or replace its registration
+///
+/// Registered repos are fetched from their clone URL and branch, may use
+/// their own webhook secret, and have their commit summaries generated with
+/// their preferred model and prompt. Huge repos can be cloned shallow and
+/// checked out sparsely, and monorepos limited to the paths holding their
+/// boards. `processing` picks the branches and tags whose pushes are
+/// processed, and whether AI summaries and thumbnails follow. The repo
+/// belongs to the caller's org, or to the org an instance-wide key names;
+/// only that org sees it and its commits. Requires an admin key.
+#[utoipa::path(
+    post,
+    path = "/api/repos",
+    request_body = RegisterRepoRequest,
+    responses(
+        (status = 200, description = "The registration", body = RegisteredRepoItem),
+        (status = 400, description = "Invalid slug, clone URL, clone depth, path filter or branch glob, model not on the allowlist or unknown prompt", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key, or an org key naming an org", body = ApiError),
+        (status = 404, description = "No org with the given slug", body = ApiError),
+        (status = 409, description = "The repo is registered to another org", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn register(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(prompts): State<Arc<PromptLibrary>>,
+    State(responses): State<Arc<ResponseCache>>,
+    viewer: Viewer,
+    Json(req): Json<RegisterRepoRequest>,
+) -> Result<Json<RegisteredRepoItem>, AppError> {
+    let slug = req.repo.trim().trim_matches('/').to_string();
+    if !slug.contains('/') {
+        return Err(AppError::bad_request("repo must look like owner/repo"));
+    }
+    let clone_url = non_empty(req.clone_url).unwrap_or_else(|| git::repo_url(&slug));
+    if !["https://", "http://", "ssh://", "git@", "file://"]
+        .iter()
+        .any(|scheme| clone_url.starts_with(scheme))
+    {
+        return Err(AppError::bad_request(format!("Unsupported clone URL {}", clone_url)));
+    }
+    let auth_token_env = non_empty(req.auth_token_env);
+    if let Some(name) = auth_token_env.as_ref().filter(|name| !config.git_token_envs.contains(name)) {
+        return Err(AppError::bad_request(format!(
+            "auth_token_env {} is not one of the server's git_token_envs [{}]",
+            name,
+            config.git_token_envs.join(", ")
+        )));
+    }
+    let summary_model = non_empty(req.summary_model);
+    if let Some(model) = summary_model.as_deref().filter(|m| !config.models.is_allowed(m)) {
+        return Err(AppError::bad_request(format!(
+            "Model '{}' is not allowed. Allowed models: {}",
+            model,
+            config.models.allowlist().join(", ")
+        )));
+    }
+    let summary_prompt = non_empty(req.summary_prompt);
+    if let Some(name) = summary_prompt.as_deref().filter(|name| prompts.get(name).is_none()) {
+        return Err(AppError::bad_request(format!("Unknown prompt template '{}'", name)));
+    }
+    if req.clone_depth.is_some_and(|depth| depth < 1) {
+        return Err(AppError::bad_request("clone_depth must be at least 1"));
+    }
+    let sparse_paths = req
+        .sparse_paths
+        .map(|paths| paths.into_iter().filter_map(|p| non_empty(Some(p))).collect::<Vec<_>>())
+        .filter(|paths| !paths.is_empty());
+    let path_filter = globs("path_filter", req.path_filter)?;
+    let schematic_globs = globs("schematic_globs", req.schematic_globs)?;
+    let processing = processing_policy(req.processing)?;
+    let org_id = viewer.owning_org(&state, req.org.as_deref()).await?;
+
+    let registration = RepoRegistration {
+        repo_url: git::repo_url(&slug),
+        slug: slug.clone(),
+        clone_url,
+        default_branch: non_empty(req.default_branch),
+        auth_token_env,
+        summary_model,
+        summary_prompt,
+        // Secrets are compared verbatim, so they are not trimmed
+        webhook_secret: req.webhook_secret.filter(|s| !s.is_empty()),
+        clone_depth: req.clone_depth,
+        sparse_paths,
+        path_filter,
+        schematic_globs,
+        submodules: req.submodules,
+        processing,
+        org_id,
+    };
+    let registered = register_repo(&state, &registration)
+        .await
+        .or_internal("Failed to register repository")
+        .for_repo(&slug)?
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::CONFLICT,
+                "conflict",
+                format!("Repository {} is already registered to another org", slug),
+            )
+            .for_repo(&slug)
+        })?;
+    info!("Registered {} (clone from {})", slug, registered.clone_url);
+
+    // The cached clone may come from a different URL or branch
+    git::set_remote(&slug, Some((&registered).into()));
+    if let Err(e) = git::invalidate_cache(&slug).await {
+        warn!("Failed to invalidate cache for {}: {}", slug, e);
+    }
+    responses.invalidate_repo(&slug).await;
+
+    Ok(Json(registered.into()))
+}
+
+/// List registered repositories
+///
+/// Webhook secrets are never returned. Org keys only list their org's repos.
+/// Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/repos",
+    responses(
+        (status = 200, description = "Registered repos, by slug", body = RegisteredRepoListResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn list_registered(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+) -> Result<Json<RegisteredRepoListResponse>, AppError> {
+    let repos = list_registered_repos(&state, viewer.orgs())
+        .await
+        .or_internal("Failed to list registered repositories")?;
+    Ok(Json(RegisteredRepoListResponse {
+        repos: repos.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Get a repository's registration
+///
+/// Includes the submodules pinned at the head of the processed branch, which
+/// are left out if the repo can't be fetched. Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    responses(
+        (status = 200, description = "The registration", body = RegisteredRepoItem),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "Repository is not registered", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn get_registered(
+    State(state): State<Arc<PgPool>>,
+    Path(repo): Path<String>,
+) -> Result<Json<RegisteredRepoItem>, AppError> {
+    let registered = get_registered_repo(&state, &git::repo_url(&repo))
+        .await
+        .or_internal("Failed to look up repository")
+        .for_repo(&repo)?
+        .ok_or_else(|| AppError::not_found(format!("Repository {} is not registered", repo)))?;
+    let mut item = RegisteredRepoItem::from(registered);
+    item.submodule_pins = match git::get_submodule_pins(&repo).await {
+        Ok(pins) => Some(pins),
+        Err(e) => {
+            warn!("Failed to read submodules of {}: {:#}", repo, e);
+            None
+        }
+    };
+    Ok(Json(item))
+}
+
+/// Change which pushes a repository processes and what runs for them
+///
+/// Replaces the processing policy alone, leaving the rest of the
+/// registration as it is; it applies from the next push or re-sync.
+/// Requires an admin key.
+#[utoipa::path(
+    put,
+    path = "/api/repos/{repo}/processing",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    request_body = ProcessingPolicy,
+    responses(
+        (status = 200, description = "The registration with its new policy", body = RegisteredRepoItem),
+        (status = 400, description = "Invalid branch glob", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "Repository is not registered", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn update_processing(
+    State(state): State<Arc<PgPool>>,
+    Path(repo): Path<String>,
+    Json(req): Json<ProcessingPolicy>,
+) -> Result<Json<RegisteredRepoItem>, AppError> {
+    let policy = processing_policy(req)?;
+    let registered = set_processing_policy(&state, &git::repo_url(&repo), &policy)
+        .await
+        .or_internal("Failed to update the processing policy")
+        .for_repo(&repo)?
+        .ok_or_else(|| AppError::not_found(format!("Repository {} is not registered", repo)))?;
+    info!("Updated the processing policy of {}: {:?}", repo, registered.processing);
+    Ok(Json(registered.into()))
+}
+
+/// The canonical URL of `repo`, if it's registered
+pub(crate) async fn registered_repo_url(pool: &PgPool, repo: &str) -> Result<String, AppError> {
+    let repo_url = git::repo_url(repo);
+    get_registered_repo(pool, &repo_url)
+        .await
+        .or_internal("Failed to look up repository")
+        .for_repo(repo)?
+        .ok_or_else(|| AppError::not_found(format!("Repository {} is not registered", repo)))?;
+    Ok(repo_url)
+}
+
+async fn subscribers_response(pool: &PgPool, repo: String, repo_url: &str) -> Result<Json<DigestSubscribersResponse>, AppError> {
+    let subscribers = list_digest_subscribers(pool, repo_url)
+        .await
+        .or_internal("Failed to list digest subscribers")
+        .for_repo(&repo)?;
+    Ok(Json(DigestSubscribersResponse {
+        repo,
+        subscribers: subscribers.into_iter().map(DigestSubscriberItem::from).collect(),
+    }))
+}
+
+/// List who gets a repository's weekly activity digest
+///
+/// Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/digest/subscribers",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    responses(
+        (status = 200, description = "The repo's digest subscribers", body = DigestSubscribersResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "Repository is not registered", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn digest_subscribers(
+    State(state): State<Arc<PgPool>>,
+    Path(repo): Path<String>,
+) -> Result<Json<DigestSubscribersResponse>, AppError> {
+    let repo_url = registered_repo_url(&state, &repo).await?;
+    subscribers_response(&state, repo, &repo_url).await
+}
+
+/// Subscribe an address to a repository's weekly activity digest
+///
+/// Each week the address gets the commits processed, the components added
+/// and removed and the ERC errors introduced, if the server has a mailer
+/// configured. Subscribing an address twice changes nothing. Requires an
+/// admin key.
+#[utoipa::path(
+    post,
+    path = "/api/repos/{repo}/digest/subscribers",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    request_body = DigestSubscriberRequest,
+    responses(
+        (status = 200, description = "The repo's digest subscribers, the new one included", body = DigestSubscribersResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "Repository is not registered", body = ApiError),
+        (status = 422, description = "Not an email address", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn subscribe_digest(
+    State(state): State<Arc<PgPool>>,
+    Path(repo): Path<String>,
+    Valid(req): Valid<DigestSubscriberRequest>,
+) -> Result<Json<DigestSubscribersResponse>, AppError> {
+    let repo_url = registered_repo_url(&state, &repo).await?;
+    if add_digest_subscriber(&state, &repo_url, req.email.trim())
+        .await
+        .or_internal("Failed to add digest subscriber")
+        .for_repo(&repo)?
+    {
+        info!("Subscribed {} to the weekly digest of {}", req.email.trim(), repo);
+    }
+    subscribers_response(&state, repo, &repo_url).await
+}
+
+/// Unsubscribe an address from a repository's weekly activity digest
+///
+/// Requires an admin key.
+#[utoipa::path(
+    delete,
+    path = "/api/repos/{repo}/digest/subscribers/{email}",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("email" = String, Path, description = "Subscribed address, in any case")
+    ),
+    responses(
+        (status = 200, description = "The repo's remaining digest subscribers", body = DigestSubscribersResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "Repository is not registered, or the address isn't subscribed", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn unsubscribe_digest(
+    State(state): State<Arc<PgPool>>,
+    Path((repo, email)): Path<(String, String)>,
+) -> Result<Json<DigestSubscribersResponse>, AppError> {
+    let repo_url = registered_repo_url(&state, &repo).await?;
+    let removed = remove_digest_subscriber(&state, &repo_url, &email)
+        .await
+        .or_internal("Failed to remove digest subscriber")
+        .for_repo(&repo)?;
+    if !removed {
+        return Err(AppError::not_found(format!("{} is not subscribed to the digest of {}", email, repo)));
+    }
+    info!("Unsubscribed {} from the weekly digest of {}", email, repo);
+    subscribers_response(&state, repo, &repo_url).await
+}
+
+/// Preview a repository's weekly activity digest
+///
+/// The HTML email of the last week to have ended, as subscribers got or
+/// will get it; a week without schematic commits renders, though it isn't
+/// sent. Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/digest/preview",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    responses(
+        (status = 200, description = "The digest email", content_type = "text/html", body = String),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "Repository is not registered", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn digest_preview(
+    State(state): State<Arc<PgPool>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    State(config): State<Arc<Config>>,
+    Path(repo): Path<String>,
+) -> Result<Response, AppError> {
+    let repo_url = registered_repo_url(&state, &repo).await?;
+    let until = digest_service::week_end(config.digest.weekday, config.digest.hour, Utc::now());
+    let digest = digest_service::build(&state, &schematics, &config.digest, &repo_url, until)
+        .await
+        .or_internal("Failed to gather the week's activity")
+        .for_repo(&repo)?;
+    let rendered = render_digest(&digest).or_internal("Failed to render the digest").for_repo(&repo)?;
+    Ok(([(header::CONTENT_TYPE, "text/html; charset=utf-8")], rendered.html).into_response())
+}
+
+/// Unregister a repository and delete its stored commits
+///
+/// Commits are soft-deleted along with their summaries, images, components
+/// and ERC findings: they disappear at once and are purged for good after
+/// `DELETED_RETENTION_SECS`. The repo's re-sync schedule is dropped too.
+/// Webhooks for it store commits afresh unless registration is required.
+/// Requires an admin key.
+#[utoipa::path(
+    delete,
+    path = "/api/repos/{repo}",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    responses(
+        (status = 200, description = "Repository unregistered and its commits deleted", body = UnregisterRepoResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "Repository is neither registered nor stored", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn unregister(
+    State(state): State<Arc<PgPool>>,
+    State(responses): State<Arc<ResponseCache>>,
+    Path(repo): Path<String>,
+) -> Result<Json<UnregisterRepoResponse>, AppError> {
+    let repo_url = git::repo_url(&repo);
+    let removed = unregister_repo(&state, &repo_url)
+        .await
+        .or_internal("Failed to unregister repository")
+        .for_repo(&repo)?;
+    let deleted_commits = soft_delete_repo(&state, &repo_url)
+        .await
+        .or_internal("Failed to delete stored commits")
+        .for_repo(&repo)?;
+    if !removed && deleted_commits == 0 {
+        return Err(AppError::not_found(format!("Repository {} is neither registered nor stored", repo)));
+    }
+    info!("Unregistered {} and deleted {} stored commit(s)", repo, deleted_commits);
+
+    git::set_remote(&repo, None);
+    if let Err(e) = git::invalidate_cache(&repo).await {
+        warn!("Failed to invalidate cache for {}: {}", repo, e);
+    }
+    responses.invalidate_repo(&repo).await;
+    Ok(Json(UnregisterRepoResponse { repo, removed, deleted_commits }))
+}
+
+/// Delete one stored commit
+///
+/// The commit is soft-deleted like those of a deleted repo, and is stored
+/// afresh if it is processed again. Requires an admin key.
+#[utoipa::path(
+    delete,
+    path = "/api/repos/{repo}/commits/{commit}",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo"),
+        ("commit" = String, Path, description = "Commit SHA (full or abbreviated), branch or tag")
+    ),
+    responses(
+        (status = 200, description = "Commit deleted", body = DeleteCommitResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "Commit is not stored", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn delete_commit(
+    State(state): State<Arc<PgPool>>,
+    State(responses): State<Arc<ResponseCache>>,
+    Path((repo, commit)): Path<(String, String)>,
+) -> Result<Json<DeleteCommitResponse>, AppError> {
+    let commit = resolve_commit(&repo, &commit).await?;
+    let deleted = soft_delete_commit(&state, &git::repo_url(&repo), &commit)
+        .await
+        .or_internal("Failed to delete commit")
+        .for_repo(&repo)
+        .at_commit(&commit)?;
+    if !deleted {
+        return Err(AppError::not_found(format!("Commit {} of {} is not stored", commit, repo)));
+    }
+    info!("Deleted stored commit {} of {}", commit, repo);
+    responses.invalidate_repo(&repo).await;
+    Ok(Json(DeleteCommitResponse { repo, commit }))
+}
+
+fn ndjson_line<T: serde::Serialize>(value: &T) -> Result<Bytes, std::io::Error> {
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+    Ok(Bytes::from(line))
+}
+
+/// Export a repository's stored data as newline-delimited JSON
+///
+/// The first line is a `RepoArchiveHeader`; each following line is one stored
+/// commit, oldest first, with its summaries, schematic image (base64), parts,
+/// distilled netlist, board summary and timings. Components and ERC findings
+/// are rebuilt from these on import. Requires an admin key.
+#[utoipa::path(
+    get,
+    path = "/api/repos/{repo}/export",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    responses(
+        (status = 200, description = "Archive, streamed", content_type = "application/x-ndjson", body = String),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 404, description = "No stored commits for this repository", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn export(
+    State(state): State<Arc<PgPool>>,
+    Path(repo): Path<String>,
+) -> Result<Response, AppError> {
+    let repo_url = git::repo_url(&repo);
+    let commits = count_schematics(&state, &repo_url)
+        .await
+        .or_internal("Failed to count stored commits")
+        .for_repo(&repo)?;
+    if commits == 0 {
+        return Err(AppError::not_found(format!("No stored commits for {}", repo)));
+    }
+    info!("Exporting {} commit(s) of {}", commits, repo);
+
+    let header = RepoArchiveHeader {
+        version: ARCHIVE_VERSION,
+        repo: repo.clone(),
+        commits,
+        exported_at: Utc::now(),
+    };
+    let filename = format!("{}.ndjson", repo.replace('/', "_"));
+    let lines = async_stream::stream! {
+        yield ndjson_line(&header);
+        let mut offset = 0;
+        loop {
+            let batch = match export_commits(&state, &repo_url, EXPORT_BATCH_SIZE, offset).await {
+                Ok(batch) => batch,
+                Err(e) => {
+                    // The client sees a truncated archive (fewer lines than the header says)
+                    warn!("Export of {} failed after {} commit(s): {}", repo, offset, e);
+                    yield Err(std::io::Error::other(e));
+                    break;
+                }
+            };
+            let done = (batch.len() as i64) < EXPORT_BATCH_SIZE;
+            offset += batch.len() as i64;
+            for commit in &batch {
+                yield ndjson_line(commit);
+            }
+            if done {
+                break;
+            }
+        }
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        Body::from_stream(lines),
+    )
+        .into_response())
+}
+
+/// Import an archive from `/api/repos/{repo}/export`
+///
+/// The archive may come from another repository or server; its commits are
+/// stored under `{repo}`, replacing any already stored. The whole archive is
+/// checked before anything is written. Requires an admin key.
+#[utoipa::path(
+    post,
+    path = "/api/repos/{repo}/import",
+    params(
+        ("repo" = String, Path, description = "URL-encoded repository, e.g. owner%2Frepo")
+    ),
+    request_body(content = String, description = "Archive from the export endpoint", content_type = "application/x-ndjson"),
+    responses(
+        (status = 200, description = "Archive imported", body = ImportRepoResponse),
+        (status = 400, description = "Malformed archive or unsupported version", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "API key is not an admin key", body = ApiError),
+        (status = 413, description = "Archive too large"),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "repo"
+)]
+pub async fn import(
+    State(state): State<Arc<PgPool>>,
+    State(responses): State<Arc<ResponseCache>>,
+    Path(repo): Path<String>,
+    body: Bytes,
+) -> Result<Json<ImportRepoResponse>, AppError> {
+    let text = std::str::from_utf8(&body).map_err(|_| AppError::bad_request("Archive is not UTF-8"))?;
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let (_, first) = lines.next().ok_or_else(|| AppError::bad_request("Archive is empty"))?;
+    let header: RepoArchiveHeader = serde_json::from_str(first)
+        .map_err(|e| AppError::bad_request(format!("Invalid archive header: {}", e)))?;
+    if header.version != ARCHIVE_VERSION {
+        return Err(AppError::bad_request(format!(
+            "Unsupported archive version {} (expected {})",
+            header.version, ARCHIVE_VERSION
+        )));
+    }
+    let commits = lines
+        .map(|(i, line)| {
+            serde_json::from_str::<ArchivedCommit>(line)
+                .map_err(|e| AppError::bad_request(format!("Invalid commit on line {}: {}", i + 1, e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if commits.len() as i64 != header.commits {
+        return Err(AppError::bad_request(format!(
+            "Archive is truncated: header lists {} commit(s), found {}",
+            header.commits,
+            commits.len()
+        )));
+    }
+
+    let repo_url = git::repo_url(&repo);
+    for commit in &commits {
+        import_commit(&state, &repo_url, commit)
+            .await
+            .or_internal("Failed to import commit")
+            .for_repo(&repo)
+            .at_commit(&commit.commit_hash)?;
+    }
+    info!("Imported {} commit(s) of {} into {}", commits.len(), header.repo, repo);
+    thumbnails::wake();
+    responses.invalidate_repo(&repo).await;
+
+    Ok(Json(ImportRepoResponse { repo, imported: commits.len() }))
+}
@@ -0,0 +1,234 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::auth::Viewer;
+use crate::config::Config;
+use crate::error::{AppError, ResultExt};
+use crate::services::{git, llm::ChatProvider};
+use crate::types::{
+    SearchQuery, SearchResponse, SearchResult, SearchType, SemanticCommitResult, SemanticComponentResult,
+    SemanticSearchResponse, TextSearchResult,
+};
+use kicad_db::{
+    search_schematic_texts, search_schematics, semantic_search_available, semantic_search_commits,
+    semantic_search_components, PgPool, TextKind,
+};
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+
+/// Full-text search across commit messages, blurbs, descriptions and change summaries
+///
+/// With a `type` other than `commits`, looks up a keyword in the text of the
+/// schematics instead: reference designators, values, labels, sheet names
+/// and text notes of each repo's newest processed commit, so "USB_DP" or
+/// "TP7" leads straight to its component or sheet. Text matches ignore case
+/// and list whole matches first. Commits with private summaries are only
+/// searched with a valid API key, and org keys only search their org's repos.
+#[utoipa::path(
+    get,
+    path = "/api/search",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Matching commits with highlighted snippets", body = SearchResponse),
+        (status = 400, description = "Missing or invalid query", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "search"
+)]
+pub async fn search(
+    State(state): State<Arc<PgPool>>,
+    viewer: Viewer,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<SearchResponse>, AppError> {
+    let text = query.q.trim();
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if text.is_empty() || !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(AppError::bad_request(format!(
+            "q must not be empty and limit must be between 1 and {}",
+            MAX_LIMIT
+        )));
+    }
+
+    let kind = query.kind.unwrap_or_default();
+    info!("Searching {:?} for {:?} (repo: {:?})", kind, text, query.repo);
+
+    let repo_url = query.repo.as_deref().map(git::repo_url);
+    if let Some(kinds) = text_kinds(kind) {
+        let hits = search_schematic_texts(
+            &state,
+            repo_url.as_deref(),
+            text,
+            kinds,
+            limit,
+            viewer.is_authenticated(),
+            viewer.orgs(),
+        )
+        .await
+        .or_internal("Search failed")?;
+        return Ok(Json(SearchResponse {
+            query: text.to_string(),
+            results: Vec::new(),
+            texts: hits
+                .into_iter()
+                .map(|hit| TextSearchResult {
+                    repo_url: hit.repo_url,
+                    commit_hash: hit.commit_hash,
+                    commit_date: hit.commit_date,
+                    kind: hit.kind,
+                    text: hit.text,
+                    reference: hit.reference,
+                    sheet_path: hit.sheet_path,
+                })
+                .collect(),
+        }));
+    }
+
+    let hits = search_schematics(
+        &state,
+        repo_url.as_deref(),
+        text,
+        limit,
+        viewer.is_authenticated(),
+        viewer.orgs(),
+    )
+        .await
+        .or_internal("Search failed")?;
+
+    let results = hits
+        .into_iter()
+        .map(|hit| SearchResult {
+            repo_url: hit.repo_url,
+            commit_hash: hit.commit_hash,
+            commit_date: hit.commit_date,
+            message: hit.git_message,
+            blurb: hit.blurb,
+            snippet: hit.snippet,
+            rank: hit.rank,
+        })
+        .collect();
+
+    Ok(Json(SearchResponse {
+        query: text.to_string(),
+        results,
+        texts: Vec::new(),
+    }))
+}
+
+/// The kinds of schematic text a search `type` looks through; None for commits
+fn text_kinds(kind: SearchType) -> Option<&'static [TextKind]> {
+    Some(match kind {
+        SearchType::Commits => return None,
+        SearchType::Text => &TextKind::ALL,
+        SearchType::Reference => &[TextKind::Reference],
+        SearchType::Value => &[TextKind::Value],
+        SearchType::Label => &[TextKind::Label],
+        SearchType::Sheet => &[TextKind::Sheet],
+        SearchType::Note => &[TextKind::Note],
+    })
+}
+
+/// Search commits and parts by meaning rather than keywords
+///
+/// Ranks commits (message and AI summaries) and parts (supplier descriptions)
+/// by embedding similarity to the query, so "where did we add reverse-polarity
+/// protection" finds a commit that only mentions a P-MOSFET on the input.
+/// Needs pgvector in Postgres; rows are embedded in the background, so new
+/// commits show up after a few minutes. Private summaries are only searched
+/// with a valid API key, and org keys only search their org's repos.
+#[utoipa::path(
+    get,
+    path = "/api/search/semantic",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Commits and parts ranked by similarity", body = SemanticSearchResponse),
+        (status = 400, description = "Missing or invalid query", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError),
+        (status = 503, description = "Semantic search is not available (no pgvector)", body = ApiError)
+    ),
+    tag = "search"
+)]
+pub async fn semantic_search(
+    State(state): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    State(chat): State<Arc<dyn ChatProvider>>,
+    viewer: Viewer,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<SemanticSearchResponse>, AppError> {
+    let text = query.q.trim();
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if text.is_empty() || !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(AppError::bad_request(format!(
+            "q must not be empty and limit must be between 1 and {}",
+            MAX_LIMIT
+        )));
+    }
+    if text_kinds(query.kind.unwrap_or_default()).is_some() {
+        return Err(AppError::bad_request("Semantic search only searches commits; use /api/search for schematic text"));
+    }
+
+    let available = semantic_search_available(&state)
+        .await
+        .or_internal("Failed to check for semantic search support")?;
+    if !available {
+        return Err(AppError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "not_configured",
+            "Semantic search needs the pgvector extension in Postgres; use /api/search meanwhile",
+        ));
+    }
+
+    info!("Semantic search for {:?} (repo: {:?})", text, query.repo);
+
+    let model = &config.models.embedding;
+    let embedding = chat
+        .embed(model, &[text.to_string()])
+        .await
+        .or_internal("Failed to embed the query")?
+        .pop()
+        .ok_or_else(|| AppError::internal("The embeddings API returned no vector"))?;
+
+    let repo_url = query.repo.as_deref().map(git::repo_url);
+    let include_private = viewer.is_authenticated();
+    let commits = semantic_search_commits(&state, model, &embedding, repo_url.as_deref(), limit, include_private, viewer.orgs())
+        .await
+        .or_internal("Semantic search failed")?;
+    let components =
+        semantic_search_components(&state, model, &embedding, repo_url.as_deref(), limit, include_private, viewer.orgs())
+            .await
+            .or_internal("Semantic search failed")?;
+
+    Ok(Json(SemanticSearchResponse {
+        query: text.to_string(),
+        model: model.clone(),
+        commits: commits
+            .into_iter()
+            .map(|hit| SemanticCommitResult {
+                repo_url: hit.repo_url,
+                commit_hash: hit.commit_hash,
+                commit_date: hit.commit_date,
+                message: hit.git_message,
+                blurb: hit.blurb,
+                similarity: hit.similarity,
+            })
+            .collect(),
+        components: components
+            .into_iter()
+            .map(|hit| SemanticComponentResult {
+                mpn: hit.mpn,
+                manufacturer: hit.manufacturer,
+                description: hit.description,
+                repo_url: hit.repo_url,
+                commit_hash: hit.commit_hash,
+                commit_date: hit.commit_date,
+                reference: hit.reference,
+                similarity: hit.similarity,
+            })
+            .collect(),
+    }))
+}
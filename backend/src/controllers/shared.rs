@@ -0,0 +1,215 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::{header, HeaderValue},
+    response::{IntoResponse, Json, Response},
+};
+use kicad_db::{retrieve_schematic, schematic::{render_sheet_svg, TitleBlock}, PgPool};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::controllers::repos::{commit_changes, projects_at};
+use crate::error::{AppError, AppResultExt, ResultExt};
+use crate::services::git;
+use crate::services::schematic_cache::SchematicCache;
+use crate::share::ShareGrant;
+use crate::types::{ShareScope, SharedCommitResponse, SharedSummaryResponse};
+
+/// Shared views never change, but the link expires; browsers may keep them
+/// for a while without sharing them with caches in between
+const SHARED_CACHE_CONTROL: &str = "private, max-age=300";
+
+/// Sheets across the commit's projects, each instance of a sub-sheet counted
+fn sheet_count(projects: &[kicad_db::schematic::Project]) -> usize {
+    projects.iter().map(|project| project.instances.len()).sum()
+}
+
+/// What a share link shows
+///
+/// The repo, commit and views the link covers, when it expires, and the
+/// commit's message, author and date. With the `svg` view, also how many
+/// sheets can be drawn. Needs no API key: the token in the path is the
+/// credential.
+#[utoipa::path(
+    get,
+    path = "/api/shared/{token}",
+    params(
+        ("token" = String, Path, description = "Share token from POST /api/repos/{repo}/commits/{commit}/share")
+    ),
+    responses(
+        (status = 200, description = "The shared commit", body = SharedCommitResponse),
+        (status = 401, description = "Invalid share link", body = ApiError),
+        (status = 404, description = "Share links are turned off", body = ApiError),
+        (status = 410, description = "The share link has expired", body = ApiError),
+        (status = 429, description = "Too many requests", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "shared"
+)]
+pub async fn shared_commit(
+    State(schematics): State<Arc<SchematicCache>>,
+    Extension(grant): Extension<ShareGrant>,
+) -> Result<Response, AppError> {
+    let info = git::get_commit_info(&grant.repo, &grant.commit)
+        .await
+        .or_internal("Failed to read commit")
+        .for_repo(&grant.repo)
+        .at_commit(&grant.commit)?;
+    let sheets = if grant.scopes.contains(&ShareScope::Svg) {
+        Some(sheet_count(&projects_at(&schematics, &grant.repo, &grant.commit).await?))
+    } else {
+        None
+    };
+    let body = SharedCommitResponse {
+        repo: grant.repo,
+        commit: grant.commit,
+        scopes: grant.scopes,
+        expires_at: grant.expires_at,
+        message: info.message,
+        author: info.author,
+        commit_date: info.commit_date,
+        sheets,
+    };
+    Ok(cached(Json(body)))
+}
+
+/// The shared commit's AI summary
+///
+/// Needs the link's `summary` view. 404 until the commit is summarized.
+#[utoipa::path(
+    get,
+    path = "/api/shared/{token}/summary",
+    params(
+        ("token" = String, Path, description = "Share token from POST /api/repos/{repo}/commits/{commit}/share")
+    ),
+    responses(
+        (status = 200, description = "The commit's summary", body = SharedSummaryResponse),
+        (status = 401, description = "Invalid share link", body = ApiError),
+        (status = 403, description = "The link doesn't include the summary", body = ApiError),
+        (status = 404, description = "The commit hasn't been summarized, or share links are turned off", body = ApiError),
+        (status = 410, description = "The share link has expired", body = ApiError),
+        (status = 429, description = "Too many requests", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "shared"
+)]
+pub async fn shared_summary(
+    State(state): State<Arc<PgPool>>,
+    Extension(grant): Extension<ShareGrant>,
+) -> Result<Response, AppError> {
+    grant.require(ShareScope::Summary)?;
+    let stored = retrieve_schematic(&state, &git::repo_url(&grant.repo), &grant.commit)
+        .await
+        .or_internal("Failed to load stored summary")
+        .for_repo(&grant.repo)
+        .at_commit(&grant.commit)?;
+    let Some((blurb, description)) = stored.and_then(|s| Some((s.blurb?, s.description))) else {
+        return Err(AppError::not_found(format!("{} has not been summarized yet", &grant.commit[..7.min(grant.commit.len())])));
+    };
+    Ok(cached(Json(SharedSummaryResponse {
+        repo: grant.repo,
+        commit: grant.commit,
+        blurb,
+        description,
+    })))
+}
+
+/// Everything the shared commit changed
+///
+/// Needs the link's `diff` view. The same payload as
+/// `/api/repos/{repo}/commits/{commit}/changes`, with the blurb and
+/// description only if the link includes the summary too.
+#[utoipa::path(
+    get,
+    path = "/api/shared/{token}/changes",
+    params(
+        ("token" = String, Path, description = "Share token from POST /api/repos/{repo}/commits/{commit}/share")
+    ),
+    responses(
+        (status = 200, description = "Changes made by the commit", body = CommitChangesResponse),
+        (status = 401, description = "Invalid share link", body = ApiError),
+        (status = 403, description = "The link doesn't include the diff", body = ApiError),
+        (status = 404, description = "Share links are turned off", body = ApiError),
+        (status = 410, description = "The share link has expired", body = ApiError),
+        (status = 429, description = "Too many requests", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "shared"
+)]
+pub async fn shared_changes(
+    State(state): State<Arc<PgPool>>,
+    State(schematics): State<Arc<SchematicCache>>,
+    Extension(grant): Extension<ShareGrant>,
+) -> Result<Response, AppError> {
+    grant.require(ShareScope::Diff)?;
+    let show_summary = grant.scopes.contains(&ShareScope::Summary);
+    let changes = commit_changes(&state, &schematics, grant.repo, grant.commit, |_| show_summary).await?;
+    Ok(cached(Json(changes)))
+}
+
+/// One sheet of the shared commit, drawn as SVG
+///
+/// Needs the link's `svg` view. Sheets are numbered from 1 in hierarchy
+/// order, as in the schematic PDF; `GET /api/shared/{token}` says how many
+/// there are.
+#[utoipa::path(
+    get,
+    path = "/api/shared/{token}/sheets/{page}",
+    params(
+        ("token" = String, Path, description = "Share token from POST /api/repos/{repo}/commits/{commit}/share"),
+        ("page" = usize, Path, description = "Sheet number, from 1")
+    ),
+    responses(
+        (status = 200, description = "The sheet", content_type = "image/svg+xml", body = String),
+        (status = 401, description = "Invalid share link", body = ApiError),
+        (status = 403, description = "The link doesn't include SVG drawings", body = ApiError),
+        (status = 404, description = "No such sheet, or share links are turned off", body = ApiError),
+        (status = 410, description = "The share link has expired", body = ApiError),
+        (status = 429, description = "Too many requests", body = ApiError),
+        (status = 500, description = "Internal server error", body = ApiError)
+    ),
+    tag = "shared"
+)]
+pub async fn shared_sheet(
+    State(schematics): State<Arc<SchematicCache>>,
+    Extension(grant): Extension<ShareGrant>,
+    Path((_token, page)): Path<(String, usize)>,
+) -> Result<Response, AppError> {
+    grant.require(ShareScope::Svg)?;
+    let (repo, commit) = (&grant.repo, &grant.commit);
+    let projects = projects_at(&schematics, repo, commit).await?;
+    let pages = sheet_count(&projects);
+    let (project, instance) = projects
+        .iter()
+        .flat_map(|project| (0..project.instances.len()).map(move |instance| (project, instance)))
+        .nth(page.wrapping_sub(1))
+        .ok_or_else(|| AppError::not_found(format!("No sheet {}; the commit has {}", page, pages)))?;
+    let date = git::get_commit_info(repo, commit)
+        .await
+        .or_internal("Failed to read commit")
+        .for_repo(repo)
+        .at_commit(commit)?
+        .commit_date
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    info!("Drawing sheet {} of {}/{} for a share link", page, repo, commit);
+    let title = TitleBlock {
+        repo,
+        commit,
+        date: &date,
+        page,
+        pages,
+    };
+    let svg = render_sheet_svg(project, instance, &title);
+    Ok(cached((
+        [(header::CONTENT_TYPE, HeaderValue::from_static("image/svg+xml; charset=utf-8"))],
+        svg,
+    )))
+}
+
+fn cached(response: impl IntoResponse) -> Response {
+    let mut response = response.into_response();
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static(SHARED_CACHE_CONTROL));
+    response
+}
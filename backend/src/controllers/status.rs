@@ -0,0 +1,138 @@
+use axum::{extract::State, response::Html};
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::services::status;
+use kicad_db::PgPool;
+
+/// Seconds between automatic reloads of the page
+const REFRESH_SECS: u32 = 10;
+
+/// Plain HTML status page for operators (health, running jobs, recent errors, token spend,
+/// deprecated route usage)
+///
+/// Self-contained on purpose: no frontend build, no external assets, readable
+/// with `curl` or a text browser over SSH.
+pub async fn status_page(State(state): State<Arc<PgPool>>) -> Html<String> {
+    let overview = status::overview(&state).await;
+    let mut html = String::new();
+
+    let _ = write!(
+        html,
+        r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><meta http-equiv="refresh" content="{refresh}">
+<title>KiCAD Watch status</title>
+<style>
+body {{ font-family: monospace; margin: 2em; }}
+table {{ border-collapse: collapse; margin-bottom: 2em; }}
+th, td {{ border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }}
+.ok {{ color: #080; }} .bad {{ color: #c00; }}
+</style></head><body>
+<h1>KiCAD Watch status</h1>
+<h2>Health</h2>
+<table>
+<tr><th>Version</th><td>{version}</td></tr>
+<tr><th>Started</th><td>{started} (up {uptime})</td></tr>
+<tr><th>Database</th><td class="{db_class}">{db}</td></tr>
+</table>
+"#,
+        refresh = REFRESH_SECS,
+        version = overview.version,
+        started = overview.started_at.format("%Y-%m-%d %H:%M:%S UTC"),
+        uptime = format_duration(overview.uptime_secs),
+        db_class = if overview.database_ok { "ok" } else { "bad" },
+        db = match &overview.database_error {
+            None => "ok".to_string(),
+            Some(e) => escape(e),
+        },
+    );
+
+    let _ = writeln!(html, "<h2>Active jobs ({})</h2>", overview.active_jobs.len());
+    if overview.active_jobs.is_empty() {
+        html.push_str("<p>None</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>Kind</th><th>Repository</th><th>Commit</th><th>Running for</th></tr>\n");
+        let now = chrono::Utc::now();
+        for job in &overview.active_jobs {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                job.kind,
+                escape(&job.repo),
+                escape(job.commit.as_deref().unwrap_or("-")),
+                format_duration((now - job.started_at).num_seconds().max(0) as u64),
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    let usage = overview.token_usage;
+    let _ = write!(
+        html,
+        "<h2>Token spend (since start)</h2>\n<table>\n\
+         <tr><th>Requests</th><td>{}</td></tr>\n\
+         <tr><th>Prompt tokens</th><td>{}</td></tr>\n\
+         <tr><th>Completion tokens</th><td>{}</td></tr>\n\
+         <tr><th>Total tokens</th><td>{}</td></tr>\n</table>\n",
+        usage.requests, usage.prompt_tokens, usage.completion_tokens, usage.total_tokens,
+    );
+
+    html.push_str("<h2>Deprecated routes (since start)</h2>\n");
+    if overview.deprecated_usage.is_empty() {
+        html.push_str("<p>No calls</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>Route</th><th>Calls</th><th>Clients</th><th>Last called</th></tr>\n");
+        for usage in &overview.deprecated_usage {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                usage.route,
+                usage.calls,
+                usage.clients,
+                usage
+                    .last_called_at
+                    .map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    let _ = writeln!(html, "<h2>Recent errors ({})</h2>", overview.recent_errors.len());
+    if overview.recent_errors.is_empty() {
+        html.push_str("<p>None</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>Time</th><th>Repository</th><th>Commit</th><th>Stage</th><th>Message</th></tr>\n");
+        for err in &overview.recent_errors {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                err.at.format("%Y-%m-%d %H:%M:%S"),
+                escape(err.repo.as_deref().unwrap_or("-")),
+                escape(err.commit.as_deref().unwrap_or("-")),
+                err.stage.unwrap_or("-"),
+                escape(&err.message),
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("</body></html>\n");
+    Html(html)
+}
+
+fn format_duration(secs: u64) -> String {
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m {}s", s / 60, s % 60),
+        s if s < 86400 => format!("{}h {}m", s / 3600, (s % 3600) / 60),
+        s => format!("{}d {}h", s / 86400, (s % 86400) / 3600),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
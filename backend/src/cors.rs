@@ -0,0 +1,119 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tracing::warn;
+
+use crate::config::CorsConfig;
+use crate::error::AppError;
+
+/// Request headers the API reads; always allowed for the configured origins
+const API_REQUEST_HEADERS: &[&str] = &[
+    "accept",
+    "authorization",
+    "cache-control",
+    "content-type",
+    "if-none-match",
+    "last-event-id",
+    "x-api-key",
+    "x-request-id",
+];
+
+/// Response headers the frontend may read
+const EXPOSED_HEADERS: &[&str] = &[
+    "deprecation",
+    "etag",
+    "link",
+    "retry-after",
+    "sunset",
+    "x-ratelimit-limit",
+    "x-ratelimit-remaining",
+    "x-ratelimit-reset",
+    "x-request-id",
+];
+
+/// The CORS layer for the router
+///
+/// Without configured origins any origin may call the API, without
+/// credentials. With them only those origins get CORS headers, for the
+/// methods, headers and exposed headers the API actually uses; preflights
+/// from anywhere else are answered without them, so browsers block the call.
+/// Responses vary on `Origin`, so caches don't hand one origin's headers to
+/// another.
+pub fn layer(config: &CorsConfig) -> CorsLayer {
+    let layer = CorsLayer::new().max_age(Duration::from_secs(config.max_age_secs));
+    if config.allowed_origins.is_empty() {
+        return layer
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any)
+            .expose_headers(Any);
+    }
+
+    let origins = config
+        .allowed_origins
+        .iter()
+        .map(|origin| HeaderValue::from_str(origin).expect("origins are checked when the config loads"));
+    let headers = API_REQUEST_HEADERS
+        .iter()
+        .map(|name| HeaderName::from_static(name))
+        .chain(config.allowed_headers.iter().filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok()));
+    layer
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE])
+        .allow_headers(headers.collect::<Vec<_>>())
+        .expose_headers(EXPOSED_HEADERS.iter().map(|name| HeaderName::from_static(name)).collect::<Vec<_>>())
+        .allow_credentials(config.allow_credentials)
+}
+
+/// Whether a request opens an event stream or a WebSocket
+///
+/// Browsers' `EventSource` and `WebSocket` can't set headers, so these may
+/// carry their credential as the `access_token` query parameter instead.
+pub fn is_stream_request(headers: &HeaderMap) -> bool {
+    let accepts_events = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/event-stream"));
+    accepts_events || is_websocket_upgrade(headers)
+}
+
+fn is_websocket_upgrade(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
+}
+
+/// Middleware refusing WebSocket upgrades from origins CORS wouldn't allow
+///
+/// Browsers open WebSockets to any origin without a preflight, leaving the
+/// server to check `Origin` itself. Non-browser clients send no `Origin`
+/// and pass.
+pub async fn check_upgrade_origin(
+    State(config): State<Arc<CorsConfig>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if is_websocket_upgrade(request.headers()) && !config.allowed_origins.is_empty() {
+        if let Some(origin) = request.headers().get(header::ORIGIN) {
+            let allowed = origin
+                .to_str()
+                .is_ok_and(|origin| config.allowed_origins.iter().any(|o| o == origin));
+            if !allowed {
+                warn!("Refused WebSocket upgrade from origin {:?}", origin);
+                return Err(AppError::new(
+                    StatusCode::FORBIDDEN,
+                    "forbidden",
+                    "Origin is not allowed to open WebSockets",
+                ));
+            }
+        }
+    }
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,67 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::rate_limit;
+use crate::services::status;
+
+static DEPRECATION_HEADER: HeaderName = HeaderName::from_static("deprecation");
+static SUNSET_HEADER: HeaderName = HeaderName::from_static("sunset");
+
+/// A route that still works but is scheduled to go away
+pub struct Deprecation {
+    /// Method and path, as shown on the status page
+    pub route: &'static str,
+    /// When the route was deprecated, as a Unix timestamp
+    pub since: i64,
+    /// HTTP-date after which the route may be removed; unset until usage
+    /// shows it's safe to pick one
+    pub sunset: Option<&'static str>,
+    /// Where callers should move to, sent as a `successor-version` link
+    pub successor: Option<&'static str>,
+}
+
+/// `GET /api/grok/chat/stream` takes no request body; chat moved to POST
+pub static CHAT_STREAM_GET: Deprecation = Deprecation {
+    route: "GET /api/grok/chat/stream",
+    since: 1792108800, // 2026-10-16
+    sunset: None,
+    successor: Some("/api/grok/chat/stream"),
+};
+
+/// Every deprecated route, for the API spec
+pub static ALL: &[&Deprecation] = &[&CHAT_STREAM_GET];
+
+/// Route middleware marking responses deprecated and counting who still calls
+///
+/// Responses get `Deprecation` (RFC 9745), `Sunset` (RFC 8594) once a date is
+/// set, and a `Link` to the successor. Calls and distinct clients per route
+/// are shown on the status page.
+/// Use as `route_layer(middleware::from_fn_with_state(&deprecation::CHAT_STREAM_GET, deprecation::deprecated))`.
+pub async fn deprecated(
+    State(deprecation): State<&'static Deprecation>,
+    request: Request,
+    next: Next,
+) -> Response {
+    status::record_deprecated_call(deprecation.route, &rate_limit::client_key(&request));
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(
+        DEPRECATION_HEADER.clone(),
+        HeaderValue::from_str(&format!("@{}", deprecation.since)).expect("timestamp is ASCII"),
+    );
+    if let Some(sunset) = deprecation.sunset {
+        headers.insert(SUNSET_HEADER.clone(), HeaderValue::from_static(sunset));
+    }
+    if let Some(successor) = deprecation.successor {
+        let link = format!("<{}>; rel=\"successor-version\"", successor);
+        if let Ok(value) = HeaderValue::from_str(&link) {
+            headers.append(axum::http::header::LINK, value);
+        }
+    }
+    response
+}
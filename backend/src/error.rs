@@ -0,0 +1,262 @@
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use std::time::Duration;
+use tracing::{error, warn};
+
+use crate::request_id;
+use crate::services::{git::RefError, metrics, status, timing::Stage};
+use crate::types::ApiError;
+use crate::validation::Violations;
+use kicad_db::XaiError;
+
+/// Retry-After for a request that found the database pool exhausted
+const POOL_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// The error type returned by every handler
+///
+/// Carries the HTTP status and public message, the underlying cause (kept as an
+/// `anyhow::Error` so the whole source chain survives), and whatever context was
+/// known where it happened: repository, commit and pipeline stage. The cause and
+/// context are logged when the error is turned into a response; clients get an
+/// `ApiError` body whose message includes the cause chain.
+#[derive(Debug)]
+pub struct AppError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    source: Option<anyhow::Error>,
+    repo: Option<String>,
+    commit: Option<String>,
+    stage: Option<Stage>,
+    /// Sent as Retry-After
+    retry_after: Option<Duration>,
+}
+
+impl AppError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            source: None,
+            repo: None,
+            commit: None,
+            stage: None,
+            retry_after: None,
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "unauthorized", message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", message)
+    }
+
+    pub fn payload_too_large(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", message)
+    }
+
+    /// A 422 for a request whose fields don't check out, listing them in the body
+    pub fn invalid(violations: Violations) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed", "Invalid request").with_source(violations)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", message)
+    }
+
+    /// Attach the underlying cause
+    ///
+    /// A cause that is the XAI circuit breaker failing fast turns the error
+    /// into a 503, and a spent AI budget into a 429, whatever it was, so
+    /// clients know to come back later, as does the database pool running out
+    /// of connections. A commit reference that names no
+    /// commit becomes a 404, and one naming several a 422.
+    pub fn with_source(mut self, source: impl Into<anyhow::Error>) -> Self {
+        let source = source.into();
+        if let Some(retry_after) = ai_unavailable(&source) {
+            self.status = StatusCode::SERVICE_UNAVAILABLE;
+            self.code = "ai_unavailable";
+            self.message = "AI temporarily unavailable".to_string();
+            self.retry_after = Some(retry_after);
+        } else if is_budget_exceeded(&source) {
+            self.status = StatusCode::TOO_MANY_REQUESTS;
+            self.code = "budget_exceeded";
+            self.message = "AI budget exceeded".to_string();
+        } else if is_pool_timeout(&source) {
+            metrics::record_pool_timeout();
+            self.status = StatusCode::SERVICE_UNAVAILABLE;
+            self.code = "database_busy";
+            self.message = "Database busy".to_string();
+            self.retry_after = Some(POOL_RETRY_AFTER);
+        } else if let Some(ref_error) = source.chain().find_map(|c| c.downcast_ref::<RefError>()) {
+            (self.status, self.code) = match ref_error {
+                RefError::NotFound { .. } => (StatusCode::NOT_FOUND, "not_found"),
+                RefError::Ambiguous { .. } => (StatusCode::UNPROCESSABLE_ENTITY, "ambiguous_commit"),
+            };
+            self.message = "Commit not resolved".to_string();
+        }
+        self.source = Some(source);
+        self
+    }
+
+    /// Attach the repository being processed
+    pub fn for_repo(mut self, repo: impl Into<String>) -> Self {
+        self.repo = Some(repo.into());
+        self
+    }
+
+    /// Attach the commit being processed
+    pub fn at_commit(mut self, commit: impl Into<String>) -> Self {
+        self.commit = Some(commit.into());
+        self
+    }
+
+    /// Attach the pipeline stage that failed
+    pub fn in_stage(mut self, stage: Stage) -> Self {
+        self.stage = Some(stage);
+        self
+    }
+
+    /// Public message, including the cause chain if there is one
+    fn full_message(&self) -> String {
+        match &self.source {
+            Some(source) => format!("{}: {:#}", self.message, source),
+            None => self.message.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.full_message())
+    }
+}
+
+/// Unexpected errors bubbled up with `?` become 500s
+impl<E> From<E> for AppError
+where
+    E: Into<anyhow::Error>,
+{
+    fn from(err: E) -> Self {
+        Self::internal("Internal error").with_source(err)
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let message = self.full_message();
+        let repo = self.repo.as_deref().unwrap_or("-");
+        let commit = self.commit.as_deref().unwrap_or("-");
+        let stage = self.stage.map(|s| s.as_str()).unwrap_or("-");
+
+        // Failing fast while the circuit is open is expected; the opening was logged
+        if self.status.is_server_error() && self.retry_after.is_none() {
+            error!(repo, commit, stage, status = %self.status, "{}", message);
+            status::record_error(
+                self.repo.as_deref(),
+                self.commit.as_deref(),
+                self.stage.map(|s| s.as_str()),
+                &message,
+            );
+        } else {
+            warn!(repo, commit, stage, status = %self.status, "{}", message);
+        }
+
+        let body = ApiError::new(self.code, message)
+            .with_request_id(request_id::current())
+            .with_violations(
+                self.source
+                    .and_then(|source| source.downcast::<Violations>().ok())
+                    .map(Violations::into_vec)
+                    .unwrap_or_default(),
+            );
+        let mut response = (self.status, Json(body)).into_response();
+        if let Some(retry_after) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+        }
+        response
+    }
+}
+
+/// Whether an error (anywhere in its source chain) is an XAI rate limit
+pub fn is_rate_limited(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<XaiError>(),
+            Some(XaiError::RateLimited(_))
+        )
+    })
+}
+
+/// Whether an error (anywhere in its source chain) is a query that found no
+/// free pool connection in time
+pub fn is_pool_timeout(err: &anyhow::Error) -> bool {
+    err.chain()
+        .any(|cause| matches!(cause.downcast_ref::<sqlx::Error>(), Some(sqlx::Error::PoolTimedOut)))
+}
+
+/// Whether an error (anywhere in its source chain) is a spent AI budget
+pub fn is_budget_exceeded(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<XaiError>(),
+            Some(XaiError::BudgetExceeded(_))
+        )
+    })
+}
+
+/// How long until an XAI call may be retried, if the error (anywhere in its
+/// source chain) is the circuit breaker failing fast
+pub fn ai_unavailable(err: &anyhow::Error) -> Option<Duration> {
+    err.chain().find_map(|cause| match cause.downcast_ref::<XaiError>() {
+        Some(XaiError::Unavailable { retry_after }) => Some(*retry_after),
+        _ => None,
+    })
+}
+
+/// Convert any error into an `AppError` with a public message, keeping the cause
+pub trait ResultExt<T> {
+    /// Map the error to a 500 with `message`
+    fn or_internal(self, message: impl Into<String>) -> Result<T, AppError>;
+}
+
+impl<T, E> ResultExt<T> for Result<T, E>
+where
+    E: Into<anyhow::Error>,
+{
+    fn or_internal(self, message: impl Into<String>) -> Result<T, AppError> {
+        self.map_err(|e| AppError::internal(message).with_source(e))
+    }
+}
+
+/// Attach request context to an `AppError` on its way out of a handler
+pub trait AppResultExt<T> {
+    fn for_repo(self, repo: &str) -> Result<T, AppError>;
+    fn at_commit(self, commit: &str) -> Result<T, AppError>;
+    fn in_stage(self, stage: Stage) -> Result<T, AppError>;
+}
+
+impl<T> AppResultExt<T> for Result<T, AppError> {
+    fn for_repo(self, repo: &str) -> Result<T, AppError> {
+        self.map_err(|e| e.for_repo(repo))
+    }
+
+    fn at_commit(self, commit: &str) -> Result<T, AppError> {
+        self.map_err(|e| e.at_commit(commit))
+    }
+
+    fn in_stage(self, stage: Stage) -> Result<T, AppError> {
+        self.map_err(|e| e.in_stage(stage))
+    }
+}
@@ -0,0 +1,81 @@
+//! The KiCad schematic API server
+//!
+//! `main.rs` loads the configuration and starts the background jobs; the
+//! router itself is built here, so tests can serve it in-process.
+
+use axum::Router;
+use std::sync::Arc;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+pub mod auth;
+pub mod compression;
+pub mod conditional;
+pub mod config;
+pub mod controllers;
+pub mod cors;
+pub mod deprecation;
+pub mod error;
+pub mod limits;
+pub mod openapi;
+pub mod rate_limit;
+pub mod redact;
+pub mod request_id;
+pub mod routes;
+pub mod services;
+pub mod share;
+pub mod shutdown;
+pub mod state;
+pub mod storage;
+pub mod types;
+pub mod validation;
+
+use openapi::ApiDoc;
+use state::AppState;
+
+/// Every route, behind timeouts and body limits, compression, auth, CORS,
+/// tracing and request IDs
+///
+/// Serve it with `into_make_service_with_connect_info::<SocketAddr>()`; the
+/// per-client rate limits read the peer address.
+pub fn app(app_state: AppState) -> Router {
+    let cors_config = Arc::new(app_state.config().cors.clone());
+    let limits = Arc::new(app_state.config().limits.clone());
+    Router::new()
+        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
+        .nest("/api/repo", routes::repo::router(app_state.pool.clone()))
+        .nest("/api/repos", routes::repos::router(app_state.pool.clone()))
+        .nest("/api/search", routes::search::router())
+        .nest("/api/components", routes::components::router())
+        .nest(
+            "/api/hook",
+            routes::hook::router(app_state.pool.clone()).layer(limits::webhook_body_limit(&limits)),
+        )
+        .nest("/api/grok", routes::grok::router(app_state.pool.clone()))
+        .nest("/api/distill", routes::distill::router(app_state.pool.clone()))
+        .nest("/api/digikey", routes::digikey::router())
+        .nest("/api/keys", routes::keys::router())
+        .nest("/api/orgs", routes::orgs::router())
+        .nest("/api/admin", routes::admin::router(app_state.pool.clone()))
+        .nest("/api/public", routes::public::router())
+        .nest("/api/shared", routes::shared::router(app_state.clone()))
+        .nest("/status", routes::status::router())
+        .merge(routes::health::router())
+        .nest("/metrics", routes::metrics::router())
+        .route_layer(axum::middleware::from_fn_with_state(
+            app_state.config().uses_sqlite(),
+            storage::require_postgres,
+        ))
+        .route_layer(axum::middleware::from_fn(services::metrics::track_requests))
+        .layer(limits::body_limit(&limits))
+        .layer(axum::middleware::from_fn_with_state(limits.clone(), limits::enforce))
+        .layer(compression::layer())
+        .layer(axum::middleware::from_fn(compression::weaken_etag))
+        .layer(axum::middleware::from_fn(services::costs::attribute))
+        .layer(axum::middleware::from_fn_with_state(app_state.clone(), auth::authenticate))
+        .layer(axum::middleware::from_fn_with_state(cors_config.clone(), cors::check_upgrade_origin))
+        .layer(cors::layer(&cors_config))
+        .layer(tower_http::trace::TraceLayer::new_for_http().make_span_with(request_id::make_span))
+        .layer(axum::middleware::from_fn(request_id::assign))
+        .with_state(app_state)
+}
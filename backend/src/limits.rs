@@ -0,0 +1,97 @@
+use axum::{
+    extract::{DefaultBodyLimit, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::LimitsConfig;
+use crate::error::AppError;
+
+/// Routes under these call the AI API, clone or process repositories
+const LONG_PREFIXES: &[&str] = &["/api/grok/", "/api/hook/", "/api/distill", "/api/repo/", "/api/admin/webhooks/", "/api/admin/onboard/"];
+
+/// Routes ending in these move whole repositories or render documents
+const LONG_SUFFIXES: &[&str] = &["/import", "/export", "/schematic.pdf"];
+
+/// Server-sent event routes, whose handlers answer as soon as the stream is set up
+const STREAM_SUFFIXES: &[&str] = &["/stream", "/events"];
+
+/// How long a route may take before its response starts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RouteClass {
+    /// CRUD and lookups
+    Short,
+    /// See [`LONG_PREFIXES`] and [`LONG_SUFFIXES`]
+    Long,
+    /// SSE; the long timeout covers the setup, not the stream itself
+    Stream,
+}
+
+impl RouteClass {
+    pub fn of(path: &str) -> Self {
+        if STREAM_SUFFIXES.iter().any(|suffix| path.ends_with(suffix)) {
+            RouteClass::Stream
+        } else if LONG_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+            || LONG_SUFFIXES.iter().any(|suffix| path.ends_with(suffix))
+        {
+            RouteClass::Long
+        } else {
+            RouteClass::Short
+        }
+    }
+
+    /// Its timeout under `limits`, if it has one
+    pub fn timeout(self, limits: &LimitsConfig) -> Option<Duration> {
+        let secs = match self {
+            RouteClass::Short => limits.request_timeout_secs,
+            RouteClass::Long | RouteClass::Stream => limits.long_request_timeout_secs,
+        };
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+}
+
+/// The body limit of every route that doesn't set its own; the hook router
+/// gets [`webhook_body_limit`] instead, and imports and image uploads
+/// theirs
+pub fn body_limit(limits: &LimitsConfig) -> DefaultBodyLimit {
+    DefaultBodyLimit::max(limits.max_body_bytes)
+}
+
+pub fn webhook_body_limit(limits: &LimitsConfig) -> DefaultBodyLimit {
+    DefaultBodyLimit::max(limits.webhook_max_body_bytes)
+}
+
+/// Middleware answering requests that run past their route's timeout with a
+/// 408, and bodies over their limit with a 413, both as `ApiError`s
+///
+/// Body limits are enforced by the extractors (see [`body_limit`]), which
+/// reject with plain text; those rejections get an `ApiError` body here.
+pub async fn enforce(State(limits): State<Arc<LimitsConfig>>, request: Request, next: Next) -> Response {
+    let class = RouteClass::of(request.uri().path());
+    let response = match class.timeout(&limits) {
+        Some(timeout) => match tokio::time::timeout(timeout, next.run(request)).await {
+            Ok(response) => response,
+            Err(_) => {
+                return AppError::new(
+                    StatusCode::REQUEST_TIMEOUT,
+                    "request_timeout",
+                    format!("The request took longer than {}s", timeout.as_secs()),
+                )
+                .into_response()
+            }
+        },
+        None => next.run(request).await,
+    };
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|content_type| content_type.as_bytes().starts_with(b"application/json"));
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE && !is_json {
+        return AppError::payload_too_large("The request body is over the size limit").into_response();
+    }
+    response
+}
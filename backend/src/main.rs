@@ -1,65 +1,162 @@
 use anyhow::Context;
-use axum::Router;
-use std::sync::Arc;
-use tower_http::cors::{Any, CorsLayer};
-use tracing::info;
+use std::net::SocketAddr;
+use tracing::{info, warn};
 use utoipa::OpenApi;
-use utoipa_swagger_ui::SwaggerUi;
 
-mod controllers;
-mod openapi;
-mod routes;
-mod services;
-mod types;
-
-use openapi::ApiDoc;
+use kicad_backend::config::Config;
+use kicad_backend::openapi::ApiDoc;
+use kicad_backend::state::AppState;
+use kicad_backend::{rate_limit, services, shutdown, storage};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Load environment variables from .env file
-    dotenvy::dotenv().ok();
-    
-    tracing_subscriber::fmt().init();
-
-    let pool = kicad_db::create_pool()
-        .await
-        .context("Failed to create database pool")?;
-
-    let app_state = Arc::new(pool);
-
-    // Configure CORS to allow requests from the frontend domain
-    // Note: If you want to restrict to specific origins, use:
-    // .allow_origin("https://grokicad.com".parse::<HeaderValue>().unwrap())
-    let cors = CorsLayer::new()
-        .allow_origin(Any) // Allow all origins in production (can be restricted to specific domains)
-        .allow_methods(Any)
-        .allow_headers(Any)
-        .expose_headers(Any)
-        .allow_credentials(false) // Set to true if you need to send cookies/auth headers
-        .max_age(std::time::Duration::from_secs(3600));
-
-    let app = Router::new()
-        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
-        .nest("/api/repo", routes::repo::router())
-        .nest("/api/hook", routes::hook::router())
-        .nest("/api/grok", routes::grok::router())
-        .nest("/api/distill", routes::distill::router())
-        .nest("/api/digikey", routes::digikey::router())
-        .layer(cors)
-        .layer(tower_http::trace::TraceLayer::new_for_http())
-        .with_state(app_state);
+    // `kicad-backend openapi` prints the API spec, e.g. to regenerate openapi.json
+    if std::env::args().nth(1).as_deref() == Some("openapi") {
+        println!("{}", ApiDoc::openapi().to_pretty_json()?);
+        return Ok(());
+    }
+
+    // `kicad-backend doctor` checks the configuration and everything it points
+    // at, prints a report and exits non-zero if anything failed
+    if std::env::args().nth(1).as_deref() == Some("doctor") {
+        let checks = services::doctor::run().await;
+        print!("{}", services::doctor::report(&checks));
+        std::process::exit(if services::doctor::passed(&checks) { 0 } else { 1 });
+    }
+
+    // Load .env files and set up tracing, then read settings on top of them
+    kicad_db::init(&[]).context("Invalid environment")?;
+    let config = Config::load().context("Invalid configuration")?;
+    info!("Configuration: {}", config.summary());
+    services::status::init();
+    services::metrics::install();
+    services::git::set_cache_dir(config.git_cache_dir.clone());
+    services::lfs::set_max_mb(config.git_lfs_max_mb);
+    rate_limit::set_trusted_proxies(config.trusted_proxies()?);
+    services::reload::apply(&config);
+    kicad_db::set_blob_store(config.blob_store()?);
+
+    let (pool, store) = storage::connect(&config).await?;
+    info!("Database migrations applied");
+
+    // A SQLite file holds schematics only; what's below needs Postgres
+    let postgres = !config.uses_sqlite();
+    if postgres {
+        services::registry::load(&pool)
+            .await
+            .context("Failed to load registered repositories")?;
+    } else {
+        warn!("Schematics are stored in SQLite; background jobs and routes needing Postgres are off");
+    }
+
+    let prompts = services::reload::load_prompts(&config, &pool).await?;
+    let labels: Vec<String> = prompts.templates().map(|t| t.label()).collect();
+    info!("Prompt templates: {}", labels.join(", "));
+    services::experiments::check(&config, &prompts).context("Invalid experiment")?;
+
+    let port = config.port;
+    let app_state = AppState::new(pool, config, prompts)?.with_store(store);
+    let models = app_state.config().models.allowlist().into_iter().map(String::from).collect();
+    services::llm::spawn_model_check(app_state.chat.clone(), models);
+    services::response_cache::spawn_invalidation(app_state.responses.clone());
+    services::reload::spawn(app_state.clone());
+    if postgres {
+        spawn_background_jobs(&app_state)?;
+    }
+
+    let pool = app_state.pool.clone();
+    let drain_timeout = std::time::Duration::from_secs(app_state.config().drain_timeout_secs);
+    let app = kicad_backend::app(app_state);
 
     // Listen on HTTP port (Cloudflare will handle HTTPS termination)
     // Use port 8080 for HTTP, or port 80 if running as root
-    let port = std::env::var("PORT")
-        .ok()
-        .and_then(|p| p.parse::<u16>().ok())
-        .unwrap_or(8080);
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await?;
     info!("Server listening on 0.0.0.0:{}", port);
     info!("Swagger UI available at http://localhost:{}/swagger-ui/", port);
 
-    axum::serve(listener, app).await?;
+    // Peer addresses feed the per-client rate limit on the public endpoints.
+    // On shutdown, stop accepting connections and let requests, streams and
+    // the jobs they started finish, within the drain timeout.
+    let drained = async {
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(shutdown::on_signal())
+            .await?;
+        services::status::wait_idle().await;
+        anyhow::Ok(())
+    };
+    let deadline = async {
+        shutdown::started().await;
+        tokio::time::sleep(drain_timeout).await;
+    };
+    tokio::select! {
+        result = drained => result?,
+        _ = deadline => warn!(
+            "Shutdown drain timed out after {:?}; abandoning {} running job(s)",
+            drain_timeout,
+            services::status::active_job_count()
+        ),
+    }
+
+    pool.close().await;
+    info!("Shutdown complete");
+    kicad_db::logging::shutdown();
+    Ok(())
+}
+
+/// The jobs working through what Postgres holds: purges, indexing,
+/// classification, re-syncs, lifecycle checks, thumbnails, backfills,
+/// commit events and digests
+fn spawn_background_jobs(app_state: &AppState) -> anyhow::Result<()> {
+    // Weekly re-run of the drift evaluation set (no-op unless configured)
+    services::drift::spawn(app_state.clone());
+
+    if app_state.config().ai_cache_ttl_secs > 0 {
+        services::ai_cache::spawn_purge(app_state.pool.clone());
+    }
+
+    if app_state.config().audit.enabled {
+        services::prompt_audit::spawn_purge(
+            app_state.pool.clone(),
+            std::time::Duration::from_secs(app_state.config().audit.retention_secs),
+        );
+    }
+
+    services::semantic::spawn_indexer(
+        app_state.pool.clone(),
+        app_state.chat.clone(),
+        app_state.config().models.embedding.clone(),
+    );
+
+    // Sorts newly seen parts into the component taxonomy
+    services::taxonomy::spawn_classifier(
+        app_state.pool.clone(),
+        services::sampling::sampled(app_state.chat.clone(), &app_state.config().sampling.classification),
+        app_state.prompts.clone(),
+        app_state.health.clone(),
+        app_state.config.clone(),
+    );
+
+    // Catch commits whose webhooks never arrived
+    services::scheduler::spawn(app_state.clone(), app_state.config().resync_interval_secs);
+
+    // Flags parts going NRND, EOL or obsolete in registered repos
+    services::lifecycle::spawn(app_state.pool.clone(), app_state.config().lifecycle_check_interval_secs);
+
+    services::thumbnails::spawn(app_state.pool.clone());
+
+    // Backfills cut short by the last restart collect their deferred summaries
+    services::backfill::spawn_resume(app_state.clone());
+
+    // Relays commit events to the repos' event streams
+    services::events::spawn(app_state.pool.clone());
+
+    services::retention::spawn_purge(
+        app_state.pool.clone(),
+        std::time::Duration::from_secs(app_state.config().deleted_retention_secs),
+    );
 
+    if let Some(mailer) = app_state.config().mailer()? {
+        services::digest::spawn(app_state.clone(), mailer);
+    }
     Ok(())
 }
@@ -1,15 +1,33 @@
-use utoipa::OpenApi;
+use utoipa::openapi::path::PathItemType;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme};
+use utoipa::openapi::Deprecated;
+use utoipa::{Modify, OpenApi};
 
-use crate::controllers::{digikey, distill, grok, hook, repo};
+use crate::controllers::{
+    admin, components, digikey, distill, grok, guidelines, health, hook, keys, orgs, public, repo, repos, search, shared,
+};
+use crate::deprecation;
 use crate::types::{
-    ApiError, CommitFilesRequest, CommitFilesResponse, CommitInfo, CommitInfoRequest,
-    CommitInfoResponse, DigiKeyParameter, DigiKeyPartInfo, DigiKeySearchRequest,
-    DigiKeySearchResponse, DistillRequest, DistillResponse, GrokCommitSummaryRequest,
+    AnalysisTimeline, ApiError, FieldViolation, BackfillError, BackfillProgress, BackfillStatus, CostLine, CostReportResponse, AdminOverviewResponse, CacheHitRate, ErrorRate, JobQueueDepth, ClassQueueDepth, WorkClass, ChatRole, ChatSource, ChatSourceKind, ChatSourcesEvent, ChatCitationItem, ChatAttachmentItem, ChatCitationsEvent, ChatAttachmentsEvent, StreamUsageEvent, ChatTurn, CreateChatSessionRequest, ChatSessionResponse, ChatSessionTurn, DependencyStatus, LivenessResponse, ReadinessResponse, ApiKeyItem, ApiKeyListResponse, BoardResponse, BoardSummary, CommitStatsItem, CommitStatsResponse, SchematicStatsItem, StatsHistoryResponse, BomLineItem, BomResponse, BoundingBox, ComponentPlacement, ComponentsResponse, CategorizedPart, PartCategoryCount, PartListResponse, ClassifyPartsResponse, ReloadConfigResponse, LifecycleCheckResponse, OnboardGithubOrgRequest, OnboardGithubOrgResponse, OnboardStatus, OnboardWebhook, OnboardedRepoItem, ShareScope, CreateShareLinkRequest, ShareLinkResponse, SharedCommitResponse, SharedSummaryResponse, CommitTimeline, ComponentHistoryItem, ComponentHistoryResponse, CommitFilesRequest, CommitFilesResponse, CommitInfo, CommitInfoRequest,
+    CommitChangesResponse, CommitInfoResponse, ComponentChangeItem, CreateApiKeyRequest, CreateApiKeyResponse, DigiKeyParameter, DiffWarningItem, ErcFindingItem, ErcResponse, NetChangeItem, PinChangeItem, ProjectChanges, DigiKeyPartInfo, DigiKeySearchRequest,
+    DigiKeySearchResponse, DigiKeyStatusResponse, DistillRequest, DistillResponse, FootprintItem, GrokCommitSummaryRequest,
     GrokCommitSummaryResponse, GrokObsoleteReplacementRequest, GrokObsoleteReplacementResponse,
-    GrokRepoSummaryRequest, GrokRepoSummaryResponse, GrokSelectionStreamRequest,
-    GrokSelectionSummaryRequest, GrokSelectionSummaryResponse, HookUpdateResponse,
-    RepoClearCacheRequest, RepoClearCacheResponse, RepoCommitsRequest, RepoCommitsResponse,
-    RepoInitRequest, RepoInitResponse, SchematicFile,
+    GrokChatRequest, GrokCompareSummaryRequest, GrokCompareSummaryResponse, GrokReleaseNotesRequest, GrokReleaseNotesResponse, ReleaseCategory, ReleaseNoteChange, GrokReviewChecklistRequest, ReviewChecklistItem, ReviewChecklistResponse, GrokSuggestFixRequest, SuggestFixResponse, UpdateChecklistItemRequest, GrokAskRequest, GrokAskResponse, GrokRepoSummaryRequest, GrokRepoSummaryResponse, GrokSelectionStreamRequest,
+    GrokSelectionSummaryRequest, GrokSelectionSummaryResponse, Selection, HookUpdateResponse, NetConnectorItem, NetConnectorKind, NetGeometryResponse, NetItem, NetNodeItem,
+    NetPinItem, NetSheetGeometry, ProjectNetGeometry,
+    NetlistResponse, PartMetadataResponse, ComponentUsageItem, ComponentUsageResponse, SharedComponentItem, SharedComponentsResponse, PersonaInfo, PublicSummaryResponse, ProjectBom, ProjectNetlist, ProjectSummary,
+    ModelListResponse, PersonaListResponse, RepoChangelogRequest, RepoChangelogResponse, RepoChangelogSettingsRequest,
+    RepoChangelogSettingsResponse, RepoClearCacheRequest, RepoClearCacheResponse, RepoCommitsRequest,
+    RepoCommitsResponse, RepoInitRequest, RepoInitResponse, RepoPersonaRequest,
+    RepoPersonaResponse, RepoVisibilityRequest, RepoVisibilityResponse, RevokeApiKeyResponse, RegisterRepoRequest, RegisteredRepoItem, RegisteredRepoListResponse, SubmodulePin, ProcessingPolicy, RepoScheduleItem, ScheduleListResponse, SchematicFile, SearchResponse, SearchResult, SemanticCommitResult, TextSearchResult,
+    SemanticComponentResult, SemanticSearchResponse, StoredCommit,
+    StoredCommitsResponse, UnregisterRepoResponse, DigestSubscriberRequest, DigestSubscriberItem, DigestSubscribersResponse, DeleteCommitResponse, UploadImageResponse, RepoArchiveHeader, ImportRepoResponse, TimelineCommit, TimelineResponse, ProcessingErrorItem, ProcessingErrorsResponse, RepoAlertsResponse, PartAlertItem, RepoEvent, RepoEventKind, UpdateScheduleRequest,
+    WebhookDeliveryItem, WebhookDeliveryListResponse, JobCommit, JobCommitStatus, JobListResponse,
+    JobDetailResponse, PromptAuditItem, PromptAuditListResponse, OrganizationItem, OrganizationListResponse,
+    CreateOrganizationRequest, MemberItem, MemberListResponse, SetMemberRequest, RemoveMemberResponse,
+    DesignGuidelineRequest, DesignGuidelineItem, OrgGuidelinesResponse, RepoGuidelinesResponse, DeleteGuidelineResponse,
+    SummaryRating, SummaryFeedbackRequest, SummaryFeedbackResponse, FeedbackTotalItem, FeedbackReportResponse,
+    ExperimentVariantItem, ExperimentsResponse,
 };
 
 #[derive(OpenApi)]
@@ -25,18 +43,122 @@ use crate::types::{
         repo::get_commit_info,
         repo::init_repo,
         repo::clear_cache,
+        repo::set_default_persona,
+        repo::set_visibility,
+        repo::set_changelog,
+        repo::generate_changelog,
+        repos::list_stored_commits,
+        repos::component_history,
+        repos::raw_file,
+        repos::file_at_commit,
+        repos::erc,
+        repos::board,
+        repos::commit_stats,
+        repos::stats_history,
+        repos::bom,
+        repos::netlist,
+        repos::components,
+        repos::parts,
+        repos::net_geometry,
+        repos::symbol_svg,
+        repos::schematic_pdf,
+        search::search,
+        search::semantic_search,
+        public::badge,
+        public::latest_summary,
+        shared::shared_commit,
+        shared::shared_summary,
+        shared::shared_changes,
+        shared::shared_sheet,
+        components::get_part_metadata,
+        components::get_component_usage,
+        components::get_datasheet,
+        components::list_shared_components,
         hook::update_repo,
         hook::refresh_repo,
         hook::github_webhook,
+        hook::gitlab_webhook,
+        hook::bitbucket_webhook,
         grok::summarize_commit,
+        grok::summarize_commit_stream,
         grok::summarize_selection,
         grok::summarize_repo,
+        grok::summarize_compare,
+        grok::release_notes,
+        grok::review_checklist,
+        grok::suggest_fix,
+        grok::ask,
+        grok::backfill_summaries,
+        grok::chat,
+        grok::create_chat_session,
+        grok::get_chat_session,
         grok::chat_stream,
         grok::selection_stream,
         grok::find_replacement,
+        grok::list_personas,
+        grok::list_models,
+        grok::summary_feedback,
         distill::distill_schematics,
         digikey::search_parts,
         digikey::get_status,
+        repos::register,
+        repos::list_registered,
+        repos::get_registered,
+        repos::update_processing,
+        repos::unregister,
+        repos::digest_subscribers,
+        repos::subscribe_digest,
+        repos::unsubscribe_digest,
+        repos::digest_preview,
+        guidelines::repo_guidelines,
+        guidelines::create_repo_guideline,
+        guidelines::update_repo_guideline,
+        guidelines::delete_repo_guideline,
+        repos::delete_commit,
+        repos::export,
+        repos::changes,
+        repos::share_commit,
+        repos::checklist,
+        repos::update_checklist_item,
+        repos::timeline,
+        repos::processing_errors,
+        repos::alerts,
+        repos::events,
+        repos::schematic_image,
+        repos::upload_image,
+        repos::thumbnail,
+        repos::import,
+        keys::list_keys,
+        keys::create_key,
+        keys::revoke_key,
+        orgs::list_orgs,
+        orgs::create_org,
+        orgs::get_members,
+        orgs::set_member,
+        orgs::remove_member,
+        guidelines::org_guidelines,
+        guidelines::create_org_guideline,
+        guidelines::update_org_guideline,
+        guidelines::delete_org_guideline,
+        admin::list_schedules,
+        admin::update_schedule,
+        admin::overview,
+        admin::classify_parts,
+        admin::reload_config,
+        admin::check_lifecycles,
+        admin::onboard_github_org,
+        admin::cost_report,
+        admin::feedback_report,
+        admin::list_experiments,
+        admin::list_webhook_deliveries,
+        admin::replay_webhook,
+        admin::list_jobs,
+        admin::get_job,
+        admin::cancel_job,
+        admin::retry_job,
+        admin::list_prompt_audits,
+        health::healthz,
+        health::readyz,
     ),
     components(schemas(
         RepoCommitsRequest,
@@ -45,6 +167,65 @@ use crate::types::{
         RepoInitResponse,
         RepoClearCacheRequest,
         RepoClearCacheResponse,
+        RepoPersonaRequest,
+        RepoPersonaResponse,
+        RepoVisibilityRequest,
+        RepoVisibilityResponse,
+        RepoChangelogSettingsRequest,
+        RepoChangelogSettingsResponse,
+        RepoChangelogRequest,
+        RepoChangelogResponse,
+        StoredCommit,
+        StoredCommitsResponse,
+        ComponentHistoryItem,
+        ComponentHistoryResponse,
+        DiffWarningItem,
+        ErcFindingItem,
+        ErcResponse,
+        FootprintItem,
+        BoardSummary,
+        BoardResponse,
+        CommitStatsItem,
+        CommitStatsResponse,
+        SchematicStatsItem,
+        StatsHistoryResponse,
+        ProjectSummary,
+        BomLineItem,
+        ProjectBom,
+        BomResponse,
+        NetNodeItem,
+        NetItem,
+        ProjectNetlist,
+        NetlistResponse,
+        BoundingBox,
+        ComponentPlacement,
+        ComponentsResponse,
+        CategorizedPart,
+        PartCategoryCount,
+        PartListResponse,
+        NetConnectorKind,
+        NetConnectorItem,
+        NetPinItem,
+        NetSheetGeometry,
+        ProjectNetGeometry,
+        NetGeometryResponse,
+        ComponentChangeItem,
+        PinChangeItem,
+        NetChangeItem,
+        ProjectChanges,
+        CommitChangesResponse,
+        SearchResult,
+        SearchResponse,
+        TextSearchResult,
+        SemanticCommitResult,
+        SemanticComponentResult,
+        SemanticSearchResponse,
+        PublicSummaryResponse,
+        PartMetadataResponse,
+        ComponentUsageItem,
+        ComponentUsageResponse,
+        SharedComponentItem,
+        SharedComponentsResponse,
         CommitInfo,
         CommitFilesRequest,
         CommitFilesResponse,
@@ -52,29 +233,211 @@ use crate::types::{
         CommitInfoRequest,
         CommitInfoResponse,
         HookUpdateResponse,
+        AnalysisTimeline,
+        CommitTimeline,
         GrokCommitSummaryRequest,
         GrokCommitSummaryResponse,
+        BackfillProgress,
+        BackfillStatus,
+        BackfillError,
         GrokSelectionStreamRequest,
+        GrokChatRequest,
+        ChatRole,
+        ChatTurn,
+        CreateChatSessionRequest,
+        ChatSessionResponse,
+        ChatSessionTurn,
+        ChatSource,
+        ChatSourceKind,
+        ChatSourcesEvent,
+        ChatCitationItem,
+        ChatAttachmentItem,
+        ChatCitationsEvent,
+        ChatAttachmentsEvent,
+        StreamUsageEvent,
         GrokSelectionSummaryRequest,
         GrokSelectionSummaryResponse,
+        Selection,
         GrokRepoSummaryRequest,
+        GrokCompareSummaryRequest,
+        GrokCompareSummaryResponse,
+        GrokReleaseNotesRequest,
+        GrokReleaseNotesResponse,
+        ReleaseNoteChange,
+        ReleaseCategory,
+        GrokReviewChecklistRequest,
+        ReviewChecklistItem,
+        ReviewChecklistResponse,
+        GrokSuggestFixRequest,
+        SuggestFixResponse,
+        GrokAskRequest,
+        GrokAskResponse,
+        UpdateChecklistItemRequest,
         GrokRepoSummaryResponse,
         GrokObsoleteReplacementRequest,
         GrokObsoleteReplacementResponse,
+        PersonaInfo,
+        PersonaListResponse,
+        ModelListResponse,
         DistillRequest,
         DistillResponse,
         DigiKeySearchRequest,
         DigiKeySearchResponse,
+        DigiKeyStatusResponse,
         DigiKeyPartInfo,
         DigiKeyParameter,
+        ApiKeyItem,
+        ApiKeyListResponse,
+        CreateApiKeyRequest,
+        CreateApiKeyResponse,
+        RevokeApiKeyResponse,
+        RegisterRepoRequest,
+        RegisteredRepoItem,
+        SubmodulePin,
+        ProcessingPolicy,
+        RegisteredRepoListResponse,
+        UnregisterRepoResponse,
+        DigestSubscriberRequest,
+        DigestSubscriberItem,
+        DigestSubscribersResponse,
+        DeleteCommitResponse,
+        UploadImageResponse,
+        RepoArchiveHeader,
+        ImportRepoResponse,
+        TimelineCommit,
+        TimelineResponse,
+        ProcessingErrorItem,
+        ProcessingErrorsResponse,
+        RepoAlertsResponse,
+        PartAlertItem,
+        RepoEvent,
+        RepoEventKind,
+        RepoScheduleItem,
+        ScheduleListResponse,
+        UpdateScheduleRequest,
+        CostLine,
+        CostReportResponse,
+        AdminOverviewResponse,
+        ClassifyPartsResponse,
+        ReloadConfigResponse,
+        LifecycleCheckResponse,
+        OnboardGithubOrgRequest,
+        OnboardGithubOrgResponse,
+        OnboardStatus,
+        OnboardWebhook,
+        OnboardedRepoItem,
+        ShareScope,
+        CreateShareLinkRequest,
+        ShareLinkResponse,
+        SharedCommitResponse,
+        SharedSummaryResponse,
+        JobQueueDepth,
+        ClassQueueDepth,
+        WorkClass,
+        CacheHitRate,
+        ErrorRate,
+        WebhookDeliveryItem,
+        WebhookDeliveryListResponse,
+        JobCommit,
+        JobCommitStatus,
+        JobListResponse,
+        JobDetailResponse,
+        PromptAuditItem,
+        PromptAuditListResponse,
+        OrganizationItem,
+        OrganizationListResponse,
+        CreateOrganizationRequest,
+        MemberItem,
+        MemberListResponse,
+        SetMemberRequest,
+        RemoveMemberResponse,
+        DesignGuidelineRequest,
+        DesignGuidelineItem,
+        OrgGuidelinesResponse,
+        RepoGuidelinesResponse,
+        DeleteGuidelineResponse,
+        SummaryRating,
+        SummaryFeedbackRequest,
+        SummaryFeedbackResponse,
+        FeedbackTotalItem,
+        FeedbackReportResponse,
+        ExperimentVariantItem,
+        ExperimentsResponse,
+        LivenessResponse,
+        DependencyStatus,
+        ReadinessResponse,
         ApiError,
+        FieldViolation,
     )),
     tags(
         (name = "repo", description = "Repository and commit information endpoints"),
         (name = "hook", description = "Webhook endpoints for triggering updates"),
         (name = "grok", description = "AI-powered analysis endpoints"),
         (name = "distill", description = "Schematic distillation endpoints"),
-        (name = "digikey", description = "DigiKey part lookup endpoints")
-    )
+        (name = "digikey", description = "DigiKey part lookup endpoints"),
+        (name = "search", description = "Full-text and semantic search over stored commits"),
+        (name = "components", description = "Supplier metadata for parts"),
+        (name = "public", description = "Unauthenticated embeds for public repositories"),
+        (name = "shared", description = "Read-only commit views opened by a share link instead of an API key"),
+        (name = "keys", description = "API key management (admin keys only)"),
+        (name = "orgs", description = "Organizations and their members (admin keys only)"),
+        (name = "admin", description = "Server administration (admin keys only)"),
+        (name = "health", description = "Liveness and readiness probes")
+    ),
+    modifiers(&Credentials, &DeprecatedRoutes)
 )]
 pub struct ApiDoc;
+
+/// Documents the two ways to send a key: `X-API-Key` or `Authorization: Bearer`
+struct Credentials;
+
+impl Modify for Credentials {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme("api_key", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))));
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+            components.add_security_scheme(
+                "access_token",
+                SecurityScheme::ApiKey(ApiKey::Query(ApiKeyValue::with_description(
+                    "access_token",
+                    "API key or JWT for event streams and WebSockets, whose browser APIs can't set headers",
+                ))),
+            );
+        }
+        openapi.security = Some(vec![
+            SecurityRequirement::new("api_key", Vec::<String>::new()),
+            SecurityRequirement::new("bearer", Vec::<String>::new()),
+        ]);
+    }
+}
+
+/// Marks the routes in [`deprecation::ALL`] deprecated
+struct DeprecatedRoutes;
+
+impl Modify for DeprecatedRoutes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for route in deprecation::ALL {
+            let Some((method, path)) = route.route.split_once(' ') else {
+                continue;
+            };
+            let method = match method {
+                "GET" => PathItemType::Get,
+                "POST" => PathItemType::Post,
+                "PUT" => PathItemType::Put,
+                "DELETE" => PathItemType::Delete,
+                _ => continue,
+            };
+            if let Some(operation) = openapi
+                .paths
+                .paths
+                .get_mut(path)
+                .and_then(|item| item.operations.get_mut(&method))
+            {
+                operation.deprecated = Some(Deprecated::True);
+            }
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use once_cell::sync::{Lazy, OnceCell};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::auth::Caller;
+use crate::config::RateLimitConfig;
+use crate::error::AppError;
+
+const WINDOW: Duration = Duration::from_secs(60);
+/// Above this many tracked clients, expired windows are dropped
+const TABLE_PRUNE_AT: usize = 10_000;
+
+static LIMIT_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+static REMAINING_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+static RESET_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+/// Routes that call the AI API: summaries, chat and replacement search
+pub static AI_CALLS: Lazy<RateLimiter> =
+    Lazy::new(|| RateLimiter::new("AI", RateLimitConfig::default().ai_per_minute));
+
+/// Cheap AI-adjacent routes, such as listing personas
+pub static LISTINGS: Lazy<RateLimiter> =
+    Lazy::new(|| RateLimiter::new("listing", RateLimitConfig::default().listing_per_minute));
+
+/// Unauthenticated badge and embed endpoints
+pub static PUBLIC_EMBEDS: Lazy<RateLimiter> = Lazy::new(|| RateLimiter::new("public embed", 60));
+
+/// Views through share links, which need no API key
+pub static SHARED_VIEWS: Lazy<RateLimiter> =
+    Lazy::new(|| RateLimiter::new("shared view", RateLimitConfig::default().shared_per_minute));
+
+/// Apply the configured budgets; called at startup and on each reload
+pub fn configure(limits: &RateLimitConfig) {
+    AI_CALLS.set_limit(limits.ai_per_minute);
+    LISTINGS.set_limit(limits.listing_per_minute);
+    SHARED_VIEWS.set_limit(limits.shared_per_minute);
+}
+
+/// Outcome of counting one request against a client's budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the client's window resets
+    pub reset_secs: u64,
+}
+
+impl Quota {
+    fn headers(&self) -> [(HeaderName, HeaderValue); 3] {
+        [
+            (LIMIT_HEADER.clone(), HeaderValue::from(self.limit)),
+            (REMAINING_HEADER.clone(), HeaderValue::from(self.remaining)),
+            (RESET_HEADER.clone(), HeaderValue::from(self.reset_secs)),
+        ]
+    }
+}
+
+/// Fixed one-minute windows of requests per client
+pub struct RateLimiter {
+    name: &'static str,
+    /// Requests per window; 0 disables the limit
+    limit: AtomicU32,
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(name: &'static str, limit: u32) -> Self {
+        Self {
+            name,
+            limit: AtomicU32::new(limit),
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Allow `limit` requests per window from now on; 0 disables the limit
+    pub fn set_limit(&self, limit: u32) {
+        self.limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// Count a request from `client`
+    pub fn check(&self, client: &str) -> Quota {
+        let limit = self.limit.load(Ordering::Relaxed);
+        if limit == 0 {
+            return Quota {
+                allowed: true,
+                limit: 0,
+                remaining: 0,
+                reset_secs: 0,
+            };
+        }
+
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() > TABLE_PRUNE_AT {
+            windows.retain(|_, (start, _)| now.duration_since(*start) < WINDOW);
+        }
+
+        let (start, count) = windows.entry(client.to_string()).or_insert((now, 0));
+        if now.duration_since(*start) >= WINDOW {
+            *start = now;
+            *count = 0;
+        }
+        let allowed = *count < limit;
+        if allowed {
+            *count += 1;
+        }
+        Quota {
+            allowed,
+            limit,
+            remaining: limit.saturating_sub(*count),
+            reset_secs: WINDOW
+                .saturating_sub(now.duration_since(*start))
+                .as_secs()
+                .max(1),
+        }
+    }
+}
+
+/// The proxies in front of the server, set once at startup
+static TRUSTED_PROXIES: OnceCell<TrustedProxies> = OnceCell::new();
+
+/// Believe forwarding headers from `proxies` (config `trusted_proxies`)
+pub fn set_trusted_proxies(proxies: TrustedProxies) {
+    if TRUSTED_PROXIES.set(proxies).is_err() {
+        warn!("Trusted proxies already set; ignoring");
+    }
+}
+
+/// Addresses and CIDR ranges of the reverse proxies in front of the server,
+/// e.g. nginx on the same host
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustedProxies(Vec<(IpAddr, u8)>);
+
+impl TrustedProxies {
+    /// Parse addresses (`127.0.0.1`) and CIDR ranges (`10.0.0.0/8`)
+    pub fn parse(entries: &[String]) -> Result<Self, String> {
+        entries
+            .iter()
+            .map(|entry| {
+                let invalid = || format!("{:?} is not an IP address or CIDR range", entry);
+                let (addr, bits) = match entry.split_once('/') {
+                    Some((addr, bits)) => (addr, Some(bits)),
+                    None => (entry.as_str(), None),
+                };
+                let ip: IpAddr = addr.trim().parse().map_err(|_| invalid())?;
+                let max = if ip.is_ipv4() { 32 } else { 128 };
+                let bits = match bits {
+                    Some(bits) => bits.trim().parse().ok().filter(|bits| *bits <= max).ok_or_else(invalid)?,
+                    None => max,
+                };
+                Ok((ip, bits))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|&(net, bits)| match (net, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(bits)).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(bits)).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        })
+    }
+
+    /// Caller address for rate limiting
+    ///
+    /// Clients can send CF-Connecting-IP and X-Forwarded-For themselves, so
+    /// only a trusted proxy's are believed: its CF-Connecting-IP, else the
+    /// rightmost X-Forwarded-For entry that isn't a trusted proxy. Anyone
+    /// else is known by the socket address, whatever headers they send.
+    pub fn client_ip(&self, headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+        if !self.contains(peer.ip()) {
+            return peer.ip();
+        }
+        // Nearest hop first; an entry that isn't an address stops the walk
+        let entries: Vec<Option<IpAddr>> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(|v| v.trim().parse().ok())
+            .collect();
+        let mut forwarded = entries.into_iter().rev().map_while(|ip| ip);
+        headers
+            .get("cf-connecting-ip")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok())
+            .or_else(|| forwarded.find(|ip| !self.contains(*ip)))
+            .unwrap_or(peer.ip())
+    }
+}
+
+/// Caller address for rate limiting, through the proxies set with
+/// [`set_trusted_proxies`]; see [`TrustedProxies::client_ip`]
+pub fn client_ip(headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+    TRUSTED_PROXIES.get_or_init(TrustedProxies::default).client_ip(headers, peer)
+}
+
+/// Who a request is counted against: its API key, else its client IP
+pub fn client_key(request: &Request) -> String {
+    match request.extensions().get::<Caller>() {
+        Some(caller) => caller.id.clone(),
+        None => request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(peer)| format!("ip:{}", client_ip(request.headers(), *peer)))
+            .unwrap_or_else(|| "ip:unknown".to_string()),
+    }
+}
+
+/// The 429 returned once a client is over its budget
+pub fn too_many_requests(quota: Quota) -> Response {
+    (
+        [(header::RETRY_AFTER, HeaderValue::from(quota.reset_secs))],
+        quota.headers(),
+        AppError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "rate_limited",
+            format!(
+                "Rate limit of {} requests per minute exceeded; retry in {}s",
+                quota.limit, quota.reset_secs
+            ),
+        ),
+    )
+        .into_response()
+}
+
+/// Route middleware enforcing a per-client budget
+///
+/// Clients are told apart by [`client_key`]. Every response carries the budget in
+/// `X-RateLimit-Limit`, `X-RateLimit-Remaining` and `X-RateLimit-Reset`.
+/// Use as `route_layer(middleware::from_fn_with_state(&*rate_limit::AI_CALLS, rate_limit::limit))`.
+pub async fn limit(
+    State(limiter): State<&'static RateLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = client_key(&request);
+    let quota = limiter.check(&client);
+    if !quota.allowed {
+        warn!("Rate limiting {} requests from {}", limiter.name, client);
+        return too_many_requests(quota);
+    }
+
+    let mut response = next.run(request).await;
+    if quota.limit > 0 {
+        response.headers_mut().extend(quota.headers());
+    }
+    response
+}
@@ -0,0 +1,41 @@
+//! Scrubbing secrets from data kept for later inspection: webhook payloads
+//! stored for replay, and AI calls in the prompt audit
+
+use serde_json::Value;
+
+/// Placeholder for redacted secrets
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Keys whose values are never stored
+const SECRET_KEYS: [&str; 5] = ["secret", "token", "password", "authorization", "credential"];
+
+/// Replace the values of secret-looking keys, and credentials in URLs, with [`REDACTED`]
+pub fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.to_ascii_lowercase();
+                if !value.is_null() && SECRET_KEYS.iter().any(|secret| key.contains(secret)) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_json),
+        Value::String(text) => {
+            if let Some(redacted) = redact_url_credentials(text) {
+                *text = redacted;
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `text` with the user info of a URL redacted, if it is a URL with any
+pub fn redact_url_credentials(text: &str) -> Option<String> {
+    let (scheme, rest) = text.split_once("://")?;
+    let authority = rest.split('/').next()?;
+    let (_, host) = authority.rsplit_once('@')?;
+    Some(format!("{}://{}@{}{}", scheme, REDACTED, host, &rest[authority.len()..]))
+}
@@ -0,0 +1,107 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue, Uri},
+    middleware::Next,
+    response::Response,
+};
+use std::future::Future;
+use tokio::task::JoinHandle;
+use tracing::{Instrument, Span};
+
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest incoming X-Request-Id that is reused rather than replaced
+const MAX_INCOMING_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The current request's ID, if running inside one (or a task spawned from one)
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// An incoming ID is kept only if it's short and plain enough to log safely
+fn valid_incoming(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_INCOMING_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+/// Middleware giving every request an ID
+///
+/// Reuses a well-formed incoming `X-Request-Id` (e.g. from Cloudflare or a
+/// caller retrying), else generates one. The ID is written back onto the
+/// request for the trace span, echoed in the response header, and available
+/// to handlers through [`current`]. Must be layered outside the `TraceLayer`.
+pub async fn assign(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| valid_incoming(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+    let header = HeaderValue::from_str(&id).expect("request IDs are ASCII");
+    request.headers_mut().insert(REQUEST_ID_HEADER.clone(), header.clone());
+
+    let mut response = REQUEST_ID.scope(id, next.run(request)).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER.clone(), header);
+    response
+}
+
+/// Span for `TraceLayer`, carrying the ID set by [`assign`]
+pub fn make_span(request: &Request) -> Span {
+    let id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-");
+    tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        uri = %redacted_uri(request.uri()),
+    )
+}
+
+/// The URI for logs, without the value of an `access_token` query parameter
+fn redacted_uri(uri: &Uri) -> String {
+    let Some(query) = uri.query().filter(|q| q.contains("access_token=")) else {
+        return uri.to_string();
+    };
+    let query: Vec<&str> = query
+        .split('&')
+        .map(|pair| if pair.starts_with("access_token=") { "access_token=redacted" } else { pair })
+        .collect();
+    format!("{}?{}", uri.path(), query.join("&"))
+}
+
+/// `tokio::spawn` that keeps the caller's span and request ID
+///
+/// Use for background work started by a request, so its logs (and any
+/// errors it records) can be traced back to that request.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let future = future.instrument(Span::current());
+    match current() {
+        Some(id) => tokio::spawn(REQUEST_ID.scope(id, future)),
+        None => tokio::spawn(future),
+    }
+}
+
+/// `spawn_blocking` that runs `f` inside the caller's span
+pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let span = Span::current();
+    tokio::task::spawn_blocking(move || span.in_scope(f))
+}
@@ -0,0 +1,36 @@
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use kicad_db::{ApiScope, PgPool};
+use std::sync::Arc;
+
+use crate::auth::{require_repo_access, require_scope};
+use crate::controllers::admin::{
+    cancel_job, cost_report, feedback_report, list_experiments, get_job, list_jobs, list_prompt_audits, list_schedules, list_webhook_deliveries,
+    check_lifecycles, classify_parts, onboard_github_org, overview, reload_config, replay_webhook, retry_job, update_schedule,
+};
+use crate::state::AppState;
+
+pub fn router(pool: Arc<PgPool>) -> Router<AppState> {
+    Router::new()
+        .route("/schedules", get(list_schedules).put(update_schedule))
+        .route("/overview", get(overview))
+        .route("/taxonomy/classify", post(classify_parts))
+        .route("/reload", post(reload_config))
+        .route("/lifecycle/check", post(check_lifecycles))
+        .route("/onboard/github-org", post(onboard_github_org))
+        .route("/costs", get(cost_report))
+        .route("/feedback", get(feedback_report))
+        .route("/experiments", get(list_experiments))
+        .route("/webhooks", get(list_webhook_deliveries))
+        .route("/webhooks/:delivery_id/replay", post(replay_webhook))
+        .route("/jobs", get(list_jobs))
+        .route("/jobs/:id", get(get_job))
+        .route("/jobs/:id/cancel", post(cancel_job))
+        .route("/jobs/:id/retry", post(retry_job))
+        .route("/prompts", get(list_prompt_audits))
+        .route_layer(middleware::from_fn_with_state(pool, require_repo_access))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Admin, require_scope))
+}
@@ -0,0 +1,17 @@
+use axum::{middleware, routing::get, Router};
+use kicad_db::ApiScope;
+
+use crate::auth::require_scope;
+use crate::conditional;
+use crate::controllers::components::{get_component_usage, get_datasheet, get_part_metadata, list_shared_components};
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/usage", get(list_shared_components))
+        .route("/:mpn", get(get_part_metadata))
+        .route("/:mpn/usage", get(get_component_usage))
+        .route("/:mpn/datasheet", get(get_datasheet))
+        .route_layer(middleware::from_fn(conditional::etag))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Read, require_scope))
+}
@@ -1,13 +1,17 @@
 use axum::{
+    middleware,
     routing::{get, post},
     Router,
 };
-use std::sync::Arc;
+use kicad_db::ApiScope;
 
+use crate::auth::require_scope;
 use crate::controllers::digikey::{get_status, search_parts};
+use crate::state::AppState;
 
-pub fn router() -> Router<Arc<sqlx::PgPool>> {
+pub fn router() -> Router<AppState> {
     Router::new()
         .route("/search", post(search_parts))
         .route("/status", get(get_status))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Read, require_scope))
 }
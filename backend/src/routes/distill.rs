@@ -1,8 +1,14 @@
-use axum::{routing::post, Router};
+use axum::{middleware, routing::post, Router};
+use kicad_db::{ApiScope, PgPool};
 use std::sync::Arc;
 
+use crate::auth::{require_repo_access, require_scope};
 use crate::controllers::distill::distill_schematics;
+use crate::state::AppState;
 
-pub fn router() -> Router<Arc<sqlx::PgPool>> {
-    Router::new().route("/", post(distill_schematics))
+pub fn router(pool: Arc<PgPool>) -> Router<AppState> {
+    Router::new()
+        .route("/", post(distill_schematics))
+        .route_layer(middleware::from_fn_with_state(pool, require_repo_access))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Hook, require_scope))
 }
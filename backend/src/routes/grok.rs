@@ -1,19 +1,60 @@
 use axum::{
+    middleware,
     routing::{get, post},
     Router,
 };
+use kicad_db::{ApiScope, PgPool};
 use std::sync::Arc;
 
+use crate::auth::{require_repo_access, require_scope};
+use crate::deprecation::{self, deprecated};
+use crate::rate_limit::{self, limit};
 use crate::controllers::grok::{
-    chat_stream, find_replacement, selection_stream, summarize_commit, summarize_repo, summarize_selection,
+    ask, backfill_summaries, chat, chat_stream, create_chat_session, get_chat_session, find_replacement, list_models, list_personas, summary_feedback, release_notes, review_checklist, selection_stream, summarize_commit, suggest_fix, summarize_commit_stream, summarize_compare, summarize_repo, summarize_selection,
 };
+use crate::state::AppState;
 
-pub fn router() -> Router<Arc<sqlx::PgPool>> {
-    Router::new()
+pub fn router(pool: Arc<PgPool>) -> Router<AppState> {
+    let read = Router::new()
+        .route("/personas", get(list_personas))
+        .route("/models", get(list_models))
+        .route_layer(middleware::from_fn_with_state(&*rate_limit::LISTINGS, limit))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Read, require_scope));
+
+    let feedback = Router::new()
+        .route("/feedback", post(summary_feedback))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Read, require_scope));
+
+    let deprecated_chat = Router::new()
+        .route("/chat/stream", get(chat_stream))
+        .route_layer(middleware::from_fn_with_state(&deprecation::CHAT_STREAM_GET, deprecated));
+
+    // Sessions hold conversations for /chat/stream, so need its scope
+    let sessions = Router::new()
+        .route("/chat/sessions", post(create_chat_session))
+        .route("/chat/sessions/:id", get(get_chat_session))
+        .route_layer(middleware::from_fn_with_state(pool.clone(), require_repo_access))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Hook, require_scope));
+
+    // Every other route calls the AI API
+    let hook = Router::new()
         .route("/summary/commit", post(summarize_commit))
+        .route("/summary/commit/stream", post(summarize_commit_stream))
         .route("/summary/selection", post(summarize_selection))
         .route("/summary/repo", post(summarize_repo))
+        .route("/summary/compare", post(summarize_compare))
+        .route("/release-notes", post(release_notes))
+        .route("/review/checklist", post(review_checklist))
+        .route("/suggest-fix/:repo/:commit", post(suggest_fix))
+        .route("/ask", post(ask))
+        .route("/summary/backfill/*repo", post(backfill_summaries))
         .route("/obsolete/replacement", post(find_replacement))
-        .route("/chat/stream", get(chat_stream))
+        .merge(deprecated_chat)
+        .route("/chat/stream", post(chat))
         .route("/selection/stream", post(selection_stream))
+        .route_layer(middleware::from_fn_with_state(pool, require_repo_access))
+        .route_layer(middleware::from_fn_with_state(&*rate_limit::AI_CALLS, limit))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Hook, require_scope));
+
+    read.merge(feedback).merge(sessions).merge(hook)
 }
@@ -0,0 +1,10 @@
+use axum::{routing::get, Router};
+
+use crate::controllers::health::{healthz, readyz};
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+}
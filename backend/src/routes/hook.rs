@@ -1,11 +1,27 @@
-use axum::{routing::post, Router};
+use axum::{middleware, routing::post, Router};
+use kicad_db::{ApiScope, PgPool};
 use std::sync::Arc;
 
-use crate::controllers::hook::{github_webhook, refresh_repo, update_repo, AppState};
+use crate::auth::{require_repo_access, require_scope};
+use crate::controllers::hook::{
+    bitbucket_webhook, github_webhook, gitlab_webhook, refresh_repo, update_repo,
+};
+use crate::state::AppState;
 
-pub fn router() -> Router<Arc<sqlx::PgPool>> {
-    Router::new()
+pub fn router(pool: Arc<PgPool>) -> Router<AppState> {
+    let manual = Router::new()
         .route("/update/*repo", post(update_repo))
         .route("/refresh/*repo", post(refresh_repo))
+        .route_layer(middleware::from_fn_with_state(pool, require_repo_access))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Hook, require_scope));
+
+    // Providers can't send API keys, so deliveries are checked against the
+    // provider's webhook secret in the handler instead, and processed for
+    // whichever org the repo is registered to
+    let webhooks = Router::new()
         .route("/github/*repo", post(github_webhook))
+        .route("/gitlab/*repo", post(gitlab_webhook))
+        .route("/bitbucket/*repo", post(bitbucket_webhook));
+
+    manual.merge(webhooks)
 }
@@ -0,0 +1,17 @@
+use axum::{
+    middleware,
+    routing::{delete, get},
+    Router,
+};
+use kicad_db::ApiScope;
+
+use crate::auth::require_scope;
+use crate::controllers::keys::{create_key, list_keys, revoke_key};
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_keys).post(create_key))
+        .route("/:id", delete(revoke_key))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Admin, require_scope))
+}
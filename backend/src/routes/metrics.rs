@@ -0,0 +1,8 @@
+use axum::{routing::get, Router};
+
+use crate::controllers::metrics::prometheus_metrics;
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/", get(prometheus_metrics))
+}
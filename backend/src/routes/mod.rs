@@ -1,5 +1,16 @@
+pub mod admin;
+pub mod components;
 pub mod digikey;
 pub mod distill;
 pub mod grok;
+pub mod health;
 pub mod hook;
+pub mod keys;
+pub mod metrics;
+pub mod orgs;
+pub mod public;
 pub mod repo;
+pub mod repos;
+pub mod search;
+pub mod shared;
+pub mod status;
@@ -0,0 +1,21 @@
+use axum::{
+    middleware,
+    routing::{delete, get, put},
+    Router,
+};
+use kicad_db::ApiScope;
+
+use crate::auth::require_scope;
+use crate::controllers::guidelines::{create_org_guideline, delete_org_guideline, org_guidelines, update_org_guideline};
+use crate::controllers::orgs::{create_org, get_members, list_orgs, remove_member, set_member};
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_orgs).post(create_org))
+        .route("/:org/members", get(get_members).put(set_member))
+        .route("/:org/members/:email", delete(remove_member))
+        .route("/:org/guidelines", get(org_guidelines).post(create_org_guideline))
+        .route("/:org/guidelines/:id", put(update_org_guideline).delete(delete_org_guideline))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Admin, require_scope))
+}
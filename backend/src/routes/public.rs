@@ -0,0 +1,10 @@
+use axum::{routing::get, Router};
+
+use crate::controllers::public::{badge, latest_summary};
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/:repo/badge.svg", get(badge))
+        .route("/:repo/latest-summary", get(latest_summary))
+}
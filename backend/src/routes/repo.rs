@@ -1,13 +1,35 @@
-use axum::{routing::post, Router};
+use axum::{middleware, routing::post, Router};
+use kicad_db::{ApiScope, PgPool};
 use std::sync::Arc;
 
-use crate::controllers::repo::{clear_cache, get_commit_files, get_commit_info, get_commits, init_repo};
+use crate::auth::{require_repo_access, require_scope};
+use crate::controllers::repo::{
+    clear_cache, generate_changelog, get_commit_files, get_commit_info, get_commits, init_repo, set_default_persona,
+    set_changelog, set_visibility,
+};
+use crate::state::AppState;
 
-pub fn router() -> Router<Arc<sqlx::PgPool>> {
-    Router::new()
+pub fn router(pool: Arc<PgPool>) -> Router<AppState> {
+    let read = Router::new()
         .route("/commits", post(get_commits))
         .route("/commit/files", post(get_commit_files))
         .route("/commit/info", post(get_commit_info))
+        .route("/changelog", post(generate_changelog))
+        .route_layer(middleware::from_fn_with_state(pool.clone(), require_repo_access))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Read, require_scope));
+
+    let hook = Router::new()
         .route("/init", post(init_repo))
         .route("/clear-cache", post(clear_cache))
+        .route_layer(middleware::from_fn_with_state(pool.clone(), require_repo_access))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Hook, require_scope));
+
+    let admin = Router::new()
+        .route("/persona", post(set_default_persona))
+        .route("/visibility", post(set_visibility))
+        .route("/changelog/settings", post(set_changelog))
+        .route_layer(middleware::from_fn_with_state(pool, require_repo_access))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Admin, require_scope));
+
+    read.merge(hook).merge(admin)
 }
@@ -0,0 +1,83 @@
+use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{delete, get, post, put},
+    Router,
+};
+use kicad_db::{ApiScope, PgPool};
+use std::sync::Arc;
+
+use crate::auth::{require_repo_access, require_scope};
+use crate::conditional;
+use crate::controllers::guidelines::{create_repo_guideline, delete_repo_guideline, repo_guidelines, update_repo_guideline};
+use crate::controllers::repos::{
+    alerts, board, bom, commit_stats, stats_history, changes, digest_preview, digest_subscribers, subscribe_digest, unsubscribe_digest, components, checklist, component_history, parts, delete_commit, erc, events, export, file_at_commit, get_registered, import, list_registered,
+    list_stored_commits, net_geometry, netlist, processing_errors, raw_file, register, schematic_image, schematic_pdf, symbol_svg, thumbnail, timeline,
+    share_commit, unregister, update_checklist_item, update_processing, upload_image,
+    MAX_IMAGE_BYTES, MAX_IMPORT_BYTES,
+};
+use crate::state::AppState;
+
+pub fn router(pool: Arc<PgPool>) -> Router<AppState> {
+    // Not guarded: the handlers only list and register the caller's org's repos
+    let listing = Router::new()
+        .route("/", get(list_registered).post(register))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Admin, require_scope));
+
+    let registry = Router::new()
+        .route("/:repo", get(get_registered).delete(unregister))
+        .route("/:repo/processing", put(update_processing))
+        .route("/:repo/digest/subscribers", get(digest_subscribers).post(subscribe_digest))
+        .route("/:repo/digest/subscribers/:email", delete(unsubscribe_digest))
+        .route("/:repo/digest/preview", get(digest_preview))
+        .route("/:repo/guidelines", get(repo_guidelines).post(create_repo_guideline))
+        .route("/:repo/guidelines/:id", put(update_repo_guideline).delete(delete_repo_guideline))
+        .route("/:repo/commits/:commit", delete(delete_commit))
+        .route("/:repo/export", get(export))
+        .route("/:repo/import", post(import).layer(DefaultBodyLimit::max(MAX_IMPORT_BYTES)))
+        .route_layer(middleware::from_fn_with_state(pool.clone(), require_repo_access))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Admin, require_scope));
+
+    // Renders come from CI alongside the hooks; reviewers tick off checklists
+    let write = Router::new()
+        .route("/:repo/commits/:commit/image", put(upload_image).layer(DefaultBodyLimit::max(MAX_IMAGE_BYTES)))
+        .route("/:repo/commits/:commit/checklist/:item", put(update_checklist_item))
+        .route_layer(middleware::from_fn_with_state(pool.clone(), require_repo_access))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Hook, require_scope));
+
+    // Minting a link only needs read access; what it opens is checked per view
+    let sharing = Router::new()
+        .route("/:repo/commits/:commit/share", post(share_commit))
+        .route_layer(middleware::from_fn_with_state(pool.clone(), require_repo_access))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Read, require_scope));
+
+    let read = Router::new()
+        .route("/:repo/commits", get(list_stored_commits))
+        .route("/:repo/commits/:commit/board", get(board))
+        .route("/:repo/commits/:commit/bom", get(bom))
+        .route("/:repo/commits/:commit/changes", get(changes))
+        .route("/:repo/commits/:commit/checklist", get(checklist))
+        .route("/:repo/commits/:commit/components", get(components))
+        .route("/:repo/commits/:commit/erc", get(erc))
+        .route("/:repo/commits/:commit/image", get(schematic_image))
+        .route("/:repo/commits/:commit/schematic.pdf", get(schematic_pdf))
+        .route("/:repo/commits/:commit/stats", get(commit_stats))
+        .route("/:repo/commits/:commit/thumbnail", get(thumbnail))
+        .route("/:repo/commits/:commit/netlist", get(netlist))
+        .route("/:repo/commits/:commit/nets/:net/geometry", get(net_geometry))
+        .route("/:repo/commits/:commit/files/*path", get(file_at_commit))
+        .route("/:repo/commits/:commit/raw/*path", get(raw_file))
+        .route("/:repo/commits/:commit/symbols/:reference", get(symbol_svg))
+        .route("/:repo/components", get(parts))
+        .route("/:repo/components/:reference/history", get(component_history))
+        .route("/:repo/timeline", get(timeline))
+        .route("/:repo/stats", get(stats_history))
+        .route("/:repo/errors", get(processing_errors))
+        .route("/:repo/alerts", get(alerts))
+        .route("/:repo/events", get(events))
+        .route_layer(middleware::from_fn(conditional::etag))
+        .route_layer(middleware::from_fn_with_state(pool, require_repo_access))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Read, require_scope));
+
+    listing.merge(registry).merge(write).merge(sharing).merge(read)
+}
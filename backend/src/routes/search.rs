@@ -0,0 +1,13 @@
+use axum::{middleware, routing::get, Router};
+use kicad_db::ApiScope;
+
+use crate::auth::require_scope;
+use crate::controllers::search::{search, semantic_search};
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(search))
+        .route("/semantic", get(semantic_search))
+        .route_layer(middleware::from_fn_with_state(ApiScope::Read, require_scope))
+}
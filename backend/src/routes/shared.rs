@@ -0,0 +1,15 @@
+use axum::{middleware, routing::get, Router};
+
+use crate::controllers::shared::{shared_changes, shared_commit, shared_sheet, shared_summary};
+use crate::share::require_share_link;
+use crate::state::AppState;
+
+/// No API key needed: the token in the path is checked instead
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/:token", get(shared_commit))
+        .route("/:token/summary", get(shared_summary))
+        .route("/:token/changes", get(shared_changes))
+        .route("/:token/sheets/:page", get(shared_sheet))
+        .route_layer(middleware::from_fn_with_state(state, require_share_link))
+}
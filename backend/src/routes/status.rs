@@ -0,0 +1,8 @@
+use axum::{routing::get, Router};
+
+use crate::controllers::status::status_page;
+use crate::state::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/", get(status_page))
+}
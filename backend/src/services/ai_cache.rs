@@ -0,0 +1,208 @@
+use futures_util::{future::BoxFuture, stream, StreamExt};
+use kicad_db::{
+    get_cached_response, messages::ChatCompletionRequest, purge_expired_responses,
+    store_cached_response,
+    xai_client::{
+        ChatCompletionResponse, ChatCompletionStream, DeferredRequest, ResponsesRequest, ResponsesResponse,
+        StreamEvent,
+    },
+    PgPool, XaiError,
+};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use super::llm::ChatProvider;
+use super::metrics;
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Cache key for a request: a hash of the endpoint and the whole request, so
+/// the model, messages and sampling settings all have to match
+fn cache_key(endpoint: &str, request: &impl Serialize) -> Option<String> {
+    let body = serde_json::to_vec(request).ok()?;
+    let mut hasher = Sha256::new();
+    hasher.update(endpoint.as_bytes());
+    hasher.update([0]);
+    hasher.update(&body);
+    Some(hex::encode(hasher.finalize()))
+}
+
+/// A [`ChatProvider`] that answers repeated identical requests from Postgres
+///
+/// Successful responses and fully received streams are stored for the TTL;
+/// errors and cancelled streams are not. A cached stream is replayed chunk by
+/// chunk. Cache failures are logged and the request goes to the provider.
+pub struct CachingChatProvider {
+    inner: Arc<dyn ChatProvider>,
+    pool: Arc<PgPool>,
+    ttl: Duration,
+}
+
+impl CachingChatProvider {
+    pub fn new(inner: Arc<dyn ChatProvider>, pool: Arc<PgPool>, ttl: Duration) -> Self {
+        Self { inner, pool, ttl }
+    }
+
+    async fn lookup(&self, endpoint: &'static str, key: &str) -> Option<Value> {
+        match get_cached_response(&self.pool, key).await {
+            Ok(Some(cached)) => {
+                metrics::record_ai_cache(endpoint, true);
+                Some(cached)
+            }
+            Ok(None) => {
+                metrics::record_ai_cache(endpoint, false);
+                None
+            }
+            Err(e) => {
+                warn!("AI response cache lookup failed: {}", e);
+                None
+            }
+        }
+    }
+}
+
+async fn store(pool: &PgPool, key: &str, endpoint: &str, model: &str, response: &Value, ttl: Duration) {
+    if let Err(e) = store_cached_response(pool, key, endpoint, model, response, ttl).await {
+        warn!("Failed to cache {} response: {}", endpoint, e);
+    }
+}
+
+/// Events of a cached stream; entries cached before streams carried events
+/// hold plain content chunks
+fn replay_events(cached: Value) -> Result<Vec<StreamEvent>, serde_json::Error> {
+    serde_json::from_value::<Vec<StreamEvent>>(cached.clone()).or_else(|e| {
+        let chunks = serde_json::from_value::<Vec<String>>(cached).map_err(|_| e)?;
+        Ok(chunks
+            .into_iter()
+            .map(StreamEvent::content)
+            .chain([StreamEvent::Done])
+            .collect())
+    })
+}
+
+impl ChatProvider for CachingChatProvider {
+    fn responses<'a>(
+        &'a self,
+        request: &'a ResponsesRequest,
+    ) -> BoxFuture<'a, Result<ResponsesResponse, XaiError>> {
+        Box::pin(async move {
+            let Some(key) = cache_key("responses", request) else {
+                return self.inner.responses(request).await;
+            };
+            if let Some(cached) = self.lookup("responses", &key).await {
+                match serde_json::from_value(cached) {
+                    Ok(response) => return Ok(response),
+                    Err(e) => warn!("Ignoring unreadable cached response: {}", e),
+                }
+            }
+
+            let response = self.inner.responses(request).await?;
+            if let Ok(value) = serde_json::to_value(&response) {
+                store(&self.pool, &key, "responses", &request.model, &value, self.ttl).await;
+            }
+            Ok(response)
+        })
+    }
+
+    fn chat_completion_stream<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<ChatCompletionStream, XaiError>> {
+        Box::pin(async move {
+            let Some(key) = cache_key("chat_completion_stream", request) else {
+                return self.inner.chat_completion_stream(request, cancel).await;
+            };
+            if let Some(cached) = self.lookup("chat_completion_stream", &key).await {
+                match replay_events(cached) {
+                    Ok(events) => {
+                        let replay: ChatCompletionStream = Box::pin(stream::iter(events.into_iter().map(Ok)));
+                        return Ok(replay);
+                    }
+                    Err(e) => warn!("Ignoring unreadable cached stream: {}", e),
+                }
+            }
+
+            let upstream = self.inner.chat_completion_stream(request, cancel).await?;
+            let pool = self.pool.clone();
+            let model = request.model.clone();
+            let ttl = self.ttl;
+            // Pass events through, storing them only once the stream ends cleanly.
+            // Usage isn't stored: a replay spends no tokens.
+            let recording: ChatCompletionStream = Box::pin(async_stream::stream! {
+                tokio::pin!(upstream);
+                let mut events = Vec::new();
+                while let Some(result) = upstream.next().await {
+                    match result {
+                        Ok(event) => {
+                            if !matches!(event, StreamEvent::Usage { .. }) {
+                                events.push(event.clone());
+                            }
+                            yield Ok(event);
+                        }
+                        Err(e) => {
+                            yield Err(e);
+                            return;
+                        }
+                    }
+                }
+                match serde_json::to_value(&events) {
+                    Ok(value) => store(&pool, &key, "chat_completion_stream", &model, &value, ttl).await,
+                    Err(e) => warn!("Failed to encode stream for the cache: {}", e),
+                }
+            });
+            Ok(recording)
+        })
+    }
+
+    /// Not cached: deferred requests are for jobs that want a fresh answer
+    fn submit_deferred<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+    ) -> BoxFuture<'a, Result<DeferredRequest, XaiError>> {
+        self.inner.submit_deferred(request)
+    }
+
+    fn deferred_completion<'a>(
+        &'a self,
+        deferred: &'a DeferredRequest,
+    ) -> BoxFuture<'a, Result<Option<ChatCompletionResponse>, XaiError>> {
+        self.inner.deferred_completion(deferred)
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, XaiError>> {
+        self.inner.list_models()
+    }
+
+    fn embed<'a>(&'a self, model: &'a str, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, XaiError>> {
+        self.inner.embed(model, texts)
+    }
+
+    fn available(&self) -> bool {
+        self.inner.available()
+    }
+
+    fn uncached(&self) -> &dyn ChatProvider {
+        self.inner.uncached()
+    }
+}
+
+/// Periodically delete expired cache entries
+pub fn spawn_purge(pool: Arc<PgPool>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match purge_expired_responses(&pool).await {
+                Ok(0) => debug!("No expired AI responses to purge"),
+                Ok(n) => info!("Purged {} expired AI responses", n),
+                Err(e) => warn!("Failed to purge expired AI responses: {}", e),
+            }
+        }
+    });
+}
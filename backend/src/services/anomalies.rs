@@ -0,0 +1,58 @@
+use kicad_db::schematic::{detect_anomalies, DiffWarning, Project, ProjectDiff};
+use kicad_db::{retrieve_diff_warnings, PgPool};
+use tracing::warn;
+
+use crate::services::git;
+
+/// Suspicious changes in `changes`, the diffs of `current` against
+/// `previous` from [`distill::diff_project_sets`](crate::services::distill::diff_project_sets)
+pub fn detect(previous: &[Project], current: &[Project], changes: &[(String, ProjectDiff)]) -> Vec<DiffWarning> {
+    let empty = Project::default();
+    let mut warnings = Vec::new();
+    for (root_file, diff) in changes {
+        // A project the commit deleted is diffed against an empty one
+        let old = previous.iter().find(|p| &p.root_file == root_file).unwrap_or(&empty);
+        let new = current.iter().find(|p| &p.root_file == root_file).unwrap_or(&empty);
+        warnings.extend(detect_anomalies(old, new, diff));
+    }
+    warnings
+}
+
+/// The warnings stored when a commit was processed; none if it hasn't been,
+/// or they can't be loaded
+pub async fn stored(pool: &PgPool, repo: &str, commit: &str) -> Vec<DiffWarning> {
+    match retrieve_diff_warnings(pool, &git::repo_url(repo), commit).await {
+        Ok(warnings) => warnings.unwrap_or_default(),
+        Err(e) => {
+            warn!("Failed to load diff warnings for {}/{}: {}", repo, commit, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Overview section listing the warnings (empty if none)
+pub fn describe(warnings: &[DiffWarning]) -> String {
+    if warnings.is_empty() {
+        return String::new();
+    }
+    let mut section = String::from("\nPossibly unintended changes:\n");
+    for warning in warnings {
+        section.push_str(&format!("  - [{}] {}\n", warning.kind.as_str(), warning.message));
+    }
+    section
+}
+
+/// Prompt section listing the warnings, so the summary calls them out
+/// (empty if none)
+pub fn prompt_section(warnings: &[DiffWarning]) -> String {
+    if warnings.is_empty() {
+        return String::new();
+    }
+    let mut section = String::from(
+        "\n\n## Possibly Unintended Changes\nHeuristics flagged the following changes as possibly unintended; call each one out in the summary so the author can confirm it was meant:\n",
+    );
+    for warning in warnings {
+        section.push_str(&format!("- [{}] {}\n", warning.kind.as_str(), warning.message));
+    }
+    section
+}
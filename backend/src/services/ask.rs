@@ -0,0 +1,168 @@
+use anyhow::{Context, Result};
+use kicad_db::{
+    count_schematics, list_schematics,
+    messages::{ChatCompletionRequest, Message},
+    prompt_budget::PromptBudget,
+    prompts::{PromptLibrary, ASK},
+    schematic::{hierarchy::natural_key, Project},
+    PgPool, SortOrder,
+};
+use std::collections::BTreeSet;
+use tracing::warn;
+
+use crate::services::{git, llm::ChatProvider, summary};
+use crate::types::ChatSource;
+
+/// Output allowed for an answer
+const ANSWER_MAX_TOKENS: u32 = 800;
+/// Stored summaries given as context: the commit's and those before it
+const MAX_SUMMARIES: usize = 5;
+/// Names of the ask prompt's sections
+const BOM_SECTION: &str = "bom";
+const SUMMARIES_SECTION: &str = "summaries";
+const SOURCES_SECTION: &str = "sources";
+
+/// "- 2x 10k, Resistor_SMD:R_0603, MPN RC0603 (R1, R2)" for each BOM line
+pub fn bom_lines(projects: &[Project]) -> String {
+    let mut lines = Vec::new();
+    for project in projects {
+        for line in project.bom() {
+            let footprint = line.footprint.map(|f| format!(", {}", f)).unwrap_or_default();
+            let mpn = line.mpn.map(|mpn| format!(", MPN {}", mpn)).unwrap_or_default();
+            lines.push(format!(
+                "- {}x {}{}{} ({})",
+                line.quantity,
+                line.value,
+                footprint,
+                mpn,
+                line.references.join(", ")
+            ));
+        }
+    }
+    lines.join("\n")
+}
+
+/// The stored blurbs of `commit` and the commits before it, newest first, as
+/// "- abc1234 blurb" lines; blurbs the caller may not see are left out
+pub async fn recent_summaries(
+    pool: &PgPool,
+    repo: &str,
+    commit: &str,
+    visible: impl Fn(&str) -> bool,
+) -> Result<String> {
+    let repo_url = git::repo_url(repo);
+    let total = count_schematics(pool, &repo_url).await?;
+    let rows = list_schematics(pool, &repo_url, total.max(1), 0, SortOrder::Desc)
+        .await
+        .context("Failed to load stored commits")?;
+    let lines: Vec<String> = rows
+        .into_iter()
+        .skip_while(|row| row.commit_hash != commit)
+        .filter(|row| visible(&row.visibility))
+        .filter_map(|row| Some(format!("- {} {}", &row.commit_hash[..7.min(row.commit_hash.len())], row.blurb?)))
+        .take(MAX_SUMMARIES)
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+/// "[S1] title\ntext" for each source
+fn sources_text(sources: &[ChatSource]) -> String {
+    sources
+        .iter()
+        .map(|source| format!("[{}] {}\n{}", source.id, source.title, source.text))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Whether `text` names `name` as a whole word
+pub(crate) fn names(text: &str, name: &str) -> bool {
+    let word = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_');
+    !name.is_empty()
+        && text.match_indices(name).any(|(at, _)| {
+            !word(text[..at].chars().next_back()) && !word(text[at + name.len()..].chars().next())
+        })
+}
+
+/// The component references and net names of `projects` that `answer`
+/// mentions, components in reference order and nets by name. A hierarchical
+/// net such as "/Power/3V3" counts when it's named by its last segment
+pub fn mentioned(answer: &str, projects: &[Project]) -> (Vec<String>, Vec<String>) {
+    let mut components = BTreeSet::new();
+    let mut nets = BTreeSet::new();
+    for project in projects {
+        let distilled = project.to_distilled();
+        for reference in distilled["components"].as_object().into_iter().flat_map(|c| c.keys()) {
+            if names(answer, reference) {
+                components.insert(reference.clone());
+            }
+        }
+        for net in distilled["nets"].as_object().into_iter().flat_map(|n| n.keys()) {
+            let short = net.rsplit('/').next().unwrap_or(net);
+            if names(answer, net) || names(answer, short) {
+                nets.insert(net.clone());
+            }
+        }
+    }
+    let mut components: Vec<String> = components.into_iter().collect();
+    components.sort_by_key(|reference| natural_key(reference));
+    (components, nets.into_iter().collect())
+}
+
+/// Ask Grok a question about a commit's design, returning its answer and
+/// the prompt version
+///
+/// `bom` comes from [`bom_lines`], `summaries` from [`recent_summaries`];
+/// they and the `sources` are cut short to fit `context_window`.
+#[allow(clippy::too_many_arguments)]
+pub async fn generate(
+    chat: &dyn ChatProvider,
+    prompts: &PromptLibrary,
+    model: &str,
+    context_window: usize,
+    repo: &str,
+    commit: &str,
+    question: &str,
+    bom: &str,
+    summaries: &str,
+    sources: &[ChatSource],
+) -> Result<(String, String)> {
+    let variables = |bom: &str, summaries: &str, sources: &str| {
+        serde_json::json!({
+            "repo": repo,
+            "commit": commit,
+            "question": question,
+            "bom": bom,
+            "summaries": summaries,
+            "sources": sources,
+        })
+    };
+    let bare = prompts.render(ASK, variables("", "", ""))?;
+    let fitted = PromptBudget::new(model, context_window)
+        .reserve_output(ANSWER_MAX_TOKENS)
+        .fixed(&bare.text)
+        .section(BOM_SECTION, bom)
+        .section(SUMMARIES_SECTION, summaries)
+        .section(SOURCES_SECTION, sources_text(sources))
+        .fit();
+    if !fitted.truncated.is_empty() {
+        warn!(
+            "Shortened {} in the ask prompt for {}@{} to fit the context window",
+            fitted.truncated.join(", "),
+            repo,
+            commit
+        );
+    }
+    let prompt = prompts.render(
+        ASK,
+        variables(
+            fitted.text(BOM_SECTION),
+            fitted.text(SUMMARIES_SECTION),
+            fitted.text(SOURCES_SECTION),
+        ),
+    )?;
+
+    let mut request = ChatCompletionRequest::with_stream(vec![Message::user(prompt.text.clone())], model.to_string(), true);
+    request.max_tokens = Some(ANSWER_MAX_TOKENS);
+    let answer = summary::complete(chat, &request).await?;
+    Ok((answer, prompt.label()))
+}
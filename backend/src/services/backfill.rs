@@ -0,0 +1,563 @@
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use kicad_db::{
+    deferred::COMMIT_SUMMARY, delete_deferred_completion, detail_levels::DetailLevel, find_deferred_completion,
+    pending_deferred_completions, personas::Persona, record_deferred_completion,
+    xai_client::{ChatCompletionResponse, DeferredRequest},
+    DeferredCompletion, GuidelinePrompt, UpdateSchematic, XaiError,
+};
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use super::{
+    anomalies, costs, git, priority,
+    sampling::sampled,
+    status,
+    summary::{self, CommitSummary},
+    timing::{self, Stage, StageTimer},
+};
+use crate::controllers::grok::{backfill_settings, erc_comparison};
+use crate::error::{ai_unavailable, is_budget_exceeded, is_rate_limited};
+use crate::request_id;
+use crate::shutdown;
+use crate::state::AppState;
+use crate::types::{BackfillError, BackfillProgress, BackfillStatus, JobCommit, JobCommitStatus, WorkClass};
+
+/// First wait before polling for a deferred summary; doubles up to
+/// [`DEFERRED_POLL_MAX`]
+const DEFERRED_POLL_START: Duration = Duration::from_secs(1);
+const DEFERRED_POLL_MAX: Duration = Duration::from_secs(30);
+/// A deferred summary with no result this long after it was submitted is
+/// given up on, and submitted again by the next backfill
+const DEFERRED_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Finished backfills kept for /api/admin/jobs
+const FINISHED_JOB_LIMIT: usize = 50;
+
+static NEXT_JOB_ID: AtomicU64 = AtomicU64::new(1);
+/// Backfills of this process, running and recently finished, by id
+static JOBS: Lazy<Mutex<BTreeMap<u64, Job>>> = Lazy::new(Default::default);
+
+/// How a repo's summaries are generated, as resolved by the request that
+/// started the backfill
+pub struct SummarySettings {
+    pub template: String,
+    pub model: String,
+    pub persona: Option<&'static Persona>,
+    /// The repo's design guidelines, as of the start of the backfill
+    pub guidelines: Option<GuidelinePrompt>,
+    /// Org the repo is registered to, whose admins see the job
+    pub org: Option<i32>,
+}
+
+/// A backfill, running or finished
+struct Job {
+    org: Option<i32>,
+    progress: Arc<watch::Sender<BackfillProgress>>,
+    /// Every commit the backfill set out to summarize, in order
+    commits: Arc<Mutex<Vec<JobCommit>>>,
+    cancel: CancellationToken,
+}
+
+impl Job {
+    fn is_running(&self) -> bool {
+        self.progress.borrow().status == BackfillStatus::Running
+    }
+}
+
+/// What a running backfill reports to and is stopped through
+struct JobHandle {
+    progress: Arc<watch::Sender<BackfillProgress>>,
+    commits: Arc<Mutex<Vec<JobCommit>>>,
+    cancel: CancellationToken,
+}
+
+impl JobHandle {
+    fn set_commit_status(&self, index: usize, status: JobCommitStatus, error: Option<String>) {
+        if let Some(commit) = self.commits.lock().unwrap().get_mut(index) {
+            commit.status = status;
+            commit.error = error;
+        }
+    }
+}
+
+/// A backfill as /api/admin/jobs shows it
+pub struct JobInfo {
+    pub progress: BackfillProgress,
+    pub org: Option<i32>,
+}
+
+/// Start summarizing every schematic commit of `repo` that has no summary,
+/// or join the backfill already running for it
+///
+/// The job outlives the request and is charged like it. Its AI calls are
+/// made as `class`, or as the class of whoever joins it if that is more
+/// urgent. Progress is published on the returned channel, ending with
+/// status completed, stopped or cancelled.
+///
+/// With `xai.deferred_backfill` set, each summary is submitted as a deferred
+/// completion and polled for, and the request is recorded until its result
+/// is stored; see [`spawn_resume`].
+pub fn start(
+    state: AppState,
+    repo: String,
+    settings: SummarySettings,
+    class: WorkClass,
+) -> watch::Receiver<BackfillProgress> {
+    let mut jobs = JOBS.lock().unwrap();
+    if let Some(job) = jobs.values().find(|job| job.is_running() && job.progress.borrow().repo == repo) {
+        info!("Summary backfill for {} already running; following it", repo);
+        job.progress.send_modify(|p| p.class = p.class.min(class));
+        return job.progress.subscribe();
+    }
+
+    let id = NEXT_JOB_ID.fetch_add(1, Ordering::Relaxed);
+    let (tx, rx) = watch::channel(BackfillProgress {
+        job_id: id,
+        repo: repo.clone(),
+        status: BackfillStatus::Running,
+        class,
+        total: 0,
+        done: 0,
+        failed: 0,
+        remaining: 0,
+        current: None,
+        errors: Vec::new(),
+        stopped_reason: None,
+        cancel_requested: false,
+        started_at: Utc::now(),
+        finished_at: None,
+    });
+    let handle = JobHandle {
+        progress: Arc::new(tx),
+        commits: Arc::default(),
+        cancel: CancellationToken::new(),
+    };
+    jobs.insert(
+        id,
+        Job {
+            org: settings.org,
+            progress: handle.progress.clone(),
+            commits: handle.commits.clone(),
+            cancel: handle.cancel.clone(),
+        },
+    );
+    prune(&mut jobs);
+    request_id::spawn(costs::carry(async move {
+        run(&state, &repo, &settings, &handle).await;
+    }));
+    rx
+}
+
+/// Forget the oldest finished jobs beyond [`FINISHED_JOB_LIMIT`]
+fn prune(jobs: &mut BTreeMap<u64, Job>) {
+    let finished: Vec<u64> = jobs.iter().filter(|(_, job)| !job.is_running()).map(|(&id, _)| id).collect();
+    for id in finished.iter().take(finished.len().saturating_sub(FINISHED_JOB_LIMIT)) {
+        jobs.remove(id);
+    }
+}
+
+/// Backfills of this process, newest first; finished ones are kept until
+/// [`FINISHED_JOB_LIMIT`] newer ones have finished, and none survive a restart
+pub fn jobs() -> Vec<JobInfo> {
+    JOBS.lock()
+        .unwrap()
+        .values()
+        .rev()
+        .map(|job| JobInfo {
+            progress: job.progress.borrow().clone(),
+            org: job.org,
+        })
+        .collect()
+}
+
+/// One backfill, with where each of its commits stands
+pub fn job(id: u64) -> Option<(JobInfo, Vec<JobCommit>)> {
+    let jobs = JOBS.lock().unwrap();
+    let job = jobs.get(&id)?;
+    let info = JobInfo {
+        progress: job.progress.borrow().clone(),
+        org: job.org,
+    };
+    let commits = job.commits.lock().unwrap().clone();
+    Some((info, commits))
+}
+
+/// Ask a running backfill to stop; it does once its current commit is done,
+/// or at once while it waits for a deferred summary
+///
+/// Returns the job as it stands, or None if there is no such job.
+pub fn cancel(id: u64) -> Option<JobInfo> {
+    let jobs = JOBS.lock().unwrap();
+    let job = jobs.get(&id)?;
+    if job.is_running() {
+        info!("Cancelling summary backfill {} of {}", id, job.progress.borrow().repo);
+        job.cancel.cancel();
+        job.progress.send_modify(|p| p.cancel_requested = true);
+    }
+    let progress = job.progress.borrow().clone();
+    Some(JobInfo { progress, org: job.org })
+}
+
+/// The running backfill of `repo`, if any
+pub fn running_job(repo: &str) -> Option<u64> {
+    JOBS.lock()
+        .unwrap()
+        .iter()
+        .find(|(_, job)| job.is_running() && job.progress.borrow().repo == repo)
+        .map(|(&id, _)| id)
+}
+
+async fn run(state: &AppState, repo: &str, settings: &SummarySettings, handle: &JobHandle) {
+    let tx = &handle.progress;
+    let job = status::track_job("summary_backfill", repo, None);
+    let pending = match pending_commits(state, repo).await {
+        Ok(pending) => pending,
+        Err(e) => {
+            warn!("Summary backfill for {} failed to list commits: {:#}", repo, e);
+            finish(tx, BackfillStatus::Stopped, Some(format!("{:#}", e)));
+            return;
+        }
+    };
+    info!("Backfilling summaries for {} commit(s) of {}", pending.len(), repo);
+    *handle.commits.lock().unwrap() = pending
+        .iter()
+        .map(|commit| JobCommit {
+            commit: commit.clone(),
+            status: JobCommitStatus::Pending,
+            error: None,
+        })
+        .collect();
+    tx.send_modify(|p| {
+        p.total = pending.len();
+        p.remaining = pending.len();
+    });
+
+    for (index, commit) in pending.iter().enumerate() {
+        if shutdown::requested() {
+            // Summaries are stored one by one, so posting again resumes here
+            info!("Shutting down; stopping the summary backfill for {}", repo);
+            finish(tx, BackfillStatus::Stopped, Some("The server is shutting down".to_string()));
+            return;
+        }
+        if handle.cancel.is_cancelled() {
+            info!("Summary backfill for {} cancelled", repo);
+            finish(tx, BackfillStatus::Cancelled, None);
+            return;
+        }
+        job.set_commit(commit);
+        handle.set_commit_status(index, JobCommitStatus::Running, None);
+        tx.send_modify(|p| p.current = Some(commit.clone()));
+        let class = tx.borrow().class;
+        let result = priority::scope(class, summarize_commit(state, repo, commit, settings, &handle.cancel)).await;
+        if result.is_err() && (shutdown::requested() || handle.cancel.is_cancelled()) {
+            // Cut short waiting for a deferred summary, which the next backfill collects
+            handle.set_commit_status(index, JobCommitStatus::Pending, None);
+            if shutdown::requested() {
+                info!("Shutting down; stopping the summary backfill for {}", repo);
+                finish(tx, BackfillStatus::Stopped, Some("The server is shutting down".to_string()));
+            } else {
+                info!("Summary backfill for {} cancelled", repo);
+                finish(tx, BackfillStatus::Cancelled, None);
+            }
+            return;
+        }
+        let fatal = result.as_ref().err().filter(|e| {
+            ai_unavailable(e).is_some() || is_budget_exceeded(e) || is_rate_limited(e)
+        });
+        if let Some(e) = fatal {
+            // Every commit after this one would fail the same way
+            warn!("Stopping the summary backfill for {}: {:#}", repo, e);
+            handle.set_commit_status(index, JobCommitStatus::Failed, Some(format!("{:#}", e)));
+            finish(tx, BackfillStatus::Stopped, Some(format!("{:#}", e)));
+            return;
+        }
+        match &result {
+            Ok(()) => handle.set_commit_status(index, JobCommitStatus::Done, None),
+            Err(e) => handle.set_commit_status(index, JobCommitStatus::Failed, Some(format!("{:#}", e))),
+        }
+        tx.send_modify(|p| {
+            p.remaining -= 1;
+            match &result {
+                Ok(()) => p.done += 1,
+                Err(e) => {
+                    warn!("Summary backfill failed for {}/{}: {:#}", repo, commit, e);
+                    p.failed += 1;
+                    p.errors.push(BackfillError {
+                        commit: commit.clone(),
+                        error: format!("{:#}", e),
+                    });
+                }
+            }
+        });
+    }
+
+    finish(tx, BackfillStatus::Completed, None);
+    let progress = tx.borrow();
+    info!(
+        "Summary backfill for {} finished: {} summarized, {} failed",
+        repo, progress.done, progress.failed
+    );
+}
+
+/// Publish a backfill's final status
+fn finish(tx: &watch::Sender<BackfillProgress>, status: BackfillStatus, reason: Option<String>) {
+    tx.send_modify(|p| {
+        p.status = status;
+        p.current = None;
+        p.stopped_reason = reason;
+        p.finished_at = Some(Utc::now());
+    });
+}
+
+/// Start again the backfills a restart interrupted: those of repos with
+/// deferred summaries in flight, which they collect instead of submitting
+/// the requests again
+pub fn spawn_resume(state: AppState) {
+    tokio::spawn(async move {
+        let pending = match pending_deferred_completions(&state.pool, COMMIT_SUMMARY).await {
+            Ok(pending) => pending,
+            Err(e) => {
+                warn!("Failed to load deferred summaries to resume: {}", e);
+                return;
+            }
+        };
+        let repos: BTreeSet<String> = pending.iter().filter_map(|p| git::repo_slug(&p.repo_url)).collect();
+        for repo in repos {
+            costs::carry(async {
+                match backfill_settings(&state, &repo).await {
+                    Ok(settings) => {
+                        info!("Resuming the summary backfill for {}", repo);
+                        start(state.clone(), repo, settings, WorkClass::Backfill);
+                    }
+                    Err(e) => warn!("Not resuming the summary backfill for {}: {}", repo, e),
+                }
+            })
+            .await;
+        }
+    });
+}
+
+/// Schematic commits of `repo` without a stored summary, newest first
+async fn pending_commits(state: &AppState, repo: &str) -> Result<Vec<String>> {
+    let commits = git::get_schematic_commits(repo).await?;
+    let summarized = kicad_db::summarized_commits(&state.pool, &git::repo_url(repo))
+        .await
+        .context("Failed to load summarized commits")?;
+    Ok(commits
+        .into_iter()
+        .map(|c| c.commit_hash)
+        .filter(|commit| !summarized.contains(commit))
+        .collect())
+}
+
+/// Summarize a commit one way or the other: collecting the deferred summary
+/// already in flight for it, submitting one, or calling the model directly
+///
+/// New calls go to the settings' model, or the fallback it's routed to while
+/// it is slow or failing.
+async fn summarize_commit(
+    state: &AppState,
+    repo: &str,
+    commit: &str,
+    settings: &SummarySettings,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let recorded = find_deferred_completion(&state.pool, COMMIT_SUMMARY, &git::repo_url(repo), commit)
+        .await
+        .context("Failed to look up deferred summaries")?;
+    let model = || state.health.route("backfill", &settings.model);
+    match recorded {
+        Some(pending) => {
+            info!("Collecting the deferred summary for {}/{} submitted at {}", repo, commit, pending.submitted_at);
+            collect_deferred(state, repo, commit, &pending, cancel).await
+        }
+        None if state.config().xai.deferred_backfill => {
+            let pending = submit_deferred(state, repo, commit, settings, &model()).await?;
+            collect_deferred(state, repo, commit, &pending, cancel).await
+        }
+        None => summarize(state, repo, commit, settings, &model()).await,
+    }
+}
+
+/// Submit a deferred completion for a commit's summary to `model` and record it
+async fn submit_deferred(
+    state: &AppState,
+    repo: &str,
+    commit: &str,
+    settings: &SummarySettings,
+    model: &str,
+) -> Result<DeferredCompletion> {
+    let erc_comparison = erc_comparison(&state.pool, repo, commit).await;
+    let new_violations = erc_comparison
+        .as_ref()
+        .map(|c| c.introduced_findings())
+        .unwrap_or_default();
+    let warnings = anomalies::stored(&state.pool, repo, commit).await;
+    let (mut request, prompt_version) = summary::commit_summary_request(
+        &state.prompts(),
+        &settings.template,
+        model,
+        state.config().models.context_window(model),
+        repo,
+        commit,
+        settings.persona,
+        settings.guidelines.as_ref(),
+        &new_violations,
+        &warnings,
+    )?;
+    state.config().sampling.summary.fill(&mut request);
+
+    let deferred = state
+        .chat
+        .submit_deferred(&request)
+        .await
+        .context("XAI API call failed")?;
+    record_deferred_completion(
+        &state.pool,
+        &deferred.request_id,
+        COMMIT_SUMMARY,
+        &git::repo_url(repo),
+        commit,
+        &deferred.model,
+        &prompt_version,
+    )
+    .await
+    .context("Failed to record the deferred summary")
+}
+
+/// Wait for a deferred summary and store it like [`summarize`]
+async fn collect_deferred(
+    state: &AppState,
+    repo: &str,
+    commit: &str,
+    pending: &DeferredCompletion,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let mut timer = StageTimer::start("commit_summary");
+    let response = timer.time(Stage::Llm, wait_for_deferred(state, pending, cancel)).await?;
+    let summary = summary::summary_from_completion(&response)?;
+
+    let repo_url = git::repo_url(repo);
+    timer
+        .time(
+            Stage::Store,
+            UpdateSchematic::new(&repo_url, commit)
+                .update_summary(&summary, DetailLevel::default().as_str())
+                .update_prompt_version(&pending.prompt_version)
+                .execute(&state.pool),
+        )
+        .await
+        .context("Failed to store the summary")?;
+    forget_deferred(state, pending).await;
+    let timeline = timer.finish();
+    timing::store_timeline(&state.pool, &repo_url, commit, "commit_summary", &timeline).await;
+    Ok(())
+}
+
+/// Poll for a deferred completion's result until it's ready
+///
+/// Requests that expired or took too long are forgotten, so the next
+/// backfill submits them again. Other errors, shutting down and `cancel`
+/// leave the request for the next backfill to collect.
+async fn wait_for_deferred(
+    state: &AppState,
+    pending: &DeferredCompletion,
+    cancel: &CancellationToken,
+) -> Result<ChatCompletionResponse> {
+    let deferred = DeferredRequest {
+        request_id: pending.request_id.clone(),
+        model: pending.model.clone(),
+    };
+    let deadline = pending.submitted_at + DEFERRED_TIMEOUT;
+    let mut delay = DEFERRED_POLL_START;
+    loop {
+        match state.chat.deferred_completion(&deferred).await {
+            Ok(Some(response)) => return Ok(response),
+            Ok(None) => {}
+            Err(XaiError::Status { status: 404, .. }) => {
+                forget_deferred(state, pending).await;
+                bail!("Deferred request {} expired before its result was collected", pending.request_id);
+            }
+            Err(e) => return Err(e).context("XAI API call failed"),
+        }
+        if Utc::now() >= deadline {
+            forget_deferred(state, pending).await;
+            bail!(
+                "Deferred request {} had no result {} minutes after it was submitted",
+                pending.request_id,
+                DEFERRED_TIMEOUT.as_secs() / 60
+            );
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown::started() => bail!("The server is shutting down"),
+            _ = cancel.cancelled() => bail!("The backfill was cancelled"),
+        }
+        delay = (delay * 2).min(DEFERRED_POLL_MAX);
+    }
+}
+
+async fn forget_deferred(state: &AppState, pending: &DeferredCompletion) {
+    if let Err(e) = delete_deferred_completion(&state.pool, &pending.request_id).await {
+        warn!("Failed to forget deferred request {}: {}", pending.request_id, e);
+    }
+}
+
+/// Generate and store a commit's summary with `model` at the default detail
+/// level, like `/api/grok/summary/commit`
+async fn summarize(state: &AppState, repo: &str, commit: &str, settings: &SummarySettings, model: &str) -> Result<()> {
+    let erc_comparison = erc_comparison(&state.pool, repo, commit).await;
+    let new_violations = erc_comparison
+        .as_ref()
+        .map(|c| c.introduced_findings())
+        .unwrap_or_default();
+    let warnings = anomalies::stored(&state.pool, repo, commit).await;
+
+    let chat = sampled(state.chat.clone(), &state.config().sampling.summary);
+    let mut timer = StageTimer::start("commit_summary");
+    let CommitSummary {
+        summary,
+        prompt_version,
+        ..
+    } = timer
+        .time(
+            Stage::Llm,
+            summary::generate_commit_summary(
+                chat.as_ref(),
+                &state.prompts(),
+                &settings.template,
+                model,
+                state.config().models.context_window(model),
+                repo,
+                commit,
+                None,
+                settings.persona,
+                settings.guidelines.as_ref(),
+                &new_violations,
+                &warnings,
+            ),
+        )
+        .await?;
+
+    let repo_url = git::repo_url(repo);
+    timer
+        .time(
+            Stage::Store,
+            UpdateSchematic::new(&repo_url, commit)
+                .update_summary(&summary, DetailLevel::default().as_str())
+                .update_prompt_version(&prompt_version)
+                .execute(&state.pool),
+        )
+        .await
+        .context("Failed to store the summary")?;
+    let timeline = timer.finish();
+    timing::store_timeline(&state.pool, &repo_url, commit, "commit_summary", &timeline).await;
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use anyhow::{Context, Result};
+use kicad_db::pcb::{self, diff_boards, Board, BoardDiff};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tracing::warn;
+
+use crate::request_id;
+use crate::services::git;
+
+/// Parse every .kicad_pcb file in the repo at a commit
+///
+/// A board the parser can't read is logged and skipped rather than failing
+/// the whole commit, since layout stats are supplementary to the schematic.
+pub async fn load_boards(repo_slug: &str, commit_hash: &str) -> Result<Vec<Board>> {
+    let files = git::get_board_files(repo_slug, commit_hash)
+        .await
+        .context("Failed to fetch board files from repo")?;
+
+    let boards = request_id::spawn_blocking(move || {
+        files
+            .into_iter()
+            .filter_map(|file| match pcb::parse_board(&file.path, &file.content) {
+                Ok(board) => Some(board),
+                Err(e) => {
+                    warn!("Skipping unreadable board {}: {}", file.path, e);
+                    None
+                }
+            })
+            .collect()
+    })
+    .await?;
+    Ok(boards)
+}
+
+/// Boards at a commit plus the layout changes since its parent, keyed by board file
+///
+/// Boards are matched to the parent commit by path; a board with no
+/// counterpart is diffed against an empty one. Unchanged boards are omitted.
+pub async fn board_changes(
+    repo_slug: &str,
+    commit_hash: &str,
+) -> Result<(Vec<Board>, Vec<(String, BoardDiff)>)> {
+    let current = load_boards(repo_slug, commit_hash).await?;
+    let previous = match git::get_parent_commit(repo_slug, commit_hash).await? {
+        Some(parent) => load_boards(repo_slug, &parent).await?,
+        None => Vec::new(),
+    };
+
+    let empty = Board::default();
+    let mut changes = Vec::new();
+    for board in &current {
+        let before = previous.iter().find(|b| b.file == board.file).unwrap_or(&empty);
+        let diff = diff_boards(before, board);
+        if !diff.is_empty() {
+            changes.push((board.file.clone(), diff));
+        }
+    }
+    for board in previous.iter().filter(|b| !current.iter().any(|c| c.file == b.file)) {
+        changes.push((board.file.clone(), diff_boards(board, &empty)));
+    }
+    Ok((current, changes))
+}
+
+/// Boards as stored in `schematics.board_json`, keyed by file path
+pub fn to_json(boards: &[Board]) -> Value {
+    let by_file: BTreeMap<&str, &Board> = boards.iter().map(|b| (b.file.as_str(), b)).collect();
+    serde_json::to_value(by_file).unwrap_or_default()
+}
+
+/// Boards back from `schematics.board_json`
+pub fn from_json(value: Value) -> Result<Vec<Board>> {
+    let by_file: BTreeMap<String, Board> =
+        serde_json::from_value(value).context("Stored board summary is malformed")?;
+    Ok(by_file.into_values().collect())
+}
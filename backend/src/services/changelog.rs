@@ -0,0 +1,105 @@
+use anyhow::{Context, Result};
+use kicad_db::{
+    count_schematics, get_changelog_settings, list_schematics, render_changelog, ChangelogEntry,
+    ChangelogRelease, PgPool, SortOrder,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::request_id;
+use crate::services::{git, github, status};
+
+/// Render HARDWARE_CHANGELOG.md from the stored commit overviews
+///
+/// Only summarized commits with public summaries are included, since the file
+/// is published into the repository itself.
+pub async fn generate(pool: &PgPool, repo_slug: &str) -> Result<String> {
+    let repo_url = git::repo_url(repo_slug);
+    let total = count_schematics(pool, &repo_url).await?;
+    let rows = list_schematics(pool, &repo_url, total.max(1), 0, SortOrder::Desc)
+        .await
+        .context("Failed to load stored commits")?;
+    let mut by_commit: HashMap<String, ChangelogEntry> = rows
+        .into_iter()
+        .filter(|row| row.visibility == "public")
+        .filter_map(|row| {
+            Some((
+                row.commit_hash.clone(),
+                ChangelogEntry {
+                    blurb: row.blurb?,
+                    commit_hash: row.commit_hash,
+                    commit_date: row.commit_date,
+                    message: row.git_message,
+                },
+            ))
+        })
+        .collect();
+
+    let releases: Vec<ChangelogRelease> = git::get_releases(repo_slug)
+        .await
+        .context("Failed to group commits into releases")?
+        .into_iter()
+        .map(|release| ChangelogRelease {
+            tag: release.tag,
+            date: release.date,
+            entries: release
+                .commits
+                .iter()
+                .filter_map(|commit| by_commit.remove(commit))
+                .collect(),
+        })
+        .collect();
+
+    Ok(render_changelog(repo_slug, &releases))
+}
+
+/// Generate the changelog and propose it to the repo as a pull request
+///
+/// Returns the pull request URL, or `None` if the file is already up to date.
+pub async fn publish(pool: &PgPool, repo_slug: &str) -> Result<Option<String>> {
+    let settings = get_changelog_settings(pool, &git::repo_url(repo_slug)).await?;
+    let markdown = generate(pool, repo_slug).await?;
+    github::propose_file(
+        repo_slug,
+        &github::FileProposal {
+            path: &settings.path,
+            content: &markdown,
+            branch: &settings.branch,
+            commit_message: &format!("Update {}", settings.path),
+            title: &format!("Update {}", settings.path),
+            body: "Regenerated from the latest analyzed commits. \
+                This branch is reset on every update, so edits here will be overwritten.",
+        },
+    )
+    .await
+}
+
+/// After new commits were processed, publish the changelog in the background if the repo opted in
+pub fn spawn_publish_if_enabled(pool: Arc<PgPool>, repo_slug: String) {
+    request_id::spawn(async move {
+        let enabled = match get_changelog_settings(&pool, &git::repo_url(&repo_slug)).await {
+            Ok(settings) => settings.enabled,
+            Err(e) => {
+                warn!("Could not read changelog settings for {}: {}", repo_slug, e);
+                return;
+            }
+        };
+        if !enabled {
+            return;
+        }
+        if !github::is_configured() {
+            warn!("Changelog enabled for {} but GITHUB_TOKEN is not set", repo_slug);
+            return;
+        }
+
+        match publish(&pool, &repo_slug).await {
+            Ok(Some(url)) => info!("Changelog pull request for {}: {}", repo_slug, url),
+            Ok(None) => info!("Changelog for {} is already up to date", repo_slug),
+            Err(e) => {
+                warn!("Failed to publish changelog for {}: {:#}", repo_slug, e);
+                status::record_error(Some(&repo_slug), None, None, &format!("Changelog: {:#}", e));
+            }
+        }
+    });
+}
@@ -0,0 +1,119 @@
+use anyhow::{Context, Result};
+use kicad_db::{
+    messages::{ChatCompletionRequest, Message},
+    prompts::{PromptLibrary, CHAT_HISTORY_SUMMARY},
+    tokens::{count_message_tokens, count_tokens},
+    ChatSession, NewChatTurn, PgPool, StoredChatTurn,
+};
+use tracing::info;
+
+use crate::config::ChatHistoryConfig;
+use crate::services::{llm::ChatProvider, summary::complete};
+
+/// Longest running summary asked for, in tokens
+const SUMMARY_MAX_TOKENS: u32 = 1_000;
+
+/// What a session sends with each request: its running summary, then the
+/// turns after it as they were said
+pub struct History {
+    pub session: ChatSession,
+    pub turns: Vec<StoredChatTurn>,
+}
+
+impl History {
+    /// The session's history from its summary on
+    pub async fn load(pool: &PgPool, session: ChatSession) -> Result<Self> {
+        let turns = kicad_db::chat_turns(pool, session.id, session.summarized_through)
+            .await
+            .context("Failed to load the chat history")?;
+        Ok(Self { session, turns })
+    }
+
+    /// Tokens the history adds to a request, as counted when it was stored
+    pub fn tokens(&self) -> usize {
+        self.session.summary_tokens as usize + self.turns.iter().map(|turn| turn.tokens as usize).sum::<usize>()
+    }
+
+    /// The summary as a system message, then the turns
+    pub fn messages(&self) -> Vec<Message> {
+        let summary = self
+            .session
+            .summary
+            .as_ref()
+            .map(|summary| Message::system(format!("Summary of the conversation so far:\n{}", summary)));
+        summary.into_iter().chain(self.turns.iter().map(message)).collect()
+    }
+
+    /// Fold the turns before the last `keep_recent_turns` into the running
+    /// summary if a request of `request` plus the history would fill more
+    /// than `compress_at_percent` of `context_window`; returns whether it did
+    ///
+    /// The folded turns stay stored; only the summary replaces them in
+    /// requests.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn compress_if_needed(
+        &mut self,
+        pool: &PgPool,
+        chat: &dyn ChatProvider,
+        prompts: &PromptLibrary,
+        config: &ChatHistoryConfig,
+        model: &str,
+        context_window: usize,
+        request: &[Message],
+    ) -> Result<bool> {
+        let limit = context_window * config.compress_at_percent as usize / 100;
+        let needed = count_message_tokens(model, request) + self.tokens();
+        if needed <= limit || self.turns.len() <= config.keep_recent_turns {
+            return Ok(false);
+        }
+
+        let kept = self.turns.split_off(self.turns.len() - config.keep_recent_turns);
+        let folded = std::mem::replace(&mut self.turns, kept);
+        let turns: Vec<_> = folded
+            .iter()
+            .map(|turn| serde_json::json!({ "role": turn.role, "content": turn.content }))
+            .collect();
+        let repo = self.session.repo_url.as_deref().and_then(crate::services::git::repo_slug);
+        let prompt = prompts.render(
+            CHAT_HISTORY_SUMMARY,
+            serde_json::json!({ "repo": repo, "summary": self.session.summary, "turns": turns }),
+        )?;
+        let request = ChatCompletionRequest::with_stream(vec![Message::user(prompt.text)], model.to_string(), true)
+            .with_max_tokens(SUMMARY_MAX_TOKENS);
+        let summary = complete(chat, &request).await.context("Failed to summarize the chat history")?;
+
+        let summary_tokens = count_tokens(model, &summary) as i32;
+        let through = folded.last().map_or(self.session.summarized_through, |turn| turn.position);
+        kicad_db::store_chat_summary(pool, self.session.id, &summary, summary_tokens, through)
+            .await
+            .context("Failed to store the chat summary")?;
+        info!(
+            "Folded {} turns of chat session {} ({} tokens needed, limit {})",
+            folded.len(),
+            self.session.id,
+            needed,
+            limit
+        );
+        self.session.summary = Some(summary);
+        self.session.summary_tokens = summary_tokens;
+        self.session.summarized_through = through;
+        Ok(true)
+    }
+}
+
+/// A turn to store, its tokens counted for `model`
+pub fn new_turn(model: &str, role: &str, content: String) -> NewChatTurn {
+    NewChatTurn {
+        role: role.to_string(),
+        tokens: count_tokens(model, &content) as i32,
+        content,
+        ..NewChatTurn::default()
+    }
+}
+
+fn message(turn: &StoredChatTurn) -> Message {
+    match turn.role.as_str() {
+        "assistant" => Message::assistant(turn.content.clone()),
+        _ => Message::user(turn.content.clone()),
+    }
+}
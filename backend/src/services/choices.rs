@@ -0,0 +1,46 @@
+use std::collections::BTreeSet;
+
+/// Picks one of the answers a request for several choices (`n` > 1) got
+pub trait ChoiceSelector: Send + Sync {
+    /// Index of the answer to use, None if none will do
+    fn select(&self, choices: &[String]) -> Option<usize>;
+}
+
+/// The first answer that isn't empty, as if only one had been asked for
+pub struct FirstChoice;
+
+impl ChoiceSelector for FirstChoice {
+    fn select(&self, choices: &[String]) -> Option<usize> {
+        choices.iter().position(|choice| !choice.trim().is_empty())
+    }
+}
+
+/// The shortest answer naming one of `references`, else the shortest; for
+/// blurbs, where a short line about the changed parts beats a long one
+pub struct ShortestMentioning<'a> {
+    pub references: &'a BTreeSet<String>,
+}
+
+impl ShortestMentioning<'_> {
+    /// Whether `text` names a reference as a word of its own, so R1 isn't
+    /// found in R12
+    fn mentions(&self, text: &str) -> bool {
+        text.split(|c: char| !c.is_ascii_alphanumeric() && c != '?')
+            .any(|word| self.references.contains(word))
+    }
+}
+
+impl ChoiceSelector for ShortestMentioning<'_> {
+    fn select(&self, choices: &[String]) -> Option<usize> {
+        let shortest = |mentioning: bool| {
+            choices
+                .iter()
+                .enumerate()
+                .filter(|(_, choice)| !choice.trim().is_empty())
+                .filter(|(_, choice)| !mentioning || self.mentions(choice))
+                .min_by_key(|(_, choice)| choice.chars().count())
+                .map(|(index, _)| index)
+        };
+        shortest(true).or_else(|| shortest(false))
+    }
+}
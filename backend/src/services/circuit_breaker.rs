@@ -0,0 +1,277 @@
+use futures_util::{future::BoxFuture, StreamExt};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use kicad_db::{
+    messages::ChatCompletionRequest,
+    xai_client::{
+        ChatCompletionResponse, ChatCompletionStream, DeferredRequest, ResponsesRequest, ResponsesResponse,
+        StreamEvent,
+    },
+    XaiError,
+};
+
+use super::{llm::ChatProvider, metrics};
+
+/// Where the breaker stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through
+    Closed,
+    /// The provider is considered down; calls fail at once
+    Open,
+    /// The cooldown is over and one probe call is deciding whether to close
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half_open",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Breaker {
+    /// Name of the provider, for logs and metrics
+    provider: String,
+    state: CircuitState,
+    /// Outage-like failures in a row
+    failures: u32,
+    opened_at: Instant,
+    /// A half-open probe is in flight, so other calls keep failing fast
+    probing: bool,
+}
+
+impl Breaker {
+    fn transition(&mut self, to: CircuitState) {
+        if self.state == to {
+            return;
+        }
+        match to {
+            CircuitState::Open => warn!(
+                "{} circuit opened after {} consecutive failure(s); AI calls fail fast until a probe succeeds",
+                self.provider, self.failures
+            ),
+            CircuitState::HalfOpen => info!("{} circuit half-open; probing the provider", self.provider),
+            CircuitState::Closed => info!("{} circuit closed; the provider is responding again", self.provider),
+        }
+        metrics::record_circuit_transition(&self.provider, to);
+        self.state = to;
+    }
+}
+
+/// Whether an error suggests the provider is down, rather than that this
+/// request was wrong, rate limited or abandoned
+fn is_outage(err: &XaiError) -> bool {
+    match err {
+        XaiError::Http(_) | XaiError::Timeout | XaiError::Idle(_) => true,
+        XaiError::Status { status, .. } => *status >= 500,
+        _ => false,
+    }
+}
+
+/// Fails LLM calls fast while the provider is down
+///
+/// After `threshold` consecutive outage-like failures (connection errors,
+/// timeouts, stalled streams, 5xx) the circuit opens: every call returns
+/// [`XaiError::Unavailable`], which handlers turn into a 503, instead of
+/// waiting out the timeout. Once `cooldown` has passed, the next call goes
+/// through as a probe; its success closes the circuit and its failure opens
+/// it for another cooldown. Rate limits, cancellations and 4xx responses
+/// don't count either way.
+///
+/// Sits below the response cache, so cached answers are still served while
+/// the circuit is open. Each provider calls can fail over to has its own
+/// breaker, and an open one sends its calls straight to the next.
+pub struct CircuitBreakerProvider {
+    inner: Arc<dyn ChatProvider>,
+    threshold: u32,
+    cooldown: Duration,
+    breaker: Arc<Mutex<Breaker>>,
+}
+
+impl CircuitBreakerProvider {
+    pub fn new(inner: Arc<dyn ChatProvider>, provider: &str, threshold: u32, cooldown: Duration) -> Self {
+        metrics::set_circuit_state(provider, CircuitState::Closed);
+        Self {
+            inner,
+            threshold: threshold.max(1),
+            cooldown,
+            breaker: Arc::new(Mutex::new(Breaker {
+                provider: provider.to_string(),
+                state: CircuitState::Closed,
+                failures: 0,
+                opened_at: Instant::now(),
+                probing: false,
+            })),
+        }
+    }
+
+    /// Let a call through, or fail it while the circuit is open
+    fn admit(&self) -> Result<Permit, XaiError> {
+        let mut breaker = self.breaker.lock().unwrap();
+        let waited = breaker.opened_at.elapsed();
+        match breaker.state {
+            CircuitState::Closed => {}
+            CircuitState::Open if waited >= self.cooldown => {
+                breaker.transition(CircuitState::HalfOpen);
+                breaker.probing = true;
+            }
+            CircuitState::HalfOpen if !breaker.probing => breaker.probing = true,
+            CircuitState::Open | CircuitState::HalfOpen => {
+                // Round up, so clients never see "retry in 0s"
+                let remaining = self.cooldown.saturating_sub(waited);
+                return Err(XaiError::Unavailable {
+                    retry_after: Duration::from_secs(remaining.as_secs() + 1),
+                });
+            }
+        }
+        Ok(Permit {
+            breaker: self.breaker.clone(),
+            threshold: self.threshold,
+            probe: breaker.state == CircuitState::HalfOpen,
+        })
+    }
+
+    async fn call<T>(&self, call: BoxFuture<'_, Result<T, XaiError>>) -> Result<T, XaiError> {
+        let permit = self.admit()?;
+        let result = call.await;
+        permit.finish(result.as_ref().map(|_| ()));
+        result
+    }
+}
+
+/// One admitted call; reports its outcome to the breaker
+///
+/// Dropped without an outcome (e.g. the caller went away), a probe just
+/// frees the way for the next one.
+struct Permit {
+    breaker: Arc<Mutex<Breaker>>,
+    threshold: u32,
+    probe: bool,
+}
+
+impl Permit {
+    fn finish(mut self, result: Result<(), &XaiError>) {
+        let mut breaker = self.breaker.lock().unwrap();
+        if self.probe {
+            breaker.probing = false;
+            self.probe = false;
+        }
+        match result {
+            Ok(()) => {
+                breaker.failures = 0;
+                breaker.transition(CircuitState::Closed);
+            }
+            Err(e) if is_outage(e) => {
+                breaker.failures = breaker.failures.saturating_add(1);
+                if breaker.state == CircuitState::HalfOpen || breaker.failures >= self.threshold {
+                    breaker.opened_at = Instant::now();
+                    breaker.transition(CircuitState::Open);
+                }
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.lock().unwrap().probing = false;
+        }
+    }
+}
+
+impl ChatProvider for CircuitBreakerProvider {
+    fn responses<'a>(
+        &'a self,
+        request: &'a ResponsesRequest,
+    ) -> BoxFuture<'a, Result<ResponsesResponse, XaiError>> {
+        Box::pin(self.call(self.inner.responses(request)))
+    }
+
+    /// A stream counts once it ends: a stream that stalls halfway is as much a
+    /// sign of an outage as one that never starts
+    fn chat_completion_stream<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<ChatCompletionStream, XaiError>> {
+        Box::pin(async move {
+            let permit = self.admit()?;
+            let mut upstream = match self.inner.chat_completion_stream(request, cancel).await {
+                Ok(upstream) => upstream,
+                Err(e) => {
+                    permit.finish(Err(&e));
+                    return Err(e);
+                }
+            };
+            let watched: ChatCompletionStream = Box::pin(async_stream::stream! {
+                let mut permit = Some(permit);
+                while let Some(result) = upstream.next().await {
+                    match &result {
+                        Ok(StreamEvent::Done) => {
+                            if let Some(permit) = permit.take() {
+                                permit.finish(Ok(()));
+                            }
+                        }
+                        Err(e) => {
+                            if let Some(permit) = permit.take() {
+                                permit.finish(Err(e));
+                            }
+                        }
+                        Ok(_) => {}
+                    }
+                    yield result;
+                }
+                // Ended without [DONE], but the provider did answer
+                if let Some(permit) = permit.take() {
+                    permit.finish(Ok(()));
+                }
+            });
+            Ok(watched)
+        })
+    }
+
+    fn submit_deferred<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+    ) -> BoxFuture<'a, Result<DeferredRequest, XaiError>> {
+        Box::pin(self.call(self.inner.submit_deferred(request)))
+    }
+
+    fn deferred_completion<'a>(
+        &'a self,
+        deferred: &'a DeferredRequest,
+    ) -> BoxFuture<'a, Result<Option<ChatCompletionResponse>, XaiError>> {
+        Box::pin(self.call(self.inner.deferred_completion(deferred)))
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, XaiError>> {
+        Box::pin(self.call(self.inner.list_models()))
+    }
+
+    fn embed<'a>(&'a self, model: &'a str, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, XaiError>> {
+        Box::pin(self.call(self.inner.embed(model, texts)))
+    }
+
+    /// Open only until the cooldown ends, so a background job can be the probe
+    fn available(&self) -> bool {
+        let breaker = self.breaker.lock().unwrap();
+        match breaker.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => breaker.opened_at.elapsed() >= self.cooldown,
+            CircuitState::HalfOpen => !breaker.probing,
+        }
+    }
+
+    fn uncached(&self) -> &dyn ChatProvider {
+        self
+    }
+}
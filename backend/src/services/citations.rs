@@ -0,0 +1,66 @@
+use kicad_db::{
+    schematic::{render_symbol_svg, Project},
+    ChatAttachment, ChatCitation, CitationKind,
+};
+
+use crate::services::ask;
+use crate::types::{ChatSource, ChatSourceKind};
+
+/// Symbols drawn for one answer; more cited components go without
+const MAX_ATTACHMENTS: usize = 4;
+/// Characters of a commit hash an answer has to quote to cite it
+const SHORT_HASH: usize = 7;
+
+/// What `answer` cites, for the viewer to link: the components and nets of
+/// `projects` (at `commit`) it names, then the commits of the retrieved
+/// `sources` it cites by tag or quotes by hash
+pub fn cite(answer: &str, commit: Option<&str>, projects: &[Project], sources: &[ChatSource]) -> Vec<ChatCitation> {
+    let mut citations = Vec::new();
+    if let Some(commit) = commit {
+        let (components, nets) = ask::mentioned(answer, projects);
+        let at = |kind, target| ChatCitation {
+            kind,
+            target,
+            commit_hash: Some(commit.to_string()),
+        };
+        citations.extend(components.into_iter().map(|reference| at(CitationKind::Component, reference)));
+        citations.extend(nets.into_iter().map(|net| at(CitationKind::Net, net)));
+    }
+    for source in sources.iter().filter(|source| source.kind == ChatSourceKind::Commit) {
+        let hash = &source.commit_hash;
+        let quoted = hash.len() >= SHORT_HASH && ask::names(answer, &hash[..SHORT_HASH]);
+        let tagged = !source.id.is_empty() && answer.contains(&format!("[{}]", source.id));
+        let cited = citations.iter().any(|c| c.kind == CitationKind::Commit && &c.target == hash);
+        if (quoted || tagged) && !cited {
+            citations.push(ChatCitation {
+                kind: CitationKind::Commit,
+                target: hash.clone(),
+                commit_hash: None,
+            });
+        }
+    }
+    citations
+}
+
+/// The symbol of each cited component, drawn as SVG, in citation order; kept
+/// with the answer so a stored conversation shows the same snippets
+pub fn attach(citations: &[ChatCitation], projects: &[Project]) -> Vec<ChatAttachment> {
+    citations
+        .iter()
+        .filter(|citation| citation.kind == CitationKind::Component)
+        .filter_map(|citation| {
+            let (symbol, lib) = projects
+                .iter()
+                .flat_map(|project| project.symbol_units(&citation.target))
+                .next()?;
+            let title = format!("{} {}", citation.target, symbol.lib_id);
+            Some(ChatAttachment {
+                content: render_symbol_svg(lib, symbol.unit, symbol.body_style, &title),
+                title,
+                media_type: "image/svg+xml".to_string(),
+                cites: Some(citation.target.clone()),
+            })
+        })
+        .take(MAX_ATTACHMENTS)
+        .collect()
+}
@@ -0,0 +1,322 @@
+use axum::{extract::Request, middleware::Next, response::Response};
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use futures_util::{future::BoxFuture, StreamExt};
+use std::cell::RefCell;
+use std::future::Future;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+use kicad_db::{
+    messages::ChatCompletionRequest,
+    record_ai_spend, spend_since,
+    xai_client::{
+        ChatCompletionResponse, ChatCompletionStream, DeferredRequest, ResponsesRequest, ResponsesResponse,
+        StreamEvent,
+    },
+    AiSpend, PgPool, SpendBy, XaiError,
+};
+
+use super::{git, llm::ChatProvider};
+use crate::auth::Caller;
+use crate::config::{Config, CostConfig};
+
+/// Who the AI calls of a request are charged to
+#[derive(Debug, Clone, Default)]
+struct Attribution {
+    repo: Option<String>,
+    api_key: Option<String>,
+    api_key_name: Option<String>,
+    org: Option<i32>,
+    /// Experiment and variant the calls are made for
+    experiment: Option<(String, String)>,
+}
+
+tokio::task_local! {
+    static ATTRIBUTION: RefCell<Attribution>;
+}
+
+/// Middleware charging the AI calls a request makes to its API key
+///
+/// Must run after authentication, so the caller is known.
+pub async fn attribute(request: Request, next: Next) -> Response {
+    let attribution = match request.extensions().get::<Caller>() {
+        Some(caller) => Attribution {
+            repo: None,
+            api_key: Some(caller.id.clone()),
+            api_key_name: Some(caller.name.clone()),
+            org: caller.org,
+            experiment: None,
+        },
+        None => Attribution::default(),
+    };
+    ATTRIBUTION.scope(RefCell::new(attribution), next.run(request)).await
+}
+
+/// Charge the AI calls made for the rest of this request to `repo_slug`,
+/// and to the org it is registered to
+pub fn charge_repo(repo_slug: &str, org: Option<i32>) {
+    let _ = ATTRIBUTION.try_with(|a| {
+        let mut a = a.borrow_mut();
+        a.repo = Some(repo_slug.to_string());
+        a.org = org.or(a.org);
+    });
+}
+
+/// Record the AI calls made for the rest of this request under a variant
+/// of an experiment, so its cost can be compared with the other variants'
+pub fn charge_experiment(experiment: &str, variant: &str) {
+    let _ = ATTRIBUTION.try_with(|a| {
+        a.borrow_mut().experiment = Some((experiment.to_string(), variant.to_string()));
+    });
+}
+
+/// Charge the AI calls of `future` like those of the current request, for
+/// background work a request starts
+pub fn carry<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    ATTRIBUTION.scope(RefCell::new(current()), future)
+}
+
+fn current() -> Attribution {
+    ATTRIBUTION.try_with(|a| a.borrow().clone()).unwrap_or_default()
+}
+
+/// Repo slug, API key and org the current request's AI calls are charged to
+pub(crate) fn charged_to() -> (Option<String>, Option<String>, Option<i32>) {
+    let a = current();
+    (a.repo, a.api_key, a.org)
+}
+
+/// Midnight UTC on the first of this month, when budgets start over
+pub fn month_start(now: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .single()
+        .unwrap_or(now)
+}
+
+/// Prices AI calls, records them against their repo and API key, and applies
+/// the monthly budgets
+///
+/// A call charged to a repo or key whose budget is spent runs on the
+/// configured fallback model instead, or fails with
+/// [`XaiError::BudgetExceeded`] if there is none. Calls outside a request
+/// (background jobs not started through [`carry`]) are recorded but never
+/// limited. Embeddings are not
+/// priced, as the provider doesn't report their usage per call.
+///
+/// Sits below the response cache: cached answers cost nothing. Each
+/// provider a call can fail over to has its own, so calls are recorded with
+/// the provider that served them.
+pub struct MeteredChatProvider {
+    inner: Arc<dyn ChatProvider>,
+    /// Name of the provider `inner` calls, as recorded in the ledger
+    provider: String,
+    pool: Arc<PgPool>,
+    /// Prices and budgets are read from the config in effect at each call
+    config: Arc<ArcSwap<Config>>,
+}
+
+impl MeteredChatProvider {
+    pub fn new(inner: Arc<dyn ChatProvider>, provider: &str, pool: Arc<PgPool>, config: Arc<ArcSwap<Config>>) -> Self {
+        Self {
+            inner,
+            provider: provider.to_string(),
+            pool,
+            config,
+        }
+    }
+
+    fn costs(&self) -> CostConfig {
+        self.config.load().costs.clone()
+    }
+
+    /// The model to call instead of `model`, if a budget is spent
+    async fn check_budgets(&self, attribution: &Attribution, model: &str) -> Result<Option<String>, XaiError> {
+        let since = month_start(Utc::now());
+        let repo_url = attribution.repo.as_deref().map(git::repo_url);
+        let costs = self.costs();
+        let mut limits = Vec::new();
+        if let (Some(slug), Some(url)) = (attribution.repo.as_deref(), repo_url.as_deref()) {
+            if let Some(budget) = costs.repo_budget(slug) {
+                limits.push((SpendBy::Repo(url), budget, format!("repo {}", slug)));
+            }
+        }
+        if let (Some(key), Some(budget)) = (attribution.api_key.as_deref(), costs.key_budget()) {
+            let name = attribution.api_key_name.as_deref().unwrap_or(key);
+            limits.push((SpendBy::ApiKey(key), budget, format!("API key {}", name)));
+        }
+
+        for (by, budget, scope) in limits {
+            let spent = match spend_since(&self.pool, by, since).await {
+                Ok(spent) => spent,
+                Err(e) => {
+                    // Don't turn a database hiccup into an AI outage
+                    warn!("Failed to check the AI budget of {}: {}", scope, e);
+                    continue;
+                }
+            };
+            if spent < budget {
+                continue;
+            }
+            return match &costs.fallback_model {
+                Some(fallback) if fallback != model => {
+                    info!(
+                        "{} spent ${:.2} of its ${:.2} budget; using {} instead of {}",
+                        scope, spent, budget, fallback, model
+                    );
+                    Ok(Some(fallback.clone()))
+                }
+                Some(_) => Ok(None),
+                None => {
+                    warn!("{} spent ${:.2} of its ${:.2} budget; refusing AI call", scope, spent, budget);
+                    Err(XaiError::BudgetExceeded(scope))
+                }
+            };
+        }
+        Ok(None)
+    }
+}
+
+/// Price a call and add it to the ledger; failures are only logged
+#[allow(clippy::too_many_arguments)]
+async fn record(
+    pool: &PgPool,
+    costs: &CostConfig,
+    attribution: Attribution,
+    endpoint: &str,
+    provider: &str,
+    model: &str,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+) {
+    let cost_usd = costs.cost(model, prompt_tokens, completion_tokens).unwrap_or_else(|| {
+        debug!("No price configured for {}; recording its tokens at no cost", model);
+        0.0
+    });
+    let (experiment, variant) = attribution.experiment.unzip();
+    let spend = AiSpend {
+        endpoint: endpoint.to_string(),
+        provider: provider.to_string(),
+        model: model.to_string(),
+        repo_url: attribution.repo.as_deref().map(git::repo_url),
+        api_key: attribution.api_key,
+        api_key_name: attribution.api_key_name,
+        org_id: attribution.org,
+        prompt_tokens: prompt_tokens as i64,
+        completion_tokens: completion_tokens as i64,
+        cost_usd,
+        experiment,
+        variant,
+    };
+    if let Err(e) = record_ai_spend(pool, &spend).await {
+        warn!("Failed to record the cost of a {} call: {}", endpoint, e);
+    }
+}
+
+impl ChatProvider for MeteredChatProvider {
+    fn responses<'a>(
+        &'a self,
+        request: &'a ResponsesRequest,
+    ) -> BoxFuture<'a, Result<ResponsesResponse, XaiError>> {
+        Box::pin(async move {
+            let attribution = current();
+            // Priced by the model asked for; the API may answer with a longer alias
+            let (response, model) = match self.check_budgets(&attribution, &request.model).await? {
+                Some(fallback) => {
+                    let mut request = request.clone();
+                    request.model = fallback;
+                    (self.inner.responses(&request).await?, request.model)
+                }
+                None => (self.inner.responses(request).await?, request.model.clone()),
+            };
+            let usage = response.usage.as_ref();
+            let prompt = usage.and_then(|u| u.prompt_tokens).unwrap_or(0);
+            let completion = usage.and_then(|u| u.completion_tokens).unwrap_or(0);
+            record(&self.pool, &self.costs(), attribution, "responses", &self.provider, &model, prompt.into(), completion.into()).await;
+            Ok(response)
+        })
+    }
+
+    fn chat_completion_stream<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<ChatCompletionStream, XaiError>> {
+        Box::pin(async move {
+            // Taken now: the stream is read after the handler has returned
+            let attribution = current();
+            let (mut upstream, model) = match self.check_budgets(&attribution, &request.model).await? {
+                Some(fallback) => {
+                    let mut request = request.clone();
+                    request.model = fallback;
+                    (self.inner.chat_completion_stream(&request, cancel).await?, request.model)
+                }
+                None => (self.inner.chat_completion_stream(request, cancel).await?, request.model.clone()),
+            };
+            let pool = self.pool.clone();
+            let costs = self.costs();
+            let provider = self.provider.clone();
+            let metered: ChatCompletionStream = Box::pin(async_stream::stream! {
+                while let Some(result) = upstream.next().await {
+                    if let Ok(StreamEvent::Usage { usage }) = &result {
+                        let prompt = usage.prompt_tokens.unwrap_or(0).into();
+                        let completion = usage.completion_tokens.unwrap_or(0).into();
+                        record(&pool, &costs, attribution.clone(), "chat_completion_stream", &provider, &model, prompt, completion).await;
+                    }
+                    yield result;
+                }
+            });
+            Ok(metered)
+        })
+    }
+
+    /// Budgets apply when the request is submitted; it's priced when its
+    /// result arrives, by the model it was submitted to
+    fn submit_deferred<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+    ) -> BoxFuture<'a, Result<DeferredRequest, XaiError>> {
+        Box::pin(async move {
+            match self.check_budgets(&current(), &request.model).await? {
+                Some(fallback) => {
+                    let mut request = request.clone();
+                    request.model = fallback;
+                    self.inner.submit_deferred(&request).await
+                }
+                None => self.inner.submit_deferred(request).await,
+            }
+        })
+    }
+
+    fn deferred_completion<'a>(
+        &'a self,
+        deferred: &'a DeferredRequest,
+    ) -> BoxFuture<'a, Result<Option<ChatCompletionResponse>, XaiError>> {
+        Box::pin(async move {
+            let response = self.inner.deferred_completion(deferred).await?;
+            if let Some(response) = &response {
+                let usage = response.usage.as_ref();
+                let prompt = usage.and_then(|u| u.prompt_tokens).unwrap_or(0);
+                let completion = usage.and_then(|u| u.completion_tokens).unwrap_or(0);
+                record(&self.pool, &self.costs(), current(), "deferred_completion", &self.provider, &deferred.model, prompt.into(), completion.into()).await;
+            }
+            Ok(response)
+        })
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, XaiError>> {
+        self.inner.list_models()
+    }
+
+    fn embed<'a>(&'a self, model: &'a str, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, XaiError>> {
+        self.inner.embed(model, texts)
+    }
+
+    fn available(&self) -> bool {
+        self.inner.available()
+    }
+
+    fn uncached(&self) -> &dyn ChatProvider {
+        self
+    }
+}
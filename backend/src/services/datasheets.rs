@@ -0,0 +1,155 @@
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use reqwest::{header, Client, Url};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tracing::info;
+
+use crate::services::git;
+
+/// Leading bytes searched for the `%PDF-` header, which readers allow some
+/// junk in front of
+const HEADER_WINDOW: usize = 1024;
+
+static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .timeout(Duration::from_secs(60))
+        .connect_timeout(Duration::from_secs(10))
+        // Some manufacturers' sites turn away requests without one
+        .user_agent("kicad-watch")
+        .build()
+        .expect("Failed to create HTTP client")
+});
+
+/// Why a datasheet couldn't be served
+#[derive(Debug)]
+pub enum DatasheetError {
+    /// The datasheet URL isn't an http(s) URL
+    InvalidUrl(String),
+    /// The PDF is larger than the limit, in bytes
+    TooLarge { limit: u64 },
+    /// The server answered with something other than a PDF
+    NotPdf { content_type: String },
+    /// The server couldn't be reached, refused, or the download failed
+    Unavailable(anyhow::Error),
+}
+
+impl std::fmt::Display for DatasheetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatasheetError::InvalidUrl(url) => write!(f, "'{}' is not an http(s) URL", url),
+            DatasheetError::TooLarge { limit } => write!(f, "the datasheet is over the {} byte limit", limit),
+            DatasheetError::NotPdf { content_type } => {
+                write!(f, "the datasheet server sent {} rather than a PDF", content_type)
+            }
+            DatasheetError::Unavailable(e) => write!(f, "the datasheet could not be downloaded: {:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for DatasheetError {}
+
+impl From<std::io::Error> for DatasheetError {
+    fn from(e: std::io::Error) -> Self {
+        DatasheetError::Unavailable(e.into())
+    }
+}
+
+impl From<reqwest::Error> for DatasheetError {
+    fn from(e: reqwest::Error) -> Self {
+        DatasheetError::Unavailable(e.into())
+    }
+}
+
+/// Get the cache directory for downloaded datasheets
+fn get_datasheet_cache_path() -> PathBuf {
+    git::cache_dir().join("kicad-datasheet-cache")
+}
+
+/// Whether a response's Content-Type may be a PDF; generic binary types are
+/// let through, since plenty of CDNs serve PDFs as them, and the file's
+/// header decides
+fn may_be_pdf(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    matches!(
+        mime.as_str(),
+        "application/pdf" | "application/x-pdf" | "application/octet-stream" | "binary/octet-stream" | ""
+    )
+}
+
+/// The datasheet at `url` on disk, downloaded unless an earlier request left
+/// it there
+///
+/// Datasheets are cached by URL. A download is streamed to disk and stopped
+/// once it passes `max_bytes`, and only kept if the server calls it a PDF
+/// (or generic binary) and it starts with a PDF header.
+pub async fn fetch(url: &str, max_bytes: u64) -> Result<PathBuf, DatasheetError> {
+    let parsed = Url::parse(url.trim())
+        .ok()
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .ok_or_else(|| DatasheetError::InvalidUrl(url.to_string()))?;
+    let cache_dir = get_datasheet_cache_path();
+    let target = cache_dir.join(format!("{}.pdf", hex::encode(Sha256::digest(parsed.as_str().as_bytes()))));
+    if let Ok(metadata) = tokio::fs::metadata(&target).await {
+        // The limit may have been lowered since
+        if metadata.len() > max_bytes {
+            return Err(DatasheetError::TooLarge { limit: max_bytes });
+        }
+        return Ok(target);
+    }
+
+    let mut response = HTTP_CLIENT
+        .get(parsed.clone())
+        .header(header::ACCEPT, "application/pdf")
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(DatasheetError::Unavailable(anyhow!(
+            "{} answered {}",
+            parsed.host_str().unwrap_or_default(),
+            response.status()
+        )));
+    }
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    if !may_be_pdf(&content_type) {
+        return Err(DatasheetError::NotPdf { content_type });
+    }
+    if response.content_length().is_some_and(|length| length > max_bytes) {
+        return Err(DatasheetError::TooLarge { limit: max_bytes });
+    }
+
+    // Write to a temp file in the same directory, then rename into place so
+    // concurrent readers never see a partially written datasheet
+    tokio::fs::create_dir_all(&cache_dir).await?;
+    let tmp = tempfile::NamedTempFile::new_in(&cache_dir)?;
+    let mut file = tokio::fs::File::from_std(tmp.reopen()?);
+    let mut head = Vec::with_capacity(HEADER_WINDOW);
+    let mut written = 0u64;
+    while let Some(chunk) = response.chunk().await? {
+        written += chunk.len() as u64;
+        if written > max_bytes {
+            return Err(DatasheetError::TooLarge { limit: max_bytes });
+        }
+        let wanted = (HEADER_WINDOW - head.len()).min(chunk.len());
+        head.extend_from_slice(&chunk[..wanted]);
+        file.write_all(&chunk).await?;
+    }
+    if !head.windows(5).any(|w| w == b"%PDF-") {
+        return Err(DatasheetError::NotPdf {
+            content_type: if content_type.is_empty() { "an untyped file".to_string() } else { content_type },
+        });
+    }
+    file.flush().await?;
+    drop(file);
+    tmp.persist(&target).map_err(|e| DatasheetError::Unavailable(e.into()))?;
+
+    info!("Cached the datasheet at {} ({} bytes)", parsed, written);
+    Ok(target)
+}
@@ -0,0 +1,163 @@
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, Utc, Weekday};
+use kicad_db::{
+    claim_digest, due_digests, list_digest_subscribers, render_digest, repo_activity, store_distilled_json,
+    undistilled_digest_commits, Digest, PgPool, RenderedDigest,
+};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+use super::{
+    distill, git,
+    mail::{Email, Mailer},
+    schematic_cache::SchematicCache,
+};
+use crate::config::DigestConfig;
+use crate::shutdown;
+use crate::state::AppState;
+
+/// How often the job checks for digests due
+const TICK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+/// The end of the last week to have ended by `now`, weeks ending on
+/// `weekday` at `hour` UTC
+pub fn week_end(weekday: Weekday, hour: u32, now: DateTime<Utc>) -> DateTime<Utc> {
+    let days_back = (now.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+    let end = (now.date_naive() - Duration::days(days_back.into()))
+        .and_hms_opt(hour, 0, 0)
+        .expect("the digest hour is validated")
+        .and_utc();
+    if end > now {
+        end - Duration::weeks(1)
+    } else {
+        end
+    }
+}
+
+/// Email each repo's subscribers a digest of its week, once the week ends
+///
+/// Every 15 minutes, repos whose digest for the last week hasn't gone out
+/// (see `kicad_db::due_digests`) are claimed and sent; a week without
+/// schematic commits is claimed but sends nothing.
+pub fn spawn(app: AppState, mailer: Arc<dyn Mailer>) {
+    let config = &app.config().digest;
+    info!(
+        "Weekly digests enabled, sent by {} on {} at {:02}:00 UTC",
+        mailer.name(),
+        config.weekday,
+        config.hour
+    );
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if shutdown::requested() {
+                break;
+            }
+            let config = app.config();
+            let sent = send_due(&app.pool, &app.schematics, &config.digest, mailer.as_ref(), Utc::now());
+            match sent.await {
+                Ok(0) => {}
+                Ok(sent) => info!("Sent {} weekly digest email(s)", sent),
+                Err(e) => warn!("Failed to send weekly digests: {:#}", e),
+            }
+        }
+    });
+}
+
+/// Send the digests due for the last week to have ended by `now`; returns
+/// how many emails went out
+pub async fn send_due(
+    pool: &PgPool,
+    schematics: &Arc<SchematicCache>,
+    config: &DigestConfig,
+    mailer: &dyn Mailer,
+    now: DateTime<Utc>,
+) -> Result<usize> {
+    let until = week_end(config.weekday, config.hour, now);
+    let mut sent = 0;
+    for repo_url in due_digests(pool, until).await? {
+        if shutdown::requested() {
+            break;
+        }
+        // Claimed before sending, so a failed send skips the week rather
+        // than repeating it to the subscribers it already reached
+        if !claim_digest(pool, &repo_url, until).await? {
+            continue;
+        }
+        match send_repo_digest(pool, schematics, config, mailer, &repo_url, until).await {
+            Ok(count) => sent += count,
+            Err(e) => warn!("Failed to send the weekly digest of {}: {:#}", repo_url, e),
+        }
+    }
+    Ok(sent)
+}
+
+async fn send_repo_digest(
+    pool: &PgPool,
+    schematics: &Arc<SchematicCache>,
+    config: &DigestConfig,
+    mailer: &dyn Mailer,
+    repo_url: &str,
+    until: DateTime<Utc>,
+) -> Result<usize> {
+    let digest = build(pool, schematics, config, repo_url, until).await?;
+    if digest.activity.is_empty() {
+        debug!("No schematic commits in {} this week; no digest", repo_url);
+        return Ok(0);
+    }
+    let RenderedDigest { subject, html, text } = render_digest(&digest)?;
+    let mut sent = 0;
+    for subscriber in list_digest_subscribers(pool, repo_url).await? {
+        let email = Email {
+            to: subscriber.email,
+            subject: subject.clone(),
+            html: html.clone(),
+            text: text.clone(),
+        };
+        match mailer.send(&email).await {
+            Ok(()) => sent += 1,
+            Err(e) => warn!("Failed to email the digest of {} to {}: {:#}", digest.repo, email.to, e),
+        }
+    }
+    info!("Sent the weekly digest of {} to {} subscriber(s)", digest.repo, sent);
+    Ok(sent)
+}
+
+/// The digest of a repo's week ending `until`
+///
+/// The week's last commit and the one before it are distilled first if
+/// nothing has yet, so their components and ERC findings can be compared.
+pub async fn build(
+    pool: &PgPool,
+    schematics: &Arc<SchematicCache>,
+    config: &DigestConfig,
+    repo_url: &str,
+    until: DateTime<Utc>,
+) -> Result<Digest> {
+    let since = until - Duration::weeks(1);
+    let repo = git::repo_slug(repo_url).unwrap_or_else(|| repo_url.to_string());
+    for commit in undistilled_digest_commits(pool, repo_url, since, until).await? {
+        // Compared against an older distilled commit instead, if any
+        match distill::distill_repo_schematics(schematics, &repo, &commit).await {
+            Ok(distilled) => store_distilled_json(pool, repo_url, &commit, &distilled).await?,
+            Err(e) => warn!("Failed to distill {}@{} for its digest: {:#}", repo, commit, e),
+        }
+    }
+    let activity = repo_activity(pool, repo_url, since, until).await?;
+    // Commit links and the frontend's deep links only know GitHub
+    let github = repo_url.starts_with("https://github.com/");
+    Ok(Digest {
+        view_url: config
+            .app_url
+            .as_ref()
+            .filter(|_| github)
+            .map(|app| format!("{}/?github=https://github.com/{}", app, repo)),
+        commit_url_prefix: github.then(|| format!("https://github.com/{}/commit/", repo)),
+        repo,
+        since,
+        until,
+        activity,
+    })
+}
+
@@ -4,25 +4,10 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::sync::RwLock;
 use std::time::{Duration, Instant};
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info};
 
 use crate::types::{DigiKeyParameter, DigiKeyPartInfo};
 
-// DigiKey API Configuration
-static DIGIKEY_CLIENT_ID: Lazy<String> = Lazy::new(|| {
-    std::env::var("DIGIKEY_CLIENT_ID").unwrap_or_else(|_| {
-        warn!("DIGIKEY_CLIENT_ID not set - DigiKey integration will not work");
-        String::new()
-    })
-});
-
-static DIGIKEY_CLIENT_SECRET: Lazy<String> = Lazy::new(|| {
-    std::env::var("DIGIKEY_CLIENT_SECRET").unwrap_or_else(|_| {
-        warn!("DIGIKEY_CLIENT_SECRET not set - DigiKey integration will not work");
-        String::new()
-    })
-});
-
 // DigiKey API endpoints
 const DIGIKEY_AUTH_URL: &str = "https://api.digikey.com/v1/oauth2/token";
 const DIGIKEY_SEARCH_URL: &str = "https://api.digikey.com/products/v4/search/keyword";
@@ -40,6 +25,8 @@ static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
 
 #[derive(Debug, Clone)]
 struct TokenCache {
+    /// Client the token was issued to, so new credentials get a new token
+    client_id: String,
     access_token: String,
     expires_at: Instant,
 }
@@ -163,16 +150,18 @@ struct ParameterInfo {
     value_text: Option<String>,
 }
 
-pub struct DigiKeyClient;
+/// The DigiKey API with one app's credentials; see `PartsConfig::digikey`
+pub struct DigiKeyClient {
+    client_id: String,
+    client_secret: String,
+}
 
 impl DigiKeyClient {
-    pub fn new() -> Self {
-        Self
-    }
-
-    /// Check if DigiKey integration is configured
-    pub fn is_configured() -> bool {
-        !DIGIKEY_CLIENT_ID.is_empty() && !DIGIKEY_CLIENT_SECRET.is_empty()
+    pub fn new(client_id: &str, client_secret: &str) -> Self {
+        Self {
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+        }
     }
 
     /// Get a valid access token, refreshing if necessary
@@ -182,7 +171,7 @@ impl DigiKeyClient {
             let cache = TOKEN_CACHE.read().unwrap();
             if let Some(ref cached) = *cache {
                 // Use token if it has at least 60 seconds remaining
-                if cached.expires_at > Instant::now() + Duration::from_secs(60) {
+                if cached.client_id == self.client_id && cached.expires_at > Instant::now() + Duration::from_secs(60) {
                     debug!("Using cached DigiKey access token");
                     return Ok(cached.access_token.clone());
                 }
@@ -193,8 +182,8 @@ impl DigiKeyClient {
         info!("Refreshing DigiKey access token");
         
         let params = [
-            ("client_id", DIGIKEY_CLIENT_ID.as_str()),
-            ("client_secret", DIGIKEY_CLIENT_SECRET.as_str()),
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
             ("grant_type", "client_credentials"),
         ];
 
@@ -223,6 +212,7 @@ impl DigiKeyClient {
         {
             let mut cache = TOKEN_CACHE.write().unwrap();
             *cache = Some(TokenCache {
+                client_id: self.client_id.clone(),
                 access_token: token_response.access_token.clone(),
                 expires_at,
             });
@@ -236,10 +226,6 @@ impl DigiKeyClient {
     /// This uses the keyword search endpoint which is more flexible and returns
     /// exact manufacturer matches when searching by MPN
     pub async fn search_keyword(&self, query: &str) -> Result<Vec<DigiKeyPartInfo>> {
-        if !Self::is_configured() {
-            anyhow::bail!("DigiKey API not configured. Set DIGIKEY_CLIENT_ID and DIGIKEY_CLIENT_SECRET environment variables.");
-        }
-
         let access_token = self.get_access_token().await?;
 
         let request_body = KeywordSearchRequest {
@@ -253,7 +239,7 @@ impl DigiKeyClient {
         let response = HTTP_CLIENT
             .post(DIGIKEY_SEARCH_URL)
             .header("Authorization", format!("Bearer {}", access_token))
-            .header("X-DIGIKEY-Client-Id", DIGIKEY_CLIENT_ID.as_str())
+            .header("X-DIGIKEY-Client-Id", self.client_id.as_str())
             .header("X-DIGIKEY-Locale-Site", "US")
             .header("X-DIGIKEY-Locale-Language", "en")
             .header("X-DIGIKEY-Locale-Currency", "USD")
@@ -383,9 +369,3 @@ impl DigiKeyClient {
         }
     }
 }
-
-impl Default for DigiKeyClient {
-    fn default() -> Self {
-        Self::new()
-    }
-}
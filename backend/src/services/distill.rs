@@ -1,88 +1,72 @@
 use anyhow::{Context, Result};
+use kicad_db::schematic::{
+    self, diff_projects, Project, ProjectDiff, SchematicFormat, StandardLibraries, SymbolLibraries,
+};
+use kicad_db::utilities::get_project_path::get_project_path;
+use once_cell::sync::Lazy;
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::{Arc, RwLock};
 use tokio::process::Command;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-use crate::services::git;
+use crate::request_id;
+use crate::services::{git, schematic_cache::SchematicCache};
 use crate::types::SchematicFile;
 
+/// The schematic-distiller checkout set with [`configure`]
+static DISTILLER_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Standard KiCad symbol library directory set with [`configure`], read
+/// when the libraries are first needed
+static SYMBOL_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Use the configured distiller checkout (config `distiller_path`) and
+/// symbol libraries (`kicad_symbol_dir`); called at startup and on each
+/// reload, though the symbol libraries stay those first loaded
+pub fn configure(distiller_path: Option<PathBuf>, symbol_dir: Option<PathBuf>) {
+    *DISTILLER_PATH.write().unwrap() = distiller_path;
+    *SYMBOL_DIR.write().unwrap() = symbol_dir;
+}
+
 /// Get the path to the schematic-distiller directory.
 ///
-/// Tries multiple strategies in order:
-/// 1. CARGO_MANIFEST_DIR (set during cargo build/run)
-/// 2. DISTILLER_PATH environment variable
-/// 3. Relative to current executable
-/// 4. Relative to current working directory
-/// 5. Parent of current working directory (if running from backend/)
-fn get_distiller_path() -> PathBuf {
-    // Try CARGO_MANIFEST_DIR first (set during cargo build/run)
-    if let Ok(manifest_dir) = std::env::var("CARGO_MANIFEST_DIR") {
-        let path = PathBuf::from(manifest_dir)
-            .parent()
-            .map(|p| p.join("schematic-distiller"))
-            .unwrap_or_else(|| PathBuf::from("schematic-distiller"));
-        if path.exists() {
-            return path;
-        }
-    }
-
-    // Try DISTILLER_PATH env var (can be set explicitly)
-    if let Ok(distiller_path) = std::env::var("DISTILLER_PATH") {
-        let path = PathBuf::from(distiller_path);
-        if path.exists() {
-            return path;
-        }
+/// Tries in order:
+/// 1. `configured`, the config's `distiller_path`
+/// 2. `schematic-distiller/` in the project directory (see `get_project_path`)
+/// 3. Relative to current working directory
+pub fn distiller_dir(configured: Option<&Path>) -> PathBuf {
+    if let Some(path) = configured.filter(|path| path.exists()) {
+        return path.to_path_buf();
     }
 
-    // Try relative to current executable
-    if let Ok(exe_path) = std::env::current_exe() {
-        // Executable might be in target/debug or target/release
-        let path = exe_path
-            .parent() // binary location
-            .and_then(|p| p.parent()) // target
-            .and_then(|p| p.parent()) // backend
-            .and_then(|p| p.parent()) // grokicad
-            .map(|p| p.join("schematic-distiller"));
-        if let Some(ref path) = path {
+    match get_project_path() {
+        Ok(project) => {
+            let path = project.join("schematic-distiller");
             if path.exists() {
-                return path.clone();
-            }
-        }
-    }
-
-    // Try relative to current working directory
-    if let Ok(cwd) = std::env::current_dir() {
-        let cwd_path = cwd.join("schematic-distiller");
-        if cwd_path.exists() {
-            return cwd_path;
-        }
-
-        // Try parent of cwd (if running from backend/)
-        if let Some(parent) = cwd.parent() {
-            let parent_path = parent.join("schematic-distiller");
-            if parent_path.exists() {
-                return parent_path;
+                return path;
             }
         }
+        Err(e) => warn!("Cannot find the schematic-distiller: {}", e),
     }
 
     // Fallback - return the relative path and let it fail with a clear error
     PathBuf::from("schematic-distiller")
 }
 
-/// Get the path to the Python executable in the venv.
-fn get_python_path() -> PathBuf {
-    get_distiller_path()
+/// Get the path to the Python executable in the distiller's venv.
+pub fn get_python_path(distiller: &Path) -> PathBuf {
+    distiller
         .join(".venv")
         .join("bin")
         .join("python")
 }
 
-/// Get the path to the distill_demo.py script.
-fn get_distill_script_path() -> PathBuf {
-    get_distiller_path()
+/// Get the path to the distiller's distill_demo.py script.
+pub fn get_distill_script_path(distiller: &Path) -> PathBuf {
+    distiller
         .join("examples")
         .join("distill")
         .join("distill_demo.py")
@@ -90,8 +74,9 @@ fn get_distill_script_path() -> PathBuf {
 
 /// Run the distill_demo.py script on a directory and return the JSON output.
 async fn run_distill_script(directory: &Path) -> Result<Value> {
-    let python_path = get_python_path();
-    let script_path = get_distill_script_path();
+    let distiller = distiller_dir(DISTILLER_PATH.read().unwrap().as_deref());
+    let python_path = get_python_path(&distiller);
+    let script_path = get_distill_script_path(&distiller);
 
     info!(
         "Running distill script: {:?} {:?} --dir {:?}",
@@ -171,20 +156,197 @@ async fn write_schematic_files_to_temp(
     Ok(temp_dir)
 }
 
+/// Standard KiCad symbol libraries, for symbols neither the sheets nor the repo define
+///
+/// Read from the configured directory, else from the usual install location
+/// if there is one. Parsed libraries are cached next to the blob cache.
+static STANDARD_LIBRARIES: Lazy<Option<Arc<StandardLibraries>>> = Lazy::new(|| {
+    let dir = SYMBOL_DIR.read().unwrap().clone().or_else(|| {
+        ["/usr/share/kicad/symbols", "/usr/local/share/kicad/symbols"]
+            .into_iter()
+            .map(PathBuf::from)
+            .find(|p| p.is_dir())
+    })?;
+    info!("Using standard KiCad symbol libraries from {:?}", dir);
+    let cache_dir = std::env::temp_dir().join("kicad-symbol-cache");
+    Some(Arc::new(StandardLibraries::new(dir, Some(cache_dir))))
+});
+
+/// The symbol libraries among `sources`, then the standard ones
+pub fn symbol_libraries(sources: &BTreeMap<String, String>) -> SymbolLibraries {
+    let libraries = SymbolLibraries::from_sources(sources);
+    match STANDARD_LIBRARIES.as_ref() {
+        Some(standard) => libraries.with_standard(standard.clone()),
+        None => libraries,
+    }
+}
+
+/// Parse every schematic project in the repo at a commit.
+///
+/// Each project is rooted at the schematic next to its .kicad_pro or legacy
+/// .pro (or at the sheets nothing else references) and spans all of its
+/// sub-sheets, in either schematic format. Symbols the sheets don't embed are
+/// resolved from the repo's symbol libraries, then from legacy cache
+/// libraries and the standard libraries. Sheets already in `cache` aren't
+/// parsed again.
+pub async fn load_projects(cache: &Arc<SchematicCache>, repo_slug: &str, commit_hash: &str) -> Result<Vec<Project>> {
+    let files = git::get_project_files(repo_slug, commit_hash)
+        .await
+        .context("Failed to fetch schematic files from repo")?;
+    parse_projects(cache, repo_slug, commit_hash, files).await
+}
+
+async fn parse_projects(
+    cache: &Arc<SchematicCache>,
+    repo_slug: &str,
+    commit_hash: &str,
+    files: Vec<SchematicFile>,
+) -> Result<Vec<Project>> {
+    let sources: BTreeMap<String, String> =
+        files.into_iter().map(|f| (f.path, f.content)).collect();
+    let (cache, repo_slug, commit_hash) = (cache.clone(), repo_slug.to_string(), commit_hash.to_string());
+    let projects = request_id::spawn_blocking(move || {
+        let libraries = symbol_libraries(&sources);
+        let parse = |path: &str, text: &str| cache.parse(&repo_slug, &commit_hash, path, text);
+        schematic::load_projects_with_parser(&sources, &libraries, &parse)
+    })
+    .await?
+    .context("Failed to parse schematic project")?;
+    for project in &projects {
+        if !project.missing_files.is_empty() {
+            warn!(
+                "Project {} references missing sheet(s): {:?}",
+                project.root_file, project.missing_files
+            );
+        }
+        if !project.unresolved_symbols.is_empty() {
+            warn!(
+                "Project {} places symbol(s) with no definition: {:?}",
+                project.root_file, project.unresolved_symbols
+            );
+        }
+    }
+    Ok(projects)
+}
+
+/// Hierarchy-wide changes a commit made to each project, keyed by root schematic
+///
+/// Projects are matched to the parent commit by root file; a project with no
+/// counterpart is diffed against an empty one. Unchanged projects are omitted.
+pub async fn project_changes(
+    cache: &Arc<SchematicCache>,
+    repo_slug: &str,
+    commit_hash: &str,
+) -> Result<Vec<(String, ProjectDiff)>> {
+    let (previous, current) = projects_around(cache, repo_slug, commit_hash).await?;
+    Ok(diff_project_sets(&previous, &current))
+}
+
+/// The projects at a commit's parent (none for a root commit) and at the
+/// commit itself, for callers that need the parsed projects as well as
+/// their [`diff_project_sets`]
+pub async fn projects_around(
+    cache: &Arc<SchematicCache>,
+    repo_slug: &str,
+    commit_hash: &str,
+) -> Result<(Vec<Project>, Vec<Project>)> {
+    let parent = git::get_parent_commit(repo_slug, commit_hash).await?;
+    let current = load_projects(cache, repo_slug, commit_hash).await?;
+    let previous = match parent {
+        Some(parent) => load_projects(cache, repo_slug, &parent).await?,
+        None => Vec::new(),
+    };
+    Ok((previous, current))
+}
+
+/// Hierarchy-wide changes to each project from `base` (None for an empty
+/// repo) to `head`, matched and omitted as by [`project_changes`]
+pub async fn changes_between(
+    cache: &Arc<SchematicCache>,
+    repo_slug: &str,
+    base: Option<&str>,
+    head: &str,
+) -> Result<Vec<(String, ProjectDiff)>> {
+    let current = load_projects(cache, repo_slug, head).await?;
+    let previous = match base {
+        Some(base) => load_projects(cache, repo_slug, base).await?,
+        None => Vec::new(),
+    };
+    Ok(diff_project_sets(&previous, &current))
+}
+
+/// The changes from `previous` to `current` of each project, matched by
+/// root file and omitted as by [`project_changes`]
+pub fn diff_project_sets(previous: &[Project], current: &[Project]) -> Vec<(String, ProjectDiff)> {
+    let empty = Project::default();
+    let mut changes = Vec::new();
+    for project in current {
+        let before = previous
+            .iter()
+            .find(|p| p.root_file == project.root_file)
+            .unwrap_or(&empty);
+        let diff = diff_projects(before, project);
+        if !diff.is_empty() {
+            changes.push((project.root_file.clone(), diff));
+        }
+    }
+    for project in previous.iter().filter(|p| !current.iter().any(|c| c.root_file == p.root_file)) {
+        changes.push((project.root_file.clone(), diff_projects(project, &empty)));
+    }
+    changes
+}
+
+/// The component and net changes of `projects` for a prompt, each project
+/// under its root file when there are several
+pub fn describe_changes(projects: &[(String, ProjectDiff)]) -> String {
+    match projects {
+        [] => "No component or net changes.".to_string(),
+        [(_, diff)] => diff.describe(),
+        _ => projects
+            .iter()
+            .map(|(root, diff)| format!("## {}\n{}", root, diff.describe()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// Merge the distilled JSON of several projects into one document
+pub fn merge_distilled(projects: &[Project]) -> Value {
+    let mut merged = serde_json::json!({ "components": {}, "nets": {}, "proximities": [] });
+    for project in projects {
+        let distilled = project.to_distilled();
+        for key in ["components", "nets"] {
+            if let (Some(into), Some(Value::Object(from))) =
+                (merged[key].as_object_mut(), distilled.get(key).cloned())
+            {
+                into.extend(from);
+            }
+        }
+        if let (Some(into), Some(Value::Array(from))) = (
+            merged["proximities"].as_array_mut(),
+            distilled.get("proximities").cloned(),
+        ) {
+            into.extend(from);
+        }
+    }
+    merged
+}
+
 /// Distill all schematic files from a repo at a specific commit.
 ///
-/// Fetches schematic files from the repository, writes them to a temp directory,
-/// runs the Python distill script, and returns the JSON output.
-pub async fn distill_repo_schematics(repo_slug: &str, commit_hash: &str) -> Result<Value> {
+/// Projects are parsed natively so nets and references follow the sheet
+/// hierarchy. If that fails (e.g. a file the parser can't read), the files are
+/// written to a temp directory and the Python distill script is run instead.
+pub async fn distill_repo_schematics(cache: &Arc<SchematicCache>, repo_slug: &str, commit_hash: &str) -> Result<Value> {
     info!("Distilling schematics for {}/{}", repo_slug, commit_hash);
 
-    let files = git::get_schematic_files(repo_slug, commit_hash)
+    let files = git::get_project_files(repo_slug, commit_hash)
         .await
         .context("Failed to fetch schematic files from repo")?;
 
-    if files.is_empty() {
+    if !files.iter().any(|f| SchematicFormat::detect(&f.content).is_some()) {
         anyhow::bail!(
-            "No .kicad_sch files found in repo {} at commit {}",
+            "No KiCad schematics found in repo {} at commit {}",
             repo_slug,
             commit_hash
         );
@@ -192,6 +354,21 @@ pub async fn distill_repo_schematics(repo_slug: &str, commit_hash: &str) -> Resu
 
     info!("Found {} schematic file(s) to distill", files.len());
 
+    match parse_projects(cache, repo_slug, commit_hash, files.clone()).await {
+        Ok(projects) if !projects.is_empty() => {
+            info!(
+                "Distillation complete for {}/{}: {} project(s), {} sheet instance(s)",
+                repo_slug,
+                commit_hash,
+                projects.len(),
+                projects.iter().map(|p| p.instances.len()).sum::<usize>()
+            );
+            return Ok(merge_distilled(&projects));
+        }
+        Ok(_) => warn!("No root schematic found, falling back to the distill script"),
+        Err(e) => warn!("Native distillation failed, falling back to the distill script: {:#}", e),
+    }
+
     let temp_dir = write_schematic_files_to_temp(&files, repo_slug, commit_hash)
         .await
         .context("Failed to write schematic files to temp directory")?;
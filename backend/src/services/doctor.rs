@@ -0,0 +1,303 @@
+use kicad_db::migration_status;
+use sqlx::postgres::PgConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command;
+
+use super::{distill, health};
+use crate::config::Config;
+use crate::redact::redact_url_credentials;
+
+/// How long the database gets to accept a connection
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the distill script gets to print its usage
+const DISTILLER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How a check came out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    /// Works, but probably not as intended
+    Warn,
+    Fail,
+    /// Not run, because a check it depends on failed
+    Skip,
+}
+
+/// One line of the report
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, outcome: Outcome, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// `kicad-backend doctor`: load the environment and configuration the way
+/// the server does, then check everything it needs to serve requests
+pub async fn run() -> Vec<Check> {
+    if let Err(e) = kicad_db::init(&[]) {
+        return vec![Check::new("environment", Outcome::Fail, e.to_string())];
+    }
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => return vec![Check::new("config", Outcome::Fail, format!("{:#}", e))],
+    };
+    let mut checks = vec![Check::new("config", Outcome::Pass, config.summary())];
+    checks.extend(check(&config).await);
+    checks
+}
+
+/// Check what `config` points at: the database and its migrations, git, the
+/// clone cache, the Python distiller, the XAI key and the webhook secrets
+pub async fn check(config: &Config) -> Vec<Check> {
+    let mut checks = check_database(config).await;
+    checks.push(check_git());
+    checks.extend(check_cache_dir(&config.git_cache_dir, config.health.min_free_disk_mb));
+    let distiller = distill::distiller_dir(config.distiller_path.as_deref());
+    checks.push(check_distiller(&distill::get_python_path(&distiller), &distill::get_distill_script_path(&distiller)).await);
+    checks.push(check_xai(config).await);
+    checks.push(check_webhooks(config));
+    checks
+}
+
+/// Open the SQLite schematic store, which the server migrates itself
+async fn check_sqlite(config: &Config, url: &str) -> Vec<Check> {
+    let ping = async {
+        let store = kicad_db::connect_store(&config.database_url, &config.pool.settings()).await?;
+        store.ping().await
+    };
+    let database = match tokio::time::timeout(DATABASE_TIMEOUT, ping).await {
+        Ok(Ok(ping)) => Check::new("database", Outcome::Pass, format!("opened {} ({})", url, ping)),
+        Ok(Err(e)) => Check::new("database", Outcome::Fail, format!("cannot open {}: {}", url, e)),
+        Err(_) => Check::new(
+            "database",
+            Outcome::Fail,
+            format!("{} didn't answer within {}s", url, DATABASE_TIMEOUT.as_secs()),
+        ),
+    };
+    let migrations = Check::new("migrations", Outcome::Skip, "the SQLite store applies its own at startup");
+    vec![database, migrations]
+}
+
+/// Connect, then compare the applied migrations with this build's
+async fn check_database(config: &Config) -> Vec<Check> {
+    let url = redact_url_credentials(&config.database_url).unwrap_or_else(|| config.database_url.clone());
+    if config.uses_sqlite() {
+        return check_sqlite(config, &url).await;
+    }
+    // A connection of its own rather than a pool, whose errors only say it
+    // timed out
+    let connect = async {
+        let options: PgConnectOptions = config.database_url.parse()?;
+        options.connect().await
+    };
+    let mut conn = match tokio::time::timeout(DATABASE_TIMEOUT, connect).await {
+        Ok(Ok(conn)) => conn,
+        Ok(Err(e)) => {
+            return vec![
+                Check::new("database", Outcome::Fail, format!("cannot connect to {}: {}", url, e)),
+                Check::new("migrations", Outcome::Skip, "no database"),
+            ];
+        }
+        Err(_) => {
+            return vec![
+                Check::new(
+                    "database",
+                    Outcome::Fail,
+                    format!("{} didn't answer within {}s", url, DATABASE_TIMEOUT.as_secs()),
+                ),
+                Check::new("migrations", Outcome::Skip, "no database"),
+            ];
+        }
+    };
+    let database = Check::new("database", Outcome::Pass, format!("connected to {}", url));
+
+    let migrations = match migration_status(&mut conn).await {
+        Err(e) => Check::new("migrations", Outcome::Fail, format!("cannot read them: {}", e)),
+        Ok(status) if status.is_broken() => {
+            let mut detail = String::new();
+            if !status.unknown.is_empty() {
+                let _ = write!(detail, "{:?} applied by a newer build; ", status.unknown);
+            }
+            if !status.mismatched.is_empty() {
+                let _ = write!(detail, "{:?} failed or changed since they were applied; ", status.mismatched);
+            }
+            detail.push_str("the server will refuse to start");
+            Check::new("migrations", Outcome::Fail, detail)
+        }
+        Ok(status) if !status.pending.is_empty() => {
+            let versions: Vec<String> = status
+                .pending
+                .iter()
+                .map(|(version, description)| format!("{} {}", version, description))
+                .collect();
+            Check::new(
+                "migrations",
+                Outcome::Warn,
+                format!(
+                    "{} applied, {} pending (applied at startup): {}",
+                    status.applied,
+                    status.pending.len(),
+                    versions.join(", ")
+                ),
+            )
+        }
+        Ok(status) => Check::new("migrations", Outcome::Pass, format!("all {} applied", status.applied)),
+    };
+    let _ = conn.close().await;
+    vec![database, migrations]
+}
+
+/// Git runs in-process through libgit2, so what matters is that it was
+/// built with HTTPS support, which cloning from GitHub needs
+fn check_git() -> Check {
+    let version = git2::Version::get();
+    let (major, minor, patch) = version.libgit2_version();
+    let detail = format!(
+        "libgit2 {}.{}.{} (https {}, ssh {})",
+        major,
+        minor,
+        patch,
+        if version.https() { "yes" } else { "no" },
+        if version.ssh() { "yes" } else { "no" }
+    );
+    if version.https() {
+        Check::new("git", Outcome::Pass, detail)
+    } else {
+        Check::new("git", Outcome::Fail, format!("{}: cannot clone over HTTPS", detail))
+    }
+}
+
+/// The clone cache can be created and written to, with room to spare
+fn check_cache_dir(dir: &Path, min_free_mb: u64) -> Vec<Check> {
+    let probe = dir.join(format!(".doctor-{}", std::process::id()));
+    let writable = std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&probe, b"ok"))
+        .and_then(|_| std::fs::remove_file(&probe));
+    if let Err(e) = writable {
+        return vec![
+            Check::new("git_cache", Outcome::Fail, format!("cannot write to {}: {}", dir.display(), e)),
+            Check::new("git_cache_disk", Outcome::Skip, "no git cache"),
+        ];
+    }
+    let cache = Check::new("git_cache", Outcome::Pass, format!("{} is writable", dir.display()));
+    let disk = health::check_disk_at(dir, min_free_mb);
+    let outcome = if disk.ok { Outcome::Pass } else { Outcome::Fail };
+    let disk = Check::new("git_cache_disk", outcome, disk.detail);
+    vec![cache, disk]
+}
+
+/// The schematic-distiller's venv interpreter and script exist, and the
+/// script starts (its imports load) when asked for its usage
+async fn check_distiller(python: &Path, script: &Path) -> Check {
+    if !python.exists() {
+        return Check::new(
+            "distiller",
+            Outcome::Fail,
+            format!("no Python venv at {}; run setup_venv.sh", python.display()),
+        );
+    }
+    if !script.exists() {
+        return Check::new("distiller", Outcome::Fail, format!("no distill script at {}", script.display()));
+    }
+    let run = Command::new(python).arg(script).arg("--help").kill_on_drop(true).output();
+    match tokio::time::timeout(DISTILLER_TIMEOUT, run).await {
+        Ok(Ok(output)) if output.status.success() => {
+            Check::new("distiller", Outcome::Pass, format!("{} runs", script.display()))
+        }
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let last = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("no output");
+            Check::new(
+                "distiller",
+                Outcome::Fail,
+                format!("{} exited with {}: {}", script.display(), output.status, last.trim()),
+            )
+        }
+        Ok(Err(e)) => Check::new("distiller", Outcome::Fail, format!("cannot run {}: {}", python.display(), e)),
+        Err(_) => Check::new(
+            "distiller",
+            Outcome::Fail,
+            format!("{} didn't finish within {}s", script.display(), DISTILLER_TIMEOUT.as_secs()),
+        ),
+    }
+}
+
+/// The key is accepted, by listing models
+async fn check_xai(config: &Config) -> Check {
+    if config.xai.api_key.trim().is_empty() {
+        return Check::new("xai", Outcome::Fail, "XAI_API_KEY is not set");
+    }
+    let xai = health::probe_xai(config).await;
+    let outcome = if xai.ok { Outcome::Pass } else { Outcome::Fail };
+    Check::new("xai", outcome, xai.detail)
+}
+
+/// Which providers' webhooks are verified with a shared secret
+fn check_webhooks(config: &Config) -> Check {
+    let providers = [
+        ("github", &config.webhooks.github),
+        ("gitlab", &config.webhooks.gitlab),
+        ("bitbucket", &config.webhooks.bitbucket),
+    ];
+    let (set, unset): (Vec<_>, Vec<_>) = providers.iter().partition(|(_, secret)| secret.is_some());
+    let names = |providers: Vec<&(&str, &Option<String>)>| {
+        providers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+    };
+    match (set.is_empty(), unset.is_empty()) {
+        (true, _) => Check::new(
+            "webhooks",
+            Outcome::Warn,
+            "no webhook secrets set (GITHUB_WEBHOOK_SECRET, ...); deliveries need a hook-scoped API key",
+        ),
+        (false, true) => Check::new("webhooks", Outcome::Pass, format!("secrets for {}", names(set))),
+        (false, false) => Check::new(
+            "webhooks",
+            Outcome::Pass,
+            format!("secrets for {}; {} deliveries need a hook-scoped API key", names(set), names(unset)),
+        ),
+    }
+}
+
+/// Whether no check failed
+pub fn passed(checks: &[Check]) -> bool {
+    checks.iter().all(|check| check.outcome != Outcome::Fail)
+}
+
+/// The report printed by `kicad-backend doctor`, a line per check and a tally
+pub fn report(checks: &[Check]) -> String {
+    let width = checks.iter().map(|check| check.name.len()).max().unwrap_or(0);
+    let mut report = String::new();
+    for check in checks {
+        let outcome = match check.outcome {
+            Outcome::Pass => "PASS",
+            Outcome::Warn => "WARN",
+            Outcome::Fail => "FAIL",
+            Outcome::Skip => "SKIP",
+        };
+        let _ = writeln!(report, "{}  {:width$}  {}", outcome, check.name, check.detail, width = width);
+    }
+    let count = |outcome| checks.iter().filter(|check| check.outcome == outcome).count();
+    let _ = writeln!(
+        report,
+        "\n{} passed, {} warnings, {} failed, {} skipped",
+        count(Outcome::Pass),
+        count(Outcome::Warn),
+        count(Outcome::Fail),
+        count(Outcome::Skip)
+    );
+    report
+}
@@ -0,0 +1,223 @@
+use anyhow::{Context, Result};
+use kicad_db::{
+    get_drift_baseline, store_drift_baseline, store_drift_run,
+    utilities::text_similarity::{cosine_similarity, jaccard_similarity},
+    prompts::COMMIT_SUMMARY,
+    PgPool, PromptLibrary,
+};
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use super::{git, notify, priority, status, summary};
+use super::llm::ChatProvider;
+use crate::config::{Config, ModelConfig};
+use crate::state::AppState;
+use crate::types::WorkClass;
+
+/// Result for one evaluation commit
+#[derive(Debug, Serialize)]
+pub struct DriftResult {
+    pub repo: String,
+    pub commit: String,
+    /// None when this run established the baseline
+    pub jaccard: Option<f64>,
+    pub cosine: Option<f64>,
+    pub drifted: bool,
+    pub error: Option<String>,
+}
+
+/// Outcome of one drift evaluation run
+#[derive(Debug, Serialize)]
+pub struct DriftReport {
+    pub threshold: f64,
+    pub mean_similarity: Option<f64>,
+    pub min_similarity: Option<f64>,
+    pub drifted: bool,
+    pub results: Vec<DriftResult>,
+}
+
+/// Start the periodic drift evaluation, if an evaluation set is configured
+///
+/// The evaluation set and threshold are read again for each run; the
+/// interval is fixed at startup.
+pub fn spawn(state: AppState) {
+    let drift = state.config().drift.clone();
+    if drift.eval_commits.is_empty() {
+        info!("No drift evaluation commits configured - drift report disabled");
+        return;
+    }
+
+    let every = Duration::from_secs(drift.interval_hours * 3600);
+    info!("Drift report enabled for {} commit(s), every {:?}", drift.eval_commits.len(), every);
+
+    tokio::spawn(priority::scope(WorkClass::Enrichment, async move {
+        let mut interval = tokio::time::interval(every);
+        // The first tick fires immediately; skip it so restarts don't trigger a run
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if !state.chat.available() {
+                info!("XAI is unavailable; skipping this drift report");
+                continue;
+            }
+            let (prompts, config) = (state.prompts(), state.config());
+            let report = run_drift_report(
+                &state.pool,
+                // Drift is measured on fresh outputs, never cached ones
+                state.chat.uncached(),
+                &prompts,
+                &config,
+            );
+            if let Err(e) = report.await {
+                error!("Drift report failed: {:#}", e);
+                status::record_error(None, None, None, &format!("Drift report failed: {:#}", e));
+            }
+        }
+    }));
+}
+
+/// Re-run the evaluation set, compare against stored baselines, record the
+/// run and alert if any output drifted past the threshold.
+pub async fn run_drift_report(
+    pool: &PgPool,
+    chat: &dyn ChatProvider,
+    prompts: &PromptLibrary,
+    config: &Config,
+) -> Result<DriftReport> {
+    let threshold = config.drift.similarity_threshold;
+    let mut results = Vec::new();
+
+    for (repo, commit) in config.drift.commits() {
+        let _job = status::track_job("drift", repo, Some(commit));
+        results.push(evaluate_commit(pool, chat, prompts, &config.models, repo, commit, threshold).await);
+    }
+
+    // Score each compared output by the lower of the two metrics
+    let scores: Vec<f64> = results
+        .iter()
+        .filter_map(|r| Some(r.jaccard?.min(r.cosine?)))
+        .collect();
+    let mean_similarity =
+        (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64);
+    let min_similarity = scores.iter().copied().reduce(f64::min);
+    let drifted = results.iter().any(|r| r.drifted);
+
+    let report = DriftReport {
+        threshold,
+        mean_similarity,
+        min_similarity,
+        drifted,
+        results,
+    };
+
+    store_drift_run(
+        pool,
+        mean_similarity.map(|v| v as f32),
+        min_similarity.map(|v| v as f32),
+        drifted,
+        &serde_json::to_value(&report)?,
+    )
+    .await
+    .context("Failed to store drift run")?;
+
+    info!(
+        "Drift report: mean={:?} min={:?} drifted={}",
+        mean_similarity, min_similarity, drifted
+    );
+
+    if drifted {
+        notify::send_alert(&format_alert(&report)).await;
+    }
+
+    Ok(report)
+}
+
+async fn evaluate_commit(
+    pool: &PgPool,
+    chat: &dyn ChatProvider,
+    prompts: &PromptLibrary,
+    models: &ModelConfig,
+    repo: &str,
+    commit: &str,
+    threshold: f64,
+) -> DriftResult {
+    let mut result = DriftResult {
+        repo: repo.to_string(),
+        commit: commit.to_string(),
+        jaccard: None,
+        cosine: None,
+        drifted: false,
+        error: None,
+    };
+
+    // Baselines are generated with fixed settings so runs stay comparable
+    let model = &models.summary;
+    let context_window = models.context_window(model);
+    let output = match summary::generate_commit_summary(
+        chat,
+        prompts,
+        COMMIT_SUMMARY,
+        model,
+        context_window,
+        repo,
+        commit,
+        None,
+        None,
+        None,
+        &[],
+        &[],
+    )
+    .await
+    {
+        Ok(s) => s.summary,
+        Err(e) => {
+            warn!("Drift evaluation failed for {}@{}: {}", repo, commit, e);
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+
+    let repo_url = git::repo_url(repo);
+    match get_drift_baseline(pool, &repo_url, commit).await {
+        Ok(Some(baseline)) => {
+            let jaccard = jaccard_similarity(&baseline, &output);
+            let cosine = cosine_similarity(&baseline, &output);
+            result.jaccard = Some(jaccard);
+            result.cosine = Some(cosine);
+            result.drifted = jaccard.min(cosine) < threshold;
+        }
+        Ok(None) => {
+            info!("Storing initial drift baseline for {}@{}", repo, commit);
+            if let Err(e) = store_drift_baseline(pool, &repo_url, commit, &output).await {
+                result.error = Some(format!("Failed to store baseline: {}", e));
+            }
+        }
+        Err(e) => {
+            result.error = Some(format!("Failed to load baseline: {}", e));
+        }
+    }
+
+    result
+}
+
+fn format_alert(report: &DriftReport) -> String {
+    let mut lines = vec![format!(
+        ":warning: Model/prompt drift detected (threshold {:.2}, mean similarity {})",
+        report.threshold,
+        report
+            .mean_similarity
+            .map(|v| format!("{:.2}", v))
+            .unwrap_or_else(|| "n/a".to_string())
+    )];
+    for r in report.results.iter().filter(|r| r.drifted) {
+        lines.push(format!(
+            "- {}@{}: jaccard {:.2}, cosine {:.2}",
+            r.repo,
+            &r.commit[..8.min(r.commit.len())],
+            r.jaccard.unwrap_or_default(),
+            r.cosine.unwrap_or_default()
+        ));
+    }
+    lines.join("\n")
+}
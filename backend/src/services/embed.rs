@@ -0,0 +1,108 @@
+use kicad_db::{get_repo_visibility, latest_public_summary, PgPool, SchematicOverview};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a repo's latest public summary is served from memory
+pub const CACHE_TTL: Duration = Duration::from_secs(300);
+/// Longest blurb shown on a badge, in characters
+const BADGE_MAX_CHARS: usize = 60;
+
+static SUMMARY_CACHE: Lazy<Mutex<HashMap<String, (Instant, RepoEmbed)>>> =
+    Lazy::new(Default::default);
+
+/// What the public endpoints may show for a repo
+#[derive(Debug, Clone)]
+pub struct RepoEmbed {
+    /// Whether the repo's summaries are public at all
+    pub public: bool,
+    /// Newest summary whose effective visibility is public
+    pub latest: Option<SchematicOverview>,
+}
+
+/// Public embed state for a repo, cached for `CACHE_TTL`
+///
+/// Misses are cached too, so an embed pointing at a private or unsummarized
+/// repo doesn't hit the database on every page view.
+pub async fn repo_embed(pool: &PgPool, repo_url: &str) -> Result<RepoEmbed, sqlx::Error> {
+    if let Some((at, cached)) = SUMMARY_CACHE.lock().unwrap().get(repo_url) {
+        if at.elapsed() < CACHE_TTL {
+            return Ok(cached.clone());
+        }
+    }
+
+    let public = get_repo_visibility(pool, repo_url).await?.is_public();
+    let latest = if public {
+        latest_public_summary(pool, repo_url).await?
+    } else {
+        None
+    };
+    let embed = RepoEmbed { public, latest };
+    SUMMARY_CACHE
+        .lock()
+        .unwrap()
+        .insert(repo_url.to_string(), (Instant::now(), embed.clone()));
+    Ok(embed)
+}
+
+/// Drop a repo's cached summary, e.g. after its visibility changed
+pub fn invalidate(repo_url: &str) {
+    SUMMARY_CACHE.lock().unwrap().remove(repo_url);
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c if c.is_control() => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Shorten to `BADGE_MAX_CHARS` on a character boundary, adding an ellipsis
+fn truncate(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= BADGE_MAX_CHARS {
+        return text;
+    }
+    let mut short: String = text.chars().take(BADGE_MAX_CHARS - 1).collect();
+    short.truncate(short.trim_end().len());
+    short.push('…');
+    short
+}
+
+/// Rough rendered width of Verdana 11px text, as shields.io badges use
+fn text_width(text: &str) -> usize {
+    text.chars().count() * 7 + 10
+}
+
+/// A flat shields-style badge: grey label on the left, coloured message on the right
+pub fn render_badge(label: &str, message: &str, color: &str) -> String {
+    let message = truncate(message);
+    let (label_width, message_width) = (text_width(label), text_width(&message));
+    let width = label_width + message_width;
+    let (label, message) = (escape_xml(label), escape_xml(&message));
+    let label_x = label_width * 5;
+    let message_x = label_width * 10 + message_width * 5;
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">
+<title>{label}: {message}</title>
+<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+<g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/><rect width="{width}" height="20" fill="url(#s)"/></g>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="110">
+<text x="{label_x}" y="150" fill="#010101" fill-opacity=".3" transform="scale(.1)">{label}</text><text x="{label_x}" y="140" transform="scale(.1)">{label}</text>
+<text x="{message_x}" y="150" fill="#010101" fill-opacity=".3" transform="scale(.1)">{message}</text><text x="{message_x}" y="140" transform="scale(.1)">{message}</text>
+</g>
+</svg>"##
+    )
+}
@@ -0,0 +1,125 @@
+use anyhow::Result;
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::config::PartsConfig;
+use crate::services::digikey::DigiKeyClient;
+use kicad_db::{PartMetadata, PgPool};
+
+/// Supplier, DigiKey credentials and cache lifetime set with [`configure`]
+static SETTINGS: Lazy<RwLock<PartsConfig>> = Lazy::new(Default::default);
+
+/// Use the configured supplier; called at startup and on each reload
+pub fn configure(parts: &PartsConfig) {
+    *SETTINGS.write().unwrap() = parts.clone();
+}
+
+/// A supplier API that can describe a part by its manufacturer part number
+///
+/// Implementations only fetch; caching in `part_metadata` is handled by
+/// [`lookup`], so a mock supplier is all a test needs.
+pub trait SupplierApi: Send + Sync {
+    /// Short identifier stored alongside cached rows
+    fn name(&self) -> &'static str;
+
+    /// Fetch metadata for an exact MPN, or `None` if the supplier doesn't know it
+    fn fetch<'a>(&'a self, mpn: &'a str) -> BoxFuture<'a, Result<Option<PartMetadata>>>;
+}
+
+impl SupplierApi for DigiKeyClient {
+    fn name(&self) -> &'static str {
+        "digikey"
+    }
+
+    fn fetch<'a>(&'a self, mpn: &'a str) -> BoxFuture<'a, Result<Option<PartMetadata>>> {
+        Box::pin(async move {
+            let parts = self.search_keyword(mpn).await?;
+            // Only trust an exact MPN match; keyword hits for other parts would poison the cache
+            let Some(part) = parts.into_iter().find(|p| {
+                p.manufacturer_part_number
+                    .as_deref()
+                    .is_some_and(|m| m.eq_ignore_ascii_case(mpn))
+            }) else {
+                return Ok(None);
+            };
+
+            Ok(Some(PartMetadata {
+                mpn: mpn.to_string(),
+                supplier: self.name().to_string(),
+                manufacturer: part.manufacturer,
+                description: part.description,
+                datasheet_url: part.datasheet_url,
+                product_url: part.product_url,
+                lifecycle_status: part.lifecycle_status,
+                is_obsolete: part.is_obsolete,
+                unit_price: part.unit_price,
+                quantity_available: part.quantity_available,
+                fetched_at: Utc::now(),
+            }))
+        })
+    }
+}
+
+/// The configured supplier, if it has the credentials it needs
+pub fn configured_supplier() -> Option<Box<dyn SupplierApi>> {
+    let parts = SETTINGS.read().unwrap();
+    match parts.supplier.as_str() {
+        "digikey" => parts.digikey().map(|client| Box::new(client) as Box<dyn SupplierApi>),
+        _ => None,
+    }
+}
+
+/// Look up metadata for an MPN: fresh cache hits are returned directly,
+/// otherwise the supplier is queried and the result cached. If the supplier
+/// is unavailable or fails, a stale cached entry is still better than nothing.
+pub async fn lookup(
+    pool: &PgPool,
+    supplier: Option<&dyn SupplierApi>,
+    mpn: &str,
+) -> Result<Option<PartMetadata>> {
+    let cached = kicad_db::get_part_metadata(pool, mpn).await?;
+    let ttl_hours = SETTINGS.read().unwrap().metadata_ttl_hours;
+    let max_age = i64::try_from(ttl_hours)
+        .ok()
+        .and_then(chrono::Duration::try_hours)
+        .unwrap_or(chrono::Duration::MAX);
+    if let Some(cached) = &cached {
+        if !cached.is_stale(max_age) {
+            return Ok(Some(cached.clone()));
+        }
+    }
+
+    let Some(supplier) = supplier else {
+        return Ok(cached);
+    };
+
+    match supplier.fetch(mpn).await {
+        Ok(Some(fetched)) => {
+            info!("Cached {} metadata for {}", supplier.name(), mpn);
+            Ok(Some(kicad_db::upsert_part_metadata(pool, &fetched).await?))
+        }
+        Ok(None) => Ok(cached),
+        Err(e) if cached.is_some() => {
+            warn!("Supplier lookup for {} failed, using stale cache: {}", mpn, e);
+            Ok(cached)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Look up several MPNs, skipping any that fail; used to enrich prompts
+pub async fn lookup_many(pool: &PgPool, mpns: &[String]) -> Vec<PartMetadata> {
+    let supplier = configured_supplier();
+    let mut found = Vec::new();
+    for mpn in mpns {
+        match lookup(pool, supplier.as_deref(), mpn).await {
+            Ok(Some(metadata)) => found.push(metadata),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to enrich {}: {}", mpn, e),
+        }
+    }
+    found
+}
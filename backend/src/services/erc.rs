@@ -0,0 +1,81 @@
+use anyhow::Result;
+use kicad_db::{get_erc_findings, introduced_findings, ErcFinding, PgPool};
+use tracing::warn;
+
+use crate::services::git;
+
+/// ERC findings of a commit, split against its parent
+pub struct ErcComparison {
+    pub findings: Vec<ErcFinding>,
+    /// Per finding, whether it is absent at the parent commit. None when the
+    /// parent hasn't been analyzed, so nothing can be attributed to this commit.
+    pub introduced: Option<Vec<bool>>,
+    pub parent_commit: Option<String>,
+}
+
+impl ErcComparison {
+    pub fn introduced_findings(&self) -> Vec<&ErcFinding> {
+        match &self.introduced {
+            Some(flags) => self
+                .findings
+                .iter()
+                .zip(flags)
+                .filter(|(_, new)| **new)
+                .map(|(f, _)| f)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Load the stored ERC findings of a commit and work out which ones it introduced.
+/// Returns None if the commit hasn't been distilled yet.
+pub async fn compare_with_parent(
+    pool: &PgPool,
+    repo: &str,
+    commit: &str,
+) -> Result<Option<ErcComparison>> {
+    let repo_url = git::repo_url(repo);
+    let Some(findings) = get_erc_findings(pool, &repo_url, commit).await? else {
+        return Ok(None);
+    };
+
+    // A root commit introduces everything it has; if the parent can't be
+    // resolved nothing is attributed
+    let (parent_commit, previous) = match git::get_parent_commit(repo, commit).await {
+        Ok(Some(parent)) => {
+            let previous = get_erc_findings(pool, &repo_url, &parent).await?;
+            (Some(parent), previous)
+        }
+        Ok(None) => (None, Some(Vec::new())),
+        Err(e) => {
+            warn!("Failed to resolve parent of {}/{}: {:#}", repo, commit, e);
+            (None, None)
+        }
+    };
+
+    let introduced = previous.map(|previous| {
+        let new = introduced_findings(&findings, &previous);
+        findings.iter().map(|f| new.contains(&f)).collect()
+    });
+
+    Ok(Some(ErcComparison {
+        findings,
+        introduced,
+        parent_commit,
+    }))
+}
+
+/// Prompt section listing the ERC violations a commit introduced (empty if none)
+pub fn prompt_section(introduced: &[&ErcFinding]) -> String {
+    if introduced.is_empty() {
+        return String::new();
+    }
+    let mut section = String::from(
+        "\n\n## New ERC Violations\nThe following electrical rule check violations were introduced by this commit; mention them in the summary:\n",
+    );
+    for finding in introduced {
+        section.push_str(&format!("- [{}] {}\n", finding.rule.as_str(), finding.message));
+    }
+    section
+}
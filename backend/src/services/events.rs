@@ -0,0 +1,57 @@
+use kicad_db::{CommitEvent, CommitEvents, PgPool};
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::shutdown;
+
+/// Events a slow subscriber may fall behind by before it misses some
+const CAPACITY: usize = 256;
+/// Wait before listening again after the listener connection failed
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+static EVENTS: Lazy<broadcast::Sender<CommitEvent>> = Lazy::new(|| broadcast::channel(CAPACITY).0);
+
+/// Commit events from now on, from every server instance
+pub fn subscribe() -> broadcast::Receiver<CommitEvent> {
+    EVENTS.subscribe()
+}
+
+/// Relay commit events from Postgres to subscribers until shutdown
+///
+/// One listener connection serves every open event stream. Events sent while
+/// it reconnects are lost.
+pub fn spawn(pool: Arc<PgPool>) {
+    tokio::spawn(async move {
+        while !shutdown::requested() {
+            tokio::select! {
+                e = relay(&pool) => warn!("Commit event listener failed; retrying in {:?}: {}", RETRY_DELAY, e),
+                _ = shutdown::started() => break,
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(RETRY_DELAY) => {}
+                _ = shutdown::started() => break,
+            }
+        }
+    });
+}
+
+/// Forward events until the listener fails, returning why
+async fn relay(pool: &PgPool) -> sqlx::Error {
+    let mut events = match CommitEvents::listen(pool).await {
+        Ok(events) => events,
+        Err(e) => return e,
+    };
+    info!("Listening for commit events");
+    loop {
+        match events.recv().await {
+            // Nobody may be subscribed; that's fine
+            Ok(event) => {
+                let _ = EVENTS.send(event);
+            }
+            Err(e) => return e,
+        }
+    }
+}
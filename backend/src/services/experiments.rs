@@ -0,0 +1,70 @@
+use anyhow::{bail, Result};
+use kicad_db::PromptLibrary;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+
+/// The variant of the running experiment a summary is generated with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub experiment: String,
+    pub variant: String,
+    /// Template name or label, e.g. "commit_summary@v1"
+    pub template: String,
+    pub model: String,
+}
+
+/// The variant `repo`'s `commit` is summarized with, if an experiment is
+/// configured
+///
+/// Variants are picked by weight from a hash of the experiment, repo and
+/// commit, so regenerating a summary keeps its variant and the comparison
+/// isn't skewed by repeat requests.
+pub fn assign(config: &Config, repo: &str, commit: &str) -> Option<Assignment> {
+    let experiment = config.experiment.as_ref()?;
+    let total: u64 = experiment.variants.iter().map(|v| u64::from(v.weight)).sum();
+    let digest = Sha256::digest(format!("{}\n{}\n{}", experiment.name, repo, commit).as_bytes());
+    let mut bucket = u64::from_be_bytes(digest[..8].try_into().ok()?) % total.max(1);
+    let variant = experiment.variants.iter().find(|v| {
+        let weight = u64::from(v.weight);
+        if bucket < weight {
+            return true;
+        }
+        bucket -= weight;
+        false
+    })?;
+    Some(Assignment {
+        experiment: experiment.name.clone(),
+        variant: variant.name.clone(),
+        template: variant.template.clone(),
+        model: variant.model.clone(),
+    })
+}
+
+/// Check that every variant of the configured experiment names a loaded
+/// template and an allowed model, so a typo fails at startup instead of
+/// on the summaries assigned to it
+pub fn check(config: &Config, prompts: &PromptLibrary) -> Result<()> {
+    let Some(experiment) = &config.experiment else {
+        return Ok(());
+    };
+    for variant in &experiment.variants {
+        if prompts.get(&variant.template).is_none() {
+            bail!(
+                "Variant {} of experiment {} uses unknown template {}",
+                variant.name,
+                experiment.name,
+                variant.template
+            );
+        }
+        if !config.models.is_allowed(&variant.model) {
+            bail!(
+                "Variant {} of experiment {} uses model {}, which is not allowed",
+                variant.name,
+                experiment.name,
+                variant.model
+            );
+        }
+    }
+    Ok(())
+}
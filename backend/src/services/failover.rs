@@ -0,0 +1,166 @@
+use futures_util::{future::BoxFuture, stream, StreamExt};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+use kicad_db::{
+    messages::ChatCompletionRequest,
+    xai_client::{
+        ChatCompletionResponse, ChatCompletionStream, DeferredRequest, ResponsesRequest, ResponsesResponse,
+    },
+    XaiError,
+};
+
+use super::{llm::ChatProvider, metrics};
+
+/// Name xAI's calls are recorded under; fallbacks use their configured names
+pub const PRIMARY_PROVIDER: &str = "xai";
+
+/// One provider a [`FailoverProvider`] can send calls to
+pub struct Provider {
+    /// Recorded with the calls it serves, and in the failover metrics
+    pub name: String,
+    pub chat: Arc<dyn ChatProvider>,
+    /// Models to ask this provider for instead of the ones requested;
+    /// others are sent unchanged
+    pub models: HashMap<String, String>,
+}
+
+/// Sends AI calls to the first of several providers that serves them
+///
+/// Providers are tried in order. A call failing with a retryable error (a
+/// rate limit, timeout, 5xx or open circuit; see [`XaiError::is_transient`])
+/// goes on to the next, with the model renamed as that provider knows it.
+/// Other errors, such as a rejected request or a spent budget, are returned
+/// at once. A stream fails over only until its first event: content already
+/// relayed can't be taken back.
+///
+/// Deferred completions, model listings and embeddings stay with the first
+/// provider: a deferred result can only be collected where it was submitted,
+/// and embeddings from different models can't be compared.
+///
+/// Sits above each provider's circuit breaker and cost metering, and below
+/// the response cache.
+pub struct FailoverProvider {
+    providers: Vec<Provider>,
+}
+
+impl FailoverProvider {
+    /// Panics without providers
+    pub fn new(providers: Vec<Provider>) -> Self {
+        assert!(!providers.is_empty(), "failover needs at least one provider");
+        Self { providers }
+    }
+
+    fn primary(&self) -> &dyn ChatProvider {
+        self.providers[0].chat.as_ref()
+    }
+
+    /// Whether a call that failed with `err` on the provider at `index` goes
+    /// on to the next; counts the failover if so
+    fn fails_over(&self, endpoint: &'static str, index: usize, err: &XaiError) -> bool {
+        let Some(next) = self.providers.get(index + 1) else {
+            return false;
+        };
+        if !err.is_transient() {
+            return false;
+        }
+        let from = &self.providers[index].name;
+        warn!("{} call to {} failed ({}); trying {}", endpoint, from, err, next.name);
+        metrics::record_ai_failover(endpoint, from, &next.name);
+        true
+    }
+}
+
+impl ChatProvider for FailoverProvider {
+    fn responses<'a>(
+        &'a self,
+        request: &'a ResponsesRequest,
+    ) -> BoxFuture<'a, Result<ResponsesResponse, XaiError>> {
+        Box::pin(async move {
+            let mut index = 0;
+            loop {
+                let provider = &self.providers[index];
+                let request = match provider.models.get(&request.model) {
+                    Some(model) => Cow::Owned(ResponsesRequest {
+                        model: model.clone(),
+                        ..request.clone()
+                    }),
+                    None => Cow::Borrowed(request),
+                };
+                match provider.chat.responses(&request).await {
+                    Err(e) if self.fails_over("responses", index, &e) => index += 1,
+                    result => return result,
+                }
+            }
+        })
+    }
+
+    fn chat_completion_stream<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<ChatCompletionStream, XaiError>> {
+        Box::pin(async move {
+            let mut index = 0;
+            loop {
+                let provider = &self.providers[index];
+                let request = match provider.models.get(&request.model) {
+                    Some(model) => Cow::Owned(ChatCompletionRequest {
+                        model: model.clone(),
+                        ..request.clone()
+                    }),
+                    None => Cow::Borrowed(request),
+                };
+                let mut upstream = match provider.chat.chat_completion_stream(&request, cancel.clone()).await {
+                    Ok(upstream) => upstream,
+                    Err(e) if self.fails_over("chat_completion_stream", index, &e) => {
+                        index += 1;
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+                // A stream that fails before sending anything can still go elsewhere
+                match upstream.next().await {
+                    Some(Err(e)) if self.fails_over("chat_completion_stream", index, &e) => index += 1,
+                    first => {
+                        let relayed: ChatCompletionStream = Box::pin(stream::iter(first).chain(upstream));
+                        return Ok(relayed);
+                    }
+                }
+            }
+        })
+    }
+
+    fn submit_deferred<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+    ) -> BoxFuture<'a, Result<DeferredRequest, XaiError>> {
+        self.primary().submit_deferred(request)
+    }
+
+    fn deferred_completion<'a>(
+        &'a self,
+        deferred: &'a DeferredRequest,
+    ) -> BoxFuture<'a, Result<Option<ChatCompletionResponse>, XaiError>> {
+        self.primary().deferred_completion(deferred)
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, XaiError>> {
+        self.primary().list_models()
+    }
+
+    fn embed<'a>(&'a self, model: &'a str, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, XaiError>> {
+        self.primary().embed(model, texts)
+    }
+
+    /// Available while any provider is
+    fn available(&self) -> bool {
+        self.providers.iter().any(|provider| provider.chat.available())
+    }
+
+    fn uncached(&self) -> &dyn ChatProvider {
+        self
+    }
+}
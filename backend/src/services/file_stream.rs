@@ -0,0 +1,173 @@
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::io::SeekFrom;
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+use crate::services::image;
+
+/// Read buffer size for streamed bodies
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Inclusive byte range requested via a `Range` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn content_length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+/// Parse a `Range` header against a file of `size` bytes
+///
+/// Returns `Ok(None)` when the whole file should be sent (no header, or a
+/// header we don't understand, which RFC 9110 says to ignore) and `Err(())`
+/// when the range is syntactically valid but unsatisfiable. Only a single
+/// range is supported; multi-range requests fall back to the full body.
+pub(crate) fn parse_range(value: Option<&HeaderValue>, size: u64) -> Result<Option<ByteRange>, ()> {
+    let Some(spec) = value
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().strip_prefix("bytes="))
+    else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return Ok(None);
+    };
+
+    let range = match (start.trim(), end.trim()) {
+        // bytes=-N: the last N bytes
+        ("", suffix) => {
+            let Ok(suffix) = suffix.parse::<u64>() else {
+                return Ok(None);
+            };
+            if suffix == 0 || size == 0 {
+                return Err(());
+            }
+            ByteRange {
+                start: size.saturating_sub(suffix),
+                end: size - 1,
+            }
+        }
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return Ok(None);
+            };
+            let end = if end.is_empty() {
+                size.saturating_sub(1)
+            } else {
+                match end.parse::<u64>() {
+                    Ok(end) if end >= start => end.min(size.saturating_sub(1)),
+                    _ => return Ok(None),
+                }
+            };
+            if start >= size {
+                return Err(());
+            }
+            ByteRange { start, end }
+        }
+    };
+
+    Ok(Some(range))
+}
+
+/// MIME type of a repository file, from its name and, if given, its first bytes
+///
+/// KiCad files are all text (s-expressions, or JSON for project settings).
+/// Unknown extensions are sniffed: images by signature, anything else that is
+/// valid UTF-8 without NULs as plain text.
+pub fn content_type(path: &str, head: &[u8]) -> &'static str {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let extension = name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("kicad_pro" | "kicad_prl" | "kicad_dru" | "json") => return "application/json",
+        Some(ext) if ext.starts_with("kicad_") => return "text/plain; charset=utf-8",
+        Some("net" | "txt" | "csv" | "tsv" | "pos" | "drl" | "gbr" | "xml") => {
+            return "text/plain; charset=utf-8"
+        }
+        Some("md") => return "text/markdown; charset=utf-8",
+        Some("pdf") => return "application/pdf",
+        Some("step" | "stp") => return "model/step",
+        _ => {}
+    }
+    if matches!(name, "sym-lib-table" | "fp-lib-table") {
+        return "text/plain; charset=utf-8";
+    }
+    if let Some(image) = image::content_type(head) {
+        return image;
+    }
+    if !head.is_empty() && !head.contains(&0) && std::str::from_utf8(head).is_ok() {
+        return "text/plain; charset=utf-8";
+    }
+    "application/octet-stream"
+}
+
+/// Stream a file from disk as the response body, honouring `Range` requests
+///
+/// The file is never read into memory as a whole: the body is an
+/// `AsyncRead`-backed stream of `CHUNK_SIZE` chunks, so large PCB and
+/// schematic files cost a constant amount of memory per request.
+pub async fn stream_file(
+    path: &Path,
+    content_type: &str,
+    request_headers: &HeaderMap,
+) -> std::io::Result<Response> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let size = file.metadata().await?.len();
+
+    let range = match parse_range(request_headers.get(header::RANGE), size) {
+        Ok(range) => range,
+        Err(()) => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [
+                    (header::CONTENT_RANGE, format!("bytes */{}", size)),
+                    (header::ACCEPT_RANGES, "bytes".to_string()),
+                ],
+            )
+                .into_response());
+        }
+    };
+
+    let mut builder = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    let body = match range {
+        Some(range) => {
+            file.seek(SeekFrom::Start(range.start)).await?;
+            builder = builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", range.start, range.end, size),
+                )
+                .header(header::CONTENT_LENGTH, range.content_length());
+            Body::from_stream(ReaderStream::with_capacity(
+                file.take(range.content_length()),
+                CHUNK_SIZE,
+            ))
+        }
+        None => {
+            builder = builder
+                .status(StatusCode::OK)
+                .header(header::CONTENT_LENGTH, size);
+            Body::from_stream(ReaderStream::with_capacity(file, CHUNK_SIZE))
+        }
+    };
+
+    builder
+        .body(body)
+        .map_err(std::io::Error::other)
+}
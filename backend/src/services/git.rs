@@ -1,20 +1,335 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, TimeZone, Utc};
-use git2::{build::RepoBuilder, ObjectType, Repository};
+use git2::{build::{CheckoutBuilder, RepoBuilder}, Cred, FetchOptions, ObjectType, RemoteCallbacks, Repository};
+use glob::{MatchOptions, Pattern};
+use once_cell::sync::{Lazy, OnceCell};
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use tracing::{info, warn};
+use fs2::FileExt;
+use std::fs::File;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::{mpsc, OwnedRwLockReadGuard, OwnedRwLockWriteGuard};
+use tokio_stream::{wrappers::ReceiverStream, StreamExt};
+use std::time::{Duration, Instant};
+use tracing::{info, warn, Instrument};
 
-use crate::types::{CommitInfo, SchematicFile};
+use crate::request_id;
+use crate::services::{lfs, metrics, submodules};
+use crate::types::{CommitInfo, SchematicFile, SubmodulePin};
+
+/// Run a git2 operation on the blocking pool in a `git` span, recording its
+/// duration
+async fn run_blocking<T: Send + 'static>(
+    operation: &'static str,
+    f: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let started = Instant::now();
+    let span = tracing::info_span!("git", operation);
+    let result = span
+        .in_scope(|| request_id::spawn_blocking(f))
+        .instrument(span.clone())
+        .await?;
+    metrics::record_git(operation, started.elapsed());
+    result
+}
+
+/// Build the clone URL for a repository slug
+///
+/// Plain "owner/repo" slugs are GitHub repositories. Slugs whose first segment
+/// is a hostname (e.g. "gitlab.com/group/project" or "bitbucket.org/team/repo")
+/// are cloned from that host instead, which is how GitLab and Bitbucket webhooks
+/// feed into the same processing path.
+pub fn repo_url(repo_slug: &str) -> String {
+    match repo_slug.split_once('/') {
+        Some((host, _)) if host.contains('.') => format!("https://{}.git", repo_slug),
+        _ => format!("https://github.com/{}.git", repo_slug),
+    }
+}
+
+/// The slug a clone URL was built from by [`repo_url`], if it looks like one
+pub fn repo_slug(repo_url: &str) -> Option<String> {
+    let path = repo_url.strip_prefix("https://")?.strip_suffix(".git")?;
+    let slug = path.strip_prefix("github.com/").unwrap_or(path);
+    slug.contains('/').then(|| slug.to_string())
+}
+
+/// Where and how a registered repo is fetched, overriding the defaults
+#[derive(Debug, Clone)]
+pub struct RemoteSettings {
+    pub clone_url: String,
+    /// Branch to check out; the remote's HEAD if unset
+    pub branch: Option<String>,
+    /// Environment variable holding an access token, read on each fetch
+    pub token_env: Option<String>,
+    /// Commits to clone up front; older ones are fetched when asked for
+    pub depth: Option<i32>,
+    /// Pathspecs to check out; empty checks out the whole tree
+    pub sparse_paths: Vec<String>,
+    /// Design files processed; empty processes the whole tree
+    pub path_filter: Vec<Pattern>,
+    /// Files read as schematics besides .kicad_sch and legacy .sch files
+    pub schematic_globs: Vec<Pattern>,
+    /// Fetch submodules and read symbol libraries from them
+    pub submodules: bool,
+}
+
+impl From<&kicad_db::RegisteredRepo> for RemoteSettings {
+    fn from(repo: &kicad_db::RegisteredRepo) -> Self {
+        Self {
+            clone_url: repo.clone_url.clone(),
+            branch: repo.default_branch.clone(),
+            token_env: repo.auth_token_env.clone(),
+            depth: repo.clone_depth,
+            sparse_paths: repo.sparse_paths.clone().unwrap_or_default(),
+            path_filter: patterns(&repo.path_filter, "path filter", &repo.slug),
+            schematic_globs: patterns(&repo.schematic_globs, "schematic glob", &repo.slug),
+            submodules: repo.submodules,
+        }
+    }
+}
+
+/// A repo's stored globs; ones that no longer parse are skipped
+fn patterns(globs: &Option<Vec<String>>, what: &str, repo_slug: &str) -> Vec<Pattern> {
+    globs
+        .iter()
+        .flatten()
+        .filter_map(|glob| match Pattern::new(glob) {
+            Ok(pattern) => Some(pattern),
+            Err(e) => {
+                warn!("Ignoring {} {:?} of {}: {}", what, glob, repo_slug, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Remote settings of registered repos, by slug
+static REMOTES: Lazy<RwLock<HashMap<String, RemoteSettings>>> = Lazy::new(Default::default);
+
+/// Use `settings` when fetching `repo_slug`, or the defaults again if None
+pub fn set_remote(repo_slug: &str, settings: Option<RemoteSettings>) {
+    let mut remotes = REMOTES.write().unwrap();
+    match settings {
+        Some(settings) => remotes.insert(repo_slug.to_string(), settings),
+        None => remotes.remove(repo_slug),
+    };
+}
+
+fn remote(repo_slug: &str) -> Option<RemoteSettings> {
+    REMOTES.read().unwrap().get(repo_slug).cloned()
+}
+
+/// Which files of a repo are read, and which of them are schematics
+#[derive(Debug, Clone, Default)]
+struct DesignFiles {
+    /// Empty processes the whole tree
+    path_filter: Vec<Pattern>,
+    schematic_globs: Vec<Pattern>,
+}
+
+impl DesignFiles {
+    /// A registered repo's path filter and schematic globs; none for other repos
+    fn of(repo_slug: &str) -> Self {
+        remote(repo_slug).map_or_else(Self::default, |r| Self {
+            path_filter: r.path_filter,
+            schematic_globs: r.schematic_globs,
+        })
+    }
+
+    /// Whether `path` is read as a schematic; which format it's in is told
+    /// by its contents, so files in neither are skipped when parsing
+    fn is_schematic(&self, path: &str) -> bool {
+        path.ends_with(".kicad_sch") || path.ends_with(".sch") || matches_any(&self.schematic_globs, path)
+    }
+
+    /// Whether `path` is a design file whose changes we summarize (schematic
+    /// or layout) within the path filter
+    fn tracks(&self, path: &str) -> bool {
+        (self.is_schematic(path) || path.ends_with(".kicad_pcb")) && self.in_path_filter(path)
+    }
+
+    fn in_path_filter(&self, path: &str) -> bool {
+        self.path_filter.is_empty() || matches_any(&self.path_filter, path)
+    }
+}
+
+/// Whether `path` matches one of `patterns`; `*` stays within a directory, `**` spans them
+fn matches_any(patterns: &[Pattern], path: &str) -> bool {
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+    patterns.iter().any(|p| p.matches_with(path, options))
+}
+
+/// Tokens repos may fetch with, by the name their `auth_token_env` gives
+static TOKENS: Lazy<RwLock<HashMap<String, String>>> = Lazy::new(Default::default);
+
+/// Fetch with `tokens` (config `git_tokens`); called at startup and on each reload
+pub fn set_tokens(tokens: HashMap<String, String>) {
+    *TOKENS.write().unwrap() = tokens;
+}
+
+/// The token a remote's `auth_token_env` names, if it's configured
+fn token(remote: Option<&RemoteSettings>) -> Option<String> {
+    let name = remote?.token_env.as_deref()?;
+    TOKENS.read().unwrap().get(name).cloned()
+}
+
+/// Fetch options that authenticate with the remote's token, if it has one
+pub(crate) fn fetch_options(remote: Option<&RemoteSettings>) -> FetchOptions<'static> {
+    let mut options = FetchOptions::new();
+    let token_env = remote.and_then(|r| r.token_env.as_deref());
+    if let Some(token) = token(remote) {
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(move |_, _, _| Cred::userpass_plaintext("x-access-token", &token));
+        options.remote_callbacks(callbacks);
+    } else if let Some(name) = token_env {
+        warn!("{} is not set or not in git_token_envs; fetching without a token", name);
+    }
+    options
+}
+
+/// Seconds a clone's latest history is read without fetching; see [`set_fetch_interval`]
+static FETCH_INTERVAL_SECS: AtomicU64 = AtomicU64::new(60);
+
+/// When each repo's clone was last fetched by this process
+static FETCHED_AT: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(Default::default);
+
+/// Fetch a clone again when its latest history is read `secs` after the
+/// last fetch (config `git_fetch_interval_secs`); called at startup and on
+/// each reload
+pub fn set_fetch_interval(secs: u64) {
+    FETCH_INTERVAL_SECS.store(secs, Ordering::Relaxed);
+}
+
+/// Whether `repo_slug` was fetched within the fetch interval
+fn fetched_recently(repo_slug: &str) -> bool {
+    let interval = Duration::from_secs(FETCH_INTERVAL_SECS.load(Ordering::Relaxed));
+    FETCHED_AT
+        .lock()
+        .unwrap()
+        .get(repo_slug)
+        .is_some_and(|at| at.elapsed() < interval)
+}
+
+/// Root for clones and materialized blobs; the system temp dir until configured
+static CACHE_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Use `dir` for the git cache; call once at startup, before any git operation
+pub fn set_cache_dir(dir: PathBuf) {
+    if CACHE_DIR.set(dir).is_err() {
+        warn!("Git cache directory already set; ignoring");
+    }
+}
+
+/// Directory holding the git cache
+pub fn cache_dir() -> PathBuf {
+    CACHE_DIR.get().cloned().unwrap_or_else(std::env::temp_dir)
+}
 
 /// Get the cache path for a repository
 fn get_cache_path(repo_slug: &str) -> PathBuf {
-    std::env::temp_dir().join(format!("kicad-cache-{}", repo_slug.replace('/', "-")))
+    cache_dir().join(format!("kicad-cache-{}", repo_slug.replace('/', "-")))
+}
+
+/// In-process half of the cache locks, one per slug
+static CACHE_LOCKS: Lazy<Mutex<HashMap<String, Arc<tokio::sync::RwLock<()>>>>> =
+    Lazy::new(Default::default);
+
+enum CacheGuard {
+    Shared(#[allow(dead_code)] OwnedRwLockReadGuard<()>),
+    Exclusive(OwnedRwLockWriteGuard<()>),
+}
+
+/// Keeps a repo's cache directory steady while held
+///
+/// Cloning, fetching and deleting the cache take it exclusively; reading from
+/// a checkout holds it shared, so a webhook can't delete the directory under a
+/// read in progress. Operations on different repos don't contend. Each lock
+/// pairs an in-process RwLock with an flock on a file beside the cache, so
+/// several server processes sharing a cache dir serialize too.
+pub struct CacheLock {
+    guard: CacheGuard,
+    file: File,
+}
+
+impl CacheLock {
+    async fn exclusive(repo_slug: &str) -> Result<Self> {
+        let guard = CacheGuard::Exclusive(Self::slot(repo_slug).write_owned().await);
+        let file = Self::lock_file(repo_slug, false).await?;
+        Ok(Self { guard, file })
+    }
+
+    /// Keep the cache steady for reading what's already in it, without the
+    /// fetch [`get_repo`] does first
+    async fn shared(repo_slug: &str) -> Result<Self> {
+        let guard = CacheGuard::Shared(Self::slot(repo_slug).read_owned().await);
+        let file = Self::lock_file(repo_slug, true).await?;
+        Ok(Self { guard, file })
+    }
+
+    fn slot(repo_slug: &str) -> Arc<tokio::sync::RwLock<()>> {
+        CACHE_LOCKS
+            .lock()
+            .unwrap()
+            .entry(repo_slug.to_string())
+            .or_default()
+            .clone()
+    }
+
+    async fn lock_file(repo_slug: &str, shared: bool) -> Result<File> {
+        let path = cache_dir().join(format!("kicad-cache-{}.lock", repo_slug.replace('/', "-")));
+        request_id::spawn_blocking(move || -> Result<File> {
+            let file = File::create(&path)
+                .with_context(|| format!("Failed to create cache lock {:?}", path))?;
+            if shared {
+                file.lock_shared()
+            } else {
+                file.lock_exclusive()
+            }
+            .context("Failed to lock repo cache")?;
+            Ok(file)
+        })
+        .await?
+    }
+
+    /// Let other readers in once the checkout is up to date
+    async fn downgrade(self) -> Result<Self> {
+        let guard = match self.guard {
+            CacheGuard::Exclusive(guard) => CacheGuard::Shared(guard.downgrade()),
+            shared => shared,
+        };
+        let file = self.file;
+        let file = request_id::spawn_blocking(move || -> Result<File> {
+            file.lock_shared().context("Failed to lock repo cache")?;
+            Ok(file)
+        })
+        .await??;
+        Ok(Self { guard, file })
+    }
+}
+
+/// How many times each repo's cache has been deleted
+static CACHE_GENERATIONS: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(Default::default);
+
+/// Changes each time the repo's cache is deleted, so what's been read from it
+/// and kept elsewhere (parsed schematics) can be dropped along with it
+pub fn cache_generation(repo_slug: &str) -> u64 {
+    CACHE_GENERATIONS.lock().unwrap().get(repo_slug).copied().unwrap_or(0)
+}
+
+fn next_generation(repo_slug: &str) {
+    *CACHE_GENERATIONS.lock().unwrap().entry(repo_slug.to_string()).or_default() += 1;
 }
 
 /// Invalidate (delete) the cache for a repository
 /// Call this when you know there are new commits (e.g., from a webhook)
 pub async fn invalidate_cache(repo_slug: &str) -> Result<()> {
     let cache_path = get_cache_path(repo_slug);
+    let _lock = CacheLock::exclusive(repo_slug).await?;
+    next_generation(repo_slug);
     if cache_path.exists() {
         tokio::fs::remove_dir_all(&cache_path).await?;
         info!(
@@ -26,30 +341,113 @@ pub async fn invalidate_cache(repo_slug: &str) -> Result<()> {
 }
 
 /// Clone or fetch a repository, returning a handle to it
-/// If force_fresh is true, deletes any existing cache first
-pub async fn get_repo(repo_slug: &str) -> Result<Repository> {
+///
+/// The cache stays locked for reading until the returned lock is dropped;
+/// hold it for as long as the handle is in use.
+pub async fn get_repo(repo_slug: &str) -> Result<(Repository, CacheLock)> {
     get_repo_with_options(repo_slug, false).await
 }
 
+/// Like [`get_repo`], but makes sure a shallow clone holds `commit_hash`
+pub async fn get_repo_at(repo_slug: &str, commit_hash: &str) -> Result<(Repository, CacheLock)> {
+    checkout(repo_slug, false, Some((commit_hash, false))).await
+}
+
+/// Like [`get_repo_at`], but also the commit's parents, for diffs
+pub async fn get_repo_with_parents(
+    repo_slug: &str,
+    commit_hash: &str,
+) -> Result<(Repository, CacheLock)> {
+    checkout(repo_slug, false, Some((commit_hash, true))).await
+}
+
 /// Clone or fetch a repository with options
 /// If force_fresh is true, deletes any existing cache first
-pub async fn get_repo_with_options(repo_slug: &str, force_fresh: bool) -> Result<Repository> {
+pub async fn get_repo_with_options(
+    repo_slug: &str,
+    force_fresh: bool,
+) -> Result<(Repository, CacheLock)> {
+    checkout(repo_slug, force_fresh, None).await
+}
+
+/// Clone or fetch, then deepen a shallow clone for `(commit_hash, with_parents)`
+///
+/// libgit2 runs no filter drivers, so files kept in Git LFS are checked out
+/// as their pointers and clones never download LFS objects; the design files
+/// among them are fetched from LFS when read (see [`lfs`]).
+async fn checkout(
+    repo_slug: &str,
+    force_fresh: bool,
+    wanted: Option<(&str, bool)>,
+) -> Result<(Repository, CacheLock)> {
     let repo_slug = repo_slug.to_string();
     let cache_path = get_cache_path(&repo_slug);
+    let remote = remote(&repo_slug);
+
+    // Most reads find what they need in the cache, so share it and only
+    // lock it exclusively to fetch
+    if !force_fresh {
+        let lock = CacheLock::shared(&repo_slug).await?;
+        if let Some(repo) = cached(&repo_slug, wanted, remote.as_ref()).await? {
+            return Ok((repo, lock));
+        }
+    }
+    let lock = CacheLock::exclusive(&repo_slug).await?;
 
     // If force_fresh, delete the cache first
     if force_fresh && cache_path.exists() {
+        next_generation(&repo_slug);
         tokio::fs::remove_dir_all(&cache_path).await?;
         info!(
             "Force-deleted cache for repo {} at {:?}",
             repo_slug, cache_path
         );
+    } else if !force_fresh {
+        // Another read may have fetched while this one waited for the lock
+        if let Some(repo) = cached(&repo_slug, wanted, remote.as_ref()).await? {
+            return Ok((repo, lock.downgrade().await?));
+        }
     }
 
-    tokio::task::spawn_blocking(move || -> Result<Repository> {
+    let operation = if cache_path.exists() { "fetch" } else { "clone" };
+    let fetched_slug = repo_slug.clone();
+    let deepen_remote = remote.clone();
+    let submodule_remote = remote.clone().filter(|r| r.submodules);
+    let repo = run_blocking(operation, move || -> Result<Repository> {
+        let url = match &remote {
+            Some(remote) => remote.clone_url.clone(),
+            None => repo_url(&repo_slug),
+        };
+        let branch = remote.as_ref().and_then(|r| r.branch.clone());
         if !cache_path.exists() {
-            let url = format!("https://github.com/{}.git", repo_slug);
-            let repo = RepoBuilder::new()
+            #[cfg(feature = "fault-injection")]
+            if kicad_db::fault_injection::should_inject(
+                kicad_db::fault_injection::Fault::GitPartialClone,
+            ) {
+                // Leave behind what an interrupted clone would: a cache dir with a broken .git
+                std::fs::create_dir_all(cache_path.join(".git"))?;
+                anyhow::bail!("Failed to clone repository: transfer interrupted (simulated partial clone)");
+            }
+
+            let mut builder = RepoBuilder::new();
+            let mut options = fetch_options(remote.as_ref());
+            if let Some(depth) = remote.as_ref().and_then(|r| r.depth) {
+                options.depth(depth);
+            }
+            builder.fetch_options(options);
+            if let Some(branch) = &branch {
+                builder.branch(branch);
+            }
+            if let Some(paths) = remote.as_ref().map(|r| &r.sparse_paths).filter(|p| !p.is_empty()) {
+                // Sheets are read from the object database, so the working
+                // tree only needs what a person browsing the cache would want
+                let mut checkout = CheckoutBuilder::new();
+                for path in paths {
+                    checkout.path(path);
+                }
+                builder.with_checkout(checkout);
+            }
+            let repo = builder
                 .clone(&url, &cache_path)
                 .context("Failed to clone repository")?;
             info!("Cloned repo {} to {:?}", repo_slug, cache_path);
@@ -58,21 +456,26 @@ pub async fn get_repo_with_options(repo_slug: &str, force_fresh: bool) -> Result
             let repo = Repository::open(&cache_path).context("Failed to open cached repository")?;
             // Fetch updates
             {
-                let mut remote = repo.find_remote("origin").or_else(|_| {
-                    let url = format!("https://github.com/{}.git", repo_slug);
+                let mut origin = repo.find_remote("origin").or_else(|_| {
                     repo.remote("origin", &url)
                 })?;
-                remote.fetch(&["refs/heads/*:refs/remotes/origin/*"], None, None)?;
+                let mut options = fetch_options(remote.as_ref());
+                origin.fetch(&["refs/heads/*:refs/remotes/origin/*"], Some(&mut options), None)?;
             }
 
-            // Update local HEAD to match remote's default branch
-            // First, find the remote HEAD (origin/HEAD or origin/main or origin/master)
+            // Update local HEAD to match the configured branch, or else the remote's
+            // default branch (origin/HEAD or origin/main or origin/master)
             let remote_commit_id = {
-                let remote_head = repo
-                    .find_reference("refs/remotes/origin/HEAD")
-                    .or_else(|_| repo.find_reference("refs/remotes/origin/main"))
-                    .or_else(|_| repo.find_reference("refs/remotes/origin/master"))
-                    .context("Failed to find remote HEAD")?;
+                let remote_head = match &branch {
+                    Some(branch) => repo
+                        .find_reference(&format!("refs/remotes/origin/{}", branch))
+                        .with_context(|| format!("Branch {} not found on the remote", branch))?,
+                    None => repo
+                        .find_reference("refs/remotes/origin/HEAD")
+                        .or_else(|_| repo.find_reference("refs/remotes/origin/main"))
+                        .or_else(|_| repo.find_reference("refs/remotes/origin/master"))
+                        .context("Failed to find remote HEAD")?,
+                };
                 remote_head.peel_to_commit()?.id()
             };
 
@@ -88,55 +491,318 @@ pub async fn get_repo_with_options(repo_slug: &str, force_fresh: bool) -> Result
             Ok(repo)
         }
     })
-    .await?
+    .await?;
+    FETCHED_AT.lock().unwrap().insert(fetched_slug, Instant::now());
+
+    let repo = match wanted {
+        Some((commit_hash, with_parents)) if repo.is_shallow() => {
+            let commit_hash = commit_hash.to_string();
+            run_blocking("deepen", move || {
+                deepen(repo, &commit_hash, with_parents, deepen_remote.as_ref())
+            })
+            .await?
+        }
+        _ => repo,
+    };
+    let repo = match submodule_remote {
+        Some(remote) => {
+            let commit_hash = wanted.map(|(commit_hash, _)| commit_hash.to_string());
+            run_blocking("submodules", move || {
+                sync_submodules(&repo, commit_hash.as_deref(), &remote);
+                Ok(repo)
+            })
+            .await?
+        }
+        None => repo,
+    };
+    Ok((repo, lock.downgrade().await?))
+}
+
+/// The clone of `repo_slug`, if it can be read without a fetch: for
+/// `(commit_hash, with_parents)` if it holds the commit (and its parents),
+/// otherwise if it was fetched within the fetch interval
+///
+/// Commits of repos with submodules always fetch, to sync the submodules
+/// they pin.
+async fn cached(
+    repo_slug: &str,
+    wanted: Option<(&str, bool)>,
+    remote: Option<&RemoteSettings>,
+) -> Result<Option<Repository>> {
+    let cache_path = get_cache_path(repo_slug);
+    if !cache_path.exists() {
+        return Ok(None);
+    }
+    let wanted = wanted.filter(|(hash, _)| is_commit_id(hash));
+    let wanted = match wanted {
+        Some(_) if remote.is_some_and(|r| r.submodules) => return Ok(None),
+        Some((hash, with_parents)) => Some((hash.to_string(), with_parents)),
+        None if fetched_recently(repo_slug) => None,
+        None => return Ok(None),
+    };
+    run_blocking("open", move || {
+        // A clone that won't open is left for the fetch to fail on
+        let Ok(repo) = Repository::open(&cache_path) else {
+            return Ok(None);
+        };
+        if let Some((hash, with_parents)) = wanted {
+            let found = repo.revparse_single(&hash).and_then(|o| o.peel_to_commit()).map(|commit| commit.id());
+            if !found.is_ok_and(|id| !with_parents || !shallow_roots(&repo).contains(&id)) {
+                return Ok(None);
+            }
+        }
+        Ok(Some(repo))
+    })
+    .await
+}
+
+/// Fetch the submodules pinned at HEAD and at `commit_hash`, if given
+fn sync_submodules(repo: &Repository, commit_hash: Option<&str>, remote: &RemoteSettings) {
+    let store = submodules::store(repo);
+    let head = repo.head().and_then(|head| head.peel_to_tree());
+    let wanted = commit_hash.map(|hash| repo.revparse_single(hash).and_then(|o| o.peel_to_tree()));
+    for tree in std::iter::once(head).chain(wanted).filter_map(Result::ok) {
+        submodules::sync(&store, repo, &tree, &remote.clone_url, Some(remote));
+    }
+}
+
+/// Commits a shallow clone has without their parents
+fn shallow_roots(repo: &Repository) -> HashSet<git2::Oid> {
+    std::fs::read_to_string(repo.path().join("shallow"))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| git2::Oid::from_str(line.trim()).ok())
+        .collect()
+}
+
+/// Whether `hash` is a commit id, maybe abbreviated, rather than a ref or
+/// revision expression whose commit can change
+fn is_commit_id(hash: &str) -> bool {
+    (4..=40).contains(&hash.len()) && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Fetch the rest of a shallow clone's history if it lacks `commit_hash`, or
+/// with `with_parents` the commit's parents
+///
+/// libgit2 can't deepen a shallow clone by some number of commits, only
+/// unshallow it, so the first commit asked for past the boundary fetches
+/// everything. Anything that isn't a commit id is left for the caller to fail
+/// on rather than fetching the whole history looking for it.
+fn deepen(
+    repo: Repository,
+    commit_hash: &str,
+    with_parents: bool,
+    remote: Option<&RemoteSettings>,
+) -> Result<Repository> {
+    if !is_commit_id(commit_hash) {
+        return Ok(repo);
+    }
+    let found = repo
+        .revparse_single(commit_hash)
+        .and_then(|o| o.peel_to_commit())
+        .map(|commit| commit.id());
+    if found.is_ok_and(|id| !with_parents || !shallow_roots(&repo).contains(&id)) {
+        return Ok(repo);
+    }
+
+    let mut options = fetch_options(remote);
+    options.depth(i32::MAX);
+    repo.find_remote("origin")?
+        .fetch(&["refs/heads/*:refs/remotes/origin/*"], Some(&mut options), None)
+        .context("Failed to fetch full history")?;
+    info!("Fetched the full history of a shallow clone looking for {}", commit_hash);
+    // The shallow boundary is read when a repository is opened
+    Repository::open(repo.path()).context("Failed to reopen repository")
 }
 
 /// Get a repo with a forced fresh clone (for webhook use)
-pub async fn get_repo_fresh(repo_slug: &str) -> Result<Repository> {
+#[allow(dead_code)]
+pub async fn get_repo_fresh(repo_slug: &str) -> Result<(Repository, CacheLock)> {
     get_repo_with_options(repo_slug, true).await
 }
 
-/// Get all commits, with a flag indicating if they modify .kicad_sch files
+/// Get all commits, with a flag indicating if they modify schematics or .kicad_pcb files
 pub async fn get_all_commits(repo_slug: &str) -> Result<Vec<CommitInfo>> {
-    let repo = get_repo(repo_slug).await?;
+    let (repo, _cache) = get_repo(repo_slug).await?;
+    let files = DesignFiles::of(repo_slug);
 
-    tokio::task::spawn_blocking(move || -> Result<Vec<CommitInfo>> {
+    run_blocking("log", move || -> Result<Vec<CommitInfo>> {
         let mut revwalk = repo.revwalk()?;
         let _ = revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME);
         revwalk.push_head()?;
 
         let mut commits = Vec::new();
+        let mut tags = tags_by_commit(&repo)?;
 
         for oid in revwalk {
-            let oid = oid?;
-            let commit = repo.find_commit(oid)?;
-            let commit_date = Utc.timestamp_opt(commit.time().seconds(), 0).single();
-            let has_changes = has_schematic_changes(&repo, &commit)?;
-
-            commits.push(CommitInfo {
-                commit_hash: commit.id().to_string(),
-                commit_date,
-                message: commit.summary().map(ToString::to_string),
-                has_schematic_changes: has_changes,
-            });
+            let commit = repo.find_commit(oid?)?;
+            let has_changes = has_schematic_changes(&repo, &commit, &files)?;
+            commits.push(commit_info(&commit, &mut tags, has_changes));
         }
 
         Ok(commits)
     })
-    .await?
+    .await
 }
 
-/// Get only commits that modify .kicad_sch files (for hook processing)
+fn commit_info(commit: &git2::Commit, tags: &mut HashMap<git2::Oid, Vec<String>>, has_schematic_changes: bool) -> CommitInfo {
+    let author = commit.author();
+    CommitInfo {
+        commit_hash: commit.id().to_string(),
+        commit_date: Utc.timestamp_opt(commit.time().seconds(), 0).single(),
+        message: commit.summary().map(ToString::to_string),
+        author: author.name().map(ToString::to_string),
+        author_email: author.email().map(ToString::to_string),
+        tags: tags.remove(&commit.id()).unwrap_or_default(),
+        has_schematic_changes,
+    }
+}
+
+/// Commits per page of a [`schematic_commit_pages`] stream
+const LOG_PAGE_SIZE: usize = 64;
+
+/// Which commits a [`schematic_commit_pages`] stream yields
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    /// Leave out this commit and its ancestors, e.g. the newest one already
+    /// processed
+    pub since_commit: Option<String>,
+    /// Leave out commits made before this
+    pub since: Option<DateTime<Utc>>,
+    /// Leave out commits made after this
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl LogFilter {
+    fn in_range(&self, date: Option<DateTime<Utc>>) -> bool {
+        match date {
+            Some(date) => self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date <= until),
+            None => self.since.is_none() && self.until.is_none(),
+        }
+    }
+}
+
+/// Pages of commits, newest first; a failure ends the stream
+pub type CommitPages = ReceiverStream<Result<Vec<CommitInfo>>>;
+
+/// Stream the commits that modify schematics or .kicad_pcb files within the
+/// repo's path filter, newest first, a page at a time
+///
+/// History is walked at most a page ahead of the consumer, so processing
+/// starts with the first page rather than after the whole log, and dropping
+/// the stream stops the walk.
+pub fn schematic_commit_pages(repo_slug: &str, filter: LogFilter) -> CommitPages {
+    let (tx, rx) = mpsc::channel(1);
+    let repo_slug = repo_slug.to_string();
+    let walk = async move {
+        if let Err(e) = walk_schematic_commits(&repo_slug, filter, &tx).await {
+            let _ = tx.send(Err(e)).await;
+        }
+    };
+    tokio::spawn(walk.instrument(tracing::Span::current()));
+    ReceiverStream::new(rx)
+}
+
+/// Send `filter`'s schematic commits to `tx` in pages, until the receiver
+/// is dropped
+///
+/// The clone is fetched once up front, then walked on a blocking thread that
+/// keeps the revwalk between pages. The cache is locked shared while a page
+/// is read, never while waiting for the consumer to take one: processing a
+/// page checks commits out, which may lock the cache exclusively. A walk
+/// whose cache is deleted between pages fails.
+async fn walk_schematic_commits(
+    repo_slug: &str,
+    filter: LogFilter,
+    tx: &mpsc::Sender<Result<Vec<CommitInfo>>>,
+) -> Result<()> {
+    let (repo, cache) = get_repo(repo_slug).await?;
+    let generation = cache_generation(repo_slug);
+    let files = DesignFiles::of(repo_slug);
+    let (repo_slug, tx) = (repo_slug.to_string(), tx.clone());
+    let runtime = tokio::runtime::Handle::current();
+    let span = tracing::info_span!("git", operation = "log");
+    let walk = move || -> Result<()> {
+        let mut revwalk = repo.revwalk()?;
+        let _ = revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME);
+        revwalk.push_head()?;
+        if let Some(since_commit) = &filter.since_commit {
+            let commit = repo
+                .revparse_single(since_commit)
+                .and_then(|object| object.peel_to_commit())
+                .with_context(|| format!("Unknown commit {}", since_commit))?;
+            revwalk.hide(commit.id())?;
+        }
+        let mut tags = tags_by_commit(&repo)?;
+
+        let mut cache = Some(cache);
+        loop {
+            let lock = match cache.take() {
+                Some(cache) => cache,
+                None => runtime.block_on(CacheLock::shared(&repo_slug))?,
+            };
+            if cache_generation(&repo_slug) != generation {
+                anyhow::bail!("The cache of {} was deleted while its history was read", repo_slug);
+            }
+            let started = Instant::now();
+            let mut page = Vec::with_capacity(LOG_PAGE_SIZE);
+            let mut done = false;
+            while page.len() < LOG_PAGE_SIZE {
+                let Some(oid) = revwalk.next() else {
+                    done = true;
+                    break;
+                };
+                let commit = repo.find_commit(oid?)?;
+                let date = Utc.timestamp_opt(commit.time().seconds(), 0).single();
+                if filter.in_range(date) && has_schematic_changes(&repo, &commit, &files)? {
+                    page.push(commit_info(&commit, &mut tags, true));
+                }
+            }
+            metrics::record_git("log", started.elapsed());
+            drop(lock);
+            if (!page.is_empty() && tx.blocking_send(Ok(page)).is_err()) || done {
+                return Ok(());
+            }
+        }
+    };
+    span.in_scope(|| request_id::spawn_blocking(walk)).instrument(span.clone()).await?
+}
+
+/// Tag names by the commit they point at, sorted
+fn tags_by_commit(repo: &Repository) -> Result<HashMap<git2::Oid, Vec<String>>> {
+    let mut tags: HashMap<git2::Oid, Vec<String>> = HashMap::new();
+    for name in repo.tag_names(None)?.iter().flatten() {
+        let reference = repo.find_reference(&format!("refs/tags/{}", name))?;
+        if let Ok(commit) = reference.peel_to_commit() {
+            tags.entry(commit.id()).or_default().push(name.to_string());
+        }
+    }
+    for names in tags.values_mut() {
+        names.sort();
+    }
+    Ok(tags)
+}
+
+/// Get only commits that modify schematics or .kicad_pcb files within the
+/// repo's path filter (for hook processing)
 pub async fn get_schematic_commits(repo_slug: &str) -> Result<Vec<CommitInfo>> {
-    let all_commits = get_all_commits(repo_slug).await?;
-    Ok(all_commits
-        .into_iter()
-        .filter(|c| c.has_schematic_changes)
-        .collect())
+    let mut pages = schematic_commit_pages(repo_slug, LogFilter::default());
+    let mut commits = Vec::new();
+    while let Some(page) = pages.next().await {
+        commits.extend(page?);
+    }
+    Ok(commits)
 }
 
-/// Check if a commit contains changes to .kicad_sch files
-fn has_schematic_changes(repo: &Repository, commit: &git2::Commit) -> Result<bool> {
+/// Check if a commit contains changes to schematics or .kicad_pcb files
+///
+/// Layout-only commits count, so board rework shows up alongside schematic edits.
+fn has_schematic_changes(
+    repo: &Repository,
+    commit: &git2::Commit,
+    files: &DesignFiles,
+) -> Result<bool> {
+    let is_tracked = |path: &str| files.tracks(path);
     if let Some(parent) = commit.parents().next() {
         let tree1 = parent.tree()?;
         let tree2 = commit.tree()?;
@@ -146,21 +812,21 @@ fn has_schematic_changes(repo: &Repository, commit: &git2::Commit) -> Result<boo
             d.old_file()
                 .path()
                 .and_then(|p| p.to_str())
-                .map(|s| s.ends_with(".kicad_sch"))
+                .map(is_tracked)
                 .unwrap_or(false)
                 || d.new_file()
                     .path()
                     .and_then(|p| p.to_str())
-                    .map(|s| s.ends_with(".kicad_sch"))
+                    .map(is_tracked)
                     .unwrap_or(false)
         }))
     } else {
-        // Root commit: check if tree has any .kicad_sch files
+        // Root commit: check if tree has any design files
         let tree = commit.tree()?;
         let mut has = false;
-        tree.walk(git2::TreeWalkMode::PreOrder, |_, entry| {
+        tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
             if let Some(name) = entry.name() {
-                if name.ends_with(".kicad_sch") && entry.kind() == Some(ObjectType::Blob) {
+                if is_tracked(&format!("{}{}", dir, name)) && entry.kind() == Some(ObjectType::Blob) {
                     has = true;
                     return git2::TreeWalkResult::Abort;
                 }
@@ -171,58 +837,440 @@ fn has_schematic_changes(repo: &Repository, commit: &git2::Commit) -> Result<boo
     }
 }
 
-/// Check if a file is a KiCad file we need for distillation
-fn is_kicad_file(name: &str) -> bool {
-    name.ends_with(".kicad_sch") || name.ends_with(".kicad_pro")
+/// Check if a file is a KiCad file we need for distillation: a schematic, or
+/// a project file (.kicad_pro, or .pro for KiCad 4 and 5) naming the root
+fn is_kicad_file(files: &DesignFiles, path: &str) -> bool {
+    files.is_schematic(path) || path.ends_with(".kicad_pro") || path.ends_with(".pro")
 }
 
-/// Get all .kicad_sch and .kicad_pro files at a specific commit
-/// We need both: .kicad_sch for the actual schematics, and .kicad_pro to identify the root
+/// Get all schematic and project files at a specific commit
+/// We need both: schematics for the actual sheets, and project files to identify the root
 pub async fn get_schematic_files(repo_slug: &str, commit_hash: &str) -> Result<Vec<SchematicFile>> {
-    let repo = get_repo(repo_slug).await?;
+    get_files_matching(repo_slug, commit_hash, is_kicad_file).await
+}
+
+/// Check if a file is a symbol library (.kicad_sym, or a legacy .lib) or the
+/// table naming the project's libraries
+fn is_library_file(name: &str) -> bool {
+    name.ends_with(".kicad_sym") || name.ends_with(".lib") || name == "sym-lib-table"
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Check if a file is read to parse a project: schematics, project files and
+/// symbol libraries, going by the default schematic names
+pub fn is_project_file(name: &str) -> bool {
+    is_project_path(&DesignFiles::default(), name)
+}
+
+fn is_project_path(files: &DesignFiles, path: &str) -> bool {
+    is_kicad_file(files, path) || is_library_file(file_name(path))
+}
+
+/// Whether `path` is fetched from Git LFS when a commit only has its pointer:
+/// files read to parse projects, and layouts. Step models, gerbers and other
+/// large assets are left as pointers.
+fn needs_lfs(files: &DesignFiles, path: &str) -> bool {
+    is_project_path(files, path) || path.ends_with(".kicad_pcb")
+}
+
+/// Schematic files plus the symbol libraries and sym-lib-tables they may draw symbols from
+pub async fn get_project_files(repo_slug: &str, commit_hash: &str) -> Result<Vec<SchematicFile>> {
+    get_files_matching(repo_slug, commit_hash, is_project_path).await
+}
+
+/// Get all .kicad_pcb board files at a specific commit
+pub async fn get_board_files(repo_slug: &str, commit_hash: &str) -> Result<Vec<SchematicFile>> {
+    get_files_matching(repo_slug, commit_hash, |_, path| path.ends_with(".kicad_pcb")).await
+}
+
+/// Read every blob at a commit whose path passes `filter`
+///
+/// Only files within the repo's path filter are read, except symbol
+/// libraries, which projects may share from anywhere in the tree. Symlinks
+/// are followed within the commit; repos registered with submodules also
+/// get the symbol libraries of their submodules' pinned commits.
+async fn get_files_matching(
+    repo_slug: &str,
+    commit_hash: &str,
+    filter: fn(&DesignFiles, &str) -> bool,
+) -> Result<Vec<SchematicFile>> {
+    let (repo, _cache) = get_repo_at(repo_slug, commit_hash).await?;
     let commit_hash = commit_hash.to_string();
+    let remote = remote(repo_slug);
+    let design_files = DesignFiles::of(repo_slug);
+    let repo_url = remote.as_ref().map_or_else(|| repo_url(repo_slug), |r| r.clone_url.clone());
+    let read_submodules = remote.is_some_and(|r| r.submodules);
 
-    tokio::task::spawn_blocking(move || -> Result<Vec<SchematicFile>> {
+    let mut files = run_blocking("read_files", move || -> Result<Vec<SchematicFile>> {
         let obj = repo.revparse_single(&commit_hash)?;
         let commit = obj.peel_to_commit()?;
-        let tree = commit.tree()?;
+        let root = commit.tree()?;
+
+        let mut walk = FileWalk {
+            filter,
+            design_files: &design_files,
+            submodule_store: read_submodules.then(|| submodules::store(&repo)),
+            files: Vec::new(),
+        };
+        let mount = Mount {
+            repo: &repo,
+            pins: if read_submodules { submodules::pins(&repo, &root, &repo_url) } else { Vec::new() },
+            root,
+            libraries_only: false,
+        };
+        walk.walk_tree(&mount, &mount.root, "", "", 0)?;
+        Ok(walk.files)
+    })
+    .await?;
+    smudge_files(repo_slug, &mut files).await;
+    Ok(files)
+}
 
-        let mut files = Vec::new();
+/// Where the Git LFS objects of `repo_slug` are fetched from
+fn lfs_remote(repo_slug: &str) -> lfs::Remote {
+    let remote = remote(repo_slug);
+    lfs::Remote {
+        clone_url: remote.as_ref().map_or_else(|| repo_url(repo_slug), |r| r.clone_url.clone()),
+        token: token(remote.as_ref()),
+    }
+}
 
-        tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
-            if let Some(name) = entry.name() {
-                if is_kicad_file(name) && entry.kind() == Some(ObjectType::Blob) {
-                    let path = if dir.is_empty() {
-                        name.to_string()
-                    } else {
-                        format!("{}{}", dir, name)
-                    };
-
-                    if let Ok(obj) = entry.to_object(&repo) {
-                        if let Ok(blob) = obj.into_blob() {
-                            let content = String::from_utf8_lossy(blob.content()).to_string();
-                            files.push(SchematicFile { path, content });
+/// Replace files that are Git LFS pointers with the files they stand for;
+/// ones that can't be fetched are left as pointers, which fail to parse
+async fn smudge_files(repo_slug: &str, files: &mut [SchematicFile]) {
+    let remote = lfs_remote(repo_slug);
+    for file in files {
+        let Some(pointer) = lfs::Pointer::parse(file.content.as_bytes()) else {
+            continue;
+        };
+        match lfs::read(&remote, &pointer).await {
+            Ok(content) => file.content = String::from_utf8_lossy(&content).to_string(),
+            Err(e) => warn!("Leaving {} of {} as a Git LFS pointer: {:#}", file.path, repo_slug, e),
+        }
+    }
+}
+
+/// Submodules pinned at the head of the processed branch
+pub async fn get_submodule_pins(repo_slug: &str) -> Result<Vec<SubmodulePin>> {
+    let (repo, _cache) = get_repo(repo_slug).await?;
+    let repo_url = remote(repo_slug).map_or_else(|| repo_url(repo_slug), |r| r.clone_url);
+
+    run_blocking("submodule_pins", move || -> Result<Vec<SubmodulePin>> {
+        let tree = repo.head()?.peel_to_tree()?;
+        Ok(submodules::pins(&repo, &tree, &repo_url)
+            .into_iter()
+            .map(|pin| SubmodulePin {
+                path: pin.path,
+                url: pin.url,
+                commit: pin.commit.to_string(),
+            })
+            .collect())
+    })
+    .await
+}
+
+/// Git file mode of a symlink, whose blob holds the target path
+const SYMLINK_MODE: i32 = 0o120000;
+
+/// A commit's tree being read, either the repo's or a submodule's
+struct Mount<'r> {
+    repo: &'r Repository,
+    root: git2::Tree<'r>,
+    pins: Vec<submodules::Pin>,
+    /// Submodules only contribute symbol libraries
+    libraries_only: bool,
+}
+
+/// Files collected from a commit's tree
+struct FileWalk<'a> {
+    filter: fn(&DesignFiles, &str) -> bool,
+    design_files: &'a DesignFiles,
+    /// Where submodule clones are kept, if submodules are read
+    submodule_store: Option<PathBuf>,
+    files: Vec<SchematicFile>,
+}
+
+/// `name` inside directory `dir`, either of which may be empty
+fn join_tree_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+/// Where a symlink in `dir` points, or None if it leaves the tree
+fn resolve_symlink(dir: &str, target: &str) -> Option<String> {
+    if target.starts_with('/') {
+        return None;
+    }
+    let mut parts: Vec<&str> = dir.split('/').filter(|p| !p.is_empty()).collect();
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+impl FileWalk<'_> {
+    /// Read the files under `tree`, found at `rel` within `mount` and listed under `shown`
+    fn walk_tree(&mut self, mount: &Mount, tree: &git2::Tree, rel: &str, shown: &str, depth: u32) -> Result<()> {
+        for entry in tree.iter() {
+            let Some(name) = entry.name() else { continue };
+            self.visit(mount, &entry, &join_tree_path(rel, name), &join_tree_path(shown, name), name, depth)?;
+        }
+        Ok(())
+    }
+
+    /// Read one entry; `name` is what it is listed as, which for a symlink's
+    /// target is the link's name
+    fn visit(
+        &mut self,
+        mount: &Mount,
+        entry: &git2::TreeEntry,
+        rel: &str,
+        shown: &str,
+        name: &str,
+        depth: u32,
+    ) -> Result<()> {
+        match entry.kind() {
+            Some(ObjectType::Tree) => {
+                let tree = mount.repo.find_tree(entry.id())?;
+                self.walk_tree(mount, &tree, rel, shown, depth)
+            }
+            // Symlinked directories and submodules count towards the depth,
+            // which also stops symlink cycles
+            Some(ObjectType::Blob) if entry.filemode() == SYMLINK_MODE => {
+                if depth >= submodules::MAX_DEPTH {
+                    return Ok(());
+                }
+                let target = mount.repo.find_blob(entry.id())?;
+                let dir = rel.rsplit_once('/').map_or("", |(dir, _)| dir);
+                let Some(resolved) = resolve_symlink(dir, &String::from_utf8_lossy(target.content())) else {
+                    return Ok(());
+                };
+                if let Some(pin) = mount.pins.iter().find(|p| resolved.starts_with(&format!("{}/", p.path))) {
+                    let rest = resolved[pin.path.len() + 1..].to_string();
+                    return self.in_submodule(pin, |walk, submodule| {
+                        match submodule.root.get_path(std::path::Path::new(&rest)) {
+                            Ok(target) => walk.visit(submodule, &target, &rest, shown, name, depth + 1),
+                            Err(_) => Ok(()),
                         }
-                    }
+                    });
+                }
+                match mount.root.get_path(std::path::Path::new(&resolved)) {
+                    Ok(target) => self.visit(mount, &target, &resolved, shown, name, depth + 1),
+                    Err(_) => Ok(()),
                 }
             }
-            git2::TreeWalkResult::Ok
-        })?;
+            Some(ObjectType::Blob) => {
+                let wanted = (self.filter)(self.design_files, shown) && (!mount.libraries_only || is_library_file(name));
+                if !wanted || (!is_library_file(name) && !self.design_files.in_path_filter(shown)) {
+                    return Ok(());
+                }
+                if let Ok(blob) = mount.repo.find_blob(entry.id()) {
+                    let content = String::from_utf8_lossy(blob.content()).to_string();
+                    self.files.push(SchematicFile { path: shown.to_string(), content });
+                }
+                Ok(())
+            }
+            Some(ObjectType::Commit) if depth < submodules::MAX_DEPTH => {
+                let Some(pin) = mount.pins.iter().find(|p| p.path == rel) else {
+                    return Ok(());
+                };
+                self.in_submodule(pin, |walk, submodule| {
+                    walk.walk_tree(submodule, &submodule.root, "", shown, depth + 1)
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Run `f` on the commit `pin` points at, if submodules are read and it has been fetched
+    fn in_submodule(
+        &mut self,
+        pin: &submodules::Pin,
+        f: impl FnOnce(&mut Self, &Mount) -> Result<()>,
+    ) -> Result<()> {
+        let Some(repo) = self.submodule_store.as_deref().and_then(|store| submodules::open(store, &pin.url)) else {
+            return Ok(());
+        };
+        let Ok(root) = repo.find_commit(pin.commit).and_then(|commit| commit.tree()) else {
+            warn!("Submodule {} has not fetched its pinned commit {}", pin.path, pin.commit);
+            return Ok(());
+        };
+        let mount = Mount {
+            repo: &repo,
+            pins: submodules::pins(&repo, &root, &pin.url),
+            root,
+            libraries_only: true,
+        };
+        f(self, &mount)
+    }
+}
+
+/// A file read from a commit
+#[derive(Debug, Clone)]
+pub struct FileAtCommit {
+    /// Blob id, which changes exactly when the content does
+    pub oid: String,
+    pub size: u64,
+    /// None when the file is larger than the limit it was read with
+    pub content: Option<Vec<u8>>,
+}
+
+/// Read the file at `path` in `commit_hash`, or None if there is no file there
+///
+/// Files over `max_bytes` are not loaded; their size is still reported.
+/// Design files kept in Git LFS are read from LFS, with the size of the file
+/// rather than of its pointer.
+pub async fn get_file_at_commit(
+    repo_slug: &str,
+    commit_hash: &str,
+    path: &str,
+    max_bytes: u64,
+) -> Result<Option<FileAtCommit>> {
+    let (repo, _cache) = get_repo_at(repo_slug, commit_hash).await?;
+    let commit_hash = commit_hash.to_string();
+    let path = path.trim_start_matches('/').to_string();
+    let smudge = needs_lfs(&DesignFiles::of(repo_slug), &path).then(|| path.clone());
+
+    let file = run_blocking("read_file", move || -> Result<Option<FileAtCommit>> {
+        let tree = repo.revparse_single(&commit_hash)?.peel_to_commit()?.tree()?;
+        let entry = match tree.get_path(std::path::Path::new(&path)) {
+            Ok(entry) if entry.kind() == Some(ObjectType::Blob) => entry,
+            _ => return Ok(None),
+        };
+
+        let (size, _) = repo.odb()?.read_header(entry.id())?;
+        let size = size as u64;
+        let content = if size <= max_bytes {
+            Some(repo.find_blob(entry.id())?.content().to_vec())
+        } else {
+            None
+        };
+        Ok(Some(FileAtCommit {
+            oid: entry.id().to_string(),
+            size,
+            content,
+        }))
+    })
+    .await?;
+
+    let Some(mut file) = file else { return Ok(None) };
+    let pointer = file.content.as_deref().and_then(lfs::Pointer::parse);
+    if let (Some(pointer), Some(path)) = (pointer, smudge) {
+        if pointer.size > max_bytes {
+            file.size = pointer.size;
+            file.content = None;
+        } else {
+            match lfs::read(&lfs_remote(repo_slug), &pointer).await {
+                Ok(content) => {
+                    file.size = pointer.size;
+                    file.content = Some(content);
+                }
+                Err(e) => warn!("Serving {} of {} as a Git LFS pointer: {:#}", path, repo_slug, e),
+            }
+        }
+    }
+    Ok(Some(file))
+}
+
+/// Get the cache directory for materialized blobs
+fn get_blob_cache_path() -> PathBuf {
+    cache_dir().join("kicad-blob-cache")
+}
+
+/// Write the blob at `path` in `commit_hash` to disk and return its location
+///
+/// Blobs are content-addressed by object id, so a file that is already on disk
+/// is reused as-is. Loose objects are copied through a streaming ODB reader;
+/// packed objects (which libgit2 cannot stream) are written from the blob in
+/// one go on the blocking pool. Either way the async side only ever sees a
+/// path, which callers stream back with `file_stream::stream_file`. Design
+/// files kept in Git LFS are fetched into the LFS object cache instead.
+/// Returns `Ok(None)` if the path does not exist at that commit.
+pub async fn materialize_blob(
+    repo_slug: &str,
+    commit_hash: &str,
+    path: &str,
+) -> Result<Option<PathBuf>> {
+    let (repo, _cache) = get_repo_at(repo_slug, commit_hash).await?;
+    let commit_hash = commit_hash.to_string();
+    let path = path.to_string();
+    let smudge = needs_lfs(&DesignFiles::of(repo_slug), &path).then(|| path.clone());
 
-        Ok(files)
+    let blob = run_blocking("materialize_blob", move || -> Result<Option<PathBuf>> {
+        let obj = repo.revparse_single(&commit_hash)?;
+        let commit = obj.peel_to_commit()?;
+        let tree = commit.tree()?;
+
+        let entry = match tree.get_path(std::path::Path::new(&path)) {
+            Ok(entry) if entry.kind() == Some(ObjectType::Blob) => entry,
+            _ => return Ok(None),
+        };
+
+        let cache_dir = get_blob_cache_path();
+        std::fs::create_dir_all(&cache_dir)?;
+        let target = cache_dir.join(entry.id().to_string());
+        if target.exists() {
+            return Ok(Some(target));
+        }
+
+        // Write to a temp file in the same directory, then rename into place so
+        // concurrent readers never see a partially written blob
+        let mut tmp = tempfile::NamedTempFile::new_in(&cache_dir)?;
+        let odb = repo.odb()?;
+        match odb.reader(entry.id()) {
+            Ok((mut reader, _, _)) => {
+                std::io::copy(&mut reader, &mut tmp)?;
+            }
+            Err(_) => {
+                let blob = repo.find_blob(entry.id())?;
+                std::io::Write::write_all(&mut tmp, blob.content())?;
+            }
+        }
+        tmp.persist(&target)
+            .context("Failed to move blob into cache")?;
+
+        Ok(Some(target))
     })
-    .await?
+    .await?;
+
+    let Some(blob) = blob else { return Ok(None) };
+    let Some(path) = smudge else { return Ok(Some(blob)) };
+    match lfs::Pointer::in_file(&blob).await {
+        Some(pointer) => match lfs::fetch(&lfs_remote(repo_slug), &pointer).await {
+            Ok(object) => Ok(Some(object)),
+            Err(e) => {
+                warn!("Serving {} of {} as a Git LFS pointer: {:#}", path, repo_slug, e);
+                Ok(Some(blob))
+            }
+        },
+        None => Ok(Some(blob)),
+    }
 }
 
-/// Get changed .kicad_sch file paths for a specific commit
+/// Get changed schematic and .kicad_pcb file paths for a specific commit,
+/// within the repo's path filter
 pub async fn get_changed_schematic_files(
     repo_slug: &str,
     commit_hash: &str,
 ) -> Result<Vec<String>> {
-    let repo = get_repo(repo_slug).await?;
+    let (repo, _cache) = get_repo_with_parents(repo_slug, commit_hash).await?;
     let commit_hash = commit_hash.to_string();
+    let files = DesignFiles::of(repo_slug);
+    let is_tracked = move |path: &str| files.tracks(path);
 
-    tokio::task::spawn_blocking(move || -> Result<Vec<String>> {
+    run_blocking("diff", move || -> Result<Vec<String>> {
         let obj = repo.revparse_single(&commit_hash)?;
         let commit = obj.peel_to_commit()?;
 
@@ -235,12 +1283,12 @@ pub async fn get_changed_schematic_files(
 
             for delta in diff.deltas() {
                 if let Some(path) = delta.new_file().path().and_then(|p| p.to_str()) {
-                    if path.ends_with(".kicad_sch") {
+                    if is_tracked(path) {
                         changed_files.push(path.to_string());
                     }
                 }
                 if let Some(path) = delta.old_file().path().and_then(|p| p.to_str()) {
-                    if path.ends_with(".kicad_sch") && !changed_files.contains(&path.to_string()) {
+                    if is_tracked(path) && !changed_files.contains(&path.to_string()) {
                         changed_files.push(path.to_string());
                     }
                 }
@@ -250,12 +1298,8 @@ pub async fn get_changed_schematic_files(
             let tree = commit.tree()?;
             tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
                 if let Some(name) = entry.name() {
-                    if name.ends_with(".kicad_sch") && entry.kind() == Some(ObjectType::Blob) {
-                        let path = if dir.is_empty() {
-                            name.to_string()
-                        } else {
-                            format!("{}{}", dir, name)
-                        };
+                    let path = format!("{}{}", dir, name);
+                    if is_tracked(&path) && entry.kind() == Some(ObjectType::Blob) {
                         changed_files.push(path);
                     }
                 }
@@ -265,40 +1309,294 @@ pub async fn get_changed_schematic_files(
 
         Ok(changed_files)
     })
-    .await?
+    .await
 }
 
 /// Get commit info (date, message) for a specific commit
 pub async fn get_commit_info(repo_slug: &str, commit_hash: &str) -> Result<CommitInfo> {
-    let repo = get_repo(repo_slug).await?;
+    let (repo, _cache) = get_repo_with_parents(repo_slug, commit_hash).await?;
     let commit_hash = commit_hash.to_string();
+    let files = DesignFiles::of(repo_slug);
 
-    tokio::task::spawn_blocking(move || -> Result<CommitInfo> {
+    run_blocking("commit_info", move || -> Result<CommitInfo> {
         let obj = repo.revparse_single(&commit_hash)?;
         let commit = obj.peel_to_commit()?;
 
         let commit_date = Utc.timestamp_opt(commit.time().seconds(), 0).single();
 
-        let has_changes = has_schematic_changes(&repo, &commit)?;
+        let has_changes = has_schematic_changes(&repo, &commit, &files)?;
+        let author = commit.author();
+        let tags = tags_by_commit(&repo)?.remove(&commit.id()).unwrap_or_default();
 
         Ok(CommitInfo {
             commit_hash: commit.id().to_string(),
             commit_date,
             message: commit.summary().map(ToString::to_string),
+            author: author.name().map(ToString::to_string),
+            author_email: author.email().map(ToString::to_string),
+            tags,
             has_schematic_changes: has_changes,
         })
     })
-    .await?
+    .await
 }
 
 /// Get the latest commit hash on the default branch
 pub async fn get_latest_commit(repo_slug: &str) -> Result<String> {
-    let repo = get_repo(repo_slug).await?;
+    let (repo, _cache) = get_repo(repo_slug).await?;
 
-    tokio::task::spawn_blocking(move || -> Result<String> {
+    run_blocking("latest_commit", move || -> Result<String> {
         let head = repo.head()?;
         let commit = head.peel_to_commit()?;
         Ok(commit.id().to_string())
     })
-    .await?
+    .await
+}
+
+/// The branch the cached clone follows when the repo sets none: the
+/// remote's HEAD, else main or master. None if it has neither
+pub async fn head_branch(repo_slug: &str) -> Result<Option<String>> {
+    let (repo, _cache) = get_repo(repo_slug).await?;
+
+    run_blocking("head_branch", move || -> Result<Option<String>> {
+        let remote_head = repo
+            .find_reference("refs/remotes/origin/HEAD")
+            .ok()
+            .and_then(|head| head.symbolic_target().map(str::to_string));
+        let branch = remote_head
+            .or_else(|| {
+                ["main", "master"]
+                    .iter()
+                    .map(|name| format!("refs/remotes/origin/{}", name))
+                    .find(|name| repo.find_reference(name).is_ok())
+            })
+            .and_then(|name| name.strip_prefix("refs/remotes/origin/").map(str::to_string));
+        Ok(branch)
+    })
+    .await
+}
+
+/// The branch updates process, as its high-water mark is recorded under,
+/// and the commit at its tip: the repo's configured branch, else the
+/// remote's default
+pub async fn processed_branch(repo_slug: &str) -> Result<(String, String)> {
+    let branch = match remote(repo_slug).and_then(|r| r.branch) {
+        Some(branch) => branch,
+        None => head_branch(repo_slug).await?.unwrap_or_else(|| "HEAD".to_string()),
+    };
+    Ok((branch, get_latest_commit(repo_slug).await?))
+}
+
+/// Whether the cached clone has `commit_hash`, which a force push may have
+/// rewritten away
+pub async fn contains_commit(repo_slug: &str, commit_hash: &str) -> Result<bool> {
+    let (repo, _cache) = get_repo(repo_slug).await?;
+    let commit_hash = commit_hash.to_string();
+
+    run_blocking("contains_commit", move || -> Result<bool> {
+        let Ok(oid) = git2::Oid::from_str(&commit_hash) else {
+            return Ok(false);
+        };
+        Ok(repo.find_commit(oid).is_ok())
+    })
+    .await
+}
+
+/// Why a commit reference didn't name exactly one commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefError {
+    /// No commit, branch or tag by that name
+    NotFound { reference: String },
+    /// Several commits answer to the name, described e.g. as "branch main (1a2b3c4d5e6f)"
+    Ambiguous { reference: String, candidates: Vec<String> },
+}
+
+impl std::fmt::Display for RefError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RefError::NotFound { reference } => write!(f, "no commit, branch or tag '{}'", reference),
+            RefError::Ambiguous { reference, candidates } => {
+                write!(f, "'{}' is ambiguous: it could be {}", reference, candidates.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for RefError {}
+
+/// Most candidates listed for an ambiguous short SHA
+const MAX_AMBIGUOUS_CANDIDATES: usize = 10;
+
+fn is_full_sha(reference: &str) -> bool {
+    reference.len() == 40 && reference.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Expand a commit SHA (full or abbreviated), branch or tag to a full commit hash
+///
+/// Full SHAs are returned as they are, without touching the repository.
+/// Anything else is looked up in a fresh fetch: as a SHA prefix, a branch on
+/// the remote and a tag. When those name different commits, or a short SHA
+/// matches several, the error is a [`RefError::Ambiguous`] listing them;
+/// when none match, a [`RefError::NotFound`].
+pub async fn resolve_ref(repo_slug: &str, reference: &str) -> Result<String> {
+    let reference = reference.trim();
+    if is_full_sha(reference) {
+        return Ok(reference.to_ascii_lowercase());
+    }
+    let (repo, _cache) = get_repo_at(repo_slug, reference).await?;
+    let reference = reference.to_string();
+
+    run_blocking("resolve_ref", move || -> Result<String> {
+        // Each commit found, with the branch or tag that named it (None for a SHA)
+        let mut found: Vec<(git2::Oid, Option<String>)> = Vec::new();
+        let mut add = |oid: git2::Oid, via: Option<String>| {
+            if !found.iter().any(|(seen, _)| *seen == oid) {
+                found.push((oid, via));
+            }
+        };
+
+        if (4..40).contains(&reference.len()) && reference.chars().all(|c| c.is_ascii_hexdigit()) {
+            match repo.find_commit_by_prefix(&reference) {
+                Ok(commit) => add(commit.id(), None),
+                Err(e) if e.code() == git2::ErrorCode::Ambiguous => {
+                    let prefix = reference.to_ascii_lowercase();
+                    let mut matches = Vec::new();
+                    repo.odb()?.foreach(|oid| {
+                        if oid.to_string().starts_with(&prefix)
+                            && repo.find_commit(*oid).is_ok()
+                        {
+                            matches.push(*oid);
+                        }
+                        matches.len() < MAX_AMBIGUOUS_CANDIDATES
+                    })?;
+                    for oid in matches {
+                        add(oid, None);
+                    }
+                }
+                Err(_) => {}
+            }
+        }
+
+        let names = [
+            (format!("refs/remotes/origin/{}", reference), "branch"),
+            (format!("refs/heads/{}", reference), "branch"),
+            (format!("refs/tags/{}", reference), "tag"),
+        ];
+        for (name, kind) in names {
+            if let Ok(commit) = repo.find_reference(&name).and_then(|r| r.peel_to_commit()) {
+                add(commit.id(), Some(format!("{} {}", kind, reference)));
+            }
+        }
+
+        match found.as_slice() {
+            [] => Err(RefError::NotFound { reference }.into()),
+            [(oid, _)] => Ok(oid.to_string()),
+            _ => Err(RefError::Ambiguous {
+                candidates: found
+                    .iter()
+                    .map(|(oid, via)| match via {
+                        Some(via) => format!("{} ({})", via, &oid.to_string()[..12]),
+                        None => format!("commit {}", &oid.to_string()[..12]),
+                    })
+                    .collect(),
+                reference,
+            }
+            .into()),
+        }
+    })
+    .await
+}
+
+/// Commits first shipped in one release
+#[derive(Debug, Clone)]
+pub struct ReleaseCommits {
+    /// Tag name; `None` for commits after the newest tag
+    pub tag: Option<String>,
+    /// Date of the tagged commit
+    pub date: Option<DateTime<Utc>>,
+    /// Newest first
+    pub commits: Vec<String>,
+}
+
+/// Group the history into releases by tag, newest release first
+///
+/// Each tag gets the commits it contains that no older tag does; commits on
+/// HEAD not contained in any tag come first as the unreleased group.
+pub async fn get_releases(repo_slug: &str) -> Result<Vec<ReleaseCommits>> {
+    let (repo, _cache) = get_repo(repo_slug).await?;
+
+    run_blocking("releases", move || -> Result<Vec<ReleaseCommits>> {
+        let mut tags = Vec::new();
+        for name in repo.tag_names(None)?.iter().flatten() {
+            let reference = repo.find_reference(&format!("refs/tags/{}", name))?;
+            if let Ok(commit) = reference.peel_to_commit() {
+                tags.push((name.to_string(), commit.id(), commit.time().seconds()));
+            }
+        }
+        tags.sort_by_key(|(_, _, time)| *time);
+
+        let walk = |tip: Option<git2::Oid>, hide: &[(String, git2::Oid, i64)]| -> Result<Vec<String>> {
+            let mut revwalk = repo.revwalk()?;
+            revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+            match tip {
+                Some(oid) => revwalk.push(oid)?,
+                None => revwalk.push_head()?,
+            }
+            for (_, oid, _) in hide {
+                revwalk.hide(*oid)?;
+            }
+            revwalk
+                .map(|oid| Ok(oid?.to_string()))
+                .collect()
+        };
+
+        let mut releases = vec![ReleaseCommits {
+            tag: None,
+            date: None,
+            commits: walk(None, &tags)?,
+        }];
+        for (idx, (name, oid, time)) in tags.iter().enumerate().rev() {
+            releases.push(ReleaseCommits {
+                tag: Some(name.clone()),
+                date: Utc.timestamp_opt(*time, 0).single(),
+                commits: walk(Some(*oid), &tags[..idx])?,
+            });
+        }
+        Ok(releases)
+    })
+    .await
+}
+
+/// Commits reachable from `head` but not from `base`, oldest first, as
+/// (hash, summary line)
+pub async fn get_commits_between(repo_slug: &str, base: &str, head: &str) -> Result<Vec<(String, Option<String>)>> {
+    let (repo, _cache) = get_repo(repo_slug).await?;
+    let (base, head) = (base.to_string(), head.to_string());
+
+    run_blocking("log", move || -> Result<Vec<(String, Option<String>)>> {
+        let mut revwalk = repo.revwalk()?;
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME | git2::Sort::REVERSE)?;
+        revwalk.push(repo.revparse_single(&head)?.peel_to_commit()?.id())?;
+        revwalk.hide(repo.revparse_single(&base)?.peel_to_commit()?.id())?;
+        revwalk
+            .map(|oid| {
+                let commit = repo.find_commit(oid?)?;
+                Ok((commit.id().to_string(), commit.summary().map(ToString::to_string)))
+            })
+            .collect()
+    })
+    .await
+}
+
+/// Get the first parent of a commit, or None for a root commit
+pub async fn get_parent_commit(repo_slug: &str, commit_hash: &str) -> Result<Option<String>> {
+    let (repo, _cache) = get_repo_with_parents(repo_slug, commit_hash).await?;
+    let commit_hash = commit_hash.to_string();
+
+    run_blocking("parent_commit", move || -> Result<Option<String>> {
+        let obj = repo.revparse_single(&commit_hash)?;
+        let commit = obj.peel_to_commit()?;
+        Ok(commit.parent_ids().next().map(|id| id.to_string()))
+    })
+    .await
 }
@@ -0,0 +1,420 @@
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use once_cell::sync::Lazy;
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use serde_json::{json, Value};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::config::GitHubConfig;
+
+/// Token and API base set with [`configure`]. The token pushes bot branches
+/// and opens pull requests (needs contents and pull-request write access),
+/// links commits to their pull requests, and onboards orgs (needs webhook
+/// admin access); all of that is off without one.
+static SETTINGS: Lazy<RwLock<GitHubConfig>> = Lazy::new(Default::default);
+
+/// Use the configured token and API base; called at startup and on each reload
+pub fn configure(github: &GitHubConfig) {
+    *SETTINGS.write().unwrap() = github.clone();
+}
+
+static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .timeout(Duration::from_secs(30))
+        .user_agent("kicad-watch")
+        .build()
+        .expect("Failed to create HTTP client")
+});
+
+pub fn is_configured() -> bool {
+    !SETTINGS.read().unwrap().token.is_empty()
+}
+
+fn request(method: Method, path: &str) -> Result<RequestBuilder> {
+    let settings = SETTINGS.read().unwrap();
+    if settings.token.is_empty() {
+        bail!("GITHUB_TOKEN is not configured");
+    }
+    Ok(HTTP_CLIENT
+        .request(method, format!("{}{}", settings.api_url, path))
+        .bearer_auth(&settings.token)
+        .header("Accept", "application/vnd.github+json")
+        .header("X-GitHub-Api-Version", "2022-11-28"))
+}
+
+/// Send a request, returning `None` on 404 and the JSON body otherwise
+async fn send(builder: RequestBuilder) -> Result<Option<Value>> {
+    let response = builder.send().await.context("GitHub request failed")?;
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let body = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        bail!("GitHub API returned {}: {}", status, body);
+    }
+    Ok(Some(serde_json::from_str(&body).unwrap_or(Value::Null)))
+}
+
+/// Whether `repo_slug` names a repository on GitHub rather than another host
+fn is_github(repo_slug: &str) -> bool {
+    repo_slug.split('/').next().is_some_and(|owner| !owner.contains('.'))
+}
+
+/// Number of the pull request that introduced `sha`, preferring a merged one
+///
+/// None when the commit was pushed directly or the repo isn't on GitHub.
+pub async fn pull_request_for_commit(repo_slug: &str, sha: &str) -> Result<Option<i64>> {
+    if !is_github(repo_slug) {
+        return Ok(None);
+    }
+    let pulls = send(request(
+        Method::GET,
+        &format!("/repos/{}/commits/{}/pulls", repo_slug, sha),
+    )?)
+    .await?
+    .unwrap_or(Value::Null);
+    let pulls = pulls.as_array().map(Vec::as_slice).unwrap_or_default();
+    Ok(pulls
+        .iter()
+        .find(|pr| !pr["merged_at"].is_null())
+        .or_else(|| pulls.first())
+        .and_then(|pr| pr["number"].as_i64()))
+}
+
+/// A file's text and blob sha on a branch, if it exists
+async fn get_file(repo_slug: &str, path: &str, branch: &str) -> Result<Option<(String, String)>> {
+    let Some(file) = send(
+        request(Method::GET, &format!("/repos/{}/contents/{}", repo_slug, path))?
+            .query(&[("ref", branch)]),
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let encoded: String = file["content"]
+        .as_str()
+        .unwrap_or("")
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    let content = BASE64
+        .decode(encoded)
+        .context("GitHub returned invalid base64 file content")?;
+    let sha = file["sha"].as_str().unwrap_or("").to_string();
+    Ok(Some((String::from_utf8_lossy(&content).into_owned(), sha)))
+}
+
+/// Point `branch` at `sha`, creating it or force-moving it
+async fn reset_branch(repo_slug: &str, branch: &str, sha: &str) -> Result<()> {
+    let existing = send(request(
+        Method::GET,
+        &format!("/repos/{}/git/ref/heads/{}", repo_slug, branch),
+    )?)
+    .await?;
+
+    if existing.is_some() {
+        send(
+            request(Method::PATCH, &format!("/repos/{}/git/refs/heads/{}", repo_slug, branch))?
+                .json(&json!({ "sha": sha, "force": true })),
+        )
+        .await?;
+    } else {
+        send(
+            request(Method::POST, &format!("/repos/{}/git/refs", repo_slug))?
+                .json(&json!({ "ref": format!("refs/heads/{}", branch), "sha": sha })),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// URL of the open pull request from `branch`, opening one into `base` if there is none
+async fn open_pull_request(repo_slug: &str, branch: &str, base: &str, title: &str, body: &str) -> Result<String> {
+    let owner = repo_slug.split('/').next().unwrap_or_default();
+    let open = send(
+        request(Method::GET, &format!("/repos/{}/pulls", repo_slug))?
+            .query(&[("state", "open"), ("head", &format!("{}:{}", owner, branch))]),
+    )
+    .await?
+    .unwrap_or(Value::Null);
+    if let Some(url) = open[0]["html_url"].as_str() {
+        return Ok(url.to_string());
+    }
+
+    let created = send(
+        request(Method::POST, &format!("/repos/{}/pulls", repo_slug))?.json(&json!({
+            "title": title,
+            "head": branch,
+            "base": base,
+            "body": body,
+        })),
+    )
+    .await?
+    .context("Failed to open pull request")?;
+    created["html_url"]
+        .as_str()
+        .map(str::to_string)
+        .context("GitHub returned no pull request URL")
+}
+
+/// A file to propose through a bot branch and pull request
+pub struct FileProposal<'a> {
+    pub path: &'a str,
+    pub content: &'a str,
+    pub branch: &'a str,
+    pub commit_message: &'a str,
+    pub title: &'a str,
+    pub body: &'a str,
+}
+
+/// Propose `content` for `path` on the default branch via a pull request from `branch`
+///
+/// The bot branch is reset to the default branch each time, so it always holds
+/// exactly one regenerated commit. An already open pull request from the
+/// branch is reused. Returns the pull request URL, or `None` if the default
+/// branch already has this content.
+pub async fn propose_file(repo_slug: &str, proposal: &FileProposal<'_>) -> Result<Option<String>> {
+    if !is_github(repo_slug) {
+        bail!("{} is not a GitHub repository", repo_slug);
+    }
+
+    let repo = send(request(Method::GET, &format!("/repos/{}", repo_slug))?)
+        .await?
+        .with_context(|| format!("GitHub repository {} not found", repo_slug))?;
+    let base = repo["default_branch"]
+        .as_str()
+        .context("GitHub repository has no default branch")?
+        .to_string();
+
+    let existing = get_file(repo_slug, proposal.path, &base).await?;
+    if existing.as_ref().is_some_and(|(content, _)| content == proposal.content) {
+        return Ok(None);
+    }
+
+    let head = send(request(
+        Method::GET,
+        &format!("/repos/{}/git/ref/heads/{}", repo_slug, base),
+    )?)
+    .await?
+    .context("Default branch not found")?;
+    let base_sha = head["object"]["sha"]
+        .as_str()
+        .context("Default branch ref has no sha")?
+        .to_string();
+
+    reset_branch(repo_slug, proposal.branch, &base_sha).await?;
+
+    let mut put = json!({
+        "message": proposal.commit_message,
+        "content": BASE64.encode(proposal.content),
+        "branch": proposal.branch,
+    });
+    if let Some((_, sha)) = &existing {
+        put["sha"] = json!(sha);
+    }
+    send(
+        request(Method::PUT, &format!("/repos/{}/contents/{}", repo_slug, proposal.path))?
+            .json(&put),
+    )
+    .await?;
+
+    open_pull_request(repo_slug, proposal.branch, &base, proposal.title, proposal.body)
+        .await
+        .map(Some)
+}
+
+/// Edited files to propose through a bot branch and pull request
+pub struct FilesProposal<'a> {
+    /// Commit the edits were made to, which the branch's commit goes on top of
+    pub base_commit: &'a str,
+    /// Paths relative to the repository root, with their new content
+    pub files: &'a [(String, String)],
+    pub branch: &'a str,
+    pub commit_message: &'a str,
+    pub title: &'a str,
+    pub body: &'a str,
+}
+
+/// Commit `files` on top of `base_commit` and propose them on the default
+/// branch via a pull request from `branch`
+///
+/// The branch is reset to the one new commit each time, and an already
+/// open pull request from it is reused. Returns the pull request URL.
+pub async fn propose_files(repo_slug: &str, proposal: &FilesProposal<'_>) -> Result<String> {
+    if !is_github(repo_slug) {
+        bail!("{} is not a GitHub repository", repo_slug);
+    }
+
+    let repo = send(request(Method::GET, &format!("/repos/{}", repo_slug))?)
+        .await?
+        .with_context(|| format!("GitHub repository {} not found", repo_slug))?;
+    let base = repo["default_branch"]
+        .as_str()
+        .context("GitHub repository has no default branch")?
+        .to_string();
+
+    let parent = send(request(
+        Method::GET,
+        &format!("/repos/{}/git/commits/{}", repo_slug, proposal.base_commit),
+    )?)
+    .await?
+    .with_context(|| format!("Commit {} is not on GitHub", proposal.base_commit))?;
+    let base_tree = parent["tree"]["sha"].as_str().context("GitHub commit has no tree")?;
+
+    let entries: Vec<Value> = proposal
+        .files
+        .iter()
+        .map(|(path, content)| json!({ "path": path, "mode": "100644", "type": "blob", "content": content }))
+        .collect();
+    let tree = send(
+        request(Method::POST, &format!("/repos/{}/git/trees", repo_slug))?
+            .json(&json!({ "base_tree": base_tree, "tree": entries })),
+    )
+    .await?
+    .context("Failed to create the tree")?;
+    let commit = send(
+        request(Method::POST, &format!("/repos/{}/git/commits", repo_slug))?.json(&json!({
+            "message": proposal.commit_message,
+            "tree": tree["sha"],
+            "parents": [proposal.base_commit],
+        })),
+    )
+    .await?
+    .context("Failed to create the commit")?;
+    let sha = commit["sha"].as_str().context("GitHub commit has no sha")?;
+
+    reset_branch(repo_slug, proposal.branch, sha).await?;
+
+    open_pull_request(repo_slug, proposal.branch, &base, proposal.title, proposal.body).await
+}
+
+/// Most pages of an org's repositories listed, 100 repos each
+const MAX_REPO_PAGES: u32 = 50;
+
+/// A repository of a GitHub org, as the repos API lists it
+#[derive(Debug, Clone)]
+pub struct OrgRepo {
+    /// "owner/repo"
+    pub full_name: String,
+    pub clone_url: String,
+    /// None for an empty repository
+    pub default_branch: Option<String>,
+    pub private: bool,
+    pub fork: bool,
+    pub archived: bool,
+    /// Size in KiB; 0 for an empty repository
+    pub size: u64,
+}
+
+/// Every repository of `org` the token can see, or `None` if there's no such org
+pub async fn list_org_repos(org: &str) -> Result<Option<Vec<OrgRepo>>> {
+    let mut repos = Vec::new();
+    for page in 1..=MAX_REPO_PAGES {
+        let Some(listed) = send(
+            request(Method::GET, &format!("/orgs/{}/repos", org))?
+                .query(&[("type", "all"), ("per_page", "100"), ("page", &page.to_string())]),
+        )
+        .await?
+        else {
+            return Ok(None);
+        };
+        let listed = listed.as_array().map(Vec::as_slice).unwrap_or_default();
+        repos.extend(listed.iter().filter_map(|repo| {
+            Some(OrgRepo {
+                full_name: repo["full_name"].as_str()?.to_string(),
+                clone_url: repo["clone_url"].as_str()?.to_string(),
+                default_branch: repo["default_branch"].as_str().map(str::to_string),
+                private: repo["private"].as_bool().unwrap_or(false),
+                fork: repo["fork"].as_bool().unwrap_or(false),
+                archived: repo["archived"].as_bool().unwrap_or(false),
+                size: repo["size"].as_u64().unwrap_or(0),
+            })
+        }));
+        if listed.len() < 100 {
+            return Ok(Some(repos));
+        }
+    }
+    bail!("GitHub org {} has more than {} repositories", org, MAX_REPO_PAGES * 100)
+}
+
+/// What a scan of a branch's file tree found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KicadScan {
+    Found,
+    NotFound,
+    /// GitHub cut the tree short without a KiCad file in the part it sent
+    Truncated,
+}
+
+/// Whether `branch` of `repo_slug` holds a KiCad project, schematic or
+/// layout, by the names in its file tree
+///
+/// Legacy (KiCad 4/5) projects count by their .pro file next to a .sch of
+/// the same name, as other tools use .sch too.
+pub async fn scan_for_kicad_files(repo_slug: &str, branch: &str) -> Result<KicadScan> {
+    let Some(tree) = send(
+        request(Method::GET, &format!("/repos/{}/git/trees/{}", repo_slug, branch))?
+            .query(&[("recursive", "1")]),
+    )
+    .await?
+    else {
+        return Ok(KicadScan::NotFound);
+    };
+    let paths: Vec<&str> = tree["tree"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter(|entry| entry["type"] == "blob")
+        .filter_map(|entry| entry["path"].as_str())
+        .collect();
+    let found = paths.iter().any(|path| {
+        [".kicad_pro", ".kicad_sch", ".kicad_pcb"].iter().any(|ext| path.ends_with(ext))
+            || path
+                .strip_suffix(".pro")
+                .is_some_and(|stem| paths.contains(&format!("{}.sch", stem).as_str()))
+    });
+    Ok(match (found, tree["truncated"].as_bool().unwrap_or(false)) {
+        (true, _) => KicadScan::Found,
+        (false, true) => KicadScan::Truncated,
+        (false, false) => KicadScan::NotFound,
+    })
+}
+
+/// Have `repo_slug` send push events to `url`, signed with `secret`
+///
+/// A hook already pointing at `url` is updated in place rather than a second
+/// one added. Returns whether a new hook was created.
+pub async fn install_webhook(repo_slug: &str, url: &str, secret: &str) -> Result<bool> {
+    let hooks = send(request(Method::GET, &format!("/repos/{}/hooks", repo_slug))?)
+        .await?
+        .with_context(|| format!("Can't list the webhooks of {}; the token needs admin:repo_hook", repo_slug))?;
+    let existing = hooks
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .find(|hook| hook["config"]["url"] == url)
+        .and_then(|hook| hook["id"].as_i64());
+    let mut hook = json!({
+        "active": true,
+        "events": ["push"],
+        "config": { "url": url, "content_type": "json", "secret": secret, "insecure_ssl": "0" },
+    });
+    match existing {
+        Some(id) => {
+            send(request(Method::PATCH, &format!("/repos/{}/hooks/{}", repo_slug, id))?.json(&hook)).await?;
+            Ok(false)
+        }
+        None => {
+            hook["name"] = json!("web");
+            send(request(Method::POST, &format!("/repos/{}/hooks", repo_slug))?.json(&hook))
+                .await?
+                .with_context(|| format!("Can't add a webhook to {}", repo_slug))?;
+            Ok(true)
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use kicad_db::{repo_design_guidelines, GuidelinePrompt, PgPool};
+use tracing::{info, warn};
+
+use super::git;
+use crate::config::Config;
+
+/// The design guidelines block for `repo`'s prompts: its own guidelines,
+/// then its org's, within `guidelines_max_chars`
+///
+/// Lookup failures only lose the guidelines, as summaries and chat work
+/// without them.
+pub async fn for_repo(pool: &PgPool, config: &Config, repo: &str) -> Option<GuidelinePrompt> {
+    if config.guidelines_max_chars == 0 {
+        return None;
+    }
+    let guidelines = match repo_design_guidelines(pool, &git::repo_url(repo)).await {
+        Ok(guidelines) => guidelines,
+        Err(e) => {
+            warn!("Failed to load design guidelines for {}: {}", repo, e);
+            return None;
+        }
+    };
+    let prompt = GuidelinePrompt::build(&guidelines, config.guidelines_max_chars)?;
+    if prompt.truncated || prompt.omitted > 0 {
+        info!(
+            "Design guidelines of {} exceed {} chars: {} included{}, {} left out",
+            repo,
+            config.guidelines_max_chars,
+            prompt.included,
+            if prompt.truncated { " (the last cut short)" } else { "" },
+            prompt.omitted
+        );
+    }
+    Some(prompt)
+}
@@ -0,0 +1,113 @@
+use kicad_db::SchematicStore;
+use once_cell::sync::Lazy;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::services::git;
+use crate::types::DependencyStatus;
+
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+const XAI_TIMEOUT_SECS: u64 = 5;
+
+static XAI_CHECK: Lazy<Mutex<Option<(Instant, DependencyStatus)>>> = Lazy::new(Default::default);
+
+fn status(name: &str, started: Instant, result: Result<String, String>) -> DependencyStatus {
+    let (ok, detail) = match result {
+        Ok(detail) => (true, detail),
+        Err(detail) => (false, detail),
+    };
+    DependencyStatus {
+        name: name.to_string(),
+        ok,
+        latency_ms: started.elapsed().as_millis() as u64,
+        detail,
+        cached: false,
+    }
+}
+
+/// Round-trip a trivial query to the database schematics are stored in
+pub async fn check_database(store: &dyn SchematicStore) -> DependencyStatus {
+    let started = Instant::now();
+    let result = match tokio::time::timeout(DATABASE_TIMEOUT, store.ping()).await {
+        Ok(Ok(detail)) => Ok(format!("{}: {}", store.name(), detail)),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("no response within {}s", DATABASE_TIMEOUT.as_secs())),
+    };
+    status("database", started, result)
+}
+
+/// Free space on the volume holding the git clone cache
+pub fn check_disk(config: &Config) -> DependencyStatus {
+    check_disk_at(&git::cache_dir(), config.health.min_free_disk_mb)
+}
+
+/// Free space on the volume holding `dir`, at least `min_free_mb`
+pub fn check_disk_at(dir: &Path, min_free_mb: u64) -> DependencyStatus {
+    let started = Instant::now();
+    let result = fs2::available_space(dir)
+        .map_err(|e| format!("cannot stat {}: {}", dir.display(), e))
+        .and_then(|bytes| {
+            let free_mb = bytes / (1024 * 1024);
+            let detail = format!("{} MB free in {}", free_mb, dir.display());
+            if free_mb >= min_free_mb {
+                Ok(detail)
+            } else {
+                Err(format!("{} (minimum {} MB)", detail, min_free_mb))
+            }
+        });
+    status("git_cache_disk", started, result)
+}
+
+/// List models with the configured key, reusing a result younger than
+/// `health.xai_cache_secs`, so probes don't hit the API every few seconds
+pub async fn check_xai(config: &Config) -> DependencyStatus {
+    if let Some((at, cached)) = XAI_CHECK.lock().unwrap().as_ref() {
+        if at.elapsed() < Duration::from_secs(config.health.xai_cache_secs) {
+            return DependencyStatus {
+                cached: true,
+                ..cached.clone()
+            };
+        }
+    }
+
+    let checked = probe_xai(config).await;
+    *XAI_CHECK.lock().unwrap() = Some((Instant::now(), checked.clone()));
+    checked
+}
+
+/// List models with the configured key
+pub async fn probe_xai(config: &Config) -> DependencyStatus {
+    let started = Instant::now();
+    let result = match config.xai_client_with_timeout(XAI_TIMEOUT_SECS) {
+        Ok(client) => client
+            .list_models()
+            .await
+            .map(|models| format!("{} models available", models.len()))
+            .map_err(|e| with_causes(&e)),
+        Err(e) => Err(e.to_string()),
+    };
+    status("xai", started, result)
+}
+
+/// `err` followed by its causes, e.g. the connection error behind a failed
+/// request, leaving out those whose message an earlier one already includes
+fn with_causes(err: &dyn std::error::Error) -> String {
+    let mut text = err.to_string();
+    let mut cause = err.source();
+    while let Some(e) = cause {
+        let message = e.to_string();
+        if !text.contains(&message) {
+            text = format!("{}: {}", text, message);
+        }
+        cause = e.source();
+    }
+    text
+}
+
+/// Every dependency the service needs to do useful work, checked concurrently
+pub async fn check_all(store: &dyn SchematicStore, config: &Config) -> Vec<DependencyStatus> {
+    let (database, xai) = tokio::join!(check_database(store), check_xai(config));
+    vec![database, check_disk(config), xai]
+}
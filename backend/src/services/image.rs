@@ -0,0 +1,99 @@
+use anyhow::{anyhow, Context, Result};
+use image::{imageops::FilterType, ImageFormat};
+use once_cell::sync::Lazy;
+use resvg::{tiny_skia, usvg};
+use std::io::Cursor;
+use std::sync::Arc;
+
+/// Narrowest thumbnail served, in pixels
+pub const MIN_THUMBNAIL_WIDTH: u32 = 16;
+/// Widest thumbnail served, in pixels
+pub const MAX_THUMBNAIL_WIDTH: u32 = 4096;
+
+/// Fonts for text in SVGs, loaded once
+static FONTS: Lazy<Arc<usvg::fontdb::Database>> = Lazy::new(|| {
+    let mut fonts = usvg::fontdb::Database::new();
+    fonts.load_system_fonts();
+    Arc::new(fonts)
+});
+
+/// MIME type of a stored schematic image, from its leading bytes
+pub fn content_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some("image/png");
+    }
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Some("image/jpeg");
+    }
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]);
+    let head = head.trim_start();
+    if head.starts_with("<svg") || (head.starts_with("<?xml") && head.contains("<svg")) {
+        return Some("image/svg+xml");
+    }
+    None
+}
+
+/// Scale a PNG or JPEG down to `width` pixels wide, keeping its aspect ratio
+/// and format
+///
+/// Returns None for images already that narrow, and for SVGs, which scale in
+/// the browser.
+pub fn thumbnail(bytes: &[u8], width: u32) -> Result<Option<Vec<u8>>> {
+    let format = match content_type(bytes) {
+        Some("image/png") => ImageFormat::Png,
+        Some("image/jpeg") => ImageFormat::Jpeg,
+        _ => return Ok(None),
+    };
+    let original = image::load_from_memory_with_format(bytes, format).context("Failed to decode image")?;
+    if original.width() <= width {
+        return Ok(None);
+    }
+    let height = (u64::from(original.height()) * u64::from(width) / u64::from(original.width())).max(1) as u32;
+    let resized = original.resize_exact(width, height, FilterType::Triangle);
+
+    let mut out = Cursor::new(Vec::new());
+    match format {
+        // JPEG has no alpha channel
+        ImageFormat::Jpeg => resized.to_rgb8().write_to(&mut out, format),
+        _ => resized.write_to(&mut out, format),
+    }
+    .context("Failed to encode thumbnail")?;
+    Ok(Some(out.into_inner()))
+}
+
+/// Render a PNG, JPEG or SVG as a PNG `width` pixels wide (never wider than a
+/// raster original), keeping its aspect ratio
+///
+/// SVGs are rasterized onto white, as KiCad draws schematics.
+pub fn png_thumbnail(bytes: &[u8], width: u32) -> Result<Vec<u8>> {
+    let format = match content_type(bytes) {
+        Some("image/svg+xml") => return rasterize_svg(bytes, width),
+        Some("image/png") => ImageFormat::Png,
+        Some("image/jpeg") => ImageFormat::Jpeg,
+        _ => return Err(anyhow!("Not a PNG, JPEG or SVG image")),
+    };
+    let mut image = image::load_from_memory_with_format(bytes, format).context("Failed to decode image")?;
+    if image.width() > width {
+        image = image.resize(width, u32::MAX, FilterType::Triangle);
+    }
+    let mut out = Cursor::new(Vec::new());
+    image.write_to(&mut out, ImageFormat::Png).context("Failed to encode thumbnail")?;
+    Ok(out.into_inner())
+}
+
+fn rasterize_svg(bytes: &[u8], width: u32) -> Result<Vec<u8>> {
+    let options = usvg::Options {
+        fontdb: FONTS.clone(),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_data(bytes, &options).context("Failed to parse SVG")?;
+    let size = tree.size();
+    let scale = width as f32 / size.width();
+    let height = (size.height() * scale).ceil().max(1.0) as u32;
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| anyhow!("Thumbnail of {}x{} is too large", width, height))?;
+    pixmap.fill(tiny_skia::Color::WHITE);
+    resvg::render(&tree, tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    pixmap.encode_png().context("Failed to encode thumbnail")
+}
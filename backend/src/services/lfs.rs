@@ -0,0 +1,253 @@
+use anyhow::{bail, Context, Result};
+use once_cell::sync::{Lazy, OnceCell};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::services::{git, metrics};
+
+/// First lines of Git LFS pointer files, current and pre-release
+const POINTER_VERSIONS: [&str; 2] = [
+    "version https://git-lfs.github.com/spec/v1",
+    "version https://hawser.github.com/spec/v1",
+];
+
+/// Pointer files are smaller than this; larger blobs are never read as one
+const MAX_POINTER_BYTES: u64 = 1024;
+
+/// Largest LFS object fetched unless configured otherwise, in MiB
+pub const DEFAULT_MAX_MB: u64 = 50;
+
+const BATCH_CONTENT_TYPE: &str = "application/vnd.git-lfs+json";
+
+static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .timeout(Duration::from_secs(120))
+        .build()
+        .expect("Failed to create HTTP client")
+});
+
+/// Largest object fetched in place of its pointer; 0 leaves pointers as they are
+static MAX_BYTES: OnceCell<u64> = OnceCell::new();
+
+/// Fetch LFS objects up to `mb` MiB, or none if 0; call once at startup
+pub fn set_max_mb(mb: u64) {
+    if MAX_BYTES.set(mb * 1024 * 1024).is_err() {
+        warn!("Git LFS size limit already set; ignoring");
+    }
+}
+
+fn max_bytes() -> u64 {
+    MAX_BYTES.get().copied().unwrap_or(DEFAULT_MAX_MB * 1024 * 1024)
+}
+
+/// A blob standing in for a file kept in Git LFS
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pointer {
+    /// SHA-256 of the file, in lowercase hex
+    pub oid: String,
+    pub size: u64,
+}
+
+impl Pointer {
+    /// The pointer `content` holds, if it is one
+    pub fn parse(content: &[u8]) -> Option<Self> {
+        if content.len() as u64 >= MAX_POINTER_BYTES {
+            return None;
+        }
+        let mut lines = std::str::from_utf8(content).ok()?.lines();
+        if !POINTER_VERSIONS.contains(&lines.next()?.trim_end()) {
+            return None;
+        }
+        let (mut oid, mut size) = (None, None);
+        for line in lines {
+            match line.trim_end().split_once(' ') {
+                Some(("oid", value)) => {
+                    oid = value
+                        .strip_prefix("sha256:")
+                        .filter(|hex| hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+                        .map(str::to_ascii_lowercase);
+                }
+                Some(("size", value)) => size = value.parse().ok(),
+                _ => {}
+            }
+        }
+        Some(Self { oid: oid?, size: size? })
+    }
+
+    /// The pointer in the file at `path`, if it holds one
+    pub async fn in_file(path: &Path) -> Option<Self> {
+        let metadata = tokio::fs::metadata(path).await.ok()?;
+        if metadata.len() >= MAX_POINTER_BYTES {
+            return None;
+        }
+        Self::parse(&tokio::fs::read(path).await.ok()?)
+    }
+}
+
+/// Where a repo's LFS objects are fetched from
+#[derive(Debug, Clone)]
+pub struct Remote {
+    pub clone_url: String,
+    /// Sent as the password of HTTP basic auth, like the clone's token
+    pub token: Option<String>,
+}
+
+/// Get the cache directory for fetched LFS objects
+fn get_object_cache_path() -> PathBuf {
+    git::cache_dir().join("kicad-lfs-cache")
+}
+
+/// The file `pointer` stands for, fetched from `remote` unless an earlier
+/// fetch left it on disk
+///
+/// Objects are content-addressed by their SHA-256, which is checked along
+/// with the size before an object is kept.
+pub async fn fetch(remote: &Remote, pointer: &Pointer) -> Result<PathBuf> {
+    let cache_dir = get_object_cache_path();
+    let target = cache_dir.join(&pointer.oid);
+    if tokio::fs::try_exists(&target).await.unwrap_or(false) {
+        return Ok(target);
+    }
+    let max_bytes = max_bytes();
+    if max_bytes == 0 {
+        bail!("Fetching Git LFS objects is off (GIT_LFS_MAX_MB)");
+    }
+    if pointer.size > max_bytes {
+        bail!("Git LFS object {} is {} bytes, over the {} byte limit", pointer.oid, pointer.size, max_bytes);
+    }
+
+    let started = Instant::now();
+    let content = match remote.clone_url.strip_prefix("file://") {
+        Some(path) => read_local(Path::new(path), &pointer.oid).await?,
+        None => download(remote, pointer).await?,
+    };
+    if content.len() as u64 != pointer.size || hex::encode(Sha256::digest(&content)) != pointer.oid {
+        bail!("Git LFS object {} does not match its pointer", pointer.oid);
+    }
+
+    // Write to a temp file in the same directory, then rename into place so
+    // concurrent readers never see a partially written object
+    tokio::fs::create_dir_all(&cache_dir).await?;
+    let target = tokio::task::spawn_blocking(move || -> Result<PathBuf> {
+        let mut tmp = tempfile::NamedTempFile::new_in(&cache_dir)?;
+        std::io::Write::write_all(&mut tmp, &content)?;
+        tmp.persist(&target).context("Failed to move LFS object into cache")?;
+        Ok(target)
+    })
+    .await??;
+    metrics::record_git("lfs_fetch", started.elapsed());
+    info!("Fetched Git LFS object {} ({} bytes)", pointer.oid, pointer.size);
+    Ok(target)
+}
+
+/// Like [`fetch`], returning the file's content
+pub async fn read(remote: &Remote, pointer: &Pointer) -> Result<Vec<u8>> {
+    let path = fetch(remote, pointer).await?;
+    Ok(tokio::fs::read(path).await?)
+}
+
+/// An object from the LFS store of a repository on this machine, as git-lfs
+/// reads it for file:// remotes
+async fn read_local(repo: &Path, oid: &str) -> Result<Vec<u8>> {
+    let relative = Path::new("lfs/objects").join(&oid[..2]).join(&oid[2..4]).join(oid);
+    for git_dir in [repo.to_path_buf(), repo.join(".git")] {
+        match tokio::fs::read(git_dir.join(&relative)).await {
+            Ok(content) => return Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    bail!("Git LFS object {} is not in {}", oid, repo.display())
+}
+
+/// The LFS endpoint git-lfs derives from a clone URL
+fn endpoint(clone_url: &str) -> String {
+    let url = clone_url.trim_end_matches('/');
+    if url.ends_with(".git") {
+        format!("{}/info/lfs", url)
+    } else {
+        format!("{}.git/info/lfs", url)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchResponse {
+    objects: Vec<BatchObject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchObject {
+    #[serde(default)]
+    actions: Option<BatchActions>,
+    #[serde(default)]
+    error: Option<BatchError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchActions {
+    download: Option<BatchAction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchAction {
+    href: String,
+    #[serde(default)]
+    header: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchError {
+    code: u16,
+    message: String,
+}
+
+/// Ask the remote's LFS batch API where the object is, then download it
+async fn download(remote: &Remote, pointer: &Pointer) -> Result<Vec<u8>> {
+    let mut request = HTTP_CLIENT
+        .post(format!("{}/objects/batch", endpoint(&remote.clone_url)))
+        .header(reqwest::header::ACCEPT, BATCH_CONTENT_TYPE)
+        .header(reqwest::header::CONTENT_TYPE, BATCH_CONTENT_TYPE)
+        .body(
+            json!({
+                "operation": "download",
+                "transfers": ["basic"],
+                "objects": [{ "oid": pointer.oid, "size": pointer.size }],
+            })
+            .to_string(),
+        );
+    if let Some(token) = &remote.token {
+        request = request.basic_auth("x-access-token", Some(token));
+    }
+    let response = request.send().await.context("Git LFS batch request failed")?;
+    if !response.status().is_success() {
+        bail!("Git LFS batch request failed with {}", response.status());
+    }
+    let batch: BatchResponse = response.json().await.context("Invalid Git LFS batch response")?;
+    let object = batch.objects.into_iter().next().context("Git LFS batch response lists no objects")?;
+    if let Some(error) = object.error {
+        bail!("Git LFS object {} unavailable ({}): {}", pointer.oid, error.code, error.message);
+    }
+    let action = object
+        .actions
+        .and_then(|actions| actions.download)
+        .context("Git LFS batch response has no download action")?;
+
+    let mut request = HTTP_CLIENT.get(&action.href);
+    for (name, value) in &action.header {
+        request = request.header(name, value);
+    }
+    let response = request.send().await.context("Git LFS download failed")?;
+    if !response.status().is_success() {
+        bail!("Git LFS download failed with {}", response.status());
+    }
+    if response.content_length().is_some_and(|length| length != pointer.size) {
+        bail!("Git LFS object {} has the wrong size", pointer.oid);
+    }
+    Ok(response.bytes().await?.to_vec())
+}
@@ -0,0 +1,138 @@
+use anyhow::Result;
+use kicad_db::{
+    mark_part_alerts_notified, registered_repo_parts, resolve_part_alerts_except, unnotified_part_alerts,
+    upsert_part_alert, LifecycleRisk, PartAlert, PgPool,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::{enrichment, git, notify};
+use crate::shutdown;
+
+/// Outcome of one lifecycle check
+#[derive(Debug, Default)]
+pub struct LifecycleCheck {
+    /// Registered repos with parts at their latest commit
+    pub repos: usize,
+    /// Distinct MPNs looked up
+    pub parts: usize,
+    /// Alerts open after the check
+    pub open_alerts: usize,
+    /// Alerts resolved because the part is gone or no longer at risk
+    pub resolved: u64,
+    /// Alerts announced by this check
+    pub notified: usize,
+}
+
+/// Check registered repos' parts every `interval_secs`, starting one interval
+/// after startup; 0 disables the check
+pub fn spawn(pool: Arc<PgPool>, interval_secs: u64) {
+    if interval_secs == 0 {
+        info!("Lifecycle checks disabled");
+        return;
+    }
+    let period = Duration::from_secs(interval_secs);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        loop {
+            interval.tick().await;
+            if shutdown::requested() {
+                break;
+            }
+            match check_lifecycles(&pool).await {
+                Ok(check) => info!(
+                    "Lifecycle check: {} parts in {} repos, {} alerts open, {} resolved, {} announced",
+                    check.parts, check.repos, check.open_alerts, check.resolved, check.notified
+                ),
+                Err(e) => warn!("Lifecycle check failed: {:#}", e),
+            }
+        }
+    });
+}
+
+/// Flag NRND, EOL and obsolete parts of every registered repo's latest commit
+///
+/// Each MPN is looked up through the supplier metadata cache. Alerts open for
+/// risky parts and resolve once the part is gone or its status recovers; a
+/// part the supplier can't describe right now keeps whatever alert it had.
+/// New alerts are announced on the notification webhook, one message per
+/// repo.
+pub async fn check_lifecycles(pool: &PgPool) -> Result<LifecycleCheck> {
+    let parts = registered_repo_parts(pool).await?;
+    let supplier = enrichment::configured_supplier();
+    let mut risks = HashMap::new();
+    for part in &parts {
+        if risks.contains_key(&part.mpn) {
+            continue;
+        }
+        let metadata = match enrichment::lookup(pool, supplier.as_deref(), &part.mpn).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                warn!("Failed to look up {}: {:#}", part.mpn, e);
+                None
+            }
+        };
+        let risk = metadata.map(|m| (LifecycleRisk::of(&m), m.lifecycle_status));
+        risks.insert(part.mpn.clone(), risk);
+    }
+
+    let mut keep = Vec::new();
+    let mut open_alerts = 0;
+    for part in &parts {
+        match &risks[&part.mpn] {
+            Some((Some(risk), status)) => {
+                upsert_part_alert(pool, part, *risk, status.as_deref()).await?;
+                open_alerts += 1;
+            }
+            Some((None, _)) => continue,
+            None => {}
+        }
+        keep.push((part.repo_url.clone(), part.mpn.clone()));
+    }
+    let resolved = resolve_part_alerts_except(pool, &keep).await?;
+
+    let mut by_repo: BTreeMap<String, Vec<PartAlert>> = BTreeMap::new();
+    for alert in unnotified_part_alerts(pool).await? {
+        by_repo.entry(alert.repo_url.clone()).or_default().push(alert);
+    }
+    let mut notified = 0;
+    for (repo_url, alerts) in by_repo {
+        notify::send_alert(&format_alert(&repo_url, &alerts)).await;
+        let ids: Vec<i64> = alerts.iter().map(|a| a.id).collect();
+        mark_part_alerts_notified(pool, &ids).await?;
+        notified += ids.len();
+    }
+
+    let repos = parts.iter().map(|p| p.repo_url.as_str()).collect::<HashSet<_>>().len();
+    Ok(LifecycleCheck {
+        repos,
+        parts: risks.len(),
+        open_alerts,
+        resolved,
+        notified,
+    })
+}
+
+fn format_alert(repo_url: &str, alerts: &[PartAlert]) -> String {
+    let repo = git::repo_slug(repo_url).unwrap_or_else(|| repo_url.to_string());
+    let mut lines = vec![format!(
+        ":warning: {} part(s) in {} are being discontinued",
+        alerts.len(),
+        repo
+    )];
+    for alert in alerts {
+        let risk = LifecycleRisk::parse(&alert.risk).map_or(alert.risk.as_str(), |risk| risk.label());
+        let status = alert.lifecycle_status.as_deref().map(|s| format!(" ({})", s)).unwrap_or_default();
+        lines.push(format!(
+            "- {} [{}]: {}{} at {}",
+            alert.mpn,
+            alert.designators.join(", "),
+            risk,
+            status,
+            &alert.commit_hash[..8.min(alert.commit_hash.len())]
+        ));
+    }
+    lines.join("\n")
+}
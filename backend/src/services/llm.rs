@@ -0,0 +1,167 @@
+use futures_util::future::BoxFuture;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use kicad_db::{
+    messages::ChatCompletionRequest,
+    xai_client::{
+        ChatCompletionResponse, ChatCompletionStream, DeferredRequest, ResponsesRequest, ResponsesResponse, XaiClient,
+    },
+    XaiError,
+};
+
+use super::metrics;
+
+/// The LLM calls handlers make, behind a trait so tests can swap in a fake
+///
+/// The server uses one shared [`XaiClient`], built at startup and held in
+/// `AppState`.
+pub trait ChatProvider: Send + Sync {
+    /// A responses API call, which may use server-side tools such as web search
+    fn responses<'a>(
+        &'a self,
+        request: &'a ResponsesRequest,
+    ) -> BoxFuture<'a, Result<ResponsesResponse, XaiError>>;
+
+    /// A streaming chat completion, yielding content as it arrives and then
+    /// the finish reason and token usage; cancelling `cancel` aborts the
+    /// upstream request
+    fn chat_completion_stream<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<ChatCompletionStream, XaiError>>;
+
+    /// Submit a chat completion to be generated in the background, for
+    /// [`deferred_completion`](Self::deferred_completion) to collect
+    fn submit_deferred<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+    ) -> BoxFuture<'a, Result<DeferredRequest, XaiError>>;
+
+    /// The result of a submitted completion, or None while it's being generated
+    fn deferred_completion<'a>(
+        &'a self,
+        deferred: &'a DeferredRequest,
+    ) -> BoxFuture<'a, Result<Option<ChatCompletionResponse>, XaiError>>;
+
+    /// IDs of the models the provider serves to our key
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, XaiError>>;
+
+    /// One embedding vector per text, in order
+    fn embed<'a>(&'a self, model: &'a str, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, XaiError>>;
+
+    /// False while the provider is known to be down, so background jobs can
+    /// wait for the next run instead of spending it on failed calls
+    fn available(&self) -> bool {
+        true
+    }
+
+    /// The provider without any response cache in front, for callers that
+    /// need a fresh answer (e.g. drift evaluation)
+    fn uncached(&self) -> &dyn ChatProvider;
+}
+
+impl ChatProvider for XaiClient {
+    fn responses<'a>(
+        &'a self,
+        request: &'a ResponsesRequest,
+    ) -> BoxFuture<'a, Result<ResponsesResponse, XaiError>> {
+        Box::pin(XaiClient::responses(self, request))
+    }
+
+    fn chat_completion_stream<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<ChatCompletionStream, XaiError>> {
+        Box::pin(XaiClient::chat_completion_stream(self, request, cancel))
+    }
+
+    fn submit_deferred<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+    ) -> BoxFuture<'a, Result<DeferredRequest, XaiError>> {
+        Box::pin(XaiClient::submit_deferred(self, request))
+    }
+
+    fn deferred_completion<'a>(
+        &'a self,
+        deferred: &'a DeferredRequest,
+    ) -> BoxFuture<'a, Result<Option<ChatCompletionResponse>, XaiError>> {
+        Box::pin(XaiClient::deferred_completion(self, &deferred.request_id))
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, XaiError>> {
+        Box::pin(XaiClient::list_models(self))
+    }
+
+    fn embed<'a>(&'a self, model: &'a str, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, XaiError>> {
+        Box::pin(XaiClient::embed(self, model, texts))
+    }
+
+    fn uncached(&self) -> &dyn ChatProvider {
+        self
+    }
+}
+
+/// Cancels an upstream stream when the SSE response that forwards it is dropped
+///
+/// Axum drops a response body once its client disconnects, so holding this
+/// inside the SSE stream turns a closed browser tab into an aborted XAI
+/// request. Call [`finish`](Self::finish) once the stream has been fully sent.
+pub struct CancelOnDisconnect {
+    token: CancellationToken,
+    route: &'static str,
+    finished: bool,
+}
+
+impl CancelOnDisconnect {
+    pub fn new(token: CancellationToken, route: &'static str) -> Self {
+        Self {
+            token,
+            route,
+            finished: false,
+        }
+    }
+
+    /// Mark the stream as delivered, so dropping it isn't counted as a disconnect
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for CancelOnDisconnect {
+    fn drop(&mut self) {
+        if !self.finished {
+            info!("Client disconnected from {}; cancelling the XAI stream", self.route);
+            metrics::record_stream_cancelled(self.route);
+        }
+        self.token.cancel();
+    }
+}
+
+/// Check in the background that the provider serves every configured model
+///
+/// Only warns: the provider may be briefly unreachable at startup, and a
+/// missing model fails the requests that use it with a clear upstream error.
+pub fn spawn_model_check(chat: Arc<dyn ChatProvider>, models: Vec<String>) {
+    tokio::spawn(async move {
+        match chat.list_models().await {
+            Ok(available) => {
+                let missing: Vec<&String> =
+                    models.iter().filter(|m| !available.contains(m)).collect();
+                if missing.is_empty() {
+                    info!("All {} configured models are available", models.len());
+                } else {
+                    warn!(
+                        "Configured models not offered by the provider: {:?} (available: {})",
+                        missing,
+                        available.join(", ")
+                    );
+                }
+            }
+            Err(e) => warn!("Could not list provider models to validate configuration: {}", e),
+        }
+    });
+}
@@ -0,0 +1,210 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use kicad_db::PgPool;
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use once_cell::sync::OnceCell;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use super::circuit_breaker::CircuitState;
+use super::{backfill, status};
+use crate::types::{BackfillStatus, WorkClass};
+
+/// Histogram buckets (seconds) spanning a fast JSON read to a slow LLM call
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0,
+];
+
+static HANDLE: OnceCell<PrometheusHandle> = OnceCell::new();
+
+/// Install the Prometheus recorder; metrics recorded before this are dropped
+pub fn install() {
+    HANDLE.get_or_init(|| {
+        PrometheusBuilder::new()
+            .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), LATENCY_BUCKETS)
+            .expect("latency buckets are not empty")
+            .install_recorder()
+            .expect("Failed to install the Prometheus recorder")
+    });
+}
+
+/// Current metrics in the Prometheus text format, with pool and backfill
+/// gauges sampled now
+///
+/// `db_pool_saturation` is the share of the pool's maximum in use; near 1
+/// queries wait for connections, and `db_pool_acquire_timeouts_total` counts
+/// those that gave up. `job_queue_depth` is the commits running backfills
+/// have yet to summarize, by work class.
+pub fn render(pool: &PgPool) -> String {
+    let max = pool.options().get_max_connections();
+    let in_use = (pool.size() as usize).saturating_sub(pool.num_idle());
+    gauge!("db_pool_connections").set(pool.size() as f64);
+    gauge!("db_pool_idle_connections").set(pool.num_idle() as f64);
+    gauge!("db_pool_in_use_connections").set(in_use as f64);
+    gauge!("db_pool_max_connections").set(max as f64);
+    gauge!("db_pool_min_connections").set(pool.options().get_min_connections() as f64);
+    gauge!("db_pool_saturation").set(in_use as f64 / max.max(1) as f64);
+
+    let mut remaining = [0usize; WorkClass::ALL.len()];
+    for job in backfill::jobs() {
+        if job.progress.status == BackfillStatus::Running {
+            remaining[job.progress.class.index()] += job.progress.remaining;
+        }
+    }
+    for class in WorkClass::ALL {
+        gauge!("job_queue_depth", "class" => class.as_str()).set(remaining[class.index()] as f64);
+    }
+
+    match HANDLE.get() {
+        Some(handle) => handle.render(),
+        None => {
+            warn!("Metrics requested before the recorder was installed");
+            String::new()
+        }
+    }
+}
+
+/// Middleware recording request counts and latency per matched route
+///
+/// Labels use the route template (`/api/repos/:repo/bom`), not the concrete
+/// path, so cardinality stays bounded.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    status::record_request(response.status().is_server_error());
+    let status = response.status().as_u16().to_string();
+
+    counter!("http_requests_total", "method" => method.clone(), "route" => route.clone(), "status" => status)
+        .increment(1);
+    histogram!("http_request_duration_seconds", "method" => method, "route" => route)
+        .record(started.elapsed().as_secs_f64());
+    response
+}
+
+/// Record how long a git operation (clone, fetch, log walk, ...) took
+pub fn record_git(operation: &'static str, elapsed: Duration) {
+    histogram!("git_operation_duration_seconds", "operation" => operation)
+        .record(elapsed.as_secs_f64());
+}
+
+/// Count a received webhook by provider and outcome ("processed", "failed", "ignored")
+pub fn record_webhook(provider: &'static str, outcome: &'static str) {
+    counter!("webhook_events_total", "provider" => provider, "outcome" => outcome).increment(1);
+}
+
+/// Count a config reload by whether it was applied ("applied", "rejected")
+pub fn record_config_reload(applied: bool) {
+    let outcome = if applied { "applied" } else { "rejected" };
+    counter!("config_reloads_total", "outcome" => outcome).increment(1);
+}
+
+/// Count a query that gave up waiting for a free pool connection
+pub fn record_pool_timeout() {
+    counter!("db_pool_acquire_timeouts_total").increment(1);
+}
+
+/// Count an SSE stream whose client left before it finished
+pub fn record_stream_cancelled(route: &'static str) {
+    counter!("sse_streams_cancelled_total", "route" => route).increment(1);
+}
+
+/// Set a provider's circuit breaker state gauge (0 closed, 1 half-open, 2 open)
+pub fn set_circuit_state(provider: &str, state: CircuitState) {
+    let value = match state {
+        CircuitState::Closed => 0.0,
+        CircuitState::HalfOpen => 1.0,
+        CircuitState::Open => 2.0,
+    };
+    gauge!("xai_circuit_state", "provider" => provider.to_string()).set(value);
+}
+
+/// Count a provider's circuit breaker moving into `state`, and update the gauge
+pub fn record_circuit_transition(provider: &str, state: CircuitState) {
+    set_circuit_state(provider, state);
+    counter!("xai_circuit_transitions_total", "provider" => provider.to_string(), "to" => state.as_str())
+        .increment(1);
+}
+
+/// Count an AI call `from` one provider failing over `to` the next
+pub fn record_ai_failover(endpoint: &'static str, from: &str, to: &str) {
+    counter!(
+        "ai_failovers_total",
+        "endpoint" => endpoint,
+        "from" => from.to_string(),
+        "to" => to.to_string()
+    )
+    .increment(1);
+}
+
+/// Count an AI response cache lookup by endpoint and outcome ("hit" or "miss")
+pub fn record_ai_cache(endpoint: &'static str, hit: bool) {
+    let outcome = if hit { "hit" } else { "miss" };
+    counter!("ai_cache_requests_total", "endpoint" => endpoint, "outcome" => outcome).increment(1);
+    status::record_cache_lookup("ai", hit);
+}
+
+/// Count an API response cache lookup by route and outcome ("hit" or "miss")
+pub fn record_response_cache(route: &'static str, hit: bool) {
+    let outcome = if hit { "hit" } else { "miss" };
+    counter!("response_cache_requests_total", "route" => route, "outcome" => outcome).increment(1);
+    status::record_cache_lookup("response", hit);
+}
+
+/// Count a parsed schematic cache lookup by outcome ("hit" or "miss")
+pub fn record_schematic_cache(hit: bool) {
+    let outcome = if hit { "hit" } else { "miss" };
+    counter!("schematic_cache_requests_total", "outcome" => outcome).increment(1);
+    status::record_cache_lookup("schematic", hit);
+}
+
+/// Count a parsed sheet dropped to make room for another
+pub fn record_schematic_cache_eviction() {
+    counter!("schematic_cache_evictions_total").increment(1);
+}
+
+/// Set the parsed schematic cache's entry count and size (bytes of source)
+pub fn set_schematic_cache_size(entries: usize, bytes: usize) {
+    gauge!("schematic_cache_entries").set(entries as f64);
+    gauge!("schematic_cache_bytes").set(bytes as f64);
+}
+
+/// Record how long `model` took to start answering, and whether the call
+/// failed
+pub fn record_model_call(model: &str, latency: Duration, ok: bool) {
+    let outcome = if ok { "ok" } else { "error" };
+    histogram!("ai_model_first_token_seconds", "model" => model.to_string()).record(latency.as_secs_f64());
+    counter!("ai_model_calls_total", "model" => model.to_string(), "outcome" => outcome).increment(1);
+}
+
+/// Count a routing decision for low-priority `work`, by the model it was
+/// meant for and the one it got
+pub fn record_model_route(work: &'static str, from: &str, to: &str) {
+    counter!(
+        "ai_model_routes_total",
+        "work" => work,
+        "from" => from.to_string(),
+        "to" => to.to_string()
+    )
+    .increment(1);
+}
+
+/// Set the number of AI calls of `class` waiting for the shared rate limit
+pub fn set_ai_queue_depth(class: WorkClass, waiting: usize) {
+    gauge!("ai_queue_depth", "class" => class.as_str()).set(waiting as f64);
+}
+
+/// Record how long an AI call of `class` waited for the shared rate limit
+pub fn record_ai_queue_wait(class: WorkClass, waited: Duration) {
+    histogram!("ai_queue_wait_seconds", "class" => class.as_str()).record(waited.as_secs_f64());
+}
@@ -1,5 +1,61 @@
+pub mod ai_cache;
+pub mod anomalies;
+pub mod ask;
+pub mod backfill;
+pub mod board;
+pub mod changelog;
+pub mod chat_history;
+pub mod choices;
+pub mod citations;
+pub mod circuit_breaker;
+pub mod costs;
+pub mod datasheets;
+pub mod digest;
 pub mod digikey;
 pub mod distill;
+pub mod doctor;
+pub mod drift;
+pub mod embed;
+pub mod enrichment;
+pub mod erc;
+pub mod events;
+pub mod experiments;
+pub mod failover;
+pub mod file_stream;
 pub mod git;
-
-pub use git::*;
+pub mod github;
+pub mod guidelines;
+pub mod health;
+pub mod lfs;
+pub mod lifecycle;
+pub mod image;
+pub mod llm;
+pub mod mail;
+pub mod metrics;
+pub mod model_health;
+pub mod notify;
+pub mod onboarding;
+pub mod priority;
+pub mod prompt_audit;
+pub mod registry;
+pub mod reload;
+pub mod response_cache;
+pub mod resumable;
+pub mod release_notes;
+pub mod retention;
+pub mod retrieval;
+pub mod review_checklist;
+pub mod sampling;
+pub mod scheduler;
+pub mod schematic_cache;
+pub mod schematic_pdf;
+pub mod selection;
+pub mod semantic;
+pub mod status;
+pub mod submodules;
+pub mod suggest_fix;
+pub mod summarization;
+pub mod summary;
+pub mod taxonomy;
+pub mod thumbnails;
+pub mod timing;
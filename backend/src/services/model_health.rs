@@ -0,0 +1,281 @@
+use futures_util::{future::BoxFuture, StreamExt};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+use kicad_db::{
+    messages::ChatCompletionRequest,
+    xai_client::{
+        ChatCompletionResponse, ChatCompletionStream, DeferredRequest, ResponsesRequest, ResponsesResponse,
+        StreamEvent,
+    },
+    XaiError,
+};
+
+use super::{llm::ChatProvider, metrics};
+use crate::config::RoutingConfig;
+
+/// Most calls kept per model, however many the window holds
+const MAX_SAMPLES: usize = 1_000;
+
+/// One finished AI call
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    /// Time to the first token, or to the whole answer for non-streamed calls
+    latency: Duration,
+    ok: bool,
+}
+
+/// How a model has done over the routing window
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModelStats {
+    pub calls: usize,
+    pub errors: usize,
+    /// 90th percentile latency of its calls
+    pub p90_latency: Duration,
+}
+
+impl ModelStats {
+    pub fn error_rate(&self) -> f64 {
+        match self.calls {
+            0 => 0.0,
+            calls => self.errors as f64 / calls as f64,
+        }
+    }
+}
+
+/// Rolling latency and error rates of each model the server calls, and the
+/// routing of low-priority work away from models that are doing badly
+///
+/// Fed by [`HealthTrackedChatProvider`], which wraps the shared provider.
+/// See [`RoutingConfig`] for when work is routed.
+pub struct ModelHealth {
+    samples: Mutex<HashMap<String, VecDeque<Sample>>>,
+    config: RoutingConfig,
+}
+
+impl ModelHealth {
+    pub fn new(config: RoutingConfig) -> Self {
+        Self {
+            samples: Mutex::new(HashMap::new()),
+            config,
+        }
+    }
+
+    fn window(&self) -> Duration {
+        Duration::from_secs(self.config.window_secs)
+    }
+
+    /// Record a call to `model`
+    pub fn record(&self, model: &str, latency: Duration, ok: bool) {
+        metrics::record_model_call(model, latency, ok);
+        let now = Instant::now();
+        let mut samples = self.samples.lock().unwrap();
+        let calls = samples.entry(model.to_string()).or_default();
+        calls.push_back(Sample { at: now, latency, ok });
+        while calls.len() > MAX_SAMPLES || calls.front().is_some_and(|s| now.duration_since(s.at) > self.window()) {
+            calls.pop_front();
+        }
+    }
+
+    /// How `model` has done over the window
+    pub fn stats(&self, model: &str) -> ModelStats {
+        let samples = self.samples.lock().unwrap();
+        let Some(calls) = samples.get(model) else {
+            return ModelStats::default();
+        };
+        let mut latencies: Vec<Duration> = calls
+            .iter()
+            .filter(|s| s.at.elapsed() <= self.window())
+            .map(|s| s.latency)
+            .collect();
+        let errors = calls
+            .iter()
+            .filter(|s| !s.ok && s.at.elapsed() <= self.window())
+            .count();
+        latencies.sort();
+        let p90_latency = match latencies.len() {
+            0 => Duration::ZERO,
+            n => latencies[(n * 9).div_ceil(10) - 1],
+        };
+        ModelStats {
+            calls: latencies.len(),
+            errors,
+            p90_latency,
+        }
+    }
+
+    /// Why `model` counts as degraded, if it does
+    fn degraded(&self, model: &str) -> Option<String> {
+        let stats = self.stats(model);
+        if stats.calls == 0 || stats.calls < self.config.min_calls {
+            return None;
+        }
+        let max_latency = Duration::from_millis(self.config.max_latency_ms);
+        if stats.p90_latency > max_latency {
+            return Some(format!(
+                "p90 latency {}ms over {}ms in {} calls",
+                stats.p90_latency.as_millis(),
+                max_latency.as_millis(),
+                stats.calls
+            ));
+        }
+        if stats.error_rate() > self.config.max_error_rate {
+            return Some(format!("{} of {} calls failed", stats.errors, stats.calls));
+        }
+        None
+    }
+
+    /// The model low-priority `work` meant for `model` should use: the
+    /// fallback while `model` is degraded and the fallback isn't, else
+    /// `model`
+    ///
+    /// Every decision is logged and counted, routed or not.
+    pub fn route(&self, work: &'static str, model: &str) -> String {
+        let Some(fallback) = self.config.fallback_model.as_deref().filter(|f| *f != model) else {
+            return model.to_string();
+        };
+        let routed = match self.degraded(model) {
+            Some(reason) => match self.degraded(fallback) {
+                None => {
+                    info!("Routing {} from {} to {}: {}", work, model, fallback, reason);
+                    fallback
+                }
+                Some(fallback_reason) => {
+                    info!(
+                        "Keeping {} on {} ({}): fallback {} is doing no better ({})",
+                        work, model, reason, fallback, fallback_reason
+                    );
+                    model
+                }
+            },
+            None => {
+                debug!("Keeping {} on {}", work, model);
+                model
+            }
+        };
+        metrics::record_model_route(work, model, routed);
+        routed.to_string()
+    }
+}
+
+/// Whether a failed call says something about the model, rather than the
+/// caller giving up on it
+fn counts(err: &XaiError) -> bool {
+    !matches!(err, XaiError::Cancelled)
+}
+
+/// Feeds [`ModelHealth`] with every chat call's latency and outcome, by the
+/// model asked for
+///
+/// Streams count once they end, with the time to their first event: a
+/// stream that fails halfway is a failed call. Deferred calls and
+/// embeddings aren't tracked.
+pub struct HealthTrackedChatProvider {
+    inner: Arc<dyn ChatProvider>,
+    health: Arc<ModelHealth>,
+}
+
+impl HealthTrackedChatProvider {
+    pub fn new(inner: Arc<dyn ChatProvider>, health: Arc<ModelHealth>) -> Self {
+        Self { inner, health }
+    }
+}
+
+impl ChatProvider for HealthTrackedChatProvider {
+    fn responses<'a>(
+        &'a self,
+        request: &'a ResponsesRequest,
+    ) -> BoxFuture<'a, Result<ResponsesResponse, XaiError>> {
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = self.inner.responses(request).await;
+            match &result {
+                Err(e) if !counts(e) => {}
+                result => self.health.record(&request.model, started.elapsed(), result.is_ok()),
+            }
+            result
+        })
+    }
+
+    fn chat_completion_stream<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<ChatCompletionStream, XaiError>> {
+        Box::pin(async move {
+            let started = Instant::now();
+            let mut upstream = match self.inner.chat_completion_stream(request, cancel).await {
+                Ok(upstream) => upstream,
+                Err(e) => {
+                    if counts(&e) {
+                        self.health.record(&request.model, started.elapsed(), false);
+                    }
+                    return Err(e);
+                }
+            };
+            let health = self.health.clone();
+            let model = request.model.clone();
+            let tracked: ChatCompletionStream = Box::pin(async_stream::stream! {
+                let mut first_event = None;
+                let mut pending = true;
+                while let Some(result) = upstream.next().await {
+                    let latency = *first_event.get_or_insert_with(|| started.elapsed());
+                    if pending {
+                        match &result {
+                            Ok(StreamEvent::Done) => {
+                                health.record(&model, latency, true);
+                                pending = false;
+                            }
+                            Err(e) => {
+                                if counts(e) {
+                                    health.record(&model, latency, false);
+                                }
+                                pending = false;
+                            }
+                            Ok(_) => {}
+                        }
+                    }
+                    yield result;
+                }
+                // Ended without [DONE], but the model did answer
+                if let (true, Some(latency)) = (pending, first_event) {
+                    health.record(&model, latency, true);
+                }
+            });
+            Ok(tracked)
+        })
+    }
+
+    fn submit_deferred<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+    ) -> BoxFuture<'a, Result<DeferredRequest, XaiError>> {
+        self.inner.submit_deferred(request)
+    }
+
+    fn deferred_completion<'a>(
+        &'a self,
+        deferred: &'a DeferredRequest,
+    ) -> BoxFuture<'a, Result<Option<ChatCompletionResponse>, XaiError>> {
+        self.inner.deferred_completion(deferred)
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, XaiError>> {
+        self.inner.list_models()
+    }
+
+    fn embed<'a>(&'a self, model: &'a str, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, XaiError>> {
+        self.inner.embed(model, texts)
+    }
+
+    fn available(&self) -> bool {
+        self.inner.available()
+    }
+
+    fn uncached(&self) -> &dyn ChatProvider {
+        self
+    }
+}
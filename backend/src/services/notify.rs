@@ -0,0 +1,44 @@
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde_json::json;
+use std::sync::RwLock;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Incoming-webhook URL for alerts (Slack, Mattermost, Discord `/slack` and
+/// anything else that accepts `{"text": ...}`). Alerts are only logged if unset.
+static WEBHOOK_URL: RwLock<Option<String>> = RwLock::new(None);
+
+/// Post alerts to the configured `notify_webhook_url`; called at startup and
+/// on each reload
+pub fn configure(webhook_url: Option<String>) {
+    *WEBHOOK_URL.write().unwrap() = webhook_url;
+}
+
+static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("Failed to create HTTP client")
+});
+
+/// Send an alert to the configured notification webhook.
+/// Failures are logged and otherwise ignored; alerts are best-effort.
+pub async fn send_alert(text: &str) {
+    let Some(url) = WEBHOOK_URL.read().unwrap().clone() else {
+        info!("Alert (no notification webhook configured): {}", text);
+        return;
+    };
+
+    match HTTP_CLIENT.post(&url).json(&json!({ "text": text })).send().await {
+        Ok(response) if response.status().is_success() => {
+            info!("Alert sent to notification webhook");
+        }
+        Ok(response) => {
+            warn!("Notification webhook returned {}", response.status());
+        }
+        Err(e) => {
+            warn!("Failed to send alert: {}", e);
+        }
+    }
+}
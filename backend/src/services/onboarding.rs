@@ -0,0 +1,135 @@
+use anyhow::Result;
+use kicad_db::{get_registered_repo, register_repo, ProcessingPolicy, RepoRegistration};
+use tracing::{info, warn};
+
+use super::github::{self, KicadScan, OrgRepo};
+use super::{git, scheduler};
+use crate::state::AppState;
+use crate::types::{OnboardStatus, OnboardWebhook, OnboardedRepoItem};
+
+/// Environment variable private repos are cloned with; the token that
+/// listed them can read them
+const TOKEN_ENV: &str = "GITHUB_TOKEN";
+
+/// What to onboard, and how its repos are registered
+pub struct Onboarding {
+    pub github_org: String,
+    /// Base URL GitHub posts webhooks to; none installs no webhooks
+    pub public_url: Option<String>,
+    pub include_forks: bool,
+    pub include_archived: bool,
+    pub processing: ProcessingPolicy,
+    /// Org the repos are registered to
+    pub org_id: Option<i32>,
+}
+
+/// Register every repository of a GitHub org that holds KiCad files
+///
+/// Each new registration gets its own webhook secret and, with a public URL,
+/// a push webhook signed with it; then an initial sync of all of them is
+/// queued in the background. Repos already registered are left alone. A
+/// failure with one repo is reported with it and the rest carry on. Returns
+/// None if GitHub has no such org.
+pub async fn onboard(app: &AppState, onboarding: &Onboarding) -> Result<Option<Vec<OnboardedRepoItem>>> {
+    let Some(repos) = github::list_org_repos(&onboarding.github_org).await? else {
+        return Ok(None);
+    };
+    info!("Onboarding {} repositories of {}", repos.len(), onboarding.github_org);
+
+    let mut items = Vec::with_capacity(repos.len());
+    for repo in &repos {
+        let item = onboard_repo(app, onboarding, repo).await.unwrap_or_else(|e| {
+            warn!("Failed to onboard {}: {:#}", repo.full_name, e);
+            item(repo, OnboardStatus::Failed, Some(format!("{:#}", e)))
+        });
+        items.push(item);
+    }
+
+    let registered: Vec<String> = items
+        .iter_mut()
+        .filter(|item| item.status == OnboardStatus::Registered)
+        .map(|item| {
+            item.backfill_queued = true;
+            git::repo_url(&item.repo)
+        })
+        .collect();
+    info!("Onboarded {} of {} repositories of {}", registered.len(), repos.len(), onboarding.github_org);
+    scheduler::spawn_initial_syncs(app.clone(), registered);
+    Ok(Some(items))
+}
+
+fn item(repo: &OrgRepo, status: OnboardStatus, detail: Option<String>) -> OnboardedRepoItem {
+    OnboardedRepoItem {
+        repo: repo.full_name.clone(),
+        status,
+        webhook: OnboardWebhook::Skipped,
+        backfill_queued: false,
+        detail,
+    }
+}
+
+async fn onboard_repo(app: &AppState, onboarding: &Onboarding, repo: &OrgRepo) -> Result<OnboardedRepoItem> {
+    let skipped = |why: &str| Ok(item(repo, OnboardStatus::Skipped, Some(why.to_string())));
+    if repo.fork && !onboarding.include_forks {
+        return skipped("fork");
+    }
+    if repo.archived && !onboarding.include_archived {
+        return skipped("archived");
+    }
+    let Some(branch) = repo.default_branch.as_deref().filter(|_| repo.size > 0) else {
+        return skipped("empty repository");
+    };
+    let repo_url = git::repo_url(&repo.full_name);
+    if get_registered_repo(&app.pool, &repo_url).await?.is_some() {
+        return Ok(item(repo, OnboardStatus::AlreadyRegistered, None));
+    }
+    match github::scan_for_kicad_files(&repo.full_name, branch).await? {
+        KicadScan::Found => {}
+        KicadScan::NotFound => return skipped(&format!("no KiCad files on {}", branch)),
+        KicadScan::Truncated => {
+            return skipped("too many files to scan; register it with POST /api/repos if it holds a KiCad project")
+        }
+    }
+
+    let webhook_url = onboarding
+        .public_url
+        .as_deref()
+        .map(|base| format!("{}/api/hook/github/{}", base.trim_end_matches('/'), repo.full_name));
+    let secret = webhook_url.as_ref().map(|_| uuid::Uuid::new_v4().simple().to_string());
+    let registration = RepoRegistration {
+        repo_url,
+        slug: repo.full_name.clone(),
+        clone_url: repo.clone_url.clone(),
+        default_branch: None,
+        auth_token_env: repo.private.then(|| TOKEN_ENV.to_string()),
+        summary_model: None,
+        summary_prompt: None,
+        webhook_secret: secret.clone(),
+        clone_depth: None,
+        sparse_paths: None,
+        path_filter: None,
+        schematic_globs: None,
+        submodules: false,
+        processing: onboarding.processing.clone(),
+        org_id: onboarding.org_id,
+    };
+    let Some(registered) = register_repo(&app.pool, &registration).await? else {
+        return Ok(item(repo, OnboardStatus::Failed, Some("registered to another org".to_string())));
+    };
+    info!("Registered {} (clone from {})", repo.full_name, registered.clone_url);
+    git::set_remote(&repo.full_name, Some((&registered).into()));
+
+    let mut onboarded = item(repo, OnboardStatus::Registered, None);
+    if let (Some(url), Some(secret)) = (webhook_url, secret) {
+        onboarded.webhook = match github::install_webhook(&repo.full_name, &url, &secret).await {
+            Ok(true) => OnboardWebhook::Installed,
+            Ok(false) => OnboardWebhook::Updated,
+            Err(e) => {
+                warn!("Failed to install the webhook of {}: {:#}", repo.full_name, e);
+                onboarded.detail = Some(format!("{:#}", e));
+                OnboardWebhook::Failed
+            }
+        };
+    }
+    Ok(onboarded)
+}
@@ -0,0 +1,229 @@
+use arc_swap::ArcSwap;
+use futures_util::future::BoxFuture;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use kicad_db::{
+    messages::ChatCompletionRequest,
+    xai_client::{ChatCompletionResponse, ChatCompletionStream, DeferredRequest, ResponsesRequest, ResponsesResponse},
+    XaiError,
+};
+
+use super::{llm::ChatProvider, metrics};
+use crate::config::Config;
+use crate::types::WorkClass;
+
+tokio::task_local! {
+    static CLASS: WorkClass;
+}
+
+/// Class of the AI calls made here: the one [`scope`] set, else interactive,
+/// like every API request's
+pub fn current() -> WorkClass {
+    CLASS.try_with(|class| *class).unwrap_or(WorkClass::Interactive)
+}
+
+/// Make the AI calls of `future` as `class`, e.g. for background work
+pub fn scope<F: Future>(class: WorkClass, future: F) -> impl Future<Output = F::Output> {
+    CLASS.scope(class, future)
+}
+
+/// Share of the bucket `class` may draw down
+fn share(class: WorkClass) -> f64 {
+    match class {
+        WorkClass::Interactive => 1.0,
+        WorkClass::Webhook => 0.75,
+        WorkClass::Backfill => 0.5,
+        WorkClass::Enrichment => 0.25,
+    }
+}
+
+/// Calls `class` leaves in a bucket of `capacity` for more urgent classes;
+/// every class can make a call once the bucket is full
+fn reserve(capacity: f64, class: WorkClass) -> f64 {
+    (capacity * (1.0 - share(class))).min(capacity - 1.0).max(0.0)
+}
+
+struct Bucket {
+    /// Calls that can be made now
+    tokens: f64,
+    refilled_at: Instant,
+    /// Calls waiting, by class
+    waiting: [usize; WorkClass::ALL.len()],
+}
+
+/// The AI calls per minute this server makes, shared out by [`WorkClass`]
+///
+/// A token bucket holding `xai.calls_per_minute` calls and refilled at that
+/// rate; 0 turns it off. Interactive calls may empty it, while each less
+/// urgent class stops short of a reserve (a quarter of the bucket for
+/// webhooks, half for backfills, three quarters for enrichment) and waits
+/// behind any more urgent call already waiting. A big backfill thus runs at
+/// most at half the rate and never delays a user's summary by more than
+/// the calls ahead of it in its own class.
+pub struct PriorityLimiter {
+    config: Arc<ArcSwap<Config>>,
+    bucket: Mutex<Bucket>,
+    /// Woken when a waiting call leaves the queue
+    freed: Notify,
+}
+
+impl PriorityLimiter {
+    pub fn new(config: Arc<ArcSwap<Config>>) -> Self {
+        Self {
+            config,
+            bucket: Mutex::new(Bucket {
+                // Full on first use, whatever the capacity then
+                tokens: f64::INFINITY,
+                refilled_at: Instant::now(),
+                waiting: [0; WorkClass::ALL.len()],
+            }),
+            freed: Notify::new(),
+        }
+    }
+
+    /// Calls of `class` waiting for the bucket now
+    pub fn waiting(&self, class: WorkClass) -> usize {
+        self.bucket.lock().unwrap().waiting[class.index()]
+    }
+
+    /// Wait until a call of the current class may be made, and count it
+    pub async fn acquire(&self) {
+        let class = current();
+        let started = Instant::now();
+        let mut queued = None;
+        loop {
+            let capacity = self.config.load().xai.calls_per_minute as f64;
+            if capacity == 0.0 {
+                break;
+            }
+            let freed = self.freed.notified();
+            tokio::pin!(freed);
+            // Register before checking, so a call leaving in between still wakes us
+            freed.as_mut().enable();
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                let refill = now.duration_since(bucket.refilled_at).as_secs_f64() * capacity / 60.0;
+                bucket.tokens = (bucket.tokens + refill).min(capacity);
+                bucket.refilled_at = now;
+
+                let needed = reserve(capacity, class) + 1.0;
+                let behind = bucket.waiting[..class.index()].iter().any(|&n| n > 0);
+                if !behind && bucket.tokens >= needed {
+                    bucket.tokens -= 1.0;
+                    break;
+                }
+                if queued.is_none() {
+                    bucket.waiting[class.index()] += 1;
+                    metrics::set_ai_queue_depth(class, bucket.waiting[class.index()]);
+                    queued = Some(Queued { limiter: self, class });
+                }
+                // Behind a more urgent call, check again once a call's worth has refilled
+                let short = if behind { 1.0 } else { needed - bucket.tokens };
+                Duration::from_secs_f64(short * 60.0 / capacity)
+            };
+            tokio::select! {
+                _ = freed => {}
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+        drop(queued);
+        metrics::record_ai_queue_wait(class, started.elapsed());
+    }
+}
+
+/// A call counted as waiting until it is dropped, having been let through or
+/// given up on
+struct Queued<'a> {
+    limiter: &'a PriorityLimiter,
+    class: WorkClass,
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        let mut bucket = self.limiter.bucket.lock().unwrap();
+        bucket.waiting[self.class.index()] -= 1;
+        metrics::set_ai_queue_depth(self.class, bucket.waiting[self.class.index()]);
+        drop(bucket);
+        // Less urgent calls may have been waiting behind this one
+        self.limiter.freed.notify_waiters();
+    }
+}
+
+/// Makes every AI call wait its turn at a [`PriorityLimiter`]
+///
+/// Sits below the response cache, so cached answers cost nothing, and above
+/// failover, so one budget covers every provider. Polling a deferred
+/// completion and listing models aren't counted.
+pub struct PrioritizedChatProvider {
+    inner: Arc<dyn ChatProvider>,
+    limiter: Arc<PriorityLimiter>,
+}
+
+impl PrioritizedChatProvider {
+    pub fn new(inner: Arc<dyn ChatProvider>, limiter: Arc<PriorityLimiter>) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+impl ChatProvider for PrioritizedChatProvider {
+    fn responses<'a>(
+        &'a self,
+        request: &'a ResponsesRequest,
+    ) -> BoxFuture<'a, Result<ResponsesResponse, XaiError>> {
+        Box::pin(async move {
+            self.limiter.acquire().await;
+            self.inner.responses(request).await
+        })
+    }
+
+    fn chat_completion_stream<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<ChatCompletionStream, XaiError>> {
+        Box::pin(async move {
+            self.limiter.acquire().await;
+            self.inner.chat_completion_stream(request, cancel).await
+        })
+    }
+
+    fn submit_deferred<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+    ) -> BoxFuture<'a, Result<DeferredRequest, XaiError>> {
+        Box::pin(async move {
+            self.limiter.acquire().await;
+            self.inner.submit_deferred(request).await
+        })
+    }
+
+    fn deferred_completion<'a>(
+        &'a self,
+        deferred: &'a DeferredRequest,
+    ) -> BoxFuture<'a, Result<Option<ChatCompletionResponse>, XaiError>> {
+        self.inner.deferred_completion(deferred)
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, XaiError>> {
+        self.inner.list_models()
+    }
+
+    fn embed<'a>(&'a self, model: &'a str, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, XaiError>> {
+        Box::pin(async move {
+            self.limiter.acquire().await;
+            self.inner.embed(model, texts).await
+        })
+    }
+
+    fn available(&self) -> bool {
+        self.inner.available()
+    }
+
+    fn uncached(&self) -> &dyn ChatProvider {
+        self
+    }
+}
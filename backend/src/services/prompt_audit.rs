@@ -0,0 +1,332 @@
+use anyhow::{Context, Result};
+use futures_util::{future::BoxFuture, StreamExt};
+use regex::Regex;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+use kicad_db::{
+    messages::ChatCompletionRequest,
+    purge_prompt_audits, record_prompt_audit,
+    xai_client::{
+        ChatCompletionResponse, ChatCompletionStream, DeferredRequest, ResponsesRequest, ResponsesResponse,
+        StreamEvent,
+    },
+    PgPool, PromptAudit, XaiError,
+};
+
+use super::{costs, git, llm::ChatProvider};
+use crate::config::PromptAuditConfig;
+use crate::redact::{redact_json, REDACTED};
+use crate::request_id;
+
+/// Secrets that find their way into prompts (pasted into a chat, or left in
+/// a schematic's text fields), redacted whatever the config says
+const SECRET_PATTERNS: [&str; 6] = [
+    r"(?i)\bbearer\s+[A-Za-z0-9._~+/=-]{8,}",
+    r"\bxai-[A-Za-z0-9]{16,}",
+    r"\bsk-[A-Za-z0-9_-]{16,}",
+    r"\bgh[pousr]_[A-Za-z0-9]{20,}",
+    r"\bkw_[0-9a-f]{32,}",
+    r"\bAKIA[0-9A-Z]{16}\b",
+];
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Stands in for an attached image, which is design data and large besides
+const IMAGE: &str = "[image]";
+
+/// Error of a stream recorded before it ended, e.g. because the client left
+const UNFINISHED: &str = "The stream was closed before it finished";
+
+/// Scrubs AI calls before they are audited: secret-looking keys and
+/// patterns, credentials in URLs and images are replaced, and long text is
+/// cut short
+pub struct Redactor {
+    patterns: Vec<Regex>,
+    url_credentials: Regex,
+    max_chars: usize,
+}
+
+impl Redactor {
+    pub fn new(config: &PromptAuditConfig) -> Result<Self> {
+        let patterns = SECRET_PATTERNS
+            .iter()
+            .map(|p| p.to_string())
+            .chain(config.redact_patterns.iter().cloned())
+            .map(|pattern| Regex::new(&pattern).with_context(|| format!("Invalid redact pattern {:?}", pattern)))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            patterns,
+            url_credentials: Regex::new(r"(?i)\b([a-z][a-z0-9+.-]*://)[^\s/@]+@").expect("valid pattern"),
+            max_chars: config.max_chars,
+        })
+    }
+
+    /// `text` with secrets redacted, cut to the configured length
+    pub fn text(&self, text: &str) -> String {
+        let mut text = self.url_credentials.replace_all(text, format!("${{1}}{}@", REDACTED)).into_owned();
+        for pattern in &self.patterns {
+            if pattern.is_match(&text) {
+                text = pattern.replace_all(&text, REDACTED).into_owned();
+            }
+        }
+        match text.char_indices().nth(self.max_chars) {
+            Some((end, _)) => {
+                let cut = text[end..].chars().count();
+                format!("{}… [{} more characters]", &text[..end], cut)
+            }
+            None => text,
+        }
+    }
+
+    /// `value` with secret-looking keys redacted, and every string as [`Redactor::text`]
+    pub fn json(&self, mut value: Value) -> Value {
+        redact_json(&mut value);
+        self.strings(&mut value);
+        value
+    }
+
+    fn strings(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => map.values_mut().for_each(|value| self.strings(value)),
+            Value::Array(items) => items.iter_mut().for_each(|value| self.strings(value)),
+            Value::String(text) if text.starts_with("data:image/") => *text = IMAGE.to_string(),
+            Value::String(text) => *text = self.text(text),
+            _ => {}
+        }
+    }
+}
+
+/// Records each AI call in the prompt audit: the messages, the answer (or
+/// error), latency and tokens, redacted by a [`Redactor`]
+///
+/// Sits directly above a provider's client and circuit breaker, below its
+/// cost metering, so it sees each call as sent, after a budget fallback
+/// changed the model. Streams are recorded when they end, including those
+/// the client abandoned. Writes happen in the background and failures are
+/// only logged. Model listings and embeddings aren't audited.
+pub struct AuditedChatProvider {
+    inner: Arc<dyn ChatProvider>,
+    provider: String,
+    pool: Arc<PgPool>,
+    redactor: Arc<Redactor>,
+}
+
+impl AuditedChatProvider {
+    pub fn new(inner: Arc<dyn ChatProvider>, provider: &str, pool: Arc<PgPool>, redactor: Arc<Redactor>) -> Self {
+        Self {
+            inner,
+            provider: provider.to_string(),
+            pool,
+            redactor,
+        }
+    }
+
+    /// An entry for a call to `endpoint` with `model`, charged like the
+    /// current request
+    fn entry(&self, endpoint: &str, model: &str, messages: Value) -> PromptAudit {
+        let (repo, api_key, org_id) = costs::charged_to();
+        PromptAudit {
+            request_id: request_id::current(),
+            endpoint: endpoint.to_string(),
+            provider: self.provider.clone(),
+            model: model.to_string(),
+            repo_url: repo.as_deref().map(git::repo_url),
+            api_key,
+            org_id,
+            messages: self.redactor.json(messages),
+            ..Default::default()
+        }
+    }
+
+    fn finish<T>(&self, mut audit: PromptAudit, started: Instant, result: &Result<T, XaiError>, answer: impl FnOnce(&T) -> Option<String>) {
+        audit.latency_ms = started.elapsed().as_millis() as i64;
+        match result {
+            Ok(value) => audit.response = answer(value).map(|text| self.redactor.text(&text)),
+            Err(e) => audit.error = Some(self.redactor.text(&e.to_string())),
+        }
+        spawn_record(self.pool.clone(), audit);
+    }
+}
+
+/// Store `audit` without holding up the call
+fn spawn_record(pool: Arc<PgPool>, audit: PromptAudit) {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    runtime.spawn(async move {
+        if let Err(e) = record_prompt_audit(&pool, &audit).await {
+            warn!("Failed to audit a {} call: {}", audit.endpoint, e);
+        }
+    });
+}
+
+/// A stream's entry, recorded when the stream is dropped: at its end, or
+/// when the client goes away
+struct StreamAudit {
+    pool: Arc<PgPool>,
+    audit: PromptAudit,
+    started: Instant,
+    answer: String,
+    redactor: Arc<Redactor>,
+    finished: bool,
+}
+
+impl Drop for StreamAudit {
+    fn drop(&mut self) {
+        let mut audit = std::mem::take(&mut self.audit);
+        audit.latency_ms = self.started.elapsed().as_millis() as i64;
+        audit.response = Some(self.redactor.text(&self.answer));
+        if !self.finished && audit.error.is_none() {
+            audit.error = Some(UNFINISHED.to_string());
+        }
+        spawn_record(self.pool.clone(), audit);
+    }
+}
+
+fn tokens(count: Option<u32>) -> Option<i64> {
+    count.map(i64::from)
+}
+
+impl ChatProvider for AuditedChatProvider {
+    fn responses<'a>(
+        &'a self,
+        request: &'a ResponsesRequest,
+    ) -> BoxFuture<'a, Result<ResponsesResponse, XaiError>> {
+        Box::pin(async move {
+            let mut audit = self.entry("responses", &request.model, json!(request.input));
+            let started = Instant::now();
+            let result = self.inner.responses(request).await;
+            if let Ok(response) = &result {
+                let usage = response.usage.as_ref();
+                audit.prompt_tokens = tokens(usage.and_then(|u| u.prompt_tokens));
+                audit.completion_tokens = tokens(usage.and_then(|u| u.completion_tokens));
+            }
+            self.finish(audit, started, &result, |response| {
+                response.output.as_ref().map(|output| json!(output).to_string())
+            });
+            result
+        })
+    }
+
+    fn chat_completion_stream<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<ChatCompletionStream, XaiError>> {
+        Box::pin(async move {
+            let mut audit = self.entry("chat_completion_stream", &request.model, json!(request.messages));
+            let started = Instant::now();
+            let mut upstream = match self.inner.chat_completion_stream(request, cancel).await {
+                Ok(upstream) => upstream,
+                Err(e) => {
+                    audit.latency_ms = started.elapsed().as_millis() as i64;
+                    audit.error = Some(self.redactor.text(&e.to_string()));
+                    spawn_record(self.pool.clone(), audit);
+                    return Err(e);
+                }
+            };
+            let mut pending = StreamAudit {
+                pool: self.pool.clone(),
+                audit,
+                started,
+                answer: String::new(),
+                redactor: self.redactor.clone(),
+                finished: false,
+            };
+            let audited: ChatCompletionStream = Box::pin(async_stream::stream! {
+                while let Some(result) = upstream.next().await {
+                    match &result {
+                        Ok(StreamEvent::ContentDelta { text }) => pending.answer.push_str(text),
+                        Ok(StreamEvent::Usage { usage }) => {
+                            pending.audit.prompt_tokens = tokens(usage.prompt_tokens);
+                            pending.audit.completion_tokens = tokens(usage.completion_tokens);
+                        }
+                        Err(e) => pending.audit.error = Some(pending.redactor.text(&e.to_string())),
+                        Ok(_) => {}
+                    }
+                    yield result;
+                }
+                // Recorded now rather than when the response is done with the stream
+                pending.finished = true;
+                drop(pending);
+            });
+            Ok(audited)
+        })
+    }
+
+    fn submit_deferred<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+    ) -> BoxFuture<'a, Result<DeferredRequest, XaiError>> {
+        Box::pin(async move {
+            let audit = self.entry("submit_deferred", &request.model, json!(request.messages));
+            let started = Instant::now();
+            let result = self.inner.submit_deferred(request).await;
+            self.finish(audit, started, &result, |deferred| Some(format!("Deferred as {}", deferred.request_id)));
+            result
+        })
+    }
+
+    /// Recorded once the result is in, under the deferred request's ID
+    fn deferred_completion<'a>(
+        &'a self,
+        deferred: &'a DeferredRequest,
+    ) -> BoxFuture<'a, Result<Option<ChatCompletionResponse>, XaiError>> {
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = self.inner.deferred_completion(deferred).await;
+            if matches!(result, Ok(None)) {
+                return result;
+            }
+            let mut audit = self.entry(
+                "deferred_completion",
+                &deferred.model,
+                json!({ "deferred_request_id": deferred.request_id }),
+            );
+            if let Ok(Some(response)) = &result {
+                let usage = response.usage.as_ref();
+                audit.prompt_tokens = tokens(usage.and_then(|u| u.prompt_tokens));
+                audit.completion_tokens = tokens(usage.and_then(|u| u.completion_tokens));
+            }
+            self.finish(audit, started, &result, |response| {
+                let message = response.as_ref()?.choices.first()?.message.as_ref()?;
+                message.content.clone()
+            });
+            result
+        })
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, XaiError>> {
+        self.inner.list_models()
+    }
+
+    fn embed<'a>(&'a self, model: &'a str, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, XaiError>> {
+        self.inner.embed(model, texts)
+    }
+
+    fn available(&self) -> bool {
+        self.inner.available()
+    }
+
+    fn uncached(&self) -> &dyn ChatProvider {
+        self
+    }
+}
+
+/// Hourly, delete audit entries older than `retention`
+pub fn spawn_purge(pool: Arc<PgPool>, retention: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match purge_prompt_audits(&pool, retention).await {
+                Ok(0) => debug!("No old prompt audit entries to purge"),
+                Ok(n) => info!("Purged {} old prompt audit entries", n),
+                Err(e) => warn!("Failed to purge old prompt audit entries: {}", e),
+            }
+        }
+    });
+}
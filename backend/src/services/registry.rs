@@ -0,0 +1,73 @@
+use axum::http::StatusCode;
+use glob::{MatchOptions, Pattern};
+use kicad_db::{get_registered_repo, list_registered_repos, OrgFilter, PgPool, ProcessingPolicy, RegisteredRepo};
+use tracing::{info, warn};
+
+use super::{costs, git};
+use crate::config::Config;
+use crate::error::{AppError, ResultExt};
+
+/// Point git at the clone URLs and branches of all registered repos
+pub async fn load(pool: &PgPool) -> Result<usize, sqlx::Error> {
+    let repos = list_registered_repos(pool, OrgFilter::Any).await?;
+    for repo in &repos {
+        git::set_remote(&repo.slug, Some(repo.into()));
+    }
+    info!("Loaded {} registered repos", repos.len());
+    Ok(repos.len())
+}
+
+/// The registration of `repo_slug`, if any
+///
+/// With `require_registered_repos` set, unregistered repos are rejected
+/// with 404, so hooks and AI calls only run for repos an admin added.
+pub async fn lookup(
+    pool: &PgPool,
+    config: &Config,
+    repo_slug: &str,
+) -> Result<Option<RegisteredRepo>, AppError> {
+    let registered = get_registered_repo(pool, &git::repo_url(repo_slug))
+        .await
+        .or_internal("Failed to look up repository")?;
+    // Whatever the request asks the AI from here on is about this repo
+    costs::charge_repo(repo_slug, registered.as_ref().and_then(|r| r.org_id));
+    if registered.is_none() && config.require_registered_repos {
+        return Err(AppError::new(
+            StatusCode::NOT_FOUND,
+            "not_registered",
+            format!("Repository {} is not registered", repo_slug),
+        ));
+    }
+    Ok(registered)
+}
+
+/// Whether `policy` processes pushes to `branch`; in its globs `*` stays
+/// within a path segment and `**` spans them, as in path filters
+pub fn processes_branch(policy: &ProcessingPolicy, branch: &str) -> bool {
+    let Some(globs) = &policy.branches else {
+        return true;
+    };
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+    globs.iter().any(|glob| match Pattern::new(glob) {
+        Ok(pattern) => pattern.matches_with(branch, options),
+        Err(e) => {
+            warn!("Ignoring branch glob {:?}: {}", glob, e);
+            false
+        }
+    })
+}
+
+/// Whether `policy` processes a push to `git_ref`, e.g. "refs/heads/main" or
+/// "refs/tags/v1.0"; refs that are neither are processed
+pub fn processes_ref(policy: &ProcessingPolicy, git_ref: &str) -> bool {
+    if let Some(branch) = git_ref.strip_prefix("refs/heads/") {
+        processes_branch(policy, branch)
+    } else if git_ref.starts_with("refs/tags/") {
+        policy.tags
+    } else {
+        true
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::{Context, Result};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use kicad_db::{
+    messages::{ChatCompletionRequest, Message},
+    prompt_budget::PromptBudget,
+    prompts::{PromptLibrary, RELEASE_NOTES},
+    count_schematics, list_schematics, PgPool, SortOrder, UpdateSchematic,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::services::{
+    choices::ShortestMentioning, distill, git, llm::ChatProvider, review_checklist, schematic_cache::SchematicCache,
+    summary,
+};
+use crate::types::{ReleaseCategory, ReleaseNoteChange};
+
+/// Most commits a release may span; each may need a blurb generated
+pub const MAX_RELEASE_COMMITS: usize = 200;
+/// Output allowed for the notes
+const NOTES_MAX_TOKENS: u32 = 2_000;
+/// Missing blurbs generated at once
+const CONCURRENCY: usize = 4;
+/// Name of the prompt budget section holding the changes
+const CHANGES_SECTION: &str = "changes";
+
+/// Words filing a change under a section, checked in this order: a fix is a
+/// bug fix whatever it touches
+const KEYWORDS: &[(ReleaseCategory, &[&str])] = &[
+    (ReleaseCategory::Bugfixes, &["fix", "fixed", "fixes", "bug", "bugfix", "rework", "erratum", "errata", "wrong", "swapped"]),
+    (
+        ReleaseCategory::Power,
+        &["power", "ldo", "regulator", "buck", "boost", "supply", "battery", "vin", "vcc", "vbus", "3v3", "5v", "pmic", "charger"],
+    ),
+    (
+        ReleaseCategory::Mcu,
+        &["mcu", "microcontroller", "stm32", "esp32", "rp2040", "atmega", "nrf52", "samd21", "swd", "jtag", "firmware", "crystal"],
+    ),
+    (
+        ReleaseCategory::Connectors,
+        &["connector", "connectors", "header", "jack", "usb", "plug", "socket", "terminal", "jst"],
+    ),
+];
+
+/// The section a commit goes under, from its message and blurb
+pub fn categorize(message: Option<&str>, blurb: &str) -> ReleaseCategory {
+    let text = format!("{} {}", message.unwrap_or_default(), blurb).to_lowercase();
+    let words: Vec<&str> = text.split(|c: char| !c.is_ascii_alphanumeric()).collect();
+    KEYWORDS
+        .iter()
+        .find(|(_, keywords)| words.iter().any(|word| keywords.contains(word)))
+        .map_or(ReleaseCategory::Other, |(category, _)| *category)
+}
+
+/// The commits among `commits` that changed schematics or layouts, each
+/// with its blurb
+///
+/// Stored blurbs are used where `visible` allows them; a commit with none
+/// stored gets one generated from its diff, the shortest of a few
+/// candidates naming a changed component, and stored. Commits whose blurb
+/// the caller may not see are left out.
+#[allow(clippy::too_many_arguments)]
+pub async fn changes(
+    pool: &PgPool,
+    schematics: &Arc<SchematicCache>,
+    chat: &dyn ChatProvider,
+    prompts: &PromptLibrary,
+    model: &str,
+    context_window: usize,
+    repo: &str,
+    commits: &[(String, Option<String>)],
+    visible: impl Fn(&str) -> bool,
+) -> Result<Vec<ReleaseNoteChange>> {
+    let repo_url = git::repo_url(repo);
+    let total = count_schematics(pool, &repo_url).await?;
+    let mut stored: HashMap<String, (Option<String>, String)> = list_schematics(pool, &repo_url, total.max(1), 0, SortOrder::Asc)
+        .await
+        .context("Failed to load stored commits")?
+        .into_iter()
+        .map(|row| (row.commit_hash, (row.blurb, row.visibility)))
+        .collect();
+
+    let mut hardware = Vec::new();
+    for (commit, message) in commits {
+        let files = git::get_changed_schematic_files(repo, commit)
+            .await
+            .with_context(|| format!("Failed to list the files changed in {}", commit))?;
+        if files.is_empty() {
+            continue;
+        }
+        match stored.remove(commit) {
+            Some((_, visibility)) if !visible(&visibility) => {}
+            Some((Some(blurb), _)) => hardware.push((commit.clone(), message.clone(), Some(blurb))),
+            _ => hardware.push((commit.clone(), message.clone(), None)),
+        }
+    }
+
+    let repo_url = &repo_url;
+    let changes: Vec<ReleaseNoteChange> = stream::iter(hardware)
+        .map(|(commit, message, blurb)| async move {
+            let (blurb, generated) = match blurb {
+                Some(blurb) => (blurb, false),
+                None => {
+                    let projects = distill::project_changes(schematics, repo, &commit)
+                        .await
+                        .with_context(|| format!("Failed to diff {}", commit))?;
+                    let references = review_checklist::changed_references(&projects, &[]);
+                    let (blurb, _) = summary::generate_blurb(
+                        chat,
+                        prompts,
+                        model,
+                        context_window,
+                        repo,
+                        &commit,
+                        message.as_deref(),
+                        &distill::describe_changes(&projects),
+                        &ShortestMentioning { references: &references },
+                    )
+                    .await
+                    .with_context(|| format!("Failed to summarize {}", commit))?;
+                    if let Err(e) = UpdateSchematic::new(repo_url, &commit)
+                        .update_blurb(&blurb)
+                        .execute(pool)
+                        .await
+                    {
+                        warn!("Failed to store the blurb of {}/{}: {}", repo, commit, e);
+                    }
+                    (blurb, true)
+                }
+            };
+            Ok::<_, anyhow::Error>(ReleaseNoteChange {
+                category: categorize(message.as_deref(), &blurb),
+                commit,
+                message,
+                blurb,
+                generated,
+            })
+        })
+        .buffered(CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    let generated = changes.iter().filter(|change| change.generated).count();
+    info!("Release of {}: {} hardware commits, {} blurbs generated", repo, changes.len(), generated);
+    Ok(changes)
+}
+
+/// "### Power\n- abc1234 Swap the LDO (Use a TPS7A02)" for each section
+/// with changes
+fn sections(changes: &[ReleaseNoteChange]) -> String {
+    let mut by_category: Vec<&ReleaseNoteChange> = changes.iter().collect();
+    by_category.sort_by_key(|change| change.category);
+    let mut text = String::new();
+    let mut current = None;
+    for change in by_category {
+        if current != Some(change.category) {
+            let heading = match change.category {
+                ReleaseCategory::Power => "Power",
+                ReleaseCategory::Mcu => "MCU",
+                ReleaseCategory::Connectors => "Connectors",
+                ReleaseCategory::Bugfixes => "Bug fixes",
+                ReleaseCategory::Other => "Other",
+            };
+            if current.is_some() {
+                text.push('\n');
+            }
+            text.push_str(&format!("### {}\n", heading));
+            current = Some(change.category);
+        }
+        let message = change.message.as_deref().and_then(|m| m.lines().next()).unwrap_or_default();
+        text.push_str(&format!("- {} {} ({})\n", &change.commit[..7.min(change.commit.len())], change.blurb, message));
+    }
+    text
+}
+
+/// Ask Grok for the release notes of `changes`, returning the Markdown and
+/// the prompt version
+#[allow(clippy::too_many_arguments)]
+pub async fn generate(
+    chat: &dyn ChatProvider,
+    prompts: &PromptLibrary,
+    model: &str,
+    context_window: usize,
+    repo: &str,
+    from_tag: &str,
+    to_tag: &str,
+    changes: &[ReleaseNoteChange],
+) -> Result<(String, String)> {
+    let variables = |changes: &str| {
+        serde_json::json!({
+            "repo": repo,
+            "from_tag": from_tag,
+            "to_tag": to_tag,
+            "changes": changes,
+        })
+    };
+    let bare = prompts.render(RELEASE_NOTES, variables(""))?;
+    let fitted = PromptBudget::new(model, context_window)
+        .reserve_output(NOTES_MAX_TOKENS)
+        .fixed(&bare.text)
+        .section(CHANGES_SECTION, sections(changes))
+        .fit();
+    if !fitted.truncated.is_empty() {
+        warn!(
+            "Shortened the changes in the release notes prompt for {} {}..{} to fit the context window",
+            repo, from_tag, to_tag
+        );
+    }
+    let prompt = prompts.render(RELEASE_NOTES, variables(fitted.text(CHANGES_SECTION)))?;
+
+    let mut request = ChatCompletionRequest::with_stream(vec![Message::user(prompt.text.clone())], model.to_string(), true);
+    request.max_tokens = Some(NOTES_MAX_TOKENS);
+    let markdown = summary::complete(chat, &request).await?;
+    Ok((markdown, prompt.label()))
+}
@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+use kicad_db::{PgPool, PromptLibrary};
+use once_cell::sync::Lazy;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use super::{distill, enrichment, experiments, git, github, metrics, notify, timing};
+use crate::config::Config;
+use crate::rate_limit;
+use crate::shutdown;
+use crate::state::AppState;
+
+/// One reload at a time, so two triggers can't interleave their swaps
+static RELOADING: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// What a reload swapped in
+#[derive(Debug)]
+pub struct Reloaded {
+    /// Labels of the prompt templates now in effect
+    pub prompts: Vec<String>,
+    /// Changed settings that keep their old values until a restart
+    pub restart_required: Vec<&'static str>,
+}
+
+/// The built-in prompt templates, overridden by PROMPTS_DIR's and then by
+/// those stored in Postgres (none under SQLite)
+pub async fn load_prompts(config: &Config, pool: &PgPool) -> Result<PromptLibrary> {
+    let mut prompts = PromptLibrary::builtin();
+    if let Some(dir) = &config.prompts_dir {
+        prompts.load_dir(dir).context("Failed to load prompt templates")?;
+    }
+    if !config.uses_sqlite() {
+        prompts
+            .load_from_db(pool)
+            .await
+            .context("Failed to load prompt templates")?;
+    }
+    Ok(prompts)
+}
+
+/// Hand `config`'s settings to the services that keep their own, being
+/// reached without the app state: rate limits, the GitHub API, the parts
+/// supplier, alerts, latency budgets, the distiller, git tokens and fetches
+///
+/// Called with the config at startup and by each reload.
+pub fn apply(config: &Config) {
+    rate_limit::configure(&config.rate_limits);
+    github::configure(&config.github);
+    enrichment::configure(&config.parts);
+    notify::configure(config.notify_webhook_url.clone());
+    timing::configure(&config.latency_budgets);
+    distill::configure(config.distiller_path.clone(), config.kicad_symbol_dir.clone());
+    git::set_tokens(config.git_tokens.clone());
+    git::set_fetch_interval(config.git_fetch_interval_secs);
+}
+
+/// Load the config and prompt templates again and swap them in together
+///
+/// The config is read and validated as at startup. If it or the templates
+/// fail to load, or an experiment names a template that isn't there, the
+/// error is returned and what's running stays in effect. `trigger` says
+/// what asked for the reload, for the logs.
+pub async fn reload(state: &AppState, trigger: &str) -> Result<Reloaded> {
+    let _reloading = RELOADING.lock().await;
+    let loaded = async {
+        let config = Config::load().context("Invalid configuration")?;
+        let prompts = load_prompts(&config, &state.pool).await?;
+        experiments::check(&config, &prompts).context("Invalid experiment")?;
+        anyhow::Ok((config, prompts))
+    };
+    let (config, prompts) = match loaded.await {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            warn!("Reload on {} failed; keeping the running configuration: {:#}", trigger, e);
+            metrics::record_config_reload(false);
+            return Err(e);
+        }
+    };
+
+    let restart_required = state.config().restart_required(&config);
+    if !restart_required.is_empty() {
+        warn!(
+            "Reload on {}: changes to [{}] take effect after a restart",
+            trigger,
+            restart_required.join(", ")
+        );
+    }
+    let labels: Vec<String> = prompts.templates().map(|t| t.label()).collect();
+    info!("Configuration reloaded on {}: {}", trigger, config.summary());
+    info!("Prompt templates: {}", labels.join(", "));
+    apply(&config);
+    state.config.store(Arc::new(config));
+    state.prompts.store(Arc::new(prompts));
+    metrics::record_config_reload(true);
+    Ok(Reloaded {
+        prompts: labels,
+        restart_required,
+    })
+}
+
+/// Reload on SIGHUP, and when the config file or prompts directory changes
+///
+/// Changes are noticed by polling modification times every
+/// `config_watch_secs`, which also sees files editors replace rather than
+/// write; a failed reload is retried only once the files change again.
+pub fn spawn(state: AppState) {
+    #[cfg(unix)]
+    {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let mut hangups = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+                Ok(signal) => signal,
+                Err(e) => {
+                    warn!("Failed to listen for SIGHUP: {}", e);
+                    return;
+                }
+            };
+            loop {
+                tokio::select! {
+                    _ = hangups.recv() => {}
+                    _ = shutdown::started() => break,
+                }
+                // Failures are logged by reload
+                let _ = reload(&state, "SIGHUP").await;
+            }
+        });
+    }
+
+    let interval = Duration::from_secs(state.config().config_watch_secs);
+    if interval.is_zero() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut seen = fingerprint(&state.config());
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = shutdown::started() => break,
+            }
+            let now = fingerprint(&state.config());
+            if now == seen {
+                continue;
+            }
+            // Taken before reading, so a write during the reload is seen next time
+            seen = now;
+            let _ = reload(&state, "file change").await;
+        }
+    });
+}
+
+/// Modification times of the config file and the prompts directory's files
+fn fingerprint(config: &Config) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut files: Vec<PathBuf> = Config::file().into_iter().collect();
+    if let Some(dir) = &config.prompts_dir {
+        files.extend(std::fs::read_dir(dir).into_iter().flatten().flatten().map(|entry| entry.path()));
+    }
+    files.sort();
+    files
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
@@ -0,0 +1,302 @@
+use anyhow::{Context, Result};
+use axum::{
+    http::{header, HeaderName, HeaderValue},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::{future::BoxFuture, StreamExt};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast::error::RecvError, OnceCell};
+use tracing::{debug, info, warn};
+
+use crate::config::ResponseCacheConfig;
+use crate::error::AppError;
+use crate::services::{events, git, metrics};
+use crate::shutdown;
+
+/// Every key starts with this, then the repo URL
+const KEY_PREFIX: &str = "api:";
+
+/// Tells clients whether a response came from the cache
+static X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+
+/// Where cached response bodies are kept
+pub trait ResponseStore: Send + Sync {
+    /// "memory" or "redis", for logs
+    fn name(&self) -> &'static str;
+
+    /// The body stored under `key`, unless it expired
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>>>;
+
+    /// Store `body` under `key` for `ttl`, replacing what was there
+    fn put<'a>(&'a self, key: &'a str, body: Vec<u8>, ttl: Duration) -> BoxFuture<'a, Result<()>>;
+
+    /// Delete every body whose key starts with `prefix`
+    fn delete_prefix<'a>(&'a self, prefix: &'a str) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Bodies kept in this process, the one expiring soonest dropped when full
+pub struct MemoryResponseStore {
+    max_entries: usize,
+    entries: Mutex<HashMap<String, (Vec<u8>, Instant)>>,
+}
+
+impl MemoryResponseStore {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl ResponseStore for MemoryResponseStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>>> {
+        let mut entries = self.entries.lock().unwrap();
+        let body = match entries.get(key) {
+            Some((body, expires)) if *expires > Instant::now() => Some(body.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        };
+        Box::pin(async move { Ok(body) })
+    }
+
+    fn put<'a>(&'a self, key: &'a str, body: Vec<u8>, ttl: Duration) -> BoxFuture<'a, Result<()>> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries && !entries.contains_key(key) {
+            let now = Instant::now();
+            entries.retain(|_, (_, expires)| *expires > now);
+            if entries.len() >= self.max_entries {
+                let soonest = entries.iter().min_by_key(|(_, (_, expires))| *expires).map(|(k, _)| k.clone());
+                if let Some(soonest) = soonest {
+                    entries.remove(&soonest);
+                }
+            }
+        }
+        if self.max_entries > 0 {
+            entries.insert(key.to_string(), (body, Instant::now() + ttl));
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    fn delete_prefix<'a>(&'a self, prefix: &'a str) -> BoxFuture<'a, Result<()>> {
+        self.entries.lock().unwrap().retain(|key, _| !key.starts_with(prefix));
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Bodies kept in Redis, shared by every server instance
+///
+/// The connection is made on first use, and made again after it fails, so
+/// the server starts without Redis; until it's reachable every lookup misses.
+pub struct RedisResponseStore {
+    client: redis::Client,
+    connection: OnceCell<ConnectionManager>,
+}
+
+impl RedisResponseStore {
+    pub fn new(url: &str) -> Result<Self> {
+        Ok(Self {
+            client: redis::Client::open(url).context("Invalid Redis URL")?,
+            connection: OnceCell::new(),
+        })
+    }
+
+    async fn connection(&self) -> Result<ConnectionManager> {
+        let connection = self
+            .connection
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .context("Failed to connect to Redis")?;
+        Ok(connection.clone())
+    }
+}
+
+/// `text` with Redis glob characters escaped
+fn escape_glob(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl ResponseStore for RedisResponseStore {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>>> {
+        Box::pin(async move { Ok(self.connection().await?.get(key).await?) })
+    }
+
+    fn put<'a>(&'a self, key: &'a str, body: Vec<u8>, ttl: Duration) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let _: () = self.connection().await?.set_ex(key, body, ttl.as_secs().max(1)).await?;
+            Ok(())
+        })
+    }
+
+    fn delete_prefix<'a>(&'a self, prefix: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let keys: Vec<String> = {
+                let mut scan = connection.scan_match::<_, String>(format!("{}*", escape_glob(prefix))).await?;
+                let mut keys = Vec::new();
+                while let Some(key) = scan.next().await {
+                    keys.push(key);
+                }
+                keys
+            };
+            if !keys.is_empty() {
+                let _: () = connection.del(keys).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Finished API responses for a repo, served again until they expire or the
+/// repo's data changes
+///
+/// Bodies are keyed by route, repo and commit (and whatever else the response
+/// depends on). A refresh, webhook or re-sync of a repo, and every commit it
+/// processes, deletes the repo's entries. Store failures are logged and
+/// treated as misses, so a broken cache only costs the recomputation.
+pub struct ResponseCache {
+    store: Option<Arc<dyn ResponseStore>>,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    /// A cache in Redis if `config` names one, else in memory; none if its
+    /// TTL is 0
+    pub fn new(config: &ResponseCacheConfig) -> Result<Self> {
+        let store: Option<Arc<dyn ResponseStore>> = match (&config.redis_url, config.ttl_secs) {
+            (_, 0) => None,
+            (Some(url), _) => Some(Arc::new(RedisResponseStore::new(url)?)),
+            (None, _) => Some(Arc::new(MemoryResponseStore::new(config.memory_entries))),
+        };
+        if let Some(store) = &store {
+            info!("Caching API responses in {} for {}s", store.name(), config.ttl_secs);
+        }
+        Ok(Self {
+            store,
+            ttl: Duration::from_secs(config.ttl_secs),
+        })
+    }
+
+    /// A cache keeping its entries in `store`
+    pub fn with_store(store: Arc<dyn ResponseStore>, ttl: Duration) -> Self {
+        Self { store: Some(store), ttl }
+    }
+
+    /// Where the entries of the repo at `repo_url` start
+    fn repo_prefix(repo_url: &str) -> String {
+        format!("{}{}|", KEY_PREFIX, repo_url)
+    }
+
+    /// `route`'s body for `commit` of `repo`, computed now if it isn't cached
+    /// (or `cache` is false), then stored unless it failed, for `ttl` if that
+    /// is shorter than the configured TTL
+    ///
+    /// `variant` tells apart responses the same commit gives to different
+    /// queries. Hits are marked `X-Cache: hit`, computed responses `miss`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn read_through<T, F>(
+        &self,
+        route: &'static str,
+        repo: &str,
+        commit: &str,
+        variant: &str,
+        ttl: Option<Duration>,
+        cache: bool,
+        compute: F,
+    ) -> Result<Response, AppError>
+    where
+        T: Serialize,
+        F: Future<Output = Result<T, AppError>>,
+    {
+        let Some(store) = self.store.as_ref().filter(|_| cache) else {
+            return Ok(Json(compute.await?).into_response());
+        };
+        let key = format!("{}{}|{}|{}", Self::repo_prefix(&git::repo_url(repo)), route, commit, variant);
+        match store.get(&key).await {
+            Ok(Some(body)) => {
+                metrics::record_response_cache(route, true);
+                return Ok(cached(body, "hit"));
+            }
+            Ok(None) => metrics::record_response_cache(route, false),
+            Err(e) => warn!("Failed to read the {} response cache for {}: {:#}", store.name(), key, e),
+        }
+
+        let value = compute.await?;
+        let body = serde_json::to_vec(&value).map_err(|e| AppError::internal("Failed to encode response").with_source(e))?;
+        if let Err(e) = store.put(&key, body.clone(), ttl.map_or(self.ttl, |ttl| ttl.min(self.ttl))).await {
+            warn!("Failed to write the {} response cache for {}: {:#}", store.name(), key, e);
+        }
+        Ok(cached(body, "miss"))
+    }
+
+    /// Forget every response cached for `repo`
+    pub async fn invalidate_repo(&self, repo: &str) {
+        self.invalidate(&Self::repo_prefix(&git::repo_url(repo))).await;
+    }
+
+    async fn invalidate(&self, prefix: &str) {
+        let Some(store) = &self.store else { return };
+        match store.delete_prefix(prefix).await {
+            Ok(()) => debug!("Dropped cached responses under {}", prefix),
+            Err(e) => warn!("Failed to drop cached responses under {} from {}: {:#}", prefix, store.name(), e),
+        }
+    }
+}
+
+/// A JSON body as a response marked `outcome` in X-Cache
+fn cached(body: Vec<u8>, outcome: &'static str) -> Response {
+    let mut response = (
+        [(header::CONTENT_TYPE, HeaderValue::from_static("application/json"))],
+        body,
+    )
+        .into_response();
+    response.headers_mut().insert(X_CACHE.clone(), HeaderValue::from_static(outcome));
+    response
+}
+
+/// Drop a repo's cached responses whenever one of its commits is processed
+/// or fails, on this instance or another, until shutdown
+pub fn spawn_invalidation(cache: Arc<ResponseCache>) {
+    if cache.store.is_none() {
+        return;
+    }
+    let mut events = events::subscribe();
+    tokio::spawn(async move {
+        loop {
+            let event = tokio::select! {
+                event = events.recv() => event,
+                _ = shutdown::started() => break,
+            };
+            match event {
+                Ok(event) => cache.invalidate(&ResponseCache::repo_prefix(&event.repo_url)).await,
+                // Missed events may have changed anything; start over
+                Err(RecvError::Lagged(_)) => cache.invalidate(KEY_PREFIX).await,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
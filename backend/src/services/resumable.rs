@@ -0,0 +1,362 @@
+use axum::http::HeaderMap;
+use axum::response::sse::Event;
+use futures_util::stream::{BoxStream, StreamExt};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use super::llm::CancelOnDisconnect;
+
+/// The events a client of a generation is sent
+pub type EventStream = BoxStream<'static, Result<Event, Infallible>>;
+
+/// The `Last-Event-ID` a reconnecting client sent, if any
+pub fn last_event_id(headers: &HeaderMap) -> Option<&str> {
+    headers.get("last-event-id")?.to_str().ok()
+}
+
+/// One SSE event of a generation, as kept for replay
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// Event type; None for plain `data` events
+    pub event: Option<&'static str>,
+    pub data: String,
+}
+
+impl Frame {
+    pub fn data(data: impl Into<String>) -> Self {
+        Self {
+            event: None,
+            data: data.into(),
+        }
+    }
+
+    pub fn named(event: &'static str, data: impl Into<String>) -> Self {
+        Self {
+            event: Some(event),
+            data: data.into(),
+        }
+    }
+
+    pub fn json(event: &'static str, value: &impl Serialize) -> Result<Self, serde_json::Error> {
+        Ok(Self::named(event, serde_json::to_string(value)?))
+    }
+
+    /// The SSE event, for streams that aren't resumable
+    pub fn into_event(self) -> Event {
+        let event = match self.event {
+            Some(name) => Event::default().event(name),
+            None => Event::default(),
+        };
+        event.data(self.data)
+    }
+}
+
+/// Streamed generations whose latest events are kept, so a client whose
+/// connection drops can reconnect with `Last-Event-ID` and carry on where it
+/// left off instead of starting the AI call over, and clients asking for
+/// what is already being generated follow that generation instead of
+/// starting another
+///
+/// Each event is sent with the ID `<generation>:<sequence>` and broadcast to
+/// every client following the generation. A generation keeps running while
+/// nobody is connected for `linger`, then its AI call is cancelled; a
+/// finished one can be replayed for `linger` more.
+pub struct ResumableStreams {
+    generations: Mutex<HashMap<Uuid, Arc<Generation>>>,
+    /// Latest events kept per generation
+    capacity: usize,
+    linger: Duration,
+}
+
+impl ResumableStreams {
+    pub fn new(capacity: usize, linger: Duration) -> Self {
+        Self {
+            generations: Mutex::new(HashMap::new()),
+            capacity: capacity.max(1),
+            linger,
+        }
+    }
+
+    /// Start keeping the events of a generation of `key`, which names what
+    /// it answers (a reconnect must ask for the same); `disconnect` cancels
+    /// its AI call once nobody listens any more
+    pub fn publish(self: &Arc<Self>, key: String, disconnect: CancelOnDisconnect) -> Publisher {
+        let generation = Arc::new(Generation {
+            id: Uuid::new_v4(),
+            key,
+            buffer: Mutex::new(Buffer {
+                events: VecDeque::new(),
+                next: 0,
+                done: false,
+                subscribers: 0,
+                disconnect: Some(disconnect),
+                live: Some(broadcast::channel(self.capacity).0),
+            }),
+            streams: Arc::downgrade(self),
+        });
+        debug!("Streaming generation {} of {:?}", generation.id, generation.key);
+        self.generations
+            .lock()
+            .unwrap()
+            .insert(generation.id, generation.clone());
+        Publisher(generation)
+    }
+
+    /// The events of a generation of `key` after `last_event_id`, then its
+    /// live ones; None if it's unknown, expired, for something else, or no
+    /// longer has every event after that one
+    pub fn resume(&self, key: &str, last_event_id: &str) -> Option<EventStream> {
+        let (id, seq) = last_event_id.trim().split_once(':')?;
+        let id: Uuid = id.parse().ok()?;
+        let seq: u64 = seq.parse().ok()?;
+        let generation = self.generations.lock().unwrap().get(&id).cloned()?;
+        if generation.key != key {
+            return None;
+        }
+        {
+            let buffer = generation.buffer.lock().unwrap();
+            let oldest = buffer.events.front().map_or(buffer.next, |(seq, _)| *seq);
+            if seq >= buffer.next || seq + 1 < oldest {
+                return None;
+            }
+        }
+        info!("Resuming generation {} after event {}", id, seq);
+        Some(generation.subscribe(seq + 1))
+    }
+
+    /// All events of the unfinished generation of `key`, from the first on;
+    /// None if nothing of `key` is being generated, or its first events are
+    /// no longer kept
+    pub fn join(&self, key: &str) -> Option<EventStream> {
+        let generation = self.in_progress(key)?;
+        if generation.buffer.lock().unwrap().events.front().is_some_and(|(seq, _)| *seq > 0) {
+            return None;
+        }
+        info!("Following generation {} of {:?} already in progress", generation.id, key);
+        Some(generation.subscribe(0))
+    }
+
+    /// The data of the `event` event of the unfinished generation of `key`,
+    /// once it is sent; None if nothing of `key` is being generated, or it
+    /// ends without one
+    ///
+    /// Counts as a client following the generation, so it isn't cancelled
+    /// while this waits.
+    pub async fn wait_for(&self, key: &str, event: &str) -> Option<String> {
+        let generation = self.in_progress(key)?;
+        let (kept, live) = {
+            let mut buffer = generation.buffer.lock().unwrap();
+            buffer.subscribers += 1;
+            let kept: Vec<Frame> = buffer.events.iter().map(|(_, frame)| frame.clone()).collect();
+            (kept, buffer.live.as_ref().map(broadcast::Sender::subscribe))
+        };
+        let _subscriber = Subscriber(generation);
+        if let Some(frame) = kept.into_iter().find(|frame| frame.event == Some(event)) {
+            return Some(frame.data);
+        }
+        let mut live = live?;
+        loop {
+            match live.recv().await {
+                Ok((_, frame)) if frame.event == Some(event) => return Some(frame.data),
+                // Only the one event matters, so skipped ones don't
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// The unfinished generation of `key`, if any
+    fn in_progress(&self, key: &str) -> Option<Arc<Generation>> {
+        self.generations
+            .lock()
+            .unwrap()
+            .values()
+            .find(|g| g.key == key && !g.buffer.lock().unwrap().done)
+            .cloned()
+    }
+
+    /// Forget generation `id` after the linger period
+    fn expire(self: Arc<Self>, id: Uuid) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        runtime.spawn(async move {
+            tokio::time::sleep(self.linger).await;
+            self.generations.lock().unwrap().remove(&id);
+        });
+    }
+}
+
+/// A generation in progress: its latest events and who is listening
+struct Generation {
+    id: Uuid,
+    key: String,
+    buffer: Mutex<Buffer>,
+    streams: Weak<ResumableStreams>,
+}
+
+struct Buffer {
+    /// Latest events with their sequence numbers, oldest first
+    events: VecDeque<(u64, Frame)>,
+    /// Sequence number of the next event
+    next: u64,
+    done: bool,
+    subscribers: usize,
+    /// Cancels the AI call; taken when the generation ends or is abandoned
+    disconnect: Option<CancelOnDisconnect>,
+    /// Sends events to the clients following along; dropped when the
+    /// generation ends, which ends their streams
+    live: Option<broadcast::Sender<(u64, Frame)>>,
+}
+
+impl Generation {
+    /// Its events from sequence number `from` on, live until it ends
+    fn subscribe(self: Arc<Self>, from: u64) -> EventStream {
+        // Kept events and the live ones, taken together so none falls in between
+        let (kept, live, gap) = {
+            let mut buffer = self.buffer.lock().unwrap();
+            buffer.subscribers += 1;
+            let oldest = buffer.events.front().map_or(buffer.next, |(seq, _)| *seq);
+            let kept: Vec<(u64, Frame)> = buffer.events.iter().filter(|(seq, _)| *seq >= from).cloned().collect();
+            (kept, buffer.live.as_ref().map(broadcast::Sender::subscribe), from < oldest)
+        };
+        let subscriber = Subscriber(self);
+        async_stream::stream! {
+            let id = subscriber.0.id;
+            if gap {
+                for event in fell_behind() {
+                    yield Ok(event);
+                }
+                return;
+            }
+            let mut cursor = from;
+            for (seq, frame) in kept {
+                cursor = seq + 1;
+                yield Ok(frame.into_event().id(format!("{}:{}", id, seq)));
+            }
+            let Some(mut live) = live else {
+                return;
+            };
+            loop {
+                match live.recv().await {
+                    Ok((seq, frame)) if seq >= cursor => {
+                        cursor = seq + 1;
+                        yield Ok(frame.into_event().id(format!("{}:{}", id, seq)));
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Closed) => return,
+                    Err(RecvError::Lagged(_)) => {
+                        for event in fell_behind() {
+                            yield Ok(event);
+                        }
+                        return;
+                    }
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+/// What a client is sent once the events it still needs are no longer kept
+fn fell_behind() -> [Event; 2] {
+    [
+        Event::default().data("[ERROR: the stream moved on past this client; request it again]"),
+        Event::default().data("[DONE]"),
+    ]
+}
+
+/// A client listening to a generation; once the last one goes away for
+/// longer than the linger period, the generation is cancelled
+struct Subscriber(Arc<Generation>);
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        let generation = &self.0;
+        {
+            let mut buffer = generation.buffer.lock().unwrap();
+            buffer.subscribers -= 1;
+            if buffer.subscribers > 0 || buffer.done {
+                return;
+            }
+        }
+        let Some(streams) = generation.streams.upgrade() else {
+            return;
+        };
+        let abandon = {
+            let generation = generation.clone();
+            move || {
+                let mut buffer = generation.buffer.lock().unwrap();
+                if buffer.subscribers == 0 && !buffer.done {
+                    // Dropping it unfinished logs the disconnect and cancels the AI call
+                    buffer.disconnect.take();
+                }
+            }
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) if !streams.linger.is_zero() => {
+                debug!("Nobody is listening to generation {}; keeping it for {:?}", generation.id, streams.linger);
+                runtime.spawn(async move {
+                    tokio::time::sleep(streams.linger).await;
+                    abandon();
+                });
+            }
+            _ => abandon(),
+        }
+    }
+}
+
+/// Adds the events of a generation as they are produced; dropping it ends
+/// the generation
+pub struct Publisher(Arc<Generation>);
+
+impl Publisher {
+    pub fn push(&self, frame: Frame) {
+        let generation = &self.0;
+        let Some(streams) = generation.streams.upgrade() else {
+            return;
+        };
+        {
+            let mut buffer = generation.buffer.lock().unwrap();
+            let seq = buffer.next;
+            buffer.next += 1;
+            buffer.events.push_back((seq, frame.clone()));
+            while buffer.events.len() > streams.capacity {
+                buffer.events.pop_front();
+            }
+            if let Some(live) = &buffer.live {
+                // Fails only when nobody is following, which is fine
+                let _ = live.send((seq, frame));
+            }
+        }
+    }
+
+    /// Its events from the first on, live until it ends
+    pub fn subscribe(&self) -> EventStream {
+        self.0.clone().subscribe(0)
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        let generation = &self.0;
+        {
+            let mut buffer = generation.buffer.lock().unwrap();
+            buffer.done = true;
+            if let Some(mut disconnect) = buffer.disconnect.take() {
+                disconnect.finish();
+            }
+            buffer.live = None;
+        }
+        if let Some(streams) = generation.streams.upgrade() {
+            streams.expire(generation.id);
+        }
+    }
+}
+
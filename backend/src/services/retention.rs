@@ -0,0 +1,44 @@
+use kicad_db::{
+    purge_deleted_schematics, purge_orphaned_images, purge_summary_chunks, purge_webhook_deliveries, PgPool,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long webhook delivery IDs are kept to recognize redeliveries; GitHub
+/// only offers redelivery of the last three days
+const DELIVERY_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How long a cached step of a repo overview is kept after it was last used
+const SUMMARY_CHUNK_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Hourly, hard-delete commits soft-deleted more than `retention` ago, then
+/// images no commit uses any more, and forget old webhook deliveries and
+/// overview steps
+pub fn spawn_purge(pool: Arc<PgPool>, retention: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match purge_deleted_schematics(&pool, retention).await {
+                Ok(0) => debug!("No deleted commits to purge"),
+                Ok(n) => info!("Purged {} deleted commits", n),
+                Err(e) => warn!("Failed to purge deleted commits: {}", e),
+            }
+            match purge_orphaned_images(&pool).await {
+                Ok(n) => debug!("Purged {} unused images", n),
+                Err(e) => warn!("Failed to purge unused images: {}", e),
+            }
+            match purge_webhook_deliveries(&pool, DELIVERY_RETENTION).await {
+                Ok(n) => debug!("Forgot {} old webhook deliveries", n),
+                Err(e) => warn!("Failed to purge old webhook deliveries: {}", e),
+            }
+            match purge_summary_chunks(&pool, SUMMARY_CHUNK_RETENTION).await {
+                Ok(n) => debug!("Purged {} unused summary steps", n),
+                Err(e) => warn!("Failed to purge unused summary steps: {}", e),
+            }
+        }
+    });
+}
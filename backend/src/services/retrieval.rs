@@ -0,0 +1,275 @@
+use kicad_db::{
+    latest_distilled_json, list_components, retrieve_distilled_json, search_schematics,
+    semantic_search_available, semantic_search_commits, semantic_search_components,
+    ComponentRecord, OrgFilter, PgPool,
+};
+use serde_json::Value;
+use std::collections::HashSet;
+use tracing::{info, warn};
+
+use super::git;
+use super::llm::ChatProvider;
+use crate::types::{ChatSource, ChatSourceKind};
+
+/// Longest source text put in the prompt, in characters
+const MAX_SOURCE_CHARS: usize = 1500;
+/// Most pins listed for a net
+const MAX_NET_PINS: usize = 24;
+
+/// Where to look, and what the caller may see
+pub struct RetrievalScope<'a> {
+    /// "owner/repo", already checked against the caller's org by the route
+    pub repo: &'a str,
+    /// Commit for components and nets; the newest distilled commit if unset
+    pub commit: Option<&'a str>,
+    pub include_private: bool,
+    pub embedding_model: &'a str,
+}
+
+/// Sources relevant to `question`: up to `top_k` each of commit summaries,
+/// components and nets
+///
+/// Commits and parts are found by semantic search when pgvector is available
+/// (full-text search on commits otherwise). Components and nets at the target
+/// commit are matched by the references and net names the question mentions.
+/// Each lookup that fails is logged and skipped, so chat still works without
+/// context.
+pub async fn retrieve(
+    pool: &PgPool,
+    chat: &dyn ChatProvider,
+    scope: &RetrievalScope<'_>,
+    question: &str,
+    top_k: usize,
+) -> Vec<ChatSource> {
+    let repo_url = git::repo_url(scope.repo);
+    let limit = top_k as i64;
+    let mut sources = Vec::new();
+
+    let embedding = match semantic_search_available(pool).await {
+        Ok(true) => match chat.embed(scope.embedding_model, &[question.to_string()]).await {
+            Ok(mut vectors) => vectors.pop(),
+            Err(e) => {
+                warn!("Failed to embed the chat question: {}", e);
+                None
+            }
+        },
+        Ok(false) => None,
+        Err(e) => {
+            warn!("Failed to check for semantic search support: {}", e);
+            None
+        }
+    };
+
+    // Commit summaries
+    match &embedding {
+        Some(embedding) => {
+            match semantic_search_commits(pool, scope.embedding_model, embedding, Some(&repo_url), limit, scope.include_private, OrgFilter::Any).await {
+                Ok(hits) => sources.extend(hits.into_iter().map(|hit| {
+                    let text = [hit.blurb.as_deref(), hit.change_summary.as_deref()]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>()
+                        .join("\n\n");
+                    commit_source(hit.repo_url, hit.commit_hash, hit.git_message.as_deref(), &text, Some(hit.similarity))
+                })),
+                Err(e) => warn!("Semantic commit search failed for {}: {}", scope.repo, e),
+            }
+        }
+        None => match search_schematics(pool, Some(&repo_url), question, limit, scope.include_private, OrgFilter::Any).await {
+            Ok(hits) => sources.extend(hits.into_iter().map(|hit| {
+                let snippet = hit.snippet.replace("<mark>", "").replace("</mark>", "");
+                let text = hit.blurb.unwrap_or(snippet);
+                commit_source(hit.repo_url, hit.commit_hash, hit.git_message.as_deref(), &text, None)
+            })),
+            Err(e) => warn!("Commit search failed for {}: {}", scope.repo, e),
+        },
+    }
+
+    // Parts similar to the question, where the repo first used them
+    if let Some(embedding) = &embedding {
+        match semantic_search_components(pool, scope.embedding_model, embedding, Some(&repo_url), limit, scope.include_private, OrgFilter::Any).await {
+            Ok(hits) => sources.extend(hits.into_iter().map(|hit| {
+                let part = [hit.manufacturer.as_deref(), Some(hit.mpn.as_str())]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                ChatSource {
+                    id: String::new(),
+                    kind: ChatSourceKind::Component,
+                    title: format!("{} ({}), first used in {}", hit.reference, part, short(&hit.commit_hash)),
+                    text: hit.description.unwrap_or_default(),
+                    repo_url: hit.repo_url,
+                    commit_hash: hit.commit_hash,
+                    reference: Some(hit.reference),
+                    net: None,
+                    score: Some(hit.similarity),
+                }
+            })),
+            Err(e) => warn!("Semantic part search failed for {}: {}", scope.repo, e),
+        }
+    }
+
+    // Components and nets the question names, at the target commit
+    let distilled = match scope.commit {
+        Some(commit) => retrieve_distilled_json(pool, &repo_url, commit)
+            .await
+            .map(|d| d.map(|d| (commit.to_string(), d))),
+        None => latest_distilled_json(pool, &repo_url).await,
+    };
+    match distilled {
+        Ok(Some((commit, distilled))) => {
+            let terms = question_terms(question);
+            match list_components(pool, &repo_url, &commit).await {
+                Ok(components) => sources.extend(
+                    named_components(&components, &terms, top_k)
+                        .into_iter()
+                        .map(|c| component_source(&repo_url, &commit, c)),
+                ),
+                Err(e) => warn!("Failed to list components of {}/{}: {}", scope.repo, commit, e),
+            }
+            sources.extend(named_nets(&distilled, &terms, top_k).into_iter().map(|(name, pins)| {
+                ChatSource {
+                    id: String::new(),
+                    kind: ChatSourceKind::Net,
+                    title: format!("Net {} at {}", name, short(&commit)),
+                    text: pins,
+                    repo_url: repo_url.clone(),
+                    commit_hash: commit.clone(),
+                    reference: None,
+                    net: Some(name),
+                    score: None,
+                }
+            }));
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Failed to load distilled schematic for {}: {}", scope.repo, e),
+    }
+
+    for (i, source) in sources.iter_mut().enumerate() {
+        source.id = format!("S{}", i + 1);
+        if source.text.chars().count() > MAX_SOURCE_CHARS {
+            source.text = source.text.chars().take(MAX_SOURCE_CHARS).collect::<String>() + "…";
+        }
+    }
+    info!("Retrieved {} sources for a question about {}", sources.len(), scope.repo);
+    sources
+}
+
+fn short(commit: &str) -> &str {
+    &commit[..8.min(commit.len())]
+}
+
+fn commit_source(
+    repo_url: String,
+    commit_hash: String,
+    message: Option<&str>,
+    text: &str,
+    score: Option<f32>,
+) -> ChatSource {
+    let title = match message.and_then(|m| m.lines().next()) {
+        Some(subject) => format!("Commit {}: {}", short(&commit_hash), subject),
+        None => format!("Commit {}", short(&commit_hash)),
+    };
+    ChatSource {
+        id: String::new(),
+        kind: ChatSourceKind::Commit,
+        title,
+        text: if text.is_empty() { message.unwrap_or_default().to_string() } else { text.to_string() },
+        repo_url,
+        commit_hash,
+        reference: None,
+        net: None,
+        score,
+    }
+}
+
+fn component_source(repo_url: &str, commit: &str, component: &ComponentRecord) -> ChatSource {
+    let details: Vec<String> = [
+        component.value.as_deref().map(|v| format!("Value: {}", v)),
+        component.mpn.as_deref().map(|m| format!("MPN: {}", m)),
+        component.footprint.as_deref().map(|f| format!("Footprint: {}", f)),
+        component.sheet.as_deref().map(|s| format!("Sheet: {}", s)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    ChatSource {
+        id: String::new(),
+        kind: ChatSourceKind::Component,
+        title: format!("Component {} at {}", component.reference, short(commit)),
+        text: details.join("\n"),
+        repo_url: repo_url.to_string(),
+        commit_hash: commit.to_string(),
+        reference: Some(component.reference.clone()),
+        net: None,
+        score: None,
+    }
+}
+
+/// Words of the question, uppercased with only letters and digits kept, so
+/// "usb_d+" matches the net "USB_D+" and "u3," the reference "U3"
+fn question_terms(question: &str) -> HashSet<String> {
+    question
+        .split_whitespace()
+        .map(normalize)
+        .filter(|term| !term.is_empty())
+        .collect()
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn named_components<'a>(
+    components: &'a [ComponentRecord],
+    terms: &HashSet<String>,
+    top_k: usize,
+) -> Vec<&'a ComponentRecord> {
+    components
+        .iter()
+        .filter(|c| terms.contains(&normalize(&c.reference)))
+        .take(top_k)
+        .collect()
+}
+
+/// Nets the question names, with their pins listed ("U3 pin 8, C1 pin 1")
+///
+/// Distilled nets map each net name to the pins on it per reference. Names
+/// are compared without sheet path, so "VIN" finds "/Power/VIN". Single
+/// characters and bare numbers don't count as names.
+fn named_nets(distilled: &Value, terms: &HashSet<String>, top_k: usize) -> Vec<(String, String)> {
+    let Some(nets) = distilled.get("nets").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+    nets.iter()
+        .filter(|(name, _)| {
+            let base = normalize(name.rsplit('/').next().unwrap_or(name));
+            base.len() > 1 && !base.chars().all(|c| c.is_ascii_digit()) && terms.contains(&base)
+        })
+        .take(top_k)
+        .map(|(name, pins)| {
+            let mut listed: Vec<String> = pins
+                .as_object()
+                .into_iter()
+                .flatten()
+                .flat_map(|(reference, pins)| {
+                    pins.as_array().into_iter().flatten().map(move |pin| {
+                        let number = pin.get("Pin").and_then(Value::as_str).unwrap_or("?");
+                        format!("{} pin {}", reference, number)
+                    })
+                })
+                .collect();
+            let total = listed.len();
+            listed.truncate(MAX_NET_PINS);
+            let mut text = format!("Connects {} pins: {}", total, listed.join(", "));
+            if total > MAX_NET_PINS {
+                text.push_str(", …");
+            }
+            (name.clone(), text)
+        })
+        .collect()
+}
@@ -0,0 +1,140 @@
+use anyhow::{bail, Result};
+use kicad_db::{
+    messages::{ChatCompletionRequest, Message},
+    prompt_budget::PromptBudget,
+    prompts::{PromptLibrary, REVIEW_CHECKLIST},
+    schematic::diff::ProjectDiff,
+    ErcFinding, NewChecklistItem,
+};
+use std::collections::BTreeSet;
+use tracing::warn;
+
+use crate::services::{llm::ChatProvider, summary};
+
+/// Most items a checklist may have
+pub const MAX_ITEMS: usize = 15;
+/// Output allowed for the checklist
+const CHECKLIST_MAX_TOKENS: u32 = 1_200;
+/// Categories the prompt offers; an item filed under anything else is "other"
+const CATEGORIES: &[&str] = &["power", "mcu", "connectors", "signal", "mechanical", "erc", "other"];
+/// Names of the checklist prompt's sections
+const CHANGES_SECTION: &str = "changes";
+const ERC_SECTION: &str = "erc";
+
+/// References of the components `projects` changed or the findings name,
+/// which checklist items are matched against
+pub fn changed_references(projects: &[(String, ProjectDiff)], findings: &[&ErcFinding]) -> BTreeSet<String> {
+    let mut references = BTreeSet::new();
+    for (_, diff) in projects {
+        references.extend(diff.added_components.iter().cloned());
+        references.extend(diff.removed_components.iter().cloned());
+        references.extend(diff.changed_components.iter().map(|change| change.reference.clone()));
+        for net in &diff.changed_nets {
+            // Pins are "R1.2"
+            let pins = net.added.iter().chain(&net.removed);
+            references.extend(pins.filter_map(|pin| Some(pin.split_once('.')?.0.to_string())));
+        }
+    }
+    references.extend(findings.iter().filter_map(|finding| finding.reference.clone()));
+    references
+}
+
+/// "- [rule] message" for each finding
+pub fn erc_lines(findings: &[&ErcFinding]) -> String {
+    findings
+        .iter()
+        .map(|finding| format!("- [{}] {}", finding.rule.as_str(), finding.message))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The items of a checklist the model wrote as "- [category] item" lines;
+/// other lines are ignored. Each item lists the `references` it mentions
+pub fn parse(text: &str, references: &BTreeSet<String>) -> Vec<NewChecklistItem> {
+    text.lines()
+        .filter_map(|line| {
+            let item = line.trim().strip_prefix("- ").or_else(|| line.trim().strip_prefix("* "))?;
+            let (category, item) = match item.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+                Some((category, item)) => (category.trim().to_lowercase(), item.trim()),
+                None => ("other".to_string(), item.trim()),
+            };
+            if item.is_empty() {
+                return None;
+            }
+            let category = match CATEGORIES.contains(&category.as_str()) {
+                true => category,
+                false => "other".to_string(),
+            };
+            let mut components: Vec<String> = Vec::new();
+            for word in item.split(|c: char| !c.is_ascii_alphanumeric() && c != '_') {
+                if references.contains(word) && !components.iter().any(|c| c == word) {
+                    components.push(word.to_string());
+                }
+            }
+            Some(NewChecklistItem {
+                category,
+                text: item.to_string(),
+                components,
+            })
+        })
+        .take(MAX_ITEMS)
+        .collect()
+}
+
+/// Ask Grok for the review checklist of a commit, returning its items and
+/// the prompt version
+///
+/// `changes` is the commit's component and net diff, `erc` the findings it
+/// introduced (see [`erc_lines`]); both are cut short to fit `context_window`.
+#[allow(clippy::too_many_arguments)]
+pub async fn generate(
+    chat: &dyn ChatProvider,
+    prompts: &PromptLibrary,
+    model: &str,
+    context_window: usize,
+    repo: &str,
+    commit: &str,
+    message: Option<&str>,
+    changes: &str,
+    erc: &str,
+    references: &BTreeSet<String>,
+) -> Result<(Vec<NewChecklistItem>, String)> {
+    let variables = |changes: &str, erc: &str| {
+        serde_json::json!({
+            "repo": repo,
+            "commit": commit,
+            "message": message.unwrap_or("(no message)"),
+            "changes": changes,
+            "erc": erc,
+            "max_items": MAX_ITEMS,
+        })
+    };
+    let bare = prompts.render(REVIEW_CHECKLIST, variables("", ""))?;
+    let fitted = PromptBudget::new(model, context_window)
+        .reserve_output(CHECKLIST_MAX_TOKENS)
+        .fixed(&bare.text)
+        .section(CHANGES_SECTION, changes)
+        .section(ERC_SECTION, erc)
+        .fit();
+    if !fitted.truncated.is_empty() {
+        warn!(
+            "Shortened {} in the review checklist prompt for {}@{} to fit the context window",
+            fitted.truncated.join(", "),
+            repo,
+            commit
+        );
+    }
+    let prompt = prompts.render(
+        REVIEW_CHECKLIST,
+        variables(fitted.text(CHANGES_SECTION), fitted.text(ERC_SECTION)),
+    )?;
+
+    let mut request = ChatCompletionRequest::with_stream(vec![Message::user(prompt.text.clone())], model.to_string(), true);
+    request.max_tokens = Some(CHECKLIST_MAX_TOKENS);
+    let answer = summary::complete(chat, &request).await?;
+    let items = parse(&answer, references);
+    if items.is_empty() {
+        bail!("The model's checklist had no \"- [category] item\" lines");
+    }
+    Ok((items, prompt.label()))
+}
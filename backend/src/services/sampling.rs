@@ -0,0 +1,97 @@
+use futures_util::future::BoxFuture;
+use kicad_db::{
+    messages::{ChatCompletionRequest, Sampling},
+    xai_client::{ChatCompletionResponse, ChatCompletionStream, DeferredRequest, ResponsesRequest, ResponsesResponse},
+    XaiError,
+};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+use super::llm::ChatProvider;
+
+/// `chat` with a kind of call's sampling settings (see `SamplingConfig`)
+/// filled into its requests
+pub fn sampled(chat: Arc<dyn ChatProvider>, sampling: &Sampling) -> Arc<dyn ChatProvider> {
+    if *sampling == Sampling::default() {
+        return chat;
+    }
+    Arc::new(SampledChatProvider {
+        inner: chat,
+        sampling: sampling.clone(),
+    })
+}
+
+/// A [`ChatProvider`] setting the temperature, top_p, token limit and stop
+/// sequences of requests that don't set their own
+///
+/// Sits in front of the response cache, whose keys include the settings.
+pub struct SampledChatProvider {
+    inner: Arc<dyn ChatProvider>,
+    sampling: Sampling,
+}
+
+impl SampledChatProvider {
+    fn fill(&self, request: &ChatCompletionRequest) -> ChatCompletionRequest {
+        let mut filled = request.clone();
+        self.sampling.fill(&mut filled);
+        filled
+    }
+}
+
+impl ChatProvider for SampledChatProvider {
+    fn responses<'a>(
+        &'a self,
+        request: &'a ResponsesRequest,
+    ) -> BoxFuture<'a, Result<ResponsesResponse, XaiError>> {
+        Box::pin(async move {
+            let mut request = request.clone();
+            self.sampling.fill_responses(&mut request);
+            self.inner.responses(&request).await
+        })
+    }
+
+    fn chat_completion_stream<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<ChatCompletionStream, XaiError>> {
+        Box::pin(async move {
+            let request = self.fill(request);
+            self.inner.chat_completion_stream(&request, cancel).await
+        })
+    }
+
+    fn submit_deferred<'a>(
+        &'a self,
+        request: &'a ChatCompletionRequest,
+    ) -> BoxFuture<'a, Result<DeferredRequest, XaiError>> {
+        Box::pin(async move {
+            let request = self.fill(request);
+            self.inner.submit_deferred(&request).await
+        })
+    }
+
+    fn deferred_completion<'a>(
+        &'a self,
+        deferred: &'a DeferredRequest,
+    ) -> BoxFuture<'a, Result<Option<ChatCompletionResponse>, XaiError>> {
+        self.inner.deferred_completion(deferred)
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, XaiError>> {
+        self.inner.list_models()
+    }
+
+    fn embed<'a>(&'a self, model: &'a str, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, XaiError>> {
+        self.inner.embed(model, texts)
+    }
+
+    fn available(&self) -> bool {
+        self.inner.available()
+    }
+
+    /// Without the cache, and without the settings either
+    fn uncached(&self) -> &dyn ChatProvider {
+        self.inner.uncached()
+    }
+}
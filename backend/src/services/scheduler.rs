@@ -0,0 +1,152 @@
+use kicad_db::{due_repo_schedules, get_registered_repo, record_repo_check};
+use once_cell::sync::Lazy;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use super::{git, registry};
+use crate::controllers::hook;
+use crate::shutdown;
+use crate::state::AppState;
+use crate::types::WorkClass;
+
+/// How often schedules are checked for due repos
+const TICK_INTERVAL: Duration = Duration::from_secs(60);
+/// Most repos enqueued per tick; the rest wait for the next one
+const MAX_DUE_PER_TICK: i64 = 50;
+const QUEUE_CAPACITY: usize = 256;
+
+/// Repos enqueued or being re-synced, so a slow re-sync isn't queued twice
+static QUEUED: Lazy<Mutex<HashSet<String>>> = Lazy::new(Default::default);
+
+/// Re-sync repos whose schedule is due, in case webhooks were missed
+///
+/// Each minute, due repos (see `kicad_db::due_repo_schedules`) are queued for
+/// a worker that re-clones them and generates overviews for any commits
+/// missing one, like `/api/hook/refresh`. Re-syncs run one at a time; each
+/// finished one is recorded, which also schedules the next. The repo's
+/// processing policy decides whether its branch is processed and summarized.
+pub fn spawn(app: AppState, default_interval_secs: u64) {
+    let default_interval_secs = i64::try_from(default_interval_secs).unwrap_or(i64::MAX);
+    let (queue, jobs) = mpsc::channel(QUEUE_CAPACITY);
+    let pool = app.pool.clone();
+    tokio::spawn(work(app, jobs));
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if shutdown::requested() {
+                break;
+            }
+            let due = match due_repo_schedules(&pool, default_interval_secs, MAX_DUE_PER_TICK).await {
+                Ok(due) => due,
+                Err(e) => {
+                    warn!("Failed to load due repo schedules: {}", e);
+                    continue;
+                }
+            };
+            for schedule in due {
+                if !QUEUED.lock().unwrap().insert(schedule.repo_url.clone()) {
+                    continue;
+                }
+                debug!("Queueing re-sync of {}", schedule.repo_url);
+                if let Err(e) = queue.try_send(schedule.repo_url) {
+                    warn!("Re-sync queue is full; will retry next tick");
+                    QUEUED.lock().unwrap().remove(&e.into_inner());
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Sync newly registered repos in the background, one at a time, as a
+/// scheduled re-sync would: their history is processed, then summarized as
+/// a backfill if their processing policy asks
+///
+/// Repos already queued for a re-sync are left to it.
+pub(crate) fn spawn_initial_syncs(app: AppState, repo_urls: Vec<String>) {
+    if repo_urls.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        for repo_url in repo_urls {
+            if shutdown::requested() {
+                break;
+            }
+            if !QUEUED.lock().unwrap().insert(repo_url.clone()) {
+                continue;
+            }
+            let error = resync(&app, &repo_url, WorkClass::Backfill).await.err();
+            if let Err(e) = record_repo_check(&app.pool, &repo_url, error.as_deref()).await {
+                warn!("Failed to record the initial sync of {}: {}", repo_url, e);
+            }
+            QUEUED.lock().unwrap().remove(&repo_url);
+        }
+    });
+}
+
+async fn work(app: AppState, mut jobs: mpsc::Receiver<String>) {
+    while let Some(repo_url) = jobs.recv().await {
+        // Queued re-syncs stay due, so they run after the restart
+        if shutdown::requested() {
+            break;
+        }
+        // Commits a webhook would have brought in, had it arrived
+        let error = resync(&app, &repo_url, WorkClass::Webhook).await.err();
+        // Cut short by the shutdown: leave it due rather than recording a failure
+        if shutdown::requested() {
+            break;
+        }
+        if let Err(e) = record_repo_check(&app.pool, &repo_url, error.as_deref()).await {
+            warn!("Failed to record re-sync of {}: {}", repo_url, e);
+        }
+        QUEUED.lock().unwrap().remove(&repo_url);
+    }
+}
+
+/// Fetch the repo afresh and process new commits, summarizing them as
+/// `class`, unless its processing policy leaves out the branch it follows;
+/// errors are summarized for the schedule
+async fn resync(app: &AppState, repo_url: &str, class: WorkClass) -> Result<(), String> {
+    let Some(repo) = git::repo_slug(repo_url) else {
+        return Err(format!("Can't re-sync {}: not a recognised clone URL", repo_url));
+    };
+    info!("Scheduled re-sync of {}", repo);
+
+    let registered = get_registered_repo(&app.pool, repo_url)
+        .await
+        .map_err(|e| format!("Failed to look up repository: {}", e))?;
+    let policy = registered.as_ref().map(|r| r.processing.clone()).unwrap_or_default();
+
+    if let Err(e) = git::invalidate_cache(&repo).await {
+        warn!("Failed to invalidate cache for {}: {}", repo, e);
+    }
+    app.responses.invalidate_repo(&repo).await;
+    if policy.branches.is_some() {
+        let branch = match registered.as_ref().and_then(|r| r.default_branch.clone()) {
+            Some(branch) => Some(branch),
+            None => git::head_branch(&repo).await.map_err(|e| format!("{:#}", e))?,
+        };
+        if let Some(branch) = branch.filter(|branch| !registry::processes_branch(&policy, branch)) {
+            info!("Skipping re-sync of {}: its processing policy leaves out {}", repo, branch);
+            return Ok(());
+        }
+    }
+    let response = hook::process_repo_internal(app.pool.clone(), app.schematics.clone(), repo.clone(), hook::Scan::Incremental)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    info!("Re-sync of {} processed {} commit(s)", repo, response.processed);
+    if policy.ai && response.processed > 0 {
+        hook::summarize_new_commits(app, &repo, class).await;
+    }
+    match response.errors.as_slice() {
+        [] => Ok(()),
+        [error] => Err(error.clone()),
+        [first, rest @ ..] => Err(format!("{} (and {} more)", first, rest.len())),
+    }
+}
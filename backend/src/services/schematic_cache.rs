@@ -0,0 +1,143 @@
+use git2::{ObjectType, Oid};
+use kicad_db::schematic::model::{parse_sheet, SheetFile};
+use kicad_db::SchematicError;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use crate::services::{git, metrics};
+
+/// What a parsed sheet is remembered by: repo, commit, path and git blob hash
+type Key = (String, String, String, Oid);
+
+struct Entry {
+    sheet: SheetFile,
+    /// Length of the source, which stands in for the parsed size
+    bytes: usize,
+    used: u64,
+    /// The repo's git cache generation when the sheet was parsed
+    generation: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<Key, Entry>,
+    /// Keys by when they were last used, oldest first
+    by_use: BTreeMap<u64, Key>,
+    clock: u64,
+    bytes: usize,
+}
+
+impl Entries {
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.by_key.remove(key) {
+            self.by_use.remove(&entry.used);
+            self.bytes -= entry.bytes;
+        }
+    }
+
+    fn touch(&mut self, key: &Key) {
+        self.clock += 1;
+        let clock = self.clock;
+        if let Some(entry) = self.by_key.get_mut(key) {
+            self.by_use.remove(&entry.used);
+            entry.used = clock;
+            self.by_use.insert(clock, key.clone());
+        }
+    }
+}
+
+/// Parsed schematic sheets, the least recently used dropped first once their
+/// sources add up to more than the capacity
+///
+/// The BOM, netlist, diff and rendering requests for a commit come in bursts
+/// and each parse the same files; through this they share one parse. A
+/// repo's sheets are dropped when its git cache is deleted.
+pub struct SchematicCache {
+    capacity_bytes: usize,
+    entries: Mutex<Entries>,
+}
+
+impl SchematicCache {
+    /// A cache for sheets parsed from up to `capacity_bytes` of source; 0
+    /// keeps nothing
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self {
+            capacity_bytes,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// How many sheets are held, and the size of their sources
+    pub fn size(&self) -> (usize, usize) {
+        let entries = self.entries.lock().unwrap();
+        (entries.by_key.len(), entries.bytes)
+    }
+
+    /// The sheet in `text`, the file at `path` in `repo` at `commit`: parsed
+    /// now, or earlier from the same blob
+    pub fn parse(&self, repo: &str, commit: &str, path: &str, text: &str) -> Result<SheetFile, SchematicError> {
+        let blob = match Oid::hash_object(ObjectType::Blob, text.as_bytes()) {
+            Ok(blob) if self.capacity_bytes > 0 => blob,
+            _ => return parse_sheet(path, text),
+        };
+        let key = (repo.to_string(), commit.to_string(), path.to_string(), blob);
+        let generation = git::cache_generation(repo);
+
+        if let Some(sheet) = self.get(&key, generation) {
+            metrics::record_schematic_cache(true);
+            return Ok(sheet);
+        }
+        metrics::record_schematic_cache(false);
+        let sheet = parse_sheet(path, text)?;
+        self.insert(key, sheet.clone(), text.len(), generation);
+        Ok(sheet)
+    }
+
+    fn get(&self, key: &Key, generation: u64) -> Option<SheetFile> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.by_key.get(key) {
+            Some(entry) if entry.generation == generation => {
+                let sheet = entry.sheet.clone();
+                entries.touch(key);
+                Some(sheet)
+            }
+            Some(_) => {
+                entries.remove(key);
+                metrics::set_schematic_cache_size(entries.by_key.len(), entries.bytes);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, key: Key, sheet: SheetFile, bytes: usize, generation: u64) {
+        if bytes > self.capacity_bytes {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(&key);
+        while entries.bytes + bytes > self.capacity_bytes {
+            let Some((_, oldest)) = entries.by_use.pop_first() else {
+                break;
+            };
+            if let Some(entry) = entries.by_key.remove(&oldest) {
+                entries.bytes -= entry.bytes;
+                metrics::record_schematic_cache_eviction();
+            }
+        }
+        entries.clock += 1;
+        let used = entries.clock;
+        entries.by_use.insert(used, key.clone());
+        entries.by_key.insert(
+            key,
+            Entry {
+                sheet,
+                bytes,
+                used,
+                generation,
+            },
+        );
+        entries.bytes += bytes;
+        metrics::set_schematic_cache_size(entries.by_key.len(), entries.bytes);
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use kicad_db::schematic::{render_sheet_svg, Project, TitleBlock};
+use once_cell::sync::Lazy;
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, TextStr};
+use svg2pdf::usvg::{self, fontdb, PostProcessingSteps, TreeParsing, TreePostProc};
+
+/// Bumped whenever sheets are drawn differently, so PDFs cached by an older
+/// renderer are drawn again
+pub const RENDERER_VERSION: u32 = 1;
+
+/// Sheets are sized in mm, which usvg turns into CSS pixels
+const SVG_DPI: f32 = 96.0;
+/// PDF points per CSS pixel
+const POINTS_PER_PX: f32 = 72.0 / SVG_DPI;
+
+/// Fonts the sheets' text is drawn with, loaded once; svg2pdf uses an older
+/// usvg than the thumbnail renderer, so they aren't shared. "sans-serif"
+/// means Arial to fontdb, so without it another installed sans face stands in
+static FONTS: Lazy<fontdb::Database> = Lazy::new(|| {
+    let mut fonts = fontdb::Database::new();
+    fonts.load_system_fonts();
+    let families: Vec<String> = fonts
+        .faces()
+        .flat_map(|face| face.families.iter().map(|(family, _)| family.clone()))
+        .collect();
+    if !families.iter().any(|family| family == "Arial") {
+        let sans = families
+            .iter()
+            .find(|family| family.contains("Sans") && !family.contains("Mono") && !family.contains("Serif"));
+        if let Some(sans) = sans {
+            fonts.set_sans_serif_family(sans.as_str());
+        }
+    }
+    fonts
+});
+
+/// A PDF of every sheet of `projects`, one page each in hierarchy order,
+/// with a title block naming the repo, commit and `date`
+///
+/// Text is turned into outlines, so the PDF needs no fonts to view; it's
+/// dropped if the host has none installed.
+pub fn render(projects: &[Project], repo: &str, commit: &str, date: &str) -> Result<Vec<u8>> {
+    let sheets: Vec<(&Project, usize)> = projects
+        .iter()
+        .flat_map(|project| (0..project.instances.len()).map(move |instance| (project, instance)))
+        .collect();
+
+    let mut next = Ref::new(1);
+    let catalog = next.bump();
+    let page_tree = next.bump();
+    let info = next.bump();
+    let mut pdf = Pdf::new();
+    let mut pages = Vec::new();
+
+    for (i, (project, instance)) in sheets.iter().enumerate() {
+        let title = TitleBlock {
+            repo,
+            commit,
+            date,
+            page: i + 1,
+            pages: sheets.len(),
+        };
+        let svg = render_sheet_svg(project, *instance, &title);
+        let mut tree = usvg::Tree::from_str(&svg, &usvg::Options::default())
+            .with_context(|| format!("Failed to parse the drawing of {}", project.instances[*instance].sheet_path))?;
+        tree.postprocess(PostProcessingSteps::default(), &FONTS);
+
+        let page = next.bump();
+        let contents = next.bump();
+        let drawing = next.bump();
+        let options = svg2pdf::Options {
+            dpi: SVG_DPI,
+            ..Default::default()
+        };
+        next = svg2pdf::convert_tree_into(&tree, options, &mut pdf, drawing);
+
+        let (width, height) = (tree.size.width() * POINTS_PER_PX, tree.size.height() * POINTS_PER_PX);
+        let mut writer = pdf.page(page);
+        writer
+            .media_box(Rect::new(0.0, 0.0, width, height))
+            .parent(page_tree)
+            .contents(contents);
+        writer.resources().x_objects().pair(Name(b"Sheet"), drawing);
+        writer.finish();
+
+        // The drawing is a unit square; stretch it over the page
+        let mut content = Content::new();
+        content.transform([width, 0.0, 0.0, height, 0.0, 0.0]).x_object(Name(b"Sheet"));
+        pdf.stream(contents, &content.finish());
+        pages.push(page);
+    }
+
+    pdf.catalog(catalog).pages(page_tree);
+    pdf.pages(page_tree).count(pages.len() as i32).kids(pages);
+    let short = &commit[..7.min(commit.len())];
+    pdf.document_info(info).title(TextStr(&format!("{} @ {}", repo, short)));
+    Ok(pdf.finish())
+}
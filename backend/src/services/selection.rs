@@ -0,0 +1,214 @@
+use kicad_db::schematic::hierarchy::natural_key;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashSet};
+
+use crate::types::Selection;
+
+/// A selection resolved against a commit's distilled schematic
+#[derive(Debug, Default)]
+pub struct Resolved {
+    /// The components selected, or those on the selected nets, sheet or
+    /// area, in reference order
+    pub references: Vec<String>,
+    /// What was selected as a whole (the nets and what they connect, or the
+    /// sheet or area and the nets leaving it), to go ahead of the component
+    /// details; empty for components
+    pub context: String,
+}
+
+/// The selection a request makes: `selection`, or `component_ids` as a
+/// components selection. None when it makes neither
+pub fn requested(component_ids: &[String], selection: Option<&Selection>) -> Option<Selection> {
+    match selection {
+        Some(selection) => Some(selection.clone()),
+        None if component_ids.is_empty() => None,
+        None => Some(Selection::Components {
+            ids: component_ids.to_vec(),
+        }),
+    }
+}
+
+/// "3 component(s)", "net /VIN", "sheet /Power/" or "an area", for logs
+pub fn describe(selection: Option<&Selection>) -> String {
+    match selection {
+        None => "no selection".to_string(),
+        Some(Selection::Components { ids }) => format!("{} component(s)", ids.len()),
+        Some(Selection::Nets { names }) if names.len() == 1 => format!("net {}", names[0]),
+        Some(Selection::Nets { names }) => format!("{} nets", names.len()),
+        Some(Selection::Sheet { path }) => format!("sheet {}", path),
+        Some(Selection::Area { .. }) => "an area".to_string(),
+    }
+}
+
+/// Resolve `selection` against `distilled`
+///
+/// Unknown component references are left out, as the viewer may lag behind
+/// the commit. Nets and sheets that don't exist, and an area with no
+/// components in it, are an error naming what's missing.
+pub fn resolve(distilled: &Value, selection: &Selection) -> Result<Resolved, String> {
+    let components = components(distilled);
+    let nets = distilled["nets"].as_object();
+
+    let resolved = match selection {
+        Selection::Components { ids } => Resolved {
+            references: components
+                .iter()
+                .map(|(reference, _)| reference.clone())
+                .filter(|reference| ids.contains(reference))
+                .collect(),
+            context: String::new(),
+        },
+        Selection::Nets { names } => {
+            let mut found = Vec::new();
+            let mut missing = Vec::new();
+            for name in names {
+                match nets.and_then(|nets| find_net(nets, name.trim())) {
+                    Some(net) => found.push(net),
+                    None => missing.push(name.as_str()),
+                }
+            }
+            if !missing.is_empty() {
+                return Err(format!("No net {} in the schematic", missing.join(", ")));
+            }
+
+            let mut references = BTreeSet::new();
+            let mut lines = Vec::new();
+            for (name, members) in found {
+                let mut pins = Vec::new();
+                for (reference, nodes) in members.as_object().into_iter().flatten() {
+                    references.insert(reference.clone());
+                    for node in nodes.as_array().into_iter().flatten() {
+                        let pin = node["Pin"].as_str().unwrap_or("?");
+                        pins.push(format!("{} pin {}", reference, pin));
+                    }
+                }
+                pins.sort_by_key(|pin| natural_key(pin));
+                lines.push(format!("**{}** connects {}", name, pins.join(", ")));
+            }
+            Resolved {
+                references: references.into_iter().collect(),
+                context: format!("## Selected Nets ({})\n\n{}", lines.len(), lines.join("\n")),
+            }
+        }
+        Selection::Sheet { path } => {
+            let path = sheet_path(path);
+            let references: Vec<String> = components
+                .iter()
+                .filter(|(_, component)| component["sheet_path"].as_str() == Some(path.as_str()))
+                .map(|(reference, _)| reference.clone())
+                .collect();
+            if references.is_empty() {
+                return Err(format!("No sheet {} in the schematic", path));
+            }
+            let context = format!(
+                "## Selected Sheet {}\n\n{} component(s).{}",
+                path,
+                references.len(),
+                leaving_nets(nets, &references)
+            );
+            Resolved { references, context }
+        }
+        Selection::Area { x1, y1, x2, y2, sheet } => {
+            let path = sheet_path(sheet.as_deref().unwrap_or("/"));
+            let (left, right) = (x1.min(*x2), x1.max(*x2));
+            let (top, bottom) = (y1.min(*y2), y1.max(*y2));
+            let references: Vec<String> = components
+                .iter()
+                .filter(|(_, component)| component["sheet_path"].as_str().unwrap_or("/") == path)
+                .filter(|(_, component)| {
+                    let bbox = &component["bbox"];
+                    let bbox = match (bbox["x1"].as_f64(), bbox["y1"].as_f64(), bbox["x2"].as_f64(), bbox["y2"].as_f64()) {
+                        (Some(x1), Some(y1), Some(x2), Some(y2)) => Some((x1, y1, x2, y2)),
+                        _ => None,
+                    };
+                    let anchor = component["position"]["x"].as_f64().zip(component["position"]["y"].as_f64());
+                    overlaps((left, top, right, bottom), bbox, anchor)
+                })
+                .map(|(reference, _)| reference.clone())
+                .collect();
+            if references.is_empty() {
+                return Err(format!("No components in the selected area of sheet {}", path));
+            }
+            let context = format!(
+                "## Selected Area\n\nThe components between ({}, {}) and ({}, {}) mm on sheet {}.{}",
+                left,
+                top,
+                right,
+                bottom,
+                path,
+                leaving_nets(nets, &references)
+            );
+            Resolved { references, context }
+        }
+    };
+    Ok(resolved)
+}
+
+/// Components of `distilled` by reference, in reference order; the native
+/// distiller keys them by reference, the distill script lists them
+fn components(distilled: &Value) -> Vec<(String, &Value)> {
+    let mut components: Vec<(String, &Value)> = match &distilled["components"] {
+        Value::Object(map) => map.iter().map(|(reference, c)| (reference.clone(), c)).collect(),
+        Value::Array(list) => list
+            .iter()
+            .filter_map(|c| Some((c["reference"].as_str()?.to_string(), c)))
+            .collect(),
+        _ => Vec::new(),
+    };
+    components.sort_by_key(|(reference, _)| natural_key(reference));
+    components
+}
+
+/// The net called `name`; local nets may be named without their sheet's "/"
+fn find_net<'a>(nets: &'a serde_json::Map<String, Value>, name: &str) -> Option<(&'a String, &'a Value)> {
+    nets.get_key_value(name)
+        .or_else(|| nets.get_key_value(&format!("/{}", name)))
+}
+
+/// Whether a component is in `area`, both `(left, top, right, bottom)` in
+/// mm: its bounding box overlaps it, or without one its anchor is inside
+pub fn overlaps(
+    (left, top, right, bottom): (f64, f64, f64, f64),
+    bbox: Option<(f64, f64, f64, f64)>,
+    anchor: Option<(f64, f64)>,
+) -> bool {
+    match (bbox, anchor) {
+        (Some((x1, y1, x2, y2)), _) => x1 <= right && x2 >= left && y1 <= bottom && y2 >= top,
+        (None, Some((x, y))) => (left..=right).contains(&x) && (top..=bottom).contains(&y),
+        (None, None) => false,
+    }
+}
+
+/// "/Power/" for "Power", "/Power" or "/Power/"; "/" for the root sheet
+pub fn sheet_path(path: &str) -> String {
+    let inner = path.trim().trim_matches('/');
+    match inner.is_empty() {
+        true => "/".to_string(),
+        false => format!("/{}/", inner),
+    }
+}
+
+/// " Nets connecting it to the rest of the design: …" for the nets joining
+/// `references` to other components; empty if there are none
+fn leaving_nets(nets: Option<&serde_json::Map<String, Value>>, references: &[String]) -> String {
+    let inside: HashSet<&str> = references.iter().map(String::as_str).collect();
+    let leaving: Vec<&str> = nets
+        .into_iter()
+        .flatten()
+        .filter(|(_, members)| {
+            let members = members.as_object();
+            let touches = |inner: bool| {
+                members
+                    .into_iter()
+                    .flat_map(|m| m.keys())
+                    .any(|reference| inside.contains(reference.as_str()) == inner)
+            };
+            touches(true) && touches(false)
+        })
+        .map(|(name, _)| name.as_str())
+        .collect();
+    match leaving.is_empty() {
+        true => String::new(),
+        false => format!(" Nets connecting it to the rest of the design: {}", leaving.join(", ")),
+    }
+}
@@ -0,0 +1,83 @@
+use kicad_db::{
+    pending_embeddings, semantic_search_available, store_embedding, EmbeddingTarget, PgPool,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use super::{llm::ChatProvider, priority};
+use crate::types::WorkClass;
+
+const INDEX_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const BATCH_SIZE: i64 = 32;
+/// Caps API calls per run, so a row that never stores can't loop forever
+const MAX_BATCHES_PER_RUN: usize = 20;
+/// Longest text sent for embedding, in characters
+const MAX_TEXT_CHARS: usize = 8000;
+
+/// Keep commit and part embeddings in step with their text
+///
+/// Every few minutes, embeds rows whose text is new or has changed since it was
+/// last embedded with `model` (so changing the model re-embeds everything).
+/// Does nothing if Postgres lacks pgvector.
+pub fn spawn_indexer(pool: Arc<PgPool>, chat: Arc<dyn ChatProvider>, model: String) {
+    tokio::spawn(priority::scope(WorkClass::Enrichment, async move {
+        match semantic_search_available(&pool).await {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("pgvector is not installed; semantic search is disabled");
+                return;
+            }
+            Err(e) => {
+                warn!("Failed to check for semantic search support: {}", e);
+                return;
+            }
+        }
+
+        let mut interval = tokio::time::interval(INDEX_INTERVAL);
+        loop {
+            interval.tick().await;
+            // Park the run rather than fail every batch while XAI is down
+            if !chat.available() {
+                info!("XAI is unavailable; skipping this embedding run");
+                continue;
+            }
+            for target in [EmbeddingTarget::Commits, EmbeddingTarget::Parts] {
+                match index(&pool, chat.as_ref(), &model, target).await {
+                    Ok(0) => debug!("No {:?} to embed", target),
+                    Ok(n) => info!("Embedded {} {:?}", n, target),
+                    Err(e) => warn!("Failed to embed {:?}: {:#}", target, e),
+                }
+            }
+        }
+    }));
+}
+
+/// Embed pending rows in batches; returns how many were stored
+async fn index(
+    pool: &PgPool,
+    chat: &dyn ChatProvider,
+    model: &str,
+    target: EmbeddingTarget,
+) -> anyhow::Result<usize> {
+    let mut stored = 0;
+    for _ in 0..MAX_BATCHES_PER_RUN {
+        let pending = pending_embeddings(pool, target, model, BATCH_SIZE).await?;
+        if pending.is_empty() {
+            break;
+        }
+        let texts: Vec<String> = pending
+            .iter()
+            .map(|source| source.text.chars().take(MAX_TEXT_CHARS).collect())
+            .collect();
+        let embeddings = chat.embed(model, &texts).await?;
+        for (source, embedding) in pending.iter().zip(&embeddings) {
+            store_embedding(pool, target, source, model, embedding).await?;
+        }
+        stored += pending.len();
+        if pending.len() < BATCH_SIZE as usize {
+            break;
+        }
+    }
+    Ok(stored)
+}
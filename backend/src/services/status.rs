@@ -0,0 +1,293 @@
+use chrono::{DateTime, Utc};
+use kicad_db::{xai_client, PgPool};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How many recent errors are kept in memory
+const RECENT_ERROR_LIMIT: usize = 50;
+/// Distinct clients remembered per deprecated route; counting stops there
+const DEPRECATED_CLIENT_LIMIT: usize = 1000;
+/// How often `wait_idle` checks for running jobs
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Minutes of request counts kept for the error rate
+const REQUEST_WINDOW_MINUTES: i64 = 60;
+
+static STARTED_AT: Lazy<(Instant, DateTime<Utc>)> = Lazy::new(|| (Instant::now(), Utc::now()));
+static NEXT_JOB_ID: AtomicU64 = AtomicU64::new(1);
+static ACTIVE_JOBS: Lazy<Mutex<HashMap<u64, ActiveJob>>> = Lazy::new(Default::default);
+static RECENT_ERRORS: Lazy<Mutex<VecDeque<RecentError>>> = Lazy::new(Default::default);
+static DEPRECATED_CALLS: Lazy<Mutex<HashMap<&'static str, DeprecatedCalls>>> =
+    Lazy::new(Default::default);
+/// Hits and misses per cache since the process started
+static CACHE_LOOKUPS: Lazy<Mutex<HashMap<&'static str, CacheLookups>>> = Lazy::new(Default::default);
+/// Oldest minute first
+static REQUEST_MINUTES: Lazy<Mutex<VecDeque<RequestMinute>>> = Lazy::new(Default::default);
+
+/// An analysis currently running in this process
+#[derive(Debug, Clone, Serialize)]
+pub struct ActiveJob {
+    pub id: u64,
+    /// Kind of work, e.g. "update", "init", "commit_summary"
+    pub kind: &'static str,
+    pub repo: String,
+    /// Commit currently being processed, if known
+    pub commit: Option<String>,
+    pub started_at: DateTime<Utc>,
+}
+
+/// A server-side failure, as reported to a client or recorded by a batch job
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentError {
+    pub at: DateTime<Utc>,
+    pub repo: Option<String>,
+    pub commit: Option<String>,
+    pub stage: Option<&'static str>,
+    pub message: String,
+}
+
+/// Usage of a deprecated route since the process started
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DeprecatedUsage {
+    pub route: &'static str,
+    pub calls: u64,
+    /// Distinct API keys and anonymous IPs, capped at `DEPRECATED_CLIENT_LIMIT`
+    pub clients: usize,
+    pub last_called_at: Option<DateTime<Utc>>,
+}
+
+/// Lookups of one cache since the process started
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheLookups {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Requests answered in the last hour, and how many of them were 5xx
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RecentRequests {
+    pub requests: u64,
+    pub server_errors: u64,
+}
+
+struct RequestMinute {
+    /// Minutes since the Unix epoch
+    minute: i64,
+    requests: u64,
+    server_errors: u64,
+}
+
+#[derive(Default)]
+struct DeprecatedCalls {
+    calls: u64,
+    clients: HashSet<String>,
+    last_called_at: Option<DateTime<Utc>>,
+}
+
+/// Everything an operator needs to see at a glance
+#[derive(Debug, Clone, Serialize)]
+pub struct Overview {
+    pub started_at: DateTime<Utc>,
+    pub uptime_secs: u64,
+    pub version: &'static str,
+    pub database_ok: bool,
+    pub database_error: Option<String>,
+    pub active_jobs: Vec<ActiveJob>,
+    /// Newest first
+    pub recent_errors: Vec<RecentError>,
+    pub token_usage: xai_client::TokenUsage,
+    /// Sorted by route
+    pub deprecated_usage: Vec<DeprecatedUsage>,
+}
+
+/// Removes its job from the active list when dropped, however the job ends
+pub struct JobGuard {
+    id: u64,
+}
+
+impl JobGuard {
+    /// Record which commit the job has moved on to
+    pub fn set_commit(&self, commit: &str) {
+        if let Some(job) = ACTIVE_JOBS.lock().unwrap().get_mut(&self.id) {
+            job.commit = Some(commit.to_string());
+        }
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        ACTIVE_JOBS.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Mark the process start; uptime is measured from the first call
+pub fn init() {
+    Lazy::force(&STARTED_AT);
+}
+
+/// Seconds since `init`
+pub fn uptime_secs() -> u64 {
+    STARTED_AT.0.elapsed().as_secs()
+}
+
+/// Register a running job until the returned guard is dropped
+pub fn track_job(kind: &'static str, repo: &str, commit: Option<&str>) -> JobGuard {
+    let id = NEXT_JOB_ID.fetch_add(1, Ordering::Relaxed);
+    ACTIVE_JOBS.lock().unwrap().insert(
+        id,
+        ActiveJob {
+            id,
+            kind,
+            repo: repo.to_string(),
+            commit: commit.map(str::to_string),
+            started_at: Utc::now(),
+        },
+    );
+    JobGuard { id }
+}
+
+/// Wait until no job is running
+pub async fn wait_idle() {
+    while !ACTIVE_JOBS.lock().unwrap().is_empty() {
+        tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+    }
+}
+
+/// Jobs running right now
+pub fn active_job_count() -> usize {
+    ACTIVE_JOBS.lock().unwrap().len()
+}
+
+/// Commits of `repo` that a running job is working on
+pub fn active_commits(repo: &str) -> HashSet<String> {
+    ACTIVE_JOBS
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|job| job.repo == repo)
+        .filter_map(|job| job.commit.clone())
+        .collect()
+}
+
+/// Remember a failure for the status page, dropping the oldest past the limit
+pub fn record_error(
+    repo: Option<&str>,
+    commit: Option<&str>,
+    stage: Option<&'static str>,
+    message: &str,
+) {
+    let mut errors = RECENT_ERRORS.lock().unwrap();
+    if errors.len() == RECENT_ERROR_LIMIT {
+        errors.pop_back();
+    }
+    errors.push_front(RecentError {
+        at: Utc::now(),
+        repo: repo.map(str::to_string),
+        commit: commit.map(str::to_string),
+        stage,
+        message: message.to_string(),
+    });
+}
+
+/// Count a call to a deprecated route by `client`
+pub fn record_deprecated_call(route: &'static str, client: &str) {
+    let mut calls = DEPRECATED_CALLS.lock().unwrap();
+    let usage = calls.entry(route).or_default();
+    usage.calls += 1;
+    usage.last_called_at = Some(Utc::now());
+    if usage.clients.len() < DEPRECATED_CLIENT_LIMIT {
+        usage.clients.insert(client.to_string());
+    }
+}
+
+/// Count a lookup of `cache` ("ai", "response" or "schematic")
+pub fn record_cache_lookup(cache: &'static str, hit: bool) {
+    let mut lookups = CACHE_LOOKUPS.lock().unwrap();
+    let lookups = lookups.entry(cache).or_default();
+    if hit {
+        lookups.hits += 1;
+    } else {
+        lookups.misses += 1;
+    }
+}
+
+/// Lookups per cache since the process started, sorted by cache
+pub fn cache_lookups() -> Vec<(&'static str, CacheLookups)> {
+    let mut lookups: Vec<_> = CACHE_LOOKUPS.lock().unwrap().iter().map(|(cache, l)| (*cache, *l)).collect();
+    lookups.sort_by_key(|(cache, _)| *cache);
+    lookups
+}
+
+/// Count an answered request, for the error rate
+pub fn record_request(server_error: bool) {
+    let minute = Utc::now().timestamp() / 60;
+    let mut minutes = REQUEST_MINUTES.lock().unwrap();
+    if minutes.back().is_none_or(|last| last.minute != minute) {
+        minutes.push_back(RequestMinute {
+            minute,
+            requests: 0,
+            server_errors: 0,
+        });
+    }
+    while minutes.front().is_some_and(|first| first.minute <= minute - REQUEST_WINDOW_MINUTES) {
+        minutes.pop_front();
+    }
+    let current = minutes.back_mut().expect("the current minute was just added");
+    current.requests += 1;
+    current.server_errors += u64::from(server_error);
+}
+
+/// Requests answered in the last hour
+pub fn recent_requests() -> RecentRequests {
+    let since = Utc::now().timestamp() / 60 - REQUEST_WINDOW_MINUTES;
+    REQUEST_MINUTES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|m| m.minute > since)
+        .fold(RecentRequests::default(), |total, m| RecentRequests {
+            requests: total.requests + m.requests,
+            server_errors: total.server_errors + m.server_errors,
+        })
+}
+
+/// Collect the current overview; the database is pinged, everything else is in memory
+pub async fn overview(pool: &PgPool) -> Overview {
+    let database_error = sqlx::query("SELECT 1")
+        .execute(pool)
+        .await
+        .err()
+        .map(|e| e.to_string());
+
+    let mut active_jobs: Vec<ActiveJob> = ACTIVE_JOBS.lock().unwrap().values().cloned().collect();
+    active_jobs.sort_by_key(|job| job.id);
+
+    let mut deprecated_usage: Vec<DeprecatedUsage> = DEPRECATED_CALLS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(route, usage)| DeprecatedUsage {
+            route,
+            calls: usage.calls,
+            clients: usage.clients.len(),
+            last_called_at: usage.last_called_at,
+        })
+        .collect();
+    deprecated_usage.sort_by_key(|usage| usage.route);
+
+    let (started, started_at) = *STARTED_AT;
+    Overview {
+        started_at,
+        uptime_secs: started.elapsed().as_secs(),
+        version: env!("CARGO_PKG_VERSION"),
+        database_ok: database_error.is_none(),
+        database_error,
+        active_jobs,
+        recent_errors: RECENT_ERRORS.lock().unwrap().iter().cloned().collect(),
+        token_usage: xai_client::token_usage(),
+        deprecated_usage,
+    }
+}
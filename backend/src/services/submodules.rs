@@ -0,0 +1,176 @@
+use anyhow::{bail, Context, Result};
+use git2::{ObjectType, Oid, Repository, Tree};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use super::git::{self, RemoteSettings};
+
+/// Submodules nested deeper than this are not fetched or read
+pub const MAX_DEPTH: u32 = 8;
+
+/// A submodule as pinned by one commit
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pin {
+    /// Where it is checked out, relative to its parent's root
+    pub path: String,
+    /// Clone URL, with relative URLs resolved against the parent's
+    pub url: String,
+    pub commit: Oid,
+}
+
+/// `(path, url)` of each submodule in a .gitmodules file
+fn parse_gitmodules(text: &str) -> Vec<(String, String)> {
+    let mut modules = Vec::new();
+    let mut current: Option<(Option<String>, Option<String>)> = None;
+    let mut finish = |module: Option<(Option<String>, Option<String>)>| {
+        if let Some((Some(path), Some(url))) = module {
+            modules.push((path, url));
+        }
+    };
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            finish(current.take());
+            if line.starts_with("[submodule") {
+                current = Some((None, None));
+            }
+        } else if let (Some((path, url)), Some((key, value))) = (current.as_mut(), line.split_once('=')) {
+            let value = value.trim().trim_matches('"').to_string();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "path" => *path = Some(value.trim_end_matches('/').to_string()),
+                "url" => *url = Some(value),
+                _ => {}
+            }
+        }
+    }
+    finish(current);
+    modules
+}
+
+/// A submodule URL, resolving `./` and `../` against the parent's clone URL like git does
+fn resolve_url(parent_url: &str, url: &str) -> String {
+    if !url.starts_with("./") && !url.starts_with("../") {
+        return url.to_string();
+    }
+    let mut base = parent_url.trim_end_matches('/');
+    let mut rest = url;
+    loop {
+        if let Some(r) = rest.strip_prefix("./") {
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("../") {
+            base = base.rsplit_once('/').map_or(base, |(b, _)| b);
+            rest = r;
+        } else {
+            break;
+        }
+    }
+    format!("{}/{}", base, rest)
+}
+
+/// The submodules `tree` pins, from its .gitmodules and gitlinks
+///
+/// Entries in .gitmodules without a gitlink in the tree (or the reverse) are skipped.
+pub fn pins(repo: &Repository, tree: &Tree, parent_url: &str) -> Vec<Pin> {
+    let Some(text) = tree
+        .get_name(".gitmodules")
+        .and_then(|entry| repo.find_blob(entry.id()).ok())
+        .map(|blob| String::from_utf8_lossy(blob.content()).into_owned())
+    else {
+        return Vec::new();
+    };
+    parse_gitmodules(&text)
+        .into_iter()
+        .filter_map(|(path, url)| {
+            let entry = tree.get_path(Path::new(&path)).ok()?;
+            (entry.kind() == Some(ObjectType::Commit)).then(|| Pin {
+                url: resolve_url(parent_url, &url),
+                commit: entry.id(),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Where the submodule clones of a cached repo are kept, inside its .git so
+/// they go when the cache does
+pub fn store(repo: &Repository) -> PathBuf {
+    repo.path().join("kicad-submodules")
+}
+
+fn clone_dir(store: &Path, url: &str) -> PathBuf {
+    let name: String = url
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '-' })
+        .collect();
+    store.join(name)
+}
+
+/// The bare clone of a submodule, if it has been fetched
+pub fn open(store: &Path, url: &str) -> Option<Repository> {
+    Repository::open_bare(clone_dir(store, url)).ok()
+}
+
+/// Clone or fetch a submodule unless it already has the pinned commit
+fn fetch(store: &Path, pin: &Pin, remote: Option<&RemoteSettings>) -> Result<Repository> {
+    let dir = clone_dir(store, &pin.url);
+    let repo = if dir.exists() {
+        Repository::open_bare(&dir).context("Failed to open submodule clone")?
+    } else {
+        let repo = Repository::init_bare(&dir).context("Failed to create submodule clone")?;
+        repo.remote("origin", &pin.url)?;
+        repo
+    };
+    if repo.find_commit(pin.commit).is_ok() {
+        return Ok(repo);
+    }
+
+    let mut options = git::fetch_options(remote);
+    repo.find_remote("origin")?
+        .fetch(
+            &["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"],
+            Some(&mut options),
+            None,
+        )
+        .with_context(|| format!("Failed to fetch {}", pin.url))?;
+    info!("Fetched submodule {} from {}", pin.path, pin.url);
+    if repo.find_commit(pin.commit).is_err() {
+        bail!("{} has no commit {}", pin.url, pin.commit);
+    }
+    Ok(repo)
+}
+
+/// Fetch the submodules `tree` pins, and those nested in them, into `store`
+///
+/// A submodule that can't be fetched is logged and skipped so the parent
+/// can still be processed; its libraries just won't resolve.
+pub fn sync(store: &Path, repo: &Repository, tree: &Tree, parent_url: &str, remote: Option<&RemoteSettings>) {
+    sync_nested(store, repo, tree, parent_url, remote, 0, &mut HashSet::new());
+}
+
+fn sync_nested(
+    store: &Path,
+    repo: &Repository,
+    tree: &Tree,
+    parent_url: &str,
+    remote: Option<&RemoteSettings>,
+    depth: u32,
+    seen: &mut HashSet<(String, Oid)>,
+) {
+    for pin in pins(repo, tree, parent_url) {
+        if !seen.insert((pin.url.clone(), pin.commit)) {
+            continue;
+        }
+        let submodule = match fetch(store, &pin, remote) {
+            Ok(submodule) => submodule,
+            Err(e) => {
+                warn!("Skipping submodule {}: {:#}", pin.path, e);
+                continue;
+            }
+        };
+        if depth + 1 < MAX_DEPTH {
+            if let Ok(tree) = submodule.find_commit(pin.commit).and_then(|c| c.tree()) {
+                sync_nested(store, &submodule, &tree, &pin.url, remote, depth + 1, seen);
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use anyhow::Result;
+use kicad_db::{
+    messages::{ChatCompletionRequest, Message},
+    prompt_budget::PromptBudget,
+    prompts::{PromptLibrary, SUGGEST_FIX},
+    schematic::{Project, SheetEdit},
+    SchematicError,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+use tracing::warn;
+
+use crate::services::{llm::ChatProvider, summary};
+use crate::types::SchematicFile;
+
+/// Most edits one suggestion may make
+pub const MAX_EDITS: usize = 20;
+/// Output allowed for the edits
+const EDITS_MAX_TOKENS: u32 = 800;
+/// Name of the prompt's section listing the components
+const COMPONENTS_SECTION: &str = "components";
+/// Properties the component lines leave out, as they say nothing about the part
+const SKIPPED_PROPERTIES: &[&str] = &["Reference", "Value", "Datasheet", "Description"];
+
+/// One change to the schematic, as the model writes it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    SetValue { reference: String, value: String },
+    SetProperty { reference: String, name: String, value: String },
+    SwapPins { reference: String, a: String, b: String },
+    NoConnect { reference: String, pin: String },
+}
+
+impl Edit {
+    pub fn reference(&self) -> &str {
+        match self {
+            Edit::SetValue { reference, .. }
+            | Edit::SetProperty { reference, .. }
+            | Edit::SwapPins { reference, .. }
+            | Edit::NoConnect { reference, .. } => reference,
+        }
+    }
+
+    fn apply(&self, sheet: &mut SheetEdit) -> Result<(), SchematicError> {
+        match self {
+            Edit::SetValue { reference, value } => sheet.set_value(reference, value),
+            Edit::SetProperty { reference, name, value } => sheet.set_property(reference, name, value),
+            Edit::SwapPins { reference, a, b } => sheet.swap_pins(reference, a, b).map(|_| ()),
+            Edit::NoConnect { reference, pin } => sheet.add_no_connect(reference, pin),
+        }
+    }
+}
+
+/// The line the prompt asks for, without the leading "- "
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::SetValue { reference, value } => write!(f, "set_value {} {}", reference, value),
+            Edit::SetProperty { reference, name, value } => write!(f, "set_property {} {} {}", reference, name, value),
+            Edit::SwapPins { reference, a, b } => write!(f, "swap_pins {} {} {}", reference, a, b),
+            Edit::NoConnect { reference, pin } => write!(f, "no_connect {} {}", reference, pin),
+        }
+    }
+}
+
+/// The edits the model wrote as "- op arguments" lines; other lines are
+/// ignored. Values run to the end of the line, so they may have spaces
+pub fn parse(text: &str) -> Vec<Edit> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")).unwrap_or(line);
+            let (op, rest) = line.split_once(char::is_whitespace)?;
+            let (reference, rest) = rest.trim().split_once(char::is_whitespace)?;
+            let (reference, rest) = (reference.to_string(), rest.trim());
+            let word = |text: &str| (!text.is_empty() && !text.contains(char::is_whitespace)).then(|| text.to_string());
+            match op {
+                "set_value" if !rest.is_empty() => Some(Edit::SetValue {
+                    reference,
+                    value: rest.to_string(),
+                }),
+                "set_property" => {
+                    let (name, value) = rest.split_once(char::is_whitespace)?;
+                    Some(Edit::SetProperty {
+                        reference,
+                        name: name.to_string(),
+                        value: value.trim().to_string(),
+                    })
+                }
+                "swap_pins" => {
+                    let (a, b) = rest.split_once(char::is_whitespace)?;
+                    Some(Edit::SwapPins {
+                        reference,
+                        a: word(a)?,
+                        b: word(b.trim())?,
+                    })
+                }
+                "no_connect" => Some(Edit::NoConnect { reference, pin: word(rest)? }),
+                _ => None,
+            }
+        })
+        .take(MAX_EDITS)
+        .collect()
+}
+
+/// "- R1 10k (Footprint=…): 1=VIN 2=VOUT" for each component of `projects`
+pub fn component_lines(projects: &[Project]) -> String {
+    let mut lines = Vec::new();
+    for project in projects {
+        let mut pins: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for net in project.netlist() {
+            for node in &net.nodes {
+                pins.entry(node.reference.clone())
+                    .or_default()
+                    .push(format!("{}={}", node.pin, net.name));
+            }
+        }
+        for component in project.components() {
+            let properties: Vec<String> = component
+                .properties
+                .iter()
+                .filter(|(name, value)| !value.is_empty() && !SKIPPED_PROPERTIES.contains(&name.as_str()))
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            let mut line = format!("- {} {}", component.reference, component.value);
+            if !properties.is_empty() {
+                line.push_str(&format!(" ({})", properties.join(", ")));
+            }
+            if let Some(pins) = pins.get(&component.reference) {
+                line.push_str(&format!(": {}", pins.join(" ")));
+            }
+            lines.push(line);
+        }
+    }
+    lines.join("\n")
+}
+
+/// Ask Grok to turn `change` into edits of the commit's schematic,
+/// returning them and the prompt version
+///
+/// No edits means the model found the change can't be made with them.
+/// `components` (see [`component_lines`]) is cut short to fit `context_window`.
+#[allow(clippy::too_many_arguments)]
+pub async fn generate(
+    chat: &dyn ChatProvider,
+    prompts: &PromptLibrary,
+    model: &str,
+    context_window: usize,
+    repo: &str,
+    commit: &str,
+    change: &str,
+    components: &str,
+) -> Result<(Vec<Edit>, String)> {
+    let variables = |components: &str| {
+        serde_json::json!({
+            "repo": repo,
+            "commit": commit,
+            "change": change,
+            "components": components,
+            "max_edits": MAX_EDITS,
+        })
+    };
+    let bare = prompts.render(SUGGEST_FIX, variables(""))?;
+    let fitted = PromptBudget::new(model, context_window)
+        .reserve_output(EDITS_MAX_TOKENS)
+        .fixed(&bare.text)
+        .section(COMPONENTS_SECTION, components)
+        .fit();
+    if !fitted.truncated.is_empty() {
+        warn!("Shortened the component list in the suggest-fix prompt for {}@{} to fit the context window", repo, commit);
+    }
+    let prompt = prompts.render(SUGGEST_FIX, variables(fitted.text(COMPONENTS_SECTION)))?;
+
+    let mut request = ChatCompletionRequest::with_stream(vec![Message::user(prompt.text.clone())], model.to_string(), true);
+    request.max_tokens = Some(EDITS_MAX_TOKENS);
+    let answer = summary::complete(chat, &request).await?;
+    Ok((parse(&answer), prompt.label()))
+}
+
+/// Apply `edits` to every .kicad_sch of `files` placing their component,
+/// returning the files that changed
+///
+/// An edit naming a component no sheet places fails, as does any edit a
+/// sheet refuses. Sheets no edit touched aren't written back at all, so
+/// they keep their formatting.
+pub fn apply(files: &[SchematicFile], edits: &[Edit]) -> Result<Vec<SchematicFile>, SchematicError> {
+    let mut sheets = Vec::new();
+    for file in files.iter().filter(|f| f.path.ends_with(".kicad_sch")) {
+        sheets.push((file, SheetEdit::parse(&file.path, &file.content)?, false));
+    }
+    for edit in edits {
+        let mut placed = false;
+        for (_, sheet, touched) in sheets.iter_mut().filter(|(_, sheet, _)| sheet.has_component(edit.reference())) {
+            edit.apply(sheet)?;
+            *touched = true;
+            placed = true;
+        }
+        if !placed {
+            return Err(SchematicError::Edit {
+                file: "the schematic".to_string(),
+                message: format!("no component {}", edit.reference()),
+            });
+        }
+    }
+    Ok(sheets
+        .into_iter()
+        .filter(|(_, _, touched)| *touched)
+        .map(|(file, sheet, _)| SchematicFile {
+            path: file.path.clone(),
+            content: sheet.to_text(),
+        })
+        .filter(|edited| files.iter().any(|f| f.path == edited.path && f.content != edited.content))
+        .collect())
+}
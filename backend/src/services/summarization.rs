@@ -0,0 +1,399 @@
+use anyhow::{bail, Context, Result};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use kicad_db::{
+    component_categories::TAXONOMY,
+    components_from_distilled,
+    detail_levels::DetailLevel,
+    get_summary_chunk,
+    messages::{ChatCompletionRequest, Message},
+    personas::Persona,
+    prompt_budget::PromptBudget,
+    prompts::{PromptLibrary, CHUNK_SUMMARY, MERGE_SUMMARIES, REPO_OVERVIEW},
+    schematic::{hierarchy::natural_key, Project},
+    part_categories, store_summary_chunk, summary_chunk_digest,
+    tokens::Encoding,
+    GuidelinePrompt, PartKey, PgPool,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use super::llm::ChatProvider;
+
+/// Most netlist tokens per chunk, however large the window; smaller chunks
+/// mean an edit to a sheet re-summarizes less of it
+const MAX_CHUNK_TOKENS: usize = 6_000;
+/// Output allowed for a chunk summary or a merge
+const STEP_MAX_TOKENS: u32 = 500;
+/// Chunks of a sheet summarized at once
+const CONCURRENCY: usize = 4;
+/// Parts named per line of the overview's parts by category
+const MAX_PARTS_PER_CATEGORY: usize = 12;
+
+/// One sheet of a project, as the netlist lines its chunks are cut from
+#[derive(Debug, Clone)]
+pub struct Sheet {
+    /// Sheet path, e.g. "/Power/", prefixed by the root file when the repo
+    /// has several projects
+    pub title: String,
+    /// One line per component: type, value and the nets on its pins
+    pub lines: Vec<String>,
+}
+
+/// A summary and what it covers, as the merge and overview templates take them
+#[derive(Debug, Clone, Serialize)]
+pub struct PartSummary {
+    pub title: String,
+    pub text: String,
+}
+
+/// The sheets of `projects`, in sheet-path order, each listing its
+/// components by reference
+pub fn sheets(projects: &[Project]) -> Vec<Sheet> {
+    let mut sheets = Vec::new();
+    for project in projects {
+        let distilled = project.to_distilled();
+        let mut by_sheet: BTreeMap<String, Vec<(&String, &Value)>> = BTreeMap::new();
+        for (reference, component) in distilled["components"].as_object().into_iter().flatten() {
+            let sheet_path = component["sheet_path"].as_str().unwrap_or("/").to_string();
+            by_sheet.entry(sheet_path).or_default().push((reference, component));
+        }
+        for (sheet_path, mut components) in by_sheet {
+            components.sort_by_key(|(reference, _)| natural_key(reference));
+            let title = if projects.len() > 1 {
+                format!("{} {}", project.root_file, sheet_path)
+            } else {
+                sheet_path
+            };
+            let lines = components
+                .into_iter()
+                .map(|(reference, component)| component_line(reference, component))
+                .collect();
+            sheets.push(Sheet { title, lines });
+        }
+    }
+    sheets
+}
+
+/// One line per taxonomy (sub)category the parts of `projects` were
+/// classified in, like "regulator/ldo: U2 AMS1117-3.3; U5, U6 TLV70033", in
+/// taxonomy order; parts not classified yet are left out
+pub async fn category_lines(pool: &PgPool, projects: &[Project]) -> Result<Vec<String>> {
+    let mut parts: BTreeMap<PartKey, Vec<String>> = BTreeMap::new();
+    for project in projects {
+        for component in components_from_distilled(&project.to_distilled()) {
+            parts.entry(PartKey::of(&component)).or_default().push(component.reference);
+        }
+    }
+    let keys: Vec<PartKey> = parts.keys().cloned().collect();
+    let categories = part_categories(pool, &keys).await?;
+
+    let rank = |category: &str| TAXONOMY.iter().position(|(name, _)| *name == category);
+    let mut by_label: BTreeMap<(Option<usize>, String), Vec<String>> = BTreeMap::new();
+    for (key, mut references) in parts {
+        let Some(category) = categories.get(&key) else { continue };
+        references.sort_by_key(|reference| natural_key(reference));
+        let name = [&key.mpn, &key.value].into_iter().find(|name| !name.is_empty());
+        let part = match name {
+            Some(name) => format!("{} {}", references.join(", "), name),
+            None => references.join(", "),
+        };
+        by_label.entry((rank(&category.category), category.label())).or_default().push(part);
+    }
+    Ok(by_label
+        .into_iter()
+        .map(|((_, label), parts)| {
+            let named: Vec<&str> = parts.iter().take(MAX_PARTS_PER_CATEGORY).map(String::as_str).collect();
+            let mut line = format!("{}: {}", label, named.join("; "));
+            if parts.len() > MAX_PARTS_PER_CATEGORY {
+                line.push_str(&format!("; and {} more", parts.len() - MAX_PARTS_PER_CATEGORY));
+            }
+            line
+        })
+        .collect())
+}
+
+/// "R1 (resistor, Device:R, 10k): 1=VIN 2=OUT"
+fn component_line(reference: &str, component: &Value) -> String {
+    let text = |key: &str| component[key].as_str().unwrap_or("?");
+    let pins: Vec<String> = component["pins"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|pin| Some(format!("{}={}", pin["number"].as_str()?, pin["net"].as_str()?)))
+        .collect();
+    format!(
+        "{} ({}, {}, {}): {}",
+        reference,
+        text("category"),
+        text("lib_id"),
+        text("value"),
+        pins.join(" ")
+    )
+}
+
+/// Map-reduce summaries of inputs too large for one prompt
+///
+/// Each sheet's netlist is cut into chunks that fit the model's window and
+/// each chunk is summarized; the chunk summaries are merged into the sheet's
+/// summary, and the sheet summaries into the project overview. Merges that
+/// don't fit one prompt are done in batches, then the batches merged, until
+/// they do. Every step's answer is cached by its prompt (see
+/// kicad_db::summary_chunks), so a re-run only asks about what changed.
+pub struct Summarizer<'a> {
+    pool: &'a PgPool,
+    chat: &'a dyn ChatProvider,
+    prompts: &'a PromptLibrary,
+    model: &'a str,
+    context_window: usize,
+    repo: &'a str,
+    generated: AtomicUsize,
+    cached: AtomicUsize,
+}
+
+impl<'a> Summarizer<'a> {
+    pub fn new(
+        pool: &'a PgPool,
+        chat: &'a dyn ChatProvider,
+        prompts: &'a PromptLibrary,
+        model: &'a str,
+        context_window: usize,
+        repo: &'a str,
+    ) -> Self {
+        Self {
+            pool,
+            chat,
+            prompts,
+            model,
+            context_window,
+            repo,
+            generated: AtomicUsize::new(0),
+            cached: AtomicUsize::new(0),
+        }
+    }
+
+    /// Overview of the project at `commit`, and the summary of each sheet
+    ///
+    /// `categories` lists its parts by taxonomy category (see
+    /// [`category_lines`]). `level`, `persona` and `guidelines` shape the
+    /// overview as they do a commit summary; the intermediate steps always
+    /// use the same short instructions.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn overview(
+        &self,
+        commit: &str,
+        files: &[String],
+        sheets: &[Sheet],
+        categories: &[String],
+        level: Option<DetailLevel>,
+        persona: Option<&Persona>,
+        guidelines: Option<&GuidelinePrompt>,
+    ) -> Result<(String, Vec<PartSummary>)> {
+        let mut sheet_summaries = Vec::new();
+        for sheet in sheets {
+            let text = self.sheet_summary(sheet).await?;
+            sheet_summaries.push(PartSummary {
+                title: sheet.title.clone(),
+                text,
+            });
+        }
+
+        let max_tokens = super::summary::max_output_tokens(level, persona);
+        let variables = |sheets: &[PartSummary]| {
+            json!({
+                "repo": self.repo,
+                "commit": commit,
+                "files": files,
+                "sheets": sheets,
+                "categories": categories,
+                "instructions": level.unwrap_or_default().instructions(),
+            })
+        };
+        let room = self.room(REPO_OVERVIEW, variables(&[]), max_tokens)?;
+        let parts = self.reduce("the project", sheet_summaries.clone(), room).await?;
+        let overview = self
+            .step(REPO_OVERVIEW, variables(&parts), max_tokens, |request| {
+                if let Some(persona) = persona {
+                    persona.apply(request);
+                    request.max_tokens = Some(max_tokens);
+                }
+                if let Some(guidelines) = guidelines {
+                    guidelines.apply(request);
+                }
+            })
+            .await?;
+
+        info!(
+            "Overview of {}@{}: {} steps generated, {} cached",
+            self.repo,
+            commit,
+            self.generated.load(Ordering::Relaxed),
+            self.cached.load(Ordering::Relaxed)
+        );
+        Ok((overview, sheet_summaries))
+    }
+
+    /// Summary of one sheet: its chunks summarized, then merged
+    async fn sheet_summary(&self, sheet: &Sheet) -> Result<String> {
+        let chunks = self.chunks(sheet)?;
+        let parts = chunks.len();
+        let summaries: Vec<PartSummary> = stream::iter(chunks.into_iter().enumerate())
+            .map(|(i, content)| async move {
+                let variables = json!({
+                    "repo": self.repo,
+                    "sheet": sheet.title,
+                    "part": i + 1,
+                    "parts": parts,
+                    "content": content,
+                });
+                let text = self.step(CHUNK_SUMMARY, variables, STEP_MAX_TOKENS, |_| {}).await?;
+                Ok::<_, anyhow::Error>(PartSummary {
+                    title: format!("Part {}", i + 1),
+                    text,
+                })
+            })
+            .buffered(CONCURRENCY)
+            .try_collect()
+            .await?;
+        let scope = format!("the sheet {}", sheet.title);
+        self.merge(&scope, summaries).await
+    }
+
+    /// The sheet's lines, cut into chunks that each fit a chunk prompt
+    fn chunks(&self, sheet: &Sheet) -> Result<Vec<String>> {
+        let bare = json!({ "repo": self.repo, "sheet": sheet.title, "part": 1, "parts": 1, "content": "" });
+        let room = self.room(CHUNK_SUMMARY, bare, STEP_MAX_TOKENS)?.min(MAX_CHUNK_TOKENS);
+        let encoding = Encoding::for_model(self.model);
+
+        let mut chunks: Vec<String> = Vec::new();
+        let mut used = 0;
+        for line in &sheet.lines {
+            let cost = encoding.count(line) + 1;
+            match chunks.last_mut() {
+                Some(chunk) if used + cost <= room => {
+                    chunk.push('\n');
+                    chunk.push_str(line);
+                    used += cost;
+                }
+                _ => {
+                    chunks.push(line.clone());
+                    used = cost;
+                }
+            }
+        }
+        Ok(chunks)
+    }
+
+    /// One summary of all `parts`, merging them in as many rounds as it takes
+    async fn merge(&self, scope: &str, parts: Vec<PartSummary>) -> Result<String> {
+        let variables = |parts: &[PartSummary]| json!({ "repo": self.repo, "scope": scope, "summaries": parts });
+        let room = self.room(MERGE_SUMMARIES, variables(&[]), STEP_MAX_TOKENS)?;
+        let mut parts = self.reduce(scope, parts, room).await?;
+        if parts.len() == 1 {
+            return Ok(parts.remove(0).text);
+        }
+        self.step(MERGE_SUMMARIES, variables(&parts), STEP_MAX_TOKENS, |_| {})
+            .await
+    }
+
+    /// Merge neighbouring `parts` in batches until together they take at most
+    /// `room` tokens
+    async fn reduce(&self, scope: &str, mut parts: Vec<PartSummary>, room: usize) -> Result<Vec<PartSummary>> {
+        let encoding = Encoding::for_model(self.model);
+        let cost = |part: &PartSummary| encoding.count(&part.title) + encoding.count(&part.text) + 4;
+        while parts.len() > 1 && parts.iter().map(cost).sum::<usize>() > room {
+            let mut batches: Vec<Vec<PartSummary>> = Vec::new();
+            let mut used = 0;
+            for part in parts {
+                let part_cost = cost(&part);
+                match batches.last_mut() {
+                    // Two parts per batch at least, so every round shrinks the list
+                    Some(batch) if batch.len() < 2 || used + part_cost <= room => {
+                        used += part_cost;
+                        batch.push(part);
+                    }
+                    _ => {
+                        used = part_cost;
+                        batches.push(vec![part]);
+                    }
+                }
+            }
+
+            parts = Vec::new();
+            for mut batch in batches {
+                if batch.len() == 1 {
+                    parts.extend(batch.pop());
+                    continue;
+                }
+                let title = format!("{} to {}", batch[0].title, batch[batch.len() - 1].title);
+                let variables = json!({ "repo": self.repo, "scope": scope, "summaries": batch });
+                let text = self.step(MERGE_SUMMARIES, variables, STEP_MAX_TOKENS, |_| {}).await?;
+                parts.push(PartSummary { title, text });
+            }
+        }
+        Ok(parts)
+    }
+
+    /// Tokens left for content in a prompt from `template`, rendered with
+    /// `bare` (the variables without the content)
+    fn room(&self, template: &str, bare: Value, max_tokens: u32) -> Result<usize> {
+        let prompt = self.prompts.render(template, bare)?;
+        PromptBudget::new(self.model, self.context_window)
+            .reserve_output(max_tokens)
+            .fixed(&prompt.text)
+            .available()
+            .filter(|&room| room > 0)
+            .with_context(|| format!("{}'s context window is too small to summarize with", self.model))
+    }
+
+    /// The model's answer to a prompt from `template`, from the cache when
+    /// it has been asked before
+    async fn step(
+        &self,
+        template: &str,
+        variables: Value,
+        max_tokens: u32,
+        customize: impl FnOnce(&mut ChatCompletionRequest),
+    ) -> Result<String> {
+        let prompt = self.prompts.render(template, variables)?;
+        let version = prompt.label();
+        let mut request = ChatCompletionRequest::with_stream(vec![Message::user(prompt.text)], self.model.to_string(), true);
+        request.max_tokens = Some(max_tokens);
+        customize(&mut request);
+
+        let digest = summary_chunk_digest(self.model, &request.to_json()?);
+        match get_summary_chunk(self.pool, &digest).await {
+            Ok(Some(summary)) => {
+                self.cached.fetch_add(1, Ordering::Relaxed);
+                return Ok(summary);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to look up a cached summary step: {}", e),
+        }
+
+        let mut stream = self
+            .chat
+            .chat_completion_stream(&request, CancellationToken::new())
+            .await
+            .context("XAI API call failed")?;
+        let mut summary = String::new();
+        while let Some(event) = stream.next().await {
+            if let Some(text) = event.context("XAI stream failed")?.text() {
+                summary.push_str(text);
+            }
+        }
+        let summary = summary.trim().to_string();
+        if summary.is_empty() {
+            bail!("The model answered {} with an empty summary", version);
+        }
+        self.generated.fetch_add(1, Ordering::Relaxed);
+
+        if let Err(e) = store_summary_chunk(self.pool, &digest, self.model, &version, &summary).await {
+            warn!("Failed to cache a summary step: {}", e);
+        }
+        Ok(summary)
+    }
+}
@@ -0,0 +1,400 @@
+use anyhow::{bail, Context, Result};
+use futures_util::StreamExt;
+use kicad_db::{
+    detail_levels::DetailLevel,
+    personas::Persona,
+    prompt_budget::PromptBudget,
+    prompts::{PromptLibrary, RenderedPrompt, COMMIT_BLURB, COMPARE_SUMMARY},
+    messages::{ChatCompletionRequest, Message},
+    xai_client::{ChatCompletionResponse, InputMessage, ResponsesRequest, StreamEvent, Tool},
+    schematic::DiffWarning,
+    ErcFinding, GuidelinePrompt,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::services::{anomalies, choices::ChoiceSelector, erc, llm::ChatProvider};
+
+/// Names of the prompt budget sections holding the ERC findings and the
+/// diff warnings
+const ERC_SECTION: &str = "erc";
+const WARNINGS_SECTION: &str = "warnings";
+/// Names of the comparison prompt's sections: the commits in between, and
+/// the component and net changes
+const COMMITS_SECTION: &str = "commits";
+const CHANGES_SECTION: &str = "changes";
+/// Blurbs asked for at once, for a [`ChoiceSelector`] to pick from
+const BLURB_CHOICES: u32 = 3;
+/// Output allowed for each blurb
+const BLURB_MAX_TOKENS: u32 = 80;
+
+/// AI-generated summary of a single commit
+#[derive(Debug, Clone)]
+pub struct CommitSummary {
+    pub summary: String,
+    pub details: String,
+    /// Template the prompt was rendered from, e.g. "commit_summary@v1"
+    pub prompt_version: String,
+}
+
+/// The commit summary prompt for a commit, with length instructions for
+/// `level`, any ERC findings the commit introduced and the warnings about
+/// possibly unintended changes in its diff
+///
+/// `template` is `COMMIT_SUMMARY` unless the repo is registered with its own;
+/// it gets the same variables. The findings and warnings are cut short if the
+/// prompt wouldn't fit in `budget`.
+#[allow(clippy::too_many_arguments)]
+pub fn commit_prompt(
+    prompts: &PromptLibrary,
+    template: &str,
+    repo: &str,
+    commit: &str,
+    level: DetailLevel,
+    new_violations: &[&ErcFinding],
+    warnings: &[DiffWarning],
+    budget: PromptBudget,
+) -> Result<RenderedPrompt> {
+    let github_url = format!("https://github.com/{}/commit/{}", repo, commit);
+    let variables = |erc_section: &str, warnings_section: &str| {
+        serde_json::json!({
+            "repo": repo,
+            "commit": commit,
+            "commit_url": github_url,
+            "instructions": level.instructions(),
+            "erc_section": erc_section,
+            "warnings_section": warnings_section,
+        })
+    };
+
+    let bare = prompts.render(template, variables("", ""))?;
+    let fitted = budget
+        .fixed(&bare.text)
+        .section(ERC_SECTION, erc::prompt_section(new_violations))
+        .section(WARNINGS_SECTION, anomalies::prompt_section(warnings))
+        .fit();
+    if !fitted.truncated.is_empty() {
+        warn!(
+            "Shortened the ERC findings or diff warnings in the summary prompt for {}@{} to fit the context window",
+            repo, commit
+        );
+    }
+    let prompt = prompts.render(
+        template,
+        variables(fitted.text(ERC_SECTION), fitted.text(WARNINGS_SECTION)),
+    )?;
+    Ok(prompt)
+}
+
+/// Output a summary request may use: an explicit detail level's budget, else
+/// the persona's, else the default level's
+pub fn max_output_tokens(detail_level: Option<DetailLevel>, persona: Option<&Persona>) -> u32 {
+    match (detail_level, persona) {
+        (None, Some(persona)) => persona.max_tokens,
+        (level, _) => level.unwrap_or_default().max_tokens(),
+    }
+}
+
+/// Ask Grok to summarize a commit.
+///
+/// The prompt is the current version of `template` (see [`commit_prompt`]). `detail_level` picks the
+/// length instructions passed to it; when given explicitly its token budget
+/// overrides the persona's. `guidelines` are the repo's design guidelines,
+/// added to the system prompt. `new_violations` are ERC findings the commit
+/// introduced and `warnings` the possibly unintended changes in its diff;
+/// both are listed in the prompt so the summary calls them out.
+/// `context_window` is the model's, which the prompt is fitted to.
+#[allow(clippy::too_many_arguments)]
+pub async fn generate_commit_summary(
+    chat: &dyn ChatProvider,
+    prompts: &PromptLibrary,
+    template: &str,
+    model: &str,
+    context_window: usize,
+    repo: &str,
+    commit: &str,
+    detail_level: Option<DetailLevel>,
+    persona: Option<&Persona>,
+    guidelines: Option<&GuidelinePrompt>,
+    new_violations: &[&ErcFinding],
+    warnings: &[DiffWarning],
+) -> Result<CommitSummary> {
+    let level = detail_level.unwrap_or_default();
+    let max_tokens = max_output_tokens(detail_level, persona);
+
+    let budget = PromptBudget::new(model, context_window).reserve_output(max_tokens);
+    let prompt = commit_prompt(prompts, template, repo, commit, level, new_violations, warnings, budget)?;
+
+    // Create input message for responses API
+    let input = vec![InputMessage::user(prompt.text.clone())];
+
+    // Create tools - use both web_search and x_search for comprehensive results
+    let tools = vec![Tool::web_search(), Tool::x_search()];
+
+    let mut responses_request = ResponsesRequest::new(model.to_string(), input, tools);
+    if let Some(persona) = persona {
+        persona.apply_to_responses(&mut responses_request);
+    }
+    if let Some(guidelines) = guidelines {
+        guidelines.apply_to_responses(&mut responses_request);
+    }
+    // Set after the persona, whose token budget an explicit detail level overrides
+    responses_request.max_output_tokens = Some(max_tokens);
+
+    // Make API call using responses endpoint
+    let api_response = chat
+        .responses(&responses_request)
+        .await
+        .context("XAI API call failed")?;
+
+    // Extract response content from tool results
+    // The responses API returns tool call results, so we need to extract meaningful information
+    let summary = if let Some(output) = &api_response.output {
+        // Try to extract text from tool results
+        let mut result_parts = Vec::new();
+
+        for item in output {
+            if let Some(name) = &item.name {
+                result_parts.push(format!("Tool: {}", name));
+            }
+            if let Some(status) = &item.status {
+                result_parts.push(format!("Status: {}", status));
+            }
+            if let Some(result) = &item.result {
+                result_parts.push(format!(
+                    "Result: {}",
+                    serde_json::to_string(result).unwrap_or_else(|_| "N/A".to_string())
+                ));
+            }
+            if let Some(content) = &item.content {
+                result_parts.push(format!(
+                    "Content: {}",
+                    serde_json::to_string(content).unwrap_or_else(|_| "N/A".to_string())
+                ));
+            }
+        }
+
+        if result_parts.is_empty() {
+            format!(
+                "Found {} tool result(s) for commit {}/{}",
+                output.len(),
+                repo,
+                commit
+            )
+        } else {
+            result_parts.join("\n")
+        }
+    } else {
+        format!("No results returned for commit {}/{}", repo, commit)
+    };
+
+    // For details, include more information about the response
+    let details = format!(
+        "Response ID: {:?}\nModel: {:?}\nTool Results: {}\n\n{}",
+        api_response.id,
+        api_response.model,
+        api_response.output.as_ref().map(|o| o.len()).unwrap_or(0),
+        summary
+    );
+
+    Ok(CommitSummary {
+        summary,
+        details,
+        prompt_version: prompt.label(),
+    })
+}
+
+/// A chat completion asking for a commit's summary at the default detail
+/// level, for submitting deferred, and the version of the prompt in it
+///
+/// The same prompt as [`generate_commit_summary`], without the web and X
+/// search tools, which only the responses API offers.
+#[allow(clippy::too_many_arguments)]
+pub fn commit_summary_request(
+    prompts: &PromptLibrary,
+    template: &str,
+    model: &str,
+    context_window: usize,
+    repo: &str,
+    commit: &str,
+    persona: Option<&Persona>,
+    guidelines: Option<&GuidelinePrompt>,
+    new_violations: &[&ErcFinding],
+    warnings: &[DiffWarning],
+) -> Result<(ChatCompletionRequest, String)> {
+    let level = DetailLevel::default();
+    let budget = PromptBudget::new(model, context_window).reserve_output(max_output_tokens(None, persona));
+    let prompt = commit_prompt(prompts, template, repo, commit, level, new_violations, warnings, budget)?;
+    let mut request = ChatCompletionRequest::new(vec![Message::user(prompt.text.clone())], model.to_string());
+    match persona {
+        Some(persona) => persona.apply(&mut request),
+        None => request.max_tokens = Some(level.max_tokens()),
+    }
+    if let Some(guidelines) = guidelines {
+        guidelines.apply(&mut request);
+    }
+    Ok((request, prompt.label()))
+}
+
+/// The summary a completed [`commit_summary_request`] answered with
+pub fn summary_from_completion(response: &ChatCompletionResponse) -> Result<String> {
+    response
+        .choices
+        .iter()
+        .filter_map(|choice| choice.message.as_ref()?.content.as_deref())
+        .map(str::trim)
+        .find(|content| !content.is_empty())
+        .map(str::to_string)
+        .context("The model answered with an empty summary")
+}
+
+/// Ask the model how a project evolved from `base` to `head`, for release
+/// notes; returns the narrative and the version of the prompt
+///
+/// `commits` are those in between, oldest first, as (hash, summary line);
+/// `changes` is the component and net diff between the two. Both are cut
+/// short if they don't fit the model's context window.
+#[allow(clippy::too_many_arguments)]
+pub async fn generate_comparison(
+    chat: &dyn ChatProvider,
+    prompts: &PromptLibrary,
+    model: &str,
+    context_window: usize,
+    repo: &str,
+    base: &str,
+    head: &str,
+    commits: &[(String, Option<String>)],
+    changes: &str,
+    level: DetailLevel,
+) -> Result<(String, String)> {
+    let variables = |commits: &str, changes: &str| {
+        serde_json::json!({
+            "repo": repo,
+            "base": base,
+            "head": head,
+            "commits": commits,
+            "changes": changes,
+            "instructions": level.instructions(),
+        })
+    };
+    let commit_lines: Vec<String> = commits
+        .iter()
+        .map(|(hash, message)| format!("- {} {}", &hash[..7.min(hash.len())], message.as_deref().unwrap_or("")))
+        .collect();
+
+    let bare = prompts.render(COMPARE_SUMMARY, variables("", ""))?;
+    let fitted = PromptBudget::new(model, context_window)
+        .reserve_output(level.max_tokens())
+        .fixed(&bare.text)
+        .section(COMMITS_SECTION, commit_lines.join("\n"))
+        .section(CHANGES_SECTION, changes)
+        .fit();
+    if !fitted.truncated.is_empty() {
+        warn!(
+            "Shortened {} in the comparison prompt for {} {}..{} to fit the context window",
+            fitted.truncated.join(", "),
+            repo,
+            base,
+            head
+        );
+    }
+    let prompt = prompts.render(
+        COMPARE_SUMMARY,
+        variables(fitted.text(COMMITS_SECTION), fitted.text(CHANGES_SECTION)),
+    )?;
+
+    let mut request = ChatCompletionRequest::with_stream(vec![Message::user(prompt.text.clone())], model.to_string(), true);
+    request.max_tokens = Some(level.max_tokens());
+    let summary = complete(chat, &request).await?;
+    Ok((summary, prompt.label()))
+}
+
+/// The whole text of a streamed completion, trimmed; an error if it's empty
+pub async fn complete(chat: &dyn ChatProvider, request: &ChatCompletionRequest) -> Result<String> {
+    let mut stream = chat
+        .chat_completion_stream(request, CancellationToken::new())
+        .await
+        .context("XAI API call failed")?;
+    let mut text = String::new();
+    while let Some(event) = stream.next().await {
+        if let Some(delta) = event.context("XAI stream failed")?.text() {
+            text.push_str(delta);
+        }
+    }
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        bail!("The model gave an empty answer");
+    }
+    Ok(text)
+}
+
+/// Every answer of a streamed completion asking for several choices, in
+/// order and trimmed; empty ones are kept so indexes match the choices
+pub async fn complete_choices(chat: &dyn ChatProvider, request: &ChatCompletionRequest) -> Result<Vec<String>> {
+    let mut stream = chat
+        .chat_completion_stream(request, CancellationToken::new())
+        .await
+        .context("XAI API call failed")?;
+    let mut choices = vec![String::new(); request.n.unwrap_or(1).max(1) as usize];
+    while let Some(event) = stream.next().await {
+        let (index, text) = match event.context("XAI stream failed")? {
+            StreamEvent::ContentDelta { text } => (0, text),
+            StreamEvent::ChoiceDelta { index, text } => (index as usize, text),
+            _ => continue,
+        };
+        if index >= choices.len() {
+            choices.resize(index + 1, String::new());
+        }
+        choices[index].push_str(&text);
+    }
+    Ok(choices.into_iter().map(|choice| choice.trim().to_string()).collect())
+}
+
+/// Ask for a few one-line blurbs of a commit and keep the one `selector`
+/// picks; returns it and the version of the prompt
+///
+/// `changes` is the commit's component and net diff, cut short to fit
+/// `context_window`.
+#[allow(clippy::too_many_arguments)]
+pub async fn generate_blurb(
+    chat: &dyn ChatProvider,
+    prompts: &PromptLibrary,
+    model: &str,
+    context_window: usize,
+    repo: &str,
+    commit: &str,
+    message: Option<&str>,
+    changes: &str,
+    selector: &dyn ChoiceSelector,
+) -> Result<(String, String)> {
+    let variables = |changes: &str| {
+        serde_json::json!({
+            "repo": repo,
+            "commit": commit,
+            "message": message.unwrap_or("(no message)"),
+            "changes": changes,
+        })
+    };
+    let bare = prompts.render(COMMIT_BLURB, variables(""))?;
+    let fitted = PromptBudget::new(model, context_window)
+        .reserve_output(BLURB_MAX_TOKENS * BLURB_CHOICES)
+        .fixed(&bare.text)
+        .section(CHANGES_SECTION, changes)
+        .fit();
+    if !fitted.truncated.is_empty() {
+        warn!("Shortened the changes in the blurb prompt for {}@{} to fit the context window", repo, commit);
+    }
+    let prompt = prompts.render(COMMIT_BLURB, variables(fitted.text(CHANGES_SECTION)))?;
+
+    let request = ChatCompletionRequest::with_stream(vec![Message::user(prompt.text.clone())], model.to_string(), true)
+        .with_max_tokens(BLURB_MAX_TOKENS)
+        .with_choices(BLURB_CHOICES);
+    let choices = complete_choices(chat, &request).await?;
+    let Some(index) = selector.select(&choices) else {
+        bail!("The model gave no usable blurb");
+    };
+    // Models sometimes wrap a one-liner in quotes or start a list
+    let blurb = choices[index].lines().next().unwrap_or_default();
+    let blurb = blurb.trim_start_matches("- ").trim_matches('"').trim();
+    Ok((blurb.to_string(), prompt.label()))
+}
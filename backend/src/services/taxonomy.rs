@@ -0,0 +1,167 @@
+use anyhow::{bail, Result};
+use arc_swap::ArcSwap;
+use kicad_db::{
+    component_categories::taxonomy_lines,
+    messages::{ChatCompletionRequest, Message},
+    prompts::{PromptLibrary, COMPONENT_TAXONOMY},
+    store_part_categories, unclassified_parts, CommitEventKind, PartCategory, PartKey, PgPool,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+use super::{events, llm::ChatProvider, model_health::ModelHealth, priority, summary};
+use crate::config::Config;
+use crate::shutdown;
+use crate::types::WorkClass;
+
+const CLASSIFY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Parts per prompt
+const BATCH_SIZE: i64 = 40;
+/// Caps API calls per run, so a backlog of parts is worked off over several runs
+const MAX_BATCHES_PER_RUN: usize = 10;
+/// Output allowed for a batch: one short line per part
+const BATCH_MAX_TOKENS: u32 = 1_000;
+/// Longest value, footprint or MPN sent, in characters
+const MAX_FIELD_CHARS: usize = 80;
+
+/// Keep every stored part classified in the component taxonomy
+///
+/// Runs when a commit finishes processing and every few minutes, asking
+/// the classification model about the parts no one has classified yet (see
+/// kicad_db::component_categories), or the fallback `health` routes it to
+/// while that model is slow or failing. Parts are shared by all repos, so
+/// each is asked about once. Each run uses the config and prompts in effect
+/// then.
+pub fn spawn_classifier(
+    pool: Arc<PgPool>,
+    chat: Arc<dyn ChatProvider>,
+    prompts: Arc<ArcSwap<PromptLibrary>>,
+    health: Arc<ModelHealth>,
+    config: Arc<ArcSwap<Config>>,
+) {
+    let mut events = events::subscribe();
+    tokio::spawn(priority::scope(WorkClass::Enrichment, async move {
+        let mut interval = tokio::time::interval(CLASSIFY_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                event = events.recv() => match event {
+                    Ok(event) if event.kind != CommitEventKind::Processed => continue,
+                    Err(RecvError::Closed) => break,
+                    // Missed events only mean the run is due
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                },
+                _ = shutdown::started() => break,
+            }
+            // Park the run rather than fail every batch while XAI is down
+            if !chat.available() {
+                info!("XAI is unavailable; skipping this classification run");
+                continue;
+            }
+            let model = health.route("classification", &config.load().models.classification);
+            match classify_pending(&pool, chat.as_ref(), &prompts.load(), &model).await {
+                Ok(0) => debug!("No parts to classify"),
+                Ok(n) => info!("Classified {} parts", n),
+                Err(e) => warn!("Failed to classify parts: {:#}", e),
+            }
+        }
+    }));
+}
+
+/// Classify unclassified parts in batches; returns how many were stored
+pub async fn classify_pending(
+    pool: &PgPool,
+    chat: &dyn ChatProvider,
+    prompts: &PromptLibrary,
+    model: &str,
+) -> Result<usize> {
+    let mut stored = 0;
+    for _ in 0..MAX_BATCHES_PER_RUN {
+        let pending = unclassified_parts(pool, BATCH_SIZE).await?;
+        if pending.is_empty() {
+            break;
+        }
+        let classified = classify(chat, prompts, model, &pending).await?;
+        store_part_categories(pool, &classified, model).await?;
+        stored += classified.len();
+        if pending.len() < BATCH_SIZE as usize {
+            break;
+        }
+    }
+    Ok(stored)
+}
+
+/// Ask `model` where each of `parts` sits in the taxonomy
+///
+/// A part the answer skips or files under an unknown name is "other", so it
+/// isn't asked about again on every run; an answer placing none is an error.
+async fn classify(
+    chat: &dyn ChatProvider,
+    prompts: &PromptLibrary,
+    model: &str,
+    parts: &[PartKey],
+) -> Result<Vec<(PartKey, PartCategory)>> {
+    let lines: Vec<String> = parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let shortened = PartKey {
+                value: part.value.chars().take(MAX_FIELD_CHARS).collect(),
+                footprint: part.footprint.chars().take(MAX_FIELD_CHARS).collect(),
+                mpn: part.mpn.chars().take(MAX_FIELD_CHARS).collect(),
+            };
+            format!("{}. {}", i + 1, shortened.prompt_line())
+        })
+        .collect();
+    let prompt = prompts.render(
+        COMPONENT_TAXONOMY,
+        serde_json::json!({
+            "taxonomy": taxonomy_lines(),
+            "parts": lines.join("\n"),
+        }),
+    )?;
+    let mut request = ChatCompletionRequest::with_stream(vec![Message::user(prompt.text)], model.to_string(), true);
+    request.max_tokens = Some(BATCH_MAX_TOKENS);
+    let answer = summary::complete(chat, &request).await?;
+
+    let mut answers = parse(&answer);
+    if !(1..=parts.len()).any(|number| answers.contains_key(&number)) {
+        bail!("The model's answer had no \"N: category\" lines for the parts asked about");
+    }
+    let other = PartCategory {
+        category: "other".to_string(),
+        subcategory: None,
+    };
+    let mut skipped = 0;
+    let classified = parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let category = answers.remove(&(i + 1)).unwrap_or_else(|| {
+                skipped += 1;
+                other.clone()
+            });
+            (part.clone(), category)
+        })
+        .collect();
+    if skipped > 0 {
+        warn!("Filed {} of {} parts the model didn't place under \"other\"", skipped, parts.len());
+    }
+    Ok(classified)
+}
+
+/// The categories of an answer's "N: category/subcategory" lines, by part
+/// number; other lines, and names outside the taxonomy, are ignored
+fn parse(text: &str) -> HashMap<usize, PartCategory> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim().trim_start_matches(['-', '*']).trim_start();
+            let (number, answer) = line.split_once([':', '.', ')'])?;
+            let number = number.trim().parse().ok()?;
+            Some((number, PartCategory::parse(answer)?))
+        })
+        .collect()
+}
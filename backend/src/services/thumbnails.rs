@@ -0,0 +1,85 @@
+use kicad_db::{pending_thumbnails, store_thumbnail, PgPool, ThumbnailSource};
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+use super::image;
+
+/// Widths thumbnails are rendered at, in pixels
+pub const THUMBNAIL_WIDTHS: [i32; 2] = [256, 1024];
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Images loaded at a time; each may be several megabytes
+const BATCH_SIZE: i64 = 8;
+
+static WAKE: Lazy<Notify> = Lazy::new(Notify::new);
+
+/// Render thumbnails soon, e.g. after new schematic images were stored
+pub fn wake() {
+    WAKE.notify_one();
+}
+
+/// Keep PNG thumbnails of stored schematic images up to date
+///
+/// Every few minutes (or on `wake`), renders each width in `THUMBNAIL_WIDTHS`
+/// for images that lack it or have changed since. A failed render is stored
+/// too, so a broken image isn't retried until it is replaced.
+pub fn spawn(pool: Arc<PgPool>) {
+    tokio::spawn(async move {
+        loop {
+            match render_pending(&pool).await {
+                Ok(0) => debug!("No thumbnails to render"),
+                Ok(n) => info!("Rendered thumbnails for {} schematic images", n),
+                Err(e) => warn!("Failed to render thumbnails: {}", e),
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(SWEEP_INTERVAL) => {}
+                _ = WAKE.notified() => {}
+            }
+        }
+    });
+}
+
+/// Render pending thumbnails in batches; returns how many images were handled
+async fn render_pending(pool: &PgPool) -> Result<usize, sqlx::Error> {
+    let mut handled = 0;
+    loop {
+        let pending = pending_thumbnails(pool, &THUMBNAIL_WIDTHS, BATCH_SIZE).await?;
+        if pending.is_empty() {
+            return Ok(handled);
+        }
+        for source in pending {
+            render(pool, source).await?;
+            handled += 1;
+        }
+    }
+}
+
+async fn render(pool: &PgPool, source: ThumbnailSource) -> Result<(), sqlx::Error> {
+    let source = Arc::new(source);
+    for &width in &source.widths {
+        let job = source.clone();
+        let result = tokio::task::spawn_blocking(move || image::png_thumbnail(&job.image, width as u32))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result)
+            .map_err(|e| format!("{:#}", e));
+        if let Err(e) = &result {
+            warn!(
+                "Failed to render {}px thumbnail of {} at {}: {}",
+                width, source.repo_url, source.commit_hash, e
+            );
+        }
+        store_thumbnail(
+            pool,
+            source.schematic_id,
+            width,
+            &source.digest,
+            result.as_deref().map_err(String::as_str),
+        )
+        .await?;
+    }
+    Ok(())
+}
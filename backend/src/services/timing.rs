@@ -0,0 +1,150 @@
+use kicad_db::PgPool;
+use once_cell::sync::Lazy;
+use std::future::Future;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::LatencyBudgets;
+use crate::types::AnalysisTimeline;
+
+/// A stage of the analysis pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Clone,
+    Parse,
+    PromptBuild,
+    Llm,
+    Store,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Clone => "clone",
+            Stage::Parse => "parse",
+            Stage::PromptBuild => "prompt_build",
+            Stage::Llm => "llm",
+            Stage::Store => "store",
+        }
+    }
+
+    /// Budget for this stage, as set with [`configure`]
+    fn budget(&self) -> Duration {
+        let budgets = BUDGETS.read().unwrap();
+        let ms = match self {
+            Stage::Clone => budgets.clone_ms,
+            Stage::Parse => budgets.parse_ms,
+            Stage::PromptBuild => budgets.prompt_build_ms,
+            Stage::Llm => budgets.llm_ms,
+            Stage::Store => budgets.store_ms,
+        };
+        Duration::from_millis(ms)
+    }
+}
+
+/// Per-stage budgets; the defaults are generous first guesses
+static BUDGETS: Lazy<RwLock<LatencyBudgets>> = Lazy::new(Default::default);
+
+/// Use the configured budgets; called at startup and on each reload
+pub fn configure(budgets: &LatencyBudgets) {
+    *BUDGETS.write().unwrap() = budgets.clone();
+}
+
+/// Records how long each stage of one analysis takes
+pub struct StageTimer {
+    analysis: &'static str,
+    started: Instant,
+    timeline: AnalysisTimeline,
+}
+
+impl StageTimer {
+    /// Start timing an analysis (e.g. "overview", "init", "commit_summary")
+    pub fn start(analysis: &'static str) -> Self {
+        Self {
+            analysis,
+            started: Instant::now(),
+            timeline: AnalysisTimeline::default(),
+        }
+    }
+
+    /// Run `fut` as `stage`, recording its duration
+    pub async fn time<F: Future>(&mut self, stage: Stage, fut: F) -> F::Output {
+        let start = Instant::now();
+        let output = fut.await;
+        self.record(stage, start.elapsed());
+        output
+    }
+
+    /// Record a duration for a stage measured elsewhere (adds to any earlier value)
+    pub fn record(&mut self, stage: Stage, elapsed: Duration) {
+        let ms = elapsed.as_millis() as u64;
+        let slot = match stage {
+            Stage::Clone => &mut self.timeline.clone_ms,
+            Stage::Parse => &mut self.timeline.parse_ms,
+            Stage::PromptBuild => &mut self.timeline.prompt_build_ms,
+            Stage::Llm => &mut self.timeline.llm_ms,
+            Stage::Store => &mut self.timeline.store_ms,
+        };
+        *slot = Some(slot.unwrap_or(0) + ms);
+    }
+
+    /// Finish the analysis: compute the total and flag stages over budget
+    pub fn finish(mut self) -> AnalysisTimeline {
+        self.timeline.total_ms = self.started.elapsed().as_millis() as u64;
+
+        let stages = [
+            (Stage::Clone, self.timeline.clone_ms),
+            (Stage::Parse, self.timeline.parse_ms),
+            (Stage::PromptBuild, self.timeline.prompt_build_ms),
+            (Stage::Llm, self.timeline.llm_ms),
+            (Stage::Store, self.timeline.store_ms),
+        ];
+        for (stage, ms) in stages {
+            let Some(ms) = ms else { continue };
+            let budget = stage.budget().as_millis() as u64;
+            if ms > budget {
+                warn!(
+                    "{} analysis: {} stage took {}ms (budget {}ms)",
+                    self.analysis,
+                    stage.as_str(),
+                    ms,
+                    budget
+                );
+                self.timeline.over_budget.push(stage.as_str().to_string());
+            }
+        }
+
+        info!(
+            "{} analysis finished in {}ms: {:?}",
+            self.analysis, self.timeline.total_ms, self.timeline
+        );
+        self.timeline
+    }
+}
+
+/// Persist a finished timeline on the commit's row. Failures are logged only;
+/// timings are diagnostics and must never fail the analysis itself.
+pub async fn store_timeline(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hash: &str,
+    analysis: &str,
+    timeline: &AnalysisTimeline,
+) {
+    let value = match serde_json::to_value(timeline) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Failed to serialize {} timeline: {}", analysis, e);
+            return;
+        }
+    };
+    if let Err(e) =
+        kicad_db::store_analysis_timing(pool, repo_url, commit_hash, analysis, &value).await
+    {
+        warn!(
+            "Failed to store {} timeline for {}: {}",
+            analysis, commit_hash, e
+        );
+    }
+}
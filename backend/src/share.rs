@@ -0,0 +1,144 @@
+//! Signed, expiring links to read-only views of one commit
+//!
+//! A share token is `<payload>.<signature>`: the base64url JSON of the repo,
+//! commit, views and expiry, and its HMAC-SHA256 under `share.secret`. Nothing
+//! is stored, so a link lasts until it expires or the secret changes.
+
+use axum::{
+    extract::{ConnectInfo, RawPathParams, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+    RequestExt,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL, Engine};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::net::SocketAddr;
+use tracing::warn;
+
+use crate::error::AppError;
+use crate::rate_limit;
+use crate::state::AppState;
+use crate::types::ShareScope;
+
+/// What a share link lets its holder see
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShareGrant {
+    pub repo: String,
+    /// Full SHA
+    pub commit: String,
+    pub scopes: Vec<ShareScope>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl ShareGrant {
+    /// Fail with 403 unless the link covers `scope`
+    pub fn require(&self, scope: ShareScope) -> Result<(), AppError> {
+        if self.scopes.contains(&scope) {
+            return Ok(());
+        }
+        Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            format!("This share link doesn't include the {}", scope.as_str()),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    repo: String,
+    commit: String,
+    scopes: Vec<ShareScope>,
+    exp: i64,
+}
+
+fn mac(secret: &str) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length")
+}
+
+/// A token for `grant`, signed with `secret`
+pub fn mint(secret: &str, grant: &ShareGrant) -> String {
+    let claims = Claims {
+        repo: grant.repo.clone(),
+        commit: grant.commit.clone(),
+        scopes: grant.scopes.clone(),
+        exp: grant.expires_at.timestamp(),
+    };
+    let payload = BASE64URL.encode(serde_json::to_vec(&claims).expect("claims serialize"));
+    let mut mac = mac(secret);
+    mac.update(payload.as_bytes());
+    format!("{}.{}", payload, BASE64URL.encode(mac.finalize().into_bytes()))
+}
+
+/// Why a token was turned away
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rejection {
+    Invalid(&'static str),
+    Expired,
+}
+
+/// The grant `token` carries, if `secret` signed it and it hasn't expired
+pub fn verify(secret: &str, token: &str) -> Result<ShareGrant, Rejection> {
+    let (payload, signature) = token.split_once('.').ok_or(Rejection::Invalid("malformed token"))?;
+    let signature = BASE64URL.decode(signature).map_err(|_| Rejection::Invalid("malformed token"))?;
+    let mut mac = mac(secret);
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&signature).map_err(|_| Rejection::Invalid("invalid signature"))?;
+
+    let claims: Claims = BASE64URL
+        .decode(payload)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or(Rejection::Invalid("malformed claims"))?;
+    let expires_at = DateTime::from_timestamp(claims.exp, 0).ok_or(Rejection::Invalid("malformed claims"))?;
+    if expires_at <= Utc::now() {
+        return Err(Rejection::Expired);
+    }
+    Ok(ShareGrant {
+        repo: claims.repo,
+        commit: claims.commit,
+        scopes: claims.scopes,
+        expires_at,
+    })
+}
+
+/// Route middleware admitting requests whose `token` path parameter is a
+/// valid share link, and attaching its [`ShareGrant`] for the handler
+///
+/// No API key is needed; the links are rate-limited per client like the
+/// public embeds. Links signed with another secret fail with 401, expired
+/// ones with 410, and all of them with 404 while share links are turned off.
+pub async fn require_share_link(
+    State(app): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let client = rate_limit::client_ip(request.headers(), peer);
+    let quota = rate_limit::SHARED_VIEWS.check(&client.to_string());
+    if !quota.allowed {
+        warn!("Rate limiting shared views from {}", client);
+        return Ok(rate_limit::too_many_requests(quota));
+    }
+
+    let secret = app.config().share.secret.clone();
+    if secret.is_empty() {
+        return Err(AppError::not_found("Share links are turned off on this server"));
+    }
+    let token = request
+        .extract_parts::<RawPathParams>()
+        .await
+        .ok()
+        .and_then(|params| params.iter().find(|(name, _)| *name == "token").map(|(_, value)| value.to_string()))
+        .unwrap_or_default();
+    let grant = verify(&secret, &token).map_err(|rejection| match rejection {
+        Rejection::Invalid(reason) => AppError::unauthorized(format!("Invalid share link: {}", reason)),
+        Rejection::Expired => AppError::new(StatusCode::GONE, "share_link_expired", "This share link has expired"),
+    })?;
+    request.extensions_mut().insert(grant);
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,80 @@
+use axum::response::sse::Event;
+use futures_util::{Stream, StreamExt};
+use once_cell::sync::Lazy;
+use std::convert::Infallible;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Cancelled once the server starts shutting down
+static SHUTDOWN: Lazy<CancellationToken> = Lazy::new(CancellationToken::new);
+
+/// Whether the server is shutting down
+///
+/// Jobs check this between units of work and stop there, so what they leave
+/// behind is picked up by the next run.
+pub fn requested() -> bool {
+    SHUTDOWN.is_cancelled()
+}
+
+/// Resolves once the server starts shutting down
+pub async fn started() {
+    SHUTDOWN.cancelled().await
+}
+
+/// Wait for Ctrl-C or SIGTERM, then start shutting down
+pub async fn on_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    info!("Shutting down: no longer accepting connections; draining requests, streams and jobs");
+    SHUTDOWN.cancel();
+}
+
+/// `stream`, cut short by a `shutdown` event and `[DONE]` if the server
+/// starts shutting down first
+///
+/// Without this, an open SSE stream would hold the shutdown up until the
+/// drain timeout.
+pub fn sse<S>(stream: S) -> impl Stream<Item = Result<Event, Infallible>>
+where
+    S: Stream<Item = Result<Event, Infallible>>,
+{
+    async_stream::stream! {
+        tokio::pin!(stream);
+        loop {
+            tokio::select! {
+                item = stream.next() => match item {
+                    Some(item) => yield item,
+                    None => return,
+                },
+                _ = started() => {
+                    yield Ok(Event::default().event("shutdown").data("The server is restarting; retry shortly"));
+                    yield Ok(Event::default().data("[DONE]"));
+                    return;
+                }
+            }
+        }
+    }
+}
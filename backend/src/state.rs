@@ -0,0 +1,239 @@
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use axum::extract::FromRef;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::services::{
+    ai_cache::CachingChatProvider,
+    circuit_breaker::CircuitBreakerProvider,
+    costs::MeteredChatProvider,
+    failover::{FailoverProvider, Provider, PRIMARY_PROVIDER},
+    llm::ChatProvider,
+    model_health::{HealthTrackedChatProvider, ModelHealth},
+    priority::{PrioritizedChatProvider, PriorityLimiter},
+    prompt_audit::{AuditedChatProvider, Redactor},
+    response_cache::ResponseCache,
+    resumable::ResumableStreams,
+    schematic_cache::SchematicCache,
+};
+use kicad_db::{PgPool, PostgresSchematicStore, PromptLibrary, SchematicStore};
+
+/// State shared by every route
+///
+/// Handlers extract only the part they need, `State<Arc<PgPool>>`,
+/// `State<Arc<dyn SchematicStore>>`, `State<Arc<Config>>`, `State<Arc<dyn ChatProvider>>`,
+/// `State<Arc<PromptLibrary>>`, `State<Arc<SchematicCache>>`,
+/// `State<Arc<ResponseCache>>`, `State<Arc<ResumableStreams>>` or
+/// `State<Arc<ModelHealth>>`, through the `FromRef` impls below.
+///
+/// The config and prompt templates are swapped out whole when they are
+/// reloaded (see `services::reload`); extractors and [`AppState::config`]
+/// return the current ones, which a request keeps until it finishes.
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: Arc<PgPool>,
+    /// Each commit's schematics: Postgres through `pool`, or a SQLite file
+    /// (see `crate::storage`)
+    pub store: Arc<dyn SchematicStore>,
+    pub config: Arc<ArcSwap<Config>>,
+    /// Shared LLM client, so requests reuse its connection pool
+    pub chat: Arc<dyn ChatProvider>,
+    /// Prompt templates, loaded at startup and on each reload
+    pub prompts: Arc<ArcSwap<PromptLibrary>>,
+    /// Parsed sheets, shared by requests about the same commits
+    pub schematics: Arc<SchematicCache>,
+    /// Finished responses, in memory or Redis
+    pub responses: Arc<ResponseCache>,
+    /// Latest events of streamed summaries, for clients that reconnect
+    pub streams: Arc<ResumableStreams>,
+    /// Latency and errors of the models called, for routing background work
+    pub health: Arc<ModelHealth>,
+    /// The AI calls per minute shared out by work class
+    pub limiter: Arc<PriorityLimiter>,
+}
+
+impl AppState {
+    /// State for the server, with an XAI client built from `config`, then
+    /// the fallback providers', each behind its own circuit breaker, prompt
+    /// audit and cost metering, failing over in that order, with model health
+    /// tracking, the priority rate limit and the response cache in front
+    /// (breakers, audit and cache unless they are disabled)
+    pub fn new(pool: PgPool, config: Config, prompts: PromptLibrary) -> Result<Self> {
+        let pool = Arc::new(pool);
+        let live = Arc::new(ArcSwap::from_pointee(config));
+        let config = live.load_full();
+        let mut providers = vec![Provider {
+            name: PRIMARY_PROVIDER.to_string(),
+            chat: Arc::new(config.xai_client().context("Failed to initialize XAI client")?),
+            models: HashMap::new(),
+        }];
+        for fallback in &config.xai.fallbacks {
+            let client = config
+                .fallback_client(fallback)
+                .with_context(|| format!("Failed to initialize the {} client", fallback.name))?;
+            providers.push(Provider {
+                name: fallback.name.clone(),
+                chat: Arc::new(client),
+                models: fallback.models.clone(),
+            });
+        }
+        let redactor = if config.audit.enabled {
+            Some(Arc::new(Redactor::new(&config.audit)?))
+        } else {
+            None
+        };
+        for provider in &mut providers {
+            if config.xai.breaker_failures > 0 {
+                let cooldown = Duration::from_secs(config.xai.breaker_cooldown_secs);
+                provider.chat = Arc::new(CircuitBreakerProvider::new(
+                    provider.chat.clone(),
+                    &provider.name,
+                    config.xai.breaker_failures,
+                    cooldown,
+                ));
+            }
+            if let Some(redactor) = &redactor {
+                provider.chat = Arc::new(AuditedChatProvider::new(
+                    provider.chat.clone(),
+                    &provider.name,
+                    pool.clone(),
+                    redactor.clone(),
+                ));
+            }
+            provider.chat = Arc::new(MeteredChatProvider::new(
+                provider.chat.clone(),
+                &provider.name,
+                pool.clone(),
+                live.clone(),
+            ));
+        }
+
+        let mut chat: Arc<dyn ChatProvider> = match providers.len() {
+            1 => providers.remove(0).chat,
+            _ => Arc::new(FailoverProvider::new(providers)),
+        };
+        let health = Arc::new(ModelHealth::new(config.routing.clone()));
+        chat = Arc::new(HealthTrackedChatProvider::new(chat, health.clone()));
+        let limiter = Arc::new(PriorityLimiter::new(live.clone()));
+        chat = Arc::new(PrioritizedChatProvider::new(chat, limiter.clone()));
+        if config.ai_cache_ttl_secs > 0 {
+            let ttl = Duration::from_secs(config.ai_cache_ttl_secs);
+            chat = Arc::new(CachingChatProvider::new(chat, pool.clone(), ttl));
+        }
+        Ok(Self {
+            store: Arc::new(PostgresSchematicStore((*pool).clone())),
+            pool,
+            schematics: Arc::new(SchematicCache::new(config.schematic_cache_mb * 1024 * 1024)),
+            responses: Arc::new(ResponseCache::new(&config.response_cache)?),
+            streams: Arc::new(resumable_streams(&config)),
+            health,
+            limiter,
+            config: live,
+            chat,
+            prompts: Arc::new(ArcSwap::from_pointee(prompts)),
+        })
+    }
+
+    /// State with a caller-supplied LLM, e.g. a fake in handler tests
+    pub fn with_chat_provider(
+        pool: PgPool,
+        config: Config,
+        prompts: PromptLibrary,
+        chat: Arc<dyn ChatProvider>,
+    ) -> Self {
+        let health = Arc::new(ModelHealth::new(config.routing.clone()));
+        let live = Arc::new(ArcSwap::from_pointee(config));
+        let config = live.load_full();
+        let limiter = Arc::new(PriorityLimiter::new(live.clone()));
+        let chat = Arc::new(HealthTrackedChatProvider::new(chat, health.clone()));
+        Self {
+            store: Arc::new(PostgresSchematicStore(pool.clone())),
+            pool: Arc::new(pool),
+            schematics: Arc::new(SchematicCache::new(config.schematic_cache_mb * 1024 * 1024)),
+            responses: Arc::new(ResponseCache::new(&config.response_cache).expect("Invalid response cache settings")),
+            streams: Arc::new(resumable_streams(&config)),
+            chat: Arc::new(PrioritizedChatProvider::new(chat, limiter.clone())),
+            health,
+            limiter,
+            config: live,
+            prompts: Arc::new(ArcSwap::from_pointee(prompts)),
+        }
+    }
+
+    /// The config in effect now
+    pub fn config(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+
+    /// The prompt templates in effect now
+    pub fn prompts(&self) -> Arc<PromptLibrary> {
+        self.prompts.load_full()
+    }
+
+    /// Keep schematics in `store` rather than the Postgres pool's
+    pub fn with_store(mut self, store: Arc<dyn SchematicStore>) -> Self {
+        self.store = store;
+        self
+    }
+}
+
+fn resumable_streams(config: &Config) -> ResumableStreams {
+    ResumableStreams::new(config.stream_buffer_events, Duration::from_secs(config.stream_resume_secs))
+}
+
+impl FromRef<AppState> for Arc<PgPool> {
+    fn from_ref(state: &AppState) -> Self {
+        state.pool.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn SchematicStore> {
+    fn from_ref(state: &AppState) -> Self {
+        state.store.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Config> {
+    fn from_ref(state: &AppState) -> Self {
+        state.config()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn ChatProvider> {
+    fn from_ref(state: &AppState) -> Self {
+        state.chat.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<PromptLibrary> {
+    fn from_ref(state: &AppState) -> Self {
+        state.prompts()
+    }
+}
+
+impl FromRef<AppState> for Arc<SchematicCache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.schematics.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ResponseCache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.responses.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ResumableStreams> {
+    fn from_ref(state: &AppState) -> Self {
+        state.streams.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ModelHealth> {
+    fn from_ref(state: &AppState) -> Self {
+        state.health.clone()
+    }
+}
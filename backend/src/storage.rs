@@ -0,0 +1,87 @@
+//! Where the server keeps its data
+//!
+//! Normally Postgres holds everything. A `sqlite://` DATABASE_URL keeps just
+//! the schematics of each commit in that file, through
+//! [`kicad_db::SchematicStore`], so a demo can run without a database
+//! server; everything else the server stores needs Postgres, so background
+//! jobs don't start, routes other than [`SQLITE_ROUTES`] answer 503, and so
+//! do requests with a stored API key or an org's JWT (see `auth::resolve`).
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use kicad_db::{PgPool, PostgresSchematicStore, SchematicStore};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::error::AppError;
+
+/// Routes served from the schematic store alone; the only ones under
+/// /api and /status a SQLite-backed server answers
+pub const SQLITE_ROUTES: &[&str] = &["/api/distill", "/api/repos/:repo/commits"];
+
+/// Connect to the database `config` names and apply its migrations
+///
+/// For SQLite the pool is one that never connects, standing in for Postgres
+/// in the state of the routes [`require_postgres`] turns away.
+pub async fn connect(config: &Config) -> Result<(PgPool, Arc<dyn SchematicStore>)> {
+    let settings = config.pool.settings();
+    if !config.uses_sqlite() {
+        let pool = kicad_db::connect_pool_with(&config.database_url, &settings)
+            .await
+            .context("Failed to create database pool")?;
+        kicad_db::apply_migrations(&pool)
+            .await
+            .context("Failed to apply database migrations")?;
+        let store = Arc::new(PostgresSchematicStore(pool.clone()));
+        return Ok((pool, store));
+    }
+
+    let store = kicad_db::connect_store(&config.database_url, &settings)
+        .await
+        .context("Failed to open the SQLite database")?;
+    store
+        .migrate()
+        .await
+        .context("Failed to apply SQLite migrations")?;
+    Ok((no_postgres(), store))
+}
+
+/// A pool whose connections fail at once, without reaching any server
+fn no_postgres() -> PgPool {
+    let options = PgConnectOptions::new().socket("/nonexistent/postgres-is-not-configured");
+    PgPoolOptions::new()
+        .max_connections(1)
+        .acquire_timeout(Duration::from_secs(1))
+        .connect_lazy_with(options)
+}
+
+/// Route middleware answering 503 for routes that need Postgres when
+/// `sqlite` is set
+///
+/// Use as `route_layer(middleware::from_fn_with_state(config.uses_sqlite(), storage::require_postgres))`.
+pub async fn require_postgres(State(sqlite): State<bool>, request: Request, next: Next) -> Response {
+    let route = request.extensions().get::<MatchedPath>().map(|path| path.as_str().trim_end_matches('/'));
+    let needs_postgres = route.is_some_and(|route| {
+        (route.starts_with("/api/") || route.starts_with("/status")) && !SQLITE_ROUTES.contains(&route)
+    });
+    if sqlite && needs_postgres {
+        return postgres_required("this route").into_response();
+    }
+    next.run(request).await
+}
+
+/// The 503 for something a SQLite-backed server can't do, e.g. "this route"
+pub fn postgres_required(what: &str) -> AppError {
+    AppError::new(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "postgres_required",
+        format!("This server stores schematics in SQLite; {} needs Postgres (DATABASE_URL)", what),
+    )
+}
@@ -1,6 +1,9 @@
 use chrono::{DateTime, Utc};
+use kicad_db::{detail_levels::DetailLevel, messages::ReasoningEffort, SortOrder};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
 
 // ============================================================================
 // DigiKey API Types
@@ -70,6 +73,14 @@ pub struct DigiKeySearchResponse {
     pub total_count: usize,
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DigiKeyStatusResponse {
+    /// Whether DigiKey credentials are set
+    pub configured: bool,
+    /// What to do if they aren't
+    pub message: String,
+}
+
 // ============================================================================
 // Repo Endpoint Types
 // ============================================================================
@@ -88,7 +99,12 @@ pub struct CommitInfo {
     pub commit_date: Option<DateTime<Utc>>,
     /// Commit message summary
     pub message: Option<String>,
-    /// Whether this commit modified .kicad_sch files
+    /// Author name
+    pub author: Option<String>,
+    pub author_email: Option<String>,
+    /// Tags pointing at this commit
+    pub tags: Vec<String>,
+    /// Whether this commit modified .kicad_sch or .kicad_pcb files
     pub has_schematic_changes: bool,
 }
 
@@ -104,11 +120,11 @@ pub struct RepoCommitsResponse {
 pub struct CommitFilesRequest {
     /// GitHub repository in "owner/repo" format
     pub repo: String,
-    /// Full commit hash
+    /// Commit SHA (full or abbreviated), branch or tag; responses carry the full hash
     pub commit: String,
 }
 
-#[derive(Debug, Serialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct SchematicFile {
     /// File path relative to repository root
     pub path: String,
@@ -130,7 +146,7 @@ pub struct CommitFilesResponse {
 pub struct CommitInfoRequest {
     /// GitHub repository in "owner/repo" format
     pub repo: String,
-    /// Full commit hash
+    /// Commit SHA (full or abbreviated), branch or tag; responses carry the full hash
     pub commit: String,
 }
 
@@ -148,7 +164,9 @@ pub struct CommitInfoResponse {
     pub blurb: Option<String>,
     /// Detailed AI-generated description
     pub description: Option<String>,
-    /// List of changed .kicad_sch file paths
+    /// Summary visibility ("public" or "private"); private summaries are omitted without an API key
+    pub visibility: String,
+    /// List of changed .kicad_sch and .kicad_pcb file paths
     pub changed_files: Vec<String>,
 }
 
@@ -164,6 +182,43 @@ pub struct HookUpdateResponse {
     pub processed: usize,
     /// List of errors encountered during processing
     pub errors: Vec<String>,
+    /// Stage timings for each processed commit
+    pub timings: Vec<CommitTimeline>,
+    /// The delivery was received before (a provider redelivery), so nothing was processed
+    pub duplicate: bool,
+    /// The branch's high-water mark: only commits after it were looked at.
+    /// None when the whole history was scanned
+    pub since_commit: Option<String>,
+}
+
+// ============================================================================
+// Latency Tracking Types
+// ============================================================================
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AnalysisTimeline {
+    /// Time spent cloning/fetching the repository and reading files
+    pub clone_ms: Option<u64>,
+    /// Time spent parsing/distilling schematics
+    pub parse_ms: Option<u64>,
+    /// Time spent assembling the prompt
+    pub prompt_build_ms: Option<u64>,
+    /// Time spent waiting on the LLM
+    pub llm_ms: Option<u64>,
+    /// Time spent writing to the database
+    pub store_ms: Option<u64>,
+    /// Wall-clock time for the whole analysis
+    pub total_ms: u64,
+    /// Stages that exceeded their configured budget
+    pub over_budget: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CommitTimeline {
+    /// Full commit hash
+    pub commit: String,
+    /// Stage timings for the analysis of this commit
+    pub timeline: AnalysisTimeline,
 }
 
 // ============================================================================
@@ -174,47 +229,240 @@ pub struct HookUpdateResponse {
 pub struct GrokCommitSummaryRequest {
     /// GitHub repository in "owner/repo" format
     pub repo: String,
-    /// Full commit hash
+    /// Commit SHA (full or abbreviated), branch or tag; responses carry the full hash
     pub commit: String,
+    /// Persona preset (terse-reviewer, teaching-assistant, formal-report); falls back to the repo default
+    #[serde(default)]
+    pub persona: Option<String>,
+    /// Response length: brief (timeline blurb), standard, or deep (design review). Defaults to standard
+    #[serde(default)]
+    #[schema(value_type = Option<String>, example = "brief")]
+    pub detail_level: Option<DetailLevel>,
+    /// Model to use instead of the configured default; must be on the allowlist (see /api/grok/models)
+    #[serde(default)]
+    pub model: Option<String>,
 }
 
-#[derive(Debug, Serialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct GrokCommitSummaryResponse {
     /// GitHub repository in "owner/repo" format
     pub repo: String,
     /// Full commit hash
     pub commit: String,
+    /// Model that generated the summary
+    pub model: String,
+    /// Detail level the summary was generated at
+    #[schema(value_type = String)]
+    pub detail_level: DetailLevel,
+    /// Prompt template the summary was generated from
+    #[schema(example = "commit_summary@v1")]
+    pub prompt_version: String,
     /// Short AI-generated summary
     pub summary: String,
     /// Detailed AI-generated analysis
     pub details: String,
+    /// ID to rate the summary with at POST /api/grok/feedback; null if it
+    /// couldn't be recorded
+    pub summary_id: Option<i64>,
+}
+
+/// How urgent some AI work is, most urgent first; less urgent work leaves
+/// part of the shared AI rate limit to more urgent work (see
+/// `services::priority`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkClass {
+    /// A user waiting on the answer, e.g. summarizing one commit
+    Interactive,
+    /// Summaries of commits a push or scheduled re-sync brought in
+    Webhook,
+    /// Summaries of a repo's history, asked for or after onboarding
+    Backfill,
+    /// Embeddings, part classification and drift reports
+    Enrichment,
+}
+
+impl WorkClass {
+    pub const ALL: [WorkClass; 4] = [
+        WorkClass::Interactive,
+        WorkClass::Webhook,
+        WorkClass::Backfill,
+        WorkClass::Enrichment,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WorkClass::Interactive => "interactive",
+            WorkClass::Webhook => "webhook",
+            WorkClass::Backfill => "backfill",
+            WorkClass::Enrichment => "enrichment",
+        }
+    }
+
+    /// Position in [`WorkClass::ALL`]
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Where a summary backfill stands
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BackfillStatus {
+    Running,
+    /// Every commit was tried
+    Completed,
+    /// Gave up early; `stopped_reason` says why
+    Stopped,
+    /// Stopped through /api/admin/jobs/{id}/cancel
+    Cancelled,
+}
+
+impl BackfillStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BackfillStatus::Running => "running",
+            BackfillStatus::Completed => "completed",
+            BackfillStatus::Stopped => "stopped",
+            BackfillStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// A commit the backfill failed to summarize
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BackfillError {
+    pub commit: String,
+    pub error: String,
+}
+
+/// Progress of a summary backfill, sent as an SSE `progress` event
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BackfillProgress {
+    /// ID of the job under /api/admin/jobs
+    pub job_id: u64,
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    pub status: BackfillStatus,
+    /// Class of its AI calls: the most urgent of those that started or joined it
+    pub class: WorkClass,
+    /// Schematic commits that had no summary when the backfill started
+    pub total: usize,
+    /// Commits summarized so far
+    pub done: usize,
+    /// Commits that failed
+    pub failed: usize,
+    /// Commits not tried yet
+    pub remaining: usize,
+    /// Commit being summarized
+    pub current: Option<String>,
+    pub errors: Vec<BackfillError>,
+    /// Why the backfill stopped early (AI unavailable, budget spent, rate
+    /// limited, or the commits couldn't be listed)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped_reason: Option<String>,
+    /// Cancellation was asked for; the job stops after its current commit
+    pub cancel_requested: bool,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Where one commit of a backfill stands
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobCommitStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+/// A commit a backfill set out to summarize
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct JobCommit {
+    pub commit: String,
+    pub status: JobCommitStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What the user selected in the viewer
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Selection {
+    /// Components by reference, e.g. ["R1", "U3"]
+    Components { ids: Vec<String> },
+    /// Nets by name, e.g. ["/VIN", "GND"]; a wire selects the net it is part of
+    Nets { names: Vec<String> },
+    /// A hierarchical sheet and everything on it, by path, e.g. "/Power/"
+    Sheet { path: String },
+    /// The components inside a rectangle, in schematic millimetres
+    Area {
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+        /// Path of the sheet the rectangle was drawn on. Defaults to the root sheet
+        #[serde(default)]
+        sheet: Option<String>,
+    },
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct GrokSelectionSummaryRequest {
     /// GitHub repository in "owner/repo" format
     pub repo: String,
-    /// Full commit hash
+    /// Commit SHA (full or abbreviated), branch or tag; responses carry the full hash
     pub commit: String,
-    /// List of component IDs to analyze
+    /// List of component IDs to analyze; shorthand for a components selection
+    #[serde(default)]
     pub component_ids: Vec<String>,
+    /// Components, nets, a sheet or an area to analyze, instead of component_ids
+    #[serde(default)]
+    pub selection: Option<Selection>,
+    /// Response length: brief (timeline blurb), standard, or deep (design review). Defaults to standard
+    #[serde(default)]
+    #[schema(value_type = Option<String>, example = "brief")]
+    pub detail_level: Option<DetailLevel>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct GrokSelectionStreamRequest {
     /// GitHub repository in "owner/repo" format
     pub repo: String,
-    /// Full commit hash
+    /// Commit SHA (full or abbreviated), branch or tag; responses carry the full hash
     pub commit: String,
-    /// List of component IDs (references) to analyze
+    /// List of component IDs (references) to analyze; shorthand for a components selection
+    #[serde(default)]
     pub component_ids: Vec<String>,
+    /// Components, nets, a sheet or an area to analyze, instead of component_ids;
+    /// with neither, the question is about the whole schematic
+    #[serde(default)]
+    pub selection: Option<Selection>,
     /// User's query about the components
     pub query: String,
     /// Pre-distilled schematic data (optional - will fetch if not provided)
     pub distilled: Option<serde_json::Value>,
-    /// Enable thinking/reasoning mode (shows chain-of-thought)
+    /// Enable thinking/reasoning mode; the model's thinking streams as `reasoning` events
     #[serde(default)]
     pub thinking_mode: bool,
+    /// How hard a reasoning model thinks ("low" or "high"); implies thinking mode. Defaults to low
+    #[serde(default)]
+    #[schema(value_type = Option<String>, example = "high")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Persona preset (terse-reviewer, teaching-assistant, formal-report); falls back to the repo default
+    #[serde(default)]
+    pub persona: Option<String>,
+    /// Model to use instead of the configured default; must be on the allowlist (see /api/grok/models)
+    #[serde(default)]
+    pub model: Option<String>,
+    /// PNG or JPEG of the selected region as rendered in the viewer, base64 or a data URL.
+    /// Shown to the model alongside the text, so the model must support image input
+    #[serde(default)]
+    pub snapshot: Option<String>,
+    /// Without a snapshot, show the model the stored schematic image for the commit (if any)
+    #[serde(default)]
+    pub attach_schematic_image: bool,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -223,30 +471,541 @@ pub struct GrokSelectionSummaryResponse {
     pub repo: String,
     /// Full commit hash
     pub commit: String,
-    /// List of component IDs that were analyzed
+    /// List of component IDs that were analyzed: those selected, or those
+    /// on the selected nets, sheet or area
     pub component_ids: Vec<String>,
+    /// Detail level the summary was generated at
+    #[schema(value_type = String)]
+    pub detail_level: DetailLevel,
     /// Short AI-generated summary
     pub summary: String,
     /// Detailed AI-generated analysis
     pub details: String,
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrokCompareSummaryRequest {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Older commit: SHA (full or abbreviated), branch or tag
+    pub base: String,
+    /// Newer commit: SHA (full or abbreviated), branch or tag
+    pub head: String,
+    /// Response length: brief, standard, or deep. Defaults to standard
+    #[serde(default)]
+    #[schema(value_type = Option<String>, example = "deep")]
+    pub detail_level: Option<DetailLevel>,
+    /// Generate the comparison again instead of returning the stored one
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrokCompareSummaryResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Full hash of the older commit
+    pub base: String,
+    /// Full hash of the newer commit
+    pub head: String,
+    /// Commits reachable from head but not from base
+    pub commit_count: i32,
+    /// Model that wrote the comparison
+    pub model: String,
+    /// Detail level the comparison was generated at
+    #[schema(value_type = String)]
+    pub detail_level: DetailLevel,
+    /// Prompt template the comparison was generated from
+    #[schema(example = "compare_summary@v1")]
+    pub prompt_version: String,
+    /// AI-written narrative of how the design evolved
+    pub summary: String,
+    /// The component and net changes between the two commits, one per line
+    pub changes: String,
+    /// Whether the comparison was stored from an earlier request
+    pub cached: bool,
+    pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrokReleaseNotesRequest {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Tag of the previous release
+    #[schema(example = "v1.0")]
+    pub from_tag: String,
+    /// Tag of the release the notes are for
+    #[schema(example = "v1.1")]
+    pub to_tag: String,
+}
+
+/// Section of the release notes a change is filed under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseCategory {
+    Power,
+    Mcu,
+    Connectors,
+    Bugfixes,
+    Other,
+}
+
+/// One commit of a release, as the notes were written from
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReleaseNoteChange {
+    /// Full commit hash
+    pub commit: String,
+    /// Commit message
+    pub message: Option<String>,
+    /// Short AI-generated blurb describing the design change
+    pub blurb: String,
+    pub category: ReleaseCategory,
+    /// Whether the blurb was generated for these notes, the commit having none stored
+    pub generated: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrokReleaseNotesResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    pub from_tag: String,
+    pub to_tag: String,
+    /// Full hash of the commit from_tag points at
+    pub from_commit: String,
+    /// Full hash of the commit to_tag points at
+    pub to_commit: String,
+    /// Model that wrote the notes; empty when the range has no hardware changes
+    pub model: String,
+    /// Prompt template the notes were generated from; empty when the range has no hardware changes
+    #[schema(example = "release_notes@v1")]
+    pub prompt_version: String,
+    /// Release notes in Markdown, by section
+    pub markdown: String,
+    /// The commits that changed schematics or layouts, oldest first
+    pub changes: Vec<ReleaseNoteChange>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrokReviewChecklistRequest {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Commit SHA (full or abbreviated), branch or tag
+    pub commit: String,
+    /// Generate the checklist again, clearing what was ticked off, instead of
+    /// returning the stored one
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+/// A thing to verify when reviewing a commit
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReviewChecklistItem {
+    /// Identifies the item when ticking it off
+    pub id: i64,
+    /// power, mcu, connectors, signal, mechanical, erc or other
+    #[schema(example = "power")]
+    pub category: String,
+    #[schema(example = "Verify the U3 feedback divider R12/R13 sets 3.3 V")]
+    pub text: String,
+    /// References of the changed components the item concerns
+    pub components: Vec<String>,
+    pub done: bool,
+    /// When the item was ticked off
+    pub done_at: Option<DateTime<Utc>>,
+}
+
+impl From<kicad_db::ChecklistItem> for ReviewChecklistItem {
+    fn from(item: kicad_db::ChecklistItem) -> Self {
+        Self {
+            id: item.id,
+            category: item.category,
+            text: item.text,
+            components: item.components,
+            done: item.done,
+            done_at: item.done_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReviewChecklistResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Full commit hash
+    pub commit: String,
+    /// Model that wrote the checklist
+    pub model: String,
+    /// Prompt template the checklist was generated from
+    #[schema(example = "review_checklist@v1")]
+    pub prompt_version: String,
+    /// Items in the order to review them
+    pub items: Vec<ReviewChecklistItem>,
+    /// Whether the checklist was stored from an earlier request
+    pub cached: bool,
+    pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrokSuggestFixRequest {
+    /// The change to make, in plain words
+    #[schema(example = "Swap pins 1 and 2 of J3, they are reversed")]
+    pub change: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SuggestFixResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Full hash of the commit the edits were made to
+    pub commit: String,
+    /// Pull request proposing the edited files
+    pub pull_request_url: String,
+    /// Branch the pull request is opened from
+    #[schema(example = "grokicad/fix-1a2b3c4-5d6e7f80")]
+    pub branch: String,
+    /// The edits made, one per line as the model wrote them
+    pub edits: Vec<String>,
+    /// Paths of the schematic files the pull request changes
+    pub files: Vec<String>,
+    /// Model that suggested the edits
+    pub model: String,
+    /// Prompt template the edits were suggested from
+    #[schema(example = "suggest_fix@v1")]
+    pub prompt_version: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateChecklistItemRequest {
+    /// Whether the item has been verified
+    pub done: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrokAskRequest {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Commit SHA (full or abbreviated), branch or tag
+    pub commit: String,
+    #[schema(example = "What does U3 do, and which nets does it drive?")]
+    pub question: String,
+    /// Number of retrieved sources per kind (commits, components, nets); 0 turns retrieval off. Defaults to 5
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    /// Answer again instead of returning the stored answer
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrokAskResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Full commit hash
+    pub commit: String,
+    /// The question as first asked; the same words in another case or
+    /// spacing share its answer
+    pub question: String,
+    /// Plain text, citing `sources` by tag, e.g. [S1]
+    pub answer: String,
+    /// References of the commit's components the answer mentions
+    pub components: Vec<String>,
+    /// Names of the commit's nets the answer mentions
+    pub nets: Vec<String>,
+    /// Context retrieved for the question, which the answer cites by `id`
+    pub sources: Vec<ChatSource>,
+    /// Model that wrote the answer
+    pub model: String,
+    /// Prompt template the answer was generated from
+    #[schema(example = "ask@v1")]
+    pub prompt_version: String,
+    /// Whether the answer was stored from an earlier request
+    pub cached: bool,
+    pub generated_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct GrokRepoSummaryRequest {
     /// GitHub repository in "owner/repo" format
     pub repo: String,
+    /// Persona preset (terse-reviewer, teaching-assistant, formal-report); falls back to the repo default
+    #[serde(default)]
+    pub persona: Option<String>,
+    /// Response length: brief (timeline blurb), standard, or deep (design review). Defaults to standard
+    #[serde(default)]
+    #[schema(value_type = Option<String>, example = "brief")]
+    pub detail_level: Option<DetailLevel>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct GrokRepoSummaryResponse {
     /// GitHub repository in "owner/repo" format
     pub repo: String,
+    /// Detail level the summary was generated at
+    #[schema(value_type = String)]
+    pub detail_level: DetailLevel,
     /// Short AI-generated summary
     pub summary: String,
     /// Detailed AI-generated analysis
     pub details: String,
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct GrokChatStreamQuery {
+    /// Repository used to look up a default persona (optional)
+    pub repo: Option<String>,
+    /// Persona preset; falls back to the repo default
+    pub persona: Option<String>,
+    /// Model to use instead of the configured default; must be on the allowlist
+    pub model: Option<String>,
+}
+
+/// Who said a chat turn; system prompts are built by the server
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    User,
+    Assistant,
+}
+
+impl ChatRole {
+    /// As stored with a chat session's turns
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ChatTurn {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrokChatRequest {
+    /// Repository the conversation is about ("owner/repo"); enables retrieval and the repo's default persona
+    #[serde(default)]
+    pub repo: Option<String>,
+    /// Commit whose components and nets are searched; defaults to the newest distilled commit
+    #[serde(default)]
+    pub commit: Option<String>,
+    /// The conversation so far, oldest first, ending with the user's question;
+    /// with a `session_id`, only the turns since the last request
+    pub messages: Vec<ChatTurn>,
+    /// Chat session (POST /api/grok/chat/sessions) whose stored history goes
+    /// before `messages`; the question and answer are added to it. Must have
+    /// the same `repo`
+    #[serde(default)]
+    #[schema(value_type = Option<String>, example = "0b5cf6a8-3f2e-4c1d-9a77-2d6f0e4b8c11")]
+    pub session_id: Option<Uuid>,
+    /// Number of retrieved sources per kind (commits, components, nets); 0 turns retrieval off. Defaults to 5
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    /// Persona preset; falls back to the repo default
+    #[serde(default)]
+    pub persona: Option<String>,
+    /// Model to use instead of the configured default; must be on the allowlist
+    #[serde(default)]
+    pub model: Option<String>,
+    /// For reasoning models, how hard to think ("low" or "high"); the thinking streams as `reasoning` events
+    #[serde(default)]
+    #[schema(value_type = Option<String>, example = "low")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+/// What a retrieved source is
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatSourceKind {
+    Commit,
+    Component,
+    Net,
+}
+
+/// Context retrieved for a chat answer, which the answer cites by `id`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ChatSource {
+    /// Tag the model cites, e.g. "S1"
+    pub id: String,
+    pub kind: ChatSourceKind,
+    /// One-line heading, e.g. "Commit 1a2b3c4d: Add input protection"
+    pub title: String,
+    /// The text given to the model
+    pub text: String,
+    /// Repository clone URL
+    pub repo_url: String,
+    /// Commit the source comes from
+    pub commit_hash: String,
+    /// Component reference, for components
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    /// Net name, for nets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub net: Option<String>,
+    /// Similarity to the question, when found by semantic search
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+}
+
+/// Payload of the `usage` event sent when a streamed answer ends
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StreamUsageEvent {
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub total_tokens: Option<u32>,
+}
+
+impl From<kicad_db::xai_client::Usage> for StreamUsageEvent {
+    fn from(usage: kicad_db::xai_client::Usage) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct CreateChatSessionRequest {
+    /// Repository the conversation is about ("owner/repo"); chat requests in
+    /// the session must name the same one
+    #[serde(default)]
+    pub repo: Option<String>,
+}
+
+/// Something a chat answer mentions that the viewer can link to
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatCitationItem {
+    /// component, net or commit
+    pub kind: String,
+    /// Component reference, net name or full commit hash
+    pub target: String,
+    /// Commit the component or net was found at (null for commits)
+    pub commit_hash: Option<String>,
+}
+
+impl From<kicad_db::ChatCitation> for ChatCitationItem {
+    fn from(citation: kicad_db::ChatCitation) -> Self {
+        Self {
+            kind: citation.kind.as_str().to_string(),
+            target: citation.target,
+            commit_hash: citation.commit_hash,
+        }
+    }
+}
+
+/// A rendering attached to a chat answer, e.g. a cited component's symbol
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatAttachmentItem {
+    /// One-line caption, e.g. "R1 Device:R"
+    pub title: String,
+    #[schema(example = "image/svg+xml")]
+    pub media_type: String,
+    /// The rendering itself, e.g. SVG markup
+    pub content: String,
+    /// `target` of the citation it illustrates
+    pub cites: Option<String>,
+}
+
+impl From<kicad_db::ChatAttachment> for ChatAttachmentItem {
+    fn from(attachment: kicad_db::ChatAttachment) -> Self {
+        Self {
+            title: attachment.title,
+            media_type: attachment.media_type,
+            content: attachment.content,
+            cites: attachment.cites,
+        }
+    }
+}
+
+/// A stored chat turn
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatSessionTurn {
+    /// 1 for the first turn
+    pub position: i32,
+    pub role: ChatRole,
+    pub content: String,
+    /// Tokens it takes up in a request
+    pub tokens: i32,
+    /// What an assistant turn cites; empty for user turns
+    pub citations: Vec<ChatCitationItem>,
+    /// Renderings attached to an assistant turn
+    pub attachments: Vec<ChatAttachmentItem>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A chat session: every turn as it was said, and the running summary that
+/// replaces the older ones in requests once the conversation nears the
+/// model's context window
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatSessionResponse {
+    #[schema(value_type = String)]
+    pub id: Uuid,
+    pub repo: Option<String>,
+    /// What the turns up to `summarized_through` said
+    pub summary: Option<String>,
+    /// Position of the last turn the summary covers; 0 before any is folded
+    pub summarized_through: i32,
+    /// Tokens the history adds to the next request: the summary and the turns after it
+    pub tokens: usize,
+    /// Every turn, oldest first, including the summarized ones
+    pub turns: Vec<ChatSessionTurn>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Payload of the `sources` event sent before a chat answer streams
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatSourcesEvent {
+    pub sources: Vec<ChatSource>,
+}
+
+/// Payload of the `citations` event sent once a chat answer has streamed
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatCitationsEvent {
+    pub citations: Vec<ChatCitationItem>,
+}
+
+/// Payload of the `attachments` event sent after the `citations` event
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatAttachmentsEvent {
+    pub attachments: Vec<ChatAttachmentItem>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PersonaInfo {
+    /// Preset name, used as the `persona` parameter
+    pub name: String,
+    /// What the preset is for
+    pub description: String,
+    /// Sampling temperature sent to the model
+    pub temperature: f32,
+    /// Maximum tokens in the response
+    pub max_tokens: u32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PersonaListResponse {
+    /// Available persona presets
+    pub personas: Vec<PersonaInfo>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ModelListResponse {
+    /// Default for commit summaries
+    pub summary: String,
+    /// Default for chat
+    pub chat: String,
+    /// Default for selection streaming
+    pub selection: String,
+    /// Default for replacement part search
+    pub replacement: String,
+    /// Every model a request may pass as `model`
+    pub allowed: Vec<String>,
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct GrokObsoleteReplacementRequest {
     /// Manufacturer part number of the obsolete part
@@ -263,6 +1022,9 @@ pub struct GrokObsoleteReplacementRequest {
     pub product_url: Option<String>,
     /// Key parameters/specifications
     pub parameters: Vec<DigiKeyParameter>,
+    /// Model to use instead of the configured default; must be on the allowlist and support tools
+    #[serde(default)]
+    pub model: Option<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -285,7 +1047,7 @@ pub struct GrokObsoleteReplacementResponse {
 pub struct DistillRequest {
     /// GitHub repository in "owner/repo" format
     pub repo: String,
-    /// Full commit hash
+    /// Commit SHA (full or abbreviated), branch or tag; responses carry the full hash
     pub commit: String,
 }
 
@@ -309,7 +1071,7 @@ pub struct DistillResponse {
 pub struct RepoInitRequest {
     /// GitHub repository in "owner/repo" format
     pub repo: String,
-    /// Full commit hash (optional - uses latest if not provided)
+    /// Commit SHA (full or abbreviated), branch or tag (optional - uses latest if not provided)
     pub commit: Option<String>,
 }
 
@@ -329,55 +1091,2196 @@ pub struct RepoInitResponse {
     pub schematic_files: Vec<String>,
     /// Distilled schematic data
     pub distilled: serde_json::Value,
+    /// Stage timings for this initialization
+    pub timeline: AnalysisTimeline,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct RepoClearCacheRequest {
     /// GitHub repository in "owner/repo" format
     pub repo: String,
-    /// Full commit hash (optional - clears all commits if not provided)
+    /// Commit SHA, branch or tag (optional - clears all commits if not provided)
     pub commit: Option<String>,
 }
 
-#[derive(Debug, Serialize, ToSchema)]
-pub struct RepoClearCacheResponse {
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RepoPersonaRequest {
     /// GitHub repository in "owner/repo" format
     pub repo: String,
-    /// Whether the cache was cleared
-    pub cleared: bool,
-    /// Message describing what was cleared
-    pub message: String,
+    /// Default persona for the repo (omit or null to clear)
+    pub persona: Option<String>,
 }
 
-// ============================================================================
-// Error Types
-// ============================================================================
-
 #[derive(Debug, Serialize, ToSchema)]
-pub struct ApiError {
-    /// Error code
-    pub error: String,
-    /// Human-readable error message
-    pub message: String,
+pub struct RepoPersonaResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Default persona now configured for the repo
+    pub persona: Option<String>,
 }
 
-impl ApiError {
-    pub fn new(error: impl Into<String>, message: impl Into<String>) -> Self {
-        Self {
-            error: error.into(),
-            message: message.into(),
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RepoVisibilityRequest {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Set the visibility of one stored commit instead of the whole repo
+    pub commit: Option<String>,
+    /// "public" or "private"; omit or null to reset (public for a repo, inherit for a commit)
+    pub visibility: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RepoVisibilityResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Commit the setting applies to (null for the whole repo)
+    pub commit: Option<String>,
+    /// Effective visibility after the change
+    pub visibility: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RepoChangelogSettingsRequest {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Whether to open a changelog pull request after new commits are processed
+    pub enabled: bool,
+    /// File path within the repository (default "HARDWARE_CHANGELOG.md")
+    pub path: Option<String>,
+    /// Bot branch the pull request is opened from (default "grokicad/changelog")
+    pub branch: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RepoChangelogSettingsResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    pub enabled: bool,
+    /// File path within the repository
+    pub path: String,
+    /// Bot branch the pull request is opened from
+    pub branch: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RepoChangelogRequest {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Also propose the changelog to the repository as a pull request (default false)
+    pub publish: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RepoChangelogResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Generated changelog markdown
+    pub markdown: String,
+    /// Pull request proposing the changelog (when published and the file changed)
+    pub pull_request_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RepoClearCacheResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Whether the cache was cleared
+    pub cleared: bool,
+    /// Message describing what was cleared
+    pub message: String,
+}
+
+// ============================================================================
+// Stored Schematic Listing Types
+// ============================================================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct StoredCommitsQuery {
+    /// Maximum number of rows to return (default 50, max 200)
+    pub limit: Option<i64>,
+    /// Number of rows to skip (default 0)
+    pub offset: Option<i64>,
+    /// Sort by commit date: "desc" (newest first, default) or "asc"
+    #[param(value_type = Option<String>)]
+    pub order: Option<SortOrder>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StoredCommit {
+    /// Full commit hash
+    pub commit_hash: String,
+    /// Commit date (if recorded)
+    pub commit_date: Option<DateTime<Utc>>,
+    /// Commit message (if recorded)
+    pub message: Option<String>,
+    /// Short AI-generated blurb (if generated and visible to the caller)
+    pub blurb: Option<String>,
+    /// Summary visibility ("public" or "private")
+    pub visibility: String,
+    /// Processing status: pending, distilled or summarized
+    pub status: String,
+    /// Stage timings keyed by analysis kind (overview, init, commit_summary)
+    pub timings: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StoredCommitsResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Total number of stored commits for the repo
+    pub total: i64,
+    /// Limit applied to this page
+    pub limit: i64,
+    /// Offset applied to this page
+    pub offset: i64,
+    /// Stored commits in the requested order
+    pub commits: Vec<StoredCommit>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ComponentHistoryItem {
+    /// Full commit hash
+    pub commit_hash: String,
+    /// Commit date (if recorded)
+    pub commit_date: Option<DateTime<Utc>>,
+    /// Commit message (if recorded)
+    pub message: Option<String>,
+    /// Component value at this commit (e.g. "10k")
+    pub value: Option<String>,
+    /// Footprint at this commit
+    pub footprint: Option<String>,
+    /// Manufacturer part number at this commit
+    pub mpn: Option<String>,
+    /// Sheet path at this commit
+    pub sheet: Option<String>,
+    /// Fields that differ from the previous entry (empty for the first)
+    pub changed: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ComponentHistoryResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Reference designator (e.g. "U1")
+    pub reference: String,
+    /// The component at each stored commit, oldest first
+    pub history: Vec<ComponentHistoryItem>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErcFindingItem {
+    /// Rule that was violated (unconnected_pin, conflicting_outputs, undriven_power_pin, duplicate_reference)
+    pub rule: String,
+    /// "error" or "warning"
+    pub severity: String,
+    /// Reference designator involved (if any)
+    pub reference: Option<String>,
+    /// Pin number involved (if any)
+    pub pin: Option<String>,
+    /// Net involved (if any)
+    pub net: Option<String>,
+    /// Human-readable description
+    pub message: String,
+    /// Whether the violation is new relative to the parent commit (null if the parent wasn't analyzed)
+    pub introduced: Option<bool>,
+}
+
+/// A change in a commit's diff that may not have been intended
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DiffWarningItem {
+    /// mass_deletion, power_net_renamed or decoupling_removed
+    pub kind: String,
+    /// Human-readable description
+    pub message: String,
+    /// Components involved, e.g. a removed capacitor and the IC it decoupled
+    pub references: Vec<String>,
+    /// Nets involved, e.g. a renamed net's old and new name
+    pub nets: Vec<String>,
+}
+
+impl From<kicad_db::schematic::DiffWarning> for DiffWarningItem {
+    fn from(warning: kicad_db::schematic::DiffWarning) -> Self {
+        Self {
+            kind: warning.kind.as_str().to_string(),
+            message: warning.message,
+            references: warning.references,
+            nets: warning.nets,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErcResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Commit hash
+    pub commit: String,
+    /// Parent commit the findings were compared against (null for a root commit)
+    pub parent_commit: Option<String>,
+    /// Number of findings with severity "error"
+    pub error_count: usize,
+    /// Number of findings with severity "warning"
+    pub warning_count: usize,
+    /// All findings at this commit
+    pub findings: Vec<ErcFindingItem>,
+}
+
+// ============================================================================
+// Project Types (BOM and netlist over the sheet hierarchy)
+// ============================================================================
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BomLineItem {
+    /// Component value, e.g. "10k"
+    pub value: String,
+    /// Footprint (if set)
+    pub footprint: Option<String>,
+    /// Manufacturer part number (if set)
+    pub mpn: Option<String>,
+    /// Library symbol of the first part on the line
+    pub lib_id: String,
+    /// Number of parts on the line
+    pub quantity: usize,
+    /// Reference designators, in natural order
+    pub references: Vec<String>,
+    /// Sheets the parts are placed on, e.g. "/Power/"
+    pub sheets: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NetNodeItem {
+    /// Reference designator
+    pub reference: String,
+    /// Pin number
+    pub pin: String,
+    /// Pin name (if the symbol names it)
+    pub pin_name: Option<String>,
+    /// KiCad electrical type, e.g. "input", "power_in", "passive"
+    pub pin_type: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NetItem {
+    /// Net name; local labels are prefixed with their sheet path, e.g. "/Power/VIN"
+    pub name: String,
+    /// Pins on the net
+    pub nodes: Vec<NetNodeItem>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProjectSummary {
+    /// Project name (root schematic without extension)
+    pub name: String,
+    /// Root schematic path within the repository
+    pub root_file: String,
+    /// Sheet paths in the hierarchy, root first
+    pub sheets: Vec<String>,
+    /// Sheet files referenced but not present at this commit
+    pub missing_sheets: Vec<String>,
+    /// Symbol lib_ids with no definition in the sheets, repo libraries or standard libraries
+    pub unresolved_symbols: Vec<String>,
+    /// Format each sheet file was read from, by path: `kicad`, `kicad_legacy` or `eagle`
+    pub formats: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProjectBom {
+    pub project: ProjectSummary,
+    /// BOM lines, excluding DNP parts and parts excluded from the BOM
+    pub lines: Vec<BomLineItem>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BomResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Commit hash
+    pub commit: String,
+    /// One entry per KiCad project in the repository
+    pub projects: Vec<ProjectBom>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProjectNetlist {
+    pub project: ProjectSummary,
+    /// Nets spanning every sheet of the project
+    pub nets: Vec<NetItem>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NetlistResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Commit hash
+    pub commit: String,
+    /// One entry per KiCad project in the repository
+    pub projects: Vec<ProjectNetlist>,
+}
+
+/// How a net leaves or names itself on a sheet
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NetConnectorKind {
+    LocalLabel,
+    GlobalLabel,
+    HierarchicalLabel,
+    /// A pin on a sheet symbol, leading into the child sheet
+    SheetPin,
+    PowerSymbol,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NetConnectorItem {
+    pub kind: NetConnectorKind,
+    /// Label text, sheet pin name or power net
+    pub name: String,
+    /// Connection point in mm, [x, y]
+    pub at: [f64; 2],
+    /// Sheet paths the net continues on through this connector: the child
+    /// sheet of a sheet pin, the parent of a hierarchical label, other sheets
+    /// with the same global label or power symbol
+    pub continues_to: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NetPinItem {
+    /// Reference designator
+    pub reference: String,
+    /// Pin number
+    pub pin: String,
+    /// Pin tip in mm, [x, y]
+    pub at: [f64; 2],
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NetSheetGeometry {
+    /// Sheet path, e.g. "/Power/"
+    pub sheet_path: String,
+    /// Schematic file of the sheet, within the repository
+    pub file: String,
+    /// Wire segments in mm, [[x1, y1], [x2, y2]]
+    pub wires: Vec<[[f64; 2]; 2]>,
+    /// Junction dots in mm, [x, y]
+    pub junctions: Vec<[f64; 2]>,
+    /// Component pins on the net, in natural reference order
+    pub pins: Vec<NetPinItem>,
+    /// Labels, sheet pins and power symbols on the net
+    pub connectors: Vec<NetConnectorItem>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProjectNetGeometry {
+    pub project: ProjectSummary,
+    /// Sheets the net is drawn on, in hierarchy order
+    pub sheets: Vec<NetSheetGeometry>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NetGeometryResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Commit hash
+    pub commit: String,
+    /// Net name as in the netlist, e.g. "/Power/VIN" or "GND"
+    pub net: String,
+    /// The projects with a net of that name
+    pub projects: Vec<ProjectNetGeometry>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ComponentChangeItem {
+    /// Reference designator
+    pub reference: String,
+    /// value, footprint, lib_id, sheet or dnp
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PinChangeItem {
+    /// Reference designator
+    pub reference: String,
+    /// Pin number
+    pub pin: String,
+    /// Net the pin was on at the parent commit
+    pub old_net: String,
+    /// Net the pin is on now
+    pub new_net: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NetChangeItem {
+    /// Net name
+    pub name: String,
+    /// Pins that joined the net, as "R1.2"
+    pub added: Vec<String>,
+    /// Pins that left the net, as "R1.2"
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProjectChanges {
+    /// Root schematic path within the repository
+    pub root_file: String,
+    pub added_sheets: Vec<String>,
+    pub removed_sheets: Vec<String>,
+    /// Reference designators placed by this commit
+    pub added_components: Vec<String>,
+    /// Reference designators removed by this commit
+    pub removed_components: Vec<String>,
+    pub changed_components: Vec<ComponentChangeItem>,
+    /// Pins of components kept by this commit that moved to another net
+    pub changed_pins: Vec<PinChangeItem>,
+    pub added_nets: Vec<String>,
+    pub removed_nets: Vec<String>,
+    /// Nets present before and after whose pins changed
+    pub changed_nets: Vec<NetChangeItem>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CommitChangesResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Commit hash
+    pub commit: String,
+    /// Parent commit the changes are relative to (null for a root commit)
+    pub parent_commit: Option<String>,
+    pub commit_date: Option<DateTime<Utc>>,
+    pub author: Option<String>,
+    /// Commit message summary
+    pub message: Option<String>,
+    /// Schematic and layout files the commit touched
+    pub changed_files: Vec<String>,
+    /// Per project, what the commit changed; unchanged projects are omitted
+    pub projects: Vec<ProjectChanges>,
+    /// ERC violations absent at the parent commit (null if either commit hasn't been distilled)
+    pub new_erc_findings: Option<Vec<ErcFindingItem>>,
+    /// Changes that look unintended: mass deletions, renamed power nets,
+    /// decoupling capacitors removed from next to an IC
+    pub warnings: Vec<DiffWarningItem>,
+    /// Short AI-generated summary (null if not generated yet or hidden from the caller)
+    pub blurb: Option<String>,
+    /// Detailed AI-generated description (null if not generated yet or hidden from the caller)
+    pub description: Option<String>,
+}
+
+// ============================================================================
+// Board Types (.kicad_pcb layout)
+// ============================================================================
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FootprintItem {
+    /// Reference designator
+    pub reference: String,
+    /// Component value (if set on the footprint)
+    pub value: Option<String>,
+    /// Library footprint, e.g. "Resistor_SMD:R_0603_1608Metric"
+    pub footprint: String,
+    /// Placement side, "F.Cu" or "B.Cu"
+    pub layer: String,
+    /// X position in mm
+    pub x: f64,
+    /// Y position in mm
+    pub y: f64,
+    /// Rotation in degrees
+    pub rotation: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BoardSummary {
+    /// Board file path within the repository
+    pub file: String,
+    /// Number of copper layers
+    pub layer_count: usize,
+    /// Copper layer names in stackup order
+    pub copper_layers: Vec<String>,
+    /// Number of straight track segments
+    pub track_segments: usize,
+    /// Number of arc tracks
+    pub track_arcs: usize,
+    /// Total routed length in mm
+    pub track_length_mm: f64,
+    /// Routed length per copper layer in mm
+    pub track_length_by_layer: BTreeMap<String, f64>,
+    /// Number of vias
+    pub vias: usize,
+    /// Via count by type ("through", "blind", "micro")
+    pub vias_by_type: BTreeMap<String, usize>,
+    /// Number of copper zones
+    pub zones: usize,
+    /// Footprint placements, in natural reference order
+    pub footprints: Vec<FootprintItem>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BoardResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Commit hash
+    pub commit: String,
+    /// One entry per .kicad_pcb file in the repository
+    pub boards: Vec<BoardSummary>,
+}
+
+/// Size of a commit's schematics, over every project
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SchematicStatsItem {
+    /// Placed parts, units merged; power symbols aren't counted
+    pub components: i32,
+    /// Nets joining pins, named or not
+    pub nets: i32,
+    /// Sheet placements, root sheets included
+    pub sheets: i32,
+    /// Distinct BOM lines (value, footprint and MPN)
+    pub unique_parts: i32,
+    /// Pins on those nets
+    pub connections: i32,
+    /// components + nets + connections, plus 10 per sheet below a root
+    pub complexity: i32,
+}
+
+impl From<kicad_db::SchematicStats> for SchematicStatsItem {
+    fn from(stats: kicad_db::SchematicStats) -> Self {
+        Self {
+            components: stats.components,
+            nets: stats.nets,
+            sheets: stats.sheets,
+            unique_parts: stats.unique_parts,
+            connections: stats.connections,
+            complexity: stats.complexity,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CommitStatsResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Commit hash
+    pub commit: String,
+    pub stats: SchematicStatsItem,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct StatsHistoryQuery {
+    /// How many of the latest processed commits to chart (1 to 200, default 50)
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CommitStatsItem {
+    pub commit_hash: String,
+    pub commit_date: Option<DateTime<Utc>>,
+    pub stats: SchematicStatsItem,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StatsHistoryResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Oldest first; commits processed before stats were kept are left out
+    pub commits: Vec<CommitStatsItem>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SchematicImageQuery {
+    /// Scale a PNG or JPEG down to this many pixels wide (16 to 4096); SVGs are served as stored
+    pub width: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct UploadImageQuery {
+    /// Attach the already-stored image with this digest instead of uploading
+    /// one; send an empty body
+    pub digest: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UploadImageResponse {
+    pub repo: String,
+    pub commit: String,
+    /// Hex sha256 of the image; pass it as `?digest=` to attach the same
+    /// render to another commit without uploading it again
+    pub digest: String,
+    /// image/png or image/svg+xml
+    pub content_type: String,
+    /// Size in bytes
+    pub size: i64,
+    /// Whether an identical image was already stored, e.g. for an earlier commit
+    pub reused: bool,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ThumbnailQuery {
+    /// 256 (default) or 1024 pixels wide
+    pub width: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ComponentsQuery {
+    /// Only components on this sheet, e.g. "/Power/" ("/" for the root)
+    pub sheet: Option<String>,
+    /// With y1, x2 and y2, only components whose bounding box overlaps this
+    /// area (in mm); components without one count by their anchor
+    pub x1: Option<f64>,
+    pub y1: Option<f64>,
+    pub x2: Option<f64>,
+    pub y2: Option<f64>,
+}
+
+/// A rectangle on a sheet, in mm
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct BoundingBox {
+    pub x1: f64,
+    pub y1: f64,
+    pub x2: f64,
+    pub y2: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ComponentPlacement {
+    /// Reference designator
+    pub reference: String,
+    pub value: Option<String>,
+    pub footprint: Option<String>,
+    /// Manufacturer part number (if set)
+    pub mpn: Option<String>,
+    /// Sheet the component is placed on, e.g. "/Power/"
+    pub sheet: Option<String>,
+    /// Anchor of the (first) unit on its sheet in mm (null if not recorded)
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    /// Rotation in degrees, as in the schematic
+    pub rotation: Option<i32>,
+    /// Box around the body and pins (null if the symbol isn't known)
+    pub bbox: Option<BoundingBox>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ComponentsResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Commit hash
+    pub commit: String,
+    /// In natural reference order
+    pub components: Vec<ComponentPlacement>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PartListQuery {
+    /// Commit SHA, branch or tag (default: the latest commit)
+    pub commit: Option<String>,
+    /// Only parts in this category of the component taxonomy, e.g.
+    /// "regulator", or a subcategory, e.g. "regulator/ldo" or "ldo"
+    pub category: Option<String>,
+}
+
+/// One distinct part of a commit (components sharing a value, footprint and MPN)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CategorizedPart {
+    pub value: Option<String>,
+    pub footprint: Option<String>,
+    /// Manufacturer part number (if set)
+    pub mpn: Option<String>,
+    /// Taxonomy category, e.g. "regulator" (null until the part is classified)
+    pub category: Option<String>,
+    /// e.g. "ldo" (null if none fits, or unclassified)
+    pub subcategory: Option<String>,
+    pub quantity: usize,
+    /// In natural order
+    pub references: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PartCategoryCount {
+    pub category: String,
+    /// Distinct parts in the category
+    pub parts: usize,
+    /// Components in the category
+    pub quantity: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PartListResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Commit hash
+    pub commit: String,
+    /// Matching parts, by category then value
+    pub parts: Vec<CategorizedPart>,
+    /// Every category at this commit (ignoring the filter), for offering filters
+    pub categories: Vec<PartCategoryCount>,
+    /// Parts at this commit not classified yet (ignoring the filter)
+    pub unclassified: usize,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SymbolQuery {
+    /// Unit of a multi-unit part to draw (default: the first placed unit)
+    pub unit: Option<u32>,
+}
+
+// ============================================================================
+// Public Embed Types
+// ============================================================================
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PublicSummaryResponse {
+    /// Repository slug ("owner/repo")
+    pub repo: String,
+    /// Full commit hash of the latest summarized change
+    pub commit_hash: String,
+    /// Commit date (if recorded)
+    pub commit_date: Option<DateTime<Utc>>,
+    /// Commit message (if recorded)
+    pub message: Option<String>,
+    /// Short AI-generated blurb describing the design change
+    pub blurb: String,
+}
+
+// ============================================================================
+// Search Types
+// ============================================================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SearchQuery {
+    /// Search text; supports "quoted phrases", -exclusions and OR
+    pub q: String,
+    /// Restrict results to one repository ("owner/repo")
+    pub repo: Option<String>,
+    /// Maximum number of results (default 20, max 100)
+    pub limit: Option<i64>,
+    /// What to search: "commits" (messages and summaries, the default), or
+    /// the text of each repo's latest schematics: "text" for all of it, or
+    /// only "reference", "value", "label", "sheet" or "note". Only
+    /// /api/search supports it
+    #[serde(rename = "type")]
+    #[param(value_type = Option<String>)]
+    pub kind: Option<SearchType>,
+}
+
+/// What /api/search looks through
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchType {
+    #[default]
+    Commits,
+    /// Any schematic text
+    Text,
+    Reference,
+    Value,
+    Label,
+    Sheet,
+    Note,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchResult {
+    /// Repository clone URL
+    pub repo_url: String,
+    /// Full commit hash
+    pub commit_hash: String,
+    /// Commit date (if recorded)
+    pub commit_date: Option<DateTime<Utc>>,
+    /// Commit message (if recorded)
+    pub message: Option<String>,
+    /// Short AI-generated blurb (if generated)
+    pub blurb: Option<String>,
+    /// Matching text with search terms wrapped in <mark></mark>
+    pub snippet: String,
+    /// Relevance score (higher is better)
+    pub rank: f32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchResponse {
+    /// The search text
+    pub query: String,
+    /// Matching commits ordered by relevance (with type "commits")
+    pub results: Vec<SearchResult>,
+    /// Matching schematic text, whole matches first (with any other type)
+    pub texts: Vec<TextSearchResult>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TextSearchResult {
+    /// Repository clone URL
+    pub repo_url: String,
+    /// The repo's newest commit with indexed text, where the match is
+    pub commit_hash: String,
+    /// Commit date (if recorded)
+    pub commit_date: Option<DateTime<Utc>>,
+    /// "reference", "value", "label", "sheet" or "note"
+    pub kind: String,
+    /// The whole matching text
+    pub text: String,
+    /// The component a reference or value belongs to
+    pub reference: Option<String>,
+    /// Sheet the text is on, e.g. "/Power/"; for a sheet name, the sheet's own
+    pub sheet_path: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SemanticCommitResult {
+    /// Repository clone URL
+    pub repo_url: String,
+    /// Full commit hash
+    pub commit_hash: String,
+    /// Commit date (if recorded)
+    pub commit_date: Option<DateTime<Utc>>,
+    /// Commit message (if recorded)
+    pub message: Option<String>,
+    /// Short AI-generated blurb (if generated)
+    pub blurb: Option<String>,
+    /// Cosine similarity to the query (higher is better, at most 1)
+    pub similarity: f32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SemanticComponentResult {
+    /// Manufacturer part number
+    pub mpn: String,
+    /// Manufacturer (if known)
+    pub manufacturer: Option<String>,
+    /// Supplier description of the part
+    pub description: Option<String>,
+    /// Repository clone URL
+    pub repo_url: String,
+    /// First commit in the repository that uses the part
+    pub commit_hash: String,
+    /// Date of that commit (if recorded)
+    pub commit_date: Option<DateTime<Utc>>,
+    /// A reference designator the part is used under there
+    pub reference: String,
+    /// Cosine similarity to the query (higher is better, at most 1)
+    pub similarity: f32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SemanticSearchResponse {
+    /// The search text
+    pub query: String,
+    /// Embedding model the query and results were compared with
+    pub model: String,
+    /// Commits whose message and summaries are closest in meaning, best first
+    pub commits: Vec<SemanticCommitResult>,
+    /// Parts whose descriptions are closest in meaning, best first
+    pub components: Vec<SemanticComponentResult>,
+}
+
+// ============================================================================
+// Component Metadata Types
+// ============================================================================
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PartMetadataResponse {
+    /// Manufacturer part number
+    pub mpn: String,
+    /// Supplier the data came from (e.g. "digikey")
+    pub supplier: String,
+    /// Manufacturer name
+    pub manufacturer: Option<String>,
+    /// Part description
+    pub description: Option<String>,
+    /// Datasheet URL
+    pub datasheet_url: Option<String>,
+    /// Product page URL at the supplier
+    pub product_url: Option<String>,
+    /// Lifecycle status (Active, Obsolete, etc.)
+    pub lifecycle_status: Option<String>,
+    /// Whether the part is obsolete/deprecated
+    pub is_obsolete: bool,
+    /// Unit price (USD)
+    pub unit_price: Option<f64>,
+    /// Quantity available at the supplier
+    pub quantity_available: Option<i64>,
+    /// When the data was fetched from the supplier
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl From<kicad_db::PartMetadata> for PartMetadataResponse {
+    fn from(m: kicad_db::PartMetadata) -> Self {
+        Self {
+            mpn: m.mpn,
+            supplier: m.supplier,
+            manufacturer: m.manufacturer,
+            description: m.description,
+            datasheet_url: m.datasheet_url,
+            product_url: m.product_url,
+            lifecycle_status: m.lifecycle_status,
+            is_obsolete: m.is_obsolete,
+            unit_price: m.unit_price,
+            quantity_available: m.quantity_available,
+            fetched_at: m.fetched_at,
+        }
+    }
+}
+
+/// One repo's use of a part, at its newest commit with stored components
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ComponentUsageItem {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    pub commit: String,
+    pub commit_date: Option<DateTime<Utc>>,
+    pub quantity: i64,
+    /// Reference designators
+    pub references: Vec<String>,
+    pub value: Option<String>,
+    pub footprint: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ComponentUsageResponse {
+    /// The MPN asked about, trimmed and in upper case as parts are compared
+    pub mpn: String,
+    /// Repos using the part, most recently committed first
+    pub repos: Vec<ComponentUsageItem>,
+    /// Components across those repos
+    pub total_quantity: i64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SharedComponentsQuery {
+    /// Only parts used by at least this many repos (default 2)
+    pub min_repos: Option<i64>,
+    /// Most parts to list, 1 to 500 (default 100)
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SharedComponentItem {
+    /// Trimmed and in upper case
+    pub mpn: String,
+    /// Repos using it, in "owner/repo" format
+    pub repos: Vec<String>,
+    /// Components across those repos
+    pub quantity: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SharedComponentsResponse {
+    pub min_repos: i64,
+    /// The most widely used first
+    pub parts: Vec<SharedComponentItem>,
+}
+
+// ============================================================================
+// Health Types
+// ============================================================================
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LivenessResponse {
+    /// Always "ok"; the process is up and serving requests
+    pub status: String,
+    /// Seconds since the process started
+    pub uptime_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DependencyStatus {
+    /// "database", "git_cache_disk" or "xai"
+    pub name: String,
+    pub ok: bool,
+    /// How long the check took
+    pub latency_ms: u64,
+    /// What was found, or why the check failed
+    pub detail: String,
+    /// Whether this is a recent result reused instead of a fresh check
+    pub cached: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    /// True when every dependency check passed
+    pub ready: bool,
+    pub checks: Vec<DependencyStatus>,
+}
+
+// ============================================================================
+// API Key Types
+// ============================================================================
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeyItem {
+    /// Key id, used to revoke it
+    pub id: i32,
+    /// Name given when the key was created
+    pub name: String,
+    /// First characters of the key, to tell keys apart
+    pub prefix: String,
+    /// "read", "hook" or "admin"
+    pub scope: String,
+    /// Org the key acts for; unset for instance-wide keys
+    pub org_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    /// Last time the key was used (updated at most once a minute)
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// Set once the key is revoked
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<kicad_db::ApiKey> for ApiKeyItem {
+    fn from(k: kicad_db::ApiKey) -> Self {
+        Self {
+            id: k.id,
+            name: k.name,
+            prefix: k.prefix,
+            scope: k.scope.to_string(),
+            org_id: k.org_id,
+            created_at: k.created_at,
+            last_used_at: k.last_used_at,
+            revoked_at: k.revoked_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeyListResponse {
+    /// All keys, newest first, including revoked ones
+    pub keys: Vec<ApiKeyItem>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    /// What the key is for, e.g. "ci" or "frontend"
+    pub name: String,
+    /// "read", "hook" or "admin"
+    pub scope: String,
+    /// Slug of the org the key acts for; instance-wide keys only. Defaults
+    /// to the caller's org, or none for an instance-wide key
+    pub org: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateApiKeyResponse {
+    pub key: ApiKeyItem,
+    /// The key itself; it is only shown once
+    pub secret: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RevokeApiKeyResponse {
+    pub id: i32,
+    pub revoked: bool,
+}
+
+// ============================================================================
+// Repo Registry Types
+// ============================================================================
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RegisterRepoRequest {
+    /// Repository slug: "owner/repo" on GitHub, or "host/group/project"
+    pub repo: String,
+    /// Where to clone from (https, ssh or file URL); defaults to the URL the slug implies
+    pub clone_url: Option<String>,
+    /// Branch to process; defaults to the remote's HEAD
+    pub default_branch: Option<String>,
+    /// Name of the environment variable holding an access token for private
+    /// repos; one of the server's `git_token_envs`
+    pub auth_token_env: Option<String>,
+    /// Model for commit summaries; must be on the model allowlist
+    pub summary_model: Option<String>,
+    /// Prompt template for commit summaries, e.g. a custom "commit_summary_terse"
+    pub summary_prompt: Option<String>,
+    /// Webhook secret for this repo; overrides the provider-wide secret
+    pub webhook_secret: Option<String>,
+    /// Clone only this many commits; older ones are fetched when asked for. Omit for full history
+    pub clone_depth: Option<i32>,
+    /// Check out only these pathspecs, e.g. ["*.kicad_sch", "*.kicad_pcb"]. Omit for the whole tree
+    pub sparse_paths: Option<Vec<String>>,
+    /// Process only design files matching these globs, e.g. ["hardware/boards/**"]. Omit for the whole tree
+    pub path_filter: Option<Vec<String>>,
+    /// Also read files matching these globs as schematics, e.g. ["**/*.schematic"]; .kicad_sch
+    /// and legacy (KiCad 4/5) .sch files always are. Each file's format is told by its contents
+    pub schematic_globs: Option<Vec<String>>,
+    /// Fetch submodules, recursively, so symbol libraries vendored in them resolve
+    #[serde(default)]
+    pub submodules: bool,
+    /// Which pushes are processed and what runs for them; omit for the defaults
+    #[serde(default)]
+    pub processing: ProcessingPolicy,
+    /// Slug of the org that owns the repo; instance-wide keys only. Defaults
+    /// to the caller's org, or none for a repo every org-less caller sees
+    pub org: Option<String>,
+}
+
+/// Which pushes a repo processes and what runs for them
+///
+/// Webhook pushes to other branches, and tag pushes with `tags` off, are
+/// acknowledged without processing; scheduled re-syncs skip repos whose
+/// processed branch is left out.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct ProcessingPolicy {
+    /// Globs of the branch names whose pushes are processed, e.g. ["main", "release/*"]. Omit for every branch
+    pub branches: Option<Vec<String>>,
+    /// Process tag pushes. Defaults to true
+    pub tags: bool,
+    /// Summarize new commits with AI as soon as a push or re-sync processes them, rather than only on request. Defaults to false
+    pub ai: bool,
+    /// Render thumbnails of the repo's uploaded schematic images. Defaults to true
+    pub render: bool,
+    /// Let POST /api/grok/suggest-fix push AI-suggested schematic edits to a branch of the repo and open a pull request. Defaults to false
+    pub suggest_fixes: bool,
+}
+
+impl Default for ProcessingPolicy {
+    fn default() -> Self {
+        kicad_db::ProcessingPolicy::default().into()
+    }
+}
+
+impl From<kicad_db::ProcessingPolicy> for ProcessingPolicy {
+    fn from(p: kicad_db::ProcessingPolicy) -> Self {
+        Self {
+            branches: p.branches,
+            tags: p.tags,
+            ai: p.ai,
+            render: p.render,
+            suggest_fixes: p.suggest_fixes,
+        }
+    }
+}
+
+impl From<ProcessingPolicy> for kicad_db::ProcessingPolicy {
+    fn from(p: ProcessingPolicy) -> Self {
+        Self {
+            branches: p.branches,
+            tags: p.tags,
+            ai: p.ai,
+            render: p.render,
+            suggest_fixes: p.suggest_fixes,
+        }
+    }
+}
+
+/// A submodule and the commit the repo pins it at
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SubmodulePin {
+    pub path: String,
+    pub url: String,
+    pub commit: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RegisteredRepoItem {
+    pub repo: String,
+    pub repo_url: String,
+    pub clone_url: String,
+    pub default_branch: Option<String>,
+    pub auth_token_env: Option<String>,
+    pub summary_model: Option<String>,
+    pub summary_prompt: Option<String>,
+    /// Whether a webhook secret is set; the secret itself is never returned
+    pub has_webhook_secret: bool,
+    pub clone_depth: Option<i32>,
+    pub sparse_paths: Option<Vec<String>>,
+    pub path_filter: Option<Vec<String>>,
+    pub schematic_globs: Option<Vec<String>>,
+    pub submodules: bool,
+    pub processing: ProcessingPolicy,
+    /// Org that owns the repo; unset for repos no org owns
+    pub org_id: Option<i32>,
+    /// Submodules pinned at the head of the processed branch; only when
+    /// getting a single repo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submodule_pins: Option<Vec<SubmodulePin>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<kicad_db::RegisteredRepo> for RegisteredRepoItem {
+    fn from(r: kicad_db::RegisteredRepo) -> Self {
+        Self {
+            repo: r.slug,
+            repo_url: r.repo_url,
+            clone_url: r.clone_url,
+            default_branch: r.default_branch,
+            auth_token_env: r.auth_token_env,
+            summary_model: r.summary_model,
+            summary_prompt: r.summary_prompt,
+            has_webhook_secret: r.webhook_secret.is_some(),
+            clone_depth: r.clone_depth,
+            sparse_paths: r.sparse_paths,
+            path_filter: r.path_filter,
+            schematic_globs: r.schematic_globs,
+            submodules: r.submodules,
+            processing: r.processing.into(),
+            org_id: r.org_id,
+            submodule_pins: None,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RegisteredRepoListResponse {
+    /// By slug
+    pub repos: Vec<RegisteredRepoItem>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UnregisterRepoResponse {
+    pub repo: String,
+    /// Whether the repo was registered
+    pub removed: bool,
+    /// Stored commits deleted; purged for good after the retention window
+    pub deleted_commits: u64,
+}
+
+/// An address to send a repository's weekly activity digest to
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DigestSubscriberRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DigestSubscriberItem {
+    /// Lowercased
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<kicad_db::DigestSubscriber> for DigestSubscriberItem {
+    fn from(subscriber: kicad_db::DigestSubscriber) -> Self {
+        Self {
+            email: subscriber.email,
+            created_at: subscriber.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DigestSubscribersResponse {
+    pub repo: String,
+    /// By address
+    pub subscribers: Vec<DigestSubscriberItem>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeleteCommitResponse {
+    pub repo: String,
+    pub commit: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TimelineQuery {
+    /// Maximum number of commits to return (default 50, max 200)
+    pub limit: Option<i64>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    /// Only commits made at or after this time (RFC 3339)
+    pub since: Option<DateTime<Utc>>,
+    /// Only commits made at or before this time (RFC 3339)
+    pub until: Option<DateTime<Utc>>,
+    /// Only commits after this one: it and its ancestors are left out
+    pub since_commit: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TimelineCommit {
+    /// Full commit hash
+    pub commit_hash: String,
+    pub commit_date: Option<DateTime<Utc>>,
+    pub author: Option<String>,
+    pub author_email: Option<String>,
+    /// Tags pointing at this commit
+    pub tags: Vec<String>,
+    /// Pull request that introduced the commit, when a GitHub token is configured
+    pub pr_number: Option<i32>,
+    /// Commit message summary
+    pub message: Option<String>,
+    /// pending, processing, failed, distilled or summarized
+    pub status: String,
+    /// Where the overview pipeline left the commit: pending, processing,
+    /// done or failed
+    pub processing_status: String,
+    pub has_blurb: bool,
+    pub has_description: bool,
+    pub has_change_summary: bool,
+    /// Components and nets have been extracted
+    pub distilled: bool,
+    /// A schematic image has been rendered
+    pub rendered: bool,
+    /// Why the last processing attempt failed
+    pub error: Option<String>,
+    pub error_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TimelineResponse {
+    pub repo: String,
+    /// Commits that changed schematic or layout files, newest first
+    pub commits: Vec<TimelineCommit>,
+    /// Pass as `cursor` for the next page; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// A commit whose last processing attempt failed
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProcessingErrorItem {
+    pub commit_hash: String,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RepoAlertsQuery {
+    /// Also list alerts resolved since (default: false)
+    pub include_resolved: Option<bool>,
+}
+
+/// A part of the repo's latest commit that the supplier marks NRND, EOL or
+/// obsolete
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PartAlertItem {
+    /// Trimmed and upper case
+    pub mpn: String,
+    /// "nrnd", "eol" or "obsolete"
+    pub risk: String,
+    /// The supplier's own wording, e.g. "Not For New Designs"
+    pub lifecycle_status: Option<String>,
+    /// The commit the part was last seen in
+    pub commit_hash: String,
+    pub designators: Vec<String>,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    /// When the part left the repo or its status recovered
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RepoAlertsResponse {
+    pub repo: String,
+    /// Open alerts first, the most recently seen first
+    pub alerts: Vec<PartAlertItem>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProcessingErrorsResponse {
+    pub repo: String,
+    /// Most recent failure first
+    pub errors: Vec<ProcessingErrorItem>,
+}
+
+/// What happened to a commit, as announced on the events stream
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RepoEventKind {
+    /// Processing finished and the commit's overview is stored
+    Processed,
+    /// Processing failed; the timeline has the error
+    Failed,
+}
+
+/// A commit of the repo changed; reload its timeline entry
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RepoEvent {
+    pub repo: String,
+    /// Full commit hash
+    pub commit_hash: String,
+    pub kind: RepoEventKind,
+}
+
+/// First line of a repo archive; each further line is one stored commit
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RepoArchiveHeader {
+    /// Archive format version
+    pub version: u32,
+    /// Repository the archive was exported from
+    pub repo: String,
+    /// Number of commit lines that follow
+    pub commits: i64,
+    pub exported_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportRepoResponse {
+    pub repo: String,
+    /// Commits stored, replacing any already stored
+    pub imported: usize,
+}
+
+// ============================================================================
+// Schedule Types
+// ============================================================================
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RepoScheduleItem {
+    /// Repository slug, e.g. "owner/repo"
+    pub repo: String,
+    pub repo_url: String,
+    pub enabled: bool,
+    /// This repo's own interval; null uses the default
+    pub interval_secs: Option<i64>,
+    /// Interval in effect, 0 if the repo is never re-checked
+    pub effective_interval_secs: i64,
+    /// When the last re-sync finished
+    pub last_checked_at: Option<DateTime<Utc>>,
+    /// Error from the last re-sync, null if it succeeded
+    pub last_error: Option<String>,
+    /// Null when the repo is never re-checked
+    pub next_check_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScheduleListResponse {
+    /// Interval for repos without their own (RESYNC_INTERVAL_SECS), 0 if disabled
+    pub default_interval_secs: u64,
+    /// Every repo with stored commits or a schedule, by URL
+    pub schedules: Vec<RepoScheduleItem>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateScheduleRequest {
+    /// Repository slug, e.g. "owner/repo"
+    pub repo: String,
+    /// False stops scheduled re-syncs of this repo (default true)
+    pub enabled: Option<bool>,
+    /// Seconds between re-syncs; omit to use the default
+    pub interval_secs: Option<u32>,
+}
+
+// ============================================================================
+// Webhook Delivery Types
+// ============================================================================
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookDeliveryItem {
+    /// "github", "gitlab" or "bitbucket"
+    pub provider: String,
+    /// The provider's delivery ID (GitHub), or one generated on receipt
+    pub delivery_id: String,
+    /// Repository slug, e.g. "owner/repo"
+    pub repo: String,
+    /// Event type from the provider's headers
+    pub event: Option<String>,
+    pub received_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookDeliveryListResponse {
+    /// Deliveries still kept for replay, newest first
+    pub deliveries: Vec<WebhookDeliveryItem>,
+}
+
+// ============================================================================
+// Job Types
+// ============================================================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct JobListQuery {
+    /// Only jobs of this repo ("owner/repo")
+    pub repo: Option<String>,
+    /// Only jobs with this status: running, completed, stopped or cancelled
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobListResponse {
+    /// Newest first
+    pub jobs: Vec<BackfillProgress>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobDetailResponse {
+    pub job: BackfillProgress,
+    /// Every commit the job set out to summarize, in order
+    pub commits: Vec<JobCommit>,
+}
+
+// ============================================================================
+// Cost Types
+// ============================================================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CostReportQuery {
+    /// Calendar month (UTC) as "YYYY-MM"; defaults to the current month
+    pub month: Option<String>,
+}
+
+/// Spend of one repo, API key or model
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CostLine {
+    /// Repository slug, API key ID, provider or model; null for calls not charged to any
+    /// repo or key (e.g. background jobs)
+    pub key: Option<String>,
+    /// API key name, for lines by key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub calls: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost_usd: f64,
+    /// Monthly budget, for repos and keys that have one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget_usd: Option<f64>,
+    /// Whether the budget is spent, so calls use the fallback model or are refused
+    pub over_budget: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CostReportResponse {
+    /// The month reported, "YYYY-MM"
+    pub month: String,
+    pub total_usd: f64,
+    /// Model calls switch to once a budget is spent; null if they are refused instead
+    pub fallback_model: Option<String>,
+    /// Most expensive first
+    pub by_repo: Vec<CostLine>,
+    pub by_api_key: Vec<CostLine>,
+    /// "xai" or the name of a fallback provider calls failed over to
+    pub by_provider: Vec<CostLine>,
+    pub by_model: Vec<CostLine>,
+}
+
+// ============================================================================
+// Admin Overview Types
+// ============================================================================
+
+/// Work waiting or under way
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobQueueDepth {
+    /// Commits waiting to be processed or being processed now
+    pub commits_queued: i64,
+    /// Jobs running on this server (webhook updates, imports, re-syncs)
+    pub active_jobs: usize,
+    /// Summary backfills running on this server
+    pub running_backfills: usize,
+    /// Commits those backfills have yet to summarize
+    pub backfill_commits_remaining: usize,
+    /// The same by work class, with the AI calls waiting for the rate limit
+    pub classes: Vec<ClassQueueDepth>,
+}
+
+/// Work of one class waiting or under way on this server
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ClassQueueDepth {
+    pub class: WorkClass,
+    /// Summary backfills running as this class
+    pub running_backfills: usize,
+    /// Commits those backfills have yet to summarize
+    pub backfill_commits_remaining: usize,
+    /// AI calls waiting their turn at the shared rate limit
+    pub ai_calls_waiting: usize,
+}
+
+/// Lookups of one cache on this server since it started
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CacheHitRate {
+    /// "ai", "response" or "schematic"
+    pub cache: String,
+    pub hits: u64,
+    pub misses: u64,
+    /// Share of lookups that hit, 0 to 1; null before the first lookup
+    pub hit_rate: Option<f64>,
+}
+
+/// Requests this server answered in the last hour
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorRate {
+    pub requests: u64,
+    /// Answered with a 5xx
+    pub server_errors: u64,
+    /// `server_errors / requests`; 0 without requests
+    pub rate: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminOverviewResponse {
+    pub registered_repos: i64,
+    /// Commits whose processing finished in the last 24 hours
+    pub commits_processed_24h: i64,
+    /// Commits whose last attempt failed in the last 24 hours
+    pub commits_failed_24h: i64,
+    pub queue: JobQueueDepth,
+    /// AI spend since midnight UTC
+    pub ai_spend_today_usd: f64,
+    pub error_rate: ErrorRate,
+    pub cache_hit_rates: Vec<CacheHitRate>,
+    pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ClassifyPartsResponse {
+    /// Parts classified by this run
+    pub classified: usize,
+    /// Model that classified them, after any routing away from a degraded one
+    pub model: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReloadConfigResponse {
+    /// Prompt templates now in effect, as name@vVERSION
+    pub prompts: Vec<String>,
+    /// Changed settings that keep their old values until a restart
+    pub restart_required: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LifecycleCheckResponse {
+    /// Registered repos with parts at their latest commit
+    pub repos: usize,
+    /// Distinct MPNs looked up
+    pub parts: usize,
+    /// Alerts open after the check
+    pub open_alerts: usize,
+    /// Alerts resolved because the part is gone or no longer at risk
+    pub resolved: u64,
+    /// Alerts announced on the notification webhook by this check
+    pub notified: usize,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct OnboardGithubOrgRequest {
+    /// GitHub org (or user) whose repositories are onboarded, e.g. "acme-hardware"
+    pub github_org: String,
+    /// Where GitHub reaches this server, e.g. https://kicad.example.com; push
+    /// webhooks are only installed with it
+    pub public_url: Option<String>,
+    /// Onboard forks too. Defaults to false
+    #[serde(default)]
+    pub include_forks: bool,
+    /// Onboard archived repos too. Defaults to false
+    #[serde(default)]
+    pub include_archived: bool,
+    /// Processing policy of every repo registered; omit for the defaults
+    #[serde(default)]
+    pub processing: ProcessingPolicy,
+    /// Slug of the org that owns the repos; instance-wide keys only. Defaults
+    /// to the caller's org
+    pub org: Option<String>,
+}
+
+/// What onboarding did with a repo
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OnboardStatus {
+    Registered,
+    /// Left as it was registered before
+    AlreadyRegistered,
+    /// A fork, archived, empty or without KiCad files; `detail` says which
+    Skipped,
+    /// Scanning or registering it failed; `detail` says why
+    Failed,
+}
+
+/// What became of a registered repo's push webhook
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OnboardWebhook {
+    Installed,
+    /// A hook pointing at this server was already there, and now signs with the new secret
+    Updated,
+    /// No `public_url`, or the repo wasn't registered
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OnboardedRepoItem {
+    /// "owner/repo"
+    pub repo: String,
+    pub status: OnboardStatus,
+    pub webhook: OnboardWebhook,
+    /// The initial sync (processing the history, then summaries as the
+    /// processing policy asks) was queued
+    pub backfill_queued: bool,
+    /// Why the repo was skipped or something failed
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OnboardGithubOrgResponse {
+    pub github_org: String,
+    /// Repositories the org has that the token can see
+    pub scanned: usize,
+    /// Repositories registered by this call
+    pub registered: usize,
+    /// Webhooks installed or updated
+    pub webhooks_installed: usize,
+    /// Initial syncs queued; each shows up under /api/admin/jobs once its
+    /// commits are processed and summaries start
+    pub backfills_queued: usize,
+    /// Every repository, in the order GitHub listed them
+    pub repos: Vec<OnboardedRepoItem>,
+}
+
+// ============================================================================
+// Share Link Types
+// ============================================================================
+
+/// A read-only view a share link opens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareScope {
+    /// The commit's AI blurb and description
+    Summary,
+    /// What the commit changed, as /commits/{commit}/changes returns it
+    Diff,
+    /// Each schematic sheet drawn as SVG
+    Svg,
+}
+
+impl ShareScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Summary => "summary",
+            Self::Diff => "diff",
+            Self::Svg => "svg",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct CreateShareLinkRequest {
+    /// Views the link opens; all of them by default
+    pub scopes: Option<Vec<ShareScope>>,
+    /// Seconds until the link expires; the server's default when absent,
+    /// at most its maximum
+    pub expires_in_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ShareLinkResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Full commit hash
+    pub commit: String,
+    pub scopes: Vec<ShareScope>,
+    pub expires_at: DateTime<Utc>,
+    /// Signed token; anyone holding it can open the views until it expires
+    pub token: String,
+    /// Where the shared commit is served, e.g. "/api/shared/{token}"
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SharedCommitResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Full commit hash
+    pub commit: String,
+    /// Views the link opens
+    pub scopes: Vec<ShareScope>,
+    pub expires_at: DateTime<Utc>,
+    /// Commit message summary
+    pub message: Option<String>,
+    pub author: Option<String>,
+    pub commit_date: Option<DateTime<Utc>>,
+    /// Sheets /sheets/{page} can draw (null unless the link opens `svg`)
+    pub sheets: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SharedSummaryResponse {
+    /// GitHub repository in "owner/repo" format
+    pub repo: String,
+    /// Full commit hash
+    pub commit: String,
+    /// Short AI-generated summary
+    pub blurb: String,
+    /// Detailed AI-generated description
+    pub description: Option<String>,
+}
+
+// ============================================================================
+// Prompt Audit Types
+// ============================================================================
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PromptAuditQuery {
+    /// Only calls made for this repo ("owner/repo")
+    pub repo: Option<String>,
+    /// Most entries to list, 1 to 200 (default 50)
+    pub limit: Option<i64>,
+}
+
+/// One AI call, as the prompt audit recorded it
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PromptAuditItem {
+    pub id: i64,
+    /// ID of the API request that made the call, if any
+    pub request_id: Option<String>,
+    /// "responses", "chat_completion_stream", "submit_deferred" or "deferred_completion"
+    pub endpoint: String,
+    /// "xai" or the name of a fallback provider
+    pub provider: String,
+    pub model: String,
+    /// Repository slug the call was charged to
+    pub repo: Option<String>,
+    /// ID of the API key the call was charged to
+    pub api_key: Option<String>,
+    /// The messages sent, redacted and with long text cut short
+    #[schema(value_type = Object)]
+    pub messages: serde_json::Value,
+    /// The answer, redacted and cut short like the messages
+    pub response: Option<String>,
+    pub error: Option<String>,
+    pub latency_ms: i64,
+    pub prompt_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PromptAuditListResponse {
+    /// Newest first
+    pub prompts: Vec<PromptAuditItem>,
+}
+
+// ============================================================================
+// Organization Types
+// ============================================================================
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrganizationItem {
+    pub id: i32,
+    /// Short name, used in JWT `org` claims and when creating keys and repos
+    pub slug: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<kicad_db::Organization> for OrganizationItem {
+    fn from(o: kicad_db::Organization) -> Self {
+        Self {
+            id: o.id,
+            slug: o.slug,
+            name: o.name,
+            created_at: o.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrganizationListResponse {
+    /// By slug
+    pub orgs: Vec<OrganizationItem>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateOrganizationRequest {
+    /// Lowercase letters, digits and dashes, e.g. "acme-robotics"
+    pub slug: String,
+    /// Display name
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MemberItem {
+    pub email: String,
+    pub name: Option<String>,
+    /// Widest scope the member gets in the org: "read", "hook" or "admin"
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<kicad_db::Member> for MemberItem {
+    fn from(m: kicad_db::Member) -> Self {
+        Self {
+            email: m.email,
+            name: m.name,
+            role: m.role.to_string(),
+            created_at: m.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MemberListResponse {
+    pub org: String,
+    /// By email
+    pub members: Vec<MemberItem>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetMemberRequest {
+    /// The user's email, as sent in the `sub` claim of their JWTs
+    pub email: String,
+    /// Display name; kept as is if omitted
+    pub name: Option<String>,
+    /// "read", "hook" or "admin"
+    pub role: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RemoveMemberResponse {
+    pub email: String,
+    pub removed: bool,
+}
+
+// ============================================================================
+// Design Guideline Types
+// ============================================================================
+
+/// A house rule for the AI to apply in summaries and chat
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DesignGuidelineRequest {
+    /// Short name, e.g. "Passives"
+    pub title: String,
+    /// The rule as the model reads it, e.g. "We always use 0402 passives"
+    pub body: String,
+    /// Higher comes first within its org or repo (default: 0)
+    pub priority: Option<i32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DesignGuidelineItem {
+    pub id: i64,
+    pub title: String,
+    pub body: String,
+    pub priority: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<kicad_db::DesignGuideline> for DesignGuidelineItem {
+    fn from(guideline: kicad_db::DesignGuideline) -> Self {
+        Self {
+            id: guideline.id,
+            title: guideline.title,
+            body: guideline.body,
+            priority: guideline.priority,
+            created_at: guideline.created_at,
+            updated_at: guideline.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrgGuidelinesResponse {
+    pub org: String,
+    /// Highest priority first
+    pub guidelines: Vec<DesignGuidelineItem>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RepoGuidelinesResponse {
+    pub repo: String,
+    /// The repo's own, highest priority first
+    pub guidelines: Vec<DesignGuidelineItem>,
+    /// Those of the org the repo is registered to, which its own take
+    /// precedence over
+    pub org_guidelines: Vec<DesignGuidelineItem>,
+    /// The block added to the repo's summary and chat system prompts, within
+    /// the character budget; null without guidelines
+    pub prompt: Option<String>,
+    /// Guidelines left out of the prompt for lack of room
+    pub omitted: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeleteGuidelineResponse {
+    pub id: i64,
+    pub deleted: bool,
+}
+
+// ============================================================================
+// Summary Feedback Types
+// ============================================================================
+
+/// Thumbs up or down
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryRating {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SummaryFeedbackRequest {
+    /// `summary_id` of the summary response being rated
+    pub summary_id: i64,
+    pub rating: SummaryRating,
+    /// What was good or missing
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SummaryFeedbackResponse {
+    /// ID of the stored rating
+    pub id: i64,
+    pub summary_id: i64,
+    pub rating: SummaryRating,
+    /// Model that generated the rated summary
+    pub model: String,
+    /// Prompt template the rated summary was generated from
+    #[schema(example = "commit_summary@v1")]
+    pub prompt_version: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct FeedbackReportQuery {
+    /// Ratings given in the last this many days; defaults to 30
+    pub days: Option<u32>,
+}
+
+/// Ratings of the summaries of one kind one prompt template and model wrote
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FeedbackTotalItem {
+    /// What was summarized, e.g. "commit_summary"
+    pub kind: String,
+    #[schema(example = "commit_summary@v1")]
+    pub prompt_version: String,
+    pub model: String,
+    /// Summaries rated at least once
+    pub summaries: i64,
+    /// Thumbs up
+    pub helpful: i64,
+    /// Thumbs down
+    pub unhelpful: i64,
+    /// Ratings that came with a comment
+    pub comments: i64,
+    /// Share of ratings that were thumbs up, 0 to 1
+    pub helpful_rate: f64,
+}
+
+impl From<kicad_db::FeedbackTotal> for FeedbackTotalItem {
+    fn from(total: kicad_db::FeedbackTotal) -> Self {
+        Self {
+            helpful_rate: total.helpful_rate(),
+            kind: total.kind,
+            prompt_version: total.prompt_version,
+            model: total.model,
+            summaries: total.summaries,
+            helpful: total.helpful,
+            unhelpful: total.unhelpful,
+            comments: total.comments,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FeedbackReportResponse {
+    /// Start of the period reported on
+    pub since: DateTime<Utc>,
+    /// By kind, prompt template and model, the most rated first
+    pub totals: Vec<FeedbackTotalItem>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ExperimentsQuery {
+    /// Summaries handed out and AI calls made in the last this many days;
+    /// defaults to 30
+    pub days: Option<u32>,
+}
+
+/// How one variant of an experiment did
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExperimentVariantItem {
+    #[schema(example = "terse-summaries")]
+    pub experiment: String,
+    #[schema(example = "fast")]
+    pub variant: String,
+    /// Prompt template and model of its latest summary; null if it has AI
+    /// calls but no summaries in the period
+    #[schema(example = "commit_summary@v1")]
+    pub prompt_version: Option<String>,
+    pub model: Option<String>,
+    /// Its weight in the running experiment; null if the variant is no
+    /// longer configured
+    pub weight: Option<u32>,
+    /// Summaries handed out
+    pub summaries: i64,
+    /// Summaries rated at least once
+    pub rated: i64,
+    /// Thumbs up
+    pub helpful: i64,
+    /// Thumbs down
+    pub unhelpful: i64,
+    /// Share of ratings that were thumbs up, 0 to 1
+    pub helpful_rate: f64,
+    /// AI calls made for its summaries
+    pub calls: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost_usd: f64,
+    /// Average cost of a summary handed out
+    pub cost_per_summary: f64,
+}
+
+impl From<kicad_db::VariantResult> for ExperimentVariantItem {
+    fn from(result: kicad_db::VariantResult) -> Self {
+        Self {
+            helpful_rate: result.helpful_rate(),
+            cost_per_summary: result.cost_per_summary(),
+            experiment: result.experiment,
+            variant: result.variant,
+            prompt_version: result.prompt_version,
+            model: result.model,
+            weight: None,
+            summaries: result.summaries,
+            rated: result.rated,
+            helpful: result.helpful,
+            unhelpful: result.unhelpful,
+            calls: result.calls,
+            prompt_tokens: result.prompt_tokens,
+            completion_tokens: result.completion_tokens,
+            cost_usd: result.cost_usd,
         }
     }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ExperimentsResponse {
+    /// Name of the experiment summaries are assigned to now, if any
+    pub active: Option<String>,
+    /// Start of the period reported on
+    pub since: DateTime<Utc>,
+    /// By experiment and variant
+    pub variants: Vec<ExperimentVariantItem>,
+}
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiError {
+    /// Error code
+    pub error: String,
+    /// Human-readable error message
+    pub message: String,
+    /// ID of the failed request, also sent as X-Request-Id; quote it when reporting problems
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// With a 422, each problem with the request
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<FieldViolation>,
+}
+
+/// One problem with a request field
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldViolation {
+    /// Field name, with an index for list items, e.g. `component_ids[2]`
+    #[schema(example = "commit")]
+    pub field: String,
+    #[schema(example = "must be a commit SHA: 4 to 40 hexadecimal characters")]
+    pub message: String,
+}
 
-    pub fn not_found(message: impl Into<String>) -> Self {
-        Self::new("not_found", message)
+impl ApiError {
+    pub fn new(error: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            message: message.into(),
+            request_id: None,
+            violations: Vec::new(),
+        }
     }
 
-    pub fn internal(message: impl Into<String>) -> Self {
-        Self::new("internal_error", message)
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
     }
 
-    pub fn bad_request(message: impl Into<String>) -> Self {
-        Self::new("bad_request", message)
+    pub fn with_violations(mut self, violations: Vec<FieldViolation>) -> Self {
+        self.violations = violations;
+        self
     }
 }
@@ -0,0 +1,313 @@
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    http::StatusCode,
+    Json,
+};
+use serde::de::DeserializeOwned;
+
+use crate::error::AppError;
+use crate::types::{
+    DesignGuidelineRequest, DigestSubscriberRequest, FieldViolation, GrokAskRequest, GrokCommitSummaryRequest, GrokCompareSummaryRequest, GrokReleaseNotesRequest,
+    GrokReviewChecklistRequest, GrokSelectionStreamRequest, GrokSelectionSummaryRequest, GrokSuggestFixRequest,
+    Selection, SummaryFeedbackRequest,
+};
+
+/// Longest repository slug accepted, host included
+const MAX_REPO_LEN: usize = 200;
+/// Most components one selection request may name
+const MAX_COMPONENT_IDS: usize = 500;
+/// Longest question /api/grok/ask takes, in characters
+const MAX_QUESTION_LEN: usize = 2_000;
+/// Longest change /api/grok/suggest-fix takes, in characters
+const MAX_CHANGE_LEN: usize = 2_000;
+/// Longest email address, per RFC 5321
+const MAX_EMAIL_LEN: usize = 254;
+/// Longest design guideline title and text, in characters
+const MAX_GUIDELINE_TITLE_LEN: usize = 200;
+const MAX_GUIDELINE_BODY_LEN: usize = 10_000;
+/// Longest comment on a summary, in characters
+const MAX_FEEDBACK_COMMENT_LEN: usize = 2_000;
+/// Most sources of each kind a chat turn or question may retrieve
+pub const MAX_TOP_K: usize = 20;
+
+/// Request bodies that check their own fields before a handler runs
+pub trait Validate {
+    /// Record every problem with the request in `violations`
+    fn validate(&self, violations: &mut Violations);
+}
+
+/// Problems found with a request, field by field
+#[derive(Debug, Default)]
+pub struct Violations(Vec<FieldViolation>);
+
+impl Violations {
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.0.push(FieldViolation {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    pub fn into_vec(self) -> Vec<FieldViolation> {
+        self.0
+    }
+
+    /// Fail with a 422 listing the violations, if there are any
+    pub fn into_result(self) -> Result<(), AppError> {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => Err(AppError::invalid(self)),
+        }
+    }
+
+    /// A repository slug: "owner/repo", or "host/path/to/repo" for other forges
+    pub fn repo(&mut self, field: &str, repo: &str) {
+        if repo.trim().is_empty() {
+            return self.add(field, "must not be empty");
+        }
+        if repo.len() > MAX_REPO_LEN {
+            return self.add(field, format!("must be at most {} characters", MAX_REPO_LEN));
+        }
+        let segments: Vec<&str> = repo.split('/').collect();
+        let valid_segment = |s: &&str| {
+            !s.is_empty()
+                && *s != "."
+                && *s != ".."
+                && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        };
+        if segments.len() < 2 || !segments.iter().all(valid_segment) {
+            self.add(field, "must be a repository like \"owner/repo\" or \"gitlab.com/group/project\"");
+        }
+    }
+
+    /// A commit SHA (full or abbreviated), branch or tag
+    pub fn commit(&mut self, field: &str, commit: &str) {
+        if commit.trim().is_empty() {
+            self.add(field, "must not be empty");
+        } else if !git2::Reference::is_valid_name(&format!("refs/heads/{}", commit.trim())) {
+            self.add(field, "must be a commit SHA, branch or tag name");
+        }
+    }
+
+    /// Component references such as "R1" or "U3"
+    pub fn component_ids(&mut self, field: &str, ids: &[String]) {
+        if ids.is_empty() {
+            return self.add(field, "must name at least one component");
+        }
+        if ids.len() > MAX_COMPONENT_IDS {
+            self.add(field, format!("must name at most {} components", MAX_COMPONENT_IDS));
+        }
+        for (i, id) in ids.iter().enumerate() {
+            if id.trim().is_empty() {
+                self.add(format!("{}[{}]", field, i), "must not be empty");
+            }
+        }
+    }
+
+    /// What was selected in the viewer; names are checked like component references
+    pub fn selection(&mut self, field: &str, selection: &Selection) {
+        match selection {
+            Selection::Components { ids } => self.component_ids(&format!("{}.ids", field), ids),
+            Selection::Nets { names } => {
+                let field = format!("{}.names", field);
+                if names.is_empty() {
+                    return self.add(field, "must name at least one net");
+                }
+                if names.len() > MAX_COMPONENT_IDS {
+                    self.add(&field, format!("must name at most {} nets", MAX_COMPONENT_IDS));
+                }
+                for (i, name) in names.iter().enumerate() {
+                    if name.trim().is_empty() {
+                        self.add(format!("{}[{}]", field, i), "must not be empty");
+                    }
+                }
+            }
+            Selection::Sheet { path } => self.not_blank(&format!("{}.path", field), path),
+            Selection::Area { x1, y1, x2, y2, .. } => {
+                if ![x1, y1, x2, y2].iter().all(|c| c.is_finite()) {
+                    self.add(field, "must have finite coordinates");
+                }
+            }
+        }
+    }
+
+    /// Free text that must say something
+    pub fn not_blank(&mut self, field: &str, text: &str) {
+        if text.trim().is_empty() {
+            self.add(field, "must not be empty");
+        }
+    }
+
+    /// A bare email address, e.g. ada@example.com
+    pub fn email(&mut self, field: &str, email: &str) {
+        if email.len() > MAX_EMAIL_LEN {
+            return self.add(field, format!("must be at most {} characters", MAX_EMAIL_LEN));
+        }
+        if email.parse::<lettre::Address>().is_err() {
+            self.add(field, "must be an email address like ada@example.com");
+        }
+    }
+}
+
+impl std::fmt::Display for Violations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list: Vec<String> = self.0.iter().map(|v| format!("{} {}", v.field, v.message)).collect();
+        f.write_str(&list.join("; "))
+    }
+}
+
+impl std::error::Error for Violations {}
+
+/// A JSON body that passed [`Validate`]
+///
+/// Bodies that aren't valid JSON for the type are a 400, bodies with invalid
+/// fields a 422 listing each violation, both before the handler runs.
+pub struct Valid<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Valid<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(body) = Json::<T>::from_request(request, state)
+            .await
+            .map_err(|rejection: JsonRejection| match rejection.status() {
+                StatusCode::PAYLOAD_TOO_LARGE => AppError::payload_too_large(rejection.body_text()),
+                _ => AppError::bad_request(rejection.body_text()),
+            })?;
+        let mut violations = Violations::default();
+        body.validate(&mut violations);
+        violations.into_result()?;
+        Ok(Valid(body))
+    }
+}
+
+/// The repository named by a `*repo` path parameter, checked like a body's
+pub fn repo_path(repo: &str) -> Result<String, AppError> {
+    let repo = repo.trim_start_matches('/');
+    let mut violations = Violations::default();
+    violations.repo("repo", repo);
+    violations.into_result()?;
+    Ok(repo.to_string())
+}
+
+impl Validate for GrokCommitSummaryRequest {
+    fn validate(&self, violations: &mut Violations) {
+        violations.repo("repo", &self.repo);
+        violations.commit("commit", &self.commit);
+    }
+}
+
+impl Validate for GrokCompareSummaryRequest {
+    fn validate(&self, violations: &mut Violations) {
+        violations.repo("repo", &self.repo);
+        violations.commit("base", &self.base);
+        violations.commit("head", &self.head);
+    }
+}
+
+impl Validate for GrokReleaseNotesRequest {
+    fn validate(&self, violations: &mut Violations) {
+        violations.repo("repo", &self.repo);
+        violations.commit("from_tag", &self.from_tag);
+        violations.commit("to_tag", &self.to_tag);
+    }
+}
+
+impl Validate for GrokAskRequest {
+    fn validate(&self, violations: &mut Violations) {
+        violations.repo("repo", &self.repo);
+        violations.commit("commit", &self.commit);
+        violations.not_blank("question", &self.question);
+        if self.question.chars().count() > MAX_QUESTION_LEN {
+            violations.add("question", format!("must be at most {} characters", MAX_QUESTION_LEN));
+        }
+        if self.top_k.is_some_and(|top_k| top_k > MAX_TOP_K) {
+            violations.add("top_k", format!("must be at most {}", MAX_TOP_K));
+        }
+    }
+}
+
+impl Validate for GrokReviewChecklistRequest {
+    fn validate(&self, violations: &mut Violations) {
+        violations.repo("repo", &self.repo);
+        violations.commit("commit", &self.commit);
+    }
+}
+
+impl Validate for GrokSuggestFixRequest {
+    fn validate(&self, violations: &mut Violations) {
+        violations.not_blank("change", &self.change);
+        if self.change.chars().count() > MAX_CHANGE_LEN {
+            violations.add("change", format!("must be at most {} characters", MAX_CHANGE_LEN));
+        }
+    }
+}
+
+impl Validate for DigestSubscriberRequest {
+    fn validate(&self, violations: &mut Violations) {
+        violations.email("email", &self.email);
+    }
+}
+
+impl Validate for DesignGuidelineRequest {
+    fn validate(&self, violations: &mut Violations) {
+        violations.not_blank("title", &self.title);
+        if self.title.chars().count() > MAX_GUIDELINE_TITLE_LEN {
+            violations.add("title", format!("must be at most {} characters", MAX_GUIDELINE_TITLE_LEN));
+        }
+        violations.not_blank("body", &self.body);
+        if self.body.chars().count() > MAX_GUIDELINE_BODY_LEN {
+            violations.add("body", format!("must be at most {} characters", MAX_GUIDELINE_BODY_LEN));
+        }
+    }
+}
+
+impl Validate for SummaryFeedbackRequest {
+    fn validate(&self, violations: &mut Violations) {
+        if let Some(comment) = &self.comment {
+            if comment.chars().count() > MAX_FEEDBACK_COMMENT_LEN {
+                violations.add("comment", format!("must be at most {} characters", MAX_FEEDBACK_COMMENT_LEN));
+            }
+        }
+    }
+}
+
+impl Validate for GrokSelectionSummaryRequest {
+    fn validate(&self, violations: &mut Violations) {
+        violations.repo("repo", &self.repo);
+        violations.commit("commit", &self.commit);
+        match &self.selection {
+            Some(selection) => {
+                violations.selection("selection", selection);
+                if !self.component_ids.is_empty() {
+                    violations.add("component_ids", "must be empty when a selection is given");
+                }
+            }
+            None => violations.component_ids("component_ids", &self.component_ids),
+        }
+    }
+}
+
+impl Validate for GrokSelectionStreamRequest {
+    fn validate(&self, violations: &mut Violations) {
+        violations.repo("repo", &self.repo);
+        violations.commit("commit", &self.commit);
+        // No selection asks about the whole schematic
+        if !self.component_ids.is_empty() {
+            violations.component_ids("component_ids", &self.component_ids);
+        }
+        if let Some(selection) = &self.selection {
+            violations.selection("selection", selection);
+            if !self.component_ids.is_empty() {
+                violations.add("component_ids", "must be empty when a selection is given");
+            }
+        }
+        violations.not_blank("query", &self.query);
+    }
+}
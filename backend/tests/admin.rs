@@ -0,0 +1,61 @@
+// End-to-end tests of the admin overview; see common/mod.rs for the harness.
+// The overview's request and cache counters are shared by the whole binary,
+// so it has a binary of its own.
+//
+// USAGE:
+// cargo test --test admin
+
+mod common;
+
+use common::{encoded, unique_slug, FakeRemote, TestApp, TWO_RESISTORS};
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn overview_counts_repos_commits_and_cache_lookups() {
+    let Some(app) = TestApp::start_caching_responses().await else { return };
+    let empty: Value = app.get("/api/admin/overview").await.json().await.unwrap();
+    assert_eq!(empty["registered_repos"], 0, "{}", empty);
+    assert_eq!(empty["commits_processed_24h"], 0);
+    assert_eq!(empty["queue"]["commits_queued"], 0);
+    assert_eq!(empty["ai_spend_today_usd"], 0.0);
+
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let slug = unique_slug("overview");
+    app.register(&slug, &remote).await;
+    app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+    let bom = format!("/api/repos/{}/commits/{}/bom", encoded(&slug), commit);
+    app.get(&bom).await;
+    app.get(&bom).await;
+
+    let response = app.get("/api/admin/overview").await;
+    assert_eq!(response.status(), 200);
+    let overview: Value = response.json().await.unwrap();
+    assert_eq!(overview["registered_repos"], 1, "{}", overview);
+    assert_eq!(overview["commits_processed_24h"], 1);
+    assert_eq!(overview["commits_failed_24h"], 0);
+    assert_eq!(overview["queue"]["commits_queued"], 0);
+    assert_eq!(overview["queue"]["running_backfills"], 0);
+    let spent: f64 = sqlx::query_scalar("SELECT COALESCE(SUM(cost_usd), 0) FROM ai_spend")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(overview["ai_spend_today_usd"].as_f64(), Some(spent));
+
+    // Every request so far was answered without a server error
+    assert!(overview["error_rate"]["requests"].as_u64().unwrap() >= 5);
+    assert_eq!(overview["error_rate"]["server_errors"], 0);
+    assert_eq!(overview["error_rate"]["rate"], 0.0);
+    let responses = overview["cache_hit_rates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["cache"] == "response")
+        .expect("no response cache lookups");
+    assert_eq!((responses["hits"].as_u64(), responses["misses"].as_u64()), (Some(1), Some(1)));
+    assert_eq!(responses["hit_rate"], 0.5);
+
+    // Needs an admin key
+    let response = app.anonymous(reqwest::Method::GET, "/api/admin/overview").send().await.unwrap();
+    assert_eq!(response.status(), 401);
+}
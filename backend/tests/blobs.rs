@@ -0,0 +1,132 @@
+// End-to-end tests of the S3 blob store against an in-process fake bucket;
+// see common/mod.rs for the harness. The blob store is process-wide, so
+// these live in a test binary of their own.
+//
+// USAGE:
+// cargo test --test blobs
+
+mod common;
+
+use axum::body::Bytes;
+use axum::extract::{Query, State};
+use axum::http::{Method as HttpMethod, StatusCode, Uri};
+use axum::response::IntoResponse;
+use common::{encoded, unique_slug, FakeRemote, TestApp, TWO_RESISTORS};
+use kicad_db::{image_digest, purge_orphaned_images, S3BlobStore, S3Config};
+use reqwest::Method;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+const SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10"/>"#;
+
+type Objects = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+/// Just enough of S3 for the blob store: object PUT, GET and DELETE, and
+/// ListObjectsV2. Requests must be signed, though signatures aren't checked.
+async fn fake_s3(
+    State(objects): State<Objects>,
+    method: HttpMethod,
+    uri: Uri,
+    Query(query): Query<HashMap<String, String>>,
+    headers: axum::http::HeaderMap,
+    body: Bytes,
+) -> axum::response::Response {
+    let signed = headers.contains_key("authorization") || query.contains_key("X-Amz-Signature");
+    if !signed {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let path = uri.path().trim_start_matches('/').to_string();
+    let mut objects = objects.lock().unwrap();
+    match method {
+        HttpMethod::PUT => {
+            objects.insert(path, body.to_vec());
+            StatusCode::OK.into_response()
+        }
+        HttpMethod::DELETE => {
+            objects.remove(&path);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        HttpMethod::GET if query.get("list-type").map(String::as_str) == Some("2") => {
+            let bucket = path.trim_end_matches('/');
+            let prefix = format!("{}/{}", bucket, query.get("prefix").cloned().unwrap_or_default());
+            let keys: String = objects
+                .keys()
+                .filter(|key| key.starts_with(&prefix))
+                .map(|key| format!("<Contents><Key>{}</Key></Contents>", &key[bucket.len() + 1..]))
+                .collect();
+            format!("<ListBucketResult><IsTruncated>false</IsTruncated>{}</ListBucketResult>", keys).into_response()
+        }
+        HttpMethod::GET => match objects.get(&path) {
+            Some(bytes) => bytes.clone().into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        },
+        _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+    }
+}
+
+/// Start the fake bucket and make it the process's blob store
+async fn start_fake_s3() -> (String, Objects) {
+    let objects = Objects::default();
+    let app = axum::Router::new().fallback(fake_s3).with_state(objects.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let store = S3BlobStore::new(S3Config {
+        bucket: "kicad".to_string(),
+        region: "us-east-1".to_string(),
+        endpoint: Some(endpoint.clone()),
+        access_key_id: "minio".to_string(),
+        secret_access_key: "minio-secret".to_string(),
+        path_style: true,
+    })
+    .unwrap();
+    kicad_db::set_blob_store(Arc::new(store));
+    (endpoint, objects)
+}
+
+#[tokio::test]
+async fn images_live_in_the_bucket_and_download_from_it() {
+    let Some(app) = TestApp::start().await else { return };
+    let (endpoint, objects) = start_fake_s3().await;
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let slug = unique_slug("blobs");
+    app.register(&slug, &remote).await;
+    let image_path = format!("/api/repos/{}/commits/{}/image", encoded(&slug), commit);
+
+    let response = app
+        .request(Method::PUT, &image_path)
+        .header("Content-Type", "image/svg+xml")
+        .body(SVG)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let digest = image_digest(SVG.as_bytes());
+    assert_eq!(body["digest"], digest);
+    let key = format!("kicad/images/{}", digest);
+    assert_eq!(objects.lock().unwrap().get(&key).map(Vec::as_slice), Some(SVG.as_bytes()));
+    let in_postgres: i64 = sqlx::query_scalar("SELECT count(*) FROM blobs").fetch_one(&app.pool).await.unwrap();
+    assert_eq!(in_postgres, 0);
+
+    // The API redirects to a presigned URL, which the client follows
+    let response = app.get(&image_path).await;
+    assert_eq!(response.status(), 200);
+    let url = response.url().to_string();
+    assert!(url.starts_with(&format!("{}/{}?", endpoint, key)), "{}", url);
+    assert!(url.contains("X-Amz-Expires=900") && url.contains("X-Amz-Signature="), "{}", url);
+    assert_eq!(response.text().await.unwrap(), SVG);
+
+    // Scaled copies are still made by the API, from the bucket's bytes
+    let response = app.get(&format!("{}?width=16", image_path)).await;
+    assert_eq!(response.status(), 200);
+    assert!(response.url().path().ends_with("/image"));
+
+    // Purging the unused image deletes the object
+    sqlx::query("UPDATE schematics SET image_digest = NULL").execute(&app.pool).await.unwrap();
+    assert_eq!(purge_orphaned_images(&app.pool).await.unwrap(), 1);
+    assert!(objects.lock().unwrap().is_empty());
+}
@@ -0,0 +1,318 @@
+// End-to-end harness: the whole app, served in-process against a throwaway
+// database, a fake git remote and the mock XAI API.
+//
+// Each TestApp creates its own database (kicad_test_<uuid>) on the server
+// DATABASE_URL points at (kicad_db::DB_URL by default, i.e. the
+// database-up.sh container), applies the migrations and drops it again when
+// the test ends. The role needs CREATEDB, as the container's superuser has.
+// Without a reachable server the tests fail, unless SKIP_DB_TESTS=1 lets
+// them print a warning and pass, like the database crate's integration tests.
+//
+// USAGE:
+// cargo test --test hook --test grok
+// SKIP_DB_TESTS=1 cargo test   (no database server at hand)
+
+#![allow(dead_code)]
+
+use git2::{Oid, Repository, Signature, Time};
+use kicad_backend::auth::{generate_key, hash_key};
+use kicad_backend::config::Config;
+use kicad_backend::services::git;
+use kicad_backend::state::AppState;
+use kicad_db::xai_mock::{MockChatProvider, MockReplies};
+use kicad_db::{ApiScope, PgPool, PromptLibrary};
+use once_cell::sync::Lazy;
+use reqwest::{Method, RequestBuilder, Response};
+use serde_json::{json, Value};
+use sqlx::postgres::PgConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use tempfile::TempDir;
+use tokio::task::JoinHandle;
+
+/// The two-resistor divider from the distiller's reference projects
+pub const TWO_RESISTORS: &str = include_str!("../fixtures/two_resistors.kicad_sch");
+
+/// Both resistors of the divider on one KiCad 5 (legacy EESchema) sheet
+pub const LEGACY_TWO_RESISTORS: &str = include_str!("../fixtures/two_resistors.sch");
+
+/// The divider again, as an Eagle schematic
+pub const EAGLE_TWO_RESISTORS: &str = include_str!("../fixtures/two_resistors_eagle.sch");
+
+/// Clones for every test in the binary; the cache dir can only be set once
+static GIT_CACHE: Lazy<TempDir> = Lazy::new(|| {
+    let dir = TempDir::new().expect("failed to create the git cache dir");
+    git::set_cache_dir(dir.path().to_path_buf());
+    dir
+});
+
+/// A database of its own, dropped with it
+struct TestDatabase {
+    server: PgConnectOptions,
+    name: String,
+}
+
+/// Skip a test that found no database server, if SKIP_DB_TESTS=1 allows
+/// it; otherwise fail it, so a missing database can't pass for green
+pub fn skip_without_database(error: impl std::fmt::Display) {
+    if std::env::var("SKIP_DB_TESTS").is_ok_and(|v| v == "1") {
+        eprintln!("Warning: Could not connect to DB ({}). Skipping end-to-end test (SKIP_DB_TESTS=1).", error);
+    } else {
+        panic!("Could not connect to DB ({}). Run `./database-up.sh` first, or set SKIP_DB_TESTS=1 to skip.", error);
+    }
+}
+
+impl TestDatabase {
+    /// A fresh database with the migrations applied, or None without a
+    /// server when SKIP_DB_TESTS=1
+    async fn create() -> Option<(Self, PgPool)> {
+        let url = std::env::var("DATABASE_URL").unwrap_or_else(|_| kicad_db::DB_URL.to_string());
+        let server = PgConnectOptions::from_str(&url).expect("DATABASE_URL is not a Postgres URL");
+        let mut admin = match server.connect().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                skip_without_database(e);
+                return None;
+            }
+        };
+        let name = format!("kicad_test_{}", uuid::Uuid::new_v4().simple());
+        sqlx::query(&format!("CREATE DATABASE {}", name))
+            .execute(&mut admin)
+            .await
+            .expect("failed to create the test database; does the role have CREATEDB?");
+        let _ = admin.close().await;
+
+        let database = Self { server, name };
+        let pool = PgPool::connect_with(database.server.clone().database(&database.name))
+            .await
+            .expect("failed to connect to the test database");
+        kicad_db::apply_migrations(&pool).await.expect("failed to apply migrations");
+        Some((database, pool))
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        // Drop can't await, and the test's runtime may be shutting down
+        let server = self.server.clone();
+        let name = self.name.clone();
+        let dropped = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+            runtime.block_on(async {
+                let mut admin = server.connect().await?;
+                sqlx::query(&format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", name))
+                    .execute(&mut admin)
+                    .await?;
+                admin.close().await
+            })?;
+            anyhow::Ok(())
+        })
+        .join();
+        if !matches!(dropped, Ok(Ok(()))) {
+            eprintln!("Warning: Could not drop test database {}", self.name);
+        }
+    }
+}
+
+/// A bare repository standing in for GitHub, cloned over file://
+pub struct FakeRemote {
+    dir: TempDir,
+    /// Commit times, one minute apart so history has a stable order
+    clock: i64,
+}
+
+impl FakeRemote {
+    pub fn new() -> Self {
+        let dir = TempDir::new().expect("failed to create the remote's dir");
+        let repo = Repository::init_bare(dir.path()).expect("failed to init the bare remote");
+        repo.set_head("refs/heads/main").expect("failed to point HEAD at main");
+        Self {
+            dir,
+            clock: 1_700_000_000,
+        }
+    }
+
+    pub fn clone_url(&self) -> String {
+        format!("file://{}", self.dir.path().display())
+    }
+
+    /// The bare repository's directory
+    pub fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// Commit `files` (top-level paths and contents) on top of main, keeping
+    /// the files earlier commits added; returns the commit hash
+    pub fn commit(&mut self, files: &[(&str, &str)], message: &str) -> String {
+        self.clock += 60;
+        let repo = Repository::open_bare(self.dir.path()).expect("failed to open the remote");
+        let parent = repo
+            .refname_to_id("refs/heads/main")
+            .ok()
+            .map(|id| repo.find_commit(id).expect("main points at a commit"));
+        let base = parent.as_ref().map(|c| c.tree().expect("commits have trees"));
+        let mut tree = repo.treebuilder(base.as_ref()).expect("failed to start a tree");
+        for (path, content) in files {
+            let blob = repo.blob(content.as_bytes()).expect("failed to write a blob");
+            tree.insert(path, blob, 0o100644).expect("failed to add a file");
+        }
+        let tree = repo.find_tree(tree.write().expect("failed to write the tree")).unwrap();
+        let author = Signature::new("Test Author", "author@example.com", &Time::new(self.clock, 0)).unwrap();
+        let parents: Vec<_> = parent.iter().collect();
+        let id: Oid = repo
+            .commit(Some("refs/heads/main"), &author, &author, message, &tree, &parents)
+            .expect("failed to commit");
+        id.to_string()
+    }
+
+    /// Tag `commit` as `name`
+    pub fn tag(&self, name: &str, commit: &str) {
+        let repo = Repository::open_bare(self.dir.path()).expect("failed to open the remote");
+        let target = repo.find_object(Oid::from_str(commit).unwrap(), None).expect("no such commit");
+        repo.tag_lightweight(name, &target, false).expect("failed to tag");
+    }
+}
+
+/// The app, listening on a local port
+pub struct TestApp {
+    pub addr: SocketAddr,
+    pub pool: PgPool,
+    /// The fake XAI API every AI call goes to
+    pub ai: MockChatProvider,
+    /// An instance-wide admin key
+    pub key: String,
+    client: reqwest::Client,
+    server: JoinHandle<()>,
+    _database: TestDatabase,
+}
+
+impl TestApp {
+    /// Start the app with the default config and mock replies
+    pub async fn start() -> Option<Self> {
+        Self::start_with(Config::default(), MockReplies::default()).await
+    }
+
+    /// Start the app with `config`, its XAI settings pointed at a mock
+    /// answering with `replies`; None without a database server
+    pub async fn start_with(mut config: Config, replies: MockReplies) -> Option<Self> {
+        // Tests that change rows directly would otherwise read stale responses
+        config.response_cache.ttl_secs = 0;
+        Self::serve(config, replies).await
+    }
+
+    /// Start the app with the default config, API responses cached in memory
+    pub async fn start_caching_responses() -> Option<Self> {
+        Self::serve(Config::default(), MockReplies::default()).await
+    }
+
+    async fn serve(mut config: Config, replies: MockReplies) -> Option<Self> {
+        Lazy::force(&GIT_CACHE);
+        let (database, pool) = TestDatabase::create().await?;
+        let ai = MockChatProvider::start(replies).await;
+        config.xai.api_key = "mock-key".to_string();
+        config.xai.base_url = Some(ai.chat_url().to_string());
+        // Every test should reach the mock, not an earlier test's cached answer
+        config.ai_cache_ttl_secs = 0;
+        // The rate limiters are shared by the whole binary, and every test's
+        // admin key is key:1 in its own database, so they would share a budget
+        config.rate_limits.ai_per_minute = 0;
+        // Tests commit to their remotes and expect the next update to see it
+        config.git_fetch_interval_secs = 0;
+        kicad_backend::services::reload::apply(&config);
+
+        let key = generate_key();
+        kicad_db::create_api_key(&pool, "e2e", &key[..8], &hash_key(&key), ApiScope::Admin, None)
+            .await
+            .expect("failed to create the admin key");
+
+        let state = AppState::new(pool.clone(), config, PromptLibrary::builtin()).expect("invalid test config");
+        let app = kicad_backend::app(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .expect("server failed");
+        });
+
+        Some(Self {
+            addr,
+            pool,
+            ai,
+            key,
+            client: reqwest::Client::new(),
+            server,
+            _database: database,
+        })
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// A request with the admin key
+    pub fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.anonymous(method, path).header("X-API-Key", &self.key)
+    }
+
+    /// A request without credentials
+    pub fn anonymous(&self, method: Method, path: &str) -> RequestBuilder {
+        self.client.request(method, self.url(path))
+    }
+
+    pub async fn get(&self, path: &str) -> Response {
+        self.request(Method::GET, path).send().await.expect("request failed")
+    }
+
+    pub async fn post(&self, path: &str, body: Value) -> Response {
+        self.request(Method::POST, path).json(&body).send().await.expect("request failed")
+    }
+
+    /// Register `slug` to be cloned from `remote`
+    pub async fn register(&self, slug: &str, remote: &FakeRemote) {
+        self.register_with(slug, remote, json!({})).await;
+    }
+
+    /// Register `slug` with further RegisterRepoRequest fields from `settings`
+    pub async fn register_with(&self, slug: &str, remote: &FakeRemote, settings: Value) {
+        let mut body = json!({ "repo": slug, "clone_url": remote.clone_url() });
+        if let (Some(body), Value::Object(settings)) = (body.as_object_mut(), settings) {
+            body.extend(settings);
+        }
+        let response = self.post("/api/repos", body).await;
+        assert_eq!(response.status(), 200, "registering {} failed: {}", slug, response.text().await.unwrap());
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// A slug no other test uses, so tests can share the clone cache
+pub fn unique_slug(name: &str) -> String {
+    format!("e2e/{}-{}", name, &uuid::Uuid::new_v4().simple().to_string()[..8])
+}
+
+/// `slug` as a single path segment, e.g. for /api/repos/{repo}
+pub fn encoded(slug: &str) -> String {
+    slug.replace('/', "%2F")
+}
+
+/// The data of an SSE body's events, in order
+pub fn sse_data(body: &str) -> Vec<String> {
+    body.lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data).to_string())
+        .collect()
+}
+
+/// The IDs of an SSE body's events, in order
+pub fn sse_ids(body: &str) -> Vec<String> {
+    body.lines()
+        .filter_map(|line| line.strip_prefix("id:"))
+        .map(|id| id.trim().to_string())
+        .collect()
+}
@@ -0,0 +1,210 @@
+// End-to-end tests of the cross-repo component endpoints and lifecycle
+// alerts; see common/mod.rs for the harness.
+//
+// USAGE:
+// cargo test --test components
+
+mod common;
+
+use axum::{http::header, routing::get, Router};
+use chrono::Utc;
+use common::{encoded, unique_slug, FakeRemote, TestApp, TWO_RESISTORS};
+use kicad_backend::config::Config;
+use kicad_db::xai_mock::MockReplies;
+use kicad_db::{upsert_part_metadata, PartMetadata};
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// The divider with an MPN on both resistors
+fn with_mpn(mpn: &str) -> String {
+    let footprint = "(property \"Footprint\" \"Resistor_SMD:R_0603_1608Metric\"";
+    TWO_RESISTORS.replace(footprint, &format!("(property \"MPN\" \"{}\" (at 0 0 0)) {}", mpn, footprint))
+}
+
+/// A registered repo whose one commit's components are stored
+async fn distilled_repo(app: &TestApp, name: &str, schematic: &str) -> String {
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", schematic)], "Add voltage divider");
+    let slug = unique_slug(name);
+    app.register(&slug, &remote).await;
+    let response = app.post("/api/distill", json!({ "repo": slug, "commit": commit })).await;
+    assert_eq!(response.status(), 200);
+    slug
+}
+
+#[tokio::test]
+async fn usage_lists_the_repos_using_a_part_and_the_parts_they_share() {
+    let Some(app) = TestApp::start().await else { return };
+    let first = distilled_repo(&app, "usage-a", &with_mpn("RC0603FR-0710KL")).await;
+    let second = distilled_repo(&app, "usage-b", &with_mpn("rc0603fr-0710kl ")).await;
+    distilled_repo(&app, "usage-c", &with_mpn("ERJ-3EKF1002V")).await;
+
+    let response = app.get(&format!("/api/components/{}/usage", encoded("Rc0603fr-0710kl"))).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["mpn"], "RC0603FR-0710KL");
+    assert_eq!(body["total_quantity"], 4);
+    let mut repos: Vec<&str> = body["repos"].as_array().unwrap().iter().map(|r| r["repo"].as_str().unwrap()).collect();
+    repos.sort();
+    let mut expected = vec![first.as_str(), second.as_str()];
+    expected.sort();
+    assert_eq!(repos, expected, "{}", body);
+    assert_eq!(body["repos"][0]["references"], json!(["R1", "R2"]));
+    assert_eq!(body["repos"][0]["footprint"], "Resistor_SMD:R_0603_1608Metric");
+
+    let body: Value = app.get("/api/components/usage").await.json().await.unwrap();
+    assert_eq!(body["min_repos"], 2);
+    assert_eq!(body["parts"].as_array().unwrap().len(), 1, "{}", body);
+    assert_eq!(body["parts"][0]["mpn"], "RC0603FR-0710KL");
+    assert_eq!(body["parts"][0]["quantity"], 4);
+    let body: Value = app.get("/api/components/usage?min_repos=1").await.json().await.unwrap();
+    assert_eq!(body["parts"].as_array().unwrap().len(), 2);
+    assert_eq!(app.get("/api/components/usage?min_repos=0").await.status(), 400);
+
+    // Unknown parts are used nowhere
+    let body: Value = app.get("/api/components/NOPE-123/usage").await.json().await.unwrap();
+    assert_eq!((&body["repos"], &body["total_quantity"]), (&json!([]), &json!(0)));
+
+    let response = app.anonymous(Method::GET, "/api/components/usage").send().await.unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn lifecycle_check_flags_parts_going_away() {
+    let Some(app) = TestApp::start().await else { return };
+    let slug = distilled_repo(&app, "alerts", &with_mpn("RC0603FR-0710KL")).await;
+    let alerts_path = format!("/api/repos/{}/alerts", encoded(&slug));
+    let mut metadata = PartMetadata {
+        mpn: "RC0603FR-0710KL".to_string(),
+        supplier: "digikey".to_string(),
+        manufacturer: Some("Yageo".to_string()),
+        description: None,
+        datasheet_url: None,
+        product_url: None,
+        lifecycle_status: Some("Active".to_string()),
+        is_obsolete: false,
+        unit_price: None,
+        quantity_available: None,
+        fetched_at: Utc::now(),
+    };
+    upsert_part_metadata(&app.pool, &metadata).await.unwrap();
+
+    // Active parts raise nothing
+    let response = app.post("/api/admin/lifecycle/check", json!({})).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!((&body["repos"], &body["parts"], &body["open_alerts"]), (&json!(1), &json!(1), &json!(0)));
+    assert_eq!(app.get(&alerts_path).await.json::<Value>().await.unwrap()["alerts"], json!([]));
+
+    metadata.lifecycle_status = Some("Not For New Designs".to_string());
+    upsert_part_metadata(&app.pool, &metadata).await.unwrap();
+    let body: Value = app.post("/api/admin/lifecycle/check", json!({})).await.json().await.unwrap();
+    assert_eq!((&body["open_alerts"], &body["notified"]), (&json!(1), &json!(1)), "{}", body);
+    let body: Value = app.get(&alerts_path).await.json().await.unwrap();
+    let alert = &body["alerts"][0];
+    assert_eq!((&alert["mpn"], &alert["risk"]), (&json!("RC0603FR-0710KL"), &json!("nrnd")), "{}", body);
+    assert_eq!(alert["lifecycle_status"], "Not For New Designs");
+    assert_eq!(alert["designators"], json!(["R1", "R2"]));
+
+    // Announced once; resolved once the status recovers
+    let body: Value = app.post("/api/admin/lifecycle/check", json!({})).await.json().await.unwrap();
+    assert_eq!(body["notified"], 0);
+    metadata.lifecycle_status = Some("Active".to_string());
+    upsert_part_metadata(&app.pool, &metadata).await.unwrap();
+    let body: Value = app.post("/api/admin/lifecycle/check", json!({})).await.json().await.unwrap();
+    assert_eq!(body["resolved"], 1);
+    assert_eq!(app.get(&alerts_path).await.json::<Value>().await.unwrap()["alerts"], json!([]));
+    let body: Value = app.get(&format!("{}?include_resolved=true", alerts_path)).await.json().await.unwrap();
+    assert!(body["alerts"][0]["resolved_at"].is_string(), "{}", body);
+
+    let response = app.get("/api/repos/nobody%2Fnothing/alerts").await;
+    assert_eq!(response.status(), 404);
+    let response = app.anonymous(Method::POST, "/api/admin/lifecycle/check").send().await.unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+/// Metadata for `mpn` pointing at `datasheet_url`
+fn with_datasheet(mpn: &str, datasheet_url: &str) -> PartMetadata {
+    PartMetadata {
+        mpn: mpn.to_string(),
+        supplier: "digikey".to_string(),
+        manufacturer: None,
+        description: None,
+        datasheet_url: Some(datasheet_url.to_string()),
+        product_url: None,
+        lifecycle_status: None,
+        is_obsolete: false,
+        unit_price: None,
+        quantity_available: None,
+        fetched_at: Utc::now(),
+    }
+}
+
+#[tokio::test]
+async fn datasheets_are_proxied_from_a_cache_and_checked() {
+    let config = Config {
+        datasheet_max_mb: 1,
+        ..Config::default()
+    };
+    let Some(app) = TestApp::start_with(config, MockReplies::default()).await else { return };
+    let pdf = format!("%PDF-1.7\n{}\n%%EOF\n", "0".repeat(4096));
+    let downloads = Arc::new(AtomicUsize::new(0));
+    let counted = downloads.clone();
+    let served = pdf.clone();
+    let manufacturer = Router::new()
+        .route(
+            "/lm358.pdf",
+            get(move || async move {
+                counted.fetch_add(1, Ordering::SeqCst);
+                ([(header::CONTENT_TYPE, "application/pdf")], served)
+            }),
+        )
+        .route("/lm358.html", get(|| async { ([(header::CONTENT_TYPE, "text/html")], "<html></html>") }))
+        .route("/fake.pdf", get(|| async { ([(header::CONTENT_TYPE, "application/octet-stream")], "MZ not a PDF") }))
+        .route(
+            "/huge.pdf",
+            get(|| async { ([(header::CONTENT_TYPE, "application/pdf")], format!("%PDF-1.7\n{}", "0".repeat(1024 * 1024))) }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, manufacturer).await.unwrap() });
+
+    upsert_part_metadata(&app.pool, &with_datasheet("LM358DR", &format!("{}/lm358.pdf", url))).await.unwrap();
+    for _ in 0..2 {
+        let response = app.get("/api/components/LM358DR/datasheet").await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "application/pdf");
+        assert_eq!(response.headers()["content-disposition"], "inline; filename=\"LM358DR.pdf\"");
+        assert_eq!(response.text().await.unwrap(), pdf);
+    }
+    assert_eq!(downloads.load(Ordering::SeqCst), 1, "the second request is served from disk");
+
+    // PDF viewers page through with ranges
+    let response = app
+        .request(Method::GET, "/api/components/LM358DR/datasheet")
+        .header("Range", "bytes=0-7")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 206);
+    assert_eq!(response.text().await.unwrap(), "%PDF-1.7");
+
+    for (mpn, path, code) in [
+        ("LM358-HTML", "/lm358.html", "datasheet_not_pdf"),
+        ("LM358-FAKE", "/fake.pdf", "datasheet_not_pdf"),
+        ("LM358-HUGE", "/huge.pdf", "datasheet_too_large"),
+        ("LM358-GONE", "/missing.pdf", "datasheet_unavailable"),
+    ] {
+        upsert_part_metadata(&app.pool, &with_datasheet(mpn, &format!("{}{}", url, path))).await.unwrap();
+        let response = app.get(&format!("/api/components/{}/datasheet", mpn)).await;
+        assert_eq!(response.status(), 502, "{}", mpn);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"], code, "{}", body);
+    }
+
+    upsert_part_metadata(&app.pool, &with_datasheet("LM358-FTP", "ftp://example.com/lm358.pdf")).await.unwrap();
+    assert_eq!(app.get("/api/components/LM358-FTP/datasheet").await.status(), 404);
+    assert_eq!(app.get("/api/components/NOPE-123/datasheet").await.status(), 404);
+}
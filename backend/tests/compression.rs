@@ -0,0 +1,68 @@
+// End-to-end tests of response compression; see common/mod.rs for the
+// harness.
+//
+// USAGE:
+// cargo test --test compression
+
+mod common;
+
+use common::{encoded, unique_slug, FakeRemote, TestApp, TWO_RESISTORS};
+use reqwest::Method;
+use serde_json::json;
+
+#[tokio::test]
+async fn large_text_is_compressed_but_events_are_not() {
+    let Some(app) = TestApp::start().await else { return };
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let slug = unique_slug("compress");
+    app.register(&slug, &remote).await;
+    app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+
+    let path = format!("/api/repos/{}/commits/{}/files/divider.kicad_sch", encoded(&slug), commit);
+    let plain = app.get(&path).await;
+    assert!(plain.headers().get("content-encoding").is_none());
+    let strong = plain.headers()["etag"].to_str().unwrap().to_string();
+    assert!(plain.bytes().await.unwrap().len() > 1024);
+
+    for encoding in ["gzip", "br"] {
+        let response = app
+            .request(Method::GET, &path)
+            .header("Accept-Encoding", encoding)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-encoding"], encoding);
+        // The compressed copy's tag is weak, and still revalidates
+        let etag = response.headers()["etag"].to_str().unwrap().to_string();
+        assert_eq!(etag, format!("W/{}", strong));
+        let response = app
+            .request(Method::GET, &path)
+            .header("Accept-Encoding", encoding)
+            .header("If-None-Match", &etag)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 304);
+    }
+
+    // Small bodies aren't worth it
+    let response = app
+        .request(Method::GET, "/healthz")
+        .header("Accept-Encoding", "gzip")
+        .send()
+        .await
+        .unwrap();
+    assert!(response.headers().get("content-encoding").is_none());
+
+    let response = app
+        .request(Method::GET, &format!("/api/repos/{}/events", encoded(&slug)))
+        .header("Accept-Encoding", "gzip")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/event-stream"));
+    assert!(response.headers().get("content-encoding").is_none());
+}
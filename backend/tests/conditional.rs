@@ -0,0 +1,56 @@
+// End-to-end tests of conditional GETs on the read endpoints; see
+// common/mod.rs for the harness.
+//
+// USAGE:
+// cargo test --test conditional
+
+mod common;
+
+use common::{encoded, unique_slug, FakeRemote, TestApp, TWO_RESISTORS};
+use reqwest::Method;
+use serde_json::json;
+
+#[tokio::test]
+async fn commit_responses_are_revalidated_by_etag() {
+    let Some(app) = TestApp::start().await else { return };
+    let mut remote = FakeRemote::new();
+    let first = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let changed = TWO_RESISTORS.replacen("\"10k\"", "\"4.7k\"", 1);
+    let second = remote.commit(&[("divider.kicad_sch", &changed)], "Change R1 to 4.7k");
+    let slug = unique_slug("etag");
+    app.register(&slug, &remote).await;
+    app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+
+    let path = |commit: &str| format!("/api/repos/{}/commits/{}/netlist", encoded(&slug), commit);
+    let response = app.get(&path(&first)).await;
+    assert_eq!(response.status(), 200);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert!(etag.starts_with('"') && !etag.starts_with("W/"), "{}", etag);
+
+    // The same content gets the same tag, and a matching request no body
+    assert_eq!(app.get(&path(&first)).await.headers()["etag"], etag.as_str());
+    let response = app
+        .request(Method::GET, &path(&first))
+        .header("If-None-Match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 304);
+    assert_eq!(response.headers()["etag"], etag.as_str());
+    assert!(response.bytes().await.unwrap().is_empty());
+
+    // Another commit's netlist differs, so the old tag doesn't match it
+    let response = app
+        .request(Method::GET, &path(&second))
+        .header("If-None-Match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_ne!(response.headers()["etag"], etag.as_str());
+
+    // Errors aren't tagged
+    let response = app.get(&path("0000000000000000000000000000000000000000")).await;
+    assert_ne!(response.status(), 200);
+    assert!(response.headers().get("etag").is_none());
+}
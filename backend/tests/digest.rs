@@ -0,0 +1,140 @@
+// End-to-end tests of the weekly activity digest: its subscribers, what's
+// sent to them and the SendGrid mailer; see common/mod.rs for the harness.
+//
+// USAGE:
+// cargo test --test digest
+
+mod common;
+
+use anyhow::Result;
+use axum::{extract::Request, http::StatusCode, routing::post, Router};
+use chrono::{TimeZone, Utc, Weekday};
+use common::{encoded, unique_slug, FakeRemote, TestApp, TWO_RESISTORS};
+use futures_util::future::BoxFuture;
+use kicad_backend::config::DigestConfig;
+use kicad_backend::services::digest::{send_due, week_end};
+use kicad_backend::services::mail::{Email, Mailer, SendGridMailer};
+use kicad_backend::services::schematic_cache::SchematicCache;
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Keeps what it's asked to send
+#[derive(Default)]
+struct RecordingMailer(Mutex<Vec<Email>>);
+
+impl Mailer for RecordingMailer {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<()>> {
+        self.0.lock().unwrap().push(email.clone());
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[test]
+fn weeks_end_on_the_configured_day_and_hour() {
+    // Friday 2026-10-16 10:30 UTC
+    let now = Utc.with_ymd_and_hms(2026, 10, 16, 10, 30, 0).unwrap();
+    let at = |day, hour| Utc.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap();
+    assert_eq!(week_end(Weekday::Mon, 9, now), at(12, 9));
+    assert_eq!(week_end(Weekday::Fri, 10, now), at(16, 10));
+    // Later today hasn't come yet, so the week ended last Friday
+    assert_eq!(week_end(Weekday::Fri, 11, now), at(9, 11));
+}
+
+#[tokio::test]
+async fn subscribers_get_one_digest_a_week() {
+    let Some(app) = TestApp::start().await else { return };
+    let slug = unique_slug("digest");
+    let path = format!("/api/repos/{}/digest/subscribers", encoded(&slug));
+    let subscribe = |email: &str| app.post(&path, json!({ "email": email }));
+    assert_eq!(subscribe("ada@example.com").await.status(), 404);
+
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    app.register(&slug, &remote).await;
+    app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+
+    assert_eq!(subscribe("not an address").await.status(), 422);
+    assert_eq!(subscribe("Ada@Example.com").await.status(), 200);
+    let response = subscribe("bob@example.com").await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["subscribers"][0]["email"], "ada@example.com");
+    assert_eq!(body["subscribers"].as_array().unwrap().len(), 2);
+    let bob = format!("{}/{}", path, "BOB@example.com");
+    let response = app.request(Method::DELETE, &bob).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["subscribers"].as_array().unwrap().len(), 1);
+    assert_eq!(app.request(Method::DELETE, &bob).send().await.unwrap().status(), 404);
+
+    // The fake remote's commits are from Tuesday 2023-11-14; the hook
+    // stores them without distilling, which the digest does for itself
+    let config = DigestConfig {
+        app_url: Some("https://grokicad.test".to_string()),
+        ..DigestConfig::default()
+    };
+    let mailer = RecordingMailer::default();
+    let schematics = Arc::new(SchematicCache::new(1 << 20));
+    let monday = Utc.with_ymd_and_hms(2023, 11, 20, 10, 0, 0).unwrap();
+    assert_eq!(send_due(&app.pool, &schematics, &config, &mailer, monday).await.unwrap(), 1);
+    let emails = mailer.0.lock().unwrap().clone();
+    assert_eq!(emails[0].to, "ada@example.com");
+    assert_eq!(emails[0].subject, format!("{}: 1 schematic commit this week", slug));
+    let text = &emails[0].text;
+    assert!(text.contains("2023-11-13 to 2023-11-20"), "{}", text);
+    assert!(text.contains(&format!("{} 2023-11-14 Test Author: Add voltage divider", &commit[..7])), "{}", text);
+    assert!(text.contains("Added: R1 (10k), R2 (10k)"), "{}", text);
+    let html = &emails[0].html;
+    assert!(html.contains(&format!("https://github.com/{}/commit/{}", slug, commit)), "{}", html);
+    assert!(html.contains(&format!("https://grokicad.test/?github=https://github.com/{}", slug)), "{}", html);
+
+    // Sent once per week, and a week without commits sends nothing
+    assert_eq!(send_due(&app.pool, &schematics, &config, &mailer, monday).await.unwrap(), 0);
+    let next_monday = monday + chrono::Duration::weeks(1);
+    assert_eq!(send_due(&app.pool, &schematics, &config, &mailer, next_monday).await.unwrap(), 0);
+    assert_eq!(mailer.0.lock().unwrap().len(), 1);
+
+    let response = app.get(&format!("/api/repos/{}/digest/preview", encoded(&slug))).await;
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/html"));
+    assert!(response.text().await.unwrap().contains("No schematic commits this week."));
+}
+
+#[tokio::test]
+async fn sendgrid_gets_both_parts_of_the_email() {
+    let received: Arc<Mutex<Vec<(String, Value)>>> = Default::default();
+    let recorded = received.clone();
+    let sendgrid = Router::new().route(
+        "/v3/mail/send",
+        post(move |request: Request| async move {
+            let auth = request.headers()["authorization"].to_str().unwrap().to_string();
+            let body = axum::body::to_bytes(request.into_body(), usize::MAX).await.unwrap();
+            recorded.lock().unwrap().push((auth, serde_json::from_slice(&body).unwrap()));
+            StatusCode::ACCEPTED
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, sendgrid).await.unwrap() });
+
+    let mailer = SendGridMailer::new(&url, "sg-key", "Grokicad <digest@grokicad.test>").unwrap();
+    let email = Email {
+        to: "ada@example.com".to_string(),
+        subject: "acme/board: 1 schematic commit this week".to_string(),
+        html: "<p>Hi</p>".to_string(),
+        text: "Hi".to_string(),
+    };
+    mailer.send(&email).await.unwrap();
+
+    let (auth, body) = received.lock().unwrap()[0].clone();
+    assert_eq!(auth, "Bearer sg-key");
+    assert_eq!(body["personalizations"][0]["to"][0]["email"], "ada@example.com");
+    assert_eq!(body["from"], json!({ "email": "digest@grokicad.test", "name": "Grokicad" }));
+    assert_eq!(body["subject"], "acme/board: 1 schematic commit this week");
+    assert_eq!(body["content"][0], json!({ "type": "text/plain", "value": "Hi" }));
+    assert_eq!(body["content"][1], json!({ "type": "text/html", "value": "<p>Hi</p>" }));
+}
@@ -0,0 +1,1337 @@
+// End-to-end tests of the AI endpoints against the mock XAI API; see
+// common/mod.rs for the harness.
+//
+// USAGE:
+// cargo test --test grok
+
+mod common;
+
+use common::{encoded, sse_data, sse_ids, unique_slug, FakeRemote, TestApp, TWO_RESISTORS};
+use kicad_backend::config::{Config, ExperimentConfig, ExperimentVariant, FallbackProvider, RoutingConfig, XaiConfig};
+use kicad_db::messages::Sampling;
+use kicad_db::xai_mock::{MockChatProvider, MockReplies};
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// A registered repo with one schematic commit, tagged v1.0
+async fn registered_repo(app: &TestApp, name: &str) -> (String, String, FakeRemote) {
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    remote.tag("v1.0", &commit);
+    let slug = unique_slug(name);
+    app.register(&slug, &remote).await;
+    (slug, commit, remote)
+}
+
+#[tokio::test]
+async fn commit_summary_comes_from_the_ai() {
+    let replies = MockReplies {
+        response_text: "Adds a 10k/10k divider halving the input.".to_string(),
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(Default::default(), replies).await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "summary").await;
+
+    // Tags resolve to the commit they point at
+    let response = app
+        .post("/api/grok/summary/commit", json!({ "repo": slug, "commit": "v1.0" }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["commit"], commit);
+    assert!(
+        body["summary"].as_str().unwrap().contains("halving the input"),
+        "summary should carry the AI's answer: {}",
+        body
+    );
+
+    let requests = app.ai.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/v1/responses");
+    assert!(requests[0].body.to_string().contains(&commit), "the prompt should name the commit");
+}
+
+#[tokio::test]
+async fn selection_stream_relays_the_ai_stream() {
+    let replies = MockReplies {
+        stream_chunks: vec!["R1 and R2".to_string(), " form a divider.".to_string()],
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(Default::default(), replies).await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "selection").await;
+
+    let response = app
+        .request(Method::POST, "/api/grok/selection/stream")
+        .header("Accept", "text/event-stream")
+        .json(&json!({
+            "repo": slug,
+            "commit": &commit[..7],
+            "component_ids": ["R1", "R2"],
+            "query": "What do these do?",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let data = sse_data(&response.text().await.unwrap());
+    assert_eq!(data.first().map(String::as_str), Some("R1 and R2"));
+    assert!(data.iter().any(|d| d == " form a divider."));
+    assert_eq!(data.last().map(String::as_str), Some("[DONE]"));
+
+    let requests = app.ai.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/v1/chat/completions");
+    assert_eq!(requests[0].body["stream"], true);
+    let prompt = requests[0].body["messages"].to_string();
+    assert!(prompt.contains("R1") && prompt.contains("What do these do?"));
+}
+
+#[tokio::test]
+async fn selection_context_is_cut_to_fit_the_context_window() {
+    let mut config = Config::default();
+    // Room for the instructions, the question and the reserved output, and
+    // a little context
+    config.models.context_windows.insert("grok-4-1-fast".to_string(), 6_000);
+    let Some(app) = TestApp::start_with(config, MockReplies::default()).await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "budget").await;
+    let selection = json!({ "repo": slug, "commit": commit, "component_ids": ["R1", "R2"], "query": "What do these do?" });
+
+    let response = app.post("/api/grok/selection/stream", selection.clone()).await;
+    assert_eq!(response.status(), 200);
+    response.text().await.unwrap();
+    let prompt = app.ai.requests()[0].body["messages"].to_string();
+    assert!(prompt.contains("left out to fit the model's context window"), "{}", prompt);
+    assert!(prompt.contains("What do these do?"));
+
+    // A model whose window can't even hold the question isn't called
+    let mut config = Config::default();
+    config.models.context_windows.insert("grok-4-1-fast".to_string(), 1_000);
+    let Some(app) = TestApp::start_with(config, MockReplies::default()).await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "overflow").await;
+    let selection = json!({ "repo": slug, "commit": commit, "component_ids": ["R1"], "query": "What is R1?" });
+    let response = app.post("/api/grok/selection/stream", selection).await;
+    assert_eq!(response.status(), 400);
+    assert!(app.ai.requests().is_empty());
+}
+
+#[tokio::test]
+async fn repo_overview_merges_sheet_summaries_and_reuses_them() {
+    let Some(app) = TestApp::start().await else { return };
+    let (slug, _commit, _remote) = registered_repo(&app, "overview").await;
+
+    let response = app.post("/api/grok/summary/repo", json!({ "repo": slug })).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["summary"], "Hello from the mock.");
+    assert!(body["details"].as_str().unwrap().contains("## /\nHello from the mock."), "{}", body);
+
+    // The one sheet fits one chunk, so its summary goes straight into the overview
+    let requests = app.ai.requests();
+    assert_eq!(requests.len(), 2);
+    let chunk = requests[0].body["messages"][0]["content"].as_str().unwrap();
+    assert!(chunk.starts_with("Below is part 1 of 1 of the sheet /"), "{}", chunk);
+    assert!(chunk.contains("\nR1 (ic, Device:R, 10k): 1="), "{}", chunk);
+    let overview = requests[1].body["messages"][0]["content"].as_str().unwrap();
+    assert!(overview.contains("### /\nHello from the mock."), "{}", overview);
+
+    // Nothing changed, so every step comes from the cache
+    let response = app.post("/api/grok/summary/repo", json!({ "repo": slug })).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(app.ai.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn repo_overview_summarizes_in_chunks_when_the_window_is_small() {
+    let mut config = Config::default();
+    // Too small for both components in one chunk
+    config.models.context_windows.insert("grok-4-1-fast".to_string(), 680);
+    let Some(app) = TestApp::start_with(config, MockReplies::default()).await else { return };
+    let (slug, _commit, _remote) = registered_repo(&app, "chunked").await;
+
+    let response = app
+        .post("/api/grok/summary/repo", json!({ "repo": slug, "detail_level": "brief" }))
+        .await;
+    assert_eq!(response.status(), 200, "{}", response.text().await.unwrap());
+    let prompts: Vec<String> = app
+        .ai
+        .requests()
+        .iter()
+        .map(|r| r.body["messages"][0]["content"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(prompts.len(), 4, "{:#?}", prompts);
+    // The chunks are summarized concurrently, so either may reach the mock first
+    let mut chunks = prompts[..2].to_vec();
+    chunks.sort();
+    assert!(chunks[0].starts_with("Below is part 1 of 2 of the sheet /"));
+    assert!(chunks[1].starts_with("Below is part 2 of 2 of the sheet /"));
+    assert!(prompts[2].starts_with("These are summaries of the parts of the sheet /"));
+    assert!(prompts[3].starts_with("Give an overview of the KiCad hardware project"));
+}
+
+#[tokio::test]
+async fn ai_calls_fail_over_to_the_next_provider() {
+    let backup = MockChatProvider::start(MockReplies::default()).await;
+    let mut config = Config::default();
+    config.xai.fallbacks.push(FallbackProvider {
+        name: "backup".to_string(),
+        base_url: backup.chat_url().to_string(),
+        api_key: "backup-key".to_string(),
+        api_key_env: None,
+        models: HashMap::from([("grok-4-1-fast".to_string(), "backup-fast".to_string())]),
+    });
+    let replies = MockReplies {
+        error: Some((503, "overloaded".to_string())),
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(config, replies).await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "failover").await;
+
+    let selection = json!({ "repo": slug, "commit": commit, "component_ids": ["R1"], "query": "What is R1?" });
+    let response = app.post("/api/grok/selection/stream", selection).await;
+    assert_eq!(response.status(), 200);
+    let data = sse_data(&response.text().await.unwrap());
+    assert_eq!(data.first().map(String::as_str), Some("Hello"));
+    assert_eq!(app.ai.requests().len(), 1);
+
+    // The backup is asked for its own name of the model
+    let requests = backup.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].body["model"], "backup-fast");
+
+    // and the call is charged to it
+    let response = app.get("/api/admin/costs").await;
+    assert_eq!(response.status(), 200);
+    let report: Value = response.json().await.unwrap();
+    assert_eq!(report["by_provider"][0]["key"], "backup", "{}", report);
+    assert_eq!(report["by_provider"][0]["calls"], 1);
+    assert_eq!(report["by_model"][0]["key"], "backup-fast");
+}
+
+#[tokio::test]
+async fn bad_requests_never_reach_the_ai() {
+    let Some(app) = TestApp::start().await else { return };
+    let (slug, _commit, _remote) = registered_repo(&app, "rejected").await;
+
+    let response = app
+        .post("/api/grok/summary/commit", json!({ "repo": slug, "commit": "no-such-branch" }))
+        .await;
+    assert_eq!(response.status(), 404);
+
+    let response = app
+        .post("/api/grok/summary/selection", json!({ "repo": slug, "commit": "main", "component_ids": [] }))
+        .await;
+    assert_eq!(response.status(), 422);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["violations"][0]["field"], "component_ids");
+
+    let response = app
+        .anonymous(Method::POST, "/api/grok/summary/commit")
+        .json(&json!({ "repo": slug, "commit": "main" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+
+    assert!(app.ai.requests().is_empty());
+}
+
+#[tokio::test]
+async fn thinking_mode_streams_reasoning_as_its_own_events() {
+    let Some(app) = TestApp::start().await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "reasoning").await;
+
+    let response = app
+        .request(Method::POST, "/api/grok/selection/stream")
+        .json(&json!({
+            "repo": slug,
+            "commit": commit,
+            "component_ids": ["R1"],
+            "query": "Why 10k?",
+            "thinking_mode": true,
+            "reasoning_effort": "high",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("event: reasoning\ndata: The user\n"), "{}", body);
+    // The answer itself is only the unnamed data events
+    let answer: Vec<&str> = body
+        .split("\n\n")
+        .filter(|event| !event.starts_with("event:"))
+        .filter_map(|event| event.strip_prefix("data: "))
+        .collect();
+    assert_eq!(answer, ["Hello", " from", " the mock.", "[DONE]"]);
+    assert_eq!(app.ai.requests()[0].body["reasoning_effort"], "high");
+
+    // Without thinking mode the model isn't asked to reason
+    let response = app
+        .post(
+            "/api/grok/selection/stream",
+            json!({ "repo": slug, "commit": commit, "component_ids": ["R1"], "query": "Why 10k?" }),
+        )
+        .await;
+    assert!(!response.text().await.unwrap().contains("event: reasoning"));
+    assert!(app.ai.requests()[1].body.get("reasoning_effort").is_none());
+}
+
+#[tokio::test]
+async fn requests_carry_the_configured_sampling_settings() {
+    let mut config = Config::default();
+    config.sampling.selection = Sampling {
+        temperature: Some(0.9),
+        top_p: Some(0.5),
+        max_tokens: None,
+        stop: vec!["END".to_string()],
+    };
+    let Some(app) = TestApp::start_with(config, MockReplies::default()).await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "sampling").await;
+
+    let body = json!({ "repo": slug, "commit": commit, "component_ids": ["R1"], "query": "Why 10k?" });
+    app.post("/api/grok/selection/stream", body).await.text().await.unwrap();
+    let sent = &app.ai.requests()[0].body;
+    assert_eq!((&sent["temperature"], &sent["top_p"]), (&json!(0.9), &json!(0.5)));
+    assert_eq!(sent["stop"], json!(["END"]));
+
+    // A persona's temperature wins over the default summary settings
+    let body = json!({ "repo": slug, "commit": commit, "persona": "teaching-assistant" });
+    app.post("/api/grok/summary/commit/stream", body).await.text().await.unwrap();
+    let sent = &app.ai.requests()[1].body;
+    assert_eq!(sent["temperature"], json!(0.7));
+    assert!(sent.get("top_p").is_none() && sent.get("stop").is_none());
+}
+
+#[tokio::test]
+async fn backfills_submit_deferred_summaries_and_collect_them_after_a_restart() {
+    let replies = MockReplies {
+        completion: "Adds a voltage divider.".to_string(),
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(Default::default(), replies).await else { return };
+    let mut remote = FakeRemote::new();
+    let first = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let touched = format!("{}\n", TWO_RESISTORS);
+    let second = remote.commit(&[("divider.kicad_sch", touched.as_str())], "Reformat");
+    let slug = unique_slug("backfill");
+    app.register(&slug, &remote).await;
+    let repo_url = format!("https://github.com/{}.git", slug);
+
+    // The first commit's request went out before a restart
+    sqlx::query(
+        "INSERT INTO deferred_completions (request_id, kind, repo_url, commit_hash, model, prompt_version) \
+         VALUES ('deferred-before-restart', 'commit_summary', $1, $2, 'grok-4-1-fast', 'commit_summary@v0')",
+    )
+    .bind(&repo_url)
+    .bind(&first)
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    let response = app.post(&format!("/api/grok/summary/backfill/{}", slug), json!({})).await;
+    assert_eq!(response.status(), 200);
+    let events = sse_data(&response.text().await.unwrap());
+    let last: Value = serde_json::from_str(&events[events.len() - 2]).unwrap();
+    assert_eq!(last["status"], "completed", "{}", last);
+    assert_eq!(last["done"], 2);
+
+    // Only the second commit was submitted; the first was collected
+    let requests = app.ai.requests();
+    let submitted: Vec<&Value> = requests.iter().filter(|r| r.method == "POST").map(|r| &r.body).collect();
+    assert_eq!(submitted.len(), 1);
+    assert_eq!(submitted[0]["deferred"], true);
+    assert!(submitted[0]["messages"].to_string().contains(&second));
+    assert!(requests.iter().any(|r| r.path == "/v1/chat/deferred-completion/deferred-before-restart"));
+
+    let stored: Vec<(String, Option<String>, Option<String>)> = sqlx::query_as(
+        "SELECT commit_hash, change_summary, prompt_version FROM schematics WHERE repo_url = $1 ORDER BY commit_hash",
+    )
+    .bind(&repo_url)
+    .fetch_all(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(stored.len(), 2);
+    for (commit, summary, prompt_version) in &stored {
+        assert_eq!(summary.as_deref(), Some("Adds a voltage divider."), "{}", commit);
+        if commit == &first {
+            assert_eq!(prompt_version.as_deref(), Some("commit_summary@v0"));
+        }
+    }
+    let in_flight: i64 = sqlx::query_scalar("SELECT count(*) FROM deferred_completions WHERE repo_url = $1")
+        .bind(&repo_url)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(in_flight, 0);
+}
+
+#[tokio::test]
+async fn interactive_summaries_go_ahead_of_a_backfill_waiting_for_the_rate_limit() {
+    // Two calls a minute: the backfill's first call leaves it one short of
+    // the half it keeps for more urgent work
+    let config = Config {
+        xai: XaiConfig {
+            calls_per_minute: 2,
+            deferred_backfill: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let Some(app) = TestApp::start_with(config, MockReplies::default()).await else { return };
+    let mut remote = FakeRemote::new();
+    remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let touched = format!("{}\n", TWO_RESISTORS);
+    remote.commit(&[("divider.kicad_sch", touched.as_str())], "Reformat");
+    let slug = unique_slug("priority");
+    app.register(&slug, &remote).await;
+
+    let response = app.post(&format!("/api/grok/summary/backfill/{}", slug), json!({})).await;
+    assert_eq!(response.status(), 200);
+    drop(response);
+
+    let waiting = |overview: &Value, class: &str| {
+        let classes = overview["queue"]["classes"].as_array().unwrap();
+        let depth = classes.iter().find(|c| c["class"] == class).expect("every class is listed");
+        depth["ai_calls_waiting"].as_u64().unwrap()
+    };
+    let mut overview: Value = app.get("/api/admin/overview").await.json().await.unwrap();
+    for _ in 0..50 {
+        if waiting(&overview, "backfill") == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        overview = app.get("/api/admin/overview").await.json().await.unwrap();
+    }
+    assert_eq!(waiting(&overview, "backfill"), 1, "{}", overview);
+    assert_eq!(app.ai.requests().len(), 1);
+    let listed: Value = app.get(&format!("/api/admin/jobs?repo={}", slug)).await.json().await.unwrap();
+    let job = &listed["jobs"][0];
+    assert_eq!(job["class"], "backfill", "{}", listed);
+    assert_eq!(job["status"], "running");
+    assert_eq!(job["remaining"], 1);
+    // Other tests' backfills run in this process too
+    let classes = overview["queue"]["classes"].as_array().unwrap();
+    let backfills = classes.iter().find(|c| c["class"] == "backfill").unwrap();
+    assert!(backfills["running_backfills"].as_u64().unwrap() >= 1, "{}", overview);
+    assert!(backfills["backfill_commits_remaining"].as_u64().unwrap() >= 1, "{}", overview);
+
+    // A user's summary of the commit the backfill is waiting on takes the last call
+    let commit = job["current"].as_str().unwrap();
+    let response = app.post("/api/grok/summary/commit", json!({ "repo": slug, "commit": commit })).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(app.ai.requests().len(), 2);
+    let overview: Value = app.get("/api/admin/overview").await.json().await.unwrap();
+    assert_eq!(waiting(&overview, "backfill"), 1, "{}", overview);
+    assert_eq!(waiting(&overview, "interactive"), 0);
+}
+
+#[tokio::test]
+async fn admins_follow_cancel_and_retry_backfill_jobs() {
+    // Deferred summaries that never finish keep the job on its first commit
+    let replies = MockReplies {
+        deferred_polls: usize::MAX,
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(Default::default(), replies).await else { return };
+    let mut remote = FakeRemote::new();
+    let first = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let touched = format!("{}\n", TWO_RESISTORS);
+    let second = remote.commit(&[("divider.kicad_sch", touched.as_str())], "Reformat");
+    let slug = unique_slug("jobs");
+    app.register(&slug, &remote).await;
+
+    let response = app.post(&format!("/api/grok/summary/backfill/{}", slug), json!({})).await;
+    assert_eq!(response.status(), 200);
+    drop(response);
+
+    let job_detail = |id: u64| {
+        let app = &app;
+        async move {
+            let response = app.get(&format!("/api/admin/jobs/{}", id)).await;
+            assert_eq!(response.status(), 200);
+            response.json::<Value>().await.unwrap()
+        }
+    };
+    let listed: Value = app.get(&format!("/api/admin/jobs?repo={}", slug)).await.json().await.unwrap();
+    let jobs = listed["jobs"].as_array().unwrap();
+    assert_eq!(jobs.len(), 1, "{}", listed);
+    let id = jobs[0]["job_id"].as_u64().unwrap();
+    assert_eq!(jobs[0]["status"], "running");
+
+    let mut detail = job_detail(id).await;
+    for _ in 0..50 {
+        if detail["commits"][0]["status"] == "running" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        detail = job_detail(id).await;
+    }
+    let commits: Vec<(&str, &str)> = detail["commits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| (c["commit"].as_str().unwrap(), c["status"].as_str().unwrap()))
+        .collect();
+    let mut expected = [(first.as_str(), "running"), (second.as_str(), "pending")];
+    if commits[0].0 != first {
+        expected = [(second.as_str(), "running"), (first.as_str(), "pending")];
+    }
+    assert_eq!(commits, expected, "{}", detail);
+
+    let response = app.get("/api/admin/jobs?status=paused").await;
+    assert_eq!(response.status(), 400);
+    let response = app.request(Method::POST, &format!("/api/admin/jobs/{}/retry", id)).send().await.unwrap();
+    assert_eq!(response.status(), 409);
+
+    // Cancelling stops the wait for the deferred summary
+    let response = app.request(Method::POST, &format!("/api/admin/jobs/{}/cancel", id)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let cancelling: Value = response.json().await.unwrap();
+    assert_eq!(cancelling["cancel_requested"], true);
+    for _ in 0..50 {
+        detail = job_detail(id).await;
+        if detail["job"]["status"] != "running" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(detail["job"]["status"], "cancelled", "{}", detail);
+    assert!(detail["job"]["finished_at"].is_string());
+    assert!(detail["commits"].as_array().unwrap().iter().all(|c| c["status"] == "pending"));
+    let response = app.request(Method::POST, &format!("/api/admin/jobs/{}/cancel", id)).send().await.unwrap();
+    assert_eq!(response.status(), 409);
+
+    let listed: Value = app
+        .get(&format!("/api/admin/jobs?repo={}&status=cancelled", slug))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed["jobs"].as_array().unwrap().len(), 1);
+
+    // A retry is a new job of the same repo
+    let response = app.request(Method::POST, &format!("/api/admin/jobs/{}/retry", id)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let retried: Value = response.json().await.unwrap();
+    let retry_id = retried["job_id"].as_u64().unwrap();
+    assert_ne!(retry_id, id);
+    assert_eq!(retried["repo"], slug.as_str());
+    assert_eq!(retried["status"], "running");
+    let response = app.request(Method::POST, &format!("/api/admin/jobs/{}/retry", id)).send().await.unwrap();
+    assert_eq!(response.status(), 409, "the repo has a job running");
+    let response = app.request(Method::POST, &format!("/api/admin/jobs/{}/cancel", retry_id)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    assert_eq!(app.get("/api/admin/jobs/999999999").await.status(), 404);
+}
+
+#[tokio::test]
+async fn audited_prompts_are_recorded_redacted() {
+    let mut config = Config::default();
+    config.audit.enabled = true;
+    config.audit.redact_patterns = vec![r"PROJ-\d{4}".to_string()];
+    let replies = MockReplies {
+        stream_chunks: vec!["R1 and R2".to_string(), " form a divider.".to_string()],
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(config, replies).await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "audit").await;
+
+    let query = "Using key xai-0123456789abcdefghij for PROJ-1234, what do these do?";
+    let selection = json!({ "repo": slug, "commit": commit, "component_ids": ["R1", "R2"], "query": query });
+    let response = app.post("/api/grok/selection/stream", selection).await;
+    assert_eq!(response.status(), 200);
+    response.text().await.unwrap();
+    // The prompt itself goes out as written
+    assert!(app.ai.requests()[0].body.to_string().contains("PROJ-1234"));
+
+    // Entries are written in the background
+    let mut listed = Value::Null;
+    for _ in 0..50 {
+        listed = app.get(&format!("/api/admin/prompts?repo={}", slug)).await.json().await.unwrap();
+        if !listed["prompts"].as_array().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let prompts = listed["prompts"].as_array().unwrap();
+    assert_eq!(prompts.len(), 1, "{}", listed);
+    let entry = &prompts[0];
+    assert_eq!(entry["endpoint"], "chat_completion_stream");
+    assert_eq!(entry["provider"], "xai");
+    assert_eq!(entry["repo"], slug.as_str());
+    assert_eq!(entry["response"], "R1 and R2 form a divider.");
+    assert_eq!(entry["error"], Value::Null);
+    assert_eq!(entry["prompt_tokens"], 10);
+    assert_eq!(entry["completion_tokens"], 5);
+    let messages = entry["messages"].to_string();
+    assert!(messages.contains("what do these do?"), "{}", messages);
+    assert!(!messages.contains("xai-0123456789") && !messages.contains("PROJ-1234"), "{}", messages);
+    assert!(messages.contains("[REDACTED]"), "{}", messages);
+
+    let response = app.get("/api/admin/prompts?limit=0").await;
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn selections_of_nets_sheets_and_areas_resolve_to_their_components() {
+    let Some(app) = TestApp::start().await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "selections").await;
+    let ask = |selection: Value| {
+        let app = &app;
+        let body = json!({ "repo": slug, "commit": commit, "selection": selection, "query": "What is this?" });
+        async move { app.post("/api/grok/selection/stream", body).await }
+    };
+    let last_prompt = || app.ai.requests().last().unwrap().body["messages"].to_string();
+
+    // R1 sits at (102.87, 68.58), R2 at (118.11, 68.58)
+    let response = ask(json!({ "kind": "area", "x1": 110, "y1": 60, "x2": 100, "y2": 75 })).await;
+    assert_eq!(response.status(), 200);
+    response.text().await.unwrap();
+    let prompt = last_prompt();
+    assert!(prompt.contains("## Selected Area") && prompt.contains("**R1**"), "{}", prompt);
+    assert!(!prompt.contains("**R2**"), "{}", prompt);
+
+    let response = ask(json!({ "kind": "sheet", "path": "/" })).await;
+    assert_eq!(response.status(), 200);
+    response.text().await.unwrap();
+    let prompt = last_prompt();
+    assert!(prompt.contains("## Selected Sheet /") && prompt.contains("**R1**") && prompt.contains("**R2**"));
+
+    let response = ask(json!({ "kind": "nets", "names": ["unconnected-(R2-Pad1)"] })).await;
+    assert_eq!(response.status(), 200);
+    response.text().await.unwrap();
+    let prompt = last_prompt();
+    assert!(prompt.contains("**unconnected-(R2-Pad1)** connects R2 pin 1"), "{}", prompt);
+    assert!(!prompt.contains("**R1**"), "{}", prompt);
+
+    // What the schematic doesn't have isn't sent to the model
+    let calls = app.ai.requests().len();
+    for selection in [
+        json!({ "kind": "nets", "names": ["/NOPE"] }),
+        json!({ "kind": "sheet", "path": "/Power/" }),
+        json!({ "kind": "area", "x1": 0, "y1": 0, "x2": 10, "y2": 10 }),
+    ] {
+        assert_eq!(ask(selection).await.status(), 404);
+    }
+    let response = app
+        .post(
+            "/api/grok/summary/selection",
+            json!({ "repo": slug, "commit": commit, "component_ids": ["R1"], "selection": { "kind": "sheet", "path": "/" } }),
+        )
+        .await;
+    assert_eq!(response.status(), 422);
+
+    let response = app
+        .post(
+            "/api/grok/summary/selection",
+            json!({ "repo": slug, "commit": commit, "selection": { "kind": "sheet", "path": "" } }),
+        )
+        .await;
+    assert_eq!(response.status(), 422);
+    assert_eq!(app.ai.requests().len(), calls);
+
+    // The summary reports the components a selection stands for
+    let response = app
+        .post(
+            "/api/grok/summary/selection",
+            json!({ "repo": slug, "commit": commit, "selection": { "kind": "sheet", "path": "/" } }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["component_ids"], json!(["R1", "R2"]));
+}
+
+#[tokio::test]
+async fn commit_comparisons_are_narrated_and_stored() {
+    let replies = MockReplies {
+        stream_chunks: vec!["R2 dropped to 4.7k, ".to_string(), "so the divider no longer halves.".to_string()],
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(Default::default(), replies).await else { return };
+    let (slug, base, mut remote) = registered_repo(&app, "compare").await;
+    let (before, after) = TWO_RESISTORS.split_at(TWO_RESISTORS.find("\"R2\"").unwrap());
+    let changed = format!("{}{}", before, after.replacen("\"10k\"", "\"4.7k\"", 1));
+    remote.commit(&[("notes.md", "Divider ratio to be tuned")], "Add notes");
+    let head = remote.commit(&[("divider.kicad_sch", &changed)], "Lower R2");
+    let compare = |body: Value| {
+        let app = &app;
+        async move { app.post("/api/grok/summary/compare", body).await }
+    };
+
+    let response = compare(json!({ "repo": slug, "base": "v1.0", "head": head })).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["base"], base);
+    assert_eq!(body["commit_count"], 2);
+    assert_eq!(body["cached"], false);
+    assert!(body["summary"].as_str().unwrap().contains("no longer halves"), "{}", body);
+    assert!(body["changes"].as_str().unwrap().contains("R2"), "{}", body);
+    let prompt = app.ai.requests()[0].body["messages"].to_string();
+    assert!(prompt.contains("Add notes") && prompt.contains("Lower R2"), "{}", prompt);
+    assert!(prompt.contains("4.7k"), "the prompt should carry the diff: {}", prompt);
+
+    // Asked again, the stored comparison comes back without a model call
+    let response = compare(json!({ "repo": slug, "base": base, "head": head })).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["cached"], true);
+    assert_eq!(app.ai.requests().len(), 1);
+
+    let response = compare(json!({ "repo": slug, "base": base, "head": head, "refresh": true })).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["cached"], false);
+    assert_eq!(app.ai.requests().len(), 2);
+
+    let response = compare(json!({ "repo": slug, "base": head, "head": head })).await;
+    assert_eq!(response.status(), 400);
+    let response = compare(json!({ "repo": slug, "base": "", "head": head })).await;
+    assert_eq!(response.status(), 422);
+    assert_eq!(app.ai.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn release_notes_cover_the_hardware_commits_between_two_tags() {
+    let replies = MockReplies {
+        stream_chunks: vec!["## Bug fixes\n".to_string(), "- Divider ratio corrected (abc1234)".to_string()],
+        // Blurb candidates after the first; the shortest naming R2 wins
+        choices: vec!["Lowers R2 to fix the divider ratio".to_string(), "Fixes the divider".to_string()],
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(Default::default(), replies).await else { return };
+    let (slug, base, mut remote) = registered_repo(&app, "release").await;
+    let (before, after) = TWO_RESISTORS.split_at(TWO_RESISTORS.find("\"R2\"").unwrap());
+    let changed = format!("{}{}", before, after.replacen("\"10k\"", "\"4.7k\"", 1));
+    remote.commit(&[("notes.md", "Divider ratio to be tuned")], "Add notes");
+    let head = remote.commit(&[("divider.kicad_sch", &changed)], "Lower R2");
+    remote.tag("v1.1", &head);
+
+    let response = app
+        .post("/api/grok/release-notes", json!({ "repo": slug, "from_tag": "v1.0", "to_tag": "v1.1" }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["from_commit"], base);
+    assert_eq!(body["to_commit"], head);
+    assert!(body["markdown"].as_str().unwrap().starts_with("## Bug fixes"), "{}", body);
+
+    // Only the schematic commit counts; its missing blurb was generated first
+    let changes = body["changes"].as_array().unwrap();
+    assert_eq!(changes.len(), 1, "{}", body);
+    assert_eq!(changes[0]["commit"], head);
+    assert_eq!(changes[0]["generated"], true);
+    assert_eq!(changes[0]["category"], "bugfixes");
+    assert_eq!(changes[0]["blurb"], "Lowers R2 to fix the divider ratio");
+    let requests = app.ai.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].body["n"], 3);
+    assert!(requests[0].body["messages"].to_string().contains("4.7k"), "{}", requests[0].body);
+    let prompt = requests[1].body["messages"].to_string();
+    assert!(prompt.contains("### Bug fixes") && prompt.contains("Lower R2"), "{}", prompt);
+
+    // The generated blurb is stored, so the next request reuses it
+    let response = app
+        .post("/api/grok/release-notes", json!({ "repo": slug, "from_tag": "v1.0", "to_tag": "v1.1" }))
+        .await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["changes"][0]["generated"], false);
+    assert_eq!(app.ai.requests().len(), 3);
+
+    let response = app
+        .post("/api/grok/release-notes", json!({ "repo": slug, "from_tag": "v1.1", "to_tag": head }))
+        .await;
+    assert_eq!(response.status(), 400);
+    let response = app
+        .post("/api/grok/release-notes", json!({ "repo": slug, "from_tag": "v1.0", "to_tag": "v1.2" }))
+        .await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn review_checklists_are_grounded_in_the_diff_and_keep_their_ticks() {
+    let replies = MockReplies {
+        stream_chunks: vec![
+            "- [signal] Verify R2 at 4.7k against R1 still sets the intended ratio\n".to_string(),
+            "- [Bogus] Re-run ERC after the change\n".to_string(),
+            "Let me know if you need more.".to_string(),
+        ],
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(Default::default(), replies).await else { return };
+    let (slug, _, mut remote) = registered_repo(&app, "checklist").await;
+    let (before, after) = TWO_RESISTORS.split_at(TWO_RESISTORS.find("\"R2\"").unwrap());
+    let changed = format!("{}{}", before, after.replacen("\"10k\"", "\"4.7k\"", 1));
+    let head = remote.commit(&[("divider.kicad_sch", &changed)], "Lower R2");
+    let checklist_path = format!("/api/repos/{}/commits/{}/checklist", encoded(&slug), head);
+    assert_eq!(app.get(&checklist_path).await.status(), 404);
+
+    let response = app.post("/api/grok/review/checklist", json!({ "repo": slug, "commit": head })).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["cached"], false);
+    let items = body["items"].as_array().unwrap();
+    assert_eq!(items.len(), 2, "{}", body);
+    assert_eq!(items[0]["category"], "signal");
+    // Only the changed component is linked; R1 is only mentioned
+    assert_eq!(items[0]["components"], json!(["R2"]));
+    assert_eq!(items[1]["category"], "other");
+    assert_eq!(items[1]["done"], false);
+    let prompt = app.ai.requests()[0].body["messages"].to_string();
+    assert!(prompt.contains("Lower R2") && prompt.contains("4.7k"), "{}", prompt);
+
+    // Ticks persist, and the stored checklist comes back without a model call
+    let id = items[0]["id"].as_i64().unwrap();
+    let response = app
+        .request(Method::PUT, &format!("{}/{}", checklist_path, id))
+        .json(&json!({ "done": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = app.get(&checklist_path).await.json().await.unwrap();
+    assert_eq!(body["items"][0]["done"], true);
+    assert!(body["items"][0]["done_at"].is_string());
+    let body: Value = app
+        .post("/api/grok/review/checklist", json!({ "repo": slug, "commit": head }))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["cached"], true);
+    assert_eq!(body["items"][0]["done"], true);
+    assert_eq!(app.ai.requests().len(), 1);
+
+    // Refreshing starts over
+    let body: Value = app
+        .post("/api/grok/review/checklist", json!({ "repo": slug, "commit": head, "refresh": true }))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["items"][0]["done"], false);
+    let response = app
+        .request(Method::PUT, &format!("{}/{}", checklist_path, id))
+        .json(&json!({ "done": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn questions_about_a_commit_are_answered_from_its_design_and_stored() {
+    let replies = MockReplies {
+        stream_chunks: vec!["R1 pin 1 floats on unconnected-(R1-Pad1). ".to_string(), "R7 isn't in this design.".to_string()],
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(Default::default(), replies).await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "ask").await;
+
+    let question = "What does R1 do?";
+    let response = app.post("/api/grok/ask", json!({ "repo": slug, "commit": "v1.0", "question": question })).await;
+    let status = response.status();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["commit"], commit);
+    assert_eq!(body["cached"], false);
+    assert_eq!(body["answer"], "R1 pin 1 floats on unconnected-(R1-Pad1). R7 isn't in this design.");
+    // Only what exists at the commit is linked
+    assert_eq!(body["components"], json!(["R1"]));
+    assert_eq!(body["nets"], json!(["unconnected-(R1-Pad1)"]));
+    assert_eq!(body["prompt_version"], "ask@v1");
+    let prompt = app.ai.requests()[0].body["messages"].to_string();
+    assert!(prompt.contains("Question: What does R1 do?"), "{}", prompt);
+    assert!(prompt.contains("2x 10k"), "{}", prompt);
+
+    // The same question, however it's spaced, is answered from the store
+    let body: Value = app
+        .post("/api/grok/ask", json!({ "repo": slug, "commit": commit, "question": "  what does r1   do? " }))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["cached"], true);
+    assert_eq!(body["question"], question);
+    assert_eq!(body["nets"], json!(["unconnected-(R1-Pad1)"]));
+    assert_eq!(app.ai.requests().len(), 1);
+
+    let body: Value = app
+        .post("/api/grok/ask", json!({ "repo": slug, "commit": commit, "question": question, "refresh": true }))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["cached"], false);
+    assert_eq!(app.ai.requests().len(), 2);
+
+    let response = app
+        .post("/api/grok/ask", json!({ "repo": slug, "commit": commit, "question": " ", "top_k": 50 }))
+        .await;
+    assert_eq!(response.status(), 422);
+    let body: Value = response.json().await.unwrap();
+    let fields: Vec<&str> = body["violations"].as_array().unwrap().iter().map(|v| v["field"].as_str().unwrap()).collect();
+    assert_eq!(fields, ["question", "top_k"]);
+}
+
+#[tokio::test]
+async fn chat_sessions_fold_older_turns_into_a_summary_near_the_context_window() {
+    let mut config = Config::default();
+    config.models.context_windows.insert(config.models.chat.clone(), 500);
+    config.chat_history.keep_recent_turns = 2;
+    let Some(app) = TestApp::start_with(config, MockReplies::default()).await else { return };
+    let (slug, _commit, _remote) = registered_repo(&app, "sessions").await;
+
+    let response = app.post("/api/grok/chat/sessions", json!({})).await;
+    assert_eq!(response.status(), 200);
+    let session: Value = response.json().await.unwrap();
+    let id = session["id"].as_str().unwrap().to_string();
+    assert_eq!(session["turns"], json!([]));
+
+    // A session is about one repo, or none
+    let question = |n: usize| format!("Question {}: how does R1 set the divider ratio? ", n).repeat(15);
+    let ask = |body: Value| app.post("/api/grok/chat/stream", body);
+    let body = json!({ "session_id": id, "repo": slug, "messages": [{ "role": "user", "content": "Hi" }] });
+    assert_eq!(ask(body).await.status(), 400);
+    let unknown = json!({ "session_id": uuid::Uuid::new_v4(), "messages": [{ "role": "user", "content": "Hi" }] });
+    assert_eq!(ask(unknown).await.status(), 404);
+
+    // Each request sends only its new turn; the history comes from the session
+    for n in 1..=3 {
+        let body = json!({ "session_id": id, "messages": [{ "role": "user", "content": question(n) }] });
+        let response = ask(body).await;
+        assert_eq!(response.status(), 200);
+        assert!(response.text().await.unwrap().ends_with("data: [DONE]\n\n"));
+    }
+    let requests = app.ai.requests();
+    let second = &requests[1].body["messages"];
+    assert_eq!(second.as_array().unwrap().len(), 4);
+    assert_eq!(second[1]["content"], question(1));
+    assert_eq!(second[2]["content"], "Hello from the mock.");
+
+    // The third would crowd the window, so the first two turns were folded
+    assert_eq!(requests.len(), 4);
+    let fold = requests[2].body["messages"][0]["content"].as_str().unwrap();
+    assert!(fold.starts_with("Below is the start of a conversation"), "{}", fold);
+    assert!(fold.contains(&question(1)) && !fold.contains(&question(2)));
+    let third = &requests[3].body["messages"];
+    assert_eq!(third[1]["content"], "Summary of the conversation so far:\nHello from the mock.");
+    assert_eq!(third[2]["content"], question(2));
+    assert_eq!(third.as_array().unwrap().len(), 5);
+
+    // Every turn stays stored
+    let session: Value = app.get(&format!("/api/grok/chat/sessions/{}", id)).await.json().await.unwrap();
+    assert_eq!(session["summary"], "Hello from the mock.");
+    assert_eq!(session["summarized_through"], 2);
+    let turns = session["turns"].as_array().unwrap();
+    assert_eq!(turns.len(), 6);
+    assert_eq!((&turns[0]["role"], &turns[0]["content"]), (&json!("user"), &json!(question(1))));
+    assert_eq!(turns[5]["role"], "assistant");
+    let unsummarized: u64 = turns[2..].iter().map(|turn| turn["tokens"].as_u64().unwrap()).sum();
+    assert!(session["tokens"].as_u64().unwrap() > unsummarized);
+    assert_eq!(app.get(&format!("/api/grok/chat/sessions/{}", uuid::Uuid::new_v4())).await.status(), 404);
+}
+
+#[tokio::test]
+async fn chat_answers_cite_components_and_nets_and_keep_them_in_the_session() {
+    let replies = MockReplies {
+        stream_chunks: vec!["R1 pin 1 floats on unconnected-(R1-Pad1). ".to_string(), "R7 isn't in this design.".to_string()],
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(Default::default(), replies).await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "citations").await;
+    let session: Value = app.post("/api/grok/chat/sessions", json!({ "repo": slug })).await.json().await.unwrap();
+
+    let body = json!({
+        "session_id": session["id"],
+        "repo": slug,
+        "commit": "v1.0",
+        "messages": [{ "role": "user", "content": "What does R1 do?" }],
+    });
+    let response = app.post("/api/grok/chat/stream", body).await;
+    assert_eq!(response.status(), 200);
+    let text = response.text().await.unwrap();
+    assert!(text.contains("event: citations") && text.contains("event: attachments"), "{}", text);
+    let events: Vec<Value> = sse_data(&text).iter().filter_map(|data| serde_json::from_str(data).ok()).collect();
+    let cited = events.iter().find_map(|event| event.get("citations")).unwrap();
+    // Only what exists at the commit is cited
+    assert_eq!(
+        cited,
+        &json!([
+            { "kind": "component", "target": "R1", "commit_hash": commit },
+            { "kind": "net", "target": "unconnected-(R1-Pad1)", "commit_hash": commit },
+        ])
+    );
+    let attached = events.iter().find_map(|event| event.get("attachments")).unwrap();
+    assert_eq!(attached.as_array().unwrap().len(), 1, "{}", attached);
+    assert_eq!(attached[0]["title"], "R1 Device:R");
+    assert_eq!(attached[0]["cites"], "R1");
+    assert!(attached[0]["content"].as_str().unwrap().contains("<svg"));
+    assert!(text.ends_with("data: [DONE]\n\n"));
+
+    // The stored answer keeps them; the question has none
+    let id = session["id"].as_str().unwrap();
+    let session: Value = app.get(&format!("/api/grok/chat/sessions/{}", id)).await.json().await.unwrap();
+    let turns = session["turns"].as_array().unwrap();
+    assert_eq!(turns.len(), 2);
+    assert_eq!(turns[0]["citations"], json!([]));
+    assert_eq!(&turns[1]["citations"], cited);
+    assert_eq!(&turns[1]["attachments"], attached);
+}
+
+#[tokio::test]
+async fn parts_are_classified_into_the_taxonomy_and_filtered_by_category() {
+    let replies = MockReplies {
+        stream_chunks: vec!["1: passive/".to_string(), "resistor\n".to_string()],
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(Default::default(), replies).await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "taxonomy").await;
+    // Distilling stores the commit's components
+    app.post("/api/distill", json!({ "repo": slug, "commit": commit })).await;
+    let parts_path = format!("/api/repos/{}/components", encoded(&slug));
+
+    // Both resistors are one part, unclassified so far
+    let body: Value = app.get(&parts_path).await.json().await.unwrap();
+    assert_eq!(body["parts"].as_array().unwrap().len(), 1, "{}", body);
+    assert_eq!(body["parts"][0]["references"], json!(["R1", "R2"]));
+    assert_eq!(body["parts"][0]["category"], Value::Null);
+    assert_eq!(body["unclassified"], 1);
+
+    let response = app.post("/api/admin/taxonomy/classify", json!({})).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!((&body["classified"], &body["model"]), (&json!(1), &json!("grok-3-mini")));
+    let requests = app.ai.requests();
+    let sent = &requests.last().unwrap().body;
+    assert_eq!((&sent["model"], &sent["temperature"]), (&json!("grok-3-mini"), &json!(0.0)));
+    let prompt = sent["messages"][0]["content"].as_str().unwrap();
+    assert!(prompt.ends_with("Parts:\n1. 10k | Resistor_SMD:R_0603_1608Metric | -"), "{}", prompt);
+
+    // Classified parts are asked about once
+    let body: Value = app.post("/api/admin/taxonomy/classify", json!({})).await.json().await.unwrap();
+    assert_eq!(body["classified"], 0);
+    assert_eq!(app.ai.requests().len(), requests.len());
+
+    for category in ["passive", "passive/resistor", "resistor"] {
+        let body: Value = app.get(&format!("{}?category={}", parts_path, category)).await.json().await.unwrap();
+        assert_eq!(body["parts"].as_array().unwrap().len(), 1, "{}: {}", category, body);
+        assert_eq!((&body["parts"][0]["category"], &body["parts"][0]["subcategory"]), (&json!("passive"), &json!("resistor")));
+        assert_eq!(body["parts"][0]["quantity"], 2);
+    }
+    let body: Value = app.get(&format!("{}?category=regulator", parts_path)).await.json().await.unwrap();
+    assert_eq!(body["parts"], json!([]));
+    assert_eq!(body["categories"], json!([{ "category": "passive", "parts": 1, "quantity": 2 }]));
+    assert_eq!(body["unclassified"], 0);
+    for unknown in ["widget", "regulator/buck"] {
+        let response = app.get(&format!("{}?category={}", parts_path, encoded(unknown))).await;
+        assert_eq!(response.status(), 400, "{}", unknown);
+    }
+
+    // The repo overview lists the parts by category
+    app.post("/api/grok/summary/repo", json!({ "repo": slug })).await;
+    let overview = app.ai.requests().last().unwrap().body["messages"][0]["content"].to_string();
+    assert!(overview.contains("parts by category:\\n- passive/resistor: R1, R2 10k\\n"), "{}", overview);
+
+    // Needs an admin key
+    let response = app.anonymous(Method::POST, "/api/admin/taxonomy/classify").send().await.unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn classification_moves_to_the_fallback_model_while_its_model_is_slow() {
+    let config = Config {
+        routing: RoutingConfig {
+            fallback_model: Some("grok-4-1-fast-non-reasoning".to_string()),
+            max_latency_ms: 20,
+            min_calls: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let replies = MockReplies {
+        stream_chunks: vec!["1: passive/resistor\n".to_string()],
+        event_delay: Duration::from_millis(50),
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(config, replies).await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "routing").await;
+    app.post("/api/distill", json!({ "repo": slug, "commit": commit })).await;
+
+    // Nothing is known about the classification model yet, so it is used
+    let body: Value = app.post("/api/admin/taxonomy/classify", json!({})).await.json().await.unwrap();
+    assert_eq!((&body["classified"], &body["model"]), (&json!(1), &json!("grok-3-mini")));
+    assert_eq!(app.ai.requests().last().unwrap().body["model"], "grok-3-mini");
+
+    // Its first token took longer than allowed, so the next run goes to the fallback
+    let body: Value = app.post("/api/admin/taxonomy/classify", json!({})).await.json().await.unwrap();
+    assert_eq!(body["model"], "grok-4-1-fast-non-reasoning");
+}
+
+#[tokio::test]
+async fn design_guidelines_reach_the_summary_prompt() {
+    let Some(app) = TestApp::start().await else { return };
+    let org = format!("guidelines-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+    let response = app.post("/api/orgs", json!({ "slug": org, "name": "Guidelines" })).await;
+    assert_eq!(response.status(), 200);
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let slug = unique_slug("guidelines");
+    app.register_with(&slug, &remote, json!({ "org": org })).await;
+
+    let guideline = |title: &str, body: &str| json!({ "title": title, "body": body });
+    let path = format!("/api/orgs/{}/guidelines", org);
+    let response = app.post(&path, guideline("Passives", "We always use 0402 passives.")).await;
+    assert_eq!(response.status(), 200);
+    let repo_path = format!("/api/repos/{}/guidelines", encoded(&slug));
+    let response = app.post(&repo_path, guideline("Regulators", "Prefer TI regulators.")).await;
+    assert_eq!(response.status(), 200);
+    let id = response.json::<Value>().await.unwrap()["id"].as_i64().unwrap();
+    assert_eq!(app.post(&repo_path, guideline(" ", "Untitled")).await.status(), 422);
+
+    // The repo's own guidelines come first
+    let body: Value = app.get(&repo_path).await.json().await.unwrap();
+    assert_eq!(body["guidelines"][0]["title"], "Regulators");
+    assert_eq!(body["org_guidelines"][0]["title"], "Passives");
+    let prompt = body["prompt"].as_str().unwrap();
+    assert!(prompt.find("Prefer TI regulators.").unwrap() < prompt.find("0402 passives").unwrap(), "{}", prompt);
+
+    let response = app.post("/api/grok/summary/commit", json!({ "repo": slug, "commit": commit })).await;
+    assert_eq!(response.status(), 200);
+    let request = app.ai.requests()[0].body.to_string();
+    assert!(request.contains("Prefer TI regulators.") && request.contains("We always use 0402 passives."), "{}", request);
+
+    let delete = |path: String| app.request(Method::DELETE, &path).send();
+    assert_eq!(delete(format!("{}/{}", repo_path, id)).await.unwrap().status(), 200);
+    assert_eq!(delete(format!("{}/{}", repo_path, id)).await.unwrap().status(), 404);
+    // Nor through the org, whose guideline it isn't
+    assert_eq!(delete(format!("{}/{}", path, id)).await.unwrap().status(), 404);
+}
+
+#[tokio::test]
+async fn org_keys_only_summarize_their_own_orgs_repos() {
+    let Some(app) = TestApp::start().await else { return };
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let slug = unique_slug("org-summary");
+    let mut keys = Vec::new();
+    for name in ["Owner", "Other"] {
+        let org = format!("{}-{}", name.to_lowercase(), &uuid::Uuid::new_v4().simple().to_string()[..8]);
+        assert_eq!(app.post("/api/orgs", json!({ "slug": org, "name": name })).await.status(), 200);
+        if name == "Owner" {
+            app.register_with(&slug, &remote, json!({ "org": org })).await;
+        }
+        let response = app.post("/api/keys", json!({ "name": name, "scope": "hook", "org": org })).await;
+        assert_eq!(response.status(), 200);
+        keys.push(response.json::<Value>().await.unwrap()["secret"].as_str().unwrap().to_string());
+    }
+
+    let summarize = |key: &str| {
+        app.anonymous(Method::POST, "/api/grok/summary/commit")
+            .header("X-API-Key", key)
+            .json(&json!({ "repo": slug, "commit": commit }))
+            .send()
+    };
+    assert_eq!(summarize(&keys[0]).await.unwrap().status(), 200);
+    let response = summarize(&keys[1]).await.unwrap();
+    assert_eq!(response.status(), 404);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "not_found", "{}", body);
+}
+
+#[tokio::test]
+async fn summary_feedback_is_totalled_per_template_and_model() {
+    let Some(app) = TestApp::start().await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "feedback").await;
+
+    let summary: Value = app
+        .post("/api/grok/summary/commit", json!({ "repo": slug, "commit": commit }))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let summary_id = summary["summary_id"].as_i64().expect("summaries carry an ID to rate them by");
+
+    let rate = |rating: &str, comment: Option<&str>| {
+        app.post("/api/grok/feedback", json!({ "summary_id": summary_id, "rating": rating, "comment": comment }))
+    };
+    let response = rate("down", Some("Missed the divider ratio")).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["model"], summary["model"]);
+    assert_eq!(body["prompt_version"], summary["prompt_version"]);
+    assert_eq!(rate("up", None).await.status(), 200);
+    assert_eq!(rate("sideways", None).await.status(), 400);
+    let unknown = app.post("/api/grok/feedback", json!({ "summary_id": -1, "rating": "up" })).await;
+    assert_eq!(unknown.status(), 404);
+
+    let report: Value = app.get("/api/admin/feedback?days=1").await.json().await.unwrap();
+    let total = report["totals"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["model"] == summary["model"] && t["prompt_version"] == summary["prompt_version"])
+        .unwrap_or_else(|| panic!("no total for the summary's model and template: {}", report));
+    assert!(total["helpful"].as_i64().unwrap() >= 1 && total["unhelpful"].as_i64().unwrap() >= 1, "{}", total);
+    assert_eq!(app.get("/api/admin/feedback?days=0").await.status(), 400);
+}
+
+#[tokio::test]
+async fn experiments_compare_variants_by_feedback_and_cost() {
+    let name = unique_slug("experiment").replace('/', "-");
+    let experiment = ExperimentConfig {
+        name: name.clone(),
+        variants: vec![
+            ExperimentVariant {
+                name: "current".to_string(),
+                template: "commit_summary".to_string(),
+                model: "grok-4-1-fast".to_string(),
+                weight: 1,
+            },
+            ExperimentVariant {
+                name: "chat-model".to_string(),
+                template: "commit_summary@v1".to_string(),
+                model: "grok-3-fast".to_string(),
+                weight: 3,
+            },
+        ],
+    };
+    let config = Config {
+        experiment: Some(experiment),
+        ..Default::default()
+    };
+    let Some(app) = TestApp::start_with(config, MockReplies::default()).await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "experiment").await;
+
+    let summary: Value = app
+        .post("/api/grok/summary/commit", json!({ "repo": slug, "commit": commit }))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let again: Value = app
+        .post("/api/grok/summary/commit", json!({ "repo": slug, "commit": commit }))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(again["model"], summary["model"], "a commit keeps its variant");
+    let rating = json!({ "summary_id": summary["summary_id"], "rating": "up" });
+    assert_eq!(app.post("/api/grok/feedback", rating).await.status(), 200);
+
+    // A model the caller picks opts the summary out of the experiment
+    let chosen = app
+        .post("/api/grok/summary/commit", json!({ "repo": slug, "commit": commit, "model": "grok-3-fast" }))
+        .await;
+    assert_eq!(chosen.status(), 200);
+
+    let report: Value = app.get("/api/admin/experiments?days=1").await.json().await.unwrap();
+    assert_eq!(report["active"], name.as_str());
+    let variants: Vec<&Value> = report["variants"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|v| v["experiment"] == name.as_str())
+        .collect();
+    assert_eq!(variants.len(), 1, "{}", report);
+    let variant = variants[0];
+    assert_eq!(variant["model"], summary["model"]);
+    assert_eq!(variant["prompt_version"], summary["prompt_version"]);
+    assert_eq!(variant["summaries"], 2);
+    assert_eq!(variant["rated"], 1);
+    assert_eq!(variant["helpful"], 1);
+    assert_eq!(variant["calls"], 2, "the opted-out summary isn't charged to it: {}", variant);
+    let weight = if variant["variant"] == "current" { 1 } else { 3 };
+    assert_eq!(variant["weight"], weight);
+    assert_eq!(app.get("/api/admin/experiments?days=0").await.status(), 400);
+}
+
+#[tokio::test]
+async fn dropped_summary_streams_resume_after_the_last_event_id() {
+    let Some(app) = TestApp::start().await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "resume").await;
+    let body = json!({ "repo": slug, "commit": commit });
+    let stream = |last_event_id: Option<&str>| {
+        let request = app.request(Method::POST, "/api/grok/summary/commit/stream").json(&body);
+        let request = match last_event_id {
+            Some(id) => request.header("Last-Event-ID", id),
+            None => request,
+        };
+        async move { request.send().await.unwrap().text().await.unwrap() }
+    };
+
+    let first = stream(None).await;
+    let (data, ids) = (sse_data(&first), sse_ids(&first));
+    assert_eq!(ids.len(), data.len(), "every event has an ID: {}", first);
+    assert_eq!(data.last().unwrap(), "[DONE]");
+    let calls = app.ai.requests().len();
+
+    // A client that lost the connection after the first chunk gets the rest
+    // of the same summary, without another AI call
+    let resumed = stream(Some(&ids[0])).await;
+    assert_eq!(sse_data(&resumed), data[1..]);
+    assert_eq!(sse_ids(&resumed), ids[1..]);
+    assert_eq!(app.ai.requests().len(), calls);
+
+    // An ID the server doesn't know, or one from another commit's stream,
+    // starts a new summary
+    let unknown = stream(Some("00000000-0000-0000-0000-000000000000:0")).await;
+    assert_eq!(sse_data(&unknown).last().unwrap(), "[DONE]");
+    assert_eq!(app.ai.requests().len(), calls + 1);
+    let (other_slug, other_commit, _other) = registered_repo(&app, "resume-other").await;
+    let other = app
+        .request(Method::POST, "/api/grok/summary/commit/stream")
+        .header("Last-Event-ID", &ids[0])
+        .json(&json!({ "repo": other_slug, "commit": other_commit }))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(sse_data(&other).len(), data.len(), "{}", other);
+    assert_eq!(app.ai.requests().len(), calls + 2);
+}
+
+#[tokio::test]
+async fn concurrent_summary_requests_share_one_generation() {
+    let replies = MockReplies {
+        event_delay: Duration::from_millis(100),
+        ..MockReplies::default()
+    };
+    let Some(app) = TestApp::start_with(Default::default(), replies).await else { return };
+    let (slug, commit, _remote) = registered_repo(&app, "shared").await;
+    let body = json!({ "repo": slug, "commit": commit });
+
+    // The first reviewer starts the summary; the response begins once it is under way
+    let first = app.post("/api/grok/summary/commit/stream", body.clone()).await;
+    assert_eq!(first.status(), 200);
+    // Others opening the commit meanwhile, streamed or not, follow it
+    let (first, second, plain) = tokio::join!(
+        first.text(),
+        async { app.post("/api/grok/summary/commit/stream", body.clone()).await.text().await },
+        async { app.post("/api/grok/summary/commit", body.clone()).await.json::<Value>().await },
+    );
+    let (first, second, plain) = (first.unwrap(), second.unwrap(), plain.unwrap());
+
+    assert_eq!(sse_data(&second), sse_data(&first));
+    assert_eq!(sse_ids(&second), sse_ids(&first));
+    let complete: Value = serde_json::from_str(&sse_data(&first)[sse_data(&first).len() - 2]).unwrap();
+    assert_eq!(plain["summary"], "Hello from the mock.");
+    assert_eq!(plain["summary_id"], complete["summary_id"]);
+    assert_eq!(app.ai.requests().len(), 1, "one AI call for all three");
+
+    // and the summary is recorded once
+    let recorded: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM generated_summaries WHERE commit_hash = $1")
+        .bind(&commit)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(recorded, 1);
+
+    // A different detail level is a different summary
+    let detailed = json!({ "repo": slug, "commit": commit, "detail_level": "deep" });
+    app.post("/api/grok/summary/commit/stream", detailed).await.text().await.unwrap();
+    assert_eq!(app.ai.requests().len(), 2);
+}
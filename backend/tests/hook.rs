@@ -0,0 +1,658 @@
+// End-to-end tests of the hook endpoints; see common/mod.rs for the harness.
+//
+// USAGE:
+// cargo test --test hook
+
+mod common;
+
+use common::{encoded, unique_slug, FakeRemote, TestApp, EAGLE_TWO_RESISTORS, LEGACY_TWO_RESISTORS, TWO_RESISTORS};
+use hmac::{Hmac, Mac};
+use kicad_backend::services::git::{self, LogFilter};
+use reqwest::Method;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+/// A remote with two schematic commits around one that only touches docs
+fn sample_remote() -> (FakeRemote, Vec<String>) {
+    let mut remote = FakeRemote::new();
+    let first = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    remote.commit(&[("README.md", "# Divider\n")], "Add readme");
+    let changed = TWO_RESISTORS.replacen("\"10k\"", "\"4.7k\"", 1);
+    let second = remote.commit(&[("divider.kicad_sch", &changed)], "Change R1 to 4.7k");
+    (remote, vec![first, second])
+}
+
+async fn stored_commits(app: &TestApp, slug: &str) -> Vec<String> {
+    let response = app.get(&format!("/api/repos/{}/commits", encoded(slug))).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let mut hashes: Vec<String> = body["commits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| c["commit_hash"].as_str().unwrap().to_string())
+        .collect();
+    hashes.sort();
+    hashes
+}
+
+#[tokio::test]
+async fn update_hook_stores_schematic_commits() {
+    let Some(app) = TestApp::start().await else { return };
+    let (remote, mut commits) = sample_remote();
+    let slug = unique_slug("update");
+    app.register(&slug, &remote).await;
+
+    let response = app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["repo"], slug);
+    assert_eq!(body["processed"], 2, "only the schematic commits are processed: {}", body);
+    assert_eq!(body["errors"], json!([]));
+
+    commits.sort();
+    assert_eq!(stored_commits(&app, &slug).await, commits);
+
+    // Processed commits are skipped the next time round
+    let response = app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["processed"], 0);
+}
+
+#[tokio::test]
+async fn processed_commits_keep_their_stats_for_the_growth_series() {
+    let Some(app) = TestApp::start().await else { return };
+    let (mut remote, commits) = sample_remote();
+    let slug = unique_slug("stats");
+    app.register(&slug, &remote).await;
+    app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+
+    let response = app.get(&format!("/api/repos/{}/stats", encoded(&slug))).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let series = body["commits"].as_array().unwrap();
+    let hashes: Vec<&str> = series.iter().map(|c| c["commit_hash"].as_str().unwrap()).collect();
+    assert_eq!(hashes, [commits[0].as_str(), commits[1].as_str()], "{}", body);
+    assert_eq!(series[0]["stats"]["components"], 2, "{}", body);
+    assert_eq!(series[0]["stats"]["sheets"], 1, "{}", body);
+    // Changing R1's value makes it a part type of its own
+    assert_eq!(series[1]["stats"]["unique_parts"], 2, "{}", body);
+
+    let response = app.get(&format!("/api/repos/{}/commits/{}/stats", encoded(&slug), commits[1])).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["stats"], series[1]["stats"]);
+
+    // Commits not processed yet are counted from their schematics
+    let unprocessed = remote.commit(&[("notes.txt", "later\n")], "Add notes");
+    let response = app.get(&format!("/api/repos/{}/commits/{}/stats", encoded(&slug), unprocessed)).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["stats"], series[1]["stats"]);
+
+    let response = app.get(&format!("/api/repos/{}/stats?limit=0", encoded(&slug))).await;
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn processed_commits_make_their_schematic_text_searchable() {
+    let Some(app) = TestApp::start().await else { return };
+    let (remote, commits) = sample_remote();
+    let slug = unique_slug("texts");
+    app.register(&slug, &remote).await;
+    app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+    let search = |query: &str| format!("/api/search?repo={}&{}", encoded(&slug), query);
+
+    // Only the latest commit's text is searched: R1 is 4.7k there
+    let response = app.get(&search("type=value&q=4.7K")).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["results"], json!([]));
+    let texts = body["texts"].as_array().unwrap();
+    assert_eq!(texts.len(), 1, "{}", body);
+    assert_eq!(texts[0]["commit_hash"], commits[1]);
+    assert_eq!(texts[0]["kind"], "value");
+    assert_eq!(texts[0]["text"], "4.7k");
+    assert_eq!(texts[0]["reference"], "R1");
+    assert_eq!(texts[0]["sheet_path"], "/");
+    let body: Value = app.get(&search("type=value&q=10k")).await.json().await.unwrap();
+    assert_eq!(body["texts"][0]["reference"], "R2", "{}", body);
+    assert_eq!(body["texts"].as_array().unwrap().len(), 1);
+
+    let body: Value = app.get(&search("type=text&q=r1")).await.json().await.unwrap();
+    let kinds: Vec<&str> = body["texts"].as_array().unwrap().iter().map(|t| t["kind"].as_str().unwrap()).collect();
+    assert_eq!(kinds, ["reference"], "{}", body);
+    let body: Value = app.get(&search("type=label&q=r1")).await.json().await.unwrap();
+    assert_eq!(body["texts"], json!([]));
+
+    // Without a type, commits are searched as before
+    let body: Value = app.get(&search("q=divider")).await.json().await.unwrap();
+    assert_eq!(body["texts"], json!([]));
+    assert_eq!(app.get(&search("type=footprint&q=r1")).await.status(), 400);
+    assert_eq!(app.get("/api/search/semantic?type=text&q=r1").await.status(), 400);
+}
+
+#[tokio::test]
+async fn renamed_power_nets_are_flagged_in_the_overview_and_the_summary_prompt() {
+    let Some(app) = TestApp::start().await else { return };
+    // Pin 1 of both resistors on a +5V label
+    let labelled = |net: &str| {
+        TWO_RESISTORS.replacen(
+            "\t(sheet_instances",
+            &format!("\t(label \"{net}\" (at 102.87 64.77 0))\n\t(label \"{net}\" (at 118.11 64.77 0))\n\t(sheet_instances"),
+            1,
+        )
+    };
+    let mut remote = FakeRemote::new();
+    remote.commit(&[("divider.kicad_sch", &labelled("+5V"))], "Add the 5V rail");
+    let renamed = remote.commit(&[("divider.kicad_sch", &labelled("+5V0"))], "Tidy labels");
+    let slug = unique_slug("anomalies");
+    app.register(&slug, &remote).await;
+    app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+
+    let response = app.get(&format!("/api/repos/{}/commits/{}/changes", encoded(&slug), renamed)).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["warnings"][0]["kind"], "power_net_renamed", "{}", body);
+    assert_eq!(body["warnings"][0]["nets"], json!(["/+5V", "/+5V0"]), "{}", body);
+    let description = body["description"].as_str().unwrap_or_default();
+    assert!(description.contains("[power_net_renamed] Power net /+5V was renamed to /+5V0"), "{}", body);
+
+    let response = app
+        .post("/api/grok/summary/commit", json!({ "repo": slug, "commit": renamed }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let prompted = app.ai.requests().iter().any(|request| {
+        let prompt = request.body.to_string();
+        prompt.contains(&renamed) && prompt.contains("Possibly Unintended Changes")
+    });
+    assert!(prompted, "the summary prompt should list the warning");
+}
+
+#[tokio::test]
+async fn failed_commits_are_listed_until_a_refresh_processes_them() {
+    let Some(app) = TestApp::start().await else { return };
+    let (remote, commits) = sample_remote();
+    let slug = unique_slug("errors");
+    app.register(&slug, &remote).await;
+    app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+
+    let timeline: Value = app.get(&format!("/api/repos/{}/timeline", encoded(&slug))).await.json().await.unwrap();
+    let statuses: Vec<&Value> = timeline["commits"].as_array().unwrap().iter().map(|c| &c["processing_status"]).collect();
+    assert_eq!(statuses, [&json!("done"), &json!("done")], "{}", timeline);
+
+    // The second commit's next attempt fails
+    let repo_url = format!("https://github.com/{}.git", slug);
+    sqlx::query(
+        "UPDATE schematics SET blurb = NULL, processing_status = 'failed', processing_error = 'LLM timed out', \
+         processing_error_at = CURRENT_TIMESTAMP WHERE repo_url = $1 AND commit_hash = $2",
+    )
+    .bind(&repo_url)
+    .bind(&commits[1])
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    let response = app.get(&format!("/api/repos/{}/errors", encoded(&slug))).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let errors = body["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 1, "{}", body);
+    assert_eq!(errors[0]["commit_hash"], commits[1].as_str());
+    assert_eq!(errors[0]["error"], "LLM timed out");
+    let timeline: Value = app.get(&format!("/api/repos/{}/timeline", encoded(&slug))).await.json().await.unwrap();
+    let failed = timeline["commits"].as_array().unwrap().iter().find(|c| c["commit_hash"] == commits[1].as_str()).unwrap();
+    assert_eq!((&failed["status"], &failed["processing_status"]), (&json!("failed"), &json!("failed")));
+    assert_eq!(failed["error"], "LLM timed out");
+
+    // An update only looks past the high-water mark; a refresh retries it
+    let response = app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["processed"], 0, "{}", body);
+    let response = app.post(&format!("/api/hook/refresh/{}", slug), json!({})).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["processed"], 1, "{}", body);
+    assert_eq!(body["since_commit"], Value::Null);
+    let body: Value = app.get(&format!("/api/repos/{}/errors", encoded(&slug))).await.json().await.unwrap();
+    assert_eq!(body["errors"], json!([]));
+}
+
+#[tokio::test]
+async fn refresh_hook_picks_up_new_commits() {
+    let Some(app) = TestApp::start().await else { return };
+    let (mut remote, _) = sample_remote();
+    let slug = unique_slug("refresh");
+    app.register(&slug, &remote).await;
+    app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+
+    let changed = TWO_RESISTORS.replace("\"10k\"", "\"22k\"");
+    let third = remote.commit(&[("divider.kicad_sch", &changed)], "Raise both resistors to 22k");
+    let response = app.post(&format!("/api/hook/refresh/{}", slug), json!({})).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["processed"], 1);
+    assert!(stored_commits(&app, &slug).await.contains(&third));
+}
+
+#[tokio::test]
+async fn legacy_schematics_and_schematic_globs_are_processed() {
+    let Some(app) = TestApp::start().await else { return };
+    let mut remote = FakeRemote::new();
+    let legacy = remote.commit(&[("divider.sch", LEGACY_TWO_RESISTORS)], "Add KiCad 5 divider");
+    let renamed = remote.commit(&[("divider.eeschema", LEGACY_TWO_RESISTORS)], "Export divider");
+
+    // Legacy .sch files count as schematics; other names only through the repo's globs
+    let plain = unique_slug("legacy");
+    app.register(&plain, &remote).await;
+    let body: Value = app.post(&format!("/api/hook/update/{}", plain), json!({})).await.json().await.unwrap();
+    assert_eq!(body["processed"], 1, "{}", body);
+    assert_eq!(stored_commits(&app, &plain).await, std::slice::from_ref(&legacy));
+
+    let globbed = unique_slug("globbed");
+    app.register_with(&globbed, &remote, json!({ "schematic_globs": ["*.eeschema"] })).await;
+    let body: Value = app.post(&format!("/api/hook/update/{}", globbed), json!({})).await.json().await.unwrap();
+    assert_eq!(body["processed"], 2, "{}", body);
+    let mut commits = vec![legacy.clone(), renamed];
+    commits.sort();
+    assert_eq!(stored_commits(&app, &globbed).await, commits);
+
+    let response = app.get(&format!("/api/repos/{}/commits/{}/bom", encoded(&plain), legacy)).await;
+    assert_eq!(response.status(), 200);
+    let bom: Value = response.json().await.unwrap();
+    let line = &bom["projects"][0]["lines"][0];
+    assert_eq!(line["lib_id"], "Device:R", "{}", bom);
+    assert_eq!(line["references"], json!(["R1", "R2"]));
+
+    // Globs are checked when registering
+    let body = json!({ "repo": unique_slug("bad-glob"), "clone_url": remote.clone_url(), "schematic_globs": ["[unclosed"] });
+    let response = app.post("/api/repos", body).await;
+    assert_eq!(response.status(), 400);
+
+    // So are token variables, so a registration can't send the server's other secrets to a remote
+    let body = json!({ "repo": unique_slug("bad-token"), "clone_url": remote.clone_url(), "auth_token_env": "XAI_API_KEY" });
+    let response = app.post("/api/repos", body).await;
+    assert_eq!(response.status(), 400);
+    assert!(response.text().await.unwrap().contains("git_token_envs"));
+}
+
+#[tokio::test]
+async fn eagle_schematics_are_processed_and_their_format_reported() {
+    let Some(app) = TestApp::start().await else { return };
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.sch", EAGLE_TWO_RESISTORS)], "Add Eagle divider");
+    let slug = unique_slug("eagle");
+    app.register(&slug, &remote).await;
+    let body: Value = app.post(&format!("/api/hook/update/{}", slug), json!({})).await.json().await.unwrap();
+    assert_eq!(body["processed"], 1, "{}", body);
+
+    let response = app.get(&format!("/api/repos/{}/commits/{}/bom", encoded(&slug), commit)).await;
+    assert_eq!(response.status(), 200);
+    let bom: Value = response.json().await.unwrap();
+    let project = &bom["projects"][0];
+    assert_eq!(project["project"]["formats"], json!({ "divider.sch": "eagle" }), "{}", bom);
+    assert_eq!(project["lines"][0]["references"], json!(["R1", "R2"]));
+    assert_eq!(project["lines"][0]["footprint"], "rcl:R0603");
+}
+
+#[tokio::test]
+async fn updates_only_walk_commits_after_the_high_water_mark() {
+    let Some(app) = TestApp::start().await else { return };
+    let (mut remote, commits) = sample_remote();
+    let slug = unique_slug("incremental");
+    app.register(&slug, &remote).await;
+    let body: Value = app.post(&format!("/api/hook/update/{}", slug), json!({})).await.json().await.unwrap();
+    assert_eq!((&body["processed"], &body["since_commit"]), (&json!(2), &Value::Null), "{}", body);
+    let repo_url = format!("https://github.com/{}.git", slug);
+    assert_eq!(kicad_db::processed_up_to(&app.pool, &repo_url, "main").await.unwrap().as_deref(), Some(commits[1].as_str()));
+
+    // Nothing new, nothing walked
+    let body: Value = app.post(&format!("/api/hook/update/{}", slug), json!({})).await.json().await.unwrap();
+    assert_eq!((&body["processed"], &body["since_commit"]), (&json!(0), &json!(commits[1])), "{}", body);
+
+    let changed = TWO_RESISTORS.replace("\"10k\"", "\"22k\"");
+    let third = remote.commit(&[("divider.kicad_sch", &changed)], "Raise both resistors to 22k");
+    let body: Value = app.post(&format!("/api/hook/update/{}", slug), json!({})).await.json().await.unwrap();
+    assert_eq!((&body["processed"], &body["since_commit"]), (&json!(1), &json!(commits[1])), "{}", body);
+    assert_eq!(kicad_db::processed_up_to(&app.pool, &repo_url, "main").await.unwrap(), Some(third));
+}
+
+#[tokio::test]
+async fn updates_process_history_spanning_several_log_pages() {
+    let Some(app) = TestApp::start().await else { return };
+    // More than two of the 64-commit pages the git log streams: processing a
+    // page checks commits out while the log walks on, so they mustn't wait
+    // on each other for the cache
+    let mut remote = FakeRemote::new();
+    let mut tip = String::new();
+    for i in 0..129 {
+        let value = TWO_RESISTORS.replacen("\"10k\"", &format!("\"{}k\"", i + 1), 1);
+        tip = remote.commit(&[("divider.kicad_sch", &value)], &format!("Set R1 to {}k", i + 1));
+    }
+    let slug = unique_slug("three-pages");
+    app.register(&slug, &remote).await;
+
+    let path = format!("/api/hook/update/{}", slug);
+    let update = app.post(&path, json!({}));
+    let response = tokio::time::timeout(Duration::from_secs(300), update)
+        .await
+        .expect("the update finishes rather than deadlocking");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!((&body["processed"], &body["errors"]), (&json!(129), &json!([])), "{}", body);
+    let repo_url = format!("https://github.com/{}.git", slug);
+    assert_eq!(kicad_db::processed_up_to(&app.pool, &repo_url, "main").await.unwrap(), Some(tip));
+}
+
+#[tokio::test]
+async fn git_reads_between_log_pages_dont_wait_on_the_walk() {
+    let Some(app) = TestApp::start().await else { return };
+    let mut remote = FakeRemote::new();
+    for i in 0..129 {
+        let value = TWO_RESISTORS.replacen("\"10k\"", &format!("\"{}k\"", i + 1), 1);
+        remote.commit(&[("divider.kicad_sch", &value)], &format!("Set R1 to {}k", i + 1));
+    }
+    let slug = unique_slug("nested-reads");
+    app.register(&slug, &remote).await;
+
+    // While a page is handled the walk has read on, and waits to hand over
+    // the next one; fetching (locking the cache exclusively) and reading a
+    // commit must not wait for it in turn
+    let walk = async {
+        let mut pages = git::schematic_commit_pages(&slug, LogFilter::default());
+        let mut walked = 0;
+        while let Some(page) = pages.next().await {
+            let page = page.expect("the walk fails");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(git::get_repo(&slug).await.expect("the fetch fails"));
+            drop(git::get_repo_at(&slug, &page[0].commit_hash).await.expect("the read fails"));
+            walked += page.len();
+        }
+        walked
+    };
+    let walked = tokio::time::timeout(Duration::from_secs(120), walk)
+        .await
+        .expect("reads finish rather than deadlocking with the walk");
+    assert_eq!(walked, 129);
+}
+
+#[tokio::test]
+async fn hooks_need_a_hook_key_and_a_valid_slug() {
+    let Some(app) = TestApp::start().await else { return };
+
+    let response = app
+        .anonymous(Method::POST, "/api/hook/update/e2e/anyone")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+
+    let response = app.post("/api/hook/update/not-a-slug", json!({})).await;
+    assert_eq!(response.status(), 422);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "validation_failed");
+    assert_eq!(body["violations"][0]["field"], "repo");
+}
+
+fn github_signature(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[tokio::test]
+async fn github_webhook_checks_signature_and_skips_redeliveries() {
+    let Some(app) = TestApp::start().await else { return };
+    let (remote, commits) = sample_remote();
+    let slug = unique_slug("github");
+    app.register_with(&slug, &remote, json!({ "webhook_secret": "hunter2" })).await;
+
+    let payload = json!({
+        "ref": "refs/heads/main",
+        "repository": { "full_name": slug },
+        "commits": [{ "id": commits[1], "message": "Change R1 to 4.7k" }],
+    })
+    .to_string();
+    let delivery = |signature: String| {
+        app.anonymous(Method::POST, &format!("/api/hook/github/{}", slug))
+            .header("Content-Type", "application/json")
+            .header("X-GitHub-Event", "push")
+            .header("X-GitHub-Delivery", "delivery-1")
+            .header("X-Hub-Signature-256", signature)
+            .body(payload.clone())
+    };
+
+    let response = delivery(github_signature("wrong", payload.as_bytes())).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+
+    let response = delivery(github_signature("hunter2", payload.as_bytes())).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["processed"], 2);
+    assert_eq!(body["duplicate"], false);
+
+    // GitHub redelivers with the same ID
+    let response = delivery(github_signature("hunter2", payload.as_bytes())).send().await.unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["duplicate"], true);
+    assert_eq!(body["processed"], 0);
+}
+
+#[tokio::test]
+async fn gitlab_webhook_skips_redeliveries() {
+    let Some(app) = TestApp::start().await else { return };
+    let (remote, _) = sample_remote();
+    let slug = format!("gitlab.com/{}", unique_slug("gitlab"));
+    app.register_with(&slug, &remote, json!({ "webhook_secret": "hunter2" })).await;
+    let delivery = |uuid: &str| {
+        app.anonymous(Method::POST, &format!("/api/hook/gitlab/{}", slug))
+            .header("X-Gitlab-Event", "Push Hook")
+            .header("X-Gitlab-Event-UUID", uuid)
+            .header("X-Gitlab-Token", "hunter2")
+            .json(&json!({ "object_kind": "push", "ref": "refs/heads/main" }))
+    };
+
+    let body: Value = delivery("gitlab-1").send().await.unwrap().json().await.unwrap();
+    assert_eq!((&body["processed"], &body["duplicate"]), (&json!(2), &json!(false)), "{}", body);
+
+    // GitLab resends a failed or retried hook with the same UUID
+    let body: Value = delivery("gitlab-1").send().await.unwrap().json().await.unwrap();
+    assert_eq!((&body["processed"], &body["duplicate"]), (&json!(0), &json!(true)), "{}", body);
+    let body: Value = delivery("gitlab-2").send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["duplicate"], false, "{}", body);
+}
+
+#[tokio::test]
+async fn bitbucket_webhook_skips_redeliveries() {
+    let Some(app) = TestApp::start().await else { return };
+    let (remote, _) = sample_remote();
+    let path = unique_slug("bitbucket");
+    app.register_with(&format!("bitbucket.org/{}", path), &remote, json!({ "webhook_secret": "hunter2" })).await;
+    let payload = json!({ "push": { "changes": [{ "new": { "name": "main", "type": "branch" } }] } }).to_string();
+    let delivery = |uuid: &str| {
+        app.anonymous(Method::POST, &format!("/api/hook/bitbucket/{}", path))
+            .header("Content-Type", "application/json")
+            .header("X-Event-Key", "repo:push")
+            .header("X-Request-UUID", uuid)
+            .header("X-Hub-Signature", github_signature("hunter2", payload.as_bytes()))
+            .body(payload.clone())
+    };
+
+    let body: Value = delivery("bitbucket-1").send().await.unwrap().json().await.unwrap();
+    assert_eq!((&body["processed"], &body["duplicate"]), (&json!(2), &json!(false)), "{}", body);
+
+    // Bitbucket resends a delivery with the same request UUID
+    let body: Value = delivery("bitbucket-1").send().await.unwrap().json().await.unwrap();
+    assert_eq!((&body["processed"], &body["duplicate"]), (&json!(0), &json!(true)), "{}", body);
+    let body: Value = delivery("bitbucket-2").send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["duplicate"], false, "{}", body);
+}
+
+#[tokio::test]
+async fn gitlab_and_bitbucket_webhooks_process_the_repo_their_url_names() {
+    let Some(app) = TestApp::start().await else { return };
+    let (remote, _) = sample_remote();
+
+    let gitlab = format!("gitlab.com/{}", unique_slug("gitlab"));
+    app.register_with(&gitlab, &remote, json!({ "webhook_secret": "hunter2" })).await;
+    let push = |project_url: &str| {
+        let payload = json!({
+            "object_kind": "push",
+            "ref": "refs/heads/main",
+            "project": { "git_http_url": project_url },
+        });
+        app.anonymous(Method::POST, &format!("/api/hook/gitlab/{}", gitlab))
+            .header("X-Gitlab-Event", "Push Hook")
+            .header("X-Gitlab-Token", "hunter2")
+            .json(&payload)
+    };
+    // Not cloned from a host the payload names, nor processed as this repo
+    let response = push("https://gitlab.attacker.example/e2e/other.git").send().await.unwrap();
+    assert_eq!(response.status(), 400);
+    assert!(stored_commits(&app, &gitlab).await.is_empty());
+    let response = push(&format!("https://{}.git", gitlab)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!((&body["repo"], &body["processed"]), (&json!(gitlab), &json!(2)), "{}", body);
+
+    // Paths without a host are on bitbucket.org
+    let path = unique_slug("bitbucket");
+    let bitbucket = format!("bitbucket.org/{}", path);
+    app.register_with(&bitbucket, &remote, json!({ "webhook_secret": "hunter2" })).await;
+    let push = |full_name: &str| {
+        let payload = json!({
+            "repository": { "full_name": full_name },
+            "push": { "changes": [{ "new": { "name": "main", "type": "branch" } }] },
+        })
+        .to_string();
+        app.anonymous(Method::POST, &format!("/api/hook/bitbucket/{}", path))
+            .header("Content-Type", "application/json")
+            .header("X-Event-Key", "repo:push")
+            .header("X-Hub-Signature", github_signature("hunter2", payload.as_bytes()))
+            .body(payload)
+    };
+    let response = push("e2e/other").send().await.unwrap();
+    assert_eq!(response.status(), 400);
+    let response = push(&path).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!((&body["repo"], &body["processed"]), (&json!(bitbucket), &json!(2)), "{}", body);
+}
+
+#[tokio::test]
+async fn processing_policy_limits_which_pushes_are_processed() {
+    let Some(app) = TestApp::start().await else { return };
+    let (mut remote, _) = sample_remote();
+    let slug = unique_slug("policy");
+    let registration = json!({
+        "webhook_secret": "hunter2",
+        "processing": { "branches": ["main", "release/*"], "tags": false },
+    });
+    app.register_with(&slug, &remote, registration).await;
+
+    let push = |git_ref: &str| {
+        let payload = json!({ "ref": git_ref, "repository": { "full_name": slug } }).to_string();
+        app.anonymous(Method::POST, &format!("/api/hook/github/{}", slug))
+            .header("Content-Type", "application/json")
+            .header("X-GitHub-Event", "push")
+            .header("X-Hub-Signature-256", github_signature("hunter2", payload.as_bytes()))
+            .body(payload)
+    };
+
+    for git_ref in ["refs/heads/feature", "refs/heads/release/2.0/rc", "refs/tags/v1"] {
+        let response = push(git_ref).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["processed"], 0, "{} is left out", git_ref);
+    }
+    assert!(stored_commits(&app, &slug).await.is_empty());
+
+    let response = push("refs/heads/release/2.0").send().await.unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["processed"], 2);
+    assert!(app.ai.requests().is_empty(), "AI summaries are off by default");
+
+    // The policy is edited through the repo API
+    let path = format!("/api/repos/{}/processing", encoded(&slug));
+    let response = app
+        .request(Method::PUT, &path)
+        .json(&json!({ "branches": ["main", "["], "ai": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400, "invalid globs are rejected");
+    let response = app
+        .request(Method::PUT, &path)
+        .json(&json!({ "tags": false, "ai": true, "render": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body["processing"],
+        json!({ "branches": null, "tags": false, "ai": true, "render": false, "suggest_fixes": false })
+    );
+    let body: Value = app.get(&format!("/api/repos/{}", encoded(&slug))).await.json().await.unwrap();
+    assert_eq!(body["processing"]["ai"], true);
+
+    // New commits are summarized in the background
+    let changed = TWO_RESISTORS.replacen("\"10k\"", "\"22k\"", 1);
+    remote.commit(&[("divider.kicad_sch", &changed)], "Change R1 to 22k");
+    let body: Value = push("refs/heads/main").send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["processed"], 1);
+    for _ in 0..50 {
+        if !app.ai.requests().is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(!app.ai.requests().is_empty(), "a summary backfill was started");
+
+    let response = app
+        .request(Method::PUT, "/api/repos/github.com%2Fnobody%2Fnothing/processing")
+        .json(&json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn timeline_pages_through_history_longer_than_a_log_page() {
+    let Some(app) = TestApp::start().await else { return };
+    // More schematic commits than the 64 the git log streams at a time
+    let mut remote = FakeRemote::new();
+    let mut commits = Vec::new();
+    for i in 0..70 {
+        let value = TWO_RESISTORS.replacen("\"10k\"", &format!("\"{}k\"", i + 1), 1);
+        commits.push(remote.commit(&[("divider.kicad_sch", &value)], &format!("Set R1 to {}k", i + 1)));
+        if i % 10 == 0 {
+            remote.commit(&[("README.md", &format!("Revision {}\n", i))], "Update readme");
+        }
+    }
+    commits.reverse();
+    let slug = unique_slug("long-history");
+    app.register(&slug, &remote).await;
+
+    let timeline = |query: String| {
+        let path = format!("/api/repos/{}/timeline?{}", encoded(&slug), query);
+        let app = &app;
+        async move { app.get(&path).await.json::<Value>().await.unwrap() }
+    };
+    let hashes = |page: &Value| -> Vec<String> {
+        page["commits"].as_array().unwrap().iter().map(|c| c["commit_hash"].as_str().unwrap().to_string()).collect()
+    };
+    let first = timeline("limit=50".to_string()).await;
+    assert_eq!(hashes(&first), commits[..50]);
+    let cursor = first["next_cursor"].as_str().unwrap();
+    let second = timeline(format!("limit=50&cursor={}", cursor)).await;
+    assert_eq!(hashes(&second), commits[50..]);
+    assert!(second["next_cursor"].is_null(), "{}", second);
+
+    // since_commit leaves out it and everything before it
+    let recent = timeline(format!("since_commit={}", &commits[3][..10])).await;
+    assert_eq!(hashes(&recent), commits[..3]);
+    let response = app.get(&format!("/api/repos/{}/timeline?since_commit=0123456789", encoded(&slug))).await;
+    assert_eq!(response.status(), 404);
+}
@@ -0,0 +1,131 @@
+// End-to-end tests of schematic image uploads; see common/mod.rs for the
+// harness.
+//
+// USAGE:
+// cargo test --test images
+
+mod common;
+
+use common::{encoded, unique_slug, FakeRemote, TestApp, TWO_RESISTORS};
+use reqwest::Method;
+use serde_json::Value;
+
+const SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#;
+
+#[tokio::test]
+async fn identical_renders_are_stored_once() {
+    let Some(app) = TestApp::start().await else { return };
+    let mut remote = FakeRemote::new();
+    let first = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let second = remote.commit(&[("README.md", "# Divider\n")], "Add readme");
+    let third = remote.commit(&[("NOTES.md", "Notes\n")], "Add notes");
+    let slug = unique_slug("images");
+    app.register(&slug, &remote).await;
+    let image_path = |commit: &str| format!("/api/repos/{}/commits/{}/image", encoded(&slug), commit);
+    let upload = |commit: &str, body: &'static str| {
+        app.request(Method::PUT, &image_path(commit))
+            .header("Content-Type", "image/svg+xml")
+            .body(body)
+            .send()
+    };
+
+    let response = upload(&first, SVG).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let stored: Value = response.json().await.unwrap();
+    assert_eq!(stored["content_type"], "image/svg+xml");
+    assert_eq!(stored["size"], SVG.len());
+    assert_eq!(stored["reused"], false);
+
+    // The same render for the next commit is recognized
+    let response = upload(&second, SVG).await.unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["digest"], stored["digest"]);
+    assert_eq!(body["reused"], true);
+
+    // ... or attached by digest, without sending it at all
+    let digest = stored["digest"].as_str().unwrap();
+    let response = app
+        .request(Method::PUT, &format!("{}?digest={}", image_path(&third[..7]), digest))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["commit"], third);
+
+    let response = app.get(&image_path(&third)).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/svg+xml");
+    assert_eq!(response.headers()["etag"], format!("\"{}\"", digest));
+    assert_eq!(response.text().await.unwrap(), SVG);
+
+    let copies: i64 = sqlx::query_scalar("SELECT count(*) FROM images")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(copies, 1);
+}
+
+#[tokio::test]
+async fn uploads_must_be_png_or_svg() {
+    let Some(app) = TestApp::start().await else { return };
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let slug = unique_slug("bad-images");
+    app.register(&slug, &remote).await;
+    let image_path = format!("/api/repos/{}/commits/{}/image", encoded(&slug), commit);
+
+    let response = app.request(Method::PUT, &image_path).body("not an image").send().await.unwrap();
+    assert_eq!(response.status(), 400);
+
+    let response = app
+        .request(Method::PUT, &format!("{}?digest={}", image_path, "0".repeat(64)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404, "unknown digests have to be uploaded");
+
+    let response = app
+        .request(Method::PUT, &format!("{}?digest=abc", image_path))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    let response = app.anonymous(Method::PUT, &image_path).body(SVG).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn schematics_export_as_pdf() {
+    let Some(app) = TestApp::start().await else { return };
+    let mut remote = FakeRemote::new();
+    let readme = remote.commit(&[("README.md", "# Divider\n")], "Add readme");
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let slug = unique_slug("pdf");
+    app.register(&slug, &remote).await;
+    let pdf_path = |commit: &str| format!("/api/repos/{}/commits/{}/schematic.pdf", encoded(&slug), commit);
+
+    let response = app.get(&pdf_path(&commit)).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/pdf");
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    let pdf = response.bytes().await.unwrap();
+    assert!(pdf.starts_with(b"%PDF-"));
+    let text = String::from_utf8_lossy(&pdf);
+    assert_eq!(text.matches("/Type /Page\n").count(), 1, "one page per sheet");
+
+    // Stored, so it comes back the same
+    let again = app.get(&pdf_path(&commit[..8])).await.bytes().await.unwrap();
+    assert_eq!(again, pdf);
+    let response = app
+        .request(Method::GET, &pdf_path(&commit))
+        .header("If-None-Match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 304);
+
+    let response = app.get(&pdf_path(&readme)).await;
+    assert_eq!(response.status(), 404);
+}
@@ -0,0 +1,184 @@
+// End-to-end tests of the kicad-tool binary on local files; see
+// common/mod.rs for the harness.
+//
+// USAGE:
+// cargo test --test kicad_tool
+
+mod common;
+
+use common::{FakeRemote, TWO_RESISTORS};
+use git2::{Repository, Signature};
+use serde_json::Value;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn kicad_tool(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_kicad-tool"))
+        .args(args)
+        .output()
+        .expect("failed to run kicad-tool")
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+/// A directory holding `divider.kicad_sch` with the given contents
+fn project(schematic: &str) -> (TempDir, String) {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("divider.kicad_sch");
+    std::fs::write(&path, schematic).unwrap();
+    (dir, path.display().to_string())
+}
+
+#[test]
+fn parses_and_lists_the_bom() {
+    let (_dir, path) = project(TWO_RESISTORS);
+    let distilled: Value = serde_json::from_str(&stdout(&kicad_tool(&["parse", &path]))).unwrap();
+    let components = distilled["components"].as_object().unwrap();
+    assert_eq!(components.len(), 2);
+    assert_eq!(components["R1"]["lib_id"], "Device:R");
+
+    let bom: Value = serde_json::from_str(&stdout(&kicad_tool(&["bom", &path]))).unwrap();
+    assert_eq!(bom[0]["quantity"], 2);
+    assert_eq!(bom[0]["references"], serde_json::json!(["R1", "R2"]));
+    let csv = stdout(&kicad_tool(&["bom", &path, "--csv"]));
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("Quantity,References,Value,Footprint,MPN"));
+    assert!(lines.next().unwrap().starts_with("2,R1 R2,10k,"), "{}", csv);
+}
+
+#[test]
+fn diffs_two_schematics() {
+    let (_old, old) = project(TWO_RESISTORS);
+    let (_new, new) = project(&TWO_RESISTORS.replacen("\"10k\"", "\"22k\"", 1));
+    let described = stdout(&kicad_tool(&["diff", &old, &new]));
+    assert!(described.contains("R1") && described.contains("22k"), "{}", described);
+    assert_eq!(stdout(&kicad_tool(&["diff", &old, &old])).trim(), "No component or net changes.");
+    let diff: Value = serde_json::from_str(&stdout(&kicad_tool(&["diff", &old, &new, "--json"]))).unwrap();
+    assert!(diff.is_object());
+}
+
+#[test]
+fn renders_each_sheet() {
+    let (dir, path) = project(TWO_RESISTORS);
+    let out = dir.path().join("svg");
+    let written = stdout(&kicad_tool(&["render", &path, "--out", &out.display().to_string()]));
+    let svg = Path::new(written.trim());
+    assert_eq!(svg, out.join("divider.svg"));
+    assert!(std::fs::read_to_string(svg).unwrap().starts_with("<svg"));
+}
+
+#[test]
+fn summarize_skips_commits_without_schematic_changes() {
+    let mut remote = FakeRemote::new();
+    remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    remote.commit(&[("README.md", "# Divider")], "Add a readme");
+    let repo = remote.path().display().to_string();
+    // Answered before any configuration or model is needed
+    let output = kicad_tool(&["summarize", "--repo", &repo]);
+    assert_eq!(stdout(&output).trim(), "No component or net changes.");
+}
+
+/// Write `divider.kicad_sch` into `repo`'s work tree and stage it
+fn stage(repo: &Repository, schematic: &str) {
+    std::fs::write(repo.workdir().unwrap().join("divider.kicad_sch"), schematic).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("divider.kicad_sch")).unwrap();
+    index.write().unwrap();
+}
+
+fn commit_staged(repo: &Repository, message: &str) {
+    let mut index = repo.index().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let author = Signature::now("Test Author", "author@example.com").unwrap();
+    let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+    let parents: Vec<_> = parent.iter().collect();
+    repo.commit(Some("HEAD"), &author, &author, message, &tree, &parents).unwrap();
+}
+
+#[test]
+fn check_blocks_new_erc_errors_unless_suppressed() {
+    let dir = TempDir::new().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    let path = dir.path().display().to_string();
+    stage(&repo, TWO_RESISTORS);
+    commit_staged(&repo, "Add voltage divider");
+    assert_eq!(stdout(&kicad_tool(&["check", "--repo", &path])).trim(), "No schematic changes to check.");
+
+    // An unannotated reference is an error; its dangling pins only warnings
+    stage(&repo, &TWO_RESISTORS.replace("\"R1\"", "\"R?\""));
+    let output = kicad_tool(&["check", "--repo", &path]);
+    assert_eq!(output.status.code(), Some(1));
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.contains("[duplicate_reference] R? is not annotated"), "{}", report);
+    assert!(report.contains("1 blocking problem(s)"), "{}", report);
+
+    let config = "[[suppress]]\nrule = \"duplicate_reference\"\nreference = \"R?\"\nreason = \"Annotated at release\"\n";
+    std::fs::write(dir.path().join(".kicad-check.toml"), config).unwrap();
+    let report = stdout(&kicad_tool(&["check", "--repo", &path]));
+    assert!(report.contains("1 suppressed:") && report.contains("(Annotated at release)"), "{}", report);
+
+    // Warnings block too when asked to
+    std::fs::write(dir.path().join(".kicad-check.toml"), format!("fail_on_warnings = true\n{}", config)).unwrap();
+    assert_eq!(kicad_tool(&["check", "--repo", &path]).status.code(), Some(1));
+
+    // A commit is checked against its parent, e.g. in CI
+    commit_staged(&repo, "Unannotate R1");
+    let output = kicad_tool(&["check", "HEAD", "--repo", &path, "--config", "/dev/null"]);
+    assert_eq!(output.status.code(), Some(1));
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.contains("in commit") && report.contains("(Unannotate R1), against"), "{}", report);
+    assert_eq!(stdout(&kicad_tool(&["check", "HEAD", "--base", "HEAD", "--repo", &path])).trim(), "No schematic changes to check.");
+}
+
+#[test]
+fn check_reports_for_ci() {
+    let dir = TempDir::new().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    let path = dir.path().display().to_string();
+    stage(&repo, TWO_RESISTORS);
+    commit_staged(&repo, "Add voltage divider");
+    stage(&repo, &TWO_RESISTORS.replace("\"R1\"", "\"R?\""));
+
+    let output = kicad_tool(&["check", "--repo", &path, "--format", "json"]);
+    assert_eq!(output.status.code(), Some(1));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["passed"], false);
+    let error = &report["problems"][0];
+    assert_eq!(error["rule"], "duplicate_reference");
+    assert_eq!(error["severity"], "error");
+    assert_eq!(error["blocking"], true);
+    assert_eq!(error["file"], "divider.kicad_sch");
+    let line = error["line"].as_u64().unwrap() as usize;
+    assert!(TWO_RESISTORS.replace("\"R1\"", "\"R?\"").lines().nth(line - 1).unwrap().contains("\"R?\""));
+
+    let output = kicad_tool(&["check", "--repo", &path, "--format", "github"]);
+    let annotations = String::from_utf8(output.stdout).unwrap();
+    let expected = format!("::error file=divider.kicad_sch,line={},title=ERC duplicate_reference::R? is not annotated", line);
+    assert!(annotations.lines().any(|l| l == expected), "{}", annotations);
+    assert!(annotations.contains("::warning file=divider.kicad_sch,"), "{}", annotations);
+
+    let output = kicad_tool(&["check", "--repo", &path, "--format", "junit"]);
+    let junit = String::from_utf8(output.stdout).unwrap();
+    assert!(junit.contains("failures=\"1\""), "{}", junit);
+    assert!(junit.contains("<testcase classname=\"erc.duplicate_reference\" name=\"R? is not annotated\""), "{}", junit);
+
+    // A schematic that doesn't parse is reported where it breaks
+    stage(&repo, "(kicad_sch (version 20250114)\n\t(paper \"A4\"\n");
+    let output = kicad_tool(&["check", "--repo", &path, "--format", "github"]);
+    assert_eq!(output.status.code(), Some(1));
+    let annotations = String::from_utf8(output.stdout).unwrap();
+    assert!(annotations.starts_with("::error file=divider.kicad_sch,line="), "{}", annotations);
+    assert!(!kicad_tool(&["check", "--repo", &path, "--format", "yaml"]).status.success());
+}
+
+#[test]
+fn rejects_bad_arguments() {
+    assert!(!kicad_tool(&["frobnicate"]).status.success());
+    assert!(!kicad_tool(&["parse"]).status.success());
+    let output = kicad_tool(&["parse", "/nonexistent/board.kicad_sch"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No such schematic"));
+}
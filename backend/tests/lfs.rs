@@ -0,0 +1,75 @@
+// End-to-end tests of repos keeping design files in Git LFS; see
+// common/mod.rs for the harness.
+//
+// USAGE:
+// cargo test --test lfs
+
+mod common;
+
+use common::{encoded, unique_slug, FakeRemote, TestApp, TWO_RESISTORS};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+/// Keep `content` in the remote's LFS store, returning the pointer committed
+/// in its place
+fn store_in_lfs(remote: &FakeRemote, content: &str) -> String {
+    let oid = hex::encode(Sha256::digest(content.as_bytes()));
+    let dir = remote.path().join("lfs/objects").join(&oid[..2]).join(&oid[2..4]);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join(&oid), content).unwrap();
+    pointer(&oid, content.len())
+}
+
+fn pointer(oid: &str, size: usize) -> String {
+    format!("version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize {}\n", oid, size)
+}
+
+#[tokio::test]
+async fn schematics_kept_in_lfs_are_fetched_but_other_assets_are_not() {
+    let Some(app) = TestApp::start().await else { return };
+    let mut remote = FakeRemote::new();
+    let schematic = store_in_lfs(&remote, TWO_RESISTORS);
+    let model = store_in_lfs(&remote, "ISO-10303-21;\nEND-ISO-10303-21;\n");
+    let commit = remote.commit(
+        &[("divider.kicad_sch", &schematic), ("divider.step", &model)],
+        "Move sheets and models to LFS",
+    );
+    let slug = unique_slug("lfs");
+    app.register(&slug, &remote).await;
+
+    let response = app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["processed"], 1, "{}", body);
+    assert_eq!(body["errors"], json!([]));
+
+    let response = app.get(&format!("/api/repos/{}/commits/{}/stats", encoded(&slug), commit)).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["stats"]["components"], 2, "{}", body);
+
+    for endpoint in ["files", "raw"] {
+        let path = format!("/api/repos/{}/commits/{}/{}/divider.kicad_sch", encoded(&slug), commit, endpoint);
+        let response = app.get(&path).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().await.unwrap(), TWO_RESISTORS, "{} serves the schematic", endpoint);
+
+        // Models aren't needed to process schematics, so they stay pointers
+        let path = format!("/api/repos/{}/commits/{}/{}/divider.step", encoded(&slug), commit, endpoint);
+        assert_eq!(app.get(&path).await.text().await.unwrap(), model);
+    }
+}
+
+#[tokio::test]
+async fn pointers_to_missing_lfs_objects_are_served_as_they_are() {
+    let Some(app) = TestApp::start().await else { return };
+    let mut remote = FakeRemote::new();
+    let missing = pointer(&hex::encode(Sha256::digest(b"never pushed")), 12);
+    let commit = remote.commit(&[("divider.kicad_sch", &missing)], "Add a sheet whose object wasn't pushed");
+    let slug = unique_slug("lfs-missing");
+    app.register(&slug, &remote).await;
+
+    let path = format!("/api/repos/{}/commits/{}/files/divider.kicad_sch", encoded(&slug), commit);
+    let response = app.get(&path).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), missing);
+}
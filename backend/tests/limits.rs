@@ -0,0 +1,80 @@
+// End-to-end tests of the request timeouts and body size limits; see
+// common/mod.rs for the harness.
+//
+// USAGE:
+// cargo test --test limits
+
+mod common;
+
+use common::TestApp;
+use kicad_backend::config::Config;
+use kicad_backend::limits::RouteClass;
+use kicad_db::xai_mock::MockReplies;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+async fn limited_app() -> Option<TestApp> {
+    let mut config = Config::default();
+    config.limits.request_timeout_secs = 1;
+    config.limits.max_body_bytes = 1024;
+    config.limits.webhook_max_body_bytes = 4096;
+    TestApp::start_with(config, MockReplies::default()).await
+}
+
+#[test]
+fn routes_are_classed_by_how_long_they_may_take() {
+    assert_eq!(RouteClass::of("/api/repos/acme%2Fboard/commits"), RouteClass::Short);
+    assert_eq!(RouteClass::of("/api/grok/summary/commit"), RouteClass::Long);
+    assert_eq!(RouteClass::of("/api/repos/acme%2Fboard/import"), RouteClass::Long);
+    assert_eq!(RouteClass::of("/api/grok/chat/stream"), RouteClass::Stream);
+    assert_eq!(RouteClass::of("/api/repos/acme%2Fboard/events"), RouteClass::Stream);
+}
+
+#[tokio::test]
+async fn bodies_over_the_limit_get_a_413() {
+    let Some(app) = limited_app().await else { return };
+    let padding = "x".repeat(2048);
+
+    // Plain JSON, validated JSON and a webhook's raw body all answer alike
+    let body = json!({ "repo": "acme/board", "clone_url": padding });
+    let response = app.post("/api/repos", body).await;
+    assert_eq!(response.status(), 413);
+    let error: Value = response.json().await.unwrap();
+    assert_eq!(error["error"], "payload_too_large");
+
+    let body = json!({ "repo": "acme/board", "commit": "HEAD", "question": padding });
+    let response = app.post("/api/grok/ask", body).await;
+    assert_eq!(response.status(), 413);
+    assert_eq!(response.json::<Value>().await.unwrap()["error"], "payload_too_large");
+
+    // Webhooks have a limit of their own
+    let response = app.post("/api/hook/github/acme/board", json!({ "padding": padding })).await;
+    assert_ne!(response.status(), 413);
+    let response = app.post("/api/hook/github/acme/board", json!({ "padding": padding.repeat(3) })).await;
+    assert_eq!(response.status(), 413);
+    assert_eq!(response.json::<Value>().await.unwrap()["error"], "payload_too_large");
+}
+
+#[tokio::test]
+async fn slow_clients_get_a_408() {
+    let Some(app) = limited_app().await else { return };
+
+    // Half a body, then nothing
+    let mut stream = tokio::net::TcpStream::connect(app.addr).await.unwrap();
+    let head = format!(
+        "POST /api/repos HTTP/1.1\r\nHost: {}\r\nX-API-Key: {}\r\nContent-Type: application/json\r\nContent-Length: 100\r\n\r\n{{\"repo\":",
+        app.addr, app.key
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    let started = Instant::now();
+    let mut response = vec![0; 4096];
+    let read = tokio::time::timeout(Duration::from_secs(10), stream.read(&mut response))
+        .await
+        .expect("the server never answered")
+        .unwrap();
+    let response = String::from_utf8_lossy(&response[..read]);
+    assert!(response.starts_with("HTTP/1.1 408"), "{}", response);
+    assert!(response.contains("\"error\":\"request_timeout\""), "{}", response);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,219 @@
+// End-to-end tests of onboarding a GitHub org's repositories, against a mock
+// GitHub API; see common/mod.rs for the harness.
+//
+// USAGE:
+// cargo test --test onboard
+
+mod common;
+
+use axum::{extract::Request, http::StatusCode, response::IntoResponse, Json, Router};
+use common::{encoded, FakeRemote, TestApp, LEGACY_TWO_RESISTORS, TWO_RESISTORS};
+use kicad_backend::config::{Config, GitHubConfig};
+use kicad_db::xai_mock::MockReplies;
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Requests the mock GitHub API received, as (method, path, body)
+static GITHUB_REQUESTS: Mutex<Vec<(String, String, Value)>> = Mutex::new(Vec::new());
+
+/// Repositories the mock lists for each org, and the file paths each repo's
+/// default branch holds
+static ORGS: Lazy<Mutex<HashMap<String, Value>>> = Lazy::new(Default::default);
+static TREES: Lazy<Mutex<HashMap<String, Vec<&'static str>>>> = Lazy::new(Default::default);
+
+/// Base URL of a mock GitHub API on a thread of its own, as each test has
+/// its own runtime
+static GITHUB: Lazy<String> = Lazy::new(|| {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            axum::serve(listener, Router::new().fallback(github)).await.unwrap();
+        });
+    });
+    url
+});
+
+/// A config pointing the app at the mock GitHub API
+fn with_github() -> Config {
+    Config {
+        github: GitHubConfig {
+            token: "test-token".to_string(),
+            api_url: GITHUB.clone(),
+        },
+        ..Default::default()
+    }
+}
+
+async fn github(request: Request) -> impl IntoResponse {
+    let (method, path) = (request.method().to_string(), request.uri().path().to_string());
+    let body = axum::body::to_bytes(request.into_body(), usize::MAX).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    GITHUB_REQUESTS.lock().unwrap().push((method.clone(), path.clone(), body));
+
+    let not_found = (StatusCode::NOT_FOUND, Json(json!({ "message": "Not Found" })));
+    let reply = match (method.as_str(), path.split('/').skip(1).collect::<Vec<_>>().as_slice()) {
+        ("GET", ["orgs", org, "repos"]) => match ORGS.lock().unwrap().get(*org) {
+            Some(repos) => repos.clone(),
+            None => return not_found,
+        },
+        ("GET", ["repos", owner, name, "git", "trees", _]) => {
+            match TREES.lock().unwrap().get(&format!("{}/{}", owner, name)) {
+                Some(paths) => json!({
+                    "tree": paths.iter().map(|path| json!({ "path": path, "type": "blob" })).collect::<Vec<_>>(),
+                    "truncated": false,
+                }),
+                None => return not_found,
+            }
+        }
+        ("GET", ["repos", _, _, "hooks"]) => json!([]),
+        ("POST", ["repos", _, _, "hooks"]) => json!({ "id": 1 }),
+        _ => return not_found,
+    };
+    (StatusCode::OK, Json(reply))
+}
+
+/// The mock's requests under `prefix`
+fn github_requests(prefix: &str) -> Vec<(String, String, Value)> {
+    let requests = GITHUB_REQUESTS.lock().unwrap();
+    requests.iter().filter(|(_, path, _)| path.starts_with(prefix)).cloned().collect()
+}
+
+fn unique_org() -> String {
+    format!("e2e-org-{}", &uuid::Uuid::new_v4().simple().to_string()[..8])
+}
+
+/// A repo as GitHub's repos API lists it
+fn listed(full_name: &str, clone_url: &str, fork: bool, size: u64) -> Value {
+    json!({
+        "full_name": full_name,
+        "clone_url": clone_url,
+        "default_branch": "main",
+        "private": false,
+        "fork": fork,
+        "archived": false,
+        "size": size,
+    })
+}
+
+#[tokio::test]
+async fn org_repos_with_kicad_files_are_registered_hooked_and_synced() {
+    let Some(app) = TestApp::start_with(with_github(), MockReplies::default()).await else { return };
+    let mut board = FakeRemote::new();
+    let commit = board.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let mut legacy = FakeRemote::new();
+    legacy.commit(&[("divider.pro", ""), ("divider.sch", LEGACY_TWO_RESISTORS)], "Add the KiCad 5 divider");
+    let org = unique_org();
+    let repo = |name: &str| format!("{}/{}", org, name);
+    ORGS.lock().unwrap().insert(
+        org.clone(),
+        json!([
+            listed(&repo("board"), &board.clone_url(), false, 12),
+            listed(&repo("firmware"), "https://github.com/x/firmware.git", false, 40),
+            listed(&repo("board-fork"), &board.clone_url(), true, 12),
+            listed(&repo("empty"), "https://github.com/x/empty.git", false, 0),
+            listed(&repo("legacy"), &legacy.clone_url(), false, 8),
+        ]),
+    );
+    TREES.lock().unwrap().extend([
+        (repo("board"), vec!["README.md", "hw/divider.kicad_sch", "hw/divider.kicad_pro"]),
+        (repo("firmware"), vec!["src/main.c", "docs/wiring.sch"]),
+        (repo("legacy"), vec!["divider.pro", "divider.sch"]),
+    ]);
+
+    let request = json!({
+        "github_org": org,
+        "public_url": "https://kicad.example.com/",
+        "processing": { "ai": true },
+    });
+    let response = app.post("/api/admin/onboard/github-org", request.clone()).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["scanned"], 5, "{}", body);
+    assert_eq!(body["registered"], 2);
+    assert_eq!(body["webhooks_installed"], 2);
+    assert_eq!(body["backfills_queued"], 2);
+    let outcomes: Vec<(&str, &str, &str)> = body["repos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| (r["repo"].as_str().unwrap(), r["status"].as_str().unwrap(), r["webhook"].as_str().unwrap()))
+        .collect();
+    let (board_slug, legacy_slug) = (repo("board"), repo("legacy"));
+    let (firmware, fork, empty) = (repo("firmware"), repo("board-fork"), repo("empty"));
+    assert_eq!(
+        outcomes,
+        [
+            (board_slug.as_str(), "registered", "installed"),
+            (firmware.as_str(), "skipped", "skipped"),
+            (fork.as_str(), "skipped", "skipped"),
+            (empty.as_str(), "skipped", "skipped"),
+            (legacy_slug.as_str(), "registered", "installed"),
+        ]
+    );
+    assert_eq!(body["repos"][1]["detail"], "no KiCad files on main");
+    assert_eq!(body["repos"][2]["detail"], "fork");
+
+    // Registered to be cloned from where GitHub said, with a webhook secret
+    let registered: Value = app.get(&format!("/api/repos/{}", encoded(&board_slug))).await.json().await.unwrap();
+    assert_eq!(registered["clone_url"], board.clone_url());
+    assert_eq!(registered["has_webhook_secret"], true);
+    assert_eq!(registered["processing"]["ai"], true);
+
+    let hooks = github_requests(&format!("/repos/{}/hooks", board_slug));
+    let (_, _, hook) = hooks.iter().find(|(method, _, _)| method == "POST").unwrap();
+    assert_eq!(hook["config"]["url"], format!("https://kicad.example.com/api/hook/github/{}", board_slug));
+    assert_eq!(hook["events"], json!(["push"]));
+    assert!(!hook["config"]["secret"].as_str().unwrap().is_empty());
+    // Skipped repos are never hooked
+    assert!(github_requests(&format!("/repos/{}/hooks", firmware)).is_empty());
+
+    // The history is processed in the background, then summarized
+    let stats = format!("/api/repos/{}/commits/{}/stats", encoded(&board_slug), commit);
+    let mut processed = false;
+    for _ in 0..100 {
+        if app.get(&stats).await.status() == 200 {
+            processed = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(processed, "{} was not processed", board_slug);
+    let mut jobs = Value::Null;
+    for _ in 0..100 {
+        jobs = app.get(&format!("/api/admin/jobs?repo={}", board_slug)).await.json().await.unwrap();
+        if !jobs["jobs"].as_array().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(jobs["jobs"].as_array().unwrap().len(), 1, "{}", jobs);
+
+    // Calling again leaves the registrations alone
+    let hooks_before = github_requests(&format!("/repos/{}", org)).len();
+    let body: Value = app.post("/api/admin/onboard/github-org", request).await.json().await.unwrap();
+    assert_eq!(body["registered"], 0, "{}", body);
+    assert_eq!(body["backfills_queued"], 0);
+    assert_eq!(body["repos"][0]["status"], "already_registered");
+    assert_eq!(body["repos"][4]["status"], "already_registered");
+    let requests = github_requests(&format!("/repos/{}", org));
+    assert!(requests[hooks_before..].iter().all(|(_, path, _)| !path.ends_with("/hooks")), "{:?}", requests);
+}
+
+#[tokio::test]
+async fn onboarding_unknown_orgs_fails() {
+    let Some(app) = TestApp::start_with(with_github(), MockReplies::default()).await else { return };
+
+    let response = app.post("/api/admin/onboard/github-org", json!({ "github_org": unique_org() })).await;
+    assert_eq!(response.status(), 404);
+    let response = app.post("/api/admin/onboard/github-org", json!({ "github_org": " " })).await;
+    assert_eq!(response.status(), 400);
+    let request = json!({ "github_org": unique_org(), "public_url": "kicad.example.com" });
+    let response = app.post("/api/admin/onboard/github-org", request).await;
+    assert_eq!(response.status(), 400);
+}
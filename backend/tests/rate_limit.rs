@@ -0,0 +1,78 @@
+// Tests of which address the per-client rate limits count a request against.
+//
+// USAGE:
+// cargo test --test rate_limit
+
+use axum::http::HeaderMap;
+use kicad_backend::rate_limit::TrustedProxies;
+use std::net::{IpAddr, SocketAddr};
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.append(*name, value.parse().unwrap());
+    }
+    headers
+}
+
+fn peer(ip: &str) -> SocketAddr {
+    SocketAddr::new(ip.parse().unwrap(), 40000)
+}
+
+fn ip(ip: &str) -> IpAddr {
+    ip.parse().unwrap()
+}
+
+fn proxies(entries: &[&str]) -> TrustedProxies {
+    TrustedProxies::parse(&entries.iter().map(|e| e.to_string()).collect::<Vec<_>>()).unwrap()
+}
+
+#[test]
+fn trusted_proxies_are_addresses_or_cidr_ranges() {
+    let trusted = proxies(&["127.0.0.1", "10.0.0.0/8", "2001:db8::/32"]);
+    assert!(trusted.contains(ip("127.0.0.1")));
+    assert!(trusted.contains(ip("::ffff:127.0.0.1")));
+    assert!(!trusted.contains(ip("127.0.0.2")));
+    assert!(trusted.contains(ip("10.200.3.4")));
+    assert!(!trusted.contains(ip("11.0.0.1")));
+    assert!(trusted.contains(ip("2001:db8:1::7")));
+    assert!(!trusted.contains(ip("2001:db9::7")));
+    assert!(proxies(&["0.0.0.0/0"]).contains(ip("203.0.113.9")));
+
+    for bad in ["localhost", "10.0.0.0/33", "::1/129", "10.0.0.0/x"] {
+        assert!(TrustedProxies::parse(&[bad.to_string()]).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn spoofed_headers_from_untrusted_peers_are_ignored() {
+    // Nothing trusted: the headers are the client's own, so only the socket
+    // address counts, or a new header would be a new budget
+    let none = TrustedProxies::default();
+    let spoofed = headers(&[
+        ("cf-connecting-ip", "1.1.1.1"),
+        ("x-forwarded-for", "1.1.1.1, 203.0.113.9"),
+    ]);
+    assert_eq!(none.client_ip(&spoofed, peer("127.0.0.1")), ip("127.0.0.1"));
+    assert_eq!(none.client_ip(&spoofed, peer("198.51.100.4")), ip("198.51.100.4"));
+    assert_eq!(none.client_ip(&HeaderMap::new(), peer("198.51.100.4")), ip("198.51.100.4"));
+    let garbage = headers(&[("x-forwarded-for", "1.1.1.1, not-an-ip")]);
+    assert_eq!(none.client_ip(&garbage, peer("198.51.100.4")), ip("198.51.100.4"));
+
+    // A proxy that isn't trusted gets no say either
+    let nginx = proxies(&["127.0.0.1"]);
+    assert_eq!(nginx.client_ip(&spoofed, peer("198.51.100.4")), ip("198.51.100.4"));
+}
+
+#[test]
+fn trusted_proxies_name_the_client() {
+    let nginx = proxies(&["127.0.0.1", "10.0.0.0/8"]);
+    let cloudflare = headers(&[("cf-connecting-ip", "192.0.2.7"), ("x-forwarded-for", "192.0.2.7, 172.70.1.1")]);
+    assert_eq!(nginx.client_ip(&cloudflare, peer("127.0.0.1")), ip("192.0.2.7"));
+
+    // Without CF-Connecting-IP, the nearest hop that isn't a trusted proxy
+    let chain = headers(&[("x-forwarded-for", "1.1.1.1"), ("x-forwarded-for", "203.0.113.9, 10.1.2.3")]);
+    assert_eq!(nginx.client_ip(&chain, peer("127.0.0.1")), ip("203.0.113.9"));
+    let all_proxies = headers(&[("x-forwarded-for", "10.1.2.3")]);
+    assert_eq!(nginx.client_ip(&all_proxies, peer("127.0.0.1")), ip("127.0.0.1"));
+}
@@ -0,0 +1,67 @@
+// End-to-end test of POST /api/admin/reload; see common/mod.rs for the
+// harness. The reload reads CONFIG_FILE, which is process-wide, so it has a
+// binary of its own.
+//
+// USAGE:
+// cargo test --test reload
+
+mod common;
+
+use common::{unique_slug, TestApp};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn reload_swaps_in_a_valid_config_and_keeps_the_old_one_otherwise() {
+    let Some(app) = TestApp::start().await else { return };
+    let dir = TempDir::new().unwrap();
+    let prompts = dir.path().join("prompts");
+    std::fs::create_dir(&prompts).unwrap();
+    std::fs::write(prompts.join("commit_summary.v99.j2"), "Summarize {{ repo }}").unwrap();
+    let file = dir.path().join("config.toml");
+    std::fs::write(
+        &file,
+        format!(
+            "port = 9999\nrequire_registered_repos = true\nprompts_dir = {:?}\n[xai]\napi_key = \"mock-key\"\n[rate_limits]\nlisting_per_minute = 7\n",
+            prompts.display().to_string()
+        ),
+    )
+    .unwrap();
+    std::env::set_var("CONFIG_FILE", &file);
+
+    // Unregistered repos are still accepted
+    let hook = format!("/api/hook/github/{}", unique_slug("reload"));
+    let push = json!({ "ref": "refs/heads/main", "commits": [] });
+    assert_ne!(app.post(&hook, push.clone()).await.status(), 404);
+
+    let response = app.post("/api/admin/reload", json!({})).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let labels: Vec<&str> = body["prompts"].as_array().unwrap().iter().filter_map(Value::as_str).collect();
+    assert!(labels.contains(&"commit_summary@v99"), "{}", body);
+    let restart: Vec<&str> = body["restart_required"].as_array().unwrap().iter().filter_map(Value::as_str).collect();
+    assert!(restart.contains(&"port"), "{}", body);
+    assert!(!restart.contains(&"require_registered_repos"), "{}", body);
+
+    // The next request reads the new config
+    let response = app.post(&hook, push.clone()).await;
+    assert_eq!(response.status(), 404);
+    assert!(response.text().await.unwrap().contains("not_registered"));
+
+    // So do the rate limiters
+    let response = app.get("/api/grok/personas").await;
+    assert_eq!(response.headers()["x-ratelimit-limit"], "7");
+
+    // A broken file is rejected and the reloaded config stays
+    for broken in [
+        "require_registered_repos = \"sometimes\"\n",
+        "[drift]\neval_commits = [\"owner/repo\"]\n",
+    ] {
+        std::fs::write(&file, broken).unwrap();
+        let response = app.post("/api/admin/reload", json!({})).await;
+        assert_eq!(response.status(), 422);
+        let body = response.text().await.unwrap();
+        assert!(body.contains("invalid_config"), "{}", body);
+    }
+    assert_eq!(app.post(&hook, push).await.status(), 404);
+}
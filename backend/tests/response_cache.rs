@@ -0,0 +1,87 @@
+// Tests of the API response cache: the in-memory store on its own, and the
+// BOM endpoint served through it (skipped without a database server).
+//
+// USAGE:
+// cargo test --test response_cache
+
+mod common;
+
+use common::{encoded, unique_slug, FakeRemote, TestApp, TWO_RESISTORS};
+use kicad_backend::error::AppError;
+use kicad_backend::services::response_cache::{MemoryResponseStore, ResponseCache, ResponseStore};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn memory_store_expires_and_evicts() {
+    let store = MemoryResponseStore::new(2);
+    store.put("api:a|1", b"one".to_vec(), Duration::from_secs(60)).await.unwrap();
+    store.put("api:a|2", b"two".to_vec(), Duration::from_secs(30)).await.unwrap();
+    assert_eq!(store.get("api:a|1").await.unwrap(), Some(b"one".to_vec()));
+
+    // Full, so the entry expiring soonest makes way
+    store.put("api:b|1", b"three".to_vec(), Duration::from_secs(60)).await.unwrap();
+    assert_eq!(store.get("api:a|2").await.unwrap(), None);
+    assert_eq!(store.get("api:b|1").await.unwrap(), Some(b"three".to_vec()));
+
+    store.put("api:a|3", b"four".to_vec(), Duration::ZERO).await.unwrap();
+    assert_eq!(store.get("api:a|3").await.unwrap(), None);
+
+    store.delete_prefix("api:a|").await.unwrap();
+    assert_eq!(store.get("api:a|1").await.unwrap(), None);
+    assert_eq!(store.get("api:b|1").await.unwrap(), Some(b"three".to_vec()));
+}
+
+#[tokio::test]
+async fn read_through_computes_once_until_invalidated() {
+    let cache = ResponseCache::with_store(Arc::new(MemoryResponseStore::new(10)), Duration::from_secs(60));
+    let repo = unique_slug("read-through");
+    let computed = AtomicUsize::new(0);
+    let compute = || async {
+        computed.fetch_add(1, Ordering::SeqCst);
+        Ok::<_, AppError>(json!({ "value": "10k" }))
+    };
+
+    let first = cache.read_through("bom", &repo, "c1", "", None, true, compute()).await.unwrap();
+    let second = cache.read_through("bom", &repo, "c1", "", None, true, compute()).await.unwrap();
+    assert_eq!(first.headers()["x-cache"], "miss");
+    assert_eq!(second.headers()["x-cache"], "hit");
+    assert_eq!(computed.load(Ordering::SeqCst), 1);
+
+    // Other commits and variants, and uncacheable requests, are computed
+    cache.read_through("bom", &repo, "c2", "", None, true, compute()).await.unwrap();
+    cache.read_through("bom", &repo, "c1", "x", None, true, compute()).await.unwrap();
+    cache.read_through("bom", &repo, "c1", "", None, false, compute()).await.unwrap();
+    assert_eq!(computed.load(Ordering::SeqCst), 4);
+
+    cache.invalidate_repo(&repo).await;
+    let third = cache.read_through("bom", &repo, "c1", "", None, true, compute()).await.unwrap();
+    assert_eq!(third.headers()["x-cache"], "miss");
+    assert_eq!(computed.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn bom_is_cached_until_the_repo_is_refreshed() {
+    let Some(app) = TestApp::start_caching_responses().await else { return };
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let slug = unique_slug("cached-bom");
+    app.register(&slug, &remote).await;
+    app.post(&format!("/api/hook/update/{}", slug), json!({})).await;
+
+    let path = format!("/api/repos/{}/commits/{}/bom", encoded(&slug), commit);
+    let first = app.get(&path).await;
+    assert_eq!(first.status(), 200);
+    assert_eq!(first.headers()["x-cache"], "miss");
+    let first: Value = first.json().await.unwrap();
+
+    let second = app.get(&path).await;
+    assert_eq!(second.headers()["x-cache"], "hit");
+    assert_eq!(second.json::<Value>().await.unwrap(), first);
+
+    let response = app.post(&format!("/api/hook/refresh/{}", slug), json!({})).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(app.get(&path).await.headers()["x-cache"], "miss");
+}
@@ -0,0 +1,60 @@
+// Tests of the parsed schematic cache; they parse the fixture directly and
+// need neither the server nor the database.
+//
+// USAGE:
+// cargo test --test schematic_cache
+
+mod common;
+
+use common::{unique_slug, TWO_RESISTORS};
+use kicad_backend::services::{git, schematic_cache::SchematicCache};
+use kicad_db::schematic::model::parse_sheet;
+
+const PATH: &str = "divider.kicad_sch";
+
+#[test]
+fn repeated_parses_share_one_entry() {
+    let cache = SchematicCache::new(1024 * 1024);
+    let repo = unique_slug("cache-hit");
+    let first = cache.parse(&repo, "c1", PATH, TWO_RESISTORS).unwrap();
+    let second = cache.parse(&repo, "c1", PATH, TWO_RESISTORS).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(first, parse_sheet(PATH, TWO_RESISTORS).unwrap());
+    assert_eq!(cache.size(), (1, TWO_RESISTORS.len()));
+
+    // Another commit's copy of the file is its own entry
+    cache.parse(&repo, "c2", PATH, TWO_RESISTORS).unwrap();
+    assert_eq!(cache.size(), (2, 2 * TWO_RESISTORS.len()));
+}
+
+#[test]
+fn least_recently_used_sheets_are_evicted() {
+    let cache = SchematicCache::new(2 * TWO_RESISTORS.len());
+    let repo = unique_slug("cache-evict");
+    for commit in ["c1", "c2", "c3"] {
+        cache.parse(&repo, commit, PATH, TWO_RESISTORS).unwrap();
+    }
+    assert_eq!(cache.size(), (2, 2 * TWO_RESISTORS.len()));
+
+    // Nothing is kept when the capacity is 0 or smaller than the sheet
+    let off = SchematicCache::new(0);
+    off.parse(&repo, "c1", PATH, TWO_RESISTORS).unwrap();
+    assert_eq!(off.size(), (0, 0));
+    let small = SchematicCache::new(TWO_RESISTORS.len() - 1);
+    small.parse(&repo, "c1", PATH, TWO_RESISTORS).unwrap();
+    assert_eq!(small.size(), (0, 0));
+}
+
+#[tokio::test]
+async fn invalidating_the_git_cache_drops_parsed_sheets() {
+    let cache = SchematicCache::new(1024 * 1024);
+    let repo = unique_slug("cache-invalidate");
+    cache.parse(&repo, "c1", PATH, TWO_RESISTORS).unwrap();
+    assert_eq!(cache.size().0, 1);
+
+    git::invalidate_cache(&repo).await.unwrap();
+    // The stale entry is found, dropped and parsed again
+    cache.parse(&repo, "c1", PATH, TWO_RESISTORS).unwrap();
+    assert_eq!(cache.size(), (1, TWO_RESISTORS.len()));
+    assert_eq!(git::cache_generation(&repo), 1);
+}
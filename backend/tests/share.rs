@@ -0,0 +1,156 @@
+// End-to-end tests of share links: minting them, and the read-only views
+// they open without an API key; see common/mod.rs for the harness.
+//
+// USAGE:
+// cargo test --test share
+
+mod common;
+
+use chrono::{Duration, Utc};
+use common::{encoded, unique_slug, FakeRemote, TestApp, TWO_RESISTORS};
+use kicad_backend::config::{Config, ShareConfig};
+use kicad_backend::share::{self, ShareGrant};
+use kicad_backend::types::ShareScope;
+use kicad_db::xai_mock::MockReplies;
+use reqwest::{header, Method};
+use serde_json::{json, Value};
+
+const SECRET: &str = "a-share-secret-of-at-least-32-chars";
+
+async fn sharing_app() -> Option<TestApp> {
+    let config = Config {
+        share: ShareConfig {
+            secret: SECRET.to_string(),
+            ..ShareConfig::default()
+        },
+        ..Config::default()
+    };
+    TestApp::start_with(config, MockReplies::default()).await
+}
+
+#[tokio::test]
+async fn share_links_open_their_views_without_a_key() {
+    let Some(app) = sharing_app().await else { return };
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let slug = unique_slug("share");
+    app.register(&slug, &remote).await;
+    let mint = format!("/api/repos/{}/commits/{}/share", encoded(&slug), &commit[..8]);
+
+    let response = app.post(&mint, json!({})).await;
+    assert_eq!(response.status(), 200);
+    let link: Value = response.json().await.unwrap();
+    assert_eq!(link["commit"], commit);
+    assert_eq!(link["scopes"], json!(["summary", "diff", "svg"]));
+    let path = link["path"].as_str().unwrap().to_string();
+    assert_eq!(path, format!("/api/shared/{}", link["token"].as_str().unwrap()));
+
+    let response = app.anonymous(Method::GET, &path).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()[header::CACHE_CONTROL], "private, max-age=300");
+    let shared: Value = response.json().await.unwrap();
+    assert_eq!(shared["repo"], slug);
+    assert_eq!(shared["message"], "Add voltage divider");
+    assert_eq!(shared["sheets"], 1);
+
+    let response = app.anonymous(Method::GET, &format!("{}/changes", path)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let changes: Value = response.json().await.unwrap();
+    assert_eq!(changes["commit"], commit);
+    assert_eq!(changes["projects"][0]["added_components"].as_array().unwrap().len(), 2, "{}", changes);
+
+    let response = app.anonymous(Method::GET, &format!("{}/sheets/1", path)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("image/svg+xml"));
+    assert!(response.text().await.unwrap().contains("<svg"));
+    let response = app.anonymous(Method::GET, &format!("{}/sheets/2", path)).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    // Nothing summarized the commit yet
+    let response = app.anonymous(Method::GET, &format!("{}/summary", path)).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+
+    // A link only opens the views it was minted with
+    let link: Value = app.post(&mint, json!({ "scopes": ["diff"] })).await.json().await.unwrap();
+    let path = link["path"].as_str().unwrap();
+    let shared: Value = app.anonymous(Method::GET, path).send().await.unwrap().json().await.unwrap();
+    assert_eq!(shared["sheets"], Value::Null);
+    for view in ["summary", "sheets/1"] {
+        let response = app.anonymous(Method::GET, &format!("{}/{}", path, view)).send().await.unwrap();
+        assert_eq!(response.status(), 403, "{}", view);
+    }
+    let response = app.anonymous(Method::GET, &format!("{}/changes", path)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn forged_expired_and_overlong_share_links_are_refused() {
+    let Some(app) = sharing_app().await else { return };
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let slug = unique_slug("share-refused");
+    app.register(&slug, &remote).await;
+    let mint = format!("/api/repos/{}/commits/{}/share", encoded(&slug), commit);
+
+    let response = app.anonymous(Method::POST, &mint).json(&json!({})).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    let response = app.post(&mint, json!({ "expires_in_secs": 365 * 24 * 60 * 60 })).await;
+    assert_eq!(response.status(), 400);
+    let response = app.post(&mint, json!({ "scopes": [] })).await;
+    assert_eq!(response.status(), 400);
+
+    // Signed by someone without the server's secret
+    let forged = ShareGrant {
+        repo: slug.clone(),
+        commit: commit.clone(),
+        scopes: vec![ShareScope::Diff],
+        expires_at: Utc::now() + Duration::hours(1),
+    };
+    let token = share::mint("not-the-servers-secret-but-long-enough", &forged);
+    let response = app.anonymous(Method::GET, &format!("/api/shared/{}", token)).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    // Tampered with after signing
+    let link: Value = app.post(&mint, json!({ "scopes": ["diff"] })).await.json().await.unwrap();
+    let token = link["token"].as_str().unwrap();
+    let (_, signature) = token.split_once('.').unwrap();
+    let widened = share::mint(SECRET, &ShareGrant { scopes: vec![ShareScope::Svg], ..forged.clone() });
+    let (payload, _) = widened.split_once('.').unwrap();
+    let response = app
+        .anonymous(Method::GET, &format!("/api/shared/{}.{}", payload, signature))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+
+    let expired = ShareGrant {
+        expires_at: Utc::now() - Duration::minutes(1),
+        ..forged
+    };
+    let token = share::mint(SECRET, &expired);
+    let response = app.anonymous(Method::GET, &format!("/api/shared/{}/changes", token)).send().await.unwrap();
+    assert_eq!(response.status(), 410);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "share_link_expired", "{}", body);
+}
+
+#[tokio::test]
+async fn share_links_are_off_without_a_secret() {
+    let Some(app) = TestApp::start().await else { return };
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let slug = unique_slug("share-off");
+    app.register(&slug, &remote).await;
+
+    let response = app
+        .post(&format!("/api/repos/{}/commits/{}/share", encoded(&slug), commit), json!({}))
+        .await;
+    assert_eq!(response.status(), 503);
+    let grant = ShareGrant {
+        repo: slug,
+        commit,
+        scopes: vec![ShareScope::Diff],
+        expires_at: Utc::now() + Duration::hours(1),
+    };
+    let token = share::mint(SECRET, &grant);
+    let response = app.anonymous(Method::GET, &format!("/api/shared/{}", token)).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}
@@ -0,0 +1,94 @@
+// End-to-end test of the server with a sqlite:// DATABASE_URL: no database
+// server, schematics in a temp file, routes needing Postgres turned away.
+//
+// USAGE:
+// cargo test --test sqlite
+
+use kicad_backend::config::Config;
+use kicad_backend::services::git;
+use kicad_backend::state::AppState;
+use kicad_backend::storage;
+use kicad_db::xai_mock::{MockChatProvider, MockReplies};
+use kicad_db::{NewSchematic, PromptLibrary};
+use serde_json::Value;
+use std::net::SocketAddr;
+use tempfile::TempDir;
+
+/// An admin key from the config, as the SQLite store holds none
+const ADMIN_KEY: &str = "sqlite-admin-key";
+
+#[tokio::test]
+async fn serves_stored_commits_from_sqlite() {
+    let dir = TempDir::new().unwrap();
+    let ai = MockChatProvider::start(MockReplies::default()).await;
+    let mut config = Config {
+        database_url: format!("sqlite://{}", dir.path().join("kicad.db").display()),
+        ..Default::default()
+    };
+    config.xai.api_key = "mock-key".to_string();
+    config.xai.base_url = Some(ai.chat_url().to_string());
+    config.auth.admin_api_keys = vec![ADMIN_KEY.to_string()];
+
+    let (pool, store) = storage::connect(&config).await.expect("failed to open the SQLite store");
+    let slug = "sqlite/demo";
+    store
+        .store_schematic(&NewSchematic {
+            repo_url: git::repo_url(slug),
+            commit_hash: "a".repeat(40),
+            git_message: Some("Add the divider".to_string()),
+            ..Default::default()
+        })
+        .await
+        .expect("failed to store a schematic");
+
+    let state = AppState::new(pool, config, PromptLibrary::builtin())
+        .expect("invalid test config")
+        .with_store(store);
+    let app = kicad_backend::app(state);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .expect("server failed");
+    });
+    let client = reqwest::Client::new();
+    let get = |path: &str| {
+        client
+            .get(format!("http://{}{}", addr, path))
+            .header("X-API-Key", ADMIN_KEY)
+            .send()
+    };
+
+    let response = get("/api/repos/sqlite%2Fdemo/commits").await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["total"], 1, "{}", body);
+    assert_eq!(body["commits"][0]["message"], "Add the divider", "{}", body);
+
+    let body: Value = get("/readyz").await.unwrap().json().await.unwrap();
+    let database = body["checks"]
+        .as_array()
+        .and_then(|checks| checks.iter().find(|check| check["name"] == "database"))
+        .unwrap_or_else(|| panic!("no database check in {}", body));
+    assert_eq!(database["ok"], true, "{}", body);
+    assert!(database["detail"].as_str().unwrap().starts_with("sqlite: "), "{}", body);
+
+    let response = get("/api/keys").await.unwrap();
+    assert_eq!(response.status(), 503);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("postgres_required"), "{}", body);
+
+    // Stored keys live in Postgres, so they are turned away rather than looked up
+    let response = client
+        .get(format!("http://{}/api/repos/sqlite%2Fdemo/commits", addr))
+        .header("X-API-Key", kicad_backend::auth::generate_key())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 503);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "postgres_required", "{}", body);
+
+    server.abort();
+}
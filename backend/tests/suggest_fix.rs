@@ -0,0 +1,155 @@
+// End-to-end tests of AI-suggested fixes opened as pull requests, against a
+// mock GitHub API; see common/mod.rs for the harness.
+//
+// USAGE:
+// cargo test --test suggest_fix
+
+mod common;
+
+use axum::{extract::Request, http::StatusCode, response::IntoResponse, Json, Router};
+use common::{encoded, unique_slug, FakeRemote, TestApp, TWO_RESISTORS};
+use kicad_backend::config::{Config, GitHubConfig};
+use kicad_db::xai_mock::MockReplies;
+use once_cell::sync::Lazy;
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+
+/// Requests the mock GitHub API received, as (method, path, body)
+static GITHUB_REQUESTS: Mutex<Vec<(String, String, Value)>> = Mutex::new(Vec::new());
+
+/// Base URL of a mock GitHub API on a thread of its own, as each test has
+/// its own runtime
+static GITHUB: Lazy<String> = Lazy::new(|| {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            axum::serve(listener, Router::new().fallback(github)).await.unwrap();
+        });
+    });
+    url
+});
+
+/// A config pointing the app at the mock GitHub API
+fn with_github() -> Config {
+    Config {
+        github: GitHubConfig {
+            token: "test-token".to_string(),
+            api_url: GITHUB.clone(),
+        },
+        ..Default::default()
+    }
+}
+
+async fn github(request: Request) -> impl IntoResponse {
+    let (method, path) = (request.method().to_string(), request.uri().path().to_string());
+    let body = axum::body::to_bytes(request.into_body(), usize::MAX).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    GITHUB_REQUESTS.lock().unwrap().push((method.clone(), path.clone(), body));
+
+    let reply = match (method.as_str(), path.split('/').skip(4).collect::<Vec<_>>().as_slice()) {
+        ("GET", []) => json!({ "default_branch": "main" }),
+        ("GET", ["git", "commits", _]) => json!({ "tree": { "sha": "base-tree" } }),
+        ("POST", ["git", "trees"]) => json!({ "sha": "fix-tree" }),
+        ("POST", ["git", "commits"]) => json!({ "sha": "fix-commit" }),
+        ("POST", ["git", "refs"]) => json!({}),
+        ("GET", ["pulls"]) => json!([]),
+        ("POST", ["pulls"]) => json!({ "html_url": "https://github.test/pull/1" }),
+        _ => return (StatusCode::NOT_FOUND, Json(json!({ "message": "Not Found" }))),
+    };
+    (StatusCode::OK, Json(reply))
+}
+
+/// The mock's requests for `slug`
+fn github_requests(slug: &str) -> Vec<(String, String, Value)> {
+    let prefix = format!("/repos/{}", slug);
+    let requests = GITHUB_REQUESTS.lock().unwrap();
+    requests.iter().filter(|(_, path, _)| path.starts_with(&prefix)).cloned().collect()
+}
+
+fn edits(lines: &[&str]) -> MockReplies {
+    MockReplies {
+        stream_chunks: lines.iter().map(|line| format!("{}\n", line)).collect(),
+        ..MockReplies::default()
+    }
+}
+
+#[tokio::test]
+async fn suggested_fixes_open_a_pull_request_once_opted_in() {
+    let replies = edits(&["- set_value R1 4.7k", "- set_property R1 MPN RC0603FR-074K7L", "Done."]);
+    let Some(app) = TestApp::start_with(with_github(), replies).await else { return };
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let slug = unique_slug("fix");
+    app.register(&slug, &remote).await;
+    let path = format!("/api/grok/suggest-fix/{}/{}", encoded(&slug), &commit[..7]);
+    let change = json!({ "change": "Make R1 4.7k so the output sits lower" });
+
+    // Off unless the repo opts in, and nothing is asked of the model or GitHub
+    let response = app.post(&path, change.clone()).await;
+    assert_eq!(response.status(), 403);
+    assert!(app.ai.requests().is_empty());
+    assert!(github_requests(&slug).is_empty());
+    let response = app
+        .request(Method::PUT, &format!("/api/repos/{}/processing", encoded(&slug)))
+        .json(&json!({ "suggest_fixes": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    assert_eq!(app.post(&path, json!({ "change": " " })).await.status(), 422);
+
+    let response = app.post(&path, change.clone()).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["pull_request_url"], "https://github.test/pull/1");
+    assert_eq!(body["commit"], commit);
+    assert_eq!(body["edits"], json!(["set_value R1 4.7k", "set_property R1 MPN RC0603FR-074K7L"]));
+    assert_eq!(body["files"], json!(["divider.kicad_sch"]));
+    assert_eq!(body["prompt_version"], "suggest_fix@v1");
+    let branch = body["branch"].as_str().unwrap();
+    assert!(branch.starts_with(&format!("grokicad/fix-{}-", &commit[..7])), "{}", branch);
+
+    // The model saw the change and each component's pins and nets
+    let prompt = app.ai.requests()[0].body["messages"].to_string();
+    assert!(prompt.contains("Make R1 4.7k so the output sits lower"), "{}", prompt);
+    assert!(prompt.contains("- R2 10k"), "{}", prompt);
+
+    // The edited file goes on top of the commit, on the branch, into a pull request
+    let requests = github_requests(&slug);
+    let tree = &requests.iter().find(|(_, path, _)| path.ends_with("/git/trees")).unwrap().2;
+    assert_eq!(tree["base_tree"], "base-tree");
+    assert_eq!(tree["tree"][0]["path"], "divider.kicad_sch");
+    let content = tree["tree"][0]["content"].as_str().unwrap();
+    assert!(content.contains("(property \"Value\" \"4.7k\""), "{}", content);
+    assert!(content.contains("(property \"MPN\" \"RC0603FR-074K7L\""), "{}", content);
+    let created = &requests.iter().find(|(_, path, _)| path.ends_with("/git/commits")).unwrap().2;
+    assert_eq!(created["parents"], json!([commit]));
+    let reference = &requests.iter().find(|(_, path, _)| path.ends_with("/git/refs")).unwrap().2;
+    assert_eq!(reference["ref"], format!("refs/heads/{}", branch));
+    assert_eq!(reference["sha"], "fix-commit");
+    let pull = &requests.iter().find(|(method, path, _)| method == "POST" && path.ends_with("/pulls")).unwrap().2;
+    assert_eq!(pull["head"], branch);
+    assert_eq!(pull["base"], "main");
+}
+
+#[tokio::test]
+async fn suggestions_that_dont_apply_open_nothing() {
+    let Some(app) = TestApp::start_with(with_github(), edits(&["- set_value R9 1k"])).await else { return };
+    let mut remote = FakeRemote::new();
+    let commit = remote.commit(&[("divider.kicad_sch", TWO_RESISTORS)], "Add voltage divider");
+    let slug = unique_slug("fix-unplaced");
+    app.register_with(&slug, &remote, json!({ "processing": { "suggest_fixes": true } })).await;
+
+    let path = format!("/api/grok/suggest-fix/{}/{}", encoded(&slug), commit);
+    let response = app.post(&path, json!({ "change": "Make R9 1k" })).await;
+    assert_eq!(response.status(), 422);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["message"].as_str().unwrap().contains("no component R9"), "{}", body);
+    assert!(github_requests(&slug).is_empty());
+}
@@ -0,0 +1,134 @@
+// Times storing a 5,000-component design: the components written one
+// INSERT at a time, as the pipeline used to, against store_distilled_json,
+// which writes them in a single UNNEST statement.
+//
+// USAGE (with the DB container up, see database-up.sh):
+// cargo bench --bench component_writes
+
+use kicad_db::{apply_migrations, create_pool, store_distilled_json, PgPool};
+use serde_json::{json, Map, Value};
+use sqlx::Row;
+use std::time::{Duration, Instant};
+
+const COMPONENTS: usize = 5_000;
+const RUNS: usize = 5;
+const REPO_URL: &str = "bench://component-writes";
+
+/// A distilled design of resistors and capacitors spread over a few sheets
+fn design() -> Value {
+    let mut components = Map::new();
+    for i in 0..COMPONENTS {
+        let (reference, value) = match i % 2 {
+            0 => (format!("R{}", i + 1), "10k"),
+            _ => (format!("C{}", i + 1), "100n"),
+        };
+        components.insert(
+            reference,
+            json!({
+                "value": value,
+                "footprint": "Resistor_SMD:R_0603_1608Metric",
+                "sheet_path": format!("/sheet{}", i % 8),
+                "properties": {"MPN": format!("PART-{}", i % 50)},
+                "position": {"x": (i % 100) as f64 * 2.54, "y": (i / 100) as f64 * 2.54},
+                "rotation": 90,
+                "bbox": {"x1": 0.0, "y1": 0.0, "x2": 2.54, "y2": 5.08},
+            }),
+        );
+    }
+    json!({ "components": components, "nets": {}, "proximities": [] })
+}
+
+/// The schematic row and its components, one statement per component
+async fn row_at_a_time(pool: &PgPool, commit: &str, distilled: &Value) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let schematic_id: i32 = sqlx::query(
+        "INSERT INTO schematics (repo_url, commit_hash, distilled_json) VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(REPO_URL)
+    .bind(commit)
+    .bind(distilled)
+    .fetch_one(&mut *tx)
+    .await?
+    .try_get("id")?;
+    for component in kicad_db::components_from_distilled(distilled) {
+        sqlx::query(
+            r#"
+            INSERT INTO components (schematic_id, repo_url, commit_hash, reference, value, footprint, mpn, sheet, part_uuid,
+                x, y, rotation, bbox_min_x, bbox_min_y, bbox_max_x, bbox_max_y)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+            ON CONFLICT (schematic_id, reference) DO NOTHING
+            "#,
+        )
+        .bind(schematic_id)
+        .bind(REPO_URL)
+        .bind(commit)
+        .bind(&component.reference)
+        .bind(&component.value)
+        .bind(&component.footprint)
+        .bind(&component.mpn)
+        .bind(&component.sheet)
+        .bind(component.part_uuid)
+        .bind(component.x)
+        .bind(component.y)
+        .bind(component.rotation)
+        .bind(component.bbox_min_x)
+        .bind(component.bbox_min_y)
+        .bind(component.bbox_max_x)
+        .bind(component.bbox_max_y)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+async fn clear(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM schematics WHERE repo_url = $1")
+        .bind(REPO_URL)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+fn median(mut times: Vec<Duration>) -> Duration {
+    times.sort();
+    times[times.len() / 2]
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = match create_pool().await {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("Could not connect to DB ({}); run ./database-up.sh first", e);
+            return Ok(());
+        }
+    };
+    apply_migrations(&pool).await?;
+    clear(&pool).await?;
+    let distilled = design();
+
+    let mut single = Vec::new();
+    let mut bulk = Vec::new();
+    for run in 0..RUNS {
+        let start = Instant::now();
+        row_at_a_time(&pool, &format!("single-{}", run), &distilled).await?;
+        single.push(start.elapsed());
+
+        let start = Instant::now();
+        store_distilled_json(&pool, REPO_URL, &format!("bulk-{}", run), &distilled).await?;
+        bulk.push(start.elapsed());
+    }
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM components WHERE repo_url = $1")
+        .bind(REPO_URL)
+        .fetch_one(&pool)
+        .await?;
+    clear(&pool).await?;
+    assert_eq!(stored as usize, 2 * RUNS * COMPONENTS);
+
+    let (single, bulk) = (median(single), median(bulk));
+    println!("{} components, median of {} runs", COMPONENTS, RUNS);
+    println!("  row at a time: {:>8.1} ms", single.as_secs_f64() * 1e3);
+    println!("  bulk (UNNEST): {:>8.1} ms", bulk.as_secs_f64() * 1e3);
+    println!("  speedup:       {:>8.1}x", single.as_secs_f64() / bulk.as_secs_f64());
+    Ok(())
+}
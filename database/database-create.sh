@@ -12,4 +12,4 @@ until docker-compose exec db pg_isready ; do
   echo "Waiting for database..."
   sleep 2
 done
-echo "New database created and tables initialized via init.sql."
\ No newline at end of file
+echo "New database created. Tables are created by migrations on first run (backend startup or \`cargo run\`)."
\ No newline at end of file
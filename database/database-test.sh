@@ -49,7 +49,7 @@ echo "Inserted schematic with id: $SCHEMATIC_ID"
 # Insert part
 docker-compose exec -T db psql -U kicad -d kicad -c "
 INSERT INTO parts (schematic_id, part_uuid, blurb) 
-VALUES ($SCHEMATIC_ID, 'test-part-uuid', 'test blurb');
+VALUES ($SCHEMATIC_ID, '00000000-0000-0000-0000-000000000001', 'test blurb');
 "
 
 # Check retrieve
@@ -0,0 +1,19 @@
+// The S-expression tokenizer on arbitrary bytes: it must return, with an
+// error pointing inside the text when it fails, and never panic.
+#![no_main]
+
+use kicad_db::error::SchematicError;
+use kicad_db::schematic::sexpr::parse;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Files are read as UTF-8 before they reach the parser
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Err(SchematicError::Syntax { line, column, .. }) = parse("fuzz.kicad_sch", text) {
+        let lines: Vec<&str> = text.split('\n').collect();
+        assert!(line >= 1 && line <= lines.len());
+        assert!(column >= 1 && column <= lines[line - 1].chars().count() + 1);
+    }
+});
@@ -1,3 +1,5 @@
+-- Baseline schema (formerly init.sql). IF NOT EXISTS keeps this safe to run
+-- against databases that were bootstrapped from init.sql before migrations.
 CREATE TABLE IF NOT EXISTS schematics (
     id SERIAL PRIMARY KEY,
     repo_url TEXT NOT NULL,
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS repo_settings (
+    repo_url TEXT PRIMARY KEY,
+    default_persona TEXT,
+    updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,2 @@
+-- part_uuid is bound as a UUID from Rust; store it as one.
+ALTER TABLE parts ALTER COLUMN part_uuid TYPE UUID USING part_uuid::uuid;
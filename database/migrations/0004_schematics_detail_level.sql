@@ -0,0 +1,2 @@
+-- Verbosity (brief|standard|deep) the stored change_summary was generated at
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS detail_level TEXT;
@@ -0,0 +1,19 @@
+-- Reference outputs for the fixed drift evaluation set, one per commit
+CREATE TABLE IF NOT EXISTS drift_baselines (
+    id SERIAL PRIMARY KEY,
+    repo_url TEXT NOT NULL,
+    commit_hash TEXT NOT NULL,
+    output TEXT NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(repo_url, commit_hash)
+);
+
+-- One row per drift evaluation run
+CREATE TABLE IF NOT EXISTS drift_runs (
+    id SERIAL PRIMARY KEY,
+    mean_similarity REAL,
+    min_similarity REAL,
+    drifted BOOLEAN NOT NULL DEFAULT FALSE,
+    report JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,11 @@
+-- Full-text search over commit messages and AI-generated text
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS search_vector tsvector
+    GENERATED ALWAYS AS (
+        to_tsvector('english',
+            coalesce(git_message, '') || ' ' ||
+            coalesce(blurb, '') || ' ' ||
+            coalesce(description, '') || ' ' ||
+            coalesce(change_summary, ''))
+    ) STORED;
+
+CREATE INDEX IF NOT EXISTS schematics_search_vector_idx ON schematics USING GIN (search_vector);
@@ -0,0 +1,3 @@
+-- Per-analysis stage timings, keyed by analysis kind:
+-- {"overview": {"clone_ms": 120, "store_ms": 4, "total_ms": 130, "over_budget": []}, ...}
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS timings JSONB;
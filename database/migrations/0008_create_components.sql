@@ -0,0 +1,17 @@
+-- One row per component (by reference designator) per stored commit
+CREATE TABLE IF NOT EXISTS components (
+    id SERIAL PRIMARY KEY,
+    schematic_id INTEGER NOT NULL REFERENCES schematics(id) ON DELETE CASCADE,
+    repo_url TEXT NOT NULL,
+    commit_hash TEXT NOT NULL,
+    reference TEXT NOT NULL,
+    value TEXT,
+    footprint TEXT,
+    mpn TEXT,
+    sheet TEXT,
+    part_uuid UUID,
+    UNIQUE(schematic_id, reference)
+);
+
+CREATE INDEX IF NOT EXISTS components_repo_reference_idx ON components (repo_url, reference);
+CREATE INDEX IF NOT EXISTS components_mpn_idx ON components (mpn);
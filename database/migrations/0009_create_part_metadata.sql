@@ -0,0 +1,14 @@
+-- Supplier data (datasheet, lifecycle, pricing) cached per manufacturer part number
+CREATE TABLE IF NOT EXISTS part_metadata (
+    mpn TEXT PRIMARY KEY,
+    supplier TEXT NOT NULL,
+    manufacturer TEXT,
+    description TEXT,
+    datasheet_url TEXT,
+    product_url TEXT,
+    lifecycle_status TEXT,
+    is_obsolete BOOLEAN NOT NULL DEFAULT FALSE,
+    unit_price DOUBLE PRECISION,
+    quantity_available BIGINT,
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,13 @@
+-- Electrical rule check findings for each commit with distilled JSON
+CREATE TABLE IF NOT EXISTS erc_findings (
+    id SERIAL PRIMARY KEY,
+    schematic_id INTEGER NOT NULL REFERENCES schematics(id) ON DELETE CASCADE,
+    rule TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    reference TEXT,
+    pin TEXT,
+    net TEXT,
+    message TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS erc_findings_schematic_idx ON erc_findings (schematic_id);
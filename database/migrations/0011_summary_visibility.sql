@@ -0,0 +1,9 @@
+-- Whether AI summaries are served to unauthenticated readers. Set per repo, optionally
+-- overridden per commit (NULL inherits the repo setting).
+ALTER TABLE repo_settings
+    ADD COLUMN IF NOT EXISTS visibility TEXT NOT NULL DEFAULT 'public'
+        CHECK (visibility IN ('public', 'private'));
+
+ALTER TABLE schematics
+    ADD COLUMN IF NOT EXISTS visibility TEXT
+        CHECK (visibility IN ('public', 'private'));
@@ -0,0 +1,3 @@
+-- Layout summary of the commit's .kicad_pcb files, keyed by path:
+-- {"board/main.kicad_pcb": {"layer_count": 4, "footprints": [...], "tracks": {...}, "vias": {...}}}
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS board_json JSONB;
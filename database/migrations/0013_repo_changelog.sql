@@ -0,0 +1,5 @@
+-- Opt-in HARDWARE_CHANGELOG.md written back to the analyzed repo through a bot branch
+-- and pull request. NULL path/branch use the defaults (HARDWARE_CHANGELOG.md, grokicad/changelog).
+ALTER TABLE repo_settings ADD COLUMN IF NOT EXISTS changelog_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE repo_settings ADD COLUMN IF NOT EXISTS changelog_path TEXT;
+ALTER TABLE repo_settings ADD COLUMN IF NOT EXISTS changelog_branch TEXT;
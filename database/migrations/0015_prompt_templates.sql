@@ -0,0 +1,12 @@
+-- Prompt templates added at runtime, alongside the built-in ones in prompts/. Versions
+-- are immutable: publish a change as a new version. The highest version of each name is used.
+CREATE TABLE IF NOT EXISTS prompt_templates (
+    name TEXT NOT NULL,
+    version INTEGER NOT NULL CHECK (version > 0),
+    body TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (name, version)
+);
+
+-- Template the stored change_summary was generated from, e.g. "commit_summary@v1"
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS prompt_version TEXT;
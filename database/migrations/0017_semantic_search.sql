@@ -0,0 +1,33 @@
+-- Embeddings for semantic search: one per commit (over its message and
+-- AI-generated text) and one per supplier part description. embedding_hash is
+-- the md5 of the text that was embedded, so edited text gets re-embedded.
+--
+-- Needs the pgvector extension (the pgvector/pgvector image in
+-- docker-compose.yml). Without it the columns are not created and semantic
+-- search reports itself unavailable; install pgvector and recreate the
+-- database (or run the statements below by hand) to enable it.
+DO $$
+BEGIN
+    CREATE EXTENSION IF NOT EXISTS vector;
+EXCEPTION WHEN OTHERS THEN
+    RAISE NOTICE 'pgvector is not available (%); semantic search is disabled', SQLERRM;
+END
+$$;
+
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'vector') THEN
+        -- No fixed dimension, so the embedding model can change; searches only
+        -- compare vectors from the same model. Few enough rows to scan without
+        -- an ANN index.
+        ALTER TABLE schematics
+            ADD COLUMN IF NOT EXISTS embedding vector,
+            ADD COLUMN IF NOT EXISTS embedding_model TEXT,
+            ADD COLUMN IF NOT EXISTS embedding_hash TEXT;
+        ALTER TABLE part_metadata
+            ADD COLUMN IF NOT EXISTS embedding vector,
+            ADD COLUMN IF NOT EXISTS embedding_model TEXT,
+            ADD COLUMN IF NOT EXISTS embedding_hash TEXT;
+    END IF;
+END
+$$;
@@ -0,0 +1,11 @@
+-- Periodic re-sync of known repos, in case webhooks were missed. Repos without
+-- a row use the server's default interval; interval_secs NULL does the same.
+-- last_checked_at is when the last re-sync finished, successfully or not.
+CREATE TABLE IF NOT EXISTS repo_schedules (
+    repo_url TEXT PRIMARY KEY,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    interval_secs INTEGER CHECK (interval_secs > 0),
+    last_checked_at TIMESTAMPTZ,
+    last_error TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,16 @@
+-- Explicitly registered repositories. repo_url is the canonical URL other
+-- tables key on (built from the slug); clone_url is where the repo is actually
+-- fetched from, e.g. a mirror. auth_token_env names an environment variable
+-- holding an access token, so the token itself is never stored here.
+CREATE TABLE IF NOT EXISTS repos (
+    repo_url TEXT PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    clone_url TEXT NOT NULL,
+    default_branch TEXT,
+    auth_token_env TEXT,
+    summary_model TEXT,
+    summary_prompt TEXT,
+    webhook_secret TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,7 @@
+-- Soft deletion of stored commits. Rows with deleted_at set are hidden from
+-- every query, and are hard-deleted (with their parts, components and ERC
+-- findings, via ON DELETE CASCADE) once the retention window has passed.
+-- Storing a soft-deleted commit again starts it afresh.
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_schematics_deleted_at ON schematics (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,3 @@
+-- Why the last attempt to process a commit failed; cleared when it succeeds
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS processing_error TEXT;
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS processing_error_at TIMESTAMPTZ;
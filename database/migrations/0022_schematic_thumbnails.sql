@@ -0,0 +1,13 @@
+-- PNG previews of each commit's schematic image, one per width. source_digest
+-- is the md5 of the image they were made from, so a replaced image gets new
+-- thumbnails. A row with an error and no image records a failed attempt, so
+-- it isn't retried until the image changes.
+CREATE TABLE IF NOT EXISTS schematic_thumbnails (
+    schematic_id INTEGER NOT NULL REFERENCES schematics(id) ON DELETE CASCADE,
+    width INTEGER NOT NULL CHECK (width > 0),
+    source_digest TEXT NOT NULL,
+    image BYTEA,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (schematic_id, width)
+);
@@ -0,0 +1,6 @@
+-- How much of a registered repo to clone. clone_depth limits the history
+-- fetched up front (NULL clones everything; older commits are fetched when
+-- asked for) and sparse_paths limits which files are checked out (NULL checks
+-- out the whole tree).
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS clone_depth INTEGER;
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS sparse_paths TEXT[];
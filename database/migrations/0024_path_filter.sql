@@ -0,0 +1,3 @@
+-- Glob patterns (e.g. hardware/boards/**) limiting which design files of a
+-- registered repo are processed, for monorepos; NULL processes the whole tree.
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS path_filter TEXT[];
@@ -0,0 +1,6 @@
+-- Who made a commit, the tags on it and the pull request that introduced it,
+-- captured when the commit is processed.
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS author_name TEXT;
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS author_email TEXT;
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS tags TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS pr_number INTEGER;
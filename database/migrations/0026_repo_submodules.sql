@@ -0,0 +1,3 @@
+-- Whether a registered repo's submodules are fetched, so symbol libraries
+-- vendored as submodules resolve. Off by default: submodules can be large.
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS submodules BOOLEAN NOT NULL DEFAULT FALSE;
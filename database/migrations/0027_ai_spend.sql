@@ -0,0 +1,19 @@
+-- One row per billed AI call: the tokens it used, what they cost by the
+-- configured pricing, and who it is charged to. Repo and API key are NULL for
+-- calls made outside a request (background jobs) or without a repo.
+CREATE TABLE IF NOT EXISTS ai_spend (
+    id BIGSERIAL PRIMARY KEY,
+    endpoint TEXT NOT NULL,
+    model TEXT NOT NULL,
+    repo_url TEXT,
+    api_key TEXT,
+    api_key_name TEXT,
+    prompt_tokens BIGINT NOT NULL DEFAULT 0,
+    completion_tokens BIGINT NOT NULL DEFAULT 0,
+    cost_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_ai_spend_created ON ai_spend (created_at);
+CREATE INDEX IF NOT EXISTS idx_ai_spend_repo ON ai_spend (repo_url, created_at);
+CREATE INDEX IF NOT EXISTS idx_ai_spend_key ON ai_spend (api_key, created_at);
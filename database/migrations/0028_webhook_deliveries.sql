@@ -0,0 +1,12 @@
+-- Webhook deliveries already received, by the provider's delivery ID, so a
+-- redelivery of the same push (e.g. after a timeout) is acknowledged without
+-- being processed again. Rows are purged once redelivery is unlikely.
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    provider TEXT NOT NULL,
+    delivery_id TEXT NOT NULL,
+    repo_url TEXT NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (provider, delivery_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_received ON webhook_deliveries (received_at);
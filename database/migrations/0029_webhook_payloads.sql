@@ -0,0 +1,7 @@
+-- Keep each delivery's event type and payload (secrets redacted) for as long
+-- as the delivery itself, so an operator can replay it. Deliveries from
+-- providers that don't send an ID get a generated one.
+ALTER TABLE webhook_deliveries ADD COLUMN IF NOT EXISTS event TEXT;
+ALTER TABLE webhook_deliveries ADD COLUMN IF NOT EXISTS payload JSONB;
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_id ON webhook_deliveries (delivery_id);
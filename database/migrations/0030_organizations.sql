@@ -0,0 +1,36 @@
+-- Organizations (tenants) and their members. Each org sees only the repos
+-- registered to it, and with them their stored commits; keys and AI spend
+-- belong to the org they were created in or charged to. NULL org_id is the
+-- instance itself: repos no org owns, which only instance-wide and anonymous
+-- callers see, and instance-wide keys, which see every org.
+CREATE TABLE IF NOT EXISTS organizations (
+    id SERIAL PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Users sign in elsewhere and arrive as JWT subjects (their email)
+CREATE TABLE IF NOT EXISTS users (
+    id SERIAL PRIMARY KEY,
+    email TEXT NOT NULL UNIQUE,
+    name TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- role is the widest API scope the user gets in the org: read, hook or admin
+CREATE TABLE IF NOT EXISTS memberships (
+    org_id INTEGER NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role TEXT NOT NULL DEFAULT 'read',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (org_id, user_id)
+);
+
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS org_id INTEGER REFERENCES organizations (id);
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS org_id INTEGER REFERENCES organizations (id);
+ALTER TABLE ai_spend ADD COLUMN IF NOT EXISTS org_id INTEGER REFERENCES organizations (id);
+
+CREATE INDEX IF NOT EXISTS idx_repos_org ON repos (org_id);
+CREATE INDEX IF NOT EXISTS idx_api_keys_org ON api_keys (org_id);
+CREATE INDEX IF NOT EXISTS idx_ai_spend_org ON ai_spend (org_id, created_at);
@@ -0,0 +1,53 @@
+-- Rendered schematic images, stored once per content. digest is the hex
+-- sha256 of data; schematics reference their image by it, so an unchanged
+-- render shared by many commits is one row. Images no schematic references
+-- any more are removed by purge_orphaned_images.
+CREATE TABLE IF NOT EXISTS images (
+    digest TEXT PRIMARY KEY,
+    content_type TEXT NOT NULL,
+    data BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS image_digest TEXT REFERENCES images (digest);
+CREATE INDEX IF NOT EXISTS idx_schematics_image_digest ON schematics (image_digest);
+
+-- Move inline images into the table. Thumbnails were keyed by the md5 of the
+-- inline image; rekey them so they aren't rendered again.
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'schematics' AND column_name = 'schematic_image'
+    ) THEN
+        INSERT INTO images (digest, content_type, data)
+        SELECT DISTINCT ON (digest) digest,
+            CASE
+                WHEN substring(schematic_image FROM 1 FOR 8) = '\x89504e470d0a1a0a'::bytea THEN 'image/png'
+                WHEN substring(schematic_image FROM 1 FOR 3) = '\xffd8ff'::bytea THEN 'image/jpeg'
+                WHEN position('<svg'::bytea IN substring(schematic_image FROM 1 FOR 1024)) > 0 THEN 'image/svg+xml'
+                ELSE 'application/octet-stream'
+            END,
+            schematic_image
+        FROM (
+            SELECT encode(sha256(schematic_image), 'hex') AS digest, schematic_image
+            FROM schematics
+            WHERE schematic_image IS NOT NULL
+        ) inline
+        ON CONFLICT (digest) DO NOTHING;
+
+        UPDATE schematic_thumbnails t
+        SET source_digest = encode(sha256(s.schematic_image), 'hex')
+        FROM schematics s
+        WHERE s.id = t.schematic_id
+            AND s.schematic_image IS NOT NULL
+            AND t.source_digest = md5(s.schematic_image);
+
+        UPDATE schematics
+        SET image_digest = encode(sha256(schematic_image), 'hex')
+        WHERE schematic_image IS NOT NULL;
+
+        ALTER TABLE schematics DROP COLUMN schematic_image;
+    END IF;
+END
+$$;
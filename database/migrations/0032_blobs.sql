@@ -0,0 +1,45 @@
+-- Bytes of large artifacts, by key, for the default blob store (see
+-- src/blob_store.rs). With S3 configured this table stays empty. Image and
+-- thumbnail rows keep only metadata; their bytes move here.
+CREATE TABLE IF NOT EXISTS blobs (
+    key TEXT PRIMARY KEY,
+    content_type TEXT NOT NULL,
+    data BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE images ADD COLUMN IF NOT EXISTS size BIGINT NOT NULL DEFAULT 0;
+-- Thumbnails are keyed by the image they were made from and their width;
+-- false with an error recorded for a failed attempt
+ALTER TABLE schematic_thumbnails ADD COLUMN IF NOT EXISTS stored BOOLEAN NOT NULL DEFAULT FALSE;
+
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'images' AND column_name = 'data'
+    ) THEN
+        INSERT INTO blobs (key, content_type, data)
+        SELECT 'images/' || digest, content_type, data FROM images
+        ON CONFLICT (key) DO NOTHING;
+        UPDATE images SET size = octet_length(data);
+        ALTER TABLE images DROP COLUMN data;
+    END IF;
+
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'schematic_thumbnails' AND column_name = 'image'
+    ) THEN
+        INSERT INTO blobs (key, content_type, data)
+        SELECT DISTINCT ON (source_digest, width)
+            'thumbnails/' || source_digest || '/' || width || '.png', 'image/png', image
+        FROM schematic_thumbnails
+        WHERE image IS NOT NULL
+        ON CONFLICT (key) DO NOTHING;
+        UPDATE schematic_thumbnails SET stored = image IS NOT NULL;
+        ALTER TABLE schematic_thumbnails DROP COLUMN image;
+    END IF;
+END
+$$;
+
+ALTER TABLE images ALTER COLUMN size DROP DEFAULT;
@@ -0,0 +1,16 @@
+-- Deferred AI completions submitted but not yet collected. The provider keeps
+-- a deferred result for a day, so a job interrupted by a restart finds its
+-- requests here and fetches their results instead of paying for them again.
+-- A row goes once its result is stored.
+CREATE TABLE IF NOT EXISTS deferred_completions (
+    request_id TEXT PRIMARY KEY,
+    -- What the result is for, e.g. 'commit_summary'
+    kind TEXT NOT NULL,
+    repo_url TEXT NOT NULL,
+    commit_hash TEXT NOT NULL,
+    -- The model it was submitted to, for pricing the result
+    model TEXT NOT NULL,
+    prompt_version TEXT NOT NULL,
+    submitted_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (kind, repo_url, commit_hash)
+);
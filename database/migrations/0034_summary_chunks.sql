@@ -0,0 +1,15 @@
+-- Intermediate results of map-reduce summaries (see
+-- backend/src/services/summarization.rs): the model's summary of one chunk
+-- or merge step, keyed by a digest of the model and the full prompt. Re-running
+-- over a mostly unchanged project only asks about the parts that changed.
+CREATE TABLE IF NOT EXISTS summary_chunks (
+    digest TEXT PRIMARY KEY,
+    model TEXT NOT NULL,
+    -- Template the prompt was rendered from, e.g. "chunk_summary@v1"
+    prompt_version TEXT NOT NULL,
+    summary TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    used_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_summary_chunks_used_at ON summary_chunks (used_at);
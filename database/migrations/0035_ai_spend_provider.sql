@@ -0,0 +1,3 @@
+-- Which provider served each billed AI call. Calls fail over from xAI to the
+-- configured fallback providers; rows from before then were all served by xAI.
+ALTER TABLE ai_spend ADD COLUMN IF NOT EXISTS provider TEXT NOT NULL DEFAULT 'xai';
@@ -0,0 +1,14 @@
+-- Where each commit stands in the overview pipeline. processing_error (0021)
+-- holds why the last attempt failed; a commit found 'processing' by no live
+-- job was interrupted, and is picked up again by the next update.
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS processing_status TEXT NOT NULL DEFAULT 'pending'
+    CHECK (processing_status IN ('pending', 'processing', 'done', 'failed'));
+
+UPDATE schematics SET processing_status = CASE
+    WHEN processing_error IS NOT NULL THEN 'failed'
+    WHEN blurb IS NOT NULL AND description IS NOT NULL THEN 'done'
+    ELSE 'pending'
+END;
+
+CREATE INDEX IF NOT EXISTS idx_schematics_failed
+    ON schematics (repo_url, processing_error_at DESC) WHERE processing_status = 'failed';
@@ -0,0 +1,24 @@
+-- AI calls as sent and answered, for debugging bad output. Only written when
+-- the prompt audit is enabled; messages and responses are redacted and
+-- truncated by the caller, and rows are purged after the configured retention.
+CREATE TABLE IF NOT EXISTS prompt_audit (
+    id BIGSERIAL PRIMARY KEY,
+    request_id TEXT,
+    endpoint TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    model TEXT NOT NULL,
+    repo_url TEXT,
+    api_key TEXT,
+    org_id INTEGER,
+    -- The request's messages (or responses API input)
+    messages JSONB NOT NULL,
+    response TEXT,
+    error TEXT,
+    latency_ms BIGINT NOT NULL,
+    prompt_tokens BIGINT,
+    completion_tokens BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_prompt_audit_created ON prompt_audit (created_at);
+CREATE INDEX IF NOT EXISTS idx_prompt_audit_repo ON prompt_audit (repo_url, created_at);
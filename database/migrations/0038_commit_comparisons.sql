@@ -0,0 +1,20 @@
+-- AI narratives of how a repo changed between two commits (see
+-- POST /api/grok/summary/compare), one per commit pair and detail level.
+-- Commits don't change, so an entry stays valid until the prompt or the
+-- model does.
+CREATE TABLE IF NOT EXISTS commit_comparisons (
+    repo_url TEXT NOT NULL,
+    base_commit TEXT NOT NULL,
+    head_commit TEXT NOT NULL,
+    detail_level TEXT NOT NULL,
+    -- Commits in base..head
+    commit_count INTEGER NOT NULL,
+    summary TEXT NOT NULL,
+    -- The component and net changes the summary was written from
+    changes TEXT NOT NULL,
+    model TEXT NOT NULL,
+    -- Template the prompt was rendered from, e.g. "compare_summary@v1"
+    prompt_version TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (repo_url, base_commit, head_commit, detail_level)
+);
@@ -0,0 +1,31 @@
+-- AI-written design-review checklists (see POST /api/grok/review/checklist),
+-- one per commit, and the items reviewers tick off. Regenerating a
+-- checklist replaces its items, and with them their completion state.
+CREATE TABLE IF NOT EXISTS review_checklists (
+    repo_url TEXT NOT NULL,
+    commit_hash TEXT NOT NULL,
+    model TEXT NOT NULL,
+    -- Template the prompt was rendered from, e.g. "review_checklist@v1"
+    prompt_version TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (repo_url, commit_hash)
+);
+
+CREATE TABLE IF NOT EXISTS review_checklist_items (
+    id BIGSERIAL PRIMARY KEY,
+    repo_url TEXT NOT NULL,
+    commit_hash TEXT NOT NULL,
+    -- Order the model listed the items in
+    position INTEGER NOT NULL,
+    -- e.g. "power", "connectors", "erc"
+    category TEXT NOT NULL,
+    text TEXT NOT NULL,
+    -- References of the changed components the item concerns
+    components TEXT[] NOT NULL DEFAULT '{}',
+    done BOOLEAN NOT NULL DEFAULT FALSE,
+    done_at TIMESTAMPTZ,
+    FOREIGN KEY (repo_url, commit_hash) REFERENCES review_checklists (repo_url, commit_hash) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS review_checklist_items_commit_idx
+    ON review_checklist_items (repo_url, commit_hash, position);
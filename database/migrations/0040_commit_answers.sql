@@ -0,0 +1,22 @@
+-- Answers to one-off questions about a commit's design (see POST
+-- /api/grok/ask). The same question about the same commit is answered from
+-- here until the prompt or the model changes. Answers that may cite private
+-- summaries are kept apart from those that may not.
+CREATE TABLE IF NOT EXISTS commit_answers (
+    repo_url TEXT NOT NULL,
+    commit_hash TEXT NOT NULL,
+    -- SHA-256 of the question with case and spacing normalized
+    question_digest TEXT NOT NULL,
+    include_private BOOLEAN NOT NULL,
+    question TEXT NOT NULL,
+    answer TEXT NOT NULL,
+    -- Component references and net names the answer mentions
+    components TEXT[] NOT NULL DEFAULT '{}',
+    nets TEXT[] NOT NULL DEFAULT '{}',
+    -- The retrieved context the answer cites, as returned to the client
+    sources JSONB NOT NULL DEFAULT '[]',
+    model TEXT NOT NULL,
+    prompt_version TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (repo_url, commit_hash, question_digest, include_private)
+);
@@ -0,0 +1,9 @@
+-- Which pushes a registered repo processes and what runs for them.
+-- process_branches holds globs of branch names (NULL processes every
+-- branch); process_tags whether tag pushes count; ai_summaries whether new
+-- commits get AI summaries straight away rather than on request; and
+-- render_thumbnails whether uploaded schematic images get thumbnails.
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS process_branches TEXT[];
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS process_tags BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS ai_summaries BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS render_thumbnails BOOLEAN NOT NULL DEFAULT TRUE;
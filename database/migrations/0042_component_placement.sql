@@ -0,0 +1,10 @@
+-- Where each component is drawn: its (first) unit's anchor and rotation on
+-- its sheet, and the box around its body and pins, all in mm. NULL for
+-- components stored before placement was recorded or whose symbol is unknown.
+ALTER TABLE components ADD COLUMN IF NOT EXISTS x DOUBLE PRECISION;
+ALTER TABLE components ADD COLUMN IF NOT EXISTS y DOUBLE PRECISION;
+ALTER TABLE components ADD COLUMN IF NOT EXISTS rotation INTEGER;
+ALTER TABLE components ADD COLUMN IF NOT EXISTS bbox_min_x DOUBLE PRECISION;
+ALTER TABLE components ADD COLUMN IF NOT EXISTS bbox_min_y DOUBLE PRECISION;
+ALTER TABLE components ADD COLUMN IF NOT EXISTS bbox_max_x DOUBLE PRECISION;
+ALTER TABLE components ADD COLUMN IF NOT EXISTS bbox_max_y DOUBLE PRECISION;
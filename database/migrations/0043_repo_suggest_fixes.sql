@@ -0,0 +1,3 @@
+-- Whether the repo's owners have opted in to AI-suggested fixes being
+-- pushed to a branch of the repo and opened as a pull request.
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS suggest_fixes BOOLEAN NOT NULL DEFAULT FALSE;
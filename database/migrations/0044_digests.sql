@@ -0,0 +1,16 @@
+-- Weekly email digests of each repo's schematic activity.
+-- digest_subscribers holds the addresses a repo's digest goes to (stored
+-- lowercase). repo_digests records the end of the last week a digest went
+-- out for, so each week is sent once however many instances run.
+CREATE TABLE IF NOT EXISTS digest_subscribers (
+    repo_url TEXT NOT NULL,
+    email TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (repo_url, email)
+);
+
+CREATE TABLE IF NOT EXISTS repo_digests (
+    repo_url TEXT PRIMARY KEY,
+    period_end TIMESTAMPTZ NOT NULL,
+    sent_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
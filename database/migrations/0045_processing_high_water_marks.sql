@@ -0,0 +1,10 @@
+-- The newest commit of each branch that an update has processed along with
+-- everything before it. Updates only look at commits after it; a refresh
+-- scans the whole history and moves it to the branch's tip again.
+CREATE TABLE IF NOT EXISTS processing_high_water_marks (
+    repo_url TEXT NOT NULL,
+    branch TEXT NOT NULL,
+    commit_hash TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (repo_url, branch)
+);
@@ -0,0 +1,23 @@
+-- Chat conversations kept by the server. Every turn is stored as it was
+-- said; once a conversation nears the model's context window, the turns up
+-- to summarized_through (a position) are folded into summary, and later
+-- requests send that in their place.
+CREATE TABLE IF NOT EXISTS chat_sessions (
+    id UUID PRIMARY KEY,
+    repo_url TEXT,
+    summary TEXT,
+    summary_tokens INTEGER NOT NULL DEFAULT 0,
+    summarized_through INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS chat_turns (
+    session_id UUID NOT NULL REFERENCES chat_sessions(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    role TEXT NOT NULL,
+    content TEXT NOT NULL,
+    tokens INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (session_id, position)
+);
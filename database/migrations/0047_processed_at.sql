@@ -0,0 +1,7 @@
+-- When each commit last finished processing successfully, for counting
+-- recent throughput (GET /api/admin/overview); failures already record
+-- processing_error_at.
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS processed_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_schematics_processed_at
+    ON schematics (processed_at) WHERE processed_at IS NOT NULL;
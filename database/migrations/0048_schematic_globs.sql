@@ -0,0 +1,4 @@
+-- Glob patterns (e.g. **/*.schematic) naming files of a registered repo to
+-- read as schematics besides .kicad_sch and legacy .sch files, for teams
+-- that name them otherwise; NULL reads only those.
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS schematic_globs TEXT[];
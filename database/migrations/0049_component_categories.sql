@@ -0,0 +1,17 @@
+-- Where each distinct part (value, footprint and MPN, as stored in
+-- components) sits in the component taxonomy, as a cheap model classified
+-- it. Parts are shared by every repo, so each is classified once; missing
+-- fields are stored as '' to keep the key unique.
+CREATE TABLE IF NOT EXISTS component_categories (
+    value TEXT NOT NULL,
+    footprint TEXT NOT NULL,
+    mpn TEXT NOT NULL,
+    category TEXT NOT NULL,
+    subcategory TEXT,
+    model TEXT NOT NULL,
+    classified_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (value, footprint, mpn)
+);
+
+CREATE INDEX IF NOT EXISTS component_categories_category_idx
+    ON component_categories (category, subcategory);
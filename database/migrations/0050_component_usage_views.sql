@@ -0,0 +1,25 @@
+-- Which repos use which parts, for questions across repos ("which of our
+-- boards use the STM32F407?", "which parts are on more than N boards?").
+-- A repo's parts are those of its newest commit with stored components, so a
+-- part replaced since no longer counts. MPNs are compared trimmed and in
+-- upper case.
+CREATE OR REPLACE VIEW latest_component_commits AS
+SELECT DISTINCT ON (s.repo_url) s.id AS schematic_id, s.repo_url, s.commit_hash, s.commit_date
+FROM schematics s
+WHERE s.deleted_at IS NULL
+    AND EXISTS (SELECT 1 FROM components c WHERE c.schematic_id = s.id)
+ORDER BY s.repo_url, s.commit_date DESC NULLS LAST, s.created_at DESC, s.id DESC;
+
+-- One row per repo and MPN at that commit
+CREATE OR REPLACE VIEW component_usage AS
+SELECT UPPER(TRIM(c.mpn)) AS mpn, l.repo_url, l.commit_hash, l.commit_date, l.schematic_id,
+    COUNT(*) AS quantity,
+    ARRAY_AGG(c.reference ORDER BY c.reference) AS designators,
+    MIN(c.value) AS value,
+    MIN(c.footprint) AS footprint
+FROM latest_component_commits l
+JOIN components c ON c.schematic_id = l.schematic_id
+WHERE TRIM(c.mpn) <> ''
+GROUP BY UPPER(TRIM(c.mpn)), l.repo_url, l.commit_hash, l.commit_date, l.schematic_id;
+
+CREATE INDEX IF NOT EXISTS components_mpn_upper_idx ON components (UPPER(TRIM(mpn)));
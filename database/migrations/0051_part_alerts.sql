@@ -0,0 +1,22 @@
+-- Parts whose supplier lifecycle status says they are going away (NRND, EOL
+-- or obsolete), per registered repo using them at its latest commit. A
+-- scheduled check opens these, resolves them once the part is gone or its
+-- status recovers, and reopens them if it comes back; `notified_at` is cleared
+-- whenever an alert (re)opens or its risk changes, so each is announced once.
+CREATE TABLE IF NOT EXISTS part_alerts (
+    id BIGSERIAL PRIMARY KEY,
+    repo_url TEXT NOT NULL,
+    -- Trimmed and upper case, as in the component_usage view
+    mpn TEXT NOT NULL,
+    risk TEXT NOT NULL CHECK (risk IN ('nrnd', 'eol', 'obsolete')),
+    lifecycle_status TEXT,
+    commit_hash TEXT NOT NULL,
+    designators TEXT[] NOT NULL DEFAULT '{}',
+    first_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMPTZ,
+    notified_at TIMESTAMPTZ,
+    UNIQUE (repo_url, mpn)
+);
+
+CREATE INDEX IF NOT EXISTS part_alerts_open_idx ON part_alerts (repo_url) WHERE resolved_at IS NULL;
@@ -0,0 +1,19 @@
+-- Design guidelines: a team's house rules ("we always use 0402 passives",
+-- "prefer TI regulators"), added to the system prompt of summaries and chat.
+-- Each belongs to an org, applying to all its repos, or to one repo; a repo's
+-- own guidelines take precedence over its org's, and within either the higher
+-- `priority` first.
+CREATE TABLE IF NOT EXISTS design_guidelines (
+    id BIGSERIAL PRIMARY KEY,
+    org_id INTEGER REFERENCES organizations (id) ON DELETE CASCADE,
+    repo_url TEXT,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    priority INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK ((org_id IS NULL) <> (repo_url IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS design_guidelines_org_idx ON design_guidelines (org_id) WHERE org_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS design_guidelines_repo_idx ON design_guidelines (repo_url) WHERE repo_url IS NOT NULL;
@@ -0,0 +1,29 @@
+-- Feedback on AI summaries, to tell which prompt template and model
+-- combinations produce summaries people find useful. Each generated summary
+-- gets a row in generated_summaries, whose id is returned with the summary;
+-- thumbs up or down (and an optional comment) on it are kept in
+-- summary_feedback, linked to the model and template it came from.
+CREATE TABLE IF NOT EXISTS generated_summaries (
+    id BIGSERIAL PRIMARY KEY,
+    repo_url TEXT NOT NULL,
+    commit_hash TEXT NOT NULL,
+    -- What was summarized, e.g. "commit_summary"
+    kind TEXT NOT NULL,
+    model TEXT NOT NULL,
+    prompt_version TEXT NOT NULL,
+    detail_level TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS generated_summaries_repo_idx ON generated_summaries (repo_url, commit_hash);
+
+CREATE TABLE IF NOT EXISTS summary_feedback (
+    id BIGSERIAL PRIMARY KEY,
+    summary_id BIGINT NOT NULL REFERENCES generated_summaries (id) ON DELETE CASCADE,
+    helpful BOOLEAN NOT NULL,
+    comment TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS summary_feedback_summary_idx ON summary_feedback (summary_id);
+CREATE INDEX IF NOT EXISTS summary_feedback_created_idx ON summary_feedback (created_at);
@@ -0,0 +1,13 @@
+-- A/B experiments on summaries: each generation may be assigned to a variant
+-- (prompt template version and model) of the running experiment. The
+-- assignment is kept with the summary handed out, so its feedback counts for
+-- the variant, and with every AI call made for it, so its cost does.
+ALTER TABLE generated_summaries ADD COLUMN IF NOT EXISTS experiment TEXT;
+ALTER TABLE generated_summaries ADD COLUMN IF NOT EXISTS variant TEXT;
+ALTER TABLE ai_spend ADD COLUMN IF NOT EXISTS experiment TEXT;
+ALTER TABLE ai_spend ADD COLUMN IF NOT EXISTS variant TEXT;
+
+CREATE INDEX IF NOT EXISTS generated_summaries_experiment_idx
+    ON generated_summaries (experiment, variant) WHERE experiment IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_ai_spend_experiment
+    ON ai_spend (experiment, variant) WHERE experiment IS NOT NULL;
@@ -0,0 +1,13 @@
+-- Size and complexity of each processed commit's schematics, summed over its
+-- projects, for charting a design's growth. One row per schematic, written
+-- when the commit is processed; commits processed before this have none.
+CREATE TABLE IF NOT EXISTS schematic_stats (
+    schematic_id INTEGER PRIMARY KEY REFERENCES schematics(id) ON DELETE CASCADE,
+    components INTEGER NOT NULL,
+    nets INTEGER NOT NULL,
+    sheets INTEGER NOT NULL,
+    unique_parts INTEGER NOT NULL,
+    connections INTEGER NOT NULL,
+    complexity INTEGER NOT NULL,
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,4 @@
+-- Suspicious changes found in each processed commit's component and net diff
+-- (mass deletions, renamed power nets, removed decoupling), as a JSON array
+-- of warnings; NULL for commits processed before they were looked for.
+ALTER TABLE schematics ADD COLUMN IF NOT EXISTS diff_warnings JSONB;
@@ -0,0 +1,5 @@
+-- What an assistant turn cites (component references, net names, commit
+-- hashes) and the renderings attached to it, as JSON arrays, so a stored
+-- conversation shows the same clickable citations as the live stream did.
+ALTER TABLE chat_turns ADD COLUMN IF NOT EXISTS citations JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE chat_turns ADD COLUMN IF NOT EXISTS attachments JSONB NOT NULL DEFAULT '[]';
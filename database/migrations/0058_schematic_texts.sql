@@ -0,0 +1,18 @@
+-- The human-readable text of each processed commit's schematics: reference
+-- designators, values, labels, sheet names and text notes, one row per
+-- distinct text and sheet, for keyword searches that need no parsing. Written
+-- when the commit is processed; commits processed before this have none.
+CREATE TABLE IF NOT EXISTS schematic_texts (
+    id BIGSERIAL PRIMARY KEY,
+    schematic_id INTEGER NOT NULL REFERENCES schematics(id) ON DELETE CASCADE,
+    -- reference, value, label, sheet or note
+    kind TEXT NOT NULL,
+    text TEXT NOT NULL,
+    -- The component a reference or value belongs to
+    reference TEXT,
+    -- Sheet the text is on, e.g. `/Power/`; a sheet name's own path
+    sheet_path TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_schematic_texts_schematic ON schematic_texts (schematic_id);
+CREATE INDEX IF NOT EXISTS idx_schematic_texts_upper ON schematic_texts (UPPER(text));
@@ -0,0 +1,42 @@
+-- Schematic storage for SQLite (see src/schematic_store.rs): what the
+-- Postgres migrations build up for schematics, parts and images, in one
+-- file. Everything else the server stores lives only in Postgres.
+
+-- Rendered images by hex sha256, shared by commits with identical renders
+CREATE TABLE IF NOT EXISTS images (
+    digest TEXT PRIMARY KEY,
+    bytes BLOB NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+CREATE TABLE IF NOT EXISTS schematics (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    repo_url TEXT NOT NULL,
+    commit_hash TEXT NOT NULL,
+    commit_date TEXT,
+    git_message TEXT,
+    image_digest TEXT REFERENCES images(digest),
+    change_summary TEXT,
+    project_overview TEXT,
+    blurb TEXT,
+    description TEXT,
+    distilled_json TEXT,
+    detail_level TEXT,
+    prompt_version TEXT,
+    timings TEXT,
+    -- public or private; NULL is public
+    visibility TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    UNIQUE (repo_url, commit_hash)
+);
+
+CREATE TABLE IF NOT EXISTS parts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    schematic_id INTEGER NOT NULL REFERENCES schematics(id) ON DELETE CASCADE,
+    part_uuid BLOB NOT NULL,
+    blurb TEXT,
+    properties TEXT NOT NULL DEFAULT '{}',
+    UNIQUE (schematic_id, part_uuid)
+);
+
+CREATE INDEX IF NOT EXISTS idx_schematics_repo_date ON schematics (repo_url, commit_date);
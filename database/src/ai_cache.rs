@@ -0,0 +1,63 @@
+// USAGE:
+// cargo test --test integration ai_response_cache -- --nocapture
+//
+// Content-addressed cache of AI responses. Keys are computed by the caller
+// (a hash of the endpoint and the request); responses are stored as JSON.
+use serde_json::Value;
+use sqlx::{Error, PgPool, Row};
+use std::time::Duration;
+
+/// The cached response for `cache_key`, if present and not expired; counts a hit
+pub async fn get_cached_response(pool: &PgPool, cache_key: &str) -> Result<Option<Value>, Error> {
+    let row = sqlx::query(
+        r#"
+        UPDATE ai_response_cache SET hits = hits + 1
+        WHERE cache_key = $1 AND expires_at > CURRENT_TIMESTAMP
+        RETURNING response
+        "#,
+    )
+    .bind(cache_key)
+    .fetch_optional(pool)
+    .await?;
+    row.map(|r| r.try_get("response")).transpose()
+}
+
+/// Store a response for `ttl`, replacing any earlier entry for the key
+pub async fn store_cached_response(
+    pool: &PgPool,
+    cache_key: &str,
+    endpoint: &str,
+    model: &str,
+    response: &Value,
+    ttl: Duration,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO ai_response_cache (cache_key, endpoint, model, response, expires_at)
+        VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP + make_interval(secs => $5))
+        ON CONFLICT (cache_key) DO UPDATE SET
+            endpoint = EXCLUDED.endpoint,
+            model = EXCLUDED.model,
+            response = EXCLUDED.response,
+            hits = 0,
+            created_at = CURRENT_TIMESTAMP,
+            expires_at = EXCLUDED.expires_at
+        "#,
+    )
+    .bind(cache_key)
+    .bind(endpoint)
+    .bind(model)
+    .bind(response)
+    .bind(ttl.as_secs_f64())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Delete expired entries, returning how many were removed
+pub async fn purge_expired_responses(pool: &PgPool) -> Result<u64, Error> {
+    let result = sqlx::query("DELETE FROM ai_response_cache WHERE expires_at <= CURRENT_TIMESTAMP")
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
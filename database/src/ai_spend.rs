@@ -0,0 +1,126 @@
+// USAGE:
+// cargo test --test integration ai_spend -- --nocapture
+//
+// Ledger of what AI calls cost. The caller prices each call from its token
+// usage and records who it is charged to; totals per repo and per API key
+// drive the monthly budgets and the admin cost report.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, PgPool};
+
+use crate::organizations::OrgFilter;
+
+/// One billed AI call
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AiSpend {
+    pub endpoint: String,
+    /// The provider that served the call, e.g. "xai" or a fallback's name
+    pub provider: String,
+    pub model: String,
+    pub repo_url: Option<String>,
+    /// Identifier of the calling API key (or token), as used for rate limits
+    pub api_key: Option<String>,
+    pub api_key_name: Option<String>,
+    /// Org of the calling key or user; None for instance-wide callers and
+    /// background jobs
+    pub org_id: Option<i32>,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost_usd: f64,
+    /// Experiment and variant the call was made for, if any
+    pub experiment: Option<String>,
+    pub variant: Option<String>,
+}
+
+/// What one repo, key, provider and model combination spent over a period
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct SpendTotal {
+    pub repo_url: Option<String>,
+    pub api_key: Option<String>,
+    pub api_key_name: Option<String>,
+    pub provider: String,
+    pub model: String,
+    pub calls: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost_usd: f64,
+}
+
+/// Whose spend to total
+#[derive(Debug, Clone, Copy)]
+pub enum SpendBy<'a> {
+    Repo(&'a str),
+    ApiKey(&'a str),
+}
+
+/// Record a call's cost
+pub async fn record_ai_spend(pool: &PgPool, spend: &AiSpend) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO ai_spend
+            (endpoint, provider, model, repo_url, api_key, api_key_name, org_id, prompt_tokens, completion_tokens, cost_usd,
+             experiment, variant)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        "#,
+    )
+    .bind(&spend.endpoint)
+    .bind(&spend.provider)
+    .bind(&spend.model)
+    .bind(&spend.repo_url)
+    .bind(&spend.api_key)
+    .bind(&spend.api_key_name)
+    .bind(spend.org_id)
+    .bind(spend.prompt_tokens)
+    .bind(spend.completion_tokens)
+    .bind(spend.cost_usd)
+    .bind(&spend.experiment)
+    .bind(&spend.variant)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Total cost charged to a repo or API key since `since`
+pub async fn spend_since(pool: &PgPool, by: SpendBy<'_>, since: DateTime<Utc>) -> Result<f64, Error> {
+    let (column, value) = match by {
+        SpendBy::Repo(repo_url) => ("repo_url", repo_url),
+        SpendBy::ApiKey(api_key) => ("api_key", api_key),
+    };
+    sqlx::query_scalar(&format!(
+        "SELECT COALESCE(SUM(cost_usd), 0) FROM ai_spend WHERE {} = $1 AND created_at >= $2",
+        column
+    ))
+    .bind(value)
+    .bind(since)
+    .fetch_one(pool)
+    .await
+}
+
+/// Spend in `[since, until)` charged to the orgs `orgs` lets through, by
+/// repo, API key, provider and model, most expensive first
+pub async fn spend_report(
+    pool: &PgPool,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    orgs: OrgFilter,
+) -> Result<Vec<SpendTotal>, Error> {
+    sqlx::query_as::<_, SpendTotal>(
+        r#"
+        SELECT repo_url, api_key, MAX(api_key_name) AS api_key_name, provider, model,
+            COUNT(*) AS calls,
+            SUM(prompt_tokens)::BIGINT AS prompt_tokens,
+            SUM(completion_tokens)::BIGINT AS completion_tokens,
+            SUM(cost_usd) AS cost_usd
+        FROM ai_spend
+        WHERE created_at >= $1 AND created_at < $2 AND ($3 OR org_id IS NOT DISTINCT FROM $4)
+        GROUP BY repo_url, api_key, provider, model
+        ORDER BY cost_usd DESC, provider, model
+        "#,
+    )
+    .bind(since)
+    .bind(until)
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .fetch_all(pool)
+    .await
+}
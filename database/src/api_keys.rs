@@ -0,0 +1,184 @@
+// USAGE:
+// cargo test api_keys -- --nocapture
+//
+// API keys for the backend's /api routes. Keys are generated and hashed by the
+// caller; only the hash and a short display prefix are stored here.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Error, PgPool, Row};
+
+use crate::organizations::OrgFilter;
+
+/// What a key may do; each scope includes the ones below it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiScope {
+    /// Read stored data, including private summaries
+    Read,
+    /// Also trigger processing: clones, distillation and AI calls
+    Hook,
+    /// Also change repo settings and manage keys
+    Admin,
+}
+
+impl ApiScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::Read => "read",
+            ApiScope::Hook => "hook",
+            ApiScope::Admin => "admin",
+        }
+    }
+
+    /// Whether a key with this scope may call a route that needs `required`
+    pub fn allows(&self, required: ApiScope) -> bool {
+        *self >= required
+    }
+}
+
+impl std::str::FromStr for ApiScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "read" => Ok(ApiScope::Read),
+            "hook" => Ok(ApiScope::Hook),
+            "admin" => Ok(ApiScope::Admin),
+            other => Err(format!(
+                "unknown scope '{}' (expected read, hook or admin)",
+                other
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A stored key, without its hash
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    /// First characters of the key, to tell keys apart
+    pub prefix: String,
+    pub scope: ApiScope,
+    /// Org the key acts for; None for keys that see every org
+    pub org_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+const API_KEY_COLUMNS: &str = "id, name, key_prefix, scope, org_id, created_at, last_used_at, revoked_at";
+
+fn api_key_from_row(row: &PgRow) -> Result<ApiKey, Error> {
+    let scope: String = row.try_get("scope")?;
+    Ok(ApiKey {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        prefix: row.try_get("key_prefix")?,
+        scope: scope.parse().map_err(|e: String| Error::Decode(e.into()))?,
+        org_id: row.try_get("org_id")?,
+        created_at: row.try_get("created_at")?,
+        last_used_at: row.try_get("last_used_at")?,
+        revoked_at: row.try_get("revoked_at")?,
+    })
+}
+
+/// Store a new key by its hash
+pub async fn create_api_key(
+    pool: &PgPool,
+    name: &str,
+    prefix: &str,
+    key_hash: &str,
+    scope: ApiScope,
+    org_id: Option<i32>,
+) -> Result<ApiKey, Error> {
+    let row = sqlx::query(&format!(
+        "INSERT INTO api_keys (name, key_prefix, key_hash, scope, org_id) VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+        API_KEY_COLUMNS
+    ))
+    .bind(name)
+    .bind(prefix)
+    .bind(key_hash)
+    .bind(scope.as_str())
+    .bind(org_id)
+    .fetch_one(pool)
+    .await?;
+
+    api_key_from_row(&row)
+}
+
+/// The unrevoked key with this hash, if any
+pub async fn find_api_key(pool: &PgPool, key_hash: &str) -> Result<Option<ApiKey>, Error> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL",
+        API_KEY_COLUMNS
+    ))
+    .bind(key_hash)
+    .fetch_optional(pool)
+    .await?;
+
+    row.as_ref().map(api_key_from_row).transpose()
+}
+
+/// Record that a key was just used
+pub async fn touch_api_key(pool: &PgPool, id: i32) -> Result<(), Error> {
+    sqlx::query("UPDATE api_keys SET last_used_at = CURRENT_TIMESTAMP WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Keys of the orgs `orgs` lets through, revoked ones included, newest first
+pub async fn list_api_keys(pool: &PgPool, orgs: OrgFilter) -> Result<Vec<ApiKey>, Error> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM api_keys WHERE ($1 OR org_id IS NOT DISTINCT FROM $2) ORDER BY created_at DESC, id DESC",
+        API_KEY_COLUMNS
+    ))
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter().map(api_key_from_row).collect()
+}
+
+/// Revoke a key of the orgs `orgs` lets through. Returns false if there is
+/// no such unrevoked key.
+pub async fn revoke_api_key(pool: &PgPool, id: i32, orgs: OrgFilter) -> Result<bool, Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE api_keys SET revoked_at = CURRENT_TIMESTAMP
+        WHERE id = $1 AND revoked_at IS NULL AND ($2 OR org_id IS NOT DISTINCT FROM $3)
+        "#,
+    )
+    .bind(id)
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes_are_cumulative() {
+        assert!(ApiScope::Admin.allows(ApiScope::Hook));
+        assert!(ApiScope::Hook.allows(ApiScope::Read));
+        assert!(!ApiScope::Read.allows(ApiScope::Hook));
+        assert!(!ApiScope::Hook.allows(ApiScope::Admin));
+        assert_eq!(" Admin ".parse::<ApiScope>(), Ok(ApiScope::Admin));
+        assert!("owner".parse::<ApiScope>().is_err());
+    }
+}
@@ -0,0 +1,282 @@
+// USAGE:
+// cargo test --test integration repo_archive -- --nocapture
+//
+// Export and import of a repo's processed data, one self-contained record per
+// commit, so it can be moved between environments or backed up without
+// database access. Components and ERC findings are derived from the parts
+// and distilled JSON, so they are rebuilt on import rather than archived;
+// embeddings are left to the indexer.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use sqlx::{Error, PgPool, Row};
+
+use crate::components::{self, components_from_distilled, ComponentRecord};
+use crate::erc::{self, run_erc};
+use crate::images::{load_image, store_image_in};
+use crate::visibility::Visibility;
+use crate::{retention, FullPart};
+
+/// Bumped when `ArchivedCommit` changes incompatibly
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Everything stored for one commit
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ArchivedCommit {
+    pub commit_hash: String,
+    pub commit_date: Option<DateTime<Utc>>,
+    pub git_message: Option<String>,
+    /// Base64 in JSON
+    #[serde(default, with = "base64_bytes")]
+    pub schematic_image: Option<Vec<u8>>,
+    pub change_summary: Option<String>,
+    pub project_overview: Option<String>,
+    pub blurb: Option<String>,
+    pub description: Option<String>,
+    pub detail_level: Option<String>,
+    pub prompt_version: Option<String>,
+    /// The commit's own override; None inherits the repo setting
+    pub visibility: Option<Visibility>,
+    pub distilled_json: Option<Value>,
+    pub board_json: Option<Value>,
+    #[serde(default)]
+    pub diff_warnings: Option<Value>,
+    pub timings: Option<Value>,
+    #[serde(default)]
+    pub author_name: Option<String>,
+    #[serde(default)]
+    pub author_email: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub pr_number: Option<i32>,
+    #[serde(default)]
+    pub parts: Vec<FullPart>,
+}
+
+/// Stored commits of a repo, oldest first, `limit` at a time
+pub async fn export_commits(
+    pool: &PgPool,
+    repo_url: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<ArchivedCommit>, Error> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, commit_hash, commit_date, git_message, image_digest, change_summary,
+            project_overview, blurb, description, detail_level, prompt_version, visibility,
+            distilled_json, board_json, diff_warnings, timings, author_name, author_email, tags, pr_number
+        FROM schematics
+        WHERE repo_url = $1 AND deleted_at IS NULL
+        ORDER BY commit_date ASC NULLS LAST, created_at ASC, id ASC
+        LIMIT $2 OFFSET $3
+        "#,
+    )
+    .bind(repo_url)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    let mut commits = Vec::with_capacity(rows.len());
+    for row in rows {
+        let id: i32 = row.try_get("id")?;
+        let parts = sqlx::query_as::<_, FullPart>(
+            "SELECT part_uuid, blurb, properties FROM parts WHERE schematic_id = $1 ORDER BY part_uuid",
+        )
+        .bind(id)
+        .fetch_all(pool)
+        .await?;
+        let visibility: Option<String> = row.try_get("visibility")?;
+        let image_digest: Option<String> = row.try_get("image_digest")?;
+        let schematic_image = match image_digest {
+            Some(digest) => load_image(pool, &digest).await?,
+            None => None,
+        };
+
+        commits.push(ArchivedCommit {
+            commit_hash: row.try_get("commit_hash")?,
+            commit_date: row.try_get("commit_date")?,
+            git_message: row.try_get("git_message")?,
+            schematic_image,
+            change_summary: row.try_get("change_summary")?,
+            project_overview: row.try_get("project_overview")?,
+            blurb: row.try_get("blurb")?,
+            description: row.try_get("description")?,
+            detail_level: row.try_get("detail_level")?,
+            prompt_version: row.try_get("prompt_version")?,
+            visibility: visibility.and_then(|v| v.parse().ok()),
+            distilled_json: row.try_get("distilled_json")?,
+            board_json: row.try_get("board_json")?,
+            diff_warnings: row.try_get("diff_warnings")?,
+            timings: row.try_get("timings")?,
+            author_name: row.try_get("author_name")?,
+            author_email: row.try_get("author_email")?,
+            tags: row.try_get("tags")?,
+            pr_number: row.try_get("pr_number")?,
+            parts,
+        });
+    }
+    Ok(commits)
+}
+
+/// Store an archived commit under `repo_url`, replacing whatever is stored for it
+pub async fn import_commit(
+    pool: &PgPool,
+    repo_url: &str,
+    commit: &ArchivedCommit,
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+    retention::forget_deleted(&mut tx, repo_url, &commit.commit_hash).await?;
+    let image_digest = match &commit.schematic_image {
+        Some(image) => Some(store_image_in(&mut tx, image).await?.0.digest),
+        None => None,
+    };
+
+    let schematic_id: i32 = sqlx::query(
+        r#"
+        INSERT INTO schematics (repo_url, commit_hash, commit_date, git_message, image_digest,
+            change_summary, project_overview, blurb, description, detail_level, prompt_version,
+            visibility, distilled_json, board_json, diff_warnings, timings, author_name, author_email, tags,
+            pr_number, processing_status)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20,
+            CASE WHEN $8::TEXT IS NOT NULL AND $9::TEXT IS NOT NULL THEN 'done' ELSE 'pending' END)
+        ON CONFLICT (repo_url, commit_hash) DO UPDATE SET
+            commit_date = EXCLUDED.commit_date,
+            git_message = EXCLUDED.git_message,
+            image_digest = EXCLUDED.image_digest,
+            change_summary = EXCLUDED.change_summary,
+            project_overview = EXCLUDED.project_overview,
+            blurb = EXCLUDED.blurb,
+            description = EXCLUDED.description,
+            detail_level = EXCLUDED.detail_level,
+            prompt_version = EXCLUDED.prompt_version,
+            visibility = EXCLUDED.visibility,
+            distilled_json = EXCLUDED.distilled_json,
+            board_json = EXCLUDED.board_json,
+            diff_warnings = EXCLUDED.diff_warnings,
+            timings = EXCLUDED.timings,
+            author_name = EXCLUDED.author_name,
+            author_email = EXCLUDED.author_email,
+            tags = EXCLUDED.tags,
+            pr_number = EXCLUDED.pr_number,
+            processing_status = EXCLUDED.processing_status,
+            processing_error = NULL,
+            processing_error_at = NULL
+        RETURNING id
+        "#,
+    )
+    .bind(repo_url)
+    .bind(&commit.commit_hash)
+    .bind(commit.commit_date)
+    .bind(&commit.git_message)
+    .bind(&image_digest)
+    .bind(&commit.change_summary)
+    .bind(&commit.project_overview)
+    .bind(&commit.blurb)
+    .bind(&commit.description)
+    .bind(&commit.detail_level)
+    .bind(&commit.prompt_version)
+    .bind(commit.visibility.map(|v| v.as_str()))
+    .bind(&commit.distilled_json)
+    .bind(&commit.board_json)
+    .bind(&commit.diff_warnings)
+    .bind(&commit.timings)
+    .bind(&commit.author_name)
+    .bind(&commit.author_email)
+    .bind(&commit.tags)
+    .bind(commit.pr_number)
+    .fetch_one(&mut *tx)
+    .await?
+    .try_get("id")?;
+
+    for table in ["parts", "components"] {
+        sqlx::query(&format!("DELETE FROM {} WHERE schematic_id = $1", table))
+            .bind(schematic_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    let parts = commit
+        .parts
+        .iter()
+        .map(|part| (part.part_uuid, part.blurb.as_deref(), &part.properties));
+    components::upsert_parts(&mut tx, schematic_id, parts).await?;
+
+    // As when stored by the pipeline: components from parts, then from the distilled schematic
+    let mut components: Vec<ComponentRecord> = commit
+        .parts
+        .iter()
+        .filter_map(|part| ComponentRecord::from_json(None, &part.properties, Some(part.part_uuid)))
+        .collect();
+    let findings = match &commit.distilled_json {
+        Some(distilled) => {
+            components.extend(components_from_distilled(distilled));
+            run_erc(distilled)
+        }
+        None => Vec::new(),
+    };
+    components::upsert_components(&mut tx, schematic_id, repo_url, &commit.commit_hash, &components)
+        .await?;
+    erc::replace_erc_findings(&mut tx, schematic_id, &findings).await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+mod base64_bytes {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        bytes.as_ref().map(|b| BASE64.encode(b)).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| BASE64.decode(s).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archived_commit_round_trips_through_json() {
+        let commit = ArchivedCommit {
+            commit_hash: "abc123".to_string(),
+            commit_date: None,
+            git_message: Some("Add LDO".to_string()),
+            schematic_image: Some(vec![0x89, b'P', b'N', b'G', 0]),
+            change_summary: None,
+            project_overview: None,
+            blurb: Some("Adds an LDO".to_string()),
+            description: None,
+            detail_level: None,
+            prompt_version: None,
+            visibility: Some(Visibility::Private),
+            distilled_json: Some(serde_json::json!({"components": {}})),
+            board_json: None,
+            diff_warnings: None,
+            timings: None,
+            author_name: Some("Ada".to_string()),
+            author_email: None,
+            tags: vec!["v1.0".to_string()],
+            pr_number: Some(7),
+            parts: Vec::new(),
+        };
+        let line = serde_json::to_string(&commit).unwrap();
+        assert!(line.contains("\"schematic_image\":\"iVBORwA=\""));
+        assert!(line.contains("\"visibility\":\"private\""));
+        assert_eq!(serde_json::from_str::<ArchivedCommit>(&line).unwrap(), commit);
+    }
+
+    #[test]
+    fn test_archived_commit_needs_only_a_hash() {
+        let commit: ArchivedCommit = serde_json::from_str(r#"{"commit_hash": "abc123"}"#).unwrap();
+        assert_eq!(commit.schematic_image, None);
+        assert!(commit.parts.is_empty());
+        assert!(serde_json::from_str::<ArchivedCommit>(r#"{"commit_hash": "a", "schematic_image": "!"}"#).is_err());
+    }
+}
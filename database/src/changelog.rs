@@ -0,0 +1,190 @@
+// USAGE:
+// cargo test changelog -- --nocapture
+//
+// HARDWARE_CHANGELOG.md generation: per-repo settings for writing the file
+// back to the analyzed repository, and rendering of stored commit overviews
+// grouped by release.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, PgPool, Row};
+use std::fmt::Write;
+
+pub const DEFAULT_CHANGELOG_PATH: &str = "HARDWARE_CHANGELOG.md";
+pub const DEFAULT_CHANGELOG_BRANCH: &str = "grokicad/changelog";
+
+/// Whether and where a repo's changelog is written back to it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChangelogSettings {
+    pub enabled: bool,
+    /// File path within the repository
+    pub path: String,
+    /// Bot branch the pull request is opened from
+    pub branch: String,
+}
+
+impl Default for ChangelogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: DEFAULT_CHANGELOG_PATH.to_string(),
+            branch: DEFAULT_CHANGELOG_BRANCH.to_string(),
+        }
+    }
+}
+
+/// Changelog settings for a repo (disabled if never set)
+pub async fn get_changelog_settings(
+    pool: &PgPool,
+    repo_url: &str,
+) -> Result<ChangelogSettings, Error> {
+    let row = sqlx::query(
+        "SELECT changelog_enabled, changelog_path, changelog_branch FROM repo_settings WHERE repo_url = $1",
+    )
+    .bind(repo_url)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(ChangelogSettings::default());
+    };
+    let defaults = ChangelogSettings::default();
+    Ok(ChangelogSettings {
+        enabled: row.try_get("changelog_enabled")?,
+        path: row
+            .try_get::<Option<String>, _>("changelog_path")?
+            .unwrap_or(defaults.path),
+        branch: row
+            .try_get::<Option<String>, _>("changelog_branch")?
+            .unwrap_or(defaults.branch),
+    })
+}
+
+/// Enable or disable the changelog for a repo; `None` path/branch reset to the defaults
+pub async fn set_changelog_settings(
+    pool: &PgPool,
+    repo_url: &str,
+    enabled: bool,
+    path: Option<&str>,
+    branch: Option<&str>,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO repo_settings (repo_url, changelog_enabled, changelog_path, changelog_branch, updated_at)
+        VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP)
+        ON CONFLICT (repo_url) DO UPDATE SET
+            changelog_enabled = EXCLUDED.changelog_enabled,
+            changelog_path = EXCLUDED.changelog_path,
+            changelog_branch = EXCLUDED.changelog_branch,
+            updated_at = EXCLUDED.updated_at
+        "#,
+    )
+    .bind(repo_url)
+    .bind(enabled)
+    .bind(path)
+    .bind(branch)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// One commit overview in the changelog
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChangelogEntry {
+    pub commit_hash: String,
+    pub commit_date: Option<DateTime<Utc>>,
+    pub message: Option<String>,
+    pub blurb: String,
+}
+
+/// Commits first released under one tag, or not released yet
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChangelogRelease {
+    /// Tag name; `None` for commits after the latest tag
+    pub tag: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+    /// Newest first
+    pub entries: Vec<ChangelogEntry>,
+}
+
+/// Render the changelog markdown, newest release first; releases without entries are left out
+pub fn render_changelog(repo: &str, releases: &[ChangelogRelease]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Hardware changelog\n");
+    let _ = writeln!(
+        out,
+        "Schematic and layout changes in {}, summarized per release. \
+         This file is generated; edits will be overwritten.",
+        repo
+    );
+
+    for release in releases.iter().filter(|r| !r.entries.is_empty()) {
+        let title = release.tag.as_deref().unwrap_or("Unreleased");
+        match release.date {
+            Some(date) => {
+                let _ = writeln!(out, "\n## {} ({})\n", title, date.format("%Y-%m-%d"));
+            }
+            None => {
+                let _ = writeln!(out, "\n## {}\n", title);
+            }
+        }
+        for entry in &release.entries {
+            let short = &entry.commit_hash[..entry.commit_hash.len().min(7)];
+            let blurb = entry.blurb.split_whitespace().collect::<Vec<_>>().join(" ");
+            let _ = write!(out, "- {} (`{}`", blurb, short);
+            if let Some(date) = entry.commit_date {
+                let _ = write!(out, ", {}", date.format("%Y-%m-%d"));
+            }
+            let _ = writeln!(out, ")");
+            if let Some(message) = entry.message.as_deref().filter(|m| !m.trim().is_empty()) {
+                let _ = writeln!(out, "  - Commit: {}", message.trim());
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry(hash: &str, blurb: &str) -> ChangelogEntry {
+        ChangelogEntry {
+            commit_hash: hash.to_string(),
+            commit_date: Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).single(),
+            message: Some("Swap regulator".to_string()),
+            blurb: blurb.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render_changelog() {
+        let releases = [
+            ChangelogRelease {
+                tag: None,
+                date: None,
+                entries: vec![entry("abcdef1234", "Replaced the LDO with a buck\nconverter")],
+            },
+            ChangelogRelease {
+                tag: Some("v1.1".to_string()),
+                date: Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).single(),
+                entries: Vec::new(),
+            },
+            ChangelogRelease {
+                tag: Some("v1.0".to_string()),
+                date: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).single(),
+                entries: vec![entry("1234567890", "Initial board")],
+            },
+        ];
+        let text = render_changelog("owner/board", &releases);
+
+        assert!(text.starts_with("# Hardware changelog\n"));
+        assert!(text.contains(
+            "## Unreleased\n\n- Replaced the LDO with a buck converter (`abcdef1`, 2024-03-01)\n  - Commit: Swap regulator\n"
+        ));
+        assert!(!text.contains("v1.1"));
+        assert!(text.contains("## v1.0 (2024-01-01)\n\n- Initial board (`1234567`"));
+        assert!(text.find("Unreleased") < text.find("v1.0"));
+    }
+}
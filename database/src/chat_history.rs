@@ -0,0 +1,206 @@
+// USAGE:
+// cargo test --test integration chat_history -- --nocapture
+//
+// Chat conversations kept by the server. Turns are stored as they were said
+// and never rewritten; a session's running summary stands in for the turns
+// up to `summarized_through` once the conversation outgrows the model's
+// context window, so both the raw and the compressed history stay around.
+// Assistant turns also keep what they cite and the renderings attached to
+// them.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, FromRow, PgPool};
+use uuid::Uuid;
+
+/// A conversation and its running summary
+#[derive(Debug, Clone, FromRow)]
+pub struct ChatSession {
+    pub id: Uuid,
+    /// Repository the conversation is about, if any
+    pub repo_url: Option<String>,
+    /// What the turns up to `summarized_through` said, once they are folded
+    pub summary: Option<String>,
+    pub summary_tokens: i32,
+    /// Position of the last turn the summary covers; 0 before any is folded
+    pub summarized_through: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// What a citation points at
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CitationKind {
+    Component,
+    Net,
+    Commit,
+}
+
+impl CitationKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Component => "component",
+            Self::Net => "net",
+            Self::Commit => "commit",
+        }
+    }
+}
+
+/// Something an assistant turn mentions that a viewer can link to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChatCitation {
+    pub kind: CitationKind,
+    /// Component reference, net name or full commit hash
+    pub target: String,
+    /// Commit the component or net was found at; None for commits
+    pub commit_hash: Option<String>,
+}
+
+/// A rendering kept with a turn, e.g. the symbol of a cited component
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChatAttachment {
+    /// One-line caption, e.g. "R1 Device:R"
+    pub title: String,
+    /// e.g. "image/svg+xml"
+    pub media_type: String,
+    pub content: String,
+    /// The citation it illustrates, by `target`
+    pub cites: Option<String>,
+}
+
+/// One stored turn; positions count from 1 within a session
+#[derive(Debug, Clone, FromRow)]
+pub struct StoredChatTurn {
+    pub position: i32,
+    /// "user" or "assistant"
+    pub role: String,
+    pub content: String,
+    /// Tokens of `content` for the model it was counted with
+    pub tokens: i32,
+    #[sqlx(json)]
+    pub citations: Vec<ChatCitation>,
+    #[sqlx(json)]
+    pub attachments: Vec<ChatAttachment>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A turn to append to a session; only assistant turns carry citations
+/// and attachments
+#[derive(Debug, Clone, Default)]
+pub struct NewChatTurn {
+    pub role: String,
+    pub content: String,
+    pub tokens: i32,
+    pub citations: Vec<ChatCitation>,
+    pub attachments: Vec<ChatAttachment>,
+}
+
+/// Start an empty conversation
+pub async fn create_chat_session(pool: &PgPool, repo_url: Option<&str>) -> Result<ChatSession, Error> {
+    sqlx::query_as(
+        r#"
+        INSERT INTO chat_sessions (id, repo_url)
+        VALUES ($1, $2)
+        RETURNING id, repo_url, summary, summary_tokens, summarized_through, created_at, updated_at
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(repo_url)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn get_chat_session(pool: &PgPool, id: Uuid) -> Result<Option<ChatSession>, Error> {
+    sqlx::query_as(
+        r#"
+        SELECT id, repo_url, summary, summary_tokens, summarized_through, created_at, updated_at
+        FROM chat_sessions
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// A session's turns after position `after`, oldest first; 0 gives them all
+pub async fn chat_turns(pool: &PgPool, session_id: Uuid, after: i32) -> Result<Vec<StoredChatTurn>, Error> {
+    sqlx::query_as(
+        r#"
+        SELECT position, role, content, tokens, citations, attachments, created_at
+        FROM chat_turns
+        WHERE session_id = $1 AND position > $2
+        ORDER BY position
+        "#,
+    )
+    .bind(session_id)
+    .bind(after)
+    .fetch_all(pool)
+    .await
+}
+
+/// Append `turns` after the session's last one
+///
+/// The session row is locked meanwhile, so two requests to one conversation
+/// don't take the same positions.
+pub async fn append_chat_turns(pool: &PgPool, session_id: Uuid, turns: &[NewChatTurn]) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT id FROM chat_sessions WHERE id = $1 FOR UPDATE")
+        .bind(session_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let last: i32 = sqlx::query_scalar("SELECT COALESCE(MAX(position), 0) FROM chat_turns WHERE session_id = $1")
+        .bind(session_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    for (position, turn) in (last + 1..).zip(turns) {
+        sqlx::query(
+            r#"
+            INSERT INTO chat_turns (session_id, position, role, content, tokens, citations, attachments)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(session_id)
+        .bind(position)
+        .bind(&turn.role)
+        .bind(&turn.content)
+        .bind(turn.tokens)
+        .bind(sqlx::types::Json(&turn.citations))
+        .bind(sqlx::types::Json(&turn.attachments))
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query("UPDATE chat_sessions SET updated_at = CURRENT_TIMESTAMP WHERE id = $1")
+        .bind(session_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+/// Replace the session's running summary with one covering the turns up to
+/// `summarized_through`
+///
+/// Never moves the summary backwards: a stale fold racing a newer one is
+/// dropped. Returns whether it was stored.
+pub async fn store_chat_summary(
+    pool: &PgPool,
+    session_id: Uuid,
+    summary: &str,
+    summary_tokens: i32,
+    summarized_through: i32,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE chat_sessions
+        SET summary = $2, summary_tokens = $3, summarized_through = $4, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1 AND summarized_through < $4
+        "#,
+    )
+    .bind(session_id)
+    .bind(summary)
+    .bind(summary_tokens)
+    .bind(summarized_through)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,93 @@
+// USAGE:
+// cargo test --test integration commit_answers -- --nocapture
+//
+// Stored answers to questions about one commit's design. A commit doesn't
+// change, so an answer only goes stale when the prompt or model does;
+// callers compare those before reusing it.
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{Error, FromRow, PgPool};
+
+/// An answer as written by the model
+#[derive(Debug, Clone, FromRow)]
+pub struct CommitAnswer {
+    pub repo_url: String,
+    pub commit_hash: String,
+    pub question_digest: String,
+    /// Whether the answer may draw on private summaries
+    pub include_private: bool,
+    pub question: String,
+    pub answer: String,
+    pub components: Vec<String>,
+    pub nets: Vec<String>,
+    pub sources: Value,
+    pub model: String,
+    pub prompt_version: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Key of a question: the same words in any case or spacing ask the same thing
+pub fn question_digest(question: &str) -> String {
+    let normalized = question.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
+
+/// The stored answer to the question with `question_digest` about a commit
+pub async fn get_commit_answer(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hash: &str,
+    question_digest: &str,
+    include_private: bool,
+) -> Result<Option<CommitAnswer>, Error> {
+    sqlx::query_as(
+        r#"
+        SELECT repo_url, commit_hash, question_digest, include_private, question, answer, components, nets,
+            sources, model, prompt_version, created_at
+        FROM commit_answers
+        WHERE repo_url = $1 AND commit_hash = $2 AND question_digest = $3 AND include_private = $4
+        "#,
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .bind(question_digest)
+    .bind(include_private)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Store an answer, replacing any earlier one to the same question
+pub async fn store_commit_answer(pool: &PgPool, answer: &CommitAnswer) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO commit_answers
+            (repo_url, commit_hash, question_digest, include_private, question, answer, components, nets, sources,
+             model, prompt_version)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        ON CONFLICT (repo_url, commit_hash, question_digest, include_private) DO UPDATE SET
+            question = EXCLUDED.question,
+            answer = EXCLUDED.answer,
+            components = EXCLUDED.components,
+            nets = EXCLUDED.nets,
+            sources = EXCLUDED.sources,
+            model = EXCLUDED.model,
+            prompt_version = EXCLUDED.prompt_version,
+            created_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(&answer.repo_url)
+    .bind(&answer.commit_hash)
+    .bind(&answer.question_digest)
+    .bind(answer.include_private)
+    .bind(&answer.question)
+    .bind(&answer.answer)
+    .bind(&answer.components)
+    .bind(&answer.nets)
+    .bind(&answer.sources)
+    .bind(&answer.model)
+    .bind(&answer.prompt_version)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
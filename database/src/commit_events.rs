@@ -0,0 +1,99 @@
+// USAGE:
+// cargo test --test integration commit_events -- --nocapture
+//
+// Commit events over Postgres LISTEN/NOTIFY. The processing pipeline
+// announces a commit in the transaction that stores it, so listeners hear of
+// it only once it is committed, whichever server instance processed it.
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgListener, PgTransaction};
+use sqlx::{Error, PgPool};
+use tracing::warn;
+
+/// Channel commit events are sent on
+pub const COMMIT_EVENTS_CHANNEL: &str = "commit_events";
+
+/// What happened to a commit
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitEventKind {
+    /// Processing finished and its results are stored
+    Processed,
+    /// Processing failed; the error is stored with the commit
+    Failed,
+}
+
+/// A change to a commit's processing state
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CommitEvent {
+    pub repo_url: String,
+    pub commit_hash: String,
+    pub kind: CommitEventKind,
+}
+
+/// Announce `event` to listeners right away
+pub async fn notify_commit_event(pool: &PgPool, event: &CommitEvent) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+    notify_commit_event_in(&mut tx, event).await?;
+    tx.commit().await
+}
+
+/// Announce `event` to listeners once `tx` commits; nothing is sent if it
+/// rolls back
+pub async fn notify_commit_event_in(tx: &mut PgTransaction<'_>, event: &CommitEvent) -> Result<(), Error> {
+    let payload = serde_json::to_string(event).map_err(|e| Error::Encode(Box::new(e)))?;
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(COMMIT_EVENTS_CHANNEL)
+        .bind(payload)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Dedicated connection receiving commit events
+pub struct CommitEvents {
+    listener: PgListener,
+}
+
+impl CommitEvents {
+    /// Start listening on a new connection from `pool`
+    pub async fn listen(pool: &PgPool) -> Result<Self, Error> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(COMMIT_EVENTS_CHANNEL).await?;
+        Ok(Self { listener })
+    }
+
+    /// Wait for the next event
+    ///
+    /// A dropped connection is re-established, but events sent while it was
+    /// down are lost. Payloads that aren't events are skipped.
+    pub async fn recv(&mut self) -> Result<CommitEvent, Error> {
+        loop {
+            let notification = self.listener.recv().await?;
+            match serde_json::from_str(notification.payload()) {
+                Ok(event) => return Ok(event),
+                Err(e) => warn!("Ignoring malformed commit event {:?}: {}", notification.payload(), e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn event_payload_round_trips() {
+        let event = CommitEvent {
+            repo_url: "https://github.com/acme/board".to_string(),
+            commit_hash: "abc123".to_string(),
+            kind: CommitEventKind::Processed,
+        };
+        let payload = serde_json::to_value(&event).unwrap();
+        assert_eq!(
+            payload,
+            json!({"repo_url": "https://github.com/acme/board", "commit_hash": "abc123", "kind": "processed"})
+        );
+        assert_eq!(serde_json::from_value::<CommitEvent>(payload).unwrap(), event);
+    }
+}
@@ -0,0 +1,80 @@
+// USAGE:
+// cargo test --test integration commit_metadata -- --nocapture
+//
+// Commit metadata beyond what the pipeline needs: author, tags and the pull
+// request that introduced the commit. Git knows the first two, but the PR
+// number costs a GitHub API call, so all of it is kept with the commit.
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgTransaction;
+use sqlx::{Error, PgPool};
+
+/// Author, tags and pull request of a commit
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, sqlx::FromRow)]
+pub struct CommitMetadata {
+    pub commit_hash: String,
+    pub author_name: Option<String>,
+    pub author_email: Option<String>,
+    /// Tags pointing at the commit when it was processed
+    pub tags: Vec<String>,
+    /// Pull request that introduced the commit, if known
+    pub pr_number: Option<i32>,
+}
+
+/// Store a commit's metadata, even if nothing else is stored for it yet
+pub async fn store_commit_metadata(
+    pool: &PgPool,
+    repo_url: &str,
+    metadata: &CommitMetadata,
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+    store_commit_metadata_in(&mut tx, repo_url, metadata).await?;
+    tx.commit().await
+}
+
+/// [`store_commit_metadata`] as part of `tx`
+pub async fn store_commit_metadata_in(
+    tx: &mut PgTransaction<'_>,
+    repo_url: &str,
+    metadata: &CommitMetadata,
+) -> Result<(), Error> {
+    crate::retention::forget_deleted(tx, repo_url, &metadata.commit_hash).await?;
+    sqlx::query(
+        r#"
+        INSERT INTO schematics (repo_url, commit_hash, author_name, author_email, tags, pr_number)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (repo_url, commit_hash) DO UPDATE SET
+            author_name = EXCLUDED.author_name,
+            author_email = EXCLUDED.author_email,
+            tags = EXCLUDED.tags,
+            pr_number = EXCLUDED.pr_number
+        "#,
+    )
+    .bind(repo_url)
+    .bind(&metadata.commit_hash)
+    .bind(&metadata.author_name)
+    .bind(&metadata.author_email)
+    .bind(&metadata.tags)
+    .bind(metadata.pr_number)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Metadata of those of `commit_hashes` that are stored, in no particular order
+pub async fn commit_metadata(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hashes: &[String],
+) -> Result<Vec<CommitMetadata>, Error> {
+    sqlx::query_as::<_, CommitMetadata>(
+        r#"
+        SELECT commit_hash, author_name, author_email, tags, pr_number
+        FROM schematics
+        WHERE repo_url = $1 AND commit_hash = ANY($2) AND deleted_at IS NULL
+        "#,
+    )
+    .bind(repo_url)
+    .bind(commit_hashes)
+    .fetch_all(pool)
+    .await
+}
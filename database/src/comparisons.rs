@@ -0,0 +1,77 @@
+// USAGE:
+// cargo test --test integration comparisons -- --nocapture
+//
+// Cached narratives of how a repo changed between two commits. Both commits
+// are fixed, so an entry only goes stale when the prompt or model changes;
+// callers compare those before reusing it.
+use chrono::{DateTime, Utc};
+use sqlx::{Error, FromRow, PgPool};
+
+/// A comparison of two commits, as written by the model
+#[derive(Debug, Clone, FromRow)]
+pub struct CommitComparison {
+    pub repo_url: String,
+    pub base_commit: String,
+    pub head_commit: String,
+    pub detail_level: String,
+    pub commit_count: i32,
+    pub summary: String,
+    pub changes: String,
+    pub model: String,
+    pub prompt_version: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The stored comparison of `base` and `head` at `detail_level`
+pub async fn get_comparison(
+    pool: &PgPool,
+    repo_url: &str,
+    base: &str,
+    head: &str,
+    detail_level: &str,
+) -> Result<Option<CommitComparison>, Error> {
+    sqlx::query_as(
+        r#"
+        SELECT repo_url, base_commit, head_commit, detail_level, commit_count, summary, changes, model,
+            prompt_version, created_at
+        FROM commit_comparisons
+        WHERE repo_url = $1 AND base_commit = $2 AND head_commit = $3 AND detail_level = $4
+        "#,
+    )
+    .bind(repo_url)
+    .bind(base)
+    .bind(head)
+    .bind(detail_level)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Store a comparison, replacing any earlier one of the same commits and level
+pub async fn store_comparison(pool: &PgPool, comparison: &CommitComparison) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO commit_comparisons
+            (repo_url, base_commit, head_commit, detail_level, commit_count, summary, changes, model, prompt_version)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (repo_url, base_commit, head_commit, detail_level) DO UPDATE SET
+            commit_count = EXCLUDED.commit_count,
+            summary = EXCLUDED.summary,
+            changes = EXCLUDED.changes,
+            model = EXCLUDED.model,
+            prompt_version = EXCLUDED.prompt_version,
+            created_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(&comparison.repo_url)
+    .bind(&comparison.base_commit)
+    .bind(&comparison.head_commit)
+    .bind(&comparison.detail_level)
+    .bind(comparison.commit_count)
+    .bind(&comparison.summary)
+    .bind(&comparison.changes)
+    .bind(&comparison.model)
+    .bind(&comparison.prompt_version)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
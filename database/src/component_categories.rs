@@ -0,0 +1,259 @@
+// USAGE:
+// cargo test component_categories -- --nocapture
+//
+// Where each distinct part sits in a fixed component taxonomy. A part is its
+// value, footprint and MPN as stored in `components`; the backend asks a cheap
+// model to classify the parts no one has classified yet and caches the answers
+// here, shared by every repo.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, FromRow, PgPool};
+use std::collections::HashMap;
+
+use crate::ComponentRecord;
+
+/// Categories and the subcategories each may be narrowed to
+pub const TAXONOMY: &[(&str, &[&str])] = &[
+    ("mcu", &[]),
+    ("fpga", &[]),
+    ("memory", &["flash", "eeprom", "ram"]),
+    ("regulator", &["ldo", "switching", "reference"]),
+    ("power", &["charger", "load_switch", "supervisor", "battery"]),
+    ("interface", &["usb", "ethernet", "can", "rs485", "level_shifter"]),
+    ("analog", &["opamp", "comparator", "adc", "dac"]),
+    ("logic", &[]),
+    ("sensor", &[]),
+    ("rf", &["module", "antenna"]),
+    ("discrete", &["diode", "led", "transistor", "mosfet"]),
+    ("passive", &["resistor", "capacitor", "inductor", "ferrite", "crystal"]),
+    ("protection", &["tvs", "fuse"]),
+    ("connector", &[]),
+    ("electromechanical", &["switch", "relay", "buzzer"]),
+    ("mechanical", &["mounting_hole", "fiducial", "test_point"]),
+    ("other", &[]),
+];
+
+/// The taxonomy as prompts list it: one "category: subcategories" line each
+pub fn taxonomy_lines() -> String {
+    TAXONOMY
+        .iter()
+        .map(|(category, subcategories)| match subcategories {
+            [] => format!("- {}", category),
+            _ => format!("- {}: {}", category, subcategories.join(", ")),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether `name` is a top-level category of the taxonomy
+pub fn is_category(name: &str) -> bool {
+    TAXONOMY.iter().any(|(category, _)| *category == name)
+}
+
+/// A distinct part; a missing value, footprint or MPN is ""
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, FromRow)]
+pub struct PartKey {
+    pub value: String,
+    pub footprint: String,
+    pub mpn: String,
+}
+
+impl PartKey {
+    pub fn of(component: &ComponentRecord) -> Self {
+        let text = |field: &Option<String>| field.clone().unwrap_or_default();
+        Self {
+            value: text(&component.value),
+            footprint: text(&component.footprint),
+            mpn: text(&component.mpn),
+        }
+    }
+
+    /// "10k | Resistor_SMD:R_0603 | -" for the classification prompt
+    pub fn prompt_line(&self) -> String {
+        let text = |field: &str| if field.is_empty() { "-".to_string() } else { field.to_string() };
+        format!("{} | {} | {}", text(&self.value), text(&self.footprint), text(&self.mpn))
+    }
+}
+
+/// Where a part sits in the taxonomy
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PartCategory {
+    pub category: String,
+    pub subcategory: Option<String>,
+}
+
+impl PartCategory {
+    /// Parse a model's "category/subcategory" (or bare "category") answer.
+    /// A bare subcategory resolves to its category; names outside the
+    /// taxonomy give None.
+    pub fn parse(answer: &str) -> Option<Self> {
+        let normalize = |name: &str| {
+            let name = name.trim().trim_matches(['`', '*', '.', '"']).trim();
+            name.to_lowercase().replace([' ', '-'], "_")
+        };
+        let (category, subcategory) = match answer.split_once('/') {
+            Some((category, subcategory)) => (normalize(category), Some(normalize(subcategory))),
+            None => (normalize(answer), None),
+        };
+        if let Some((_, subcategories)) = TAXONOMY.iter().find(|(name, _)| *name == category) {
+            let subcategory = subcategory.filter(|sub| subcategories.contains(&sub.as_str()));
+            return Some(Self { category, subcategory });
+        }
+        if subcategory.is_none() {
+            let parent = TAXONOMY.iter().find(|(_, subcategories)| subcategories.contains(&category.as_str()));
+            if let Some((parent, _)) = parent {
+                return Some(Self { category: parent.to_string(), subcategory: Some(category) });
+            }
+        }
+        None
+    }
+
+    /// "regulator/ldo", or just the category
+    pub fn label(&self) -> String {
+        match &self.subcategory {
+            Some(subcategory) => format!("{}/{}", self.category, subcategory),
+            None => self.category.clone(),
+        }
+    }
+}
+
+/// A cached classification, as stored in `component_categories`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+pub struct ClassifiedPart {
+    pub value: String,
+    pub footprint: String,
+    pub mpn: String,
+    pub category: String,
+    pub subcategory: Option<String>,
+    pub model: String,
+    pub classified_at: DateTime<Utc>,
+}
+
+/// Up to `limit` parts of non-deleted commits that have no classification
+/// yet, in a stable order
+pub async fn unclassified_parts(pool: &PgPool, limit: i64) -> Result<Vec<PartKey>, Error> {
+    sqlx::query_as::<_, PartKey>(
+        r#"
+        SELECT DISTINCT COALESCE(c.value, '') AS value, COALESCE(c.footprint, '') AS footprint,
+            COALESCE(c.mpn, '') AS mpn
+        FROM components c
+        JOIN schematics s ON s.id = c.schematic_id
+        WHERE s.deleted_at IS NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM component_categories cc
+                WHERE cc.value = COALESCE(c.value, '') AND cc.footprint = COALESCE(c.footprint, '')
+                    AND cc.mpn = COALESCE(c.mpn, '')
+            )
+        ORDER BY value, footprint, mpn
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Cache what `model` classified each part as, replacing earlier answers
+pub async fn store_part_categories(
+    pool: &PgPool,
+    classified: &[(PartKey, PartCategory)],
+    model: &str,
+) -> Result<(), Error> {
+    if classified.is_empty() {
+        return Ok(());
+    }
+    let column = |field: fn(&(PartKey, PartCategory)) -> &str| classified.iter().map(field).collect::<Vec<_>>();
+    sqlx::query(
+        r#"
+        INSERT INTO component_categories (value, footprint, mpn, category, subcategory, model)
+        SELECT *, $6 FROM UNNEST($1::TEXT[], $2::TEXT[], $3::TEXT[], $4::TEXT[], $5::TEXT[])
+        ON CONFLICT (value, footprint, mpn) DO UPDATE SET
+            category = EXCLUDED.category,
+            subcategory = EXCLUDED.subcategory,
+            model = EXCLUDED.model,
+            classified_at = NOW()
+        "#,
+    )
+    .bind(column(|(part, _)| &part.value))
+    .bind(column(|(part, _)| &part.footprint))
+    .bind(column(|(part, _)| &part.mpn))
+    .bind(column(|(_, category)| &category.category))
+    .bind(classified.iter().map(|(_, category)| category.subcategory.as_deref()).collect::<Vec<_>>())
+    .bind(model)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The cached classifications of `parts`; unclassified ones are absent
+pub async fn part_categories(pool: &PgPool, parts: &[PartKey]) -> Result<HashMap<PartKey, PartCategory>, Error> {
+    if parts.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let column = |field: fn(&PartKey) -> &str| parts.iter().map(field).collect::<Vec<_>>();
+    let rows = sqlx::query_as::<_, ClassifiedPart>(
+        r#"
+        SELECT cc.* FROM component_categories cc
+        JOIN UNNEST($1::TEXT[], $2::TEXT[], $3::TEXT[]) AS p(value, footprint, mpn)
+            ON cc.value = p.value AND cc.footprint = p.footprint AND cc.mpn = p.mpn
+        "#,
+    )
+    .bind(column(|part| &part.value))
+    .bind(column(|part| &part.footprint))
+    .bind(column(|part| &part.mpn))
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let part = PartKey { value: row.value, footprint: row.footprint, mpn: row.mpn };
+            (part, PartCategory { category: row.category, subcategory: row.subcategory })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(answer: &str) -> Option<String> {
+        PartCategory::parse(answer).map(|category| category.label())
+    }
+
+    #[test]
+    fn test_parse_category() {
+        assert_eq!(parsed("regulator/ldo").as_deref(), Some("regulator/ldo"));
+        assert_eq!(parsed(" Passive / Resistor ").as_deref(), Some("passive/resistor"));
+        assert_eq!(parsed("connector").as_deref(), Some("connector"));
+        assert_eq!(parsed("`mcu`").as_deref(), Some("mcu"));
+        assert_eq!(parsed("power/load switch").as_deref(), Some("power/load_switch"));
+    }
+
+    #[test]
+    fn test_parse_category_resolves_bare_subcategories() {
+        assert_eq!(parsed("ldo").as_deref(), Some("regulator/ldo"));
+        assert_eq!(parsed("capacitor").as_deref(), Some("passive/capacitor"));
+    }
+
+    #[test]
+    fn test_parse_category_drops_unknown_names() {
+        // An unknown subcategory still keeps the category
+        assert_eq!(parsed("regulator/buck_boost").as_deref(), Some("regulator"));
+        assert_eq!(parsed("widget"), None);
+        assert_eq!(parsed("widget/ldo"), None);
+        assert_eq!(parsed(""), None);
+    }
+
+    #[test]
+    fn test_prompt_line_marks_missing_fields() {
+        let part = PartKey {
+            value: "10k".to_string(),
+            footprint: "Resistor_SMD:R_0603".to_string(),
+            mpn: String::new(),
+        };
+        assert_eq!(part.prompt_line(), "10k | Resistor_SMD:R_0603 | -");
+        assert!(taxonomy_lines().contains("- regulator: ldo, switching, reference\n"));
+        assert!(is_category("passive"));
+        assert!(!is_category("ldo"));
+    }
+}
@@ -0,0 +1,99 @@
+// USAGE:
+// cargo test --test integration component_usage -- --nocapture
+//
+// Which repos use which parts, read from the `component_usage` view: each
+// repo counts with the newest commit whose components are stored, and MPNs
+// are compared trimmed and in upper case.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, FromRow, PgPool};
+
+use crate::organizations::OrgFilter;
+use crate::visibility::EFFECTIVE_VISIBILITY_SQL;
+
+/// One repo's use of a part
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+pub struct ComponentUsage {
+    pub repo_url: String,
+    pub commit_hash: String,
+    pub commit_date: Option<DateTime<Utc>>,
+    pub quantity: i64,
+    /// Reference designators, in text order
+    pub designators: Vec<String>,
+    pub value: Option<String>,
+    pub footprint: Option<String>,
+}
+
+/// A part used by several repos
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+pub struct SharedComponent {
+    pub mpn: String,
+    /// Repos using it, in order
+    pub repo_urls: Vec<String>,
+    /// Components across those repos
+    pub quantity: i64,
+}
+
+/// Repos whose latest components include `mpn`, most recently committed first
+///
+/// Private commits only count with `include_private`; `orgs` limits the repos
+/// to one org's.
+pub async fn component_usage(
+    pool: &PgPool,
+    mpn: &str,
+    include_private: bool,
+    orgs: OrgFilter,
+) -> Result<Vec<ComponentUsage>, Error> {
+    sqlx::query_as::<_, ComponentUsage>(&format!(
+        r#"
+        SELECT u.repo_url, u.commit_hash, u.commit_date, u.quantity, u.designators, u.value, u.footprint
+        FROM component_usage u
+        JOIN schematics s ON s.id = u.schematic_id
+        LEFT JOIN repos owner ON owner.repo_url = u.repo_url
+        WHERE u.mpn = UPPER(TRIM($1))
+            AND ($2 OR {visibility} = 'public')
+            AND ($3 OR owner.org_id IS NOT DISTINCT FROM $4)
+        ORDER BY u.commit_date DESC NULLS LAST, u.repo_url
+        "#,
+        visibility = EFFECTIVE_VISIBILITY_SQL
+    ))
+    .bind(mpn)
+    .bind(include_private)
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .fetch_all(pool)
+    .await
+}
+
+/// Up to `limit` parts used by at least `min_repos` repos, the most widely
+/// used first; filtered like [`component_usage`]
+pub async fn shared_components(
+    pool: &PgPool,
+    min_repos: i64,
+    limit: i64,
+    include_private: bool,
+    orgs: OrgFilter,
+) -> Result<Vec<SharedComponent>, Error> {
+    sqlx::query_as::<_, SharedComponent>(&format!(
+        r#"
+        SELECT u.mpn, ARRAY_AGG(u.repo_url ORDER BY u.repo_url) AS repo_urls, SUM(u.quantity)::BIGINT AS quantity
+        FROM component_usage u
+        JOIN schematics s ON s.id = u.schematic_id
+        LEFT JOIN repos owner ON owner.repo_url = u.repo_url
+        WHERE ($3 OR {visibility} = 'public')
+            AND ($4 OR owner.org_id IS NOT DISTINCT FROM $5)
+        GROUP BY u.mpn
+        HAVING COUNT(*) >= $1
+        ORDER BY COUNT(*) DESC, SUM(u.quantity) DESC, u.mpn
+        LIMIT $2
+        "#,
+        visibility = EFFECTIVE_VISIBILITY_SQL
+    ))
+    .bind(min_repos)
+    .bind(limit)
+    .bind(include_private)
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .fetch_all(pool)
+    .await
+}
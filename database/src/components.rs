@@ -0,0 +1,352 @@
+// USAGE:
+// cargo test components -- --nocapture
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use sqlx::{Error, PgConnection, PgPool};
+use uuid::Uuid;
+
+/// Property names KiCad libraries commonly use for the manufacturer part number
+pub(crate) const MPN_PROPERTY_KEYS: &[&str] = &[
+    "MPN",
+    "mpn",
+    "Manufacturer_Part_Number",
+    "Manufacturer Part Number",
+    "MFR_PN",
+    "Mfr. No",
+    "PartNumber",
+    "Part Number",
+];
+
+/// A component as stored in the `components` table
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ComponentRecord {
+    pub reference: String,
+    pub value: Option<String>,
+    pub footprint: Option<String>,
+    pub mpn: Option<String>,
+    pub sheet: Option<String>,
+    pub part_uuid: Option<Uuid>,
+    /// Anchor of the (first) unit on its sheet, in mm
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    /// In degrees, as in the schematic
+    pub rotation: Option<i32>,
+    /// Box around the body and pins, in mm
+    pub bbox_min_x: Option<f64>,
+    pub bbox_min_y: Option<f64>,
+    pub bbox_max_x: Option<f64>,
+    pub bbox_max_y: Option<f64>,
+}
+
+/// A component's state at one commit
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct ComponentHistoryEntry {
+    pub commit_hash: String,
+    pub commit_date: Option<DateTime<Utc>>,
+    pub git_message: Option<String>,
+    pub value: Option<String>,
+    pub footprint: Option<String>,
+    pub mpn: Option<String>,
+    pub sheet: Option<String>,
+    pub part_uuid: Option<Uuid>,
+}
+
+fn non_empty_str(value: Option<&Value>) -> Option<String> {
+    value
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|s| !s.is_empty() && *s != "~")
+        .map(str::to_string)
+}
+
+fn find_mpn(properties: Option<&Value>) -> Option<String> {
+    let properties = properties?.as_object()?;
+    MPN_PROPERTY_KEYS
+        .iter()
+        .find_map(|key| non_empty_str(properties.get(*key)))
+}
+
+impl ComponentRecord {
+    /// Build a record from a distilled component object (or a part's properties).
+    /// `reference` overrides the object's own "reference" field when given.
+    /// Returns None if no reference designator is available.
+    pub fn from_json(reference: Option<&str>, data: &Value, part_uuid: Option<Uuid>) -> Option<Self> {
+        let reference = reference
+            .map(str::to_string)
+            .or_else(|| non_empty_str(data.get("reference")))?;
+
+        let number = |value: &Value| value.as_f64();
+        let bbox = data.get("bbox");
+        Some(Self {
+            reference,
+            value: non_empty_str(data.get("value")),
+            footprint: non_empty_str(data.get("footprint")),
+            mpn: non_empty_str(data.get("mpn")).or_else(|| find_mpn(data.get("properties"))),
+            sheet: non_empty_str(data.get("sheet_path")).or_else(|| non_empty_str(data.get("sheet"))),
+            part_uuid: part_uuid.or_else(|| {
+                data.get("uuid")
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| Uuid::parse_str(s).ok())
+            }),
+            x: data.pointer("/position/x").and_then(number),
+            y: data.pointer("/position/y").and_then(number),
+            rotation: data.get("rotation").and_then(Value::as_i64).map(|r| r as i32),
+            bbox_min_x: bbox.and_then(|b| b.get("x1")).and_then(number),
+            bbox_min_y: bbox.and_then(|b| b.get("y1")).and_then(number),
+            bbox_max_x: bbox.and_then(|b| b.get("x2")).and_then(number),
+            bbox_max_y: bbox.and_then(|b| b.get("y2")).and_then(number),
+        })
+    }
+}
+
+/// Extract component records from distilled schematic JSON.
+/// Components can be an object keyed by reference or an array with a reference field.
+pub fn components_from_distilled(distilled: &Value) -> Vec<ComponentRecord> {
+    match distilled.get("components") {
+        Some(Value::Object(obj)) => obj
+            .iter()
+            .filter_map(|(reference, data)| ComponentRecord::from_json(Some(reference), data, None))
+            .collect(),
+        Some(Value::Array(arr)) => arr
+            .iter()
+            .filter_map(|data| ComponentRecord::from_json(None, data, None))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Upsert parts for a stored schematic (keyed by part UUID) in one statement
+pub(crate) async fn upsert_parts<'a>(
+    conn: &mut PgConnection,
+    schematic_id: i32,
+    parts: impl IntoIterator<Item = (Uuid, Option<&'a str>, &'a Value)>,
+) -> Result<(), Error> {
+    let mut uuids = Vec::new();
+    let mut blurbs = Vec::new();
+    let mut properties = Vec::new();
+    for (part_uuid, blurb, props) in parts {
+        uuids.push(part_uuid);
+        blurbs.push(blurb);
+        properties.push(props.clone());
+    }
+    if uuids.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO parts (schematic_id, part_uuid, blurb, properties)
+        SELECT $1, * FROM UNNEST($2::UUID[], $3::TEXT[], $4::JSONB[])
+        ON CONFLICT (schematic_id, part_uuid) DO UPDATE SET
+            blurb = EXCLUDED.blurb,
+            properties = EXCLUDED.properties
+        "#,
+    )
+    .bind(schematic_id)
+    .bind(&uuids)
+    .bind(&blurbs)
+    .bind(&properties)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Fold records that share a reference into one, in order: later fields win,
+/// but a later record's missing part UUID or placement keeps the earlier one,
+/// as the upsert's COALESCE would have row by row
+fn merge_by_reference(components: &[ComponentRecord]) -> Vec<ComponentRecord> {
+    let mut merged: Vec<ComponentRecord> = Vec::with_capacity(components.len());
+    let mut index: HashMap<&str, usize> = HashMap::with_capacity(components.len());
+    for component in components {
+        match index.get(component.reference.as_str()) {
+            Some(&at) => {
+                let earlier = &merged[at];
+                merged[at] = ComponentRecord {
+                    part_uuid: component.part_uuid.or(earlier.part_uuid),
+                    x: component.x.or(earlier.x),
+                    y: component.y.or(earlier.y),
+                    rotation: component.rotation.or(earlier.rotation),
+                    bbox_min_x: component.bbox_min_x.or(earlier.bbox_min_x),
+                    bbox_min_y: component.bbox_min_y.or(earlier.bbox_min_y),
+                    bbox_max_x: component.bbox_max_x.or(earlier.bbox_max_x),
+                    bbox_max_y: component.bbox_max_y.or(earlier.bbox_max_y),
+                    ..component.clone()
+                };
+            }
+            None => {
+                index.insert(&component.reference, merged.len());
+                merged.push(component.clone());
+            }
+        }
+    }
+    merged
+}
+
+/// Upsert components for a stored schematic (keyed by reference)
+///
+/// All rows go in one statement, the columns bound as arrays and unnested,
+/// so a design with thousands of parts costs one round trip, not thousands.
+pub(crate) async fn upsert_components(
+    conn: &mut PgConnection,
+    schematic_id: i32,
+    repo_url: &str,
+    commit_hash: &str,
+    components: &[ComponentRecord],
+) -> Result<(), Error> {
+    // A statement can't update the same row twice
+    let components = merge_by_reference(components);
+    if components.is_empty() {
+        return Ok(());
+    }
+    let column = |field: fn(&ComponentRecord) -> Option<f64>| components.iter().map(field).collect::<Vec<_>>();
+    sqlx::query(
+        r#"
+        INSERT INTO components (schematic_id, repo_url, commit_hash, reference, value, footprint, mpn, sheet, part_uuid,
+            x, y, rotation, bbox_min_x, bbox_min_y, bbox_max_x, bbox_max_y)
+        SELECT $1, $2, $3, * FROM UNNEST($4::TEXT[], $5::TEXT[], $6::TEXT[], $7::TEXT[], $8::TEXT[], $9::UUID[],
+            $10::FLOAT8[], $11::FLOAT8[], $12::INTEGER[], $13::FLOAT8[], $14::FLOAT8[], $15::FLOAT8[], $16::FLOAT8[])
+        ON CONFLICT (schematic_id, reference) DO UPDATE SET
+            value = EXCLUDED.value,
+            footprint = EXCLUDED.footprint,
+            mpn = EXCLUDED.mpn,
+            sheet = EXCLUDED.sheet,
+            part_uuid = COALESCE(EXCLUDED.part_uuid, components.part_uuid),
+            x = COALESCE(EXCLUDED.x, components.x),
+            y = COALESCE(EXCLUDED.y, components.y),
+            rotation = COALESCE(EXCLUDED.rotation, components.rotation),
+            bbox_min_x = COALESCE(EXCLUDED.bbox_min_x, components.bbox_min_x),
+            bbox_min_y = COALESCE(EXCLUDED.bbox_min_y, components.bbox_min_y),
+            bbox_max_x = COALESCE(EXCLUDED.bbox_max_x, components.bbox_max_x),
+            bbox_max_y = COALESCE(EXCLUDED.bbox_max_y, components.bbox_max_y)
+        "#,
+    )
+    .bind(schematic_id)
+    .bind(repo_url)
+    .bind(commit_hash)
+    .bind(components.iter().map(|c| c.reference.as_str()).collect::<Vec<_>>())
+    .bind(components.iter().map(|c| c.value.as_deref()).collect::<Vec<_>>())
+    .bind(components.iter().map(|c| c.footprint.as_deref()).collect::<Vec<_>>())
+    .bind(components.iter().map(|c| c.mpn.as_deref()).collect::<Vec<_>>())
+    .bind(components.iter().map(|c| c.sheet.as_deref()).collect::<Vec<_>>())
+    .bind(components.iter().map(|c| c.part_uuid).collect::<Vec<_>>())
+    .bind(column(|c| c.x))
+    .bind(column(|c| c.y))
+    .bind(components.iter().map(|c| c.rotation).collect::<Vec<_>>())
+    .bind(column(|c| c.bbox_min_x))
+    .bind(column(|c| c.bbox_min_y))
+    .bind(column(|c| c.bbox_max_x))
+    .bind(column(|c| c.bbox_max_y))
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// How a component (by reference designator) changed across a repo's commits, oldest first
+pub async fn find_component_history(
+    pool: &PgPool,
+    repo_url: &str,
+    reference: &str,
+) -> Result<Vec<ComponentHistoryEntry>, Error> {
+    sqlx::query_as::<_, ComponentHistoryEntry>(
+        r#"
+        SELECT s.commit_hash, s.commit_date, s.git_message,
+            c.value, c.footprint, c.mpn, c.sheet, c.part_uuid
+        FROM components c
+        JOIN schematics s ON s.id = c.schematic_id
+        WHERE c.repo_url = $1 AND c.reference = $2 AND s.deleted_at IS NULL
+        ORDER BY s.commit_date ASC NULLS LAST, s.created_at ASC, s.id ASC
+        "#,
+    )
+    .bind(repo_url)
+    .bind(reference)
+    .fetch_all(pool)
+    .await
+}
+
+/// All components stored for one commit, ordered by reference
+pub async fn list_components(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hash: &str,
+) -> Result<Vec<ComponentRecord>, Error> {
+    sqlx::query_as::<_, ComponentRecord>(
+        r#"
+        SELECT c.reference, c.value, c.footprint, c.mpn, c.sheet, c.part_uuid,
+            c.x, c.y, c.rotation, c.bbox_min_x, c.bbox_min_y, c.bbox_max_x, c.bbox_max_y
+        FROM components c
+        JOIN schematics s ON s.id = c.schematic_id
+        WHERE c.repo_url = $1 AND c.commit_hash = $2 AND s.deleted_at IS NULL
+        ORDER BY c.reference
+        "#,
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_components_from_distilled_object() {
+        let distilled = json!({
+            "components": {
+                "U1": {
+                    "value": "ESP32-S3",
+                    "footprint": "Package_DFN_QFN:QFN-56",
+                    "sheet_path": "/mcu",
+                    "properties": {"MPN": "ESP32-S3FH4R2"}
+                },
+                "R1": {"value": "10k", "footprint": "~"}
+            }
+        });
+
+        let mut components = components_from_distilled(&distilled);
+        components.sort_by(|a, b| a.reference.cmp(&b.reference));
+
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0].reference, "R1");
+        assert_eq!(components[0].footprint, None);
+        assert_eq!(components[1].mpn.as_deref(), Some("ESP32-S3FH4R2"));
+        assert_eq!(components[1].sheet.as_deref(), Some("/mcu"));
+    }
+
+    #[test]
+    fn test_components_from_distilled_array_requires_reference() {
+        let distilled = json!({
+            "components": [
+                {"reference": "C1", "value": "100n"},
+                {"value": "orphan"}
+            ]
+        });
+
+        let components = components_from_distilled(&distilled);
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].reference, "C1");
+    }
+
+    #[test]
+    fn test_merge_by_reference_keeps_earlier_placement() {
+        let uuid = Uuid::new_v4();
+        let from_part = ComponentRecord::from_json(Some("R1"), &json!({"value": "10k"}), Some(uuid)).unwrap();
+        let from_distilled = ComponentRecord::from_json(
+            Some("R1"),
+            &json!({"value": "4k7", "position": {"x": 10.0, "y": 20.0}}),
+            None,
+        )
+        .unwrap();
+        let other = ComponentRecord::from_json(Some("C1"), &json!({"value": "100n"}), None).unwrap();
+
+        let merged = merge_by_reference(&[from_part, other, from_distilled]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].reference, "R1");
+        assert_eq!(merged[0].value.as_deref(), Some("4k7"));
+        assert_eq!(merged[0].part_uuid, Some(uuid));
+        assert_eq!((merged[0].x, merged[0].y), (Some(10.0), Some(20.0)));
+        assert_eq!(merged[1].reference, "C1");
+    }
+}
@@ -0,0 +1,100 @@
+// USAGE:
+// cargo test --test integration deferred_completions -- --nocapture
+//
+// Deferred AI completions in flight. A job submits a request, records its id
+// here and polls the provider for the result; when the server restarts
+// before the result arrives, the job started again finds the id and carries
+// on polling instead of submitting (and paying for) the request twice. There
+// is at most one request per kind, repo and commit.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, PgPool};
+
+/// Commit summaries generated by a backfill
+pub const COMMIT_SUMMARY: &str = "commit_summary";
+
+/// A submitted request whose result hasn't been stored yet
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct DeferredCompletion {
+    /// The provider's id for the request
+    pub request_id: String,
+    /// What the result is for, e.g. [`COMMIT_SUMMARY`]
+    pub kind: String,
+    pub repo_url: String,
+    pub commit_hash: String,
+    /// The model it was submitted to
+    pub model: String,
+    /// Template the prompt was rendered from, e.g. "commit_summary@v1"
+    pub prompt_version: String,
+    pub submitted_at: DateTime<Utc>,
+}
+
+/// Record a submitted request, replacing any earlier one for the same kind,
+/// repo and commit
+pub async fn record_deferred_completion(
+    pool: &PgPool,
+    request_id: &str,
+    kind: &str,
+    repo_url: &str,
+    commit_hash: &str,
+    model: &str,
+    prompt_version: &str,
+) -> Result<DeferredCompletion, Error> {
+    sqlx::query_as::<_, DeferredCompletion>(
+        r#"
+        INSERT INTO deferred_completions (request_id, kind, repo_url, commit_hash, model, prompt_version)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (kind, repo_url, commit_hash) DO UPDATE SET
+            request_id = EXCLUDED.request_id,
+            model = EXCLUDED.model,
+            prompt_version = EXCLUDED.prompt_version,
+            submitted_at = CURRENT_TIMESTAMP
+        RETURNING *
+        "#,
+    )
+    .bind(request_id)
+    .bind(kind)
+    .bind(repo_url)
+    .bind(commit_hash)
+    .bind(model)
+    .bind(prompt_version)
+    .fetch_one(pool)
+    .await
+}
+
+/// The request in flight for a kind, repo and commit, if there is one
+pub async fn find_deferred_completion(
+    pool: &PgPool,
+    kind: &str,
+    repo_url: &str,
+    commit_hash: &str,
+) -> Result<Option<DeferredCompletion>, Error> {
+    sqlx::query_as::<_, DeferredCompletion>(
+        "SELECT * FROM deferred_completions WHERE kind = $1 AND repo_url = $2 AND commit_hash = $3",
+    )
+    .bind(kind)
+    .bind(repo_url)
+    .bind(commit_hash)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Every request in flight of a kind, oldest first
+pub async fn pending_deferred_completions(pool: &PgPool, kind: &str) -> Result<Vec<DeferredCompletion>, Error> {
+    sqlx::query_as::<_, DeferredCompletion>(
+        "SELECT * FROM deferred_completions WHERE kind = $1 ORDER BY submitted_at, request_id",
+    )
+    .bind(kind)
+    .fetch_all(pool)
+    .await
+}
+
+/// Forget a request, once its result is stored or given up on; returns
+/// whether it was recorded
+pub async fn delete_deferred_completion(pool: &PgPool, request_id: &str) -> Result<bool, Error> {
+    let result = sqlx::query("DELETE FROM deferred_completions WHERE request_id = $1")
+        .bind(request_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,295 @@
+// USAGE:
+// cargo test design_guidelines -- --nocapture
+// cargo test --test integration design_guidelines -- --nocapture
+//
+// Design guidelines: a team's house rules, kept per org or per repo and added
+// to the system prompt of summaries and chat. A repo's own guidelines come
+// before its org's, and the higher priority first within each; when they
+// don't all fit the character budget, the last ones are cut.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, FromRow, PgPool};
+
+use crate::messages::{ChatCompletionRequest, Message, MessageRole};
+use crate::xai_client::{InputMessage, ResponsesRequest};
+
+/// Least room, in characters, worth filling with the start of a guideline
+/// that doesn't fit whole
+const MIN_PARTIAL_CHARS: usize = 80;
+
+/// Who a guideline applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GuidelineScope {
+    /// Every repo of the org
+    Org(i32),
+    /// One repo, by URL
+    Repo(String),
+}
+
+impl GuidelineScope {
+    fn org_id(&self) -> Option<i32> {
+        match self {
+            GuidelineScope::Org(id) => Some(*id),
+            GuidelineScope::Repo(_) => None,
+        }
+    }
+
+    fn repo_url(&self) -> Option<&str> {
+        match self {
+            GuidelineScope::Org(_) => None,
+            GuidelineScope::Repo(url) => Some(url),
+        }
+    }
+}
+
+/// A stored guideline; exactly one of `org_id` and `repo_url` is set
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+pub struct DesignGuideline {
+    pub id: i64,
+    pub org_id: Option<i32>,
+    pub repo_url: Option<String>,
+    pub title: String,
+    pub body: String,
+    /// Higher comes first within its scope
+    pub priority: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A guideline's title, text and priority, for creating or replacing it
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewDesignGuideline {
+    pub title: String,
+    pub body: String,
+    pub priority: i32,
+}
+
+/// The guidelines of `scope`, in precedence order
+pub async fn list_design_guidelines(pool: &PgPool, scope: &GuidelineScope) -> Result<Vec<DesignGuideline>, Error> {
+    sqlx::query_as::<_, DesignGuideline>(
+        r#"
+        SELECT * FROM design_guidelines
+        WHERE org_id IS NOT DISTINCT FROM $1 AND repo_url IS NOT DISTINCT FROM $2
+        ORDER BY priority DESC, id
+        "#,
+    )
+    .bind(scope.org_id())
+    .bind(scope.repo_url())
+    .fetch_all(pool)
+    .await
+}
+
+/// Add a guideline to `scope`
+pub async fn create_design_guideline(
+    pool: &PgPool,
+    scope: &GuidelineScope,
+    guideline: &NewDesignGuideline,
+) -> Result<DesignGuideline, Error> {
+    sqlx::query_as::<_, DesignGuideline>(
+        r#"
+        INSERT INTO design_guidelines (org_id, repo_url, title, body, priority)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING *
+        "#,
+    )
+    .bind(scope.org_id())
+    .bind(scope.repo_url())
+    .bind(&guideline.title)
+    .bind(&guideline.body)
+    .bind(guideline.priority)
+    .fetch_one(pool)
+    .await
+}
+
+/// Replace a guideline of `scope`; None if it has no guideline `id`
+pub async fn update_design_guideline(
+    pool: &PgPool,
+    scope: &GuidelineScope,
+    id: i64,
+    guideline: &NewDesignGuideline,
+) -> Result<Option<DesignGuideline>, Error> {
+    sqlx::query_as::<_, DesignGuideline>(
+        r#"
+        UPDATE design_guidelines SET title = $4, body = $5, priority = $6, updated_at = NOW()
+        WHERE id = $1 AND org_id IS NOT DISTINCT FROM $2 AND repo_url IS NOT DISTINCT FROM $3
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(scope.org_id())
+    .bind(scope.repo_url())
+    .bind(&guideline.title)
+    .bind(&guideline.body)
+    .bind(guideline.priority)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Delete a guideline of `scope`; false if it has no guideline `id`
+pub async fn delete_design_guideline(pool: &PgPool, scope: &GuidelineScope, id: i64) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "DELETE FROM design_guidelines WHERE id = $1 AND org_id IS NOT DISTINCT FROM $2 AND repo_url IS NOT DISTINCT FROM $3",
+    )
+    .bind(id)
+    .bind(scope.org_id())
+    .bind(scope.repo_url())
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Every guideline that applies to a repo, in precedence order: its own,
+/// then those of the org it is registered to
+pub async fn repo_design_guidelines(pool: &PgPool, repo_url: &str) -> Result<Vec<DesignGuideline>, Error> {
+    sqlx::query_as::<_, DesignGuideline>(
+        r#"
+        SELECT g.* FROM design_guidelines g
+        WHERE g.repo_url = $1
+            OR g.org_id = (SELECT org_id FROM repos WHERE repo_url = $1)
+        ORDER BY g.repo_url IS NULL, g.priority DESC, g.id
+        "#,
+    )
+    .bind(repo_url)
+    .fetch_all(pool)
+    .await
+}
+
+/// The guidelines block added to a system prompt
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GuidelinePrompt {
+    pub text: String,
+    /// Guidelines in the block, the last possibly cut short
+    pub included: usize,
+    /// Whether the last included guideline was cut short
+    pub truncated: bool,
+    /// Guidelines left out for lack of room
+    pub omitted: usize,
+}
+
+impl GuidelinePrompt {
+    /// The block for `guidelines`, taken in the order given, in at most
+    /// `max_chars` characters; None without guidelines or room for one
+    ///
+    /// Guidelines are added whole while they fit. The first that doesn't is
+    /// cut short if there's still a fair amount of room, and it and the rest
+    /// are left out otherwise, so the lowest precedence goes first.
+    pub fn build(guidelines: &[DesignGuideline], max_chars: usize) -> Option<Self> {
+        let header = "## Design Guidelines\n\
+            The team's house rules. Follow them where they apply, and point out where the design departs from them. \
+            Where two conflict, the one listed first wins.";
+        let mut room = max_chars.checked_sub(header.chars().count())?;
+        let mut text = header.to_string();
+        let mut included = 0;
+        let mut truncated = false;
+        for guideline in guidelines {
+            let entry = format!("\n\n### {}\n{}", guideline.title.trim(), guideline.body.trim());
+            let len = entry.chars().count();
+            if len <= room {
+                text.push_str(&entry);
+                room -= len;
+                included += 1;
+                continue;
+            }
+            if room >= MIN_PARTIAL_CHARS {
+                text.extend(entry.chars().take(room - 1));
+                text.push('…');
+                included += 1;
+                truncated = true;
+            }
+            break;
+        }
+        (included > 0).then(|| GuidelinePrompt {
+            text,
+            included,
+            truncated,
+            omitted: guidelines.len() - included,
+        })
+    }
+
+    /// Append the block to the request's system message, or add one
+    pub fn apply(&self, request: &mut ChatCompletionRequest) {
+        match request
+            .messages
+            .iter_mut()
+            .find(|m| m.role == MessageRole::System)
+        {
+            Some(system) => system.content.push_text(&format!("\n\n---\n\n{}", self.text)),
+            None => request.messages.insert(0, Message::system(self.text.clone())),
+        }
+    }
+
+    /// Append the block to a responses API request's system input, or add one
+    pub fn apply_to_responses(&self, request: &mut ResponsesRequest) {
+        match request.input.iter_mut().find(|m| m.role == "system") {
+            Some(system) => system.content.push_str(&format!("\n\n---\n\n{}", self.text)),
+            None => request.input.insert(0, InputMessage::system(self.text.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guideline(title: &str, body: &str) -> DesignGuideline {
+        DesignGuideline {
+            id: 1,
+            org_id: Some(1),
+            repo_url: None,
+            title: title.to_string(),
+            body: body.to_string(),
+            priority: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_build_keeps_order_and_budget() {
+        let guidelines = [
+            guideline("Passives", "We always use 0402 passives."),
+            guideline("Regulators", "Prefer TI regulators."),
+        ];
+        let block = GuidelinePrompt::build(&guidelines, 4000).unwrap();
+        assert!(block.text.starts_with("## Design Guidelines\n"));
+        let passives = block.text.find("### Passives\nWe always use 0402 passives.").unwrap();
+        assert!(passives < block.text.find("### Regulators").unwrap());
+        assert_eq!((block.included, block.truncated, block.omitted), (2, false, 0));
+
+        // The last guideline goes first
+        let without_last = block.text.find("\n\n### Regulators").unwrap();
+        let block = GuidelinePrompt::build(&guidelines, without_last + 10).unwrap();
+        assert_eq!((block.included, block.truncated, block.omitted), (1, false, 1));
+        assert!(!block.text.contains("Regulators"));
+
+        assert_eq!(GuidelinePrompt::build(&guidelines, 20), None);
+        assert_eq!(GuidelinePrompt::build(&[], 4000), None);
+    }
+
+    #[test]
+    fn test_build_cuts_a_long_guideline_short() {
+        let guidelines = [guideline("Layout", &"Keep decoupling capacitors close. ".repeat(50))];
+        let block = GuidelinePrompt::build(&guidelines, 600).unwrap();
+        assert_eq!(block.text.chars().count(), 600);
+        assert!(block.text.ends_with('…'));
+        assert_eq!((block.included, block.truncated, block.omitted), (1, true, 0));
+    }
+
+    #[test]
+    fn test_apply_appends_to_the_system_message() {
+        let block = GuidelinePrompt::build(&[guideline("Passives", "0402 only.")], 4000).unwrap();
+        let mut request = ChatCompletionRequest::new(
+            vec![Message::system("Base prompt".to_string()), Message::user("Question".to_string())],
+            "grok-4".to_string(),
+        );
+        block.apply(&mut request);
+        assert_eq!(request.messages.len(), 2);
+        let system = request.messages[0].content.text();
+        assert!(system.starts_with("Base prompt") && system.ends_with("0402 only."), "{}", system);
+
+        let mut responses = ResponsesRequest::new("grok-4".to_string(), vec![InputMessage::user("Q".to_string())], vec![]);
+        block.apply_to_responses(&mut responses);
+        assert_eq!(responses.input[0].role, "system");
+        assert_eq!(responses.input[0].content, block.text);
+    }
+}
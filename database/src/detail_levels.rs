@@ -0,0 +1,94 @@
+// USAGE:
+// cargo test detail_levels -- --nocapture
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How much the model should write: a timeline blurb, a normal summary,
+/// or a full design review.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DetailLevel {
+    Brief,
+    #[default]
+    Standard,
+    Deep,
+}
+
+impl DetailLevel {
+    pub const ALL: [DetailLevel; 3] = [DetailLevel::Brief, DetailLevel::Standard, DetailLevel::Deep];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DetailLevel::Brief => "brief",
+            DetailLevel::Standard => "standard",
+            DetailLevel::Deep => "deep",
+        }
+    }
+
+    /// Token budget for a response at this level
+    pub fn max_tokens(&self) -> u32 {
+        match self {
+            DetailLevel::Brief => 150,
+            DetailLevel::Standard => 800,
+            DetailLevel::Deep => 3000,
+        }
+    }
+
+    /// Length and structure instructions appended to a summary prompt
+    pub fn instructions(&self) -> &'static str {
+        match self {
+            DetailLevel::Brief => {
+                "Respond with one or two sentences suitable for a commit timeline. \
+                No headings, lists or preamble."
+            }
+            DetailLevel::Standard => {
+                "Respond with a short paragraph followed by a bulleted list of the notable changes."
+            }
+            DetailLevel::Deep => {
+                "Respond with a thorough design review using the headings: Overview, Changes, \
+                Electrical Impact, Risks, Recommendations. Cover every changed subsystem."
+            }
+        }
+    }
+}
+
+impl fmt::Display for DetailLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DetailLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DetailLevel::ALL
+            .into_iter()
+            .find(|level| level.as_str() == s)
+            .ok_or_else(|| format!("Unknown detail level '{}'. Expected brief, standard or deep", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detail_level_round_trip() {
+        for level in DetailLevel::ALL {
+            assert_eq!(level.as_str().parse::<DetailLevel>().unwrap(), level);
+            let json = serde_json::to_string(&level).unwrap();
+            assert_eq!(json, format!("\"{}\"", level.as_str()));
+        }
+        assert!("verbose".parse::<DetailLevel>().is_err());
+        assert_eq!(DetailLevel::default(), DetailLevel::Standard);
+    }
+
+    #[test]
+    fn test_detail_level_budgets_increase() {
+        assert!(DetailLevel::Brief.max_tokens() < DetailLevel::Standard.max_tokens());
+        assert!(DetailLevel::Standard.max_tokens() < DetailLevel::Deep.max_tokens());
+        assert!(DetailLevel::Deep.instructions().contains("Recommendations"));
+    }
+}
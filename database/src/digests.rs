@@ -0,0 +1,409 @@
+// USAGE:
+// cargo test digests -- --nocapture
+// cargo test --test integration digests -- --nocapture
+//
+// Weekly activity digests: who each repo's digest goes to, which weeks have
+// gone out, what a repo's week held (commits, components added and removed,
+// ERC errors introduced) and the email that says so, rendered from the
+// templates in templates/.
+use chrono::{DateTime, Utc};
+use minijinja::{AutoEscape, Environment, Value};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, PgPool};
+
+const HTML_TEMPLATE: &str = include_str!("../templates/digest.html.j2");
+const TEXT_TEMPLATE: &str = include_str!("../templates/digest.txt.j2");
+
+/// Most components or findings an email lists before "and N more"
+pub const MAX_LISTED: usize = 25;
+
+/// An address a repo's digest goes to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct DigestSubscriber {
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Subscribe `email` to a repo's digest; false if it already was
+pub async fn add_digest_subscriber(pool: &PgPool, repo_url: &str, email: &str) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "INSERT INTO digest_subscribers (repo_url, email) VALUES ($1, LOWER($2)) ON CONFLICT DO NOTHING",
+    )
+    .bind(repo_url)
+    .bind(email)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Unsubscribe `email` from a repo's digest; false if it wasn't subscribed
+pub async fn remove_digest_subscriber(pool: &PgPool, repo_url: &str, email: &str) -> Result<bool, Error> {
+    let result = sqlx::query("DELETE FROM digest_subscribers WHERE repo_url = $1 AND email = LOWER($2)")
+        .bind(repo_url)
+        .bind(email)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// A repo's digest subscribers, by address
+pub async fn list_digest_subscribers(pool: &PgPool, repo_url: &str) -> Result<Vec<DigestSubscriber>, Error> {
+    sqlx::query_as::<_, DigestSubscriber>(
+        "SELECT email, created_at FROM digest_subscribers WHERE repo_url = $1 ORDER BY email",
+    )
+    .bind(repo_url)
+    .fetch_all(pool)
+    .await
+}
+
+/// Repos with subscribers whose digest for the week ending `period_end`
+/// hasn't gone out yet
+pub async fn due_digests(pool: &PgPool, period_end: DateTime<Utc>) -> Result<Vec<String>, Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT s.repo_url
+        FROM digest_subscribers s
+        LEFT JOIN repo_digests d USING (repo_url)
+        WHERE d.period_end IS NULL OR d.period_end < $1
+        ORDER BY s.repo_url
+        "#,
+    )
+    .bind(period_end)
+    .fetch_all(pool)
+    .await
+}
+
+/// Claim the digest for the week ending `period_end`, so only one instance
+/// sends it; false if it was already claimed
+pub async fn claim_digest(pool: &PgPool, repo_url: &str, period_end: DateTime<Utc>) -> Result<bool, Error> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO repo_digests (repo_url, period_end) VALUES ($1, $2)
+        ON CONFLICT (repo_url) DO UPDATE SET period_end = EXCLUDED.period_end, sent_at = CURRENT_TIMESTAMP
+        WHERE repo_digests.period_end < EXCLUDED.period_end
+        "#,
+    )
+    .bind(repo_url)
+    .bind(period_end)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// A commit made during the week
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct DigestCommit {
+    pub commit_hash: String,
+    pub commit_date: Option<DateTime<Utc>>,
+    pub message: Option<String>,
+    pub author_name: Option<String>,
+    /// One-line AI summary, if the commit has one
+    pub blurb: Option<String>,
+    /// pending, processing, done or failed
+    pub processing_status: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct DigestComponent {
+    pub reference: String,
+    pub value: Option<String>,
+}
+
+/// An ERC error the week introduced
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct DigestFinding {
+    pub rule: String,
+    pub reference: Option<String>,
+    pub message: String,
+}
+
+/// What happened in a repo between two instants
+///
+/// Components and findings compare the last distilled commit of the period
+/// against the last one before it; a repo with no earlier commit has every
+/// component added.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RepoActivity {
+    /// Oldest first
+    pub commits: Vec<DigestCommit>,
+    pub added_components: Vec<DigestComponent>,
+    pub removed_components: Vec<DigestComponent>,
+    pub erc_errors: Vec<DigestFinding>,
+}
+
+impl RepoActivity {
+    pub fn is_empty(&self) -> bool {
+        self.commits.is_empty()
+    }
+}
+
+/// The last distilled commit of the period `$2`..`$3`
+const HEAD_SQL: &str = r#"
+    SELECT id FROM schematics
+    WHERE repo_url = $1 AND deleted_at IS NULL AND distilled_json IS NOT NULL
+        AND commit_date >= $2 AND commit_date < $3
+    ORDER BY commit_date DESC, id DESC
+    LIMIT 1
+"#;
+
+/// The last distilled commit before the period
+const BASE_SQL: &str = r#"
+    SELECT id FROM schematics
+    WHERE repo_url = $1 AND deleted_at IS NULL AND distilled_json IS NOT NULL AND commit_date < $2
+    ORDER BY commit_date DESC, id DESC
+    LIMIT 1
+"#;
+
+/// Components of the commit `head` selects missing from the one `base` selects
+fn components_missing_sql(head: &str, base: &str) -> String {
+    format!(
+        r#"
+        SELECT c.reference, c.value FROM components c
+        WHERE c.schematic_id = ({head})
+            AND NOT EXISTS (
+                SELECT 1 FROM components b WHERE b.schematic_id = ({base}) AND b.reference = c.reference
+            )
+        ORDER BY LENGTH(c.reference), c.reference
+        "#,
+        head = head,
+        base = base
+    )
+}
+
+/// The last commit of the period `since`..`until` and the last one before
+/// it, those of them with no distilled netlist yet
+///
+/// [`repo_activity`] compares the last distilled commits, so distill these
+/// first for it to see the whole period.
+pub async fn undistilled_digest_commits(
+    pool: &PgPool,
+    repo_url: &str,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<Vec<String>, Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT commit_hash FROM (
+            (SELECT commit_hash, distilled_json FROM schematics
+             WHERE repo_url = $1 AND deleted_at IS NULL AND commit_date >= $2 AND commit_date < $3
+             ORDER BY commit_date DESC, id DESC LIMIT 1)
+            UNION ALL
+            (SELECT commit_hash, distilled_json FROM schematics
+             WHERE repo_url = $1 AND deleted_at IS NULL AND commit_date < $2
+             ORDER BY commit_date DESC, id DESC LIMIT 1)
+        ) latest
+        WHERE distilled_json IS NULL
+        "#,
+    )
+    .bind(repo_url)
+    .bind(since)
+    .bind(until)
+    .fetch_all(pool)
+    .await
+}
+
+/// The commits, component changes and new ERC errors of a repo from
+/// `since` up to (not including) `until`, by commit date
+pub async fn repo_activity(
+    pool: &PgPool,
+    repo_url: &str,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<RepoActivity, Error> {
+    let commits = sqlx::query_as::<_, DigestCommit>(
+        r#"
+        SELECT commit_hash, commit_date, git_message AS message, author_name, blurb, processing_status
+        FROM schematics
+        WHERE repo_url = $1 AND deleted_at IS NULL AND commit_date >= $2 AND commit_date < $3
+        ORDER BY commit_date, id
+        "#,
+    )
+    .bind(repo_url)
+    .bind(since)
+    .bind(until)
+    .fetch_all(pool)
+    .await?;
+    if commits.is_empty() {
+        return Ok(RepoActivity::default());
+    }
+
+    let mut changed_components = Vec::new();
+    for (head, base) in [(HEAD_SQL, BASE_SQL), (BASE_SQL, HEAD_SQL)] {
+        let components = sqlx::query_as::<_, DigestComponent>(&components_missing_sql(head, base))
+            .bind(repo_url)
+            .bind(since)
+            .bind(until)
+            .fetch_all(pool)
+            .await?;
+        changed_components.push(components);
+    }
+    let removed_components = changed_components.pop().unwrap_or_default();
+    let added_components = changed_components.pop().unwrap_or_default();
+
+    let erc_errors = sqlx::query_as::<_, DigestFinding>(&format!(
+        r#"
+        SELECT e.rule, e.reference, e.message FROM erc_findings e
+        WHERE e.schematic_id = ({head}) AND e.severity = 'error'
+            AND NOT EXISTS (
+                SELECT 1 FROM erc_findings b
+                WHERE b.schematic_id = ({base}) AND b.rule = e.rule
+                    AND b.reference IS NOT DISTINCT FROM e.reference
+                    AND b.pin IS NOT DISTINCT FROM e.pin
+                    AND b.net IS NOT DISTINCT FROM e.net
+            )
+        ORDER BY e.id
+        "#,
+        head = HEAD_SQL,
+        base = BASE_SQL
+    ))
+    .bind(repo_url)
+    .bind(since)
+    .bind(until)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(RepoActivity {
+        commits,
+        added_components,
+        removed_components,
+        erc_errors,
+    })
+}
+
+/// One repo's digest for one week
+#[derive(Serialize, Debug, Clone)]
+pub struct Digest {
+    /// "owner/repo"
+    pub repo: String,
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub activity: RepoActivity,
+    /// Where to view the repo, if known
+    pub view_url: Option<String>,
+    /// Prefix a commit hash completes into a link to the commit, if known
+    pub commit_url_prefix: Option<String>,
+}
+
+/// A digest ready to send
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedDigest {
+    pub subject: String,
+    pub html: String,
+    /// Plain-text alternative for mail clients without HTML
+    pub text: String,
+}
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_auto_escape_callback(|name| match name.ends_with(".html") {
+        true => AutoEscape::Html,
+        false => AutoEscape::None,
+    });
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.add_filter("date", |date: String| date.get(..10).unwrap_or(&date).to_string());
+    env.add_filter("short", |hash: String| hash.get(..7).unwrap_or(&hash).to_string());
+    // The HTML escaper also escapes "/", which some mail clients leave in links
+    env.add_filter("url", |url: String| {
+        let escaped = url.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;").replace('>', "&gt;");
+        Value::from_safe_string(escaped)
+    });
+    env
+}
+
+/// Render the HTML and plain-text email of a digest
+pub fn render_digest(digest: &Digest) -> Result<RenderedDigest, minijinja::Error> {
+    let mut env = environment();
+    env.add_template("digest.html", HTML_TEMPLATE)?;
+    env.add_template("digest.txt", TEXT_TEMPLATE)?;
+    let context = minijinja::context! {
+        digest => digest,
+        max_listed => MAX_LISTED,
+    };
+    let commits = digest.activity.commits.len();
+    Ok(RenderedDigest {
+        subject: format!(
+            "{}: {} schematic commit{} this week",
+            digest.repo,
+            commits,
+            if commits == 1 { "" } else { "s" }
+        ),
+        html: env.get_template("digest.html")?.render(&context)?,
+        text: env.get_template("digest.txt")?.render(&context)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn digest() -> Digest {
+        Digest {
+            repo: "acme/board".to_string(),
+            since: Utc.with_ymd_and_hms(2026, 10, 5, 9, 0, 0).unwrap(),
+            until: Utc.with_ymd_and_hms(2026, 10, 12, 9, 0, 0).unwrap(),
+            activity: RepoActivity {
+                commits: vec![DigestCommit {
+                    commit_hash: "1a2b3c4d5e6f".to_string(),
+                    commit_date: Some(Utc.with_ymd_and_hms(2026, 10, 7, 15, 30, 0).unwrap()),
+                    message: Some("Swap <LDO> for a buck".to_string()),
+                    author_name: Some("Ada".to_string()),
+                    blurb: Some("Replaces the LDO with a TPS62 buck".to_string()),
+                    processing_status: "done".to_string(),
+                }],
+                added_components: (1..=30)
+                    .map(|i| DigestComponent {
+                        reference: format!("C{}", i),
+                        value: Some("100n".to_string()),
+                    })
+                    .collect(),
+                removed_components: vec![DigestComponent {
+                    reference: "U2".to_string(),
+                    value: None,
+                }],
+                erc_errors: vec![DigestFinding {
+                    rule: "undriven_power_pin".to_string(),
+                    reference: Some("U3".to_string()),
+                    message: "U3 pin 1 (VIN) is a power input with no driver".to_string(),
+                }],
+            },
+            view_url: Some("https://grokicad.com/?github=https://github.com/acme/board".to_string()),
+            commit_url_prefix: Some("https://github.com/acme/board/commit/".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_render_digest() {
+        let rendered = render_digest(&digest()).unwrap();
+        assert_eq!(rendered.subject, "acme/board: 1 schematic commit this week");
+
+        // Commit messages are escaped in HTML, not in text
+        assert!(rendered.html.contains("Swap &lt;LDO&gt; for a buck"), "{}", rendered.html);
+        assert!(rendered.text.contains("Swap <LDO> for a buck"), "{}", rendered.text);
+        assert!(rendered.html.contains("href=\"https://github.com/acme/board/commit/1a2b3c4d5e6f\""));
+        assert!(rendered.text.contains("2026-10-05 to 2026-10-12"), "{}", rendered.text);
+        assert!(rendered.text.contains("1a2b3c4 2026-10-07 Ada: Swap <LDO> for a buck"), "{}", rendered.text);
+
+        // Long lists are cut short
+        assert!(rendered.text.contains("C25 (100n)"));
+        assert!(!rendered.text.contains("C26"));
+        assert!(rendered.text.contains("and 5 more"));
+        assert!(rendered.html.contains("and 5 more"));
+        assert!(rendered.text.contains("Removed: U2\n"), "{}", rendered.text);
+        assert!(rendered.text.contains("[undriven_power_pin] U3 pin 1 (VIN) is a power input with no driver"));
+    }
+
+    #[test]
+    fn test_render_quiet_digest() {
+        let mut digest = digest();
+        digest.activity = RepoActivity::default();
+        digest.view_url = None;
+        digest.commit_url_prefix = None;
+        let rendered = render_digest(&digest).unwrap();
+        assert_eq!(rendered.subject, "acme/board: 0 schematic commits this week");
+        assert!(rendered.text.contains("No schematic commits this week."));
+        assert!(!rendered.html.contains("href="));
+        assert!(!rendered.text.contains("ERC"));
+    }
+}
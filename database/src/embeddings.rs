@@ -0,0 +1,261 @@
+// USAGE:
+// cargo test --test integration semantic_search -- --nocapture
+//
+// Embedding storage and similarity search (pgvector). Commits are embedded
+// over their message and AI-generated text, parts over their supplier
+// description. The caller computes the vectors; this module finds what needs
+// (re-)embedding, stores the results and ranks rows against a query vector.
+//
+// The columns only exist when pgvector was installed at migration time (see
+// migrations/0017_semantic_search.sql); check `semantic_search_available`
+// before calling anything else here.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, PgPool};
+
+use crate::organizations::OrgFilter;
+use crate::visibility::EFFECTIVE_VISIBILITY_SQL;
+
+/// Text embedded for a commit; NULL columns are skipped
+const COMMIT_TEXT_SQL: &str =
+    "concat_ws(E'\\n', git_message, blurb, description, change_summary, project_overview)";
+
+/// Text embedded for a part
+const PART_TEXT_SQL: &str = "concat_ws(' ', manufacturer, mpn, description)";
+
+/// Rows that carry an embedding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbeddingTarget {
+    /// `schematics`, keyed by row ID
+    Commits,
+    /// `part_metadata`, keyed by MPN
+    Parts,
+}
+
+impl EmbeddingTarget {
+    fn table(self) -> &'static str {
+        match self {
+            EmbeddingTarget::Commits => "schematics",
+            EmbeddingTarget::Parts => "part_metadata",
+        }
+    }
+
+    fn key_sql(self) -> &'static str {
+        match self {
+            EmbeddingTarget::Commits => "id::TEXT",
+            EmbeddingTarget::Parts => "mpn",
+        }
+    }
+
+    /// Rows that may be embedded
+    fn filter_sql(self) -> &'static str {
+        match self {
+            EmbeddingTarget::Commits => "deleted_at IS NULL",
+            EmbeddingTarget::Parts => "TRUE",
+        }
+    }
+
+    fn text_sql(self) -> &'static str {
+        match self {
+            EmbeddingTarget::Commits => COMMIT_TEXT_SQL,
+            EmbeddingTarget::Parts => PART_TEXT_SQL,
+        }
+    }
+}
+
+/// Text waiting to be embedded
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct EmbeddingSource {
+    /// Row key, passed back to `store_embedding`
+    pub key: String,
+    pub text: String,
+    /// md5 of `text`
+    pub hash: String,
+}
+
+/// A commit ranked by similarity to the query
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct SemanticCommitHit {
+    pub repo_url: String,
+    pub commit_hash: String,
+    pub commit_date: Option<DateTime<Utc>>,
+    pub git_message: Option<String>,
+    pub blurb: Option<String>,
+    pub change_summary: Option<String>,
+    /// Cosine similarity, 1.0 for identical direction
+    pub similarity: f32,
+}
+
+/// A part ranked by similarity to the query, with the first commit in a repo that uses it
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct SemanticComponentHit {
+    pub mpn: String,
+    pub manufacturer: Option<String>,
+    pub description: Option<String>,
+    pub repo_url: String,
+    pub commit_hash: String,
+    pub commit_date: Option<DateTime<Utc>>,
+    /// One reference designator the part is used under in that commit
+    pub reference: String,
+    pub similarity: f32,
+}
+
+/// Whether the embedding columns exist, i.e. pgvector was installed when migrating
+pub async fn semantic_search_available(pool: &PgPool) -> Result<bool, Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM information_schema.columns
+            WHERE table_schema = current_schema() AND table_name = 'schematics' AND column_name = 'embedding'
+        )
+        "#,
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Rows whose text has no embedding from `model`, or changed since it was embedded
+pub async fn pending_embeddings(
+    pool: &PgPool,
+    target: EmbeddingTarget,
+    model: &str,
+    limit: i64,
+) -> Result<Vec<EmbeddingSource>, Error> {
+    sqlx::query_as::<_, EmbeddingSource>(&format!(
+        r#"
+        SELECT key, text, md5(text) AS hash
+        FROM (
+            SELECT {key} AS key, {text} AS text, embedding_model, embedding_hash FROM {table} WHERE {filter}
+        ) t
+        WHERE text <> ''
+            AND (embedding_model IS DISTINCT FROM $1 OR embedding_hash IS DISTINCT FROM md5(text))
+        ORDER BY key
+        LIMIT $2
+        "#,
+        key = target.key_sql(),
+        text = target.text_sql(),
+        table = target.table(),
+        filter = target.filter_sql(),
+    ))
+    .bind(model)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Store the embedding of a row's text, as returned by `pending_embeddings`
+pub async fn store_embedding(
+    pool: &PgPool,
+    target: EmbeddingTarget,
+    source: &EmbeddingSource,
+    model: &str,
+    embedding: &[f32],
+) -> Result<(), Error> {
+    sqlx::query(&format!(
+        r#"
+        UPDATE {table}
+        SET embedding = $1::REAL[]::vector, embedding_model = $2, embedding_hash = $3
+        WHERE {key} = $4
+        "#,
+        table = target.table(),
+        key = target.key_sql(),
+    ))
+    .bind(embedding)
+    .bind(model)
+    .bind(&source.hash)
+    .bind(&source.key)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Commits closest to `query` (embedded with `model`), most similar first
+///
+/// Private summaries are only included when `include_private` is set.
+pub async fn semantic_search_commits(
+    pool: &PgPool,
+    model: &str,
+    query: &[f32],
+    repo_url: Option<&str>,
+    limit: i64,
+    include_private: bool,
+    orgs: OrgFilter,
+) -> Result<Vec<SemanticCommitHit>, Error> {
+    sqlx::query_as::<_, SemanticCommitHit>(&format!(
+        r#"
+        SELECT s.repo_url, commit_hash, commit_date, git_message, blurb, change_summary,
+            (1 - (embedding <=> $1::REAL[]::vector))::REAL AS similarity
+        FROM schematics s
+        LEFT JOIN repos owner ON owner.repo_url = s.repo_url
+        WHERE embedding IS NOT NULL
+            AND deleted_at IS NULL
+            AND embedding_model = $2
+            AND vector_dims(embedding) = cardinality($1::REAL[])
+            AND ($3::TEXT IS NULL OR s.repo_url = $3)
+            AND ($5 OR {visibility} = 'public')
+            AND ($6 OR owner.org_id IS NOT DISTINCT FROM $7)
+        ORDER BY embedding <=> $1::REAL[]::vector
+        LIMIT $4
+        "#,
+        visibility = EFFECTIVE_VISIBILITY_SQL
+    ))
+    .bind(query)
+    .bind(model)
+    .bind(repo_url)
+    .bind(limit)
+    .bind(include_private)
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .fetch_all(pool)
+    .await
+}
+
+/// Parts closest to `query`, each with the first commit (per repo) using it
+///
+/// A part used in several repos appears once per repo. Visibility follows the
+/// commit, as for [`semantic_search_commits`].
+pub async fn semantic_search_components(
+    pool: &PgPool,
+    model: &str,
+    query: &[f32],
+    repo_url: Option<&str>,
+    limit: i64,
+    include_private: bool,
+    orgs: OrgFilter,
+) -> Result<Vec<SemanticComponentHit>, Error> {
+    sqlx::query_as::<_, SemanticComponentHit>(&format!(
+        r#"
+        SELECT * FROM (
+            SELECT DISTINCT ON (p.mpn, c.repo_url)
+                p.mpn, p.manufacturer, p.description, c.repo_url, c.commit_hash,
+                s.commit_date, c.reference,
+                (1 - (p.embedding <=> $1::REAL[]::vector))::REAL AS similarity
+            FROM part_metadata p
+            JOIN components c ON c.mpn = p.mpn
+            JOIN schematics s ON s.id = c.schematic_id
+            LEFT JOIN repos owner ON owner.repo_url = c.repo_url
+            WHERE p.embedding IS NOT NULL
+                AND s.deleted_at IS NULL
+                AND p.embedding_model = $2
+                AND vector_dims(p.embedding) = cardinality($1::REAL[])
+                AND ($3::TEXT IS NULL OR c.repo_url = $3)
+                AND ($5 OR {visibility} = 'public')
+                AND ($6 OR owner.org_id IS NOT DISTINCT FROM $7)
+            ORDER BY p.mpn, c.repo_url, s.commit_date ASC NULLS LAST, c.reference
+        ) first_use
+        ORDER BY similarity DESC
+        LIMIT $4
+        "#,
+        visibility = EFFECTIVE_VISIBILITY_SQL
+    ))
+    .bind(query)
+    .bind(model)
+    .bind(repo_url)
+    .bind(limit)
+    .bind(include_private)
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .fetch_all(pool)
+    .await
+}
+
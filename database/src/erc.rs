@@ -0,0 +1,506 @@
+// USAGE:
+// cargo test erc -- --nocapture
+//
+// Basic electrical rule checks over distilled schematic JSON. The distiller
+// doesn't export pin electrical types, so drivers and outputs are inferred from
+// pin names, designator prefixes and net names; the rules err on the side of
+// staying quiet rather than flagging every passive.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Error, PgConnection, PgPool};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Pin names that drive a net (compared after stripping digits/suffixes)
+const OUTPUT_PIN_NAMES: &[&str] = &["OUT", "VOUT", "Q", "TX", "TXD", "DO", "DOUT", "MISO"];
+
+/// Pin names that must be supplied by something else on the net
+const POWER_INPUT_PIN_NAMES: &[&str] = &[
+    "VCC", "VDD", "VDDA", "VDDIO", "AVDD", "DVDD", "VIN", "VBAT", "VS", "V+", "VCCA", "VCCIO",
+];
+
+/// Designator prefixes of parts that bring power onto a board
+const SOURCE_REFERENCE_PREFIXES: &[&str] = &["J", "P", "CN", "BT", "PS", "TP"];
+
+/// Pin names that are deliberately left open
+const NO_CONNECT_PIN_NAMES: &[&str] = &["NC", "DNC", "N.C.", "NC/NC"];
+
+/// An ERC rule
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ErcRule {
+    UnconnectedPin,
+    ConflictingOutputs,
+    UndrivenPowerPin,
+    DuplicateReference,
+}
+
+impl ErcRule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErcRule::UnconnectedPin => "unconnected_pin",
+            ErcRule::ConflictingOutputs => "conflicting_outputs",
+            ErcRule::UndrivenPowerPin => "undriven_power_pin",
+            ErcRule::DuplicateReference => "duplicate_reference",
+        }
+    }
+
+    pub fn severity(&self) -> ErcSeverity {
+        match self {
+            ErcRule::UnconnectedPin => ErcSeverity::Warning,
+            ErcRule::ConflictingOutputs
+            | ErcRule::UndrivenPowerPin
+            | ErcRule::DuplicateReference => ErcSeverity::Error,
+        }
+    }
+}
+
+impl std::str::FromStr for ErcRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unconnected_pin" => Ok(ErcRule::UnconnectedPin),
+            "conflicting_outputs" => Ok(ErcRule::ConflictingOutputs),
+            "undriven_power_pin" => Ok(ErcRule::UndrivenPowerPin),
+            "duplicate_reference" => Ok(ErcRule::DuplicateReference),
+            other => Err(format!("unknown ERC rule '{}'", other)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ErcSeverity {
+    Warning,
+    Error,
+}
+
+impl ErcSeverity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErcSeverity::Warning => "warning",
+            ErcSeverity::Error => "error",
+        }
+    }
+}
+
+/// One ERC violation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ErcFinding {
+    pub rule: ErcRule,
+    pub reference: Option<String>,
+    pub pin: Option<String>,
+    pub net: Option<String>,
+    pub message: String,
+}
+
+impl ErcFinding {
+    /// Identity used to match the same violation across commits (the message may change)
+    fn key(&self) -> (ErcRule, Option<&str>, Option<&str>, Option<&str>) {
+        (
+            self.rule,
+            self.reference.as_deref(),
+            self.pin.as_deref(),
+            self.net.as_deref(),
+        )
+    }
+
+    pub fn severity(&self) -> ErcSeverity {
+        self.rule.severity()
+    }
+}
+
+struct Pin<'a> {
+    reference: &'a str,
+    number: &'a str,
+    name: Option<&'a str>,
+    net: Option<&'a str>,
+}
+
+/// Normalize a pin name for matching: uppercase, with a trailing index or
+/// polarity marker removed ("VDD_1" -> "VDD", "OUT2" -> "OUT")
+fn base_pin_name(name: &str) -> String {
+    let upper = name.trim().trim_start_matches('~').to_uppercase();
+    let upper = upper.split(['_', '/', ' ']).next().unwrap_or_default();
+    upper.trim_end_matches(|c: char| c.is_ascii_digit()).to_string()
+}
+
+fn is_output_pin(name: Option<&str>) -> bool {
+    name.is_some_and(|n| OUTPUT_PIN_NAMES.contains(&base_pin_name(n).as_str()))
+}
+
+fn is_power_input_pin(name: Option<&str>) -> bool {
+    name.is_some_and(|n| POWER_INPUT_PIN_NAMES.contains(&base_pin_name(n).as_str()))
+}
+
+fn is_no_connect_pin(name: Option<&str>) -> bool {
+    name.is_some_and(|n| NO_CONNECT_PIN_NAMES.contains(&n.trim().to_uppercase().as_str()))
+}
+
+fn is_source_reference(reference: &str) -> bool {
+    let prefix: String = reference
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_uppercase();
+    SOURCE_REFERENCE_PREFIXES.contains(&prefix.as_str())
+}
+
+/// Nets named by the user or a power symbol, as opposed to KiCad's generated names
+fn is_named_net(net: &str) -> bool {
+    !(net.starts_with("Net-") || net.starts_with("unconnected-") || net == "unnamed")
+}
+
+/// Flatten distilled components into (reference, component) pairs, keeping duplicates
+fn component_entries(distilled: &Value) -> Vec<(String, &Value)> {
+    match distilled.get("components") {
+        Some(Value::Object(obj)) => obj.iter().map(|(r, c)| (r.clone(), c)).collect(),
+        Some(Value::Array(arr)) => arr
+            .iter()
+            .filter_map(|c| Some((c.get("reference")?.as_str()?.to_string(), c)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Run all ERC rules over a distilled schematic
+pub fn run_erc(distilled: &Value) -> Vec<ErcFinding> {
+    let entries = component_entries(distilled);
+    let mut findings = Vec::new();
+
+    // Duplicate (or colliding unannotated) references
+    let mut reference_counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for (reference, _) in &entries {
+        *reference_counts.entry(reference.as_str()).or_default() += 1;
+    }
+    for (reference, count) in &reference_counts {
+        if *count > 1 || reference.ends_with('?') {
+            findings.push(ErcFinding {
+                rule: ErcRule::DuplicateReference,
+                reference: Some(reference.to_string()),
+                pin: None,
+                net: None,
+                message: if reference.ends_with('?') {
+                    format!("{} is not annotated", reference)
+                } else {
+                    format!("Reference {} is used by {} components", reference, count)
+                },
+            });
+        }
+    }
+
+    let pins: Vec<Pin> = entries
+        .iter()
+        .flat_map(|(reference, component)| {
+            component
+                .get("pins")
+                .and_then(|p| p.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(move |pin| {
+                    Some(Pin {
+                        reference: reference.as_str(),
+                        number: pin.get("number")?.as_str()?,
+                        name: pin.get("name").and_then(|n| n.as_str()),
+                        net: pin.get("net").and_then(|n| n.as_str()),
+                    })
+                })
+        })
+        .collect();
+
+    let mut pins_by_net: HashMap<&str, Vec<&Pin>> = HashMap::new();
+    for pin in &pins {
+        if let Some(net) = pin.net {
+            pins_by_net.entry(net).or_default().push(pin);
+        }
+    }
+
+    // Unconnected pins: no net at all, or alone on a generated net
+    for pin in &pins {
+        if is_no_connect_pin(pin.name) {
+            continue;
+        }
+        let dangling = match pin.net {
+            None => true,
+            Some(net) => !is_named_net(net) && pins_by_net.get(net).map_or(0, Vec::len) == 1,
+        };
+        if dangling && !is_power_input_pin(pin.name) {
+            findings.push(ErcFinding {
+                rule: ErcRule::UnconnectedPin,
+                reference: Some(pin.reference.to_string()),
+                pin: Some(pin.number.to_string()),
+                net: pin.net.map(str::to_string),
+                message: format!(
+                    "Pin {}{} of {} is not connected",
+                    pin.number,
+                    pin.name.map(|n| format!(" ({})", n)).unwrap_or_default(),
+                    pin.reference
+                ),
+            });
+        }
+    }
+
+    let mut nets: Vec<&&str> = pins_by_net.keys().collect();
+    nets.sort();
+
+    for net in nets {
+        let net_pins = &pins_by_net[*net];
+
+        // Conflicting outputs: more than one part driving the same net
+        let drivers: Vec<&&Pin> = net_pins.iter().filter(|p| is_output_pin(p.name)).collect();
+        let driving_parts: HashSet<&str> = drivers.iter().map(|p| p.reference).collect();
+        if driving_parts.len() > 1 {
+            let mut names: Vec<String> = drivers
+                .iter()
+                .map(|p| format!("{}.{}", p.reference, p.name.unwrap_or(p.number)))
+                .collect();
+            names.sort();
+            findings.push(ErcFinding {
+                rule: ErcRule::ConflictingOutputs,
+                reference: None,
+                pin: None,
+                net: Some(net.to_string()),
+                message: format!("Net {} is driven by multiple outputs: {}", net, names.join(", ")),
+            });
+        }
+
+        // Power inputs on a net nothing supplies
+        let driven = is_named_net(net)
+            || !drivers.is_empty()
+            || net_pins.iter().any(|p| is_source_reference(p.reference));
+        if !driven {
+            for pin in net_pins.iter().filter(|p| is_power_input_pin(p.name)) {
+                findings.push(ErcFinding {
+                    rule: ErcRule::UndrivenPowerPin,
+                    reference: Some(pin.reference.to_string()),
+                    pin: Some(pin.number.to_string()),
+                    net: Some(net.to_string()),
+                    message: format!(
+                        "Power pin {} ({}) of {} is on net {}, which has no power source",
+                        pin.number,
+                        pin.name.unwrap_or_default(),
+                        pin.reference,
+                        net
+                    ),
+                });
+            }
+        }
+    }
+
+    // Power inputs with no net at all
+    for pin in pins.iter().filter(|p| p.net.is_none() && is_power_input_pin(p.name)) {
+        findings.push(ErcFinding {
+            rule: ErcRule::UndrivenPowerPin,
+            reference: Some(pin.reference.to_string()),
+            pin: Some(pin.number.to_string()),
+            net: None,
+            message: format!(
+                "Power pin {} ({}) of {} is not connected to any supply",
+                pin.number,
+                pin.name.unwrap_or_default(),
+                pin.reference
+            ),
+        });
+    }
+
+    findings
+}
+
+/// Findings in `current` that were not present in `previous` (e.g. the parent commit)
+pub fn introduced_findings<'a>(
+    current: &'a [ErcFinding],
+    previous: &[ErcFinding],
+) -> Vec<&'a ErcFinding> {
+    let previous: HashSet<_> = previous.iter().map(ErcFinding::key).collect();
+    current
+        .iter()
+        .filter(|f| !previous.contains(&f.key()))
+        .collect()
+}
+
+/// Replace the stored findings for a schematic
+pub(crate) async fn replace_erc_findings(
+    conn: &mut PgConnection,
+    schematic_id: i32,
+    findings: &[ErcFinding],
+) -> Result<(), Error> {
+    sqlx::query("DELETE FROM erc_findings WHERE schematic_id = $1")
+        .bind(schematic_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for finding in findings {
+        sqlx::query(
+            r#"
+            INSERT INTO erc_findings (schematic_id, rule, severity, reference, pin, net, message)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(schematic_id)
+        .bind(finding.rule.as_str())
+        .bind(finding.severity().as_str())
+        .bind(&finding.reference)
+        .bind(&finding.pin)
+        .bind(&finding.net)
+        .bind(&finding.message)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+#[derive(sqlx::FromRow)]
+struct ErcFindingRow {
+    rule: String,
+    reference: Option<String>,
+    pin: Option<String>,
+    net: Option<String>,
+    message: String,
+}
+
+/// Stored ERC findings for a commit, or None if the commit hasn't been distilled
+pub async fn get_erc_findings(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hash: &str,
+) -> Result<Option<Vec<ErcFinding>>, Error> {
+    let distilled: Option<(bool,)> = sqlx::query_as(
+        "SELECT distilled_json IS NOT NULL FROM schematics WHERE repo_url = $1 AND commit_hash = $2 AND deleted_at IS NULL",
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .fetch_optional(pool)
+    .await?;
+    if !matches!(distilled, Some((true,))) {
+        return Ok(None);
+    }
+
+    let rows: Vec<ErcFindingRow> = sqlx::query_as(
+        r#"
+        SELECT e.rule, e.reference, e.pin, e.net, e.message
+        FROM erc_findings e
+        JOIN schematics s ON s.id = e.schematic_id
+        WHERE s.repo_url = $1 AND s.commit_hash = $2 AND s.deleted_at IS NULL
+        ORDER BY e.id
+        "#,
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .fetch_all(pool)
+    .await?;
+
+    let findings = rows
+        .into_iter()
+        .filter_map(|row| {
+            Some(ErcFinding {
+                rule: row.rule.parse().ok()?,
+                reference: row.reference,
+                pin: row.pin,
+                net: row.net,
+                message: row.message,
+            })
+        })
+        .collect();
+    Ok(Some(findings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rules(findings: &[ErcFinding]) -> Vec<ErcRule> {
+        findings.iter().map(|f| f.rule).collect()
+    }
+
+    #[test]
+    fn test_clean_schematic() {
+        let distilled = json!({
+            "components": {
+                "U1": {"pins": [
+                    {"number": "1", "name": "VDD", "net": "+3V3"},
+                    {"number": "2", "name": "OUT", "net": "SIG"}
+                ]},
+                "R1": {"pins": [
+                    {"number": "1", "name": null, "net": "SIG"},
+                    {"number": "2", "name": null, "net": "GND"}
+                ]}
+            }
+        });
+        assert!(run_erc(&distilled).is_empty());
+    }
+
+    #[test]
+    fn test_unconnected_and_no_connect_pins() {
+        let distilled = json!({
+            "components": {
+                "U1": {"pins": [
+                    {"number": "1", "name": "EN", "net": null},
+                    {"number": "2", "name": "NC", "net": null},
+                    {"number": "3", "name": "SDA", "net": "Net-(U1-SDA)"}
+                ]}
+            }
+        });
+        let findings = run_erc(&distilled);
+        assert_eq!(rules(&findings), vec![ErcRule::UnconnectedPin, ErcRule::UnconnectedPin]);
+        assert_eq!(findings[0].pin.as_deref(), Some("1"));
+        assert_eq!(findings[1].pin.as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn test_conflicting_outputs() {
+        let distilled = json!({
+            "components": {
+                "U1": {"pins": [{"number": "3", "name": "OUT", "net": "VMID"}]},
+                "U2": {"pins": [{"number": "5", "name": "VOUT", "net": "VMID"}]}
+            }
+        });
+        let findings = run_erc(&distilled);
+        assert_eq!(rules(&findings), vec![ErcRule::ConflictingOutputs]);
+        assert!(findings[0].message.contains("U1.OUT, U2.VOUT"));
+    }
+
+    #[test]
+    fn test_undriven_power_pin() {
+        let distilled = json!({
+            "components": {
+                "U1": {"pins": [{"number": "1", "name": "VDD", "net": "Net-(U1-VDD)"}]},
+                "C1": {"pins": [{"number": "1", "name": null, "net": "Net-(U1-VDD)"}]},
+                "U2": {"pins": [{"number": "1", "name": "VCC", "net": "Net-(J1-Pad1)"}]},
+                "J1": {"pins": [{"number": "1", "name": "Pin_1", "net": "Net-(J1-Pad1)"}]}
+            }
+        });
+        let findings = run_erc(&distilled);
+        assert_eq!(rules(&findings), vec![ErcRule::UndrivenPowerPin]);
+        assert_eq!(findings[0].reference.as_deref(), Some("U1"));
+    }
+
+    #[test]
+    fn test_duplicate_references() {
+        let distilled = json!({
+            "components": [
+                {"reference": "R1", "pins": []},
+                {"reference": "R1", "pins": []},
+                {"reference": "C?", "pins": []}
+            ]
+        });
+        let findings = run_erc(&distilled);
+        assert_eq!(rules(&findings), vec![ErcRule::DuplicateReference, ErcRule::DuplicateReference]);
+    }
+
+    #[test]
+    fn test_introduced_findings() {
+        let finding = |reference: &str| ErcFinding {
+            rule: ErcRule::UnconnectedPin,
+            reference: Some(reference.to_string()),
+            pin: Some("1".to_string()),
+            net: None,
+            message: String::new(),
+        };
+        let previous = vec![finding("U1")];
+        let current = vec![finding("U1"), finding("U2")];
+        let introduced = introduced_findings(&current, &previous);
+        assert_eq!(introduced.len(), 1);
+        assert_eq!(introduced[0].reference.as_deref(), Some("U2"));
+    }
+}
@@ -0,0 +1,175 @@
+// USAGE:
+// cargo test error -- --nocapture
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Failures loading configuration from the environment
+#[derive(Debug, Error)]
+pub enum EnvError {
+    #[error("environment variable '{name}' not found")]
+    MissingVar {
+        name: String,
+        #[source]
+        source: std::env::VarError,
+    },
+    #[error("required environment variables not set: {}", .0.join(", "))]
+    MissingVars(Vec<String>),
+    #[error("failed to load environment file from {}", path.display())]
+    LoadFile {
+        path: PathBuf,
+        #[source]
+        source: dotenv::Error,
+    },
+    #[error("project directory not found: no backend/ above the executable or {}, and KICAD_PROJECT_DIR isn't set", .0.display())]
+    ProjectRootNotFound(PathBuf),
+    #[error("environment variable '{name}' is {value:?}; expected {expected}")]
+    InvalidVar {
+        name: String,
+        value: String,
+        expected: &'static str,
+    },
+    #[error("failed to set up OTLP span export")]
+    Otlp(#[source] opentelemetry_otlp::ExporterBuildError),
+}
+
+/// Failures talking to the xAI API
+#[derive(Debug, Error)]
+pub enum XaiError {
+    #[error("XAI client is not configured")]
+    Config(#[from] EnvError),
+    #[error("request to the XAI API failed")]
+    Http(#[from] reqwest::Error),
+    #[error("RATE LIMITED: XAI API returned 429. Response: {0}")]
+    RateLimited(String),
+    #[error("API request failed with status {status}: {body}")]
+    Status { status: u16, body: String },
+    #[error("failed to decode XAI API response")]
+    Decode(#[from] serde_json::Error),
+    #[error("operation timed out")]
+    Timeout,
+    #[error("model stopped responding (nothing received for {:?})", .0)]
+    Idle(Duration),
+    #[error("XAI circuit open after repeated failures; retry in {}s", .retry_after.as_secs())]
+    Unavailable { retry_after: Duration },
+    #[error("monthly AI budget for {0} is spent")]
+    BudgetExceeded(String),
+    #[error("request cancelled")]
+    Cancelled,
+}
+
+impl XaiError {
+    /// Whether retrying the same request later could succeed
+    pub fn is_transient(&self) -> bool {
+        match self {
+            XaiError::RateLimited(_)
+            | XaiError::Timeout
+            | XaiError::Idle(_)
+            | XaiError::Unavailable { .. } => true,
+            XaiError::Http(e) => e.is_timeout() || e.is_connect(),
+            XaiError::Status { status, .. } => *status >= 500,
+            XaiError::Config(_)
+            | XaiError::Decode(_)
+            | XaiError::Cancelled
+            | XaiError::BudgetExceeded(_) => false,
+        }
+    }
+}
+
+/// Failures reading a KiCad schematic project or board file
+#[derive(Debug, Error)]
+pub enum SchematicError {
+    #[error("{file}:{line}:{column}: {message}")]
+    Syntax {
+        file: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    #[error("{file} is not a KiCad schematic (found '{found}')")]
+    NotASchematic { file: String, found: String },
+    #[error("{file} is not a KiCad board (found '{found}')")]
+    NotABoard { file: String, found: String },
+    #[error("{file} is not a KiCad symbol library (found '{found}')")]
+    NotASymbolLibrary { file: String, found: String },
+    #[error("root schematic {0} not found")]
+    MissingRoot(String),
+    #[error("sheet {file} includes itself (via {path})")]
+    RecursiveSheet { file: String, path: String },
+    #[error("can't edit {file}: {message}")]
+    Edit { file: String, message: String },
+}
+
+/// Failures loading or rendering a prompt template
+#[derive(Debug, Error)]
+pub enum PromptError {
+    #[error("no prompt template named '{0}'")]
+    UnknownTemplate(String),
+    #[error("prompt template {label} is invalid")]
+    Syntax {
+        label: String,
+        #[source]
+        source: minijinja::Error,
+    },
+    #[error("failed to render prompt template {label}")]
+    Render {
+        label: String,
+        #[source]
+        source: minijinja::Error,
+    },
+    #[error("failed to read prompt templates from {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to load prompt templates from the database")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Any error produced by this crate
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("database query failed")]
+    Database(#[from] sqlx::Error),
+    #[error("failed to apply database migrations")]
+    Migration(#[from] sqlx::migrate::MigrateError),
+    #[error(transparent)]
+    Xai(#[from] XaiError),
+    #[error(transparent)]
+    Env(#[from] EnvError),
+    #[error(transparent)]
+    Schematic(#[from] SchematicError),
+    #[error(transparent)]
+    Prompt(#[from] PromptError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn test_env_error_keeps_source() {
+        let err = EnvError::MissingVar {
+            name: "XAI_API_KEY".to_string(),
+            source: std::env::VarError::NotPresent,
+        };
+        assert_eq!(err.to_string(), "environment variable 'XAI_API_KEY' not found");
+        assert!(err.source().is_some());
+
+        let err = XaiError::from(err);
+        assert!(err.source().unwrap().to_string().contains("XAI_API_KEY"));
+        assert!(!err.is_transient());
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        assert!(XaiError::RateLimited(String::new()).is_transient());
+        assert!(XaiError::Status { status: 503, body: String::new() }.is_transient());
+        assert!(!XaiError::Status { status: 400, body: String::new() }.is_transient());
+        let idle = XaiError::Idle(Duration::from_secs(120));
+        assert!(idle.is_transient());
+        assert_eq!(idle.to_string(), "model stopped responding (nothing received for 120s)");
+    }
+}
@@ -0,0 +1,109 @@
+// USAGE:
+// cargo test --test integration experiments -- --nocapture
+//
+// Results of A/B experiments on summaries. Which variant a generation is
+// assigned to is decided by the caller and kept on the summary handed out
+// (generated_summaries) and on the AI calls made for it (ai_spend); this
+// totals both per variant so they can be compared.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, FromRow, PgPool};
+
+use crate::organizations::OrgFilter;
+
+/// How one variant of an experiment did over a period
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+pub struct VariantResult {
+    pub experiment: String,
+    pub variant: String,
+    /// Template and model of the variant's latest summary; None if it has
+    /// spend but no recorded summaries
+    pub prompt_version: Option<String>,
+    pub model: Option<String>,
+    /// Summaries handed out
+    pub summaries: i64,
+    /// Summaries rated at least once
+    pub rated: i64,
+    pub helpful: i64,
+    pub unhelpful: i64,
+    /// AI calls made for the variant, and what they used and cost
+    pub calls: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost_usd: f64,
+}
+
+impl VariantResult {
+    /// Share of ratings that were thumbs up
+    pub fn helpful_rate(&self) -> f64 {
+        match self.helpful + self.unhelpful {
+            0 => 0.0,
+            total => self.helpful as f64 / total as f64,
+        }
+    }
+
+    /// Average cost of a summary handed out
+    pub fn cost_per_summary(&self) -> f64 {
+        match self.summaries {
+            0 => 0.0,
+            summaries => self.cost_usd / summaries as f64,
+        }
+    }
+}
+
+/// Every experiment variant's summaries, ratings and spend since `since`,
+/// for the repos of the orgs `orgs` lets through, by experiment and variant
+pub async fn experiment_results(
+    pool: &PgPool,
+    since: DateTime<Utc>,
+    orgs: OrgFilter,
+) -> Result<Vec<VariantResult>, Error> {
+    sqlx::query_as::<_, VariantResult>(
+        r#"
+        WITH summaries AS (
+            SELECT s.experiment, s.variant,
+                (ARRAY_AGG(s.prompt_version ORDER BY s.id DESC))[1] AS prompt_version,
+                (ARRAY_AGG(s.model ORDER BY s.id DESC))[1] AS model,
+                COUNT(*) AS summaries,
+                COUNT(*) FILTER (WHERE f.ratings > 0) AS rated,
+                COALESCE(SUM(f.helpful), 0)::BIGINT AS helpful,
+                COALESCE(SUM(f.ratings - f.helpful), 0)::BIGINT AS unhelpful
+            FROM generated_summaries s
+            LEFT JOIN (
+                SELECT summary_id, COUNT(*) AS ratings, COUNT(*) FILTER (WHERE helpful) AS helpful
+                FROM summary_feedback
+                GROUP BY summary_id
+            ) f ON f.summary_id = s.id
+            LEFT JOIN repos r ON r.repo_url = s.repo_url
+            WHERE s.experiment IS NOT NULL AND s.created_at >= $1 AND ($2 OR r.org_id IS NOT DISTINCT FROM $3)
+            GROUP BY s.experiment, s.variant
+        ),
+        spend AS (
+            SELECT experiment, variant,
+                COUNT(*) AS calls,
+                SUM(prompt_tokens)::BIGINT AS prompt_tokens,
+                SUM(completion_tokens)::BIGINT AS completion_tokens,
+                SUM(cost_usd) AS cost_usd
+            FROM ai_spend
+            WHERE experiment IS NOT NULL AND created_at >= $1 AND ($2 OR org_id IS NOT DISTINCT FROM $3)
+            GROUP BY experiment, variant
+        )
+        SELECT experiment, variant, summaries.prompt_version, summaries.model,
+            COALESCE(summaries.summaries, 0) AS summaries,
+            COALESCE(summaries.rated, 0) AS rated,
+            COALESCE(summaries.helpful, 0) AS helpful,
+            COALESCE(summaries.unhelpful, 0) AS unhelpful,
+            COALESCE(spend.calls, 0) AS calls,
+            COALESCE(spend.prompt_tokens, 0) AS prompt_tokens,
+            COALESCE(spend.completion_tokens, 0) AS completion_tokens,
+            COALESCE(spend.cost_usd, 0) AS cost_usd
+        FROM summaries FULL JOIN spend USING (experiment, variant)
+        ORDER BY experiment, variant
+        "#,
+    )
+    .bind(since)
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .fetch_all(pool)
+    .await
+}
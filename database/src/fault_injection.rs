@@ -0,0 +1,161 @@
+// USAGE:
+// cargo test --features fault-injection fault_injection -- --nocapture
+//
+// Test-only fault injection for upstream dependencies. Compiled only with the
+// `fault-injection` feature; each fault fires with the probability (0.0-1.0)
+// given in its environment variable, read on every call so a running server
+// can be switched over, or in a FaultConfig:
+//
+//   FAULT_XAI_RATE_LIMIT     XAI requests fail as if the API returned 429
+//   FAULT_XAI_TIMEOUT        XAI requests stall for FAULT_TIMEOUT_MS, then time out
+//   FAULT_XAI_MALFORMED_SSE  a malformed `data:` line is spliced into XAI streams
+//   FAULT_GIT_PARTIAL_CLONE  git clones abort, leaving a half-written cache directory
+use crate::error::XaiError;
+use std::time::Duration;
+use tracing::warn;
+
+/// A simulated upstream failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    XaiRateLimit,
+    XaiTimeout,
+    XaiMalformedSse,
+    GitPartialClone,
+}
+
+impl Fault {
+    fn env_var(&self) -> &'static str {
+        match self {
+            Fault::XaiRateLimit => "FAULT_XAI_RATE_LIMIT",
+            Fault::XaiTimeout => "FAULT_XAI_TIMEOUT",
+            Fault::XaiMalformedSse => "FAULT_XAI_MALFORMED_SSE",
+            Fault::GitPartialClone => "FAULT_GIT_PARTIAL_CLONE",
+        }
+    }
+}
+
+/// SSE line injected for `Fault::XaiMalformedSse` (truncated JSON)
+pub const MALFORMED_SSE_LINE: &str = "data: {\"choices\": [{\"delta\": {\"content\": \"tru";
+
+/// Fault probabilities and the simulated timeout, as the environment sets
+/// them; tests build one directly rather than changing the environment
+#[derive(Debug, Clone, Default)]
+pub struct FaultConfig {
+    pub xai_rate_limit: f64,
+    pub xai_timeout: f64,
+    pub xai_malformed_sse: f64,
+    pub git_partial_clone: f64,
+    /// How long a simulated XAI timeout stalls; 100 ms when unset
+    pub timeout_ms: Option<u64>,
+}
+
+impl FaultConfig {
+    /// Read the `FAULT_*` variables; unset or unparsable ones never fire
+    pub fn from_env() -> Self {
+        let probability = |fault: Fault| {
+            std::env::var(fault.env_var())
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .unwrap_or(0.0)
+        };
+        Self {
+            xai_rate_limit: probability(Fault::XaiRateLimit),
+            xai_timeout: probability(Fault::XaiTimeout),
+            xai_malformed_sse: probability(Fault::XaiMalformedSse),
+            git_partial_clone: probability(Fault::GitPartialClone),
+            timeout_ms: std::env::var("FAULT_TIMEOUT_MS").ok().and_then(|v| v.parse().ok()),
+        }
+    }
+
+    fn probability(&self, fault: Fault) -> f64 {
+        match fault {
+            Fault::XaiRateLimit => self.xai_rate_limit,
+            Fault::XaiTimeout => self.xai_timeout,
+            Fault::XaiMalformedSse => self.xai_malformed_sse,
+            Fault::GitPartialClone => self.git_partial_clone,
+        }
+    }
+
+    /// Whether `fault` should fire on this call
+    pub fn should_inject(&self, fault: Fault) -> bool {
+        let probability = self.probability(fault);
+        let fire = probability > 0.0 && fastrand::f64() < probability;
+        if fire {
+            warn!("Fault injection: simulating {:?}", fault);
+        }
+        fire
+    }
+
+    /// Simulated failures at the start of an XAI request: a 429 or a timeout
+    pub async fn xai_request_fault(&self) -> Result<(), XaiError> {
+        if self.should_inject(Fault::XaiRateLimit) {
+            return Err(XaiError::RateLimited(
+                "simulated by fault injection".to_string(),
+            ));
+        }
+
+        if self.should_inject(Fault::XaiTimeout) {
+            tokio::time::sleep(Duration::from_millis(self.timeout_ms.unwrap_or(100))).await;
+            return Err(XaiError::Timeout);
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `fault` should fire on this call, going by the environment
+pub fn should_inject(fault: Fault) -> bool {
+    FaultConfig::from_env().should_inject(fault)
+}
+
+/// [`FaultConfig::xai_request_fault`] with the environment's faults
+pub async fn xai_request_fault() -> Result<(), XaiError> {
+    FaultConfig::from_env().xai_request_fault().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Faults are passed in rather than set in the environment, which the
+    // other tests read from their own threads
+
+    #[test]
+    fn test_disabled_fault_never_fires() {
+        let faults = FaultConfig::default();
+        assert!((0..100).all(|_| !faults.should_inject(Fault::XaiMalformedSse)));
+    }
+
+    #[test]
+    fn test_certain_fault_always_fires() {
+        let faults = FaultConfig {
+            git_partial_clone: 1.0,
+            ..Default::default()
+        };
+        assert!((0..100).all(|_| faults.should_inject(Fault::GitPartialClone)));
+        assert!((0..100).all(|_| !faults.should_inject(Fault::XaiRateLimit)));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_fault() {
+        let faults = FaultConfig {
+            xai_rate_limit: 1.0,
+            ..Default::default()
+        };
+        let err = faults.xai_request_fault().await.unwrap_err();
+        assert!(matches!(err, XaiError::RateLimited(_)));
+        assert!(err.to_string().contains("429"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_fault_stalls_for_the_configured_time() {
+        let faults = FaultConfig {
+            xai_timeout: 1.0,
+            timeout_ms: Some(20),
+            ..Default::default()
+        };
+        let started = std::time::Instant::now();
+        assert!(matches!(faults.xai_request_fault().await, Err(XaiError::Timeout)));
+        assert!(started.elapsed() >= Duration::from_millis(20));
+    }
+}
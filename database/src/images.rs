@@ -0,0 +1,173 @@
+// USAGE:
+// cargo test --test integration schematic_images -- --nocapture
+//
+// Rendered schematic images, stored once per content. Each image is keyed by
+// the hex sha256 of its bytes and schematics reference it by that digest, so
+// a render that doesn't change between commits is stored (and uploaded) once:
+// a client that already knows the digest can attach the stored copy to a new
+// commit without sending the bytes again. The bytes are kept in the blob
+// store (see blob_store.rs) under `images/<digest>`.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Error, PgConnection, PgPool};
+use tracing::warn;
+
+use crate::blob_store::{blob_store, image_key, thumbnails_prefix};
+
+/// An image as stored, without its bytes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct StoredImage {
+    /// Hex sha256 of the bytes
+    pub digest: String,
+    pub content_type: String,
+    /// Size in bytes
+    pub size: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The digest images are stored under: hex sha256 of `bytes`
+pub fn image_digest(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// MIME type of an image from its leading bytes; application/octet-stream
+/// for anything that isn't a PNG, JPEG or SVG
+pub fn image_content_type(bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return "image/png";
+    }
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return "image/jpeg";
+    }
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]);
+    let head = head.trim_start();
+    if head.starts_with("<svg") || (head.starts_with("<?xml") && head.contains("<svg")) {
+        return "image/svg+xml";
+    }
+    "application/octet-stream"
+}
+
+/// Store `bytes` unless an identical image is already stored; returns the
+/// stored image and whether it already was
+pub async fn store_image_in(conn: &mut PgConnection, bytes: &[u8]) -> Result<(StoredImage, bool), Error> {
+    let digest = image_digest(bytes);
+    // Locks the row against purge_orphaned_images until the caller commits
+    if let Some(image) = find_image_in(conn, &digest).await? {
+        return Ok((image, true));
+    }
+
+    let content_type = image_content_type(bytes);
+    blob_store().put(conn, &image_key(&digest), content_type, bytes).await?;
+    let inserted = sqlx::query_as::<_, StoredImage>(
+        r#"
+        INSERT INTO images (digest, content_type, size)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (digest) DO NOTHING
+        RETURNING digest, content_type, size, created_at
+        "#,
+    )
+    .bind(&digest)
+    .bind(content_type)
+    .bind(bytes.len() as i64)
+    .fetch_optional(&mut *conn)
+    .await?;
+    match inserted {
+        Some(image) => Ok((image, false)),
+        // Stored concurrently since the lookup above
+        None => Ok((find_image_in(conn, &digest).await?.ok_or(Error::RowNotFound)?, true)),
+    }
+}
+
+/// The stored image with `digest`, if there is one
+pub async fn find_image(pool: &PgPool, digest: &str) -> Result<Option<StoredImage>, Error> {
+    let mut conn = pool.acquire().await?;
+    find_image_in(&mut conn, digest).await
+}
+
+/// [`find_image`], locking the row so it isn't purged before the caller's
+/// transaction commits
+pub(crate) async fn find_image_in(conn: &mut PgConnection, digest: &str) -> Result<Option<StoredImage>, Error> {
+    sqlx::query_as::<_, StoredImage>(
+        r#"
+        SELECT digest, content_type, size, created_at
+        FROM images
+        WHERE digest = $1
+        FOR KEY SHARE
+        "#,
+    )
+    .bind(digest)
+    .fetch_optional(conn)
+    .await
+}
+
+/// The bytes of the stored image with `digest`
+pub async fn load_image(pool: &PgPool, digest: &str) -> Result<Option<Vec<u8>>, Error> {
+    let mut conn = pool.acquire().await?;
+    load_image_in(&mut conn, digest).await
+}
+
+pub(crate) async fn load_image_in(conn: &mut PgConnection, digest: &str) -> Result<Option<Vec<u8>>, Error> {
+    blob_store().get(conn, &image_key(digest)).await
+}
+
+/// Delete images no schematic references any more (replaced, or their
+/// commits purged), with their bytes and thumbnails; returns how many
+///
+/// A blob that can't be deleted is logged and left behind rather than
+/// keeping its image's row.
+pub async fn purge_orphaned_images(pool: &PgPool) -> Result<u64, Error> {
+    let mut tx = pool.begin().await?;
+    // The rows stay locked until commit, so nobody reuses an image whose
+    // bytes are going
+    let digests: Vec<String> = sqlx::query_scalar(
+        r#"
+        DELETE FROM images
+        WHERE digest IN (
+            SELECT i.digest FROM images i
+            WHERE NOT EXISTS (SELECT 1 FROM schematics s WHERE s.image_digest = i.digest)
+            -- Skips images a transaction is about to reference
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING digest
+        "#,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let store = blob_store();
+    for digest in &digests {
+        for prefix in [image_key(digest), thumbnails_prefix(digest)] {
+            if let Err(e) = store.delete_prefix(&mut tx, &prefix).await {
+                warn!("Failed to delete blobs under {} from {}: {}", prefix, store.name(), e);
+            }
+        }
+    }
+    tx.commit().await?;
+    Ok(digests.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_digest() {
+        assert_eq!(
+            image_digest(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(image_digest(&[1]), image_digest(&[1]));
+        assert_ne!(image_digest(&[1]), image_digest(&[2]));
+    }
+
+    #[test]
+    fn test_image_content_type() {
+        assert_eq!(image_content_type(b"\x89PNG\r\n\x1a\nrest"), "image/png");
+        assert_eq!(image_content_type(&[0xFF, 0xD8, 0xFF, 0xE0]), "image/jpeg");
+        assert_eq!(image_content_type(b"  <svg xmlns=\"http://www.w3.org/2000/svg\"/>"), "image/svg+xml");
+        assert_eq!(image_content_type(b"<?xml version=\"1.0\"?>\n<svg/>"), "image/svg+xml");
+        assert_eq!(image_content_type(b"<?xml version=\"1.0\"?><html/>"), "application/octet-stream");
+        assert_eq!(image_content_type(b""), "application/octet-stream");
+    }
+}
@@ -0,0 +1,148 @@
+// USAGE:
+// cargo test init -- --nocapture
+//
+// One-time process setup shared by the backend, the CLI and tests: layered
+// .env files, the tracing subscriber (see logging) and a check of required
+// variables.
+use crate::error::EnvError;
+use crate::utilities::get_project_path::get_project_path;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Env files loaded by the first `init()`, in load order
+static LOADED_ENV_FILES: OnceLock<Vec<PathBuf>> = OnceLock::new();
+
+/// Candidate env files, most specific first
+///
+/// Variables already set are never overridden, so the process environment
+/// wins over `.env.local`, which wins over `.env` in the working directory,
+/// which wins over the project's `backend/.env`. Files that don't exist are
+/// skipped, as is the project's when there is no project directory (see
+/// `get_project_path`), so deployments can set real environment variables
+/// instead.
+fn env_layers() -> Result<Vec<PathBuf>, EnvError> {
+    let mut layers = vec![PathBuf::from(".env.local"), PathBuf::from(".env")];
+    match get_project_path() {
+        Ok(project) => layers.push(project.join("backend").join(".env")),
+        Err(EnvError::ProjectRootNotFound(_)) => {}
+        // KICAD_PROJECT_DIR is set, but wrong
+        Err(e) => return Err(e),
+    }
+
+    let mut seen = Vec::new();
+    Ok(layers
+        .into_iter()
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+            let first = !seen.contains(&canonical);
+            seen.push(canonical);
+            first
+        })
+        .collect())
+}
+
+fn load_env_files() -> Result<Vec<PathBuf>, EnvError> {
+    let mut loaded = Vec::new();
+    for path in env_layers()? {
+        dotenv::from_path(&path).map_err(|source| EnvError::LoadFile {
+            path: path.clone(),
+            source,
+        })?;
+        loaded.push(path);
+    }
+    Ok(loaded)
+}
+
+/// Names in `required` that are unset or empty
+fn missing_vars(required: &[&str]) -> Vec<String> {
+    required
+        .iter()
+        .filter(|name| std::env::var(name).map_or(true, |v| v.trim().is_empty()))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// Gets an environment variable by name.
+/// Returns an error if the variable is not found.
+pub fn get_environment_variable(environment_variable_name: &str) -> Result<String, EnvError> {
+    std::env::var(environment_variable_name).map_err(|source| EnvError::MissingVar {
+        name: environment_variable_name.to_string(),
+        source,
+    })
+}
+
+/// Load env files, set up tracing and check that `required` variables are set
+///
+/// Call this once at startup instead of loading `.env` where a variable is
+/// needed. The env files and subscriber are set up exactly once per process,
+/// by whichever caller gets there first, so concurrent tests can all call it.
+/// A malformed env file is reported to that first caller only; `required` is
+/// checked on every call.
+pub fn init(required: &[&str]) -> Result<(), EnvError> {
+    let mut load_error = None;
+    LOADED_ENV_FILES.get_or_init(|| {
+        let loaded = load_env_files().unwrap_or_else(|e| {
+            load_error = Some(e);
+            Vec::new()
+        });
+        // After the env files, which may set LOG_FORMAT and OTEL_*
+        if let Err(e) = crate::logging::install() {
+            load_error.get_or_insert(e);
+        }
+        for path in &loaded {
+            tracing::debug!("Loaded environment from {}", path.display());
+        }
+        loaded
+    });
+    if let Some(e) = load_error {
+        return Err(e);
+    }
+
+    let missing = missing_vars(required);
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(EnvError::MissingVars(missing))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_is_idempotent() {
+        let handles: Vec<_> = (0..8).map(|_| std::thread::spawn(|| init(&[]))).collect();
+        for handle in handles {
+            handle.join().unwrap().expect("init should succeed");
+        }
+        assert!(LOADED_ENV_FILES.get().is_some());
+    }
+
+    #[test]
+    fn test_init_reports_missing_vars() {
+        std::env::set_var("INIT_TEST_PRESENT", "1");
+        std::env::set_var("INIT_TEST_BLANK", " ");
+
+        init(&["INIT_TEST_PRESENT"]).expect("present variable should pass");
+        match init(&["INIT_TEST_PRESENT", "INIT_TEST_BLANK", "INIT_TEST_ABSENT"]) {
+            Err(EnvError::MissingVars(missing)) => {
+                assert_eq!(missing, vec!["INIT_TEST_BLANK", "INIT_TEST_ABSENT"])
+            }
+            other => panic!("expected MissingVars, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_get_environment_variable() {
+        std::env::set_var("INIT_TEST_VAR", "test_value_123");
+        assert_eq!(get_environment_variable("INIT_TEST_VAR").unwrap(), "test_value_123");
+        std::env::remove_var("INIT_TEST_VAR");
+
+        match get_environment_variable("INIT_TEST_NON_EXISTENT_VAR") {
+            Err(EnvError::MissingVar { name, .. }) => assert_eq!(name, "INIT_TEST_NON_EXISTENT_VAR"),
+            other => panic!("expected MissingVar, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+// USAGE:
+// cargo test logging -- --nocapture
+//
+// Where tracing output goes. Logs are human-readable lines unless
+// LOG_FORMAT=json, which writes one JSON object per event, with the fields
+// of the spans it happened in, for log shippers. With
+// OTEL_EXPORTER_OTLP_ENDPOINT (or OTEL_EXPORTER_OTLP_TRACES_ENDPOINT) set,
+// spans are also exported over OTLP/HTTP to a collector such as Jaeger or
+// Tempo: HTTP requests, git operations and AI calls each get one, with the
+// SQL statements run under them as events, so a webhook can be followed
+// through the whole pipeline. OTEL_SERVICE_NAME names the service, the
+// executable's name by default. RUST_LOG filters both.
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use std::sync::OnceLock;
+use tracing::Subscriber;
+use tracing_subscriber::{
+    layer::SubscriberExt, registry::LookupSpan, util::SubscriberInitExt, EnvFilter, Layer,
+};
+
+use crate::error::EnvError;
+
+/// Exports spans while OTLP is configured; kept to flush them at exit
+static TRACER_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// How log lines are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    /// The format LOG_FORMAT asks for, text if it's unset
+    pub fn from_env() -> Result<Self, EnvError> {
+        Self::parse(std::env::var("LOG_FORMAT").ok().as_deref())
+    }
+
+    fn parse(value: Option<&str>) -> Result<Self, EnvError> {
+        match value.map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+            None | Some("") | Some("text") => Ok(Self::Text),
+            Some("json") => Ok(Self::Json),
+            Some(other) => Err(EnvError::InvalidVar {
+                name: "LOG_FORMAT".to_string(),
+                value: other.to_string(),
+                expected: "text or json",
+            }),
+        }
+    }
+}
+
+/// RUST_LOG, "info" if unset or invalid
+fn env_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))
+}
+
+fn env_set(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+/// OTEL_SERVICE_NAME, else the executable's name
+fn service_name() -> String {
+    env_set("OTEL_SERVICE_NAME")
+        .or_else(|| {
+            let exe = std::env::current_exe().ok()?;
+            Some(exe.file_stem()?.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "kicad".to_string())
+}
+
+/// Spans batched to `exporter`, from a service called `service_name`
+fn tracer_provider(exporter: SpanExporter, service_name: &str) -> SdkTracerProvider {
+    SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name.to_string()).build())
+        .build()
+}
+
+/// The layer sending spans to `provider`, with the SQL statements sqlx logs
+/// at debug level as events on them
+fn otlp_layer<S>(provider: &SdkTracerProvider) -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let filter = env_filter().add_directive("sqlx::query=debug".parse().expect("valid directive"));
+    tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer("kicad"))
+        .with_filter(filter)
+}
+
+/// Install the global subscriber: logs in the LOG_FORMAT format, plus OTLP
+/// span export when an endpoint is configured
+///
+/// Does nothing if another subscriber is already installed, e.g. by a test
+/// harness.
+pub fn install() -> Result<(), EnvError> {
+    let logs = match LogFormat::from_env()? {
+        LogFormat::Text => tracing_subscriber::fmt::layer().with_filter(env_filter()).boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_filter(env_filter())
+            .boxed(),
+    };
+    let otlp = if env_set("OTEL_EXPORTER_OTLP_ENDPOINT").is_some()
+        || env_set("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_some()
+    {
+        // The exporter reads the endpoint, headers and timeout from the
+        // standard OTEL_EXPORTER_OTLP_* variables
+        let exporter = SpanExporter::builder().with_http().build().map_err(EnvError::Otlp)?;
+        let provider = TRACER_PROVIDER.get_or_init(|| tracer_provider(exporter, &service_name()));
+        Some(otlp_layer(provider))
+    } else {
+        None
+    };
+    let _ = tracing_subscriber::registry().with(logs).with(otlp).try_init();
+    Ok(())
+}
+
+/// Export the spans still buffered; call before the process exits
+pub fn shutdown() {
+    let Some(provider) = TRACER_PROVIDER.get().cloned() else {
+        return;
+    };
+    // The exporter's blocking HTTP client mustn't run on an async runtime's thread
+    let result = std::thread::spawn(move || provider.shutdown()).join();
+    if let Ok(Err(e)) = result {
+        eprintln!("Failed to export the last spans: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_otlp::WithExportConfig;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_log_format_parse() {
+        assert_eq!(LogFormat::parse(None).unwrap(), LogFormat::Text);
+        assert_eq!(LogFormat::parse(Some(" JSON ")).unwrap(), LogFormat::Json);
+        assert_eq!(LogFormat::parse(Some("text")).unwrap(), LogFormat::Text);
+        let err = LogFormat::parse(Some("xml")).unwrap_err();
+        assert_eq!(err.to_string(), "environment variable 'LOG_FORMAT' is \"xml\"; expected text or json");
+    }
+
+    /// Accept one HTTP request and send back its first line and body
+    fn collector() -> (String, mpsc::Receiver<(String, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let mut stream = stream;
+            stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").unwrap();
+            tx.send((request_line.trim().to_string(), body)).unwrap();
+        });
+        (endpoint, rx)
+    }
+
+    #[test]
+    fn test_spans_are_exported_over_otlp() {
+        let (endpoint, requests) = collector();
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()
+            .unwrap();
+        let provider = tracer_provider(exporter, "logging-test");
+        let subscriber = tracing_subscriber::registry().with(otlp_layer(&provider));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", uri = "/api/hook/github").in_scope(|| {
+                tracing::info_span!("git", operation = "fetch").in_scope(|| tracing::info!("fetched"));
+            });
+        });
+        provider.force_flush().unwrap();
+
+        let (request_line, body) = requests.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(request_line, "POST /v1/traces HTTP/1.1");
+        // Protobuf keeps strings as they are
+        let body = String::from_utf8_lossy(&body);
+        for expected in ["logging-test", "request", "/api/hook/github", "git", "fetch"] {
+            assert!(body.contains(expected), "{} not in {:?}", expected, body);
+        }
+    }
+}
@@ -1,11 +1,14 @@
-use kicad_db::{create_pool, retrieve_schematic, find_schematics_by_part};
+use kicad_db::{connect_pool, connect_store, find_schematics_by_part, init, is_sqlite_url, PoolSettings, DB_URL};
 use uuid::Uuid;
 
-use tokio;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let pool = create_pool().await?;
+    init(&[])?;
+    // A sqlite: URL stores schematics in a local file, no server needed
+    let url = std::env::var("DATABASE_URL").unwrap_or_else(|_| DB_URL.to_string());
+    let store = connect_store(&url, &PoolSettings::default()).await?;
+    store.migrate().await?;
 
     // Example store (commented; run with DB up)
     /*
@@ -14,33 +17,34 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Uuid::parse_str("00000000-0000-0000-0000-00005e6bfc95")?,
         (Some("Main MCU".to_string()), json!({"reference": "U6", "value": "ESP32"}))
     );
-    let id = store_schematic(
-        &pool,
-        "https://github.com/evanhekman/hackathon.git",
-        "main",
-        None, // commit_date
-        Some("Test git message"), // git_message
-        None,  // image bytes
-        Some("Initial commit"), // change_summary
-        Some("Smartwatch project"), // project_overview
-        Some("Brief blurb"), // blurb
-        Some("Detailed description of changes"), // description
+    let id = store.store_schematic(&NewSchematic {
+        repo_url: "https://github.com/evanhekman/hackathon.git".to_string(),
+        commit_hash: "main".to_string(),
+        git_message: Some("Test git message".to_string()),
+        change_summary: Some("Initial commit".to_string()),
+        project_overview: Some("Smartwatch project".to_string()),
+        blurb: Some("Brief blurb".to_string()),
+        description: Some("Detailed description of changes".to_string()),
         parts,
-    ).await?;
+        ..Default::default()
+    }).await?;
     println!("Stored with id: {}", id);
     */
 
     // Example retrieve
-    if let Some(sch) = retrieve_schematic(&pool, "https://github.com/evanhekman/hackathon.git", "main").await? {
+    if let Some(sch) = store.retrieve_schematic("https://github.com/evanhekman/hackathon.git", "main").await? {
         println!("Retrieved: {:?} parts: {}", sch.commit_hash, sch.parts.len());
     } else {
         println!("No schematic found");
     }
 
-    // Example query by part
-    let part_uuid = Uuid::parse_str("00000000-0000-0000-0000-00005e6bfc95")?;
-    let commits = find_schematics_by_part(&pool, part_uuid).await?;
-    println!("Schematics with part: {:?}", commits);
+    // Example query by part; Postgres only
+    if !is_sqlite_url(&url) {
+        let pool = connect_pool(&url).await?;
+        let part_uuid = Uuid::parse_str("00000000-0000-0000-0000-00005e6bfc95")?;
+        let commits = find_schematics_by_part(&pool, part_uuid).await?;
+        println!("Schematics with part: {:?}", commits);
+    }
 
     Ok(())
 }
\ No newline at end of file
@@ -1,6 +1,9 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use serde::{Deserialize, Serialize};
 use serde_json;
 
+use crate::xai_client::ResponsesRequest;
+
 /// Message role types for XAI API
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -10,40 +13,174 @@ pub enum MessageRole {
     Assistant,
 }
 
+/// Largest image accepted for a vision request, matching the XAI API limit
+pub const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+/// Detail level the model should look at an image with
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageDetail {
+    Auto,
+    Low,
+    High,
+}
+
+/// An image for a vision model, sent inline as a base64 data URL
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImageUrl {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<ImageDetail>,
+}
+
+impl ImageUrl {
+    /// Wrap PNG or JPEG bytes as a data URL; other formats (including SVG)
+    /// and images over [`MAX_IMAGE_BYTES`] are not accepted by the API
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() > MAX_IMAGE_BYTES {
+            return None;
+        }
+        let mime = image_mime(bytes)?;
+        Some(Self {
+            url: format!("data:{};base64,{}", mime, BASE64.encode(bytes)),
+            detail: None,
+        })
+    }
+
+    /// Decode base64 image data, either bare or as a `data:image/...;base64,` URL
+    pub fn from_base64(data: &str) -> Option<Self> {
+        let encoded = match data.trim().strip_prefix("data:") {
+            Some(url) => url.split_once(";base64,")?.1,
+            None => data.trim(),
+        };
+        let bytes = BASE64.decode(encoded).ok()?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Set the detail level
+    pub fn with_detail(mut self, detail: ImageDetail) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+}
+
+/// MIME type of an image the API accepts, from its magic bytes
+fn image_mime(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else {
+        None
+    }
+}
+
+/// One part of a multi-part message
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+/// Message content: plain text, or text and images for vision models
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+impl MessageContent {
+    /// All of the text, with the text parts joined by blank lines
+    pub fn text(&self) -> String {
+        match self {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    ContentPart::ImageUrl { .. } => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        }
+    }
+
+    /// Append text to the end, as a new part for multi-part content
+    pub fn push_text(&mut self, text: &str) {
+        match self {
+            MessageContent::Text(existing) => existing.push_str(text),
+            MessageContent::Parts(parts) => parts.push(ContentPart::Text {
+                text: text.to_string(),
+            }),
+        }
+    }
+
+    /// Number of images attached
+    pub fn image_count(&self) -> usize {
+        match self {
+            MessageContent::Text(_) => 0,
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter(|part| matches!(part, ContentPart::ImageUrl { .. }))
+                .count(),
+        }
+    }
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        MessageContent::Text(text)
+    }
+}
+
+impl PartialEq<&str> for MessageContent {
+    fn eq(&self, other: &&str) -> bool {
+        matches!(self, MessageContent::Text(text) if text == other)
+    }
+}
+
 /// A message in the chat completion request
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Message {
     pub role: MessageRole,
-    pub content: String,
+    pub content: MessageContent,
 }
 
 impl Message {
     /// Create a new message
     pub fn new(role: MessageRole, content: String) -> Self {
-        Self { role, content }
+        Self {
+            role,
+            content: content.into(),
+        }
     }
 
     /// Create a system message
     pub fn system(content: String) -> Self {
-        Self {
-            role: MessageRole::System,
-            content,
-        }
+        Self::new(MessageRole::System, content)
     }
 
     /// Create a user message
     pub fn user(content: String) -> Self {
-        Self {
-            role: MessageRole::User,
-            content,
-        }
+        Self::new(MessageRole::User, content)
     }
 
     /// Create an assistant message
     pub fn assistant(content: String) -> Self {
+        Self::new(MessageRole::Assistant, content)
+    }
+
+    /// Create a user message showing the model an image (e.g. a schematic
+    /// snapshot) ahead of the text; needs a vision-capable model
+    pub fn user_with_image(content: String, image: ImageUrl) -> Self {
         Self {
-            role: MessageRole::Assistant,
-            content,
+            role: MessageRole::User,
+            content: MessageContent::Parts(vec![
+                ContentPart::ImageUrl { image_url: image },
+                ContentPart::Text { text: content },
+            ]),
         }
     }
 
@@ -58,18 +195,51 @@ impl Message {
     }
 }
 
-/// Reasoning effort level for thinking mode
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// How long a reasoning model thinks before answering (`reasoning_effort`)
+///
+/// Only reasoning models accept it; the API rejects it for the others.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum ReasoningEffort {
     Low,
     High,
 }
 
-/// Reasoning configuration for thinking mode
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct ReasoningConfig {
-    pub effort: ReasoningEffort,
+/// Options for streaming chat completions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamOptions {
+    /// Send a final chunk with the token usage of the whole completion
+    pub include_usage: bool,
+}
+
+/// Sampling settings for a kind of request, each left to the API when unset
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sampling {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub stop: Vec<String>,
+}
+
+impl Sampling {
+    /// Set what `request` leaves unset, so a caller's own choice (a persona's
+    /// temperature, a detail level's token budget) wins
+    pub fn fill(&self, request: &mut ChatCompletionRequest) {
+        request.temperature = request.temperature.or(self.temperature);
+        request.top_p = request.top_p.or(self.top_p);
+        request.max_tokens = request.max_tokens.or(self.max_tokens);
+        if request.stop.is_none() && !self.stop.is_empty() {
+            request.stop = Some(self.stop.clone());
+        }
+    }
+
+    /// [`fill`](Self::fill) for a responses API request, which takes no stop sequences
+    pub fn fill_responses(&self, request: &mut ResponsesRequest) {
+        request.temperature = request.temperature.or(self.temperature);
+        request.top_p = request.top_p.or(self.top_p);
+        request.max_output_tokens = request.max_output_tokens.or(self.max_tokens);
+    }
 }
 
 /// Chat completion request for XAI API
@@ -80,7 +250,28 @@ pub struct ChatCompletionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub reasoning: Option<ReasoningConfig>,
+    pub stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Nucleus sampling: only the most likely tokens making up this much of
+    /// the probability mass are considered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Sequences that end the answer when the model generates them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    /// Answers to generate, each a choice of the response; streamed, the
+    /// ones after the first arrive as `StreamEvent::ChoiceDelta`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+    /// Generate in the background and answer with a request id instead (see
+    /// `XaiClient::submit_deferred`, which sets it)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deferred: Option<bool>,
 }
 
 impl ChatCompletionRequest {
@@ -90,7 +281,14 @@ impl ChatCompletionRequest {
             messages,
             model,
             stream: None,
-            reasoning: None,
+            stream_options: None,
+            reasoning_effort: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            stop: None,
+            n: None,
+            deferred: None,
         }
     }
 
@@ -100,7 +298,14 @@ impl ChatCompletionRequest {
             messages,
             model,
             stream: Some(stream),
-            reasoning: None,
+            stream_options: None,
+            reasoning_effort: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            stop: None,
+            n: None,
+            deferred: None,
         }
     }
 
@@ -110,10 +315,47 @@ impl ChatCompletionRequest {
             messages,
             model,
             stream: Some(stream),
-            reasoning: Some(ReasoningConfig { effort }),
+            stream_options: None,
+            reasoning_effort: Some(effort),
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            stop: None,
+            n: None,
+            deferred: None,
         }
     }
 
+    /// Sample with `temperature`; lower is more deterministic
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Sample from the smallest set of tokens whose probabilities add up to `top_p`
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// Answer with at most `max_tokens` tokens
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// End the answer at any of `stop`
+    pub fn with_stop(mut self, stop: Vec<String>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Generate `n` alternative answers
+    pub fn with_choices(mut self, n: u32) -> Self {
+        self.n = Some(n);
+        self
+    }
+
     /// Convert to JSON string (for use in curl -d flag)
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string(self)
@@ -213,4 +455,80 @@ mod tests {
         let value: serde_json::Value = serde_json::from_str(&json).expect("Should parse JSON");
         assert_eq!(value["stream"], serde_json::json!(false));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_chat_completion_request_with_reasoning_to_json() {
+        let messages = vec![Message::user("Why is R1 10k?".to_string())];
+
+        let request = ChatCompletionRequest::with_reasoning(messages, "grok-3-mini".to_string(), true, ReasoningEffort::High);
+        let value = request.to_dict().expect("Should serialize to JSON");
+        assert_eq!(value["reasoning_effort"], "high");
+        assert!(value.get("reasoning").is_none());
+
+        // Left out entirely for models that don't reason
+        let request = ChatCompletionRequest::new(Vec::new(), "grok-4".to_string());
+        assert!(request.to_dict().unwrap().get("reasoning_effort").is_none());
+    }
+
+    #[test]
+    fn test_sampling_fills_what_the_request_leaves_unset() {
+        let sampling = Sampling {
+            temperature: Some(0.2),
+            top_p: Some(0.9),
+            max_tokens: Some(500),
+            stop: vec!["\n\n".to_string()],
+        };
+        let mut request = ChatCompletionRequest::new(Vec::new(), "grok-4".to_string()).with_max_tokens(100);
+        sampling.fill(&mut request);
+        let value = request.to_dict().unwrap();
+        assert_eq!(value["temperature"], serde_json::json!(0.2f32));
+        assert_eq!(value["top_p"], serde_json::json!(0.9f32));
+        assert_eq!(value["max_tokens"], 100);
+        assert_eq!(value["stop"], serde_json::json!(["\n\n"]));
+
+        // Nothing configured, nothing sent
+        let mut request = ChatCompletionRequest::new(Vec::new(), "grok-4".to_string());
+        Sampling::default().fill(&mut request);
+        let value = request.to_dict().unwrap();
+        assert!(value.get("top_p").is_none() && value.get("stop").is_none());
+    }
+
+    #[test]
+    fn test_user_message_with_image_to_json() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let image = ImageUrl::from_bytes(png).expect("PNG should be accepted").with_detail(ImageDetail::High);
+        let message = Message::user_with_image("What does U1 do?".to_string(), image);
+
+        let value = message.to_dict().expect("Should serialize to JSON");
+        println!("Vision message JSON: {}", value);
+
+        assert_eq!(value["role"], "user");
+        assert_eq!(value["content"][0]["type"], "image_url");
+        assert!(value["content"][0]["image_url"]["url"]
+            .as_str()
+            .unwrap()
+            .starts_with("data:image/png;base64,iVBORw0KGgo"));
+        assert_eq!(value["content"][0]["image_url"]["detail"], "high");
+        assert_eq!(value["content"][1], serde_json::json!({ "type": "text", "text": "What does U1 do?" }));
+
+        let deserialized: Message = serde_json::from_value(value).expect("Should deserialize");
+        assert_eq!(deserialized.content.text(), "What does U1 do?");
+        assert_eq!(deserialized.content.image_count(), 1);
+    }
+
+    #[test]
+    fn test_image_url_from_base64() {
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10];
+        let encoded = BASE64.encode(jpeg);
+
+        let bare = ImageUrl::from_base64(&encoded).expect("bare base64 should be accepted");
+        assert_eq!(bare.url, format!("data:image/jpeg;base64,{}", encoded));
+        let data_url = ImageUrl::from_base64(&format!("data:image/jpeg;base64,{}", encoded));
+        assert_eq!(data_url, Some(bare));
+
+        // SVG and undecodable input are rejected rather than sent to the API
+        let svg = BASE64.encode("<svg xmlns=\"http://www.w3.org/2000/svg\"/>");
+        assert_eq!(ImageUrl::from_base64(&svg), None);
+        assert_eq!(ImageUrl::from_base64("not base64!"), None);
+    }
+}
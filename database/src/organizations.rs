@@ -0,0 +1,227 @@
+// USAGE:
+// cargo test --test integration organizations -- --nocapture
+//
+// Organizations (tenants) and their members. An org owns the repos registered
+// to it, its API keys and the AI spend charged to it; queries that span repos
+// take an `OrgFilter` so one org never sees another's data.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Error, PgPool, Row};
+
+use crate::api_keys::ApiScope;
+
+/// Which orgs' data a query may return
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrgFilter {
+    /// Every org's and the shared data, for instance-wide callers
+    Any,
+    /// One org's data, or with `None` only the data no org owns
+    Only(Option<i32>),
+}
+
+impl OrgFilter {
+    /// Whether data owned by `org_id` passes
+    pub fn allows(&self, org_id: Option<i32>) -> bool {
+        match self {
+            OrgFilter::Any => true,
+            OrgFilter::Only(only) => *only == org_id,
+        }
+    }
+
+    /// Bound as `$n` in `($n OR org_id IS NOT DISTINCT FROM $m)`
+    pub(crate) fn is_any(&self) -> bool {
+        matches!(self, OrgFilter::Any)
+    }
+
+    /// Bound as `$m` in `($n OR org_id IS NOT DISTINCT FROM $m)`
+    pub(crate) fn org_id(&self) -> Option<i32> {
+        match self {
+            OrgFilter::Any => None,
+            OrgFilter::Only(org_id) => *org_id,
+        }
+    }
+}
+
+/// A tenant
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct Organization {
+    pub id: i32,
+    /// Short name used in URLs and JWT `org` claims
+    pub slug: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A user's membership of an org
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub email: String,
+    pub name: Option<String>,
+    /// The widest scope the user gets in the org
+    pub role: ApiScope,
+    pub created_at: DateTime<Utc>,
+}
+
+fn member_from_row(row: &PgRow) -> Result<Member, Error> {
+    let role: String = row.try_get("role")?;
+    Ok(Member {
+        email: row.try_get("email")?,
+        name: row.try_get("name")?,
+        role: role.parse().map_err(|e: String| Error::Decode(e.into()))?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+/// Create an org; fails if the slug is taken
+pub async fn create_organization(pool: &PgPool, slug: &str, name: &str) -> Result<Organization, Error> {
+    sqlx::query_as::<_, Organization>("INSERT INTO organizations (slug, name) VALUES ($1, $2) RETURNING *")
+        .bind(slug)
+        .bind(name)
+        .fetch_one(pool)
+        .await
+}
+
+/// An org by slug
+pub async fn get_organization(pool: &PgPool, slug: &str) -> Result<Option<Organization>, Error> {
+    sqlx::query_as::<_, Organization>("SELECT * FROM organizations WHERE slug = $1")
+        .bind(slug)
+        .fetch_optional(pool)
+        .await
+}
+
+/// An org by id
+pub async fn get_organization_by_id(pool: &PgPool, id: i32) -> Result<Option<Organization>, Error> {
+    sqlx::query_as::<_, Organization>("SELECT * FROM organizations WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Every org, by slug
+pub async fn list_organizations(pool: &PgPool) -> Result<Vec<Organization>, Error> {
+    sqlx::query_as::<_, Organization>("SELECT * FROM organizations ORDER BY slug")
+        .fetch_all(pool)
+        .await
+}
+
+/// Add a user to an org, creating the user if needed, or change their role
+pub async fn set_membership(
+    pool: &PgPool,
+    org_id: i32,
+    email: &str,
+    name: Option<&str>,
+    role: ApiScope,
+) -> Result<Member, Error> {
+    let mut tx = pool.begin().await?;
+    let user_id: i32 = sqlx::query_scalar(
+        r#"
+        INSERT INTO users (email, name) VALUES ($1, $2)
+        ON CONFLICT (email) DO UPDATE SET name = COALESCE(EXCLUDED.name, users.name)
+        RETURNING id
+        "#,
+    )
+    .bind(email)
+    .bind(name)
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO memberships (org_id, user_id, role) VALUES ($1, $2, $3)
+        ON CONFLICT (org_id, user_id) DO UPDATE SET role = EXCLUDED.role
+        "#,
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .bind(role.as_str())
+    .execute(&mut *tx)
+    .await?;
+    let row = sqlx::query(
+        r#"
+        SELECT u.email, u.name, m.role, m.created_at
+        FROM memberships m JOIN users u ON u.id = m.user_id
+        WHERE m.org_id = $1 AND m.user_id = $2
+        "#,
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    member_from_row(&row)
+}
+
+/// Remove a user from an org. False if they weren't a member.
+pub async fn remove_membership(pool: &PgPool, org_id: i32, email: &str) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "DELETE FROM memberships WHERE org_id = $1 AND user_id = (SELECT id FROM users WHERE email = $2)",
+    )
+    .bind(org_id)
+    .bind(email)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Members of an org, by email
+pub async fn list_members(pool: &PgPool, org_id: i32) -> Result<Vec<Member>, Error> {
+    let rows = sqlx::query(
+        r#"
+        SELECT u.email, u.name, m.role, m.created_at
+        FROM memberships m JOIN users u ON u.id = m.user_id
+        WHERE m.org_id = $1
+        ORDER BY u.email
+        "#,
+    )
+    .bind(org_id)
+    .fetch_all(pool)
+    .await?;
+    rows.iter().map(member_from_row).collect()
+}
+
+/// The org with this slug and the user's role in it, if the user is a member
+pub async fn find_membership(
+    pool: &PgPool,
+    org_slug: &str,
+    email: &str,
+) -> Result<Option<(Organization, ApiScope)>, Error> {
+    let row = sqlx::query(
+        r#"
+        SELECT o.id, o.slug, o.name, o.created_at, m.role
+        FROM organizations o
+        JOIN memberships m ON m.org_id = o.id
+        JOIN users u ON u.id = m.user_id
+        WHERE o.slug = $1 AND u.email = $2
+        "#,
+    )
+    .bind(org_slug)
+    .bind(email)
+    .fetch_optional(pool)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let role: String = row.try_get("role")?;
+    let org = Organization {
+        id: row.try_get("id")?,
+        slug: row.try_get("slug")?,
+        name: row.try_get("name")?,
+        created_at: row.try_get("created_at")?,
+    };
+    Ok(Some((org, role.parse().map_err(|e: String| Error::Decode(e.into()))?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_org_filter() {
+        assert!(OrgFilter::Any.allows(Some(1)));
+        assert!(OrgFilter::Any.allows(None));
+        assert!(OrgFilter::Only(Some(1)).allows(Some(1)));
+        assert!(!OrgFilter::Only(Some(1)).allows(Some(2)));
+        assert!(!OrgFilter::Only(Some(1)).allows(None));
+        assert!(OrgFilter::Only(None).allows(None));
+        assert!(!OrgFilter::Only(None).allows(Some(1)));
+    }
+}
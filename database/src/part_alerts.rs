@@ -0,0 +1,252 @@
+// USAGE:
+// cargo test part_alerts -- --nocapture
+//
+// Lifecycle alerts: parts of a registered repo's latest commit that the
+// supplier marks not recommended for new designs, end of life or obsolete.
+// The backend's scheduled check looks every used MPN up in `part_metadata`,
+// opens an alert per repo and risky part, and resolves alerts whose part is
+// gone or no longer at risk.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, FromRow, PgPool};
+
+use crate::PartMetadata;
+
+/// How close a part is to being unobtainable
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LifecycleRisk {
+    /// Not recommended for new designs
+    Nrnd,
+    /// End of life announced; last time buy
+    Eol,
+    Obsolete,
+}
+
+impl LifecycleRisk {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LifecycleRisk::Nrnd => "nrnd",
+            LifecycleRisk::Eol => "eol",
+            LifecycleRisk::Obsolete => "obsolete",
+        }
+    }
+
+    /// The risk stored as `name`
+    pub fn parse(name: &str) -> Option<Self> {
+        [LifecycleRisk::Nrnd, LifecycleRisk::Eol, LifecycleRisk::Obsolete]
+            .into_iter()
+            .find(|risk| risk.as_str() == name)
+    }
+
+    /// "NRND", "EOL" or "obsolete", for alert messages
+    pub fn label(&self) -> &'static str {
+        match self {
+            LifecycleRisk::Nrnd => "NRND",
+            LifecycleRisk::Eol => "EOL",
+            LifecycleRisk::Obsolete => "obsolete",
+        }
+    }
+
+    /// The risk a supplier's lifecycle status spells, if any. Suppliers word
+    /// it differently ("Not For New Designs", "Last Time Buy",
+    /// "Discontinued at Digi-Key"), so this matches on the usual phrases.
+    pub fn of_status(status: &str) -> Option<Self> {
+        let status = status.to_lowercase();
+        let words: Vec<&str> = status.split(|c: char| !c.is_ascii_alphanumeric()).collect();
+        if status.contains("obsolete") || status.contains("discontinued") {
+            Some(LifecycleRisk::Obsolete)
+        } else if status.contains("end of life") || status.contains("last time buy") || words.contains(&"eol") || words.contains(&"ltb") {
+            Some(LifecycleRisk::Eol)
+        } else if status.contains("not recommended") || status.contains("not for new design") || words.contains(&"nrnd") {
+            Some(LifecycleRisk::Nrnd)
+        } else {
+            None
+        }
+    }
+
+    /// The risk of a part with `metadata`; the obsolete flag wins over the
+    /// status text
+    pub fn of(metadata: &PartMetadata) -> Option<Self> {
+        if metadata.is_obsolete {
+            return Some(LifecycleRisk::Obsolete);
+        }
+        metadata.lifecycle_status.as_deref().and_then(Self::of_status)
+    }
+}
+
+/// A part used by a registered repo at its latest commit
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+pub struct RepoPart {
+    pub repo_url: String,
+    pub mpn: String,
+    pub commit_hash: String,
+    pub designators: Vec<String>,
+}
+
+/// A stored alert
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+pub struct PartAlert {
+    pub id: i64,
+    pub repo_url: String,
+    pub mpn: String,
+    /// "nrnd", "eol" or "obsolete"
+    pub risk: String,
+    /// The supplier's own wording
+    pub lifecycle_status: Option<String>,
+    /// The commit the part was last seen in
+    pub commit_hash: String,
+    pub designators: Vec<String>,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub notified_at: Option<DateTime<Utc>>,
+}
+
+/// Every part of every registered repo's latest commit, by repo and MPN
+pub async fn registered_repo_parts(pool: &PgPool) -> Result<Vec<RepoPart>, Error> {
+    sqlx::query_as::<_, RepoPart>(
+        r#"
+        SELECT u.repo_url, u.mpn, u.commit_hash, u.designators
+        FROM component_usage u
+        JOIN repos r ON r.repo_url = u.repo_url
+        ORDER BY u.repo_url, u.mpn
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Open, refresh or reopen the alert for `part`
+///
+/// An alert that was resolved, or whose risk changed, is due to be announced
+/// again.
+pub async fn upsert_part_alert(
+    pool: &PgPool,
+    part: &RepoPart,
+    risk: LifecycleRisk,
+    lifecycle_status: Option<&str>,
+) -> Result<PartAlert, Error> {
+    sqlx::query_as::<_, PartAlert>(
+        r#"
+        INSERT INTO part_alerts (repo_url, mpn, risk, lifecycle_status, commit_hash, designators)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (repo_url, mpn) DO UPDATE SET
+            first_seen_at = CASE WHEN part_alerts.resolved_at IS NULL THEN part_alerts.first_seen_at ELSE NOW() END,
+            notified_at = CASE WHEN part_alerts.resolved_at IS NULL AND part_alerts.risk = EXCLUDED.risk
+                THEN part_alerts.notified_at END,
+            risk = EXCLUDED.risk,
+            lifecycle_status = EXCLUDED.lifecycle_status,
+            commit_hash = EXCLUDED.commit_hash,
+            designators = EXCLUDED.designators,
+            last_seen_at = NOW(),
+            resolved_at = NULL
+        RETURNING *
+        "#,
+    )
+    .bind(&part.repo_url)
+    .bind(&part.mpn)
+    .bind(risk.as_str())
+    .bind(lifecycle_status)
+    .bind(&part.commit_hash)
+    .bind(&part.designators)
+    .fetch_one(pool)
+    .await
+}
+
+/// Resolve every open alert except those of `keep` (repo URL and MPN pairs);
+/// returns how many were resolved
+pub async fn resolve_part_alerts_except(pool: &PgPool, keep: &[(String, String)]) -> Result<u64, Error> {
+    let (repo_urls, mpns): (Vec<&str>, Vec<&str>) = keep.iter().map(|(r, m)| (r.as_str(), m.as_str())).unzip();
+    let result = sqlx::query(
+        r#"
+        UPDATE part_alerts a SET resolved_at = NOW()
+        WHERE a.resolved_at IS NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM UNNEST($1::TEXT[], $2::TEXT[]) AS k(repo_url, mpn)
+                WHERE k.repo_url = a.repo_url AND k.mpn = a.mpn
+            )
+        "#,
+    )
+    .bind(repo_urls)
+    .bind(mpns)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Open alerts not announced yet, by repo
+pub async fn unnotified_part_alerts(pool: &PgPool) -> Result<Vec<PartAlert>, Error> {
+    sqlx::query_as::<_, PartAlert>(
+        "SELECT * FROM part_alerts WHERE resolved_at IS NULL AND notified_at IS NULL ORDER BY repo_url, mpn",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Record that the alerts `ids` were announced
+pub async fn mark_part_alerts_notified(pool: &PgPool, ids: &[i64]) -> Result<(), Error> {
+    sqlx::query("UPDATE part_alerts SET notified_at = NOW() WHERE id = ANY($1)")
+        .bind(ids)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// A repo's alerts, open ones first and the most recently seen first within
+/// them; resolved ones only with `include_resolved`
+pub async fn list_part_alerts(pool: &PgPool, repo_url: &str, include_resolved: bool) -> Result<Vec<PartAlert>, Error> {
+    sqlx::query_as::<_, PartAlert>(
+        r#"
+        SELECT * FROM part_alerts
+        WHERE repo_url = $1 AND ($2 OR resolved_at IS NULL)
+        ORDER BY resolved_at IS NOT NULL, last_seen_at DESC, mpn
+        "#,
+    )
+    .bind(repo_url)
+    .bind(include_resolved)
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_risk_of_status() {
+        let risk = |status: &str| LifecycleRisk::of_status(status);
+        assert_eq!(risk("Active"), None);
+        assert_eq!(risk("Not For New Designs"), Some(LifecycleRisk::Nrnd));
+        assert_eq!(risk("NRND"), Some(LifecycleRisk::Nrnd));
+        assert_eq!(risk("Not Recommended for New Designs"), Some(LifecycleRisk::Nrnd));
+        assert_eq!(risk("Last Time Buy"), Some(LifecycleRisk::Eol));
+        assert_eq!(risk("EOL"), Some(LifecycleRisk::Eol));
+        assert_eq!(risk("Obsolete"), Some(LifecycleRisk::Obsolete));
+        assert_eq!(risk("Discontinued at Digi-Key"), Some(LifecycleRisk::Obsolete));
+        // Words that only contain the abbreviations don't count
+        assert_eq!(risk("Preliminary (Revol)"), None);
+    }
+
+    #[test]
+    fn test_obsolete_flag_wins() {
+        let metadata = PartMetadata {
+            mpn: "LM358DR".to_string(),
+            supplier: "digikey".to_string(),
+            manufacturer: None,
+            description: None,
+            datasheet_url: None,
+            product_url: None,
+            lifecycle_status: Some("Active".to_string()),
+            is_obsolete: true,
+            unit_price: None,
+            quantity_available: None,
+            fetched_at: Utc::now(),
+        };
+        assert_eq!(LifecycleRisk::of(&metadata), Some(LifecycleRisk::Obsolete));
+        let active = PartMetadata { is_obsolete: false, ..metadata };
+        assert_eq!(LifecycleRisk::of(&active), None);
+        assert_eq!(LifecycleRisk::parse("nrnd").map(|risk| risk.label()), Some("NRND"));
+        assert_eq!(LifecycleRisk::parse("active"), None);
+    }
+}
@@ -0,0 +1,140 @@
+// USAGE:
+// cargo test part_metadata -- --nocapture
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, PgPool};
+
+/// Supplier data for one manufacturer part number, as cached in `part_metadata`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct PartMetadata {
+    pub mpn: String,
+    pub supplier: String,
+    pub manufacturer: Option<String>,
+    pub description: Option<String>,
+    pub datasheet_url: Option<String>,
+    pub product_url: Option<String>,
+    pub lifecycle_status: Option<String>,
+    pub is_obsolete: bool,
+    pub unit_price: Option<f64>,
+    pub quantity_available: Option<i64>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl PartMetadata {
+    /// Whether the cached entry is older than `max_age`
+    pub fn is_stale(&self, max_age: chrono::Duration) -> bool {
+        Utc::now() - self.fetched_at > max_age
+    }
+
+    /// One-line summary for inclusion in prompts
+    pub fn prompt_line(&self) -> String {
+        let mut line = self.mpn.clone();
+        if let Some(manufacturer) = &self.manufacturer {
+            line.push_str(&format!(" ({})", manufacturer));
+        }
+        if let Some(description) = &self.description {
+            line.push_str(&format!(": {}", description));
+        }
+        if let Some(status) = &self.lifecycle_status {
+            line.push_str(&format!("; lifecycle: {}", status));
+        }
+        if let Some(price) = self.unit_price {
+            line.push_str(&format!("; unit price: ${:.2}", price));
+        }
+        if let Some(url) = &self.datasheet_url {
+            line.push_str(&format!("; datasheet: {}", url));
+        }
+        line
+    }
+}
+
+/// Cached metadata for one MPN
+pub async fn get_part_metadata(pool: &PgPool, mpn: &str) -> Result<Option<PartMetadata>, Error> {
+    sqlx::query_as::<_, PartMetadata>("SELECT * FROM part_metadata WHERE mpn = $1")
+        .bind(mpn)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Cached metadata for several MPNs (missing ones are simply absent)
+pub async fn get_part_metadata_many(
+    pool: &PgPool,
+    mpns: &[String],
+) -> Result<Vec<PartMetadata>, Error> {
+    sqlx::query_as::<_, PartMetadata>("SELECT * FROM part_metadata WHERE mpn = ANY($1) ORDER BY mpn")
+        .bind(mpns)
+        .fetch_all(pool)
+        .await
+}
+
+/// Insert or refresh the cached metadata for an MPN; `fetched_at` is set to now
+pub async fn upsert_part_metadata(pool: &PgPool, metadata: &PartMetadata) -> Result<PartMetadata, Error> {
+    sqlx::query_as::<_, PartMetadata>(
+        r#"
+        INSERT INTO part_metadata (mpn, supplier, manufacturer, description, datasheet_url,
+            product_url, lifecycle_status, is_obsolete, unit_price, quantity_available, fetched_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, NOW())
+        ON CONFLICT (mpn) DO UPDATE SET
+            supplier = EXCLUDED.supplier,
+            manufacturer = EXCLUDED.manufacturer,
+            description = EXCLUDED.description,
+            datasheet_url = EXCLUDED.datasheet_url,
+            product_url = EXCLUDED.product_url,
+            lifecycle_status = EXCLUDED.lifecycle_status,
+            is_obsolete = EXCLUDED.is_obsolete,
+            unit_price = EXCLUDED.unit_price,
+            quantity_available = EXCLUDED.quantity_available,
+            fetched_at = NOW()
+        RETURNING *
+        "#,
+    )
+    .bind(&metadata.mpn)
+    .bind(&metadata.supplier)
+    .bind(&metadata.manufacturer)
+    .bind(&metadata.description)
+    .bind(&metadata.datasheet_url)
+    .bind(&metadata.product_url)
+    .bind(&metadata.lifecycle_status)
+    .bind(metadata.is_obsolete)
+    .bind(metadata.unit_price)
+    .bind(metadata.quantity_available)
+    .fetch_one(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> PartMetadata {
+        PartMetadata {
+            mpn: "LM358DR".to_string(),
+            supplier: "digikey".to_string(),
+            manufacturer: Some("Texas Instruments".to_string()),
+            description: Some("IC OPAMP GP 2 CIRCUIT 8SOIC".to_string()),
+            datasheet_url: Some("https://www.ti.com/lit/ds/symlink/lm358.pdf".to_string()),
+            product_url: None,
+            lifecycle_status: Some("Active".to_string()),
+            is_obsolete: false,
+            unit_price: Some(0.5),
+            quantity_available: Some(1000),
+            fetched_at: Utc::now() - chrono::Duration::days(10),
+        }
+    }
+
+    #[test]
+    fn test_is_stale() {
+        let metadata = sample();
+        assert!(metadata.is_stale(chrono::Duration::days(7)));
+        assert!(!metadata.is_stale(chrono::Duration::days(30)));
+    }
+
+    #[test]
+    fn test_prompt_line() {
+        let line = sample().prompt_line();
+        assert!(line.starts_with("LM358DR (Texas Instruments): IC OPAMP"));
+        assert!(line.contains("lifecycle: Active"));
+        assert!(line.contains("unit price: $0.50"));
+        assert!(line.contains("datasheet: https://www.ti.com/"));
+    }
+}
@@ -0,0 +1,8 @@
+// Native reader for KiCad board files: footprint placements, copper stackup
+// and routing statistics per .kicad_pcb, plus revision diffs so layout-only
+// commits can be summarized. Shares the S-expression reader with `schematic`.
+pub mod board;
+pub mod diff;
+
+pub use board::{parse_board, Board, FootprintPlacement, TrackStats, ViaStats};
+pub use diff::{diff_boards, BoardDiff, PlacementChange};
@@ -0,0 +1,265 @@
+// USAGE:
+// cargo test pcb::board -- --nocapture
+//
+// Layout facts from a .kicad_pcb file: where each footprint sits, how many
+// copper layers the stackup has, and how much routing (tracks, arcs, vias)
+// is on the board. Handles KiCad 6+ `footprint` blocks as well as KiCad 5
+// `module` blocks with `fp_text` references.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::error::SchematicError;
+use crate::schematic::hierarchy::natural_key;
+use crate::schematic::sexpr::{self, SExpr};
+
+/// A footprint placed on the board
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FootprintPlacement {
+    pub reference: String,
+    pub value: Option<String>,
+    /// Library footprint, e.g. `Resistor_SMD:R_0603_1608Metric`
+    pub footprint: String,
+    /// `F.Cu` or `B.Cu`
+    pub layer: String,
+    /// Position in mm
+    pub x: f64,
+    pub y: f64,
+    /// Rotation in degrees
+    pub rotation: f64,
+}
+
+/// Copper routing totals
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TrackStats {
+    pub segments: usize,
+    pub arcs: usize,
+    /// Combined length of segments and arcs in mm
+    pub total_length_mm: f64,
+    /// Routed length per copper layer in mm
+    pub length_by_layer: BTreeMap<String, f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ViaStats {
+    pub count: usize,
+    /// Count per via type: `through`, `blind` or `micro`
+    pub by_type: BTreeMap<String, usize>,
+}
+
+/// Summary of one board file
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Board {
+    pub file: String,
+    pub layer_count: usize,
+    /// Copper layer names in stackup order
+    pub copper_layers: Vec<String>,
+    /// In natural reference order
+    pub footprints: Vec<FootprintPlacement>,
+    pub tracks: TrackStats,
+    pub vias: ViaStats,
+    pub zones: usize,
+}
+
+impl Board {
+    pub fn footprint(&self, reference: &str) -> Option<&FootprintPlacement> {
+        self.footprints.iter().find(|f| f.reference == reference)
+    }
+}
+
+fn point(expr: &SExpr) -> Option<(f64, f64)> {
+    Some((expr.number(0)?, expr.number(1)?))
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+/// Length of the circular arc from `start` through `mid` to `end`
+///
+/// Falls back to the chord lengths when the points are (nearly) collinear.
+fn arc_length(start: (f64, f64), mid: (f64, f64), end: (f64, f64)) -> f64 {
+    let (ax, ay) = start;
+    let (bx, by) = mid;
+    let (cx, cy) = end;
+    let d = 2.0 * (ax * (by - cy) + bx * (cy - ay) + cx * (ay - by));
+    if d.abs() < 1e-9 {
+        return distance(start, mid) + distance(mid, end);
+    }
+    let a2 = ax * ax + ay * ay;
+    let b2 = bx * bx + by * by;
+    let c2 = cx * cx + cy * cy;
+    let ux = (a2 * (by - cy) + b2 * (cy - ay) + c2 * (ay - by)) / d;
+    let uy = (a2 * (cx - bx) + b2 * (ax - cx) + c2 * (bx - ax)) / d;
+    let radius = distance((ux, uy), start);
+
+    // Sweep from start to end, going the way that passes through mid
+    let angle = |(x, y): (f64, f64)| (y - uy).atan2(x - ux);
+    let tau = std::f64::consts::TAU;
+    let sweep_to = |p| (angle(p) - angle(start)).rem_euclid(tau);
+    let (to_mid, to_end) = (sweep_to(mid), sweep_to(end));
+    let sweep = if to_mid <= to_end { to_end } else { tau - to_end };
+    radius * sweep
+}
+
+fn parse_footprint(item: &SExpr) -> Option<FootprintPlacement> {
+    let (x, y) = point(item.child("at")?)?;
+    let rotation = item.child("at").and_then(|at| at.number(2)).unwrap_or(0.0);
+
+    // KiCad 8+ keeps reference and value in properties, older files in fp_text
+    let text = |name: &str| {
+        item.children("property")
+            .find(|p| p.arg(0) == Some(name))
+            .and_then(|p| p.arg(1))
+            .or_else(|| {
+                item.children("fp_text")
+                    .find(|t| t.arg(0) == Some(&name.to_lowercase()))
+                    .and_then(|t| t.arg(1))
+            })
+            .map(str::to_string)
+    };
+
+    Some(FootprintPlacement {
+        reference: text("Reference")?,
+        value: text("Value"),
+        footprint: item.arg(0).unwrap_or("").to_string(),
+        layer: item
+            .child("layer")
+            .and_then(|l| l.arg(0))
+            .unwrap_or("F.Cu")
+            .to_string(),
+        x,
+        y,
+        rotation,
+    })
+}
+
+fn add_track(tracks: &mut TrackStats, item: &SExpr, length: f64) {
+    tracks.total_length_mm += length;
+    if let Some(layer) = item.child("layer").and_then(|l| l.arg(0)) {
+        *tracks.length_by_layer.entry(layer.to_string()).or_default() += length;
+    }
+}
+
+/// Parse one .kicad_pcb file; `file` is only used in error messages
+pub fn parse_board(file: &str, text: &str) -> Result<Board, SchematicError> {
+    let root = sexpr::parse(file, text)?;
+    if root.head() != Some("kicad_pcb") {
+        return Err(SchematicError::NotABoard {
+            file: file.to_string(),
+            found: root.head().unwrap_or("").to_string(),
+        });
+    }
+
+    let mut board = Board {
+        file: file.to_string(),
+        ..Default::default()
+    };
+
+    // (layers (0 "F.Cu" signal) (31 "B.Cu" signal) (36 "B.SilkS" user) ...)
+    if let Some(layers) = root.child("layers") {
+        board.copper_layers = layers
+            .items()
+            .iter()
+            .filter_map(|layer| layer.arg(0))
+            .filter(|name| name.ends_with(".Cu"))
+            .map(str::to_string)
+            .collect();
+        board.layer_count = board.copper_layers.len();
+    }
+
+    for item in root.items() {
+        match item.head() {
+            Some("footprint" | "module") => board.footprints.extend(parse_footprint(item)),
+            Some("segment") => {
+                let ends = item.child("start").and_then(point).zip(item.child("end").and_then(point));
+                if let Some((start, end)) = ends {
+                    board.tracks.segments += 1;
+                    add_track(&mut board.tracks, item, distance(start, end));
+                }
+            }
+            Some("arc") => {
+                let start = item.child("start").and_then(point);
+                let mid = item.child("mid").and_then(point);
+                let end = item.child("end").and_then(point);
+                if let (Some(start), Some(mid), Some(end)) = (start, mid, end) {
+                    board.tracks.arcs += 1;
+                    add_track(&mut board.tracks, item, arc_length(start, mid, end));
+                }
+            }
+            Some("via") => {
+                let kind = ["blind", "micro"]
+                    .into_iter()
+                    .find(|kind| item.has_flag(kind))
+                    .unwrap_or("through");
+                board.vias.count += 1;
+                *board.vias.by_type.entry(kind.to_string()).or_default() += 1;
+            }
+            Some("zone") => board.zones += 1,
+            _ => {}
+        }
+    }
+
+    board.footprints.sort_by_key(|f| natural_key(&f.reference));
+    Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOARD: &str = r#"(kicad_pcb (version 20240108) (generator "pcbnew")
+        (layers (0 "F.Cu" signal) (1 "In1.Cu" signal) (2 "In2.Cu" signal) (31 "B.Cu" signal)
+            (36 "B.SilkS" user "B.Silkscreen") (44 "Edge.Cuts" user))
+        (net 0 "") (net 1 "GND")
+        (footprint "Resistor_SMD:R_0603" (layer "F.Cu") (at 10 20 90)
+            (property "Reference" "R2" (at 0 0 0)) (property "Value" "10k" (at 0 0 0)))
+        (module "Capacitor_SMD:C_0402" (layer "B.Cu") (at 5 5)
+            (fp_text reference "C1" (at 0 0)) (fp_text value "100n" (at 0 0)))
+        (footprint "Resistor_SMD:R_0603" (layer "F.Cu") (at 0 0)
+            (property "Reference" "R10" (at 0 0 0)))
+        (segment (start 0 0) (end 3 4) (width 0.25) (layer "F.Cu") (net 1))
+        (segment (start 3 4) (end 3 10) (width 0.25) (layer "B.Cu") (net 1))
+        (arc (start 10 0) (mid 0 10) (end -10 0) (width 0.25) (layer "F.Cu") (net 1))
+        (via (at 3 4) (size 0.6) (drill 0.3) (layers "F.Cu" "B.Cu") (net 1))
+        (via micro (at 5 5) (size 0.3) (drill 0.1) (layers "F.Cu" "In1.Cu") (net 1))
+        (zone (net 1) (net_name "GND") (layer "B.Cu")))"#;
+
+    #[test]
+    fn test_parse_board() {
+        let board = parse_board("b.kicad_pcb", BOARD).unwrap();
+        assert_eq!(board.layer_count, 4);
+        assert_eq!(board.copper_layers, ["F.Cu", "In1.Cu", "In2.Cu", "B.Cu"]);
+
+        let refs: Vec<&str> = board.footprints.iter().map(|f| f.reference.as_str()).collect();
+        assert_eq!(refs, ["C1", "R2", "R10"]);
+        let c1 = board.footprint("C1").unwrap();
+        assert_eq!(c1.layer, "B.Cu");
+        assert_eq!(c1.value.as_deref(), Some("100n"));
+        assert_eq!(board.footprint("R2").unwrap().rotation, 90.0);
+
+        assert_eq!(board.tracks.segments, 2);
+        assert_eq!(board.tracks.arcs, 1);
+        // 5 + 6 + a half circle of radius 10
+        let expected = 11.0 + std::f64::consts::PI * 10.0;
+        assert!((board.tracks.total_length_mm - expected).abs() < 1e-9);
+        assert!((board.tracks.length_by_layer["B.Cu"] - 6.0).abs() < 1e-9);
+
+        assert_eq!(board.vias.count, 2);
+        assert_eq!(board.vias.by_type["micro"], 1);
+        assert_eq!(board.vias.by_type["through"], 1);
+        assert_eq!(board.zones, 1);
+    }
+
+    #[test]
+    fn test_arc_length_takes_the_long_way_through_mid() {
+        // Three quarters of a unit circle, counter-clockwise via (-1, 0)
+        let length = arc_length((1.0, 0.0), (-1.0, 0.0), (0.0, -1.0));
+        assert!((length - 1.5 * std::f64::consts::PI).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        let err = parse_board("x.kicad_sch", "(kicad_sch (version 1))").unwrap_err();
+        assert!(matches!(err, SchematicError::NotABoard { .. }));
+    }
+}
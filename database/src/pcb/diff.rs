@@ -0,0 +1,190 @@
+// USAGE:
+// cargo test pcb::diff -- --nocapture
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+use super::board::{Board, FootprintPlacement};
+
+/// Placements closer than this (in mm) count as unmoved
+const MOVE_TOLERANCE_MM: f64 = 0.001;
+
+/// A footprint that was moved, rotated, flipped or swapped for another footprint
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlacementChange {
+    pub reference: String,
+    pub old: FootprintPlacement,
+    pub new: FootprintPlacement,
+}
+
+impl PlacementChange {
+    fn describe(&self) -> String {
+        let (old, new) = (&self.old, &self.new);
+        let mut parts = Vec::new();
+        if old.footprint != new.footprint {
+            parts.push(format!("footprint {} -> {}", old.footprint, new.footprint));
+        }
+        if old.layer != new.layer {
+            parts.push(format!("flipped {} -> {}", old.layer, new.layer));
+        }
+        if (old.x - new.x).hypot(old.y - new.y) > MOVE_TOLERANCE_MM {
+            parts.push(format!(
+                "moved ({:.2}, {:.2}) -> ({:.2}, {:.2}) mm",
+                old.x, old.y, new.x, new.y
+            ));
+        }
+        if (old.rotation - new.rotation).abs() > MOVE_TOLERANCE_MM {
+            parts.push(format!("rotated {}° -> {}°", old.rotation, new.rotation));
+        }
+        format!("{}: {}", self.reference, parts.join(", "))
+    }
+}
+
+/// Layout differences between two revisions of a board
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BoardDiff {
+    /// Copper layer count before and after, if it changed
+    pub layer_count: Option<(usize, usize)>,
+    pub added_footprints: Vec<String>,
+    pub removed_footprints: Vec<String>,
+    pub changed_placements: Vec<PlacementChange>,
+    /// Total routed length before and after in mm, if it changed
+    pub track_length_mm: Option<(f64, f64)>,
+    /// Track plus arc count before and after, if it changed
+    pub track_count: Option<(usize, usize)>,
+    pub via_count: Option<(usize, usize)>,
+    pub zone_count: Option<(usize, usize)>,
+}
+
+fn changed<T: PartialEq>(old: T, new: T) -> Option<(T, T)> {
+    (old != new).then_some((old, new))
+}
+
+fn placement_changed(old: &FootprintPlacement, new: &FootprintPlacement) -> bool {
+    old.footprint != new.footprint
+        || old.layer != new.layer
+        || (old.x - new.x).hypot(old.y - new.y) > MOVE_TOLERANCE_MM
+        || (old.rotation - new.rotation).abs() > MOVE_TOLERANCE_MM
+}
+
+/// Compare two revisions; pass `Board::default()` for a side that has no board
+pub fn diff_boards(old: &Board, new: &Board) -> BoardDiff {
+    let mut diff = BoardDiff {
+        layer_count: changed(old.layer_count, new.layer_count),
+        track_count: changed(
+            old.tracks.segments + old.tracks.arcs,
+            new.tracks.segments + new.tracks.arcs,
+        ),
+        via_count: changed(old.vias.count, new.vias.count),
+        zone_count: changed(old.zones, new.zones),
+        ..Default::default()
+    };
+    // Rounded so re-saving a file doesn't report float noise as rerouting
+    let (old_length, new_length) = (
+        (old.tracks.total_length_mm * 100.0).round() / 100.0,
+        (new.tracks.total_length_mm * 100.0).round() / 100.0,
+    );
+    diff.track_length_mm = changed(old_length, new_length);
+
+    for placement in &new.footprints {
+        match old.footprint(&placement.reference) {
+            None => diff.added_footprints.push(placement.reference.clone()),
+            Some(previous) if placement_changed(previous, placement) => {
+                diff.changed_placements.push(PlacementChange {
+                    reference: placement.reference.clone(),
+                    old: previous.clone(),
+                    new: placement.clone(),
+                });
+            }
+            Some(_) => {}
+        }
+    }
+    for placement in &old.footprints {
+        if new.footprint(&placement.reference).is_none() {
+            diff.removed_footprints.push(placement.reference.clone());
+        }
+    }
+    diff
+}
+
+impl BoardDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == BoardDiff::default()
+    }
+
+    /// Plain-text summary, one change per line, for prompts and descriptions
+    pub fn describe(&self) -> String {
+        let mut out = String::new();
+        let list = |items: &[String]| items.join(", ");
+
+        if let Some((old, new)) = self.layer_count {
+            let _ = writeln!(out, "Copper layers: {} -> {}", old, new);
+        }
+        if !self.added_footprints.is_empty() {
+            let _ = writeln!(out, "Footprints added: {}", list(&self.added_footprints));
+        }
+        if !self.removed_footprints.is_empty() {
+            let _ = writeln!(out, "Footprints removed: {}", list(&self.removed_footprints));
+        }
+        for change in &self.changed_placements {
+            let _ = writeln!(out, "{}", change.describe());
+        }
+        if let Some((old, new)) = self.track_count {
+            let _ = writeln!(out, "Tracks: {} -> {}", old, new);
+        }
+        if let Some((old, new)) = self.track_length_mm {
+            let _ = writeln!(out, "Routed length: {:.2} -> {:.2} mm", old, new);
+        }
+        if let Some((old, new)) = self.via_count {
+            let _ = writeln!(out, "Vias: {} -> {}", old, new);
+        }
+        if let Some((old, new)) = self.zone_count {
+            let _ = writeln!(out, "Zones: {} -> {}", old, new);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcb::parse_board;
+
+    #[test]
+    fn test_diff_boards() {
+        let old = parse_board(
+            "b.kicad_pcb",
+            r#"(kicad_pcb (layers (0 "F.Cu" signal) (31 "B.Cu" signal))
+                (footprint "R_0603" (layer "F.Cu") (at 10 10) (property "Reference" "R1"))
+                (footprint "R_0603" (layer "F.Cu") (at 20 10) (property "Reference" "R2"))
+                (footprint "C_0402" (layer "F.Cu") (at 30 10) (property "Reference" "C1"))
+                (segment (start 0 0) (end 10 0) (width 0.2) (layer "F.Cu")))"#,
+        )
+        .unwrap();
+        let new = parse_board(
+            "b.kicad_pcb",
+            r#"(kicad_pcb (layers (0 "F.Cu" signal) (1 "In1.Cu" signal) (2 "In2.Cu" signal) (31 "B.Cu" signal))
+                (footprint "R_0603" (layer "F.Cu") (at 10 10) (property "Reference" "R1"))
+                (footprint "R_0603" (layer "B.Cu") (at 25 10 180) (property "Reference" "R2"))
+                (footprint "U_SOIC8" (layer "F.Cu") (at 40 10) (property "Reference" "U1"))
+                (segment (start 0 0) (end 10 0) (width 0.2) (layer "F.Cu"))
+                (via (at 10 0) (layers "F.Cu" "B.Cu")))"#,
+        )
+        .unwrap();
+
+        let diff = diff_boards(&old, &new);
+        assert_eq!(diff.layer_count, Some((2, 4)));
+        assert_eq!(diff.added_footprints, ["U1"]);
+        assert_eq!(diff.removed_footprints, ["C1"]);
+        assert_eq!(diff.changed_placements.len(), 1);
+        assert_eq!(diff.via_count, Some((0, 1)));
+        assert_eq!(diff.track_count, None);
+        assert_eq!(diff.track_length_mm, None);
+
+        let text = diff.describe();
+        assert!(text.contains("Copper layers: 2 -> 4"));
+        assert!(text.contains("R2: flipped F.Cu -> B.Cu, moved (20.00, 10.00) -> (25.00, 10.00) mm, rotated 0° -> 180°"));
+
+        assert!(diff_boards(&new, &new).is_empty());
+        assert_eq!(diff_boards(&Board::default(), &new).added_footprints, ["R1", "R2", "U1"]);
+    }
+}
@@ -0,0 +1,144 @@
+// USAGE:
+// cargo test personas -- --nocapture
+use crate::messages::{ChatCompletionRequest, Message, MessageRole};
+use crate::xai_client::{InputMessage, ResponsesRequest};
+use serde::Serialize;
+
+/// A named inference preset: the voice the model answers in, how it formats
+/// the answer, and the sampling settings that go with it.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Persona {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub system_prompt: &'static str,
+    pub formatting_rules: &'static str,
+    pub temperature: f32,
+    pub max_tokens: u32,
+}
+
+/// All built-in personas, selectable by name
+pub const PERSONAS: &[Persona] = &[
+    Persona {
+        name: "terse-reviewer",
+        description: "Short, blunt design-review notes for experienced engineers",
+        system_prompt: "You are a senior hardware engineer doing a quick design review. \
+            Assume the reader knows electronics. Point out what changed and anything risky; skip the basics.",
+        formatting_rules: "Use at most 5 bullet points. No introductions or closing remarks. \
+            Reference components by designator (e.g. U3, R12).",
+        temperature: 0.2,
+        max_tokens: 400,
+    },
+    Persona {
+        name: "teaching-assistant",
+        description: "Patient explanations for readers who are not electrical engineers",
+        system_prompt: "You are a friendly teaching assistant explaining circuits to someone who is not an \
+            electrical engineer. Explain what each part does and why it is there, using everyday analogies.",
+        formatting_rules: "Use short paragraphs and plain language. \
+            Define any technical term the first time it is used.",
+        temperature: 0.7,
+        max_tokens: 1200,
+    },
+    Persona {
+        name: "formal-report",
+        description: "Structured, neutral prose suitable for design documentation",
+        system_prompt: "You are writing a formal engineering design report. \
+            Be precise, neutral and complete; do not speculate beyond the provided data.",
+        formatting_rules: "Use Markdown headings: Summary, Changes, Impact, Open Questions. \
+            Write in complete sentences and the third person.",
+        temperature: 0.3,
+        max_tokens: 2000,
+    },
+];
+
+impl Persona {
+    /// Look up a persona by name
+    pub fn by_name(name: &str) -> Option<&'static Persona> {
+        PERSONAS.iter().find(|p| p.name == name)
+    }
+
+    /// Names of all built-in personas
+    pub fn names() -> Vec<&'static str> {
+        PERSONAS.iter().map(|p| p.name).collect()
+    }
+
+    /// The persona block appended to (or used as) the system prompt
+    pub fn system_block(&self) -> String {
+        format!(
+            "## Persona\n{}\n\n## Formatting Rules\n{}",
+            self.system_prompt, self.formatting_rules
+        )
+    }
+
+    /// Apply this persona to a chat completion request.
+    /// Appends the persona block to an existing system message (or inserts one)
+    /// and sets temperature and max_tokens.
+    pub fn apply(&self, request: &mut ChatCompletionRequest) {
+        match request
+            .messages
+            .iter_mut()
+            .find(|m| m.role == MessageRole::System)
+        {
+            Some(system) => {
+                system.content.push_text(&format!("\n\n---\n\n{}", self.system_block()));
+            }
+            None => request.messages.insert(0, Message::system(self.system_block())),
+        }
+        request.temperature = Some(self.temperature);
+        request.max_tokens = Some(self.max_tokens);
+    }
+
+    /// Apply this persona to a responses API request
+    pub fn apply_to_responses(&self, request: &mut ResponsesRequest) {
+        request
+            .input
+            .insert(0, InputMessage::system(self.system_block()));
+        request.temperature = Some(self.temperature);
+        request.max_output_tokens = Some(self.max_tokens);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_persona_lookup() {
+        assert!(Persona::by_name("terse-reviewer").is_some());
+        assert!(Persona::by_name("pirate").is_none());
+        assert_eq!(Persona::names().len(), PERSONAS.len());
+    }
+
+    #[test]
+    fn test_apply_merges_into_existing_system_message() {
+        let persona = Persona::by_name("formal-report").unwrap();
+        let mut request = ChatCompletionRequest::new(
+            vec![
+                Message::system("Base prompt".to_string()),
+                Message::user("Question".to_string()),
+            ],
+            "grok-4".to_string(),
+        );
+
+        persona.apply(&mut request);
+
+        assert_eq!(request.messages.len(), 2);
+        assert!(request.messages[0].content.text().starts_with("Base prompt"));
+        assert!(request.messages[0].content.text().contains(persona.formatting_rules));
+        assert_eq!(request.temperature, Some(persona.temperature));
+        assert_eq!(request.max_tokens, Some(persona.max_tokens));
+    }
+
+    #[test]
+    fn test_apply_inserts_system_message() {
+        let persona = Persona::by_name("teaching-assistant").unwrap();
+        let mut request = ChatCompletionRequest::new(
+            vec![Message::user("Question".to_string())],
+            "grok-4".to_string(),
+        );
+
+        persona.apply(&mut request);
+
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.messages[0].role, MessageRole::System);
+    }
+}
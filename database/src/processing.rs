@@ -0,0 +1,256 @@
+// USAGE:
+// cargo test --test integration commit_processing -- --nocapture
+//
+// Per-commit processing state: which AI outputs exist for a commit, whether
+// it has been distilled and rendered, where it stands in the overview
+// pipeline, and why its last processing attempt failed, if it did. Per
+// branch, the commit up to which everything has been processed.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::postgres::PgTransaction;
+use sqlx::{Error, PgPool};
+use std::collections::HashSet;
+
+/// Where a commit stands in the overview pipeline
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessingStatus {
+    /// Not processed yet
+    #[default]
+    Pending,
+    /// An update is generating its overview
+    Processing,
+    /// Its overview is stored
+    Done,
+    /// The last attempt failed; the error is stored with it
+    Failed,
+}
+
+impl ProcessingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProcessingStatus::Pending => "pending",
+            ProcessingStatus::Processing => "processing",
+            ProcessingStatus::Done => "done",
+            ProcessingStatus::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for ProcessingStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(ProcessingStatus::Pending),
+            "processing" => Ok(ProcessingStatus::Processing),
+            "done" => Ok(ProcessingStatus::Done),
+            "failed" => Ok(ProcessingStatus::Failed),
+            other => Err(format!("unknown processing status '{}'", other)),
+        }
+    }
+}
+
+/// What has been stored for a commit so far
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, sqlx::FromRow)]
+pub struct CommitProcessing {
+    pub commit_hash: String,
+    pub has_blurb: bool,
+    pub has_description: bool,
+    pub has_change_summary: bool,
+    /// Distilled JSON (components and nets) is stored
+    pub distilled: bool,
+    /// A schematic image is stored
+    pub rendered: bool,
+    /// A [`ProcessingStatus`], as stored; see [`CommitProcessing::status`]
+    pub processing_status: String,
+    /// Why the last attempt failed; None if it succeeded or none was made
+    pub processing_error: Option<String>,
+    pub processing_error_at: Option<DateTime<Utc>>,
+    /// Stage timings keyed by analysis kind
+    pub timings: Option<Value>,
+}
+
+impl CommitProcessing {
+    pub fn status(&self) -> ProcessingStatus {
+        self.processing_status.parse().unwrap_or_default()
+    }
+}
+
+/// A commit whose last processing attempt failed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ProcessingFailure {
+    pub commit_hash: String,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Processing state of those of `commit_hashes` that are stored, in no particular order
+pub async fn commit_processing(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hashes: &[String],
+) -> Result<Vec<CommitProcessing>, Error> {
+    sqlx::query_as::<_, CommitProcessing>(
+        r#"
+        SELECT commit_hash,
+            blurb IS NOT NULL AS has_blurb,
+            description IS NOT NULL AS has_description,
+            change_summary IS NOT NULL AS has_change_summary,
+            distilled_json IS NOT NULL AS distilled,
+            image_digest IS NOT NULL AS rendered,
+            processing_status,
+            processing_error,
+            processing_error_at,
+            timings
+        FROM schematics
+        WHERE repo_url = $1 AND commit_hash = ANY($2) AND deleted_at IS NULL
+        "#,
+    )
+    .bind(repo_url)
+    .bind(commit_hashes)
+    .fetch_all(pool)
+    .await
+}
+
+/// Those of `commit_hashes` that already have both overview parts, the blurb
+/// and the description, so updates can skip them
+pub async fn commits_with_overviews(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hashes: &[String],
+) -> Result<HashSet<String>, Error> {
+    let hashes: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT commit_hash FROM schematics
+        WHERE repo_url = $1 AND commit_hash = ANY($2) AND deleted_at IS NULL
+            AND blurb IS NOT NULL AND description IS NOT NULL
+        "#,
+    )
+    .bind(repo_url)
+    .bind(commit_hashes)
+    .fetch_all(pool)
+    .await?;
+    Ok(hashes.into_iter().collect())
+}
+
+/// Mark a commit as being processed; a row is stored for it if there is none
+pub async fn record_processing_started(pool: &PgPool, repo_url: &str, commit_hash: &str) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+    crate::retention::forget_deleted(&mut tx, repo_url, commit_hash).await?;
+    sqlx::query(
+        r#"
+        INSERT INTO schematics (repo_url, commit_hash, processing_status)
+        VALUES ($1, $2, 'processing')
+        ON CONFLICT (repo_url, commit_hash) DO UPDATE SET processing_status = 'processing'
+        "#,
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+/// Record how processing a commit ended: failed with `error`, or done with
+/// `None`, which also clears an earlier error
+///
+/// A failure is recorded even if nothing else is stored for the commit yet.
+pub async fn record_processing_error(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hash: &str,
+    error: Option<&str>,
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+    record_processing_error_in(&mut tx, repo_url, commit_hash, error).await?;
+    tx.commit().await
+}
+
+/// [`record_processing_error`] as part of `tx`
+pub async fn record_processing_error_in(
+    tx: &mut PgTransaction<'_>,
+    repo_url: &str,
+    commit_hash: &str,
+    error: Option<&str>,
+) -> Result<(), Error> {
+    let Some(error) = error else {
+        sqlx::query(
+            r#"
+            UPDATE schematics SET processing_status = 'done', processing_error = NULL, processing_error_at = NULL,
+                processed_at = CURRENT_TIMESTAMP
+            WHERE repo_url = $1 AND commit_hash = $2
+            "#,
+        )
+        .bind(repo_url)
+        .bind(commit_hash)
+        .execute(&mut **tx)
+        .await?;
+        return Ok(());
+    };
+
+    crate::retention::forget_deleted(tx, repo_url, commit_hash).await?;
+    sqlx::query(
+        r#"
+        INSERT INTO schematics (repo_url, commit_hash, processing_status, processing_error, processing_error_at)
+        VALUES ($1, $2, 'failed', $3, CURRENT_TIMESTAMP)
+        ON CONFLICT (repo_url, commit_hash) DO UPDATE SET
+            processing_status = EXCLUDED.processing_status,
+            processing_error = EXCLUDED.processing_error,
+            processing_error_at = EXCLUDED.processing_error_at
+        "#,
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .bind(error)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Commits of a repo whose last processing attempt failed, most recent
+/// failure first, at most `limit`
+pub async fn processing_failures(pool: &PgPool, repo_url: &str, limit: i64) -> Result<Vec<ProcessingFailure>, Error> {
+    sqlx::query_as::<_, ProcessingFailure>(
+        r#"
+        SELECT commit_hash, processing_error AS error, processing_error_at AS failed_at
+        FROM schematics
+        WHERE repo_url = $1 AND processing_status = 'failed' AND deleted_at IS NULL
+            AND processing_error IS NOT NULL AND processing_error_at IS NOT NULL
+        ORDER BY processing_error_at DESC, commit_hash
+        LIMIT $2
+        "#,
+    )
+    .bind(repo_url)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// The newest commit of `branch` processed along with everything before it,
+/// if an update has finished one
+pub async fn processed_up_to(pool: &PgPool, repo_url: &str, branch: &str) -> Result<Option<String>, Error> {
+    sqlx::query_scalar("SELECT commit_hash FROM processing_high_water_marks WHERE repo_url = $1 AND branch = $2")
+        .bind(repo_url)
+        .bind(branch)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Record that `branch` is processed up to and including `commit_hash`
+pub async fn set_processed_up_to(pool: &PgPool, repo_url: &str, branch: &str, commit_hash: &str) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO processing_high_water_marks (repo_url, branch, commit_hash)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (repo_url, branch) DO UPDATE SET commit_hash = $3, updated_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(repo_url)
+    .bind(branch)
+    .bind(commit_hash)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,118 @@
+// USAGE:
+// cargo test --test integration prompt_audit -- --nocapture
+//
+// Audit log of AI calls: what was sent to the model, what came back, how long
+// it took and the tokens it used. The caller redacts and truncates messages
+// and responses before recording them; this module only stores and lists.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Error, PgPool};
+use std::time::Duration;
+
+use crate::organizations::OrgFilter;
+
+/// One AI call as audited
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PromptAudit {
+    /// ID of the HTTP request that made the call, if any
+    pub request_id: Option<String>,
+    pub endpoint: String,
+    /// The provider the call went to, e.g. "xai" or a fallback's name
+    pub provider: String,
+    pub model: String,
+    pub repo_url: Option<String>,
+    /// Identifier of the calling API key (or token)
+    pub api_key: Option<String>,
+    pub org_id: Option<i32>,
+    /// The messages sent, already redacted
+    pub messages: Value,
+    /// The answer's text, already redacted; None if the call failed
+    pub response: Option<String>,
+    pub error: Option<String>,
+    pub latency_ms: i64,
+    pub prompt_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
+}
+
+/// A recorded call
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct StoredPromptAudit {
+    pub id: i64,
+    pub request_id: Option<String>,
+    pub endpoint: String,
+    pub provider: String,
+    pub model: String,
+    pub repo_url: Option<String>,
+    pub api_key: Option<String>,
+    pub messages: Value,
+    pub response: Option<String>,
+    pub error: Option<String>,
+    pub latency_ms: i64,
+    pub prompt_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Record a call
+pub async fn record_prompt_audit(pool: &PgPool, audit: &PromptAudit) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO prompt_audit
+            (request_id, endpoint, provider, model, repo_url, api_key, org_id, messages, response, error,
+             latency_ms, prompt_tokens, completion_tokens)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        "#,
+    )
+    .bind(&audit.request_id)
+    .bind(&audit.endpoint)
+    .bind(&audit.provider)
+    .bind(&audit.model)
+    .bind(&audit.repo_url)
+    .bind(&audit.api_key)
+    .bind(audit.org_id)
+    .bind(&audit.messages)
+    .bind(&audit.response)
+    .bind(&audit.error)
+    .bind(audit.latency_ms)
+    .bind(audit.prompt_tokens)
+    .bind(audit.completion_tokens)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The latest `limit` calls charged to orgs `orgs` lets through, newest
+/// first; only those for `repo_url` if it's given
+pub async fn recent_prompt_audits(
+    pool: &PgPool,
+    repo_url: Option<&str>,
+    limit: i64,
+    orgs: OrgFilter,
+) -> Result<Vec<StoredPromptAudit>, Error> {
+    sqlx::query_as::<_, StoredPromptAudit>(
+        r#"
+        SELECT id, request_id, endpoint, provider, model, repo_url, api_key, messages, response, error,
+            latency_ms, prompt_tokens, completion_tokens, created_at
+        FROM prompt_audit
+        WHERE ($1::TEXT IS NULL OR repo_url = $1) AND ($3 OR org_id IS NOT DISTINCT FROM $4)
+        ORDER BY created_at DESC, id DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(repo_url)
+    .bind(limit)
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .fetch_all(pool)
+    .await
+}
+
+/// Delete calls recorded more than `retention` ago; returns how many
+pub async fn purge_prompt_audits(pool: &PgPool, retention: Duration) -> Result<u64, Error> {
+    let result = sqlx::query("DELETE FROM prompt_audit WHERE created_at <= CURRENT_TIMESTAMP - make_interval(secs => $1)")
+        .bind(retention.as_secs_f64())
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
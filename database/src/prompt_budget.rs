@@ -0,0 +1,268 @@
+// USAGE:
+// cargo test prompt_budget -- --nocapture
+//
+// Fitting a prompt's variable-length context into a model's window. A prompt
+// is fixed text (instructions, the user's question), which is always sent
+// whole, plus named context sections (netlists, ERC findings, supplier data),
+// which are shortened when everything doesn't fit:
+//
+//     let fitted = PromptBudget::new(model, context_window)
+//         .reserve_output(max_tokens)
+//         .fixed(&question)
+//         .section("netlist", netlist)
+//         .fit();
+//     let netlist = fitted.text("netlist");
+//
+// The room left after the fixed text and the reserved output is shared
+// evenly: sections smaller than their share keep all of it and pass what
+// they don't use to the others. A shortened section keeps its first lines and
+// ends with a note saying how many were left out.
+use crate::tokens::Encoding;
+
+/// Output reserved when a request doesn't set max_tokens
+pub const DEFAULT_RESERVED_OUTPUT: usize = 4_096;
+
+/// Share of the window kept free for errors in the token estimate
+const MARGIN_PERCENT: usize = 5;
+
+/// Room a prompt may take in a model's context window, and the context
+/// competing for it
+#[derive(Debug, Clone)]
+pub struct PromptBudget {
+    encoding: Encoding,
+    context_window: usize,
+    reserved_output: usize,
+    fixed_tokens: usize,
+    sections: Vec<(String, String)>,
+}
+
+/// Context sections cut to fit a [`PromptBudget`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FittedPrompt {
+    sections: Vec<(String, String)>,
+    /// Names of the sections that were shortened (or dropped), in the order added
+    pub truncated: Vec<String>,
+    /// Estimated prompt tokens: the fixed text plus the fitted sections
+    pub prompt_tokens: usize,
+    /// False when the fixed text and reserved output alone overflow the
+    /// window; every section is then dropped
+    pub fits: bool,
+}
+
+impl PromptBudget {
+    /// A budget for a prompt to `model`, whose window holds `context_window`
+    /// tokens of prompt and completion together
+    pub fn new(model: &str, context_window: usize) -> Self {
+        Self {
+            encoding: Encoding::for_model(model),
+            context_window,
+            reserved_output: DEFAULT_RESERVED_OUTPUT,
+            fixed_tokens: 0,
+            sections: Vec::new(),
+        }
+    }
+
+    /// Keep room for a completion of up to `tokens`
+    pub fn reserve_output(mut self, tokens: u32) -> Self {
+        self.reserved_output = tokens as usize;
+        self
+    }
+
+    /// Text sent as is, whatever the budget
+    pub fn fixed(mut self, text: &str) -> Self {
+        self.fixed_tokens += self.encoding.count(text);
+        self
+    }
+
+    /// Tokens sent as is that aren't text, e.g. an attached image
+    pub fn fixed_tokens(mut self, tokens: usize) -> Self {
+        self.fixed_tokens += tokens;
+        self
+    }
+
+    /// Context that may be shortened to fit, looked up by `name` afterwards
+    pub fn section(mut self, name: &str, text: impl Into<String>) -> Self {
+        self.sections.push((name.to_string(), text.into()));
+        self
+    }
+
+    /// Tokens available to the sections
+    pub fn available(&self) -> Option<usize> {
+        let margin = self.context_window * MARGIN_PERCENT / 100;
+        self.context_window
+            .checked_sub(margin + self.reserved_output + self.fixed_tokens)
+    }
+
+    /// Shorten the sections until the prompt fits
+    pub fn fit(self) -> FittedPrompt {
+        let sizes: Vec<usize> = self.sections.iter().map(|(_, text)| self.encoding.count(text)).collect();
+        let available = self.available();
+
+        // Smallest sections first, each taking at most an even share of what's left
+        let mut allowances = sizes.clone();
+        if let Some(mut remaining) = available.filter(|&room| room < sizes.iter().sum()) {
+            let mut order: Vec<usize> = (0..sizes.len()).collect();
+            order.sort_by_key(|&i| sizes[i]);
+            for (placed, &i) in order.iter().enumerate() {
+                let share = remaining / (order.len() - placed);
+                allowances[i] = sizes[i].min(share);
+                remaining -= allowances[i];
+            }
+        } else if available.is_none() {
+            allowances.iter_mut().for_each(|allowance| *allowance = 0);
+        }
+
+        let mut truncated = Vec::new();
+        let mut prompt_tokens = self.fixed_tokens;
+        let sections = self
+            .sections
+            .into_iter()
+            .zip(sizes.iter().zip(&allowances))
+            .map(|((name, text), (&size, &allowance))| {
+                if allowance >= size {
+                    prompt_tokens += size;
+                    return (name, text);
+                }
+                let text = truncate_to(self.encoding, &text, allowance);
+                prompt_tokens += self.encoding.count(&text);
+                truncated.push(name.clone());
+                (name, text)
+            })
+            .collect();
+
+        FittedPrompt {
+            sections,
+            truncated,
+            prompt_tokens,
+            fits: available.is_some(),
+        }
+    }
+}
+
+impl FittedPrompt {
+    /// The fitted text of the section called `name`; empty if there's none
+    pub fn text(&self, name: &str) -> &str {
+        self.sections
+            .iter()
+            .find(|(section, _)| section == name)
+            .map_or("", |(_, text)| text.as_str())
+    }
+}
+
+/// Note ending a section that lost `lines` lines
+fn omission_note(lines: usize) -> String {
+    format!("\n[… {} more line(s) left out to fit the model's context window]", lines)
+}
+
+/// The leading whole lines of `text` that fit in `max_tokens` with the
+/// omission note; the start of the first line if even that doesn't fit, and
+/// nothing if the note doesn't
+fn truncate_to(encoding: Encoding, text: &str, max_tokens: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let Some(room) = max_tokens.checked_sub(encoding.count(&omission_note(lines.len()))) else {
+        return String::new();
+    };
+
+    let mut kept = 0;
+    let mut used = 0;
+    for line in &lines {
+        let cost = encoding.count(line) + 1;
+        if used + cost > room {
+            break;
+        }
+        used += cost;
+        kept += 1;
+    }
+    if kept > 0 {
+        return lines[..kept].join("\n") + &omission_note(lines.len() - kept);
+    }
+
+    // The first line alone is too long: keep as many of its characters as fit
+    let first = lines.first().copied().unwrap_or_default();
+    let boundaries: Vec<usize> = first.char_indices().map(|(i, _)| i).skip(1).collect();
+    let end = boundaries.partition_point(|&end| encoding.count(&first[..end]) <= room);
+    if end == 0 {
+        return String::new();
+    }
+    first[..boundaries[end - 1]].to_string() + &omission_note(lines.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn netlist(lines: usize) -> String {
+        (0..lines)
+            .map(|i| format!("- Net N{}: U1 pin {} to R{} pin 1", i, i, i))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_everything_fits() {
+        let fitted = PromptBudget::new("grok-3", 10_000)
+            .reserve_output(800)
+            .fixed("What does U1 do?")
+            .section("netlist", netlist(20))
+            .fit();
+        assert!(fitted.fits);
+        assert!(fitted.truncated.is_empty());
+        assert_eq!(fitted.text("netlist"), netlist(20));
+        assert_eq!(fitted.text("missing"), "");
+    }
+
+    #[test]
+    fn test_sections_share_the_room_left() {
+        let budget = PromptBudget::new("grok-3", 2_000)
+            .reserve_output(500)
+            .fixed("What does U1 do?")
+            .section("erc", "- [unconnected_pin] U1 pin 3 is not connected")
+            .section("netlist", netlist(200));
+        let available = budget.available().unwrap();
+        let fitted = budget.fit();
+
+        assert!(fitted.fits);
+        assert_eq!(fitted.truncated, ["netlist"]);
+        // The small section is kept whole; the large one gets the rest
+        assert_eq!(fitted.text("erc"), "- [unconnected_pin] U1 pin 3 is not connected");
+        let kept = fitted.text("netlist");
+        assert!(kept.starts_with("- Net N0: U1 pin 0 to R0 pin 1\n"));
+        assert!(kept.ends_with("line(s) left out to fit the model's context window]"));
+        assert!(fitted.prompt_tokens <= 2_000 - 100 - 500);
+        assert!(fitted.prompt_tokens > available / 2);
+    }
+
+    #[test]
+    fn test_large_sections_split_the_room_evenly() {
+        let fitted = PromptBudget::new("grok-3", 3_000)
+            .reserve_output(0)
+            .section("a", netlist(300))
+            .section("b", netlist(300))
+            .fit();
+        let (a, b) = (Encoding::Standard.count(fitted.text("a")), Encoding::Standard.count(fitted.text("b")));
+        assert_eq!(fitted.truncated, ["a", "b"]);
+        assert!(a.abs_diff(b) <= 20, "{} vs {}", a, b);
+    }
+
+    #[test]
+    fn test_long_single_line_is_cut_mid_line() {
+        let line = "decoupling ".repeat(400);
+        let fitted = PromptBudget::new("grok-3", 1_000).reserve_output(0).section("notes", line.clone()).fit();
+        let kept = fitted.text("notes");
+        assert!(kept.starts_with("decoupling decoupling"));
+        assert!(kept.len() < line.len());
+        assert!(kept.ends_with("[… 1 more line(s) left out to fit the model's context window]"));
+    }
+
+    #[test]
+    fn test_fixed_text_overflowing_drops_every_section() {
+        let fitted = PromptBudget::new("grok-3", 1_000)
+            .reserve_output(900)
+            .fixed(&netlist(20))
+            .section("netlist", netlist(5))
+            .fit();
+        assert!(!fitted.fits);
+        assert_eq!(fitted.truncated, ["netlist"]);
+        assert_eq!(fitted.text("netlist"), "");
+    }
+}
@@ -0,0 +1,604 @@
+// USAGE:
+// cargo test prompts -- --nocapture
+use minijinja::{Environment, UndefinedBehavior};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::error::PromptError;
+
+/// Prompt for summarizing one commit: `repo`, `commit`, `commit_url`,
+/// `instructions`, `erc_section`, `warnings_section`
+pub const COMMIT_SUMMARY: &str = "commit_summary";
+/// Prompt for a commit's one-line blurb: `repo`, `commit`, `message` and
+/// `changes` (the component and net diff)
+pub const COMMIT_BLURB: &str = "commit_blurb";
+/// System prompt for questions about selected parts: `schematic_context`
+pub const SELECTION_SUMMARY: &str = "selection_summary";
+/// Prompt for a whole-project overview: `repo`, `commit`, `files`,
+/// `instructions`, and optionally `sheets` (summaries, each with `title` and
+/// `text`) and `categories` (lines like "regulator/ldo: U2 AMS1117-3.3")
+pub const REPO_OVERVIEW: &str = "repo_overview";
+/// Prompt for summarizing part of a sheet's netlist: `repo`, `sheet`, `part`,
+/// `parts`, `content`
+pub const CHUNK_SUMMARY: &str = "chunk_summary";
+/// Prompt for merging summaries of parts into one: `repo`, `scope` (what the
+/// parts make up, e.g. "the sheet /Power/"), `summaries` (each with `title` and `text`)
+pub const MERGE_SUMMARIES: &str = "merge_summaries";
+/// Prompt for a narrative of the changes between two commits: `repo`,
+/// `base`, `head`, `commits` (their messages, one per line), `changes` (the
+/// component and net diff) and `instructions`
+pub const COMPARE_SUMMARY: &str = "compare_summary";
+/// Prompt for the release notes of a tag range: `repo`, `from_tag`, `to_tag`
+/// and `changes` (each commit's blurb, under the heading of its section)
+pub const RELEASE_NOTES: &str = "release_notes";
+/// Prompt for a commit's design-review checklist: `repo`, `commit`,
+/// `message`, `changes` (the component and net diff), `erc` (introduced
+/// findings, one per line; may be empty) and `max_items`
+pub const REVIEW_CHECKLIST: &str = "review_checklist";
+/// Prompt for turning a described change into schematic edits: `repo`,
+/// `commit`, `change`, `components` (one per line, with value, properties
+/// and the net on each pin) and `max_edits`
+pub const SUGGEST_FIX: &str = "suggest_fix";
+/// Prompt for answering a question about one commit's design: `repo`,
+/// `commit`, `question`, `bom` (one line per BOM line), `summaries` (stored
+/// summaries of recent commits) and `sources` (retrieved context, each
+/// "[S1] title" then its text); the last two may be empty
+pub const ASK: &str = "ask";
+/// System prompt for chat; all optional: `repo`, `commit`, `sources` (retrieved
+/// context, each with `id`, `title` and `text`)
+pub const CHAT_SYSTEM: &str = "chat_system";
+/// Prompt for folding a chat's older turns into its running summary: `repo`
+/// (optional), `summary` (the earlier summary, may be empty) and `turns`
+/// (each with `role` and `content`)
+pub const CHAT_HISTORY_SUMMARY: &str = "chat_history_summary";
+/// Prompt for placing parts in the component taxonomy: `taxonomy` (one
+/// "category: subcategories" line per category) and `parts` (one numbered
+/// "N. value | footprint | MPN" line per part)
+pub const COMPONENT_TAXONOMY: &str = "component_taxonomy";
+
+/// Templates compiled into the binary, so every name always resolves; where a
+/// name has several versions the newest is used
+const BUILTIN: &[(&str, i32, &str)] = &[
+    (COMMIT_SUMMARY, 1, include_str!("../prompts/commit_summary.v1.j2")),
+    (COMMIT_SUMMARY, 2, include_str!("../prompts/commit_summary.v2.j2")),
+    (COMMIT_BLURB, 1, include_str!("../prompts/commit_blurb.v1.j2")),
+    (SELECTION_SUMMARY, 1, include_str!("../prompts/selection_summary.v1.j2")),
+    (REPO_OVERVIEW, 1, include_str!("../prompts/repo_overview.v1.j2")),
+    (REPO_OVERVIEW, 2, include_str!("../prompts/repo_overview.v2.j2")),
+    (REPO_OVERVIEW, 3, include_str!("../prompts/repo_overview.v3.j2")),
+    (CHUNK_SUMMARY, 1, include_str!("../prompts/chunk_summary.v1.j2")),
+    (MERGE_SUMMARIES, 1, include_str!("../prompts/merge_summaries.v1.j2")),
+    (COMPARE_SUMMARY, 1, include_str!("../prompts/compare_summary.v1.j2")),
+    (RELEASE_NOTES, 1, include_str!("../prompts/release_notes.v1.j2")),
+    (REVIEW_CHECKLIST, 1, include_str!("../prompts/review_checklist.v1.j2")),
+    (SUGGEST_FIX, 1, include_str!("../prompts/suggest_fix.v1.j2")),
+    (ASK, 1, include_str!("../prompts/ask.v1.j2")),
+    (CHAT_SYSTEM, 1, include_str!("../prompts/chat_system.v1.j2")),
+    (CHAT_SYSTEM, 2, include_str!("../prompts/chat_system.v2.j2")),
+    (CHAT_HISTORY_SUMMARY, 1, include_str!("../prompts/chat_history_summary.v1.j2")),
+    (COMPONENT_TAXONOMY, 1, include_str!("../prompts/component_taxonomy.v1.j2")),
+];
+
+/// Where a template was loaded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptOrigin {
+    Builtin,
+    File(PathBuf),
+    Database,
+}
+
+/// One version of a named prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub version: i32,
+    pub source: String,
+    pub origin: PromptOrigin,
+}
+
+impl PromptTemplate {
+    /// Name and version, as stored alongside generated output: "commit_summary@v2"
+    pub fn label(&self) -> String {
+        format!("{}@v{}", self.name, self.version)
+    }
+}
+
+/// A rendered prompt and the template version it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedPrompt {
+    pub text: String,
+    pub name: String,
+    pub version: i32,
+}
+
+impl RenderedPrompt {
+    pub fn label(&self) -> String {
+        format!("{}@v{}", self.name, self.version)
+    }
+}
+
+/// The current version of every named prompt
+///
+/// Starts from the built-in templates; files and database rows can add newer
+/// versions. For each name the highest version wins, and a later source
+/// replaces an earlier one at the same version; older versions are still
+/// rendered when asked for by label (`commit_summary@v1`), e.g. by
+/// experiments comparing them.
+///
+/// ```ignore
+/// let mut prompts = PromptLibrary::builtin();
+/// prompts.load_dir("prompts/")?;
+/// prompts.load_from_db(&pool).await?;
+/// let prompt = prompts.render(COMMIT_SUMMARY, context)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct PromptLibrary {
+    /// Every version loaded of each name
+    templates: BTreeMap<String, BTreeMap<i32, PromptTemplate>>,
+}
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    // Printing a variable the caller didn't pass is an error rather than an
+    // empty string, but `{% if var %}` still works for optional sections
+    env.set_undefined_behavior(UndefinedBehavior::SemiStrict);
+    env
+}
+
+/// Name and version from a template file name like `commit_summary.v2.j2`
+fn parse_file_name(file_name: &str) -> Option<(&str, i32)> {
+    let stem = file_name.strip_suffix(".j2")?;
+    let (name, version) = stem.rsplit_once(".v")?;
+    let version = version.parse().ok().filter(|v| *v > 0)?;
+    (!name.is_empty()).then_some((name, version))
+}
+
+/// Name and version from a label like `commit_summary@v2`
+fn parse_label(label: &str) -> Option<(&str, i32)> {
+    let (name, version) = label.rsplit_once("@v")?;
+    let version = version.parse().ok().filter(|v| *v > 0)?;
+    (!name.is_empty()).then_some((name, version))
+}
+
+impl PromptLibrary {
+    /// Only the templates shipped with the crate
+    pub fn builtin() -> Self {
+        let mut library = Self {
+            templates: BTreeMap::new(),
+        };
+        for (name, version, source) in BUILTIN {
+            library
+                .insert(PromptTemplate {
+                    name: name.to_string(),
+                    version: *version,
+                    source: source.to_string(),
+                    origin: PromptOrigin::Builtin,
+                })
+                .expect("built-in prompt templates are valid");
+        }
+        library
+    }
+
+    /// Add a template, replacing any loaded earlier with the same name and
+    /// version; older versions stay available by label
+    ///
+    /// The source is compiled up front so a broken template fails at load time,
+    /// not on the first request that uses it.
+    pub fn insert(&mut self, template: PromptTemplate) -> Result<(), PromptError> {
+        environment()
+            .template_from_str(&template.source)
+            .map_err(|source| PromptError::Syntax {
+                label: template.label(),
+                source,
+            })?;
+        self.templates
+            .entry(template.name.clone())
+            .or_default()
+            .insert(template.version, template);
+        Ok(())
+    }
+
+    /// Load every `<name>.v<version>.j2` file in `dir`
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> Result<usize, PromptError> {
+        let dir = dir.as_ref();
+        let io_error = |path: &Path| {
+            let path = path.to_path_buf();
+            move |source| PromptError::Io { path, source }
+        };
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .map_err(io_error(dir))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<_, _>>()
+            .map_err(io_error(dir))?;
+        paths.sort();
+
+        let mut loaded = 0;
+        for path in paths {
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let Some((name, version)) = parse_file_name(file_name) else {
+                warn!(
+                    "Skipping {}: prompt files are named <name>.v<version>.j2",
+                    path.display()
+                );
+                continue;
+            };
+            let source = std::fs::read_to_string(&path).map_err(io_error(&path))?;
+            self.insert(PromptTemplate {
+                name: name.to_string(),
+                version,
+                source,
+                origin: PromptOrigin::File(path.clone()),
+            })?;
+            loaded += 1;
+        }
+        info!("Loaded {} prompt templates from {}", loaded, dir.display());
+        Ok(loaded)
+    }
+
+    /// Load every row of the `prompt_templates` table
+    pub async fn load_from_db(&mut self, pool: &PgPool) -> Result<usize, PromptError> {
+        let rows = sqlx::query("SELECT name, version, body FROM prompt_templates ORDER BY name, version")
+            .fetch_all(pool)
+            .await?;
+        let loaded = rows.len();
+        for row in rows {
+            self.insert(PromptTemplate {
+                name: row.try_get("name")?,
+                version: row.try_get("version")?,
+                source: row.try_get("body")?,
+                origin: PromptOrigin::Database,
+            })?;
+        }
+        Ok(loaded)
+    }
+
+    /// The current version of a prompt, or with a label like
+    /// `commit_summary@v1` that version
+    pub fn get(&self, name: &str) -> Option<&PromptTemplate> {
+        match parse_label(name) {
+            Some((name, version)) => self.templates.get(name)?.get(&version),
+            None => self.templates.get(name)?.values().next_back(),
+        }
+    }
+
+    /// The current version of every prompt, by name
+    pub fn templates(&self) -> impl Iterator<Item = &PromptTemplate> {
+        self.templates.values().filter_map(|versions| versions.values().next_back())
+    }
+
+    /// Render the current version of `name` (or the version a label names)
+    /// with the given variables
+    pub fn render(&self, name: &str, context: impl Serialize) -> Result<RenderedPrompt, PromptError> {
+        let template = self
+            .get(name)
+            .ok_or_else(|| PromptError::UnknownTemplate(name.to_string()))?;
+        let text = environment()
+            .render_str(&template.source, context)
+            .map_err(|source| PromptError::Render {
+                label: template.label(),
+                source,
+            })?;
+        Ok(RenderedPrompt {
+            text,
+            name: template.name.clone(),
+            version: template.version,
+        })
+    }
+}
+
+/// Publish a new template version; it's picked up the next time the library loads
+pub async fn store_prompt_template(
+    pool: &PgPool,
+    name: &str,
+    version: i32,
+    body: &str,
+) -> Result<(), PromptError> {
+    environment()
+        .template_from_str(body)
+        .map_err(|source| PromptError::Syntax {
+            label: format!("{}@v{}", name, version),
+            source,
+        })?;
+    sqlx::query("INSERT INTO prompt_templates (name, version, body) VALUES ($1, $2, $3)")
+        .bind(name)
+        .bind(version)
+        .bind(body)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn template(name: &str, version: i32, source: &str) -> PromptTemplate {
+        PromptTemplate {
+            name: name.to_string(),
+            version,
+            source: source.to_string(),
+            origin: PromptOrigin::Builtin,
+        }
+    }
+
+    #[test]
+    fn test_builtin_templates_render() {
+        let prompts = PromptLibrary::builtin();
+        // Older built-in versions are kept in BUILTIN but superseded
+        let names: std::collections::BTreeSet<_> = BUILTIN.iter().map(|(name, _, _)| name).collect();
+        assert_eq!(prompts.templates().count(), names.len());
+
+        let prompt = prompts
+            .render(
+                COMMIT_SUMMARY,
+                json!({
+                    "repo": "https://github.com/a/b.git",
+                    "commit": "abc123",
+                    "commit_url": "https://github.com/a/b/commit/abc123",
+                    "instructions": "Be brief.",
+                    "erc_section": "",
+                    "warnings_section": "",
+                }),
+            )
+            .unwrap();
+        assert!(prompt.text.contains("https://github.com/a/b/commit/abc123"));
+        assert!(prompt.text.ends_with("Be brief."));
+        assert_eq!(prompt.label(), "commit_summary@v2");
+
+        let prompt = prompts
+            .render(SELECTION_SUMMARY, json!({ "schematic_context": "{\"components\": []}" }))
+            .unwrap();
+        assert!(prompt.text.ends_with("{\"components\": []}"));
+
+        let prompt = prompts
+            .render(
+                REPO_OVERVIEW,
+                json!({
+                    "repo": "a/b",
+                    "commit": "abc123",
+                    "files": ["main.kicad_sch", "power.kicad_sch"],
+                    "instructions": "",
+                }),
+            )
+            .unwrap();
+        assert!(prompt.text.contains("- power.kicad_sch"));
+        assert!(!prompt.text.contains("Summaries of its sheets"));
+        assert!(!prompt.text.contains("parts by category"));
+
+        let prompt = prompts
+            .render(
+                REPO_OVERVIEW,
+                json!({
+                    "repo": "a/b",
+                    "commit": "abc123",
+                    "files": ["main.kicad_sch"],
+                    "sheets": [{ "title": "/Power/", "text": "A 3.3 V LDO" }],
+                    "categories": ["regulator/ldo: U2 AMS1117-3.3"],
+                    "instructions": "Be brief.",
+                }),
+            )
+            .unwrap();
+        assert_eq!(prompt.label(), "repo_overview@v3");
+        assert!(prompt.text.contains("parts by category:\n- regulator/ldo: U2 AMS1117-3.3\n"));
+        assert!(prompt.text.contains("### /Power/\nA 3.3 V LDO"));
+        assert!(prompt.text.ends_with("Be brief."));
+
+        let prompt = prompts
+            .render(
+                CHUNK_SUMMARY,
+                json!({ "repo": "a/b", "sheet": "/Power/", "part": 2, "parts": 3, "content": "U1 (ic): 1=VIN" }),
+            )
+            .unwrap();
+        assert!(prompt.text.starts_with("Below is part 2 of 3 of the sheet /Power/"));
+        assert!(prompt.text.contains("\n\nU1 (ic): 1=VIN\n\n"));
+
+        let prompt = prompts
+            .render(
+                MERGE_SUMMARIES,
+                json!({
+                    "repo": "a/b",
+                    "scope": "the sheet /Power/",
+                    "summaries": [{ "title": "Part 1", "text": "An LDO" }, { "title": "Part 2", "text": "Its caps" }],
+                }),
+            )
+            .unwrap();
+        assert!(prompt.text.contains("### Part 1\nAn LDO\n\n### Part 2\nIts caps\n"));
+        assert!(prompt.text.ends_with("stay as short as the parts allow."));
+
+        let prompt = prompts
+            .render(
+                COMPARE_SUMMARY,
+                json!({
+                    "repo": "a/b",
+                    "base": "abc123",
+                    "head": "def456",
+                    "commits": "- abc124 Add LDO",
+                    "changes": "Components added: U2",
+                    "instructions": "Be brief.",
+                }),
+            )
+            .unwrap();
+        assert!(prompt.text.contains("from commit abc123 to commit def456"));
+        assert!(prompt.text.contains("oldest first:\n- abc124 Add LDO\n"));
+        assert!(prompt.text.ends_with("Be brief."));
+
+        let prompt = prompts
+            .render(
+                RELEASE_NOTES,
+                json!({
+                    "repo": "a/b",
+                    "from_tag": "v1.0",
+                    "to_tag": "v1.1",
+                    "changes": "### Power\n- abc1234 Swap the LDO",
+                }),
+            )
+            .unwrap();
+        assert!(prompt.text.contains("version v1.1 (changes since v1.0)"));
+        assert!(prompt.text.ends_with("### Power\n- abc1234 Swap the LDO"));
+
+        let variables = |erc: &str| {
+            json!({
+                "repo": "a/b",
+                "commit": "abc123",
+                "message": "Add buck converter",
+                "changes": "Components added: U3",
+                "erc": erc,
+                "max_items": 12,
+            })
+        };
+        let prompt = prompts.render(REVIEW_CHECKLIST, variables("")).unwrap();
+        assert!(prompt.text.contains("at most 12, each in the form"));
+        assert!(!prompt.text.contains("rule check violations"));
+        let prompt = prompts.render(REVIEW_CHECKLIST, variables("- [pin_not_connected] U3 pin 4")).unwrap();
+        assert!(prompt.text.ends_with("introduced:\n- [pin_not_connected] U3 pin 4"));
+
+        let prompt = prompts
+            .render(
+                SUGGEST_FIX,
+                json!({
+                    "repo": "a/b",
+                    "commit": "abc123",
+                    "change": "Make R1 4.7k",
+                    "components": "- R1 10k: 1=VIN 2=VOUT",
+                    "max_edits": 10,
+                }),
+            )
+            .unwrap();
+        assert!(prompt.text.contains("needs this change:\n\nMake R1 4.7k\n"));
+        assert!(prompt.text.contains("at most 10, one per line"));
+        assert!(prompt.text.ends_with("net on each pin:\n- R1 10k: 1=VIN 2=VOUT"));
+
+        let variables = |sources: &str| {
+            json!({
+                "repo": "a/b",
+                "commit": "abc123",
+                "question": "What does U3 do?",
+                "bom": "- 1x TPS7A02 (U3)",
+                "summaries": "",
+                "sources": sources,
+            })
+        };
+        let prompt = prompts.render(ASK, variables("")).unwrap();
+        assert!(prompt.text.contains("Question: What does U3 do?"));
+        assert!(prompt.text.ends_with("Bill of materials:\n- 1x TPS7A02 (U3)"));
+        let prompt = prompts.render(ASK, variables("[S1] Net VIN\nU3 pin 1")).unwrap();
+        assert!(prompt.text.ends_with("Retrieved context:\n[S1] Net VIN\nU3 pin 1"));
+
+        assert!(prompts.render(CHAT_SYSTEM, json!({})).unwrap().text.starts_with("You are Grok"));
+
+        let prompt = prompts
+            .render(
+                COMPONENT_TAXONOMY,
+                json!({ "taxonomy": "- mcu\n- regulator: ldo", "parts": "1. 10k | R_0603 | -" }),
+            )
+            .unwrap();
+        assert!(prompt.text.contains("(category: subcategories):\n- mcu\n- regulator: ldo\n"));
+        assert!(prompt.text.ends_with("Parts:\n1. 10k | R_0603 | -"));
+    }
+
+    #[test]
+    fn test_chat_system_with_sources() {
+        let prompts = PromptLibrary::builtin();
+        let plain = prompts.render(CHAT_SYSTEM, ()).unwrap().text;
+        assert!(!plain.contains("Retrieved Context"));
+        assert!(!plain.ends_with('\n'));
+
+        let prompt = prompts
+            .render(
+                CHAT_SYSTEM,
+                json!({
+                    "repo": "a/b",
+                    "sources": [
+                        { "id": "S1", "title": "Commit 1a2b3c4d", "text": "Added a P-FET on VIN" },
+                        { "id": "S2", "title": "Net VIN", "text": "Q1 pin 3, J1 pin 1" },
+                    ],
+                }),
+            )
+            .unwrap();
+        assert_eq!(prompt.label(), "chat_system@v2");
+        assert!(prompt.text.contains("KiCad project a/b."));
+        assert!(prompt.text.contains("e.g. [S1]"));
+        assert!(prompt.text.contains("[S2] Net VIN\nQ1 pin 3, J1 pin 1"));
+    }
+
+    #[test]
+    fn test_missing_variable_is_an_error() {
+        let prompts = PromptLibrary::builtin();
+        let err = prompts.render(SELECTION_SUMMARY, json!({})).unwrap_err();
+        assert!(err.to_string().contains("selection_summary@v1"));
+        assert!(matches!(
+            prompts.render("no_such_prompt", json!({})),
+            Err(PromptError::UnknownTemplate(_))
+        ));
+    }
+
+    #[test]
+    fn test_highest_version_wins() {
+        let mut prompts = PromptLibrary::builtin();
+        prompts.insert(template(CHAT_SYSTEM, 3, "v3")).unwrap();
+        prompts.insert(template(CHAT_SYSTEM, 2, "v2")).unwrap();
+        assert_eq!(prompts.get(CHAT_SYSTEM).unwrap().version, 3);
+
+        prompts.insert(template(CHAT_SYSTEM, 3, "v3 again")).unwrap();
+        assert_eq!(prompts.render(CHAT_SYSTEM, ()).unwrap().text, "v3 again");
+
+        let err = prompts.insert(template(CHAT_SYSTEM, 4, "{% if %}")).unwrap_err();
+        assert!(matches!(err, PromptError::Syntax { .. }));
+        assert_eq!(prompts.get(CHAT_SYSTEM).unwrap().version, 3);
+    }
+
+    #[test]
+    fn test_older_versions_render_by_label() {
+        let mut prompts = PromptLibrary::builtin();
+        prompts.insert(template(COMMIT_SUMMARY, 3, "v3")).unwrap();
+        assert_eq!(prompts.get(COMMIT_SUMMARY).unwrap().version, 3);
+
+        let rendered = prompts.render("commit_summary@v1", json!({
+            "repo": "o/r", "commit": "abc", "commit_url": "", "instructions": "", "erc_section": ""
+        }));
+        assert_eq!(rendered.unwrap().label(), "commit_summary@v1");
+        assert_eq!(prompts.render("commit_summary@v3", ()).unwrap().text, "v3");
+        assert!(matches!(
+            prompts.render("commit_summary@v9", ()),
+            Err(PromptError::UnknownTemplate(_))
+        ));
+        assert_eq!(prompts.templates().filter(|t| t.name == COMMIT_SUMMARY).count(), 1);
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        assert_eq!(parse_file_name("commit_summary.v2.j2"), Some(("commit_summary", 2)));
+        assert_eq!(parse_file_name("a.b.v10.j2"), Some(("a.b", 10)));
+        assert_eq!(parse_file_name("commit_summary.j2"), None);
+        assert_eq!(parse_file_name("commit_summary.v0.j2"), None);
+        assert_eq!(parse_file_name("commit_summary.v2.txt"), None);
+        assert_eq!(parse_file_name(".v2.j2"), None);
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = std::env::temp_dir().join(format!("kicad-prompts-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("chat_system.v2.j2"), "Hello from {{ repo }}").unwrap();
+        std::fs::write(dir.join("README.md"), "not a template").unwrap();
+
+        let mut prompts = PromptLibrary::builtin();
+        assert_eq!(prompts.load_dir(&dir).unwrap(), 1);
+        let template = prompts.get(CHAT_SYSTEM).unwrap();
+        assert_eq!(template.label(), "chat_system@v2");
+        assert_eq!(template.origin, PromptOrigin::File(dir.join("chat_system.v2.j2")));
+        assert_eq!(
+            prompts.render(CHAT_SYSTEM, json!({ "repo": "a/b" })).unwrap().text,
+            "Hello from a/b"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(prompts.load_dir(&dir), Err(PromptError::Io { .. })));
+    }
+}
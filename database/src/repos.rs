@@ -0,0 +1,220 @@
+// USAGE:
+// cargo test --test integration registered_repos -- --nocapture
+//
+// The repository registry: where each repo is cloned from, how to
+// authenticate, and per-repo processing preferences. Repos are keyed by the
+// same canonical URL as the rest of the schema.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, PgPool};
+
+use crate::organizations::OrgFilter;
+
+/// Which pushes a repo processes and what runs for them
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct ProcessingPolicy {
+    /// Globs of the branch names whose pushes are processed, e.g. `release/*`;
+    /// None processes every branch
+    #[sqlx(rename = "process_branches")]
+    pub branches: Option<Vec<String>>,
+    /// Process tag pushes
+    #[sqlx(rename = "process_tags")]
+    pub tags: bool,
+    /// Summarize new commits with AI as they are processed, rather than only
+    /// when a summary is asked for
+    #[sqlx(rename = "ai_summaries")]
+    pub ai: bool,
+    /// Render thumbnails of the schematic images uploaded for the repo
+    #[sqlx(rename = "render_thumbnails")]
+    pub render: bool,
+    /// Let AI-suggested schematic fixes be pushed to a branch and opened as
+    /// a pull request
+    pub suggest_fixes: bool,
+}
+
+impl Default for ProcessingPolicy {
+    fn default() -> Self {
+        Self {
+            branches: None,
+            tags: true,
+            ai: false,
+            render: true,
+            suggest_fixes: false,
+        }
+    }
+}
+
+/// What is stored when registering a repo
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepoRegistration {
+    /// Canonical URL, as used by `schematics` and the other tables
+    pub repo_url: String,
+    /// "owner/repo", or "host/group/project" outside GitHub
+    pub slug: String,
+    pub clone_url: String,
+    /// Branch to process; None follows the remote's HEAD
+    pub default_branch: Option<String>,
+    /// Environment variable holding an access token for cloning
+    pub auth_token_env: Option<String>,
+    /// Model for commit summaries instead of the server default
+    pub summary_model: Option<String>,
+    /// Prompt template for commit summaries instead of `commit_summary`
+    pub summary_prompt: Option<String>,
+    /// Webhook secret instead of the provider-wide one
+    pub webhook_secret: Option<String>,
+    /// Commits to clone up front; None clones the whole history
+    pub clone_depth: Option<i32>,
+    /// Pathspecs to check out, e.g. `*.kicad_sch`; None checks out everything
+    pub sparse_paths: Option<Vec<String>>,
+    /// Globs, e.g. `hardware/boards/**`, limiting which design files are processed
+    pub path_filter: Option<Vec<String>>,
+    /// Globs, e.g. `**/*.schematic`, of further files to read as schematics
+    pub schematic_globs: Option<Vec<String>>,
+    /// Fetch submodules, recursively, and read symbol libraries from them
+    pub submodules: bool,
+    pub processing: ProcessingPolicy,
+    /// Org the repo belongs to; None shares it with every caller
+    pub org_id: Option<i32>,
+}
+
+/// A registered repo
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct RegisteredRepo {
+    pub repo_url: String,
+    pub slug: String,
+    pub clone_url: String,
+    pub default_branch: Option<String>,
+    pub auth_token_env: Option<String>,
+    pub summary_model: Option<String>,
+    pub summary_prompt: Option<String>,
+    pub webhook_secret: Option<String>,
+    pub clone_depth: Option<i32>,
+    pub sparse_paths: Option<Vec<String>>,
+    pub path_filter: Option<Vec<String>>,
+    pub schematic_globs: Option<Vec<String>>,
+    pub submodules: bool,
+    #[sqlx(flatten)]
+    pub processing: ProcessingPolicy,
+    pub org_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Register a repo, replacing its settings if it's already registered to
+/// the same org. None if another org (or none) has registered it.
+pub async fn register_repo(
+    pool: &PgPool,
+    registration: &RepoRegistration,
+) -> Result<Option<RegisteredRepo>, Error> {
+    sqlx::query_as::<_, RegisteredRepo>(
+        r#"
+        INSERT INTO repos (repo_url, slug, clone_url, default_branch, auth_token_env,
+            summary_model, summary_prompt, webhook_secret, clone_depth, sparse_paths, path_filter,
+            submodules, process_branches, process_tags, ai_summaries, render_thumbnails, suggest_fixes,
+            org_id, schematic_globs)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)
+        ON CONFLICT (repo_url) DO UPDATE SET
+            slug = EXCLUDED.slug,
+            clone_url = EXCLUDED.clone_url,
+            default_branch = EXCLUDED.default_branch,
+            auth_token_env = EXCLUDED.auth_token_env,
+            summary_model = EXCLUDED.summary_model,
+            summary_prompt = EXCLUDED.summary_prompt,
+            webhook_secret = EXCLUDED.webhook_secret,
+            clone_depth = EXCLUDED.clone_depth,
+            sparse_paths = EXCLUDED.sparse_paths,
+            path_filter = EXCLUDED.path_filter,
+            schematic_globs = EXCLUDED.schematic_globs,
+            submodules = EXCLUDED.submodules,
+            process_branches = EXCLUDED.process_branches,
+            process_tags = EXCLUDED.process_tags,
+            ai_summaries = EXCLUDED.ai_summaries,
+            render_thumbnails = EXCLUDED.render_thumbnails,
+            suggest_fixes = EXCLUDED.suggest_fixes,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE repos.org_id IS NOT DISTINCT FROM EXCLUDED.org_id
+        RETURNING *
+        "#,
+    )
+    .bind(&registration.repo_url)
+    .bind(&registration.slug)
+    .bind(&registration.clone_url)
+    .bind(&registration.default_branch)
+    .bind(&registration.auth_token_env)
+    .bind(&registration.summary_model)
+    .bind(&registration.summary_prompt)
+    .bind(&registration.webhook_secret)
+    .bind(registration.clone_depth)
+    .bind(&registration.sparse_paths)
+    .bind(&registration.path_filter)
+    .bind(registration.submodules)
+    .bind(&registration.processing.branches)
+    .bind(registration.processing.tags)
+    .bind(registration.processing.ai)
+    .bind(registration.processing.render)
+    .bind(registration.processing.suggest_fixes)
+    .bind(registration.org_id)
+    .bind(&registration.schematic_globs)
+    .fetch_optional(pool)
+    .await
+}
+
+/// A registered repo by canonical URL
+pub async fn get_registered_repo(
+    pool: &PgPool,
+    repo_url: &str,
+) -> Result<Option<RegisteredRepo>, Error> {
+    sqlx::query_as::<_, RegisteredRepo>("SELECT * FROM repos WHERE repo_url = $1")
+        .bind(repo_url)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Registered repos of the orgs `orgs` lets through, by slug
+pub async fn list_registered_repos(pool: &PgPool, orgs: OrgFilter) -> Result<Vec<RegisteredRepo>, Error> {
+    sqlx::query_as::<_, RegisteredRepo>(
+        "SELECT * FROM repos WHERE ($1 OR org_id IS NOT DISTINCT FROM $2) ORDER BY slug",
+    )
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .fetch_all(pool)
+    .await
+}
+
+/// Replace the processing policy of a registered repo; None if it isn't registered
+pub async fn set_processing_policy(
+    pool: &PgPool,
+    repo_url: &str,
+    policy: &ProcessingPolicy,
+) -> Result<Option<RegisteredRepo>, Error> {
+    sqlx::query_as::<_, RegisteredRepo>(
+        r#"
+        UPDATE repos SET
+            process_branches = $2,
+            process_tags = $3,
+            ai_summaries = $4,
+            render_thumbnails = $5,
+            suggest_fixes = $6,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE repo_url = $1
+        RETURNING *
+        "#,
+    )
+    .bind(repo_url)
+    .bind(&policy.branches)
+    .bind(policy.tags)
+    .bind(policy.ai)
+    .bind(policy.render)
+    .bind(policy.suggest_fixes)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Remove a repo from the registry; stored commits are kept. False if it wasn't registered.
+pub async fn unregister_repo(pool: &PgPool, repo_url: &str) -> Result<bool, Error> {
+    let result = sqlx::query("DELETE FROM repos WHERE repo_url = $1")
+        .bind(repo_url)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
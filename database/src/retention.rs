@@ -0,0 +1,106 @@
+// USAGE:
+// cargo test --test integration soft_delete -- --nocapture
+//
+// Soft deletion and retention of stored commits. Deleting sets `deleted_at`,
+// which hides the commit (with its summaries, image, components and ERC
+// findings) from every query; `purge_deleted_schematics` hard-deletes rows
+// soft-deleted longer ago than the retention window. Storing a soft-deleted
+// commit again drops the old row first, so it starts afresh.
+use sqlx::{Error, PgConnection, PgPool};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::blob_store::{blob_store, schematic_pdfs_prefix};
+
+/// Soft-delete one stored commit; false if it isn't stored (or already deleted)
+pub async fn soft_delete_commit(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hash: &str,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE schematics SET deleted_at = CURRENT_TIMESTAMP
+        WHERE repo_url = $1 AND commit_hash = $2 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Soft-delete every stored commit of a repo and drop its re-sync schedule,
+/// commit comparisons, review checklists, stored answers, digest subscribers,
+/// processing high-water marks and schematic PDFs; returns how many commits
+/// were deleted
+pub async fn soft_delete_repo(pool: &PgPool, repo_url: &str) -> Result<u64, Error> {
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query(
+        "UPDATE schematics SET deleted_at = CURRENT_TIMESTAMP WHERE repo_url = $1 AND deleted_at IS NULL",
+    )
+    .bind(repo_url)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM repo_schedules WHERE repo_url = $1")
+        .bind(repo_url)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM commit_comparisons WHERE repo_url = $1")
+        .bind(repo_url)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM review_checklists WHERE repo_url = $1")
+        .bind(repo_url)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM commit_answers WHERE repo_url = $1")
+        .bind(repo_url)
+        .execute(&mut *tx)
+        .await?;
+    for table in ["digest_subscribers", "repo_digests", "processing_high_water_marks", "chat_sessions"] {
+        sqlx::query(&format!("DELETE FROM {} WHERE repo_url = $1", table))
+            .bind(repo_url)
+            .execute(&mut *tx)
+            .await?;
+    }
+    let store = blob_store();
+    let prefix = schematic_pdfs_prefix(repo_url);
+    if let Err(e) = store.delete_prefix(&mut tx, &prefix).await {
+        warn!("Failed to delete blobs under {} from {}: {}", prefix, store.name(), e);
+    }
+    tx.commit().await?;
+    Ok(result.rows_affected())
+}
+
+/// Hard-delete commits soft-deleted more than `retention` ago; returns how many
+pub async fn purge_deleted_schematics(pool: &PgPool, retention: Duration) -> Result<u64, Error> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM schematics
+        WHERE deleted_at IS NOT NULL
+            AND deleted_at <= CURRENT_TIMESTAMP - make_interval(secs => $1)
+        "#,
+    )
+    .bind(retention.as_secs_f64())
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Drop a soft-deleted row before the commit is stored again
+pub(crate) async fn forget_deleted(
+    conn: &mut PgConnection,
+    repo_url: &str,
+    commit_hash: &str,
+) -> Result<(), Error> {
+    sqlx::query(
+        "DELETE FROM schematics WHERE repo_url = $1 AND commit_hash = $2 AND deleted_at IS NOT NULL",
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,149 @@
+// USAGE:
+// cargo test --test integration review_checklists -- --nocapture
+//
+// Design-review checklists for a commit and the completion state of their
+// items. A checklist is stored whole: regenerating it replaces every item,
+// ticked or not.
+use chrono::{DateTime, Utc};
+use sqlx::{Error, FromRow, PgPool};
+
+/// Who wrote a commit's checklist, and when
+#[derive(Debug, Clone, FromRow)]
+pub struct ReviewChecklist {
+    pub repo_url: String,
+    pub commit_hash: String,
+    pub model: String,
+    pub prompt_version: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An item of a checklist, as stored
+#[derive(Debug, Clone, PartialEq, FromRow)]
+pub struct ChecklistItem {
+    pub id: i64,
+    pub position: i32,
+    pub category: String,
+    pub text: String,
+    pub components: Vec<String>,
+    pub done: bool,
+    pub done_at: Option<DateTime<Utc>>,
+}
+
+/// An item to store, not yet done
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewChecklistItem {
+    pub category: String,
+    pub text: String,
+    pub components: Vec<String>,
+}
+
+const ITEM_COLUMNS: &str = "id, position, category, text, components, done, done_at";
+
+/// The stored checklist of a commit with its items, in order
+pub async fn get_review_checklist(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hash: &str,
+) -> Result<Option<(ReviewChecklist, Vec<ChecklistItem>)>, Error> {
+    let checklist: Option<ReviewChecklist> = sqlx::query_as(
+        r#"
+        SELECT repo_url, commit_hash, model, prompt_version, created_at
+        FROM review_checklists
+        WHERE repo_url = $1 AND commit_hash = $2
+        "#,
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .fetch_optional(pool)
+    .await?;
+    let Some(checklist) = checklist else {
+        return Ok(None);
+    };
+
+    let items = sqlx::query_as(&format!(
+        "SELECT {ITEM_COLUMNS} FROM review_checklist_items WHERE repo_url = $1 AND commit_hash = $2 ORDER BY position"
+    ))
+    .bind(repo_url)
+    .bind(commit_hash)
+    .fetch_all(pool)
+    .await?;
+    Ok(Some((checklist, items)))
+}
+
+/// Store a commit's checklist, replacing any earlier one and its items;
+/// returns the items as stored
+pub async fn store_review_checklist(
+    pool: &PgPool,
+    checklist: &ReviewChecklist,
+    items: &[NewChecklistItem],
+) -> Result<Vec<ChecklistItem>, Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        r#"
+        INSERT INTO review_checklists (repo_url, commit_hash, model, prompt_version)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (repo_url, commit_hash) DO UPDATE SET
+            model = EXCLUDED.model,
+            prompt_version = EXCLUDED.prompt_version,
+            created_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(&checklist.repo_url)
+    .bind(&checklist.commit_hash)
+    .bind(&checklist.model)
+    .bind(&checklist.prompt_version)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM review_checklist_items WHERE repo_url = $1 AND commit_hash = $2")
+        .bind(&checklist.repo_url)
+        .bind(&checklist.commit_hash)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut stored = Vec::with_capacity(items.len());
+    for (position, item) in items.iter().enumerate() {
+        let row = sqlx::query_as(&format!(
+            r#"
+            INSERT INTO review_checklist_items (repo_url, commit_hash, position, category, text, components)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING {ITEM_COLUMNS}
+            "#
+        ))
+        .bind(&checklist.repo_url)
+        .bind(&checklist.commit_hash)
+        .bind(position as i32)
+        .bind(&item.category)
+        .bind(&item.text)
+        .bind(&item.components)
+        .fetch_one(&mut *tx)
+        .await?;
+        stored.push(row);
+    }
+    tx.commit().await?;
+    Ok(stored)
+}
+
+/// Tick off (or untick) an item of a commit's checklist; None if the commit
+/// has no item `id`
+pub async fn set_checklist_item_done(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hash: &str,
+    id: i64,
+    done: bool,
+) -> Result<Option<ChecklistItem>, Error> {
+    sqlx::query_as(&format!(
+        r#"
+        UPDATE review_checklist_items
+        SET done = $4, done_at = CASE WHEN $4 THEN COALESCE(done_at, CURRENT_TIMESTAMP) END
+        WHERE repo_url = $1 AND commit_hash = $2 AND id = $3
+        RETURNING {ITEM_COLUMNS}
+        "#
+    ))
+    .bind(repo_url)
+    .bind(commit_hash)
+    .bind(id)
+    .bind(done)
+    .fetch_optional(pool)
+    .await
+}
@@ -0,0 +1,161 @@
+// USAGE:
+// cargo test --test integration repo_schedules -- --nocapture
+//
+// Periodic re-sync schedules. Every repo with stored commits is re-checked at
+// the server's default interval unless its row in `repo_schedules` sets its
+// own interval or disables it. The caller passes the default in (0 means
+// repos without their own interval are never re-checked).
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, PgPool};
+
+use crate::organizations::OrgFilter;
+
+/// Known repos joined with their schedules; `$1` is the default interval in seconds
+const SCHEDULES_SQL: &str = r#"
+    WITH repos AS (
+        SELECT DISTINCT repo_url FROM schematics WHERE deleted_at IS NULL
+        UNION
+        SELECT repo_url FROM repo_schedules
+    ),
+    schedules AS (
+        SELECT r.repo_url,
+            COALESCE(rs.enabled, TRUE) AS enabled,
+            rs.interval_secs::BIGINT AS interval_secs,
+            COALESCE(rs.interval_secs::BIGINT, $1) AS effective_interval_secs,
+            rs.last_checked_at,
+            rs.last_error
+        FROM repos r
+        LEFT JOIN repo_schedules rs USING (repo_url)
+    )
+    SELECT *,
+        CASE WHEN enabled AND effective_interval_secs > 0 THEN
+            COALESCE(
+                last_checked_at + make_interval(secs => effective_interval_secs::DOUBLE PRECISION),
+                CURRENT_TIMESTAMP
+            )
+        END AS next_check_at
+    FROM schedules
+"#;
+
+/// When a repo is next re-checked, and how the last check went
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct RepoSchedule {
+    pub repo_url: String,
+    pub enabled: bool,
+    /// This repo's own interval; None uses the default
+    pub interval_secs: Option<i64>,
+    /// The interval in effect, 0 if none applies
+    pub effective_interval_secs: i64,
+    pub last_checked_at: Option<DateTime<Utc>>,
+    /// Error from the last check, None if it succeeded
+    pub last_error: Option<String>,
+    /// None when the repo is never re-checked
+    pub next_check_at: Option<DateTime<Utc>>,
+}
+
+/// Schedules of the known repos of the orgs `orgs` lets through, by repo URL
+pub async fn list_repo_schedules(
+    pool: &PgPool,
+    default_interval_secs: i64,
+    orgs: OrgFilter,
+) -> Result<Vec<RepoSchedule>, Error> {
+    sqlx::query_as::<_, RepoSchedule>(&format!(
+        r#"
+        SELECT s.* FROM ({}) s
+        LEFT JOIN repos r USING (repo_url)
+        WHERE ($2 OR r.org_id IS NOT DISTINCT FROM $3)
+        ORDER BY s.repo_url
+        "#,
+        SCHEDULES_SQL
+    ))
+    .bind(default_interval_secs)
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .fetch_all(pool)
+    .await
+}
+
+/// The schedule of one repo (whether or not it has stored commits)
+pub async fn get_repo_schedule(
+    pool: &PgPool,
+    repo_url: &str,
+    default_interval_secs: i64,
+) -> Result<Option<RepoSchedule>, Error> {
+    sqlx::query_as::<_, RepoSchedule>(&format!(
+        "SELECT * FROM ({}) s WHERE repo_url = $2",
+        SCHEDULES_SQL
+    ))
+    .bind(default_interval_secs)
+    .bind(repo_url)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Repos due for a re-check, longest overdue first
+pub async fn due_repo_schedules(
+    pool: &PgPool,
+    default_interval_secs: i64,
+    limit: i64,
+) -> Result<Vec<RepoSchedule>, Error> {
+    sqlx::query_as::<_, RepoSchedule>(&format!(
+        r#"
+        SELECT * FROM ({}) s
+        WHERE next_check_at <= CURRENT_TIMESTAMP
+        ORDER BY next_check_at, repo_url
+        LIMIT $2
+        "#,
+        SCHEDULES_SQL
+    ))
+    .bind(default_interval_secs)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Enable or disable re-checks for a repo; `None` interval uses the default
+pub async fn set_repo_schedule(
+    pool: &PgPool,
+    repo_url: &str,
+    enabled: bool,
+    interval_secs: Option<i32>,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO repo_schedules (repo_url, enabled, interval_secs, updated_at)
+        VALUES ($1, $2, $3, CURRENT_TIMESTAMP)
+        ON CONFLICT (repo_url) DO UPDATE SET
+            enabled = EXCLUDED.enabled,
+            interval_secs = EXCLUDED.interval_secs,
+            updated_at = EXCLUDED.updated_at
+        "#,
+    )
+    .bind(repo_url)
+    .bind(enabled)
+    .bind(interval_secs)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Record that a re-check finished now, with its error if it failed
+pub async fn record_repo_check(
+    pool: &PgPool,
+    repo_url: &str,
+    error: Option<&str>,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO repo_schedules (repo_url, last_checked_at, last_error)
+        VALUES ($1, CURRENT_TIMESTAMP, $2)
+        ON CONFLICT (repo_url) DO UPDATE SET
+            last_checked_at = EXCLUDED.last_checked_at,
+            last_error = EXCLUDED.last_error
+        "#,
+    )
+    .bind(repo_url)
+    .bind(error)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,31 @@
+// Native reader for KiCad schematic projects: parses .kicad_sch files,
+// follows sheet symbols from the root into one project model, and derives the
+// netlist, BOM and revision diffs over the whole hierarchy, flagging diffs
+// that look unintended. Symbols the sheets don't embed are looked up in
+// .kicad_sym libraries. Single sheets can be edited and written back. Legacy .sch projects of KiCad 4 and 5 and Eagle
+// schematics are read into the same model.
+pub mod anomalies;
+pub mod bom;
+pub mod diff;
+pub mod distilled;
+pub mod eagle;
+pub mod edit;
+pub mod hierarchy;
+pub mod legacy;
+pub mod library;
+pub mod model;
+pub mod netlist;
+pub mod render;
+pub mod sexpr;
+
+pub use anomalies::{detect_anomalies, DiffWarning, WarningKind};
+pub use bom::BomLine;
+pub use diff::{diff_projects, ComponentChange, NetChange, PinChange, ProjectDiff};
+pub use eagle::parse_eagle_sheet;
+pub use edit::SheetEdit;
+pub use hierarchy::{load_projects, load_projects_with_libraries, load_projects_with_parser, Component, Project, SheetInstance, SheetParser};
+pub use legacy::{parse_legacy_library, parse_legacy_sheet};
+pub use library::{parse_library, StandardLibraries, SymbolLibraries, SymbolLibrary};
+pub use netlist::{ConnectorKind, Net, NetConnector, NetGeometry, NetNode, NetPin, NetSheet};
+pub use model::SchematicFormat;
+pub use render::{render_sheet_svg, render_symbol_svg, TitleBlock};
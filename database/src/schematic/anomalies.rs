@@ -0,0 +1,301 @@
+// USAGE:
+// cargo test schematic::anomalies -- --nocapture
+//
+// Heuristics over a project diff that flag changes likely to be mistakes:
+// most of the parts deleted at once, a power net renamed out from under the
+// parts on it, a decoupling capacitor taken away from an IC that stays. Like
+// the ERC rules they err on the side of staying quiet; a warning asks the
+// reviewer to double-check, it doesn't say the change is wrong.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::diff::ProjectDiff;
+use super::hierarchy::{natural_key, Component, Project};
+use super::netlist::Net;
+
+/// Fewest removed components that count as a mass deletion
+const MASS_DELETION_MIN: usize = 5;
+/// Share of the previous components, in percent, a mass deletion removes
+const MASS_DELETION_PERCENT: usize = 30;
+/// How far from an IC on the same sheet, in mm, a capacitor still decouples it
+const DECOUPLING_RADIUS_MM: f64 = 30.0;
+
+/// Designator prefixes of integrated circuits
+const IC_PREFIXES: &[&str] = &["U", "IC"];
+/// Net names (without the sheet path) of supplies, compared in upper case
+const SUPPLY_NET_PREFIXES: &[&str] = &["VCC", "VDD", "VBAT", "VBUS", "VIN", "VSYS", "VREF", "VEE", "AVDD", "DVDD"];
+
+/// What kind of suspicious change a warning is about
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    /// Much of the design was deleted in one commit
+    MassDeletion,
+    /// A supply or ground net took another name
+    PowerNetRenamed,
+    /// A capacitor between a supply and ground next to an IC was removed
+    DecouplingRemoved,
+}
+
+impl WarningKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarningKind::MassDeletion => "mass_deletion",
+            WarningKind::PowerNetRenamed => "power_net_renamed",
+            WarningKind::DecouplingRemoved => "decoupling_removed",
+        }
+    }
+}
+
+/// A change that may not have been intended
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DiffWarning {
+    pub kind: WarningKind,
+    pub message: String,
+    /// Components involved, e.g. the removed capacitor and the IC it decoupled
+    pub references: Vec<String>,
+    /// Nets involved, e.g. a renamed net's old and new name
+    pub nets: Vec<String>,
+}
+
+/// The net name without its sheet path, in upper case
+fn base_name(net: &str) -> String {
+    net.rsplit('/').next().unwrap_or(net).to_uppercase()
+}
+
+fn is_ground(net: &str) -> bool {
+    let base = base_name(net);
+    base.contains("GND") || base.starts_with("VSS")
+}
+
+/// Whether a net carries a supply rather than a signal: by its name, e.g.
+/// `+3V3`, `VCC` or `5V`, or by a power pin on it
+fn is_supply(net: &Net) -> bool {
+    if net.name.starts_with("Net-(") || net.name.starts_with("unconnected-(") || is_ground(&net.name) {
+        return false;
+    }
+    let base = base_name(&net.name);
+    let voltage = base.trim_start_matches(['+', '-']);
+    voltage.starts_with(|c: char| c.is_ascii_digit()) && voltage.contains('V')
+        || SUPPLY_NET_PREFIXES.iter().any(|prefix| base.starts_with(prefix))
+        || net.nodes.iter().any(|node| node.pin_type == "power_in" || node.pin_type == "power_out")
+}
+
+fn is_power(net: &Net) -> bool {
+    is_supply(net) || (is_ground(&net.name) && !net.name.starts_with("Net-("))
+}
+
+fn prefix(reference: &str) -> String {
+    natural_key(reference).0
+}
+
+/// Flag suspicious changes in `diff`, the changes from `old` to `new`
+pub fn detect_anomalies(old: &Project, new: &Project, diff: &ProjectDiff) -> Vec<DiffWarning> {
+    let old_components = old.components();
+    let old_nets = old.netlist();
+    let mut warnings = Vec::new();
+    warnings.extend(mass_deletion(&old_components, diff));
+    warnings.extend(renamed_power_nets(&old_nets, &new.netlist(), diff));
+    warnings.extend(removed_decoupling(&old_components, &old_nets, diff));
+    warnings
+}
+
+fn mass_deletion(old_components: &[Component], diff: &ProjectDiff) -> Option<DiffWarning> {
+    let removed = diff.removed_components.len();
+    let total = old_components.len();
+    if removed < MASS_DELETION_MIN || removed * 100 < total * MASS_DELETION_PERCENT {
+        return None;
+    }
+    let mut message = format!("{} of {} components were removed", removed, total);
+    if !diff.removed_sheets.is_empty() {
+        message.push_str(&format!(", along with sheets {}", diff.removed_sheets.join(", ")));
+    }
+    Some(DiffWarning {
+        kind: WarningKind::MassDeletion,
+        message,
+        references: diff.removed_components.clone(),
+        nets: Vec::new(),
+    })
+}
+
+/// Removed power nets whose pins, half or more of them, are now on one net
+/// the commit added
+fn renamed_power_nets(old_nets: &[Net], new_nets: &[Net], diff: &ProjectDiff) -> Vec<DiffWarning> {
+    let added: BTreeSet<&str> = diff.added_nets.iter().map(String::as_str).collect();
+    let new_net_of_pin: BTreeMap<(&str, &str), &str> = new_nets
+        .iter()
+        .filter(|net| added.contains(net.name.as_str()))
+        .flat_map(|net| net.nodes.iter().map(move |n| ((n.reference.as_str(), n.pin.as_str()), net.name.as_str())))
+        .collect();
+
+    let mut warnings = Vec::new();
+    for net in old_nets.iter().filter(|net| diff.removed_nets.contains(&net.name) && is_power(net)) {
+        let mut followed: BTreeMap<&str, usize> = BTreeMap::new();
+        for node in &net.nodes {
+            if let Some(new_name) = new_net_of_pin.get(&(node.reference.as_str(), node.pin.as_str())) {
+                *followed.entry(new_name).or_default() += 1;
+            }
+        }
+        let Some((new_name, count)) = followed.into_iter().max_by_key(|(_, count)| *count) else {
+            continue;
+        };
+        if count * 2 < net.nodes.len() {
+            continue;
+        }
+        let mut references: Vec<String> = net.nodes.iter().map(|n| n.reference.clone()).collect();
+        references.dedup();
+        warnings.push(DiffWarning {
+            kind: WarningKind::PowerNetRenamed,
+            message: format!(
+                "Power net {} was renamed to {} ({} of {} pins moved with it); anything still expecting {} is no longer on it",
+                net.name,
+                new_name,
+                count,
+                net.nodes.len(),
+                net.name
+            ),
+            references,
+            nets: vec![net.name.clone(), new_name.to_string()],
+        });
+    }
+    warnings
+}
+
+/// Removed capacitors between a supply and ground, placed within
+/// [`DECOUPLING_RADIUS_MM`] of an IC on that supply which the commit kept
+fn removed_decoupling(old_components: &[Component], old_nets: &[Net], diff: &ProjectDiff) -> Vec<DiffWarning> {
+    let nets_of = |reference: &str| -> Vec<&Net> {
+        old_nets
+            .iter()
+            .filter(|net| net.nodes.iter().any(|n| n.reference == reference))
+            .collect()
+    };
+
+    let mut warnings = Vec::new();
+    for cap in old_components
+        .iter()
+        .filter(|c| prefix(&c.reference) == "C" && diff.removed_components.contains(&c.reference))
+    {
+        let nets = nets_of(&cap.reference);
+        let (Some(supply), Some(ground)) = (
+            nets.iter().find(|net| is_supply(net)),
+            nets.iter().find(|net| is_ground(&net.name)),
+        ) else {
+            continue;
+        };
+        let ics: Vec<&Component> = old_components
+            .iter()
+            .filter(|ic| {
+                IC_PREFIXES.contains(&prefix(&ic.reference).as_str())
+                    && !diff.removed_components.contains(&ic.reference)
+                    && ic.sheet_path == cap.sheet_path
+                    && (ic.position.0 - cap.position.0).hypot(ic.position.1 - cap.position.1) <= DECOUPLING_RADIUS_MM
+                    && supply.nodes.iter().any(|n| n.reference == ic.reference)
+            })
+            .collect();
+        if ics.is_empty() {
+            continue;
+        }
+        let ic_list: Vec<&str> = ics.iter().map(|ic| ic.reference.as_str()).collect();
+        warnings.push(DiffWarning {
+            kind: WarningKind::DecouplingRemoved,
+            message: format!(
+                "{} ({}) decoupling {} to {} was removed from next to {}, which is still there",
+                cap.reference,
+                cap.value,
+                supply.name,
+                ground.name,
+                ic_list.join(", ")
+            ),
+            references: std::iter::once(cap.reference.clone())
+                .chain(ic_list.iter().map(|r| r.to_string()))
+                .collect(),
+            nets: vec![supply.name.clone(), ground.name.clone()],
+        });
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::diff_projects;
+
+    fn load(sheet: &str) -> Project {
+        let sources: BTreeMap<String, String> =
+            [("top.kicad_sch".to_string(), sheet.to_string())].into_iter().collect();
+        Project::load("top.kicad_sch", &sources).unwrap()
+    }
+
+    const LIB: &str = r#"(lib_symbols
+        (symbol "Device:C" (symbol "C_1_1"
+            (pin passive line (at 0 3.81 270) (name "~") (number "1"))
+            (pin passive line (at 0 -3.81 90) (name "~") (number "2"))))
+        (symbol "Device:R" (symbol "R_1_1"
+            (pin passive line (at 0 3.81 270) (name "~") (number "1"))
+            (pin passive line (at 0 -3.81 90) (name "~") (number "2"))))
+        (symbol "MCU:Chip" (symbol "Chip_1_1"
+            (pin power_in line (at 0 3.81 270) (name "VCC") (number "1"))
+            (pin power_in line (at 0 -3.81 90) (name "GND") (number "2")))))"#;
+
+    /// U1 at the origin and `parts`, each labelled `supply` above and GND below
+    fn sheet(supply: &str, parts: &[(&str, &str, f64)]) -> String {
+        let mut sheet = format!(r#"(kicad_sch (uuid "top") {LIB}"#);
+        for (reference, lib_id, x) in std::iter::once(&("U1", "MCU:Chip", 0.0)).chain(parts) {
+            sheet.push_str(&format!(
+                r#"(symbol (lib_id "{lib_id}") (at {x} 0 0) (unit 1) (property "Reference" "{reference}") (property "Value" "100n"))
+                (label "{supply}" (at {x} -3.81 0)) (label "GND" (at {x} 3.81 0))"#
+            ));
+        }
+        sheet.push(')');
+        sheet
+    }
+
+    fn warnings(before: &str, after: &str) -> Vec<DiffWarning> {
+        let (old, new) = (load(before), load(after));
+        detect_anomalies(&old, &new, &diff_projects(&old, &new))
+    }
+
+    #[test]
+    fn test_removed_decoupling_cap_next_to_an_ic() {
+        let before = sheet("+3V3", &[("C1", "Device:C", 10.0), ("C2", "Device:C", 80.0)]);
+        let found = warnings(&before, &sheet("+3V3", &[("C2", "Device:C", 80.0)]));
+        assert_eq!(found.len(), 1, "{:?}", found);
+        assert_eq!(found[0].kind, WarningKind::DecouplingRemoved);
+        assert_eq!(found[0].references, ["C1", "U1"]);
+        assert_eq!(found[0].nets, ["/+3V3", "/GND"]);
+
+        // Too far from the IC to decouple it
+        assert!(warnings(&before, &sheet("+3V3", &[("C1", "Device:C", 10.0)])).is_empty());
+        // Resistors aren't decoupling
+        let resistor = sheet("+3V3", &[("R1", "Device:R", 10.0)]);
+        assert!(warnings(&resistor, &sheet("+3V3", &[])).is_empty());
+    }
+
+    #[test]
+    fn test_renamed_power_net() {
+        let parts = [("C1", "Device:C", 10.0)];
+        let found = warnings(&sheet("+3V3", &parts), &sheet("+3V3A", &parts));
+        assert_eq!(found.len(), 1, "{:?}", found);
+        assert_eq!(found[0].kind, WarningKind::PowerNetRenamed);
+        assert_eq!(found[0].nets, ["/+3V3", "/+3V3A"]);
+        assert!(found[0].message.contains("2 of 2 pins"), "{}", found[0].message);
+    }
+
+    #[test]
+    fn test_mass_deletion() {
+        let resistors: Vec<(String, f64)> = (1..=8).map(|i| (format!("R{}", i), 100.0 + 20.0 * i as f64)).collect();
+        let parts: Vec<(&str, &str, f64)> = resistors.iter().map(|(r, x)| (r.as_str(), "Device:R", *x)).collect();
+        let before = sheet("+3V3", &parts);
+
+        let found = warnings(&before, &sheet("+3V3", &parts[..3]));
+        assert_eq!(found.len(), 1, "{:?}", found);
+        assert_eq!(found[0].kind, WarningKind::MassDeletion);
+        assert_eq!(found[0].message, "5 of 9 components were removed");
+        assert_eq!(found[0].references, ["R4", "R5", "R6", "R7", "R8"]);
+
+        // A few parts at a time is ordinary editing
+        assert!(warnings(&before, &sheet("+3V3", &parts[..4])).is_empty());
+        assert!(warnings(&before, &before).is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+// USAGE:
+// cargo test schematic::bom -- --nocapture
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::hierarchy::{natural_key, Component, Project};
+use crate::components::MPN_PROPERTY_KEYS;
+
+/// One BOM line: identical parts grouped across every sheet
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BomLine {
+    pub value: String,
+    pub footprint: Option<String>,
+    pub mpn: Option<String>,
+    pub lib_id: String,
+    pub quantity: usize,
+    /// In natural order
+    pub references: Vec<String>,
+    /// Sheets the parts are placed on, e.g. `/Power/`
+    pub sheets: Vec<String>,
+}
+
+fn mpn_of(component: &Component) -> Option<String> {
+    MPN_PROPERTY_KEYS.iter().find_map(|key| {
+        component
+            .properties
+            .get(*key)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty() && *v != "~")
+            .map(str::to_string)
+    })
+}
+
+impl Project {
+    /// Bill of materials for the whole hierarchy
+    ///
+    /// Parts marked DNP or excluded from the BOM are left out. Lines are
+    /// grouped by value, footprint and MPN and ordered by their first reference.
+    pub fn bom(&self) -> Vec<BomLine> {
+        let mut lines: BTreeMap<(String, Option<String>, Option<String>), BomLine> = BTreeMap::new();
+        for component in self.components() {
+            if !component.in_bom || component.dnp {
+                continue;
+            }
+            let mpn = mpn_of(&component);
+            let line = lines
+                .entry((component.value.clone(), component.footprint.clone(), mpn.clone()))
+                .or_insert_with(|| BomLine {
+                    value: component.value.clone(),
+                    footprint: component.footprint.clone(),
+                    mpn,
+                    lib_id: component.lib_id.clone(),
+                    quantity: 0,
+                    references: Vec::new(),
+                    sheets: Vec::new(),
+                });
+            line.quantity += 1;
+            line.references.push(component.reference.clone());
+            if !line.sheets.contains(&component.sheet_path) {
+                line.sheets.push(component.sheet_path.clone());
+            }
+        }
+
+        // components() is already in natural order, so references are too
+        let mut lines: Vec<BomLine> = lines.into_values().collect();
+        for line in &mut lines {
+            line.sheets.sort();
+        }
+        lines.sort_by_key(|line| natural_key(&line.references[0]));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bom_groups_across_sheets() {
+        let child = r#"(kicad_sch (uuid "c")
+            (symbol (lib_id "Device:R") (at 0 0 0) (unit 1) (uuid "r")
+                (property "Reference" "R?") (property "Value" "10k")
+                (property "Footprint" "R_0402") (property "MPN" "RC0402")
+                (instances (project "p"
+                    (path "/root/a" (reference "R10"))
+                    (path "/root/b" (reference "R2"))))))"#;
+        let root = r#"(kicad_sch (uuid "root")
+            (symbol (lib_id "Device:C") (at 0 0 0) (unit 1) (in_bom yes) (dnp no)
+                (property "Reference" "C1") (property "Value" "100n"))
+            (symbol (lib_id "Device:C") (at 0 0 0) (unit 1) (in_bom yes) (dnp yes)
+                (property "Reference" "C2") (property "Value" "100n"))
+            (sheet (at 0 0) (uuid "a") (property "Sheetname" "A") (property "Sheetfile" "c.kicad_sch"))
+            (sheet (at 0 0) (uuid "b") (property "Sheetname" "B") (property "Sheetfile" "c.kicad_sch")))"#;
+        let sources = [
+            ("r.kicad_sch".to_string(), root.to_string()),
+            ("c.kicad_sch".to_string(), child.to_string()),
+        ]
+        .into_iter()
+        .collect();
+
+        let bom = Project::load("r.kicad_sch", &sources).unwrap().bom();
+        assert_eq!(bom.len(), 2);
+        assert_eq!(bom[0].references, ["C1"]);
+        assert_eq!(bom[1].references, ["R2", "R10"]);
+        assert_eq!(bom[1].quantity, 2);
+        assert_eq!(bom[1].mpn.as_deref(), Some("RC0402"));
+        assert_eq!(bom[1].sheets, ["/A/", "/B/"]);
+    }
+}
@@ -0,0 +1,332 @@
+// USAGE:
+// cargo test schematic::diff -- --nocapture
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::hierarchy::{natural_key, Component, Project};
+use super::netlist::Net;
+
+/// A field of a component that differs between two revisions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ComponentChange {
+    pub reference: String,
+    /// `value`, `footprint`, `lib_id`, `sheet` or `dnp`
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// A pin of a component present in both revisions that's now on another net
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PinChange {
+    pub reference: String,
+    pub pin: String,
+    pub old_net: String,
+    pub new_net: String,
+}
+
+/// Pins that joined or left a net present in both revisions, as `R1.2`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NetChange {
+    pub name: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Differences between two revisions of a project, over the whole hierarchy
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ProjectDiff {
+    pub added_sheets: Vec<String>,
+    pub removed_sheets: Vec<String>,
+    pub added_components: Vec<String>,
+    pub removed_components: Vec<String>,
+    pub changed_components: Vec<ComponentChange>,
+    /// Pins of components present in both revisions whose net changed
+    pub changed_pins: Vec<PinChange>,
+    pub added_nets: Vec<String>,
+    pub removed_nets: Vec<String>,
+    pub changed_nets: Vec<NetChange>,
+}
+
+fn fields(component: &Component) -> [(&'static str, Option<String>); 5] {
+    [
+        ("value", Some(component.value.clone())),
+        ("footprint", component.footprint.clone()),
+        ("lib_id", Some(component.lib_id.clone())),
+        ("sheet", Some(component.sheet_path.clone())),
+        ("dnp", Some(component.dnp.to_string())),
+    ]
+}
+
+fn pins(net: &Net) -> BTreeSet<String> {
+    net.nodes
+        .iter()
+        .map(|n| format!("{}.{}", n.reference, n.pin))
+        .collect()
+}
+
+/// Nets named after their first pin rather than a label or power symbol
+fn is_unnamed(net: &str) -> bool {
+    net.starts_with("Net-(") || net.starts_with("unconnected-(")
+}
+
+/// The net each pin is on, as `R1.2`
+fn net_of_pins(nets: &BTreeMap<String, BTreeSet<String>>) -> BTreeMap<&str, &str> {
+    nets.iter()
+        .flat_map(|(name, pins)| pins.iter().map(move |pin| (pin.as_str(), name.as_str())))
+        .collect()
+}
+
+/// Compare two revisions; pass `Project::default()` for a side that has no schematic
+pub fn diff_projects(old: &Project, new: &Project) -> ProjectDiff {
+    let mut diff = ProjectDiff::default();
+
+    let old_sheets: BTreeSet<&str> = old.instances.iter().map(|i| i.sheet_path.as_str()).collect();
+    let new_sheets: BTreeSet<&str> = new.instances.iter().map(|i| i.sheet_path.as_str()).collect();
+    diff.added_sheets = new_sheets.difference(&old_sheets).map(|s| s.to_string()).collect();
+    diff.removed_sheets = old_sheets.difference(&new_sheets).map(|s| s.to_string()).collect();
+
+    let old_components = old.components();
+    let new_components = new.components();
+    let old_by_ref: BTreeMap<&str, &Component> =
+        old_components.iter().map(|c| (c.reference.as_str(), c)).collect();
+    let new_by_ref: BTreeMap<&str, &Component> =
+        new_components.iter().map(|c| (c.reference.as_str(), c)).collect();
+
+    for component in &new_components {
+        match old_by_ref.get(component.reference.as_str()) {
+            None => diff.added_components.push(component.reference.clone()),
+            Some(previous) => {
+                for ((field, old_value), (_, new_value)) in
+                    fields(previous).into_iter().zip(fields(component))
+                {
+                    if old_value != new_value {
+                        diff.changed_components.push(ComponentChange {
+                            reference: component.reference.clone(),
+                            field: field.to_string(),
+                            old: old_value,
+                            new: new_value,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    diff.removed_components = old_components
+        .iter()
+        .filter(|c| !new_by_ref.contains_key(c.reference.as_str()))
+        .map(|c| c.reference.clone())
+        .collect();
+
+    let old_nets: BTreeMap<String, BTreeSet<String>> =
+        old.netlist().iter().map(|n| (n.name.clone(), pins(n))).collect();
+    let new_nets: BTreeMap<String, BTreeSet<String>> =
+        new.netlist().iter().map(|n| (n.name.clone(), pins(n))).collect();
+    for (name, new_pins) in &new_nets {
+        match old_nets.get(name) {
+            None => diff.added_nets.push(name.clone()),
+            Some(old_pins) if old_pins != new_pins => diff.changed_nets.push(NetChange {
+                name: name.clone(),
+                added: new_pins.difference(old_pins).cloned().collect(),
+                removed: old_pins.difference(new_pins).cloned().collect(),
+            }),
+            Some(_) => {}
+        }
+    }
+    diff.removed_nets = old_nets
+        .keys()
+        .filter(|name| !new_nets.contains_key(*name))
+        .cloned()
+        .collect();
+
+    // A pin on an unnamed net hasn't moved just because the net took the name
+    // of another first pin; it has if none of its old neighbours came along
+    let old_pin_nets = net_of_pins(&old_nets);
+    let new_pin_nets = net_of_pins(&new_nets);
+    for component in &new_components {
+        if !old_by_ref.contains_key(component.reference.as_str()) {
+            continue;
+        }
+        let prefix = format!("{}.", component.reference);
+        let mut moved: Vec<PinChange> = new_pin_nets
+            .range(prefix.as_str()..)
+            .take_while(|(pin, _)| pin.starts_with(&prefix))
+            .filter_map(|(&pin, &new_net)| {
+                let old_net = *old_pin_nets.get(pin)?;
+                if old_net == new_net {
+                    return None;
+                }
+                if is_unnamed(old_net) && is_unnamed(new_net) {
+                    let (before, after) = (&old_nets[old_net], &new_nets[new_net]);
+                    let stayed = before.intersection(after).any(|other| other != pin);
+                    if stayed || (before.len() == 1 && after.len() == 1) {
+                        return None;
+                    }
+                }
+                Some(PinChange {
+                    reference: component.reference.clone(),
+                    pin: pin[prefix.len()..].to_string(),
+                    old_net: old_net.to_string(),
+                    new_net: new_net.to_string(),
+                })
+            })
+            .collect();
+        moved.sort_by_key(|change| natural_key(&change.pin));
+        diff.changed_pins.extend(moved);
+    }
+
+    diff
+}
+
+impl ProjectDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == ProjectDiff::default()
+    }
+
+    /// Plain-text change list, one line per change, for overviews and prompts
+    pub fn describe(&self) -> String {
+        let mut out = String::new();
+        let list = |items: &[String]| items.join(", ");
+
+        if !self.added_sheets.is_empty() {
+            let _ = writeln!(out, "Sheets added: {}", list(&self.added_sheets));
+        }
+        if !self.removed_sheets.is_empty() {
+            let _ = writeln!(out, "Sheets removed: {}", list(&self.removed_sheets));
+        }
+        if !self.added_components.is_empty() {
+            let _ = writeln!(out, "Components added: {}", list(&self.added_components));
+        }
+        if !self.removed_components.is_empty() {
+            let _ = writeln!(out, "Components removed: {}", list(&self.removed_components));
+        }
+        for change in &self.changed_components {
+            let _ = writeln!(
+                out,
+                "{} {}: {} -> {}",
+                change.reference,
+                change.field,
+                change.old.as_deref().unwrap_or("(none)"),
+                change.new.as_deref().unwrap_or("(none)")
+            );
+        }
+        for change in &self.changed_pins {
+            let _ = writeln!(
+                out,
+                "{} pin {} moved from {} to {}",
+                change.reference, change.pin, change.old_net, change.new_net
+            );
+        }
+        if !self.added_nets.is_empty() {
+            let _ = writeln!(out, "Nets added: {}", list(&self.added_nets));
+        }
+        if !self.removed_nets.is_empty() {
+            let _ = writeln!(out, "Nets removed: {}", list(&self.removed_nets));
+        }
+        for change in &self.changed_nets {
+            let _ = write!(out, "Net {}:", change.name);
+            if !change.added.is_empty() {
+                let _ = write!(out, " +{}", list(&change.added));
+            }
+            if !change.removed.is_empty() {
+                let _ = write!(out, " -{}", list(&change.removed));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn load(root: &str, child: &str) -> Project {
+        let sources: BTreeMap<String, String> = [
+            ("top.kicad_sch".to_string(), root.to_string()),
+            ("child.kicad_sch".to_string(), child.to_string()),
+        ]
+        .into_iter()
+        .collect();
+        Project::load("top.kicad_sch", &sources).unwrap()
+    }
+
+    const LIB: &str = r#"(lib_symbols (symbol "Device:R" (symbol "R_1_1"
+        (pin passive line (at 0 3.81 270) (name "~") (number "1"))
+        (pin passive line (at 0 -3.81 90) (name "~") (number "2")))))"#;
+
+    #[test]
+    fn test_diff_sees_child_sheets() {
+        let root = r#"(kicad_sch (uuid "root")
+            (sheet (at 0 0) (uuid "s") (property "Sheetname" "Power") (property "Sheetfile" "child.kicad_sch")))"#;
+        let before = format!(
+            r#"(kicad_sch (uuid "c") {LIB}
+            (symbol (lib_id "Device:R") (at 0 0 0) (unit 1) (property "Reference" "R1") (property "Value" "1k"))
+            (label "VIN" (at 0 -3.81 0)))"#
+        );
+        let after = format!(
+            r#"(kicad_sch (uuid "c") {LIB}
+            (symbol (lib_id "Device:R") (at 0 0 0) (unit 1) (property "Reference" "R1") (property "Value" "2k"))
+            (symbol (lib_id "Device:R") (at 20 0 0) (unit 1) (property "Reference" "R2") (property "Value" "1k"))
+            (label "VIN" (at 0 -3.81 0))
+            (label "VIN" (at 20 -3.81 0)))"#
+        );
+
+        let diff = diff_projects(&load(root, &before), &load(root, &after));
+        assert_eq!(diff.added_components, ["R2"]);
+        assert_eq!(diff.changed_components.len(), 1);
+        assert_eq!(diff.changed_components[0].field, "value");
+        assert_eq!(diff.changed_nets[0].name, "/Power/VIN");
+        assert_eq!(diff.changed_nets[0].added, ["R2.1"]);
+
+        let text = diff.describe();
+        assert!(text.contains("Components added: R2"));
+        assert!(text.contains("R1 value: 1k -> 2k"));
+        assert!(text.contains("Net /Power/VIN: +R2.1"));
+
+        let empty = diff_projects(&load(root, &after), &load(root, &after));
+        assert!(empty.is_empty());
+
+        let initial = diff_projects(&Project::default(), &load(root, &before));
+        assert_eq!(initial.added_sheets, ["/", "/Power/"]);
+    }
+
+    #[test]
+    fn test_diff_reports_moved_pins() {
+        let root = r#"(kicad_sch (uuid "root")
+            (sheet (at 0 0) (uuid "s") (property "Sheetname" "Power") (property "Sheetfile" "child.kicad_sch")))"#;
+        // R2 pin 2 meets R3 pin 1 on an unnamed net
+        let sheet = |label: &str, extra: &str| {
+            format!(
+                r#"(kicad_sch (uuid "c") {LIB}
+                (symbol (lib_id "Device:R") (at 0 0 0) (unit 1) (property "Reference" "R1") (property "Value" "1k"))
+                (label "{label}" (at 0 -3.81 0))
+                (symbol (lib_id "Device:R") (at 20 0 0) (unit 1) (property "Reference" "R2") (property "Value" "1k"))
+                (symbol (lib_id "Device:R") (at 20 7.62 0) (unit 1) (property "Reference" "R3") (property "Value" "1k"))
+                {extra})"#
+            )
+        };
+        let before = sheet("VIN", "");
+        // R0 joins the unnamed net at R2 pin 2, which renames it
+        let after = sheet(
+            "5V",
+            r#"(symbol (lib_id "Device:R") (at 20 3.81 90) (unit 1) (property "Reference" "R0") (property "Value" "0R"))
+               (wire (pts (xy 16.19 3.81) (xy 20 3.81)))"#,
+        );
+
+        let diff = diff_projects(&load(root, &before), &load(root, &after));
+        assert!(diff.added_nets.iter().any(|net| net.starts_with("Net-(R0-")));
+        assert_eq!(
+            diff.changed_pins,
+            [PinChange {
+                reference: "R1".into(),
+                pin: "1".into(),
+                old_net: "/Power/VIN".into(),
+                new_net: "/Power/5V".into(),
+            }]
+        );
+        assert!(diff.describe().contains("R1 pin 1 moved from /Power/VIN to /Power/5V"));
+    }
+}
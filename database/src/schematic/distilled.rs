@@ -0,0 +1,175 @@
+// USAGE:
+// cargo test schematic::distilled -- --nocapture
+//
+// Renders a project in the distilled JSON format the Python distiller emits
+// (components keyed by reference, nets, proximities), so everything
+// downstream of `store_distilled_json` works unchanged.
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use super::hierarchy::{natural_key, Component, Project};
+
+/// Components closer than this (in mm) are reported as neighbours
+const PROXIMITY_RADIUS_MM: f64 = 20.0;
+
+fn category(component: &Component) -> &'static str {
+    let reference = component.reference.to_uppercase();
+    let lib = component.lib_id.to_lowercase();
+    if reference.starts_with('C') || lib.contains("cap") {
+        "capacitor"
+    } else if reference.starts_with('U') || lib.contains("mcu") || lib.contains("ic") {
+        "ic"
+    } else if reference.starts_with('R') || lib.contains("res") {
+        "resistor"
+    } else if reference.starts_with('L') || lib.contains("ind") {
+        "inductor"
+    } else if reference.starts_with('Q') || lib.contains("transistor") {
+        "transistor"
+    } else {
+        "other"
+    }
+}
+
+fn proximity_weight(a: &str, b: &str, a_ref: &str, b_ref: &str) -> f64 {
+    let mut weight = match (a, b) {
+        ("capacitor", "ic") | ("ic", "capacitor") => 2.0,
+        ("capacitor", "other") | ("other", "capacitor") => 1.2,
+        _ => 1.0,
+    };
+    let ic_cap = matches!((a, b), ("capacitor", "ic") | ("ic", "capacitor"));
+    if ic_cap && (a_ref.to_uppercase().starts_with('U') || b_ref.to_uppercase().starts_with('U')) {
+        weight *= 3.0;
+    }
+    weight
+}
+
+/// Nearby parts on the same sheet, scored like the Python distiller
+fn proximities(components: &[Component]) -> Vec<Value> {
+    let mut edges = Vec::new();
+    for (i, a) in components.iter().enumerate() {
+        for b in &components[i + 1..] {
+            if a.sheet_path != b.sheet_path {
+                continue;
+            }
+            let (cat_a, cat_b) = (category(a), category(b));
+            let ic_cap = matches!((cat_a, cat_b), ("capacitor", "ic") | ("ic", "capacitor"));
+            let radius = if ic_cap { PROXIMITY_RADIUS_MM * 1.5 } else { PROXIMITY_RADIUS_MM };
+            let distance = (a.position.0 - b.position.0).hypot(a.position.1 - b.position.1);
+            if distance > radius {
+                continue;
+            }
+            let weight = proximity_weight(cat_a, cat_b, &a.reference, &b.reference);
+            let base = ((PROXIMITY_RADIUS_MM - distance) / PROXIMITY_RADIUS_MM).max(0.0);
+            edges.push(json!({
+                "ref_a": a.reference,
+                "ref_b": b.reference,
+                "distance_mm": distance,
+                "score": base * weight,
+                "category_a": cat_a,
+                "category_b": cat_b,
+                "weight": weight,
+            }));
+        }
+    }
+    edges
+}
+
+impl Project {
+    /// The project as distilled JSON, with hierarchy-wide nets
+    pub fn to_distilled(&self) -> Value {
+        let components = self.components();
+        let nets = self.netlist();
+
+        let mut pins_by_ref: HashMap<&str, Vec<Value>> = HashMap::new();
+        let mut net_map = Map::new();
+        for net in &nets {
+            let mut members = Map::new();
+            for node in &net.nodes {
+                pins_by_ref.entry(&node.reference).or_default().push(json!({
+                    "number": node.pin,
+                    "name": node.pin_name,
+                    "net": net.name,
+                }));
+                members
+                    .entry(node.reference.clone())
+                    .or_insert_with(|| Value::Array(Vec::new()))
+                    .as_array_mut()
+                    .expect("net members are arrays")
+                    .push(json!({ "Pin": node.pin }));
+            }
+            net_map.insert(net.name.clone(), Value::Object(members));
+        }
+
+        let mut component_map = Map::new();
+        for component in &components {
+            let mut pins = pins_by_ref.remove(component.reference.as_str()).unwrap_or_default();
+            pins.sort_by(|a, b| {
+                let key = |v: &Value| natural_key(v["number"].as_str().unwrap_or(""));
+                key(a).cmp(&key(b))
+            });
+            component_map.insert(
+                component.reference.clone(),
+                json!({
+                    "lib_id": component.lib_id,
+                    "value": component.value,
+                    "position": { "x": component.position.0, "y": component.position.1 },
+                    "rotation": component.rotation,
+                    "bbox": component.bounds.map(|((x1, y1), (x2, y2))| json!({ "x1": x1, "y1": y1, "x2": x2, "y2": y2 })),
+                    "footprint": component.footprint,
+                    "properties": component.properties,
+                    "category": category(component),
+                    "pins": pins,
+                    "sheet_path": component.sheet_path,
+                }),
+            );
+        }
+
+        json!({
+            "components": component_map,
+            "nets": net_map,
+            "proximities": proximities(&components),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components_from_distilled, run_erc};
+
+    #[test]
+    fn test_distilled_feeds_existing_consumers() {
+        let sheet = r#"(kicad_sch (uuid "a")
+            (lib_symbols (symbol "Device:C" (symbol "C_1_1"
+                (pin passive line (at 0 3.81 270) (name "~") (number "1"))
+                (pin passive line (at 0 -3.81 90) (name "~") (number "2")))))
+            (symbol (lib_id "Device:C") (at 0 0 0) (unit 1) (property "Reference" "C1")
+                (property "Value" "100n") (property "Footprint" "C_0402"))
+            (symbol (lib_id "MCU:Chip") (at 10 0 0) (unit 1) (property "Reference" "U1")
+                (property "Value" "Chip"))
+            (label "VDD" (at 0 -3.81 0))
+            (label "GND" (at 0 3.81 0)))"#;
+        let sources = [("a.kicad_sch".to_string(), sheet.to_string())].into_iter().collect();
+        let distilled = Project::load("a.kicad_sch", &sources).unwrap().to_distilled();
+
+        assert_eq!(distilled["components"]["C1"]["category"], "capacitor");
+        assert_eq!(distilled["components"]["C1"]["sheet_path"], "/");
+        assert_eq!(distilled["components"]["C1"]["pins"][0]["net"], "/VDD");
+        // Body and pins of C1 span its two pin tips; U1's symbol isn't known
+        assert_eq!(distilled["components"]["C1"]["bbox"], json!({ "x1": 0.0, "y1": -3.81, "x2": 0.0, "y2": 3.81 }));
+        assert_eq!(distilled["components"]["U1"]["bbox"], Value::Null);
+        assert_eq!(distilled["nets"]["/GND"]["C1"][0]["Pin"], "2");
+
+        let proximities = distilled["proximities"].as_array().unwrap();
+        assert_eq!(proximities.len(), 1);
+        assert_eq!(proximities[0]["weight"], 6.0);
+
+        let mut records = components_from_distilled(&distilled);
+        records.sort_by(|a, b| a.reference.cmp(&b.reference));
+        assert_eq!(records[0].footprint.as_deref(), Some("C_0402"));
+        assert_eq!(records[0].sheet.as_deref(), Some("/"));
+        assert_eq!((records[0].x, records[0].y, records[0].rotation), (Some(0.0), Some(0.0), Some(0)));
+        assert_eq!(records[0].bbox_max_y, Some(3.81));
+        assert!(run_erc(&distilled).is_empty());
+    }
+}
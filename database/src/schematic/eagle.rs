@@ -0,0 +1,565 @@
+// USAGE:
+// cargo test schematic::eagle -- --nocapture
+//
+// Reader for Eagle's XML schematics (Eagle 6 on), read into the same model as
+// KiCad sheets so teams migrating from Eagle get netlists, BOMs and diffs of
+// their old history. Symbols come from the libraries embedded in the file.
+// Eagle sheets share nets by name rather than through a hierarchy, so they
+// are laid side by side on one sheet, and every pin a net names gets a global
+// label of the net's name. Eagle counts in mm with Y up; sheets are flipped to
+// Y down. Modules, buses, text and frames are skipped.
+use roxmltree::{Document, Node, ParsingOptions};
+use std::collections::{BTreeMap, HashMap};
+
+use super::model::{
+    Fill, Label, LabelKind, LibGraphic, LibPin, LibSymbol, PlacedSymbol, Point, SchematicFormat, SheetFile, Shape,
+    TextNote,
+};
+use crate::error::SchematicError;
+
+/// Space left between Eagle sheets laid side by side, in mm
+const SHEET_GAP_MM: f64 = 50.0;
+
+/// Whether `text` (with any byte order mark and leading space trimmed) is an
+/// Eagle schematic, as opposed to a board or library
+pub(crate) fn is_eagle_schematic(start: &str) -> bool {
+    let head = start.get(..1024).unwrap_or(start);
+    start.starts_with('<') && head.contains("<eagle") && start.contains("<schematic")
+}
+
+fn children<'a, 'input>(node: Node<'a, 'input>, tag: &'static str) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(move |n| n.has_tag_name(tag))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, tag: &'static str) -> Option<Node<'a, 'input>> {
+    children(node, tag).next()
+}
+
+/// Children of `node`'s `group` element, e.g. the `part`s in `parts`
+fn grouped<'a, 'input>(
+    node: Node<'a, 'input>,
+    group: &'static str,
+    tag: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    child(node, group).into_iter().flat_map(move |g| children(g, tag))
+}
+
+fn attr<'a>(node: Node<'a, '_>, name: &str) -> &'a str {
+    node.attribute(name).unwrap_or("")
+}
+
+fn number(node: Node, name: &str) -> Option<f64> {
+    node.attribute(name)?.parse().ok()
+}
+
+fn xy(node: Node, x: &str, y: &str) -> Option<(f64, f64)> {
+    Some((number(node, x)?, number(node, y)?))
+}
+
+/// Rotation and mirror of an Eagle `rot` such as `R90` or `MR180`, rounded
+/// to a quarter turn; `S` (spin) only affects text
+fn rotation_of(rot: &str) -> (i32, Option<char>) {
+    let rot = rot.trim_start_matches('S');
+    let (mirror, rot) = match rot.strip_prefix('M') {
+        Some(rest) => (Some('y'), rest),
+        None => (None, rot),
+    };
+    let degrees: f64 = rot.trim_start_matches('R').parse().unwrap_or(0.0);
+    (((degrees / 90.0).round() as i32 * 90).rem_euclid(360), mirror)
+}
+
+/// A library in the file, keyed by name and (Eagle 9) URN, as parts refer to it
+struct Library<'a, 'input> {
+    symbols: HashMap<&'a str, Node<'a, 'input>>,
+    devicesets: HashMap<&'a str, Node<'a, 'input>>,
+}
+
+/// A part's device: its symbol, and each pin's Eagle name in symbol order
+struct Device {
+    lib_id: String,
+    symbol: LibSymbol,
+    /// Unit the gate of each name is drawn as
+    gates: HashMap<String, u32>,
+    pin_names: Vec<String>,
+    package: Option<String>,
+    /// From the device's technology of the part
+    attributes: BTreeMap<String, String>,
+}
+
+fn electrical_type(direction: &str) -> &'static str {
+    match direction {
+        "nc" => "no_connect",
+        "in" => "input",
+        "out" => "output",
+        "oc" => "open_collector",
+        "hiz" => "tri_state",
+        "pas" => "passive",
+        "pwr" | "sup" => "power_in",
+        _ => "bidirectional",
+    }
+}
+
+/// A symbol's body shape: a (curved) wire, rectangle, circle or polygon
+fn read_graphic(node: Node, unit: u32) -> Option<LibGraphic> {
+    let (shape, fill) = match node.tag_name().name() {
+        "wire" => {
+            let (start, end) = (xy(node, "x1", "y1")?, xy(node, "x2", "y2")?);
+            match number(node, "curve").filter(|curve| *curve != 0.0) {
+                // The arc turns `curve` degrees counterclockwise from start to end
+                Some(curve) => {
+                    let bulge = (curve.to_radians() / 4.0).tan() / 2.0;
+                    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+                    let mid = ((start.0 + end.0) / 2.0 + dy * bulge, (start.1 + end.1) / 2.0 - dx * bulge);
+                    (Shape::Arc { start, mid, end }, Fill::None)
+                }
+                None => (Shape::Polyline { points: vec![start, end] }, Fill::None),
+            }
+        }
+        "rectangle" => (
+            Shape::Rectangle {
+                start: xy(node, "x1", "y1")?,
+                end: xy(node, "x2", "y2")?,
+            },
+            Fill::Outline,
+        ),
+        "circle" => {
+            let fill = if number(node, "width") == Some(0.0) { Fill::Outline } else { Fill::None };
+            (
+                Shape::Circle {
+                    center: xy(node, "x", "y")?,
+                    radius: number(node, "radius")?,
+                },
+                fill,
+            )
+        }
+        "polygon" => {
+            let mut points: Vec<(f64, f64)> = children(node, "vertex").filter_map(|v| xy(v, "x", "y")).collect();
+            points.extend(points.first().copied());
+            (Shape::Polyline { points }, Fill::Outline)
+        }
+        _ => return None,
+    };
+    Some(LibGraphic {
+        unit,
+        body_style: 0,
+        shape,
+        fill,
+    })
+}
+
+/// The device `device` of `deviceset`, with every gate's symbol as a unit
+///
+/// Gates only placed on request (the power pins of logic ICs) become pins of
+/// every unit, hidden, so they join the nets of their names like KiCad's
+/// hidden power pins.
+fn read_device(library_name: &str, library: &Library, deviceset: Node, device: Node, technology: &str) -> Device {
+    let device_name = attr(device, "name");
+    let pads: HashMap<(&str, &str), &str> = grouped(device, "connects", "connect")
+        .map(|c| ((attr(c, "gate"), attr(c, "pin")), attr(c, "pad")))
+        .collect();
+    let package = device.attribute("package").map(|p| format!("{}:{}", library_name, p));
+
+    let mut symbol = LibSymbol::default();
+    let mut gates = HashMap::new();
+    let mut pin_names = Vec::new();
+    let mut all_supply = true;
+    for (index, gate) in grouped(deviceset, "gates", "gate").enumerate() {
+        let gate_name = attr(gate, "name");
+        let on_request = attr(gate, "addlevel") == "request";
+        let unit = if on_request { 0 } else { index as u32 + 1 };
+        gates.insert(gate_name.to_string(), unit);
+        let Some(&gate_symbol) = library.symbols.get(attr(gate, "symbol")) else { continue };
+        for pin in children(gate_symbol, "pin") {
+            let Some(at) = xy(pin, "x", "y") else { continue };
+            let name = attr(pin, "name");
+            let direction = pin.attribute("direction").unwrap_or("io");
+            all_supply &= direction == "sup";
+            // Pins on several pads are one pin; without a package the pin name stands in
+            let number = match pads.get(&(gate_name, name)) {
+                Some(pads) => pads.split_whitespace().collect::<Vec<_>>().join(","),
+                None => name.to_string(),
+            };
+            let length = match pin.attribute("length").unwrap_or("long") {
+                "point" => 0.0,
+                "short" => 2.54,
+                "middle" => 5.08,
+                _ => 7.62,
+            };
+            symbol.pins.push(LibPin {
+                number,
+                // `GND@2` is the second pin named GND
+                name: name.split('@').next().unwrap_or(name).to_string(),
+                electrical_type: electrical_type(direction).to_string(),
+                unit,
+                body_style: 0,
+                hidden: on_request && direction == "pwr",
+                at,
+                orientation: rotation_of(attr(pin, "rot")).0,
+                length,
+            });
+            pin_names.push(name.to_string());
+        }
+        if !on_request {
+            symbol.graphics.extend(gate_symbol.children().filter_map(|n| read_graphic(n, unit)));
+        }
+    }
+    // Supply symbols (GND, VCC) have no package and only supply pins
+    symbol.power = package.is_none() && all_supply && !symbol.pins.is_empty();
+
+    let attributes = grouped(device, "technologies", "technology")
+        .filter(|t| attr(*t, "name") == technology)
+        .flat_map(|t| children(t, "attribute"))
+        .map(|a| (attr(a, "name").to_string(), attr(a, "value").to_string()))
+        .collect();
+    Device {
+        lib_id: format!("{}:{}{}", library_name, attr(deviceset, "name"), device_name),
+        symbol,
+        gates,
+        pin_names,
+        package,
+        attributes,
+    }
+}
+
+/// Leftmost and rightmost x of what a sheet places, in mm
+fn horizontal_extent(sheet: Node) -> Option<(f64, f64)> {
+    let instances = grouped(sheet, "instances", "instance").filter_map(|i| number(i, "x"));
+    let wires = grouped(sheet, "nets", "net")
+        .flat_map(|net| children(net, "segment"))
+        .flat_map(|segment| children(segment, "wire"))
+        .flat_map(|wire| [number(wire, "x1"), number(wire, "x2")])
+        .flatten();
+    instances
+        .chain(wires)
+        .fold(None, |extent, x| match extent {
+            None => Some((x, x)),
+            Some((min, max)) => Some((f64::min(min, x), f64::max(max, x))),
+        })
+}
+
+/// Parse one Eagle schematic; `file` is only used in error messages
+pub fn parse_eagle_sheet(file: &str, text: &str) -> Result<SheetFile, SchematicError> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let document = Document::parse_with_options(text.trim_start_matches('\u{feff}'), options).map_err(|e| {
+        SchematicError::Syntax {
+            file: file.to_string(),
+            line: e.pos().row as usize,
+            column: e.pos().col as usize,
+            message: e.to_string(),
+        }
+    })?;
+    let Some(schematic) = document.descendants().find(|n| n.has_tag_name("schematic")) else {
+        return Err(SchematicError::NotASchematic {
+            file: file.to_string(),
+            found: document.root_element().tag_name().name().to_string(),
+        });
+    };
+
+    let libraries: HashMap<(&str, &str), Library> = grouped(schematic, "libraries", "library")
+        .map(|library| {
+            let named = |group, tag| grouped(library, group, tag).map(|n| (attr(n, "name"), n)).collect();
+            let key = (attr(library, "name"), attr(library, "urn"));
+            (key, Library {
+                symbols: named("symbols", "symbol"),
+                devicesets: named("devicesets", "deviceset"),
+            })
+        })
+        .collect();
+
+    let mut sheet = SheetFile {
+        format: SchematicFormat::Eagle,
+        ..Default::default()
+    };
+    let mut parts: HashMap<&str, (Node, Device)> = HashMap::new();
+    for part in grouped(schematic, "parts", "part") {
+        let library_name = attr(part, "library");
+        let Some(library) = libraries.get(&(library_name, attr(part, "library_urn"))) else { continue };
+        let Some(&deviceset) = library.devicesets.get(attr(part, "deviceset")) else { continue };
+        let device_name = attr(part, "device");
+        let Some(device) = grouped(deviceset, "devices", "device").find(|d| attr(*d, "name") == device_name) else {
+            continue;
+        };
+        let device = read_device(library_name, library, deviceset, device, attr(part, "technology"));
+        sheet.lib_symbols.entry(device.lib_id.clone()).or_insert_with(|| device.symbol.clone());
+        parts.insert(attr(part, "name"), (part, device));
+    }
+
+    let mut offset = 0.0;
+    let mut next_left = None;
+    for eagle_sheet in grouped(schematic, "sheets", "sheet") {
+        let extent = horizontal_extent(eagle_sheet);
+        if let (Some(left), Some((min, _))) = (next_left, extent) {
+            offset = left - min;
+        }
+        if let Some((_, max)) = extent {
+            next_left = Some(offset + max + SHEET_GAP_MM);
+        }
+        let at = |node: Node, x: &str, y: &str| xy(node, x, y).map(|(x, y)| Point::from_mm(x + offset, -y));
+
+        // Where each part's gate puts its pins, by Eagle pin name
+        let mut pins: HashMap<(&str, &str, &str), Point> = HashMap::new();
+        for instance in grouped(eagle_sheet, "instances", "instance") {
+            let Some((part, device)) = parts.get(attr(instance, "part")) else { continue };
+            let Some((x, y)) = xy(instance, "x", "y") else { continue };
+            let gate = attr(instance, "gate");
+            let (rotation, mirror) = rotation_of(attr(instance, "rot"));
+            let mut properties = device.attributes.clone();
+            for attribute in children(*part, "attribute") {
+                properties.insert(attr(attribute, "name").to_string(), attr(attribute, "value").to_string());
+            }
+            properties.insert("Reference".to_string(), attr(*part, "name").to_string());
+            // Parts without a value of their own show their device's name
+            let device_name = device.lib_id.rsplit(':').next().unwrap_or("");
+            properties.insert("Value".to_string(), part.attribute("value").unwrap_or(device_name).to_string());
+            if let Some(package) = &device.package {
+                properties.insert("Footprint".to_string(), package.clone());
+            }
+            let symbol = PlacedSymbol {
+                lib_id: device.lib_id.clone(),
+                lib_name: None,
+                uuid: None,
+                at: (x + offset, -y),
+                rotation,
+                mirror,
+                unit: device.gates.get(gate).copied().unwrap_or(1),
+                body_style: 1,
+                in_bom: device.package.is_some(),
+                dnp: false,
+                properties,
+                instances: HashMap::new(),
+            };
+            for (pin, name) in device.symbol.pins.iter().zip(&device.pin_names) {
+                if pin.unit == symbol.unit {
+                    let (x, y) = symbol.to_sheet(pin.at);
+                    pins.insert((attr(*part, "name"), gate, name), Point::from_mm(x, y));
+                }
+            }
+            sheet.symbols.push(symbol);
+        }
+
+        for text in grouped(eagle_sheet, "plain", "text") {
+            let (Some(content), Some(at)) = (text.text(), at(text, "x", "y")) else { continue };
+            sheet.notes.push(TextNote {
+                text: content.to_string(),
+                at,
+            });
+        }
+
+        for net in grouped(eagle_sheet, "nets", "net") {
+            let name = attr(net, "name");
+            for segment in children(net, "segment") {
+                let mut labelled = false;
+                for pinref in children(segment, "pinref") {
+                    if let Some(&at) = pins.get(&(attr(pinref, "part"), attr(pinref, "gate"), attr(pinref, "pin"))) {
+                        sheet.labels.push(Label {
+                            kind: LabelKind::Global,
+                            text: name.to_string(),
+                            at,
+                        });
+                        labelled = true;
+                    }
+                }
+                for wire in children(segment, "wire") {
+                    let (Some(a), Some(b)) = (at(wire, "x1", "y1"), at(wire, "x2", "y2")) else { continue };
+                    // Segments that reach no pin still belong to the net
+                    if !labelled {
+                        sheet.labels.push(Label {
+                            kind: LabelKind::Global,
+                            text: name.to_string(),
+                            at: a,
+                        });
+                        labelled = true;
+                    }
+                    sheet.wires.push((a, b));
+                }
+                sheet.junctions.extend(children(segment, "junction").filter_map(|j| at(j, "x", "y")));
+            }
+        }
+    }
+    Ok(sheet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::hierarchy::load_projects;
+
+    /// A divider over two sheets: R1 from VIN to N$1 on the first, R2 (turned
+    /// a quarter) from N$1 to a GND supply on the second
+    const DIVIDER: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE eagle SYSTEM "eagle.dtd">
+<eagle version="9.6.2">
+<drawing>
+<settings><setting alwaysvectorfont="no"/></settings>
+<layers><layer number="91" name="Nets" color="2" fill="1" visible="yes" active="yes"/></layers>
+<schematic xreflabel="%F%N/%S.%C%R" xrefpart="/%S.%C%R">
+<libraries>
+<library name="rcl" urn="urn:adsk.eagle:library:334">
+<packages><package name="R0603"/></packages>
+<symbols>
+<symbol name="R-US">
+<wire x1="-2.54" y1="0" x2="-2.159" y2="1.016" width="0.2032" layer="94"/>
+<wire x1="-2.159" y1="1.016" x2="2.54" y2="0" width="0.2032" layer="94"/>
+<text x="-3.81" y="1.4986" size="1.778" layer="95">&gt;NAME</text>
+<pin name="1" x="-5.08" y="0" visible="off" length="short" direction="pas" swaplevel="1"/>
+<pin name="2" x="5.08" y="0" visible="off" length="short" direction="pas" swaplevel="1" rot="R180"/>
+</symbol>
+</symbols>
+<devicesets>
+<deviceset name="R-US_" prefix="R" uservalue="yes">
+<gates><gate name="G$1" symbol="R-US" x="0" y="0"/></gates>
+<devices>
+<device name="R0603" package="R0603">
+<connects>
+<connect gate="G$1" pin="1" pad="1"/>
+<connect gate="G$1" pin="2" pad="2"/>
+</connects>
+<technologies><technology name=""><attribute name="MPN" value="RC0603FR-0710KL"/></technology></technologies>
+</device>
+</devices>
+</deviceset>
+</devicesets>
+</library>
+<library name="supply1">
+<symbols>
+<symbol name="GND">
+<wire x1="-1.905" y1="0" x2="1.905" y2="0" width="0.254" layer="94"/>
+<pin name="GND" x="0" y="2.54" visible="off" length="short" direction="sup" rot="R270"/>
+</symbol>
+</symbols>
+<devicesets>
+<deviceset name="GND" prefix="GND">
+<gates><gate name="1" symbol="GND" x="0" y="0"/></gates>
+<devices><device name=""><technologies><technology name=""/></technologies></device></devices>
+</deviceset>
+</devicesets>
+</library>
+</libraries>
+<parts>
+<part name="R1" library="rcl" library_urn="urn:adsk.eagle:library:334" deviceset="R-US_" device="R0603" value="10k"/>
+<part name="R2" library="rcl" library_urn="urn:adsk.eagle:library:334" deviceset="R-US_" device="R0603" value="10k">
+<attribute name="MPN" value="ERJ-3EKF1002V"/>
+</part>
+<part name="GND1" library="supply1" deviceset="GND" device=""/>
+</parts>
+<sheets>
+<sheet>
+<instances>
+<instance part="R1" gate="G$1" x="50.8" y="50.8"/>
+</instances>
+<nets>
+<net name="VIN" class="0">
+<segment>
+<pinref part="R1" gate="G$1" pin="1"/>
+<wire x1="45.72" y1="50.8" x2="40.64" y2="50.8" width="0.1524" layer="91"/>
+<label x="40.64" y="50.8" size="1.778" layer="95"/>
+</segment>
+</net>
+<net name="N$1" class="0">
+<segment>
+<pinref part="R1" gate="G$1" pin="2"/>
+<wire x1="55.88" y1="50.8" x2="60.96" y2="50.8" width="0.1524" layer="91"/>
+</segment>
+</net>
+</nets>
+</sheet>
+<sheet>
+<instances>
+<instance part="R2" gate="G$1" x="50.8" y="50.8" rot="R90"/>
+<instance part="GND1" gate="1" x="50.8" y="58.42" rot="R180"/>
+</instances>
+<nets>
+<net name="N$1" class="0">
+<segment>
+<pinref part="R2" gate="G$1" pin="1"/>
+<wire x1="50.8" y1="45.72" x2="50.8" y2="40.64" width="0.1524" layer="91"/>
+</segment>
+</net>
+<net name="GND" class="0">
+<segment>
+<pinref part="R2" gate="G$1" pin="2"/>
+<pinref part="GND1" gate="1" pin="GND"/>
+<wire x1="50.8" y1="55.88" x2="50.8" y2="60.96" width="0.1524" layer="91"/>
+</segment>
+</net>
+</nets>
+</sheet>
+</sheets>
+</schematic>
+</drawing>
+</eagle>
+"#;
+
+    #[test]
+    fn test_rotation_of() {
+        assert_eq!(rotation_of(""), (0, None));
+        assert_eq!(rotation_of("R90"), (90, None));
+        assert_eq!(rotation_of("MR180"), (180, Some('y')));
+        assert_eq!(rotation_of("SR270"), (270, None));
+        assert_eq!(rotation_of("R315"), (0, None));
+    }
+
+    #[test]
+    fn test_parse_eagle_sheet() {
+        let sheet = parse_eagle_sheet("divider.sch", DIVIDER).unwrap();
+        assert_eq!(sheet.format, SchematicFormat::Eagle);
+        assert_eq!(sheet.symbols.len(), 3);
+        let r1 = &sheet.symbols[0];
+        assert_eq!(r1.lib_id, "rcl:R-US_R0603");
+        assert_eq!((r1.reference(), r1.value()), ("R1", "10k"));
+        assert_eq!(r1.property("Footprint"), Some("rcl:R0603"));
+        assert_eq!(r1.property("MPN"), Some("RC0603FR-0710KL"));
+        assert_eq!(r1.at, (50.8, -50.8));
+        // The part's own attributes win over its technology's
+        assert_eq!(sheet.symbols[1].property("MPN"), Some("ERJ-3EKF1002V"));
+        assert_eq!(sheet.symbols[1].rotation, 90);
+
+        let resistor = &sheet.lib_symbols["rcl:R-US_R0603"];
+        assert_eq!(resistor.pins[1].number, "2");
+        assert_eq!((resistor.pins[1].orientation, resistor.pins[1].length), (180, 2.54));
+        assert_eq!(resistor.graphics.len(), 2);
+        assert!(sheet.lib_symbols["supply1:GND"].power);
+
+        // The second sheet is laid to the right of the first
+        assert!(sheet.symbols[1].at.0 > 60.96 + SHEET_GAP_MM - 1e-9);
+        assert_eq!(sheet.wires.len(), 4);
+    }
+
+    #[test]
+    fn test_eagle_project_netlist() {
+        let sources = BTreeMap::from([("divider.sch".to_string(), DIVIDER.to_string())]);
+        let projects = load_projects(&sources).unwrap();
+        assert_eq!(projects.len(), 1);
+        let project = &projects[0];
+
+        let mut nets: Vec<(String, Vec<String>)> = project
+            .netlist()
+            .into_iter()
+            .map(|net| (net.name, net.nodes.iter().map(|n| format!("{}.{}", n.reference, n.pin)).collect()))
+            .collect();
+        nets.sort();
+        assert_eq!(
+            nets,
+            [
+                ("GND".to_string(), vec!["R2.2".to_string()]),
+                ("N$1".to_string(), vec!["R1.2".to_string(), "R2.1".to_string()]),
+                ("VIN".to_string(), vec!["R1.1".to_string()]),
+            ]
+        );
+
+        let bom = project.bom();
+        assert_eq!(bom.len(), 2, "the MPNs differ: {:?}", bom);
+        assert_eq!(bom[0].references, ["R1"]);
+        assert_eq!(bom[0].footprint.as_deref(), Some("rcl:R0603"));
+    }
+
+    #[test]
+    fn test_malformed_eagle_file() {
+        let truncated = &DIVIDER[..DIVIDER.find("<sheets>").unwrap()];
+        let error = parse_eagle_sheet("divider.sch", truncated).unwrap_err();
+        assert!(matches!(error, SchematicError::Syntax { .. }), "{}", error);
+    }
+}
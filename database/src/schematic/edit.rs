@@ -0,0 +1,432 @@
+// USAGE:
+// cargo test schematic::edit -- --nocapture
+//
+// Changes to one .kicad_sch file, made on its S-expression tree so whatever
+// the model doesn't read (graphics, field positions, uuids) survives, then
+// written back the way KiCad writes it. This is how a suggested fix ("swap
+// pins 1 and 2 of J3") becomes a file a pull request can carry.
+use uuid::Uuid;
+
+use super::model::{parse_symbol, read_sheet, Point, SheetFile};
+use super::sexpr::{self, SExpr};
+use crate::error::SchematicError;
+
+/// Text size of an added property, KiCad's default
+const FONT_SIZE: &str = "1.27";
+
+/// A .kicad_sch file being edited
+///
+/// Components are named by reference. A multi-unit component is edited on
+/// every unit; pins are looked up on whichever unit has them. Each change
+/// either applies whole or fails with [`SchematicError::Edit`] and leaves
+/// the sheet as it was.
+#[derive(Debug, Clone)]
+pub struct SheetEdit {
+    file: String,
+    root: SExpr,
+    sheet: SheetFile,
+}
+
+/// `value` mm as KiCad writes coordinates: at most four decimals, no
+/// trailing zeros
+fn mm(value: f64) -> String {
+    let text = format!("{:.4}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        _ => text.to_string(),
+    }
+}
+
+fn atom(text: impl Into<String>) -> SExpr {
+    SExpr::Atom(text.into())
+}
+
+fn list(items: Vec<SExpr>) -> SExpr {
+    SExpr::List(items)
+}
+
+fn items_mut(expr: &mut SExpr) -> &mut Vec<SExpr> {
+    match expr {
+        SExpr::List(items) => items,
+        _ => unreachable!("only lists are edited"),
+    }
+}
+
+/// Point the first two numbers of `coordinates`, an `at` or `xy` list, at
+/// `to`, keeping any angle after them
+fn move_to(coordinates: &mut SExpr, to: Point) {
+    let items = items_mut(coordinates);
+    if items.len() >= 3 {
+        items[1] = atom(mm(to.x_mm()));
+        items[2] = atom(mm(to.y_mm()));
+    }
+}
+
+fn point_of(coordinates: &SExpr) -> Option<Point> {
+    Some(Point::from_mm(coordinates.number(0)?, coordinates.number(1)?))
+}
+
+impl SheetEdit {
+    /// Read `text`, the file at `file`, for editing
+    pub fn parse(file: &str, text: &str) -> Result<Self, SchematicError> {
+        let root = sexpr::parse(file, text)?;
+        let sheet = read_sheet(file, &root)?;
+        Ok(SheetEdit {
+            file: file.to_string(),
+            root,
+            sheet,
+        })
+    }
+
+    /// The edited file
+    pub fn to_text(&self) -> String {
+        sexpr::write(&self.root)
+    }
+
+    /// The sheet as it now reads
+    pub fn sheet(&self) -> &SheetFile {
+        &self.sheet
+    }
+
+    /// Whether a component `reference` is placed on this sheet
+    pub fn has_component(&self, reference: &str) -> bool {
+        self.units_of(reference).is_ok()
+    }
+
+    fn error(&self, message: impl Into<String>) -> SchematicError {
+        SchematicError::Edit {
+            file: self.file.clone(),
+            message: message.into(),
+        }
+    }
+
+    /// Indices into the root's items of the units of `reference`
+    fn units_of(&self, reference: &str) -> Result<Vec<usize>, SchematicError> {
+        let units: Vec<usize> = self
+            .root
+            .items()
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.head() == Some("symbol"))
+            .filter(|(_, item)| {
+                parse_symbol(item).is_some_and(|symbol| {
+                    symbol.reference() == reference || symbol.instances.values().any(|r| r == reference)
+                })
+            })
+            .map(|(index, _)| index)
+            .collect();
+        match units.is_empty() {
+            true => Err(self.error(format!("no component {}", reference))),
+            false => Ok(units),
+        }
+    }
+
+    /// Where pin `number` of `reference` meets wires on the sheet
+    fn pin_point(&self, reference: &str, number: &str) -> Result<Point, SchematicError> {
+        for index in self.units_of(reference)? {
+            let Some(symbol) = parse_symbol(&self.root.items()[index]) else { continue };
+            let Some(lib) = self.sheet.lib_symbols.get(symbol.lib_key()) else { continue };
+            if let Some((_, at)) = symbol.placed_pins(lib).into_iter().find(|(pin, _)| pin.number == number) {
+                return Ok(at);
+            }
+        }
+        Err(self.error(format!("{} has no pin {}", reference, number)))
+    }
+
+    /// Re-read the model after the tree changed, or restore the tree if the
+    /// change left it unreadable
+    fn commit(&mut self, before: SExpr) -> Result<(), SchematicError> {
+        match read_sheet(&self.file, &self.root) {
+            Ok(sheet) => {
+                self.sheet = sheet;
+                Ok(())
+            }
+            Err(e) => {
+                self.root = before;
+                Err(e)
+            }
+        }
+    }
+
+    /// Set the value of `reference`, e.g. "4.7k"
+    pub fn set_value(&mut self, reference: &str, value: &str) -> Result<(), SchematicError> {
+        self.set_property(reference, "Value", value)
+    }
+
+    /// Set the property `name` of `reference`, adding it hidden at the
+    /// symbol's anchor if it has none
+    pub fn set_property(&mut self, reference: &str, name: &str, value: &str) -> Result<(), SchematicError> {
+        if name == "Reference" {
+            return Err(self.error("references can't be changed here; KiCad keeps them per sheet instance"));
+        }
+        let units = self.units_of(reference)?;
+        let before = self.root.clone();
+        for index in units {
+            let symbol = &mut items_mut(&mut self.root)[index];
+            let at = symbol
+                .child("at")
+                .map(|at| (at.arg(0).unwrap_or("0").to_string(), at.arg(1).unwrap_or("0").to_string()));
+            let items = items_mut(symbol);
+            match items.iter_mut().find(|item| item.head() == Some("property") && item.arg(0) == Some(name)) {
+                Some(property) => {
+                    let property = items_mut(property);
+                    if property.len() < 3 {
+                        property.resize(3, SExpr::Str(String::new()));
+                    }
+                    property[2] = SExpr::Str(value.to_string());
+                }
+                None => {
+                    let (x, y) = at.unwrap_or_default();
+                    let property = list(vec![
+                        atom("property"),
+                        SExpr::Str(name.to_string()),
+                        SExpr::Str(value.to_string()),
+                        list(vec![atom("at"), atom(x), atom(y), atom("0")]),
+                        list(vec![
+                            atom("effects"),
+                            list(vec![atom("font"), list(vec![atom("size"), atom(FONT_SIZE), atom(FONT_SIZE)])]),
+                            list(vec![atom("hide"), atom("yes")]),
+                        ]),
+                    ]);
+                    // After the other properties, where KiCad keeps them
+                    let after = items
+                        .iter()
+                        .rposition(|item| item.head() == Some("property"))
+                        .map_or(items.len(), |i| i + 1);
+                    items.insert(after, property);
+                }
+            }
+        }
+        self.commit(before)
+    }
+
+    /// Swap what's connected to pins `a` and `b` of `reference`, returning
+    /// how many wire ends, labels, junctions and no-connect flags moved
+    ///
+    /// Everything that meets one pin is moved onto the other, so wires end
+    /// up crossing between them; tidying the drawing is left to whoever
+    /// reviews it. Pins touching another pin directly, or part-way along a
+    /// wire, can't be swapped this way and fail.
+    pub fn swap_pins(&mut self, reference: &str, a: &str, b: &str) -> Result<usize, SchematicError> {
+        let (pa, pb) = (self.pin_point(reference, a)?, self.pin_point(reference, b)?);
+        if pa == pb {
+            return Err(self.error(format!("pins {} and {} of {} are in the same place", a, b, reference)));
+        }
+        for symbol in &self.sheet.symbols {
+            let Some(lib) = self.sheet.lib_symbols.get(symbol.lib_key()) else { continue };
+            for (pin, at) in symbol.placed_pins(lib) {
+                let own = symbol.reference() == reference && (pin.number == a || pin.number == b);
+                if !own && (at == pa || at == pb) {
+                    return Err(self.error(format!(
+                        "pin {} of {} touches {} of {} directly",
+                        if at == pa { a } else { b },
+                        reference,
+                        pin.number,
+                        symbol.reference()
+                    )));
+                }
+            }
+        }
+        for &(start, end) in &self.sheet.wires {
+            for (number, at) in [(a, pa), (b, pb)] {
+                if at != start && at != end && at.is_on_segment(start, end) {
+                    return Err(self.error(format!("pin {} of {} meets a wire part-way along it", number, reference)));
+                }
+            }
+        }
+
+        let before = self.root.clone();
+        let mut moved = 0;
+        let mut swap = |coordinates: &mut SExpr| match point_of(coordinates) {
+            Some(at) if at == pa => {
+                move_to(coordinates, pb);
+                moved += 1;
+            }
+            Some(at) if at == pb => {
+                move_to(coordinates, pa);
+                moved += 1;
+            }
+            _ => {}
+        };
+        for item in items_mut(&mut self.root) {
+            match item.head() {
+                Some("wire") => {
+                    let Some(pts) = items_mut(item).iter_mut().find(|i| i.head() == Some("pts")) else { continue };
+                    for xy in items_mut(pts).iter_mut().filter(|i| i.head() == Some("xy")) {
+                        swap(xy);
+                    }
+                }
+                Some("junction" | "no_connect" | "label" | "global_label" | "hierarchical_label") => {
+                    if let Some(at) = items_mut(item).iter_mut().find(|i| i.head() == Some("at")) {
+                        swap(at);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.commit(before.clone())?;
+
+        // A wire moved onto the other pin may now run through something,
+        // such as the pin it left when both sit in line
+        let mut anchors: Vec<Point> = self.sheet.labels.iter().map(|label| label.at).collect();
+        anchors.extend(self.sheet.junctions.iter().chain(&self.sheet.no_connects).copied());
+        anchors.extend(self.sheet.wires.iter().flat_map(|&(start, end)| [start, end]));
+        for symbol in &self.sheet.symbols {
+            if let Some(lib) = self.sheet.lib_symbols.get(symbol.lib_key()) {
+                anchors.extend(symbol.placed_pins(lib).into_iter().map(|(_, at)| at));
+            }
+        }
+        let crossed = self
+            .sheet
+            .wires
+            .iter()
+            .filter(|&&(start, end)| [start, end].iter().any(|p| *p == pa || *p == pb))
+            .any(|&(start, end)| anchors.iter().any(|&at| at != start && at != end && at.is_on_segment(start, end)));
+        if crossed {
+            self.root = before;
+            self.sheet = read_sheet(&self.file, &self.root)?;
+            return Err(self.error(format!(
+                "swapping pins {} and {} of {} would run a wire through another connection",
+                a, b, reference
+            )));
+        }
+        Ok(moved)
+    }
+
+    /// Flag pin `number` of `reference` as deliberately unconnected; a pin
+    /// already flagged is left alone
+    pub fn add_no_connect(&mut self, reference: &str, number: &str) -> Result<(), SchematicError> {
+        let at = self.pin_point(reference, number)?;
+        if self.sheet.no_connects.contains(&at) {
+            return Ok(());
+        }
+        let connected = self.sheet.wires.iter().any(|&(start, end)| at.is_on_segment(start, end))
+            || self.sheet.labels.iter().any(|label| label.at == at);
+        if connected {
+            return Err(self.error(format!("pin {} of {} is connected", number, reference)));
+        }
+
+        let before = self.root.clone();
+        let flag = list(vec![
+            atom("no_connect"),
+            list(vec![atom("at"), atom(mm(at.x_mm())), atom(mm(at.y_mm()))]),
+            list(vec![atom("uuid"), SExpr::Str(Uuid::new_v4().to_string())]),
+        ]);
+        // With the other flags, which KiCad writes after junctions and
+        // before wires and symbols
+        let items = items_mut(&mut self.root);
+        let after = items
+            .iter()
+            .rposition(|item| matches!(item.head(), Some("no_connect" | "junction" | "lib_symbols")))
+            .or_else(|| items.iter().rposition(|item| matches!(item.head(), Some("paper" | "title_block"))))
+            .map_or(items.len(), |i| i + 1);
+        items.insert(after, flag);
+        self.commit(before)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::load_projects;
+    use std::collections::BTreeMap;
+
+    // R1 pin 1 is on SIG and pin 2 on GND, both wired off to the right;
+    // R2 pin 1 is on SIG too
+    const DIVIDER: &str = r#"(kicad_sch (version 20250114) (uuid "root")
+        (lib_symbols
+            (symbol "Device:R"
+                (symbol "R_1_1"
+                    (pin passive line (at 0 3.81 270) (length 1.27) (name "~") (number "1"))
+                    (pin passive line (at 0 -3.81 90) (length 1.27) (name "~") (number "2")))))
+        (wire (pts (xy 100 46.19) (xy 110 46.19)))
+        (wire (pts (xy 100 53.81) (xy 110 53.81)))
+        (wire (pts (xy 120 46.19) (xy 120 40)))
+        (label "SIG" (at 110 46.19 0))
+        (label "SIG" (at 120 40 0))
+        (label "GND" (at 110 53.81 0))
+        (symbol (lib_id "Device:R") (at 100 50 0) (unit 1) (uuid "r1")
+            (property "Reference" "R1") (property "Value" "10k"))
+        (symbol (lib_id "Device:R") (at 120 50 0) (unit 1) (uuid "r2")
+            (property "Reference" "R2") (property "Value" "10k"))
+    )"#;
+
+    /// Net of each "R1.1"-style pin, read from `text` the way the app does
+    fn nets(text: &str) -> BTreeMap<String, String> {
+        let sources = BTreeMap::from([("d.kicad_sch".to_string(), text.to_string())]);
+        let project = load_projects(&sources).unwrap().remove(0);
+        let mut nets = BTreeMap::new();
+        for net in project.netlist() {
+            for node in net.nodes {
+                nets.insert(format!("{}.{}", node.reference, node.pin), net.name.clone());
+            }
+        }
+        nets
+    }
+
+    #[test]
+    fn test_set_value_and_property() {
+        let mut edit = SheetEdit::parse("d.kicad_sch", DIVIDER).unwrap();
+        edit.set_value("R1", "4.7k").unwrap();
+        edit.set_property("R2", "MPN", "RC0603FR-0710KL").unwrap();
+        assert!(edit.set_value("R9", "1k").is_err());
+        assert!(edit.set_property("R1", "Reference", "R5").is_err());
+
+        let sheet = crate::schematic::model::parse_sheet("d.kicad_sch", &edit.to_text()).unwrap();
+        assert_eq!(sheet.symbols[0].value(), "4.7k");
+        assert_eq!(sheet.symbols[1].value(), "10k");
+        assert_eq!(sheet.symbols[1].property("MPN"), Some("RC0603FR-0710KL"));
+        assert_eq!(edit.sheet(), &sheet);
+    }
+
+    #[test]
+    fn test_swap_pins() {
+        let before = nets(DIVIDER);
+        assert_eq!((before["R1.1"].as_str(), before["R1.2"].as_str()), ("/SIG", "/GND"));
+
+        let mut edit = SheetEdit::parse("d.kicad_sch", DIVIDER).unwrap();
+        assert_eq!(edit.swap_pins("R1", "1", "2").unwrap(), 2);
+        let after = nets(&edit.to_text());
+        assert_eq!((after["R1.1"].as_str(), after["R1.2"].as_str()), ("/GND", "/SIG"));
+        assert_eq!(after["R2.1"], "/SIG");
+
+        assert!(edit.swap_pins("R1", "1", "3").is_err());
+    }
+
+    #[test]
+    fn test_swap_refuses_pins_it_cant_move() {
+        // R2 pin 1 sits part-way along a wire
+        let text = DIVIDER.replace("(xy 120 46.19) (xy 120 40)", "(xy 120 48) (xy 120 40)");
+        let mut edit = SheetEdit::parse("d.kicad_sch", &text).unwrap();
+        let err = edit.swap_pins("R2", "1", "2").unwrap_err();
+        assert!(err.to_string().contains("part-way"), "{}", err);
+        assert_eq!(edit.to_text(), sexpr::write(&sexpr::parse("d", &text).unwrap()));
+
+        // Wires leaving in line with the pins would cross the other pin
+        let text = DIVIDER.replace("(xy 110 53.81)", "(xy 100 60)").replace("(at 110 53.81 0)", "(at 100 60 0)");
+        let mut edit = SheetEdit::parse("d.kicad_sch", &text).unwrap();
+        let err = edit.swap_pins("R1", "1", "2").unwrap_err();
+        assert!(err.to_string().contains("would run a wire"), "{}", err);
+        assert_eq!(edit.to_text(), sexpr::write(&sexpr::parse("d", &text).unwrap()));
+    }
+
+    #[test]
+    fn test_add_no_connect() {
+        let mut edit = SheetEdit::parse("d.kicad_sch", DIVIDER).unwrap();
+        edit.add_no_connect("R2", "2").unwrap();
+        edit.add_no_connect("R2", "2").unwrap();
+        assert!(edit.add_no_connect("R1", "1").is_err());
+
+        let sheet = crate::schematic::model::parse_sheet("d.kicad_sch", &edit.to_text()).unwrap();
+        assert_eq!(sheet.no_connects, [Point::from_mm(120.0, 53.81)]);
+    }
+
+    #[test]
+    fn test_mm() {
+        assert_eq!(mm(53.81), "53.81");
+        assert_eq!(mm(120.0), "120");
+        assert_eq!(mm(-0.00001), "0");
+        assert_eq!(mm(1.27 * 3.0), "3.81");
+    }
+}
@@ -0,0 +1,519 @@
+// USAGE:
+// cargo test schematic::hierarchy -- --nocapture
+//
+// Builds one project model from a root sheet by following sheet symbols. A
+// file used by several sheet symbols is parsed once but instantiated once per
+// placement, each with its own references.
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::library::SymbolLibraries;
+use super::model::{parse_sheet, LibSymbol, PlacedSymbol, SchematicFormat, SheetFile};
+use crate::error::SchematicError;
+
+/// Reads one sheet file, given its path and contents
+pub type SheetParser<'a> = &'a dyn Fn(&str, &str) -> Result<SheetFile, SchematicError>;
+
+/// One placement of a sheet file in the hierarchy
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetInstance {
+    /// Sheet name shown on the parent's sheet symbol (empty for the root)
+    pub name: String,
+    /// Key into `Project::files`
+    pub file: String,
+    /// Uuid path from the root, e.g. `/<root uuid>/<sheet uuid>`
+    pub path: String,
+    /// Human-readable path, `/` for the root and `/Power/` for a child sheet
+    pub sheet_path: String,
+    pub parent: Option<usize>,
+    pub depth: usize,
+}
+
+/// A schematic project: every sheet file reachable from the root, and the sheet tree
+#[derive(Debug, Clone, Default)]
+pub struct Project {
+    /// Root file name without extension
+    pub name: String,
+    pub root_file: String,
+    pub files: BTreeMap<String, SheetFile>,
+    /// Depth first, root first
+    pub instances: Vec<SheetInstance>,
+    /// Sheet files referenced by a sheet symbol but not found
+    pub missing_files: Vec<String>,
+    /// lib_ids placed without an embedded definition that no library provides
+    pub unresolved_symbols: Vec<String>,
+}
+
+/// A component of the whole project, with its units merged
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub reference: String,
+    pub lib_id: String,
+    pub value: String,
+    pub footprint: Option<String>,
+    pub properties: BTreeMap<String, String>,
+    /// Sheet the (first) unit is placed on, e.g. `/Power/`
+    pub sheet_path: String,
+    /// Position of the (first) unit on its sheet, in mm
+    pub position: (f64, f64),
+    /// Rotation of the (first) unit in degrees, as in the schematic
+    pub rotation: i32,
+    /// Box around the (first) unit's body and pins as `(min, max)` in mm;
+    /// None if its symbol isn't known
+    pub bounds: Option<((f64, f64), (f64, f64))>,
+    pub units: Vec<u32>,
+    pub in_bom: bool,
+    pub dnp: bool,
+}
+
+fn dir_of(path: &str) -> &str {
+    path.rfind('/').map(|i| &path[..i]).unwrap_or("")
+}
+
+/// Join `relative` onto `dir`, resolving `.` and `..` segments
+pub(crate) fn join_path(dir: &str, relative: &str) -> String {
+    let relative = relative.replace('\\', "/");
+    let mut parts: Vec<&str> = if relative.starts_with('/') {
+        Vec::new()
+    } else {
+        dir.split('/').filter(|p| !p.is_empty()).collect()
+    };
+    for part in relative.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            p => parts.push(p),
+        }
+    }
+    parts.join("/")
+}
+
+fn stem(path: &str) -> &str {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.rsplit_once('.').map(|(s, _)| s).unwrap_or(name)
+}
+
+/// Sort key that orders references naturally (R2 before R10)
+pub fn natural_key(reference: &str) -> (String, u64, String) {
+    let split = reference
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(reference.len());
+    let (prefix, rest) = reference.split_at(split);
+    let digits_end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    (
+        prefix.to_string(),
+        rest[..digits_end].parse().unwrap_or(0),
+        rest[digits_end..].to_string(),
+    )
+}
+
+/// Whether a placed symbol is a power symbol or flag rather than a part
+pub(crate) fn is_virtual(symbol: &PlacedSymbol, file: &SheetFile, reference: &str) -> bool {
+    reference.starts_with('#')
+        || file
+            .lib_symbols
+            .get(symbol.lib_key())
+            .is_some_and(|lib| lib.power)
+}
+
+impl Project {
+    /// Load the project rooted at `root_file` from a map of path -> file contents
+    ///
+    /// Symbol libraries among the sources fill in definitions the sheets don't embed.
+    pub fn load(root_file: &str, sources: &BTreeMap<String, String>) -> Result<Self, SchematicError> {
+        Self::load_with_libraries(root_file, sources, &SymbolLibraries::from_sources(sources))
+    }
+
+    /// Load a project, looking up symbols the sheets don't embed in `libraries`
+    pub fn load_with_libraries(
+        root_file: &str,
+        sources: &BTreeMap<String, String>,
+        libraries: &SymbolLibraries,
+    ) -> Result<Self, SchematicError> {
+        Self::load_with_parser(root_file, sources, libraries, &parse_sheet)
+    }
+
+    /// Load a project, reading each sheet file with `parse`
+    pub fn load_with_parser(
+        root_file: &str,
+        sources: &BTreeMap<String, String>,
+        libraries: &SymbolLibraries,
+        parse: SheetParser,
+    ) -> Result<Self, SchematicError> {
+        let text = sources
+            .get(root_file)
+            .ok_or_else(|| SchematicError::MissingRoot(root_file.to_string()))?;
+        let root = parse(root_file, text)?;
+
+        let mut project = Project {
+            name: stem(root_file).to_string(),
+            root_file: root_file.to_string(),
+            ..Default::default()
+        };
+        let root_path = format!("/{}", root.uuid.as_deref().unwrap_or(""));
+        project.instances.push(SheetInstance {
+            name: String::new(),
+            file: root_file.to_string(),
+            path: root_path,
+            sheet_path: "/".to_string(),
+            parent: None,
+            depth: 0,
+        });
+        project.add_file(root_file, root, libraries);
+        project.expand(0, sources, libraries, parse)?;
+        Ok(project)
+    }
+
+    fn add_file(&mut self, path: &str, mut sheet: SheetFile, libraries: &SymbolLibraries) {
+        for lib_id in libraries.resolve_sheet(&mut sheet) {
+            if !self.unresolved_symbols.contains(&lib_id) {
+                self.unresolved_symbols.push(lib_id);
+            }
+        }
+        self.files.insert(path.to_string(), sheet);
+    }
+
+    fn expand(
+        &mut self,
+        index: usize,
+        sources: &BTreeMap<String, String>,
+        libraries: &SymbolLibraries,
+        parse: SheetParser,
+    ) -> Result<(), SchematicError> {
+        let instance = self.instances[index].clone();
+        let sheets = self.files[&instance.file].sheets.clone();
+        let project_dir = dir_of(&self.root_file).to_string();
+
+        for sheet in sheets {
+            // KiCad resolves sheet files against the project directory; fall
+            // back to the parent file's directory for hand-edited projects
+            let candidates = [
+                join_path(&project_dir, &sheet.file),
+                join_path(dir_of(&instance.file), &sheet.file),
+            ];
+            let Some(file) = candidates
+                .iter()
+                .find(|c| self.files.contains_key(*c) || sources.contains_key(*c))
+                .cloned()
+            else {
+                if !self.missing_files.contains(&candidates[0]) {
+                    self.missing_files.push(candidates[0].clone());
+                }
+                continue;
+            };
+
+            let mut ancestor = Some(index);
+            while let Some(i) = ancestor {
+                if self.instances[i].file == file {
+                    return Err(SchematicError::RecursiveSheet {
+                        file,
+                        path: format!("{}{}/", instance.sheet_path, sheet.name),
+                    });
+                }
+                ancestor = self.instances[i].parent;
+            }
+
+            if !self.files.contains_key(&file) {
+                let parsed = parse(&file, &sources[&file])?;
+                self.add_file(&file, parsed, libraries);
+            }
+
+            self.instances.push(SheetInstance {
+                name: sheet.name.clone(),
+                file,
+                path: format!("{}/{}", instance.path, sheet.uuid),
+                sheet_path: format!("{}{}/", instance.sheet_path, sheet.name),
+                parent: Some(index),
+                depth: instance.depth + 1,
+            });
+            let child = self.instances.len() - 1;
+            self.expand(child, sources, libraries, parse)?;
+        }
+        Ok(())
+    }
+
+    pub fn root(&self) -> &SheetFile {
+        &self.files[&self.root_file]
+    }
+
+    /// Direct children of a sheet instance
+    pub fn children(&self, index: usize) -> impl Iterator<Item = (usize, &SheetInstance)> {
+        self.instances
+            .iter()
+            .enumerate()
+            .filter(move |(_, i)| i.parent == Some(index))
+    }
+
+    /// The reference a symbol carries in one sheet instance
+    ///
+    /// Falls back to the root's legacy `symbol_instances` table and then to
+    /// the symbol's own Reference field.
+    pub fn reference_of(&self, instance: &SheetInstance, symbol: &PlacedSymbol) -> String {
+        if let Some(reference) = symbol.instances.get(&instance.path) {
+            return reference.clone();
+        }
+        if let Some(uuid) = &symbol.uuid {
+            // Legacy paths leave out the root sheet's uuid
+            let sheet_path = instance
+                .path
+                .splitn(3, '/')
+                .nth(2)
+                .map(|rest| format!("/{}/", rest))
+                .unwrap_or_else(|| "/".to_string());
+            if let Some(reference) = self
+                .root()
+                .legacy_instances
+                .get(&format!("{}{}", sheet_path, uuid))
+            {
+                return reference.clone();
+            }
+        }
+        symbol.reference().to_string()
+    }
+
+    /// Each placed unit of a part with its definition, in unit order
+    ///
+    /// The definition is `None` when the symbol could not be resolved.
+    pub fn symbol_units(&self, reference: &str) -> Vec<(&PlacedSymbol, Option<&LibSymbol>)> {
+        let mut units = Vec::new();
+        for instance in &self.instances {
+            let file = &self.files[&instance.file];
+            for symbol in &file.symbols {
+                if self.reference_of(instance, symbol) == reference {
+                    units.push((symbol, file.lib_symbols.get(symbol.lib_key())));
+                }
+            }
+        }
+        units.sort_by_key(|(symbol, _)| symbol.unit);
+        units
+    }
+
+    /// Every part in the project, units merged, in natural reference order
+    pub fn components(&self) -> Vec<Component> {
+        let mut by_reference: HashMap<String, Component> = HashMap::new();
+        for instance in &self.instances {
+            let file = &self.files[&instance.file];
+            for symbol in &file.symbols {
+                let reference = self.reference_of(instance, symbol);
+                if is_virtual(symbol, file, &reference) {
+                    continue;
+                }
+                by_reference
+                    .entry(reference.clone())
+                    .and_modify(|c| {
+                        if !c.units.contains(&symbol.unit) {
+                            c.units.push(symbol.unit);
+                        }
+                    })
+                    .or_insert_with(|| Component {
+                        reference,
+                        lib_id: symbol.lib_id.clone(),
+                        value: symbol.value().to_string(),
+                        footprint: symbol
+                            .property("Footprint")
+                            .filter(|f| !f.is_empty())
+                            .map(str::to_string),
+                        properties: symbol.properties.clone(),
+                        sheet_path: instance.sheet_path.clone(),
+                        position: symbol.at,
+                        rotation: symbol.rotation,
+                        bounds: file.lib_symbols.get(symbol.lib_key()).and_then(|lib| symbol.bounds(lib)),
+                        units: vec![symbol.unit],
+                        in_bom: symbol.in_bom,
+                        dnp: symbol.dnp,
+                    });
+            }
+        }
+        let mut components: Vec<Component> = by_reference.into_values().collect();
+        for component in &mut components {
+            component.units.sort_unstable();
+        }
+        components.sort_by_key(|c| natural_key(&c.reference));
+        components
+    }
+}
+
+/// Load every project in a set of files (path -> contents)
+///
+/// Roots are the schematics named after a .kicad_pro, or after a legacy .pro
+/// with no .kicad_pro beside it; without any project file, every schematic
+/// no other sheet references is treated as a root. Schematics are the
+/// .kicad_sch files and whatever else reads as one, whatever its name.
+/// Symbol libraries among the files resolve symbols the sheets don't embed;
+/// other files (boards) are ignored.
+pub fn load_projects(sources: &BTreeMap<String, String>) -> Result<Vec<Project>, SchematicError> {
+    load_projects_with_libraries(sources, &SymbolLibraries::from_sources(sources))
+}
+
+/// Load every project, looking up symbols the sheets don't embed in `libraries`
+pub fn load_projects_with_libraries(
+    sources: &BTreeMap<String, String>,
+    libraries: &SymbolLibraries,
+) -> Result<Vec<Project>, SchematicError> {
+    load_projects_with_parser(sources, libraries, &parse_sheet)
+}
+
+/// Load every project, reading each sheet file with `parse`, e.g. one that
+/// remembers files it has parsed before
+pub fn load_projects_with_parser(
+    sources: &BTreeMap<String, String>,
+    libraries: &SymbolLibraries,
+    parse: SheetParser,
+) -> Result<Vec<Project>, SchematicError> {
+    let mut roots: Vec<String> = sources
+        .keys()
+        .filter_map(|p| {
+            if let Some(stem) = p.strip_suffix(".kicad_pro") {
+                return Some(format!("{}.kicad_sch", stem));
+            }
+            // Projects converted to KiCad 6 often keep their legacy files
+            let stem = p.strip_suffix(".pro")?;
+            (!sources.contains_key(&format!("{}.kicad_pro", stem))).then(|| format!("{}.sch", stem))
+        })
+        .filter(|sch| sources.contains_key(sch))
+        .collect();
+
+    if roots.is_empty() {
+        let mut referenced = HashSet::new();
+        let schematics: Vec<&String> = sources
+            .iter()
+            .filter(|(p, text)| p.ends_with(".kicad_sch") || SchematicFormat::detect(text).is_some())
+            .map(|(p, _)| p)
+            .collect();
+        for path in &schematics {
+            let sheet = parse(path, &sources[*path])?;
+            for child in &sheet.sheets {
+                referenced.insert(join_path(dir_of(path), &child.file));
+            }
+        }
+        roots = schematics
+            .into_iter()
+            .filter(|p| !referenced.contains(*p))
+            .cloned()
+            .collect();
+    }
+
+    roots
+        .iter()
+        .map(|root| Project::load_with_parser(root, sources, libraries, parse))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(files: &[(&str, &str)]) -> BTreeMap<String, String> {
+        files
+            .iter()
+            .map(|(p, c)| (p.to_string(), c.to_string()))
+            .collect()
+    }
+
+    const CHILD: &str = r#"(kicad_sch (uuid "child")
+        (symbol (lib_id "Device:R") (at 0 0 0) (unit 1) (uuid "r")
+            (property "Reference" "R?")
+            (instances (project "p"
+                (path "/root/s1" (reference "R1") (unit 1))
+                (path "/root/s2" (reference "R2") (unit 1))))))"#;
+
+    const ROOT: &str = r#"(kicad_sch (uuid "root")
+        (sheet (at 0 0) (uuid "s1") (property "Sheetname" "A") (property "Sheetfile" "sub/child.kicad_sch"))
+        (sheet (at 0 0) (uuid "s2") (property "Sheetname" "B") (property "Sheetfile" "sub/child.kicad_sch")))"#;
+
+    #[test]
+    fn test_reused_sheet_gets_instance_references() {
+        let files = sources(&[
+            ("hw/top.kicad_sch", ROOT),
+            ("hw/sub/child.kicad_sch", CHILD),
+            ("hw/top.kicad_pro", "{}"),
+        ]);
+        let projects = load_projects(&files).unwrap();
+        assert_eq!(projects.len(), 1);
+        let project = &projects[0];
+        assert_eq!(project.name, "top");
+        assert_eq!(project.files.len(), 2);
+
+        let paths: Vec<&str> = project.instances.iter().map(|i| i.sheet_path.as_str()).collect();
+        assert_eq!(paths, ["/", "/A/", "/B/"]);
+        assert_eq!(project.children(0).count(), 2);
+
+        let components = project.components();
+        let refs: Vec<(&str, &str)> = components
+            .iter()
+            .map(|c| (c.reference.as_str(), c.sheet_path.as_str()))
+            .collect();
+        assert_eq!(refs, [("R1", "/A/"), ("R2", "/B/")]);
+    }
+
+    #[test]
+    fn test_roots_without_project_file() {
+        let files = sources(&[("top.kicad_sch", ROOT), ("sub/child.kicad_sch", CHILD)]);
+        let projects = load_projects(&files).unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].root_file, "top.kicad_sch");
+    }
+
+    #[test]
+    fn test_missing_and_recursive_sheets() {
+        let files = sources(&[("top.kicad_sch", ROOT)]);
+        let project = Project::load("top.kicad_sch", &files).unwrap();
+        assert_eq!(project.missing_files, ["sub/child.kicad_sch"]);
+        assert_eq!(project.instances.len(), 1);
+
+        let looped = r#"(kicad_sch (uuid "l")
+            (sheet (at 0 0) (uuid "x") (property "Sheetname" "Self") (property "Sheetfile" "loop.kicad_sch")))"#;
+        let files = sources(&[("loop.kicad_sch", looped)]);
+        let err = Project::load("loop.kicad_sch", &files).unwrap_err();
+        assert!(matches!(err, SchematicError::RecursiveSheet { .. }));
+    }
+
+    #[test]
+    fn test_example_bms_project() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../kicad-example-files/BMS");
+        let mut files = BTreeMap::new();
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            if name.ends_with(".kicad_sch") || name.ends_with(".kicad_pro") {
+                files.insert(name, std::fs::read_to_string(&path).unwrap());
+            }
+        }
+
+        let projects = load_projects(&files).unwrap();
+        assert_eq!(projects.len(), 1);
+        let project = &projects[0];
+        assert_eq!(project.root_file, "uBMS-2.kicad_sch");
+        assert_eq!(project.instances.len(), 6);
+        assert!(project.missing_files.is_empty());
+
+        let components = project.components();
+        assert_eq!(components.len(), 245);
+        assert!(components.iter().all(|c| !c.reference.contains('?')));
+        let sheets: HashSet<&str> = components.iter().map(|c| c.sheet_path.as_str()).collect();
+        assert!(sheets.contains("/BatteryBalance/"));
+
+        // Ground reaches parts on every sheet through the power symbols
+        let sheet_of: HashMap<&str, &str> = components
+            .iter()
+            .map(|c| (c.reference.as_str(), c.sheet_path.as_str()))
+            .collect();
+        let nets = project.netlist();
+        let gnd = nets.iter().find(|n| n.name == "GND").unwrap();
+        let gnd_sheets: HashSet<&str> = gnd.nodes.iter().map(|n| sheet_of[n.reference.as_str()]).collect();
+        assert_eq!(gnd_sheets.len(), 6);
+
+        // Only pins that are really left open come out unconnected
+        let unconnected = nets.iter().filter(|n| n.name.starts_with("unconnected-")).count();
+        assert_eq!(unconnected, 11);
+    }
+
+    #[test]
+    fn test_join_path_and_natural_key() {
+        assert_eq!(join_path("hw", "sub/../power.kicad_sch"), "hw/power.kicad_sch");
+        assert_eq!(join_path("", "./a.kicad_sch"), "a.kicad_sch");
+        assert!(natural_key("R2") < natural_key("R10"));
+        assert!(natural_key("C9") < natural_key("R1"));
+    }
+}
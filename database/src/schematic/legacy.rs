@@ -0,0 +1,574 @@
+// USAGE:
+// cargo test schematic::legacy -- --nocapture
+//
+// Reader for the legacy EESchema format of KiCad 4 and 5: .sch sheets and
+// .lib symbol libraries, read into the same model as .kicad_sch files so old
+// projects get netlists, BOMs and diffs like new ones. Legacy files count in
+// mils, Y down on sheets and Y up in libraries like the newer format. Text
+// notes, buses and bitmaps are skipped; lines that can't be read are too.
+use std::collections::{BTreeMap, HashMap};
+
+use super::library::SymbolLibrary;
+use super::model::{
+    orientation_of, Fill, Label, LabelKind, LibGraphic, LibPin, LibSymbol, PlacedSymbol, Point, SchematicFormat, SheetFile,
+    SheetPin, SheetSymbol, Shape, TextNote,
+};
+use crate::error::SchematicError;
+
+/// First line of a legacy sheet, followed by the format version
+pub(crate) const SCHEMATIC_HEADER: &str = "EESchema Schematic File Version";
+
+/// First line of a legacy symbol library
+pub(crate) const LIBRARY_HEADER: &str = "EESchema-LIBRARY Version";
+
+/// Millimetres per mil, the legacy unit
+const MM_PER_MIL: f64 = 0.0254;
+
+/// Names of the fixed component fields, by number; later ones carry their own
+const FIELD_NAMES: [&str; 4] = ["Reference", "Value", "Footprint", "Datasheet"];
+
+/// Whether `text` is a legacy symbol library (as opposed to e.g. a SPICE .lib)
+pub fn is_legacy_library(text: &str) -> bool {
+    text.trim_start_matches('\u{feff}').trim_start().starts_with(LIBRARY_HEADER)
+}
+
+/// Split a line into words; a quoted string is one word, without its quotes
+fn words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut word = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => word.extend(chars.next()),
+                    '"' => break,
+                    c => word.push(c),
+                }
+            }
+            words.push(word);
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek().filter(|c| !c.is_whitespace()) {
+                word.push(c);
+                chars.next();
+            }
+            words.push(word);
+        }
+    }
+    words
+}
+
+fn word(words: &[String], i: usize) -> &str {
+    words.get(i).map_or("", String::as_str)
+}
+
+fn mm(words: &[String], i: usize) -> Option<f64> {
+    Some(words.get(i)?.parse::<f64>().ok()? * MM_PER_MIL)
+}
+
+fn point(words: &[String], x: usize) -> Option<Point> {
+    Some(Point::from_mm(mm(words, x)?, mm(words, x + 1)?))
+}
+
+/// Legacy files write empty texts as "~"
+fn text(word: &str) -> String {
+    if word == "~" {
+        String::new()
+    } else {
+        word.to_string()
+    }
+}
+
+/// The quoted value of `name="..."` on a line, as in `AR Path="/a/b" Ref="R1"`
+fn attribute<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let start = line.find(&format!("{}=\"", name))? + name.len() + 2;
+    let len = line[start..].find('"')?;
+    Some(&line[start..start + len])
+}
+
+/// Parse one legacy .sch file; `file` is only used in error messages
+pub fn parse_legacy_sheet(file: &str, text: &str) -> Result<SheetFile, SchematicError> {
+    let mut lines = text.trim_start_matches('\u{feff}').lines().map(str::trim);
+    let header = lines.next().unwrap_or("");
+    if !header.starts_with(SCHEMATIC_HEADER) {
+        return Err(SchematicError::NotASchematic {
+            file: file.to_string(),
+            found: header.chars().take(40).collect(),
+        });
+    }
+
+    let mut sheet = SheetFile {
+        format: SchematicFormat::Legacy,
+        ..Default::default()
+    };
+    while let Some(line) = lines.next() {
+        let words = words(line);
+        match (word(&words, 0), word(&words, 1)) {
+            ("$Comp", _) => sheet.symbols.extend(read_component(&mut lines)),
+            ("$Sheet", _) => sheet.sheets.extend(read_sheet_symbol(&mut lines)),
+            ("$Bitmap", _) => {
+                lines.by_ref().find(|line| line.starts_with("$EndBitmap"));
+            }
+            // The points are on the next line
+            ("Wire", kind) | ("Entry", kind) => {
+                let points = words_of(lines.next());
+                if kind == "Wire" && word(&words, 2) == "Line" {
+                    sheet.wires.extend(point(&points, 0).zip(point(&points, 2)));
+                }
+            }
+            ("Connection", _) => sheet.junctions.extend(point(&words, 2)),
+            ("NoConn", _) => sheet.no_connects.extend(point(&words, 2)),
+            // The text is on the next line
+            ("Text", kind) => {
+                let label = lines.next().unwrap_or("");
+                if kind == "Notes" {
+                    // Line breaks are kept as a literal `\n`
+                    sheet.notes.extend(point(&words, 2).map(|at| TextNote {
+                        text: label.replace("\\n", "\n"),
+                        at,
+                    }));
+                    continue;
+                }
+                let kind = match kind {
+                    "Label" => LabelKind::Local,
+                    "GLabel" => LabelKind::Global,
+                    "HLabel" => LabelKind::Hierarchical,
+                    _ => continue,
+                };
+                if let Some(at) = point(&words, 2) {
+                    sheet.labels.push(Label {
+                        kind,
+                        text: label.to_string(),
+                        at,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(sheet)
+}
+
+fn words_of(line: Option<&str>) -> Vec<String> {
+    line.map(words).unwrap_or_default()
+}
+
+/// A placed component, from after `$Comp` through `$EndComp`
+fn read_component<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Option<PlacedSymbol> {
+    let mut lib_id = None;
+    let mut symbol = PlacedSymbol {
+        lib_id: String::new(),
+        lib_name: None,
+        uuid: None,
+        at: (0.0, 0.0),
+        rotation: 0,
+        mirror: None,
+        unit: 1,
+        body_style: 1,
+        in_bom: true,
+        dnp: false,
+        properties: BTreeMap::new(),
+        instances: HashMap::new(),
+    };
+    for line in lines.by_ref() {
+        let words = words(line);
+        match word(&words, 0) {
+            "$EndComp" => break,
+            "L" => {
+                lib_id = Some(word(&words, 1).to_string());
+                symbol.properties.insert("Reference".to_string(), word(&words, 2).to_string());
+            }
+            "U" => {
+                symbol.unit = word(&words, 1).parse().unwrap_or(1);
+                symbol.body_style = word(&words, 2).parse().unwrap_or(1);
+                symbol.uuid = words.get(3).cloned();
+            }
+            "P" => symbol.at = (mm(&words, 1).unwrap_or(0.0), mm(&words, 2).unwrap_or(0.0)),
+            // References per sheet instance; the path ends with the component's own timestamp
+            "AR" => {
+                if let (Some(path), Some(reference)) = (attribute(line, "Path"), attribute(line, "Ref")) {
+                    let sheet_path = path.rsplit_once('/').map_or("", |(sheets, _)| sheets);
+                    symbol.instances.insert(format!("/{}", sheet_path), reference.to_string());
+                }
+            }
+            "F" => {
+                let Ok(number) = word(&words, 1).parse::<usize>() else { continue };
+                let name = match FIELD_NAMES.get(number) {
+                    Some(name) => name.to_string(),
+                    None => word(&words, 10).to_string(),
+                };
+                if !name.is_empty() {
+                    symbol.properties.insert(name, text(word(&words, 2)));
+                }
+            }
+            // The orientation matrix; the line before it repeats the unit and position
+            _ if words.len() == 4 => {
+                let matrix: Vec<i64> = words.iter().filter_map(|w| w.parse().ok()).collect();
+                if let Some((rotation, mirror)) = matrix.try_into().ok().and_then(orientation_of) {
+                    symbol.rotation = rotation;
+                    symbol.mirror = mirror;
+                }
+            }
+            _ => {}
+        }
+    }
+    symbol.lib_id = lib_id?;
+    Some(symbol)
+}
+
+/// A sheet symbol, from after `$Sheet` through `$EndSheet`
+fn read_sheet_symbol<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Option<SheetSymbol> {
+    let mut sheet = SheetSymbol {
+        uuid: String::new(),
+        name: String::new(),
+        file: String::new(),
+        at: (0.0, 0.0),
+        size: (0.0, 0.0),
+        pins: Vec::new(),
+    };
+    for line in lines.by_ref() {
+        let words = words(line);
+        match word(&words, 0) {
+            "$EndSheet" => break,
+            "S" => {
+                sheet.at = (mm(&words, 1).unwrap_or(0.0), mm(&words, 2).unwrap_or(0.0));
+                sheet.size = (mm(&words, 3).unwrap_or(0.0), mm(&words, 4).unwrap_or(0.0));
+            }
+            "U" => sheet.uuid = word(&words, 1).to_string(),
+            "F0" => sheet.name = word(&words, 1).to_string(),
+            "F1" => sheet.file = word(&words, 1).replace('\\', "/"),
+            field if field.starts_with('F') => {
+                if let Some(at) = point(&words, 4) {
+                    sheet.pins.push(SheetPin {
+                        name: word(&words, 1).to_string(),
+                        at,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    (!sheet.file.is_empty()).then_some(sheet)
+}
+
+/// Parse one legacy .lib file; `file` is only used in error messages
+///
+/// Aliases get their symbol's definition.
+pub fn parse_legacy_library(file: &str, nickname: &str, text: &str) -> Result<SymbolLibrary, SchematicError> {
+    if !is_legacy_library(text) {
+        return Err(SchematicError::NotASymbolLibrary {
+            file: file.to_string(),
+            found: text.trim_start().lines().next().unwrap_or("").chars().take(40).collect(),
+        });
+    }
+
+    let mut symbols = HashMap::new();
+    let mut lines = text.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        let def = words(line);
+        if word(&def, 0) != "DEF" {
+            continue;
+        }
+        // DEF name reference unused text_offset draw_numbers draw_names units locked flag
+        let mut names = vec![word(&def, 1).trim_start_matches('~').to_string()];
+        let mut symbol = LibSymbol {
+            power: word(&def, 9) == "P",
+            hide_pin_numbers: word(&def, 5) == "N",
+            hide_pin_names: word(&def, 6) == "N",
+            ..Default::default()
+        };
+        let mut drawing = false;
+        for line in lines.by_ref() {
+            let words = words(line);
+            match word(&words, 0) {
+                "ENDDEF" => break,
+                "ALIAS" => names.extend(words[1..].iter().cloned()),
+                "DRAW" => drawing = true,
+                "ENDDRAW" => drawing = false,
+                "X" if drawing => symbol.pins.extend(read_pin(&words)),
+                _ if drawing => symbol.graphics.extend(read_graphic(&words)),
+                _ => {}
+            }
+        }
+        for name in names {
+            symbols.insert(name, symbol.clone());
+        }
+    }
+    Ok(SymbolLibrary {
+        nickname: nickname.to_string(),
+        symbols,
+    })
+}
+
+fn unit_of(words: &[String], i: usize) -> u32 {
+    word(words, i).parse().unwrap_or(0)
+}
+
+/// `X name number x y length orientation number_size name_size unit convert type [shape]`
+fn read_pin(words: &[String]) -> Option<LibPin> {
+    let electrical_type = match word(words, 11) {
+        "I" => "input",
+        "O" => "output",
+        "B" => "bidirectional",
+        "T" => "tri_state",
+        "P" => "passive",
+        "W" => "power_in",
+        "w" => "power_out",
+        "C" => "open_collector",
+        "E" => "open_emitter",
+        "N" => "no_connect",
+        _ => "unspecified",
+    };
+    // The direction the pin points from its connection point, towards the body
+    let orientation = match word(words, 6) {
+        "U" => 90,
+        "L" => 180,
+        "D" => 270,
+        _ => 0,
+    };
+    Some(LibPin {
+        number: word(words, 2).to_string(),
+        name: text(word(words, 1)),
+        electrical_type: electrical_type.to_string(),
+        unit: unit_of(words, 9),
+        body_style: unit_of(words, 10),
+        hidden: word(words, 12).starts_with('N'),
+        at: (mm(words, 3)?, mm(words, 4)?),
+        orientation,
+        length: mm(words, 5).unwrap_or(0.0),
+    })
+}
+
+fn fill_of(word: &str) -> Fill {
+    match word {
+        "F" => Fill::Outline,
+        "f" => Fill::Background,
+        _ => Fill::None,
+    }
+}
+
+/// A body shape: `S` rectangle, `P` polyline, `C` circle or `A` arc
+fn read_graphic(words: &[String]) -> Option<LibGraphic> {
+    let xy = |i: usize| Some((mm(words, i)?, mm(words, i + 1)?));
+    let (shape, unit, fill) = match word(words, 0) {
+        "S" => (Shape::Rectangle { start: xy(1)?, end: xy(3)? }, 5, word(words, 8)),
+        "P" => {
+            let count: usize = word(words, 1).parse().ok()?;
+            let points = (0..count).map(|i| xy(5 + 2 * i)).collect::<Option<Vec<_>>>()?;
+            (Shape::Polyline { points }, 2, word(words, 5 + 2 * count))
+        }
+        "C" => (Shape::Circle { center: xy(1)?, radius: mm(words, 3)? }, 4, word(words, 7)),
+        // A x y radius start_angle end_angle unit convert width fill start_x start_y end_x end_y,
+        // angles in tenths of a degree; the arc takes the short way round
+        "A" => {
+            let (x, y, radius) = (mm(words, 1)?, mm(words, 2)?, mm(words, 3)?);
+            let from: f64 = word(words, 4).parse().ok()?;
+            let to: f64 = word(words, 5).parse().ok()?;
+            let sweep = (to - from + 1800.0).rem_euclid(3600.0) - 1800.0;
+            let mid = ((from + sweep / 2.0) / 10.0).to_radians();
+            let arc = Shape::Arc {
+                start: xy(10)?,
+                mid: (x + radius * mid.cos(), y + radius * mid.sin()),
+                end: xy(12)?,
+            };
+            (arc, 6, word(words, 9))
+        }
+        _ => return None,
+    };
+    Some(LibGraphic {
+        unit: unit_of(words, unit),
+        body_style: unit_of(words, unit + 1),
+        shape,
+        fill: fill_of(fill),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::hierarchy::load_projects;
+
+    /// A KiCad 5 divider: R1 from VIN to VOUT on the root, R2 from VOUT to
+    /// GND on a sub-sheet
+    const ROOT: &str = "EESchema Schematic File Version 4
+EELAYER 30 0
+EELAYER END
+$Descr A4 11693 8268
+encoding utf-8
+Sheet 1 2
+Title \"Divider\"
+$EndDescr
+$Comp
+L Device:R R1
+U 1 1 5C8A1B2F
+P 3000 2000
+F 0 \"R1\" H 3070 2046 50  0000 L CNN
+F 1 \"10k\" H 3070 1955 50  0000 L CNN
+F 2 \"Resistor_SMD:R_0603_1608Metric\" V 2930 2000 50  0001 C CNN
+F 3 \"~\" H 3000 2000 50  0001 C CNN
+F 4 \"RC0603FR-0710KL\" H 3000 2000 50  0001 C CNN \"MPN\"
+	1    3000 2000
+	1    0    0    -1
+$EndComp
+Wire Wire Line
+	3000 2150 3000 2400
+Wire Wire Line
+	3000 2400 5000 2400
+Text GLabel 3000 1850 1    50   Input ~ 0
+VIN
+Connection ~ 3000 2400
+$Sheet
+S 5000 2300 1000 500
+U 5C8A1C00
+F0 \"Lower\" 50
+F1 \"lower.sch\" 50
+F2 \"VOUT\" I L 5000 2400 50
+$EndSheet
+$EndSCHEMATC
+";
+
+    const LOWER: &str = "EESchema Schematic File Version 4
+$Descr A4 11693 8268
+$EndDescr
+$Comp
+L Device:R R?
+U 1 1 5C8A1D00
+P 2000 2000
+AR Path=\"/5C8A1C00/5C8A1D00\" Ref=\"R2\"  Part=\"1\"
+F 0 \"R?\" H 2070 2046 50  0000 L CNN
+F 1 \"10k\" H 2070 1955 50  0000 L CNN
+	1    2000 2000
+	0    -1   -1   0
+$EndComp
+Text HLabel 1850 2000 0    50   Input ~ 0
+VOUT
+$Comp
+L power:GND #PWR01
+U 1 1 5C8A1E00
+P 2150 2000
+F 0 \"#PWR01\" H 2150 1750 50  0001 C CNN
+F 1 \"GND\" H 2155 1827 50  0000 C CNN
+	1    2150 2000
+	1    0    0    -1
+$EndComp
+$EndSCHEMATC
+";
+
+    const CACHE: &str = "EESchema-LIBRARY Version 2.4
+#encoding utf-8
+#
+# Device_R
+#
+DEF Device_R R 0 0 N Y 1 F N
+F0 \"R\" 80 0 50 V V C CNN
+F1 \"Device_R\" 0 0 50 V V C CNN
+$FPLIST
+ R_*
+ SOT*
+$ENDFPLIST
+DRAW
+S -40 -100 40 100 0 1 10 N
+X ~ 1 0 150 50 D 50 50 1 1 P
+X ~ 2 0 -150 50 U 50 50 1 1 P
+ENDDRAW
+ENDDEF
+#
+# power_GND
+#
+DEF power_GND #PWR 0 0 Y Y 1 F P
+F0 \"#PWR\" 0 -250 50 H I C CNN
+F1 \"power_GND\" 0 -150 50 H V C CNN
+DRAW
+P 6 0 1 0  0 0  0 -50  50 -50  0 -100  -50 -50  0 -50 N
+X GND 1 0 0 0 D 50 50 1 1 W N
+ENDDRAW
+ENDDEF
+#
+#End Library
+";
+
+    #[test]
+    fn test_parse_legacy_sheet() {
+        let sheet = parse_legacy_sheet("divider.sch", ROOT).unwrap();
+        assert_eq!(sheet.symbols.len(), 1);
+        let r1 = &sheet.symbols[0];
+        assert_eq!(r1.lib_id, "Device:R");
+        assert_eq!((r1.reference(), r1.value()), ("R1", "10k"));
+        assert_eq!(r1.property("Datasheet"), Some(""));
+        assert_eq!(r1.property("MPN"), Some("RC0603FR-0710KL"));
+        assert_eq!(r1.at, (76.2, 50.8));
+        assert_eq!(r1.uuid.as_deref(), Some("5C8A1B2F"));
+        assert_eq!(sheet.wires.len(), 2);
+        assert_eq!(sheet.junctions, [Point::from_mm(76.2, 60.96)]);
+        assert_eq!(sheet.labels[0].kind, LabelKind::Global);
+        assert_eq!(sheet.labels[0].text, "VIN");
+        assert_eq!(sheet.sheets[0].file, "lower.sch");
+        assert_eq!(sheet.sheets[0].pins[0].name, "VOUT");
+
+        let lower = parse_legacy_sheet("lower.sch", LOWER).unwrap();
+        assert_eq!(lower.symbols[0].rotation, 90);
+        assert_eq!(lower.symbols[0].instances["//5C8A1C00"], "R2");
+
+        let err = parse_legacy_sheet("x.sch", "<eagle/>").unwrap_err();
+        assert!(matches!(err, SchematicError::NotASchematic { .. }));
+    }
+
+    #[test]
+    fn test_parse_legacy_library() {
+        let library = parse_legacy_library("divider-cache.lib", "divider-cache", CACHE).unwrap();
+        let resistor = &library.symbols["Device_R"];
+        assert!(!resistor.power && resistor.hide_pin_numbers && !resistor.hide_pin_names);
+        assert_eq!(resistor.pins.len(), 2);
+        assert_eq!(resistor.pins[0].at, (0.0, 3.81));
+        assert_eq!(resistor.pins[0].orientation, 270);
+        assert_eq!(resistor.pins[0].electrical_type, "passive");
+        assert_eq!(resistor.graphics.len(), 1);
+
+        let ground = &library.symbols["power_GND"];
+        assert!(ground.power);
+        assert!(ground.pins[0].hidden);
+        assert_eq!(ground.pins[0].electrical_type, "power_in");
+        assert!(matches!(&ground.graphics[0].shape, Shape::Polyline { points } if points.len() == 6));
+
+        assert!(!is_legacy_library("* SPICE model\n.model D1N4148 D(Is=2.52n)"));
+    }
+
+    #[test]
+    fn test_legacy_project_netlist() {
+        let sources: BTreeMap<String, String> = [
+            ("hw/divider.pro", "update=22/05/2019\nversion=1\n"),
+            ("hw/divider.sch", ROOT),
+            ("hw/lower.sch", LOWER),
+            ("hw/divider-cache.lib", CACHE),
+        ]
+        .into_iter()
+        .map(|(path, text)| (path.to_string(), text.to_string()))
+        .collect();
+
+        let projects = load_projects(&sources).unwrap();
+        assert_eq!(projects.len(), 1);
+        let project = &projects[0];
+        assert_eq!(project.root_file, "hw/divider.sch");
+        assert!(project.unresolved_symbols.is_empty(), "{:?}", project.unresolved_symbols);
+        let references: Vec<_> = project.components().into_iter().map(|c| c.reference).collect();
+        assert_eq!(references, ["R1", "R2"]);
+
+        let nets = project.netlist();
+        let pins_of = |name: &str| -> Vec<String> {
+            nets.iter()
+                .find(|net| net.name == name)
+                .unwrap_or_else(|| panic!("no net {name} in {nets:?}"))
+                .nodes
+                .iter()
+                .map(|node| format!("{}.{}", node.reference, node.pin))
+                .collect()
+        };
+        assert_eq!(pins_of("VIN"), ["R1.1"]);
+        assert_eq!(pins_of("GND"), ["R2.2"]);
+        assert_eq!(pins_of("/Lower/VOUT"), ["R1.2", "R2.1"]);
+    }
+}
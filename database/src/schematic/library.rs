@@ -0,0 +1,434 @@
+// USAGE:
+// cargo test schematic::library -- --nocapture
+//
+// Symbol definitions for placed symbols whose lib_id isn't embedded in the
+// sheet's `lib_symbols` (hand-edited files, sheets saved by tools that skip
+// the cache, legacy sheets, which never embed them). Definitions come from
+// .kicad_sym and legacy .lib libraries in the repo, named by its
+// sym-lib-table or by file name, then from legacy projects' cache libraries,
+// and then from a directory of standard KiCad libraries, which are parsed
+// once and cached on disk as JSON.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+use super::hierarchy::join_path;
+use super::legacy::{is_legacy_library, parse_legacy_library};
+use super::model::{parse_lib_symbol, LibSymbol, SheetFile};
+use super::sexpr;
+use crate::error::SchematicError;
+
+/// One parsed .kicad_sym file, symbols keyed by name without the library nickname
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SymbolLibrary {
+    pub nickname: String,
+    pub symbols: HashMap<String, LibSymbol>,
+}
+
+/// Parse one .kicad_sym file; `file` is only used in error messages
+///
+/// Derived symbols (`extends`) get their parent's pins and body, keeping
+/// their own power flag.
+pub fn parse_library(file: &str, nickname: &str, text: &str) -> Result<SymbolLibrary, SchematicError> {
+    let root = sexpr::parse(file, text)?;
+    if root.head() != Some("kicad_symbol_lib") {
+        return Err(SchematicError::NotASymbolLibrary {
+            file: file.to_string(),
+            found: root.head().unwrap_or("").to_string(),
+        });
+    }
+
+    let mut symbols = HashMap::new();
+    let mut derived = Vec::new();
+    for expr in root.children("symbol") {
+        let Some(name) = expr.arg(0) else { continue };
+        let symbol = parse_lib_symbol(expr);
+        if let Some(parent) = expr.child("extends").and_then(|e| e.arg(0)) {
+            derived.push((name.to_string(), parent.to_string(), symbol.power));
+        }
+        symbols.insert(name.to_string(), symbol);
+    }
+    for (name, parent, power) in derived {
+        if let Some(parent) = symbols.get(&parent).cloned() {
+            symbols.insert(
+                name,
+                LibSymbol {
+                    power: power || parent.power,
+                    ..parent
+                },
+            );
+        }
+    }
+
+    Ok(SymbolLibrary {
+        nickname: nickname.to_string(),
+        symbols,
+    })
+}
+
+/// `Device:R` -> (`Device`, `R`)
+fn split_lib_id(lib_id: &str) -> Option<(&str, &str)> {
+    lib_id.split_once(':').filter(|(nickname, name)| !nickname.is_empty() && !name.is_empty())
+}
+
+/// Nicknames end up in file names, so only plain library names are looked up on disk
+fn is_plain_nickname(nickname: &str) -> bool {
+    !nickname.starts_with('.')
+        && nickname
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '+' | ' '))
+}
+
+/// The standard KiCad libraries in a directory (`<dir>/<nickname>.kicad_sym`), loaded on first use
+///
+/// Parsed libraries are kept in memory and, when `cache_dir` is set, written
+/// there as JSON keyed by the source file's size and modification time, so
+/// restarts don't re-parse multi-megabyte libraries.
+#[derive(Debug)]
+pub struct StandardLibraries {
+    dir: PathBuf,
+    cache_dir: Option<PathBuf>,
+    loaded: Mutex<HashMap<String, Option<Arc<SymbolLibrary>>>>,
+}
+
+impl StandardLibraries {
+    pub fn new(dir: impl Into<PathBuf>, cache_dir: Option<PathBuf>) -> Self {
+        StandardLibraries {
+            dir: dir.into(),
+            cache_dir,
+            loaded: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The library with this nickname, or `None` if there is no such (readable) file
+    pub fn get(&self, nickname: &str) -> Option<Arc<SymbolLibrary>> {
+        if !is_plain_nickname(nickname) {
+            return None;
+        }
+        let mut loaded = self.loaded.lock().unwrap_or_else(|e| e.into_inner());
+        loaded
+            .entry(nickname.to_string())
+            .or_insert_with(|| self.load(nickname).map(Arc::new))
+            .clone()
+    }
+
+    fn load(&self, nickname: &str) -> Option<SymbolLibrary> {
+        let path = self.dir.join(format!("{}.kicad_sym", nickname));
+        let metadata = std::fs::metadata(&path).ok()?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let cached = self
+            .cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}-{}-{}.json", nickname, metadata.len(), modified)));
+
+        if let Some(library) = cached
+            .as_ref()
+            .and_then(|c| std::fs::read(c).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        {
+            return Some(library);
+        }
+
+        let text = std::fs::read_to_string(&path).ok()?;
+        let library = match parse_library(&path.to_string_lossy(), nickname, &text) {
+            Ok(library) => library,
+            Err(e) => {
+                warn!("Skipping unreadable symbol library {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        if let Some(cached) = cached {
+            if let Err(e) = write_cache(&cached, &library) {
+                warn!("Could not cache symbol library {}: {}", nickname, e);
+            }
+        }
+        Some(library)
+    }
+}
+
+/// Write to a temp file next to `path` and rename, so readers never see a partial file
+fn write_cache(path: &std::path::Path, library: &SymbolLibrary) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    std::fs::write(&tmp, serde_json::to_vec(library)?)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Where a `sym-lib-table` entry points
+#[derive(Debug, PartialEq)]
+enum LibUri {
+    /// A path inside the repo
+    Repo(String),
+    /// The path after a variable such as `${VENDOR_LIBS}`, which may name a
+    /// library vendored into the repo, e.g. as a submodule
+    Variable(String),
+}
+
+/// Nickname -> library location from a `sym-lib-table`
+///
+/// `${KIPRJMOD}` and relative URIs resolve against the table's directory.
+/// Absolute paths point outside the repo and are left to the standard libraries.
+fn parse_lib_table(path: &str, text: &str) -> Vec<(String, LibUri)> {
+    let Ok(root) = sexpr::parse(path, text) else {
+        return Vec::new();
+    };
+    let dir = path.rfind('/').map(|i| &path[..i]).unwrap_or("");
+    root.children("lib")
+        .filter_map(|lib| {
+            let name = lib.child("name")?.arg(0)?;
+            let uri = lib.child("uri")?.arg(0)?.replace('\\', "/");
+            let location = match uri.strip_prefix("${KIPRJMOD}") {
+                Some(rest) => LibUri::Repo(join_path(dir, rest.trim_start_matches('/'))),
+                None if uri.starts_with("${") => {
+                    let rest = uri.split_once('}')?.1;
+                    LibUri::Variable(join_path("", rest))
+                }
+                None if uri.contains("${") || uri.starts_with('/') => return None,
+                None => LibUri::Repo(join_path(dir, &uri)),
+            };
+            Some((name.to_string(), location))
+        })
+        .collect()
+}
+
+/// The library in `sources` whose path ends with `suffix`, unless different
+/// libraries do (the same one may be listed twice through a symlink)
+fn find_by_suffix<'a>(sources: &'a BTreeMap<String, String>, suffix: &str) -> Option<&'a str> {
+    let mut found = sources
+        .iter()
+        .filter(|(p, _)| *p == suffix || p.ends_with(&format!("/{}", suffix)));
+    let (first, text) = found.next()?;
+    found.all(|(_, other)| other == text).then_some(first.as_str())
+}
+
+/// Where placed symbols without an embedded definition are looked up
+#[derive(Debug, Clone, Default)]
+pub struct SymbolLibraries {
+    repo: HashMap<String, Arc<SymbolLibrary>>,
+    /// Legacy `<project>-cache.lib` and `-rescue.lib` libraries, holding
+    /// every symbol a KiCad 5 project placed as `Device_R` for `Device:R`
+    caches: Vec<Arc<SymbolLibrary>>,
+    standard: Option<Arc<StandardLibraries>>,
+}
+
+impl SymbolLibraries {
+    /// The .kicad_sym and legacy .lib libraries in a set of files (path -> contents)
+    ///
+    /// Libraries listed in a sym-lib-table use its nicknames; every other
+    /// library is known by its file name. .lib files that aren't symbol
+    /// libraries (SPICE models) are left out. A table entry behind a path
+    /// variable matches the one library anywhere in the repo (such as in a
+    /// submodule) whose path ends the same way. Unreadable libraries are skipped.
+    pub fn from_sources(sources: &BTreeMap<String, String>) -> Self {
+        let mut nicknames: HashMap<&str, String> = HashMap::new();
+        for (path, text) in sources {
+            if path == "sym-lib-table" || path.ends_with("/sym-lib-table") {
+                for (nickname, location) in parse_lib_table(path, text) {
+                    let file = match &location {
+                        LibUri::Repo(file) => sources.get_key_value(file).map(|(key, _)| key.as_str()),
+                        LibUri::Variable(suffix) => find_by_suffix(sources, suffix),
+                    };
+                    if let Some(file) = file {
+                        nicknames.insert(file, nickname);
+                    }
+                }
+            }
+        }
+
+        let mut repo = HashMap::new();
+        let mut caches = Vec::new();
+        for (path, text) in sources {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            let (stem, parse): (_, fn(&str, &str, &str) -> _) = if let Some(stem) = name.strip_suffix(".kicad_sym") {
+                (stem, parse_library)
+            } else if let Some(stem) = name.strip_suffix(".lib").filter(|_| is_legacy_library(text)) {
+                (stem, parse_legacy_library)
+            } else {
+                continue;
+            };
+            let nickname = nicknames.get(path.as_str()).cloned().unwrap_or_else(|| stem.to_string());
+            match parse(path, &nickname, text) {
+                Ok(library) if stem.ends_with("-cache") || stem.ends_with("-rescue") => {
+                    caches.push(Arc::new(library));
+                }
+                Ok(library) => {
+                    repo.insert(nickname, Arc::new(library));
+                }
+                Err(e) => warn!("Skipping unreadable symbol library {}: {}", path, e),
+            }
+        }
+
+        SymbolLibraries {
+            repo,
+            caches,
+            standard: None,
+        }
+    }
+
+    /// Fall back to the standard libraries for nicknames the repo doesn't define
+    pub fn with_standard(mut self, standard: Arc<StandardLibraries>) -> Self {
+        self.standard = Some(standard);
+        self
+    }
+
+    /// The definition of `lib_id`, e.g. `Device:R`, or `R` in KiCad 4 sheets
+    pub fn resolve(&self, lib_id: &str) -> Option<LibSymbol> {
+        let split = split_lib_id(lib_id);
+        if let Some((nickname, name)) = split {
+            if let Some(library) = self.repo.get(nickname) {
+                return library.symbols.get(name).cloned();
+            }
+        }
+        // Caches name symbols without a nickname; KiCad 4 sheets place them so
+        let flattened = lib_id.replace(':', "_");
+        if let Some(symbol) = self.caches.iter().find_map(|cache| cache.symbols.get(&flattened)) {
+            return Some(symbol.clone());
+        }
+        let (nickname, name) = split?;
+        self.standard
+            .as_ref()?
+            .get(nickname)?
+            .symbols
+            .get(name)
+            .cloned()
+    }
+
+    /// Fill in missing `lib_symbols` for the symbols placed on a sheet
+    ///
+    /// Returns the lib_ids that could not be found anywhere.
+    pub fn resolve_sheet(&self, sheet: &mut SheetFile) -> Vec<String> {
+        let mut unresolved = Vec::new();
+        for symbol in &sheet.symbols {
+            let key = symbol.lib_key();
+            if sheet.lib_symbols.contains_key(key) || unresolved.contains(&symbol.lib_id) {
+                continue;
+            }
+            match self.resolve(&symbol.lib_id) {
+                Some(lib) => {
+                    sheet.lib_symbols.insert(key.to_string(), lib);
+                }
+                None => unresolved.push(symbol.lib_id.clone()),
+            }
+        }
+        unresolved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::model::parse_sheet;
+
+    const LIBRARY: &str = r#"(kicad_symbol_lib (version 20231120) (generator "kicad_symbol_editor")
+        (symbol "LDO" (pin_names (offset 0.254))
+            (symbol "LDO_0_1" (rectangle (start -5 2.54) (end 5 -2.54) (fill (type background))))
+            (symbol "LDO_1_1"
+                (pin power_in line (at -7.62 0 0) (length 2.54) (name "VIN") (number "1"))
+                (pin power_out line (at 7.62 0 180) (length 2.54) (name "VOUT") (number "3"))
+                (pin power_in line (at 0 -5.08 90) (length 2.54) (name "GND") (number "2"))))
+        (symbol "LDO_3V3" (extends "LDO") (property "Value" "LDO_3V3")))"#;
+
+    const SHEET: &str = r#"(kicad_sch (uuid "root")
+        (symbol (lib_id "power_parts:LDO_3V3") (at 50 50 0) (unit 1) (uuid "u1")
+            (property "Reference" "U1") (property "Value" "LDO_3V3"))
+        (symbol (lib_id "Missing:Thing") (at 80 50 0) (unit 1) (uuid "u2")
+            (property "Reference" "U2")))"#;
+
+    #[test]
+    fn test_parse_library_with_extends() {
+        let library = parse_library("p.kicad_sym", "p", LIBRARY).unwrap();
+        assert_eq!(library.symbols.len(), 2);
+        let ldo = &library.symbols["LDO_3V3"];
+        assert_eq!(ldo.pins.len(), 3);
+        assert_eq!(ldo.graphics.len(), 1);
+        assert_eq!(ldo.pins[0].name, "VIN");
+
+        let err = parse_library("x.kicad_sch", "x", "(kicad_sch)").unwrap_err();
+        assert!(matches!(err, SchematicError::NotASymbolLibrary { .. }));
+    }
+
+    #[test]
+    fn test_resolve_from_lib_table() {
+        let sources: BTreeMap<String, String> = [
+            ("hw/libs/parts.kicad_sym", LIBRARY),
+            (
+                "hw/sym-lib-table",
+                r#"(sym_lib_table (version 7)
+                    (lib (name "power_parts") (type "KiCad") (uri "${KIPRJMOD}/libs/parts.kicad_sym") (options "") (descr ""))
+                    (lib (name "Device") (type "KiCad") (uri "${KICAD8_SYMBOL_DIR}/Device.kicad_sym") (options "") (descr "")))"#,
+            ),
+        ]
+        .into_iter()
+        .map(|(p, c)| (p.to_string(), c.to_string()))
+        .collect();
+        let libraries = SymbolLibraries::from_sources(&sources);
+        assert!(libraries.resolve("power_parts:LDO").is_some());
+        assert!(libraries.resolve("parts:LDO").is_none());
+
+        let mut sheet = parse_sheet("top.kicad_sch", SHEET).unwrap();
+        let unresolved = libraries.resolve_sheet(&mut sheet);
+        assert_eq!(unresolved, ["Missing:Thing"]);
+        assert_eq!(sheet.lib_symbols["power_parts:LDO_3V3"].pins.len(), 3);
+    }
+
+    #[test]
+    fn test_variable_uris_match_vendored_libraries() {
+        let sources: BTreeMap<String, String> = [
+            ("vendor/acme-kicad/symbols/acme_power.kicad_sym", LIBRARY),
+            ("hw/libs/symbols/acme_power.kicad_sym", LIBRARY),
+            (
+                "hw/sym-lib-table",
+                r#"(sym_lib_table (version 7)
+                    (lib (name "acme") (type "KiCad") (uri "${ACME_LIBS}/symbols/acme_power.kicad_sym") (options "") (descr ""))
+                    (lib (name "Device") (type "KiCad") (uri "${KICAD8_SYMBOL_DIR}/Device.kicad_sym") (options "") (descr "")))"#,
+            ),
+        ]
+        .into_iter()
+        .map(|(p, c)| (p.to_string(), c.to_string()))
+        .collect();
+        let table = parse_lib_table("hw/sym-lib-table", &sources["hw/sym-lib-table"]);
+        assert_eq!(table[1], ("Device".to_string(), LibUri::Variable("Device.kicad_sym".to_string())));
+
+        let libraries = SymbolLibraries::from_sources(&sources);
+        assert!(libraries.resolve("acme:LDO_3V3").is_some());
+        assert!(libraries.resolve("Device:R").is_none());
+
+        // Different libraries ending the same way are ambiguous
+        let mut sources = sources;
+        sources.insert("old/symbols/acme_power.kicad_sym".to_string(), "(kicad_symbol_lib)".to_string());
+        assert!(!SymbolLibraries::from_sources(&sources).repo.contains_key("acme"));
+    }
+
+    #[test]
+    fn test_standard_libraries_are_cached_on_disk() {
+        let root = std::env::temp_dir().join(format!("kicad-db-symlib-test-{}", std::process::id()));
+        let (dir, cache) = (root.join("symbols"), root.join("cache"));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Regulator.kicad_sym"), LIBRARY).unwrap();
+
+        let standard = Arc::new(StandardLibraries::new(&dir, Some(cache.clone())));
+        let libraries = SymbolLibraries::default().with_standard(standard);
+        assert_eq!(libraries.resolve("Regulator:LDO").unwrap().pins.len(), 3);
+        assert!(libraries.resolve("Regulator:Nope").is_none());
+        assert!(libraries.resolve("../symbols/Regulator:LDO").is_none());
+        assert_eq!(std::fs::read_dir(&cache).unwrap().count(), 1);
+
+        // A fresh loader takes the cached JSON instead of re-parsing the file
+        let entry = std::fs::read_dir(&cache).unwrap().next().unwrap().unwrap().path();
+        let mut library: SymbolLibrary = serde_json::from_slice(&std::fs::read(&entry).unwrap()).unwrap();
+        library.symbols.insert("FromCache".to_string(), LibSymbol::default());
+        std::fs::write(&entry, serde_json::to_vec(&library).unwrap()).unwrap();
+        let fresh = StandardLibraries::new(&dir, Some(cache.clone()));
+        assert!(fresh.get("Regulator").unwrap().symbols.contains_key("FromCache"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
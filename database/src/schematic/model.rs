@@ -0,0 +1,792 @@
+// USAGE:
+// cargo test schematic::model -- --nocapture
+//
+// What a single schematic file contains, as far as connectivity and the BOM
+// are concerned. Sheet graphics, text and field positions are skipped; library
+// symbols keep their body outline and sheet symbols their box, so sheets can
+// be drawn. .kicad_sch files are read here; legacy .sch files by `legacy` and
+// Eagle's by `eagle`.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use super::{eagle, legacy};
+use super::sexpr::{self, SExpr};
+use crate::error::SchematicError;
+
+/// Schematic internal units per millimetre (KiCad uses 100 nm)
+const UNITS_PER_MM: f64 = 10_000.0;
+/// Coordinates are clamped to a kilometre either way: far beyond any real
+/// sheet, and small enough that differences between points can't overflow
+const MAX_MM: f64 = 1_000_000.0;
+
+/// A point on a sheet in internal units, so coincidence checks are exact
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Point {
+    pub fn from_mm(x: f64, y: f64) -> Self {
+        Point {
+            x: (x.clamp(-MAX_MM, MAX_MM) * UNITS_PER_MM).round() as i64,
+            y: (y.clamp(-MAX_MM, MAX_MM) * UNITS_PER_MM).round() as i64,
+        }
+    }
+
+    pub fn x_mm(&self) -> f64 {
+        self.x as f64 / UNITS_PER_MM
+    }
+
+    pub fn y_mm(&self) -> f64 {
+        self.y as f64 / UNITS_PER_MM
+    }
+
+    /// Whether the point lies on the segment `a`-`b`, endpoints included
+    pub fn is_on_segment(&self, a: Point, b: Point) -> bool {
+        let cross = (b.x - a.x) as i128 * (self.y - a.y) as i128
+            - (b.y - a.y) as i128 * (self.x - a.x) as i128;
+        cross == 0
+            && self.x >= a.x.min(b.x)
+            && self.x <= a.x.max(b.x)
+            && self.y >= a.y.min(b.y)
+            && self.y <= a.y.max(b.y)
+    }
+}
+
+/// A pin of a library symbol, in symbol coordinates (Y up)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LibPin {
+    pub number: String,
+    pub name: String,
+    /// KiCad electrical type, e.g. `input`, `power_in`, `passive`
+    pub electrical_type: String,
+    /// Unit the pin belongs to; 0 means every unit
+    pub unit: u32,
+    /// Body style (De Morgan) the pin belongs to; 0 means every style
+    pub body_style: u32,
+    pub hidden: bool,
+    /// Connection point relative to the symbol anchor, in mm
+    pub at: (f64, f64),
+    /// Direction from the connection point towards the body, in degrees
+    pub orientation: i32,
+    /// In mm
+    pub length: f64,
+}
+
+/// How a body shape is filled
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Fill {
+    #[default]
+    None,
+    /// Filled with the outline colour
+    Outline,
+    /// Filled with the body background colour
+    Background,
+}
+
+/// Outline of a library symbol body, in symbol coordinates (Y up, mm)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Shape {
+    Rectangle { start: (f64, f64), end: (f64, f64) },
+    Polyline { points: Vec<(f64, f64)> },
+    Circle { center: (f64, f64), radius: f64 },
+    /// Circular arc from `start` through `mid` to `end`
+    Arc { start: (f64, f64), mid: (f64, f64), end: (f64, f64) },
+}
+
+/// One body shape and the unit/body style it is drawn for
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LibGraphic {
+    /// 0 means every unit
+    pub unit: u32,
+    /// 0 means every body style
+    pub body_style: u32,
+    pub shape: Shape,
+    pub fill: Fill,
+}
+
+/// A symbol definition, embedded in the schematic's `lib_symbols` or read from a .kicad_sym library
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct LibSymbol {
+    /// Power symbols (GND, +3V3, ...) name a global net after their value
+    pub power: bool,
+    pub pins: Vec<LibPin>,
+    #[serde(default)]
+    pub graphics: Vec<LibGraphic>,
+    #[serde(default)]
+    pub hide_pin_names: bool,
+    #[serde(default)]
+    pub hide_pin_numbers: bool,
+}
+
+impl LibSymbol {
+    /// Pins drawn for one unit and body style
+    pub fn unit_pins(&self, unit: u32, body_style: u32) -> impl Iterator<Item = &LibPin> {
+        self.pins
+            .iter()
+            .filter(move |pin| pin.unit == 0 || pin.unit == unit)
+            .filter(move |pin| pin.body_style == 0 || pin.body_style == body_style)
+    }
+
+    /// Body shapes drawn for one unit and body style
+    pub fn unit_graphics(&self, unit: u32, body_style: u32) -> impl Iterator<Item = &LibGraphic> {
+        self.graphics
+            .iter()
+            .filter(move |g| g.unit == 0 || g.unit == unit)
+            .filter(move |g| g.body_style == 0 || g.body_style == body_style)
+    }
+
+    /// Units the symbol defines pins or graphics for, at least `[1]`
+    pub fn units(&self) -> Vec<u32> {
+        let mut units: Vec<u32> = self
+            .pins
+            .iter()
+            .map(|p| p.unit)
+            .chain(self.graphics.iter().map(|g| g.unit))
+            .filter(|&u| u > 0)
+            .collect();
+        units.sort_unstable();
+        units.dedup();
+        if units.is_empty() {
+            units.push(1);
+        }
+        units
+    }
+}
+
+/// A symbol placed on a sheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedSymbol {
+    pub lib_id: String,
+    /// Key into `lib_symbols` when it differs from `lib_id`
+    pub lib_name: Option<String>,
+    pub uuid: Option<String>,
+    /// Anchor position in mm
+    pub at: (f64, f64),
+    pub rotation: i32,
+    /// `x` or `y`
+    pub mirror: Option<char>,
+    pub unit: u32,
+    pub body_style: u32,
+    pub in_bom: bool,
+    pub dnp: bool,
+    pub properties: BTreeMap<String, String>,
+    /// Reference per sheet instance path, from the symbol's `instances`
+    pub instances: HashMap<String, String>,
+}
+
+/// Transformation matrix from symbol to sheet coordinates, as KiCad's TRANSFORM
+#[derive(Debug, Clone, Copy)]
+struct Transform {
+    x1: i64,
+    y1: i64,
+    x2: i64,
+    y2: i64,
+}
+
+impl Transform {
+    /// Apply `temp` on top of the current transform (KiCad's SetOrientation)
+    fn then(self, temp: Transform) -> Transform {
+        Transform {
+            x1: self.x1 * temp.x1 + self.x2 * temp.y1,
+            y1: self.y1 * temp.x1 + self.y2 * temp.y1,
+            x2: self.x1 * temp.x2 + self.x2 * temp.y2,
+            y2: self.y1 * temp.x2 + self.y2 * temp.y2,
+        }
+    }
+
+    fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            self.x1 as f64 * x + self.y1 as f64 * y,
+            self.x2 as f64 * x + self.y2 as f64 * y,
+        )
+    }
+}
+
+fn orientation_transform(rotation: i32, mirror: Option<char>) -> Transform {
+    // Symbol space has Y pointing up; the sheet has Y pointing down
+    let mut t = Transform { x1: 1, y1: 0, x2: 0, y2: -1 };
+    let counterclockwise = Transform { x1: 0, y1: 1, x2: -1, y2: 0 };
+    for _ in 0..(rotation.rem_euclid(360) / 90) {
+        t = t.then(counterclockwise);
+    }
+    match mirror {
+        Some('x') => t.then(Transform { x1: 1, y1: 0, x2: 0, y2: -1 }),
+        Some('y') => t.then(Transform { x1: -1, y1: 0, x2: 0, y2: 1 }),
+        _ => t,
+    }
+}
+
+/// Rotation and mirror of KiCad's orientation matrix `[x1, y1, x2, y2]`, as
+/// legacy files store it; None for a matrix no orientation gives
+pub(crate) fn orientation_of(matrix: [i64; 4]) -> Option<(i32, Option<char>)> {
+    [None, Some('x')]
+        .into_iter()
+        .flat_map(|mirror| [0, 90, 180, 270].map(|rotation| (rotation, mirror)))
+        .find(|&(rotation, mirror)| {
+            let t = orientation_transform(rotation, mirror);
+            [t.x1, t.y1, t.x2, t.y2] == matrix
+        })
+}
+
+impl PlacedSymbol {
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties.get(name).map(String::as_str)
+    }
+
+    pub fn reference(&self) -> &str {
+        self.property("Reference").unwrap_or("?")
+    }
+
+    pub fn value(&self) -> &str {
+        self.property("Value").unwrap_or("")
+    }
+
+    /// Name of the definition in `lib_symbols`
+    pub fn lib_key(&self) -> &str {
+        self.lib_name.as_deref().unwrap_or(&self.lib_id)
+    }
+
+    fn transform(&self) -> Transform {
+        orientation_transform(self.rotation, self.mirror)
+    }
+
+    /// Where a point in symbol coordinates lands on the sheet, in mm
+    pub fn to_sheet(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (dx, dy) = self.transform().apply(x, y);
+        (self.at.0 + dx, self.at.1 + dy)
+    }
+
+    /// The box around this unit's body and pins on the sheet, as
+    /// `(min, max)` in mm; None if the symbol draws nothing for it
+    pub fn bounds(&self, lib: &LibSymbol) -> Option<((f64, f64), (f64, f64))> {
+        let mut points = Vec::new();
+        for graphic in lib.unit_graphics(self.unit, self.body_style) {
+            match &graphic.shape {
+                Shape::Rectangle { start, end } => points.extend([*start, *end]),
+                Shape::Polyline { points: line } => points.extend(line.iter().copied()),
+                Shape::Circle { center, radius } => points.extend([
+                    (center.0 - radius, center.1 - radius),
+                    (center.0 + radius, center.1 + radius),
+                ]),
+                Shape::Arc { start, mid, end } => points.extend([*start, *mid, *end]),
+            }
+        }
+        for pin in lib.unit_pins(self.unit, self.body_style).filter(|pin| !pin.hidden) {
+            let towards = match pin.orientation.rem_euclid(360) {
+                90 => (0.0, 1.0),
+                180 => (-1.0, 0.0),
+                270 => (0.0, -1.0),
+                _ => (1.0, 0.0),
+            };
+            points.extend([pin.at, (pin.at.0 + towards.0 * pin.length, pin.at.1 + towards.1 * pin.length)]);
+        }
+        points.into_iter().map(|p| self.to_sheet(p)).fold(None, |bounds, (x, y)| match bounds {
+            None => Some(((x, y), (x, y))),
+            Some(((x1, y1), (x2, y2))) => Some(((x1.min(x), y1.min(y)), (x2.max(x), y2.max(y)))),
+        })
+    }
+
+    /// Pins of this unit with their connection points on the sheet
+    pub fn placed_pins<'a>(&self, lib: &'a LibSymbol) -> Vec<(&'a LibPin, Point)> {
+        lib.unit_pins(self.unit, self.body_style)
+            .map(|pin| {
+                let (x, y) = self.to_sheet(pin.at);
+                (pin, Point::from_mm(x, y))
+            })
+            .collect()
+    }
+}
+
+/// A pin on a sheet symbol, matched by name to a hierarchical label inside the sheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetPin {
+    pub name: String,
+    pub at: Point,
+}
+
+/// A hierarchical sheet symbol placed on a parent sheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetSymbol {
+    pub uuid: String,
+    pub name: String,
+    /// Path of the child file, relative to the project directory
+    pub file: String,
+    /// Top-left corner of the box, in mm
+    pub at: (f64, f64),
+    /// Width and height of the box, in mm
+    pub size: (f64, f64),
+    pub pins: Vec<SheetPin>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LabelKind {
+    /// Connects within one sheet instance
+    Local,
+    /// Connects to the matching pin of the parent's sheet symbol
+    Hierarchical,
+    /// Connects across the whole project
+    Global,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub kind: LabelKind,
+    pub text: String,
+    pub at: Point,
+}
+
+/// Free text drawn on a sheet, e.g. a design note; connects to nothing
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextNote {
+    pub text: String,
+    pub at: Point,
+}
+
+/// The parsed contents of one schematic file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SheetFile {
+    /// What the file was read from
+    pub format: SchematicFormat,
+    pub uuid: Option<String>,
+    pub lib_symbols: HashMap<String, LibSymbol>,
+    pub symbols: Vec<PlacedSymbol>,
+    pub sheets: Vec<SheetSymbol>,
+    pub wires: Vec<(Point, Point)>,
+    pub junctions: Vec<Point>,
+    pub no_connects: Vec<Point>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<TextNote>,
+    /// KiCad 6 root files list references here instead of on each symbol,
+    /// keyed by sheet path (without the root uuid) plus symbol uuid
+    pub legacy_instances: HashMap<String, String>,
+}
+
+fn at_of(expr: &SExpr) -> Option<(f64, f64, i32)> {
+    let at = expr.child("at")?;
+    Some((
+        at.number(0)?,
+        at.number(1)?,
+        at.number(2).unwrap_or(0.0).round() as i32,
+    ))
+}
+
+fn point_of(expr: &SExpr) -> Option<Point> {
+    at_of(expr).map(|(x, y, _)| Point::from_mm(x, y))
+}
+
+fn yes(expr: &SExpr, name: &str, default: bool) -> bool {
+    match expr.child(name).and_then(|e| e.arg(0)) {
+        Some(v) => v == "yes",
+        None => default,
+    }
+}
+
+fn properties_of(expr: &SExpr) -> BTreeMap<String, String> {
+    expr.children("property")
+        .filter_map(|p| Some((p.arg(0)?.to_string(), p.arg(1)?.to_string())))
+        .collect()
+}
+
+/// References from `(instances (project "x" (path "/..." (reference "R1") (unit 1))))`
+fn instances_of(expr: &SExpr) -> HashMap<String, String> {
+    let mut instances = HashMap::new();
+    for project in expr.child("instances").into_iter().flat_map(|i| i.children("project")) {
+        for path in project.children("path") {
+            if let (Some(p), Some(reference)) = (
+                path.arg(0),
+                path.child("reference").and_then(|r| r.arg(0)),
+            ) {
+                instances.insert(p.to_string(), reference.to_string());
+            }
+        }
+    }
+    instances
+}
+
+/// Unit and body style from a sub-symbol name such as `R_1_1`
+fn unit_and_style(name: &str) -> (u32, u32) {
+    let mut parts = name.rsplitn(3, '_');
+    let style = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+    let unit = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+    (unit, style)
+}
+
+fn parse_pin(pin: &SExpr, unit: u32, body_style: u32) -> Option<LibPin> {
+    let (x, y, orientation) = at_of(pin)?;
+    Some(LibPin {
+        number: pin.child("number").and_then(|n| n.arg(0)).unwrap_or("").to_string(),
+        name: pin.child("name").and_then(|n| n.arg(0)).unwrap_or("").to_string(),
+        electrical_type: pin.arg(0).unwrap_or("unspecified").to_string(),
+        unit,
+        body_style,
+        hidden: pin.has_flag("hide") || yes(pin, "hide", false),
+        at: (x, y),
+        orientation,
+        length: pin.child("length").and_then(|l| l.number(0)).unwrap_or(0.0),
+    })
+}
+
+fn xy(expr: &SExpr) -> Option<(f64, f64)> {
+    Some((expr.number(0)?, expr.number(1)?))
+}
+
+fn parse_shape(item: &SExpr) -> Option<Shape> {
+    let point = |name: &str| item.child(name).and_then(xy);
+    match item.head()? {
+        "rectangle" => Some(Shape::Rectangle {
+            start: point("start")?,
+            end: point("end")?,
+        }),
+        "polyline" => Some(Shape::Polyline {
+            points: item.child("pts")?.children("xy").filter_map(xy).collect(),
+        }),
+        "circle" => Some(Shape::Circle {
+            center: point("center")?,
+            radius: item.child("radius")?.number(0)?,
+        }),
+        "arc" => Some(Shape::Arc {
+            start: point("start")?,
+            mid: point("mid")?,
+            end: point("end")?,
+        }),
+        _ => None,
+    }
+}
+
+fn parse_graphic(item: &SExpr, unit: u32, body_style: u32) -> Option<LibGraphic> {
+    let fill = match item.child("fill").and_then(|f| f.child("type")).and_then(|t| t.arg(0)) {
+        Some("outline") => Fill::Outline,
+        Some("background") => Fill::Background,
+        _ => Fill::None,
+    };
+    Some(LibGraphic {
+        unit,
+        body_style,
+        shape: parse_shape(item)?,
+        fill,
+    })
+}
+
+/// `(pin_names hide)` in KiCad 6-8, `(pin_names (hide yes))` from KiCad 9
+fn hides(expr: &SExpr, name: &str) -> bool {
+    expr.child(name).is_some_and(|e| e.has_flag("hide") || yes(e, "hide", false))
+}
+
+pub(crate) fn parse_lib_symbol(expr: &SExpr) -> LibSymbol {
+    let mut symbol = LibSymbol {
+        power: expr.child("power").is_some(),
+        hide_pin_names: hides(expr, "pin_names"),
+        hide_pin_numbers: hides(expr, "pin_numbers"),
+        ..Default::default()
+    };
+    let mut add_items = |parent: &SExpr, unit: u32, style: u32| {
+        for item in parent.items() {
+            match item.head() {
+                Some("pin") => symbol.pins.extend(parse_pin(item, unit, style)),
+                Some("rectangle" | "polyline" | "circle" | "arc") => {
+                    symbol.graphics.extend(parse_graphic(item, unit, style))
+                }
+                _ => {}
+            }
+        }
+    };
+    add_items(expr, 0, 0);
+    for unit in expr.children("symbol") {
+        let (unit_number, style) = unit.arg(0).map(unit_and_style).unwrap_or((0, 0));
+        add_items(unit, unit_number, style);
+    }
+    symbol
+}
+
+pub(crate) fn parse_symbol(expr: &SExpr) -> Option<PlacedSymbol> {
+    let (x, y, rotation) = at_of(expr)?;
+    Some(PlacedSymbol {
+        lib_id: expr.child("lib_id")?.arg(0)?.to_string(),
+        lib_name: expr.child("lib_name").and_then(|e| e.arg(0)).map(str::to_string),
+        uuid: expr.child("uuid").and_then(|e| e.arg(0)).map(str::to_string),
+        at: (x, y),
+        rotation,
+        mirror: expr
+            .child("mirror")
+            .and_then(|m| m.arg(0))
+            .and_then(|m| m.chars().next()),
+        unit: expr.child("unit").and_then(|u| u.number(0)).unwrap_or(1.0) as u32,
+        body_style: expr
+            .child("body_style")
+            .or_else(|| expr.child("convert"))
+            .and_then(|c| c.number(0))
+            .unwrap_or(1.0) as u32,
+        in_bom: yes(expr, "in_bom", true),
+        dnp: yes(expr, "dnp", false),
+        properties: properties_of(expr),
+        instances: instances_of(expr),
+    })
+}
+
+fn parse_sheet_symbol(expr: &SExpr) -> Option<SheetSymbol> {
+    let properties = properties_of(expr);
+    let name = properties
+        .get("Sheetname")
+        .or_else(|| properties.get("Sheet name"))?;
+    let file = properties
+        .get("Sheetfile")
+        .or_else(|| properties.get("Sheet file"))?;
+    Some(SheetSymbol {
+        uuid: expr.child("uuid").and_then(|u| u.arg(0)).unwrap_or("").to_string(),
+        name: name.clone(),
+        file: file.clone(),
+        at: at_of(expr).map_or((0.0, 0.0), |(x, y, _)| (x, y)),
+        size: expr.child("size").and_then(xy).unwrap_or((0.0, 0.0)),
+        pins: expr
+            .children("pin")
+            .filter_map(|pin| {
+                Some(SheetPin {
+                    name: pin.arg(0)?.to_string(),
+                    at: point_of(pin)?,
+                })
+            })
+            .collect(),
+    })
+}
+
+fn parse_wire(expr: &SExpr) -> Option<(Point, Point)> {
+    let mut points = expr
+        .child("pts")?
+        .children("xy")
+        .filter_map(|xy| Some(Point::from_mm(xy.number(0)?, xy.number(1)?)));
+    Some((points.next()?, points.next()?))
+}
+
+/// How a schematic file is written, told by how it starts rather than its name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum SchematicFormat {
+    /// S-expressions (.kicad_sch), KiCad 6 on
+    #[default]
+    SExpr,
+    /// Legacy EESchema (.sch), KiCad 4 and 5
+    Legacy,
+    /// Eagle XML (.sch), Eagle 6 on
+    Eagle,
+}
+
+impl SchematicFormat {
+    /// The format of `text`, or None if it isn't a schematic we can read
+    pub fn detect(text: &str) -> Option<Self> {
+        let start = text.trim_start_matches('\u{feff}').trim_start();
+        if start.starts_with(legacy::SCHEMATIC_HEADER) {
+            Some(SchematicFormat::Legacy)
+        } else if start.strip_prefix('(').is_some_and(|rest| rest.trim_start().starts_with("kicad_sch")) {
+            Some(SchematicFormat::SExpr)
+        } else if eagle::is_eagle_schematic(start) {
+            Some(SchematicFormat::Eagle)
+        } else {
+            None
+        }
+    }
+
+    /// Name as reported by the API
+    pub fn name(self) -> &'static str {
+        match self {
+            SchematicFormat::SExpr => "kicad",
+            SchematicFormat::Legacy => "kicad_legacy",
+            SchematicFormat::Eagle => "eagle",
+        }
+    }
+}
+
+/// Parse one schematic file, in any format; `file` is only used in error messages
+pub fn parse_sheet(file: &str, text: &str) -> Result<SheetFile, SchematicError> {
+    match SchematicFormat::detect(text) {
+        Some(SchematicFormat::Legacy) => legacy::parse_legacy_sheet(file, text),
+        Some(SchematicFormat::Eagle) => eagle::parse_eagle_sheet(file, text),
+        _ => read_sheet(file, &sexpr::parse(file, text)?),
+    }
+}
+
+/// The sheet in a .kicad_sch file's expression
+pub(crate) fn read_sheet(file: &str, root: &SExpr) -> Result<SheetFile, SchematicError> {
+    if root.head() != Some("kicad_sch") {
+        return Err(SchematicError::NotASchematic {
+            file: file.to_string(),
+            found: root.head().unwrap_or("").to_string(),
+        });
+    }
+
+    let mut sheet = SheetFile {
+        uuid: root.child("uuid").and_then(|u| u.arg(0)).map(str::to_string),
+        ..Default::default()
+    };
+
+    if let Some(lib_symbols) = root.child("lib_symbols") {
+        for symbol in lib_symbols.children("symbol") {
+            if let Some(name) = symbol.arg(0) {
+                sheet.lib_symbols.insert(name.to_string(), parse_lib_symbol(symbol));
+            }
+        }
+    }
+
+    for item in root.items() {
+        match item.head() {
+            Some("symbol") => sheet.symbols.extend(parse_symbol(item)),
+            Some("sheet") => sheet.sheets.extend(parse_sheet_symbol(item)),
+            Some("wire") => sheet.wires.extend(parse_wire(item)),
+            Some("junction") => sheet.junctions.extend(point_of(item)),
+            Some("no_connect") => sheet.no_connects.extend(point_of(item)),
+            Some(head @ ("label" | "global_label" | "hierarchical_label")) => {
+                let kind = match head {
+                    "label" => LabelKind::Local,
+                    "global_label" => LabelKind::Global,
+                    _ => LabelKind::Hierarchical,
+                };
+                if let (Some(text), Some(at)) = (item.arg(0), point_of(item)) {
+                    sheet.labels.push(Label {
+                        kind,
+                        text: text.to_string(),
+                        at,
+                    });
+                }
+            }
+            Some("text" | "text_box") => {
+                if let (Some(text), Some(at)) = (item.arg(0), point_of(item)) {
+                    sheet.notes.push(TextNote {
+                        text: text.to_string(),
+                        at,
+                    });
+                }
+            }
+            Some("symbol_instances") => {
+                for path in item.children("path") {
+                    if let (Some(p), Some(reference)) =
+                        (path.arg(0), path.child("reference").and_then(|r| r.arg(0)))
+                    {
+                        sheet
+                            .legacy_instances
+                            .insert(p.to_string(), reference.to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(sheet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESISTOR: &str = r#"(kicad_sch (version 20250114) (uuid "root")
+        (lib_symbols
+            (symbol "Device:R"
+                (symbol "R_1_1"
+                    (pin passive line (at 0 3.81 270) (length 1.27) (name "~") (number "1"))
+                    (pin passive line (at 0 -3.81 90) (length 1.27) (name "~") (number "2")))))
+        (symbol (lib_id "Device:R") (at 100 50 90) (unit 1) (in_bom yes) (dnp no) (uuid "r1")
+            (property "Reference" "R1") (property "Value" "10k"))
+        (wire (pts (xy 96.19 50) (xy 90 50)))
+        (label "SIG" (at 90 50 0))
+        (text "Keep R1 near the ADC" (at 80 40 0))
+    )"#;
+
+    fn pin_at(sheet: &SheetFile, symbol: &PlacedSymbol, number: &str) -> Point {
+        let lib = &sheet.lib_symbols[symbol.lib_key()];
+        symbol
+            .placed_pins(lib)
+            .into_iter()
+            .find(|(pin, _)| pin.number == number)
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn test_parse_sheet() {
+        let sheet = parse_sheet("r.kicad_sch", RESISTOR).unwrap();
+        assert_eq!(sheet.uuid.as_deref(), Some("root"));
+        assert_eq!(sheet.symbols.len(), 1);
+        assert_eq!(sheet.symbols[0].reference(), "R1");
+        assert_eq!(sheet.symbols[0].value(), "10k");
+        assert_eq!(sheet.wires.len(), 1);
+        assert_eq!(sheet.labels[0].kind, LabelKind::Local);
+        assert_eq!(sheet.notes[0].text, "Keep R1 near the ADC");
+        assert_eq!(sheet.lib_symbols["Device:R"].pins.len(), 2);
+    }
+
+    #[test]
+    fn test_lib_symbol_graphics() {
+        let text = r#"(symbol "Device:C" (pin_numbers hide) (pin_names (offset 0.254) hide)
+            (symbol "C_0_1"
+                (polyline (pts (xy -2.032 -0.762) (xy 2.032 -0.762)) (stroke (width 0.508)) (fill (type none)))
+                (rectangle (start -1 1) (end 1 -1) (fill (type background))))
+            (symbol "C_1_1"
+                (pin passive line (at 0 3.81 270) (length 2.794) (name "~") (number "1"))))"#;
+        let symbol = parse_lib_symbol(&sexpr::parse("c", text).unwrap());
+        assert!(symbol.hide_pin_names && symbol.hide_pin_numbers);
+        assert_eq!(symbol.graphics.len(), 2);
+        assert_eq!(symbol.graphics[1].fill, Fill::Background);
+        assert_eq!(symbol.pins[0].orientation, 270);
+        assert_eq!(symbol.pins[0].length, 2.794);
+        assert_eq!(symbol.unit_graphics(1, 1).count(), 2);
+        assert_eq!(symbol.units(), [1]);
+    }
+
+    #[test]
+    fn test_pin_transform() {
+        let mut sheet = parse_sheet("r.kicad_sch", RESISTOR).unwrap();
+        let mut symbol = sheet.symbols.remove(0);
+
+        // Rotated 90 degrees counterclockwise, pin 1 (top) ends up on the left
+        assert_eq!(pin_at(&sheet, &symbol, "1"), Point::from_mm(96.19, 50.0));
+        assert_eq!(pin_at(&sheet, &symbol, "2"), Point::from_mm(103.81, 50.0));
+
+        symbol.rotation = 0;
+        assert_eq!(pin_at(&sheet, &symbol, "1"), Point::from_mm(100.0, 46.19));
+
+        symbol.mirror = Some('x');
+        assert_eq!(pin_at(&sheet, &symbol, "1"), Point::from_mm(100.0, 53.81));
+    }
+
+    #[test]
+    fn test_not_a_schematic() {
+        let err = parse_sheet("x.kicad_pcb", "(kicad_pcb (version 1))").unwrap_err();
+        assert!(matches!(err, SchematicError::NotASchematic { .. }));
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(SchematicFormat::detect(RESISTOR), Some(SchematicFormat::SExpr));
+        assert_eq!(
+            SchematicFormat::detect("\u{feff}EESchema Schematic File Version 4\nEELAYER 30 0\n"),
+            Some(SchematicFormat::Legacy)
+        );
+        assert_eq!(SchematicFormat::detect("(kicad_pcb (version 1))"), None);
+        assert_eq!(
+            SchematicFormat::detect("<?xml version=\"1.0\"?>\n<eagle version=\"9.6\"><drawing><schematic/></drawing></eagle>"),
+            Some(SchematicFormat::Eagle)
+        );
+        assert_eq!(SchematicFormat::detect("<?xml version=\"1.0\"?><eagle><drawing><board/></drawing></eagle>"), None);
+    }
+
+    #[test]
+    fn test_orientation_of_matrix() {
+        assert_eq!(orientation_of([1, 0, 0, -1]), Some((0, None)));
+        assert_eq!(orientation_of([0, -1, -1, 0]), Some((90, None)));
+        assert_eq!(orientation_of([0, 1, 1, 0]), Some((270, None)));
+        assert_eq!(orientation_of([1, 0, 0, 1]), Some((0, Some('x'))));
+        assert_eq!(orientation_of([2, 0, 0, -1]), None);
+    }
+
+    #[test]
+    fn test_point_on_segment() {
+        let a = Point::from_mm(0.0, 0.0);
+        let b = Point::from_mm(10.0, 0.0);
+        assert!(Point::from_mm(5.0, 0.0).is_on_segment(a, b));
+        assert!(b.is_on_segment(a, b));
+        assert!(!Point::from_mm(11.0, 0.0).is_on_segment(a, b));
+        assert!(!Point::from_mm(5.0, 0.1).is_on_segment(a, b));
+    }
+}
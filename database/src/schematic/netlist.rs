@@ -0,0 +1,614 @@
+// USAGE:
+// cargo test schematic::netlist -- --nocapture
+//
+// Connectivity for a whole project. Each sheet instance is wired up on its
+// own (pins, wires, junctions and labels meeting at the same point), then
+// instances are stitched together through named anchors: local labels per
+// instance, hierarchical labels to their parent's sheet pins, and global
+// labels and power symbols project-wide. The drawn items each net is made of
+// are kept too, so a net's wires and pins can be found on every sheet.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::hierarchy::{natural_key, Project};
+use super::model::{LabelKind, Point};
+
+/// A component pin on a net
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NetNode {
+    pub reference: String,
+    pub pin: String,
+    /// Pin name from the symbol, if it has one
+    pub pin_name: Option<String>,
+    pub pin_type: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Net {
+    pub name: String,
+    /// In natural reference order, then pin number
+    pub nodes: Vec<NetNode>,
+}
+
+/// How a net leaves or names itself on a sheet
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectorKind {
+    LocalLabel,
+    GlobalLabel,
+    HierarchicalLabel,
+    /// A pin on a sheet symbol, leading into the child sheet
+    SheetPin,
+    PowerSymbol,
+}
+
+/// A label, sheet pin or power symbol on a net
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NetConnector {
+    pub kind: ConnectorKind,
+    pub name: String,
+    /// Connection point, in mm
+    pub at: (f64, f64),
+    /// Sheet paths the net continues on through this connector: the child
+    /// of a sheet pin, the parent of a hierarchical label, and the other
+    /// sheets with a global label or power symbol of the same name
+    pub continues_to: Vec<String>,
+}
+
+/// A component pin on a net, where it's drawn
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NetPin {
+    pub reference: String,
+    pub pin: String,
+    /// Pin tip, in mm
+    pub at: (f64, f64),
+}
+
+/// What a net is drawn with on one sheet instance
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NetSheet {
+    pub sheet_path: String,
+    pub file: String,
+    /// Wire segments, end to end in mm
+    pub wires: Vec<((f64, f64), (f64, f64))>,
+    pub junctions: Vec<(f64, f64)>,
+    pub pins: Vec<NetPin>,
+    pub connectors: Vec<NetConnector>,
+}
+
+/// Everything drawn for a net, sheet by sheet in hierarchy order
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NetGeometry {
+    pub name: String,
+    pub sheets: Vec<NetSheet>,
+}
+
+/// Where a net name comes from; earlier variants win
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum NameSource {
+    PowerSymbol,
+    GlobalLabel,
+    LocalLabel,
+    HierarchicalLabel,
+    HiddenPowerPin,
+}
+
+/// A possible name for a net: source, sheet depth, text
+type NameCandidate = (NameSource, usize, String);
+
+enum Item {
+    Pin(NetNode),
+    Name { source: NameSource, depth: usize, name: String },
+    Anchor,
+}
+
+/// Something drawn on a sheet instance that a graph item stands for
+enum Feature {
+    Wire(Point, Point),
+    Junction(Point),
+    Pin { reference: String, pin: String, at: Point },
+    /// `target` is the instance a sheet pin or hierarchical label leads to
+    Connector {
+        kind: ConnectorKind,
+        name: String,
+        at: Point,
+        target: Option<usize>,
+    },
+}
+
+/// A project's connectivity: the graph, and the drawn features of each
+/// sheet instance as (item, instance, feature)
+struct Connectivity {
+    graph: Graph,
+    features: Vec<(usize, usize, Feature)>,
+}
+
+struct Graph {
+    items: Vec<Item>,
+    parent: Vec<usize>,
+    anchors: HashMap<String, usize>,
+}
+
+impl Graph {
+    fn add(&mut self, item: Item) -> usize {
+        self.items.push(item);
+        self.parent.push(self.parent.len());
+        self.parent.len() - 1
+    }
+
+    fn find(&mut self, mut id: usize) -> usize {
+        while self.parent[id] != id {
+            self.parent[id] = self.parent[self.parent[id]];
+            id = self.parent[id];
+        }
+        id
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[b] = a;
+        }
+    }
+
+    /// Join an item to the named anchor shared by everything with the same key
+    fn attach(&mut self, id: usize, key: String) {
+        let anchor = match self.anchors.get(&key) {
+            Some(&anchor) => anchor,
+            None => {
+                let anchor = self.add(Item::Anchor);
+                self.anchors.insert(key, anchor);
+                anchor
+            }
+        };
+        self.union(id, anchor);
+    }
+
+    fn name(&mut self, id: usize, source: NameSource, depth: usize, name: String) {
+        let name_id = self.add(Item::Name { source, depth, name });
+        self.union(id, name_id);
+    }
+}
+
+impl Project {
+    /// Nets of the whole hierarchy; nets without any component pin are dropped
+    pub fn netlist(&self) -> Vec<Net> {
+        let mut connectivity = self.connect();
+        let mut nets: Vec<Net> = named_nets(&mut connectivity.graph).into_iter().map(|(_, net)| net).collect();
+        nets.sort_by(|a, b| a.name.cmp(&b.name));
+        nets
+    }
+
+    /// The wires, junctions, pins and connectors of the net called `name` on
+    /// each sheet it's drawn on, or None if there's no such net
+    pub fn net_geometry(&self, name: &str) -> Option<NetGeometry> {
+        let mut connectivity = self.connect();
+        let (root, net) = named_nets(&mut connectivity.graph)
+            .into_iter()
+            .find(|(_, net)| net.name == name)?;
+
+        let graph = &mut connectivity.graph;
+        let mut sheets: BTreeMap<usize, Vec<&Feature>> = BTreeMap::new();
+        for (id, instance, feature) in &connectivity.features {
+            if graph.find(*id) == root {
+                sheets.entry(*instance).or_default().push(feature);
+            }
+        }
+
+        // Global labels and power symbols continue on every sheet that has one
+        // of the same name
+        let mut globals: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (&instance, features) in &sheets {
+            for feature in features {
+                if let Feature::Connector {
+                    kind: ConnectorKind::GlobalLabel | ConnectorKind::PowerSymbol,
+                    name,
+                    ..
+                } = feature
+                {
+                    let on = globals.entry(name.as_str()).or_default();
+                    if !on.contains(&instance) {
+                        on.push(instance);
+                    }
+                }
+            }
+        }
+
+        let mm = |p: &Point| (p.x_mm(), p.y_mm());
+        let sheets = sheets
+            .iter()
+            .map(|(&index, features)| {
+                let instance = &self.instances[index];
+                let mut sheet = NetSheet {
+                    sheet_path: instance.sheet_path.clone(),
+                    file: instance.file.clone(),
+                    wires: Vec::new(),
+                    junctions: Vec::new(),
+                    pins: Vec::new(),
+                    connectors: Vec::new(),
+                };
+                for feature in features {
+                    match feature {
+                        Feature::Wire(a, b) => sheet.wires.push((mm(a), mm(b))),
+                        Feature::Junction(at) => sheet.junctions.push(mm(at)),
+                        Feature::Pin { reference, pin, at } => sheet.pins.push(NetPin {
+                            reference: reference.clone(),
+                            pin: pin.clone(),
+                            at: mm(at),
+                        }),
+                        Feature::Connector { kind, name, at, target } => {
+                            let targets = match kind {
+                                ConnectorKind::LocalLabel => Vec::new(),
+                                ConnectorKind::SheetPin | ConnectorKind::HierarchicalLabel => {
+                                    target.iter().copied().filter(|t| sheets.contains_key(t)).collect()
+                                }
+                                ConnectorKind::GlobalLabel | ConnectorKind::PowerSymbol => globals[name.as_str()]
+                                    .iter()
+                                    .copied()
+                                    .filter(|&other| other != index)
+                                    .collect(),
+                            };
+                            sheet.connectors.push(NetConnector {
+                                kind: *kind,
+                                name: name.clone(),
+                                at: mm(at),
+                                continues_to: targets
+                                    .into_iter()
+                                    .map(|t| self.instances[t].sheet_path.clone())
+                                    .collect(),
+                            });
+                        }
+                    }
+                }
+                sheet.pins.sort_by(|a, b| {
+                    natural_key(&a.reference)
+                        .cmp(&natural_key(&b.reference))
+                        .then_with(|| natural_key(&a.pin).cmp(&natural_key(&b.pin)))
+                });
+                sheet
+            })
+            .collect();
+
+        Some(NetGeometry { name: net.name, sheets })
+    }
+
+    fn connect(&self) -> Connectivity {
+        let mut graph = Graph {
+            items: Vec::new(),
+            parent: Vec::new(),
+            anchors: HashMap::new(),
+        };
+        let mut features = Vec::new();
+
+        for (index, instance) in self.instances.iter().enumerate() {
+            let file = &self.files[&instance.file];
+            let depth = instance.depth;
+            let mut points: Vec<(Point, usize)> = Vec::new();
+
+            for symbol in &file.symbols {
+                let Some(lib) = file.lib_symbols.get(symbol.lib_key()) else {
+                    continue;
+                };
+                let reference = self.reference_of(instance, symbol);
+                for (pin, at) in symbol.placed_pins(lib) {
+                    if lib.power {
+                        let id = graph.add(Item::Anchor);
+                        points.push((at, id));
+                        let net = symbol.value().to_string();
+                        graph.name(id, NameSource::PowerSymbol, depth, net.clone());
+                        graph.attach(id, format!("global:{}", net));
+                        features.push((
+                            id,
+                            index,
+                            Feature::Connector {
+                                kind: ConnectorKind::PowerSymbol,
+                                name: net,
+                                at,
+                                target: None,
+                            },
+                        ));
+                        continue;
+                    }
+                    let id = graph.add(Item::Pin(NetNode {
+                        reference: reference.clone(),
+                        pin: pin.number.clone(),
+                        pin_name: Some(pin.name.clone()).filter(|n| !n.is_empty() && n != "~"),
+                        pin_type: pin.electrical_type.clone(),
+                    }));
+                    points.push((at, id));
+                    features.push((
+                        id,
+                        index,
+                        Feature::Pin {
+                            reference: reference.clone(),
+                            pin: pin.number.clone(),
+                            at,
+                        },
+                    ));
+                    // Invisible power pins still join the global net of their name
+                    if pin.hidden && pin.electrical_type == "power_in" && !pin.name.is_empty() {
+                        graph.name(id, NameSource::HiddenPowerPin, depth, pin.name.clone());
+                        graph.attach(id, format!("global:{}", pin.name));
+                    }
+                }
+            }
+
+            let mut wires = Vec::with_capacity(file.wires.len());
+            for &(a, b) in &file.wires {
+                let id = graph.add(Item::Anchor);
+                points.push((a, id));
+                points.push((b, id));
+                wires.push((a, b, id));
+                features.push((id, index, Feature::Wire(a, b)));
+            }
+
+            for &at in &file.junctions {
+                let id = graph.add(Item::Anchor);
+                points.push((at, id));
+                features.push((id, index, Feature::Junction(at)));
+            }
+
+            for label in &file.labels {
+                let id = graph.add(Item::Anchor);
+                points.push((label.at, id));
+                let (source, key, name) = match label.kind {
+                    LabelKind::Local => (
+                        NameSource::LocalLabel,
+                        format!("local:{}:{}", instance.path, label.text),
+                        format!("{}{}", instance.sheet_path, label.text),
+                    ),
+                    LabelKind::Hierarchical => (
+                        NameSource::HierarchicalLabel,
+                        format!("hier:{}:{}", instance.path, label.text),
+                        format!("{}{}", instance.sheet_path, label.text),
+                    ),
+                    LabelKind::Global => (
+                        NameSource::GlobalLabel,
+                        format!("global:{}", label.text),
+                        label.text.clone(),
+                    ),
+                };
+                graph.name(id, source, depth, name);
+                graph.attach(id, key);
+                let (kind, target) = match label.kind {
+                    LabelKind::Local => (ConnectorKind::LocalLabel, None),
+                    LabelKind::Hierarchical => (ConnectorKind::HierarchicalLabel, instance.parent),
+                    LabelKind::Global => (ConnectorKind::GlobalLabel, None),
+                };
+                features.push((
+                    id,
+                    index,
+                    Feature::Connector {
+                        kind,
+                        name: label.text.clone(),
+                        at: label.at,
+                        target,
+                    },
+                ));
+            }
+
+            // Sheet pins meet the child instance's hierarchical labels
+            for sheet in &file.sheets {
+                let child_path = format!("{}/{}", instance.path, sheet.uuid);
+                let child = self.instances.iter().position(|i| i.path == child_path);
+                for pin in &sheet.pins {
+                    let id = graph.add(Item::Anchor);
+                    points.push((pin.at, id));
+                    graph.attach(id, format!("hier:{}:{}", child_path, pin.name));
+                    features.push((
+                        id,
+                        index,
+                        Feature::Connector {
+                            kind: ConnectorKind::SheetPin,
+                            name: pin.name.clone(),
+                            at: pin.at,
+                            target: child,
+                        },
+                    ));
+                }
+            }
+
+            points.sort_unstable();
+            for pair in points.windows(2) {
+                if pair[0].0 == pair[1].0 {
+                    graph.union(pair[0].1, pair[1].1);
+                }
+            }
+
+            // Anything touching a wire part-way along it (T-joints, labels, pins)
+            for (a, b, wire) in wires {
+                let start = points.partition_point(|(p, _)| p.x < a.x.min(b.x));
+                let end = points.partition_point(|(p, _)| p.x <= a.x.max(b.x));
+                for &(p, id) in &points[start..end] {
+                    if p.is_on_segment(a, b) {
+                        graph.union(wire, id);
+                    }
+                }
+            }
+        }
+
+        Connectivity { graph, features }
+    }
+}
+
+/// Every net with a component pin, named, with the graph root it grew from
+fn named_nets(graph: &mut Graph) -> Vec<(usize, Net)> {
+    let mut groups: BTreeMap<usize, (Vec<NetNode>, Vec<NameCandidate>)> = BTreeMap::new();
+    for id in 0..graph.items.len() {
+        let root = graph.find(id);
+        match &graph.items[id] {
+            Item::Pin(node) => groups.entry(root).or_default().0.push(node.clone()),
+            Item::Name { source, depth, name } => groups
+                .entry(root)
+                .or_default()
+                .1
+                .push((*source, *depth, name.clone())),
+            Item::Anchor => {}
+        }
+    }
+
+    let mut nets = Vec::new();
+    let mut used_names = HashSet::new();
+    for (root, (mut nodes, names)) in groups {
+        if nodes.is_empty() {
+            continue;
+        }
+        nodes.sort_by(|a, b| {
+            natural_key(&a.reference)
+                .cmp(&natural_key(&b.reference))
+                .then_with(|| natural_key(&a.pin).cmp(&natural_key(&b.pin)))
+        });
+        nodes.dedup();
+
+        let mut name = match names.into_iter().min() {
+            Some((_, _, name)) => name,
+            None => {
+                let first = &nodes[0];
+                let prefix = if nodes.len() == 1 { "unconnected" } else { "Net" };
+                format!("{}-({}-Pad{})", prefix, first.reference, first.pin)
+            }
+        };
+        // Labels of different kinds can share text without sharing a net
+        if !used_names.insert(name.clone()) {
+            let mut n = 2;
+            while !used_names.insert(format!("{}_{}", name, n)) {
+                n += 1;
+            }
+            name = format!("{}_{}", name, n);
+        }
+        nets.push((root, Net { name, nodes }));
+    }
+    nets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    const LIBS: &str = r#"(lib_symbols
+        (symbol "Device:R"
+            (symbol "R_1_1"
+                (pin passive line (at 0 3.81 270) (length 1.27) (name "~") (number "1"))
+                (pin passive line (at 0 -3.81 90) (length 1.27) (name "~") (number "2"))))
+        (symbol "power:GND" (power)
+            (symbol "GND_0_1"
+                (pin power_in line (at 0 0 270) (length 0) (hide yes) (name "GND") (number "1")))))"#;
+
+    fn project() -> Project {
+        // Root: R1 from a GND symbol up to a sheet pin "IN" of sheet "Child"
+        let root = format!(
+            r##"(kicad_sch (uuid "root") {LIBS}
+            (symbol (lib_id "Device:R") (at 10 10 0) (unit 1) (uuid "r1")
+                (property "Reference" "R1") (property "Value" "1k"))
+            (symbol (lib_id "power:GND") (at 10 13.81 0) (unit 1) (uuid "g1")
+                (property "Reference" "#PWR01") (property "Value" "GND"))
+            (wire (pts (xy 10 6.19) (xy 10 0)))
+            (wire (pts (xy 10 0) (xy 30 0)))
+            (sheet (at 30 -5) (uuid "s1")
+                (property "Sheetname" "Child") (property "Sheetfile" "child.kicad_sch")
+                (pin "IN" input (at 30 0 180))))"##
+        );
+        // Child: R2 between the hierarchical label and a global label
+        let child = format!(
+            r#"(kicad_sch (uuid "child") {LIBS}
+            (symbol (lib_id "Device:R") (at 50 50 0) (unit 1) (uuid "r2")
+                (property "Reference" "R2") (property "Value" "2k"))
+            (hierarchical_label "IN" (shape input) (at 50 46.19 0))
+            (wire (pts (xy 50 53.81) (xy 50 60)))
+            (wire (pts (xy 40 60) (xy 60 60)))
+            (global_label "VSENSE" (shape output) (at 40 60 0))
+            (label "MID" (at 45 60 0)))"#
+        );
+        let sources: BTreeMap<String, String> = [
+            ("top.kicad_sch".to_string(), root),
+            ("child.kicad_sch".to_string(), child),
+        ]
+        .into_iter()
+        .collect();
+        Project::load("top.kicad_sch", &sources).unwrap()
+    }
+
+    fn members(nets: &[Net], name: &str) -> Vec<String> {
+        nets.iter()
+            .find(|n| n.name == name)
+            .unwrap_or_else(|| panic!("no net {name} in {nets:?}"))
+            .nodes
+            .iter()
+            .map(|n| format!("{}.{}", n.reference, n.pin))
+            .collect()
+    }
+
+    #[test]
+    fn test_nets_cross_sheets() {
+        let nets = project().netlist();
+        let names: Vec<&str> = nets.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, ["/Child/IN", "GND", "VSENSE"]);
+
+        assert_eq!(members(&nets, "GND"), ["R1.2"]);
+        // Root wire -> sheet pin -> hierarchical label -> R2 pin 1
+        assert_eq!(members(&nets, "/Child/IN"), ["R1.1", "R2.1"]);
+        // The global label outranks the local one sitting mid-wire
+        assert_eq!(members(&nets, "VSENSE"), ["R2.2"]);
+    }
+
+    #[test]
+    fn test_net_geometry_follows_sheets() {
+        let project = project();
+        let geometry = project.net_geometry("/Child/IN").unwrap();
+        let sheets: Vec<&str> = geometry.sheets.iter().map(|s| s.sheet_path.as_str()).collect();
+        assert_eq!(sheets, ["/", "/Child/"]);
+
+        let root = &geometry.sheets[0];
+        assert_eq!(root.wires, [((10.0, 6.19), (10.0, 0.0)), ((10.0, 0.0), (30.0, 0.0))]);
+        assert_eq!(root.pins, [NetPin { reference: "R1".into(), pin: "1".into(), at: (10.0, 6.19) }]);
+        assert_eq!(
+            root.connectors,
+            [NetConnector {
+                kind: ConnectorKind::SheetPin,
+                name: "IN".into(),
+                at: (30.0, 0.0),
+                continues_to: vec!["/Child/".into()],
+            }]
+        );
+
+        // The hierarchical label leads back up to the sheet symbol
+        let child = &geometry.sheets[1];
+        assert_eq!(child.file, "child.kicad_sch");
+        assert!(child.wires.is_empty());
+        assert_eq!(child.pins[0].reference, "R2");
+        assert_eq!(child.connectors[0].kind, ConnectorKind::HierarchicalLabel);
+        assert_eq!(child.connectors[0].continues_to, ["/"]);
+
+        // The local label on VSENSE's wire is drawn with it but leads nowhere
+        let vsense = project.net_geometry("VSENSE").unwrap();
+        assert_eq!(vsense.sheets.len(), 1);
+        assert_eq!(vsense.sheets[0].wires.len(), 2);
+        let kinds: Vec<ConnectorKind> = vsense.sheets[0].connectors.iter().map(|c| c.kind).collect();
+        assert_eq!(kinds, [ConnectorKind::GlobalLabel, ConnectorKind::LocalLabel]);
+        assert!(vsense.sheets[0].connectors.iter().all(|c| c.continues_to.is_empty()));
+
+        assert!(project.net_geometry("/MID").is_none());
+    }
+
+    #[test]
+    fn test_unnamed_nets() {
+        let sources: BTreeMap<String, String> = [(
+            "a.kicad_sch".to_string(),
+            format!(
+                r#"(kicad_sch (uuid "a") {LIBS}
+                (symbol (lib_id "Device:R") (at 0 0 0) (unit 1) (property "Reference" "R1"))
+                (symbol (lib_id "Device:R") (at 0 7.62 0) (unit 1) (property "Reference" "R2")))"#
+            ),
+        )]
+        .into_iter()
+        .collect();
+        let nets = Project::load("a.kicad_sch", &sources).unwrap().netlist();
+        let names: Vec<&str> = nets.iter().map(|n| n.name.as_str()).collect();
+        // R1 pin 2 and R2 pin 1 touch directly; the outer pins float
+        assert_eq!(
+            names,
+            ["Net-(R1-Pad2)", "unconnected-(R1-Pad1)", "unconnected-(R2-Pad2)"]
+        );
+    }
+}
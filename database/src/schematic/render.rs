@@ -0,0 +1,686 @@
+// USAGE:
+// cargo test schematic::render -- --nocapture
+//
+// Draws one unit of a library symbol as a standalone SVG: the body outline,
+// pins with their names and numbers, and a caption. A symbol that couldn't be
+// resolved is drawn as a labelled placeholder box instead. Whole sheets are
+// drawn the same way, with their wires, labels and sheet symbols, on the
+// smallest landscape paper they fit and with a title block.
+use std::fmt::Write;
+
+use super::hierarchy::Project;
+use super::model::{Fill, LabelKind, LibPin, LibSymbol, PlacedSymbol, Shape};
+
+const TEXT_SIZE_MM: f64 = 1.27;
+/// Gap between a pin's inner end and its name
+const NAME_OFFSET_MM: f64 = 0.508;
+const MARGIN_MM: f64 = 2.54;
+const PX_PER_MM: f64 = 10.0;
+const PLACEHOLDER_HALF_MM: f64 = 5.08;
+/// Gap between a symbol's body and its reference and value
+const FIELD_OFFSET_MM: f64 = 0.635;
+const JUNCTION_RADIUS_MM: f64 = 0.457;
+const NO_CONNECT_HALF_MM: f64 = 0.635;
+/// Landscape ISO paper, smallest first; a sheet goes on the first it fits
+const PAPER_SIZES_MM: [(f64, f64); 5] = [(297.0, 210.0), (420.0, 297.0), (594.0, 420.0), (841.0, 594.0), (1189.0, 841.0)];
+/// Space kept clear around a sheet's contents; the frame runs through its middle
+const PAGE_MARGIN_MM: f64 = 10.0;
+const TITLE_BLOCK_WIDTH_MM: f64 = 110.0;
+const TITLE_ROW_MM: f64 = 5.0;
+
+// KiCad's default schematic colours
+const BODY_COLOR: &str = "#840000";
+const BODY_BACKGROUND: &str = "#ffffc2";
+const PIN_NAME_COLOR: &str = "#006464";
+const PIN_NUMBER_COLOR: &str = "#a90000";
+const WIRE_COLOR: &str = "#009600";
+const NO_CONNECT_COLOR: &str = "#0000c8";
+const LABEL_COLOR: &str = "#000000";
+const GLOBAL_LABEL_COLOR: &str = "#840000";
+const HIERARCHICAL_LABEL_COLOR: &str = "#7e4000";
+const SHEET_COLOR: &str = "#840084";
+const SHEET_BACKGROUND: &str = "#ffffe1";
+const FIELD_COLOR: &str = "#006464";
+const FRAME_COLOR: &str = "#840000";
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Pin names use `~{...}` for overbars; the SVG shows the plain text
+fn display_name(name: &str) -> &str {
+    name.strip_prefix("~{")
+        .and_then(|n| n.strip_suffix('}'))
+        .unwrap_or(name)
+}
+
+/// Rough extent of a label, enough to keep it inside the view box
+fn text_width(text: &str) -> f64 {
+    text.chars().count() as f64 * TEXT_SIZE_MM * 0.6
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl Bounds {
+    fn empty() -> Self {
+        Bounds {
+            min: (f64::INFINITY, f64::INFINITY),
+            max: (f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    fn add(&mut self, (x, y): (f64, f64)) {
+        self.min = (self.min.0.min(x), self.min.1.min(y));
+        self.max = (self.max.0.max(x), self.max.1.max(y));
+    }
+
+    fn is_empty(&self) -> bool {
+        self.min.0 > self.max.0
+    }
+
+    fn merge(&mut self, other: &Bounds) {
+        if !other.is_empty() {
+            self.add(other.min);
+            self.add(other.max);
+        }
+    }
+}
+
+/// Where a point in symbol coordinates (Y up, mm) lands in the drawing
+type Place<'a> = &'a dyn Fn((f64, f64)) -> (f64, f64);
+
+/// Symbol coordinates have Y up, SVG has Y down
+fn flip((x, y): (f64, f64)) -> (f64, f64) {
+    (x, -y)
+}
+
+fn text(out: &mut String, (x, y): (f64, f64), size: f64, anchor: &str, color: &str, label: &str) {
+    let _ = writeln!(
+        out,
+        r#"<text x="{:.3}" y="{:.3}" font-size="{}" text-anchor="{}" dominant-baseline="middle" fill="{}">{}</text>"#,
+        x,
+        y,
+        size,
+        anchor,
+        color,
+        escape_xml(label)
+    );
+}
+
+fn line(out: &mut String, a: (f64, f64), b: (f64, f64), color: &str, width: f64) {
+    let _ = writeln!(
+        out,
+        r#"<line x1="{:.3}" y1="{:.3}" x2="{:.3}" y2="{:.3}" stroke="{}" stroke-width="{}"/>"#,
+        a.0, a.1, b.0, b.1, color, width
+    );
+}
+
+/// SVG path for the arc from `start` through `mid` to `end` (already flipped)
+fn arc_path(start: (f64, f64), mid: (f64, f64), end: (f64, f64)) -> String {
+    let (ax, ay) = start;
+    let (bx, by) = mid;
+    let (cx, cy) = end;
+    let d = 2.0 * (ax * (by - cy) + bx * (cy - ay) + cx * (ay - by));
+    if d.abs() < 1e-9 {
+        return format!("M {:.3} {:.3} L {:.3} {:.3} L {:.3} {:.3}", ax, ay, bx, by, cx, cy);
+    }
+    let a2 = ax * ax + ay * ay;
+    let b2 = bx * bx + by * by;
+    let c2 = cx * cx + cy * cy;
+    let ux = (a2 * (by - cy) + b2 * (cy - ay) + c2 * (ay - by)) / d;
+    let uy = (a2 * (cx - bx) + b2 * (ax - cx) + c2 * (bx - ax)) / d;
+    let radius = (ax - ux).hypot(ay - uy);
+
+    let angle = |(x, y): (f64, f64)| (y - uy).atan2(x - ux);
+    let tau = std::f64::consts::TAU;
+    let to_mid = (angle(mid) - angle(start)).rem_euclid(tau);
+    let to_end = (angle(end) - angle(start)).rem_euclid(tau);
+    let (sweep, extent) = if to_mid <= to_end { (1, to_end) } else { (0, tau - to_end) };
+    let large = u8::from(extent > std::f64::consts::PI);
+    format!(
+        "M {:.3} {:.3} A {:.3} {:.3} 0 {} {} {:.3} {:.3}",
+        ax, ay, radius, radius, large, sweep, cx, cy
+    )
+}
+
+fn fill_attr(fill: Fill) -> &'static str {
+    match fill {
+        Fill::None => "none",
+        Fill::Outline => BODY_COLOR,
+        Fill::Background => BODY_BACKGROUND,
+    }
+}
+
+fn draw_shape(out: &mut String, bounds: &mut Bounds, shape: &Shape, fill: Fill, place: Place) {
+    let style = format!(
+        r#"fill="{}" stroke="{}" stroke-width="0.254""#,
+        fill_attr(fill),
+        BODY_COLOR
+    );
+    match shape {
+        Shape::Rectangle { start, end } => {
+            let (a, b) = (place(*start), place(*end));
+            bounds.add(a);
+            bounds.add(b);
+            let _ = writeln!(
+                out,
+                r#"<rect x="{:.3}" y="{:.3}" width="{:.3}" height="{:.3}" {}/>"#,
+                a.0.min(b.0),
+                a.1.min(b.1),
+                (a.0 - b.0).abs(),
+                (a.1 - b.1).abs(),
+                style
+            );
+        }
+        Shape::Polyline { points } => {
+            let points: Vec<String> = points
+                .iter()
+                .map(|p| {
+                    let p = place(*p);
+                    bounds.add(p);
+                    format!("{:.3},{:.3}", p.0, p.1)
+                })
+                .collect();
+            let _ = writeln!(out, r#"<polyline points="{}" {}/>"#, points.join(" "), style);
+        }
+        Shape::Circle { center, radius } => {
+            let c = place(*center);
+            bounds.add((c.0 - radius, c.1 - radius));
+            bounds.add((c.0 + radius, c.1 + radius));
+            let _ = writeln!(
+                out,
+                r#"<circle cx="{:.3}" cy="{:.3}" r="{:.3}" {}/>"#,
+                c.0, c.1, radius, style
+            );
+        }
+        Shape::Arc { start, mid, end } => {
+            let (s, m, e) = (place(*start), place(*mid), place(*end));
+            for p in [s, m, e] {
+                bounds.add(p);
+            }
+            let _ = writeln!(out, r#"<path d="{}" {}/>"#, arc_path(s, m, e), style);
+        }
+    }
+}
+
+fn draw_pin(out: &mut String, bounds: &mut Bounds, pin: &LibPin, symbol: &LibSymbol, place: Place) {
+    // Unit vector from the connection point towards the body, in symbol space
+    let towards = match pin.orientation.rem_euclid(360) {
+        90 => (0.0, 1.0),
+        180 => (-1.0, 0.0),
+        270 => (0.0, -1.0),
+        _ => (1.0, 0.0),
+    };
+    // ... and in the drawing
+    let start = place(pin.at);
+    let ahead = place((pin.at.0 + towards.0, pin.at.1 + towards.1));
+    let (dx, dy) = ((ahead.0 - start.0).round(), (ahead.1 - start.1).round());
+    let end = (start.0 + dx * pin.length, start.1 + dy * pin.length);
+    bounds.add(start);
+    bounds.add(end);
+    line(out, start, end, BODY_COLOR, 0.152);
+
+    let vertical = dx == 0.0;
+    let pin_text = |out: &mut String, (x, y): (f64, f64), anchor: &str, color: &str, label: &str| {
+        let rotate = if vertical {
+            format!(r#" transform="rotate(-90 {:.3} {:.3})""#, x, y)
+        } else {
+            String::new()
+        };
+        let _ = writeln!(
+            out,
+            r#"<text x="{:.3}" y="{:.3}" font-size="{}" text-anchor="{}" dominant-baseline="middle" fill="{}"{}>{}</text>"#,
+            x, y, TEXT_SIZE_MM, anchor, color, rotate, escape_xml(label)
+        );
+    };
+
+    if !symbol.hide_pin_numbers && !pin.number.is_empty() {
+        // Centred along the pin, just above it (left of it once rotated)
+        let mid = ((start.0 + end.0) / 2.0, (start.1 + end.1) / 2.0);
+        let at = if vertical {
+            (mid.0 - TEXT_SIZE_MM * 0.6, mid.1)
+        } else {
+            (mid.0, mid.1 - TEXT_SIZE_MM * 0.6)
+        };
+        pin_text(out, at, "middle", PIN_NUMBER_COLOR, &pin.number);
+    }
+
+    let name = display_name(&pin.name);
+    if !symbol.hide_pin_names && !name.is_empty() && name != "~" {
+        let at = (end.0 + dx * NAME_OFFSET_MM, end.1 + dy * NAME_OFFSET_MM);
+        // Text runs away from the pin: rightwards, or upwards once rotated
+        let anchor = if dx > 0.0 || dy < 0.0 { "start" } else { "end" };
+        pin_text(out, at, anchor, PIN_NAME_COLOR, name);
+        let width = text_width(name);
+        bounds.add((at.0 + dx * width, at.1 + dy * width));
+    }
+}
+
+/// A dashed box with a question mark, for a symbol with nothing to draw
+fn draw_placeholder(out: &mut String, bounds: &mut Bounds, (x, y): (f64, f64), half: f64) {
+    bounds.add((x - half, y - half));
+    bounds.add((x + half, y + half));
+    let _ = writeln!(
+        out,
+        r#"<rect x="{:.3}" y="{:.3}" width="{:.3}" height="{:.3}" fill="none" stroke="{}" stroke-width="0.254" stroke-dasharray="1 0.5"/>"#,
+        x - half,
+        y - half,
+        2.0 * half,
+        2.0 * half,
+        BODY_COLOR
+    );
+    text(out, (x, y), TEXT_SIZE_MM * 2.0, "middle", BODY_COLOR, "?");
+}
+
+/// Render one unit/body style of `symbol` as an SVG document
+///
+/// `caption` (usually the reference and lib_id) is written under the symbol.
+/// With no definition, or one with nothing to draw, a dashed placeholder box
+/// is rendered so callers always get an image.
+pub fn render_symbol_svg(symbol: Option<&LibSymbol>, unit: u32, body_style: u32, caption: &str) -> String {
+    let mut body = String::new();
+    let mut bounds = Bounds::empty();
+
+    if let Some(symbol) = symbol {
+        for graphic in symbol.unit_graphics(unit, body_style) {
+            draw_shape(&mut body, &mut bounds, &graphic.shape, graphic.fill, &flip);
+        }
+        for pin in symbol.unit_pins(unit, body_style).filter(|p| !p.hidden) {
+            draw_pin(&mut body, &mut bounds, pin, symbol, &flip);
+        }
+    }
+
+    if bounds.is_empty() {
+        draw_placeholder(&mut body, &mut bounds, (0.0, 0.0), PLACEHOLDER_HALF_MM);
+    }
+
+    let caption_y = bounds.max.1 + MARGIN_MM;
+    let centre_x = (bounds.min.0 + bounds.max.0) / 2.0;
+    let half_caption = text_width(caption) / 2.0;
+    bounds.add((centre_x - half_caption, caption_y + TEXT_SIZE_MM));
+    bounds.add((centre_x + half_caption, caption_y));
+
+    let x = bounds.min.0 - MARGIN_MM;
+    let y = bounds.min.1 - MARGIN_MM;
+    let width = bounds.max.0 - bounds.min.0 + 2.0 * MARGIN_MM;
+    let height = bounds.max.1 - bounds.min.1 + 2.0 * MARGIN_MM;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{:.3} {:.3} {:.3} {:.3}" width="{:.0}" height="{:.0}" font-family="sans-serif">"#,
+        x,
+        y,
+        width,
+        height,
+        width * PX_PER_MM,
+        height * PX_PER_MM
+    );
+    out.push_str(&body);
+    let _ = writeln!(
+        out,
+        r##"<text x="{:.3}" y="{:.3}" font-size="{}" text-anchor="middle" dominant-baseline="middle" fill="#555">{}</text>"##,
+        centre_x,
+        caption_y,
+        TEXT_SIZE_MM,
+        escape_xml(caption)
+    );
+    out.push_str("</svg>\n");
+    out
+}
+
+/// Draw one placed unit with its reference and value beside it; power
+/// symbols show only their value, under them
+fn draw_placed(out: &mut String, bounds: &mut Bounds, symbol: &PlacedSymbol, lib: Option<&LibSymbol>, reference: &str) {
+    let place = |p| symbol.to_sheet(p);
+    let mut body = Bounds::empty();
+    if let Some(lib) = lib {
+        for graphic in lib.unit_graphics(symbol.unit, symbol.body_style) {
+            draw_shape(out, &mut body, &graphic.shape, graphic.fill, &place);
+        }
+        for pin in lib.unit_pins(symbol.unit, symbol.body_style).filter(|p| !p.hidden) {
+            draw_pin(out, &mut body, pin, lib, &place);
+        }
+    }
+    if body.is_empty() {
+        draw_placeholder(out, &mut body, symbol.at, PLACEHOLDER_HALF_MM / 2.0);
+    }
+    bounds.merge(&body);
+
+    let power = lib.is_some_and(|lib| lib.power) || reference.starts_with('#');
+    let value = symbol.value();
+    if power {
+        let at = ((body.min.0 + body.max.0) / 2.0, body.max.1 + TEXT_SIZE_MM);
+        text(out, at, TEXT_SIZE_MM, "middle", FIELD_COLOR, value);
+        bounds.add((at.0, at.1 + TEXT_SIZE_MM / 2.0));
+        return;
+    }
+    let x = body.max.0 + FIELD_OFFSET_MM;
+    let middle = (body.min.1 + body.max.1) / 2.0;
+    let fields = [(reference, middle - TEXT_SIZE_MM * 0.6), (value, middle + TEXT_SIZE_MM * 0.6)];
+    for (field, y) in fields.into_iter().filter(|(field, _)| !field.is_empty()) {
+        text(out, (x, y), TEXT_SIZE_MM, "start", FIELD_COLOR, field);
+        bounds.add((x + text_width(field), y));
+    }
+}
+
+/// What the title block of a drawn sheet says
+#[derive(Debug, Clone, Copy)]
+pub struct TitleBlock<'a> {
+    pub repo: &'a str,
+    pub commit: &'a str,
+    /// The commit's date, as printed
+    pub date: &'a str,
+    /// This sheet's page, from 1
+    pub page: usize,
+    pub pages: usize,
+}
+
+/// The page a sheet with these contents goes on, as `(x, y, width, height)`
+/// in mm: the smallest paper they fit inside the margin, else one sized to
+/// them with room for the title block below
+fn page_of(bounds: &Bounds) -> (f64, f64, f64, f64) {
+    let fits = |(width, height): &(f64, f64)| {
+        bounds.is_empty()
+            || (bounds.min.0 >= PAGE_MARGIN_MM
+                && bounds.min.1 >= PAGE_MARGIN_MM
+                && bounds.max.0 <= width - PAGE_MARGIN_MM
+                && bounds.max.1 <= height - PAGE_MARGIN_MM)
+    };
+    if let Some((width, height)) = PAPER_SIZES_MM.iter().find(|paper| fits(paper)) {
+        return (0.0, 0.0, *width, *height);
+    }
+    let x = (bounds.min.0 - PAGE_MARGIN_MM).min(0.0);
+    let y = (bounds.min.1 - PAGE_MARGIN_MM).min(0.0);
+    let width = (bounds.max.0 + PAGE_MARGIN_MM - x).max(TITLE_BLOCK_WIDTH_MM + 2.0 * PAGE_MARGIN_MM);
+    let height = bounds.max.1 + PAGE_MARGIN_MM + 5.0 * TITLE_ROW_MM - y;
+    (x, y, width, height)
+}
+
+/// Render one sheet instance of `project` as an SVG document sized in mm
+///
+/// Placed symbols are drawn from their definitions with their references in
+/// this instance, along with wires, junctions, no-connect flags, labels and
+/// the boxes of child sheets. A frame goes round the page and a title block
+/// in its bottom right corner names the sheet and `title`'s repo, commit,
+/// date and page.
+pub fn render_sheet_svg(project: &Project, instance: usize, title: &TitleBlock) -> String {
+    let sheet = &project.instances[instance];
+    let file = &project.files[&sheet.file];
+    let mut body = String::new();
+    let mut bounds = Bounds::empty();
+
+    for (a, b) in &file.wires {
+        let (a, b) = ((a.x_mm(), a.y_mm()), (b.x_mm(), b.y_mm()));
+        bounds.add(a);
+        bounds.add(b);
+        line(&mut body, a, b, WIRE_COLOR, 0.152);
+    }
+    for point in &file.junctions {
+        let _ = writeln!(
+            body,
+            r#"<circle cx="{:.3}" cy="{:.3}" r="{}" fill="{}"/>"#,
+            point.x_mm(),
+            point.y_mm(),
+            JUNCTION_RADIUS_MM,
+            WIRE_COLOR
+        );
+    }
+    for point in &file.no_connects {
+        let (x, y) = (point.x_mm(), point.y_mm());
+        let half = NO_CONNECT_HALF_MM;
+        bounds.add((x - half, y - half));
+        bounds.add((x + half, y + half));
+        line(&mut body, (x - half, y - half), (x + half, y + half), NO_CONNECT_COLOR, 0.152);
+        line(&mut body, (x - half, y + half), (x + half, y - half), NO_CONNECT_COLOR, 0.152);
+    }
+    for label in &file.labels {
+        let color = match label.kind {
+            LabelKind::Local => LABEL_COLOR,
+            LabelKind::Global => GLOBAL_LABEL_COLOR,
+            LabelKind::Hierarchical => HIERARCHICAL_LABEL_COLOR,
+        };
+        // Just above the wire it names
+        let at = (label.at.x_mm(), label.at.y_mm() - TEXT_SIZE_MM * 0.6);
+        text(&mut body, at, TEXT_SIZE_MM, "start", color, &label.text);
+        bounds.add(at);
+        bounds.add((at.0 + text_width(&label.text), at.1));
+    }
+    for child in &file.sheets {
+        let ((x, y), (width, height)) = (child.at, child.size);
+        bounds.add((x, y - TEXT_SIZE_MM));
+        bounds.add((x + width, y + height + TEXT_SIZE_MM));
+        let _ = writeln!(
+            body,
+            r#"<rect x="{:.3}" y="{:.3}" width="{:.3}" height="{:.3}" fill="{}" stroke="{}" stroke-width="0.254"/>"#,
+            x, y, width, height, SHEET_BACKGROUND, SHEET_COLOR
+        );
+        text(&mut body, (x, y - TEXT_SIZE_MM * 0.6), TEXT_SIZE_MM, "start", SHEET_COLOR, &child.name);
+        text(&mut body, (x, y + height + TEXT_SIZE_MM * 0.6), TEXT_SIZE_MM, "start", SHEET_COLOR, &child.file);
+        for pin in &child.pins {
+            // Named inside the box, from the edge the pin sits on
+            let (px, py) = (pin.at.x_mm(), pin.at.y_mm());
+            let (at, anchor) = if px < x + width / 2.0 {
+                ((px + NAME_OFFSET_MM, py), "start")
+            } else {
+                ((px - NAME_OFFSET_MM, py), "end")
+            };
+            text(&mut body, at, TEXT_SIZE_MM, anchor, HIERARCHICAL_LABEL_COLOR, &pin.name);
+        }
+    }
+    for symbol in &file.symbols {
+        let lib = file.lib_symbols.get(symbol.lib_key());
+        draw_placed(&mut body, &mut bounds, symbol, lib, &project.reference_of(sheet, symbol));
+    }
+
+    let (x, y, width, height) = page_of(&bounds);
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{:.3} {:.3} {:.3} {:.3}" width="{:.3}mm" height="{:.3}mm" font-family="sans-serif">"#,
+        x, y, width, height, width, height
+    );
+    let inset = PAGE_MARGIN_MM / 2.0;
+    let _ = writeln!(
+        out,
+        r#"<rect x="{:.3}" y="{:.3}" width="{:.3}" height="{:.3}" fill="none" stroke="{}" stroke-width="0.254"/>"#,
+        x + inset,
+        y + inset,
+        width - 2.0 * inset,
+        height - 2.0 * inset,
+        FRAME_COLOR
+    );
+    out.push_str(&body);
+
+    let name = match sheet.name.is_empty() {
+        true => project.name.as_str(),
+        false => sheet.name.as_str(),
+    };
+    let rows = [
+        (format!("{} ({})", name, sheet.sheet_path), format!("File: {}", sheet.file)),
+        (format!("Repo: {}", title.repo), String::new()),
+        (format!("Commit: {}", title.commit), String::new()),
+        (format!("Date: {}", title.date), format!("Page {}/{}", title.page, title.pages)),
+    ];
+    let left = x + width - inset - TITLE_BLOCK_WIDTH_MM;
+    let right = x + width - inset;
+    let top = y + height - inset - rows.len() as f64 * TITLE_ROW_MM;
+    let _ = writeln!(
+        out,
+        r##"<rect x="{:.3}" y="{:.3}" width="{:.3}" height="{:.3}" fill="#ffffff" stroke="{}" stroke-width="0.254"/>"##,
+        left,
+        top,
+        TITLE_BLOCK_WIDTH_MM,
+        rows.len() as f64 * TITLE_ROW_MM,
+        FRAME_COLOR
+    );
+    for (i, (first, second)) in rows.iter().enumerate() {
+        let row_top = top + i as f64 * TITLE_ROW_MM;
+        if i > 0 {
+            line(&mut out, (left, row_top), (right, row_top), FRAME_COLOR, 0.152);
+        }
+        let middle = row_top + TITLE_ROW_MM / 2.0;
+        text(&mut out, (left + 1.0, middle), TEXT_SIZE_MM * 1.5, "start", LABEL_COLOR, first);
+        if !second.is_empty() {
+            text(&mut out, (right - 1.0, middle), TEXT_SIZE_MM * 1.5, "end", LABEL_COLOR, second);
+        }
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::model::{LibGraphic, LibPin};
+
+    fn pin(number: &str, name: &str, at: (f64, f64), orientation: i32, unit: u32) -> LibPin {
+        LibPin {
+            number: number.to_string(),
+            name: name.to_string(),
+            electrical_type: "passive".to_string(),
+            unit,
+            body_style: 0,
+            hidden: false,
+            at,
+            orientation,
+            length: 2.54,
+        }
+    }
+
+    #[test]
+    fn test_renders_pins_and_body() {
+        let symbol = LibSymbol {
+            pins: vec![
+                pin("1", "VIN", (-7.62, 0.0), 0, 1),
+                pin("2", "GND", (0.0, -5.08), 90, 1),
+                pin("8", "~{EN}", (7.62, 0.0), 180, 2),
+            ],
+            graphics: vec![LibGraphic {
+                unit: 0,
+                body_style: 0,
+                shape: Shape::Rectangle { start: (-5.08, 2.54), end: (5.08, -2.54) },
+                fill: Fill::Background,
+            }],
+            ..Default::default()
+        };
+        let svg = render_symbol_svg(Some(&symbol), 1, 1, "U1 Regulator:LDO & <co>");
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains(r##"<rect x="-5.080" y="-2.540" width="10.160" height="5.080" fill="#ffffc2""##));
+        assert!(svg.contains(">VIN</text>"));
+        assert!(svg.contains(">GND</text>"));
+        assert!(svg.contains("rotate(-90"));
+        // Unit 2 pins stay out of unit 1
+        assert!(!svg.contains(">EN</text>"));
+        assert!(svg.contains("U1 Regulator:LDO &amp; &lt;co&gt;"));
+        assert!(!svg.contains("stroke-dasharray"));
+
+        let unit2 = render_symbol_svg(Some(&symbol), 2, 1, "U1");
+        assert!(unit2.contains(">EN</text>"));
+    }
+
+    #[test]
+    fn test_placeholder_when_unresolved() {
+        let svg = render_symbol_svg(None, 1, 1, "U2 Missing:Thing");
+        assert!(svg.contains("stroke-dasharray"));
+        assert!(svg.contains(">?</text>"));
+    }
+
+    const ROOT: &str = r#"(kicad_sch (version 20250114) (uuid "root")
+        (lib_symbols
+            (symbol "Device:R"
+                (symbol "R_0_1" (rectangle (start -1.016 -2.54) (end 1.016 2.54) (fill (type none))))
+                (symbol "R_1_1"
+                    (pin passive line (at 0 3.81 270) (length 1.27) (name "~") (number "1"))
+                    (pin passive line (at 0 -3.81 90) (length 1.27) (name "~") (number "2")))))
+        (symbol (lib_id "Device:R") (at 100 50 90) (unit 1) (uuid "r1")
+            (property "Reference" "R1") (property "Value" "10k"))
+        (symbol (lib_id "Device:Missing") (at 120 80 0) (unit 1) (uuid "u1")
+            (property "Reference" "U1") (property "Value" "MCU"))
+        (wire (pts (xy 96.19 50) (xy 90 50)))
+        (junction (at 90 50))
+        (label "SIG" (at 90 50 0))
+        (sheet (at 150 40) (size 30 20) (uuid "s1")
+            (property "Sheetname" "Power") (property "Sheetfile" "power.kicad_sch")
+            (pin "VIN" input (at 150 45 180)))
+    )"#;
+
+    fn project(root: &str) -> Project {
+        let sources = [
+            ("board.kicad_sch".to_string(), root.to_string()),
+            (
+                "power.kicad_sch".to_string(),
+                r#"(kicad_sch (version 20250114) (uuid "p") (hierarchical_label "VIN" (at 50 50 0)) (no_connect (at 60 60)))"#
+                    .to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        Project::load("board.kicad_sch", &sources).unwrap()
+    }
+
+    fn title(page: usize) -> TitleBlock<'static> {
+        TitleBlock {
+            repo: "acme/board",
+            commit: "0123456789abcdef",
+            date: "2024-05-01",
+            page,
+            pages: 2,
+        }
+    }
+
+    #[test]
+    fn test_renders_sheet_with_title_block() {
+        let project = project(ROOT);
+        let svg = render_sheet_svg(&project, 0, &title(1));
+        assert!(svg.contains(r#"width="297.000mm" height="210.000mm""#), "fits on A4");
+        assert!(svg.contains(r##"<line x1="96.190" y1="50.000" x2="90.000" y2="50.000" stroke="#009600""##));
+        assert!(svg.contains(r#"<circle cx="90.000" cy="50.000""#));
+        assert!(svg.contains(">SIG</text>"));
+        assert!(svg.contains(">R1</text>") && svg.contains(">10k</text>"));
+        // Rotated 90 degrees, the body lies across the wire
+        assert!(svg.contains(r#"<rect x="97.460" y="48.984" width="5.080" height="2.032""#));
+        // U1 has no definition
+        assert!(svg.contains("stroke-dasharray") && svg.contains(">U1</text>"));
+        assert!(svg.contains(">Power</text>") && svg.contains(">power.kicad_sch</text>") && svg.contains(">VIN</text>"));
+        assert!(svg.contains(">board (/)</text>"));
+        assert!(svg.contains(">Repo: acme/board</text>"));
+        assert!(svg.contains(">Commit: 0123456789abcdef</text>"));
+        assert!(svg.contains(">Date: 2024-05-01</text>"));
+        assert!(svg.contains(">Page 1/2</text>"));
+
+        let child = render_sheet_svg(&project, 1, &title(2));
+        assert!(child.contains(">Power (/Power/)</text>"));
+        assert!(child.contains(r##"stroke="#0000c8""##), "no-connect flag");
+        assert!(child.contains(">Page 2/2</text>"));
+    }
+
+    #[test]
+    fn test_sheet_paper_grows_with_contents() {
+        let large = project(&ROOT.replace("(at 120 80 0)", "(at 500 250 0)"));
+        let svg = render_sheet_svg(&large, 0, &title(1));
+        assert!(svg.contains(r#"width="594.000mm" height="420.000mm""#), "A2: {}", &svg[..200]);
+
+        let off_page = project(&ROOT.replace("(at 120 80 0)", "(at -50 80 0)"));
+        let svg = render_sheet_svg(&off_page, 0, &title(1));
+        assert!(svg.contains(r#"viewBox="-62.540 0.000"#), "sized to the contents: {}", &svg[..200]);
+    }
+
+    #[test]
+    fn test_arc_path_flags() {
+        // Quarter circle from (1,0) through the diagonal to (0,1): short, positive sweep
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        let path = arc_path((1.0, 0.0), (half, half), (0.0, 1.0));
+        assert_eq!(path, "M 1.000 0.000 A 1.000 1.000 0 0 1 0.000 1.000");
+        // Same ends the long way round
+        let path = arc_path((1.0, 0.0), (-half, -half), (0.0, 1.0));
+        assert!(path.ends_with("0 1 0 0.000 1.000"));
+    }
+}
@@ -0,0 +1,338 @@
+// USAGE:
+// cargo test sexpr -- --nocapture
+//
+// Minimal S-expression reader and writer for KiCad files. Atoms and quoted
+// strings are kept apart so callers can tell `(hide yes)` from `(name "yes")`,
+// but both read back through `arg`.
+use crate::error::SchematicError;
+
+/// Deepest nesting accepted. KiCad files nest a dozen lists at most; the
+/// limit keeps hostile files from building trees too deep to walk or drop.
+const MAX_DEPTH: usize = 256;
+
+/// One node of a KiCad S-expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum SExpr {
+    /// Bare token, e.g. `symbol`, `yes`, `12.7`
+    Atom(String),
+    /// Quoted string with escapes resolved
+    Str(String),
+    List(Vec<SExpr>),
+}
+
+impl SExpr {
+    /// Text of an atom or string
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            SExpr::Atom(s) | SExpr::Str(s) => Some(s),
+            SExpr::List(_) => None,
+        }
+    }
+
+    pub fn items(&self) -> &[SExpr] {
+        match self {
+            SExpr::List(items) => items,
+            _ => &[],
+        }
+    }
+
+    /// Leading atom of a list, e.g. `wire` for `(wire (pts ...))`
+    pub fn head(&self) -> Option<&str> {
+        match self.items().first() {
+            Some(SExpr::Atom(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Text of the `index`-th element after the head
+    pub fn arg(&self, index: usize) -> Option<&str> {
+        self.items().get(index + 1).and_then(SExpr::as_str)
+    }
+
+    /// Numeric value of the `index`-th element after the head
+    pub fn number(&self, index: usize) -> Option<f64> {
+        self.arg(index).and_then(|s| s.parse().ok())
+    }
+
+    /// Child lists with the given head
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a SExpr> + 'a {
+        self.items().iter().filter(move |item| item.head() == Some(name))
+    }
+
+    /// First child list with the given head
+    pub fn child(&self, name: &str) -> Option<&SExpr> {
+        self.items().iter().find(|item| item.head() == Some(name))
+    }
+
+    /// Whether a bare flag atom such as `power` or `hide` is present
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.items()
+            .iter()
+            .skip(1)
+            .any(|item| matches!(item, SExpr::Atom(s) if s == flag))
+    }
+}
+
+/// Parse the single top-level expression of a KiCad file
+///
+/// `file` is only used in error messages. A leading byte order mark is
+/// skipped.
+pub fn parse(file: &str, text: &str) -> Result<SExpr, SchematicError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut reader = Reader {
+        file,
+        chars: text.char_indices().peekable(),
+        line: 1,
+        column: 1,
+    };
+    reader.skip_whitespace();
+    let expr = reader.expr()?;
+    reader.skip_whitespace();
+    if reader.chars.peek().is_some() {
+        return Err(reader.error("trailing content after top-level expression"));
+    }
+    Ok(expr)
+}
+
+/// `expr` laid out the way KiCad writes files, ending in a newline
+///
+/// Each child list starts a line, indented with tabs, and a list holding
+/// any closes on its own line; atoms and strings stay on their list's line.
+/// The `xy` points of a `pts` share one line. Reading a file KiCad saved and
+/// writing it back gives the same text.
+pub fn write(expr: &SExpr) -> String {
+    let mut out = String::new();
+    write_expr(&mut out, expr, 0);
+    out.push('\n');
+    out
+}
+
+fn write_expr(out: &mut String, expr: &SExpr, depth: usize) {
+    match expr {
+        SExpr::Atom(s) => out.push_str(s),
+        SExpr::Str(s) => write_string(out, s),
+        SExpr::List(items) => {
+            let points = expr.head() == Some("pts");
+            let mut nested = false;
+            out.push('(');
+            for (i, item) in items.iter().enumerate() {
+                if matches!(item, SExpr::List(_)) && !(points && nested) {
+                    newline(out, depth + 1);
+                    nested = true;
+                } else if i > 0 {
+                    out.push(' ');
+                }
+                write_expr(out, item, depth + 1);
+            }
+            if nested {
+                newline(out, depth);
+            }
+            out.push(')');
+        }
+    }
+}
+
+fn newline(out: &mut String, depth: usize) {
+    out.push('\n');
+    out.extend(std::iter::repeat_n('\t', depth));
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+struct Reader<'a> {
+    file: &'a str,
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl Reader<'_> {
+    fn error(&self, message: impl Into<String>) -> SchematicError {
+        self.error_at((self.line, self.column), message)
+    }
+
+    fn error_at(&self, (line, column): (usize, usize), message: impl Into<String>) -> SchematicError {
+        SchematicError::Syntax {
+            file: self.file.to_string(),
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let (_, c) = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn expr(&mut self) -> Result<SExpr, SchematicError> {
+        match self.peek() {
+            None => Err(self.error("unexpected end of file")),
+            Some('(') => self.list(),
+            Some(')') => Err(self.error("unexpected ')'")),
+            Some('"') => self.string(),
+            Some(_) => Ok(self.atom()),
+        }
+    }
+
+    // Iterative so a malformed or hostile file can't overflow the stack
+    fn list(&mut self) -> Result<SExpr, SchematicError> {
+        let mut stack: Vec<Vec<SExpr>> = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => return Err(self.error("unterminated list")),
+                Some('(') => {
+                    if stack.len() == MAX_DEPTH {
+                        return Err(self.error(format!("lists nested more than {} deep", MAX_DEPTH)));
+                    }
+                    self.bump();
+                    stack.push(Vec::new());
+                }
+                Some(')') => {
+                    self.bump();
+                    let done = SExpr::List(stack.pop().expect("list stack is non-empty"));
+                    match stack.last_mut() {
+                        Some(parent) => parent.push(done),
+                        None => return Ok(done),
+                    }
+                }
+                Some('"') => {
+                    let s = self.string()?;
+                    stack.last_mut().expect("list stack is non-empty").push(s);
+                }
+                Some(_) => {
+                    let atom = self.atom();
+                    stack.last_mut().expect("list stack is non-empty").push(atom);
+                }
+            }
+        }
+    }
+
+    // An unterminated string runs to the end of the file, so its error
+    // points back at the opening quote
+    fn string(&mut self) -> Result<SExpr, SchematicError> {
+        let start = (self.line, self.column);
+        self.bump(); // opening quote
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error_at(start, "unterminated string")),
+                Some('"') => return Ok(SExpr::Str(out)),
+                Some('\\') => match self.bump() {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some(c) => out.push(c),
+                    None => return Err(self.error_at(start, "unterminated string")),
+                },
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn atom(&mut self) -> SExpr {
+        let mut out = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                break;
+            }
+            out.push(c);
+            self.bump();
+        }
+        SExpr::Atom(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nested() {
+        let expr = parse(
+            "t.kicad_sch",
+            r#"(kicad_sch (version 20250114) (property "Value" "10k \"x\"") (pin passive line hide))"#,
+        )
+        .unwrap();
+        assert_eq!(expr.head(), Some("kicad_sch"));
+        assert_eq!(expr.child("version").unwrap().number(0), Some(20250114.0));
+
+        let property = expr.child("property").unwrap();
+        assert_eq!(property.arg(0), Some("Value"));
+        assert_eq!(property.arg(1), Some("10k \"x\""));
+
+        let pin = expr.child("pin").unwrap();
+        assert!(pin.has_flag("hide"));
+        assert!(!pin.has_flag("passive_line"));
+    }
+
+    #[test]
+    fn test_syntax_error_position() {
+        let err = parse("bad.kicad_sch", "(kicad_sch\n  (wire (pts)\n").unwrap_err();
+        match err {
+            SchematicError::Syntax { file, line, .. } => {
+                assert_eq!(file, "bad.kicad_sch");
+                assert_eq!(line, 3);
+            }
+            other => panic!("unexpected error {other:?}"),
+        }
+        assert!(parse("t", "(a))").is_err());
+        assert!(parse("t", "(a \"open)").is_err());
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus/two_resistors.kicad_sch");
+        let text = std::fs::read_to_string(path).unwrap();
+        assert_eq!(write(&parse("t", &text).unwrap()), text);
+
+        let expr = parse("t", r#"(wire (pts (xy 1 2) (xy 3 4)) (name "a \"b\"\\\nc"))"#).unwrap();
+        let written = write(&expr);
+        assert_eq!(written, "(wire\n\t(pts\n\t\t(xy 1 2) (xy 3 4)\n\t)\n\t(name \"a \\\"b\\\"\\\\\\nc\")\n)\n");
+        assert_eq!(parse("t", &written).unwrap(), expr);
+    }
+
+    #[test]
+    fn test_hostile_input() {
+        // Byte order marks are skipped
+        assert_eq!(parse("t", "\u{feff}(a)").unwrap().head(), Some("a"));
+
+        // Unterminated strings point at their opening quote, not the end of the file
+        match parse("t", "(a\n  \"open)\n(b)\n").unwrap_err() {
+            SchematicError::Syntax { line, column, .. } => assert_eq!((line, column), (2, 3)),
+            other => panic!("unexpected error {other:?}"),
+        }
+
+        let nested = |depth: usize| format!("{}{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(parse("t", &nested(MAX_DEPTH)).is_ok());
+        assert!(parse("t", &nested(MAX_DEPTH + 1)).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+// USAGE:
+// cargo test --test integration schematic_stats -- --nocapture
+//
+// Size and complexity of a commit's schematics: counted from the parsed
+// projects when the commit is processed (see `UpdateSchematic::update_stats`)
+// and kept per commit, so a repo's history can be charted without parsing
+// every revision again.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgTransaction;
+use sqlx::{Error, PgPool};
+use std::collections::BTreeSet;
+
+use crate::schematic::Project;
+
+/// Extra complexity of each sheet placed below a project's root
+const SHEET_WEIGHT: i32 = 10;
+
+/// Counts over every project of a commit
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, sqlx::FromRow)]
+pub struct SchematicStats {
+    /// Placed parts, units merged; power symbols and other virtual parts
+    /// aren't counted
+    pub components: i32,
+    /// Nets joining pins, named or not; single unconnected pins aren't nets
+    pub nets: i32,
+    /// Sheet placements, roots included
+    pub sheets: i32,
+    /// Distinct BOM lines (value, footprint and MPN), DNP parts left out
+    pub unique_parts: i32,
+    /// Pins on those nets
+    pub connections: i32,
+    /// `components + nets + connections`, plus 10 per sheet below a root
+    pub complexity: i32,
+}
+
+/// A commit's stats, for a repo's series
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct CommitStats {
+    pub commit_hash: String,
+    pub commit_date: Option<DateTime<Utc>>,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub stats: SchematicStats,
+}
+
+fn count(n: usize) -> i32 {
+    i32::try_from(n).unwrap_or(i32::MAX)
+}
+
+impl SchematicStats {
+    /// Count `projects`, e.g. every project of a commit
+    pub fn of_projects(projects: &[Project]) -> Self {
+        let mut stats = Self::default();
+        let mut parts = BTreeSet::new();
+        for project in projects {
+            stats.components += count(project.components().len());
+            for net in project.netlist() {
+                if net.name.starts_with("unconnected-(") {
+                    continue;
+                }
+                stats.nets += 1;
+                stats.connections += count(net.nodes.len());
+            }
+            stats.sheets += count(project.instances.len());
+            stats.complexity += SHEET_WEIGHT * count(project.instances.len().saturating_sub(1));
+            parts.extend(project.bom().into_iter().map(|line| (line.value, line.footprint, line.mpn)));
+        }
+        stats.unique_parts = count(parts.len());
+        stats.complexity += stats.components + stats.nets + stats.connections;
+        stats
+    }
+}
+
+/// Store the stats of a schematic row, replacing any it had
+pub async fn store_schematic_stats_in(
+    tx: &mut PgTransaction<'_>,
+    schematic_id: i32,
+    stats: &SchematicStats,
+) -> Result<(), Error> {
+    sqlx::query(
+        "INSERT INTO schematic_stats (schematic_id, components, nets, sheets, unique_parts, connections, complexity)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (schematic_id) DO UPDATE SET
+            components = EXCLUDED.components, nets = EXCLUDED.nets, sheets = EXCLUDED.sheets,
+            unique_parts = EXCLUDED.unique_parts, connections = EXCLUDED.connections,
+            complexity = EXCLUDED.complexity, computed_at = NOW()",
+    )
+    .bind(schematic_id)
+    .bind(stats.components)
+    .bind(stats.nets)
+    .bind(stats.sheets)
+    .bind(stats.unique_parts)
+    .bind(stats.connections)
+    .bind(stats.complexity)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// The stats stored for a commit, if it was processed since they were kept
+pub async fn retrieve_schematic_stats(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hash: &str,
+) -> Result<Option<SchematicStats>, Error> {
+    sqlx::query_as(
+        "SELECT st.components, st.nets, st.sheets, st.unique_parts, st.connections, st.complexity
+        FROM schematic_stats st
+        JOIN schematics s ON s.id = st.schematic_id
+        WHERE s.repo_url = $1 AND s.commit_hash = $2 AND s.deleted_at IS NULL",
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .fetch_optional(pool)
+    .await
+}
+
+/// The stats of a repo's latest `limit` commits that have them, oldest first
+///
+/// Commits are ordered by date; those without one come last, in the order
+/// they were stored.
+pub async fn schematic_stats_series(pool: &PgPool, repo_url: &str, limit: i64) -> Result<Vec<CommitStats>, Error> {
+    sqlx::query_as(
+        "SELECT * FROM (
+            SELECT s.id, s.commit_hash, s.commit_date,
+                st.components, st.nets, st.sheets, st.unique_parts, st.connections, st.complexity
+            FROM schematic_stats st
+            JOIN schematics s ON s.id = st.schematic_id
+            WHERE s.repo_url = $1 AND s.deleted_at IS NULL
+            ORDER BY s.commit_date DESC NULLS FIRST, s.id DESC
+            LIMIT $2
+        ) latest
+        ORDER BY commit_date ASC NULLS LAST, id ASC",
+    )
+    .bind(repo_url)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,530 @@
+// USAGE:
+// cargo test --test integration schematic_store -- --nocapture
+//
+// The schematics of each commit (metadata, image, summary, distilled JSON
+// and parts) behind one interface, stored in Postgres or in a single SQLite
+// file picked by the database URL's scheme. SQLite keeps its own migrations
+// (migrations_sqlite/) covering just these tables, so a demo or test can run
+// the schematic pipeline without a database server; components, ERC
+// findings and everything else the server stores stay Postgres only.
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use serde_json::Value;
+use sqlx::migrate::MigrateError;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::types::Json;
+use sqlx::{Error, PgPool, Row};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::images::image_digest;
+use crate::{FullPart, FullSchematic, PoolSettings, SchematicOverview, SortOrder, UpdateSchematic};
+
+/// A commit's schematic as first stored
+#[derive(Debug, Clone, Default)]
+pub struct NewSchematic {
+    pub repo_url: String,
+    pub commit_hash: String,
+    pub commit_date: Option<DateTime<Utc>>,
+    pub git_message: Option<String>,
+    pub schematic_image: Option<Vec<u8>>,
+    pub change_summary: Option<String>,
+    pub project_overview: Option<String>,
+    pub blurb: Option<String>,
+    pub description: Option<String>,
+    /// part_uuid -> (blurb, properties)
+    pub parts: HashMap<Uuid, (Option<String>, Value)>,
+}
+
+/// Storage for the schematics of each commit, by repo URL and commit hash
+pub trait SchematicStore: Send + Sync {
+    /// "postgres" or "sqlite", for logs
+    fn name(&self) -> &'static str;
+
+    /// Apply the store's pending migrations
+    fn migrate(&self) -> BoxFuture<'_, Result<(), MigrateError>>;
+
+    /// Round-trip a trivial query, for readiness checks; says how many
+    /// connections are open
+    fn ping(&self) -> BoxFuture<'_, Result<String, Error>>;
+
+    /// Store a commit's schematic, replacing what was stored for it; returns its ID
+    fn store_schematic<'a>(&'a self, schematic: &'a NewSchematic) -> BoxFuture<'a, Result<i32, Error>>;
+
+    /// A commit's schematic with its image and parts, if stored
+    fn retrieve_schematic<'a>(
+        &'a self,
+        repo_url: &'a str,
+        commit_hash: &'a str,
+    ) -> BoxFuture<'a, Result<Option<FullSchematic>, Error>>;
+
+    /// Store a commit's change summary, written at `detail_level`
+    fn store_summary<'a>(
+        &'a self,
+        repo_url: &'a str,
+        commit_hash: &'a str,
+        summary: &'a str,
+        detail_level: &'a str,
+    ) -> BoxFuture<'a, Result<(), Error>>;
+
+    fn store_distilled_json<'a>(
+        &'a self,
+        repo_url: &'a str,
+        commit_hash: &'a str,
+        distilled_json: &'a Value,
+    ) -> BoxFuture<'a, Result<(), Error>>;
+
+    fn retrieve_distilled_json<'a>(
+        &'a self,
+        repo_url: &'a str,
+        commit_hash: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Value>, Error>>;
+
+    /// A page of a repo's stored commits, newest or oldest first
+    fn list_schematics<'a>(
+        &'a self,
+        repo_url: &'a str,
+        limit: i64,
+        offset: i64,
+        order: SortOrder,
+    ) -> BoxFuture<'a, Result<Vec<SchematicOverview>, Error>>;
+
+    fn count_schematics<'a>(&'a self, repo_url: &'a str) -> BoxFuture<'a, Result<i64, Error>>;
+
+    /// Commits of a repo that already have a change summary
+    fn summarized_commits<'a>(&'a self, repo_url: &'a str) -> BoxFuture<'a, Result<HashSet<String>, Error>>;
+}
+
+/// Whether `url` names a SQLite database, e.g. "sqlite://demo.db" or
+/// "sqlite::memory:"
+pub fn is_sqlite_url(url: &str) -> bool {
+    url.starts_with("sqlite:")
+}
+
+/// Connect to the schematic store at `url`: SQLite for `sqlite:` URLs (the
+/// file is created if missing), Postgres for `postgres://` and
+/// `postgresql://` ones
+pub async fn connect_store(url: &str, settings: &PoolSettings) -> Result<Arc<dyn SchematicStore>, Error> {
+    if is_sqlite_url(url) {
+        return Ok(Arc::new(SqliteSchematicStore::connect(url, settings).await?));
+    }
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        return Ok(Arc::new(PostgresSchematicStore(crate::connect_pool_with(url, settings).await?)));
+    }
+    Err(Error::Configuration(
+        format!(
+            "Unsupported database URL scheme in {:?}; expected postgres:// or sqlite:",
+            url.split("://").next().unwrap_or(url)
+        )
+        .into(),
+    ))
+}
+
+/// Schematics in Postgres, through the crate's functions
+#[derive(Debug, Clone)]
+pub struct PostgresSchematicStore(pub PgPool);
+
+impl SchematicStore for PostgresSchematicStore {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    fn migrate(&self) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(crate::apply_migrations(&self.0))
+    }
+
+    fn ping(&self) -> BoxFuture<'_, Result<String, Error>> {
+        Box::pin(async move {
+            sqlx::query("SELECT 1").execute(&self.0).await?;
+            Ok(format!("{} connections, {} idle", self.0.size(), self.0.num_idle()))
+        })
+    }
+
+    fn store_schematic<'a>(&'a self, schematic: &'a NewSchematic) -> BoxFuture<'a, Result<i32, Error>> {
+        Box::pin(crate::store_schematic(
+            &self.0,
+            &schematic.repo_url,
+            &schematic.commit_hash,
+            schematic.commit_date,
+            schematic.git_message.as_deref(),
+            schematic.schematic_image.clone(),
+            schematic.change_summary.as_deref(),
+            schematic.project_overview.as_deref(),
+            schematic.blurb.as_deref(),
+            schematic.description.as_deref(),
+            schematic.parts.clone(),
+        ))
+    }
+
+    fn retrieve_schematic<'a>(
+        &'a self,
+        repo_url: &'a str,
+        commit_hash: &'a str,
+    ) -> BoxFuture<'a, Result<Option<FullSchematic>, Error>> {
+        Box::pin(crate::retrieve_schematic(&self.0, repo_url, commit_hash))
+    }
+
+    fn store_summary<'a>(
+        &'a self,
+        repo_url: &'a str,
+        commit_hash: &'a str,
+        summary: &'a str,
+        detail_level: &'a str,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            UpdateSchematic::new(repo_url, commit_hash)
+                .update_summary(summary, detail_level)
+                .execute(&self.0)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn store_distilled_json<'a>(
+        &'a self,
+        repo_url: &'a str,
+        commit_hash: &'a str,
+        distilled_json: &'a Value,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(crate::store_distilled_json(&self.0, repo_url, commit_hash, distilled_json))
+    }
+
+    fn retrieve_distilled_json<'a>(
+        &'a self,
+        repo_url: &'a str,
+        commit_hash: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Value>, Error>> {
+        Box::pin(crate::retrieve_distilled_json(&self.0, repo_url, commit_hash))
+    }
+
+    fn list_schematics<'a>(
+        &'a self,
+        repo_url: &'a str,
+        limit: i64,
+        offset: i64,
+        order: SortOrder,
+    ) -> BoxFuture<'a, Result<Vec<SchematicOverview>, Error>> {
+        Box::pin(crate::list_schematics(&self.0, repo_url, limit, offset, order))
+    }
+
+    fn count_schematics<'a>(&'a self, repo_url: &'a str) -> BoxFuture<'a, Result<i64, Error>> {
+        Box::pin(crate::count_schematics(&self.0, repo_url))
+    }
+
+    fn summarized_commits<'a>(&'a self, repo_url: &'a str) -> BoxFuture<'a, Result<HashSet<String>, Error>> {
+        Box::pin(crate::summarized_commits(&self.0, repo_url))
+    }
+}
+
+/// Schematics in a SQLite file
+///
+/// Stores what [`PostgresSchematicStore`] does for these calls, except that
+/// distilled JSON isn't broken down into components and ERC findings, and
+/// images always live in the file rather than the blob store.
+#[derive(Debug, Clone)]
+pub struct SqliteSchematicStore(pub SqlitePool);
+
+impl SqliteSchematicStore {
+    pub async fn connect(url: &str, settings: &PoolSettings) -> Result<Self, Error> {
+        let options = SqliteConnectOptions::from_str(url)?
+            .create_if_missing(true)
+            .foreign_keys(true);
+        // Every connection to an in-memory database gets a database of its
+        // own, so one connection is kept for good
+        let in_memory = url.contains(":memory:") || url.contains("mode=memory");
+        let pool = if in_memory {
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .min_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+        } else {
+            SqlitePoolOptions::new()
+                .max_connections(settings.max_connections)
+                .min_connections(settings.min_connections)
+                .idle_timeout(settings.idle_timeout)
+        };
+        Ok(Self(
+            pool.acquire_timeout(settings.acquire_timeout)
+                .connect_with(options)
+                .await?,
+        ))
+    }
+}
+
+fn overview_from_row(row: SqliteRow) -> Result<SchematicOverview, Error> {
+    Ok(SchematicOverview {
+        commit_hash: row.try_get("commit_hash")?,
+        commit_date: row.try_get("commit_date")?,
+        git_message: row.try_get("git_message")?,
+        blurb: row.try_get("blurb")?,
+        status: row.try_get("status")?,
+        timings: row.try_get::<Option<Json<Value>>, _>("timings")?.map(|json| json.0),
+        created_at: row.try_get("created_at")?,
+        visibility: row.try_get("visibility")?,
+    })
+}
+
+impl SchematicStore for SqliteSchematicStore {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn migrate(&self) -> BoxFuture<'_, Result<(), MigrateError>> {
+        Box::pin(async move { sqlx::migrate!("./migrations_sqlite").run(&self.0).await })
+    }
+
+    fn ping(&self) -> BoxFuture<'_, Result<String, Error>> {
+        Box::pin(async move {
+            sqlx::query("SELECT 1").execute(&self.0).await?;
+            Ok(format!("{} connections, {} idle", self.0.size(), self.0.num_idle()))
+        })
+    }
+
+    fn store_schematic<'a>(&'a self, schematic: &'a NewSchematic) -> BoxFuture<'a, Result<i32, Error>> {
+        Box::pin(async move {
+            let mut tx = self.0.begin().await?;
+            let digest = match &schematic.schematic_image {
+                Some(bytes) => {
+                    let digest = image_digest(bytes);
+                    sqlx::query("INSERT INTO images (digest, bytes) VALUES (?, ?) ON CONFLICT (digest) DO NOTHING")
+                        .bind(&digest)
+                        .bind(bytes)
+                        .execute(&mut *tx)
+                        .await?;
+                    Some(digest)
+                }
+                None => None,
+            };
+            let schematic_id: i32 = sqlx::query_scalar(
+                r#"
+                INSERT INTO schematics (repo_url, commit_hash, commit_date, git_message, image_digest, change_summary, project_overview, blurb, description)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (repo_url, commit_hash) DO UPDATE SET
+                    commit_date = excluded.commit_date,
+                    git_message = excluded.git_message,
+                    image_digest = excluded.image_digest,
+                    change_summary = excluded.change_summary,
+                    project_overview = excluded.project_overview,
+                    blurb = excluded.blurb,
+                    description = excluded.description
+                RETURNING id
+                "#,
+            )
+            .bind(&schematic.repo_url)
+            .bind(&schematic.commit_hash)
+            .bind(schematic.commit_date)
+            .bind(&schematic.git_message)
+            .bind(&digest)
+            .bind(&schematic.change_summary)
+            .bind(&schematic.project_overview)
+            .bind(&schematic.blurb)
+            .bind(&schematic.description)
+            .fetch_one(&mut *tx)
+            .await?;
+            for (part_uuid, (blurb, properties)) in &schematic.parts {
+                sqlx::query(
+                    r#"
+                    INSERT INTO parts (schematic_id, part_uuid, blurb, properties)
+                    VALUES (?, ?, ?, ?)
+                    ON CONFLICT (schematic_id, part_uuid) DO UPDATE SET
+                        blurb = excluded.blurb,
+                        properties = excluded.properties
+                    "#,
+                )
+                .bind(schematic_id)
+                .bind(part_uuid)
+                .bind(blurb)
+                .bind(Json(properties))
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+            Ok(schematic_id)
+        })
+    }
+
+    fn retrieve_schematic<'a>(
+        &'a self,
+        repo_url: &'a str,
+        commit_hash: &'a str,
+    ) -> BoxFuture<'a, Result<Option<FullSchematic>, Error>> {
+        Box::pin(async move {
+            let row = sqlx::query(
+                r#"
+                SELECT s.*, i.bytes AS schematic_image
+                FROM schematics s
+                LEFT JOIN images i ON i.digest = s.image_digest
+                WHERE s.repo_url = ? AND s.commit_hash = ?
+                "#,
+            )
+            .bind(repo_url)
+            .bind(commit_hash)
+            .fetch_optional(&self.0)
+            .await?;
+            let Some(row) = row else {
+                return Ok(None);
+            };
+            let id: i32 = row.try_get("id")?;
+            let parts = sqlx::query("SELECT part_uuid, blurb, properties FROM parts WHERE schematic_id = ?")
+                .bind(id)
+                .fetch_all(&self.0)
+                .await?
+                .into_iter()
+                .map(|part| {
+                    let part = FullPart {
+                        part_uuid: part.try_get("part_uuid")?,
+                        blurb: part.try_get("blurb")?,
+                        properties: part.try_get::<Json<Value>, _>("properties")?.0,
+                    };
+                    Ok((part.part_uuid, part))
+                })
+                .collect::<Result<HashMap<_, _>, Error>>()?;
+            Ok(Some(FullSchematic {
+                repo_url: row.try_get("repo_url")?,
+                commit_hash: row.try_get("commit_hash")?,
+                commit_date: row.try_get("commit_date")?,
+                git_message: row.try_get("git_message")?,
+                image_digest: row.try_get("image_digest")?,
+                schematic_image: row.try_get("schematic_image")?,
+                change_summary: row.try_get("change_summary")?,
+                project_overview: row.try_get("project_overview")?,
+                blurb: row.try_get("blurb")?,
+                description: row.try_get("description")?,
+                distilled_json: row.try_get::<Option<Json<Value>>, _>("distilled_json")?.map(|json| json.0),
+                detail_level: row.try_get("detail_level")?,
+                prompt_version: row.try_get("prompt_version")?,
+                created_at: row.try_get("created_at")?,
+                parts,
+            }))
+        })
+    }
+
+    fn store_summary<'a>(
+        &'a self,
+        repo_url: &'a str,
+        commit_hash: &'a str,
+        summary: &'a str,
+        detail_level: &'a str,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            sqlx::query(
+                r#"
+                INSERT INTO schematics (repo_url, commit_hash, change_summary, detail_level)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT (repo_url, commit_hash) DO UPDATE SET
+                    change_summary = excluded.change_summary,
+                    detail_level = excluded.detail_level
+                "#,
+            )
+            .bind(repo_url)
+            .bind(commit_hash)
+            .bind(summary)
+            .bind(detail_level)
+            .execute(&self.0)
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn store_distilled_json<'a>(
+        &'a self,
+        repo_url: &'a str,
+        commit_hash: &'a str,
+        distilled_json: &'a Value,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            sqlx::query(
+                r#"
+                INSERT INTO schematics (repo_url, commit_hash, distilled_json)
+                VALUES (?, ?, ?)
+                ON CONFLICT (repo_url, commit_hash) DO UPDATE SET
+                    distilled_json = excluded.distilled_json
+                "#,
+            )
+            .bind(repo_url)
+            .bind(commit_hash)
+            .bind(Json(distilled_json))
+            .execute(&self.0)
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn retrieve_distilled_json<'a>(
+        &'a self,
+        repo_url: &'a str,
+        commit_hash: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Value>, Error>> {
+        Box::pin(async move {
+            let json: Option<Option<Json<Value>>> =
+                sqlx::query_scalar("SELECT distilled_json FROM schematics WHERE repo_url = ? AND commit_hash = ?")
+                    .bind(repo_url)
+                    .bind(commit_hash)
+                    .fetch_optional(&self.0)
+                    .await?;
+            Ok(json.flatten().map(|json| json.0))
+        })
+    }
+
+    fn list_schematics<'a>(
+        &'a self,
+        repo_url: &'a str,
+        limit: i64,
+        offset: i64,
+        order: SortOrder,
+    ) -> BoxFuture<'a, Result<Vec<SchematicOverview>, Error>> {
+        let direction = match order {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        };
+        Box::pin(async move {
+            sqlx::query(&format!(
+                r#"
+                SELECT commit_hash, commit_date, git_message, blurb,
+                    CASE
+                        WHEN blurb IS NOT NULL OR change_summary IS NOT NULL THEN 'summarized'
+                        WHEN distilled_json IS NOT NULL THEN 'distilled'
+                        ELSE 'pending'
+                    END AS status,
+                    timings,
+                    created_at,
+                    COALESCE(visibility, 'public') AS visibility
+                FROM schematics
+                WHERE repo_url = ?
+                ORDER BY commit_date {direction} NULLS LAST, created_at {direction}, id {direction}
+                LIMIT ? OFFSET ?
+                "#
+            ))
+            .bind(repo_url)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.0)
+            .await?
+            .into_iter()
+            .map(overview_from_row)
+            .collect()
+        })
+    }
+
+    fn count_schematics<'a>(&'a self, repo_url: &'a str) -> BoxFuture<'a, Result<i64, Error>> {
+        Box::pin(
+            sqlx::query_scalar("SELECT COUNT(*) FROM schematics WHERE repo_url = ?")
+                .bind(repo_url)
+                .fetch_one(&self.0),
+        )
+    }
+
+    fn summarized_commits<'a>(&'a self, repo_url: &'a str) -> BoxFuture<'a, Result<HashSet<String>, Error>> {
+        Box::pin(async move {
+            let commits: Vec<String> =
+                sqlx::query_scalar("SELECT commit_hash FROM schematics WHERE repo_url = ? AND change_summary IS NOT NULL")
+                    .bind(repo_url)
+                    .fetch_all(&self.0)
+                    .await?;
+            Ok(commits.into_iter().collect())
+        })
+    }
+}
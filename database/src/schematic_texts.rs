@@ -0,0 +1,194 @@
+// USAGE:
+// cargo test --test integration schematic_texts -- --nocapture
+//
+// The human-readable text of a commit's schematics (reference designators,
+// values, labels, sheet names and text notes): read from the parsed projects
+// when the commit is processed (see `UpdateSchematic::update_texts`) and kept
+// per commit, so a keyword like "USB_DP" or "TP7" is found without parsing
+// anything or asking a model.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgTransaction;
+use sqlx::{Error, PgPool};
+use std::collections::BTreeSet;
+
+use crate::organizations::OrgFilter;
+use crate::schematic::Project;
+use crate::visibility::EFFECTIVE_VISIBILITY_SQL;
+
+/// Where on a schematic a text comes from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum TextKind {
+    /// A component's reference designator, e.g. `TP7`
+    Reference,
+    /// A component's value, e.g. `10k` or `STM32F411CEU6`
+    Value,
+    /// A net label of any kind
+    Label,
+    /// The name of a sub-sheet
+    Sheet,
+    /// Free text drawn on a sheet
+    Note,
+}
+
+impl TextKind {
+    pub const ALL: [TextKind; 5] = [
+        TextKind::Reference,
+        TextKind::Value,
+        TextKind::Label,
+        TextKind::Sheet,
+        TextKind::Note,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TextKind::Reference => "reference",
+            TextKind::Value => "value",
+            TextKind::Label => "label",
+            TextKind::Sheet => "sheet",
+            TextKind::Note => "note",
+        }
+    }
+}
+
+/// One text of a commit's schematics
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SchematicText {
+    pub kind: TextKind,
+    pub text: String,
+    /// The component a reference or value belongs to
+    pub reference: Option<String>,
+    /// Sheet the text is on, e.g. `/Power/`; for a sheet name, the sheet's own
+    pub sheet_path: String,
+}
+
+impl SchematicText {
+    /// Every distinct text of `projects`, e.g. every project of a commit, in
+    /// kind then text order; a sheet used twice has its labels and notes
+    /// listed under both paths
+    pub fn of_projects(projects: &[Project]) -> Vec<SchematicText> {
+        let mut texts = BTreeSet::new();
+        let mut add = |kind, text: &str, reference: Option<&str>, sheet_path: &str| {
+            let text = text.trim();
+            if !text.is_empty() && text != "~" {
+                texts.insert(SchematicText {
+                    kind,
+                    text: text.to_string(),
+                    reference: reference.map(str::to_string),
+                    sheet_path: sheet_path.to_string(),
+                });
+            }
+        };
+        for project in projects {
+            for component in project.components() {
+                let reference = Some(component.reference.as_str());
+                add(TextKind::Reference, &component.reference, reference, &component.sheet_path);
+                add(TextKind::Value, &component.value, reference, &component.sheet_path);
+            }
+            for instance in &project.instances {
+                let file = &project.files[&instance.file];
+                add(TextKind::Sheet, &instance.name, None, &instance.sheet_path);
+                for label in &file.labels {
+                    add(TextKind::Label, &label.text, None, &instance.sheet_path);
+                }
+                for note in &file.notes {
+                    add(TextKind::Note, &note.text, None, &instance.sheet_path);
+                }
+            }
+        }
+        texts.into_iter().collect()
+    }
+}
+
+/// A schematic text matching a keyword, in the newest indexed commit of its repo
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct TextSearchHit {
+    pub repo_url: String,
+    pub commit_hash: String,
+    pub commit_date: Option<DateTime<Utc>>,
+    /// A [`TextKind`], as stored
+    pub kind: String,
+    pub text: String,
+    pub reference: Option<String>,
+    pub sheet_path: String,
+}
+
+/// Store the texts of a schematic row, replacing any it had
+pub async fn store_schematic_texts_in(
+    tx: &mut PgTransaction<'_>,
+    schematic_id: i32,
+    texts: &[SchematicText],
+) -> Result<(), Error> {
+    sqlx::query("DELETE FROM schematic_texts WHERE schematic_id = $1")
+        .bind(schematic_id)
+        .execute(&mut **tx)
+        .await?;
+    let kinds: Vec<&str> = texts.iter().map(|t| t.kind.as_str()).collect();
+    let text: Vec<&str> = texts.iter().map(|t| t.text.as_str()).collect();
+    let references: Vec<Option<&str>> = texts.iter().map(|t| t.reference.as_deref()).collect();
+    let sheet_paths: Vec<&str> = texts.iter().map(|t| t.sheet_path.as_str()).collect();
+    sqlx::query(
+        "INSERT INTO schematic_texts (schematic_id, kind, text, reference, sheet_path)
+        SELECT $1, * FROM UNNEST($2::TEXT[], $3::TEXT[], $4::TEXT[], $5::TEXT[])",
+    )
+    .bind(schematic_id)
+    .bind(kinds)
+    .bind(text)
+    .bind(references)
+    .bind(sheet_paths)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Schematic texts of the `kinds` given that contain `keyword`, ignoring case
+///
+/// Only each repo's newest commit with indexed texts is searched, so a
+/// keyword resolves to where it is now rather than once per commit. Whole
+/// matches come first, then texts starting with it, then the rest. Private
+/// commits only count with `include_private`; `orgs` limits the repos to one
+/// org's.
+pub async fn search_schematic_texts(
+    pool: &PgPool,
+    repo_url: Option<&str>,
+    keyword: &str,
+    kinds: &[TextKind],
+    limit: i64,
+    include_private: bool,
+    orgs: OrgFilter,
+) -> Result<Vec<TextSearchHit>, Error> {
+    let kinds: Vec<&str> = kinds.iter().map(TextKind::as_str).collect();
+    sqlx::query_as::<_, TextSearchHit>(&format!(
+        r#"
+        WITH latest AS (
+            SELECT DISTINCT ON (s.repo_url) s.id, s.repo_url, s.commit_hash, s.commit_date
+            FROM schematics s
+            LEFT JOIN repos owner ON owner.repo_url = s.repo_url
+            WHERE s.deleted_at IS NULL
+                AND ($1::TEXT IS NULL OR s.repo_url = $1)
+                AND EXISTS (SELECT 1 FROM schematic_texts t WHERE t.schematic_id = s.id)
+                AND ($5 OR {visibility} = 'public')
+                AND ($6 OR owner.org_id IS NOT DISTINCT FROM $7)
+            ORDER BY s.repo_url, s.commit_date DESC NULLS LAST, s.id DESC
+        )
+        SELECT l.repo_url, l.commit_hash, l.commit_date, t.kind, t.text, t.reference, t.sheet_path
+        FROM latest l
+        JOIN schematic_texts t ON t.schematic_id = l.id
+        WHERE t.kind = ANY($3) AND STRPOS(UPPER(t.text), UPPER($2)) > 0
+        ORDER BY UPPER(t.text) = UPPER($2) DESC, STARTS_WITH(UPPER(t.text), UPPER($2)) DESC,
+            l.commit_date DESC NULLS LAST, l.repo_url, t.text, t.kind, t.sheet_path
+        LIMIT $4
+        "#,
+        visibility = EFFECTIVE_VISIBILITY_SQL
+    ))
+    .bind(repo_url)
+    .bind(keyword)
+    .bind(kinds)
+    .bind(limit)
+    .bind(include_private)
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,188 @@
+// USAGE:
+// cargo test sse -- --nocapture
+//
+// Incremental decoder for server-sent events, as the XAI API streams chat
+// completions. Follows the event-stream format of the HTML spec: lines end in
+// CRLF, LF or a lone CR, `data:` lines of one event are joined with newlines,
+// an empty line dispatches the event, and lines starting with `:` are
+// comments. Bytes may arrive split anywhere, including inside a CRLF or a
+// multi-byte character.
+
+/// One dispatched event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// The `event:` field; None for the default `message` type
+    pub event: Option<String>,
+    pub data: String,
+    /// The `id:` field, if the event set one
+    pub id: Option<String>,
+}
+
+/// Turns chunks of an event stream into events
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    /// Bytes of the current, unfinished line
+    line: Vec<u8>,
+    /// The last chunk ended in CR, so a leading LF belongs to that line break
+    after_cr: bool,
+    event: Option<String>,
+    data: String,
+    id: Option<String>,
+    has_data: bool,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the next bytes of the stream, returning the events they complete
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        let mut bytes = bytes;
+        if self.after_cr && !bytes.is_empty() {
+            self.after_cr = false;
+            bytes = bytes.strip_prefix(b"\n").unwrap_or(bytes);
+        }
+        while let Some(end) = bytes.iter().position(|&b| b == b'\n' || b == b'\r') {
+            self.line.extend_from_slice(&bytes[..end]);
+            let crlf = bytes[end] == b'\r' && bytes.get(end + 1) == Some(&b'\n');
+            if bytes[end] == b'\r' && end + 1 == bytes.len() {
+                self.after_cr = true;
+            }
+            bytes = &bytes[end + if crlf { 2 } else { 1 }..];
+
+            let line = std::mem::take(&mut self.line);
+            if let Some(event) = self.process_line(&line) {
+                events.push(event);
+            }
+        }
+        self.line.extend_from_slice(bytes);
+        events
+    }
+
+    /// The stream ended: the event in progress, if its last line was complete
+    ///
+    /// The spec drops an event that wasn't followed by an empty line; it is
+    /// kept here since some servers close the stream right after the last `data:`.
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        let line = std::mem::take(&mut self.line);
+        if !line.is_empty() {
+            self.process_line(&line);
+        }
+        self.dispatch()
+    }
+
+    fn process_line(&mut self, line: &[u8]) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line[0] == b':' {
+            return None;
+        }
+        let line = String::from_utf8_lossy(line);
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line.as_ref(), ""),
+        };
+        match field {
+            "data" => {
+                if self.has_data {
+                    self.data.push('\n');
+                }
+                self.data.push_str(value);
+                self.has_data = true;
+            }
+            "event" => self.event = Some(value.to_string()),
+            "id" if !value.contains('\0') => self.id = Some(value.to_string()),
+            // `retry` and unknown fields don't matter to a one-shot stream
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if !std::mem::take(&mut self.has_data) {
+            return None;
+        }
+        Some(SseEvent {
+            event,
+            data: std::mem::take(&mut self.data),
+            id: self.id.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(events: &[SseEvent]) -> Vec<&str> {
+        events.iter().map(|e| e.data.as_str()).collect()
+    }
+
+    /// Feed `input` split at every position, checking each split decodes the same
+    fn decode_every_split(input: &[u8]) -> Vec<SseEvent> {
+        let mut whole = SseDecoder::new();
+        let mut expected = whole.push(input);
+        expected.extend(whole.finish());
+        for split in 0..=input.len() {
+            let mut decoder = SseDecoder::new();
+            let mut events = decoder.push(&input[..split]);
+            events.extend(decoder.push(&input[split..]));
+            events.extend(decoder.finish());
+            assert_eq!(events, expected, "split at {}", split);
+        }
+        expected
+    }
+
+    #[test]
+    fn test_line_endings() {
+        for input in [
+            "data: a\n\ndata: b\n\n",
+            "data: a\r\n\r\ndata: b\r\n\r\n",
+            "data: a\r\rdata: b\r\r",
+            "data: a\r\n\ndata: b\n\r\n",
+        ] {
+            assert_eq!(data(&decode_every_split(input.as_bytes())), ["a", "b"], "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_multi_line_data_and_fields() {
+        let input = b": keep-alive\nevent: usage\nid: 7\ndata: {\"a\":\ndata:1}\nretry: 10\n\ndata\n\n";
+        let events = decode_every_split(input);
+        assert_eq!(
+            events,
+            [
+                SseEvent {
+                    event: Some("usage".to_string()),
+                    data: "{\"a\":\n1}".to_string(),
+                    id: Some("7".to_string()),
+                },
+                SseEvent {
+                    event: None,
+                    data: String::new(),
+                    id: Some("7".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_characters_and_unterminated_end() {
+        // A multi-byte character split between chunks stays intact
+        let input = "data: Ω µF\n\ndata: [DONE]".as_bytes();
+        assert_eq!(data(&decode_every_split(input)), ["Ω µF", "[DONE]"]);
+    }
+
+    #[test]
+    fn test_events_without_data_are_dropped() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.push(b"event: ping\n\n: comment\n\n").is_empty());
+        // The event type doesn't carry over to the next event
+        assert_eq!(decoder.push(b"data: x\n\n")[0].event, None);
+        assert_eq!(decoder.finish(), None);
+    }
+}
@@ -0,0 +1,86 @@
+// USAGE:
+// cargo test --test integration summary_chunks -- --nocapture
+//
+// Cached steps of hierarchical summaries. Each entry is one model answer
+// (a chunk's summary, or several summaries merged), keyed by
+// [`summary_chunk_digest`] of the model and the exact prompt, so any change
+// to the input or the template makes a new key and stale entries simply stop
+// being used.
+use sha2::{Digest, Sha256};
+use sqlx::{Error, PgPool};
+use std::time::Duration;
+
+/// Key of the cached answer `model` gives to `prompt`
+pub fn summary_chunk_digest(model: &str, prompt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    hasher.update([0]);
+    hasher.update(prompt.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// The cached summary for `digest`, marking it used
+pub async fn get_summary_chunk(pool: &PgPool, digest: &str) -> Result<Option<String>, Error> {
+    sqlx::query_scalar(
+        r#"
+        UPDATE summary_chunks SET used_at = CURRENT_TIMESTAMP
+        WHERE digest = $1
+        RETURNING summary
+        "#,
+    )
+    .bind(digest)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Cache a summary, replacing any earlier one for the digest
+pub async fn store_summary_chunk(
+    pool: &PgPool,
+    digest: &str,
+    model: &str,
+    prompt_version: &str,
+    summary: &str,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO summary_chunks (digest, model, prompt_version, summary)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (digest) DO UPDATE SET
+            summary = EXCLUDED.summary,
+            created_at = CURRENT_TIMESTAMP,
+            used_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(digest)
+    .bind(model)
+    .bind(prompt_version)
+    .bind(summary)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Delete summaries unused for `retention`, returning how many
+pub async fn purge_summary_chunks(pool: &PgPool, retention: Duration) -> Result<u64, Error> {
+    let result = sqlx::query("DELETE FROM summary_chunks WHERE used_at <= CURRENT_TIMESTAMP - make_interval(secs => $1)")
+        .bind(retention.as_secs_f64())
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_chunk_digest() {
+        let digest = summary_chunk_digest("grok-4", "Summarize R1");
+        assert_eq!(digest.len(), 64);
+        assert_eq!(digest, summary_chunk_digest("grok-4", "Summarize R1"));
+        assert_ne!(digest, summary_chunk_digest("grok-3", "Summarize R1"));
+        assert_ne!(digest, summary_chunk_digest("grok-4", "Summarize R2"));
+        // The separator keeps the model and prompt from running together
+        assert_ne!(summary_chunk_digest("a", "bc"), summary_chunk_digest("ab", "c"));
+    }
+}
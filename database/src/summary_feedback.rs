@@ -0,0 +1,153 @@
+// USAGE:
+// cargo test --test integration summary_feedback -- --nocapture
+//
+// Thumbs up or down on AI summaries. Every summary handed out is recorded
+// with the model and prompt template that wrote it, so feedback on it can be
+// totalled per template and model to see which combinations people find
+// useful.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, FromRow, PgPool};
+
+use crate::organizations::OrgFilter;
+
+/// A summary as handed out, without its text
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+pub struct GeneratedSummary {
+    pub id: i64,
+    pub repo_url: String,
+    pub commit_hash: String,
+    /// What was summarized, e.g. "commit_summary"
+    pub kind: String,
+    pub model: String,
+    pub prompt_version: String,
+    pub detail_level: String,
+    /// Experiment and variant the summary was generated under, if any
+    pub experiment: Option<String>,
+    pub variant: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A summary about to be handed out
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NewGeneratedSummary {
+    pub repo_url: String,
+    pub commit_hash: String,
+    pub kind: String,
+    pub model: String,
+    pub prompt_version: String,
+    pub detail_level: String,
+    pub experiment: Option<String>,
+    pub variant: Option<String>,
+}
+
+/// One rating of a summary
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+pub struct SummaryFeedback {
+    pub id: i64,
+    pub summary_id: i64,
+    /// Thumbs up
+    pub helpful: bool,
+    pub comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Ratings of the summaries one prompt template and model wrote
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)]
+pub struct FeedbackTotal {
+    pub kind: String,
+    pub prompt_version: String,
+    pub model: String,
+    /// Summaries rated at least once
+    pub summaries: i64,
+    pub helpful: i64,
+    pub unhelpful: i64,
+    /// Ratings with a comment
+    pub comments: i64,
+}
+
+impl FeedbackTotal {
+    /// Share of ratings that were thumbs up
+    pub fn helpful_rate(&self) -> f64 {
+        match self.helpful + self.unhelpful {
+            0 => 0.0,
+            total => self.helpful as f64 / total as f64,
+        }
+    }
+}
+
+/// Record a summary being handed out; returns its ID
+pub async fn record_generated_summary(pool: &PgPool, summary: &NewGeneratedSummary) -> Result<i64, Error> {
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO generated_summaries
+            (repo_url, commit_hash, kind, model, prompt_version, detail_level, experiment, variant)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id
+        "#,
+    )
+    .bind(&summary.repo_url)
+    .bind(&summary.commit_hash)
+    .bind(&summary.kind)
+    .bind(&summary.model)
+    .bind(&summary.prompt_version)
+    .bind(&summary.detail_level)
+    .bind(&summary.experiment)
+    .bind(&summary.variant)
+    .fetch_one(pool)
+    .await
+}
+
+/// The summary handed out with `id`
+pub async fn get_generated_summary(pool: &PgPool, id: i64) -> Result<Option<GeneratedSummary>, Error> {
+    sqlx::query_as::<_, GeneratedSummary>("SELECT * FROM generated_summaries WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Store a rating of summary `summary_id`
+pub async fn record_summary_feedback(
+    pool: &PgPool,
+    summary_id: i64,
+    helpful: bool,
+    comment: Option<&str>,
+) -> Result<SummaryFeedback, Error> {
+    sqlx::query_as::<_, SummaryFeedback>(
+        r#"
+        INSERT INTO summary_feedback (summary_id, helpful, comment)
+        VALUES ($1, $2, $3)
+        RETURNING *
+        "#,
+    )
+    .bind(summary_id)
+    .bind(helpful)
+    .bind(comment)
+    .fetch_one(pool)
+    .await
+}
+
+/// Ratings given since `since` to summaries of the repos of the orgs `orgs`
+/// lets through, by kind, prompt template and model, the most rated first
+pub async fn feedback_totals(pool: &PgPool, since: DateTime<Utc>, orgs: OrgFilter) -> Result<Vec<FeedbackTotal>, Error> {
+    sqlx::query_as::<_, FeedbackTotal>(
+        r#"
+        SELECT s.kind, s.prompt_version, s.model,
+            COUNT(DISTINCT s.id) AS summaries,
+            COUNT(*) FILTER (WHERE f.helpful) AS helpful,
+            COUNT(*) FILTER (WHERE NOT f.helpful) AS unhelpful,
+            COUNT(f.comment) AS comments
+        FROM summary_feedback f
+        JOIN generated_summaries s ON s.id = f.summary_id
+        LEFT JOIN repos r ON r.repo_url = s.repo_url
+        WHERE f.created_at >= $1 AND ($2 OR r.org_id IS NOT DISTINCT FROM $3)
+        GROUP BY s.kind, s.prompt_version, s.model
+        ORDER BY COUNT(*) DESC, s.kind, s.prompt_version, s.model
+        "#,
+    )
+    .bind(since)
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,56 @@
+// USAGE:
+// cargo test --test integration system_overview -- --nocapture
+//
+// Counts for the admin overview, gathered in one round trip so a dashboard
+// polling it costs one query.
+use chrono::{DateTime, Utc};
+use sqlx::{Error, FromRow, PgPool};
+
+use crate::OrgFilter;
+
+/// What the instance (or one org's part of it) holds and has done lately
+#[derive(Debug, Clone, Default, PartialEq, FromRow)]
+pub struct SystemTotals {
+    pub registered_repos: i64,
+    /// Commits whose processing finished since `processed_since`
+    pub commits_processed: i64,
+    /// Commits whose last attempt failed since `processed_since`
+    pub commits_failed: i64,
+    /// Commits waiting to be processed or being processed now
+    pub commits_queued: i64,
+    /// AI spend since `spend_since`, in USD
+    pub ai_spend_usd: f64,
+}
+
+/// Totals of the data `orgs` lets through; commits of unregistered repos
+/// count as owned by no org
+pub async fn system_totals(
+    pool: &PgPool,
+    orgs: OrgFilter,
+    processed_since: DateTime<Utc>,
+    spend_since: DateTime<Utc>,
+) -> Result<SystemTotals, Error> {
+    sqlx::query_as(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM repos WHERE ($1 OR org_id IS NOT DISTINCT FROM $2)) AS registered_repos,
+            (SELECT COUNT(*) FROM schematics s LEFT JOIN repos r ON r.repo_url = s.repo_url
+                WHERE s.processed_at >= $3 AND s.processing_status = 'done' AND s.deleted_at IS NULL
+                    AND ($1 OR r.org_id IS NOT DISTINCT FROM $2)) AS commits_processed,
+            (SELECT COUNT(*) FROM schematics s LEFT JOIN repos r ON r.repo_url = s.repo_url
+                WHERE s.processing_error_at >= $3 AND s.processing_status = 'failed' AND s.deleted_at IS NULL
+                    AND ($1 OR r.org_id IS NOT DISTINCT FROM $2)) AS commits_failed,
+            (SELECT COUNT(*) FROM schematics s LEFT JOIN repos r ON r.repo_url = s.repo_url
+                WHERE s.processing_status IN ('pending', 'processing') AND s.deleted_at IS NULL
+                    AND ($1 OR r.org_id IS NOT DISTINCT FROM $2)) AS commits_queued,
+            (SELECT COALESCE(SUM(cost_usd), 0) FROM ai_spend
+                WHERE created_at >= $4 AND ($1 OR org_id IS NOT DISTINCT FROM $2)) AS ai_spend_usd
+        "#,
+    )
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .bind(processed_since)
+    .bind(spend_since)
+    .fetch_one(pool)
+    .await
+}
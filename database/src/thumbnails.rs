@@ -0,0 +1,185 @@
+// USAGE:
+// cargo test --test integration schematic_thumbnails -- --nocapture
+//
+// PNG thumbnails of stored schematic images. The caller renders them; this
+// module finds images whose thumbnails are missing or were made from an older
+// image, and stores and serves the results. The PNGs are kept in the blob
+// store under the digest of the image they were made from, so commits
+// sharing an image share its thumbnails' bytes.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, PgPool};
+
+use crate::blob_store::{blob_store, load_blob, thumbnail_key};
+use crate::images::load_image_in;
+
+/// A schematic image waiting for thumbnails
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ThumbnailSource {
+    pub schematic_id: i32,
+    pub repo_url: String,
+    pub commit_hash: String,
+    /// Empty if the image's bytes are missing from the blob store
+    #[sqlx(default)]
+    pub image: Vec<u8>,
+    /// Digest of `image` (see `images`), passed back to `store_thumbnail`
+    pub digest: String,
+    /// Widths missing or made from another image
+    pub widths: Vec<i32>,
+}
+
+/// A stored thumbnail
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Thumbnail {
+    pub width: i32,
+    /// Digest of the image it was made from
+    pub source_digest: String,
+    #[sqlx(default)]
+    pub image: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Images missing a thumbnail at any of `widths`, oldest first
+///
+/// Failed attempts count as done until the image changes.
+pub async fn pending_thumbnails(
+    pool: &PgPool,
+    widths: &[i32],
+    limit: i64,
+) -> Result<Vec<ThumbnailSource>, Error> {
+    let mut sources = sqlx::query_as::<_, ThumbnailSource>(
+        r#"
+        SELECT * FROM (
+            SELECT s.id AS schematic_id, s.repo_url, s.commit_hash, s.image_digest AS digest,
+                ARRAY(
+                    SELECT w FROM unnest($1::INTEGER[]) AS w
+                    WHERE NOT EXISTS (
+                        SELECT 1 FROM schematic_thumbnails t
+                        WHERE t.schematic_id = s.id AND t.width = w
+                            AND t.source_digest = s.image_digest
+                    )
+                    ORDER BY w
+                ) AS widths
+            FROM schematics s
+            WHERE s.image_digest IS NOT NULL AND s.deleted_at IS NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM repos r WHERE r.repo_url = s.repo_url AND NOT r.render_thumbnails
+                )
+        ) pending
+        WHERE cardinality(widths) > 0
+        ORDER BY schematic_id
+        LIMIT $2
+        "#,
+    )
+    .bind(widths)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    let mut conn = pool.acquire().await?;
+    for source in &mut sources {
+        source.image = load_image_in(&mut conn, &source.digest).await?.unwrap_or_default();
+    }
+    Ok(sources)
+}
+
+/// Store a rendered thumbnail (`Ok`) or why rendering it failed (`Err`)
+pub async fn store_thumbnail(
+    pool: &PgPool,
+    schematic_id: i32,
+    width: i32,
+    source_digest: &str,
+    result: Result<&[u8], &str>,
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+    let error = match result {
+        Ok(image) => {
+            blob_store()
+                .put(&mut tx, &thumbnail_key(source_digest, width), "image/png", image)
+                .await?;
+            None
+        }
+        Err(error) => Some(error),
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO schematic_thumbnails (schematic_id, width, source_digest, stored, error)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (schematic_id, width) DO UPDATE SET
+            source_digest = EXCLUDED.source_digest,
+            stored = EXCLUDED.stored,
+            error = EXCLUDED.error,
+            created_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(schematic_id)
+    .bind(width)
+    .bind(source_digest)
+    .bind(error.is_none())
+    .bind(error)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+/// Digest of the image a commit's thumbnail at `width` was made from, if
+/// one has been rendered from its current image; cheaper than loading it
+pub async fn thumbnail_source_digest(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hash: &str,
+    width: i32,
+) -> Result<Option<String>, Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT t.source_digest
+        FROM schematic_thumbnails t
+        JOIN schematics s ON s.id = t.schematic_id
+        WHERE s.repo_url = $1 AND s.commit_hash = $2 AND t.width = $3
+            AND s.deleted_at IS NULL
+            AND t.stored
+            AND t.source_digest = s.image_digest
+        "#,
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .bind(width)
+    .fetch_optional(pool)
+    .await
+}
+
+/// The thumbnail of a commit's current image at `width`, if one has been rendered
+pub async fn get_thumbnail(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hash: &str,
+    width: i32,
+) -> Result<Option<Thumbnail>, Error> {
+    let thumbnail = sqlx::query_as::<_, Thumbnail>(
+        r#"
+        SELECT t.width, t.source_digest, t.created_at
+        FROM schematic_thumbnails t
+        JOIN schematics s ON s.id = t.schematic_id
+        WHERE s.repo_url = $1 AND s.commit_hash = $2 AND t.width = $3
+            AND s.deleted_at IS NULL
+            AND t.stored
+            AND t.source_digest = s.image_digest
+        "#,
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .bind(width)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(mut thumbnail) = thumbnail else {
+        return Ok(None);
+    };
+    match load_blob(pool, &thumbnail_key(&thumbnail.source_digest, width)).await? {
+        Some(image) => {
+            thumbnail.image = image;
+            Ok(Some(thumbnail))
+        }
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,167 @@
+// USAGE:
+// cargo test tokens -- --nocapture
+//
+// Token counts estimated without a tokenizer. xAI doesn't publish Grok's
+// vocabulary, so text is split roughly the way tiktoken's BPE encodings
+// pre-split it (letter runs with their leading space, digit groups of up to
+// three, punctuation runs, whitespace) and each piece is charged what such
+// an encoding typically spends on it. Counts are estimates; the prompt
+// budget (prompt_budget.rs) keeps a margin of the window free for the error.
+use crate::messages::Message;
+
+/// Context window of models missing from [`context_window`]
+pub const DEFAULT_CONTEXT_WINDOW: usize = 131_072;
+
+/// Tokens charged per image in a vision request, at the most detailed setting
+pub const IMAGE_TOKENS: usize = 1_800;
+
+/// Tokens each chat message costs besides its content (role and separators)
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Tokens that prime the reply, once per request
+const REPLY_OVERHEAD: usize = 3;
+
+/// Context window of a model, in tokens: prompt and completion together
+pub fn context_window(model: &str) -> usize {
+    match model {
+        "grok-4-1-fast" | "grok-4-1-fast-reasoning" | "grok-4-1-fast-non-reasoning" => 2_000_000,
+        "grok-4" | "grok-4-0709" => 256_000,
+        "grok-3" | "grok-3-fast" | "grok-3-mini" | "grok-3-mini-fast" => 131_072,
+        _ => DEFAULT_CONTEXT_WINDOW,
+    }
+}
+
+/// How a model's tokenizer splits text, as far as the estimate cares
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// A ~100k-entry vocabulary like cl100k: about four letters per token
+    Standard,
+    /// The larger vocabulary of the Grok 4 family, which merges longer words
+    Large,
+}
+
+impl Encoding {
+    /// The encoding `model` uses; unknown models get the more expensive one
+    pub fn for_model(model: &str) -> Self {
+        if model.starts_with("grok-4") || model.starts_with("grok-code") {
+            Encoding::Large
+        } else {
+            Encoding::Standard
+        }
+    }
+
+    /// Letters per token in a run of ASCII letters
+    fn letters_per_token(self) -> usize {
+        match self {
+            Encoding::Standard => 4,
+            Encoding::Large => 5,
+        }
+    }
+
+    /// Estimated tokens in `text`
+    pub fn count(self, text: &str) -> usize {
+        let mut tokens = 0;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c.is_ascii_alphabetic() {
+                let mut letters: usize = 1;
+                while chars.next_if(char::is_ascii_alphabetic).is_some() {
+                    letters += 1;
+                }
+                tokens += letters.div_ceil(self.letters_per_token());
+            } else if c.is_ascii_digit() {
+                let mut digits: usize = 1;
+                while chars.next_if(char::is_ascii_digit).is_some() {
+                    digits += 1;
+                }
+                tokens += digits.div_ceil(3);
+            } else if c == '\n' || c == '\r' {
+                // A run of line breaks (and the indentation after it) is one token
+                while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+                tokens += 1;
+            } else if c.is_whitespace() {
+                let mut spaces = 1;
+                while chars.next_if(|&c| c.is_whitespace() && c != '\n' && c != '\r').is_some() {
+                    spaces += 1;
+                }
+                // A single space before a word or number is part of that token
+                let joins_next = spaces == 1 && chars.peek().is_some_and(|c| c.is_ascii_alphanumeric());
+                if !joins_next {
+                    tokens += 1;
+                }
+            } else if c.is_ascii_punctuation() {
+                let mut marks: usize = 1;
+                while chars.next_if(char::is_ascii_punctuation).is_some() {
+                    marks += 1;
+                }
+                // Common pairs like "**", "()" and "->" are single tokens
+                tokens += marks.div_ceil(2);
+            } else {
+                // Other scripts and symbols: a token per character, at least
+                tokens += c.len_utf8().div_ceil(3);
+            }
+        }
+        tokens
+    }
+}
+
+/// Estimated tokens in `text` for `model`
+pub fn count_tokens(model: &str, text: &str) -> usize {
+    Encoding::for_model(model).count(text)
+}
+
+/// Estimated prompt tokens of a chat request's messages, images included
+pub fn count_message_tokens(model: &str, messages: &[Message]) -> usize {
+    let encoding = Encoding::for_model(model);
+    let content: usize = messages
+        .iter()
+        .map(|m| MESSAGE_OVERHEAD + encoding.count(&m.content.text()) + m.content.image_count() * IMAGE_TOKENS)
+        .sum();
+    content + REPLY_OVERHEAD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::ImageUrl;
+
+    #[test]
+    fn test_count_tokens() {
+        let standard = Encoding::Standard;
+        assert_eq!(standard.count(""), 0);
+        assert_eq!(standard.count("hello world"), 4);
+        assert_eq!(standard.count("the R1 pin"), 4);
+        assert_eq!(standard.count("1234567"), 3);
+        assert_eq!(standard.count("**U1**:"), 5);
+        assert_eq!(standard.count("a\n\n    b"), 3);
+        assert_eq!(standard.count("電源"), 2);
+
+        // Grok 4 merges long words into fewer tokens
+        let text = "decoupling capacitors stabilize regulators";
+        assert!(Encoding::Large.count(text) < standard.count(text));
+        assert_eq!(Encoding::for_model("grok-4-1-fast"), Encoding::Large);
+        assert_eq!(Encoding::for_model("grok-3-mini"), Encoding::Standard);
+        assert_eq!(Encoding::for_model("someone-elses-model"), Encoding::Standard);
+    }
+
+    #[test]
+    fn test_count_message_tokens() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let messages = vec![
+            Message::system("Explain circuits.".to_string()),
+            Message::user_with_image("What is U1?".to_string(), ImageUrl::from_bytes(png).unwrap()),
+        ];
+        let text = count_tokens("grok-3", "Explain circuits.") + count_tokens("grok-3", "What is U1?");
+        assert_eq!(
+            count_message_tokens("grok-3", &messages),
+            text + 2 * MESSAGE_OVERHEAD + IMAGE_TOKENS + REPLY_OVERHEAD
+        );
+    }
+
+    #[test]
+    fn test_context_window() {
+        assert_eq!(context_window("grok-4-1-fast"), 2_000_000);
+        assert_eq!(context_window("grok-3-fast"), 131_072);
+        assert_eq!(context_window("unheard-of"), DEFAULT_CONTEXT_WINDOW);
+    }
+}
@@ -0,0 +1,291 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::postgres::PgTransaction;
+use sqlx::{Error, PgPool, Postgres, QueryBuilder, Row};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::components::{upsert_components, upsert_parts, ComponentRecord};
+use crate::images::{find_image_in, store_image_in};
+use crate::schematic::DiffWarning;
+use crate::schematic_stats::{store_schematic_stats_in, SchematicStats};
+use crate::schematic_texts::{store_schematic_texts_in, SchematicText};
+
+/// Targeted update of one stored schematic row.
+///
+/// Only the fields that were set are written, so e.g. adding an AI blurb
+/// doesn't require re-passing the image and parts. The row is created if it
+/// doesn't exist yet.
+///
+/// ```ignore
+/// UpdateSchematic::new(repo_url, commit_hash)
+///     .update_blurb("Swapped the buck converter")
+///     .update_summary("...", "standard")
+///     .execute(&pool)
+///     .await?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct UpdateSchematic {
+    repo_url: String,
+    commit_hash: String,
+    commit_date: Option<Option<DateTime<Utc>>>,
+    git_message: Option<Option<String>>,
+    blurb: Option<String>,
+    description: Option<String>,
+    project_overview: Option<String>,
+    change_summary: Option<(String, String)>,
+    prompt_version: Option<String>,
+    image: Option<Vec<u8>>,
+    image_digest: Option<String>,
+    board: Option<Value>,
+    warnings: Option<Vec<DiffWarning>>,
+    stats: Option<SchematicStats>,
+    texts: Option<Vec<SchematicText>>,
+    parts: Option<HashMap<Uuid, (Option<String>, Value)>>,
+}
+
+impl UpdateSchematic {
+    pub fn new(repo_url: impl Into<String>, commit_hash: impl Into<String>) -> Self {
+        Self {
+            repo_url: repo_url.into(),
+            commit_hash: commit_hash.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the git metadata (date and message) for the commit
+    pub fn update_commit_info(
+        mut self,
+        commit_date: Option<DateTime<Utc>>,
+        git_message: Option<&str>,
+    ) -> Self {
+        self.commit_date = Some(commit_date);
+        self.git_message = Some(git_message.map(str::to_string));
+        self
+    }
+
+    pub fn update_blurb(mut self, blurb: impl Into<String>) -> Self {
+        self.blurb = Some(blurb.into());
+        self
+    }
+
+    pub fn update_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn update_overview(mut self, project_overview: impl Into<String>) -> Self {
+        self.project_overview = Some(project_overview.into());
+        self
+    }
+
+    /// Set the AI change summary and the detail level it was generated at
+    pub fn update_summary(
+        mut self,
+        change_summary: impl Into<String>,
+        detail_level: impl Into<String>,
+    ) -> Self {
+        self.change_summary = Some((change_summary.into(), detail_level.into()));
+        self
+    }
+
+    /// Record the prompt template the summary came from, e.g. "commit_summary@v2"
+    pub fn update_prompt_version(mut self, prompt_version: impl Into<String>) -> Self {
+        self.prompt_version = Some(prompt_version.into());
+        self
+    }
+
+    /// Set the rendered image; identical bytes already stored for another
+    /// commit are shared rather than stored again
+    pub fn update_image(mut self, image: Vec<u8>) -> Self {
+        self.image = Some(image);
+        self.image_digest = None;
+        self
+    }
+
+    /// Set the image to one already stored, by digest (see `images`);
+    /// `execute` fails with `RowNotFound` if there is no such image
+    pub fn update_image_digest(mut self, digest: impl Into<String>) -> Self {
+        self.image_digest = Some(digest.into());
+        self.image = None;
+        self
+    }
+
+    /// Set the layout summary of the commit's board files
+    pub fn update_board(mut self, board: Value) -> Self {
+        self.board = Some(board);
+        self
+    }
+
+    /// Set the suspicious changes found in the commit's diff (see
+    /// `schematic::anomalies`); an empty list records that there were none
+    pub fn update_warnings(mut self, warnings: Vec<DiffWarning>) -> Self {
+        self.warnings = Some(warnings);
+        self
+    }
+
+    /// Set the counts of the commit's parsed projects (see `schematic_stats`)
+    pub fn update_stats(mut self, stats: SchematicStats) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Set the text of the commit's parsed projects (see `schematic_texts`),
+    /// replacing what was indexed before
+    pub fn update_texts(mut self, texts: Vec<SchematicText>) -> Self {
+        self.texts = Some(texts);
+        self
+    }
+
+    /// Upsert parts (part_uuid -> (blurb, properties)); parts not listed are kept
+    pub fn update_parts(mut self, parts: HashMap<Uuid, (Option<String>, Value)>) -> Self {
+        self.parts = Some(parts);
+        self
+    }
+
+    /// Whether any column or parts update has been set
+    pub fn is_empty(&self) -> bool {
+        self.commit_date.is_none()
+            && self.git_message.is_none()
+            && self.blurb.is_none()
+            && self.description.is_none()
+            && self.project_overview.is_none()
+            && self.change_summary.is_none()
+            && self.prompt_version.is_none()
+            && self.image.is_none()
+            && self.image_digest.is_none()
+            && self.board.is_none()
+            && self.warnings.is_none()
+            && self.stats.is_none()
+            && self.texts.is_none()
+            && self.parts.is_none()
+    }
+
+    /// Apply the update in a single transaction, returning the schematic id
+    pub async fn execute(self, pool: &PgPool) -> Result<i32, Error> {
+        let mut tx = pool.begin().await?;
+        let schematic_id = self.execute_in(&mut tx).await?;
+        tx.commit().await?;
+        Ok(schematic_id)
+    }
+
+    /// Apply the update as part of `tx`, returning the schematic id
+    ///
+    /// Nothing is visible to other connections until the caller commits.
+    pub async fn execute_in(self, tx: &mut PgTransaction<'_>) -> Result<i32, Error> {
+        crate::retention::forget_deleted(tx, &self.repo_url, &self.commit_hash).await?;
+
+        let image_digest = match (self.image, self.image_digest) {
+            (Some(image), _) => Some(store_image_in(tx, &image).await?.0.digest),
+            (None, Some(digest)) => Some(find_image_in(tx, &digest).await?.ok_or(Error::RowNotFound)?.digest),
+            (None, None) => None,
+        };
+
+        sqlx::query(
+            "INSERT INTO schematics (repo_url, commit_hash) VALUES ($1, $2)
+            ON CONFLICT (repo_url, commit_hash) DO NOTHING",
+        )
+        .bind(&self.repo_url)
+        .bind(&self.commit_hash)
+        .execute(&mut **tx)
+        .await?;
+
+        let mut query: QueryBuilder<Postgres> = QueryBuilder::new("UPDATE schematics SET ");
+        {
+            let mut set = query.separated(", ");
+            // Keeps the statement valid when only parts are being updated
+            set.push("id = id");
+            if let Some(commit_date) = self.commit_date {
+                set.push("commit_date = ").push_bind_unseparated(commit_date);
+            }
+            if let Some(git_message) = self.git_message {
+                set.push("git_message = ").push_bind_unseparated(git_message);
+            }
+            if let Some(blurb) = self.blurb {
+                set.push("blurb = ").push_bind_unseparated(blurb);
+            }
+            if let Some(description) = self.description {
+                set.push("description = ").push_bind_unseparated(description);
+            }
+            if let Some(overview) = self.project_overview {
+                set.push("project_overview = ").push_bind_unseparated(overview);
+            }
+            if let Some((summary, detail_level)) = self.change_summary {
+                set.push("change_summary = ").push_bind_unseparated(summary);
+                set.push("detail_level = ").push_bind_unseparated(detail_level);
+            }
+            if let Some(prompt_version) = self.prompt_version {
+                set.push("prompt_version = ").push_bind_unseparated(prompt_version);
+            }
+            if let Some(digest) = image_digest {
+                set.push("image_digest = ").push_bind_unseparated(digest);
+            }
+            if let Some(board) = self.board {
+                set.push("board_json = ").push_bind_unseparated(board);
+            }
+            if let Some(warnings) = self.warnings {
+                set.push("diff_warnings = ").push_bind_unseparated(sqlx::types::Json(warnings));
+            }
+        }
+        query
+            .push(" WHERE repo_url = ")
+            .push_bind(&self.repo_url)
+            .push(" AND commit_hash = ")
+            .push_bind(&self.commit_hash)
+            .push(" RETURNING id");
+
+        let schematic_id: i32 = query.build().fetch_one(&mut **tx).await?.try_get("id")?;
+        if let Some(stats) = &self.stats {
+            store_schematic_stats_in(tx, schematic_id, stats).await?;
+        }
+        if let Some(texts) = &self.texts {
+            store_schematic_texts_in(tx, schematic_id, texts).await?;
+        }
+
+        let parts = self.parts.unwrap_or_default();
+        let components: Vec<ComponentRecord> = parts
+            .iter()
+            .filter_map(|(part_uuid, (_, properties))| {
+                ComponentRecord::from_json(None, properties, Some(*part_uuid))
+            })
+            .collect();
+
+        let rows = parts
+            .iter()
+            .map(|(part_uuid, (blurb, properties))| (*part_uuid, blurb.as_deref(), properties));
+        upsert_parts(tx, schematic_id, rows).await?;
+
+        upsert_components(
+            tx,
+            schematic_id,
+            &self.repo_url,
+            &self.commit_hash,
+            &components,
+        )
+        .await?;
+        Ok(schematic_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_schematic_builder() {
+        let update = UpdateSchematic::new("https://github.com/a/b.git", "abc");
+        assert!(update.is_empty());
+
+        let update = update.update_blurb("New blurb");
+        assert!(!update.is_empty());
+        assert_eq!(update.blurb.as_deref(), Some("New blurb"));
+        assert!(update.image.is_none());
+
+        // The last image set wins, whether bytes or a stored image's digest
+        let update = update.update_image(vec![1]).update_image_digest("abc123");
+        assert!(update.image.is_none());
+        assert_eq!(update.image_digest.as_deref(), Some("abc123"));
+        let update = update.update_image(vec![2]);
+        assert_eq!((update.image.as_deref(), update.image_digest.as_deref()), (Some(&[2u8][..]), None));
+    }
+}
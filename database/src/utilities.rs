@@ -1,2 +1,2 @@
 pub mod get_project_path;
-pub mod load_environment_file;
\ No newline at end of file
+pub mod text_similarity;
\ No newline at end of file
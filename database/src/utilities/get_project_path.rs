@@ -1,31 +1,75 @@
 // USAGE:
 // cargo test get_project_path -- --nocapture
-use std::error::Error;
+use crate::error::EnvError;
 use std::path::{Path, PathBuf};
 
-/// Gets the project repository base directory by walking up from the current file's location
-/// until it finds a .git directory.
-/// Returns the absolute path to the repo base.
-pub fn get_project_path() -> Result<PathBuf, Box<dyn Error>> {
-    // Get the current file's absolute path (equivalent to Path(__file__).resolve() in Python)
-    let current_filepath = Path::new(file!())
-        .canonicalize()
-        .map_err(|e| format!("Failed to canonicalize current file path: {}", e))?;
-
-    // Traverse up directories to find one containing .git
-    for parent in current_filepath.ancestors() {
-        // Check if '.git' is in this directory
-        let git_dir = parent.join(".git");
-        if git_dir.exists() {
-            return Ok(parent.to_path_buf());
-        }
+/// Names the project directory explicitly, e.g. in a container
+pub const PROJECT_DIR_VAR: &str = "KICAD_PROJECT_DIR";
+
+/// The project directory baked in at build time: KICAD_PROJECT_DIR if it was
+/// set for the build, else the checkout the build ran in
+const BUILT_IN_PROJECT_DIR: &str = match option_env!("KICAD_PROJECT_DIR") {
+    Some(dir) => dir,
+    None => concat!(env!("CARGO_MANIFEST_DIR"), "/.."),
+};
+
+/// Whether `dir` looks like the project's base directory: one holding
+/// `backend/`, as a checkout or a deployment does
+fn is_project_dir(dir: &Path) -> bool {
+    dir.join("backend").is_dir()
+}
+
+/// Gets the project base directory, trying in order:
+///
+/// 1. the KICAD_PROJECT_DIR environment variable, which must then name an
+///    existing directory
+/// 2. the directories above the running executable
+/// 3. the current directory and the ones above it
+/// 4. the directory set at build time, if it still exists
+///
+/// Needs no `.git`, so release builds and containers find it too.
+pub fn get_project_path() -> Result<PathBuf, EnvError> {
+    find_project_path(
+        std::env::var_os(PROJECT_DIR_VAR).map(PathBuf::from),
+        std::env::current_exe().ok(),
+        std::env::current_dir().ok(),
+        Path::new(BUILT_IN_PROJECT_DIR),
+    )
+}
+
+fn find_project_path(
+    explicit: Option<PathBuf>,
+    exe: Option<PathBuf>,
+    cwd: Option<PathBuf>,
+    built_in: &Path,
+) -> Result<PathBuf, EnvError> {
+    if let Some(dir) = explicit.filter(|dir| !dir.as_os_str().is_empty()) {
+        return dir
+            .canonicalize()
+            .ok()
+            .filter(|dir| dir.is_dir())
+            .ok_or_else(|| EnvError::InvalidVar {
+                name: PROJECT_DIR_VAR.to_string(),
+                value: dir.display().to_string(),
+                expected: "an existing directory",
+            });
     }
 
-    // If we've exhausted all ancestors without finding .git, raise an error
-    Err(format!(
-        "Repository main directory not found or .git wasn't found. Started from: {}",
-        current_filepath.display()
-    ).into())
+    let walked = exe
+        .as_deref()
+        .and_then(Path::parent)
+        .into_iter()
+        .chain(cwd.as_deref())
+        .flat_map(Path::ancestors)
+        .find(|dir| is_project_dir(dir));
+    if let Some(dir) = walked {
+        return Ok(dir.to_path_buf());
+    }
+
+    match built_in.canonicalize() {
+        Ok(dir) if is_project_dir(&dir) => Ok(dir),
+        _ => Err(EnvError::ProjectRootNotFound(cwd.unwrap_or_default())),
+    }
 }
 
 #[cfg(test)]
@@ -36,18 +80,65 @@ mod tests {
     fn test_get_project_path() {
         let result = get_project_path();
         assert!(result.is_ok(), "Should find the project repository directory");
-        
+
         let path = result.unwrap();
-        
+
         // Print the absolute path
         let canonical_path = path.canonicalize().unwrap_or_else(|_| path.clone());
         println!("Project repository path: {}", canonical_path.display());
-        
+
         // Verify it's a directory
         assert!(path.is_dir(), "Path should be a directory");
-        
-        // Verify .git exists in that directory
-        let git_dir = path.join(".git");
-        assert!(git_dir.exists(), "Should find .git directory in repo base");
+
+        // Verify it holds backend/, which is what's looked for
+        assert!(path.join("backend").is_dir(), "Should find backend/ in the project directory");
     }
-}
\ No newline at end of file
+
+    /// A directory of its own under the system temp dir
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kicad-project-{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn test_find_project_path_layers() {
+        // A deployment without .git: <root>/backend/.env and <root>/bin/kicad-backend
+        let root = temp_dir("app");
+        std::fs::create_dir_all(root.join("backend")).unwrap();
+        std::fs::create_dir_all(root.join("bin")).unwrap();
+        let elsewhere = temp_dir("elsewhere");
+        let missing = elsewhere.join("missing");
+
+        // From the executable's directory, wherever the process runs
+        let exe = Some(root.join("bin").join("kicad-backend"));
+        let cwd = Some(elsewhere.clone());
+        assert_eq!(find_project_path(None, exe, cwd, &missing).unwrap(), root);
+
+        // From the current directory, for an executable installed elsewhere
+        let exe = Some(elsewhere.join("kicad-backend"));
+        let cwd = Some(root.join("backend"));
+        assert_eq!(find_project_path(None, exe.clone(), cwd, &missing).unwrap(), root);
+
+        // From the build, when neither is inside the project
+        let cwd = Some(elsewhere.clone());
+        assert_eq!(find_project_path(None, exe.clone(), cwd.clone(), &root).unwrap(), root);
+
+        // Nowhere
+        match find_project_path(None, exe.clone(), cwd.clone(), &missing) {
+            Err(EnvError::ProjectRootNotFound(from)) => assert_eq!(Some(from), cwd),
+            other => panic!("expected ProjectRootNotFound, got {:?}", other),
+        }
+
+        // The variable wins, and must name a directory
+        let found = find_project_path(Some(elsewhere.clone()), exe.clone(), Some(root.clone()), &root).unwrap();
+        assert_eq!(found, elsewhere);
+        match find_project_path(Some(missing), exe, Some(root.clone()), &root) {
+            Err(EnvError::InvalidVar { name, .. }) => assert_eq!(name, PROJECT_DIR_VAR),
+            other => panic!("expected InvalidVar, got {:?}", other),
+        }
+
+        let _ = std::fs::remove_dir_all(root);
+        let _ = std::fs::remove_dir_all(elsewhere);
+    }
+}
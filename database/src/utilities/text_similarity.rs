@@ -0,0 +1,76 @@
+// USAGE:
+// cargo test text_similarity -- --nocapture
+use std::collections::{HashMap, HashSet};
+
+/// Lowercased alphanumeric tokens of a text
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// Jaccard similarity of the two texts' token sets, in [0, 1].
+/// Two empty texts are considered identical.
+pub fn jaccard_similarity(a: &str, b: &str) -> f64 {
+    let a: HashSet<String> = tokenize(a).into_iter().collect();
+    let b: HashSet<String> = tokenize(b).into_iter().collect();
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let intersection = a.intersection(&b).count() as f64;
+    let union = a.union(&b).count() as f64;
+    intersection / union
+}
+
+/// Cosine similarity of the two texts' term-frequency vectors, in [0, 1].
+/// Two empty texts are considered identical.
+pub fn cosine_similarity(a: &str, b: &str) -> f64 {
+    let counts = |text: &str| {
+        let mut map: HashMap<String, f64> = HashMap::new();
+        for token in tokenize(text) {
+            *map.entry(token).or_insert(0.0) += 1.0;
+        }
+        map
+    };
+    let a = counts(a);
+    let b = counts(b);
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+
+    let dot: f64 = a
+        .iter()
+        .filter_map(|(token, x)| b.get(token).map(|y| x * y))
+        .sum();
+    let norm = |m: &HashMap<String, f64>| m.values().map(|v| v * v).sum::<f64>().sqrt();
+    let denominator = norm(&a) * norm(&b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot / denominator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_texts() {
+        let text = "Added a 3.3V LDO regulator (U4) to the power rail";
+        assert!((jaccard_similarity(text, text) - 1.0).abs() < 1e-9);
+        assert!((cosine_similarity(text, text) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_disjoint_and_partial_texts() {
+        assert_eq!(jaccard_similarity("alpha beta", "gamma delta"), 0.0);
+        assert_eq!(cosine_similarity("alpha beta", "gamma delta"), 0.0);
+
+        let j = jaccard_similarity("added R1 and R2", "added R1 and C3");
+        assert!(j > 0.0 && j < 1.0);
+        assert_eq!(cosine_similarity("", "something"), 0.0);
+        assert_eq!(jaccard_similarity("", ""), 1.0);
+    }
+}
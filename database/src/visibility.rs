@@ -0,0 +1,155 @@
+// USAGE:
+// cargo test visibility -- --nocapture
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, PgPool, Row};
+
+/// Who may read a repo's (or commit's) AI summaries
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Served on unauthenticated read endpoints (e.g. an open-source changelog)
+    #[default]
+    Public,
+    /// Only served to authenticated callers
+    Private,
+}
+
+impl Visibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Private => "private",
+        }
+    }
+
+    pub fn is_public(&self) -> bool {
+        *self == Visibility::Public
+    }
+}
+
+impl std::str::FromStr for Visibility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "public" => Ok(Visibility::Public),
+            "private" => Ok(Visibility::Private),
+            other => Err(format!(
+                "unknown visibility '{}' (expected public or private)",
+                other
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Visibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// SQL expression for the effective visibility of a `schematics` row aliased as `s`
+pub(crate) const EFFECTIVE_VISIBILITY_SQL: &str = "COALESCE(s.visibility, \
+    (SELECT r.visibility FROM repo_settings r WHERE r.repo_url = s.repo_url), 'public')";
+
+fn parse_column(value: Option<String>) -> Visibility {
+    value.and_then(|v| v.parse().ok()).unwrap_or_default()
+}
+
+/// Visibility configured for a repo (public if never set)
+pub async fn get_repo_visibility(pool: &PgPool, repo_url: &str) -> Result<Visibility, Error> {
+    let row = sqlx::query("SELECT visibility FROM repo_settings WHERE repo_url = $1")
+        .bind(repo_url)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(parse_column(match row {
+        Some(row) => row.try_get("visibility")?,
+        None => None,
+    }))
+}
+
+/// Set the visibility for a repo
+pub async fn set_repo_visibility(
+    pool: &PgPool,
+    repo_url: &str,
+    visibility: Visibility,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO repo_settings (repo_url, visibility, updated_at)
+        VALUES ($1, $2, CURRENT_TIMESTAMP)
+        ON CONFLICT (repo_url) DO UPDATE SET
+            visibility = EXCLUDED.visibility,
+            updated_at = EXCLUDED.updated_at
+        "#,
+    )
+    .bind(repo_url)
+    .bind(visibility.as_str())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Override (or with None, stop overriding) the visibility of one stored commit.
+/// Returns false if the commit isn't stored.
+pub async fn set_commit_visibility(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hash: &str,
+    visibility: Option<Visibility>,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "UPDATE schematics SET visibility = $3 WHERE repo_url = $1 AND commit_hash = $2 AND deleted_at IS NULL",
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .bind(visibility.map(|v| v.as_str()))
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Effective visibility of a commit's summaries: its own override, else the repo's
+pub async fn get_commit_visibility(
+    pool: &PgPool,
+    repo_url: &str,
+    commit_hash: &str,
+) -> Result<Visibility, Error> {
+    let row = sqlx::query(
+        "SELECT visibility FROM schematics WHERE repo_url = $1 AND commit_hash = $2 AND deleted_at IS NULL",
+    )
+    .bind(repo_url)
+    .bind(commit_hash)
+    .fetch_optional(pool)
+    .await?;
+
+    let own: Option<String> = match row {
+        Some(row) => row.try_get("visibility")?,
+        None => None,
+    };
+    match own {
+        Some(own) => Ok(parse_column(Some(own))),
+        None => get_repo_visibility(pool, repo_url).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_visibility() {
+        assert_eq!("Private".parse::<Visibility>(), Ok(Visibility::Private));
+        assert_eq!(" public ".parse::<Visibility>(), Ok(Visibility::Public));
+        assert!("internal".parse::<Visibility>().is_err());
+    }
+
+    #[test]
+    fn test_unknown_column_value_is_public() {
+        assert_eq!(parse_column(None), Visibility::Public);
+        assert_eq!(parse_column(Some("private".to_string())), Visibility::Private);
+    }
+}
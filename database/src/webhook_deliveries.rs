@@ -0,0 +1,118 @@
+// USAGE:
+// cargo test --test integration webhook_deliveries -- --nocapture
+//
+// Webhook deliveries already received, keyed by the ID the provider sends
+// with each delivery (GitHub's X-GitHub-Delivery). Providers redeliver on
+// timeouts with the same ID; recognizing it lets the hook skip the work.
+// The payload is kept too, so a delivery can be replayed after a fix.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Error, PgPool};
+use std::time::Duration;
+
+use crate::organizations::OrgFilter;
+
+/// A delivery as received
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct WebhookDelivery {
+    /// "github", "gitlab" or "bitbucket"
+    pub provider: String,
+    pub delivery_id: String,
+    pub repo_url: String,
+    /// Event type from the provider's headers, where it sends one there
+    pub event: Option<String>,
+    /// The payload, with secrets already redacted by the caller
+    pub payload: Option<Value>,
+}
+
+/// A recorded delivery
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct StoredDelivery {
+    pub provider: String,
+    pub delivery_id: String,
+    pub repo_url: String,
+    pub event: Option<String>,
+    pub payload: Option<Value>,
+    pub received_at: DateTime<Utc>,
+}
+
+/// Record a delivery; false if it was already recorded, i.e. this is a redelivery
+pub async fn record_webhook_delivery(pool: &PgPool, delivery: &WebhookDelivery) -> Result<bool, Error> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO webhook_deliveries (provider, delivery_id, repo_url, event, payload)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (provider, delivery_id) DO NOTHING
+        "#,
+    )
+    .bind(&delivery.provider)
+    .bind(&delivery.delivery_id)
+    .bind(&delivery.repo_url)
+    .bind(&delivery.event)
+    .bind(&delivery.payload)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Forget a delivery, so a redelivery is processed, e.g. after it failed
+pub async fn forget_webhook_delivery(pool: &PgPool, provider: &str, delivery_id: &str) -> Result<(), Error> {
+    sqlx::query("DELETE FROM webhook_deliveries WHERE provider = $1 AND delivery_id = $2")
+        .bind(provider)
+        .bind(delivery_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// A delivery by ID, from any provider; the latest if IDs collide
+pub async fn get_webhook_delivery(pool: &PgPool, delivery_id: &str) -> Result<Option<StoredDelivery>, Error> {
+    sqlx::query_as::<_, StoredDelivery>(
+        r#"
+        SELECT provider, delivery_id, repo_url, event, payload, received_at
+        FROM webhook_deliveries
+        WHERE delivery_id = $1
+        ORDER BY received_at DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(delivery_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// The latest `limit` deliveries for repos of the orgs `orgs` lets through,
+/// newest first
+pub async fn recent_webhook_deliveries(
+    pool: &PgPool,
+    limit: i64,
+    orgs: OrgFilter,
+) -> Result<Vec<StoredDelivery>, Error> {
+    sqlx::query_as::<_, StoredDelivery>(
+        r#"
+        SELECT d.provider, d.delivery_id, d.repo_url, d.event, d.payload, d.received_at
+        FROM webhook_deliveries d
+        LEFT JOIN repos r USING (repo_url)
+        WHERE ($2 OR r.org_id IS NOT DISTINCT FROM $3)
+        ORDER BY d.received_at DESC
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .bind(orgs.is_any())
+    .bind(orgs.org_id())
+    .fetch_all(pool)
+    .await
+}
+
+/// Delete deliveries received more than `retention` ago; returns how many
+pub async fn purge_webhook_deliveries(pool: &PgPool, retention: Duration) -> Result<u64, Error> {
+    let result = sqlx::query(
+        "DELETE FROM webhook_deliveries WHERE received_at <= CURRENT_TIMESTAMP - make_interval(secs => $1)",
+    )
+    .bind(retention.as_secs_f64())
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
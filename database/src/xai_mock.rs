@@ -0,0 +1,516 @@
+// USAGE:
+// cargo test xai_mock -- --nocapture
+//
+// An in-process stand-in for the XAI API, so client code can be tested without
+// a key or network access. Compiled for this crate's tests and, for other
+// crates, with the `mock-xai` feature. It serves the three endpoints the
+// client uses, replaying canned replies:
+//
+//   POST /v1/chat/completions  a completion, SSE chunks when "stream" is true,
+//                              or a request id when "deferred" is true
+//   GET  /v1/chat/deferred-completion/<id>
+//                              202 for the first few polls, then the completion
+//   POST /v1/responses         one output message
+//   GET  /v1/models            the model list
+//   POST /v1/embeddings        a bag-of-words vector per input, so texts
+//                              sharing words come out similar
+//
+// Every request is recorded, so tests can check what the client sent.
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::xai_client::XaiClient;
+
+/// What the mock answers with
+#[derive(Debug, Clone)]
+pub struct MockReplies {
+    /// Message content of a non-streaming chat completion
+    pub completion: String,
+    /// Content deltas sent, in order, to a streaming chat completion
+    pub stream_chunks: Vec<String>,
+    /// Answers of the choices after the first when a request asks for `n`
+    /// of them, in order, each streamed as one delta; `completion` when
+    /// there are too few
+    pub choices: Vec<String>,
+    /// Reasoning deltas sent ahead of the content when the request sets
+    /// `reasoning_effort`, like a reasoning model; joined into the message's
+    /// `reasoning_content` for a completion that isn't streamed
+    pub reasoning_chunks: Vec<String>,
+    /// Polls of a deferred completion answered with 202 (still working) before
+    /// its result; counted per request id
+    pub deferred_polls: usize,
+    /// Text of the message a responses call returns
+    pub response_text: String,
+    /// IDs listed by the models endpoint
+    pub models: Vec<String>,
+    /// Answer every request with this status and body instead, e.g. a 429
+    pub error: Option<(u16, String)>,
+    /// Pause before each event of a streamed answer, like a model writing
+    pub event_delay: Duration,
+}
+
+impl Default for MockReplies {
+    fn default() -> Self {
+        Self {
+            completion: "Hello from the mock.".to_string(),
+            stream_chunks: vec!["Hello".to_string(), " from".to_string(), " the mock.".to_string()],
+            choices: Vec::new(),
+            reasoning_chunks: vec!["The user".to_string(), " says hi.".to_string()],
+            deferred_polls: 1,
+            response_text: "The mock found nothing new.".to_string(),
+            models: vec!["grok-4-1-fast".to_string(), "grok-3-fast".to_string()],
+            error: None,
+            event_delay: Duration::ZERO,
+        }
+    }
+}
+
+/// A request the mock received
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    /// JSON body, or null for requests without one
+    pub body: Value,
+}
+
+/// Fake XAI API on a local port, replaying [`MockReplies`]
+///
+/// ```ignore
+/// let mock = MockChatProvider::start(MockReplies::default()).await;
+/// let reply = mock.client().chat_completion(&request).await?;
+/// assert_eq!(mock.requests()[0].body["model"], "grok-4-1-fast");
+/// ```
+pub struct MockChatProvider {
+    chat_url: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    server: JoinHandle<()>,
+}
+
+impl MockChatProvider {
+    /// Start serving on a free local port; the server stops when this is dropped
+    pub async fn start(replies: MockReplies) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind the mock XAI server");
+        let chat_url = format!(
+            "http://{}/v1/chat/completions",
+            listener.local_addr().expect("bound listener has an address")
+        );
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let replies = Arc::new(replies);
+        let recorded = requests.clone();
+        let server = tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(serve(socket, replies.clone(), recorded.clone()));
+            }
+        });
+
+        Self {
+            chat_url,
+            requests,
+            server,
+        }
+    }
+
+    /// Chat completions URL; the client derives the other endpoints from it
+    pub fn chat_url(&self) -> &str {
+        &self.chat_url
+    }
+
+    /// A client pointed at this mock
+    pub fn client(&self) -> XaiClient {
+        XaiClient::with_api_key("mock-key".to_string(), Some(self.chat_url.clone()), Some(30))
+            .expect("mock client settings are valid")
+    }
+
+    /// Requests received so far, oldest first
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockChatProvider {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// Answer one connection's request; the connection is closed afterwards
+async fn serve(
+    mut socket: TcpStream,
+    replies: Arc<MockReplies>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+) {
+    let Some(request) = read_request(&mut socket).await else {
+        return;
+    };
+    let earlier = {
+        let mut requests = requests.lock().unwrap();
+        let earlier = requests.iter().filter(|r| r.method == request.method && r.path == request.path).count();
+        requests.push(request.clone());
+        earlier
+    };
+
+    let (status, content_type, events) = reply(&replies, &request, earlier);
+    let length: usize = events.iter().map(String::len).sum();
+    let head = format!(
+        "HTTP/1.1 {} {}\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+        status,
+        match status {
+            200 => "OK",
+            202 => "Accepted",
+            _ => "Error",
+        },
+        content_type,
+        length
+    );
+    if socket.write_all(head.as_bytes()).await.is_err() {
+        return;
+    }
+    // Written one event at a time, so streams arrive in pieces like the real API
+    for event in events {
+        if !replies.event_delay.is_zero() && content_type == "text/event-stream" {
+            tokio::time::sleep(replies.event_delay).await;
+        }
+        if socket.write_all(event.as_bytes()).await.is_err() {
+            return;
+        }
+        let _ = socket.flush().await;
+    }
+}
+
+async fn read_request(socket: &mut TcpStream) -> Option<MockRequest> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).into_owned();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let content_length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    while buf.len() < header_end + content_length {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let body = serde_json::from_slice(&buf[header_end..]).unwrap_or(Value::Null);
+    Some(MockRequest { method, path, body })
+}
+
+/// Status, content type and body (as pieces to write) for a request, which
+/// was made `earlier` times before
+fn reply(replies: &MockReplies, request: &MockRequest, earlier: usize) -> (u16, &'static str, Vec<String>) {
+    if let Some((status, body)) = &replies.error {
+        return (*status, "application/json", vec![body.clone()]);
+    }
+    let model = request.body["model"].as_str().unwrap_or("mock-model");
+    let usage = json!({ "prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15 });
+
+    let deferred = request.path.contains("/chat/deferred-completion/");
+    if deferred && earlier < replies.deferred_polls {
+        return (202, "application/json", vec!["{}".to_string()]);
+    }
+    if request.path.ends_with("/chat/completions") && request.body["deferred"] == json!(true) {
+        let id = json!({ "request_id": format!("deferred-{}", earlier + 1) });
+        return (200, "application/json", vec![id.to_string()]);
+    }
+
+    if request.path.ends_with("/chat/completions") || deferred {
+        let reasoning: &[String] = if request.body["reasoning_effort"].is_string() {
+            &replies.reasoning_chunks
+        } else {
+            &[]
+        };
+        if request.body["stream"] == json!(true) {
+            let deltas = reasoning
+                .iter()
+                .map(|chunk| json!({ "reasoning_content": chunk }))
+                .chain(replies.stream_chunks.iter().map(|chunk| json!({ "content": chunk })));
+            let mut events: Vec<String> = deltas
+                .map(|delta| {
+                    let event = json!({ "model": model, "choices": [{ "index": 0, "delta": delta }] });
+                    format!("data: {}\n\n", event)
+                })
+                .collect();
+            for (index, answer) in further_choices(replies, request) {
+                let event = json!({ "model": model, "choices": [{ "index": index, "delta": { "content": answer } }] });
+                events.push(format!("data: {}\n\n", event));
+            }
+            let finish = json!({ "model": model, "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }] });
+            events.push(format!("data: {}\n\n", finish));
+            if request.body["stream_options"]["include_usage"] == json!(true) {
+                events.push(format!("data: {}\n\n", json!({ "model": model, "choices": [], "usage": usage })));
+            }
+            events.push("data: [DONE]\n\n".to_string());
+            return (200, "text/event-stream", events);
+        }
+        let mut message = json!({ "role": "assistant", "content": replies.completion });
+        if !reasoning.is_empty() {
+            message["reasoning_content"] = json!(reasoning.concat());
+        }
+        let mut choices = vec![json!({ "index": 0, "message": message, "finish_reason": "stop" })];
+        choices.extend(further_choices(replies, request).map(|(index, answer)| {
+            let message = json!({ "role": "assistant", "content": answer });
+            json!({ "index": index, "message": message, "finish_reason": "stop" })
+        }));
+        let completion = json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "created": 0,
+            "model": model,
+            "choices": choices,
+            "usage": usage,
+        });
+        return (200, "application/json", vec![completion.to_string()]);
+    }
+
+    if request.path.ends_with("/responses") {
+        let response = json!({
+            "id": "resp-mock",
+            "object": "response",
+            "created_at": 0,
+            "model": model,
+            "output": [{
+                "type": "message",
+                "id": "msg-mock",
+                "status": "completed",
+                "content": [{ "type": "output_text", "text": replies.response_text }],
+            }],
+            "usage": usage,
+        });
+        return (200, "application/json", vec![response.to_string()]);
+    }
+
+    if request.path.ends_with("/models") {
+        let data: Vec<Value> = replies.models.iter().map(|id| json!({ "id": id, "object": "model" })).collect();
+        return (200, "application/json", vec![json!({ "object": "list", "data": data }).to_string()]);
+    }
+
+    if request.path.ends_with("/embeddings") {
+        let inputs = request.body["input"].as_array().cloned().unwrap_or_default();
+        let data: Vec<Value> = inputs
+            .iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let vector = embedding(text.as_str().unwrap_or(""));
+                json!({ "object": "embedding", "index": index, "embedding": vector })
+            })
+            .collect();
+        let list = json!({ "object": "list", "model": model, "data": data, "usage": usage });
+        return (200, "application/json", vec![list.to_string()]);
+    }
+
+    let error = json!({ "error": format!("no mock for {} {}", request.method, request.path) });
+    (404, "application/json", vec![error.to_string()])
+}
+
+/// Index and answer of each choice after the first that `request` asks for
+fn further_choices<'a>(replies: &'a MockReplies, request: &MockRequest) -> impl Iterator<Item = (u64, &'a str)> {
+    let n = request.body["n"].as_u64().unwrap_or(1);
+    (1..n).map(|index| {
+        let answer = replies.choices.get(index as usize - 1).unwrap_or(&replies.completion);
+        (index, answer.as_str())
+    })
+}
+
+/// Dimensions of mock embeddings
+pub const MOCK_EMBEDDING_DIMS: usize = 16;
+
+/// Each lowercased word adds to a bucket chosen by hashing it (FNV-1a)
+fn embedding(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0f32; MOCK_EMBEDDING_DIMS];
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+        let hash = word
+            .to_lowercase()
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+        vector[(hash % MOCK_EMBEDDING_DIMS as u64) as usize] += 1.0;
+    }
+    vector
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::XaiError;
+    use crate::messages::{ChatCompletionRequest, Message, ReasoningEffort};
+    use crate::xai_client::{StreamEvent, Usage};
+    use futures_util::StreamExt;
+    use tokio_util::sync::CancellationToken;
+
+    #[tokio::test]
+    async fn test_mock_replays_stream_chunks() {
+        let mock = MockChatProvider::start(MockReplies::default()).await;
+        let request = ChatCompletionRequest::with_stream(
+            vec![Message::user("Hi".to_string())],
+            "grok-3-fast".to_string(),
+            true,
+        );
+        let stream = mock
+            .client()
+            .chat_completion_stream(&request, CancellationToken::new())
+            .await
+            .unwrap();
+        let events: Vec<StreamEvent> = stream.map(Result::unwrap).collect().await;
+        let chunks: Vec<&str> = events.iter().filter_map(StreamEvent::text).collect();
+        assert_eq!(chunks, MockReplies::default().stream_chunks);
+        let usage = Usage {
+            prompt_tokens: Some(10),
+            completion_tokens: Some(5),
+            total_tokens: Some(15),
+        };
+        assert_eq!(
+            events[chunks.len()..],
+            [
+                StreamEvent::FinishReason { reason: "stop".to_string() },
+                StreamEvent::Usage { usage },
+                StreamEvent::Done,
+            ]
+        );
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/v1/chat/completions");
+        assert_eq!(requests[0].body["model"], "grok-3-fast");
+        assert_eq!(requests[0].body["stream"], true);
+        assert_eq!(requests[0].body["stream_options"]["include_usage"], true);
+    }
+
+    #[tokio::test]
+    async fn test_mock_reasoning() {
+        let mock = MockChatProvider::start(MockReplies::default()).await;
+        let messages = vec![Message::user("Hi".to_string())];
+        let request = ChatCompletionRequest::with_reasoning(messages, "grok-3-mini".to_string(), true, ReasoningEffort::Low);
+        let stream = mock
+            .client()
+            .chat_completion_stream(&request, CancellationToken::new())
+            .await
+            .unwrap();
+        let events: Vec<StreamEvent> = stream.map(Result::unwrap).collect().await;
+        assert_eq!(
+            events[..3],
+            [
+                StreamEvent::ReasoningDelta { text: "The user".to_string() },
+                StreamEvent::ReasoningDelta { text: " says hi.".to_string() },
+                StreamEvent::content("Hello"),
+            ]
+        );
+        // Thinking isn't part of the answer
+        let answer: String = events.iter().filter_map(StreamEvent::text).collect();
+        assert_eq!(answer, "Hello from the mock.");
+        assert_eq!(mock.requests()[0].body["reasoning_effort"], "low");
+
+        let request = ChatCompletionRequest {
+            stream: None,
+            ..request
+        };
+        let response = mock.client().chat_completion(&request).await.unwrap();
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("Hello from the mock."));
+        assert_eq!(message.reasoning_content.as_deref(), Some("The user says hi."));
+    }
+
+    #[tokio::test]
+    async fn test_mock_multiple_choices() {
+        let mock = MockChatProvider::start(MockReplies {
+            choices: vec!["Second.".to_string()],
+            ..Default::default()
+        })
+        .await;
+        let request = ChatCompletionRequest::with_stream(vec![Message::user("Hi".to_string())], "grok-4".to_string(), true)
+            .with_choices(3);
+        let stream = mock.client().chat_completion_stream(&request, CancellationToken::new()).await.unwrap();
+        let events: Vec<StreamEvent> = stream.map(Result::unwrap).collect().await;
+        let answer: String = events.iter().filter_map(StreamEvent::text).collect();
+        assert_eq!(answer, "Hello from the mock.");
+        let further: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::ChoiceDelta { index, text } => Some((*index, text.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(further, [(1, "Second."), (2, "Hello from the mock.")]);
+        assert_eq!(mock.requests()[0].body["n"], 3);
+
+        let request = ChatCompletionRequest { stream: None, ..request };
+        let response = mock.client().chat_completion(&request).await.unwrap();
+        assert_eq!(response.choices.len(), 3);
+        assert_eq!(response.choices[1].message.as_ref().unwrap().content.as_deref(), Some("Second."));
+    }
+
+    #[tokio::test]
+    async fn test_mock_deferred_completion() {
+        let mock = MockChatProvider::start(MockReplies::default()).await;
+        let client = mock.client();
+        let request = ChatCompletionRequest::with_stream(vec![Message::user("Hi".to_string())], "grok-4".to_string(), true);
+
+        let deferred = client.submit_deferred(&request).await.unwrap();
+        assert_eq!(deferred.request_id, "deferred-1");
+        assert_eq!(deferred.model, "grok-4");
+        let submitted = &mock.requests()[0].body;
+        assert_eq!(submitted["deferred"], true);
+        assert!(submitted.get("stream").is_none(), "a deferred result isn't streamed");
+
+        // Still working on the first poll, done on the second
+        assert!(client.deferred_completion(&deferred.request_id).await.unwrap().is_none());
+        let response = client.deferred_completion(&deferred.request_id).await.unwrap().unwrap();
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("Hello from the mock."));
+        assert_eq!(mock.requests()[2].path, "/v1/chat/deferred-completion/deferred-1");
+    }
+
+    #[tokio::test]
+    async fn test_mock_error_status() {
+        let mock = MockChatProvider::start(MockReplies {
+            error: Some((429, "slow down".to_string())),
+            ..Default::default()
+        })
+        .await;
+        let err = mock.client().list_models().await.unwrap_err();
+        assert!(matches!(err, XaiError::RateLimited(body) if body == "slow down"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_embeddings() {
+        let mock = MockChatProvider::start(MockReplies::default()).await;
+        let texts = vec![
+            "Add reverse polarity protection".to_string(),
+            "reverse polarity protection diode".to_string(),
+            String::new(),
+        ];
+        let embeddings = mock.client().embed("v1", &texts).await.unwrap();
+
+        assert_eq!(embeddings.len(), 3);
+        assert!(embeddings.iter().all(|e| e.len() == MOCK_EMBEDDING_DIMS));
+        assert_eq!(embeddings[0].iter().sum::<f32>(), 4.0);
+        assert!(embeddings[2].iter().all(|&x| x == 0.0));
+        assert_eq!(mock.requests()[0].path, "/v1/embeddings");
+        assert_eq!(mock.requests()[0].body["model"], "v1");
+    }
+}
@@ -0,0 +1,386 @@
+// USAGE:
+// cargo test --test parser_fuzz
+// PARSER_FUZZ_CASES=100000 PARSER_FUZZ_SEED=7 cargo test --test parser_fuzz
+// (a debug build, so arithmetic overflow panics too)
+//
+// Property tests for the KiCad readers, which parse whatever arbitrary repos
+// commit. Generated S-expressions must read back as written, and as the
+// writer lays them out; generated KiCad-shaped files and mutations of real
+// ones must never panic anywhere from the tokenizer to the netlist; syntax
+// errors must point inside the text. Each case is seeded, so a failure names
+// the seed that reproduces it.
+//
+// tests/corpus holds a real schematic and malformed variants of the kind
+// found in the wild, each with what reading it should do. fuzz/ runs the
+// tokenizer under cargo-fuzz with the corpus as seeds.
+use kicad_db::error::SchematicError;
+use kicad_db::pcb::{diff_boards, parse_board};
+use kicad_db::schematic::model::parse_sheet;
+use kicad_db::schematic::sexpr::{parse, write, SExpr};
+use kicad_db::schematic::{diff_projects, load_projects, parse_library, render_symbol_svg};
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+
+const DEFAULT_CASES: u64 = 300;
+const DEFAULT_SEED: u64 = 20_250_114;
+
+/// Cases per property and the first seed, from PARSER_FUZZ_CASES / PARSER_FUZZ_SEED
+fn cases() -> std::ops::Range<u64> {
+    let env = |name: &str, default: u64| {
+        std::env::var(name)
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(default)
+    };
+    let seed = env("PARSER_FUZZ_SEED", DEFAULT_SEED);
+    seed..seed + env("PARSER_FUZZ_CASES", DEFAULT_CASES)
+}
+
+/// Run `check` on the input generated for each seed, naming the seed and
+/// input of the first case that panics
+fn for_each_case(name: &str, generate: impl Fn(&mut Gen) -> String, check: impl Fn(&str)) {
+    for seed in cases() {
+        let input = generate(&mut Gen(fastrand::Rng::with_seed(seed)));
+        if panic::catch_unwind(AssertUnwindSafe(|| check(&input))).is_err() {
+            let shown: String = input.chars().take(2000).collect();
+            panic!(
+                "{} failed; rerun with PARSER_FUZZ_SEED={} PARSER_FUZZ_CASES=1\n--- input ({} bytes) ---\n{}",
+                name,
+                seed,
+                input.len(),
+                shown
+            );
+        }
+    }
+}
+
+struct Gen(fastrand::Rng);
+
+/// Heads and flags KiCad files use, so generated trees reach past the root check
+const KEYWORDS: &[&str] = &[
+    "kicad_sch", "kicad_pcb", "kicad_symbol_lib", "version", "uuid", "lib_symbols", "symbol", "lib_id",
+    "lib_name", "at", "mirror", "unit", "body_style", "convert", "property", "pin", "pin_names",
+    "pin_numbers", "hide", "yes", "no", "number", "name", "length", "power", "extends", "rectangle",
+    "circle", "arc", "polyline", "pts", "xy", "start", "mid", "end", "center", "radius", "fill", "type",
+    "wire", "bus", "junction", "no_connect", "label", "global_label", "hierarchical_label", "sheet",
+    "instances", "project", "path", "reference", "sheet_instances", "symbol_instances", "page",
+    "footprint", "module", "layer", "layers", "segment", "via", "net", "width", "x", "y", "passive",
+    "input", "power_in", "line", "Reference", "Value", "Footprint", "Sheetfile", "Sheetname",
+];
+
+/// Numbers a hostile file might carry
+const NUMBERS: &[&str] = &[
+    "0", "-0", "1", "-1", "90", "180", "270", "-90", "360", "0.5", "2.54", "1e308", "-1e308", "1e400",
+    "nan", "NaN", "inf", "-inf", "9223372036854775807", "-9223372036854775808", "18446744073709551616",
+    "4294967296", "0x10", "1.2.3",
+];
+
+impl Gen {
+    fn below(&mut self, n: usize) -> usize {
+        self.0.usize(..n)
+    }
+
+    fn chance(&mut self, percent: u8) -> bool {
+        self.0.u8(..100) < percent
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+
+    /// Any text, biased towards characters the reader treats specially
+    fn text(&mut self, max: usize) -> String {
+        (0..self.below(max + 1))
+            .map(|_| match self.below(10) {
+                0 => ['(', ')', '"', '\\', ' ', '\n', '\t', '\r'][self.below(8)],
+                1 => self.0.char(..),
+                2 => ['é', 'Ω', 'µ', '\u{feff}', '\0', '日', '🔌'][self.below(7)],
+                _ => self.0.alphanumeric(),
+            })
+            .collect()
+    }
+
+    /// Text an atom can hold: no whitespace, parentheses or quotes
+    fn atom(&mut self) -> String {
+        let atom: String = match self.below(3) {
+            0 => self.pick(KEYWORDS).to_string(),
+            1 => self.pick(NUMBERS).to_string(),
+            _ => self.text(12),
+        };
+        let atom: String = atom
+            .chars()
+            .filter(|c| !c.is_whitespace() && !matches!(c, '(' | ')' | '"'))
+            .collect();
+        if atom.is_empty() {
+            "a".to_string()
+        } else {
+            atom
+        }
+    }
+
+    /// An arbitrary tree, at most `depth` lists deep
+    fn sexpr(&mut self, depth: usize) -> SExpr {
+        match self.below(if depth == 0 { 2 } else { 4 }) {
+            0 => SExpr::Atom(self.atom()),
+            1 => SExpr::Str(self.text(16)),
+            _ => SExpr::List((0..self.below(6)).map(|_| self.sexpr(depth - 1)).collect()),
+        }
+    }
+
+    /// A tree shaped like a KiCad file: keyword-headed lists of numbers,
+    /// strings and further lists, under the given root head
+    fn kicad(&mut self, root: &str, depth: usize) -> SExpr {
+        let mut items = vec![SExpr::Atom(root.to_string())];
+        items.extend((0..self.below(12)).map(|_| self.kicad_item(depth)));
+        SExpr::List(items)
+    }
+
+    fn kicad_item(&mut self, depth: usize) -> SExpr {
+        if depth == 0 || self.chance(35) {
+            return match self.below(4) {
+                0 => SExpr::Atom(self.pick(NUMBERS).to_string()),
+                1 => SExpr::Atom(self.pick(KEYWORDS).to_string()),
+                2 => SExpr::Str(self.pick(&["R1", "10k", "Device:R", "#PWR01", "/", "child.kicad_sch", ""]).to_string()),
+                _ => SExpr::Str(self.text(8)),
+            };
+        }
+        let mut items = vec![SExpr::Atom(self.pick(KEYWORDS).to_string())];
+        items.extend((0..self.below(5)).map(|_| self.kicad_item(depth - 1)));
+        SExpr::List(items)
+    }
+
+    /// `expr` as text, with arbitrary whitespace between items
+    fn render(&mut self, expr: &SExpr) -> String {
+        let mut out = String::new();
+        self.render_into(expr, &mut out);
+        out
+    }
+
+    fn render_into(&mut self, expr: &SExpr, out: &mut String) {
+        match expr {
+            SExpr::Atom(atom) => out.push_str(atom),
+            SExpr::Str(text) => {
+                out.push('"');
+                for c in text.chars() {
+                    match c {
+                        '"' => out.push_str("\\\""),
+                        '\\' => out.push_str("\\\\"),
+                        '\n' => out.push_str("\\n"),
+                        '\t' => out.push_str("\\t"),
+                        c => out.push(c),
+                    }
+                }
+                out.push('"');
+            }
+            SExpr::List(items) => {
+                out.push('(');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(self.pick(&[" ", "\n\t", "\r\n", "  \t "]));
+                    }
+                    self.render_into(item, out);
+                }
+                out.push(')');
+            }
+        }
+    }
+
+    /// `text` with a few random edits: truncation, deletions, insertions and
+    /// duplicated spans, the damage bad merges and partial writes leave
+    fn mutate(&mut self, text: &str) -> String {
+        let mut chars: Vec<char> = text.chars().collect();
+        for _ in 0..1 + self.below(4) {
+            let at = self.below(chars.len() + 1);
+            match self.below(4) {
+                0 => chars.truncate(at),
+                1 if at < chars.len() => {
+                    let end = (at + 1 + self.below(40)).min(chars.len());
+                    chars.drain(at..end);
+                }
+                2 => {
+                    let insert: Vec<char> = self.text(4).chars().collect();
+                    chars.splice(at..at, insert);
+                }
+                _ if at < chars.len() => {
+                    let end = (at + 1 + self.below(200)).min(chars.len());
+                    let span: Vec<char> = chars[at..end].to_vec();
+                    let to = self.below(chars.len() + 1);
+                    chars.splice(to..to, span);
+                }
+                _ => {}
+            }
+        }
+        chars.into_iter().collect()
+    }
+}
+
+/// Panics unless a syntax error points at a character of `text` or just past its end
+fn assert_position_in_text(text: &str, err: &SchematicError) {
+    let SchematicError::Syntax { line, column, .. } = err else {
+        return;
+    };
+    let lines: Vec<&str> = text.split('\n').collect();
+    assert!(
+        (1..=lines.len()).contains(line),
+        "line {} is outside the text's {} lines: {}",
+        line,
+        lines.len(),
+        err
+    );
+    let width = lines[line - 1].chars().count();
+    assert!(
+        (1..=width + 1).contains(column),
+        "column {} is outside line {} ({} characters): {}",
+        column,
+        line,
+        width,
+        err
+    );
+}
+
+/// Everything the server does with a file it finds in a repo, as every kind
+/// of KiCad file, with errors checked and results discarded
+fn read_everything(text: &str) {
+    if let Err(err) = parse("fuzz.kicad_sch", text) {
+        assert_position_in_text(text, &err);
+    }
+    for result in [
+        parse_sheet("fuzz.kicad_sch", text).map(drop),
+        parse_board("fuzz.kicad_pcb", text).map(|board| drop(diff_boards(&board, &board))),
+        parse_library("fuzz.kicad_sym", "fuzz", text).map(|library| {
+            for symbol in library.symbols.values() {
+                for unit in symbol.units() {
+                    render_symbol_svg(Some(symbol), unit, 1, "fuzz");
+                }
+            }
+        }),
+    ] {
+        if let Err(err) = result {
+            assert_position_in_text(text, &err);
+        }
+    }
+
+    // A root and a child it may include, or itself
+    let sources = BTreeMap::from([
+        ("fuzz.kicad_sch".to_string(), text.to_string()),
+        ("child.kicad_sch".to_string(), text.to_string()),
+    ]);
+    if let Ok(projects) = load_projects(&sources) {
+        for project in &projects {
+            project.components();
+            project.netlist();
+            project.bom();
+            diff_projects(project, project).describe();
+        }
+    }
+}
+
+#[test]
+fn test_generated_sexprs_round_trip() {
+    for_each_case(
+        "round trip",
+        |gen| {
+            let expr = SExpr::List((0..gen.below(6)).map(|_| gen.sexpr(6)).collect());
+            gen.render(&expr)
+        },
+        |text| {
+            let parsed = parse("t", text).expect("generated text is well-formed");
+            let mut again = Gen(fastrand::Rng::with_seed(0));
+            assert_eq!(parse("t", &again.render(&parsed)).unwrap(), parsed);
+            assert_eq!(parse("t", &write(&parsed)).unwrap(), parsed);
+        },
+    );
+}
+
+#[test]
+fn test_arbitrary_text_never_panics() {
+    for_each_case("arbitrary text", |gen| gen.text(400), read_everything);
+}
+
+#[test]
+fn test_kicad_shaped_files_never_panic() {
+    for_each_case(
+        "KiCad-shaped file",
+        |gen| {
+            let root = gen.pick(&["kicad_sch", "kicad_pcb", "kicad_symbol_lib"]);
+            let expr = gen.kicad(root, 5);
+            gen.render(&expr)
+        },
+        read_everything,
+    );
+}
+
+#[test]
+fn test_mutated_corpus_files_never_panic() {
+    let corpus: Vec<String> = std::fs::read_dir(corpus_path(""))
+        .unwrap()
+        .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+        .collect();
+    for_each_case(
+        "mutated corpus file",
+        |gen| {
+            let original = &corpus[gen.below(corpus.len())];
+            gen.mutate(original)
+        },
+        read_everything,
+    );
+}
+
+#[test]
+fn test_deep_nesting_is_an_error_not_a_stack_overflow() {
+    let depth = 1_000_000;
+    let text = format!("{}{}", "(a ".repeat(depth), ")".repeat(depth));
+    let err = parse("deep.kicad_sch", &text).unwrap_err();
+    assert!(err.to_string().contains("nested"), "unexpected error {}", err);
+    assert_position_in_text(&text, &err);
+}
+
+fn corpus_path(name: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus").join(name)
+}
+
+/// What reading a corpus file as a schematic should do
+#[derive(Debug)]
+enum Expect {
+    Parses,
+    /// A syntax error at this line and column
+    SyntaxAt(usize, usize),
+    /// Any other error
+    Rejected,
+}
+
+#[test]
+fn test_corpus() {
+    let corpus = [
+        ("two_resistors.kicad_sch", Expect::Parses),
+        // Cut off mid-file, as by a partial write or a size-limited fetch
+        ("truncated.kicad_sch", Expect::SyntaxAt(151, 1)),
+        // A closing quote lost on line 152: quotes pair up wrongly from there
+        // on, leaving the file's last quote to open a string that never ends
+        ("unterminated_string.kicad_sch", Expect::SyntaxAt(275, 12)),
+        // One ')' too many on line 10 closes the file's list early, with
+        // its last lines left over
+        ("extra_paren.kicad_sch", Expect::SyntaxAt(133, 2)),
+        // Conflict markers are bare atoms, which readers skip
+        ("merge_conflict.kicad_sch", Expect::Parses),
+        // Saved by an editor that writes a byte order mark
+        ("bom.kicad_sch", Expect::Parses),
+        ("crlf.kicad_sch", Expect::Parses),
+        ("nul_bytes.kicad_sch", Expect::Parses),
+        // Fields missing, mistyped or out of range everywhere
+        ("garbage_fields.kicad_sch", Expect::Parses),
+        ("not_a_schematic.kicad_sch", Expect::Rejected),
+        ("empty.kicad_sch", Expect::SyntaxAt(1, 1)),
+    ];
+    for (name, expect) in corpus {
+        let text = std::fs::read_to_string(corpus_path(name)).unwrap();
+        let result = parse_sheet(name, &text);
+        match (&expect, &result) {
+            (Expect::Parses, Ok(_)) | (Expect::Rejected, Err(SchematicError::NotASchematic { .. })) => {}
+            (Expect::SyntaxAt(line, column), Err(SchematicError::Syntax { line: l, column: c, .. }))
+                if (line, column) == (l, c) => {}
+            _ => panic!("{}: expected {:?}, got {:?}", name, expect, result.map(drop)),
+        }
+        read_everything(&text);
+    }
+}
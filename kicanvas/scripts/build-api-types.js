@@ -0,0 +1,191 @@
+/*
+    Generates src/kicanvas/services/api-types.ts from the backend's OpenAPI
+    spec (backend/openapi.json, written by `kicad-backend openapi`).
+
+    Every schema becomes an interface or a union of string literals, and
+    ApiOperations maps each "METHOD /path" to its parameters, request body
+    and response, so fetch calls are typed against what the server sends.
+
+    The server sends unset optional fields as null, so in responses they are
+    typed `T | null`; the few it leaves out instead arrive as undefined,
+    which `== null` checks also catch. In request bodies they may be omitted.
+*/
+
+import * as fs from "node:fs/promises";
+import { resolve } from "node:path";
+
+const SPEC_FILE = resolve("../backend/openapi.json");
+const OUT_FILE = resolve("src/kicanvas/services/api-types.ts");
+
+const spec = JSON.parse(await fs.readFile(SPEC_FILE, "utf-8"));
+
+function refName(ref) {
+    return ref.split("/").pop();
+}
+
+/** Names of the schemas request bodies use, directly or nested */
+function requestSchemas() {
+    const names = new Set();
+    const visit = (schema) => {
+        if (!schema || typeof schema != "object") {
+            return;
+        }
+        if (schema.$ref) {
+            const name = refName(schema.$ref);
+            if (!names.has(name)) {
+                names.add(name);
+                visit(spec.components.schemas[name]);
+            }
+            return;
+        }
+        Object.values(schema).forEach(visit);
+    };
+    for (const item of Object.values(spec.paths)) {
+        for (const operation of Object.values(item)) {
+            visit(operation.requestBody);
+        }
+    }
+    return names;
+}
+
+const REQUEST_SCHEMAS = requestSchemas();
+
+/** TypeScript type of a JSON schema */
+function tsType(schema) {
+    if (!schema) {
+        return "unknown";
+    }
+    let type;
+    if (schema.$ref) {
+        type = refName(schema.$ref);
+    } else if (schema.enum) {
+        type = schema.enum.map((v) => JSON.stringify(v)).join(" | ");
+    } else if (schema.allOf || schema.oneOf || schema.anyOf) {
+        const parts = schema.allOf ?? schema.oneOf ?? schema.anyOf;
+        const joiner = schema.allOf ? " & " : " | ";
+        type = parts.map(tsType).join(joiner);
+    } else if (schema.type == "array") {
+        const item = tsType(schema.items);
+        type = /[|&]/.test(item) ? `(${item})[]` : `${item}[]`;
+    } else if (schema.type == "object" || schema.properties) {
+        type = schema.properties
+            ? inlineObject(schema, false)
+            : `Record<string, ${tsType(schema.additionalProperties)}>`;
+    } else if (schema.type == "integer" || schema.type == "number") {
+        type = "number";
+    } else if (schema.type == "string") {
+        type = "string";
+    } else if (schema.type == "boolean") {
+        type = "boolean";
+    } else {
+        type = "unknown";
+    }
+    return schema.nullable && type != "unknown" ? `${type} | null` : type;
+}
+
+function comment(text, indent) {
+    if (!text) {
+        return "";
+    }
+    const lines = text.trim().split("\n");
+    if (lines.length == 1) {
+        return `${indent}/** ${lines[0]} */\n`;
+    }
+    return `${indent}/**\n${lines
+        .map((l) => `${indent} * ${l}`.trimEnd())
+        .join("\n")}\n${indent} */\n`;
+}
+
+function fields(schema, indent, isRequest) {
+    const required = new Set(schema.required ?? []);
+    return Object.entries(schema.properties ?? {})
+        .map(([name, prop]) => {
+            const present =
+                required.has(name) || (prop.nullable && !isRequest);
+            const optional = present ? "" : "?";
+            return `${comment(prop.description, indent)}${indent}${name}${optional}: ${tsType(prop)};`;
+        })
+        .join("\n");
+}
+
+function inlineObject(schema, isRequest) {
+    return `{ ${fields(schema, "", isRequest).replaceAll("\n", " ")} }`;
+}
+
+function declaration(name, schema) {
+    const doc = comment(schema.description, "");
+    if (schema.properties && !schema.allOf && !schema.oneOf) {
+        const isRequest = REQUEST_SCHEMAS.has(name);
+        return `${doc}export interface ${name} {\n${fields(schema, "    ", isRequest)}\n}`;
+    }
+    return `${doc}export type ${name} = ${tsType(schema)};`;
+}
+
+/** Fields of the parameters `location` ("path" or "query") of an operation */
+function params(operation, location) {
+    const found = (operation.parameters ?? []).filter((p) => p.in == location);
+    if (!found.length) {
+        return null;
+    }
+    const body = found
+        .map((p) => `${p.name}${p.required ? "" : "?"}: ${tsType(p.schema)}`)
+        .join("; ");
+    return `{ ${body} }`;
+}
+
+function content(body) {
+    const json = body?.content?.["application/json"];
+    if (json) {
+        return tsType(json.schema);
+    }
+    return body?.content ? "string" : "void";
+}
+
+function operationType(operation) {
+    const parts = [];
+    const path = params(operation, "path");
+    const query = params(operation, "query");
+    if (path) {
+        parts.push(`path: ${path}`);
+    }
+    if (query) {
+        parts.push(`query: ${query}`);
+    }
+    if (operation.requestBody) {
+        parts.push(`body: ${content(operation.requestBody)}`);
+    }
+    parts.push(`response: ${content(operation.responses?.["200"])}`);
+    return `{ ${parts.join("; ")} }`;
+}
+
+const out = [
+    "/*",
+    "    Generated by scripts/build-api-types.js from backend/openapi.json.",
+    "    Do not edit; run `npm run build:api` after regenerating the spec.",
+    "*/",
+    "",
+    "/* eslint-disable */",
+    "",
+];
+
+for (const [name, schema] of Object.entries(spec.components.schemas)) {
+    out.push(declaration(name, schema), "");
+}
+
+out.push(
+    "/** Every API operation by method and path, with what it takes and returns */",
+    "export interface ApiOperations {",
+);
+for (const [path, item] of Object.entries(spec.paths)) {
+    for (const [method, operation] of Object.entries(item)) {
+        const key = `${method.toUpperCase()} ${path}`;
+        const deprecated = operation.deprecated ? "    /** @deprecated */\n" : "";
+        out.push(`${deprecated}    "${key}": ${operationType(operation)};`);
+    }
+}
+out.push("}", "");
+
+await fs.writeFile(OUT_FILE, out.join("\n"));
+console.log(
+    `Wrote ${Object.keys(spec.components.schemas).length} schemas to ${OUT_FILE}`,
+);
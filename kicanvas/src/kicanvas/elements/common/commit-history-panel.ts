@@ -341,6 +341,12 @@ export class KCCommitHistoryPanelElement extends KCUIElement {
     private error: string | null = null;
     private repo: string | null = null;
     private expandedGroups: Set<number> = new Set();
+    private events: EventSource | null = null;
+
+    override disconnectedCallback() {
+        super.disconnectedCallback();
+        this.closeEvents();
+    }
 
     /**
      * Set the repository and load commits
@@ -352,9 +358,36 @@ export class KCCommitHistoryPanelElement extends KCUIElement {
 
         this.repo = repo;
         this.selectedCommit = null;
+        this.openEvents();
         await this.loadCommits();
     }
 
+    /**
+     * Reload the commits whenever the backend finishes processing one, so
+     * pushed commits show up without a page reload
+     */
+    private openEvents(): void {
+        this.closeEvents();
+        if (!this.repo) {
+            return;
+        }
+
+        const events = new EventSource(GrokiAPI.getRepoEventsUrl(this.repo));
+        const refresh = () => {
+            if (!this.loading) {
+                void this.loadCommits();
+            }
+        };
+        events.addEventListener("commit", refresh);
+        events.addEventListener("resync", refresh);
+        this.events = events;
+    }
+
+    private closeEvents(): void {
+        this.events?.close();
+        this.events = null;
+    }
+
     /**
      * Get the currently selected commit hash
      */
@@ -3,7 +3,13 @@
     Extracted from grok-chat-panel.ts for better separation of concerns.
 */
 
-import { GrokiAPI, type DistilledSchematic, type RepoInitResponse, type RepoClearCacheResponse } from "../../services/api";
+import {
+    GrokiAPI,
+    type DistilledSchematic,
+    type GrokSelectionStreamRequest,
+    type RepoInitResponse,
+    type RepoClearCacheResponse,
+} from "../../services/api";
 import type { SelectedComponent, GrokContext } from "./types";
 import { API_BASE_URL } from "../../../config";
 
@@ -11,6 +17,8 @@ import { API_BASE_URL } from "../../../config";
 export interface StreamCallbacks {
     onStart?: () => void;
     onChunk?: (content: string) => void;
+    /** The model's thinking so far (thinking mode), ahead of the answer */
+    onReasoning?: (reasoning: string) => void;
     onComplete?: (fullContent: string) => void;
     onError?: (error: string) => void;
 }
@@ -23,14 +31,7 @@ export interface InitCallbacks {
 }
 
 /** Request payload for the Grok selection stream endpoint */
-export interface GrokStreamRequest {
-    repo: string;
-    commit: string;
-    component_ids: string[];
-    query: string;
-    distilled: DistilledSchematic;
-    thinking_mode: boolean;
-}
+export type GrokStreamRequest = GrokSelectionStreamRequest;
 
 /**
  * Service for interacting with the Grok AI backend.
@@ -217,6 +218,8 @@ export class GrokAPIService {
             const reader = response.body?.getReader();
             const decoder = new TextDecoder();
             let fullContent = "";
+            let fullReasoning = "";
+            let eventName = "";
 
             if (reader) {
                 let done = false;
@@ -229,7 +232,20 @@ export class GrokAPIService {
                         const lines = chunk.split("\n");
 
                         for (const line of lines) {
-                            if (line.startsWith("data: ")) {
+                            // Named events (finish reason, token usage)
+                            // are metadata; only unnamed data is answer text.
+                            // Reasoning events carry the model's thinking.
+                            if (line.startsWith("event: ")) {
+                                eventName = line.slice(7).trim();
+                            } else if (line.trim() === "") {
+                                eventName = "";
+                            } else if (
+                                line.startsWith("data: ") &&
+                                eventName === "reasoning"
+                            ) {
+                                fullReasoning += line.slice(6);
+                                callbacks.onReasoning?.(fullReasoning);
+                            } else if (line.startsWith("data: ") && !eventName) {
                                 const data = line.slice(6);
 
                                 if (data === "[DONE]") {
@@ -52,6 +52,7 @@ export class KCGrokChatPanelElement extends KCUIElement {
     private _selectedPreset: string | null = null;
     private _customQuery = "";
     private _responseContent = "";
+    private _reasoningContent = ""; // Model's thinking behind the streaming answer (thinking mode)
     private _isLoading = false;
     private _error: string | null = null;
     private _hoveredComponentUuid: string | null = null; // Component hovered in viewer
@@ -135,11 +136,10 @@ export class KCGrokChatPanelElement extends KCUIElement {
         }
 
         // Ensure streaming response content is rendered as HTML after re-render
-        if (this._responseContent && this.streaming && !this._isLoading && !this._error) {
+        if ((this._responseContent || this._reasoningContent) && this.streaming && !this._isLoading && !this._error) {
             const streamingEl = this.renderRoot.querySelector(".streaming-response");
             if (streamingEl) {
-                streamingEl.innerHTML = this._formatContent(this._responseContent) +
-                    '<span class="cursor"></span>';
+                streamingEl.innerHTML = this._streamingHTML();
             }
         }
     }
@@ -909,6 +909,7 @@ export class KCGrokChatPanelElement extends KCUIElement {
         this._isLoading = true;
         this._error = null;
         this._responseContent = "";
+        this._reasoningContent = "";
         this.streaming = true;
         this._shouldAutoScroll = true; // Reset auto-scroll for new query
         
@@ -925,17 +926,13 @@ export class KCGrokChatPanelElement extends KCUIElement {
                     this._isLoading = false;
                     this._scheduleUpdate();
                 },
+                onReasoning: (reasoning) => {
+                    this._reasoningContent = reasoning;
+                    this._renderStreaming();
+                },
                 onChunk: (content) => {
                     this._responseContent = content;
-                    // Update streaming response content directly for performance
-                    const streamingEl = this.renderRoot.querySelector(".streaming-response");
-                    if (streamingEl) {
-                        streamingEl.innerHTML = this._formatContent(content) + 
-                            '<span class="cursor"></span>';
-                        this._scrollResponseToBottom();
-                    } else {
-                        this._scheduleUpdate();
-                    }
+                    this._renderStreaming();
                 },
                 onComplete: () => {
                     this.streaming = false;
@@ -944,6 +941,7 @@ export class KCGrokChatPanelElement extends KCUIElement {
                         this._conversationHistory.push({ role: 'assistant', content: this._responseContent });
                     }
                     this._responseContent = ""; // Clear streaming content
+                    this._reasoningContent = "";
                     this._scheduleUpdate();
                 },
                 onError: (error) => {
@@ -957,6 +955,25 @@ export class KCGrokChatPanelElement extends KCUIElement {
         );
     }
 
+    /** Update the streaming response directly, for performance */
+    private _renderStreaming() {
+        const streamingEl = this.renderRoot.querySelector(".streaming-response");
+        if (streamingEl) {
+            streamingEl.innerHTML = this._streamingHTML();
+            this._scrollResponseToBottom();
+        } else {
+            this._scheduleUpdate();
+        }
+    }
+
+    /** The answer so far, below a pane with the model's thinking (if any) */
+    private _streamingHTML(): string {
+        const thinking = this._reasoningContent
+            ? `<div class="thinking-block">${this._escapeHtml(this._reasoningContent)}</div>`
+            : "";
+        return thinking + this._formatContent(this._responseContent) + '<span class="cursor"></span>';
+    }
+
     private _shouldAutoScroll = true;
 
     private _scrollResponseToBottom() {
@@ -1156,7 +1173,7 @@ export class KCGrokChatPanelElement extends KCUIElement {
         // Show loading when:
         // 1. _isLoading is true (waiting for server to respond)
         // 2. OR streaming is true but no content yet (stream started, waiting for first chunk)
-        const showLoading = this._isLoading || (this.streaming && !this._responseContent);
+        const showLoading = this._isLoading || (this.streaming && !this._responseContent && !this._reasoningContent);
 
         // Determine the loading message based on thinking mode and state
         const loadingMessage = this._thinkingMode 
@@ -1197,7 +1214,7 @@ export class KCGrokChatPanelElement extends KCUIElement {
                                         <span>${loadingMessage}</span>
                                     </div>
                                 </div>
-                            ` : this._responseContent ? html`
+                            ` : this._responseContent || this._reasoningContent ? html`
                                 <div class="message assistant-bubble">
                                     <div class="streaming-response"></div>
                                 </div>
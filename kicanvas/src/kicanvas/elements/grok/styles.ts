@@ -795,7 +795,8 @@ export const responseStyles = css`
     }
 
     /* Thinking/reasoning content - shows chain of thought */
-    .response-content .thinking-block {
+    .response-content .thinking-block,
+    .streaming-response .thinking-block {
         margin: 8px 0 12px 0;
         padding: 10px 12px;
         background: rgba(147, 51, 234, 0.08);
@@ -806,7 +807,8 @@ export const responseStyles = css`
         font-style: italic;
     }
 
-    .response-content .thinking-block::before {
+    .response-content .thinking-block::before,
+    .streaming-response .thinking-block::before {
         content: '💭 Thinking...';
         display: block;
         font-weight: 600;
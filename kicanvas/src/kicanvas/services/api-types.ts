@@ -0,0 +1,2492 @@
+/*
+    Generated by scripts/build-api-types.js from backend/openapi.json.
+    Do not edit; run `npm run build:api` after regenerating the spec.
+*/
+
+/* eslint-disable */
+
+export interface AdminOverviewResponse {
+    /** AI spend since midnight UTC */
+    ai_spend_today_usd: number;
+    cache_hit_rates: CacheHitRate[];
+    /** Commits whose last attempt failed in the last 24 hours */
+    commits_failed_24h: number;
+    /** Commits whose processing finished in the last 24 hours */
+    commits_processed_24h: number;
+    error_rate: ErrorRate;
+    generated_at: string;
+    queue: JobQueueDepth;
+    registered_repos: number;
+}
+
+export interface AnalysisTimeline {
+    /** Time spent cloning/fetching the repository and reading files */
+    clone_ms: number | null;
+    /** Time spent waiting on the LLM */
+    llm_ms: number | null;
+    /** Stages that exceeded their configured budget */
+    over_budget: string[];
+    /** Time spent parsing/distilling schematics */
+    parse_ms: number | null;
+    /** Time spent assembling the prompt */
+    prompt_build_ms: number | null;
+    /** Time spent writing to the database */
+    store_ms: number | null;
+    /** Wall-clock time for the whole analysis */
+    total_ms: number;
+}
+
+export interface ApiError {
+    /** Error code */
+    error: string;
+    /** Human-readable error message */
+    message: string;
+    /** ID of the failed request, also sent as X-Request-Id; quote it when reporting problems */
+    request_id: string | null;
+    /** With a 422, each problem with the request */
+    violations?: FieldViolation[];
+}
+
+export interface ApiKeyItem {
+    created_at: string;
+    /** Key id, used to revoke it */
+    id: number;
+    /** Last time the key was used (updated at most once a minute) */
+    last_used_at: string | null;
+    /** Name given when the key was created */
+    name: string;
+    /** Org the key acts for; unset for instance-wide keys */
+    org_id: number | null;
+    /** First characters of the key, to tell keys apart */
+    prefix: string;
+    /** Set once the key is revoked */
+    revoked_at: string | null;
+    /** "read", "hook" or "admin" */
+    scope: string;
+}
+
+export interface ApiKeyListResponse {
+    /** All keys, newest first, including revoked ones */
+    keys: ApiKeyItem[];
+}
+
+/** A commit the backfill failed to summarize */
+export interface BackfillError {
+    commit: string;
+    error: string;
+}
+
+/** Progress of a summary backfill, sent as an SSE `progress` event */
+export interface BackfillProgress {
+    /** Cancellation was asked for; the job stops after its current commit */
+    cancel_requested: boolean;
+    class: WorkClass;
+    /** Commit being summarized */
+    current: string | null;
+    /** Commits summarized so far */
+    done: number;
+    errors: BackfillError[];
+    /** Commits that failed */
+    failed: number;
+    finished_at: string | null;
+    /** ID of the job under /api/admin/jobs */
+    job_id: number;
+    /** Commits not tried yet */
+    remaining: number;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    started_at: string;
+    status: BackfillStatus;
+    /**
+     * Why the backfill stopped early (AI unavailable, budget spent, rate
+     * limited, or the commits couldn't be listed)
+     */
+    stopped_reason: string | null;
+    /** Schematic commits that had no summary when the backfill started */
+    total: number;
+}
+
+/** Where a summary backfill stands */
+export type BackfillStatus = "running" | "completed" | "stopped" | "cancelled";
+
+export interface BoardResponse {
+    /** One entry per .kicad_pcb file in the repository */
+    boards: BoardSummary[];
+    /** Commit hash */
+    commit: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface BoardSummary {
+    /** Copper layer names in stackup order */
+    copper_layers: string[];
+    /** Board file path within the repository */
+    file: string;
+    /** Footprint placements, in natural reference order */
+    footprints: FootprintItem[];
+    /** Number of copper layers */
+    layer_count: number;
+    /** Number of arc tracks */
+    track_arcs: number;
+    /** Routed length per copper layer in mm */
+    track_length_by_layer: Record<string, number>;
+    /** Total routed length in mm */
+    track_length_mm: number;
+    /** Number of straight track segments */
+    track_segments: number;
+    /** Number of vias */
+    vias: number;
+    /** Via count by type ("through", "blind", "micro") */
+    vias_by_type: Record<string, number>;
+    /** Number of copper zones */
+    zones: number;
+}
+
+export interface BomLineItem {
+    /** Footprint (if set) */
+    footprint: string | null;
+    /** Library symbol of the first part on the line */
+    lib_id: string;
+    /** Manufacturer part number (if set) */
+    mpn: string | null;
+    /** Number of parts on the line */
+    quantity: number;
+    /** Reference designators, in natural order */
+    references: string[];
+    /** Sheets the parts are placed on, e.g. "/Power/" */
+    sheets: string[];
+    /** Component value, e.g. "10k" */
+    value: string;
+}
+
+export interface BomResponse {
+    /** Commit hash */
+    commit: string;
+    /** One entry per KiCad project in the repository */
+    projects: ProjectBom[];
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+/** A rectangle on a sheet, in mm */
+export interface BoundingBox {
+    x1: number;
+    x2: number;
+    y1: number;
+    y2: number;
+}
+
+/** Lookups of one cache on this server since it started */
+export interface CacheHitRate {
+    /** "ai", "response" or "schematic" */
+    cache: string;
+    /** Share of lookups that hit, 0 to 1; null before the first lookup */
+    hit_rate: number | null;
+    hits: number;
+    misses: number;
+}
+
+/** One distinct part of a commit (components sharing a value, footprint and MPN) */
+export interface CategorizedPart {
+    /** Taxonomy category, e.g. "regulator" (null until the part is classified) */
+    category: string | null;
+    footprint: string | null;
+    /** Manufacturer part number (if set) */
+    mpn: string | null;
+    quantity: number;
+    /** In natural order */
+    references: string[];
+    /** e.g. "ldo" (null if none fits, or unclassified) */
+    subcategory: string | null;
+    value: string | null;
+}
+
+/** A rendering attached to a chat answer, e.g. a cited component's symbol */
+export interface ChatAttachmentItem {
+    /** `target` of the citation it illustrates */
+    cites: string | null;
+    /** The rendering itself, e.g. SVG markup */
+    content: string;
+    media_type: string;
+    /** One-line caption, e.g. "R1 Device:R" */
+    title: string;
+}
+
+/** Payload of the `attachments` event sent after the `citations` event */
+export interface ChatAttachmentsEvent {
+    attachments: ChatAttachmentItem[];
+}
+
+/** Something a chat answer mentions that the viewer can link to */
+export interface ChatCitationItem {
+    /** Commit the component or net was found at (null for commits) */
+    commit_hash: string | null;
+    /** component, net or commit */
+    kind: string;
+    /** Component reference, net name or full commit hash */
+    target: string;
+}
+
+/** Payload of the `citations` event sent once a chat answer has streamed */
+export interface ChatCitationsEvent {
+    citations: ChatCitationItem[];
+}
+
+/** Who said a chat turn; system prompts are built by the server */
+export type ChatRole = "user" | "assistant";
+
+/**
+ * A chat session: every turn as it was said, and the running summary that
+ * replaces the older ones in requests once the conversation nears the
+ * model's context window
+ */
+export interface ChatSessionResponse {
+    created_at: string;
+    id: string;
+    repo: string | null;
+    /** Position of the last turn the summary covers; 0 before any is folded */
+    summarized_through: number;
+    /** What the turns up to `summarized_through` said */
+    summary: string | null;
+    /** Tokens the history adds to the next request: the summary and the turns after it */
+    tokens: number;
+    /** Every turn, oldest first, including the summarized ones */
+    turns: ChatSessionTurn[];
+    updated_at: string;
+}
+
+/** A stored chat turn */
+export interface ChatSessionTurn {
+    /** Renderings attached to an assistant turn */
+    attachments: ChatAttachmentItem[];
+    /** What an assistant turn cites; empty for user turns */
+    citations: ChatCitationItem[];
+    content: string;
+    created_at: string;
+    /** 1 for the first turn */
+    position: number;
+    role: ChatRole;
+    /** Tokens it takes up in a request */
+    tokens: number;
+}
+
+/** Context retrieved for a chat answer, which the answer cites by `id` */
+export interface ChatSource {
+    /** Commit the source comes from */
+    commit_hash: string;
+    /** Tag the model cites, e.g. "S1" */
+    id: string;
+    kind: ChatSourceKind;
+    /** Net name, for nets */
+    net: string | null;
+    /** Component reference, for components */
+    reference: string | null;
+    /** Repository clone URL */
+    repo_url: string;
+    /** Similarity to the question, when found by semantic search */
+    score: number | null;
+    /** The text given to the model */
+    text: string;
+    /** One-line heading, e.g. "Commit 1a2b3c4d: Add input protection" */
+    title: string;
+}
+
+/** What a retrieved source is */
+export type ChatSourceKind = "commit" | "component" | "net";
+
+/** Payload of the `sources` event sent before a chat answer streams */
+export interface ChatSourcesEvent {
+    sources: ChatSource[];
+}
+
+export interface ChatTurn {
+    content: string;
+    role: ChatRole;
+}
+
+/** Work of one class waiting or under way on this server */
+export interface ClassQueueDepth {
+    /** AI calls waiting their turn at the shared rate limit */
+    ai_calls_waiting: number;
+    /** Commits those backfills have yet to summarize */
+    backfill_commits_remaining: number;
+    class: WorkClass;
+    /** Summary backfills running as this class */
+    running_backfills: number;
+}
+
+export interface ClassifyPartsResponse {
+    /** Parts classified by this run */
+    classified: number;
+    /** Model that classified them, after any routing away from a degraded one */
+    model: string;
+}
+
+export interface CommitChangesResponse {
+    author: string | null;
+    /** Short AI-generated summary (null if not generated yet or hidden from the caller) */
+    blurb: string | null;
+    /** Schematic and layout files the commit touched */
+    changed_files: string[];
+    /** Commit hash */
+    commit: string;
+    commit_date: string | null;
+    /** Detailed AI-generated description (null if not generated yet or hidden from the caller) */
+    description: string | null;
+    /** Commit message summary */
+    message: string | null;
+    /** ERC violations absent at the parent commit (null if either commit hasn't been distilled) */
+    new_erc_findings: ErcFindingItem[] | null;
+    /** Parent commit the changes are relative to (null for a root commit) */
+    parent_commit: string | null;
+    /** Per project, what the commit changed; unchanged projects are omitted */
+    projects: ProjectChanges[];
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /**
+     * Changes that look unintended: mass deletions, renamed power nets,
+     * decoupling capacitors removed from next to an IC
+     */
+    warnings: DiffWarningItem[];
+}
+
+export interface CommitFilesRequest {
+    /** Commit SHA (full or abbreviated), branch or tag; responses carry the full hash */
+    commit: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface CommitFilesResponse {
+    /** Full commit hash */
+    commit: string;
+    /** List of .kicad_sch files at this commit */
+    files: SchematicFile[];
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface CommitInfo {
+    /** Author name */
+    author: string | null;
+    author_email: string | null;
+    /** Timestamp of the commit */
+    commit_date: string | null;
+    /** Full commit hash */
+    commit_hash: string;
+    /** Whether this commit modified .kicad_sch or .kicad_pcb files */
+    has_schematic_changes: boolean;
+    /** Commit message summary */
+    message: string | null;
+    /** Tags pointing at this commit */
+    tags: string[];
+}
+
+export interface CommitInfoRequest {
+    /** Commit SHA (full or abbreviated), branch or tag; responses carry the full hash */
+    commit: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface CommitInfoResponse {
+    /** Short AI-generated summary */
+    blurb: string | null;
+    /** List of changed .kicad_sch and .kicad_pcb file paths */
+    changed_files: string[];
+    /** Full commit hash */
+    commit: string;
+    /** Timestamp of the commit */
+    commit_date: string | null;
+    /** Detailed AI-generated description */
+    description: string | null;
+    /** Commit message summary */
+    message: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** Summary visibility ("public" or "private"); private summaries are omitted without an API key */
+    visibility: string;
+}
+
+export interface CommitStatsItem {
+    commit_date: string | null;
+    commit_hash: string;
+    stats: SchematicStatsItem;
+}
+
+export interface CommitStatsResponse {
+    /** Commit hash */
+    commit: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    stats: SchematicStatsItem;
+}
+
+export interface CommitTimeline {
+    /** Full commit hash */
+    commit: string;
+    timeline: AnalysisTimeline;
+}
+
+export interface ComponentChangeItem {
+    /** value, footprint, lib_id, sheet or dnp */
+    field: string;
+    new: string | null;
+    old: string | null;
+    /** Reference designator */
+    reference: string;
+}
+
+export interface ComponentHistoryItem {
+    /** Fields that differ from the previous entry (empty for the first) */
+    changed: string[];
+    /** Commit date (if recorded) */
+    commit_date: string | null;
+    /** Full commit hash */
+    commit_hash: string;
+    /** Footprint at this commit */
+    footprint: string | null;
+    /** Commit message (if recorded) */
+    message: string | null;
+    /** Manufacturer part number at this commit */
+    mpn: string | null;
+    /** Sheet path at this commit */
+    sheet: string | null;
+    /** Component value at this commit (e.g. "10k") */
+    value: string | null;
+}
+
+export interface ComponentHistoryResponse {
+    /** The component at each stored commit, oldest first */
+    history: ComponentHistoryItem[];
+    /** Reference designator (e.g. "U1") */
+    reference: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface ComponentPlacement {
+    bbox: BoundingBox | null;
+    footprint: string | null;
+    /** Manufacturer part number (if set) */
+    mpn: string | null;
+    /** Reference designator */
+    reference: string;
+    /** Rotation in degrees, as in the schematic */
+    rotation: number | null;
+    /** Sheet the component is placed on, e.g. "/Power/" */
+    sheet: string | null;
+    value: string | null;
+    /** Anchor of the (first) unit on its sheet in mm (null if not recorded) */
+    x: number | null;
+    y: number | null;
+}
+
+/** One repo's use of a part, at its newest commit with stored components */
+export interface ComponentUsageItem {
+    commit: string;
+    commit_date: string | null;
+    footprint: string | null;
+    quantity: number;
+    /** Reference designators */
+    references: string[];
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    value: string | null;
+}
+
+export interface ComponentUsageResponse {
+    /** The MPN asked about, trimmed and in upper case as parts are compared */
+    mpn: string;
+    /** Repos using the part, most recently committed first */
+    repos: ComponentUsageItem[];
+    /** Components across those repos */
+    total_quantity: number;
+}
+
+export interface ComponentsResponse {
+    /** Commit hash */
+    commit: string;
+    /** In natural reference order */
+    components: ComponentPlacement[];
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+/** Spend of one repo, API key or model */
+export interface CostLine {
+    /** Monthly budget, for repos and keys that have one */
+    budget_usd: number | null;
+    calls: number;
+    completion_tokens: number;
+    cost_usd: number;
+    /**
+     * Repository slug, API key ID, provider or model; null for calls not charged to any
+     * repo or key (e.g. background jobs)
+     */
+    key: string | null;
+    /** API key name, for lines by key */
+    name: string | null;
+    /** Whether the budget is spent, so calls use the fallback model or are refused */
+    over_budget: boolean;
+    prompt_tokens: number;
+}
+
+export interface CostReportResponse {
+    by_api_key: CostLine[];
+    by_model: CostLine[];
+    /** "xai" or the name of a fallback provider calls failed over to */
+    by_provider: CostLine[];
+    /** Most expensive first */
+    by_repo: CostLine[];
+    /** Model calls switch to once a budget is spent; null if they are refused instead */
+    fallback_model: string | null;
+    /** The month reported, "YYYY-MM" */
+    month: string;
+    total_usd: number;
+}
+
+export interface CreateApiKeyRequest {
+    /** What the key is for, e.g. "ci" or "frontend" */
+    name: string;
+    /**
+     * Slug of the org the key acts for; instance-wide keys only. Defaults
+     * to the caller's org, or none for an instance-wide key
+     */
+    org?: string | null;
+    /** "read", "hook" or "admin" */
+    scope: string;
+}
+
+export interface CreateApiKeyResponse {
+    key: ApiKeyItem;
+    /** The key itself; it is only shown once */
+    secret: string;
+}
+
+export interface CreateChatSessionRequest {
+    /**
+     * Repository the conversation is about ("owner/repo"); chat requests in
+     * the session must name the same one
+     */
+    repo?: string | null;
+}
+
+export interface CreateOrganizationRequest {
+    /** Display name */
+    name: string;
+    /** Lowercase letters, digits and dashes, e.g. "acme-robotics" */
+    slug: string;
+}
+
+export interface CreateShareLinkRequest {
+    /**
+     * Seconds until the link expires; the server's default when absent,
+     * at most its maximum
+     */
+    expires_in_secs?: number | null;
+    /** Views the link opens; all of them by default */
+    scopes?: ShareScope[] | null;
+}
+
+export interface DeleteCommitResponse {
+    commit: string;
+    repo: string;
+}
+
+export interface DeleteGuidelineResponse {
+    deleted: boolean;
+    id: number;
+}
+
+export interface DependencyStatus {
+    /** Whether this is a recent result reused instead of a fresh check */
+    cached: boolean;
+    /** What was found, or why the check failed */
+    detail: string;
+    /** How long the check took */
+    latency_ms: number;
+    /** "database", "git_cache_disk" or "xai" */
+    name: string;
+    ok: boolean;
+}
+
+export interface DesignGuidelineItem {
+    body: string;
+    created_at: string;
+    id: number;
+    priority: number;
+    title: string;
+    updated_at: string;
+}
+
+/** A house rule for the AI to apply in summaries and chat */
+export interface DesignGuidelineRequest {
+    /** The rule as the model reads it, e.g. "We always use 0402 passives" */
+    body: string;
+    /** Higher comes first within its org or repo (default: 0) */
+    priority?: number | null;
+    /** Short name, e.g. "Passives" */
+    title: string;
+}
+
+/** A change in a commit's diff that may not have been intended */
+export interface DiffWarningItem {
+    /** mass_deletion, power_net_renamed or decoupling_removed */
+    kind: string;
+    /** Human-readable description */
+    message: string;
+    /** Nets involved, e.g. a renamed net's old and new name */
+    nets: string[];
+    /** Components involved, e.g. a removed capacitor and the IC it decoupled */
+    references: string[];
+}
+
+export interface DigestSubscriberItem {
+    created_at: string;
+    /** Lowercased */
+    email: string;
+}
+
+/** An address to send a repository's weekly activity digest to */
+export interface DigestSubscriberRequest {
+    email: string;
+}
+
+export interface DigestSubscribersResponse {
+    repo: string;
+    /** By address */
+    subscribers: DigestSubscriberItem[];
+}
+
+export interface DigiKeyParameter {
+    /** Parameter name (e.g., "Resistance", "Capacitance") */
+    name: string;
+    /** Parameter value */
+    value: string;
+}
+
+export interface DigiKeyPartInfo {
+    /** Category name */
+    category: string | null;
+    /** Datasheet URL */
+    datasheet_url: string | null;
+    /** Part description */
+    description: string | null;
+    /** Detailed product description */
+    detailed_description: string | null;
+    /** DigiKey part number */
+    digikey_part_number: string | null;
+    /** Whether the part is obsolete/deprecated */
+    is_obsolete: boolean;
+    /** Lifecycle status description */
+    lifecycle_status: string | null;
+    /** Manufacturer name */
+    manufacturer: string | null;
+    /** Manufacturer part number */
+    manufacturer_part_number: string | null;
+    /** Product parameters/specifications */
+    parameters: DigiKeyParameter[];
+    /** Primary photo URL */
+    photo_url: string | null;
+    /** Product status (Active, Obsolete, etc.) */
+    product_status: string | null;
+    /** Product URL on DigiKey */
+    product_url: string | null;
+    /** Quantity available */
+    quantity_available: number | null;
+    /** Unit price (USD) */
+    unit_price: number | null;
+}
+
+export interface DigiKeySearchRequest {
+    /** Manufacturer part number (optional, for more precise search) */
+    mpn?: string | null;
+    /** Part number or keyword to search for */
+    query: string;
+}
+
+export interface DigiKeySearchResponse {
+    /** Error message if search failed */
+    error: string | null;
+    /** List of matching parts (may be empty) */
+    parts: DigiKeyPartInfo[];
+    /** The search query used */
+    query: string;
+    /** Whether the search was successful */
+    success: boolean;
+    /** Total number of results found */
+    total_count: number;
+}
+
+export interface DigiKeyStatusResponse {
+    /** Whether DigiKey credentials are set */
+    configured: boolean;
+    /** What to do if they aren't */
+    message: string;
+}
+
+export interface DistillRequest {
+    /** Commit SHA (full or abbreviated), branch or tag; responses carry the full hash */
+    commit: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface DistillResponse {
+    /** Whether the result was served from cache */
+    cached: boolean;
+    /** Full commit hash */
+    commit: string;
+    /** Distilled schematic data (JSON object with components, nets, proximities) */
+    distilled: unknown;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface ErcFindingItem {
+    /** Whether the violation is new relative to the parent commit (null if the parent wasn't analyzed) */
+    introduced: boolean | null;
+    /** Human-readable description */
+    message: string;
+    /** Net involved (if any) */
+    net: string | null;
+    /** Pin number involved (if any) */
+    pin: string | null;
+    /** Reference designator involved (if any) */
+    reference: string | null;
+    /** Rule that was violated (unconnected_pin, conflicting_outputs, undriven_power_pin, duplicate_reference) */
+    rule: string;
+    /** "error" or "warning" */
+    severity: string;
+}
+
+export interface ErcResponse {
+    /** Commit hash */
+    commit: string;
+    /** Number of findings with severity "error" */
+    error_count: number;
+    /** All findings at this commit */
+    findings: ErcFindingItem[];
+    /** Parent commit the findings were compared against (null for a root commit) */
+    parent_commit: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** Number of findings with severity "warning" */
+    warning_count: number;
+}
+
+/** Requests this server answered in the last hour */
+export interface ErrorRate {
+    /** `server_errors / requests`; 0 without requests */
+    rate: number;
+    requests: number;
+    /** Answered with a 5xx */
+    server_errors: number;
+}
+
+/** How one variant of an experiment did */
+export interface ExperimentVariantItem {
+    /** AI calls made for its summaries */
+    calls: number;
+    completion_tokens: number;
+    /** Average cost of a summary handed out */
+    cost_per_summary: number;
+    cost_usd: number;
+    experiment: string;
+    /** Thumbs up */
+    helpful: number;
+    /** Share of ratings that were thumbs up, 0 to 1 */
+    helpful_rate: number;
+    model: string | null;
+    prompt_tokens: number;
+    /**
+     * Prompt template and model of its latest summary; null if it has AI
+     * calls but no summaries in the period
+     */
+    prompt_version: string | null;
+    /** Summaries rated at least once */
+    rated: number;
+    /** Summaries handed out */
+    summaries: number;
+    /** Thumbs down */
+    unhelpful: number;
+    variant: string;
+    /**
+     * Its weight in the running experiment; null if the variant is no
+     * longer configured
+     */
+    weight: number | null;
+}
+
+export interface ExperimentsResponse {
+    /** Name of the experiment summaries are assigned to now, if any */
+    active: string | null;
+    /** Start of the period reported on */
+    since: string;
+    /** By experiment and variant */
+    variants: ExperimentVariantItem[];
+}
+
+export interface FeedbackReportResponse {
+    /** Start of the period reported on */
+    since: string;
+    /** By kind, prompt template and model, the most rated first */
+    totals: FeedbackTotalItem[];
+}
+
+/** Ratings of the summaries of one kind one prompt template and model wrote */
+export interface FeedbackTotalItem {
+    /** Ratings that came with a comment */
+    comments: number;
+    /** Thumbs up */
+    helpful: number;
+    /** Share of ratings that were thumbs up, 0 to 1 */
+    helpful_rate: number;
+    /** What was summarized, e.g. "commit_summary" */
+    kind: string;
+    model: string;
+    prompt_version: string;
+    /** Summaries rated at least once */
+    summaries: number;
+    /** Thumbs down */
+    unhelpful: number;
+}
+
+/** One problem with a request field */
+export interface FieldViolation {
+    /** Field name, with an index for list items, e.g. `component_ids[2]` */
+    field: string;
+    message: string;
+}
+
+export interface FootprintItem {
+    /** Library footprint, e.g. "Resistor_SMD:R_0603_1608Metric" */
+    footprint: string;
+    /** Placement side, "F.Cu" or "B.Cu" */
+    layer: string;
+    /** Reference designator */
+    reference: string;
+    /** Rotation in degrees */
+    rotation: number;
+    /** Component value (if set on the footprint) */
+    value: string | null;
+    /** X position in mm */
+    x: number;
+    /** Y position in mm */
+    y: number;
+}
+
+export interface GrokAskRequest {
+    /** Commit SHA (full or abbreviated), branch or tag */
+    commit: string;
+    question: string;
+    /** Answer again instead of returning the stored answer */
+    refresh?: boolean;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** Number of retrieved sources per kind (commits, components, nets); 0 turns retrieval off. Defaults to 5 */
+    top_k?: number | null;
+}
+
+export interface GrokAskResponse {
+    /** Plain text, citing `sources` by tag, e.g. [S1] */
+    answer: string;
+    /** Whether the answer was stored from an earlier request */
+    cached: boolean;
+    /** Full commit hash */
+    commit: string;
+    /** References of the commit's components the answer mentions */
+    components: string[];
+    generated_at: string;
+    /** Model that wrote the answer */
+    model: string;
+    /** Names of the commit's nets the answer mentions */
+    nets: string[];
+    /** Prompt template the answer was generated from */
+    prompt_version: string;
+    /**
+     * The question as first asked; the same words in another case or
+     * spacing share its answer
+     */
+    question: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** Context retrieved for the question, which the answer cites by `id` */
+    sources: ChatSource[];
+}
+
+export interface GrokChatRequest {
+    /** Commit whose components and nets are searched; defaults to the newest distilled commit */
+    commit?: string | null;
+    /**
+     * The conversation so far, oldest first, ending with the user's question;
+     * with a `session_id`, only the turns since the last request
+     */
+    messages: ChatTurn[];
+    /** Model to use instead of the configured default; must be on the allowlist */
+    model?: string | null;
+    /** Persona preset; falls back to the repo default */
+    persona?: string | null;
+    /** For reasoning models, how hard to think ("low" or "high"); the thinking streams as `reasoning` events */
+    reasoning_effort?: string | null;
+    /** Repository the conversation is about ("owner/repo"); enables retrieval and the repo's default persona */
+    repo?: string | null;
+    /**
+     * Chat session (POST /api/grok/chat/sessions) whose stored history goes
+     * before `messages`; the question and answer are added to it. Must have
+     * the same `repo`
+     */
+    session_id?: string | null;
+    /** Number of retrieved sources per kind (commits, components, nets); 0 turns retrieval off. Defaults to 5 */
+    top_k?: number | null;
+}
+
+export interface GrokCommitSummaryRequest {
+    /** Commit SHA (full or abbreviated), branch or tag; responses carry the full hash */
+    commit: string;
+    /** Response length: brief (timeline blurb), standard, or deep (design review). Defaults to standard */
+    detail_level?: string | null;
+    /** Model to use instead of the configured default; must be on the allowlist (see /api/grok/models) */
+    model?: string | null;
+    /** Persona preset (terse-reviewer, teaching-assistant, formal-report); falls back to the repo default */
+    persona?: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface GrokCommitSummaryResponse {
+    /** Full commit hash */
+    commit: string;
+    /** Detail level the summary was generated at */
+    detail_level: string;
+    /** Detailed AI-generated analysis */
+    details: string;
+    /** Model that generated the summary */
+    model: string;
+    /** Prompt template the summary was generated from */
+    prompt_version: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** Short AI-generated summary */
+    summary: string;
+    /**
+     * ID to rate the summary with at POST /api/grok/feedback; null if it
+     * couldn't be recorded
+     */
+    summary_id: number | null;
+}
+
+export interface GrokCompareSummaryRequest {
+    /** Older commit: SHA (full or abbreviated), branch or tag */
+    base: string;
+    /** Response length: brief, standard, or deep. Defaults to standard */
+    detail_level?: string | null;
+    /** Newer commit: SHA (full or abbreviated), branch or tag */
+    head: string;
+    /** Generate the comparison again instead of returning the stored one */
+    refresh?: boolean;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface GrokCompareSummaryResponse {
+    /** Full hash of the older commit */
+    base: string;
+    /** Whether the comparison was stored from an earlier request */
+    cached: boolean;
+    /** The component and net changes between the two commits, one per line */
+    changes: string;
+    /** Commits reachable from head but not from base */
+    commit_count: number;
+    /** Detail level the comparison was generated at */
+    detail_level: string;
+    generated_at: string;
+    /** Full hash of the newer commit */
+    head: string;
+    /** Model that wrote the comparison */
+    model: string;
+    /** Prompt template the comparison was generated from */
+    prompt_version: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** AI-written narrative of how the design evolved */
+    summary: string;
+}
+
+export interface GrokObsoleteReplacementRequest {
+    /** Product category */
+    category?: string | null;
+    /** Datasheet URL (PDF link for Grok to analyze) */
+    datasheet_url?: string | null;
+    /** Part description */
+    description?: string | null;
+    /** Manufacturer name */
+    manufacturer?: string | null;
+    /** Manufacturer part number of the obsolete part */
+    manufacturer_part_number: string;
+    /** Model to use instead of the configured default; must be on the allowlist and support tools */
+    model?: string | null;
+    /** Key parameters/specifications */
+    parameters: DigiKeyParameter[];
+    /** DigiKey product page URL */
+    product_url?: string | null;
+}
+
+export interface GrokObsoleteReplacementResponse {
+    /** AI-generated analysis and replacement recommendations */
+    analysis: string;
+    /** Error message if failed */
+    error: string | null;
+    /** The original obsolete part number */
+    original_part: string;
+    /** Whether the search was successful */
+    success: boolean;
+}
+
+export interface GrokReleaseNotesRequest {
+    /** Tag of the previous release */
+    from_tag: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** Tag of the release the notes are for */
+    to_tag: string;
+}
+
+export interface GrokReleaseNotesResponse {
+    /** The commits that changed schematics or layouts, oldest first */
+    changes: ReleaseNoteChange[];
+    /** Full hash of the commit from_tag points at */
+    from_commit: string;
+    from_tag: string;
+    /** Release notes in Markdown, by section */
+    markdown: string;
+    /** Model that wrote the notes; empty when the range has no hardware changes */
+    model: string;
+    /** Prompt template the notes were generated from; empty when the range has no hardware changes */
+    prompt_version: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** Full hash of the commit to_tag points at */
+    to_commit: string;
+    to_tag: string;
+}
+
+export interface GrokRepoSummaryRequest {
+    /** Response length: brief (timeline blurb), standard, or deep (design review). Defaults to standard */
+    detail_level?: string | null;
+    /** Persona preset (terse-reviewer, teaching-assistant, formal-report); falls back to the repo default */
+    persona?: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface GrokRepoSummaryResponse {
+    /** Detail level the summary was generated at */
+    detail_level: string;
+    /** Detailed AI-generated analysis */
+    details: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** Short AI-generated summary */
+    summary: string;
+}
+
+export interface GrokReviewChecklistRequest {
+    /** Commit SHA (full or abbreviated), branch or tag */
+    commit: string;
+    /**
+     * Generate the checklist again, clearing what was ticked off, instead of
+     * returning the stored one
+     */
+    refresh?: boolean;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface GrokSelectionStreamRequest {
+    /** Without a snapshot, show the model the stored schematic image for the commit (if any) */
+    attach_schematic_image?: boolean;
+    /** Commit SHA (full or abbreviated), branch or tag; responses carry the full hash */
+    commit: string;
+    /** List of component IDs (references) to analyze; shorthand for a components selection */
+    component_ids?: string[];
+    /** Pre-distilled schematic data (optional - will fetch if not provided) */
+    distilled?: unknown;
+    /** Model to use instead of the configured default; must be on the allowlist (see /api/grok/models) */
+    model?: string | null;
+    /** Persona preset (terse-reviewer, teaching-assistant, formal-report); falls back to the repo default */
+    persona?: string | null;
+    /** User's query about the components */
+    query: string;
+    /** How hard a reasoning model thinks ("low" or "high"); implies thinking mode. Defaults to low */
+    reasoning_effort?: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    selection?: Selection | null;
+    /**
+     * PNG or JPEG of the selected region as rendered in the viewer, base64 or a data URL.
+     * Shown to the model alongside the text, so the model must support image input
+     */
+    snapshot?: string | null;
+    /** Enable thinking/reasoning mode; the model's thinking streams as `reasoning` events */
+    thinking_mode?: boolean;
+}
+
+export interface GrokSelectionSummaryRequest {
+    /** Commit SHA (full or abbreviated), branch or tag; responses carry the full hash */
+    commit: string;
+    /** List of component IDs to analyze; shorthand for a components selection */
+    component_ids?: string[];
+    /** Response length: brief (timeline blurb), standard, or deep (design review). Defaults to standard */
+    detail_level?: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    selection?: Selection | null;
+}
+
+export interface GrokSelectionSummaryResponse {
+    /** Full commit hash */
+    commit: string;
+    /**
+     * List of component IDs that were analyzed: those selected, or those
+     * on the selected nets, sheet or area
+     */
+    component_ids: string[];
+    /** Detail level the summary was generated at */
+    detail_level: string;
+    /** Detailed AI-generated analysis */
+    details: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** Short AI-generated summary */
+    summary: string;
+}
+
+export interface GrokSuggestFixRequest {
+    /** The change to make, in plain words */
+    change: string;
+}
+
+export interface HookUpdateResponse {
+    /** The delivery was received before (a provider redelivery), so nothing was processed */
+    duplicate: boolean;
+    /** List of errors encountered during processing */
+    errors: string[];
+    /** Number of commits successfully processed */
+    processed: number;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /**
+     * The branch's high-water mark: only commits after it were looked at.
+     * None when the whole history was scanned
+     */
+    since_commit: string | null;
+    /** Stage timings for each processed commit */
+    timings: CommitTimeline[];
+}
+
+export interface ImportRepoResponse {
+    /** Commits stored, replacing any already stored */
+    imported: number;
+    repo: string;
+}
+
+/** A commit a backfill set out to summarize */
+export interface JobCommit {
+    commit: string;
+    error: string | null;
+    status: JobCommitStatus;
+}
+
+/** Where one commit of a backfill stands */
+export type JobCommitStatus = "pending" | "running" | "done" | "failed";
+
+export interface JobDetailResponse {
+    /** Every commit the job set out to summarize, in order */
+    commits: JobCommit[];
+    job: BackfillProgress;
+}
+
+export interface JobListResponse {
+    /** Newest first */
+    jobs: BackfillProgress[];
+}
+
+/** Work waiting or under way */
+export interface JobQueueDepth {
+    /** Jobs running on this server (webhook updates, imports, re-syncs) */
+    active_jobs: number;
+    /** Commits those backfills have yet to summarize */
+    backfill_commits_remaining: number;
+    /** The same by work class, with the AI calls waiting for the rate limit */
+    classes: ClassQueueDepth[];
+    /** Commits waiting to be processed or being processed now */
+    commits_queued: number;
+    /** Summary backfills running on this server */
+    running_backfills: number;
+}
+
+export interface LifecycleCheckResponse {
+    /** Alerts announced on the notification webhook by this check */
+    notified: number;
+    /** Alerts open after the check */
+    open_alerts: number;
+    /** Distinct MPNs looked up */
+    parts: number;
+    /** Registered repos with parts at their latest commit */
+    repos: number;
+    /** Alerts resolved because the part is gone or no longer at risk */
+    resolved: number;
+}
+
+export interface LivenessResponse {
+    /** Always "ok"; the process is up and serving requests */
+    status: string;
+    /** Seconds since the process started */
+    uptime_secs: number;
+}
+
+export interface MemberItem {
+    created_at: string;
+    email: string;
+    name: string | null;
+    /** Widest scope the member gets in the org: "read", "hook" or "admin" */
+    role: string;
+}
+
+export interface MemberListResponse {
+    /** By email */
+    members: MemberItem[];
+    org: string;
+}
+
+export interface ModelListResponse {
+    /** Every model a request may pass as `model` */
+    allowed: string[];
+    /** Default for chat */
+    chat: string;
+    /** Default for replacement part search */
+    replacement: string;
+    /** Default for selection streaming */
+    selection: string;
+    /** Default for commit summaries */
+    summary: string;
+}
+
+export interface NetChangeItem {
+    /** Pins that joined the net, as "R1.2" */
+    added: string[];
+    /** Net name */
+    name: string;
+    /** Pins that left the net, as "R1.2" */
+    removed: string[];
+}
+
+export interface NetConnectorItem {
+    /** Connection point in mm, [x, y] */
+    at: number[];
+    /**
+     * Sheet paths the net continues on through this connector: the child
+     * sheet of a sheet pin, the parent of a hierarchical label, other sheets
+     * with the same global label or power symbol
+     */
+    continues_to: string[];
+    kind: NetConnectorKind;
+    /** Label text, sheet pin name or power net */
+    name: string;
+}
+
+/** How a net leaves or names itself on a sheet */
+export type NetConnectorKind = "local_label" | "global_label" | "hierarchical_label" | "sheet_pin" | "power_symbol";
+
+export interface NetGeometryResponse {
+    /** Commit hash */
+    commit: string;
+    /** Net name as in the netlist, e.g. "/Power/VIN" or "GND" */
+    net: string;
+    /** The projects with a net of that name */
+    projects: ProjectNetGeometry[];
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface NetItem {
+    /** Net name; local labels are prefixed with their sheet path, e.g. "/Power/VIN" */
+    name: string;
+    /** Pins on the net */
+    nodes: NetNodeItem[];
+}
+
+export interface NetNodeItem {
+    /** Pin number */
+    pin: string;
+    /** Pin name (if the symbol names it) */
+    pin_name: string | null;
+    /** KiCad electrical type, e.g. "input", "power_in", "passive" */
+    pin_type: string;
+    /** Reference designator */
+    reference: string;
+}
+
+export interface NetPinItem {
+    /** Pin tip in mm, [x, y] */
+    at: number[];
+    /** Pin number */
+    pin: string;
+    /** Reference designator */
+    reference: string;
+}
+
+export interface NetSheetGeometry {
+    /** Labels, sheet pins and power symbols on the net */
+    connectors: NetConnectorItem[];
+    /** Schematic file of the sheet, within the repository */
+    file: string;
+    /** Junction dots in mm, [x, y] */
+    junctions: number[][];
+    /** Component pins on the net, in natural reference order */
+    pins: NetPinItem[];
+    /** Sheet path, e.g. "/Power/" */
+    sheet_path: string;
+    /** Wire segments in mm, [[x1, y1], [x2, y2]] */
+    wires: number[][][];
+}
+
+export interface NetlistResponse {
+    /** Commit hash */
+    commit: string;
+    /** One entry per KiCad project in the repository */
+    projects: ProjectNetlist[];
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface OnboardGithubOrgRequest {
+    /** GitHub org (or user) whose repositories are onboarded, e.g. "acme-hardware" */
+    github_org: string;
+    /** Onboard archived repos too. Defaults to false */
+    include_archived?: boolean;
+    /** Onboard forks too. Defaults to false */
+    include_forks?: boolean;
+    /**
+     * Slug of the org that owns the repos; instance-wide keys only. Defaults
+     * to the caller's org
+     */
+    org?: string | null;
+    processing?: ProcessingPolicy;
+    /**
+     * Where GitHub reaches this server, e.g. https://kicad.example.com; push
+     * webhooks are only installed with it
+     */
+    public_url?: string | null;
+}
+
+export interface OnboardGithubOrgResponse {
+    /**
+     * Initial syncs queued; each shows up under /api/admin/jobs once its
+     * commits are processed and summaries start
+     */
+    backfills_queued: number;
+    github_org: string;
+    /** Repositories registered by this call */
+    registered: number;
+    /** Every repository, in the order GitHub listed them */
+    repos: OnboardedRepoItem[];
+    /** Repositories the org has that the token can see */
+    scanned: number;
+    /** Webhooks installed or updated */
+    webhooks_installed: number;
+}
+
+/** What onboarding did with a repo */
+export type OnboardStatus = "registered" | "already_registered" | "skipped" | "failed";
+
+/** What became of a registered repo's push webhook */
+export type OnboardWebhook = "installed" | "updated" | "skipped" | "failed";
+
+export interface OnboardedRepoItem {
+    /**
+     * The initial sync (processing the history, then summaries as the
+     * processing policy asks) was queued
+     */
+    backfill_queued: boolean;
+    /** Why the repo was skipped or something failed */
+    detail: string | null;
+    /** "owner/repo" */
+    repo: string;
+    status: OnboardStatus;
+    webhook: OnboardWebhook;
+}
+
+export interface OrgGuidelinesResponse {
+    /** Highest priority first */
+    guidelines: DesignGuidelineItem[];
+    org: string;
+}
+
+export interface OrganizationItem {
+    created_at: string;
+    id: number;
+    name: string;
+    /** Short name, used in JWT `org` claims and when creating keys and repos */
+    slug: string;
+}
+
+export interface OrganizationListResponse {
+    /** By slug */
+    orgs: OrganizationItem[];
+}
+
+/**
+ * A part of the repo's latest commit that the supplier marks NRND, EOL or
+ * obsolete
+ */
+export interface PartAlertItem {
+    /** The commit the part was last seen in */
+    commit_hash: string;
+    designators: string[];
+    first_seen_at: string;
+    last_seen_at: string;
+    /** The supplier's own wording, e.g. "Not For New Designs" */
+    lifecycle_status: string | null;
+    /** Trimmed and upper case */
+    mpn: string;
+    /** When the part left the repo or its status recovered */
+    resolved_at: string | null;
+    /** "nrnd", "eol" or "obsolete" */
+    risk: string;
+}
+
+export interface PartCategoryCount {
+    category: string;
+    /** Distinct parts in the category */
+    parts: number;
+    /** Components in the category */
+    quantity: number;
+}
+
+export interface PartListResponse {
+    /** Every category at this commit (ignoring the filter), for offering filters */
+    categories: PartCategoryCount[];
+    /** Commit hash */
+    commit: string;
+    /** Matching parts, by category then value */
+    parts: CategorizedPart[];
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** Parts at this commit not classified yet (ignoring the filter) */
+    unclassified: number;
+}
+
+export interface PartMetadataResponse {
+    /** Datasheet URL */
+    datasheet_url: string | null;
+    /** Part description */
+    description: string | null;
+    /** When the data was fetched from the supplier */
+    fetched_at: string;
+    /** Whether the part is obsolete/deprecated */
+    is_obsolete: boolean;
+    /** Lifecycle status (Active, Obsolete, etc.) */
+    lifecycle_status: string | null;
+    /** Manufacturer name */
+    manufacturer: string | null;
+    /** Manufacturer part number */
+    mpn: string;
+    /** Product page URL at the supplier */
+    product_url: string | null;
+    /** Quantity available at the supplier */
+    quantity_available: number | null;
+    /** Supplier the data came from (e.g. "digikey") */
+    supplier: string;
+    /** Unit price (USD) */
+    unit_price: number | null;
+}
+
+export interface PersonaInfo {
+    /** What the preset is for */
+    description: string;
+    /** Maximum tokens in the response */
+    max_tokens: number;
+    /** Preset name, used as the `persona` parameter */
+    name: string;
+    /** Sampling temperature sent to the model */
+    temperature: number;
+}
+
+export interface PersonaListResponse {
+    /** Available persona presets */
+    personas: PersonaInfo[];
+}
+
+export interface PinChangeItem {
+    /** Net the pin is on now */
+    new_net: string;
+    /** Net the pin was on at the parent commit */
+    old_net: string;
+    /** Pin number */
+    pin: string;
+    /** Reference designator */
+    reference: string;
+}
+
+/** A commit whose last processing attempt failed */
+export interface ProcessingErrorItem {
+    commit_hash: string;
+    error: string;
+    failed_at: string;
+}
+
+export interface ProcessingErrorsResponse {
+    /** Most recent failure first */
+    errors: ProcessingErrorItem[];
+    repo: string;
+}
+
+/**
+ * Which pushes a repo processes and what runs for them
+ *
+ * Webhook pushes to other branches, and tag pushes with `tags` off, are
+ * acknowledged without processing; scheduled re-syncs skip repos whose
+ * processed branch is left out.
+ */
+export interface ProcessingPolicy {
+    /** Summarize new commits with AI as soon as a push or re-sync processes them, rather than only on request. Defaults to false */
+    ai?: boolean;
+    /** Globs of the branch names whose pushes are processed, e.g. ["main", "release/*"]. Omit for every branch */
+    branches?: string[] | null;
+    /** Render thumbnails of the repo's uploaded schematic images. Defaults to true */
+    render?: boolean;
+    /** Let POST /api/grok/suggest-fix push AI-suggested schematic edits to a branch of the repo and open a pull request. Defaults to false */
+    suggest_fixes?: boolean;
+    /** Process tag pushes. Defaults to true */
+    tags?: boolean;
+}
+
+export interface ProjectBom {
+    /** BOM lines, excluding DNP parts and parts excluded from the BOM */
+    lines: BomLineItem[];
+    project: ProjectSummary;
+}
+
+export interface ProjectChanges {
+    /** Reference designators placed by this commit */
+    added_components: string[];
+    added_nets: string[];
+    added_sheets: string[];
+    changed_components: ComponentChangeItem[];
+    /** Nets present before and after whose pins changed */
+    changed_nets: NetChangeItem[];
+    /** Pins of components kept by this commit that moved to another net */
+    changed_pins: PinChangeItem[];
+    /** Reference designators removed by this commit */
+    removed_components: string[];
+    removed_nets: string[];
+    removed_sheets: string[];
+    /** Root schematic path within the repository */
+    root_file: string;
+}
+
+export interface ProjectNetGeometry {
+    project: ProjectSummary;
+    /** Sheets the net is drawn on, in hierarchy order */
+    sheets: NetSheetGeometry[];
+}
+
+export interface ProjectNetlist {
+    /** Nets spanning every sheet of the project */
+    nets: NetItem[];
+    project: ProjectSummary;
+}
+
+export interface ProjectSummary {
+    /** Format each sheet file was read from, by path: `kicad`, `kicad_legacy` or `eagle` */
+    formats: Record<string, string>;
+    /** Sheet files referenced but not present at this commit */
+    missing_sheets: string[];
+    /** Project name (root schematic without extension) */
+    name: string;
+    /** Root schematic path within the repository */
+    root_file: string;
+    /** Sheet paths in the hierarchy, root first */
+    sheets: string[];
+    /** Symbol lib_ids with no definition in the sheets, repo libraries or standard libraries */
+    unresolved_symbols: string[];
+}
+
+/** One AI call, as the prompt audit recorded it */
+export interface PromptAuditItem {
+    /** ID of the API key the call was charged to */
+    api_key: string | null;
+    completion_tokens: number | null;
+    created_at: string;
+    /** "responses", "chat_completion_stream", "submit_deferred" or "deferred_completion" */
+    endpoint: string;
+    error: string | null;
+    id: number;
+    latency_ms: number;
+    /** The messages sent, redacted and with long text cut short */
+    messages: Record<string, unknown>;
+    model: string;
+    prompt_tokens: number | null;
+    /** "xai" or the name of a fallback provider */
+    provider: string;
+    /** Repository slug the call was charged to */
+    repo: string | null;
+    /** ID of the API request that made the call, if any */
+    request_id: string | null;
+    /** The answer, redacted and cut short like the messages */
+    response: string | null;
+}
+
+export interface PromptAuditListResponse {
+    /** Newest first */
+    prompts: PromptAuditItem[];
+}
+
+export interface PublicSummaryResponse {
+    /** Short AI-generated blurb describing the design change */
+    blurb: string;
+    /** Commit date (if recorded) */
+    commit_date: string | null;
+    /** Full commit hash of the latest summarized change */
+    commit_hash: string;
+    /** Commit message (if recorded) */
+    message: string | null;
+    /** Repository slug ("owner/repo") */
+    repo: string;
+}
+
+export interface ReadinessResponse {
+    checks: DependencyStatus[];
+    /** True when every dependency check passed */
+    ready: boolean;
+}
+
+export interface RegisterRepoRequest {
+    /**
+     * Name of the environment variable holding an access token for private
+     * repos; one of the server's `git_token_envs`
+     */
+    auth_token_env?: string | null;
+    /** Clone only this many commits; older ones are fetched when asked for. Omit for full history */
+    clone_depth?: number | null;
+    /** Where to clone from (https, ssh or file URL); defaults to the URL the slug implies */
+    clone_url?: string | null;
+    /** Branch to process; defaults to the remote's HEAD */
+    default_branch?: string | null;
+    /**
+     * Slug of the org that owns the repo; instance-wide keys only. Defaults
+     * to the caller's org, or none for a repo every org-less caller sees
+     */
+    org?: string | null;
+    /** Process only design files matching these globs, e.g. ["hardware/boards/**"]. Omit for the whole tree */
+    path_filter?: string[] | null;
+    processing?: ProcessingPolicy;
+    /** Repository slug: "owner/repo" on GitHub, or "host/group/project" */
+    repo: string;
+    /**
+     * Also read files matching these globs as schematics, e.g. ["**/*.schematic"]; .kicad_sch
+     * and legacy (KiCad 4/5) .sch files always are. Each file's format is told by its contents
+     */
+    schematic_globs?: string[] | null;
+    /** Check out only these pathspecs, e.g. ["*.kicad_sch", "*.kicad_pcb"]. Omit for the whole tree */
+    sparse_paths?: string[] | null;
+    /** Fetch submodules, recursively, so symbol libraries vendored in them resolve */
+    submodules?: boolean;
+    /** Model for commit summaries; must be on the model allowlist */
+    summary_model?: string | null;
+    /** Prompt template for commit summaries, e.g. a custom "commit_summary_terse" */
+    summary_prompt?: string | null;
+    /** Webhook secret for this repo; overrides the provider-wide secret */
+    webhook_secret?: string | null;
+}
+
+export interface RegisteredRepoItem {
+    auth_token_env: string | null;
+    clone_depth: number | null;
+    clone_url: string;
+    created_at: string;
+    default_branch: string | null;
+    /** Whether a webhook secret is set; the secret itself is never returned */
+    has_webhook_secret: boolean;
+    /** Org that owns the repo; unset for repos no org owns */
+    org_id: number | null;
+    path_filter: string[] | null;
+    processing: ProcessingPolicy;
+    repo: string;
+    repo_url: string;
+    schematic_globs: string[] | null;
+    sparse_paths: string[] | null;
+    /**
+     * Submodules pinned at the head of the processed branch; only when
+     * getting a single repo
+     */
+    submodule_pins: SubmodulePin[] | null;
+    submodules: boolean;
+    summary_model: string | null;
+    summary_prompt: string | null;
+    updated_at: string;
+}
+
+export interface RegisteredRepoListResponse {
+    /** By slug */
+    repos: RegisteredRepoItem[];
+}
+
+/** Section of the release notes a change is filed under */
+export type ReleaseCategory = "power" | "mcu" | "connectors" | "bugfixes" | "other";
+
+/** One commit of a release, as the notes were written from */
+export interface ReleaseNoteChange {
+    /** Short AI-generated blurb describing the design change */
+    blurb: string;
+    category: ReleaseCategory;
+    /** Full commit hash */
+    commit: string;
+    /** Whether the blurb was generated for these notes, the commit having none stored */
+    generated: boolean;
+    /** Commit message */
+    message: string | null;
+}
+
+export interface ReloadConfigResponse {
+    /** Prompt templates now in effect, as name@vVERSION */
+    prompts: string[];
+    /** Changed settings that keep their old values until a restart */
+    restart_required: string[];
+}
+
+export interface RemoveMemberResponse {
+    email: string;
+    removed: boolean;
+}
+
+export interface RepoAlertsResponse {
+    /** Open alerts first, the most recently seen first */
+    alerts: PartAlertItem[];
+    repo: string;
+}
+
+/** First line of a repo archive; each further line is one stored commit */
+export interface RepoArchiveHeader {
+    /** Number of commit lines that follow */
+    commits: number;
+    exported_at: string;
+    /** Repository the archive was exported from */
+    repo: string;
+    /** Archive format version */
+    version: number;
+}
+
+export interface RepoChangelogRequest {
+    /** Also propose the changelog to the repository as a pull request (default false) */
+    publish?: boolean | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface RepoChangelogResponse {
+    /** Generated changelog markdown */
+    markdown: string;
+    /** Pull request proposing the changelog (when published and the file changed) */
+    pull_request_url: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface RepoChangelogSettingsRequest {
+    /** Bot branch the pull request is opened from (default "grokicad/changelog") */
+    branch?: string | null;
+    /** Whether to open a changelog pull request after new commits are processed */
+    enabled: boolean;
+    /** File path within the repository (default "HARDWARE_CHANGELOG.md") */
+    path?: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface RepoChangelogSettingsResponse {
+    /** Bot branch the pull request is opened from */
+    branch: string;
+    enabled: boolean;
+    /** File path within the repository */
+    path: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface RepoClearCacheRequest {
+    /** Commit SHA, branch or tag (optional - clears all commits if not provided) */
+    commit?: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface RepoClearCacheResponse {
+    /** Whether the cache was cleared */
+    cleared: boolean;
+    /** Message describing what was cleared */
+    message: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface RepoCommitsRequest {
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface RepoCommitsResponse {
+    /** List of all commits (includes flag for schematic changes) */
+    commits: CommitInfo[];
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+/** A commit of the repo changed; reload its timeline entry */
+export interface RepoEvent {
+    /** Full commit hash */
+    commit_hash: string;
+    kind: RepoEventKind;
+    repo: string;
+}
+
+/** What happened to a commit, as announced on the events stream */
+export type RepoEventKind = "processed" | "failed";
+
+export interface RepoGuidelinesResponse {
+    /** The repo's own, highest priority first */
+    guidelines: DesignGuidelineItem[];
+    /** Guidelines left out of the prompt for lack of room */
+    omitted: number;
+    /**
+     * Those of the org the repo is registered to, which its own take
+     * precedence over
+     */
+    org_guidelines: DesignGuidelineItem[];
+    /**
+     * The block added to the repo's summary and chat system prompts, within
+     * the character budget; null without guidelines
+     */
+    prompt: string | null;
+    repo: string;
+}
+
+export interface RepoInitRequest {
+    /** Commit SHA (full or abbreviated), branch or tag (optional - uses latest if not provided) */
+    commit?: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface RepoInitResponse {
+    /** Whether the distilled data was already cached */
+    cached: boolean;
+    /** Full commit hash that was initialized */
+    commit: string;
+    /** Number of components found in the schematic */
+    component_count: number;
+    /** Distilled schematic data */
+    distilled: unknown;
+    /** Number of nets found in the schematic */
+    net_count: number;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** List of schematic files found */
+    schematic_files: string[];
+    timeline: AnalysisTimeline;
+}
+
+export interface RepoPersonaRequest {
+    /** Default persona for the repo (omit or null to clear) */
+    persona?: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface RepoPersonaResponse {
+    /** Default persona now configured for the repo */
+    persona: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface RepoScheduleItem {
+    /** Interval in effect, 0 if the repo is never re-checked */
+    effective_interval_secs: number;
+    enabled: boolean;
+    /** This repo's own interval; null uses the default */
+    interval_secs: number | null;
+    /** When the last re-sync finished */
+    last_checked_at: string | null;
+    /** Error from the last re-sync, null if it succeeded */
+    last_error: string | null;
+    /** Null when the repo is never re-checked */
+    next_check_at: string | null;
+    /** Repository slug, e.g. "owner/repo" */
+    repo: string;
+    repo_url: string;
+}
+
+export interface RepoVisibilityRequest {
+    /** Set the visibility of one stored commit instead of the whole repo */
+    commit?: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** "public" or "private"; omit or null to reset (public for a repo, inherit for a commit) */
+    visibility?: string | null;
+}
+
+export interface RepoVisibilityResponse {
+    /** Commit the setting applies to (null for the whole repo) */
+    commit: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** Effective visibility after the change */
+    visibility: string;
+}
+
+/** A thing to verify when reviewing a commit */
+export interface ReviewChecklistItem {
+    /** power, mcu, connectors, signal, mechanical, erc or other */
+    category: string;
+    /** References of the changed components the item concerns */
+    components: string[];
+    done: boolean;
+    /** When the item was ticked off */
+    done_at: string | null;
+    /** Identifies the item when ticking it off */
+    id: number;
+    text: string;
+}
+
+export interface ReviewChecklistResponse {
+    /** Whether the checklist was stored from an earlier request */
+    cached: boolean;
+    /** Full commit hash */
+    commit: string;
+    generated_at: string;
+    /** Items in the order to review them */
+    items: ReviewChecklistItem[];
+    /** Model that wrote the checklist */
+    model: string;
+    /** Prompt template the checklist was generated from */
+    prompt_version: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface RevokeApiKeyResponse {
+    id: number;
+    revoked: boolean;
+}
+
+export interface ScheduleListResponse {
+    /** Interval for repos without their own (RESYNC_INTERVAL_SECS), 0 if disabled */
+    default_interval_secs: number;
+    /** Every repo with stored commits or a schedule, by URL */
+    schedules: RepoScheduleItem[];
+}
+
+export interface SchematicFile {
+    /** Raw file content */
+    content: string;
+    /** File path relative to repository root */
+    path: string;
+}
+
+/** Size of a commit's schematics, over every project */
+export interface SchematicStatsItem {
+    /** components + nets + connections, plus 10 per sheet below a root */
+    complexity: number;
+    /** Placed parts, units merged; power symbols aren't counted */
+    components: number;
+    /** Pins on those nets */
+    connections: number;
+    /** Nets joining pins, named or not */
+    nets: number;
+    /** Sheet placements, root sheets included */
+    sheets: number;
+    /** Distinct BOM lines (value, footprint and MPN) */
+    unique_parts: number;
+}
+
+export interface SearchResponse {
+    /** The search text */
+    query: string;
+    /** Matching commits ordered by relevance (with type "commits") */
+    results: SearchResult[];
+    /** Matching schematic text, whole matches first (with any other type) */
+    texts: TextSearchResult[];
+}
+
+export interface SearchResult {
+    /** Short AI-generated blurb (if generated) */
+    blurb: string | null;
+    /** Commit date (if recorded) */
+    commit_date: string | null;
+    /** Full commit hash */
+    commit_hash: string;
+    /** Commit message (if recorded) */
+    message: string | null;
+    /** Relevance score (higher is better) */
+    rank: number;
+    /** Repository clone URL */
+    repo_url: string;
+    /** Matching text with search terms wrapped in <mark></mark> */
+    snippet: string;
+}
+
+/** What the user selected in the viewer */
+export type Selection = { ids: string[]; kind: "components"; } | { kind: "nets"; names: string[]; } | { kind: "sheet"; path: string; } | { kind: "area"; /** Path of the sheet the rectangle was drawn on. Defaults to the root sheet */ sheet: string | null; x1: number; x2: number; y1: number; y2: number; };
+
+export interface SemanticCommitResult {
+    /** Short AI-generated blurb (if generated) */
+    blurb: string | null;
+    /** Commit date (if recorded) */
+    commit_date: string | null;
+    /** Full commit hash */
+    commit_hash: string;
+    /** Commit message (if recorded) */
+    message: string | null;
+    /** Repository clone URL */
+    repo_url: string;
+    /** Cosine similarity to the query (higher is better, at most 1) */
+    similarity: number;
+}
+
+export interface SemanticComponentResult {
+    /** Date of that commit (if recorded) */
+    commit_date: string | null;
+    /** First commit in the repository that uses the part */
+    commit_hash: string;
+    /** Supplier description of the part */
+    description: string | null;
+    /** Manufacturer (if known) */
+    manufacturer: string | null;
+    /** Manufacturer part number */
+    mpn: string;
+    /** A reference designator the part is used under there */
+    reference: string;
+    /** Repository clone URL */
+    repo_url: string;
+    /** Cosine similarity to the query (higher is better, at most 1) */
+    similarity: number;
+}
+
+export interface SemanticSearchResponse {
+    /** Commits whose message and summaries are closest in meaning, best first */
+    commits: SemanticCommitResult[];
+    /** Parts whose descriptions are closest in meaning, best first */
+    components: SemanticComponentResult[];
+    /** Embedding model the query and results were compared with */
+    model: string;
+    /** The search text */
+    query: string;
+}
+
+export interface SetMemberRequest {
+    /** The user's email, as sent in the `sub` claim of their JWTs */
+    email: string;
+    /** Display name; kept as is if omitted */
+    name?: string | null;
+    /** "read", "hook" or "admin" */
+    role: string;
+}
+
+export interface ShareLinkResponse {
+    /** Full commit hash */
+    commit: string;
+    expires_at: string;
+    /** Where the shared commit is served, e.g. "/api/shared/{token}" */
+    path: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    scopes: ShareScope[];
+    /** Signed token; anyone holding it can open the views until it expires */
+    token: string;
+}
+
+/** A read-only view a share link opens */
+export type ShareScope = "summary" | "diff" | "svg";
+
+export interface SharedCommitResponse {
+    author: string | null;
+    /** Full commit hash */
+    commit: string;
+    commit_date: string | null;
+    expires_at: string;
+    /** Commit message summary */
+    message: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** Views the link opens */
+    scopes: ShareScope[];
+    /** Sheets /sheets/{page} can draw (null unless the link opens `svg`) */
+    sheets: number | null;
+}
+
+export interface SharedComponentItem {
+    /** Trimmed and in upper case */
+    mpn: string;
+    /** Components across those repos */
+    quantity: number;
+    /** Repos using it, in "owner/repo" format */
+    repos: string[];
+}
+
+export interface SharedComponentsResponse {
+    min_repos: number;
+    /** The most widely used first */
+    parts: SharedComponentItem[];
+}
+
+export interface SharedSummaryResponse {
+    /** Short AI-generated summary */
+    blurb: string;
+    /** Full commit hash */
+    commit: string;
+    /** Detailed AI-generated description */
+    description: string | null;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface StatsHistoryResponse {
+    /** Oldest first; commits processed before stats were kept are left out */
+    commits: CommitStatsItem[];
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface StoredCommit {
+    /** Short AI-generated blurb (if generated and visible to the caller) */
+    blurb: string | null;
+    /** Commit date (if recorded) */
+    commit_date: string | null;
+    /** Full commit hash */
+    commit_hash: string;
+    /** Commit message (if recorded) */
+    message: string | null;
+    /** Processing status: pending, distilled or summarized */
+    status: string;
+    /** Stage timings keyed by analysis kind (overview, init, commit_summary) */
+    timings: unknown;
+    /** Summary visibility ("public" or "private") */
+    visibility: string;
+}
+
+export interface StoredCommitsResponse {
+    /** Stored commits in the requested order */
+    commits: StoredCommit[];
+    /** Limit applied to this page */
+    limit: number;
+    /** Offset applied to this page */
+    offset: number;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+    /** Total number of stored commits for the repo */
+    total: number;
+}
+
+/** Payload of the `usage` event sent when a streamed answer ends */
+export interface StreamUsageEvent {
+    completion_tokens: number | null;
+    prompt_tokens: number | null;
+    total_tokens: number | null;
+}
+
+/** A submodule and the commit the repo pins it at */
+export interface SubmodulePin {
+    commit: string;
+    path: string;
+    url: string;
+}
+
+export interface SuggestFixResponse {
+    /** Branch the pull request is opened from */
+    branch: string;
+    /** Full hash of the commit the edits were made to */
+    commit: string;
+    /** The edits made, one per line as the model wrote them */
+    edits: string[];
+    /** Paths of the schematic files the pull request changes */
+    files: string[];
+    /** Model that suggested the edits */
+    model: string;
+    /** Prompt template the edits were suggested from */
+    prompt_version: string;
+    /** Pull request proposing the edited files */
+    pull_request_url: string;
+    /** GitHub repository in "owner/repo" format */
+    repo: string;
+}
+
+export interface SummaryFeedbackRequest {
+    /** What was good or missing */
+    comment?: string | null;
+    rating: SummaryRating;
+    /** `summary_id` of the summary response being rated */
+    summary_id: number;
+}
+
+export interface SummaryFeedbackResponse {
+    /** ID of the stored rating */
+    id: number;
+    /** Model that generated the rated summary */
+    model: string;
+    /** Prompt template the rated summary was generated from */
+    prompt_version: string;
+    rating: SummaryRating;
+    summary_id: number;
+}
+
+/** Thumbs up or down */
+export type SummaryRating = "up" | "down";
+
+export interface TextSearchResult {
+    /** Commit date (if recorded) */
+    commit_date: string | null;
+    /** The repo's newest commit with indexed text, where the match is */
+    commit_hash: string;
+    /** "reference", "value", "label", "sheet" or "note" */
+    kind: string;
+    /** The component a reference or value belongs to */
+    reference: string | null;
+    /** Repository clone URL */
+    repo_url: string;
+    /** Sheet the text is on, e.g. "/Power/"; for a sheet name, the sheet's own */
+    sheet_path: string;
+    /** The whole matching text */
+    text: string;
+}
+
+export interface TimelineCommit {
+    author: string | null;
+    author_email: string | null;
+    commit_date: string | null;
+    /** Full commit hash */
+    commit_hash: string;
+    /** Components and nets have been extracted */
+    distilled: boolean;
+    /** Why the last processing attempt failed */
+    error: string | null;
+    error_at: string | null;
+    has_blurb: boolean;
+    has_change_summary: boolean;
+    has_description: boolean;
+    /** Commit message summary */
+    message: string | null;
+    /** Pull request that introduced the commit, when a GitHub token is configured */
+    pr_number: number | null;
+    /**
+     * Where the overview pipeline left the commit: pending, processing,
+     * done or failed
+     */
+    processing_status: string;
+    /** A schematic image has been rendered */
+    rendered: boolean;
+    /** pending, processing, failed, distilled or summarized */
+    status: string;
+    /** Tags pointing at this commit */
+    tags: string[];
+}
+
+export interface TimelineResponse {
+    /** Commits that changed schematic or layout files, newest first */
+    commits: TimelineCommit[];
+    /** Pass as `cursor` for the next page; absent on the last page */
+    next_cursor: string | null;
+    repo: string;
+}
+
+export interface UnregisterRepoResponse {
+    /** Stored commits deleted; purged for good after the retention window */
+    deleted_commits: number;
+    /** Whether the repo was registered */
+    removed: boolean;
+    repo: string;
+}
+
+export interface UpdateChecklistItemRequest {
+    /** Whether the item has been verified */
+    done: boolean;
+}
+
+export interface UpdateScheduleRequest {
+    /** False stops scheduled re-syncs of this repo (default true) */
+    enabled?: boolean | null;
+    /** Seconds between re-syncs; omit to use the default */
+    interval_secs?: number | null;
+    /** Repository slug, e.g. "owner/repo" */
+    repo: string;
+}
+
+export interface UploadImageResponse {
+    commit: string;
+    /** image/png or image/svg+xml */
+    content_type: string;
+    /**
+     * Hex sha256 of the image; pass it as `?digest=` to attach the same
+     * render to another commit without uploading it again
+     */
+    digest: string;
+    repo: string;
+    /** Whether an identical image was already stored, e.g. for an earlier commit */
+    reused: boolean;
+    /** Size in bytes */
+    size: number;
+}
+
+export interface WebhookDeliveryItem {
+    /** The provider's delivery ID (GitHub), or one generated on receipt */
+    delivery_id: string;
+    /** Event type from the provider's headers */
+    event: string | null;
+    /** "github", "gitlab" or "bitbucket" */
+    provider: string;
+    received_at: string;
+    /** Repository slug, e.g. "owner/repo" */
+    repo: string;
+}
+
+export interface WebhookDeliveryListResponse {
+    /** Deliveries still kept for replay, newest first */
+    deliveries: WebhookDeliveryItem[];
+}
+
+/**
+ * How urgent some AI work is, most urgent first; less urgent work leaves
+ * part of the shared AI rate limit to more urgent work (see
+ * `services::priority`)
+ */
+export type WorkClass = "interactive" | "webhook" | "backfill" | "enrichment";
+
+/** Every API operation by method and path, with what it takes and returns */
+export interface ApiOperations {
+    "GET /api/admin/costs": { query: { month?: string | null }; response: CostReportResponse };
+    "GET /api/admin/experiments": { query: { days?: number | null }; response: ExperimentsResponse };
+    "GET /api/admin/feedback": { query: { days?: number | null }; response: FeedbackReportResponse };
+    "GET /api/admin/jobs": { query: { repo?: string | null; status?: string | null }; response: JobListResponse };
+    "GET /api/admin/jobs/{id}": { path: { id: number }; response: JobDetailResponse };
+    "POST /api/admin/jobs/{id}/cancel": { path: { id: number }; response: BackfillProgress };
+    "POST /api/admin/jobs/{id}/retry": { path: { id: number }; response: BackfillProgress };
+    "POST /api/admin/lifecycle/check": { response: LifecycleCheckResponse };
+    "POST /api/admin/onboard/github-org": { body: OnboardGithubOrgRequest; response: OnboardGithubOrgResponse };
+    "GET /api/admin/overview": { response: AdminOverviewResponse };
+    "GET /api/admin/prompts": { query: { repo?: string | null; limit?: number | null }; response: PromptAuditListResponse };
+    "POST /api/admin/reload": { response: ReloadConfigResponse };
+    "GET /api/admin/schedules": { response: ScheduleListResponse };
+    "PUT /api/admin/schedules": { body: UpdateScheduleRequest; response: RepoScheduleItem };
+    "POST /api/admin/taxonomy/classify": { response: ClassifyPartsResponse };
+    "GET /api/admin/webhooks": { response: WebhookDeliveryListResponse };
+    "POST /api/admin/webhooks/{delivery_id}/replay": { path: { delivery_id: string }; response: HookUpdateResponse };
+    "GET /api/components/usage": { query: { min_repos?: number | null; limit?: number | null }; response: SharedComponentsResponse };
+    "GET /api/components/{mpn}": { path: { mpn: string }; response: PartMetadataResponse };
+    "GET /api/components/{mpn}/datasheet": { path: { mpn: string }; response: void };
+    "GET /api/components/{mpn}/usage": { path: { mpn: string }; response: ComponentUsageResponse };
+    "POST /api/digikey/search": { body: DigiKeySearchRequest; response: DigiKeySearchResponse };
+    "GET /api/digikey/status": { response: DigiKeyStatusResponse };
+    "POST /api/distill": { body: DistillRequest; response: DistillResponse };
+    "POST /api/grok/ask": { body: GrokAskRequest; response: GrokAskResponse };
+    "POST /api/grok/chat/sessions": { body: CreateChatSessionRequest; response: ChatSessionResponse };
+    "GET /api/grok/chat/sessions/{id}": { path: { id: string }; response: ChatSessionResponse };
+    /** @deprecated */
+    "GET /api/grok/chat/stream": { query: { repo?: string | null; persona?: string | null; model?: string | null }; response: string };
+    "POST /api/grok/chat/stream": { body: GrokChatRequest; response: string };
+    "POST /api/grok/feedback": { body: SummaryFeedbackRequest; response: SummaryFeedbackResponse };
+    "GET /api/grok/models": { response: ModelListResponse };
+    "POST /api/grok/obsolete/replacement": { body: GrokObsoleteReplacementRequest; response: GrokObsoleteReplacementResponse };
+    "GET /api/grok/personas": { response: PersonaListResponse };
+    "POST /api/grok/release-notes": { body: GrokReleaseNotesRequest; response: GrokReleaseNotesResponse };
+    "POST /api/grok/review/checklist": { body: GrokReviewChecklistRequest; response: ReviewChecklistResponse };
+    "POST /api/grok/selection/stream": { body: GrokSelectionStreamRequest; response: string };
+    "POST /api/grok/suggest-fix/{repo}/{commit}": { path: { repo: string; commit: string }; body: GrokSuggestFixRequest; response: SuggestFixResponse };
+    "POST /api/grok/summary/backfill/{repo}": { path: { repo: string }; response: string };
+    "POST /api/grok/summary/commit": { body: GrokCommitSummaryRequest; response: GrokCommitSummaryResponse };
+    "POST /api/grok/summary/commit/stream": { body: GrokCommitSummaryRequest; response: string };
+    "POST /api/grok/summary/compare": { body: GrokCompareSummaryRequest; response: GrokCompareSummaryResponse };
+    "POST /api/grok/summary/repo": { body: GrokRepoSummaryRequest; response: GrokRepoSummaryResponse };
+    "POST /api/grok/summary/selection": { body: GrokSelectionSummaryRequest; response: GrokSelectionSummaryResponse };
+    "POST /api/hook/bitbucket/{repo}": { path: { repo: string }; body: string; response: HookUpdateResponse };
+    "POST /api/hook/github/{repo}": { path: { repo: string }; body: string; response: HookUpdateResponse };
+    "POST /api/hook/gitlab/{repo}": { path: { repo: string }; body: string; response: HookUpdateResponse };
+    "POST /api/hook/refresh/{repo}": { path: { repo: string }; response: HookUpdateResponse };
+    "POST /api/hook/update/{repo}": { path: { repo: string }; response: HookUpdateResponse };
+    "GET /api/keys": { response: ApiKeyListResponse };
+    "POST /api/keys": { body: CreateApiKeyRequest; response: CreateApiKeyResponse };
+    "DELETE /api/keys/{id}": { path: { id: number }; response: RevokeApiKeyResponse };
+    "GET /api/orgs": { response: OrganizationListResponse };
+    "POST /api/orgs": { body: CreateOrganizationRequest; response: OrganizationItem };
+    "GET /api/orgs/{org}/guidelines": { path: { org: string }; response: OrgGuidelinesResponse };
+    "POST /api/orgs/{org}/guidelines": { path: { org: string }; body: DesignGuidelineRequest; response: DesignGuidelineItem };
+    "PUT /api/orgs/{org}/guidelines/{id}": { path: { org: string; id: number }; body: DesignGuidelineRequest; response: DesignGuidelineItem };
+    "DELETE /api/orgs/{org}/guidelines/{id}": { path: { org: string; id: number }; response: DeleteGuidelineResponse };
+    "GET /api/orgs/{org}/members": { path: { org: string }; response: MemberListResponse };
+    "PUT /api/orgs/{org}/members": { path: { org: string }; body: SetMemberRequest; response: MemberItem };
+    "DELETE /api/orgs/{org}/members/{email}": { path: { org: string; email: string }; response: RemoveMemberResponse };
+    "GET /api/public/{repo}/badge.svg": { path: { repo: string }; response: string };
+    "GET /api/public/{repo}/latest-summary": { path: { repo: string }; response: PublicSummaryResponse };
+    "POST /api/repo/changelog": { body: RepoChangelogRequest; response: RepoChangelogResponse };
+    "POST /api/repo/changelog/settings": { body: RepoChangelogSettingsRequest; response: RepoChangelogSettingsResponse };
+    "POST /api/repo/clear-cache": { body: RepoClearCacheRequest; response: RepoClearCacheResponse };
+    "POST /api/repo/commit/files": { body: CommitFilesRequest; response: CommitFilesResponse };
+    "POST /api/repo/commit/info": { body: CommitInfoRequest; response: CommitInfoResponse };
+    "POST /api/repo/commits": { body: RepoCommitsRequest; response: RepoCommitsResponse };
+    "POST /api/repo/init": { body: RepoInitRequest; response: RepoInitResponse };
+    "POST /api/repo/persona": { body: RepoPersonaRequest; response: RepoPersonaResponse };
+    "POST /api/repo/visibility": { body: RepoVisibilityRequest; response: RepoVisibilityResponse };
+    "GET /api/repos": { response: RegisteredRepoListResponse };
+    "POST /api/repos": { body: RegisterRepoRequest; response: RegisteredRepoItem };
+    "GET /api/repos/{repo}": { path: { repo: string }; response: RegisteredRepoItem };
+    "DELETE /api/repos/{repo}": { path: { repo: string }; response: UnregisterRepoResponse };
+    "GET /api/repos/{repo}/alerts": { path: { repo: string }; query: { include_resolved?: boolean | null }; response: RepoAlertsResponse };
+    "GET /api/repos/{repo}/commits": { path: { repo: string }; query: { limit?: number | null; offset?: number | null; order?: string | null }; response: StoredCommitsResponse };
+    "DELETE /api/repos/{repo}/commits/{commit}": { path: { repo: string; commit: string }; response: DeleteCommitResponse };
+    "GET /api/repos/{repo}/commits/{commit}/board": { path: { repo: string; commit: string }; response: BoardResponse };
+    "GET /api/repos/{repo}/commits/{commit}/bom": { path: { repo: string; commit: string }; response: BomResponse };
+    "GET /api/repos/{repo}/commits/{commit}/changes": { path: { repo: string; commit: string }; response: CommitChangesResponse };
+    "GET /api/repos/{repo}/commits/{commit}/checklist": { path: { repo: string; commit: string }; response: ReviewChecklistResponse };
+    "PUT /api/repos/{repo}/commits/{commit}/checklist/{item}": { path: { repo: string; commit: string; item: number }; body: UpdateChecklistItemRequest; response: ReviewChecklistItem };
+    "GET /api/repos/{repo}/commits/{commit}/components": { path: { repo: string; commit: string }; query: { sheet?: string | null; x1?: number | null; y1?: number | null; x2?: number | null; y2?: number | null }; response: ComponentsResponse };
+    "GET /api/repos/{repo}/commits/{commit}/erc": { path: { repo: string; commit: string }; response: ErcResponse };
+    "GET /api/repos/{repo}/commits/{commit}/files/{path}": { path: { repo: string; commit: string; path: string }; response: void };
+    "GET /api/repos/{repo}/commits/{commit}/image": { path: { repo: string; commit: string }; query: { width?: number | null }; response: void };
+    "PUT /api/repos/{repo}/commits/{commit}/image": { path: { repo: string; commit: string }; query: { digest?: string | null }; body: string; response: UploadImageResponse };
+    "GET /api/repos/{repo}/commits/{commit}/netlist": { path: { repo: string; commit: string }; response: NetlistResponse };
+    "GET /api/repos/{repo}/commits/{commit}/nets/{net}/geometry": { path: { repo: string; commit: string; net: string }; response: NetGeometryResponse };
+    "GET /api/repos/{repo}/commits/{commit}/raw/{path}": { path: { repo: string; commit: string; path: string }; response: void };
+    "GET /api/repos/{repo}/commits/{commit}/schematic.pdf": { path: { repo: string; commit: string }; response: void };
+    "POST /api/repos/{repo}/commits/{commit}/share": { path: { repo: string; commit: string }; body: CreateShareLinkRequest; response: ShareLinkResponse };
+    "GET /api/repos/{repo}/commits/{commit}/stats": { path: { repo: string; commit: string }; response: CommitStatsResponse };
+    "GET /api/repos/{repo}/commits/{commit}/symbols/{reference}": { path: { repo: string; commit: string; reference: string }; query: { unit?: number | null }; response: string };
+    "GET /api/repos/{repo}/commits/{commit}/thumbnail": { path: { repo: string; commit: string }; query: { width?: number | null }; response: void };
+    "GET /api/repos/{repo}/components": { path: { repo: string }; query: { commit?: string | null; category?: string | null }; response: PartListResponse };
+    "GET /api/repos/{repo}/components/{reference}/history": { path: { repo: string; reference: string }; response: ComponentHistoryResponse };
+    "GET /api/repos/{repo}/digest/preview": { path: { repo: string }; response: string };
+    "GET /api/repos/{repo}/digest/subscribers": { path: { repo: string }; response: DigestSubscribersResponse };
+    "POST /api/repos/{repo}/digest/subscribers": { path: { repo: string }; body: DigestSubscriberRequest; response: DigestSubscribersResponse };
+    "DELETE /api/repos/{repo}/digest/subscribers/{email}": { path: { repo: string; email: string }; response: DigestSubscribersResponse };
+    "GET /api/repos/{repo}/errors": { path: { repo: string }; response: ProcessingErrorsResponse };
+    "GET /api/repos/{repo}/events": { path: { repo: string }; response: string };
+    "GET /api/repos/{repo}/export": { path: { repo: string }; response: string };
+    "GET /api/repos/{repo}/guidelines": { path: { repo: string }; response: RepoGuidelinesResponse };
+    "POST /api/repos/{repo}/guidelines": { path: { repo: string }; body: DesignGuidelineRequest; response: DesignGuidelineItem };
+    "PUT /api/repos/{repo}/guidelines/{id}": { path: { repo: string; id: number }; body: DesignGuidelineRequest; response: DesignGuidelineItem };
+    "DELETE /api/repos/{repo}/guidelines/{id}": { path: { repo: string; id: number }; response: DeleteGuidelineResponse };
+    "POST /api/repos/{repo}/import": { path: { repo: string }; body: string; response: ImportRepoResponse };
+    "PUT /api/repos/{repo}/processing": { path: { repo: string }; body: ProcessingPolicy; response: RegisteredRepoItem };
+    "GET /api/repos/{repo}/stats": { path: { repo: string }; query: { limit?: number | null }; response: StatsHistoryResponse };
+    "GET /api/repos/{repo}/timeline": { path: { repo: string }; query: { limit?: number | null; cursor?: string | null; since?: string | null; until?: string | null; since_commit?: string | null }; response: TimelineResponse };
+    "GET /api/search": { query: { q: string; repo?: string | null; limit?: number | null; type?: string | null }; response: SearchResponse };
+    "GET /api/search/semantic": { query: { q: string; repo?: string | null; limit?: number | null; type?: string | null }; response: SemanticSearchResponse };
+    "GET /api/shared/{token}": { path: { token: string }; response: SharedCommitResponse };
+    "GET /api/shared/{token}/changes": { path: { token: string }; response: CommitChangesResponse };
+    "GET /api/shared/{token}/sheets/{page}": { path: { token: string; page: number }; response: string };
+    "GET /api/shared/{token}/summary": { path: { token: string }; response: SharedSummaryResponse };
+    "GET /healthz": { response: LivenessResponse };
+    "GET /readyz": { response: ReadinessResponse };
+}
@@ -4,131 +4,46 @@
 */
 
 import { API_BASE_URL } from "../../config";
+import type {
+    DistillResponse as GeneratedDistillResponse,
+    RepoInitResponse as GeneratedRepoInitResponse,
+    RepoClearCacheRequest,
+    RepoClearCacheResponse,
+    RepoCommitsResponse,
+    RepoInitRequest,
+    CommitFilesResponse,
+    CommitInfo,
+    CommitInfoResponse,
+    DigiKeyPartInfo,
+    DigiKeySearchResponse,
+    DigiKeyStatusResponse,
+    GrokObsoleteReplacementRequest,
+    GrokObsoleteReplacementResponse,
+    SchematicFile,
+} from "./api-types";
 
 console.log(`[API] Using backend URL: ${API_BASE_URL}`);
 
 // ============================================================================
-// DigiKey Types
+// Request and response types, generated from the backend's OpenAPI spec
 // ============================================================================
 
-export interface DigiKeyParameter {
-    name: string;
-    value: string;
-}
-
-export interface DigiKeyPartInfo {
-    digikey_part_number: string | null;
-    manufacturer_part_number: string | null;
-    manufacturer: string | null;
-    description: string | null;
-    detailed_description: string | null;
-    product_url: string | null;
-    datasheet_url: string | null;
-    photo_url: string | null;
-    quantity_available: number | null;
-    unit_price: number | null;
-    product_status: string | null;
-    is_obsolete: boolean;
-    lifecycle_status: string | null;
-    category: string | null;
-    parameters: DigiKeyParameter[];
-}
-
-export interface DigiKeySearchResponse {
-    query: string;
-    success: boolean;
-    error: string | null;
-    parts: DigiKeyPartInfo[];
-    total_count: number;
-}
-
-export interface DigiKeyStatusResponse {
-    configured: boolean;
-    message: string;
-}
-
-export interface GrokObsoleteReplacementRequest {
-    manufacturer_part_number: string;
-    manufacturer: string | null;
-    description: string | null;
-    category: string | null;
-    datasheet_url: string | null;
-    product_url: string | null;
-    parameters: DigiKeyParameter[];
-}
-
-export interface GrokObsoleteReplacementResponse {
-    original_part: string;
-    analysis: string;
-    success: boolean;
-    error: string | null;
-}
-
-export interface CommitInfo {
-    commit_hash: string;
-    commit_date: string | null;
-    message: string | null;
-    has_schematic_changes: boolean;
-}
-
-export interface RepoCommitsResponse {
-    repo: string;
-    commits: CommitInfo[];
-}
-
-export interface SchematicFile {
-    path: string;
-    content: string;
-}
+export type * from "./api-types";
 
-export interface CommitFilesResponse {
-    repo: string;
-    commit: string;
-    files: SchematicFile[];
-}
-
-export interface CommitInfoResponse {
-    repo: string;
-    commit: string;
-    commit_date: string | null;
-    message: string | null;
-    blurb: string | null;
-    description: string | null;
-    changed_files: string[];
-}
-
-export interface DistillResponse {
-    repo: string;
-    commit: string;
-    cached: boolean;
+// The spec types distilled schematics as arbitrary JSON
+export interface DistillResponse
+    extends Omit<GeneratedDistillResponse, "distilled"> {
     distilled: DistilledSchematic;
 }
 
-export interface RepoInitRequest {
-    repo: string;
-    commit?: string;
-}
-
-export interface RepoInitResponse {
-    repo: string;
-    commit: string;
-    cached: boolean;
-    component_count: number;
-    net_count: number;
-    schematic_files: string[];
+export interface RepoInitResponse
+    extends Omit<GeneratedRepoInitResponse, "distilled"> {
     distilled: DistilledSchematic;
 }
 
-export interface RepoClearCacheRequest {
-    repo: string;
-    commit?: string;
-}
-
-export interface RepoClearCacheResponse {
-    repo: string;
-    cleared: boolean;
-    message: string;
-}
+// ============================================================================
+// Distilled schematic types (the distiller's output, not in the spec)
+// ============================================================================
 
 export interface DistilledSchematic {
     components: DistilledComponent[];
@@ -164,13 +79,6 @@ export interface ProximityEdge {
     weight: number;
 }
 
-export interface GrokSelectionRequest {
-    repo: string;
-    commit: string;
-    component_ids: string[];
-    query: string;
-}
-
 export class GrokiAPI {
     private static baseUrl = API_BASE_URL;
 
@@ -390,6 +298,14 @@ export class GrokiAPI {
         return `${this.baseUrl}/grok/selection/stream?${params.toString()}`;
     }
 
+    /**
+     * URL of a repository's live processing events (server-sent events)
+     * Caller manages the EventSource
+     */
+    static getRepoEventsUrl(repo: string): string {
+        return `${this.baseUrl}/repos/${encodeURIComponent(repo)}/events`;
+    }
+
     /**
      * Clear the cached distilled schematic data for a repository.
      * This forces a re-distillation on the next init call.